@@ -713,7 +713,8 @@ using (make-hash-table :test \='equal)."
       (with-temp-buffer
         (let* ((standard-output (current-buffer))
                (exit-status (stgit-run-silent "series"
-                                              "--description" "--empty")))
+                                              "--description" "--empty"
+                                              "--no-window")))
           (goto-char (point-min))
           (if (not (zerop exit-status))
               (cond ((looking-at "stg series: \\(.*\\)")
@@ -72,14 +72,8 @@ impl Alias {
 /// Generate mapping of built-in aliases.
 pub(crate) fn get_default_aliases() -> Aliases {
     let aliases: Aliases = BTreeMap::from(
-        [
-            ("add", "!git -C \"$GIT_PREFIX\" add"),
-            ("mv", "!git -C \"$GIT_PREFIX\" mv"),
-            ("resolved", "!git -C \"$GIT_PREFIX\" add"),
-            ("rm", "!git -C \"$GIT_PREFIX\" rm"),
-            ("status", "!git status -s"),
-        ]
-        .map(|(name, command)| (name.into(), Alias::new(name, command))),
+        [("status", "!git status -s")]
+            .map(|(name, command)| (name.into(), Alias::new(name, command))),
     );
     aliases
 }
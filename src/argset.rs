@@ -2,10 +2,19 @@
 
 //! [`clap::Arg`] definitions common to several StGit commands.
 
+use anyhow::{anyhow, Result};
 use bstr::ByteSlice;
 use clap::Arg;
 
-use crate::branchloc::BranchLocator;
+use crate::{
+    branchloc::BranchLocator,
+    patch::{
+        name::NameCollision, patchrange, PatchLocator, PatchName, PatchRange, RangeConstraint,
+    },
+    stack::{InitializationPolicy, MergedCheck, Stack, StackConfig, StackStateAccess},
+    stupid::{DiffFormat, Stupid},
+    wrap::PartialRefName,
+};
 
 /// The `--branch`/`-b` option for selecting an alternative branch.
 pub(crate) fn branch_arg() -> Arg {
@@ -19,12 +28,55 @@ pub(crate) fn branch_arg() -> Arg {
         .value_parser(clap::value_parser!(BranchLocator))
 }
 
+/// The `--remote` option for inspecting a fetched remote stack.
+pub(crate) fn remote_stack_arg() -> Arg {
+    Arg::new("remote-stack")
+        .long("remote")
+        .help("Inspect the fetched stack of <remote>/<branch>")
+        .long_help(
+            "Inspect the stack of the fetched remote branch <remote>/<branch>, \
+             without creating a local branch. The remote's stack state must have \
+             been fetched into 'refs/remote-stacks/<remote>/<branch>', e.g. by \
+             fetching with the '+refs/stacks/*:refs/remote-stacks/<remote>/*' \
+             refspec.",
+        )
+        .num_args(1)
+        .value_name("remote/branch")
+        .value_hint(clap::ValueHint::Other)
+        .value_parser(clap::value_parser!(PartialRefName))
+}
+
+/// Get the stack selected by the `--remote` or `--branch` options.
+///
+/// Both options must be defined for the command.
+pub(crate) fn stack_from_args<'repo>(
+    repo: &'repo gix::Repository,
+    matches: &clap::ArgMatches,
+    init_policy: InitializationPolicy,
+) -> Result<Stack<'repo>> {
+    if let Some(remote_branch) = matches.get_one::<PartialRefName>("remote-stack") {
+        Stack::from_remote_branch(repo, remote_branch)
+    } else {
+        Stack::from_branch_locator(
+            repo,
+            matches.get_one::<BranchLocator>("branch"),
+            init_policy,
+        )
+    }
+}
+
 /// The `--keep/-k` option.
 pub(crate) fn keep_arg() -> Arg {
     Arg::new("keep")
         .long("keep")
         .short('k')
         .help("Keep the local changes")
+        .long_help(
+            "Keep the local changes in the index and worktree. The operation only \
+             proceeds if none of the files changed by the operation have local \
+             changes; otherwise the operation is aborted and the files with \
+             conflicting local changes are listed.",
+        )
         .action(clap::ArgAction::SetTrue)
 }
 
@@ -37,6 +89,145 @@ pub(crate) fn merged_arg() -> Arg {
         .action(clap::ArgAction::SetTrue)
 }
 
+/// Modes for detecting patches merged upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MergedMode {
+    /// Only patches whose diffs are already present upstream are merged.
+    Exact,
+
+    /// Additionally, patches whose subjects match upstream commits are merged.
+    Subject,
+
+    /// Additionally, runs of consecutive patches whose combined diff matches a single
+    /// upstream commit, e.g. when upstream squashed the patches, are merged.
+    Aggressive,
+}
+
+impl std::str::FromStr for MergedMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "exact" => Ok(Self::Exact),
+            "subject" => Ok(Self::Subject),
+            "aggressive" => Ok(Self::Aggressive),
+            _ => Err(anyhow!(
+                "'{s}' is not one of \"exact\", \"subject\", or \"aggressive\""
+            )),
+        }
+    }
+}
+
+/// The `--merged[=<mode>]` option for commands that move the stack base.
+///
+/// Unlike [`merged_arg()`], a mode may be specified to also detect patches that were
+/// merged upstream with modifications.
+pub(crate) fn merged_mode_arg() -> Arg {
+    Arg::new("merged")
+        .long("merged")
+        .short('m')
+        .help("Check for patches merged upstream")
+        .value_name("mode")
+        .value_parser(|s: &str| s.parse::<MergedMode>())
+        .num_args(0..=1)
+        .require_equals(true)
+        .action(clap::ArgAction::Set)
+}
+
+/// The `--not-merged` option for excluding patches from merged detection.
+pub(crate) fn not_merged_arg() -> Arg {
+    Arg::new("not-merged")
+        .long("not-merged")
+        .help("Never consider <patch> to be merged upstream")
+        .long_help(
+            "Never consider <patch> to be merged upstream when checking for merged \
+             patches with `--merged`. This option may be specified multiple times and \
+             may be used to avoid false positives.",
+        )
+        .value_name("patch")
+        .value_parser(clap::value_parser!(PatchLocator))
+        .action(clap::ArgAction::Append)
+        .requires("merged")
+}
+
+/// Determine the merged detection mode from `--merged[=<mode>]` and the config.
+///
+/// When `--merged` is given without a mode, the `stgit.mergeddetect` config variable
+/// determines the mode, defaulting to "exact". `None` is returned when `--merged` is not
+/// given.
+pub(crate) fn resolve_merged_mode(
+    config: &StackConfig,
+    matches: &clap::ArgMatches,
+) -> Result<Option<MergedMode>> {
+    if !matches.contains_id("merged") {
+        Ok(None)
+    } else if let Some(mode) = matches.get_one::<MergedMode>("merged") {
+        Ok(Some(*mode))
+    } else if let Some(value) = config.string("stgit.mergeddetect") {
+        value
+            .to_str()
+            .map_err(|_| anyhow!("invalid `stgit.mergeddetect` value"))?
+            .parse::<MergedMode>()
+            .map_err(|e| anyhow!("invalid `stgit.mergeddetect` value: {e}"))
+            .map(Some)
+    } else {
+        Ok(Some(MergedMode::Exact))
+    }
+}
+
+/// Make the [`MergedCheck`] for a merged detection `mode`.
+///
+/// The subject and aggressive modes match patches against the upstream commits that
+/// are reachable from `new_base_id`, but not from any of `old_base_ids`. The number of
+/// patches combined by the aggressive mode is limited by the `stgit.mergedmaxrun`
+/// config variable, which defaults to 5.
+pub(crate) fn make_merged_check(
+    repo: &gix::Repository,
+    config: &StackConfig,
+    mode: MergedMode,
+    exclude: Vec<PatchName>,
+    old_base_ids: impl IntoIterator<Item = gix::ObjectId>,
+    new_base_id: gix::ObjectId,
+) -> Result<MergedCheck> {
+    let upstream = if mode == MergedMode::Exact {
+        None
+    } else {
+        Some(
+            repo.stupid()
+                .rev_list_excluding([new_base_id], old_base_ids)?,
+        )
+    };
+    let max_run = if mode == MergedMode::Aggressive {
+        Some(
+            config
+                .integer("stgit.mergedmaxrun")
+                .filter(|n| n.is_positive())
+                .map_or(5, |n| n as usize),
+        )
+    } else {
+        None
+    };
+    Ok(MergedCheck {
+        exclude,
+        upstream,
+        max_run,
+    })
+}
+
+/// Resolve the patches provided with `--not-merged`.
+pub(crate) fn resolve_not_merged<'repo>(
+    matches: &clap::ArgMatches,
+    stack: &impl StackStateAccess<'repo>,
+) -> Result<Vec<PatchName>> {
+    let mut patchnames = Vec::new();
+    if let Some(locators) = matches.get_many::<PatchLocator>("not-merged") {
+        for locator in locators {
+            patchnames.push(locator.resolve_name(stack)?);
+        }
+    }
+    Ok(patchnames)
+}
+
 /// The --conflicts option determining how push-time conflicts are handled.
 pub(crate) fn push_conflicts_arg() -> clap::Arg {
     clap::Arg::new("conflicts")
@@ -66,6 +257,62 @@ pub(crate) fn push_conflicts_arg() -> clap::Arg {
         .action(clap::ArgAction::Set)
 }
 
+/// The `--marker-size` option for the length of conflict markers left by merges.
+pub(crate) fn marker_size_arg() -> Arg {
+    Arg::new("marker-size")
+        .long("marker-size")
+        .help("Use conflict markers of <n> characters")
+        .long_help(
+            "Use conflict markers of <n> characters instead of the default 7 when \
+             merges leave conflicts. Longer markers help to tell the conflict markers \
+             apart from content that contains marker-like lines such as \"=======\". \
+             Files given a \"conflict-marker-size\" attribute by the repository keep \
+             their own marker size.",
+        )
+        .value_name("n")
+        .value_parser(parse_marker_size)
+}
+
+/// Parse a conflict marker size, which may not be shorter than git's default.
+fn parse_marker_size(s: &str) -> anyhow::Result<usize> {
+    let size = parse_usize(s)?;
+    if size < 7 {
+        Err(anyhow::anyhow!("marker size must be at least 7"))
+    } else {
+        Ok(size)
+    }
+}
+
+pub(crate) fn name_collision_arg() -> clap::Arg {
+    Arg::new("name-collision")
+        .long("name-collision")
+        .help("How to resolve derived patch names colliding with hidden patches")
+        .long_help(
+            "Determine how a derived patch name that collides with a hidden patch is \
+             resolved. With `suffix`, the default, the name is suffixed with a unique \
+             integer as for any other collision. With `error`, the command fails, \
+             naming the colliding hidden patch. With `replace-hidden`, the hidden \
+             patch is renamed aside with a `.old` suffix and its name is taken. This \
+             overrides the \"stgit.name.collision\" variable.",
+        )
+        .value_name("mode")
+        .value_parser(["suffix", "error", "replace-hidden"])
+}
+
+/// Get the name collision mode from `--name-collision` or `stgit.name.collision`.
+pub(crate) fn resolve_name_collision(
+    config: &StackConfig,
+    matches: &clap::ArgMatches,
+) -> Result<NameCollision> {
+    if let Some(mode) = get_one_str(matches, "name-collision") {
+        mode.parse()
+    } else if let Some(mode) = config.string("stgit.name.collision") {
+        mode.to_str_lossy().parse()
+    } else {
+        Ok(NameCollision::default())
+    }
+}
+
 pub(crate) fn committer_date_is_author_date_arg() -> clap::Arg {
     Arg::new("committer-date-is-author-date")
         .long("committer-date-is-author-date")
@@ -78,6 +325,484 @@ pub(crate) fn committer_date_is_author_date_arg() -> clap::Arg {
         .action(clap::ArgAction::SetTrue)
 }
 
+pub(crate) fn ignore_date_arg() -> clap::Arg {
+    Arg::new("ignore-date")
+        .long("ignore-date")
+        .alias("reset-author-date")
+        .help("Use current time as author date")
+        .long_help(
+            "Instead of using the author date of the original commit, use the current \
+             time as the author date of rewritten commits.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// The `--dry-run` option for commands modifying the stack.
+pub(crate) fn dry_run_arg() -> Arg {
+    Arg::new("dry-run")
+        .long("dry-run")
+        .help("Show what would be done without changing anything")
+        .long_help(
+            "Report the effect the command would have on the stack, including the \
+             patches that would be pushed, popped, created, or removed, the patches \
+             that would become empty, and any merge conflicts that would occur. \
+             Pushes are simulated with in-memory merges and no references, the index, \
+             or the worktree are modified.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// The `--date` option for fixing the dates of the commits created by a command.
+/// Help paragraph describing the date formats accepted by [`parse_time()`].
+pub(crate) const DATE_FORMATS_HELP: &str =
+    "The date may be given in ISO 8601 format, e.g. \"2022-01-06 09:32:07 -0500\", \
+     RFC 2822 format, e.g. \"Thu, 6 Jan 2022 09:32:07 -0500\", as a Unix timestamp, \
+     e.g. \"@1641479527 -0500\", or relative to the current time, e.g. \"now\", \
+     \"yesterday\", \"2 weeks ago\", or \"last friday 3pm\". Dates without a time \
+     zone offset are in the local time zone.";
+
+pub(crate) fn date_arg() -> Arg {
+    Arg::new("date")
+        .long("date")
+        .help("Use <date> for the author and committer dates of new commits")
+        .long_help(format!(
+            "Use <date> as the committer date of every commit created by this \
+             command, and as the author date of any new patch. This allows the same \
+             sequence of operations to yield identical commit ids.\n\
+             \n\
+             The committer date is otherwise taken from the `STG_COMMITTER_DATE` \
+             environment variable, the `stgit.committerdate` configuration value, or \
+             the current time, in that order of precedence. Likewise, the author date \
+             of a new patch is taken from '--authdate', `STG_AUTHOR_DATE`, \
+             `stgit.authordate`, or the current time.\n\
+             \n\
+             {DATE_FORMATS_HELP}"
+        ))
+        .value_name("date")
+        .num_args(1)
+        .value_parser(parse_time)
+        .value_hint(clap::ValueHint::Other)
+}
+
+/// Parse a date argument, e.g. `2 days ago` or `2022-01-06 09:32:07`, into a time.
+pub(crate) fn parse_time(s: &str) -> Result<gix::date::Time> {
+    use crate::ext::TimeExtended;
+    gix::date::Time::parse_time(s)
+}
+
+/// Get the date from `--date`, which fixes the dates of the commits created.
+///
+/// The date takes precedence over the `STG_AUTHOR_DATE` and `STG_COMMITTER_DATE`
+/// environment variables and their configuration counterparts.
+pub(crate) fn get_fixed_date(matches: &clap::ArgMatches) -> Option<gix::date::Time> {
+    matches.get_one::<gix::date::Time>("date").copied()
+}
+
+/// The `--gpg-sign` and `--no-gpg-sign` options for commands creating patch commits.
+pub(crate) fn sign_args() -> [Arg; 2] {
+    [
+        Arg::new("gpg-sign")
+            .long("gpg-sign")
+            .help("Sign the patch commits created by this command")
+            .long_help(
+                "Sign the patch commits created by this command, regardless of \
+                 \"commit.gpgsign\". Commits are signed by git using the configured \
+                 \"gpg.format\" and \"user.signingkey\". Unlike signing enabled by \
+                 \"commit.gpgsign\", the command fails up front when no signing key \
+                 can be determined.",
+            )
+            .overrides_with("no-gpg-sign")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("no-gpg-sign")
+            .long("no-gpg-sign")
+            .help("Do not sign the patch commits created by this command")
+            .long_help(
+                "Do not sign the patch commits created by this command, even if \
+                 \"commit.gpgsign\" is set to \"true\".",
+            )
+            .overrides_with("gpg-sign")
+            .action(clap::ArgAction::SetTrue),
+    ]
+}
+
+/// Override `commit.gpgsign` for this process if `--gpg-sign` or `--no-gpg-sign` is
+/// specified.
+///
+/// The override is made in the repository's in-memory configuration such that every
+/// patch commit created with this repository is affected. Stack metadata commits
+/// remain governed by `stgit.gpgsign`.
+pub(crate) fn set_commit_signing(
+    repo: &mut gix::Repository,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let sign = if matches.get_flag("gpg-sign") {
+        check_signing_key(&repo.config_snapshot())?;
+        true
+    } else if matches.get_flag("no-gpg-sign") {
+        false
+    } else {
+        return Ok(());
+    };
+    let mut config = repo.config_snapshot_mut();
+    config.set_raw_value(&"commit.gpgsign", if sign { "true" } else { "false" })?;
+    config.commit()?;
+    Ok(())
+}
+
+/// Check that a key is available for explicitly requested signatures.
+///
+/// For the default `openpgp` and the `x509` formats, git falls back to the committer
+/// identity when `user.signingkey` is not set. The `ssh` format has no such fallback
+/// and instead needs either `user.signingkey` or `gpg.ssh.defaultKeyCommand`.
+fn check_signing_key(config: &gix::config::Snapshot) -> Result<()> {
+    let has_signing_key = config
+        .string("user.signingkey")
+        .is_some_and(|key| !key.is_empty());
+    match config.string("gpg.format").as_deref().map(|f| f.as_bytes()) {
+        None | Some(b"openpgp" | b"x509") => Ok(()),
+        Some(b"ssh") => {
+            if has_signing_key || config.string("gpg.ssh.defaultKeyCommand").is_some() {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "`--gpg-sign` requires a signing key; configure `user.signingkey` \
+                     or `gpg.ssh.defaultKeyCommand` for `gpg.format=ssh`"
+                ))
+            }
+        }
+        Some(format) => Err(anyhow!("unsupported `gpg.format` `{}`", format.as_bstr())),
+    }
+}
+
+/// The `--force-overwrite-untracked` option for checkouts that would overwrite
+/// untracked files.
+pub(crate) fn force_overwrite_untracked_arg() -> Arg {
+    Arg::new("force-overwrite-untracked")
+        .long("force-overwrite-untracked")
+        .help("Overwrite untracked files that are not ignored")
+        .long_help(
+            "Allow untracked files in the work tree to be overwritten by files from \
+             the patches being checked out. Untracked files that are ignored are \
+             always overwritten, but by default the command fails if an untracked \
+             file that is not ignored would be overwritten.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// The `--restore-mtime` option for preserving modification times of files whose
+/// content is unchanged by checkouts.
+pub(crate) fn restore_mtime_arg() -> Arg {
+    Arg::new("restore-mtime")
+        .long("restore-mtime")
+        .help("Keep modification times of files with unchanged content")
+        .long_help(
+            "Restore the modification times of work tree files whose content after \
+             the operation is the same as before, even if the files were rewritten \
+             along the way. This helps build systems that rely on modification times \
+             avoid unnecessary rebuilds. This is best-effort: files whose \
+             modification times cannot be read or set, for example on filesystems \
+             lacking the capability, are skipped. This behavior may also be enabled \
+             by setting \"stgit.checkout.restore-mtime\" to \"true\".",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// The `--verbose`/`-v` option for reporting timings of patch pushes.
+pub(crate) fn push_verbose_arg() -> Arg {
+    Arg::new("verbose")
+        .long("verbose")
+        .short('v')
+        .help("Report push timings and a summary")
+        .long_help(
+            "Report the time taken to merge and check out each pushed patch, and \
+             print a summary of the pushed patches, conflicts, empty patches, and \
+             total elapsed time when done. The time taken to check the index and \
+             worktree status before pushing is reported first, where applicable.",
+        )
+        .action(clap::ArgAction::Count)
+}
+
+/// The `--no-backup` option for commands that discard changes with `--hard`.
+pub(crate) fn no_backup_arg() -> Arg {
+    Arg::new("no-backup")
+        .long("no-backup")
+        .help("Do not save the discarded changes")
+        .long_help(
+            "Do not save the index and worktree changes discarded by '--hard'. By \
+             default, the discarded changes are saved to \
+             `refs/stgit/discarded/<branch>` and may be recovered with `git stash \
+             apply`.",
+        )
+        .requires("hard")
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// Save the changes about to be discarded by a `--hard` command.
+///
+/// Nothing is saved unless `--hard` is in effect and `--no-backup` is not.
+pub(crate) fn backup_discarded_changes(
+    repo: &gix::Repository,
+    branch_name: &str,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    if !matches.get_flag("hard") || matches.get_flag("no-backup") {
+        return Ok(());
+    }
+    if let Some(refname) = crate::stack::discarded::record(repo, branch_name)? {
+        crate::print_info_message(
+            matches,
+            &format!(
+                "saved discarded changes to `{refname}`; \
+                 recover them with `git stash apply {refname}`"
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// The `--autostash` option for `stg rebase` and `stg pull`.
+pub(crate) fn autostash_arg(operation: &str) -> Arg {
+    Arg::new("autostash")
+        .long("autostash")
+        .help(format!(
+            "Stash changes before the {operation} and reapply them after"
+        ))
+        .long_help(format!(
+            "Automatically stash the index and work tree changes before the \
+             {operation} begins, and reapply them after the patches are pushed back. \
+             This allows a {operation} to be performed on a dirty work tree. When \
+             pushing the patches back halts on conflicts, the changes are reapplied \
+             once the push is continued or aborted, or the {operation} is undone. \
+             Untracked files are also stashed when \"stgit.autostash.includeUntracked\" \
+             is set. If reapplying the changes results in conflicts, they are kept in \
+             the stash as `stash@{{0}}`. Defaults to the \"stgit.autostash\" \
+             configuration variable."
+        ))
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// Determine whether to autostash from `--autostash` or `stgit.autostash`.
+pub(crate) fn resolve_autostash(config: &StackConfig, matches: &clap::ArgMatches) -> bool {
+    matches.get_flag("autostash")
+        || config
+            .try_boolean("stgit.autostash")
+            .transpose()
+            .unwrap_or_else(|e| {
+                crate::print_warning_message(
+                    matches,
+                    &format!("Invalid config value `stgit.autostash`: {e}"),
+                );
+                Some(false)
+            })
+            .unwrap_or(false)
+}
+
+/// Stash local changes for the duration of a rebase or pull when autostashing.
+///
+/// Returns whether any changes were stashed. Without autostashing, the index and work
+/// tree are required to be clean.
+pub(crate) fn autostash_changes(
+    repo: &gix::Repository,
+    config: &StackConfig,
+    branch_name: &str,
+    matches: &clap::ArgMatches,
+) -> Result<bool> {
+    if resolve_autostash(config, matches) {
+        let include_untracked = config
+            .boolean("stgit.autostash.includeUntracked")
+            .unwrap_or(false);
+        let stashed = crate::stack::autostash::save(repo, branch_name, include_untracked)?;
+        if stashed {
+            crate::print_info_message(
+                matches,
+                &format!(
+                    "Created autostash `{}`",
+                    crate::stack::autostash::autostash_refname(branch_name)
+                ),
+            );
+        }
+        Ok(stashed)
+    } else {
+        repo.stupid()
+            .statuses(None)?
+            .check_index_and_worktree_clean()?;
+        Ok(false)
+    }
+}
+
+/// Reapply changes autostashed by a rebase or pull of the branch, if any.
+///
+/// Conflicts from reapplying the changes are reported as an error after the changes
+/// are kept in the stash.
+pub(crate) fn reapply_autostash(
+    repo: &gix::Repository,
+    branch_name: &str,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    use crate::stack::autostash::{reapply, Reapplied};
+    match reapply(repo, branch_name)? {
+        Reapplied::Nothing => Ok(()),
+        Reapplied::Clean => {
+            crate::print_info_message(matches, "Applied autostash");
+            Ok(())
+        }
+        Reapplied::Conflicts => Err(crate::cmd::Error::CausedConflicts(
+            "reapplying autostash resulted in conflicts\n\
+             your changes are safe in `stash@{0}`; resolve the conflicts and run \
+             `git stash drop` when done"
+                .to_string(),
+        )
+        .into()),
+    }
+}
+
+/// The `--stdin`, `-z`, and `--allow-empty-selection` options for reading the patches
+/// to operate on from standard input.
+///
+/// The `--stdin` option conflicts with the command line patches argument identified by
+/// `patchranges_id`. Use [`get_patch_ranges()`] to get the patches from either source.
+pub(crate) fn stdin_patches_args(patchranges_id: &'static str) -> [Arg; 3] {
+    [
+        Arg::new("stdin")
+            .long("stdin")
+            .help("Read patches from standard input, one per line")
+            .long_help(
+                "Read the patches from standard input instead of the command line. \
+                 Each line names one patch, using any patch locator or range allowed \
+                 on the command line, e.g. as output by `stg series --format %(name)`. \
+                 Blank lines are ignored. The patches are resolved the same way as \
+                 patches given on the command line, in the order read.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with(patchranges_id),
+        Arg::new("stdin-nul")
+            .short('z')
+            .help("Patches read with --stdin are NUL-terminated")
+            .action(clap::ArgAction::SetTrue)
+            .requires("stdin"),
+        Arg::new("allow-empty-selection")
+            .long("allow-empty-selection")
+            .help("Do nothing, successfully, if --stdin reads no patches")
+            .action(clap::ArgAction::SetTrue)
+            .requires("stdin"),
+    ]
+}
+
+/// Get the patches given on the command line, or read from standard input with
+/// `--stdin`.
+///
+/// `None` is returned if no patches are given on the command line and `--stdin` is not
+/// used. Reading no patches from standard input is an error unless
+/// `--allow-empty-selection` is specified, in which case an empty list is returned.
+pub(crate) fn get_patch_ranges<T>(matches: &clap::ArgMatches, id: &str) -> Result<Option<Vec<T>>>
+where
+    T: std::str::FromStr + Clone + Send + Sync + 'static,
+    T::Err: std::fmt::Display,
+{
+    if !matches.get_flag("stdin") {
+        return Ok(matches
+            .get_many::<T>(id)
+            .map(|specs| specs.cloned().collect()));
+    }
+
+    let mut input = Vec::new();
+    std::io::Read::read_to_end(&mut std::io::stdin(), &mut input)?;
+    let nul_terminated = matches.get_flag("stdin-nul");
+    let records: Vec<&[u8]> = if nul_terminated {
+        input.split_str("\0").collect()
+    } else {
+        input.lines().map(|line| line.trim()).collect()
+    };
+    let mut specs = Vec::new();
+    for record in records.into_iter().filter(|record| !record.is_empty()) {
+        let record = record
+            .to_str()
+            .map_err(|_| anyhow!("non-UTF-8 patch `{}` read from stdin", record.as_bstr()))?;
+        let spec = record
+            .parse::<T>()
+            .map_err(|e| anyhow!("{e} read from stdin"))?;
+        specs.push(spec);
+    }
+    if specs.is_empty() && !matches.get_flag("allow-empty-selection") {
+        return Err(anyhow!(
+            "no patches read from stdin; use `--allow-empty-selection` to allow an empty \
+             selection"
+        ));
+    }
+    Ok(Some(specs))
+}
+
+/// The `--include-gaps` option for commands operating on contiguous patches.
+pub(crate) fn include_gaps_arg() -> Arg {
+    Arg::new("include-gaps")
+        .long("include-gaps")
+        .help("Include patches in between discontiguous patches")
+        .long_help(
+            "Select the minimal contiguous range covering the specified patches, \
+             including any patches in between them that were not specified. The \
+             included patches are reported. Without this option, the user is asked \
+             whether to include them when running in a terminal. Hidden patches are \
+             never included unless specified.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// Resolve patch ranges that must be contiguous, offering to fill any gaps.
+///
+/// When the resolved patches are discontiguous, the patches in between are included
+/// if `--include-gaps` is specified or if the user confirms when prompted on a
+/// terminal.
+pub(crate) fn resolve_names_contiguous<'a>(
+    stack: &'a impl StackStateAccess<'a>,
+    ranges: impl IntoIterator<Item = &'a PatchRange> + Clone,
+    allow: RangeConstraint,
+    matches: &clap::ArgMatches,
+) -> Result<Vec<PatchName>> {
+    match patchrange::resolve_names_contiguous(stack, ranges.clone(), allow) {
+        Err(err @ patchrange::Error::NotContiguous { .. }) => {
+            let (patches, gap_patches) =
+                patchrange::resolve_names_filling_gaps(stack, ranges, allow)?;
+            let gap_list = gap_patches
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            if matches.get_flag("include-gaps") || confirm_include_gaps(&gap_list)? {
+                crate::print_info_message(
+                    matches,
+                    &format!("including patches in between: {gap_list}"),
+                );
+                Ok(patches)
+            } else {
+                Err(anyhow!(
+                    "{err}; use `--include-gaps` to also include {gap_list}"
+                ))
+            }
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Ask the user whether to include the patches in a gap, if running in a terminal.
+fn confirm_include_gaps(gap_list: &str) -> Result<bool> {
+    use std::io::{BufRead, Write};
+
+    use is_terminal::IsTerminal;
+
+    if !(std::io::stdin().is_terminal() && std::io::stderr().is_terminal()) {
+        return Ok(false);
+    }
+    let mut stderr = std::io::stderr();
+    write!(
+        stderr,
+        "Patches are not contiguous. Include {gap_list} in between? [y/N] "
+    )?;
+    stderr.flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
 /// The `--diff-opt`/`-O` option for pass-through to subordinate `git` processes.
 pub(crate) fn diff_opts_arg() -> Arg {
     Arg::new("git-diff-opt")
@@ -98,6 +823,111 @@ pub(crate) fn diff_opts_arg() -> Arg {
         .value_hint(clap::ValueHint::Other)
 }
 
+/// The `--unified`/`-U` option for commands that generate diffs.
+pub(crate) fn unified_arg() -> Arg {
+    Arg::new("unified")
+        .long("unified")
+        .short('U')
+        .help("Generate diffs with <n> lines of context")
+        .long_help(
+            "Generate diffs with <n> lines of context instead of the usual three. The \
+             default is taken from the diff.context configuration.",
+        )
+        .value_name("n")
+        .value_parser(parse_usize)
+}
+
+/// The `--numstat` option for commands that show diffs.
+///
+/// Commands using this option are expected to also have a `--stat` option, which
+/// this option conflicts with.
+pub(crate) fn numstat_arg() -> Arg {
+    Arg::new("numstat")
+        .long("numstat")
+        .help("Show the number of added and deleted lines instead of the diff")
+        .long_help(
+            "Show the number of added and deleted lines of each file instead of the \
+             diff, in the machine-readable format of `git diff --numstat`. Binary \
+             files are shown with `-` instead of line counts.",
+        )
+        .conflicts_with("stat")
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// Get the diff output format from the `--stat` and `--numstat` options.
+pub(crate) fn get_diff_format(matches: &clap::ArgMatches) -> DiffFormat {
+    if matches.get_flag("stat") {
+        DiffFormat::Stat
+    } else if matches.get_flag("numstat") {
+        DiffFormat::NumStat
+    } else {
+        DiffFormat::Patch
+    }
+}
+
+/// The `--fuzz` option for commands that apply diffs.
+pub(crate) fn fuzz_arg() -> Arg {
+    Arg::new("fuzz")
+        .long("fuzz")
+        .help("Allow up to <n> lines of mismatched context per hunk")
+        .long_help(
+            "Allow up to <n> lines of mismatched context at the start and end of each \
+             hunk. Since `git apply` has no notion of fuzz, this is equivalent to \
+             '-C' with the widest context in the diff reduced by <n>.",
+        )
+        .value_name("n")
+        .value_parser(parse_usize)
+        .conflicts_with("context-lines")
+}
+
+/// The `--ignore-whitespace` option for commands that apply diffs.
+pub(crate) fn ignore_whitespace_arg() -> Arg {
+    Arg::new("ignore-whitespace")
+        .long("ignore-whitespace")
+        .help("Ignore whitespace differences in context lines")
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// The `--skip-applied` option for commands that apply diffs.
+pub(crate) fn skip_applied_arg() -> Arg {
+    Arg::new("skip-applied")
+        .long("skip-applied")
+        .help("Skip hunks that are already applied")
+        .long_help(
+            "Skip the hunks of the diff that are already applied instead of failing to \
+             apply the diff. A hunk is already applied when it applies in reverse, but \
+             not forward. The number of skipped hunks is reported for each file and \
+             only the remaining hunks are applied.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// The `--whitespace` option for commands that apply diffs.
+pub(crate) fn whitespace_arg() -> Arg {
+    Arg::new("whitespace")
+        .long("whitespace")
+        .help("Handle whitespace errors in added lines according to <mode>")
+        .long_help(
+            "Handle whitespace errors in added lines according to <mode>, as with \
+             `git apply --whitespace`. What counts as a whitespace error is \
+             controlled by the core.whitespace configuration.\n\
+             \n\
+             Modes:\n\
+             \n  - 'nowarn' turns off the whitespace error warnings\
+             \n  - 'warn' warns about whitespace errors, but applies the diff as is\
+             \n  - 'fix' fixes whitespace errors, such as trailing whitespace and \
+             spaces before tabs, and reports the number of lines fixed in each file\
+             \n  - 'error' refuses to apply a diff that introduces whitespace errors\
+             \n  - 'error-all' is similar to 'error', but reports all errors\n\
+             \n\
+             The default is the value of the apply.whitespace configuration, or \
+             'warn' if that is not set.",
+        )
+        .hide_possible_values(true)
+        .value_name("mode")
+        .value_parser(["nowarn", "warn", "fix", "error", "error-all"])
+}
+
 /// Get a `&str` from a `clap::ArgMatches` instance for the given `id`.
 ///
 /// This function may be cleaner than calling `ArgMatches::get_one::<String>()` directly
@@ -121,21 +951,27 @@ pub(crate) fn parse_usize(s: &str) -> anyhow::Result<usize> {
 /// These options are meant to be passed to various subordinate `git` commands that take
 /// diff options.
 ///
-/// The base set of options come from `stgit.diff-opts` in the config. Additional
-/// options from `--diff-opt`/`-O` command line options are appended. And StGit
-/// command-specific policies for displaying the full object id (`--full-index`) and
-/// including binary diffs (`--binary`) are tacked on at the end.
+/// The base set of options come from `stgit.diff-opts` in the config, preceded by the
+/// number of context lines from `diff.context`, if set. Additional options from
+/// `--diff-opt`/`-O` command line options are appended, followed by the number of
+/// context lines from `--unified`/`-U`. And StGit command-specific policies for
+/// displaying the full object id (`--full-index`) and including binary diffs
+/// (`--binary`) are tacked on at the end.
 ///
 /// The returned `Vec<String>` is appropriate for inserting directly into the command
 /// line of subordinate `git` commands.
 pub(crate) fn get_diff_opts(
     matches: &clap::ArgMatches,
-    config: &gix::config::Snapshot,
+    config: &StackConfig,
     force_full_index: bool,
     force_binary: bool,
 ) -> Vec<String> {
     let mut opts = Vec::new();
 
+    if let Some(context_lines) = get_config_context_lines(config) {
+        opts.push(format!("--unified={context_lines}"));
+    }
+
     if let Some(value) = config.string("stgit.diff-opts") {
         if let Ok(value) = value.to_str() {
             for arg in value.split_ascii_whitespace() {
@@ -148,6 +984,10 @@ pub(crate) fn get_diff_opts(
         opts.extend(values.cloned());
     }
 
+    if let Ok(Some(context_lines)) = matches.try_get_one::<usize>("unified") {
+        opts.push(format!("--unified={context_lines}"));
+    }
+
     if force_full_index {
         opts.push(String::from("--full-index"));
     }
@@ -159,8 +999,33 @@ pub(crate) fn get_diff_opts(
     opts
 }
 
+/// Get the number of diff context lines from `--unified`/`-U` or `diff.context`.
+///
+/// `None` is returned when neither is set, leaving the choice to `git`.
+pub(crate) fn get_context_lines(matches: &clap::ArgMatches, config: &StackConfig) -> Option<usize> {
+    matches
+        .try_get_one::<usize>("unified")
+        .ok()
+        .flatten()
+        .copied()
+        .or_else(|| get_config_context_lines(config))
+}
+
+fn get_config_context_lines(config: &StackConfig) -> Option<usize> {
+    config
+        .integer("diff.context")
+        .and_then(|n| usize::try_from(n).ok())
+}
+
+pub(crate) fn resolve_restore_mtime(config: &StackConfig, matches: &clap::ArgMatches) -> bool {
+    matches.get_flag("restore-mtime")
+        || config
+            .boolean("stgit.checkout.restore-mtime")
+            .unwrap_or(false)
+}
+
 pub(crate) fn resolve_allow_push_conflicts(
-    config: &gix::config::Snapshot,
+    config: &StackConfig,
     matches: &clap::ArgMatches,
 ) -> bool {
     get_one_str(matches, "conflicts")
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Resolution of abbreviated and misspelled command names.
+//!
+//! A command name that is neither a builtin command nor an alias is resolved to the
+//! single command or alias it is a prefix of. Otherwise, similarly named commands are
+//! suggested, and with `stgit.autocorrect` set to a delay, the most similar command is
+//! run in place of the misspelled one, as with git's `help.autocorrect`.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use crate::alias::Aliases;
+
+/// Error for a command name that cannot be resolved.
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub(crate) struct UnresolvedCommandError(String);
+
+/// How command names that are not exact matches are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Neither resolve prefixes nor suggest similar commands.
+    Never,
+
+    /// Resolve unambiguous prefixes and suggest similar commands.
+    Show,
+
+    /// Resolve unambiguous prefixes and run the most similar command after a delay.
+    Run(Duration),
+}
+
+impl Mode {
+    /// Determine the mode from the `stgit.autocorrect` configuration variable.
+    ///
+    /// As with git's `help.autocorrect`, a positive number is the delay in tenths of a
+    /// second before running the most similar command and a negative number or
+    /// "immediate" runs it without delay.
+    fn from_config(repo: Option<&gix::Repository>) -> Result<Self> {
+        let snapshot = repo.map(|repo| repo.config_snapshot());
+        let global_config_file;
+        let config_file = if let Some(snapshot) = snapshot.as_ref() {
+            Some(snapshot.plumbing())
+        } else {
+            global_config_file = gix::config::File::from_globals().ok();
+            global_config_file.as_ref()
+        };
+        let Some(value) = config_file.and_then(|file| file.string("stgit.autocorrect")) else {
+            return Ok(Self::Show);
+        };
+        let value = value.to_str_lossy();
+        match value.as_ref() {
+            "never" => Ok(Self::Never),
+            "show" | "false" | "no" | "off" | "0" | "" => Ok(Self::Show),
+            "immediate" => Ok(Self::Run(Duration::ZERO)),
+            _ => match value.parse::<i64>() {
+                Ok(tenths) => Ok(Self::Run(Duration::from_millis(
+                    u64::try_from(tenths).unwrap_or(0) * 100,
+                ))),
+                Err(_) => Err(anyhow!("invalid stgit.autocorrect value `{value}`")),
+            },
+        }
+    }
+}
+
+/// Resolve a name that is neither a builtin command nor an alias.
+///
+/// The builtin command or alias to run instead is returned, if any. `None` is returned
+/// when there is nothing similar to suggest, or when autocorrection is disabled, such
+/// that the usual unrecognized subcommand error may be reported.
+pub(crate) fn resolve(
+    name: &str,
+    builtin_names: impl Iterator<Item = &'static str>,
+    aliases: &Aliases,
+    repo: Option<&gix::Repository>,
+) -> Result<Option<String>> {
+    let mode = Mode::from_config(repo)?;
+    if mode == Mode::Never {
+        return Ok(None);
+    }
+
+    let mut candidates: Vec<&str> = aliases.keys().map(String::as_str).collect();
+    for builtin_name in builtin_names.chain(std::iter::once("help")) {
+        candidates.push(builtin_name);
+    }
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    let prefixed: Vec<&str> = candidates
+        .iter()
+        .copied()
+        .filter(|candidate| candidate.starts_with(name))
+        .collect();
+    match prefixed.as_slice() {
+        [candidate] => return Ok(Some(candidate.to_string())),
+        [] => {}
+        _ => {
+            return Err(UnresolvedCommandError(format!(
+                "ambiguous command `{name}`; could be any of:\n{}",
+                list_candidates(&prefixed)
+            ))
+            .into())
+        }
+    }
+
+    let mut similar: Vec<&str> = candidates
+        .iter()
+        .copied()
+        .filter(|candidate| strsim::jaro_winkler(candidate, name) > 0.75)
+        .collect();
+    similar.sort_by_cached_key(|candidate| strsim::levenshtein(candidate, name));
+    if let Some(best_distance) = similar
+        .first()
+        .map(|candidate| strsim::levenshtein(candidate, name))
+    {
+        similar.retain(|candidate| strsim::levenshtein(candidate, name) == best_distance);
+    }
+
+    match (mode, similar.as_slice()) {
+        (_, []) => Ok(None),
+        (Mode::Run(delay), [candidate]) => {
+            if delay.is_zero() {
+                eprintln!(
+                    "warning: `{name}` is not a stg command; \
+                     continuing assuming that you meant `{candidate}`"
+                );
+            } else {
+                eprintln!(
+                    "warning: `{name}` is not a stg command; \
+                     continuing in {:.1} seconds assuming that you meant `{candidate}`",
+                    delay.as_secs_f64()
+                );
+                std::thread::sleep(delay);
+            }
+            Ok(Some(candidate.to_string()))
+        }
+        (_, [candidate]) => Err(UnresolvedCommandError(format!(
+            "`{name}` is not a stg command\n\
+             the most similar command is `{candidate}`"
+        ))
+        .into()),
+        (_, candidates) => Err(UnresolvedCommandError(format!(
+            "`{name}` is not a stg command\n\
+             the most similar commands are:\n{}",
+            list_candidates(candidates)
+        ))
+        .into()),
+    }
+}
+
+fn list_candidates(candidates: &[&str]) -> String {
+    candidates
+        .iter()
+        .map(|candidate| format!("    {candidate}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -61,4 +61,16 @@ impl BranchLocator {
             }
         }
     }
+
+    /// Resolve the locator to a branch that may be modified from the current worktree.
+    ///
+    /// Branches checked out in other worktrees may be read, but not modified.
+    pub(crate) fn resolve_for_update<'repo>(
+        &self,
+        repo: &'repo gix::Repository,
+    ) -> Result<Branch<'repo>> {
+        let branch = self.resolve(repo)?;
+        branch.check_not_checked_out_elsewhere()?;
+        Ok(branch)
+    }
 }
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg add` implementation.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use bstr::{BString, ByteSlice};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    ext::RepositoryExtended,
+    index::{IndexEditor, WorktreeState},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "add",
+    category: super::CommandCategory::PatchManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Add file contents to the index")
+        .long_about(
+            "Add the work tree contents of the given files to the index. Files in \
+             given directories are added recursively. Tracked files that no longer \
+             exist in the work tree are removed from the index.\n\
+             \n\
+             Untracked files that are ignored by the repository's ignore rules are \
+             not added unless '--force' is used.\n\
+             \n\
+             Adding a file with conflicts from pushing a patch marks its conflict as \
+             resolved, replacing the file's unmerged index entries with its work tree \
+             contents.\n\
+             \n\
+             As with git-add(1), content filters and end-of-line conversions \
+             configured with gitattributes(5) are applied to the added content.",
+        )
+        .override_usage(super::make_usage(
+            "stg add",
+            &["[OPTIONS] <path>...", "[OPTIONS] --update [<path>...]"],
+        ))
+        .arg(
+            Arg::new("paths")
+                .help("Files or directories to add")
+                .value_name("path")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::AnyPath)
+                .required_unless_present("update"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Allow adding otherwise ignored files")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("update")
+                .long("update")
+                .short('u')
+                .help("Only update files that are already tracked")
+                .long_help(
+                    "Only update the index entries of files that are already tracked. \
+                     Without paths, all tracked files in the work tree are updated.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sparse")
+                .long("sparse")
+                .help("Allow updating files outside of the sparse-checkout")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let mut editor = IndexEditor::open(&repo)?;
+    let force = matches.get_flag("force");
+    let update = matches.get_flag("update");
+
+    let paths = if let Some(paths) = matches.get_many::<PathBuf>("paths") {
+        paths
+            .map(|path| editor.root_relative_path(path))
+            .collect::<Result<Vec<BString>>>()?
+    } else {
+        vec![BString::default()]
+    };
+
+    let mut to_add: Vec<BString> = Vec::new();
+    let mut to_remove: Vec<BString> = Vec::new();
+    let mut ignored: Vec<BString> = Vec::new();
+
+    for path in &paths {
+        let tracked = editor.tracked_paths(path.as_bstr());
+        for tracked_path in &tracked {
+            if editor.is_skip_worktree(tracked_path.as_bstr()) {
+                // Tracked files outside the sparse-checkout are absent from the work
+                // tree and must not be taken as being removed.
+                if matches!(
+                    editor.worktree_state(tracked_path.as_bstr())?,
+                    WorktreeState::Changed
+                ) {
+                    to_add.push(tracked_path.clone());
+                }
+                continue;
+            }
+            match editor.worktree_state(tracked_path.as_bstr())? {
+                WorktreeState::Missing => to_remove.push(tracked_path.clone()),
+                WorktreeState::Unchanged if !editor.is_conflicted(tracked_path.as_bstr()) => {}
+                _ => to_add.push(tracked_path.clone()),
+            }
+        }
+
+        if update {
+            continue;
+        }
+
+        let work_path = editor.work_path(path.as_bstr());
+        match work_path.symlink_metadata() {
+            Ok(meta) if meta.is_dir() => {
+                let mut files = Vec::new();
+                editor.collect_files(path.as_bstr(), force, &mut files)?;
+                to_add.extend(
+                    files
+                        .into_iter()
+                        .filter(|file| !editor.is_tracked(file.as_bstr())),
+                );
+            }
+            Ok(_) => {
+                if !editor.is_tracked(path.as_bstr()) {
+                    if !force && editor.is_ignored(path.as_bstr(), false)? {
+                        ignored.push(path.clone());
+                    } else {
+                        to_add.push(path.clone());
+                    }
+                }
+            }
+            Err(_) if tracked.is_empty() => {
+                return Err(anyhow!(
+                    "pathspec `{}` did not match any files",
+                    path.to_str_lossy()
+                ));
+            }
+            Err(_) => {}
+        }
+    }
+
+    if !ignored.is_empty() {
+        let mut msg = "the following paths are ignored:".to_string();
+        for path in ignored {
+            msg.push_str(&format!("\n  {}", path.to_str_lossy()));
+        }
+        msg.push_str("\nuse `--force` to add them");
+        return Err(anyhow!(msg));
+    }
+
+    to_add.sort();
+    to_add.dedup();
+    to_remove.sort();
+    to_remove.dedup();
+
+    if !matches.get_flag("sparse") {
+        let outside: Vec<&BString> = to_add
+            .iter()
+            .chain(to_remove.iter())
+            .filter(|path| editor.is_outside_sparse_checkout(path.as_bstr()))
+            .collect();
+        if !outside.is_empty() {
+            let mut msg = "the following paths are outside of the sparse-checkout:".to_string();
+            for path in outside {
+                msg.push_str(&format!("\n  {}", path.to_str_lossy()));
+            }
+            msg.push_str("\nuse `--sparse` to update them");
+            return Err(anyhow!(msg));
+        }
+    }
+
+    for path in &to_remove {
+        editor.remove(path.as_bstr());
+    }
+    editor.check_filters(&to_add.iter().map(|path| path.as_bstr()).collect::<Vec<_>>())?;
+    for path in &to_add {
+        editor.add(path.as_bstr())?;
+    }
+    editor.write()?;
+
+    for path in &to_remove {
+        crate::print_info_message(matches, &format!("Removed `{}`", path.to_str_lossy()));
+    }
+    for path in &to_add {
+        crate::print_info_message(matches, &format!("Added `{}`", path.to_str_lossy()));
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg base` implementation.
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::{CommitExtended, RepositoryExtended},
+    output::Output,
+    patch::SingleRevisionSpec,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "base",
+    category: super::CommandCategory::StackManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Print or set the stack base")
+        .long_about(
+            "Print or set the base commit of the stack.\n\
+             \n\
+             Without options, the commit id and subject of the stack's base commit \
+             are printed.\n\
+             \n\
+             With '--set', the stack base is moved to the given commit without \
+             rebasing the patches. This is useful when the history below the stack \
+             was rewritten, e.g. upstream, such that the new base has the same tree as \
+             the old base. The applied patches are reparented onto the new base while \
+             keeping their trees; no merges are performed. The change is recorded as a \
+             single entry in the stack log and may thus be undone with 'stg undo'.\n\
+             \n\
+             If patches are applied and the tree of the new base differs from the tree \
+             of the old base, reparenting the patches would silently change what the \
+             bottommost applied patch changes. Use 'stg rebase' to move the patches \
+             onto such a base instead. Use '--force' to reparent the patches \
+             regardless.",
+        )
+        .override_usage(super::make_usage(
+            "stg base",
+            &["[--branch <branch>]", "--set <committish> [--force]"],
+        ))
+        .arg(argset::branch_arg().conflicts_with("set"))
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .help("Move the stack base to <committish>")
+                .value_name("committish")
+                .value_parser(clap::value_parser!(SingleRevisionSpec)),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Move the base even if its tree differs from the old base")
+                .requires("set")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+
+    let Some(target_rev_spec) = matches.get_one::<SingleRevisionSpec>("set") else {
+        let stack = Stack::from_branch_locator(
+            &repo,
+            matches.get_one::<BranchLocator>("branch"),
+            InitializationPolicy::AllowUninitialized,
+        )?;
+        let base = stack.base();
+        let message = base.message_ex();
+        let message = message.decode()?;
+        let subject = message.lines().next().unwrap_or_default();
+        println!("{} {subject}", base.id);
+        return Ok(());
+    };
+
+    let target_name = matches
+        .get_raw("set")
+        .and_then(|mut values| values.next())
+        .expect("set is present")
+        .to_string_lossy()
+        .to_string();
+
+    let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+    repo.check_repository_state()?;
+    let statuses = repo.stupid().statuses(None)?;
+    statuses.check_conflicts()?;
+    stack.check_head_top_mismatch()?;
+
+    let target_commit = target_rev_spec.resolve(&repo, Some(&stack))?.commit;
+    if target_commit.id == stack.base().id {
+        return Ok(());
+    }
+
+    let applied = stack.applied().to_vec();
+    if !applied.is_empty()
+        && !matches.get_flag("force")
+        && target_commit.tree_id()? != stack.base().tree_id()?
+    {
+        return Err(anyhow!(
+            "tree of `{target_name}` differs from the tree of the stack base; \
+             moving the base would change the applied patches, \
+             use `stg rebase` or `--force` to reparent the patches anyway"
+        ));
+    }
+
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .print_delta(true)
+        .use_index_and_worktree(true)
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            trans.pop_patches(|pn| applied.contains(pn))?;
+            trans.set_base(target_commit.clone());
+            trans.push_tree_patches(&applied)
+        })
+        .execute(&format!("base set to {target_name}"))?;
+
+    Ok(())
+}
@@ -45,7 +45,8 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
         matches.get_one::<BranchLocator>("branch"),
         InitializationPolicy::RequireInitialized,
     )?;
-    if stack.is_protected(&repo.config_snapshot()) {
+    stack.check_not_checked_out_elsewhere()?;
+    if stack.is_protected() {
         return Err(anyhow!("clean up not permitted: this branch is protected"));
     } else if !matches.get_flag("force") && stack.all_patches().count() > 0 {
         return Err(anyhow!(
@@ -3,11 +3,12 @@
 //! `stg branch --delete` implementation.
 
 use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
 
 use crate::{
     branchloc::BranchLocator,
     ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -24,8 +25,15 @@ pub(super) fn command() -> clap::Command {
         .long_about(
             "Delete a branch.\n\
              \n\
-             The branch will not be deleted if there are any patches remaining unless \
-             the '--force' option is provided.\n\
+             When the branch has a StGit stack, a summary of the patches deleted along \
+             with the stack is reported: the number of applied, unapplied, and hidden \
+             patches, the date of the most recently changed patch, and how many patch \
+             commits are not reachable from any other ref, e.g. another branch, tag, \
+             or remote-tracking branch. Like unmerged branches with `git branch -d`, \
+             the branch will not be deleted if any of its patches would become \
+             unreachable unless the '--force' option is provided. When forced, the \
+             ids of the deleted stack state and branch head are reported, allowing the \
+             stack to be restored until the commits are garbage collected.\n\
              \n\
              If the current branch is selected for deletion, its parent branch must be \
              configured and the worktree must be clean. The parent branch will be \
@@ -42,14 +50,14 @@ pub(super) fn command() -> clap::Command {
         .arg(
             clap::Arg::new("force")
                 .long("force")
-                .help("Force deletion even if branch has patches")
+                .help("Force deletion even if patches would become unreachable")
                 .action(clap::ArgAction::SetTrue),
         )
 }
 
 pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
     let target_branch = if let Some(branch_loc) = matches.get_one::<BranchLocator>("branch-any") {
-        branch_loc.resolve(repo)?
+        branch_loc.resolve_for_update(repo)?
     } else if let Ok(branch) = repo.get_current_branch() {
         branch
     } else {
@@ -87,12 +95,43 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
         target_branch.clone(),
         InitializationPolicy::RequireInitialized,
     ) {
-        if stack.is_protected(&config_snapshot) {
+        if stack.is_protected() {
             return Err(anyhow!("delete not permitted: this branch is protected"));
-        } else if !matches.get_flag("force") && stack.all_patches().count() > 0 {
-            return Err(anyhow!(
-                "delete not permitted: the series still contains patches (override with --force)"
-            ));
+        }
+        if stack.all_patches().next().is_some() {
+            let num_unreachable = report_patches(repo, &stack, matches)?;
+            if num_unreachable > 0 {
+                if !matches.get_flag("force") {
+                    return Err(anyhow!(
+                        "delete not permitted: the series still contains patches not \
+                         reachable from other refs (override with --force)"
+                    ));
+                }
+                let state_id = repo
+                    .find_reference(stack.get_stack_refname())?
+                    .peel_to_id_in_place()?
+                    .detach();
+                let head_id = stack.get_branch_head().id;
+                crate::print_info_message(
+                    matches,
+                    &format!(
+                        "deleting stack state {state_id} of branch `{target_branchname}`; \
+                         until garbage collected, restore it with `git branch \
+                         {target_branchname} {head_id} && git update-ref \
+                         {} {state_id}`",
+                        stack.get_stack_refname(),
+                    ),
+                );
+            }
+        }
+        if let Some(linked_branchname) = stack.linked_branch() {
+            crate::print_warning_message(
+                matches,
+                &format!(
+                    "branch `{target_branchname}` is linked to `{linked_branchname}`, \
+                     which will no longer be updated"
+                ),
+            );
         }
         stack.deinitialize()?;
     }
@@ -112,3 +151,80 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
 
     Ok(())
 }
+
+/// Report the patches to be deleted along with the stack.
+///
+/// The number of patch commits that are not reachable from any ref other than the
+/// refs deleted along with the branch is returned.
+fn report_patches(
+    repo: &gix::Repository,
+    stack: &Stack,
+    matches: &clap::ArgMatches,
+) -> Result<usize> {
+    let branch_name = stack.get_branch_name();
+    let owned_refnames = [
+        format!("refs/heads/{branch_name}"),
+        stack.get_stack_refname().to_string(),
+    ];
+    let patch_ref_prefix = format!("refs/patches/{branch_name}/");
+    let is_owned = |refname: &str| {
+        owned_refnames.iter().any(|owned| owned == refname)
+            || refname.starts_with(&patch_ref_prefix)
+            || refname
+                .strip_prefix("refs/stgit/")
+                .and_then(|rest| rest.split_once('/'))
+                .is_some_and(|(_, name)| name == branch_name)
+    };
+    let mut other_tips = Vec::new();
+    for reference in repo.references()?.all()?.filter_map(Result::ok) {
+        if let Some(id) = reference.target().try_id() {
+            if !is_owned(reference.name().as_bstr().to_str_lossy().as_ref()) {
+                other_tips.push(id.to_owned());
+            }
+        }
+    }
+
+    let patch_commit_ids: Vec<gix::ObjectId> = stack
+        .all_patches()
+        .map(|pn| stack.get_patch_commit_id(pn))
+        .collect();
+    let unreachable = repo
+        .stupid()
+        .rev_list_excluding(patch_commit_ids.iter().copied(), other_tips)?;
+    let num_unreachable = patch_commit_ids
+        .iter()
+        .filter(|id| unreachable.contains(id))
+        .count();
+
+    let newest = stack
+        .all_patches()
+        .filter_map(|pn| stack.get_patch_commit(pn).committer().ok().map(|c| c.time))
+        .max_by_key(|time| time.seconds);
+
+    let num_patches = patch_commit_ids.len();
+    let plural = |n: usize| if n == 1 { "" } else { "es" };
+    let mut summary = format!(
+        "branch `{branch_name}` has {num_patches} patch{} ({} applied, {} unapplied, {} \
+         hidden)",
+        plural(num_patches),
+        stack.applied().len(),
+        stack.unapplied().len(),
+        stack.hidden().len(),
+    );
+    if let Some(newest) = newest {
+        summary.push_str(&format!(
+            ", the newest changed {}",
+            newest.format(gix::date::time::format::ISO8601)
+        ));
+    }
+    if num_unreachable == 0 {
+        summary.push_str("; all are reachable from other refs");
+    } else {
+        summary.push_str(&format!(
+            "; {num_unreachable} patch{} not reachable from other refs",
+            plural(num_unreachable),
+        ));
+    }
+    crate::print_info_message(matches, &summary);
+    Ok(num_unreachable)
+}
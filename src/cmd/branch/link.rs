@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg branch --link` implementation.
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    branchloc::BranchLocator,
+    stack::{InitializationPolicy, Stack, StackAccess},
+    wrap::PartialRefName,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("--link")
+        .override_usage(super::super::make_usage(
+            "stg branch --link",
+            &["<linked-branch> [branch]"],
+        ))
+        .about("Link a branch to follow the stack's branch head")
+        .long_about(
+            "Link a branch to follow the stack's branch head.\n\
+             \n\
+             The linked branch is immediately set to the branch head of the stack. \
+             Thereafter, whenever StGit updates the stack, the linked branch is \
+             force-updated to the new branch head in the same reference transaction \
+             as the branch and stack state updates. This is useful for external \
+             tools that watch a branch that is expected to always equal the top of \
+             the stack.\n\
+             \n\
+             The link is recorded in the 'branch.<branch>.stgit.linkedbranch' \
+             configuration variable, the branch-specific form of \
+             'stgit.linkedbranch'. Setting 'stgit.linkedbranch' itself links the \
+             stacks of all branches without a branch-specific value.",
+        )
+        .arg(
+            clap::Arg::new("linked-branch")
+                .help("Branch to link")
+                .value_name("linked-branch")
+                .required(true)
+                .value_parser(clap::value_parser!(PartialRefName)),
+        )
+        .arg(
+            clap::Arg::new("branch")
+                .help("Branch of the stack to link to")
+                .value_name("branch")
+                .value_parser(clap::value_parser!(BranchLocator)),
+        )
+}
+
+pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
+    let stack = Stack::from_branch_locator(
+        repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+    let linked_branchname = matches
+        .get_one::<PartialRefName>("linked-branch")
+        .expect("required argument");
+    if linked_branchname.as_ref() == stack.get_branch_name() {
+        return Err(anyhow!("a branch cannot be linked to itself"));
+    }
+
+    repo.reference(
+        format!("refs/heads/{linked_branchname}"),
+        stack.get_branch_head().id,
+        gix::refs::transaction::PreviousValue::Any,
+        format!("stgit: link to {}", stack.get_branch_name()),
+    )?;
+    stack.set_linked_branch(Some(linked_branchname))
+}
@@ -48,7 +48,7 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
 
     let config = repo.config_snapshot();
 
-    let mut stdout = crate::color::get_color_stdout(matches);
+    let mut stdout = crate::output::Output::new(matches).data();
     let mut color_spec = termcolor::ColorSpec::new();
 
     for branchname in &branchnames {
@@ -71,7 +71,7 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
             write!(stdout, "s")?;
             color_spec.clear();
             stdout.set_color(&color_spec)?;
-            if stack.is_protected(&config) {
+            if stack.is_protected() {
                 color_spec.set_fg(Some(termcolor::Color::Yellow));
                 stdout.set_color(&color_spec)?;
                 write!(stdout, "p\t")?;
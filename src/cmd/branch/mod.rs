@@ -7,9 +7,11 @@ mod clone;
 mod create;
 mod delete;
 mod describe;
+mod link;
 mod list;
 mod protect;
 mod rename;
+mod unlink;
 mod unprotect;
 
 use anyhow::Result;
@@ -22,6 +24,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "branch",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -34,7 +37,9 @@ fn make() -> clap::Command {
              \n\
              With no arguments, the current branch is printed to stdout.\n\
              \n\
-             With a single argument, switch to the named branch.\n\
+             With a single argument, switch to the named branch. Local changes in \
+             the work tree prevent switching unless '--merge' or '--autostash' is \
+             used.\n\
              \n\
              StGit supports specifying a branch using the `@{-<n>}` syntax supported \
              by git, including `-` as a synonym for `@{-1}`. Thus `stg branch -` may \
@@ -49,13 +54,15 @@ fn make() -> clap::Command {
             "stg branch",
             &[
                 "",
-                "[--merge] <branch>",
+                "[--merge | --autostash] <branch>",
                 "{--list,-l}",
                 "{--create,-c} <new-branch> [committish]",
                 "{--clone,-C} [new-branch]",
                 "{--rename,-r} [old-name] <new-name>",
                 "{--protect,-p} [branch]",
                 "{--unprotect,-u} [branch]",
+                "--link <linked-branch> [branch]",
+                "--unlink [branch]",
                 "{--delete,-D} [--force] [branch]",
                 "--cleanup [--force] [branch]",
                 "{--describe,-d} <description> [branch]",
@@ -67,6 +74,8 @@ fn make() -> clap::Command {
         .subcommand(self::rename::command())
         .subcommand(self::protect::command())
         .subcommand(self::unprotect::command())
+        .subcommand(self::link::command())
+        .subcommand(self::unlink::command())
         .subcommand(self::delete::command())
         .subcommand(self::cleanup::command())
         .subcommand(self::describe::command())
@@ -77,6 +86,20 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .requires("branch-any"),
         )
+        .arg(
+            clap::Arg::new("autostash")
+                .long("autostash")
+                .help("Stash local changes before switching and reapply them after")
+                .long_help(
+                    "Stash local changes in the index and work tree before switching \
+                     branches, and reapply them to the work tree once the other branch \
+                     is checked out. If reapplying the changes results in conflicts, \
+                     the stash entry is kept so that the changes are not lost.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .requires("branch-any")
+                .conflicts_with("merge"),
+        )
         .arg(
             clap::Arg::new("branch-any")
                 .help("Branch to switch to")
@@ -85,7 +108,7 @@ fn make() -> clap::Command {
         )
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     if let Some((subname, submatches)) = matches.subcommand() {
         match subname {
@@ -95,6 +118,8 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             "--rename" => self::rename::dispatch(&repo, submatches),
             "--protect" => self::protect::dispatch(&repo, submatches),
             "--unprotect" => self::unprotect::dispatch(&repo, submatches),
+            "--link" => self::link::dispatch(&repo, submatches),
+            "--unlink" => self::unlink::dispatch(&repo, submatches),
             "--delete" => self::delete::dispatch(&repo, submatches),
             "--cleanup" => self::cleanup::dispatch(&repo, submatches),
             "--describe" => self::describe::dispatch(&repo, submatches),
@@ -120,7 +145,7 @@ fn switch(
     let current_branchname = current_branch
         .as_ref()
         .and_then(|branch| branch.get_branch_partial_name().ok());
-    let target_branch = target_branch_loc.resolve(repo)?;
+    let target_branch = target_branch_loc.resolve_for_update(repo)?;
     let target_branchname = target_branch.get_branch_partial_name()?;
 
     if Some(&target_branchname) == current_branchname.as_ref() {
@@ -131,10 +156,32 @@ fn switch(
 
     let stupid = repo.stupid();
     let statuses = stupid.statuses(None)?;
-    if !matches.get_flag("merge") {
-        statuses.check_worktree_clean()?;
-    }
     statuses.check_conflicts()?;
+
+    if matches.get_flag("autostash") {
+        if statuses.check_index_and_worktree_clean().is_err() {
+            stupid.stash_push()?;
+            if let Err(e) = stupid.checkout(target_branchname.as_ref()) {
+                stupid.stash_pop()?;
+                return Err(e);
+            }
+            if !stupid.stash_pop()? {
+                return Err(super::Error::CausedConflicts(format!(
+                    "reapplying stashed changes on `{target_branchname}` resulted in \
+                     conflicts; the changes remain in the stash until `git stash drop`"
+                ))
+                .into());
+            }
+            return Ok(());
+        }
+    } else if !matches.get_flag("merge") {
+        statuses.check_worktree_clean().map_err(|_| {
+            anyhow::anyhow!(
+                "worktree not clean; use `--autostash` to stash and reapply local \
+                 changes, or `--merge` to merge them into `{target_branchname}`"
+            )
+        })?;
+    }
     stupid.checkout(target_branchname.as_ref())
 }
 
@@ -2,26 +2,39 @@
 
 //! `stg branch --rename` implementation.
 
-use std::str::FromStr;
+use std::collections::BTreeMap;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use bstr::{BString, ByteSlice};
-
-use crate::{
-    ext::RepositoryExtended,
-    stack::{state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess},
-    stupid::Stupid,
-    wrap::PartialRefName,
+use gix::refs::{
+    transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog},
+    FullName, Target,
 };
 
+use crate::{ext::RepositoryExtended, stack::metadata_references, wrap::PartialRefName};
+
 pub(super) fn command() -> clap::Command {
     clap::Command::new("--rename")
         .short_flag('r')
         .override_usage(super::super::make_usage(
             "stg branch --rename",
-            &["[old-name] <new-name>"],
+            &["[--force] [old-name] <new-name>"],
         ))
         .about("Rename an existing branch")
+        .long_about(
+            "Rename an existing branch.\n\
+             \n\
+             The branch, its stack state, and its patch references are renamed in a \
+             single reference transaction, such that either the whole rename succeeds \
+             or nothing is changed. The branch's configuration is also renamed, and \
+             the 'parentbranch', 'linkedbranch', and 'stackedon' settings of other \
+             branches referring to the renamed branch are updated.\n\
+             \n\
+             The rename is refused if StGit metadata, e.g. a stack state reference or \
+             patch references, already exists for the new branch name. Such metadata \
+             may be left behind when a branch is deleted with git instead of StGit. \
+             Use '--force' to overwrite it.",
+        )
         .arg(
             clap::Arg::new("branch-any")
                 .help("Optional name of branch to rename and new branch name")
@@ -30,6 +43,12 @@ pub(super) fn command() -> clap::Command {
                 .num_args(1..=2)
                 .value_parser(clap::value_parser!(PartialRefName)),
         )
+        .arg(
+            clap::Arg::new("force")
+                .long("force")
+                .help("Overwrite existing StGit metadata for the new branch name")
+                .action(clap::ArgAction::SetTrue),
+        )
 }
 
 pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
@@ -39,75 +58,227 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
         .collect();
     let current_branch_name;
     let (old_branchname, new_branchname) = if names.len() == 2 {
-        repo.get_branch(names[0])?;
         (names[0], names[1])
     } else {
         current_branch_name = repo.get_current_branch()?.get_branch_partial_name()?;
         (&current_branch_name, names[0])
     };
+    let force = matches.get_flag("force");
+
+    let old_branch = repo.get_branch(old_branchname)?;
+    let old_refname = FullName::from(old_branch.get_reference_name());
+    let new_refname = FullName::try_from(format!("refs/heads/{new_branchname}"))?;
+    if old_branchname == new_branchname {
+        return Ok(());
+    } else if repo.try_find_reference(new_refname.as_ref())?.is_some() {
+        return Err(anyhow!("branch `{new_branchname}` already exists"));
+    }
+    old_branch.check_not_checked_out_elsewhere()?;
+
+    let mut local_config_file = repo.local_config_file().context("opening local config")?;
+    let old_section_name = format!("{old_branchname}.stgit");
+    let new_section_name = format!("{new_branchname}.stgit");
+    let stale_section = local_config_file
+        .section("branch", Some(new_section_name.as_str().into()))
+        .is_ok();
+
+    let stale_refs = metadata_references(repo, new_branchname.as_ref())?;
+    if !force {
+        if let Some(stale_ref) = stale_refs.first() {
+            return Err(anyhow!(
+                "`{}` already exists for branch `{new_branchname}`; \
+                 use `--force` to overwrite it",
+                stale_ref.name.as_bstr(),
+            ));
+        } else if stale_section {
+            return Err(anyhow!(
+                "config section `branch.{new_section_name}` already exists; \
+                 use `--force` to overwrite it"
+            ));
+        }
+    }
+
+    let old_branch_ref = repo.find_reference(&old_refname)?.detach();
+    let old_refs = std::iter::once(old_branch_ref)
+        .chain(metadata_references(repo, old_branchname.as_ref())?)
+        .map(|reference| (reference.name, reference.target));
+    let mut stale_refs: BTreeMap<FullName, Target> = stale_refs
+        .into_iter()
+        .map(|reference| (reference.name, reference.target))
+        .collect();
 
-    let stupid = repo.stupid();
-    let parent_branchname = super::get_stgit_parent(&repo.config_snapshot(), old_branchname);
-    let parent_branchname = parent_branchname
-        .map(|name| PartialRefName::from_str(name.as_str()))
-        .transpose()?;
-
-    if let Ok(stack) = Stack::from_branch_name(
-        repo,
-        old_branchname,
-        InitializationPolicy::RequireInitialized,
-    ) {
-        let state_commit = repo
-            .find_reference(stack.get_stack_refname())
-            .expect("just found this stack state reference")
-            .peel_to_commit()?;
-        repo.edit_reference(gix::refs::transaction::RefEdit {
-            change: gix::refs::transaction::Change::Update {
-                log: gix::refs::transaction::LogChange {
-                    mode: gix::refs::transaction::RefLog::AndReference,
-                    force_create_reflog: false,
-                    message: format!("rename {old_branchname} to {new_branchname}").into(),
-                },
-                expected: gix::refs::transaction::PreviousValue::MustNotExist,
-                new: gix::refs::Target::Object(state_commit.id),
+    let log = LogChange {
+        mode: RefLog::AndReference,
+        force_create_reflog: false,
+        message: format!("rename {old_branchname} to {new_branchname}").into(),
+    };
+    let mut ref_edits = Vec::new();
+    for (old_name, target) in old_refs {
+        let new_name = renamed_refname(&old_name, old_branchname, new_branchname)?;
+        let expected = stale_refs.remove(&new_name).map_or(
+            PreviousValue::MustNotExist,
+            PreviousValue::ExistingMustMatch,
+        );
+        ref_edits.push(RefEdit {
+            change: Change::Update {
+                log: log.clone(),
+                expected,
+                new: target.clone(),
+            },
+            name: new_name,
+            deref: false,
+        });
+        ref_edits.push(RefEdit {
+            change: Change::Delete {
+                expected: PreviousValue::ExistingMustMatch(target),
+                log: RefLog::AndReference,
+            },
+            name: old_name,
+            deref: false,
+        });
+    }
+    for (name, target) in stale_refs {
+        ref_edits.push(RefEdit {
+            change: Change::Delete {
+                expected: PreviousValue::ExistingMustMatch(target),
+                log: RefLog::AndReference,
+            },
+            name,
+            deref: false,
+        });
+    }
+    if repo
+        .head_name()?
+        .is_some_and(|head_name| head_name == old_refname)
+    {
+        ref_edits.push(RefEdit {
+            change: Change::Update {
+                log: log.clone(),
+                expected: PreviousValue::ExistingMustMatch(Target::Symbolic(old_refname.clone())),
+                new: Target::Symbolic(new_refname.clone()),
             },
-            name: gix::refs::FullName::try_from(state_refname_from_branch_name(
-                new_branchname.as_ref(),
-            ))?,
+            name: FullName::try_from("HEAD")?,
             deref: false,
-        })?;
+        });
+    }
+
+    let old_reflog_path = reflog_path(repo, &old_refname);
+    let old_reflog = std::fs::read(&old_reflog_path).ok();
+
+    repo.edit_references(ref_edits)
+        .with_context(|| format!("renaming branch `{old_branchname}` to `{new_branchname}`"))?;
 
-        let mut local_config_file = repo.local_config_file().context("opening local config")?;
-        let old_section_name = format!("{old_branchname}.stgit");
-        let old_section_name = old_section_name.as_bytes().as_bstr();
+    if let Some(old_reflog) = old_reflog {
+        let new_reflog_path = reflog_path(repo, &new_refname);
+        let new_reflog = std::fs::read(&new_reflog_path).unwrap_or_default();
+        if let Some(parent) = new_reflog_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&new_reflog_path, [old_reflog, new_reflog].concat())
+            .with_context(|| format!("writing reflog for `{}`", new_refname.as_bstr()))?;
+    }
+
+    if stale_section {
+        local_config_file.remove_section("branch", Some(new_section_name.as_str().into()));
+    }
+    for (old_subsection, new_subsection) in [
+        (old_branchname.as_ref(), new_branchname.as_ref()),
+        (old_section_name.as_str(), new_section_name.as_str()),
+    ] {
         if local_config_file
-            .section("branch", Some(old_section_name))
+            .section("branch", Some(old_subsection.into()))
             .is_ok()
         {
-            let new_section_name =
-                std::borrow::Cow::Owned(BString::from(format!("{new_branchname}.stgit")));
             local_config_file
                 .rename_section(
                     "branch",
-                    Some(old_section_name),
+                    Some(old_subsection.into()),
                     "branch",
-                    Some(new_section_name),
+                    Some(std::borrow::Cow::Owned(BString::from(new_subsection))),
                 )
                 .with_context(|| {
                     format!(
-                        "renaming config section `branch.{old_branchname}.stgit` \
-                         to `branch.{new_branchname}.stgit`"
+                        "renaming config section `branch.{old_subsection}` \
+                         to `branch.{new_subsection}`"
                     )
                 })?;
-            repo.write_local_config(local_config_file)
-                .context("writing local config")?;
         }
+    }
+    update_branch_references(&mut local_config_file, old_branchname, new_branchname)?;
+    repo.write_local_config(local_config_file)
+        .context("writing local config")?;
 
-        stupid.branch_move(Some(old_branchname.as_ref()), new_branchname.as_ref())?;
-        stack.deinitialize()?;
+    Ok(())
+}
+
+/// Map a reference of the old branch to the corresponding reference of the new branch.
+///
+/// The reference is either the branch itself or one of the branch's StGit metadata
+/// references, all of which are namespaced by the branch name.
+fn renamed_refname(
+    refname: &FullName,
+    old_branchname: &PartialRefName,
+    new_branchname: &PartialRefName,
+) -> Result<FullName> {
+    let refname = refname
+        .as_bstr()
+        .to_str()
+        .expect("metadata refnames are valid UTF-8");
+    let old_branchname = old_branchname.as_ref();
+    let patch_ref_prefix = format!("refs/patches/{old_branchname}/");
+    let new_refname = if let Some(patchname) = refname.strip_prefix(&patch_ref_prefix) {
+        format!("refs/patches/{new_branchname}/{patchname}")
     } else {
-        stupid.branch_move(Some(old_branchname.as_ref()), new_branchname.as_ref())?;
+        let prefix = refname
+            .strip_suffix(old_branchname)
+            .expect("refname is namespaced by the branch name");
+        format!("{prefix}{new_branchname}")
+    };
+    Ok(FullName::try_from(new_refname)?)
+}
+
+/// Get path to the reflog of a reference.
+fn reflog_path(repo: &gix::Repository, refname: &FullName) -> std::path::PathBuf {
+    repo.common_dir()
+        .join("logs")
+        .join(gix::path::from_bstr(refname.as_bstr()))
+}
+
+/// Update other branches' StGit configuration referring to the renamed branch.
+///
+/// I.e. `branch.<name>.stgit.parentbranch`, `branch.<name>.stgit.linkedbranch`, and
+/// `branch.<name>.stgit.stackedon`.
+fn update_branch_references(
+    config_file: &mut gix::config::File,
+    old_branchname: &PartialRefName,
+    new_branchname: &PartialRefName,
+) -> Result<()> {
+    let mut updates = Vec::new();
+    if let Some(sections) = config_file.sections_by_name("branch") {
+        for section in sections {
+            let Some(subsection) = section.header().subsection_name() else {
+                continue;
+            };
+            if !subsection.ends_with(b".stgit") {
+                continue;
+            }
+            for key in ["parentbranch", "linkedbranch", "stackedon"] {
+                if section
+                    .value(key)
+                    .is_some_and(|value| value.as_ref() == old_branchname.as_ref().as_bytes())
+                {
+                    updates.push((subsection.to_owned(), key));
+                }
+            }
+        }
+    }
+    for (subsection, key) in updates {
+        config_file.set_raw_value_by(
+            "branch",
+            Some(subsection.as_bstr()),
+            key,
+            new_branchname.as_ref(),
+        )?;
     }
-    super::set_stgit_parent(repo, new_branchname, parent_branchname.as_ref())?;
     Ok(())
 }
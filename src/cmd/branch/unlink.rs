@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg branch --unlink` implementation.
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    branchloc::BranchLocator,
+    stack::{InitializationPolicy, Stack, StackAccess},
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("--unlink")
+        .override_usage(super::super::make_usage(
+            "stg branch --unlink",
+            &["[branch]"],
+        ))
+        .about("Stop updating the branch linked to a stack")
+        .long_about(
+            "Stop updating the branch linked to a stack. The previously linked branch \
+             is left as-is.\n\
+             \n\
+             The 'branch.<branch>.stgit.linkedbranch' configuration variable is \
+             removed, or set empty when needed to override a general \
+             'stgit.linkedbranch' value.",
+        )
+        .arg(
+            clap::Arg::new("branch")
+                .help("Branch of the stack to unlink")
+                .value_name("branch")
+                .value_parser(clap::value_parser!(BranchLocator)),
+        )
+}
+
+pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
+    let stack = Stack::from_branch_locator(
+        repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+    if stack.linked_branch().is_none() {
+        return Err(anyhow!(
+            "branch `{}` has no linked branch",
+            stack.get_branch_name()
+        ));
+    }
+    stack.set_linked_branch(None)
+}
@@ -17,6 +17,14 @@ pub(super) fn command() -> clap::Command {
             &["[branch]"],
         ))
         .about("Allow StGit to modify a previously protected branch")
+        .long_about(
+            "Allow StGit to modify a previously protected branch.\n\
+             \n\
+             When 'stgit.protect-default-branches' is enabled, default branches such \
+             as 'main' or 'master' are protected without 'stg branch --protect'. \
+             Unprotecting such a branch records an explicit exception in \
+             'branch.<name>.stgit.protect'.",
+        )
         .arg(
             clap::Arg::new("branch")
                 .help("Branch to unprotect")
@@ -6,16 +6,18 @@ use anyhow::Result;
 use clap::{Arg, ArgMatches};
 
 use crate::{
-    color::get_color_stdout,
+    argset,
     ext::{CommitExtended, RepositoryExtended},
+    output::Output,
     patch::PatchName,
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{message, InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "clean",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -42,9 +44,10 @@ fn make() -> clap::Command {
                 .help("Delete empty unapplied patches")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::dry_run_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     stack.check_head_top_mismatch()?;
@@ -87,15 +90,17 @@ fn run(matches: &ArgMatches) -> Result<()> {
     if !to_delete.is_empty() {
         stack
             .setup_transaction()
+            .command_line(command_line)
+            .dry_run(matches.get_flag("dry-run"))
             .allow_conflicts(true)
             .use_index_and_worktree(false)
-            .with_output_stream(get_color_stdout(matches))
+            .with_output(Output::new(matches))
             .transact(|trans| {
                 let to_push = trans.delete_patches(|pn| to_delete.contains(pn))?;
                 trans.push_patches(&to_push, false)?;
                 Ok(())
             })
-            .execute("delete")?;
+            .execute(&message::with_patches("clean", &to_delete))?;
     }
 
     Ok(())
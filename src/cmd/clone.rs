@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg clone` implementation.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    ext::RepositoryExtended,
+    stack::{InitializationPolicy, Stack},
+    stupid::StupidContext,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "clone",
+    category: super::CommandCategory::StackManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Clone a repository and initialize a StGit stack")
+        .long_about(
+            "Clone a Git repository into a new directory and initialize a StGit stack \
+             on the checked-out branch.\n\
+             \n\
+             The clone is performed by `git clone`, so any repository URL or path \
+             accepted by git-clone(1) may be used. Without a directory argument, the \
+             directory name is derived from the repository the same way `git clone` \
+             does, e.g. \"repo\" for \"https://example.com/repo.git\". The directory \
+             must not already exist.\n\
+             \n\
+             With '--depth', a shallow clone is made. StGit works in shallow clones, \
+             but commands needing history beyond the clone's depth, such as `stg \
+             uncommit` of many commits or `stg rebase` onto an older commit, fail \
+             until more history is fetched with `git fetch --deepen=<depth>` or `git \
+             fetch --unshallow`.",
+        )
+        .arg(
+            Arg::new("repository")
+                .help("Repository to clone")
+                .value_name("repository")
+                .value_hint(ValueHint::AnyPath)
+                .required(true),
+        )
+        .arg(
+            Arg::new("directory")
+                .help("Directory to clone into")
+                .value_name("directory")
+                .value_hint(ValueHint::DirPath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("branch")
+                .long("branch")
+                .short('b')
+                .help("Check out and initialize <name> instead of the remote's HEAD")
+                .value_name("name")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("depth")
+                .long("depth")
+                .help("Create a shallow clone with history truncated to <depth> commits")
+                .value_name("depth")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let repository = matches
+        .get_one::<String>("repository")
+        .expect("required argument");
+    let directory = if let Some(directory) = matches.get_one::<PathBuf>("directory") {
+        directory.clone()
+    } else {
+        guess_directory(repository).ok_or_else(|| {
+            anyhow!(
+                "cannot determine a directory name for `{repository}`; specify a \
+                 directory"
+            )
+        })?
+    };
+
+    if directory.exists() {
+        return Err(anyhow!(
+            "destination path `{}` already exists",
+            directory.display()
+        ));
+    }
+
+    let depth = matches.get_one::<u32>("depth").copied();
+    StupidContext::default().clone_repository(
+        repository,
+        &directory,
+        matches.get_one::<String>("branch").map(String::as_str),
+        depth,
+    )?;
+
+    std::env::set_current_dir(&directory)
+        .with_context(|| format!("changing to `{}`", directory.display()))?;
+    let repo = gix::Repository::open()?;
+    let branch = repo.get_current_branch().with_context(|| {
+        format!(
+            "cloned into `{}` but could not initialize a StGit stack",
+            directory.display()
+        )
+    })?;
+    let branchname = branch.get_branch_name()?.to_string();
+    Stack::from_branch(&repo, branch, InitializationPolicy::MustInitialize)?;
+
+    crate::print_info_message(
+        matches,
+        &format!(
+            "cloned into `{}` and initialized StGit stack on branch `{branchname}`",
+            directory.display()
+        ),
+    );
+    if repo.is_shallow() {
+        crate::print_warning_message(
+            matches,
+            "the clone is shallow; commands needing older history fail until more is \
+             fetched with `git fetch --deepen=<depth>` or `git fetch --unshallow`",
+        );
+    } else if depth.is_some() {
+        crate::print_warning_message(
+            matches,
+            "`--depth` was ignored by `git clone`; use a file:// URL to make a shallow \
+             clone of a local repository",
+        );
+    }
+    Ok(())
+}
+
+/// Derive the directory name for a clone of the given repository.
+///
+/// This follows `git clone`: the last path component of the repository URL or path is
+/// used, without any trailing "/.git" or ".git" suffix.
+fn guess_directory(repository: &str) -> Option<PathBuf> {
+    let trimmed = repository.trim_end_matches(['/', '\\']);
+    let trimmed = trimmed.strip_suffix("/.git").unwrap_or(trimmed);
+    let trimmed = trimmed.trim_end_matches(['/', '\\']);
+    let name = trimmed.rsplit(['/', '\\', ':']).next().unwrap_or(trimmed);
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    let name = name.strip_suffix(".bundle").unwrap_or(name);
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(Path::new(name).to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_names() {
+        for (repository, expected) in [
+            ("https://example.com/repo.git", Some("repo")),
+            ("https://example.com/repo.git/", Some("repo")),
+            ("https://example.com/repo", Some("repo")),
+            ("git@example.com:user/repo.git", Some("repo")),
+            ("example.com:repo", Some("repo")),
+            ("/path/to/repo/.git", Some("repo")),
+            ("../repo/", Some("repo")),
+            ("repo.bundle", Some("repo")),
+            ("/", None),
+            ("..", None),
+        ] {
+            assert_eq!(
+                guess_directory(repository).as_deref(),
+                expected.map(Path::new),
+                "{repository}"
+            );
+        }
+    }
+}
@@ -6,15 +6,17 @@ use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
-    color::get_color_stdout,
+    argset,
     ext::{CommitExtended, RepositoryExtended},
+    output::Output,
     patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{message, InitializationPolicy, Stack, StackStateAccess},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "commit",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -36,6 +38,16 @@ fn make() -> clap::Command {
              commit (counting from the bottom of the stack). If -a/--all is given, \
              all applied patches are committed.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg commit -n 2",
+                "Commit the two bottommost applied patches",
+            ),
+            (
+                "stg commit p3",
+                "Move `p3` to the bottom of the stack and commit it",
+            ),
+        ]))
         .override_usage(super::make_usage(
             "stg commit",
             &[
@@ -58,7 +70,7 @@ fn make() -> clap::Command {
                 .short('n')
                 .help("Commit the specified number of applied patches")
                 .value_name("number")
-                .value_parser(crate::argset::parse_usize)
+                .value_parser(argset::parse_usize)
                 .conflicts_with("all"),
         )
         .arg(
@@ -76,7 +88,7 @@ fn make() -> clap::Command {
         )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
 
@@ -116,9 +128,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     if !matches.get_flag("allow-empty") {
         let mut empty_patches: Vec<&PatchName> = Vec::new();
         for pn in &patches {
-            let patch_commit = stack.get_patch_commit(pn);
-            let parent = patch_commit.get_parent_commit()?;
-            if patch_commit.tree_id()? == parent.tree_id()? {
+            if stack.get_patch_commit(pn).is_no_change()? {
                 empty_patches.push(pn);
             }
         }
@@ -139,11 +149,13 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .print_delta(true)
         .use_index_and_worktree(true)
         .allow_conflicts_if_same_top(true)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| trans.commit_patches(&patches))
-        .execute("commit")?;
+        .execute(&message::with_patches("commit", &patches))?;
 
     Ok(())
 }
@@ -209,7 +209,7 @@ fn insert_compreply(script: &mut ShStream, arg: &clap::Arg) {
         clap::ValueHint::Unknown | clap::ValueHint::Other
     ) {
         match arg.get_id().as_str() {
-            "branch" | "ref-branch" => {
+            "branch" | "ref-branch" | "missing" => {
                 script
                     .line("mapfile -t COMPREPLY < <(compgen -W \"$(_stg_branches)\" -- \"$cur\")");
             }
@@ -245,9 +245,12 @@ fn insert_compreply(script: &mut ShStream, arg: &clap::Arg) {
             "patchranges" => {
                 script.line("_patch_range \"$(_visible_patches)\"");
             }
-            "patchranges-all" | "set-tree" | "stgit-revision" => {
+            "patchranges-all" | "set-tree" => {
                 script.line("_patch_range \"$(_all_patches)\"");
             }
+            "stgit-revision" => {
+                script.line("_revision_range \"$(_all_patches)\"");
+            }
             "patchranges-applied" => {
                 script.line("_patch_range \"$(_applied_patches)\"");
             }
@@ -302,7 +305,7 @@ fn insert_compreply(script: &mut ShStream, arg: &clap::Arg) {
             clap::ValueHint::EmailAddress => {
                 script.line(":");
             }
-            clap::ValueHint::CommandName => {
+            clap::ValueHint::CommandName | clap::ValueHint::CommandString => {
                 script.line("mapfile -t COMPREPLY < <(compgen -A command -- \"$cur\")");
             }
             clap::ValueHint::Username => {
@@ -312,7 +315,6 @@ fn insert_compreply(script: &mut ShStream, arg: &clap::Arg) {
                 script.line("mapfile -t COMPREPLY < <(compgen -A hostname -- \"$cur\")");
             }
             clap::ValueHint::ExecutablePath => todo!(),
-            clap::ValueHint::CommandString => todo!(),
             clap::ValueHint::CommandWithArguments => todo!(),
             clap::ValueHint::Url => todo!(),
             _ => todo!(),
@@ -520,6 +522,26 @@ _patch_range ()
     esac
 }
 
+# Complete patches of another branch for a "<branch>:" prefixed revision.
+_revision_range ()
+{
+    local cur
+    _get_comp_words_by_ref -n : cur
+
+    case "$cur" in
+    *:*)
+        local branch="${cur%%:*}"
+        local cur="${cur#*:}"
+        _patch_range "$(__stg completion list patches --branch "$branch")"
+        COMPREPLY=("${COMPREPLY[@]/#/$branch:}")
+        __ltrim_colon_completions "$branch:$cur"
+        ;;
+    *)
+        _patch_range "$1"
+        ;;
+    esac
+}
+
 _stg_branches ()
 {
     __stg branch --list 2>/dev/null | grep ". s" | cut -f2 | cut -d" " -f1
@@ -143,6 +143,17 @@ function __fish_stg_patches
     end
 end
 
+function __fish_stg_revisions
+    set -l token (commandline -ct)
+    if string match -q -- '*:*' $token
+        set -l branch (string split -m 1 : -- $token)[1]
+        __fish_stg completion list patches --style=fish --branch $branch 2>/dev/null | \
+            string replace -r '^' "$branch:"
+    else
+        __fish_stg_patches --all
+    end
+end
+
 function __fish_stg_tags
     __fish_stg_git tag --sort=-creatordate 2>/dev/null
 end
@@ -360,16 +371,19 @@ fn get_arg_completion_params(arg: &clap::Arg) -> ShStream {
         clap::ValueHint::Unknown | clap::ValueHint::Other
     ) {
         match arg.get_id().as_str() {
-            "branch" | "ref-branch" => params.word("-xa '(__fish_stg_stg_branches)'"),
+            "branch" | "ref-branch" | "missing" => {
+                params.word("-xa '(__fish_stg_stg_branches)'");
+            }
             "branch-any" => params.word("-xa '(__fish_stg_all_branches)'"),
             "committish" => params.word("-xa '(__fish_stg_commit)'"),
             "git-diff-opt" => params.word("-xa '(__fish_stg_git_diff_opts)'"),
             "git-format-patch-opt" => params.word("-xa '(__fish_stg_git_format_patch_opts)'"),
             "git-send-email-opt" => params.word("-xa '(__fish_stg_git_send_email_opts)'"),
             "patch" | "patchranges" => params.word("-kxa '(__fish_stg_patches -A -U)'"),
-            "patchranges-all" | "set-tree" | "stgit-revision" => {
+            "patchranges-all" | "set-tree" => {
                 params.word("-kxa '(__fish_stg_patches --all)'");
             }
+            "stgit-revision" => params.word("-kxa '(__fish_stg_revisions)'"),
             "patchranges-applied" => params.word("-kxa '(__fish_stg_patches --applied)'"),
             "patchranges-hidden" => params.word("-kxa '(__fish_stg_patches --hidden)'"),
             "patchranges-unapplied" => params.word("-kxa '(__fish_stg_patches --unapplied)'"),
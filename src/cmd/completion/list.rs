@@ -6,7 +6,13 @@ use std::str::FromStr;
 
 use anyhow::Result;
 
-use crate::cmd::STGIT_COMMANDS;
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    cmd::STGIT_COMMANDS,
+    ext::RepositoryExtended,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+};
 
 pub(super) fn command() -> clap::Command {
     clap::Command::new("list")
@@ -32,6 +38,16 @@ pub(super) fn command() -> clap::Command {
         .subcommand(
             clap::Command::new("commands-and-aliases").about("List StGit commands and aliases"),
         )
+        .subcommand(
+            clap::Command::new("patches")
+                .about("List patches of a stack")
+                .long_about(
+                    "List the applied, unapplied, and hidden patches of the current \
+                     branch's stack, or of <branch>'s stack when '--branch' is used. The \
+                     stack of another branch is read without checking out that branch.",
+                )
+                .arg(argset::branch_arg()),
+        )
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -75,6 +91,7 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
             list_commands(&mut output, style)?;
             list_aliases(&mut output, style, false)
         }
+        Some(("patches", sub_matches)) => list_patches(&mut output, style, sub_matches),
         _ => panic!("valid subcommand is required"),
     }
 }
@@ -117,6 +134,34 @@ fn list_aliases(
     Ok(())
 }
 
+fn list_patches(
+    output: &mut Box<dyn std::io::Write>,
+    style: OutputStyle,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+
+    for (patchnames, state) in [
+        (stack.applied(), "applied"),
+        (stack.unapplied(), "unapplied"),
+        (stack.hidden(), "hidden"),
+    ] {
+        for patchname in patchnames {
+            match style {
+                OutputStyle::NameOnly | OutputStyle::AsciiDoc => writeln!(output, "{patchname}"),
+                OutputStyle::Fish => writeln!(output, "{patchname}\t{state}"),
+                OutputStyle::Zsh => writeln!(output, "{patchname}:{state}"),
+            }?;
+        }
+    }
+    Ok(())
+}
+
 fn list_commands(output: &mut Box<dyn std::io::Write>, style: OutputStyle) -> Result<()> {
     use crate::cmd::CommandCategory;
 
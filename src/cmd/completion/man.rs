@@ -21,7 +21,10 @@ pub(super) fn command() -> clap::Command {
              \n\
              One file is generated for each `stg` command. The output directory \
              defaults to the current directory, but may be specified with '--output'. \
-             The output files are named `stg-<command>.txt`.",
+             The output files are named `stg-<command>.txt`.\n\
+             \n\
+             A section 7 man page is also generated for each help topic shown by \
+             `stg help <topic>`. These output files are named `stg<topic>.txt`.",
         )
         .arg(
             clap::Arg::new("output")
@@ -50,14 +53,26 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     for command in stg.get_subcommands_mut() {
         let asciidoc = generate_asciidoc(command);
         let path = output_dir.join(format!("stg-{}.txt", command.get_name()));
-        if std::fs::read_to_string(&path).ok().as_ref() != Some(&asciidoc) {
-            std::fs::write(path, asciidoc)?;
-        }
+        write_if_changed(&path, &asciidoc)?;
+    }
+
+    for topic in crate::help::HELP_TOPICS {
+        let path = output_dir.join(format!("stg{}.txt", topic.name));
+        write_if_changed(&path, &topic.asciidoc())?;
     }
 
     Ok(())
 }
 
+/// Write the file unless it already has the given contents, such that the timestamps
+/// of unchanged pages are preserved for make.
+fn write_if_changed(path: &Path, contents: &str) -> Result<()> {
+    if std::fs::read_to_string(path).ok().as_deref() != Some(contents) {
+        std::fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
 fn generate_asciidoc(command: &mut clap::Command) -> String {
     let mut page = String::new();
     let name = command.get_name().to_string();
@@ -107,7 +122,11 @@ fn generate_asciidoc(command: &mut clap::Command) -> String {
         page.push_str(&options_section);
     }
 
-    // TODO use command.get_after_long_help()
+    if let Some(examples_section) = get_examples_section(command) {
+        write_underlined(&mut page, "EXAMPLES", '-');
+        page.push('\n');
+        page.push_str(&examples_section);
+    }
 
     write_underlined(&mut page, "StGit", '-');
     page.push_str("Part of the StGit suite - see linkman:stg[1]\n");
@@ -241,6 +260,28 @@ fn add_command_stanza(section: &mut String, command: &clap::Command, stack: &[&s
     }
 }
 
+/// Get the examples section from the command's after long help.
+///
+/// The after long help is expected to be made with [`crate::cmd::make_examples()`].
+fn get_examples_section(command: &clap::Command) -> Option<String> {
+    let after_help = command.get_after_long_help()?.to_string();
+    let examples = after_help
+        .strip_prefix("Examples:\n")
+        .expect("after long help is made with make_examples()");
+    let mut section = String::new();
+    for example in paragraphs(examples) {
+        let (command_line, description) = example
+            .split_once('\n')
+            .expect("example has a command line and a description");
+        writeln!(section, "`{}`::", command_line.trim()).unwrap();
+        for line in wrap(description.trim(), WIDTH - 4) {
+            writeln!(section, "    {line}").unwrap();
+        }
+        section.push('\n');
+    }
+    Some(section)
+}
+
 fn get_options_section(command: &clap::Command) -> Option<String> {
     let mut section = String::new();
     add_options(&mut section, command, "OPTIONS", '-');
@@ -465,7 +506,7 @@ impl<'a> Iterator for Paragraphs<'a> {
     }
 }
 
-fn paragraphs(text: &str) -> Paragraphs {
+fn paragraphs(text: &str) -> Paragraphs<'_> {
     Paragraphs { text }
 }
 
@@ -507,6 +548,6 @@ impl<'a> Iterator for WrappedLines<'a> {
     }
 }
 
-fn wrap(text: &str, width: usize) -> WrappedLines {
+fn wrap(text: &str, width: usize) -> WrappedLines<'_> {
     WrappedLines { text, width }
 }
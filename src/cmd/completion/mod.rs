@@ -16,6 +16,7 @@ use anyhow::Result;
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "completion",
     category: super::CommandCategory::Administration,
+    read_only: false,
     make,
     run,
 };
@@ -46,7 +47,7 @@ fn make() -> clap::Command {
         )
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, _command_line: &[String]) -> Result<()> {
     match matches.subcommand() {
         Some(("bash", sub_matches)) => bash::dispatch(sub_matches),
         Some(("fish", sub_matches)) => fish::dispatch(sub_matches),
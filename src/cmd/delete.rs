@@ -2,22 +2,26 @@
 
 //! `stg delete` implementation.
 
-use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
     branchloc::BranchLocator,
-    color::get_color_stdout,
-    ext::RepositoryExtended,
+    ext::{CommitExtended, RepositoryExtended},
+    output::Output,
     patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    print_info_message,
+    stack::{message, InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "delete",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -25,6 +29,20 @@ pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
 fn make() -> clap::Command {
     clap::Command::new(STGIT_COMMAND.name)
         .about("Delete patches")
+        .after_long_help(super::make_examples(&[
+            (
+                "stg delete p0..p4",
+                "Delete the patches from `p0` up to and including `p4`",
+            ),
+            (
+                "stg delete ~2..",
+                "Delete the three topmost applied patches",
+            ),
+            (
+                "stg delete 'wip-*' '!wip-keep'",
+                "Delete the patches named `wip-*`, except for `wip-keep`",
+            ),
+        ]))
         .override_usage(super::make_usage(
             "stg delete",
             &[
@@ -42,7 +60,11 @@ fn make() -> clap::Command {
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange))
                 .conflicts_with_all(["top", "all", "A-U-H"])
-                .required_unless_present_any(["all", "top", "A-U-H"]),
+                .required_unless_present_any(["all", "top", "A-U-H", "stdin"]),
+        )
+        .args(
+            argset::stdin_patches_args("patchranges-all")
+                .map(|arg| arg.conflicts_with_all(["top", "all", "A-U-H"])),
         )
         .arg(
             Arg::new("all")
@@ -101,17 +123,46 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("backup")
+                .long("backup")
+                .help("Export the patches to <dir> before deleting them")
+                .long_help(
+                    "Export the patches to <dir> before deleting them, in the same \
+                     format as 'stg export'. By default, the patches are exported to a \
+                     new timestamped directory in '.git/stgit-backups/'. Backups may \
+                     also be enabled by setting 'stgit.delete.backup' to true.\n\
+                     \n\
+                     The deleted patches may be restored with 'stg import --series'.",
+                )
+                .num_args(0..=1)
+                .require_equals(true)
+                .value_name("dir")
+                .value_hint(clap::ValueHint::DirPath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("no-backup")
+                .long("no-backup")
+                .help("Do not back up the deleted patches")
+                .long_help(
+                    "Do not back up the deleted patches, overriding \
+                     'stgit.delete.backup'.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("backup"),
+        )
         .arg(argset::branch_arg())
+        .arg(argset::dry_run_arg())
         .arg(argset::push_conflicts_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let opt_branch = matches.get_one::<BranchLocator>("branch");
     let stack =
         Stack::from_branch_locator(&repo, opt_branch, InitializationPolicy::AllowUninitialized)?;
-    let allow_push_conflicts =
-        argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches);
+    let allow_push_conflicts = argset::resolve_allow_push_conflicts(&stack.config(), matches);
     let spill_flag = matches.get_flag("spill");
 
     let patches: Vec<PatchName> = if matches.get_flag("top") {
@@ -120,8 +171,18 @@ fn run(matches: &ArgMatches) -> Result<()> {
         } else {
             return Err(super::Error::NoAppliedPatches.into());
         }
-    } else if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
-        patchrange::resolve_names(&stack, range_specs, RangeConstraint::AllWithAppliedBoundary)?
+    } else if let Some(range_specs) =
+        argset::get_patch_ranges::<PatchRange>(matches, "patchranges-all")?
+    {
+        if range_specs.is_empty() {
+            return Ok(());
+        }
+        patchrange::check_message_duplicates(&stack, range_specs.iter())?;
+        patchrange::resolve_names(
+            &stack,
+            range_specs.iter(),
+            RangeConstraint::AllWithAppliedBoundary,
+        )?
     } else if matches.get_flag("all") {
         stack.all_patches().cloned().collect::<Vec<_>>()
     } else {
@@ -151,6 +212,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     repo.check_repository_state()?;
     let statuses = repo.stupid().statuses(None)?;
+    stack.check_conflicted_patch()?;
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
     // TODO: compat: these are not checked in Python version. How well is
@@ -162,17 +224,87 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Ok(());
     }
 
+    let dry_run = matches.get_flag("dry-run");
+    let backup_dir = if dry_run {
+        None
+    } else if matches.contains_id("backup") {
+        Some(matches.get_one::<PathBuf>("backup").cloned())
+    } else if !matches.get_flag("no-backup")
+        && stack
+            .config()
+            .boolean("stgit.delete.backup")
+            .unwrap_or(false)
+    {
+        Some(None)
+    } else {
+        None
+    };
+
+    if let Some(backup_dir) = backup_dir {
+        let backup_dir = if let Some(backup_dir) = backup_dir {
+            backup_dir
+        } else {
+            default_backup_dir(&repo)
+        };
+        backup_patches(&stack, &patches, &backup_dir)?;
+        print_info_message(
+            matches,
+            &format!("deleted patches backed up to `{}`", backup_dir.display()),
+        );
+    }
+
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .dry_run(dry_run)
+        .print_delta(true)
         .use_index_and_worktree(opt_branch.is_none() && !spill_flag)
         .allow_push_conflicts(allow_push_conflicts)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| {
             let to_push = trans.delete_patches(|pn| patches.contains(pn))?;
             trans.push_patches(&to_push, false)?;
             Ok(())
         })
-        .execute("delete")?;
+        .execute(&message::with_patches("delete", &patches))?;
 
     Ok(())
 }
+
+/// Get a new timestamped directory in `.git/stgit-backups/` for backing up patches.
+fn default_backup_dir(repo: &gix::Repository) -> PathBuf {
+    let backups_dir = repo.common_dir().join("stgit-backups");
+    let timestamp = jiff::Zoned::now().strftime("%Y%m%d-%H%M%S").to_string();
+    let mut backup_dir = backups_dir.join(&timestamp);
+    let mut n = 1;
+    while backup_dir.exists() {
+        n += 1;
+        backup_dir = backups_dir.join(format!("{timestamp}-{n}"));
+    }
+    backup_dir
+}
+
+/// Export patches, including any binary diffs, along with a series file.
+fn backup_patches(stack: &Stack, patches: &[PatchName], backup_dir: &Path) -> Result<()> {
+    let template = super::export::get_template(stack.repo, None)?;
+    let diff_opts = [String::from("--binary")];
+    std::fs::create_dir_all(backup_dir).with_context(|| format!("creating {backup_dir:?}"))?;
+
+    let mut series = format!(
+        "# This series applies on Git commit {}\n",
+        stack
+            .get_patch_commit(&patches[0])
+            .get_parent_commit()?
+            .id()
+    );
+    for patchname in patches {
+        let exported = super::export::export_patch(stack, patchname, &template, None, &diff_opts)?;
+        let patch_path = backup_dir.join(patchname.to_string());
+        std::fs::write(&patch_path, exported).with_context(|| format!("writing {patch_path:?}"))?;
+        series.push_str(patchname.as_ref());
+        series.push('\n');
+    }
+    let series_path = backup_dir.join("series");
+    std::fs::write(&series_path, series).with_context(|| format!("writing {series_path:?}"))?;
+    Ok(())
+}
@@ -11,13 +11,15 @@ use crate::{
     argset,
     ext::RepositoryExtended,
     patch::{RangeRevisionSpec, StGitBoundaryRevisions},
-    stack::Stack,
+    pathspec,
+    stack::{Stack, StackConfig},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "diff",
     category: super::CommandCategory::PatchInspection,
+    read_only: true,
     make,
     run,
 };
@@ -29,8 +31,21 @@ fn make() -> clap::Command {
             "Show the diff (default) or diffstat between the current working copy \
              or a tree-ish object and another tree-ish object (defaulting to HEAD). \
              File names can also be given to restrict the diff output. The \
-             tree-ish object has the format accepted by the 'stg id' command.",
+             tree-ish object has the format accepted by the 'stg id' command.\n\
+             \n\
+             File names may use pathspec magic such as ':(exclude)' and may be \
+             given after '--'.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg diff -r '{base}..@'",
+                "Show the combined diff of all applied patches",
+            ),
+            (
+                "stg diff -r p1..p3",
+                "Show the changes made by `p2` and `p3`",
+            ),
+        ]))
         .arg(
             Arg::new("pathspecs")
                 .help("Limit diff to files matching path(s)")
@@ -48,7 +63,9 @@ fn make() -> clap::Command {
                     "Show diff between specified revisions. \
                      Revisions ranges are specified as 'rev1[..[rev2]]'. \
                      The revisions may be standard Git revision specifiers or \
-                     patches.",
+                     patches. A range in another branch's stack may be given \
+                     as '<branch>:rev1..[<branch>:]rev2', e.g. \
+                     '<branch>:{base}..<branch>:@'.",
                 )
                 .value_name("revspec")
                 .value_parser(clap::value_parser!(RangeRevisionSpec))
@@ -61,10 +78,12 @@ fn make() -> clap::Command {
                 .help("Show the stat instead of the diff")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::numstat_arg())
+        .arg(argset::unified_arg())
         .arg(argset::diff_opts_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
 
     let revspec = if let Some(range_spec) = matches.get_one::<RangeRevisionSpec>("range") {
@@ -78,11 +97,16 @@ fn run(matches: &ArgMatches) -> Result<()> {
         "HEAD".to_string()
     };
 
+    let pathspecs = matches
+        .get_many::<PathBuf>("pathspecs")
+        .map(|pathspecs| pathspec::normalize(&repo, pathspecs))
+        .transpose()?;
+
     repo.stupid().diff(
         &revspec,
-        matches.get_many::<PathBuf>("pathspecs"),
-        matches.get_flag("stat"),
+        pathspecs,
+        argset::get_diff_format(matches),
         crate::color::use_color(matches),
-        argset::get_diff_opts(matches, &repo.config_snapshot(), false, false),
+        argset::get_diff_opts(matches, &StackConfig::current(&repo), false, false),
     )
 }
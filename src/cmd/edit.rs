@@ -6,8 +6,9 @@ use anyhow::{Context, Result};
 use clap::{Arg, ArgMatches, ValueHint};
 
 use crate::{
-    color::get_color_stdout,
+    argset,
     ext::RepositoryExtended,
+    output::Output,
     patch::{patchedit, PatchLocator, SingleRevisionSpec},
     stack::{InitializationPolicy, Stack, StackStateAccess},
 };
@@ -15,6 +16,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "edit",
     category: super::CommandCategory::PatchManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -38,8 +40,19 @@ fn make() -> clap::Command {
              message). The StGit attempts to apply the modified diff to the patch's \
              parent tree. If the updated diff does not apply, no changes are made to \
              the patch and the edited patch is saved to a file which may be corrected \
-             and then fed-back into `stg edit --file`.",
+             and then fed-back into `stg edit --file`.\n\
+             \n\
+             When the patch is renamed by editing its 'Patch:' header, '--keep-alias' \
+             records the old name as an alias of the renamed patch, as with `stg \
+             rename --keep-alias`.",
         )
+        .after_long_help(super::make_examples(&[
+            ("stg edit ~1", "Edit the patch below the topmost patch"),
+            (
+                "stg edit --diff ':/fix typo'",
+                "Edit the patch whose message contains `fix typo`, with its diff",
+            ),
+        ]))
         .arg(
             Arg::new("patch")
                 .help("Patch to edit")
@@ -47,27 +60,39 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PatchLocator))
                 .value_hint(ValueHint::Other),
         );
-    patchedit::add_args(app, true, true).arg(
-        Arg::new("set-tree")
-            .long("set-tree")
-            .short('t')
-            .help("Set patch's tree to treeish")
-            .long_help(
-                "Set the patch's git tree to the specified treeish without changing \
-                 the tree of any other patches. When used on the top patch, the index \
-                 and work tree will be updated to match the new tree. This low-level \
-                 option is primarily meant to be used by tools built on top of StGit, \
-                 such as the Emacs mode. See also the '--set-tree' flag of 'stg \
-                 push'.",
-            )
-            .allow_hyphen_values(true)
-            .value_parser(clap::value_parser!(SingleRevisionSpec))
-            .value_name("treeish"),
-    )
+    patchedit::add_args(app, true, true)
+        .arg(argset::date_arg())
+        .arg(argset::whitespace_arg())
+        .args(argset::sign_args())
+        .arg(
+            Arg::new("set-tree")
+                .long("set-tree")
+                .short('t')
+                .help("Set patch's tree to treeish")
+                .long_help(
+                    "Set the patch's git tree to the specified treeish without changing \
+                     the tree of any other patches. When used on the top patch, the \
+                     index and work tree will be updated to match the new tree. This \
+                     low-level option is primarily meant to be used by tools built on \
+                     top of StGit, such as the Emacs mode. See also the '--set-tree' flag \
+                     of 'stg push'.",
+                )
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(SingleRevisionSpec))
+                .value_name("treeish"),
+        )
+        .arg(
+            Arg::new("keep-alias")
+                .long("keep-alias")
+                .help("Keep the old patch name as an alias if the patch is renamed")
+                .action(clap::ArgAction::SetTrue),
+        )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
-    let repo = gix::Repository::open()?;
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let fixed_date = argset::get_fixed_date(matches);
+    let mut repo = gix::Repository::open()?;
+    argset::set_commit_signing(&mut repo, matches)?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     stack.check_head_top_mismatch()?;
 
@@ -96,6 +121,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .allow_implicit_edit(!matches.contains_id("set-tree"))
         .allow_template_save(true)
         .override_tree_id(tree_id)
+        .fixed_date(fixed_date)
         .edit(&stack, &repo, matches)?
     {
         patchedit::EditOutcome::TemplateSaved(_) => Ok(()),
@@ -106,9 +132,11 @@ fn run(matches: &ArgMatches) -> Result<()> {
             if new_patchname.is_some() || new_commit_id.is_some() {
                 stack
                     .setup_transaction()
+                    .command_line(command_line)
+                    .fixed_date(fixed_date)
                     .allow_conflicts(true)
                     .use_index_and_worktree(true)
-                    .with_output_stream(get_color_stdout(matches))
+                    .with_output(Output::new(matches))
                     .transact(|trans| {
                         let popped = if let Some(pos) =
                             trans.applied().iter().position(|pn| pn == &patchname)
@@ -123,6 +151,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
                         let patchname = if let Some(new_patchname) = new_patchname.as_ref() {
                             trans.rename_patch(&patchname, new_patchname)?;
+                            if matches.get_flag("keep-alias") {
+                                trans.add_alias(&patchname, new_patchname);
+                            }
                             // TODO: log stack state here?
                             new_patchname
                         } else {
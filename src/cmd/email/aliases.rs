@@ -0,0 +1,371 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg email aliases` implementation and expansion of email address aliases.
+//!
+//! Aliases are read from the files named by the `sendemail.aliasesFile`
+//! configuration, in the format given by `sendemail.aliasFileType`, as with `git
+//! send-email`. The mutt, mailrc, and sendmail formats are supported.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+use clap::Arg;
+
+use crate::{ext::RepositoryExtended, stack::StackConfig};
+
+const MAX_SUGGESTIONS: usize = 5;
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("aliases")
+        .about("List resolved email aliases")
+        .long_about(
+            "List the email aliases from the `sendemail.aliasesFile` files along with \
+             the addresses each alias resolves to, one alias per line. Aliases \
+             referring to other aliases are resolved recursively.\n\
+             \n\
+             The `sendemail.aliasFileType` configuration determines the format of the \
+             aliases files, which may be 'mutt', 'mailrc', or 'sendmail'. Sendmail \
+             aliases to files, programs, or included files are ignored.\n\
+             \n\
+             Aliases given to the '--to', '--cc', and '--bcc' options of `stg email \
+             format` and `stg email send` are expanded to these addresses. An address \
+             without an '@' that is not a known alias is an error.",
+        )
+        .arg(
+            Arg::new("aliases")
+                .help("Only list these aliases")
+                .value_name("alias")
+                .num_args(1..)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let aliases = Aliases::load(&StackConfig::current(&repo))?;
+    let names: Vec<&str> = if let Some(names) = matches.get_many::<String>("aliases") {
+        names.map(String::as_str).collect()
+    } else {
+        aliases.map.keys().map(String::as_str).collect()
+    };
+    let mut lines = String::new();
+    for name in names {
+        let addresses = aliases.expand(name)?;
+        writeln!(&mut lines, "{name}: {}", addresses.join(", "))?;
+    }
+    print!("{lines}");
+    Ok(())
+}
+
+/// Format of an aliases file, per `sendemail.aliasFileType`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AliasFileType {
+    Mutt,
+    Mailrc,
+    Sendmail,
+}
+
+impl std::str::FromStr for AliasFileType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "mutt" => Ok(Self::Mutt),
+            "mailrc" => Ok(Self::Mailrc),
+            "sendmail" => Ok(Self::Sendmail),
+            _ => Err(anyhow!(
+                "unsupported `sendemail.aliasFileType` `{s}`; \
+                 expected `mutt`, `mailrc`, or `sendmail`"
+            )),
+        }
+    }
+}
+
+/// Email address aliases, mapping each alias to its addresses or other aliases.
+#[derive(Default)]
+pub(super) struct Aliases {
+    map: BTreeMap<String, Vec<String>>,
+}
+
+impl Aliases {
+    /// Load the aliases from the configured aliases files.
+    ///
+    /// There are no aliases if `sendemail.aliasesFile` is not set.
+    pub(super) fn load(config: &StackConfig) -> Result<Self> {
+        let mut aliases = Self::default();
+        let Some(paths) = config
+            .snapshot()
+            .plumbing()
+            .strings("sendemail.aliasesfile")
+        else {
+            return Ok(aliases);
+        };
+        let file_type = config
+            .string("sendemail.aliasfiletype")
+            .map(|value| value.to_str_lossy().parse::<AliasFileType>())
+            .transpose()?
+            .ok_or_else(|| {
+                anyhow!("`sendemail.aliasFileType` must be set with `sendemail.aliasesFile`")
+            })?;
+        let home_dir = gix::path::env::home_dir();
+        for path in paths {
+            let path = gix::config::Path::from(path)
+                .interpolate(gix::config::path::interpolate::Context {
+                    home_dir: home_dir.as_deref(),
+                    ..Default::default()
+                })
+                .context("interpolating `sendemail.aliasesFile`")?;
+            let content = std::fs::read(&path)
+                .with_context(|| format!("reading aliases file `{}`", path.display()))?;
+            aliases.parse(&content.to_str_lossy(), file_type);
+        }
+        Ok(aliases)
+    }
+
+    /// Add the aliases defined by `content` in the given format.
+    ///
+    /// Later definitions of an alias replace earlier ones.
+    fn parse(&mut self, content: &str, file_type: AliasFileType) {
+        match file_type {
+            AliasFileType::Mutt => {
+                for line in content.lines() {
+                    let Some(("alias", mut rest)) = next_word(line) else {
+                        continue;
+                    };
+                    let mut name = None;
+                    while let Some((word, after)) = next_word(rest) {
+                        rest = after;
+                        if word == "-group" {
+                            rest = next_word(rest).map_or("", |(_, after)| after);
+                        } else {
+                            name = Some(word);
+                            break;
+                        }
+                    }
+                    if let Some(name) = name {
+                        let rest = rest.split('#').next().unwrap_or_default();
+                        self.map.insert(name.to_string(), split_addresses(rest));
+                    }
+                }
+            }
+            AliasFileType::Mailrc => {
+                for line in content.lines() {
+                    let Some(("alias", rest)) = next_word(line) else {
+                        continue;
+                    };
+                    let mut words = split_words(rest).into_iter();
+                    if let Some(name) = words.next() {
+                        self.map.insert(name, words.collect());
+                    }
+                }
+            }
+            AliasFileType::Sendmail => {
+                let mut entries: Vec<String> = Vec::new();
+                for line in content.lines() {
+                    if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                        continue;
+                    }
+                    match entries.last_mut() {
+                        Some(entry) if line.starts_with(char::is_whitespace) => {
+                            entry.push(' ');
+                            entry.push_str(line.trim());
+                        }
+                        _ => entries.push(line.trim().to_string()),
+                    }
+                }
+                for entry in entries {
+                    if let Some((name, rest)) = entry.split_once(':') {
+                        let addresses = split_addresses(rest)
+                            .into_iter()
+                            .filter(|address| !address.starts_with(['|', '/', ':']))
+                            .collect();
+                        self.map.insert(name.trim().to_string(), addresses);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Expand an address, or comma-separated list of addresses, that may use aliases.
+    ///
+    /// Aliases are expanded recursively. An address without an '@' that is not an
+    /// alias is an error, as is an alias that expands to itself.
+    pub(super) fn expand(&self, value: &str) -> Result<Vec<String>> {
+        let mut addresses = Vec::new();
+        for address in split_addresses(value) {
+            self.expand_into(&address, &mut Vec::new(), &mut addresses)?;
+        }
+        Ok(addresses)
+    }
+
+    fn expand_into<'a>(
+        &'a self,
+        address: &'a str,
+        expanding: &mut Vec<&'a str>,
+        addresses: &mut Vec<String>,
+    ) -> Result<()> {
+        if let Some(expansion) = self.map.get(address) {
+            if expanding.contains(&address) {
+                return Err(anyhow!("email alias `{address}` expands to itself"));
+            }
+            expanding.push(address);
+            for address in expansion {
+                self.expand_into(address, expanding, addresses)?;
+            }
+            expanding.pop();
+            Ok(())
+        } else if address.contains('@') {
+            if !addresses.iter().any(|a| a == address) {
+                addresses.push(address.to_string());
+            }
+            Ok(())
+        } else {
+            Err(self.unknown_alias(address))
+        }
+    }
+
+    fn unknown_alias(&self, name: &str) -> anyhow::Error {
+        let mut similar: Vec<&str> = self
+            .map
+            .keys()
+            .map(String::as_str)
+            .filter(|alias| strsim::jaro_winkler(alias, name) > 0.75)
+            .collect();
+        similar.sort_by_cached_key(|alias| strsim::levenshtein(alias, name));
+        similar.truncate(MAX_SUGGESTIONS);
+        if similar.is_empty() {
+            anyhow!("unknown email alias `{name}`")
+        } else {
+            let similar: Vec<String> = similar.iter().map(|alias| format!("`{alias}`")).collect();
+            anyhow!(
+                "unknown email alias `{name}`; did you mean {}?",
+                similar.join(", ")
+            )
+        }
+    }
+}
+
+/// Get the first whitespace-separated word of `s` along with the remainder.
+fn next_word(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start();
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    (end > 0).then(|| s.split_at(end))
+}
+
+/// Split a comma-separated list of addresses.
+///
+/// Commas within double quotes or angle brackets do not separate addresses.
+pub(super) fn split_addresses(value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let mut address = String::new();
+    let mut in_quotes = false;
+    let mut in_brackets = false;
+    for c in value.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_brackets = true,
+            '>' if !in_quotes => in_brackets = false,
+            ',' if !in_quotes && !in_brackets => {
+                addresses.push(std::mem::take(&mut address));
+                continue;
+            }
+            _ => {}
+        }
+        address.push(c);
+    }
+    addresses.push(address);
+    addresses
+        .into_iter()
+        .map(|address| address.trim().to_string())
+        .filter(|address| !address.is_empty())
+        .collect()
+}
+
+/// Split whitespace-separated words, which may be single- or double-quoted.
+fn split_words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in value.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_addresses, AliasFileType, Aliases};
+
+    #[test]
+    fn parse_alias_files() {
+        let mut aliases = Aliases::default();
+        aliases.parse(
+            "alias ann Ann Author <ann@example.com> # comment\n\
+             alias -group devs bob bob@example.com, \"Lee, Carl\" <carl@example.com>\n\
+             set foo=bar\n",
+            AliasFileType::Mutt,
+        );
+        assert_eq!(
+            aliases.expand("ann").unwrap(),
+            ["Ann Author <ann@example.com>"]
+        );
+        assert_eq!(
+            aliases.expand("bob").unwrap(),
+            ["bob@example.com", "\"Lee, Carl\" <carl@example.com>"]
+        );
+
+        aliases.parse(
+            "alias team ann 'Dee Dev <dee@example.com>'\n",
+            AliasFileType::Mailrc,
+        );
+        assert_eq!(
+            aliases.expand("team").unwrap(),
+            ["Ann Author <ann@example.com>", "Dee Dev <dee@example.com>"]
+        );
+
+        aliases.parse(
+            "# comment\n\
+             all: team,\n  bob, |/usr/bin/prog\n",
+            AliasFileType::Sendmail,
+        );
+        assert_eq!(aliases.expand("all, ann").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn expand_unknown_and_looping_aliases() {
+        let mut aliases = Aliases::default();
+        aliases.parse(
+            "alias loop loop2\nalias loop2 loop\n",
+            AliasFileType::Mailrc,
+        );
+        assert!(aliases.expand("someone@example.com").is_ok());
+        assert!(aliases
+            .expand("loop")
+            .unwrap_err()
+            .to_string()
+            .contains("expands to itself"));
+        assert_eq!(
+            aliases.expand("loop3").unwrap_err().to_string(),
+            "unknown email alias `loop3`; did you mean `loop`, `loop2`?"
+        );
+    }
+
+    #[test]
+    fn split_address_lists() {
+        assert_eq!(
+            split_addresses(" a@x, \"B, b\" <b@x>,,<c,@x> "),
+            ["a@x", "\"B, b\" <b@x>", "<c,@x>"]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Interactive review of the emails sent by `stg email send --confirm`.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use crate::{patch::PatchName, stack::StackConfig};
+
+/// Number of message body lines shown in each email's preview.
+const PREVIEW_BODY_LINES: usize = 15;
+
+/// An email file to be reviewed before sending.
+pub(super) struct Email {
+    /// Path to the email file, which may be rewritten when the email is edited.
+    pub(super) path: PathBuf,
+
+    /// The patch the email was formatted from, if known.
+    pub(super) patchname: Option<PatchName>,
+}
+
+/// Decision made for one email.
+enum Choice {
+    Accept,
+    Skip,
+    Edit,
+    Abort,
+}
+
+/// Review each email, letting the user accept, skip, or edit it, or abort.
+///
+/// The accepted emails are returned in their original order, or `None` if the user
+/// aborted. Nothing is sent here; sending only happens after all the decisions are
+/// made.
+pub(super) fn review(
+    emails: Vec<Email>,
+    config: &StackConfig,
+    matches: &clap::ArgMatches,
+) -> Result<Option<Vec<Email>>> {
+    let total = emails.len();
+    let mut accepted = Vec::with_capacity(total);
+    for (i, email) in emails.into_iter().enumerate() {
+        let mut edited = false;
+        loop {
+            let content = std::fs::read(&email.path)?;
+            print_preview(&email, &content, i + 1, total)?;
+            match prompt()? {
+                Choice::Accept => {
+                    // Only edited emails are checked; other emails are sent as given.
+                    if edited {
+                        if let Err(e) = check_headers(&content) {
+                            crate::print_warning_message(
+                                matches,
+                                &format!("edited email is invalid: {e}; edit it again or skip it"),
+                            );
+                            continue;
+                        }
+                    }
+                    accepted.push(email);
+                    break;
+                }
+                Choice::Skip => break,
+                Choice::Abort => return Ok(None),
+                Choice::Edit => {
+                    let content = crate::patch::patchedit::call_editor(&email.path, config)?;
+                    std::fs::write(&email.path, &content)?;
+                    edited = true;
+                    if let Err(e) = check_headers(&content) {
+                        crate::print_warning_message(
+                            matches,
+                            &format!("edited email is invalid: {e}"),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(Some(accepted))
+}
+
+/// Print the email's headers and the beginning of its body to stderr.
+fn print_preview(email: &Email, content: &[u8], number: usize, total: usize) -> Result<()> {
+    let mut stderr = std::io::stderr().lock();
+    let name = email
+        .path
+        .file_name()
+        .map_or_else(Default::default, |name| name.to_string_lossy());
+    if let Some(patchname) = email.patchname.as_ref() {
+        writeln!(stderr, "--- Email {number}/{total}: {name} ({patchname})")?;
+    } else {
+        writeln!(stderr, "--- Email {number}/{total}: {name}")?;
+    }
+    let (headers, body) = split_message(content);
+    for line in headers.lines() {
+        writeln!(stderr, "{}", line.as_bstr())?;
+    }
+    writeln!(stderr)?;
+    let num_lines = body.lines().count();
+    for line in body.lines().take(PREVIEW_BODY_LINES) {
+        writeln!(stderr, "{}", line.as_bstr())?;
+    }
+    if num_lines > PREVIEW_BODY_LINES {
+        writeln!(
+            stderr,
+            "[... {} more lines]",
+            num_lines - PREVIEW_BODY_LINES
+        )?;
+    }
+    Ok(())
+}
+
+/// Ask for the decision on an email until a valid answer is given.
+///
+/// The end of input aborts.
+fn prompt() -> Result<Choice> {
+    let mut stderr = std::io::stderr();
+    let stdin = std::io::stdin();
+    loop {
+        write!(stderr, "Send this email? ([y]es|[s]kip|[e]dit|[a]bort): ")?;
+        stderr.flush()?;
+        let mut answer = String::new();
+        if stdin.read_line(&mut answer)? == 0 {
+            writeln!(stderr)?;
+            return Ok(Choice::Abort);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(Choice::Accept),
+            "s" | "skip" => return Ok(Choice::Skip),
+            "e" | "edit" => return Ok(Choice::Edit),
+            "a" | "abort" => return Ok(Choice::Abort),
+            _ => {}
+        }
+    }
+}
+
+/// Split an email into its header block and body.
+///
+/// An mbox "From " line preceding the headers, as written by `git format-patch`, is
+/// not part of the headers.
+pub(super) fn split_message(content: &[u8]) -> (&[u8], &[u8]) {
+    let content = if content.starts_with(b"From ") {
+        content
+            .find_byte(b'\n')
+            .map_or(&content[..0], |i| &content[i + 1..])
+    } else {
+        content
+    };
+    if let Some(i) = content.find(b"\n\n") {
+        (&content[..i + 1], &content[i + 2..])
+    } else {
+        (content, &content[content.len()..])
+    }
+}
+
+/// Check that the email has a well-formed header block with a subject.
+fn check_headers(content: &[u8]) -> Result<()> {
+    let (headers, body) = split_message(content);
+    if body.is_empty() && !content.ends_with(b"\n\n") {
+        return Err(anyhow!("no blank line follows the headers"));
+    }
+    let mut has_subject = false;
+    for (i, line) in headers.lines().enumerate() {
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            if i == 0 {
+                return Err(anyhow!(
+                    "header continuation `{}` without a header",
+                    line.as_bstr()
+                ));
+            }
+            continue;
+        }
+        let name = line
+            .split_once_str(":")
+            .map(|(name, _)| name)
+            .filter(|name| {
+                !name.is_empty() && name.iter().all(|&c| c.is_ascii_graphic() && c != b':')
+            })
+            .ok_or_else(|| anyhow!("invalid header line `{}`", line.as_bstr()))?;
+        if name.eq_ignore_ascii_case(b"subject") {
+            has_subject = true;
+        }
+    }
+    if has_subject {
+        Ok(())
+    } else {
+        Err(anyhow!("missing `Subject` header"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers() {
+        let email = b"From 1234 Mon Sep 17 00:00:00 2001\n\
+                      From: A U Thor <author@example.com>\n\
+                      Subject: [PATCH 1/2] Fix\n \
+                       the bug\n\
+                      \n\
+                      Body\n";
+        let (headers, body) = split_message(email);
+        assert_eq!(
+            headers,
+            b"From: A U Thor <author@example.com>\nSubject: [PATCH 1/2] Fix\n the bug\n"
+        );
+        assert_eq!(body, b"Body\n");
+        assert!(check_headers(email).is_ok());
+        assert!(check_headers(b"Subject: x\n\n").is_ok());
+
+        for (email, expected) in [
+            (&b"From: a@b\n\nBody\n"[..], "missing `Subject` header"),
+            (b"Subject: x\nBody\n", "no blank line follows the headers"),
+            (
+                b"Subject: x\nnot a header\n\n",
+                "invalid header line `not a header`",
+            ),
+            (b"Bad Name: x\n\n", "invalid header line `Bad Name: x`"),
+            (
+                b" x\nSubject: x\n\n",
+                "header continuation ` x` without a header",
+            ),
+        ] {
+            assert_eq!(check_headers(email).unwrap_err().to_string(), expected);
+        }
+    }
+}
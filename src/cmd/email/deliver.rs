@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Delivery of email files with `git send-email`.
+//!
+//! Normally, all emails are handed to a single `git send-email` invocation, which
+//! sends them over one SMTP connection, or several with `--batch-size`. With
+//! `--pause`, each email is instead sent by its own invocation, waiting in between.
+//! The emails are then threaded by StGit, using the Message-IDs written into the
+//! email files before sending.
+//!
+//! Should sending fail midway, the emails that were sent are determined such that
+//! the remaining emails may be sent with `--resume-from`.
+
+use std::{io::Write, path::Path, time::Duration};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use super::confirm::Email;
+use crate::stupid::Stupid;
+
+/// How the emails of a sequence of `git send-email` invocations are threaded.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Threading {
+    /// Emails are not threaded.
+    None,
+
+    /// Each email is a reply to the first email.
+    Shallow,
+
+    /// Each email is a reply to the previous email.
+    Deep,
+}
+
+/// Options for delivering the emails.
+pub(super) struct Delivery<'a> {
+    /// Options passed to each `git send-email` invocation.
+    pub(super) send_args: &'a [String],
+
+    /// Time to wait between emails, each of which is then sent separately.
+    pub(super) pause: Option<Duration>,
+
+    /// Threading of separately sent emails.
+    pub(super) threading: Threading,
+
+    /// Domain of the Message-IDs written into separately sent emails.
+    pub(super) domain: &'a str,
+
+    /// Number of emails composed by `git send-email` ahead of the email files.
+    pub(super) num_composed: usize,
+
+    /// Whether `git send-email` is run with `--quiet`, which hides the Message-IDs.
+    pub(super) quiet: bool,
+
+    /// Whether `git send-email` is run with `--dry-run`.
+    pub(super) dry_run: bool,
+}
+
+/// Failure to send all of the emails.
+pub(super) struct Failure {
+    /// Number of email files sent before the failure, if known.
+    pub(super) num_sent: Option<usize>,
+
+    /// Message-IDs of the emails sent before the failure.
+    pub(super) message_ids: Vec<String>,
+
+    /// The error that stopped sending.
+    pub(super) error: anyhow::Error,
+}
+
+/// Send the emails, returning the output of `git send-email`.
+pub(super) fn deliver(
+    repo: &gix::Repository,
+    delivery: &Delivery,
+    emails: &[Email],
+) -> Result<Vec<u8>, Failure> {
+    if let Some(pause) = delivery.pause {
+        deliver_separately(repo, delivery, emails, pause)
+    } else {
+        let mut args = delivery.send_args.to_vec();
+        args.extend(
+            emails
+                .iter()
+                .map(|email| email.path.to_string_lossy().to_string()),
+        );
+        let (output, status) = repo.stupid().send_email(args).map_err(|error| Failure {
+            num_sent: Some(0),
+            message_ids: Vec::new(),
+            error,
+        })?;
+        match status {
+            Ok(()) => Ok(output),
+            Err(error) => {
+                let message_ids = super::parse_message_ids(&output);
+                let num_sent = (!delivery.quiet || !message_ids.is_empty()).then(|| {
+                    message_ids
+                        .len()
+                        .saturating_sub(delivery.num_composed)
+                        .min(emails.len())
+                });
+                Err(Failure {
+                    num_sent,
+                    message_ids,
+                    error,
+                })
+            }
+        }
+    }
+}
+
+/// Send each email by its own `git send-email` invocation, pausing in between.
+fn deliver_separately(
+    repo: &gix::Repository,
+    delivery: &Delivery,
+    emails: &[Email],
+    pause: Duration,
+) -> Result<Vec<u8>, Failure> {
+    let mut message_ids: Vec<String> = Vec::with_capacity(emails.len());
+    let mut outputs = Vec::new();
+    for (i, email) in emails.iter().enumerate() {
+        let result = (|| -> Result<()> {
+            if i > 0 && !delivery.dry_run {
+                std::io::stdout().flush()?;
+                std::thread::sleep(pause);
+            }
+            let message_id = ensure_message_id(&email.path, delivery.domain, i)?;
+            let mut args = delivery.send_args.to_vec();
+            let reply_to = match delivery.threading {
+                Threading::None => None,
+                Threading::Shallow => message_ids.first(),
+                Threading::Deep => message_ids.last(),
+            };
+            if let Some(reply_to) = reply_to {
+                args.push(format!("--in-reply-to={reply_to}"));
+            }
+            args.push(email.path.to_string_lossy().to_string());
+            let (output, status) = repo.stupid().send_email(args)?;
+            status?;
+            outputs.extend(output);
+            message_ids.push(message_id);
+            Ok(())
+        })();
+        if let Err(error) = result {
+            return Err(Failure {
+                num_sent: Some(i),
+                message_ids,
+                error,
+            });
+        }
+    }
+    Ok(outputs)
+}
+
+/// Get the Message-ID of the email file, first adding one if it has none.
+///
+/// The added Message-ID is made unique by the current time, the process id, and the
+/// email's `index` in the series.
+fn ensure_message_id(path: &Path, domain: &str, index: usize) -> Result<String> {
+    let content = std::fs::read(path)?;
+    let headers_end = content.find("\n\n").map_or(content.len(), |pos| pos + 1);
+    if let Some(message_id) = super::parse_message_ids(&content[..headers_end])
+        .into_iter()
+        .next()
+    {
+        return Ok(message_id);
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|_| anyhow!("system time is before the epoch"))?;
+    let message_id = format!(
+        "<{}.{}-{}-{index}-stgit@{domain}>",
+        now.as_secs(),
+        now.subsec_nanos(),
+        std::process::id(),
+    );
+    let mut new_content = Vec::with_capacity(content.len() + message_id.len() + 13);
+    new_content.extend_from_slice(&content[..headers_end]);
+    if !new_content.is_empty() && !new_content.ends_with(b"\n") {
+        new_content.push(b'\n');
+    }
+    new_content.extend_from_slice(format!("Message-ID: {message_id}\n").as_bytes());
+    new_content.extend_from_slice(&content[headers_end..]);
+    std::fs::write(path, new_content)?;
+    Ok(message_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_id_is_added_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0001-p1.patch");
+        std::fs::write(&path, "From: a@b\nSubject: [PATCH] p1\n\nbody\n").unwrap();
+
+        let message_id = ensure_message_id(&path, "example.com", 3).unwrap();
+        assert!(message_id.starts_with('<'));
+        assert!(message_id.ends_with("-3-stgit@example.com>"));
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            format!("From: a@b\nSubject: [PATCH] p1\nMessage-ID: {message_id}\n\nbody\n")
+        );
+
+        assert_eq!(
+            ensure_message_id(&path, "example.com", 4).unwrap(),
+            message_id
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+    }
+}
@@ -3,14 +3,15 @@
 //! `stg email format` implementation.
 
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::Arg;
 
 use crate::{
     argset,
-    branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    output::Output,
+    patch::{PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -40,12 +41,39 @@ pub(super) fn command() -> clap::Command {
              always generate a cover letter or 'auto' to generate a cover letter when \
              formatting more than one patch.\n\
              \n\
+             When threading is enabled with '--thread' or the `format.thread` \
+             configuration, the Message-ID generated for each email is printed after \
+             the email file names, labeled with its patch name. `git send-email` reuses \
+             these Message-IDs when sending the files. If `stgit.email.linktemplate` \
+             is set, a link made by replacing \"{msgid}\" with the Message-ID is \
+             printed instead.\n\
+             \n\
              Recipients may be specified using the '--to' and '--cc', or setting \
-             recipients may be deferred to `stg email send`.\n\
+             recipients may be deferred to `stg email send`. Aliases from the \
+             `sendemail.aliasesFile` files given to '--to' and '--cc' are expanded \
+             such that the email files contain the full addresses; see `stg email \
+             aliases`.\n\
              \n\
              Many aspects of the format behavior may be controlled via `format.*` \
-             configuration values. Refer to the git-config(1) and git-format-patch(1) \
-             man pages for more details.",
+             configuration values, which are honored as they are by `git \
+             format-patch`. In particular:\n\
+             \n  \
+             - `format.to` and `format.cc` add recipients to every email\n  \
+             - `format.headers` adds arbitrary email headers\n  \
+             - `format.subjectPrefix` replaces \"PATCH\" in the bracketed subject \
+             prefix\n  \
+             - `format.signOff` adds a Signed-off-by trailer with the committer \
+             identity\n  \
+             - `format.coverLetter` generates a cover letter\n  \
+             - `format.numbered`, `format.thread`, `format.signature`, \
+             `format.suffix`, and `format.outputDirectory`\n\
+             \n\
+             Command line options take precedence over these configuration values. \
+             The '--no-to', '--no-cc', '--no-signoff', '--no-cover-letter', \
+             '--no-numbered', '--no-thread', and '--no-signature' options may be used \
+             to override configuration values enabling the respective feature. Refer \
+             to the git-config(1) and git-format-patch(1) man pages for more \
+             details.",
         )
         .override_usage(super::super::make_usage(
             "stg email format",
@@ -58,9 +86,12 @@ pub(super) fn command() -> clap::Command {
                 .num_args(1..)
                 .value_parser(clap::value_parser!(PatchRange))
                 .conflicts_with("all")
-                .required_unless_present_any(["all"]),
+                .required_unless_present_any(["all", "stdin"]),
         )
+        .args(argset::stdin_patches_args("patchranges").map(|arg| arg.conflicts_with("all")))
         .arg(argset::branch_arg())
+        .arg(argset::remote_stack_arg().conflicts_with("branch"))
+        .arg(argset::include_gaps_arg())
         .arg(
             Arg::new("all")
                 .long("all")
@@ -68,6 +99,18 @@ pub(super) fn command() -> clap::Command {
                 .help("Format all applied patches")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Format patches even if their subject is empty")
+                .long_help(
+                    "Format patches even if their message is entirely empty, which \
+                     would otherwise be refused since their emails would have empty \
+                     subjects.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::unified_arg())
         .arg(
             Arg::new("git-format-patch-opt")
                 .long("git-opt")
@@ -113,6 +156,15 @@ fn format_options() -> Vec<Arg> {
                  fill in a description in the file before sending it out.",
             )
             .action(clap::ArgAction::SetTrue),
+        Arg::new("no-cover-letter")
+            .long("no-cover-letter")
+            .help("Do not generate a cover letter")
+            .long_help(
+                "Do not generate a cover letter, even if the `format.coverLetter` \
+                 configuration is set.",
+            )
+            .overrides_with("cover-letter")
+            .action(clap::ArgAction::SetTrue),
         Arg::new("numbered")
             .long("numbered")
             .short('n')
@@ -166,10 +218,6 @@ fn format_options() -> Vec<Arg> {
             )
             .value_name("prefix")
             .num_args(1),
-        Arg::new("quiet")
-            .long("quiet")
-            .help("Do not print the names of the generated files")
-            .action(clap::ArgAction::SetTrue),
         Arg::new("signoff")
             .long("signoff")
             .short('s')
@@ -180,6 +228,15 @@ fn format_options() -> Vec<Arg> {
                  git-commit(1) for more information.",
             )
             .action(clap::ArgAction::SetTrue),
+        Arg::new("no-signoff")
+            .long("no-signoff")
+            .help("Do not add a Signed-off-by trailer")
+            .long_help(
+                "Do not add a Signed-off-by trailer, even if the `format.signOff` \
+                 configuration is set.",
+            )
+            .overrides_with("signoff")
+            .action(clap::ArgAction::SetTrue),
         Arg::new("numbered-files")
             .long("numbered-files")
             .help("Use simple number sequence for output file names")
@@ -233,7 +290,7 @@ fn format_options() -> Vec<Arg> {
 }
 
 fn message_options() -> Vec<Arg> {
-    vec![
+    let mut options = vec![
         Arg::new("to")
             .long("to")
             .help("Specify a To: address for each email")
@@ -297,26 +354,6 @@ fn message_options() -> Vec<Arg> {
             .num_args(1)
             .value_parser(clap::builder::NonEmptyStringValueParser::new())
             .action(clap::ArgAction::Append),
-        // N.B. not supporting the optional mime-boundary value
-        Arg::new("attach")
-            .long("attach")
-            .help("Attach the patch")
-            .long_help(
-                "Create multipart/mixed attachment, the first part of which is the \
-                 commit message and the patch itself in the second part, with \
-                 `Content-Disposition:` attachment.",
-            )
-            .action(clap::ArgAction::SetTrue),
-        // N.B. not supporting the optional mime-boundary value
-        Arg::new("inline")
-            .long("inline")
-            .help("Inline the patch")
-            .long_help(
-                "Create multipart/mixed attachment, the first part of which is the \
-                 commit message and the patch itself in the second part, with \
-                 `Content-Disposition: inline`.",
-            )
-            .action(clap::ArgAction::SetTrue),
         Arg::new("thread")
             .long("thread")
             .help("Enable message threading, styles: shallow or deep")
@@ -409,10 +446,16 @@ fn message_options() -> Vec<Arg> {
                  version of the patch series and the series currently being formatted. \
                  <refspec> can be a single revision naming the tip of the previous \
                  series if it shares a common base with the series being formatted \
-                 (for example `git format-patch --cover-letter --range-diff=feature/v1 \
-                 -3 feature/v2`), or a revision range if the two versions of the \
-                 series are disjoint (for example `git format-patch --cover-letter \
-                 --range-diff=feature/v1~3..feature/v1 -3 feature/v2`).\n\
+                 (for example `--range-diff=feature/v1`), or a revision range if the \
+                 two versions of the series are disjoint (for example \
+                 `--range-diff=feature/v1~3..feature/v1`).\n\
+                 \n\
+                 <refspec> may also be a stack state commit id from the stack log, as \
+                 shown by 'stg log'. The previous version of the series is then the \
+                 versions of the formatted patches in that stack state, following any \
+                 renames of the patches since.\n\
+                 \n\
+                 A cover letter is implied when formatting more than one patch.\n\
                  \n\
                  Note that diff options passed to the command affect how the primary \
                  product of `format-patch` is generated, and they are not passed to \
@@ -433,25 +476,73 @@ fn message_options() -> Vec<Arg> {
             )
             .num_args(1)
             .value_name("n")
-            .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .requires("range-diff"),
         // NO --from
         // NO --no-add-header
+    ];
+    options.extend(attach_options());
+    options
+}
+
+/// Options for sending the patch as a MIME attachment, shared with `stg email send`.
+pub(super) fn attach_options() -> Vec<Arg> {
+    vec![
+        // N.B. not supporting the mime-boundary value of `git format-patch --attach`
+        Arg::new("attach")
+            .long("attach")
+            .help("Attach the patch, with the given <disposition>")
+            .long_help(
+                "Create a multipart/mixed message. The first part holds the commit \
+                 message, i.e. the commentary above the `---` line, and the second \
+                 part holds the patch as a `text/x-patch` attachment named after the \
+                 email file, e.g. `0001-Subject.patch`.\n\
+                 \n\
+                 The optional <disposition> is either `attachment`, the default, for \
+                 `Content-Disposition: attachment`, or `inline`, which is the same as \
+                 '--inline'. The subjects and threading of the emails are unaffected.",
+            )
+            .value_name("disposition")
+            .value_parser(["attachment", "inline"])
+            .num_args(0..=1)
+            .default_missing_value("attachment")
+            .require_equals(true),
+        Arg::new("inline")
+            .long("inline")
+            .help("Attach the patch inline")
+            .long_help(
+                "Create a multipart/mixed message as with '--attach', but with \
+                 `Content-Disposition: inline` for the patch.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("attach"),
     ]
 }
 
+/// Get the `git format-patch` option for an `--attach=<disposition>` value.
+pub(super) fn attach_arg(disposition: &str) -> &'static str {
+    if disposition == "inline" {
+        "--inline"
+    } else {
+        "--attach"
+    }
+}
+
 pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     let repo = gix::Repository::open()?;
-    let stack = Stack::from_branch_locator(
-        &repo,
-        matches.get_one::<BranchLocator>("branch"),
-        InitializationPolicy::AllowUninitialized,
-    )?;
+    let stack = argset::stack_from_args(&repo, matches, InitializationPolicy::AllowUninitialized)?;
 
-    let patches = if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
-        let patches = patchrange::resolve_names_contiguous(
+    let patches = if let Some(range_specs) =
+        argset::get_patch_ranges::<PatchRange>(matches, "patchranges")?
+    {
+        if range_specs.is_empty() {
+            return Ok(());
+        }
+        let patches = argset::resolve_names_contiguous(
             &stack,
-            range_specs,
-            RangeConstraint::VisibleWithAppliedBoundary,
+            range_specs.iter(),
+            RangeConstraint::AllWithAppliedBoundary,
+            matches,
         )?;
         if patches.is_empty() {
             return Err(anyhow!("no patches to format"));
@@ -472,7 +563,15 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
             return Err(anyhow!("cannot format empty patch `{patchname}`"));
         }
     }
+    super::check_subjects(&stack, &patches, matches)?;
+
+    stack.prefetch_patch_blobs(&patches)?;
+
+    let range_diff = argset::get_one_str(matches, "range-diff")
+        .map(|spec| super::resolve_range_diff(&stack, spec, &patches))
+        .transpose()?;
 
+    let aliases = super::aliases::Aliases::load(&stack.config())?;
     let mut format_args: Vec<(usize, String)> = Vec::new();
 
     // This dummy command is constructed with just the Args that are to be
@@ -494,13 +593,22 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
             if num_args.takes_values() {
                 let values = matches.get_many::<String>(arg_id).unwrap();
                 assert!(indices.len() == values.len());
-                indices.into_iter().zip(values).for_each(|(index, value)| {
+                for (index, value) in indices.into_iter().zip(values) {
                     if arg_id == "thread" && value.is_empty() {
                         format_args.push((index, format!("--{long}")));
+                    } else if arg_id == "attach" {
+                        format_args.push((index, attach_arg(value).to_string()));
+                    } else if arg_id == "range-diff" {
+                        let range_diff = range_diff.as_ref().expect("range-diff is resolved");
+                        format_args.push((index, format!("--{long}={range_diff}")));
+                    } else if matches!(arg_id, "to" | "cc") {
+                        for address in aliases.expand(value)? {
+                            format_args.push((index, format!("--{long}={address}")));
+                        }
                     } else {
                         format_args.push((index, format!("--{long}={value}")));
                     }
-                });
+                }
             } else {
                 indices.for_each(|index| format_args.push((index, format!("--{long}"))));
             }
@@ -511,6 +619,24 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
 
     let mut format_args = format_args.drain(..).map(|(_, s)| s).collect::<Vec<_>>();
 
+    if Output::new(matches).is_quiet() {
+        format_args.push("--quiet".to_string());
+    }
+
+    if range_diff.is_some() && patches.len() > 1 {
+        if matches.get_flag("no-cover-letter") {
+            return Err(anyhow!(
+                "`--range-diff` requires a cover letter when formatting more than one patch"
+            ));
+        } else if !matches.get_flag("cover-letter") {
+            format_args.push("--cover-letter".to_string());
+        }
+    }
+
+    if let Some(context_lines) = matches.get_one::<usize>("unified") {
+        format_args.push(format!("--unified={context_lines}"));
+    }
+
     if let Some(values) = matches.get_many::<String>("git-format-patch-opt") {
         format_args.extend(values.cloned());
     }
@@ -526,5 +652,9 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         format_args.push(format!("{base}..{last}"));
     }
 
-    repo.stupid().format_patch(format_args)
+    let output = repo.stupid().format_patch(format_args)?;
+    let message_ids = super::message_ids_from_files(output.lines());
+    let pairs = super::pair_message_ids(Some(&patches), &message_ids);
+    super::print_links(super::link_template(&stack.config()).as_deref(), &pairs);
+    Ok(())
 }
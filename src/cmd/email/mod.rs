@@ -2,14 +2,27 @@
 
 //! `stg email` implementation.
 
+mod aliases;
+mod confirm;
+mod deliver;
 mod format;
+mod recipients;
 mod send;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use gix::prelude::ObjectIdExt;
+
+use crate::{
+    ext::CommitExtended,
+    patch::{PatchName, Subject, SubjectSource},
+    stack::{history, Stack, StackConfig, StackStateAccess},
+};
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "email",
     category: super::CommandCategory::StackInspection,
+    read_only: false,
     make,
     run,
 };
@@ -29,17 +42,252 @@ fn make() -> clap::Command {
              The `format` and `send` subcommands are thin wrappers over `git \
              format-patch` and `git send-email`, respectively. Refer to the \
              git-format-patch(1) and git-send-email(1) manpages for more details about \
-             configuration and options.",
+             configuration and options.\n\
+             \n\
+             Recipient aliases from the `sendemail.aliasesFile` files are expanded by \
+             both subcommands; `stg email aliases` lists the resolved aliases.",
         )
         .subcommand_required(true)
         .subcommand(format::command())
         .subcommand(send::command())
+        .subcommand(aliases::command())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     match matches.subcommand() {
         Some(("format", sub_matches)) => format::dispatch(sub_matches),
-        Some(("send", sub_matches)) => send::dispatch(sub_matches),
+        Some(("send", sub_matches)) => send::dispatch(sub_matches, command_line),
+        Some(("aliases", sub_matches)) => aliases::dispatch(sub_matches),
         _ => panic!("valid subcommand is expected"),
     }
 }
+
+/// Resolve the `--range-diff` spec to the previous version of the series for git.
+///
+/// A spec containing `..` is a range of the previous version's commits and is passed
+/// to git as-is. A spec naming a commit from the stack log, as shown by `stg log`,
+/// selects the versions of `patches` recorded in that stack state; the range spanning
+/// those patches in that state is returned. Any other commit is the tip of the
+/// previous version, sharing its base with the current version.
+///
+/// The spec is resolved here, before anything is formatted, such that an invalid spec
+/// fails before any files are written.
+fn resolve_range_diff(stack: &Stack, spec: &str, patches: &[PatchName]) -> Result<String> {
+    let repo = stack.repo;
+    if spec.contains("..") {
+        repo.rev_parse(spec)
+            .map_err(|_| anyhow!("invalid range-diff range `{spec}`"))?;
+        return Ok(spec.to_string());
+    }
+
+    let commit_id = repo
+        .rev_parse_single(spec)
+        .map_err(|_| anyhow!("invalid range-diff committish `{spec}`"))?
+        .object()?
+        .peel_tags_to_end()?
+        .try_into_commit()
+        .map_err(|_| anyhow!("range-diff target `{spec}` is not a commit"))?
+        .id;
+
+    let in_stack_log = history::stack_log(stack)?.any(|entry| {
+        entry.is_ok_and(|entry| entry.commit.id == commit_id || entry.simplified_id() == commit_id)
+    });
+    if !in_stack_log {
+        return Ok(commit_id.to_string());
+    }
+
+    let mut positions = Vec::new();
+    let mut old_state = None;
+    for patchname in patches {
+        let Some(version) = history::patch_at_state(stack, patchname, commit_id)? else {
+            continue;
+        };
+        let state = &version.entry.state;
+        let position = state
+            .applied()
+            .iter()
+            .position(|pn| pn == &version.patchname)
+            .ok_or_else(|| {
+                anyhow!(
+                    "patch `{}` is not applied in stack state `{}`",
+                    version.patchname,
+                    commit_id.attach(repo).shorten_or_id(),
+                )
+            })?;
+        positions.push(position);
+        old_state = Some(version.entry.state);
+    }
+
+    let (Some(old_state), Some(&first), Some(&last)) =
+        (old_state, positions.iter().min(), positions.iter().max())
+    else {
+        return Err(anyhow!(
+            "none of the patches exist in stack state `{}`",
+            commit_id.attach(repo).shorten_or_id()
+        ));
+    };
+    let applied = old_state.applied();
+    let base_id = old_state
+        .get_patch_commit(&applied[first])
+        .parent_ids()
+        .next()
+        .expect("patch commit has a parent")
+        .detach();
+    let last_id = old_state.get_patch_commit_id(&applied[last]);
+    Ok(format!("{base_id}..{last_id}"))
+}
+
+/// Get the values of the Message-ID header lines found in `content`.
+/// Ensure that none of the patches would be emailed with an empty subject.
+///
+/// A patch whose message is entirely empty would yield an email with a subject such as
+/// "[PATCH 3/7] ", which is refused unless `--force` is given.
+fn check_subjects(stack: &Stack, patches: &[PatchName], matches: &clap::ArgMatches) -> Result<()> {
+    if matches.get_flag("force") {
+        return Ok(());
+    }
+    for patchname in patches {
+        let message = stack.get_patch_commit(patchname).message_ex();
+        if Subject::new(&message.decode()?, patchname).source == SubjectSource::PatchName {
+            return Err(anyhow!(
+                "patch `{patchname}` has an empty subject; use `stg edit {patchname}` to \
+                 give it a message or `--force` to proceed anyway"
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn parse_message_ids(content: &[u8]) -> Vec<String> {
+    let mut message_ids = Vec::new();
+    for line in content.lines() {
+        if let Some((name, value)) = line.split_once_str(":") {
+            if name.trim().eq_ignore_ascii_case(b"message-id") {
+                let value = value.trim();
+                if value.starts_with(b"<") && value.ends_with(b">") {
+                    if let Ok(value) = value.to_str() {
+                        message_ids.push(value.to_string());
+                    }
+                }
+            }
+        }
+    }
+    message_ids
+}
+
+/// Get the Message-ID of each email in the given email files.
+fn message_ids_from_files<'a>(paths: impl IntoIterator<Item = &'a [u8]>) -> Vec<String> {
+    paths
+        .into_iter()
+        .filter_map(|path| path.to_path().ok())
+        .filter(|path| path.is_file())
+        .filter_map(|path| std::fs::read(path).ok())
+        .filter_map(|content| {
+            let headers_end = content.find("\n\n").unwrap_or(content.len());
+            parse_message_ids(&content[..headers_end])
+                .into_iter()
+                .next()
+        })
+        .collect()
+}
+
+/// Make a link to the email with the given Message-ID.
+///
+/// The `stgit.email.linktemplate` configuration value is used as the link template,
+/// with "{msgid}" replaced with the Message-ID, sans angle brackets. Without a
+/// template, the bracketed Message-ID is used as-is.
+fn make_link(template: Option<&str>, message_id: &str) -> String {
+    if let Some(template) = template {
+        let bare_id = message_id
+            .strip_prefix('<')
+            .and_then(|id| id.strip_suffix('>'))
+            .unwrap_or(message_id);
+        template.replace("{msgid}", bare_id)
+    } else {
+        message_id.to_string()
+    }
+}
+
+/// Get the configured link template.
+fn link_template(config: &StackConfig) -> Option<String> {
+    config
+        .string("stgit.email.linktemplate")
+        .map(|template| template.to_str_lossy().to_string())
+}
+
+/// Pair Message-IDs with the patches they were generated for.
+///
+/// The emails for the patches are the last ones; any preceding email, such as a cover
+/// letter, is paired with `None`. If there are fewer Message-IDs than patches, no
+/// Message-ID is paired with any patch.
+fn pair_message_ids<'a>(
+    patches: Option<&'a [PatchName]>,
+    message_ids: &'a [String],
+) -> Vec<(Option<&'a PatchName>, &'a str)> {
+    let patches = patches.unwrap_or_default();
+    let num_extra = message_ids.len().checked_sub(patches.len());
+    message_ids
+        .iter()
+        .enumerate()
+        .map(|(i, message_id)| {
+            let patchname = num_extra.and_then(|num_extra| i.checked_sub(num_extra));
+            (patchname.map(|i| &patches[i]), message_id.as_str())
+        })
+        .collect()
+}
+
+/// Print a link for each Message-ID, labeled with its patch name if known.
+fn print_links(template: Option<&str>, pairs: &[(Option<&PatchName>, &str)]) {
+    for (patchname, message_id) in pairs {
+        let link = make_link(template, message_id);
+        if let Some(patchname) = patchname {
+            println!("{patchname}: {link}");
+        } else {
+            println!("{link}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn message_ids_and_links() {
+        let output = b"From: a@b\n\
+                       Subject: [PATCH 0/2] cover\n\
+                       Message-ID: <0.x@y>\n\
+                       \n\
+                       Result: OK\n\
+                       Message-Id: <1.x@y>\n\
+                       message-id:    <2.x@y>  \n\
+                       Message-ID: not-an-id\n";
+        let message_ids = parse_message_ids(output);
+        assert_eq!(message_ids, ["<0.x@y>", "<1.x@y>", "<2.x@y>"]);
+
+        let patches = [
+            PatchName::from_str("p1").unwrap(),
+            PatchName::from_str("p2").unwrap(),
+        ];
+        let pairs = pair_message_ids(Some(&patches), &message_ids);
+        assert_eq!(
+            pairs,
+            [
+                (None, "<0.x@y>"),
+                (Some(&patches[0]), "<1.x@y>"),
+                (Some(&patches[1]), "<2.x@y>"),
+            ]
+        );
+        assert!(pair_message_ids(Some(&patches), &message_ids[..1])
+            .iter()
+            .all(|(patchname, _)| patchname.is_none()));
+
+        assert_eq!(
+            make_link(Some("https://lore.kernel.org/r/{msgid}"), "<1.x@y>"),
+            "https://lore.kernel.org/r/1.x@y"
+        );
+        assert_eq!(make_link(None, "<1.x@y>"), "<1.x@y>");
+    }
+}
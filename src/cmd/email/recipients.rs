@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Cover letter recipients and the recipient table of `stg email send`.
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use super::{aliases::split_addresses, confirm::split_message};
+use crate::patch::PatchName;
+
+/// Recipients of one email, as reported by `git send-email --dry-run`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct Message {
+    subject: String,
+    message_id: Option<String>,
+    in_reply_to: Option<String>,
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+}
+
+/// Add the cover-letter-only recipients to the cover letter among `emails`.
+///
+/// The recipients are added as "To:" and "Cc:" headers of the cover letter, which `git
+/// send-email` adds to that email's recipients only.
+pub(super) fn add_cover_recipients(
+    emails: &[super::confirm::Email],
+    to: &[String],
+    cc: &[String],
+) -> Result<()> {
+    for email in emails {
+        let content = std::fs::read(&email.path)?;
+        if !is_cover_letter(&subject(split_message(&content).0)) {
+            continue;
+        }
+        let insert_at = content.find(b"\n\n").map_or(content.len(), |i| i + 1);
+        let mut new_content = content[..insert_at].to_vec();
+        for address in to {
+            new_content.extend_from_slice(format!("To: {address}\n").as_bytes());
+        }
+        for address in cc {
+            new_content.extend_from_slice(format!("Cc: {address}\n").as_bytes());
+        }
+        new_content.extend_from_slice(&content[insert_at..]);
+        std::fs::write(&email.path, new_content)?;
+        return Ok(());
+    }
+    Err(anyhow!(
+        "`--to-cover` and `--cc-cover` require a cover letter; \
+         use `--cover-letter` to generate one"
+    ))
+}
+
+/// Determine whether the subject is that of a cover letter, e.g. "[PATCH 0/3] ...".
+fn is_cover_letter(subject: &str) -> bool {
+    subject
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(prefix, _)| prefix.split_whitespace().last())
+        .and_then(|numbering| numbering.split_once('/'))
+        .is_some_and(|(number, total)| {
+            !number.is_empty()
+                && number.bytes().all(|c| c == b'0')
+                && !total.is_empty()
+                && total.bytes().all(|c| c.is_ascii_digit())
+        })
+}
+
+/// Get the headers of a header block, with continuation lines unfolded.
+fn unfold_headers(headers: &[u8]) -> Vec<(String, String)> {
+    let mut unfolded: Vec<(String, String)> = Vec::new();
+    for line in headers.lines() {
+        let line = line.to_str_lossy();
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = unfolded.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            unfolded.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    unfolded
+}
+
+fn subject(headers: &[u8]) -> String {
+    unfold_headers(headers)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("subject"))
+        .map(|(_, value)| value)
+        .unwrap_or_default()
+}
+
+/// Get the bare address of an address such as "A U Thor <author@example.com>".
+fn bare_address(address: &str) -> &str {
+    address
+        .rsplit_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map_or(address, |(bare, _)| bare)
+        .trim()
+}
+
+/// Get the recipients of each email from the output of `git send-email --dry-run`.
+///
+/// Each email is reported as a block of lines with its headers, preceded by the
+/// envelope recipients, which also include the "Bcc:" recipients. The envelope
+/// recipients follow "-i" on the "Sendmail:" line when sending with a sendmail
+/// program, or are listed on the "RCPT TO:" line when sending with SMTP.
+pub(super) fn parse_dry_run(output: &[u8]) -> Vec<Message> {
+    let mut messages = Vec::new();
+    for block in output.split_str("\n\n") {
+        let mut message = Message::default();
+        let mut has_subject = false;
+        let mut envelope = Vec::new();
+        for (name, value) in unfold_headers(block) {
+            match name.to_ascii_lowercase().as_str() {
+                "subject" => {
+                    has_subject = true;
+                    message.subject = value;
+                }
+                "message-id" => message.message_id = Some(value),
+                "in-reply-to" => message.in_reply_to = Some(value),
+                "to" => message.to.extend(split_addresses(&value)),
+                "cc" => message.cc.extend(split_addresses(&value)),
+                "sendmail" => {
+                    envelope.extend(
+                        value
+                            .split_whitespace()
+                            .skip_while(|word| *word != "-i")
+                            .skip(1)
+                            .map(ToString::to_string),
+                    );
+                }
+                "rcpt to" => {
+                    envelope.extend(
+                        value
+                            .split(">,<")
+                            .map(|address| address.trim_matches(['<', '>']).to_string()),
+                    );
+                }
+                _ => {}
+            }
+        }
+        if !has_subject {
+            continue;
+        }
+        for address in envelope {
+            let is_listed = message
+                .to
+                .iter()
+                .chain(message.cc.iter())
+                .chain(message.bcc.iter())
+                .any(|listed| bare_address(listed).eq_ignore_ascii_case(&address));
+            if !is_listed {
+                message.bcc.push(address);
+            }
+        }
+        messages.push(message);
+    }
+    messages
+}
+
+/// Print a table with the recipients of each email and the email it replies to.
+///
+/// Emails for patches are labeled with the patch name. The emails for the patches are
+/// the last ones, so any preceding email is a cover letter or is labeled with its
+/// subject.
+pub(super) fn print_table(messages: &[Message], patches: Option<&[PatchName]>) {
+    let patches = patches.unwrap_or_default();
+    let num_extra = messages.len().checked_sub(patches.len());
+    let labels: Vec<String> = messages
+        .iter()
+        .enumerate()
+        .map(|(i, message)| {
+            if let Some(patchname) = num_extra
+                .and_then(|num_extra| i.checked_sub(num_extra))
+                .map(|i| &patches[i])
+            {
+                patchname.to_string()
+            } else if is_cover_letter(&message.subject) {
+                "cover letter".to_string()
+            } else {
+                message.subject.clone()
+            }
+        })
+        .collect();
+
+    let rows: Vec<[Vec<String>; 5]> = messages
+        .iter()
+        .zip(labels.iter())
+        .map(|(message, label)| {
+            let reply_to = message.in_reply_to.as_ref().map(|in_reply_to| {
+                messages
+                    .iter()
+                    .position(|other| other.message_id.as_ref() == Some(in_reply_to))
+                    .map_or_else(|| in_reply_to.clone(), |i| labels[i].clone())
+            });
+            [
+                vec![label.clone()],
+                message.to.clone(),
+                message.cc.clone(),
+                message.bcc.clone(),
+                reply_to.into_iter().collect(),
+            ]
+        })
+        .collect();
+
+    let header = ["Email", "To", "Cc", "Bcc", "Reply to"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = cell
+                .iter()
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(1)
+                .max(*width);
+        }
+    }
+
+    print_line(&header.map(ToString::to_string), &widths);
+    for row in &rows {
+        let num_lines = row.iter().map(Vec::len).max().unwrap_or(1).max(1);
+        for i in 0..num_lines {
+            let line = std::array::from_fn(|column| {
+                let cell = &row[column];
+                match cell.get(i) {
+                    Some(value) => value.clone(),
+                    None if i == 0 => "-".to_string(),
+                    None => String::new(),
+                }
+            });
+            print_line(&line, &widths);
+        }
+    }
+}
+
+fn print_line(cells: &[String; 5], widths: &[usize; 5]) {
+    let line = cells
+        .iter()
+        .zip(widths.iter())
+        .map(|(cell, width)| format!("{cell:width$}"))
+        .collect::<Vec<_>>()
+        .join("  ");
+    println!("{}", line.trim_end());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_letter_subjects() {
+        assert!(is_cover_letter("[PATCH 0/3] Series"));
+        assert!(is_cover_letter("[RFC PATCH v2 00/12] Series"));
+        assert!(!is_cover_letter("[PATCH 1/3] Fix"));
+        assert!(!is_cover_letter("[PATCH] Fix"));
+        assert!(!is_cover_letter("Fix [PATCH 0/3]"));
+    }
+
+    #[test]
+    fn parse_dry_run_output() {
+        let output = b"Dry-OK. Log says:\n\
+                       Sendmail: /usr/sbin/sendmail -i list@example.com \
+                       jane@example.com secret@example.com\n\
+                       From: A U Thor <author@example.com>\n\
+                       To: list@example.com\n\
+                       Cc: \"Doe, Jane\" <jane@example.com>\n\
+                       Subject: [PATCH 0/1] Series\n\
+                       Message-ID: <0@example.com>\n\
+                       \n\
+                       Result: OK\n\
+                       Dry-OK. Log says:\n\
+                       Server: localhost\n\
+                       MAIL FROM:<author@example.com>\n\
+                       RCPT TO:<maint@example.com>,<b@example.com>\n\
+                       From: A U Thor <author@example.com>\n\
+                       To: maint@example.com,\n\
+                       \tb@example.com\n\
+                       Subject: [PATCH 1/1] Fix\n\
+                       Message-ID: <1@example.com>\n\
+                       In-Reply-To: <0@example.com>\n\
+                       \n\
+                       Result: OK\n";
+        let messages = parse_dry_run(output);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].to, ["list@example.com"]);
+        assert_eq!(messages[0].cc, ["\"Doe, Jane\" <jane@example.com>"]);
+        assert_eq!(messages[0].bcc, ["secret@example.com"]);
+        assert_eq!(messages[1].to, ["maint@example.com", "b@example.com"]);
+        assert!(messages[1].bcc.is_empty());
+        assert_eq!(messages[1].in_reply_to.as_deref(), Some("<0@example.com>"));
+    }
+}
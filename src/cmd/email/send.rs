@@ -2,18 +2,30 @@
 
 //! `stg email send` implementation.
 
-use std::{path::Path, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::Arg;
+use is_terminal::IsTerminal;
 
+use super::{
+    confirm::Email,
+    deliver::{Delivery, Failure, Threading},
+};
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    output::Output,
+    patch::{PatchName, PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, Stack, StackAccess, StackConfig, StackStateAccess},
     stupid::Stupid,
+    wrap::Message,
 };
 
 pub(super) fn command() -> clap::Command {
@@ -38,7 +50,28 @@ pub(super) fn command() -> clap::Command {
              configuration options. In particular, it is recommended to statically \
              configure SMTP details such as `sendemail.smtpServer`, \
              `sendemail.smtpUser`, etc. Refer to git-config(1) and git-send-email(1) \
-             man pages for more detail on all the available configuration options.",
+             man pages for more detail on all the available configuration options.\n\
+             \n\
+             When patches are specified by name, they are formatted by `git \
+             format-patch` and the `format.*` configuration values are honored as \
+             they are by `stg email format`, e.g. `format.subjectPrefix`, \
+             `format.signOff`, `format.to`, and `format.cc`. The format options given \
+             on the command line, such as '--no-signoff' or '--subject-prefix', take \
+             precedence over the configuration.\n\
+             \n\
+             After sending, the Message-ID of each sent email is printed, labeled with \
+             its patch name. If the `stgit.email.linktemplate` configuration option is \
+             set, e.g. to \"https://lore.kernel.org/r/{msgid}\", a link made by \
+             replacing \"{msgid}\" with the Message-ID is printed instead.\n\
+             \n\
+             With '--confirm=always', each email is previewed before anything is sent \
+             and may be accepted, skipped, or edited, or the whole send aborted. The \
+             emails are only sent once a decision is made for every email. Edits are \
+             made to copies, so email files given on the command line are left \
+             unmodified. Skipped emails leave gaps in the series: the sent emails keep \
+             their original \"[PATCH n/m]\" numbering, and `git send-email` threads \
+             them as replies to the first email that is sent, even when that is not \
+             the cover letter.",
         )
         .override_usage(super::super::make_usage(
             "stg email send",
@@ -70,6 +103,7 @@ pub(super) fn command() -> clap::Command {
                 .required_unless_present_any(["all", "dump-aliases"]),
         )
         .arg(argset::branch_arg())
+        .arg(argset::include_gaps_arg())
         .arg(
             Arg::new("all")
                 .long("all")
@@ -78,6 +112,34 @@ pub(super) fn command() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with_all(["patchranges-or-paths", "dump-aliases"]),
         )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Send patches even if their subject is empty")
+                .long_help(
+                    "Send patches even if their message is entirely empty, which \
+                     would otherwise be refused since their emails would have empty \
+                     subjects. This option is not passed to `git send-email`; use \
+                     `--git-opt=--force` for that.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("dump-aliases"),
+        )
+        .arg(
+            Arg::new("add-link-trailer")
+                .long("add-link-trailer")
+                .help("Add a Link trailer to each sent patch")
+                .long_help(
+                    "After sending, add a \"Link:\" trailer to the message of each \
+                     sent patch, linking to the sent email. The link is made from the \
+                     `stgit.email.linktemplate` configuration option, with \"{msgid}\" \
+                     replaced by the email's Message-ID. For example, \
+                     \"https://lore.kernel.org/r/{msgid}\". The patches must be \
+                     specified by name or with '--all'.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("dump-aliases"),
+        )
         .arg(
             Arg::new("git-send-email-opt")
                 .long("git-opt")
@@ -95,11 +157,61 @@ pub(super) fn command() -> clap::Command {
         )
         .next_help_heading("Compose Options")
         .args(compose_options())
+        .args(cover_options())
         .next_help_heading("Send Options")
+        .args(send_options())
+        .arg(
+            Arg::new("pause")
+                .long("pause")
+                .help("Wait <secs> seconds between sending emails")
+                .long_help(
+                    "Send each email by its own `git send-email` invocation, waiting \
+                     <secs> seconds in between, for servers that throttle senders. The \
+                     emails are threaded by StGit, which writes a Message-ID into each \
+                     email that lacks one.",
+                )
+                .value_name("secs")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64))
+                .conflicts_with_all(["compose", "dump-aliases", "show-recipients"]),
+        )
+        .arg(
+            Arg::new("resume-from")
+                .long("resume-from")
+                .help("Resume sending a series at <patch> or <file>")
+                .long_help(
+                    "Skip the emails preceding the email for <patch>, or the email file \
+                     named <file>, such that a series whose sending failed midway may \
+                     be continued without sending any email twice. When sending fails, \
+                     the emails that were sent are reported along with the options to \
+                     resume with.\n\
+                     \n\
+                     The skipped emails include any cover letter. Use '--in-reply-to' \
+                     to thread the remaining emails with those already sent.",
+                )
+                .value_name("patch|file")
+                .num_args(1)
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .conflicts_with("dump-aliases"),
+        )
         .next_help_heading("Automate Options")
         .args(automate_options())
         .next_help_heading("Administer Options")
         .args(administer_options())
+        .arg(
+            Arg::new("show-recipients")
+                .long("show-recipients")
+                .help("Show the recipients of each email as a table")
+                .long_help(
+                    "With '--dry-run', show a table with the \"To:\", \"Cc:\", and \
+                     \"Bcc:\" recipients of each email and the email it is a reply to, \
+                     instead of the emails themselves. The recipients are those \
+                     determined by `git send-email`, including any addresses it adds \
+                     from the patches' trailers or from '--to-cmd' and '--cc-cmd'.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .requires("dry-run"),
+        )
         .next_help_heading("Format Options")
         .args(format_options())
 }
@@ -247,6 +359,84 @@ fn compose_options() -> Vec<Arg> {
     ]
 }
 
+fn cover_options() -> Vec<Arg> {
+    vec![
+        Arg::new("to-cover")
+            .long("to-cover")
+            .help("Specify a \"To:\" address for the cover letter only")
+            .long_help(
+                "Specify a \"To:\" address that is added to the cover letter only. The \
+                 patches are not sent to this address, such that, for example, the \
+                 cover letter of a large series goes to the mailing list while the \
+                 patches are only sent to the recipients given with '--to' and \
+                 '--cc'.\n\
+                 \n\
+                 Unlike the option of the same name of `git send-email`, which copies \
+                 the cover letter's \"To:\" addresses to the patches, this option takes \
+                 an address. A cover letter is required, e.g. from '--cover-letter' or \
+                 the `format.coverLetter` configuration value.\n\
+                 \n\
+                 This option may be specified multiple times.",
+            )
+            .value_name("address")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .action(clap::ArgAction::Append)
+            .value_hint(clap::ValueHint::EmailAddress),
+        Arg::new("cc-cover")
+            .long("cc-cover")
+            .help("Specify a \"Cc:\" address for the cover letter only")
+            .long_help(
+                "Specify a \"Cc:\" address that is added to the cover letter only. The \
+                 address is added as a \"Cc:\" header of the cover letter, so it is \
+                 subject to '--suppress-cc=cc' when that is configured for `git \
+                 send-email`.\n\
+                 \n\
+                 This option may be specified multiple times.",
+            )
+            .value_name("address")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .action(clap::ArgAction::Append)
+            .value_hint(clap::ValueHint::EmailAddress),
+    ]
+}
+
+fn send_options() -> Vec<Arg> {
+    vec![
+        Arg::new("batch-size")
+            .long("batch-size")
+            .help("Send at most <n> messages per SMTP connection")
+            .long_help(
+                "Some email servers (e.g. smtp.163.com) limit the number of emails to be \
+                 sent per session (connection) and this will lead to a failure when \
+                 sending many messages. With this option, `git send-email` will \
+                 disconnect after sending <n> messages and then reconnect, waiting \
+                 for '--relogin-delay' seconds before reconnecting.\n\
+                 \n\
+                 Default is the value of the sendemail.smtpBatchSize configuration \
+                 value; if that is unspecified, all messages are sent over a single \
+                 connection.",
+            )
+            .value_name("n")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32).range(1..)),
+        Arg::new("relogin-delay")
+            .long("relogin-delay")
+            .help("Wait <secs> seconds before reconnecting to the SMTP server")
+            .long_help(
+                "Wait <secs> seconds before reconnecting to the SMTP server. Used \
+                 together with '--batch-size'.\n\
+                 \n\
+                 Default is the value of the sendemail.smtpReloginDelay configuration \
+                 value.",
+            )
+            .value_name("secs")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32)),
+    ]
+}
+
 fn automate_options() -> Vec<Arg> {
     vec![
         Arg::new("identity")
@@ -282,7 +472,35 @@ fn automate_options() -> Vec<Arg> {
                  Failure to do so may not produce the expected result in the \
                  recipient’s MUA.",
             )
-            .action(clap::ArgAction::SetTrue),
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("thread"),
+        Arg::new("thread")
+            .long("thread")
+            .help("Thread the emails, with shallow or deep threading")
+            .long_help(
+                "Add In-Reply-To and References headers to each email. With \
+                 '--thread=shallow', each email is a reply to the first email, i.e. \
+                 the cover letter or the first patch. With '--thread=deep', each email \
+                 is a reply to the previous email. These are the same as \
+                 '--no-chain-reply-to' and '--chain-reply-to' of `git send-email`, \
+                 respectively. Without a style, the sendemail.chainReplyTo \
+                 configuration value decides, which defaults to shallow threading.",
+            )
+            .value_name("style")
+            .num_args(0..=1)
+            .require_equals(true)
+            .value_parser(["shallow", "deep"]),
+        Arg::new("no-thread-deep")
+            .long("no-thread-deep")
+            .help("Do not chain each email as a reply to the previous email")
+            .long_help(
+                "When threading, make each email a reply to the first email instead \
+                 of to the previous email, overriding a sendemail.chainReplyTo \
+                 configuration value. Unlike '--thread=shallow', threading is not \
+                 enabled when disabled by the sendemail.thread configuration value.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("thread"),
     ]
 }
 
@@ -294,31 +512,26 @@ fn administer_options() -> Vec<Arg> {
             .long_help(
                 "Confirm just before sending.\n\
                  \n\
-                 Default is the value of sendemail.confirm configuration value; if \
-                 that is unspecified, default to auto unless any of the suppress \
-                 options have been specified, in which case default to compose.\n\
+                 The 'always' and 'auto' modes are handled by StGit, which previews \
+                 each email and asks whether to send, skip, or edit it, or to abort \
+                 without sending anything. The other modes are passed on to `git \
+                 send-email`. When this option is not given, the sendemail.confirm \
+                 configuration value is used by `git send-email`.\n\
                  \n\
                  Confirmation modes:\n\
-                 \n  - 'always' will always confirm before sending\
+                 \n  - 'always' will review each email before sending\
+                 \n  - 'auto' will review each email when stdin and stderr are\
+                 \n    terminals, and otherwise send without confirmation\
                  \n  - 'never' will never confirm before sending\
                  \n  - 'cc' will confirm before sending when send-email has\
                  \n    automatically added addresses from the patch to the Cc list\
                  \n  - 'compose' will confirm before sending the first message\
-                 \n    when using --compose\
-                 \n  - 'auto' is equivalent to cc + compose",
+                 \n    when using --compose",
             )
             .hide_possible_values(true)
             .num_args(1)
             .value_name("mode")
             .value_parser(["always", "never", "cc", "compose", "auto"]),
-        Arg::new("quiet")
-            .long("quiet")
-            .help("Output one line of info per email")
-            .long_help(
-                "Make git-send-email less verbose. One line per email should be all \
-                 that is output.",
-            )
-            .action(clap::ArgAction::SetTrue),
         Arg::new("dry-run")
             .long("dry-run")
             .help("Do not actually send the emails")
@@ -332,7 +545,7 @@ fn administer_options() -> Vec<Arg> {
 }
 
 fn format_options() -> Vec<Arg> {
-    vec![
+    let mut options = vec![
         Arg::new("numbered")
             .long("numbered")
             .short('n')
@@ -364,10 +577,65 @@ fn format_options() -> Vec<Arg> {
             .help("Use [<prefix>] instead of [PATCH]")
             .value_name("prefix")
             .num_args(1),
-    ]
+        Arg::new("signoff")
+            .long("signoff")
+            .help("Add a Signed-off-by trailer")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("no-signoff")
+            .long("no-signoff")
+            .help("Do not add a Signed-off-by trailer")
+            .overrides_with("signoff")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("cover-letter")
+            .long("cover-letter")
+            .help("Generate a cover letter")
+            .long_help(
+                "In addition to the patches, generate and send a cover letter. \
+                 Unless edited with '--confirm=always' or '--annotate', the cover \
+                 letter is sent with its template subject and body.",
+            )
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("no-cover-letter")
+            .long("no-cover-letter")
+            .help("Do not generate a cover letter")
+            .overrides_with("cover-letter")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("range-diff")
+            .long("range-diff")
+            .help("Show changes against <refspec> in the patch")
+            .long_help(
+                "When sending a single patch by name, insert a range-diff (see \
+                 git-range-diff(1)) as commentary of the patch, showing the \
+                 differences from the previous version of the patch. <refspec> may \
+                 be a revision naming the tip of the previous version, a revision \
+                 range, or a stack state commit id from the stack log, as with `stg \
+                 email format --range-diff`.\n\
+                 \n\
+                 A range-diff of a series of multiple patches belongs in the cover \
+                 letter, which needs editing before it is sent. Use `stg email format \
+                 --range-diff` to format such a series instead.",
+            )
+            .num_args(1)
+            .value_name("refspec")
+            .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        Arg::new("creation-factor")
+            .long("creation-factor")
+            .help("Percentage by which creation is weighed")
+            .long_help(
+                "Used with '--range-diff', tweak the heuristic which matches up \
+                 commits between the previous and current versions of the patch. See \
+                 git-range-diff(1) for details.",
+            )
+            .num_args(1)
+            .value_name("n")
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .requires("range-diff"),
+    ];
+    options.extend(super::format::attach_options());
+    options
 }
 
-pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+pub(super) fn dispatch(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
 
     if matches.get_flag("dump-aliases") {
@@ -381,15 +649,16 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     )?;
 
     let source_args = matches.get_many::<String>("patchranges-or-paths");
-    let sources = if let Some(patchranges_or_paths) = source_args {
+    let (sources, sent_patches) = if let Some(patchranges_or_paths) = source_args {
         let patchranges_or_paths = patchranges_or_paths.collect::<Vec<_>>();
         if patchranges_or_paths.iter().all(|s| Path::new(s).is_dir())
             || patchranges_or_paths.iter().all(|s| Path::new(s).is_file())
         {
-            patchranges_or_paths
+            let sources = patchranges_or_paths
                 .iter()
                 .map(ToString::to_string)
-                .collect::<Vec<_>>()
+                .collect::<Vec<_>>();
+            (sources, None)
         } else {
             let mut ranges = Vec::new();
             for arg in patchranges_or_paths {
@@ -401,10 +670,11 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 })?;
                 ranges.push(range);
             }
-            let patches = patchrange::resolve_names_contiguous(
+            let patches = argset::resolve_names_contiguous(
                 &stack,
                 &ranges,
                 RangeConstraint::VisibleWithAppliedBoundary,
+                matches,
             )?;
             if patches.is_empty() {
                 return Err(anyhow!("no patches to send"));
@@ -422,7 +692,7 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 .unwrap()
                 .detach();
             let last = stack.get_patch_commit_id(patches.last().unwrap());
-            vec![format!("{base}..{last}")]
+            (vec![format!("{base}..{last}")], Some(patches))
         }
     } else if matches.get_flag("all") {
         let applied = stack.applied();
@@ -437,18 +707,248 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         }
         let base = stack.base().id;
         let last = stack.get_patch_commit_id(applied.last().unwrap());
-        vec![format!("{base}..{last}")]
+        (vec![format!("{base}..{last}")], Some(applied.to_vec()))
     } else {
         panic!("expect either patchranges or -a/--all")
     };
 
-    let mut send_args = Vec::new();
+    if let Some(patches) = sent_patches.as_deref() {
+        super::check_subjects(&stack, patches, matches)?;
+    }
 
-    let mut dummy_command = clap::Command::new("dummy")
-        .args(compose_options())
-        .args(automate_options())
-        .args(administer_options())
-        .args(format_options());
+    let link_template = super::link_template(&stack.config());
+    let add_link_trailer = matches.get_flag("add-link-trailer");
+    if add_link_trailer {
+        if link_template.is_none() {
+            return Err(anyhow!(
+                "`--add-link-trailer` requires `stgit.email.linktemplate` to be set"
+            ));
+        } else if sent_patches.is_none() {
+            return Err(anyhow!(
+                "`--add-link-trailer` requires patches to be specified by name"
+            ));
+        }
+    }
+
+    let range_diff = if let Some(spec) = argset::get_one_str(matches, "range-diff") {
+        match sent_patches.as_deref() {
+            Some([patchname]) => Some(super::resolve_range_diff(
+                &stack,
+                spec,
+                std::slice::from_ref(patchname),
+            )?),
+            Some(_) => {
+                return Err(anyhow!(
+                    "`--range-diff` can only be used when sending a single patch; \
+                     use `stg email format --range-diff` to format a series"
+                ))
+            }
+            None => {
+                return Err(anyhow!(
+                    "`--range-diff` requires patches to be specified by name"
+                ))
+            }
+        }
+    } else {
+        None
+    };
+
+    let aliases = super::aliases::Aliases::load(&stack.config())?;
+
+    // With `--confirm=always` or `--confirm=auto`, the emails are reviewed here instead
+    // of by `git send-email`. Named patches are then formatted up front such that the
+    // review sees the same emails as are sent.
+    let stg_confirm = matches!(
+        argset::get_one_str(matches, "confirm"),
+        Some("always" | "auto")
+    );
+    let review = match argset::get_one_str(matches, "confirm") {
+        Some("always") => true,
+        Some("auto") => std::io::stdin().is_terminal() && std::io::stderr().is_terminal(),
+        _ => false,
+    };
+    let mut to_cover = Vec::new();
+    for value in matches.get_many::<String>("to-cover").into_iter().flatten() {
+        to_cover.extend(aliases.expand(value)?);
+    }
+    let mut cc_cover = Vec::new();
+    for value in matches.get_many::<String>("cc-cover").into_iter().flatten() {
+        cc_cover.extend(aliases.expand(value)?);
+    }
+    let cover_recipients = !to_cover.is_empty() || !cc_cover.is_empty();
+    let show_recipients = matches.get_flag("show-recipients");
+    let pause = matches
+        .get_one::<u64>("pause")
+        .map(|secs| Duration::from_secs(*secs));
+    let quiet = Output::new(matches).is_quiet() && !show_recipients;
+
+    let mut options = compose_options();
+    options.extend(send_options());
+    options.extend(automate_options());
+    options.extend(
+        administer_options()
+            .into_iter()
+            .filter(|arg| !(stg_confirm && arg.get_id() == "confirm")),
+    );
+    // Named patches are formatted up front, such that the emails being sent are known
+    // to the review and when reporting the emails sent before a failure.
+    if sent_patches.is_none() {
+        options.extend(format_options());
+    }
+    let mut send_args = passthrough_args(matches, options, &aliases, range_diff.as_deref())?;
+
+    if stg_confirm {
+        send_args.push("--confirm=never".to_string());
+    }
+
+    // The recipients are read from the emails' headers, which are not shown with
+    // `--quiet`.
+    if quiet {
+        send_args.push("--quiet".to_string());
+    }
+
+    if let Some(values) = matches.get_many::<String>("git-send-email-opt") {
+        send_args.extend(values.cloned());
+    }
+
+    let temp_dir = tempfile::tempdir()?;
+    let emails = if let Some(patches) = sent_patches.as_deref() {
+        let format_args =
+            passthrough_args(matches, format_options(), &aliases, range_diff.as_deref())?;
+        format_emails(&repo, temp_dir.path(), format_args, &sources, patches)?
+    } else if review || cover_recipients || pause.is_some() {
+        copy_emails(temp_dir.path(), &sources)?
+    } else {
+        email_files(&sources)?
+            .into_iter()
+            .map(|path| Email {
+                path,
+                patchname: None,
+            })
+            .collect()
+    };
+    if cover_recipients {
+        super::recipients::add_cover_recipients(&emails, &to_cover, &cc_cover)?;
+    }
+    let emails = if let Some(resume_from) = argset::get_one_str(matches, "resume-from") {
+        skip_to_resume_point(emails, resume_from)?
+    } else {
+        emails
+    };
+    let emails = if review {
+        let total = emails.len();
+        let Some(emails) = super::confirm::review(emails, &stack.config(), matches)? else {
+            return Err(anyhow!("aborted; no emails were sent"));
+        };
+        if emails.is_empty() {
+            crate::print_info_message(matches, "all emails skipped; no emails were sent");
+            return Ok(());
+        } else if emails.len() < total {
+            crate::print_warning_message(
+                matches,
+                &format!(
+                    "skipped {} of {total} emails; the sent emails keep their \
+                     original numbering",
+                    total - emails.len()
+                ),
+            );
+        }
+        emails
+    } else {
+        emails
+    };
+    let sent_patches: Option<Vec<PatchName>> = sent_patches
+        .is_some()
+        .then(|| emails.iter().filter_map(|e| e.patchname.clone()).collect());
+
+    if show_recipients {
+        send_args.extend(
+            emails
+                .iter()
+                .map(|email| email.path.to_string_lossy().to_string()),
+        );
+        let output = repo.stupid().send_email_captured(send_args)?;
+        let messages = super::recipients::parse_dry_run(&output);
+        if messages.is_empty() {
+            return Err(anyhow!(
+                "could not determine the recipients from the `git send-email` output"
+            ));
+        }
+        super::recipients::print_table(&messages, sent_patches.as_deref());
+        return Ok(());
+    }
+
+    let threading = threading(matches, &stack.config());
+    let domain = repo
+        .get_committer()
+        .ok()
+        .and_then(|committer| {
+            committer
+                .email
+                .rsplit_once_str("@")
+                .and_then(|(_, domain)| domain.to_str().ok())
+                .map(ToString::to_string)
+        })
+        .unwrap_or_else(|| "localhost.localdomain".to_string());
+    let delivery = Delivery {
+        send_args: &send_args,
+        pause,
+        threading,
+        domain: &domain,
+        num_composed: usize::from(matches.get_flag("compose")),
+        quiet,
+        dry_run: matches.get_flag("dry-run"),
+    };
+    let output = super::deliver::deliver(&repo, &delivery, &emails)
+        .map_err(|failure| failure_error(failure, &emails, &delivery))?;
+
+    if matches.get_flag("dry-run") {
+        return Ok(());
+    }
+
+    let message_ids = super::parse_message_ids(&output);
+    let pairs = super::pair_message_ids(sent_patches.as_deref(), &message_ids);
+    super::print_links(link_template.as_deref(), &pairs);
+
+    if add_link_trailer {
+        let links: Vec<(PatchName, String)> = pairs
+            .iter()
+            .filter_map(|(patchname, message_id)| {
+                patchname.map(|patchname| {
+                    (
+                        patchname.clone(),
+                        super::make_link(link_template.as_deref(), message_id),
+                    )
+                })
+            })
+            .collect();
+        if links.is_empty() {
+            crate::print_warning_message(
+                matches,
+                "could not determine the Message-IDs of the sent patches; \
+                 no Link trailers added",
+            );
+        } else {
+            add_link_trailers(stack, matches, command_line, &links)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the options from `args` given on the command line, for passing on to git.
+///
+/// The options are returned in the order given on the command line. Recipient aliases
+/// are expanded and the `--range-diff` value is replaced with its resolved range.
+fn passthrough_args(
+    matches: &clap::ArgMatches,
+    args: Vec<Arg>,
+    aliases: &super::aliases::Aliases,
+    range_diff: Option<&str>,
+) -> Result<Vec<String>> {
+    let mut passthrough = Vec::new();
+
+    let mut dummy_command = clap::Command::new("dummy").args(args);
     dummy_command.build();
 
     for arg in dummy_command.get_arguments() {
@@ -459,29 +959,282 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         ) {
             let num_args = arg.get_num_args().expect("built Arg's num_args is Some");
             let long = arg.get_long().expect("passthrough arg has long option");
+            if arg_id == "thread" {
+                // The threading style is passed on as `--[no-]chain-reply-to`. Without
+                // a style, there is no value index, so it is placed last.
+                let index = matches
+                    .indices_of(arg_id)
+                    .and_then(Iterator::last)
+                    .unwrap_or(usize::MAX);
+                passthrough.push((index, format!("--{long}")));
+                match argset::get_one_str(matches, arg_id) {
+                    Some("deep") => passthrough.push((index, "--chain-reply-to".into())),
+                    Some(_) => passthrough.push((index, "--no-chain-reply-to".into())),
+                    None => {}
+                }
+                continue;
+            }
             let indices = matches.indices_of(arg_id).expect("value source is cmdline");
             if num_args.takes_values() {
-                let values = matches.get_many::<String>(arg_id).unwrap();
+                let values = matches.get_raw(arg_id).unwrap();
                 assert!(indices.len() == values.len());
-                indices.into_iter().zip(values).for_each(|(index, value)| {
-                    send_args.push((index, format!("--{long}={value}")));
-                });
+                for (index, value) in indices.into_iter().zip(values) {
+                    let value = value.to_string_lossy();
+                    let value = value.as_ref();
+                    if arg_id == "attach" {
+                        passthrough.push((index, super::format::attach_arg(value).to_string()));
+                    } else if arg_id == "range-diff" {
+                        let range_diff = range_diff.expect("range-diff is resolved");
+                        passthrough.push((index, format!("--{long}={range_diff}")));
+                    } else if matches!(arg_id, "to" | "cc" | "bcc") {
+                        for address in aliases.expand(value)? {
+                            passthrough.push((index, format!("--{long}={address}")));
+                        }
+                    } else {
+                        passthrough.push((index, format!("--{long}={value}")));
+                    }
+                }
+            } else if arg_id == "no-thread-deep" {
+                indices.for_each(|index| passthrough.push((index, "--no-chain-reply-to".into())));
             } else {
-                indices.for_each(|index| send_args.push((index, format!("--{long}"))));
+                indices.for_each(|index| passthrough.push((index, format!("--{long}"))));
             }
         }
     }
 
-    send_args.sort_by_key(|(index, _)| *index);
+    passthrough.sort_by_key(|(index, _)| *index);
+    Ok(passthrough.into_iter().map(|(_, s)| s).collect())
+}
 
-    let mut send_args = send_args.drain(..).map(|(_, s)| s).collect::<Vec<_>>();
+/// Format the patches to be reviewed into email files in `dir`.
+///
+/// `sources` holds the revision range of the patches. Any email preceding the
+/// patches' emails, i.e. a cover letter, is not associated with a patch.
+fn format_emails(
+    repo: &gix::Repository,
+    dir: &Path,
+    format_args: Vec<String>,
+    sources: &[String],
+    patches: &[PatchName],
+) -> Result<Vec<Email>> {
+    let mut args = vec![
+        "--quiet".to_string(),
+        "-o".to_string(),
+        dir.to_string_lossy().to_string(),
+    ];
+    args.extend(format_args);
+    args.extend(sources.iter().cloned());
+    repo.stupid().format_patch(args)?;
 
-    if let Some(values) = matches.get_many::<String>("git-send-email-opt") {
-        send_args.extend(values.cloned());
+    let paths = sorted_files(dir)?;
+    let num_extra = paths.len().saturating_sub(patches.len());
+    Ok(paths
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| Email {
+            path,
+            patchname: i.checked_sub(num_extra).map(|i| patches[i].clone()),
+        })
+        .collect())
+}
+
+/// Copy the email files and directories of email files in `sources` into `dir`.
+///
+/// The emails are reviewed, and possibly edited, from the copies such that the
+/// original files are left untouched.
+fn copy_emails(dir: &Path, sources: &[String]) -> Result<Vec<Email>> {
+    let paths = email_files(sources)?;
+    let mut emails = Vec::with_capacity(paths.len());
+    for (i, path) in paths.into_iter().enumerate() {
+        let copy_dir = dir.join(i.to_string());
+        std::fs::create_dir(&copy_dir)?;
+        let copy_path = copy_dir.join(path.file_name().expect("email file has a name"));
+        std::fs::copy(&path, &copy_path)?;
+        emails.push(Email {
+            path: copy_path,
+            patchname: None,
+        });
+    }
+    Ok(emails)
+}
+
+/// Get the paths of the email files and of the files in the directories in `sources`.
+fn email_files(sources: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for source in sources {
+        let source = Path::new(source);
+        if source.is_dir() {
+            paths.extend(sorted_files(source)?);
+        } else {
+            paths.push(source.to_path_buf());
+        }
+    }
+    Ok(paths)
+}
+
+/// Get the name identifying an email: its patch name, or else its file name.
+fn email_label(email: &Email) -> String {
+    if let Some(patchname) = email.patchname.as_ref() {
+        patchname.to_string()
+    } else {
+        email
+            .path
+            .file_name()
+            .unwrap_or(email.path.as_os_str())
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// Drop the emails preceding the email for the patch or file named by `resume_from`.
+fn skip_to_resume_point(mut emails: Vec<Email>, resume_from: &str) -> Result<Vec<Email>> {
+    let file_name = Path::new(resume_from).file_name();
+    let position = emails
+        .iter()
+        .position(|email| {
+            email
+                .patchname
+                .as_ref()
+                .is_some_and(|patchname| AsRef::<str>::as_ref(patchname) == resume_from)
+        })
+        .or_else(|| {
+            emails
+                .iter()
+                .position(|email| file_name.is_some() && email.path.file_name() == file_name)
+        })
+        .ok_or_else(|| anyhow!("`{resume_from}` is not a patch or email file to be sent"))?;
+    Ok(emails.split_off(position))
+}
+
+/// Determine how `git send-email` threads the emails.
+fn threading(matches: &clap::ArgMatches, config: &StackConfig) -> Threading {
+    let thread_given = matches!(
+        matches.value_source("thread"),
+        Some(clap::parser::ValueSource::CommandLine)
+    );
+    if matches.get_flag("no-thread")
+        || (!thread_given && !config.boolean("sendemail.thread").unwrap_or(true))
+    {
+        Threading::None
+    } else {
+        match argset::get_one_str(matches, "thread") {
+            Some("deep") => Threading::Deep,
+            Some(_) => Threading::Shallow,
+            None if matches.get_flag("no-thread-deep") => Threading::Shallow,
+            None if config.boolean("sendemail.chainreplyto").unwrap_or(false) => Threading::Deep,
+            None => Threading::Shallow,
+        }
+    }
+}
+
+/// Make the error for a failure to send all emails.
+///
+/// The error lists the emails that were sent along with their Message-IDs and the
+/// options with which the remaining emails may be sent.
+fn failure_error(failure: Failure, emails: &[Email], delivery: &Delivery) -> anyhow::Error {
+    let Failure {
+        num_sent,
+        message_ids,
+        error,
+    } = failure;
+    let Some(num_sent) = num_sent else {
+        return anyhow!(
+            "{error:#}\n\
+             the emails sent before the failure are unknown since `--quiet` hides their \
+             Message-IDs"
+        );
+    };
+    if num_sent == 0 {
+        return anyhow!("{error:#}\nno emails were sent");
+    }
+
+    let mut msg = format!("{error:#}\nsent {num_sent} of {} emails:", emails.len());
+    let sent_ids = message_ids.get(delivery.num_composed..).unwrap_or_default();
+    for (i, email) in emails[..num_sent].iter().enumerate() {
+        msg.push_str(&format!("\n  {}", email_label(email)));
+        if let Some(message_id) = sent_ids.get(i) {
+            msg.push_str(&format!(" {message_id}"));
+        }
+    }
+    if let Some(email) = emails.get(num_sent) {
+        msg.push_str(&format!(
+            "\nto send the remaining emails, use `--resume-from={}",
+            email_label(email)
+        ));
+        let reply_to = match delivery.threading {
+            Threading::None => None,
+            Threading::Shallow => message_ids.first(),
+            Threading::Deep => message_ids.last(),
+        };
+        if let Some(reply_to) = reply_to {
+            msg.push_str(&format!(" --in-reply-to={reply_to}"));
+        }
+        msg.push('`');
+    }
+    anyhow!(msg)
+}
+
+/// Get the paths of the files in `dir`, sorted by name as `git send-email` does.
+fn sorted_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
     }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Add a `Link:` trailer to the message of each sent patch.
+///
+/// Only the patch messages are changed. The patches above the lowest changed applied
+/// patch are pushed back with their trees unchanged.
+fn add_link_trailers(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    links: &[(PatchName, String)],
+) -> Result<()> {
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            let to_pop = trans
+                .applied()
+                .iter()
+                .position(|pn| links.iter().any(|(patchname, _)| patchname == pn))
+                .map_or_else(Vec::new, |pos| trans.applied()[pos..].to_vec());
+            if !to_pop.is_empty() {
+                let popped_extra = trans.pop_patches(|pn| to_pop.contains(pn))?;
+                assert!(popped_extra.is_empty());
+            }
 
-    let mut sources = sources;
-    send_args.append(&mut sources);
+            let repo = trans.repo();
+            let committer = repo.get_committer()?;
+            for (patchname, link) in links {
+                let commit = trans.get_patch_commit(patchname);
+                let message = commit.message_ex();
+                let message = repo
+                    .stupid()
+                    .interpret_trailers(message.decode()?.as_bytes(), [("Link", link.as_str())])?;
+                let message = String::from_utf8(message)
+                    .map_err(|_| anyhow!("could not decode message after adding trailers"))?;
+                let parent_id = commit.parent_ids().next().unwrap().detach();
+                let commit_id = repo.commit_ex(
+                    &commit.author_strict()?,
+                    committer,
+                    &Message::from(message),
+                    commit.tree_id()?.detach(),
+                    [parent_id],
+                )?;
+                trans.update_patch(patchname, commit_id)?;
+            }
 
-    repo.stupid().send_email(send_args)
+            trans.push_tree_patches(&to_pop)
+        })
+        .execute("email send: add Link trailers")?;
+    Ok(())
 }
@@ -4,21 +4,20 @@
 
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
     ffi::OsString,
     io::Write,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
-use bstr::BStr;
+use anyhow::{anyhow, Context, Result};
+use bstr::{BStr, ByteSlice};
 use clap::Arg;
 
 use crate::{
     argset,
-    branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint, Subject},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
@@ -26,6 +25,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "export",
     category: super::CommandCategory::StackInspection,
+    read_only: true,
     make,
     run,
 };
@@ -52,8 +52,50 @@ fn make() -> clap::Command {
              \n    %(authemail)s   - author email\
              \n    %(authdate)s    - patch creation date (ISO-8601 format)\
              \n    %(commname)s    - committer name\
-             \n    %(commemail)s   - committer email",
+             \n    %(commemail)s   - committer email\n\
+             \n\
+             Additional header lines may be added to each exported patch with a \
+             header template, given by the '--header-template' option or the \
+             \"stgit.export.headertemplate\" configuration variable. The expanded \
+             header template is inserted before the \"---\" separator line of the \
+             exported patch, or before the diff if the patch template has no such \
+             separator. Besides the variables above, the header template supports:\n\
+             \n    %(patchname)s      - the patch name\
+             \n    %(subject)s        - the first line of the patch description, or a \
+             fallback as for `stg series --description` when that line is empty\
+             \n    %(author)s         - author name and email\
+             \n    %(date)s           - patch creation date (RFC-2822 format)\
+             \n    %(trailer:<token>)s - values of the <token> trailer of the patch \
+             description, separated by \", \"\n\
+             \n\
+             Unknown variables in the header template are an error.\n\
+             \n\
+             With '--format=json', the patches are instead exported as a single \
+             \"stack.json\" file (or to stdout with '--stdout') containing the stack \
+             base commit and, for each patch, its name, message, author, and diff. \
+             Such a file may be imported with 'stg import --format=json'. The \
+             file-naming and template options do not apply to the JSON format.\n\
+             \n\
+             With '--combined', the patches are instead concatenated into a single \
+             mbox file which may be applied with 'git am'. Each patch starts with a \
+             \"From <commit>\" boundary line followed by the expanded template, which \
+             defaults to one producing \"From:\", \"Date:\", and \"Subject:\" mail \
+             headers.\n\
+             \n\
+             With '--split-by-dir', the output directory gets one subdirectory per \
+             top-level path component, each holding the patches touching that path \
+             along with their own series file. A patch touching several top-level \
+             paths is exported to each of their subdirectories, unless '--strict' is \
+             given, in which case such a patch is an error. Patches without any \
+             changes are not exported in this mode.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg export --dir=out p1..p3",
+                "Export `p1` through `p3` to the `out` directory",
+            ),
+            ("stg export --stdout @", "Print the topmost patch"),
+        ]))
         .arg(
             Arg::new("patchranges")
                 .help("Patches to export")
@@ -61,14 +103,17 @@ fn make() -> clap::Command {
                     "Patches to export.\n\
                      \n\
                      A patch name or patch range of the form \
-                     '[begin-patch]..[end-patch]' may be specified.",
+                     '[begin-patch]..[end-patch]' may be specified. Hidden patches \
+                     are exported when named explicitly.",
                 )
                 .value_name("patch")
                 .num_args(1..)
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange)),
         )
+        .args(argset::stdin_patches_args("patchranges"))
         .arg(argset::branch_arg())
+        .arg(argset::remote_stack_arg().conflicts_with("branch"))
         .arg(
             Arg::new("dir")
                 .long("dir")
@@ -110,6 +155,19 @@ fn make() -> clap::Command {
                 .value_hint(clap::ValueHint::FilePath)
                 .value_parser(clap::value_parser!(PathBuf)),
         )
+        .arg(
+            Arg::new("header-template")
+                .long("header-template")
+                .help("Insert header lines expanded from <file>")
+                .long_help(
+                    "Insert header lines expanded from the template <file> before the \
+                     \"---\" separator of each exported patch. This overrides the \
+                     \"stgit.export.headertemplate\" configuration variable.",
+                )
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
         .arg(
             Arg::new("stdout")
                 .long("stdout")
@@ -118,17 +176,89 @@ fn make() -> clap::Command {
                 .conflicts_with("dir")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("combined")
+                .long("combined")
+                .help("Export patches to a single mbox <file>")
+                .long_help(
+                    "Concatenate the exported patches into a single mbox <file> \
+                     suitable for 'git am' instead of exporting them to a directory.",
+                )
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["dir", "stdout", "patch", "extension", "numbered"]),
+        )
+        .arg(
+            Arg::new("split-by-dir")
+                .long("split-by-dir")
+                .help("Export patches to one subdirectory per top-level path")
+                .conflicts_with_all(["stdout", "combined"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail for patches touching several top-level paths")
+                .requires("split-by-dir")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Export patches in <format>")
+                .long_help(
+                    "Export patches in <format>. The default \"patch\" format writes one \
+                     patch file per patch along with a series file. The \"json\" format \
+                     writes the whole series as a single, versioned JSON document.",
+                )
+                .value_name("format")
+                .value_parser(["patch", "json"])
+                .default_value("patch"),
+        )
+        .arg(argset::unified_arg())
         .arg(argset::diff_opts_arg())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+/// Version of the JSON export format.
+///
+/// This must be incremented for any change to the format that older versions of
+/// `stg import` would not be able to load.
+pub(super) const JSON_FORMAT_VERSION: u32 = 1;
+
+/// JSON representation of an exported series of patches.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct JsonExport {
+    pub(super) version: u32,
+    /// Commit id the first patch applies on.
+    pub(super) base: String,
+    pub(super) patches: Vec<JsonPatch>,
+}
+
+/// JSON representation of a single exported patch.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct JsonPatch {
+    pub(super) name: String,
+    pub(super) message: String,
+    pub(super) author: JsonAuthor,
+    pub(super) diff: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct JsonAuthor {
+    pub(super) name: String,
+    pub(super) email: String,
+    /// Author date in git's raw "<seconds> <offset>" format.
+    pub(super) date: String,
+}
+
+fn run(matches: &clap::ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
-    let opt_branch = matches.get_one::<BranchLocator>("branch");
-    let stack =
-        Stack::from_branch_locator(&repo, opt_branch, InitializationPolicy::AllowUninitialized)?;
-    let stupid = repo.stupid();
+    let stack = argset::stack_from_args(&repo, matches, InitializationPolicy::AllowUninitialized)?;
 
-    if opt_branch.is_none()
+    if !matches.contains_id("branch")
+        && !matches.contains_id("remote-stack")
+        && repo.work_dir().is_some()
         && repo
             .stupid()
             .statuses(None)?
@@ -141,11 +271,16 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         );
     }
 
-    let patches = if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
+    let patches = if let Some(range_specs) =
+        argset::get_patch_ranges::<PatchRange>(matches, "patchranges")?
+    {
+        if range_specs.is_empty() {
+            return Ok(());
+        }
         patchrange::resolve_names(
             &stack,
-            range_specs,
-            RangeConstraint::VisibleWithAppliedBoundary,
+            range_specs.iter(),
+            RangeConstraint::AllWithAppliedBoundary,
         )?
     } else {
         stack.applied().to_vec()
@@ -155,6 +290,8 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         return Err(super::Error::NoAppliedPatches.into());
     }
 
+    stack.prefetch_patch_blobs(&patches)?;
+
     let default_output_dir;
     let output_dir = if let Some(dir) = matches.get_one::<PathBuf>("dir").map(PathBuf::as_path) {
         dir
@@ -173,81 +310,345 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         ""
     };
 
+    let format = matches.get_one::<String>("format").map(String::as_str);
+    if format == Some("json") {
+        if matches.contains_id("combined") || matches.get_flag("split-by-dir") {
+            return Err(anyhow!(
+                "`--combined` and `--split-by-dir` do not apply to the JSON format"
+            ));
+        }
+        return export_json(&stack, matches, &patches, output_dir);
+    }
+
     let numbered_flag = matches.get_flag("numbered");
-    let num_width = std::cmp::max(patches.len().to_string().len(), 2);
+    let diff_opts = argset::get_diff_opts(matches, &stack.config(), false, true);
+    let template_file = matches.get_one::<PathBuf>("template").map(PathBuf::as_path);
+    let header_template = get_header_template(&stack, matches)?;
+    let export = |patchname: &PatchName, template: &str| {
+        export_patch(
+            &stack,
+            patchname,
+            template,
+            header_template.as_deref(),
+            &diff_opts,
+        )
+    };
 
-    let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, true);
+    if let Some(combined_path) = matches.get_one::<PathBuf>("combined") {
+        let template = if let Some(template_file) = template_file {
+            Cow::Owned(std::fs::read_to_string(template_file)?)
+        } else {
+            Cow::Borrowed(crate::templates::PATCHEXPORT_MBOX_TMPL)
+        };
+        let mut mbox = Vec::new();
+        for patchname in &patches {
+            writeln!(
+                mbox,
+                "From {} Mon Sep 17 00:00:00 2001",
+                stack.get_patch_commit_id(patchname)
+            )?;
+            mbox.extend_from_slice(&export(patchname, &template)?);
+        }
+        return std::fs::write(combined_path, mbox)
+            .with_context(|| format!("writing {combined_path:?}"));
+    }
 
-    let template = if let Some(template_file) = matches.get_one::<PathBuf>("template") {
-        Cow::Owned(std::fs::read_to_string(template_file)?)
+    let template = get_template(&repo, template_file)?;
+    let patchfile_name = |i: usize, count: usize, patchname: &PatchName| {
+        if numbered_flag {
+            let num_width = std::cmp::max(count.to_string().len(), 2);
+            let patch_number = i + 1;
+            format!("{patch_number:0num_width$}-{patchname}{extension}")
+        } else {
+            format!("{patchname}{extension}")
+        }
+    };
+
+    if matches.get_flag("stdout") {
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        for (i, patchname) in patches.iter().enumerate() {
+            let exported = export(patchname, &template)?;
+            if patches.len() > 1 {
+                let marker = if stack.is_hidden(patchname) { "! " } else { "" };
+                write!(
+                    stdout,
+                    "{0:->79}\n\
+                     {marker}{patchfile_name}\n\
+                     {0:->79}\n",
+                    '-',
+                    patchfile_name = patchfile_name(i, patches.len(), patchname),
+                )?;
+            }
+            stdout.write_all(&exported)?;
+        }
+        return Ok(());
+    }
+
+    let groups = if matches.get_flag("split-by-dir") {
+        group_by_top_level_path(&stack, &patches, matches.get_flag("strict"))?
+            .into_iter()
+            .map(|(component, patches)| (output_dir.join(component), patches))
+            .collect()
     } else {
-        match crate::templates::get_template(&repo, "patchexport.tmpl") {
-            Ok(Some(template)) => Cow::Owned(template),
-            Ok(None) => Cow::Borrowed(crate::templates::PATCHEXPORT_TMPL),
-            Err(e) => return Err(e),
+        vec![(output_dir.to_path_buf(), patches)]
+    };
+
+    for (dir, patches) in groups {
+        let mut series = format!(
+            "# This series applies on Git commit {}\n",
+            stack.base().id()
+        );
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {dir:?}"))?;
+        for (i, patchname) in patches.iter().enumerate() {
+            let patchfile_name = patchfile_name(i, patches.len(), patchname);
+            series.push_str(&patchfile_name);
+            series.push('\n');
+            let mut file = std::fs::File::options()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(dir.join(&patchfile_name))
+                .with_context(|| format!("opening {patchfile_name}"))?;
+            file.write_all(&export(patchname, &template)?)?;
         }
+        let series_path = dir.join("series");
+        std::fs::write(&series_path, series.as_str())
+            .with_context(|| format!("writing {series_path:?}"))?;
+    }
+
+    Ok(())
+}
+
+/// Group patches by the top-level path components of the files they change.
+///
+/// A patch changing files below several top-level paths belongs to each of their
+/// groups, or is an error when `strict`. The patches keep their order within each
+/// group.
+fn group_by_top_level_path(
+    stack: &Stack,
+    patches: &[PatchName],
+    strict: bool,
+) -> Result<BTreeMap<OsString, Vec<PatchName>>> {
+    let stupid = stack.repo.stupid();
+    let mut groups: BTreeMap<OsString, Vec<PatchName>> = BTreeMap::new();
+    for patchname in patches {
+        let patch_commit = stack.get_patch_commit(patchname);
+        let parent_commit = patch_commit.get_parent_commit()?;
+        let files = stupid.diff_tree_files(
+            parent_commit.tree_id()?.detach(),
+            patch_commit.tree_id()?.detach(),
+        )?;
+        let components: BTreeSet<OsString> = files
+            .iter()
+            .filter_map(|path| path.components().next())
+            .map(|component| component.as_os_str().to_os_string())
+            .collect();
+        if strict && components.len() > 1 {
+            let names: Vec<String> = components
+                .iter()
+                .map(|component| format!("`{}`", component.to_string_lossy()))
+                .collect();
+            return Err(anyhow!(
+                "patch `{patchname}` touches several top-level paths: {}",
+                names.join(", ")
+            ));
+        }
+        for component in components {
+            groups.entry(component).or_default().push(patchname.clone());
+        }
+    }
+    Ok(groups)
+}
+
+/// Get the patch export template.
+///
+/// The template is read from `template_file` if provided, otherwise from the
+/// repository's or user's `patchexport.tmpl`, falling back to the built-in template.
+pub(super) fn get_template(
+    repo: &gix::Repository,
+    template_file: Option<&Path>,
+) -> Result<Cow<'static, str>> {
+    if let Some(template_file) = template_file {
+        Ok(Cow::Owned(std::fs::read_to_string(template_file)?))
+    } else {
+        Ok(
+            crate::templates::get_template(repo, "patchexport.tmpl")?.map_or(
+                Cow::Borrowed(crate::templates::PATCHEXPORT_TMPL),
+                Cow::Owned,
+            ),
+        )
+    }
+}
+
+/// Get the header template from `--header-template` or `stgit.export.headertemplate`.
+fn get_header_template(stack: &Stack, matches: &clap::ArgMatches) -> Result<Option<String>> {
+    let config_path;
+    let path = if let Some(path) = matches.get_one::<PathBuf>("header-template") {
+        path.as_path()
+    } else if let Some(path) = stack
+        .config()
+        .trusted_path("stgit.export.headertemplate")
+        .transpose()?
+    {
+        config_path = path;
+        config_path.as_ref()
+    } else {
+        return Ok(None);
     };
+    std::fs::read_to_string(path)
+        .with_context(|| format!("reading header template `{}`", path.display()))
+        .map(Some)
+}
 
-    let need_diffstat = template.contains("%(diffstat)");
+/// Render a patch in the export format using the given template and diff options.
+///
+/// If a `header_template` is provided, it is expanded and inserted before the `---`
+/// separator of the expanded `template`.
+pub(super) fn export_patch(
+    stack: &Stack,
+    patchname: &PatchName,
+    template: &str,
+    header_template: Option<&str>,
+    diff_opts: &[String],
+) -> Result<Vec<u8>> {
+    let stupid = stack.repo.stupid();
+    let need_diffstat = template.contains("%(diffstat)")
+        || header_template.is_some_and(|header| header.contains("%(diffstat)"));
+    let patch_commit = stack.get_patch_commit(patchname);
+    let parent_commit = patch_commit.get_parent_commit()?;
 
-    let stdout_flag = matches.get_flag("stdout");
-    let mut series = format!(
-        "# This series applies on Git commit {}\n",
-        stack.base().id()
+    let mut replacements: HashMap<&str, Cow<'_, BStr>> = HashMap::new();
+    let message = patch_commit.message_ex();
+    let description = message.decode()?;
+    let description = description.as_ref();
+    let (shortdescr, longdescr) = if let Some((shortdescr, rest)) = description.split_once('\n') {
+        let longdescr = rest.trim_start_matches('\n').trim_end();
+        (shortdescr, longdescr)
+    } else {
+        (description, "")
+    };
+    replacements.insert("description", Cow::Borrowed(description.into()));
+    replacements.insert("shortdescr", Cow::Borrowed(shortdescr.into()));
+    replacements.insert("longdescr", Cow::Borrowed(longdescr.into()));
+    let author = patch_commit.author()?;
+    replacements.insert("authname", Cow::Borrowed(author.name));
+    replacements.insert("authemail", Cow::Borrowed(author.email));
+    replacements.insert(
+        "authdate",
+        Cow::Owned(author.time.format(gix::date::time::format::ISO8601).into()),
+    );
+    let committer = patch_commit.committer()?;
+    replacements.insert("commname", Cow::Borrowed(committer.name));
+    replacements.insert("commemail", Cow::Borrowed(committer.email));
+    replacements.insert(
+        "commdate",
+        Cow::Owned(
+            committer
+                .time
+                .format(gix::date::time::format::ISO8601)
+                .into(),
+        ),
     );
 
-    if !stdout_flag {
-        std::fs::create_dir_all(output_dir).with_context(|| format!("creating {output_dir:?}"))?;
+    let diff = stupid.diff_tree_patch(
+        parent_commit.tree_id()?.detach(),
+        patch_commit.tree_id()?.detach(),
+        <Option<Vec<OsString>>>::None,
+        false,
+        diff_opts.iter(),
+    )?;
+
+    if need_diffstat {
+        replacements.insert(
+            "diffstat",
+            if parent_commit.tree_id()? == patch_commit.tree_id()? {
+                Cow::Borrowed("".into())
+            } else {
+                Cow::Owned(stupid.diffstat(diff.as_ref())?)
+            },
+        );
     }
 
-    for (i, patchname) in patches.iter().enumerate() {
-        let patchfile_name = if numbered_flag {
-            let patch_number = i + 1;
-            format!("{patch_number:0num_width$}-{patchname}{extension}")
+    let mut exported = crate::templates::specialize_template(template, &replacements);
+
+    if let Some(header_template) = header_template {
+        let mut header =
+            crate::templates::try_specialize_template(header_template, |name| match name {
+                "patchname" => Some(Cow::Owned(patchname.to_string().into())),
+                "subject" => Some(Cow::Owned(Subject::new(description, patchname).text.into())),
+                "author" => Some(Cow::Owned(
+                    format!("{} <{}>", author.name, author.email).into(),
+                )),
+                "date" => Some(Cow::Owned(
+                    author
+                        .time
+                        .format(gix::date::time::format::GIT_RFC2822)
+                        .into(),
+                )),
+                _ => {
+                    if let Some(token) = name.strip_prefix("trailer:") {
+                        Some(Cow::Owned(trailer_values(description, token).into()))
+                    } else {
+                        replacements.get(name).cloned()
+                    }
+                }
+            })
+            .with_context(|| format!("expanding header template for patch `{patchname}`"))?;
+        if !header.is_empty() && !header.ends_with(b"\n") {
+            header.push(b'\n');
+        }
+        let separator_pos = if exported.starts_with(b"---\n") {
+            Some(0)
         } else {
-            format!("{patchname}{extension}")
+            exported.rfind(b"\n---\n").map(|pos| pos + 1)
         };
+        let insert_pos = separator_pos.unwrap_or(exported.len());
+        exported.splice(insert_pos..insert_pos, header);
+    }
+
+    exported.extend_from_slice(&diff);
+    Ok(exported)
+}
+
+/// Get the values of the trailers with the given token, separated by ", ".
+///
+/// Only the trailers in the last paragraph of the message are considered and tokens
+/// are compared case-insensitively.
+fn trailer_values(message: &str, token: &str) -> String {
+    let last_paragraph = message.trim_end().rsplit("\n\n").next().unwrap_or_default();
+    let values: Vec<&str> = last_paragraph
+        .lines()
+        .filter_map(|line| {
+            let (line_token, value) = line.split_once(':')?;
+            line_token
+                .trim_end()
+                .eq_ignore_ascii_case(token)
+                .then(|| value.trim())
+        })
+        .collect();
+    values.join(", ")
+}
 
-        series.push_str(&patchfile_name);
-        series.push('\n');
+fn export_json(
+    stack: &Stack,
+    matches: &clap::ArgMatches,
+    patches: &[crate::patch::PatchName],
+    output_dir: &Path,
+) -> Result<()> {
+    let stupid = stack.repo.stupid();
+    let diff_opts = argset::get_diff_opts(matches, &stack.config(), false, true);
+    let mut export = JsonExport {
+        version: JSON_FORMAT_VERSION,
+        base: stack.base().id().to_string(),
+        patches: Vec::with_capacity(patches.len()),
+    };
 
+    for patchname in patches {
         let patch_commit = stack.get_patch_commit(patchname);
         let parent_commit = patch_commit.get_parent_commit()?;
-
-        let mut replacements: HashMap<&str, Cow<'_, BStr>> = HashMap::new();
-        let message = patch_commit.message_ex();
-        let description = message.decode()?;
-        let description = description.as_ref();
-        let (shortdescr, longdescr) = if let Some((shortdescr, rest)) = description.split_once('\n')
-        {
-            let longdescr = rest.trim_start_matches('\n').trim_end();
-            (shortdescr, longdescr)
-        } else {
-            (description, "")
-        };
-        replacements.insert("description", Cow::Borrowed(description.into()));
-        replacements.insert("shortdescr", Cow::Borrowed(shortdescr.into()));
-        replacements.insert("longdescr", Cow::Borrowed(longdescr.into()));
+        let message = patch_commit.message_ex().decode()?.to_string();
         let author = patch_commit.author()?;
-        replacements.insert("authname", Cow::Borrowed(author.name));
-        replacements.insert("authemail", Cow::Borrowed(author.email));
-        replacements.insert(
-            "authdate",
-            Cow::Owned(author.time.format(gix::date::time::format::ISO8601).into()),
-        );
-        let committer = patch_commit.committer()?;
-        replacements.insert("commname", Cow::Borrowed(committer.name));
-        replacements.insert("commemail", Cow::Borrowed(committer.email));
-        replacements.insert(
-            "commdate",
-            Cow::Owned(
-                committer
-                    .time
-                    .format(gix::date::time::format::ISO8601)
-                    .into(),
-            ),
-        );
-
         let diff = stupid.diff_tree_patch(
             parent_commit.tree_id()?.detach(),
             patch_commit.tree_id()?.detach(),
@@ -255,50 +656,31 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             false,
             diff_opts.iter(),
         )?;
-
-        if need_diffstat {
-            replacements.insert(
-                "diffstat",
-                if parent_commit.tree_id()? == patch_commit.tree_id()? {
-                    Cow::Borrowed("".into())
-                } else {
-                    Cow::Owned(stupid.diffstat(diff.as_ref())?)
-                },
-            );
-        }
-
-        let specialized = crate::templates::specialize_template(&template, &replacements);
-
-        if stdout_flag {
-            let stdout = std::io::stdout();
-            let mut stdout = stdout.lock();
-            if patches.len() > 1 {
-                write!(
-                    stdout,
-                    "{0:->79}\n\
-                     {patchfile_name}\n\
-                     {0:->79}\n",
-                    '-'
-                )?;
-            }
-            stdout.write_all(&specialized)?;
-            stdout.write_all(&diff)?;
-        } else {
-            let mut file = std::fs::File::options()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(output_dir.join(&patchfile_name))
-                .with_context(|| format!("opening {patchfile_name}"))?;
-            file.write_all(&specialized)?;
-            file.write_all(&diff)?;
-        }
+        let diff = String::from_utf8(diff.into())
+            .map_err(|_| anyhow!("diff of patch `{patchname}` is not valid UTF-8"))?;
+        export.patches.push(JsonPatch {
+            name: patchname.to_string(),
+            message,
+            author: JsonAuthor {
+                name: author.name.to_string(),
+                email: author.email.to_string(),
+                date: author.time.format(gix::date::time::format::RAW),
+            },
+            diff,
+        });
     }
 
-    if !stdout_flag {
-        let series_path = output_dir.join("series");
-        std::fs::write(&series_path, series.as_str())
-            .with_context(|| format!("writing {series_path:?}"))?;
+    if matches.get_flag("stdout") {
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        serde_json::to_writer_pretty(&mut stdout, &export)?;
+        stdout.write_all(b"\n")?;
+    } else {
+        std::fs::create_dir_all(output_dir).with_context(|| format!("creating {output_dir:?}"))?;
+        let json_path = output_dir.join("stack.json");
+        let mut json = serde_json::to_vec_pretty(&export)?;
+        json.push(b'\n');
+        std::fs::write(&json_path, json).with_context(|| format!("writing {json_path:?}"))?;
     }
 
     Ok(())
@@ -9,15 +9,18 @@ use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
 
 use crate::{
-    ext::{CommitExtended, RepositoryExtended},
+    argset,
+    ext::RepositoryExtended,
     patch::SingleRevisionSpec,
-    stack::Stack,
+    stack::{Stack, StackStateAccess},
     stupid::Stupid,
+    wrap::PartialRefName,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "files",
     category: super::CommandCategory::PatchInspection,
+    read_only: true,
     make,
     run,
 };
@@ -55,23 +58,104 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with("stat"),
         )
+        .arg(
+            Arg::new("null")
+                .short('z')
+                .help("Terminate status and file names with NUL")
+                .long_help(
+                    "Terminate status and file names with NUL instead of separating \
+                     them with a space and terminating lines with newline. Each file \
+                     is output as the status, NUL, the path, and NUL. For renamed or \
+                     copied files, the source path and its NUL terminator precede \
+                     the destination path. Paths are output verbatim, without any \
+                     quoting. With '--bare', only the NUL-terminated paths are output.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("stat"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Output files as a JSON array")
+                .long_help(
+                    "Output files as a JSON array of objects, each with \"status\" \
+                     and \"path\" members. The status is the single letter status as \
+                     shown by default (e.g. \"M\"). Renamed or copied files also \
+                     have a \"src_path\" member and a status that includes the \
+                     similarity score (e.g. \"R100\"). Paths that are not valid UTF-8 \
+                     are converted lossily.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["stat", "bare", "null"]),
+        )
+        .arg(argset::remote_stack_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
+    let remote_stack = matches
+        .get_one::<PartialRefName>("remote-stack")
+        .map(|remote_branch| Stack::from_remote_branch(&repo, remote_branch))
+        .transpose()?;
     let commit = if let Some(commit) = matches
         .get_one::<SingleRevisionSpec>("stgit-revision")
-        .map(|spec| spec.resolve(&repo, None::<&Stack>).map(|rev| rev.commit))
+        .map(|spec| {
+            spec.resolve(&repo, remote_stack.as_ref())
+                .map(|rev| rev.commit)
+        })
         .transpose()?
     {
         commit
+    } else if let Some(remote_stack) = remote_stack.as_ref() {
+        remote_stack.top().clone()
     } else {
-        Rc::new(repo.head_commit()?)
+        Rc::new(repo.head_commit_ex()?)
     };
-    let parent = commit.get_parent_commit()?;
+    let (bottom_tree_id, top_tree_id) = crate::stack::commit_trees(&commit)?;
+
+    if matches.get_flag("null") || matches.get_flag("json") {
+        let statuses = repo
+            .stupid()
+            .diff_tree_name_status(bottom_tree_id, top_tree_id)?;
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        if matches.get_flag("json") {
+            let files: Vec<serde_json::Value> = statuses
+                .iter()
+                .map(|entry| {
+                    let mut file = serde_json::json!({
+                        "status": entry.status,
+                        "path": gix::path::into_bstr(entry.path).to_str_lossy(),
+                    });
+                    if let Some(src_path) = entry.src_path {
+                        file["src_path"] = gix::path::into_bstr(src_path).to_str_lossy().into();
+                    }
+                    file
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut stdout, &files)?;
+            writeln!(stdout)?;
+        } else {
+            let bare = matches.get_flag("bare");
+            for entry in statuses.iter() {
+                if !bare {
+                    stdout.write_all(entry.status.as_bytes())?;
+                    stdout.write_all(b"\0")?;
+                    if let Some(src_path) = entry.src_path {
+                        stdout.write_all(&gix::path::into_bstr(src_path))?;
+                        stdout.write_all(b"\0")?;
+                    }
+                }
+                stdout.write_all(&gix::path::into_bstr(entry.path))?;
+                stdout.write_all(b"\0")?;
+            }
+        }
+        return Ok(());
+    }
+
     let mut output = repo.stupid().diff_tree_files_status(
-        parent.tree_id()?.detach(),
-        commit.tree_id()?.detach(),
+        bottom_tree_id,
+        top_tree_id,
         matches.get_flag("stat"),
         matches.get_flag("bare"),
         crate::color::use_color(matches),
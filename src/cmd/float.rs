@@ -10,18 +10,20 @@ use std::{
 use anyhow::{anyhow, Context, Result};
 use clap::{Arg, ArgMatches};
 
+use super::sink::{place_patches, resolve_target, Placement};
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::RepositoryExtended,
-    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    output::Output,
+    patch::{patchrange, PatchLocator, PatchName, PatchRange, RangeConstraint},
+    stack::{history, message, InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "float",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -36,11 +38,56 @@ fn make() -> clap::Command {
              to be floated may currently be either applied or unapplied. The necessary \
              pop and push operations will be performed to float the named patches. \
              Patches not specified will remain applied or unapplied as they were prior \
-             to the float operation.",
+             to the float operation.\n\
+             \n\
+             With '--reorder-from', the whole stack is instead reordered to match the \
+             order of patch names in a file, such as one written by `stg series \
+             --save-order`. Patches missing from the file keep their relative order \
+             after the listed patches. The number of applied patches is unchanged.\n\
+             \n\
+             With '--restore', the whole stack layout recorded in a file written by \
+             `stg series --save` is restored: the patches are reordered, pushed or \
+             popped, and hidden or unhidden as marked in the file. Patches missing \
+             from the file are reported and kept at the end of their applied, \
+             unapplied, or hidden group.\n\
+             \n\
+             With '--above' or '--below', the patches are instead moved immediately \
+             above or below a target patch, keeping their order. If the target patch \
+             is unapplied, the unapplied patches up to the target are pushed as well. \
+             With '--noapply', the patches are moved without being applied, popping \
+             any applied patches above the target.\n\
+             \n\
+             With '--sort', the patches are floated in order of creation or by name \
+             instead of in the order given. Without any patches, the applied patches \
+             are sorted, or the unapplied patches with '--noapply'.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg float '%{net}'",
+                "Float the patches labeled `net` to the top, keeping their order",
+            ),
+            (
+                "stg float a..e '!c'",
+                "Float the patches `a` through `e`, except for `c`",
+            ),
+            (
+                "stg float --above '{base}+' fix",
+                "Move `fix` just above the bottommost patch",
+            ),
+            (
+                "stg float --sort=created --noapply",
+                "Put the unapplied patches back in the order they were created",
+            ),
+        ]))
         .override_usage(super::make_usage(
             "stg float",
-            &["[OPTIONS] <patch>...", "[OPTIONS] <-S|--series> <file>"],
+            &[
+                "[OPTIONS] <patch>...",
+                "[OPTIONS] --sort <key> [<patch>...]",
+                "[OPTIONS] <-S|--series> <file>",
+                "[OPTIONS] --reorder-from <file>",
+                "[OPTIONS] --restore <file>",
+            ],
         ))
         .arg(
             Arg::new("patchranges")
@@ -49,8 +96,18 @@ fn make() -> clap::Command {
                 .num_args(1..)
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange))
-                .conflicts_with("series")
-                .required_unless_present("series"),
+                .conflicts_with_all(["series", "reorder-from", "restore"])
+                .required_unless_present_any([
+                    "series",
+                    "reorder-from",
+                    "restore",
+                    "stdin",
+                    "sort",
+                ]),
+        )
+        .args(
+            argset::stdin_patches_args("patchranges")
+                .map(|arg| arg.conflicts_with_all(["series", "reorder-from", "restore"])),
         )
         .arg(
             Arg::new("noapply")
@@ -58,20 +115,110 @@ fn make() -> clap::Command {
                 .help("Reorder patches without reapplying any patches")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("target-above")
+                .long("above")
+                .short('T')
+                .help("Float patches above <target> patch")
+                .long_help(
+                    "Float patches above <target> patch.\n\
+                     \n\
+                     Specified patches are placed above <target> instead of at the \
+                     top of the stack.",
+                )
+                .value_name("target")
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all(["reorder-from", "restore"]),
+        )
+        .arg(
+            Arg::new("target-below")
+                .long("below")
+                .short('t')
+                .help("Float patches below <target> patch")
+                .long_help(
+                    "Float patches below <target> patch.\n\
+                     \n\
+                     Specified patches are placed below <target> instead of at the \
+                     top of the stack.",
+                )
+                .value_name("target")
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all(["target-above", "reorder-from", "restore"]),
+        )
         .arg(
             Arg::new("series")
                 .long("series")
                 .short('S')
                 .help("Rearrange according to a series <file>")
+                .long_help(
+                    "Float the patches listed in a series <file>, in the order they are \
+                     listed. Each line of the file holds a patch name or patch range, \
+                     or '!<patch>' to leave out a patch selected by a preceding line. \
+                     Blank lines are ignored and '#' starts a comment. The whole file \
+                     is checked against the stack before any patch is moved. Use '-' \
+                     to read the series from stdin.",
+                )
                 .value_name("file")
                 .value_hint(clap::ValueHint::FilePath)
                 .value_parser(clap::value_parser!(PathBuf)),
         )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .help("Float the patches sorted by <key>")
+                .long_help(
+                    "Float the patches sorted by <key> instead of in the order given. \
+                     With 'created', patches are sorted by when their name first \
+                     appears in the stack log, i.e. by when they were created or last \
+                     renamed. With 'name', patches are sorted by name. Patches with \
+                     equal keys keep their current relative order. The order before \
+                     and after sorting is reported.\n\
+                     \n\
+                     Without any patches, all applied patches are sorted, or all \
+                     unapplied patches with '--noapply'.",
+                )
+                .value_name("key")
+                .value_parser(["created", "name"])
+                .conflicts_with_all(["reorder-from", "restore"]),
+        )
+        .arg(
+            Arg::new("reorder-from")
+                .long("reorder-from")
+                .help("Reorder the stack to match the patch order in <file>")
+                .long_help(
+                    "Reorder the stack to match the patch order in <file>. The file \
+                     lists one patch name per line, and '#' starts a comment. Use '-' \
+                     to read the order from stdin.",
+                )
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["series", "noapply"]),
+        )
+        .arg(
+            Arg::new("restore")
+                .long("restore")
+                .help("Restore the stack layout saved in <file>")
+                .long_help(
+                    "Restore the stack layout saved in <file> by `stg series --save`. \
+                     Each line of the file has a state marker, '+' or '>' for applied, \
+                     '-' for unapplied, or '!' for hidden, followed by a patch name. \
+                     '#' starts a comment. Use '-' to read the layout from stdin.",
+                )
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["series", "reorder-from", "noapply"]),
+        )
+        .arg(argset::dry_run_arg())
         .arg(argset::keep_arg())
+        .arg(argset::force_overwrite_untracked_arg())
         .arg(argset::committer_date_is_author_date_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
@@ -79,103 +226,394 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let noapply_flag = matches.get_flag("noapply");
     let keep_flag = matches.get_flag("keep");
     let opt_series = matches.get_one::<PathBuf>("series").map(PathBuf::as_path);
+    let opt_reorder_from = matches
+        .get_one::<PathBuf>("reorder-from")
+        .map(PathBuf::as_path);
+    let opt_restore = matches.get_one::<PathBuf>("restore").map(PathBuf::as_path);
 
     repo.check_repository_state()?;
     let statuses = stupid.statuses(None)?;
+    stack.check_conflicted_patch()?;
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
 
-    let patches: Vec<PatchName> = if let Some(series_path) = opt_series {
-        parse_series(series_path, &stack)?
-    } else {
-        let range_specs = matches
-            .get_many::<PatchRange>("patchranges")
-            .expect("clap ensures either patches or series");
-        patchrange::resolve_names(&stack, range_specs, RangeConstraint::Visible)?
-    };
-
-    if patches.is_empty() {
-        return Err(anyhow!("no patches to float"));
-    }
+    let opt_target = resolve_target(&stack, matches, "float")?;
 
-    if !keep_flag && (!noapply_flag || patches.iter().any(|pn| stack.is_applied(pn))) {
-        statuses.check_index_and_worktree_clean()?;
+    if let Some(layout_path) = opt_restore {
+        let layout = parse_layout(layout_path, &stack)?;
+        if !layout.missing.is_empty() {
+            let missing: Vec<&str> = layout.missing.iter().map(AsRef::as_ref).collect();
+            crate::print_warning_message(
+                matches,
+                &format!(
+                    "patches missing from the layout are kept at the end: {}",
+                    missing.join(", ")
+                ),
+            );
+        }
+        if !keep_flag {
+            statuses.check_index_and_worktree_clean()?;
+        }
+        stack
+            .setup_transaction()
+            .command_line(command_line)
+            .dry_run(matches.get_flag("dry-run"))
+            .print_delta(true)
+            .use_index_and_worktree(true)
+            .keep_local_changes(keep_flag)
+            .force_overwrite_untracked(matches.get_flag("force-overwrite-untracked"))
+            .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+            .with_output(Output::new(matches))
+            .transact(|trans| {
+                trans.reorder_patches(
+                    Some(&layout.applied),
+                    Some(&layout.unapplied),
+                    Some(&layout.hidden),
+                )
+            })
+            .execute("float --restore")?;
+        return Ok(());
     }
 
-    let (applied, unapplied) = if noapply_flag {
-        let applied: Vec<PatchName> = stack
-            .applied()
-            .iter()
-            .filter(|pn| !patches.contains(pn))
-            .cloned()
-            .collect();
-        let unapplied: Vec<PatchName> = patches
+    let (applied, unapplied, log_msg) = if let Some(order_path) = opt_reorder_from {
+        let order = parse_order(order_path, &stack)?;
+        if !keep_flag {
+            statuses.check_index_and_worktree_clean()?;
+        }
+        let mut applied: Vec<PatchName> = order
             .iter()
-            .chain(stack.unapplied().iter().filter(|pn| !patches.contains(pn)))
+            .chain(
+                stack
+                    .applied()
+                    .iter()
+                    .chain(stack.unapplied())
+                    .filter(|pn| !order.contains(pn)),
+            )
             .cloned()
             .collect();
-        (applied, unapplied)
+        let unapplied = applied.split_off(stack.applied().len());
+        let log_msg = message::with_patches("float", &order);
+        (applied, unapplied, log_msg)
     } else {
-        let applied: Vec<PatchName> = stack
-            .applied()
-            .iter()
-            .filter(|pn| !patches.contains(pn))
-            .chain(patches.iter())
-            .cloned()
-            .collect();
-        let unapplied: Vec<PatchName> = stack
-            .unapplied()
-            .iter()
-            .filter(|pn| !patches.contains(pn))
-            .cloned()
-            .collect();
-        (applied, unapplied)
+        let mut patches: Vec<PatchName> = if let Some(series_path) = opt_series {
+            parse_series(series_path, &stack)?
+        } else if let Some(range_specs) =
+            argset::get_patch_ranges::<PatchRange>(matches, "patchranges")?
+        {
+            if range_specs.is_empty() {
+                return Ok(());
+            }
+            patchrange::resolve_names(&stack, range_specs.iter(), RangeConstraint::Visible)?
+        } else if noapply_flag {
+            stack.unapplied().to_vec()
+        } else {
+            stack.applied().to_vec()
+        };
+
+        if let Some(key) = matches.get_one::<String>("sort") {
+            let before = patches.clone();
+            sort_patches(&stack, &mut patches, key)?;
+            let join = |patches: &[PatchName]| {
+                patches
+                    .iter()
+                    .map(AsRef::as_ref)
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            };
+            crate::print_info_message(matches, &format!("order before: {}", join(&before)));
+            crate::print_info_message(matches, &format!("order after: {}", join(&patches)));
+        }
+
+        if patches.is_empty() {
+            return Err(anyhow!("no patches to float"));
+        }
+        let log_msg = message::with_patches("float", &patches);
+
+        if !keep_flag
+            && (!noapply_flag
+                || opt_target.is_some()
+                || patches.iter().any(|pn| stack.is_applied(pn)))
+        {
+            statuses.check_index_and_worktree_clean()?;
+        }
+
+        if opt_target.is_some() {
+            let placement = if noapply_flag {
+                Placement::NoApply
+            } else {
+                Placement::Apply { push_back: true }
+            };
+            let (applied, unapplied) =
+                place_patches(&stack, &patches, opt_target.as_ref(), placement, "float")?;
+            (applied, unapplied, log_msg)
+        } else if noapply_flag {
+            let applied: Vec<PatchName> = stack
+                .applied()
+                .iter()
+                .filter(|pn| !patches.contains(pn))
+                .cloned()
+                .collect();
+            let unapplied: Vec<PatchName> = patches
+                .iter()
+                .chain(stack.unapplied().iter().filter(|pn| !patches.contains(pn)))
+                .cloned()
+                .collect();
+            (applied, unapplied, log_msg)
+        } else {
+            let applied: Vec<PatchName> = stack
+                .applied()
+                .iter()
+                .filter(|pn| !patches.contains(pn))
+                .chain(patches.iter())
+                .cloned()
+                .collect();
+            let unapplied: Vec<PatchName> = stack
+                .unapplied()
+                .iter()
+                .filter(|pn| !patches.contains(pn))
+                .cloned()
+                .collect();
+            (applied, unapplied, log_msg)
+        }
     };
 
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .dry_run(matches.get_flag("dry-run"))
+        .print_delta(true)
         .use_index_and_worktree(true)
+        .keep_local_changes(keep_flag)
+        .force_overwrite_untracked(matches.get_flag("force-overwrite-untracked"))
         .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| trans.reorder_patches(Some(&applied), Some(&unapplied), None))
-        .execute("float")?;
+        .execute(&log_msg)?;
 
     Ok(())
 }
 
-fn parse_series(path: &Path, stack: &Stack) -> Result<Vec<PatchName>> {
-    let use_stdin = path == Path::new("-");
-    let contents: String = if use_stdin {
-        use std::io::Read;
-        let mut stdin = std::io::stdin();
-        let mut contents = String::new();
-        stdin.read_to_string(&mut contents)?;
-        contents
+/// Stably sort patches by creation order or by name, as selected by `key`.
+fn sort_patches(stack: &Stack, patches: &mut Vec<PatchName>, key: &str) -> Result<()> {
+    if key == "name" {
+        patches.sort();
     } else {
-        std::fs::read_to_string(path)?
-    };
+        let depths = history::first_appearances(stack, patches)?;
+        let mut keyed: Vec<(usize, PatchName)> =
+            depths.into_iter().zip(patches.drain(..)).collect();
+        keyed.sort_by_key(|(depth, _)| std::cmp::Reverse(*depth));
+        patches.extend(keyed.into_iter().map(|(_, pn)| pn));
+    }
+    Ok(())
+}
+
+/// Series file read by `--series`, `--reorder-from`, or `--layout`.
+struct SeriesFile {
+    /// Name of the file for use in error messages.
+    source: String,
+    contents: String,
+}
 
-    let mut series: Vec<PatchRange> = Vec::new();
-    for s in contents
-        .lines()
-        .map(|line| {
-            if let Some((content, _comment)) = line.split_once('#') {
+impl SeriesFile {
+    /// Read a series file, or stdin if the path is `-`.
+    fn read(path: &Path) -> Result<Self> {
+        if path == Path::new("-") {
+            use std::io::Read;
+            let mut stdin = std::io::stdin();
+            let mut contents = String::new();
+            stdin.read_to_string(&mut contents)?;
+            Ok(Self {
+                source: "<stdin>".to_string(),
+                contents,
+            })
+        } else {
+            Ok(Self {
+                source: path.to_string_lossy().to_string(),
+                contents: std::fs::read_to_string(path)?,
+            })
+        }
+    }
+
+    /// Iterate the series file's lines along with their one-based line numbers.
+    ///
+    /// Any '#' comment is removed from each line, as is leading and trailing
+    /// whitespace. Lines that are then empty are skipped.
+    fn lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.contents.lines().enumerate().filter_map(|(i, line)| {
+            let s = if let Some((content, _comment)) = line.split_once('#') {
                 content
             } else {
                 line
             }
-            .trim()
+            .trim();
+            (!s.is_empty()).then_some((i + 1, s))
         })
-        .filter(|s| !s.is_empty())
-    {
-        series.push(PatchRange::from_str(s)?);
     }
+}
 
-    patchrange::resolve_names(stack, series.iter(), RangeConstraint::Visible).with_context(|| {
-        if use_stdin {
-            "<stdin>".to_string()
-        } else {
-            path.to_string_lossy().to_string()
+/// Read the patches to float from a series file.
+///
+/// Each line holds a patch range, which may select several patches, or an exclusion,
+/// i.e. `!<patch>`, which removes a patch selected by an earlier line. Every line is
+/// validated against the stack before any patches are floated: unparsable ranges,
+/// unknown or hidden patches, and patches listed more than once are all reported
+/// together, each with the line it is on.
+fn parse_series(path: &Path, stack: &Stack) -> Result<Vec<PatchName>> {
+    let file = SeriesFile::read(path)?;
+    let source = &file.source;
+
+    let mut series: Vec<PatchName> = Vec::new();
+    let mut line_nums: Vec<usize> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    for (line_num, s) in file.lines() {
+        let range = match PatchRange::from_str(s) {
+            Ok(range) => range,
+            Err(e) => {
+                errors.push(format!("{source}:{line_num}: {e}"));
+                continue;
+            }
+        };
+        if let PatchRange::Exclude(patch_loc) = &range {
+            match patch_loc.resolve_name(stack) {
+                Ok(patchname) => {
+                    if let Some(pos) = series.iter().position(|pn| pn == &patchname) {
+                        series.remove(pos);
+                        line_nums.remove(pos);
+                    } else {
+                        errors.push(format!(
+                            "{source}:{line_num}: excluded patch `{patchname}` is not listed on \
+                             a preceding line"
+                        ));
+                    }
+                }
+                Err(e) => errors.push(format!("{source}:{line_num}: {e}")),
+            }
+            continue;
         }
-    })
+        match patchrange::resolve_names(stack, [&range], RangeConstraint::Visible) {
+            Ok(patchnames) => {
+                for patchname in patchnames {
+                    if let Some(pos) = series.iter().position(|pn| pn == &patchname) {
+                        errors.push(format!(
+                            "{source}:{line_num}: patch `{patchname}` is already listed on \
+                             line {}",
+                            line_nums[pos],
+                        ));
+                    } else {
+                        series.push(patchname);
+                        line_nums.push(line_num);
+                    }
+                }
+            }
+            Err(e) => errors.push(format!("{source}:{line_num}: {e}")),
+        }
+    }
+
+    match errors.len() {
+        0 => Ok(series),
+        1 => Err(anyhow!("{}", errors[0])),
+        n => Err(anyhow!(
+            "{n} errors in series `{source}`:\n  {}",
+            errors.join("\n  ")
+        )),
+    }
+}
+
+/// Read patch names from an order file.
+///
+/// Unknown, hidden, and repeated patch names are errors.
+fn parse_order(path: &Path, stack: &Stack) -> Result<Vec<PatchName>> {
+    let file = SeriesFile::read(path)?;
+    let source = &file.source;
+
+    let mut order: Vec<PatchName> = Vec::new();
+    for (line_num, s) in file.lines() {
+        let patchname = PatchName::from_str(s).with_context(|| format!("{source}:{line_num}"))?;
+        if !stack.has_patch(&patchname) {
+            return Err(anyhow!(
+                "{source}:{line_num}: patch `{patchname}` does not exist"
+            ));
+        } else if stack.is_hidden(&patchname) {
+            return Err(anyhow!(
+                "{source}:{line_num}: patch `{patchname}` is hidden"
+            ));
+        } else if order.contains(&patchname) {
+            return Err(anyhow!(
+                "{source}:{line_num}: patch `{patchname}` is listed more than once"
+            ));
+        }
+        order.push(patchname);
+    }
+    Ok(order)
+}
+
+/// Stack layout read from a layout file by [`parse_layout()`].
+struct Layout {
+    applied: Vec<PatchName>,
+    unapplied: Vec<PatchName>,
+    hidden: Vec<PatchName>,
+
+    /// Patches of the stack not listed in the layout file.
+    ///
+    /// These are also included, at the end of their current group, in the applied,
+    /// unapplied, or hidden patches of the layout.
+    missing: Vec<PatchName>,
+}
+
+/// Read a stack layout file, as written by `stg series --save`.
+///
+/// Unknown and repeated patch names and invalid state markers are errors.
+fn parse_layout(path: &Path, stack: &Stack) -> Result<Layout> {
+    let file = SeriesFile::read(path)?;
+    let source = &file.source;
+
+    let mut layout = Layout {
+        applied: Vec::new(),
+        unapplied: Vec::new(),
+        hidden: Vec::new(),
+        missing: Vec::new(),
+    };
+    let mut listed: Vec<PatchName> = Vec::new();
+    for (line_num, s) in file.lines() {
+        let (marker, name) = s.split_at(s.chars().next().map_or(0, char::len_utf8));
+        let group = match marker {
+            "+" | ">" => &mut layout.applied,
+            "-" => &mut layout.unapplied,
+            "!" => &mut layout.hidden,
+            _ => {
+                return Err(anyhow!(
+                    "{source}:{line_num}: invalid patch state marker `{marker}`; \
+                     expected `+`, `>`, `-`, or `!`"
+                ))
+            }
+        };
+        let patchname =
+            PatchName::from_str(name.trim()).with_context(|| format!("{source}:{line_num}"))?;
+        if !stack.has_patch(&patchname) {
+            return Err(anyhow!(
+                "{source}:{line_num}: patch `{patchname}` does not exist"
+            ));
+        } else if listed.contains(&patchname) {
+            return Err(anyhow!(
+                "{source}:{line_num}: patch `{patchname}` is listed more than once"
+            ));
+        }
+        listed.push(patchname.clone());
+        group.push(patchname);
+    }
+
+    for (group, patchnames) in [
+        (&mut layout.applied, stack.applied()),
+        (&mut layout.unapplied, stack.unapplied()),
+        (&mut layout.hidden, stack.hidden()),
+    ] {
+        for patchname in patchnames {
+            if !listed.contains(patchname) {
+                group.push(patchname.clone());
+                layout.missing.push(patchname.clone());
+            }
+        }
+    }
+
+    Ok(layout)
 }
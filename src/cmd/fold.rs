@@ -13,12 +13,13 @@ use crate::{
     patch::SingleRevisionSpec,
     print_info_message,
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
-    stupid::Stupid,
+    stupid::{ApplyOptions, ApplyReport, MergeOptions, Stupid},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "fold",
     category: super::CommandCategory::PatchManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -57,6 +58,7 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(SingleRevisionSpec)),
         )
         .group(ArgGroup::new("merge-style").args(["three-way", "base"]))
+        .arg(crate::argset::marker_size_arg().requires("merge-style"))
         .arg(
             Arg::new("strip")
                 .long("strip")
@@ -72,15 +74,19 @@ fn make() -> clap::Command {
                 .value_name("n")
                 .value_parser(crate::argset::parse_usize),
         )
+        .arg(crate::argset::fuzz_arg())
+        .arg(crate::argset::ignore_whitespace_arg())
+        .arg(crate::argset::whitespace_arg())
         .arg(
             Arg::new("reject")
                 .long("reject")
                 .help("Leave rejected hunks in \".rej\" files")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(crate::argset::skip_applied_arg().conflicts_with("merge-style"))
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
@@ -104,9 +110,15 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     }
     .into();
 
-    let reject_flag = matches.get_flag("reject");
-    let strip_level = matches.get_one::<usize>("strip").copied();
-    let context_lines = matches.get_one::<usize>("context-lines").copied();
+    let mut apply_options = ApplyOptions::default();
+    apply_options
+        .reject(matches.get_flag("reject"))
+        .strip_level(matches.get_one::<usize>("strip").copied())
+        .context_lines(matches.get_one::<usize>("context-lines").copied())
+        .fuzz(matches.get_one::<usize>("fuzz").copied())
+        .ignore_whitespace(matches.get_flag("ignore-whitespace"))
+        .whitespace(crate::argset::get_one_str(matches, "whitespace"))
+        .skip_applied(matches.get_flag("skip-applied"));
 
     let stupid = repo.stupid();
 
@@ -125,29 +137,39 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         let orig_head_tree_id = stack.get_branch_head().tree_id()?.detach();
         let base_tree_id = base_commit.tree_id()?.detach();
         stupid.read_tree_checkout(orig_head_tree_id, base_tree_id)?;
-        let applied_cleanly = match stupid.apply_to_worktree_and_index(
-            diff.as_ref(),
-            reject_flag,
-            false,
-            strip_level,
-            None,
-            context_lines,
-        ) {
-            Ok(None) => true,
-            Ok(Some(output)) => {
-                // If patch applied with conflicts print output of "git apply".
-                print_info_message(matches, &output);
-                false
-            }
-            Err(e) => {
-                // Reset the work tree on failure.
-                stupid.read_tree_checkout_hard(orig_head_tree_id)?;
-                return Err(e);
-            }
-        };
+        let applied_cleanly =
+            match stupid.apply_to_worktree_and_index(diff.as_ref(), &apply_options) {
+                Ok(report) => {
+                    print_apply_report(matches, &report);
+                    if let Some(output) = report.rejects {
+                        // If patch applied with conflicts print output of "git apply".
+                        print_info_message(matches, &output);
+                        false
+                    } else {
+                        true
+                    }
+                }
+                Err(e) => {
+                    // Reset the work tree on failure.
+                    stupid.read_tree_checkout_hard(orig_head_tree_id)?;
+                    return Err(e);
+                }
+            };
         let applied_tree_id = stupid.write_tree()?;
         stupid.read_tree_checkout(applied_tree_id, orig_head_tree_id)?;
-        if !stupid.merge_recursive(base_tree_id, orig_head_tree_id, applied_tree_id)? {
+        let ancestor_label = format!("ancestor {}", base_commit.id.to_hex_with_len(7));
+        let mut merge_options = MergeOptions::default();
+        merge_options
+            .ancestor_label(&ancestor_label)
+            .our_label("current top")
+            .their_label("folded diff")
+            .marker_size(matches.get_one::<usize>("marker-size").copied());
+        if !stupid.merge_recursive(
+            base_tree_id,
+            orig_head_tree_id,
+            applied_tree_id,
+            &merge_options,
+        )? {
             return Err(super::Error::CausedConflicts("merge conflicts".to_string()).into());
         }
         if !applied_cleanly {
@@ -155,20 +177,43 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         }
         Ok(())
     } else {
-        match stupid.apply_to_worktree_and_index(
-            diff.as_ref(),
-            reject_flag,
-            false,
-            strip_level,
-            None,
-            context_lines,
-        ) {
-            Ok(None) => Ok(()),
-            Ok(Some(output)) => {
-                print_info_message(matches, &output);
-                Err(super::Error::CausedConflicts("patch conflicts".to_string()).into())
-            }
-            Err(e) => Err(e),
+        let report = stupid.apply_to_worktree_and_index(diff.as_ref(), &apply_options)?;
+        print_apply_report(matches, &report);
+        if let Some(output) = report.rejects {
+            print_info_message(matches, &output);
+            Err(super::Error::CausedConflicts("patch conflicts".to_string()).into())
+        } else {
+            Ok(())
         }
     }
 }
+
+pub(super) fn print_apply_report(matches: &clap::ArgMatches, report: &ApplyReport) {
+    if !report.skipped_hunks.is_empty() {
+        print_info_message(
+            matches,
+            &format!(
+                "skipped already applied hunks:\n  {}",
+                report.skipped_hunks.join("\n  ")
+            ),
+        );
+    }
+    if !report.whitespace.is_empty() {
+        crate::print_warning_message(
+            matches,
+            &format!(
+                "diff has whitespace errors:\n  {}",
+                report.whitespace.join("\n  ")
+            ),
+        );
+    }
+    if !report.adjusted_hunks.is_empty() {
+        print_info_message(
+            matches,
+            &format!(
+                "diff applied with adjustments:\n  {}",
+                report.adjusted_hunks.join("\n  ")
+            ),
+        );
+    }
+}
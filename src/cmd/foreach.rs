@@ -0,0 +1,501 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg foreach` implementation.
+
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgMatches};
+
+use super::refresh;
+use crate::{
+    ext::{CommitExtended, RepositoryExtended},
+    output::Output,
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    print_warning_message,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess, TransactionError},
+    stupid::{program::git_program, Stupid},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "foreach",
+    category: super::CommandCategory::StackManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Run a command on each patch and refresh it")
+        .long_about(
+            "Run a shell command with each of the given patches in turn as the \
+             topmost applied patch, and refresh each patch with the changes the \
+             command makes to the worktree. The applied patches are used by default. \
+             The patches are visited in stack order and the original topmost patch \
+             is restored once all patches are done.\n\
+             \n\
+             The command is run by the shell from the root of the worktree. As with \
+             'stg refresh', changes to tracked files, including deleted files, are \
+             refreshed into the patch while untracked files are ignored; new files \
+             must be added to the index by the command to be included. With \
+             '--update', only the files already modified by each patch are \
+             refreshed. A patch whose tree does not change is left untouched.\n\
+             \n\
+             If the command exits with a non-zero status, pushing a patch results in \
+             conflicts, or changes remain in the worktree after refreshing, the \
+             stack is left at the patch in question. Once the problem is fixed, \
+             'stg foreach --continue' refreshes that patch with the worktree changes, \
+             without running the command again, and carries on with the remaining \
+             patches. 'stg foreach --quit' forgets about the remaining patches and \
+             leaves the stack as it is.",
+        )
+        .override_usage(super::make_usage(
+            "stg foreach",
+            &[
+                "[OPTIONS] --exec <command> [patch]...",
+                "--continue",
+                "--quit",
+            ],
+        ))
+        .arg(
+            Arg::new("patchranges")
+                .help("Patches to run the command on")
+                .value_name("patch")
+                .num_args(1..)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchRange)),
+        )
+        .arg(
+            Arg::new("exec")
+                .long("exec")
+                .short('x')
+                .help("Shell command to run on each patch")
+                .value_name("command")
+                .allow_hyphen_values(true)
+                .required_unless_present_any(["continue", "quit"]),
+        )
+        .arg(
+            Arg::new("update")
+                .long("update")
+                .short('u')
+                .help("Only refresh the files already modified by each patch")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .help("Refresh the stopped patch and continue with the remaining patches")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["patchranges", "exec", "update", "quit"]),
+        )
+        .arg(
+            Arg::new("quit")
+                .long("quit")
+                .help("Forget about the remaining patches of a stopped foreach")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["patchranges", "exec", "update"]),
+        )
+}
+
+/// Record of a foreach that stopped before visiting all of its patches.
+///
+/// The record is stored in the git dir so that `stg foreach --continue` may pick up
+/// where the foreach stopped.
+struct ForeachInProgress {
+    /// Name of the branch of the stack being visited.
+    branch_name: String,
+
+    /// Shell command run on each patch.
+    command: String,
+
+    /// Whether only the files already modified by each patch are refreshed.
+    update: bool,
+
+    /// The topmost applied patch from before the foreach, which is restored at the end.
+    original_top: Option<PatchName>,
+
+    /// The patch the stack is stopped at, which is to be refreshed on continue.
+    stopped: Option<PatchName>,
+
+    /// Patches the command has yet to be run on.
+    remaining: Vec<PatchName>,
+}
+
+impl ForeachInProgress {
+    fn path(repo: &gix::Repository) -> PathBuf {
+        repo.git_dir().join("stgit-foreach")
+    }
+
+    /// Read the record of the foreach in progress, if any.
+    fn read(repo: &gix::Repository) -> Result<Option<Self>> {
+        let path = Self::path(repo);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        let bad_record = || anyhow!("invalid foreach record `{}`", path.display());
+        let mut branch_name = None;
+        let mut command = None;
+        let mut update = false;
+        let mut original_top = None;
+        let mut stopped = None;
+        let mut remaining = Vec::new();
+        for line in contents.lines() {
+            let (key, value) = line.split_once(' ').ok_or_else(bad_record)?;
+            match key {
+                "branch" => branch_name = Some(value.to_string()),
+                "exec" => {
+                    command = Some(serde_json::from_str::<String>(value).map_err(|_| bad_record())?)
+                }
+                "update" => update = value == "true",
+                "top" => original_top = Some(PatchName::from_str(value)?),
+                "stopped" => stopped = Some(PatchName::from_str(value)?),
+                "patch" => remaining.push(PatchName::from_str(value)?),
+                _ => return Err(bad_record()),
+            }
+        }
+        Ok(Some(Self {
+            branch_name: branch_name.ok_or_else(bad_record)?,
+            command: command.ok_or_else(bad_record)?,
+            update,
+            original_top,
+            stopped,
+            remaining,
+        }))
+    }
+
+    fn write(&self, repo: &gix::Repository) -> Result<()> {
+        let mut contents = format!(
+            "branch {}\nexec {}\nupdate {}\n",
+            self.branch_name,
+            serde_json::to_string(&self.command)?,
+            self.update,
+        );
+        if let Some(original_top) = self.original_top.as_ref() {
+            contents.push_str(&format!("top {original_top}\n"));
+        }
+        if let Some(stopped) = self.stopped.as_ref() {
+            contents.push_str(&format!("stopped {stopped}\n"));
+        }
+        for patchname in &self.remaining {
+            contents.push_str(&format!("patch {patchname}\n"));
+        }
+        std::fs::write(Self::path(repo), contents)?;
+        Ok(())
+    }
+
+    fn remove(repo: &gix::Repository) -> Result<()> {
+        let path = Self::path(repo);
+        if path.is_file() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Instructions for resuming a foreach that stopped at a patch.
+const CONTINUE_HINT: &str = "\
+    fix the problem and run `stg foreach --continue` to refresh the patch and continue \
+    with the remaining patches\n\
+    or run `stg foreach --quit` to stop here";
+
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
+    let branch_name = stack.get_branch_name().to_string();
+
+    let record = ForeachInProgress::read(&repo)?;
+
+    if matches.get_flag("quit") {
+        return if record.is_some() {
+            ForeachInProgress::remove(&repo)
+        } else {
+            Err(anyhow!("no foreach in progress"))
+        };
+    }
+
+    let stupid = repo.stupid();
+    repo.check_repository_state()?;
+    stack.check_head_top_mismatch()?;
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+
+    if matches.get_flag("continue") {
+        let mut record = match record {
+            Some(record) if record.branch_name == branch_name => record,
+            Some(record) => {
+                return Err(anyhow!(
+                    "the foreach in progress is for branch `{}`, not `{branch_name}`",
+                    record.branch_name
+                ))
+            }
+            None => return Err(anyhow!("no foreach in progress")),
+        };
+
+        let stack = if let Some(stopped) = record.stopped.take() {
+            if stack.applied().last() != Some(&stopped) {
+                return Err(anyhow!(
+                    "the foreach stopped at patch `{}`, which is no longer the topmost \
+                     applied patch",
+                    stopped.quoted()
+                ));
+            }
+            refresh_stopped(stack, matches, command_line, &mut record, stopped)?
+        } else {
+            statuses.check_index_and_worktree_clean()?;
+            stack
+        };
+        return visit_patches(&repo, stack, matches, command_line, record);
+    } else if let Some(record) = record {
+        return Err(anyhow!(
+            "a foreach is already in progress on branch `{}`; \
+             use `stg foreach --continue` or `stg foreach --quit`",
+            record.branch_name
+        ));
+    }
+
+    statuses.check_index_and_worktree_clean()?;
+
+    let remaining: Vec<PatchName> =
+        if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
+            patchrange::resolve_names(&stack, range_specs, RangeConstraint::Visible)?
+        } else {
+            stack.applied().to_vec()
+        };
+    if remaining.is_empty() {
+        return Err(super::Error::NoAppliedPatches.into());
+    }
+
+    // Visit the patches in stack order regardless of the order they were given.
+    let remaining = stack
+        .all_patches()
+        .filter(|pn| remaining.contains(pn))
+        .cloned()
+        .collect();
+
+    let record = ForeachInProgress {
+        branch_name,
+        command: matches
+            .get_one::<String>("exec")
+            .expect("required unless continuing")
+            .clone(),
+        update: matches.get_flag("update"),
+        original_top: stack.applied().last().cloned(),
+        stopped: None,
+        remaining,
+    };
+
+    visit_patches(&repo, stack, matches, command_line, record)
+}
+
+/// Run the command on each remaining patch, then restore the original topmost patch.
+fn visit_patches<'repo>(
+    repo: &'repo gix::Repository,
+    mut stack: Stack<'repo>,
+    matches: &ArgMatches,
+    command_line: &[String],
+    mut record: ForeachInProgress,
+) -> Result<()> {
+    let work_dir = repo.work_dir().expect("not a bare repo");
+
+    while let Some(patchname) = record.remaining.first().cloned() {
+        if !stack.has_patch(&patchname) || stack.is_hidden(&patchname) {
+            print_warning_message(
+                matches,
+                &format!(
+                    "skipping patch `{}`, which is no longer in the stack",
+                    patchname.quoted()
+                ),
+            );
+            record.remaining.remove(0);
+            continue;
+        }
+
+        if let Err(e) = goto_patch(stack, matches, command_line, &patchname) {
+            record.write(repo)?;
+            return Err(with_continue_hint(e));
+        }
+        record.remaining.remove(0);
+
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg(&record.command).current_dir(work_dir);
+        git_program().setup_child_env(&mut command);
+        let status = command
+            .status()
+            .with_context(|| format!("could not execute `{}`", record.command))?;
+
+        // The command may have changed the stack, e.g. by running stg.
+        stack = Stack::current(repo, InitializationPolicy::AllowUninitialized)?;
+
+        if stack.applied().last() != Some(&patchname) {
+            record.write(repo)?;
+            return Err(anyhow!(
+                "`{}` changed the topmost patch away from `{}`\n{CONTINUE_HINT}",
+                record.command,
+                patchname.quoted(),
+            ));
+        } else if !status.success() {
+            record.stopped = Some(patchname.clone());
+            record.write(repo)?;
+            let reason = if let Some(code) = status.code() {
+                format!("exited with code {code}")
+            } else {
+                "failed".to_string()
+            };
+            return Err(anyhow!(
+                "`{}` {reason} on patch `{}`\n{CONTINUE_HINT}",
+                record.command,
+                patchname.quoted(),
+            ));
+        }
+
+        stack = refresh_stopped(stack, matches, command_line, &mut record, patchname)?;
+    }
+
+    ForeachInProgress::remove(repo)?;
+
+    match record.original_top {
+        Some(original_top) if stack.has_patch(&original_top) && !stack.is_hidden(&original_top) => {
+            goto_patch(stack, matches, command_line, &original_top)?;
+        }
+        Some(_) => {}
+        None => {
+            stack
+                .setup_transaction()
+                .command_line(command_line)
+                .use_index_and_worktree(true)
+                .with_output(Output::new(matches))
+                .transact(|trans| {
+                    let popped_extra = trans.pop_patches(|_| true)?;
+                    assert!(popped_extra.is_empty());
+                    Ok(())
+                })
+                .execute("foreach (pop)")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Refresh the topmost patch with the changes from the worktree.
+///
+/// If the refresh fails or leaves changes behind in the worktree, the record is written
+/// such that the refresh may be retried with `--continue`.
+fn refresh_stopped<'repo>(
+    stack: Stack<'repo>,
+    matches: &ArgMatches,
+    command_line: &[String],
+    record: &mut ForeachInProgress,
+    patchname: PatchName,
+) -> Result<Stack<'repo>> {
+    let repo = stack.repo;
+    let result =
+        refresh_top(stack, matches, command_line, &patchname, record.update).and_then(|stack| {
+            repo.stupid()
+                .statuses(None)?
+                .check_index_and_worktree_clean()
+                .with_context(|| {
+                    format!(
+                        "changes remain after refreshing patch `{}`",
+                        patchname.quoted()
+                    )
+                })?;
+            Ok(stack)
+        });
+    if result.is_err() {
+        record.stopped = Some(patchname);
+        record.write(repo)?;
+    }
+    result.map_err(with_continue_hint)
+}
+
+/// Refresh the topmost patch with the changes to tracked files in the worktree.
+///
+/// The patch is left untouched if its tree would not change.
+fn refresh_top<'repo>(
+    stack: Stack<'repo>,
+    matches: &ArgMatches,
+    command_line: &[String],
+    patchname: &PatchName,
+    update: bool,
+) -> Result<Stack<'repo>> {
+    let repo = stack.repo;
+    let stupid = repo.stupid();
+    let statuses = stupid.statuses(None)?;
+    let patch_commit = stack.get_patch_commit(patchname);
+    let refresh_paths =
+        refresh::determine_refresh_paths(&stupid, &statuses, update.then_some(patch_commit), true)?;
+    if refresh_paths.is_empty() {
+        return Ok(stack);
+    }
+
+    let tree_id = refresh::write_tree(&stack, &refresh_paths, update)?;
+    if tree_id == patch_commit.tree_id()?.detach() {
+        return Ok(stack);
+    }
+
+    let commit_id = repo.commit_ex(
+        &patch_commit.author_strict()?,
+        repo.get_committer()?,
+        &patch_commit.message_ex(),
+        tree_id,
+        [stack.get_patch_commit(patchname).get_parent_commit()?.id],
+    )?;
+
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .with_output(Output::new(matches))
+        .transact(|trans| trans.update_patch(patchname, commit_id))
+        .execute(&format!("foreach {patchname}"))
+}
+
+/// Make the given patch the topmost applied patch.
+fn goto_patch<'repo>(
+    stack: Stack<'repo>,
+    matches: &ArgMatches,
+    command_line: &[String],
+    patchname: &PatchName,
+) -> Result<Stack<'repo>> {
+    if stack.applied().last() == Some(patchname) {
+        return Ok(stack);
+    }
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .use_index_and_worktree(true)
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            if let Some(pos) = trans.applied().iter().position(|pn| pn == patchname) {
+                let applied = trans.applied()[0..=pos].to_vec();
+                let mut unapplied = trans.applied()[pos + 1..].to_vec();
+                unapplied.extend(trans.unapplied().iter().cloned());
+                trans.reorder_patches(Some(&applied), Some(&unapplied), None)
+            } else {
+                let pos = trans
+                    .unapplied()
+                    .iter()
+                    .position(|pn| pn == patchname)
+                    .expect("patch is known to exist and not be hidden or applied");
+                let to_apply: Vec<PatchName> = trans.unapplied()[0..=pos].to_vec();
+                trans.push_patches(&to_apply, false)
+            }
+        })
+        .execute(&format!("foreach (goto {patchname})"))
+}
+
+/// Append the continue instructions to a transaction halt.
+fn with_continue_hint(err: anyhow::Error) -> anyhow::Error {
+    match err.downcast::<TransactionError>() {
+        Ok(TransactionError::TransactionHalt { msg, conflicts }) => {
+            TransactionError::TransactionHalt {
+                msg: format!("{msg}\n{CONTINUE_HINT}"),
+                conflicts,
+            }
+            .into()
+        }
+        Ok(e) => e.into(),
+        Err(e) => anyhow!("{e:#}\n{CONTINUE_HINT}"),
+    }
+}
@@ -2,21 +2,25 @@
 
 //! `stg goto` implementation.
 
-use anyhow::Result;
-use clap::{Arg, ArgMatches};
+use std::{path::PathBuf, time::Instant};
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgGroup, ArgMatches, ValueHint};
 
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::RepositoryExtended,
+    output::Output,
     patch::{LocationConstraint, PatchLocator, PatchName},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    pathspec,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "goto",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -24,50 +28,156 @@ pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
 fn make() -> clap::Command {
     clap::Command::new(STGIT_COMMAND.name)
         .about("Go to patch by pushing or popping as necessary")
+        .long_about(
+            "Go to patch by pushing or popping as necessary.\n\
+             \n\
+             Instead of naming the patch, '--path' may be used to go to the applied or \
+             unapplied patch that modifies the given paths. If more than one patch \
+             modifies the paths, the candidate patches are listed and either '--first' \
+             or '--last' must be used to pick the bottommost or topmost candidate.",
+        )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg goto refac",
+                "Go to the one patch whose name begins with `refac`",
+            ),
+            ("stg goto ~2", "Pop the two topmost applied patches"),
+            (
+                "stg goto ':/fix typo'",
+                "Go to the patch whose message contains `fix typo`",
+            ),
+        ]))
+        .override_usage(super::make_usage(
+            "stg goto",
+            &[
+                "[OPTIONS] <patch>",
+                "[OPTIONS] --path <path>... [--first | --last]",
+            ],
+        ))
+        .arg(argset::dry_run_arg())
         .arg(argset::keep_arg())
+        .arg(argset::force_overwrite_untracked_arg())
         .arg(argset::merged_arg())
         .arg(argset::committer_date_is_author_date_arg())
         .arg(argset::push_conflicts_arg())
+        .arg(argset::push_verbose_arg())
+        .arg(argset::restore_mtime_arg())
         .arg(
             Arg::new("patch")
                 .help("Patch to go to")
-                .required(true)
+                .required_unless_present("path")
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchLocator)),
         )
+        .arg(
+            Arg::new("path")
+                .long("path")
+                .help("Go to the patch modifying <path>")
+                .long_help(
+                    "Go to the patch modifying <path>. This option may be repeated to \
+                     find the patch modifying any of several paths.",
+                )
+                .value_name("path")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::AnyPath)
+                .conflicts_with("patch"),
+        )
+        .arg(
+            Arg::new("first")
+                .long("first")
+                .help("Go to the bottommost patch modifying the paths")
+                .action(clap::ArgAction::SetTrue)
+                .requires("path"),
+        )
+        .arg(
+            Arg::new("last")
+                .long("last")
+                .help("Go to the topmost patch modifying the paths")
+                .action(clap::ArgAction::SetTrue)
+                .requires("path"),
+        )
+        .group(
+            ArgGroup::new("pick")
+                .args(["first", "last"])
+                .conflicts_with("patch"),
+        )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
 
     let keep_flag = matches.get_flag("keep");
     let merged_flag = matches.get_flag("merged");
-    let allow_push_conflicts =
-        argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches);
+    let allow_push_conflicts = argset::resolve_allow_push_conflicts(&stack.config(), matches);
+    let restore_mtime = argset::resolve_restore_mtime(&stack.config(), matches);
     let committer_date_is_author_date = matches.get_flag("committer-date-is-author-date");
 
     repo.check_repository_state()?;
+    let status_start = Instant::now();
     let statuses = stupid.statuses(None)?;
+    let status_check = status_start.elapsed();
+    stack.check_conflicted_patch()?;
+    stack.check_pending_push()?;
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
     if !keep_flag {
         statuses.check_index_and_worktree_clean()?;
     }
 
-    let patchname = matches
-        .get_one::<PatchLocator>("patch")
-        .expect("required argument")
-        .resolve_name(&stack)?
-        .constrain(&stack, LocationConstraint::Visible)?;
+    let patchname = if let Some(pathspecs) = matches.get_many::<PathBuf>("path") {
+        let pathspecs = pathspec::normalize(&repo, pathspecs)?;
+        let patch_ids: Vec<gix::ObjectId> = stack
+            .applied_and_unapplied()
+            .map(|pn| stack.get_patch_commit_id(pn))
+            .collect();
+        let revs = stupid.rev_list_tips(stack.base().id, patch_ids, pathspecs)?;
+        let candidates: Vec<&PatchName> = stack
+            .applied_and_unapplied()
+            .filter(|pn| revs.contains(&stack.get_patch_commit_id(pn)))
+            .collect();
+        match candidates.as_slice() {
+            [] => return Err(anyhow!("no patches modify the given paths")),
+            [patchname] => (*patchname).clone(),
+            [first, .., last] => {
+                if matches.get_flag("first") {
+                    (*first).clone()
+                } else if matches.get_flag("last") {
+                    (*last).clone()
+                } else {
+                    let candidates: Vec<String> =
+                        candidates.iter().map(|pn| format!("  {pn}")).collect();
+                    return Err(anyhow!(
+                        "multiple patches modify the given paths:\n\
+                         {}\n\
+                         use `--first`, `--last`, or specify the patch to go to",
+                        candidates.join("\n")
+                    ));
+                }
+            }
+        }
+    } else {
+        matches
+            .get_one::<PatchLocator>("patch")
+            .expect("required argument")
+            .resolve_name_allowed(&stack, LocationConstraint::Visible)?
+            .constrain(&stack, LocationConstraint::Visible)?
+    };
 
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .dry_run(matches.get_flag("dry-run"))
         .use_index_and_worktree(true)
+        .keep_local_changes(keep_flag)
+        .force_overwrite_untracked(matches.get_flag("force-overwrite-untracked"))
         .allow_push_conflicts(allow_push_conflicts)
+        .restore_mtime(restore_mtime)
         .committer_date_is_author_date(committer_date_is_author_date)
-        .with_output_stream(get_color_stdout(matches))
+        .status_check_time(status_check)
+        .with_output(Output::new(matches))
         .transact(|trans| {
             if let Some(pos) = trans.applied().iter().position(|pn| pn == &patchname) {
                 let applied = trans.applied()[0..=pos].to_vec();
@@ -86,7 +196,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 Ok(())
             }
         })
-        .execute("goto")?;
+        .execute(&format!("goto {patchname}"))?;
 
     Ok(())
 }
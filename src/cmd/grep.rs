@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg grep` implementation.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::Result;
+use bstr::{BStr, BString, ByteSlice};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    pathspec,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "grep",
+    category: super::CommandCategory::StackInspection,
+    read_only: true,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Search the lines added by patches")
+        .long_about(
+            "Search the lines added by patches for a pattern. Unlike 'git grep', \
+             which searches whole files, only the lines introduced by the patches' \
+             diffs are searched. This answers which patch introduced a given string.\n\
+             \n\
+             The applied patches are searched by default. Each matching line is \
+             reported as \"<patch>:<path>:<line>\".\n\
+             \n\
+             The pattern is a basic regular expression, as with 'git grep', unless \
+             '--fixed-strings' is used.",
+        )
+        .override_usage(super::make_usage(
+            "stg grep",
+            &["[OPTIONS] <pattern> [patch]... [-- <path>...]"],
+        ))
+        .arg(
+            Arg::new("pattern")
+                .help("Pattern to search for")
+                .required(true)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("patchranges")
+                .help("Patches to search")
+                .value_name("patch")
+                .num_args(1..)
+                .allow_negative_numbers(true)
+                .value_parser(clap::value_parser!(PatchRange)),
+        )
+        .arg(
+            Arg::new("pathspecs")
+                .help("Limit the search to files matching path")
+                .value_name("path")
+                .last(true)
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("ignore-case")
+                .long("ignore-case")
+                .short('i')
+                .help("Ignore case differences between the pattern and lines")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fixed-strings")
+                .long("fixed-strings")
+                .short('F')
+                .help("Interpret the pattern as a fixed string")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("name-only")
+                .long("name-only")
+                .help("Only show the names of patches with matching lines")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+    let stupid = repo.stupid();
+
+    let patchnames: Vec<PatchName> =
+        if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
+            patchrange::resolve_names(&stack, range_specs, RangeConstraint::All)?
+        } else {
+            stack.applied().to_vec()
+        };
+
+    let pathspecs = matches
+        .get_many::<PathBuf>("pathspecs")
+        .map(|pathspecs| pathspec::normalize(&repo, pathspecs))
+        .transpose()?;
+
+    // All added lines are searched with a single `git grep`. Each line's patch and path
+    // are kept alongside so that matching line indexes can be mapped back to them.
+    let mut lines: Vec<u8> = Vec::new();
+    let mut origins: Vec<(&PatchName, BString)> = Vec::new();
+
+    for patchname in &patchnames {
+        let patch_commit = stack.get_patch_commit(patchname);
+        let parent_commit = patch_commit.get_parent_commit()?;
+        let diff = stupid.diff_tree_patch(
+            parent_commit.tree_id()?.detach(),
+            patch_commit.tree_id()?.detach(),
+            pathspecs.as_ref(),
+            false,
+            std::iter::empty::<&str>(),
+        )?;
+        for (path, line) in added_lines(diff.as_bstr()) {
+            lines.extend_from_slice(line);
+            lines.push(b'\n');
+            origins.push((patchname, path));
+        }
+    }
+
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let matching = stupid.grep_lines(
+        &lines,
+        matches
+            .get_one::<String>("pattern")
+            .expect("required argument"),
+        matches.get_flag("ignore-case"),
+        matches.get_flag("fixed-strings"),
+    )?;
+
+    let quote_names = stack.config().boolean("stgit.quotenames").unwrap_or(true);
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    if matches.get_flag("name-only") {
+        let mut last_patchname = None;
+        for index in matching {
+            let (patchname, _) = &origins[index];
+            if last_patchname != Some(patchname) {
+                writeln!(stdout, "{}", patchname.quoted_if(quote_names))?;
+                last_patchname = Some(patchname);
+            }
+        }
+    } else {
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(lines.find_iter(b"\n").map(|pos| pos + 1))
+            .collect();
+        for index in matching {
+            let (patchname, path) = &origins[index];
+            let line = &lines[line_starts[index]..line_starts[index + 1] - 1];
+            write!(stdout, "{}:{path}:", patchname.quoted_if(quote_names))?;
+            stdout.write_all(line)?;
+            stdout.write_all(b"\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the lines added by a diff along with the paths of the files they are added to.
+///
+/// The returned lines exclude the leading `+` and the line terminator.
+fn added_lines(diff: &BStr) -> Vec<(BString, &[u8])> {
+    let mut added = Vec::new();
+    let mut path: Option<BString> = None;
+    let mut in_hunk = false;
+
+    for line in diff.lines() {
+        if line.starts_with(b"diff ") {
+            path = None;
+            in_hunk = false;
+        } else if in_hunk {
+            if let (Some(content), Some(path)) = (line.strip_prefix(b"+"), path.as_ref()) {
+                added.push((path.clone(), content));
+            }
+        } else if let Some(new_path) = line.strip_prefix(b"+++ ") {
+            let new_path = new_path
+                .strip_prefix(b"\"")
+                .and_then(|p| p.strip_suffix(b"\""))
+                .unwrap_or(new_path);
+            path = new_path.strip_prefix(b"b/").map(BString::from);
+        } else if line.starts_with(b"@@ ") {
+            in_hunk = true;
+        }
+    }
+
+    added
+}
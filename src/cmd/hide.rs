@@ -2,21 +2,30 @@
 
 //! `stg hide` implementation.
 
-use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
     branchloc::BranchLocator,
-    color::get_color_stdout,
-    ext::RepositoryExtended,
+    ext::{CommitExtended, RepositoryExtended},
+    output::Output,
     patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    print_info_message,
+    stack::{
+        message, normalize_subject, InitializationPolicy, Stack, StackAccess, StackStateAccess,
+        TransactionError,
+    },
+    stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "hide",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -27,8 +36,27 @@ fn make() -> clap::Command {
         .long_about(
             "Hide patches in the series.\n\
              \n\
-             Hidden patches are no longer shown in the plain 'series' output.",
+             Hidden patches are no longer shown in the plain 'series' output.\n\
+             \n\
+             Only unapplied patches may be hidden. Applied patches are popped, along \
+             with any patches above them, and hidden in the same operation with the \
+             '--pop' option or when the `stgit.hide.autopop` configuration value is \
+             true. The patches popped from above the hidden patches remain unapplied \
+             unless '--keep-above' is given, in which case they are pushed back. If \
+             pushing them back would result in conflicts, nothing is hidden.\n\
+             \n\
+             With '--empty' or '--merged', the patches to hide are selected from the \
+             given patches, or from all applied and unapplied patches when no patches \
+             are given. Unlike 'stg clean', the selected patches are kept in the \
+             series as hidden patches for future reference.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg hide 'wip-*'",
+                "Hide the patches whose names begin with `wip-`",
+            ),
+            ("stg hide --pop @", "Pop and hide the topmost applied patch"),
+        ]))
         .arg(
             Arg::new("patchranges")
                 .help("Patches to hide")
@@ -36,12 +64,72 @@ fn make() -> clap::Command {
                 .num_args(1..)
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange))
-                .required(true),
+                .required_unless_present_any(["empty", "merged", "stdin"]),
+        )
+        .args(argset::stdin_patches_args("patchranges"))
+        .arg(
+            Arg::new("empty")
+                .long("empty")
+                .help("Hide the empty patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("merged")
+                .long("merged")
+                .help("Hide the patches already merged upstream")
+                .long_help(
+                    "Hide the patches whose changes are already merged upstream, i.e. \
+                     into the stack's base, such as after a rebase.\n\
+                     \n\
+                     The patches are compared against the commits that were added to the \
+                     stack's base since the patches' parent commits. With 'patch-id', \
+                     the default, a patch is merged if its patch id, as computed by \
+                     git-patch-id(1), matches that of one of these commits. With \
+                     'subject', patches whose normalized subjects match the subject of \
+                     one of these commits are also considered merged.",
+                )
+                .value_name("method")
+                .value_parser(["patch-id", "subject"])
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("patch-id"),
         )
         .arg(argset::branch_arg())
+        .arg(
+            Arg::new("pop")
+                .long("pop")
+                .help("Pop applied patches before hiding them")
+                .long_help(
+                    "Pop applied patches, and any patches above them, before hiding \
+                     them. This is the default when `stgit.hide.autopop` is true.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("keep-above")
+                .long("keep-above")
+                .help("Push back the patches above the popped patches")
+                .long_help(
+                    "Push back the patches that were popped from above applied patches \
+                     being hidden. The hide is aborted if any of these patches do not \
+                     push back cleanly.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Hide the patch with unresolved conflicts")
+                .long_help(
+                    "Hide the patch left with unresolved conflicts by a conflicting \
+                     push. By default, such a patch may not be hidden until the \
+                     conflicts are resolved or the push is undone.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
         &repo,
@@ -51,26 +139,206 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack.check_head_top_mismatch()?;
 
-    let patches: Vec<PatchName> = patchrange::resolve_names(
-        &stack,
-        matches
-            .get_many::<PatchRange>("patchranges")
-            .expect("clap ensures at least one range is provided"),
-        RangeConstraint::All,
-    )?;
+    let patches: Vec<PatchName> = if let Some(range_specs) =
+        argset::get_patch_ranges::<PatchRange>(matches, "patchranges")?
+    {
+        if range_specs.is_empty() {
+            return Ok(());
+        }
+        patchrange::resolve_names(&stack, range_specs.iter(), RangeConstraint::All)?
+    } else {
+        stack
+            .applied()
+            .iter()
+            .chain(stack.unapplied())
+            .cloned()
+            .collect()
+    };
 
     // Already hidden patches are silent no-ops.
-    let to_hide: Vec<PatchName> = patches
+    let mut to_hide: Vec<PatchName> = patches
         .iter()
         .filter(|pn| !stack.is_hidden(pn))
         .cloned()
         .collect();
 
+    let hide_empty = matches.get_flag("empty");
+    let merged_method = matches.get_one::<String>("merged").map(String::as_str);
+    if hide_empty || merged_method.is_some() {
+        let empty = if hide_empty {
+            find_empty(&stack, &to_hide)?
+        } else {
+            Vec::new()
+        };
+        let merged = if let Some(method) = merged_method {
+            find_merged(&stack, &to_hide, method == "subject")?
+        } else {
+            Vec::new()
+        };
+        to_hide.retain(|pn| empty.contains(pn) || merged.contains(pn));
+        if to_hide.is_empty() {
+            print_info_message(matches, "no patches to hide");
+            return Ok(());
+        }
+    }
+
+    if !matches.get_flag("force") {
+        stack.check_conflicted_patch_filter(|pn| to_hide.contains(pn))?;
+    }
+
+    let applied_to_hide: Vec<&PatchName> =
+        to_hide.iter().filter(|pn| stack.is_applied(pn)).collect();
+
+    if !applied_to_hide.is_empty()
+        && !matches.get_flag("pop")
+        && !stack
+            .config()
+            .boolean("stgit.hide.autopop")
+            .unwrap_or(false)
+    {
+        let names = applied_to_hide
+            .iter()
+            .map(|pn| format!("`{pn}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(if applied_to_hide.len() == 1 {
+            anyhow!("patch {names} is applied; use `--pop` to pop and hide it")
+        } else {
+            anyhow!("patches {names} are applied; use `--pop` to pop and hide them")
+        });
+    }
+
+    let keep_above = matches.get_flag("keep-above");
+
     stack
         .setup_transaction()
-        .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| trans.hide_patches(&to_hide))
-        .execute("hide")?;
+        .command_line(command_line)
+        .use_index_and_worktree(
+            !applied_to_hide.is_empty() && matches.get_one::<BranchLocator>("branch").is_none(),
+        )
+        .allow_push_conflicts(false)
+        .print_delta(!applied_to_hide.is_empty())
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            let popped_extra = trans.pop_patches(|pn| to_hide.contains(pn))?;
+            trans.hide_patches(&to_hide)?;
+            if keep_above {
+                trans.push_patches(&popped_extra, false).map_err(|e| {
+                    if let Some(TransactionError::TransactionHalt { .. }) =
+                        e.downcast_ref::<TransactionError>()
+                    {
+                        anyhow!(
+                            "patches above the hidden patches do not push back cleanly; \
+                             nothing was hidden"
+                        )
+                    } else {
+                        e
+                    }
+                })?;
+            }
+            Ok(())
+        })
+        .execute(&message::with_patches("hide", &to_hide))?;
 
     Ok(())
 }
+
+/// Find the empty patches among `patches`.
+///
+/// As with `stg clean`, the topmost applied patch is not considered to be empty while
+/// there are outstanding conflicts since its changes are then in the work tree.
+fn find_empty(stack: &Stack, patches: &[PatchName]) -> Result<Vec<PatchName>> {
+    let mut empty = Vec::new();
+    for patchname in patches {
+        if stack.get_patch_commit(patchname).is_no_change()? {
+            if stack.applied().last() == Some(patchname)
+                && stack
+                    .repo
+                    .stupid()
+                    .statuses(None)?
+                    .check_conflicts()
+                    .is_err()
+            {
+                continue;
+            }
+            empty.push(patchname.clone());
+        }
+    }
+    Ok(empty)
+}
+
+/// Find the patches among `patches` whose changes are already in the stack's base.
+///
+/// Each patch is compared with the commits that are in the stack's base, but not in the
+/// patch's parent, i.e. the upstream commits the patch has not yet been rebased onto.
+/// Patches match by patch id, or also by normalized subject when `by_subject` is true.
+fn find_merged(stack: &Stack, patches: &[PatchName], by_subject: bool) -> Result<Vec<PatchName>> {
+    let stupid = stack.repo.stupid();
+    let base_id = stack.base().id;
+
+    let mut upstream_ids: Vec<gix::ObjectId> = Vec::new();
+    let mut seen_parents: HashSet<gix::ObjectId> = HashSet::new();
+    let mut candidates: Vec<(&PatchName, gix::ObjectId)> = Vec::new();
+    for patchname in patches {
+        let commit = stack.get_patch_commit(patchname);
+        if commit.is_no_change()? {
+            continue;
+        }
+        let parent_id = commit.get_parent_commit()?.id;
+        if seen_parents.insert(parent_id) {
+            for commit_id in stupid.rev_list(parent_id, base_id, None::<[&str; 0]>)? {
+                if !upstream_ids.contains(&commit_id) {
+                    upstream_ids.push(commit_id);
+                }
+            }
+        }
+        candidates.push((patchname, commit.id));
+    }
+
+    if candidates.is_empty() || upstream_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let upstream_patch_ids: HashSet<gix::ObjectId> = stupid
+        .patch_ids(upstream_ids.iter().copied())?
+        .into_iter()
+        .map(|(_, patch_id)| patch_id)
+        .collect();
+    let candidate_patch_ids: HashMap<gix::ObjectId, gix::ObjectId> = stupid
+        .patch_ids(candidates.iter().map(|(_, commit_id)| *commit_id))?
+        .into_iter()
+        .collect();
+    let upstream_subjects: HashSet<String> = if by_subject {
+        let mut subjects = HashSet::new();
+        for commit_id in &upstream_ids {
+            let commit = stack.repo.find_commit(*commit_id)?;
+            let subject = normalize_subject(&commit.decode()?.message_summary().to_str_lossy());
+            if !subject.is_empty() {
+                subjects.insert(subject);
+            }
+        }
+        subjects
+    } else {
+        HashSet::new()
+    };
+
+    let mut merged = Vec::new();
+    for (patchname, commit_id) in candidates {
+        let is_merged = if candidate_patch_ids
+            .get(&commit_id)
+            .is_some_and(|patch_id| upstream_patch_ids.contains(patch_id))
+        {
+            true
+        } else if by_subject {
+            let commit = stack.get_patch_commit(patchname);
+            let subject = normalize_subject(&commit.decode()?.message_summary().to_str_lossy());
+            upstream_subjects.contains(&subject)
+        } else {
+            false
+        };
+        if is_merged {
+            merged.push(patchname.clone());
+        }
+    }
+    Ok(merged)
+}
@@ -2,20 +2,22 @@
 
 //! `stg id` implementation.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::RepositoryExtended,
-    patch::SingleRevisionSpec,
-    stack::{InitializationPolicy, Stack, StackAccess},
+    patch::{patchrange, revspec, PatchName, RangeConstraint, RangeRevisionSpec},
+    patchfmt::CommitFormat,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "id",
     category: super::CommandCategory::PatchInspection,
+    read_only: true,
     make,
     run,
 };
@@ -29,20 +31,90 @@ fn make() -> clap::Command {
              In addition to standard Git revision specifiers (revspecs), \
              patches may be specified in the form '[<branch>:]<patch>' or \
              '[<branch>:]{base}' for the base of a stack. If no branch is \
-             specified, the current branch is used by default. The parent \
-             of a patch may be specified with '[<branch>:]<patch>^'.",
+             specified, the current branch is used by default. Other stack \
+             positions, such as '@' or '^', and offsets may also be prefixed with \
+             a branch, as may git revision suffixes, e.g. '<branch>:{base}~2^2'. \
+             The parent of a patch may be specified with '[<branch>:]<patch>^'.\n\
+             \n\
+             Patch ranges, e.g. 'p1..p3' or '[<branch>:]p2..', may also be \
+             specified, in which case the ids of all patches in the range are \
+             printed, one per line, in stack order. Open-ended ranges extend to the \
+             first or last visible patch in the stack, whereas ranges with both ends \
+             given may also name hidden patches. Multiple revisions and ranges \
+             may be specified, with the ids printed in the order of the arguments.\n\
+             \n\
+             With '--format', selected fields of each resolved commit are printed \
+             instead of its id, one line per commit.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg id ~1",
+                "Print the commit id of the patch below the topmost patch",
+            ),
+            (
+                "stg id 'other:{base}'",
+                "Print the base commit of the stack on branch `other`",
+            ),
+            (
+                "stg id p1..p3",
+                "Print the commit ids of `p1` through `p3`, one per line",
+            ),
+            (
+                "stg id --format='%h %an %s' @ 'other:{base}'",
+                "Print the short id, author, and subject of both commits",
+            ),
+        ]))
         .arg(argset::branch_arg())
         .arg(
             Arg::new("stgit-revision")
                 .value_name("revision")
+                .num_args(1..)
                 .allow_hyphen_values(true)
-                .value_parser(clap::value_parser!(SingleRevisionSpec))
-                .help("StGit revision"),
+                .value_parser(clap::value_parser!(RangeRevisionSpec))
+                .help("StGit revisions or patch ranges"),
+        )
+        .arg(
+            Arg::new("patch-names")
+                .long("patch-names")
+                .help("Prefix each id with its patch name")
+                .long_help(
+                    "Prefix each printed id with the name of the corresponding patch, \
+                     separated by a space. Commits that are not patches in the stack are \
+                     prefixed with '-'.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Print the commits' fields with a custom <format>")
+                .long_help(
+                    "Print each resolved commit with a custom <format> instead of its \
+                     id, followed by a newline. The format string may use these \
+                     placeholders from 'git log --format':\n\
+                     \n\
+                     - %H and %h: the full or abbreviated commit id\n\
+                     - %T and %t: the full or abbreviated tree id\n\
+                     - %P and %p: the full or abbreviated parent ids, separated by \
+                     spaces\n\
+                     - %an and %ae: the author name and email\n\
+                     - %ad, %ai, and %at: the author date in git's default format, in \
+                     ISO 8601-like format, or as a UNIX timestamp\n\
+                     - %cn, %ce, %cd, %ci, and %ct: likewise for the committer\n\
+                     - %s: the first line of the commit message\n\
+                     - %b: the commit message after the subject line\n\
+                     - %n and %%: a newline or a literal %\n\
+                     \n\
+                     Revisions that do not resolve to commits are an error with \
+                     --format.",
+                )
+                .value_name("format")
+                .value_parser(clap::value_parser!(CommitFormat))
+                .conflicts_with("patch-names"),
         )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
         &repo,
@@ -50,12 +122,66 @@ fn run(matches: &ArgMatches) -> Result<()> {
         InitializationPolicy::AllowUninitialized,
     )?;
 
-    let oid = matches
-        .get_one::<SingleRevisionSpec>("stgit-revision")
-        .map(|spec| spec.resolve_object(&repo, &stack).map(|object| object.id))
-        .transpose()?
-        .unwrap_or_else(|| stack.get_branch_head().id);
+    let format = matches.get_one::<CommitFormat>("format");
+    let mut ids: Vec<(Option<PatchName>, gix::ObjectId)> = Vec::new();
+    if let Some(specs) = matches.get_many::<RangeRevisionSpec>("stgit-revision") {
+        for spec in specs {
+            if let RangeRevisionSpec::Single(single_spec) = spec {
+                let object = single_spec.resolve_object(&repo, &stack)?;
+                let id = if format.is_some() {
+                    object
+                        .peel_tags_to_end()?
+                        .try_into_commit()
+                        .map_err(|_| anyhow!("`{single_spec}` is not a commit"))?
+                        .id
+                } else {
+                    object.id
+                };
+                let patchname = single_spec
+                    .resolve(&repo, Some(&stack))
+                    .ok()
+                    .and_then(|rev| rev.patchname)
+                    .or_else(|| find_patchname(&stack, id));
+                ids.push((patchname, id));
+            } else {
+                let constraint = if spec.is_bounded() {
+                    RangeConstraint::All
+                } else {
+                    RangeConstraint::Visible
+                };
+                let revs = revspec::resolve(&repo, Some(&stack), [spec], constraint)?;
+                if revs.is_empty() {
+                    return Err(patchrange::Error::NoMatchingPatches(spec.to_string()).into());
+                }
+                ids.extend(revs.into_iter().map(|rev| (rev.patchname, rev.commit.id)));
+            }
+        }
+    } else {
+        let id = stack.get_branch_head().id;
+        ids.push((find_patchname(&stack, id), id));
+    }
 
-    println!("{oid}");
+    let show_patchnames = matches.get_flag("patch-names");
+    for (patchname, id) in ids {
+        if let Some(format) = format {
+            println!("{}", format.render(&repo.find_commit(id)?)?);
+        } else if show_patchnames {
+            if let Some(patchname) = patchname {
+                println!("{patchname} {id}");
+            } else {
+                println!("- {id}");
+            }
+        } else {
+            println!("{id}");
+        }
+    }
     Ok(())
 }
+
+/// Find the patch in the stack whose commit is the given commit id.
+fn find_patchname(stack: &Stack, id: gix::ObjectId) -> Option<PatchName> {
+    stack
+        .all_patches()
+        .find(|patchname| stack.get_patch_commit(patchname).id == id)
+        .cloned()
+}
@@ -12,17 +12,18 @@ use bstr::{BStr, BString, ByteSlice, ByteVec};
 use clap::{Arg, ArgGroup};
 
 use crate::{
-    color::get_color_stdout,
     ext::{RepositoryExtended, TimeExtended},
-    patch::{patchedit, PatchName},
-    print_info_message,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
-    stupid::Stupid,
+    output::Output,
+    patch::{name::NameTemplate, patchedit, PatchName},
+    print_info_message, print_warning_message,
+    stack::{InitializationPolicy, Stack, StackAccess, StackConfig, StackStateAccess},
+    stupid::{ApplyOptions, Stupid, StupidContext},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "import",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -43,14 +44,32 @@ fn make() -> clap::Command {
              Patches may also be imported from a mail file (-m/--mail), an mbox \
              (-M/--mbox), or a series (-S/--series). Furthermore, the -u/--url option \
              allows the patches source to be fetched from a url instead of from a \
-             local file.\n\
+             local file. When none of '--mail', '--mbox', or '--series' are given \
+             with '--url', the kind of source is detected from the downloaded \
+             content: tar archives are imported as series, content starting with an \
+             mbox \"From \" line is imported as an mbox, and anything else is \
+             imported as a plain patch. Compressed downloads are decompressed.\n\
+             \n\
+             With '--thread', the patch series of a mailing list thread is fetched \
+             from a public-inbox archive, such as lore.kernel.org, by the message id \
+             of any message in the thread. The patches of the series are imported in \
+             the order of their \"[PATCH n/m]\" subject tags, skipping the cover \
+             letter and replies. The archive's base url is taken from the \
+             \"stgit.import.inbox\" configuration option and defaults to \
+             https://lore.kernel.org/all.\n\
              \n\
              If a patch does not apply cleanly import is aborted unless '--reject' \
              is specified, in which case it will apply to the work tree the parts \
              of the patch that are  applicable, leave the rejected hunks in \
              corresponding *.rej files, and add an empty patch to the stack.\n\
              \n\
-             The patch description must be separated from the diff with a \"---\" line.",
+             The patch description must be separated from the diff with a \"---\" line.\n\
+             \n\
+             With '--format=json', the source is a JSON document as written by \
+             'stg export --format=json'. Each patch in the document is applied in \
+             order, keeping its recorded name, message, and author. If a patch's diff \
+             does not apply, the import stops and reports the position and name of \
+             the failing patch.",
         )
         .override_usage(super::make_usage(
             "stg import",
@@ -64,6 +83,7 @@ fn make() -> clap::Command {
                     "[OPTIONS] -u -m <mail-url>",
                     "[OPTIONS] -u -M <mbox-url>",
                     "[OPTIONS] -u -S <series-url>",
+                    "[OPTIONS] --thread <message-id>",
                 ]
             } else {
                 &[
@@ -108,6 +128,19 @@ fn make() -> clap::Command {
                 .long_help("Import patch series from a series file are tar archive.")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Import patches from a source in <format>")
+                .long_help(
+                    "Import patches from a source in <format>. The default \"patch\" \
+                     format is a diff/patch file, optionally in mail format. The \
+                     \"json\" format is a stack exported with \
+                     'stg export --format=json'.",
+                )
+                .value_name("format")
+                .value_parser(["patch", "json"]),
+        )
         .group(ArgGroup::new("whence").args(["mail", "mbox", "series"]));
 
     let app = if cfg!(feature = "import-url") {
@@ -119,6 +152,18 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .requires("source"),
         )
+        .arg(
+            Arg::new("thread")
+                .long("thread")
+                .help("Import the patch series of a mailing list thread")
+                .long_help(
+                    "Import the patch series of the mailing list thread containing the \
+                     message with <message-id>. The thread is fetched as an mbox from \
+                     the public-inbox archive configured with \"stgit.import.inbox\".",
+                )
+                .value_name("message-id")
+                .conflicts_with_all(["source", "url", "whence", "format"]),
+        )
     } else {
         app
     };
@@ -133,6 +178,30 @@ fn make() -> clap::Command {
                 .value_name("name")
                 .value_parser(clap::value_parser!(PatchName)),
         )
+        .arg(
+            Arg::new("names-from")
+                .long("names-from")
+                .help("Use patch names from <file> for a series import")
+                .long_help(
+                    "Use the patch names listed in <file> instead of names derived from \
+                     the series' patch file names. Each non-empty line of <file> is \
+                     either a single patch name, in which case there must be exactly \
+                     one name for each patch in the series, or an \
+                     \"<patch-file>=<name>\" mapping from a patch file name in the \
+                     series to a patch name, in which case patches without a mapping \
+                     use their derived names. Lines starting with '#' are ignored.\n\
+                     \n\
+                     The names must be valid patch names and may not collide with \
+                     each other. Unless '--ignore' or '--replace' is used, the names \
+                     also may not collide with existing patches. No patches are \
+                     imported if any of the names are invalid.",
+                )
+                .value_name("file")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath)
+                .requires("series")
+                .conflicts_with("name"),
+        )
         .arg(
             Arg::new("strip")
                 .long("strip")
@@ -167,6 +236,9 @@ fn make() -> clap::Command {
                 .value_name("n")
                 .value_parser(crate::argset::parse_usize),
         )
+        .arg(crate::argset::fuzz_arg())
+        .arg(crate::argset::ignore_whitespace_arg())
+        .arg(crate::argset::whitespace_arg())
         .arg(
             Arg::new("3way")
                 .long("3way")
@@ -178,6 +250,7 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(crate::argset::skip_applied_arg().conflicts_with("3way"))
         .arg(
             Arg::new("ignore")
                 .long("ignore")
@@ -191,6 +264,7 @@ fn make() -> clap::Command {
                 .help("Replace the unapplied patches in the series")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(crate::argset::name_collision_arg().conflicts_with_all(["ignore", "replace"]))
         .arg(
             Arg::new("base")
                 .long("base")
@@ -208,8 +282,41 @@ fn make() -> clap::Command {
             Arg::new("keep-cr")
                 .long("keep-cr")
                 .help("Do not remove \"\\r\" from email lines ending with \"\\r\\n\"")
+                .long_help(
+                    "Do not remove \"\\r\" from email lines ending with \"\\r\\n\". \
+                     This is needed for patches to files with CRLF line endings. This \
+                     behavior may also be enabled via the \"stgit.import.keep-cr\" or \
+                     \"am.keepcr\" configuration options.",
+                )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("no-keep-cr")
+                .long("no-keep-cr")
+                .help("Remove \"\\r\" from email lines ending with \"\\r\\n\"")
+                .long_help(
+                    "Remove \"\\r\" from email lines ending with \"\\r\\n\", \
+                     overriding the \"stgit.import.keep-cr\" and \"am.keepcr\" \
+                     configuration options.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("keep-cr"),
+        )
+        .arg(
+            Arg::new("charset")
+                .long("charset")
+                .help("Decode email messages using <encoding>")
+                .long_help(
+                    "Decode each email's headers and message body using <encoding> \
+                     instead of the charset declared in its MIME headers. This is \
+                     useful for emails with a wrong or missing charset declaration. \
+                     The patch description and author are transcoded to UTF-8 while \
+                     the diff is left untouched. This option is applicable when \
+                     importing with '--mail' or '--mbox'.",
+                )
+                .value_name("encoding")
+                .value_parser(parse_charset),
+        )
         .arg(
             Arg::new("message-id")
                 .long("message-id")
@@ -222,11 +329,12 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         );
-    patchedit::add_args(app, false, false)
+    patchedit::add_args(app, false, false).args(crate::argset::sign_args())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
-    let repo = gix::Repository::open()?;
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
+    let mut repo = gix::Repository::open()?;
+    crate::argset::set_commit_signing(&mut repo, matches)?;
     let stack = Stack::current(&repo, InitializationPolicy::AutoInitialize)?;
     let stupid = repo.stupid();
 
@@ -244,29 +352,45 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     stack.check_head_top_mismatch()?;
     //stupid.update_index_refresh()?;
 
-    if cfg!(feature = "import-url") && matches.get_flag("url") {
-        import_url(stack, matches)
+    let json_format = matches.get_one::<String>("format").map(String::as_str) == Some("json");
+    if json_format && matches.contains_id("whence") {
+        return Err(anyhow!(
+            "`--format=json` cannot be combined with `--mail`, `--mbox`, or `--series`"
+        ));
+    }
+
+    if cfg!(feature = "import-url") && matches.contains_id("thread") {
+        import_thread(stack, matches, command_line)
+    } else if cfg!(feature = "import-url") && matches.get_flag("url") {
+        import_url(stack, matches, command_line)
+    } else if json_format {
+        import_json(stack, matches, command_line, source_path.as_deref())
     } else if matches.get_flag("series") {
-        import_series(stack, matches, source_path.as_deref())
+        import_series(stack, matches, command_line, source_path.as_deref())
     } else if matches.get_flag("mail") || matches.get_flag("mbox") {
-        import_mail(stack, matches, source_path.as_deref())
+        import_mail(stack, matches, command_line, source_path.as_deref())
     } else {
-        import_file(stack, matches, source_path.as_deref(), None)?;
+        import_file(
+            stack,
+            matches,
+            command_line,
+            source_path.as_deref(),
+            None,
+            None,
+        )?;
         Ok(())
     }
 }
 
 #[cfg(not(feature = "import-url"))]
-fn import_url(_stack: Stack, _matches: &clap::ArgMatches) -> Result<()> {
+fn import_url(_stack: Stack, _matches: &clap::ArgMatches, _command_line: &[String]) -> Result<()> {
     Err(anyhow!(
         "StGit not built with support for downloading imports"
     ))
 }
 
 #[cfg(feature = "import-url")]
-fn import_url(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
-    use std::io::Write;
-
+fn import_url(stack: Stack, matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     let url_osstr = matches
         .get_one::<PathBuf>("source")
         .expect("source url must be present")
@@ -275,12 +399,215 @@ fn import_url(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
     let url_str = url_osstr
         .to_str()
         .ok_or_else(|| anyhow!("source url is not UTF-8 encoded"))?;
+    let (_download_dir, download_path) = download_url(url_str)?;
+
+    if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+        import_json(stack, matches, command_line, Some(download_path.as_path()))
+    } else if matches.get_flag("series") || is_tar_path(&download_path) {
+        import_series(stack, matches, command_line, Some(download_path.as_path()))
+    } else if matches.get_flag("mail") || matches.get_flag("mbox") {
+        let mail_path = decompress_download(&download_path)?;
+        import_mail(stack, matches, command_line, Some(mail_path.as_path()))
+    } else {
+        let mail_path = decompress_download(&download_path)?;
+        if read_patch_file(&mail_path)?.starts_with(b"From ") {
+            import_mail(stack, matches, command_line, Some(mail_path.as_path()))
+        } else {
+            import_file(
+                stack,
+                matches,
+                command_line,
+                Some(mail_path.as_path()),
+                None,
+                None,
+            )?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "import-url"))]
+fn import_thread(
+    _stack: Stack,
+    _matches: &clap::ArgMatches,
+    _command_line: &[String],
+) -> Result<()> {
+    Err(anyhow!(
+        "StGit not built with support for downloading imports"
+    ))
+}
+
+/// Import the patch series of a mailing list thread from a public-inbox archive.
+#[cfg(feature = "import-url")]
+fn import_thread(stack: Stack, matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
+    let message_id = matches
+        .get_one::<String>("thread")
+        .expect("thread message id must be present");
+    let message_id = message_id
+        .strip_prefix('<')
+        .and_then(|id| id.strip_suffix('>'))
+        .unwrap_or(message_id);
+    if message_id.is_empty() {
+        return Err(anyhow!("message id may not be empty"));
+    }
+
+    let config = stack.config();
+    let inbox = config.string("stgit.import.inbox").map_or_else(
+        || "https://lore.kernel.org/all".to_string(),
+        |s| s.to_string(),
+    );
+    let url_str = format!(
+        "{}/{}/t.mbox.gz",
+        inbox.trim_end_matches('/'),
+        encode_message_id(message_id)
+    );
+    let (_download_dir, download_path) = download_url(&url_str)?;
+    let mbox_path = decompress_download(&download_path)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let num_mails = stack.repo.stupid().mailsplit(
+        Some(mbox_path.as_path()),
+        out_dir.path(),
+        use_keep_cr(matches, &config),
+        false,
+    )?;
+    let mut series: Vec<(usize, PathBuf)> = Vec::new();
+    for i in 1..=num_mails {
+        let mail_path = out_dir.path().join(format!("{i:04}"));
+        let mail = std::fs::read(&mail_path)?;
+        if let Some(position) = mail_subject(&mail).and_then(|s| series_position(&s)) {
+            series.push((position, mail_path));
+        }
+    }
+    if series.is_empty() {
+        return Err(anyhow!("no patches found in thread `{message_id}`"));
+    }
+    series.sort_by_key(|(position, _)| *position);
+    let mail_paths: Vec<PathBuf> = series.into_iter().map(|(_, path)| path).collect();
+    import_mail_files(stack, matches, command_line, &mail_paths)
+}
+
+/// Percent-encode the characters of a message id that are not allowed in a url path
+/// segment.
+#[cfg(feature = "import-url")]
+fn encode_message_id(message_id: &str) -> String {
+    let mut encoded = String::with_capacity(message_id.len());
+    for b in message_id.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~@!$&'()*+,;=:".contains(&b) {
+            encoded.push(char::from(b));
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Get the unfolded "Subject" header of a raw mail.
+#[cfg(feature = "import-url")]
+fn mail_subject(mail: &[u8]) -> Option<String> {
+    let mut subject: Option<String> = None;
+    for line in mail.lines() {
+        if line.is_empty() {
+            break;
+        } else if let Some(subject) = subject.as_mut() {
+            if line.starts_with(b" ") || line.starts_with(b"\t") {
+                subject.push(' ');
+                subject.push_str(line.trim().to_str_lossy().as_ref());
+                continue;
+            }
+            break;
+        } else if line.len() > 8 && line[..8].eq_ignore_ascii_case(b"subject:") {
+            subject = Some(line[8..].trim().to_str_lossy().to_string());
+        }
+    }
+    subject
+}
+
+/// Get the position of a mail in a patch series from the mail's subject.
+///
+/// Only subjects with a "[PATCH]" tag are part of the series. The position is the
+/// `n` of a "[PATCH n/m]" tag, or zero for a single patch without such a counter.
+/// Replies and cover letters, i.e. "[PATCH 0/m]", are not part of the series.
+#[cfg(feature = "import-url")]
+fn series_position(subject: &str) -> Option<usize> {
+    if subject
+        .get(..3)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:"))
+    {
+        return None;
+    }
+    let tag = subject.strip_prefix('[')?.split_once(']')?.0;
+    let mut words = tag.split_ascii_whitespace();
+    if !words.any(|word| word.eq_ignore_ascii_case("patch")) {
+        return None;
+    }
+    let counter = tag
+        .split_ascii_whitespace()
+        .find_map(|word| word.split_once('/'));
+    match counter {
+        None => Some(0),
+        Some((n, m)) => {
+            let n: usize = n.parse().ok()?;
+            m.parse::<usize>().ok()?;
+            (n > 0).then_some(n)
+        }
+    }
+}
+
+/// Whether the path names a tar archive that is to be imported as a series.
+#[cfg(feature = "import-url")]
+fn is_tar_path(path: &Path) -> bool {
+    path.file_name().is_some_and(|filename| {
+        let filename = filename.to_string_lossy().to_ascii_lowercase();
+        filename.ends_with(".tar.gz")
+            || filename.ends_with(".tgz")
+            || filename.ends_with(".tar.bz2")
+            || filename.ends_with(".tar")
+    })
+}
+
+/// Decompress a downloaded `.gz` or `.bz2` file alongside the download.
+///
+/// The path to the decompressed file is returned, which is the download path itself
+/// if the download is not compressed.
+#[cfg(feature = "import-url")]
+fn decompress_download(download_path: &Path) -> Result<PathBuf> {
+    match download_path.extension().and_then(std::ffi::OsStr::to_str) {
+        Some("gz" | "bz2") => {
+            let content = read_patch_file(download_path)?;
+            let decompressed_path = download_path.with_extension("");
+            std::fs::write(&decompressed_path, content)?;
+            Ok(decompressed_path)
+        }
+        _ => Ok(download_path.to_owned()),
+    }
+}
+
+#[cfg(not(feature = "import-url"))]
+pub(super) fn download_url(_url_str: &str) -> Result<(tempfile::TempDir, PathBuf)> {
+    Err(anyhow!(
+        "StGit not built with support for downloading imports"
+    ))
+}
+
+/// Download the patch at `url_str` into a new temporary directory.
+///
+/// The downloaded file is named after the last component of the url. Redirects are
+/// followed and compressed transfer encodings are decoded. The returned directory must
+/// be kept alive for as long as the downloaded file is needed.
+#[cfg(feature = "import-url")]
+pub(super) fn download_url(url_str: &str) -> Result<(tempfile::TempDir, PathBuf)> {
+    use std::io::Write;
+
     let mut handle = curl::easy::Easy::new();
     handle.url(url_str)?;
+    handle.follow_location(true)?;
+    handle.accept_encoding("")?;
     let url_decoded = handle.url_decode(url_str);
     let filename = url_decoded
         .rsplit_str(b"/")
         .next()
+        .filter(|b| !b.is_empty())
         .and_then(|b| b.to_str().ok())
         .unwrap_or("patch");
     let download_dir = tempfile::tempdir()?;
@@ -296,66 +623,83 @@ fn import_url(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
     })?;
 
     match transfer.perform() {
-        Ok(()) => {}
+        Ok(()) => {
+            drop(transfer);
+            let status = handle.response_code()?;
+            if status != 0 && !(200..300).contains(&status) {
+                return Err(anyhow!(
+                    "downloading `{url_str}` failed with HTTP status {status}"
+                ));
+            }
+        }
         Err(e) if e.is_url_malformed() && url_str.starts_with("file://") => {
             let source_path = Path::new(url_str.strip_prefix("file://").unwrap());
             std::fs::copy(source_path, &download_path)
-                .with_context(|| format!("copying {url_str}"))?;
+                .with_context(|| format!("downloading `{url_str}`"))?;
         }
-        e @ Err(_) => e?,
+        Err(e) => return Err(anyhow!("downloading `{url_str}`: {e}")),
     }
 
-    if matches.get_flag("series") {
-        import_series(stack, matches, Some(download_path.as_path()))
-    } else if matches.get_flag("mail") || matches.get_flag("mbox") {
-        import_mail(stack, matches, Some(download_path.as_path()))
-    } else {
-        import_file(stack, matches, Some(download_path.as_path()), None)?;
-        Ok(())
-    }
+    Ok((download_dir, download_path))
 }
 
-fn import_tgz_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
+fn import_tgz_series(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    source_path: &Path,
+) -> Result<()> {
     let source_file = std::fs::File::open(source_path)?;
     let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(source_file));
     let temp_dir = tempfile::tempdir()?;
     archive.unpack(temp_dir.path())?;
     let series_path = find_series_path(temp_dir.path())?;
-    import_series(stack, matches, Some(series_path.as_path()))
+    import_series(stack, matches, command_line, Some(series_path.as_path()))
 }
 
-fn import_tbz2_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
+fn import_tbz2_series(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    source_path: &Path,
+) -> Result<()> {
     let source_file = std::fs::File::open(source_path)?;
     let mut archive = tar::Archive::new(bzip2_rs::DecoderReader::new(source_file));
     let temp_dir = tempfile::tempdir()?;
     archive.unpack(temp_dir.path())?;
     let series_path = find_series_path(temp_dir.path())?;
-    import_series(stack, matches, Some(series_path.as_path()))
+    import_series(stack, matches, command_line, Some(series_path.as_path()))
 }
 
-fn import_tar_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
+fn import_tar_series(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    source_path: &Path,
+) -> Result<()> {
     let source_file = std::fs::File::open(source_path)?;
     let mut archive = tar::Archive::new(source_file);
     let temp_dir = tempfile::tempdir()?;
     archive.unpack(temp_dir.path())?;
     let series_path = find_series_path(temp_dir.path())?;
-    import_series(stack, matches, Some(series_path.as_path()))
+    import_series(stack, matches, command_line, Some(series_path.as_path()))
 }
 
 fn import_series(
     stack: Stack,
     matches: &clap::ArgMatches,
+    command_line: &[String],
     source_path: Option<&Path>,
 ) -> Result<()> {
     let series = if let Some(source_path) = source_path {
         if let Some(filename) = source_path.file_name() {
             let filename = filename.to_string_lossy().to_ascii_lowercase();
             if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
-                return import_tgz_series(stack, matches, source_path);
+                return import_tgz_series(stack, matches, command_line, source_path);
             } else if filename.ends_with(".tar.bz2") {
-                return import_tbz2_series(stack, matches, source_path);
+                return import_tbz2_series(stack, matches, command_line, source_path);
             } else if filename.ends_with(".tar") {
-                return import_tar_series(stack, matches, source_path);
+                return import_tar_series(stack, matches, command_line, source_path);
             }
         }
         std::fs::read(source_path)?
@@ -367,7 +711,7 @@ fn import_series(
         buf
     };
 
-    let mut stack = stack;
+    let mut entries = Vec::new();
 
     for line in series.lines() {
         let line = line
@@ -407,11 +751,175 @@ fn import_series(
             None
         };
 
-        stack = import_file(stack, matches, Some(patch_path.as_path()), strip_level)?;
+        entries.push((
+            raw_patchname.to_string_lossy().to_string(),
+            patch_path,
+            strip_level,
+        ));
+    }
+
+    let names = if let Some(names_path) = matches.get_one::<PathBuf>("names-from") {
+        let raw_patchnames: Vec<&str> = entries.iter().map(|(raw, _, _)| raw.as_str()).collect();
+        let names = read_series_names(names_path, &raw_patchnames)?;
+        check_series_names(&stack, matches, &names)?;
+        names
+    } else {
+        vec![None; entries.len()]
+    };
+
+    let mut stack = stack;
+    for ((_, patch_path, strip_level), patchname) in entries.into_iter().zip(names) {
+        stack = import_file(
+            stack,
+            matches,
+            command_line,
+            Some(patch_path.as_path()),
+            strip_level,
+            patchname.as_ref(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Read the patch names to use for a series import from a names file.
+///
+/// The returned vector has one entry for each of the series' patches, which is `None`
+/// for any patch without an explicit name in a mapping-style names file.
+fn read_series_names(names_path: &Path, raw_patchnames: &[&str]) -> Result<Vec<Option<PatchName>>> {
+    let content = std::fs::read_to_string(names_path)
+        .with_context(|| format!("reading names file `{}`", names_path.display()))?;
+    let lines: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let parse_name = |name: &str| {
+        name.trim()
+            .parse::<PatchName>()
+            .with_context(|| format!("invalid name in names file `{}`", names_path.display()))
+    };
+
+    let is_mapping = lines.iter().any(|line| line.contains('='));
+    if is_mapping {
+        let mut names = vec![None; raw_patchnames.len()];
+        for line in lines {
+            let Some((raw_patchname, name)) = line.split_once('=') else {
+                return Err(anyhow!(
+                    "names file `{}` mixes names and mappings: `{line}`",
+                    names_path.display()
+                ));
+            };
+            let raw_patchname = raw_patchname.trim();
+            let pos = raw_patchnames
+                .iter()
+                .position(|raw| *raw == raw_patchname)
+                .ok_or_else(|| anyhow!("patch file `{raw_patchname}` is not in the series"))?;
+            if names[pos].is_some() {
+                return Err(anyhow!(
+                    "patch file `{raw_patchname}` is mapped more than once"
+                ));
+            }
+            names[pos] = Some(parse_name(name)?);
+        }
+        Ok(names)
+    } else if lines.len() != raw_patchnames.len() {
+        Err(anyhow!(
+            "names file `{}` has {} names, but the series has {} patches",
+            names_path.display(),
+            lines.len(),
+            raw_patchnames.len(),
+        ))
+    } else {
+        lines
+            .into_iter()
+            .map(|name| parse_name(name).map(Some))
+            .collect()
+    }
+}
+
+/// Ensure explicitly named patches do not collide with each other or existing patches.
+fn check_series_names(
+    stack: &Stack,
+    matches: &clap::ArgMatches,
+    names: &[Option<PatchName>],
+) -> Result<()> {
+    let allow_existing = matches.get_flag("ignore") || matches.get_flag("replace");
+    let mut seen: Vec<&PatchName> = Vec::with_capacity(names.len());
+    for name in names.iter().flatten() {
+        if seen.contains(&name) {
+            return Err(anyhow!("patch name `{name}` is used more than once"));
+        }
+        if !allow_existing && stack.has_patch(name) {
+            return Err(anyhow!("patch `{name}` already exists"));
+        }
+        seen.push(name);
     }
     Ok(())
 }
 
+fn import_json(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    source_path: Option<&Path>,
+) -> Result<()> {
+    use super::export::{JsonExport, JSON_FORMAT_VERSION};
+
+    let content = if let Some(source_path) = source_path {
+        std::fs::read(source_path)?
+    } else {
+        let stdin = std::io::stdin();
+        let mut stdin = stdin.lock();
+        let mut buf = Vec::new();
+        stdin.read_to_end(&mut buf)?;
+        buf
+    };
+
+    let version = serde_json::from_slice::<serde_json::Value>(&content)
+        .context("parsing JSON stack export")?
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .ok_or_else(|| anyhow!("JSON stack export is missing its format version"))?;
+    if version > u64::from(JSON_FORMAT_VERSION) {
+        return Err(anyhow!(
+            "JSON stack export format version {version} is newer than the supported \
+             version {JSON_FORMAT_VERSION}"
+        ));
+    }
+
+    let export: JsonExport =
+        serde_json::from_slice(&content).context("parsing JSON stack export")?;
+    let num_patches = export.patches.len();
+    let mut stack = stack;
+
+    for (i, patch) in export.patches.into_iter().enumerate() {
+        let position = i + 1;
+        let name = patch.name;
+        let headers = Headers {
+            patchname: Some(name.clone()),
+            author_name: Some(patch.author.name),
+            author_email: Some(patch.author.email),
+            author_date: Some(patch.author.date),
+            subject: None,
+            message_id: None,
+        };
+        stack = create_patch(
+            stack,
+            matches,
+            command_line,
+            NameSource::Derived(None),
+            headers,
+            patch.message.as_bytes().as_bstr(),
+            patch.diff.as_bytes().as_bstr(),
+            None,
+        )
+        .with_context(|| format!("importing patch {position} of {num_patches} (`{name}`)"))?;
+    }
+
+    Ok(())
+}
+
 fn find_series_path(base: &Path) -> Result<PathBuf> {
     for entry in base.read_dir()? {
         let entry = entry?;
@@ -427,27 +935,78 @@ fn find_series_path(base: &Path) -> Result<PathBuf> {
     Err(anyhow!("series file not found"))
 }
 
-fn use_message_id(matches: &clap::ArgMatches, config: &gix::config::Snapshot) -> bool {
+fn use_message_id(matches: &clap::ArgMatches, config: &StackConfig) -> bool {
     matches.get_flag("message-id") || config.boolean("stgit.import.message-id").unwrap_or(false)
 }
 
-fn import_mail(stack: Stack, matches: &clap::ArgMatches, source_path: Option<&Path>) -> Result<()> {
+fn use_keep_cr(matches: &clap::ArgMatches, config: &StackConfig) -> bool {
+    if matches.get_flag("keep-cr") {
+        true
+    } else if matches.get_flag("no-keep-cr") {
+        false
+    } else {
+        config
+            .boolean("stgit.import.keep-cr")
+            .or_else(|| config.boolean("am.keepcr"))
+            .unwrap_or(false)
+    }
+}
+
+fn parse_charset(label: &str) -> Result<&'static encoding_rs::Encoding> {
+    encoding_rs::Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| anyhow!("unknown encoding `{label}`"))
+}
+
+fn import_mail(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    source_path: Option<&Path>,
+) -> Result<()> {
     let out_dir = tempfile::tempdir()?;
     let missing_from_ok = matches.get_flag("mail");
-    let keep_cr = matches.get_flag("keep-cr");
-    let message_id = use_message_id(matches, &stack.repo.config_snapshot());
+    let keep_cr = use_keep_cr(matches, &stack.config());
+    let num_patches =
+        stack
+            .repo
+            .stupid()
+            .mailsplit(source_path, out_dir.path(), keep_cr, missing_from_ok)?;
+    let patch_paths: Vec<PathBuf> = (1..=num_patches)
+        .map(|i| out_dir.path().join(format!("{i:04}")))
+        .collect();
+    import_mail_files(stack, matches, command_line, &patch_paths)
+}
+
+/// Import patches from mail files as split by `git mailsplit`.
+fn import_mail_files(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    patch_paths: &[PathBuf],
+) -> Result<()> {
+    let config = stack.config();
+    let message_id = use_message_id(matches, &config);
+    let charset = matches.get_one::<&'static encoding_rs::Encoding>("charset");
     let stupid = stack.repo.stupid();
-    let num_patches = stupid.mailsplit(source_path, out_dir.path(), keep_cr, missing_from_ok)?;
     let mut stack = stack;
-    for i in 1..=num_patches {
-        let patch_path = out_dir.path().join(format!("{i:04}"));
+    for patch_path in patch_paths {
         let patch_file = std::fs::File::open(patch_path)?;
-        let (mailinfo, message, diff) = stupid.mailinfo(Some(patch_file), message_id)?;
+        let (mailinfo, message, diff) =
+            stupid.mailinfo(Some(patch_file), message_id, charset.is_none())?;
+        let (mailinfo, message) = if let Some(encoding) = charset {
+            (
+                BString::from(encoding.decode_without_bom_handling(&mailinfo).0.as_bytes()),
+                BString::from(encoding.decode_without_bom_handling(&message).0.as_bytes()),
+            )
+        } else {
+            (mailinfo, message)
+        };
         let headers = Headers::parse_mailinfo(mailinfo.as_bstr()).unwrap_or_default();
         stack = create_patch(
             stack,
             matches,
-            None,
+            command_line,
+            NameSource::Derived(None),
             headers,
             message.as_bstr(),
             diff.as_bstr(),
@@ -467,26 +1026,60 @@ fn read_bz2(source_file: std::fs::File, content: &mut Vec<u8>) -> Result<()> {
     Ok(())
 }
 
+/// Read the content of a patch file, decompressing `.gz` and `.bz2` files.
+pub(super) fn read_patch_file(source_path: &Path) -> Result<Vec<u8>> {
+    let mut content = Vec::with_capacity(4096);
+    let source_file = std::fs::File::open(source_path)?;
+    match source_path.extension().and_then(std::ffi::OsStr::to_str) {
+        Some("gz") => read_gz(source_file, &mut content)?,
+        Some("bz2") => read_bz2(source_file, &mut content)?,
+        _ => {
+            let mut source_file = source_file;
+            source_file.read_to_end(&mut content)?;
+        }
+    }
+    Ok(content)
+}
+
+/// Parse the content of a single patch into its headers, message, and diff.
+///
+/// Patches in mbox format, e.g. as output by `git format-patch`, are parsed with `git
+/// mailinfo`. Otherwise the headers are taken from the top of the patch description.
+pub(super) fn parse_patch(
+    stupid: &StupidContext,
+    content: Vec<u8>,
+) -> Result<(Headers, BString, BString)> {
+    if content.starts_with(b"From ") {
+        use std::io::{Seek, Write};
+
+        let mut mail_file = tempfile::tempfile()?;
+        mail_file.write_all(&content)?;
+        mail_file.rewind()?;
+        let (mailinfo, message, diff) = stupid.mailinfo(Some(mail_file), false, true)?;
+        let headers = Headers::parse_mailinfo(mailinfo.as_bstr()).unwrap_or_default();
+        Ok((headers, message, diff))
+    } else {
+        let (message, diff) = split_patch(content)?;
+        let (headers, message) = Headers::parse_message(message.as_ref())?;
+        Ok((headers, message, diff))
+    }
+}
+
 fn import_file<'repo>(
     stack: Stack<'repo>,
     matches: &clap::ArgMatches,
+    command_line: &[String],
     source_path: Option<&Path>,
     strip_level: Option<usize>,
+    patchname: Option<&PatchName>,
 ) -> Result<Stack<'repo>> {
-    let mut content = Vec::with_capacity(4096);
-    if let Some(source_path) = source_path {
-        let source_file = std::fs::File::open(source_path)?;
-        match source_path.extension().and_then(std::ffi::OsStr::to_str) {
-            Some("gz") => read_gz(source_file, &mut content)?,
-            Some("bz2") => read_bz2(source_file, &mut content)?,
-            _ => {
-                let mut source_file = source_file;
-                source_file.read_to_end(&mut content)?;
-            }
-        }
+    let content = if let Some(source_path) = source_path {
+        read_patch_file(source_path)?
     } else {
+        let mut content = Vec::with_capacity(4096);
         let stdin = std::io::stdin();
         stdin.lock().read_to_end(&mut content)?;
+        content
     };
 
     let (message, diff) = split_patch(content)?;
@@ -495,7 +1088,7 @@ fn import_file<'repo>(
     if let Some(message_id) = headers
         .message_id
         .as_ref()
-        .filter(|_| use_message_id(matches, &stack.repo.config_snapshot()))
+        .filter(|_| use_message_id(matches, &stack.config()))
     {
         if message.last() != Some(&b'\n') {
             message.push(b'\n');
@@ -508,10 +1101,17 @@ fn import_file<'repo>(
         message.push(b'\n');
     }
 
+    let name_source = if let Some(patchname) = patchname {
+        NameSource::Explicit(patchname)
+    } else {
+        NameSource::Derived(source_path)
+    };
+
     create_patch(
         stack,
         matches,
-        source_path,
+        command_line,
+        name_source,
         headers,
         message.as_bstr(),
         diff.as_bstr(),
@@ -519,32 +1119,36 @@ fn import_file<'repo>(
     )
 }
 
+/// How the name of an imported patch is to be determined.
+enum NameSource<'a> {
+    /// Derive the name from the patch's headers, the `--name` option, the patch file
+    /// name, or the patch message, in that order of precedence.
+    Derived(Option<&'a Path>),
+
+    /// Use the given name as-is.
+    Explicit(&'a PatchName),
+}
+
+#[allow(clippy::too_many_arguments)]
 fn create_patch<'repo>(
     stack: Stack<'repo>,
     matches: &clap::ArgMatches,
-    source_path: Option<&Path>,
+    command_line: &[String],
+    name_source: NameSource,
     headers: Headers,
     message: &BStr,
     diff: &BStr,
     strip_level: Option<usize>,
 ) -> Result<Stack<'repo>> {
-    let config = stack.repo.config_snapshot();
-
-    let Headers {
-        patchname,
-        author_name,
-        author_email,
-        author_date,
-        subject,
-        message_id: _message_id,
-    } = headers;
-
-    let message = if let Some(mut subject) = subject {
-        subject.push_str("\n\n");
-        subject.push_str(&message.to_str_lossy());
-        subject
-    } else {
-        message.to_str_lossy().to_string()
+    let config = stack.config();
+
+    let author = headers.author(stack.repo)?;
+    let message = headers.full_message(message);
+    let patchname = headers.patchname;
+
+    let (source_path, explicit_patchname) = match name_source {
+        NameSource::Derived(source_path) => (source_path, None),
+        NameSource::Explicit(patchname) => (None, Some(patchname)),
     };
 
     let patchname = if patchname.is_some() {
@@ -563,49 +1167,36 @@ fn create_patch<'repo>(
         patchname
     };
 
-    let name_len_limit = PatchName::get_length_limit(&config);
+    let mut name_template = NameTemplate::new(&stack)?;
+    let name_len_limit = name_template.len_limit();
 
-    let patchname = if let Some(patchname) = patchname {
+    let patchname = if let Some(patchname) = explicit_patchname {
+        patchname.clone()
+    } else if let Some(patchname) = patchname {
         PatchName::make(patchname, false, name_len_limit)
     } else {
-        PatchName::make(&message, true, name_len_limit)
+        name_template.make(&message, true)?
     };
 
     let ignore_flag = matches.get_flag("ignore");
     let replace_flag = matches.get_flag("replace");
 
-    let patchname = if !ignore_flag && !replace_flag {
-        let disallow_patchnames: Vec<&PatchName> = stack.all_patches().collect();
-        patchname.uniquify(&[], &disallow_patchnames)
+    let mut replaced_hidden = None;
+    let patchname = if explicit_patchname.is_some() && !ignore_flag && !replace_flag {
+        patchname
+    } else if !ignore_flag && !replace_flag {
+        let name_collision = crate::argset::resolve_name_collision(&config, matches)?;
+        let (patchname, replaced) =
+            patchname.uniquify_in_stack(&stack, &[], name_collision, name_len_limit)?;
+        replaced_hidden = replaced;
+        patchname
     } else if ignore_flag && stack.applied().contains(&patchname) {
-        eprintln!("info: ignoring already applied patch `{patchname}`");
+        Output::new(matches).info(&format!("ignoring already applied patch `{patchname}`"));
         return Ok(stack);
     } else {
         patchname
     };
 
-    let author_date = author_date.and_then(|date| gix::date::Time::parse_time(&date).ok());
-    let author = if let (Some(name), Some(email), Some(time)) =
-        (author_name.as_deref(), author_email.as_deref(), author_date)
-    {
-        gix::actor::Signature {
-            name: BString::from(name),
-            email: BString::from(email),
-            time,
-        }
-    } else {
-        let default_author = stack.repo.get_author()?;
-        if let (Some(name), Some(email)) = (author_name.as_deref(), author_email.as_deref()) {
-            gix::actor::Signature {
-                name: BString::from(name),
-                email: BString::from(email),
-                time: default_author.time,
-            }
-        } else {
-            default_author.to_owned()
-        }
-    };
-
     let stupid = stack.repo.stupid();
     let strip_level = strip_level.or_else(|| matches.get_one::<usize>("strip").copied());
 
@@ -614,22 +1205,55 @@ fn create_patch<'repo>(
     let applied_cleanly = if trimmed_diff.is_empty() || trimmed_diff == b"---" {
         true
     } else {
-        match stupid.apply_to_worktree_and_index(
+        let report = stupid.apply_to_worktree_and_index(
             diff,
-            matches.get_flag("reject"),
-            matches.get_flag("3way"),
-            strip_level,
-            matches
-                .get_one::<PathBuf>("directory")
-                .map(|path_buf| path_buf.as_path()),
-            matches.get_one::<usize>("context-lines").copied(),
-        ) {
-            Ok(None) => true,
-            Ok(Some(output)) => {
-                print_info_message(matches, &output);
-                false
-            }
-            Err(e) => return Err(e),
+            ApplyOptions::default()
+                .reject(matches.get_flag("reject"))
+                .threeway(matches.get_flag("3way"))
+                .strip_level(strip_level)
+                .directory(
+                    matches
+                        .get_one::<PathBuf>("directory")
+                        .map(|path_buf| path_buf.as_path()),
+                )
+                .context_lines(matches.get_one::<usize>("context-lines").copied())
+                .fuzz(matches.get_one::<usize>("fuzz").copied())
+                .ignore_whitespace(matches.get_flag("ignore-whitespace"))
+                .whitespace(crate::argset::get_one_str(matches, "whitespace"))
+                .skip_applied(matches.get_flag("skip-applied")),
+        )?;
+        if !report.skipped_hunks.is_empty() {
+            print_info_message(
+                matches,
+                &format!(
+                    "patch `{patchname}` skipped already applied hunks:\n  {}",
+                    report.skipped_hunks.join("\n  ")
+                ),
+            );
+        }
+        if !report.whitespace.is_empty() {
+            print_warning_message(
+                matches,
+                &format!(
+                    "patch `{patchname}` has whitespace errors:\n  {}",
+                    report.whitespace.join("\n  ")
+                ),
+            );
+        }
+        if !report.adjusted_hunks.is_empty() {
+            print_info_message(
+                matches,
+                &format!(
+                    "patch `{patchname}` applied with adjustments:\n  {}",
+                    report.adjusted_hunks.join("\n  ")
+                ),
+            );
+        }
+        if let Some(output) = report.rejects {
+            print_info_message(matches, &output);
+            false
+        } else {
+            true
         }
     };
 
@@ -660,13 +1284,19 @@ fn create_patch<'repo>(
 
     let stack = stack
         .setup_transaction()
-        .with_output_stream(get_color_stdout(matches))
+        .command_line(command_line)
+        .with_output(Output::new(matches))
         .use_index_and_worktree(false)
         .allow_conflicts(false)
         .transact(|trans| {
             if replace_flag && trans.unapplied().contains(&new_patchname) {
                 trans.delete_patches(|pn| pn == &new_patchname)?;
             }
+            if let Some((hidden, aside)) = &replaced_hidden {
+                if new_patchname.collides(hidden) {
+                    trans.rename_patch(hidden, aside)?;
+                }
+            }
             trans.new_applied(&new_patchname, commit_id)
         })
         .execute(&format!("import: {new_patchname}"))?;
@@ -712,6 +1342,8 @@ fn split_patch(content: Vec<u8>) -> Result<(BString, BString)> {
 mod test {
     use bstr::B;
 
+    #[cfg(feature = "import-url")]
+    use super::series_position;
     use super::{split_patch, stripname};
 
     #[test]
@@ -816,11 +1448,26 @@ mod test {
         let name = String::from("01-patch-name.patch.diff");
         assert_eq!(stripname(&name), "patch-name.patch");
     }
+
+    #[test]
+    #[cfg(feature = "import-url")]
+    fn series_positions() {
+        assert_eq!(series_position("[PATCH] single"), Some(0));
+        assert_eq!(series_position("[PATCH 2/3] second"), Some(2));
+        assert_eq!(series_position("[PATCH v2 10/12] tenth"), Some(10));
+        assert_eq!(series_position("[RFC PATCH 1/2] first"), Some(1));
+        assert_eq!(series_position("[patch 1/2] lowercase"), Some(1));
+        assert_eq!(series_position("[PATCH 0/3] cover letter"), None);
+        assert_eq!(series_position("Re: [PATCH 1/3] reply"), None);
+        assert_eq!(series_position("RE: [PATCH] reply"), None);
+        assert_eq!(series_position("no tag"), None);
+        assert_eq!(series_position("[GIT PULL] not a patch"), None);
+    }
 }
 
 #[derive(Default, Debug)]
-struct Headers {
-    patchname: Option<String>,
+pub(super) struct Headers {
+    pub(super) patchname: Option<String>,
     author_name: Option<String>,
     author_email: Option<String>,
     author_date: Option<String>,
@@ -829,6 +1476,54 @@ struct Headers {
 }
 
 impl Headers {
+    /// Get the patch author from the headers.
+    ///
+    /// The repository's author identity fills in for any missing author name, email,
+    /// or date.
+    pub(super) fn author(&self, repo: &gix::Repository) -> Result<gix::actor::Signature> {
+        let author_date = self
+            .author_date
+            .as_deref()
+            .and_then(|date| gix::date::Time::parse_time(date).ok());
+        let author = if let (Some(name), Some(email), Some(time)) = (
+            self.author_name.as_deref(),
+            self.author_email.as_deref(),
+            author_date,
+        ) {
+            gix::actor::Signature {
+                name: BString::from(name),
+                email: BString::from(email),
+                time,
+            }
+        } else {
+            let default_author = repo.get_author()?;
+            if let (Some(name), Some(email)) =
+                (self.author_name.as_deref(), self.author_email.as_deref())
+            {
+                gix::actor::Signature {
+                    name: BString::from(name),
+                    email: BString::from(email),
+                    time: default_author.time,
+                }
+            } else {
+                default_author.to_owned()
+            }
+        };
+        Ok(author)
+    }
+
+    /// Get the full commit message, prefixing `message` with the subject header, if any.
+    pub(super) fn full_message(&self, message: &BStr) -> String {
+        if let Some(subject) = self.subject.as_ref() {
+            let mut full_message = subject.clone();
+            full_message.push_str("\n\n");
+            full_message.push_str(&message.to_str_lossy());
+            full_message
+        } else {
+            message.to_str_lossy().to_string()
+        }
+    }
+
     fn parse_mailinfo(headers: &BStr) -> Option<Headers> {
         let mut author_name = None;
         let mut author_email = None;
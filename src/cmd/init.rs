@@ -15,6 +15,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "init",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -29,7 +30,9 @@ fn make() -> clap::Command {
              state for the branch to the repository. Theses stack metadata commits are \
              tracked by the `refs/stacks/<branch>` reference. Updated stack state is \
              committed by each StGit command that modifies the stack. StGit users do \
-             not have to do anything with the `refs/stacks/<branch>` ref directly.\n\
+             not have to do anything with the `refs/stacks/<branch>` ref directly. \
+             Tools that need the name of the reference should get it with \
+             `stg ref --stack`.\n\
              \n\
              Some StGit commands, such as `stg new` and `stg uncommit`, will \
              automatically initialize the stack, so it is often not necessary to \
@@ -45,12 +48,13 @@ fn make() -> clap::Command {
         .arg(argset::branch_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
-    Stack::from_branch_locator(
-        &repo,
-        matches.get_one::<BranchLocator>("branch"),
-        InitializationPolicy::MustInitialize,
-    )?;
+    let branch = if let Some(branch_loc) = matches.get_one::<BranchLocator>("branch") {
+        branch_loc.resolve_for_update(&repo)?
+    } else {
+        repo.get_current_branch()?
+    };
+    Stack::from_branch(&repo, branch, InitializationPolicy::MustInitialize)?;
     Ok(())
 }
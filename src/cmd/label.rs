@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg label` implementation.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::RepositoryExtended,
+    output::Output,
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "label",
+    category: super::CommandCategory::StackManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Manage patch labels")
+        .long_about(
+            "Add, remove, and list patch labels.\n\
+             \n\
+             Labels group patches into named topics within a stack. A patch may have \
+             any number of labels and a label may be applied to any number of patches. \
+             Labels follow their patches when patches are pushed, popped, or renamed, \
+             and are dropped when their patches are deleted.\n\
+             \n\
+             Wherever patch ranges are accepted, '%{<label>}' may be used to refer to \
+             all patches having the label, in stack order. For example, 'stg float \
+             %{net}' floats all patches labeled 'net' to the top of the stack.",
+        )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg label add net p1..p3",
+                "Label the patches `p1` through `p3` with `net`",
+            ),
+            (
+                "stg float '%{net}'",
+                "Float the patches labeled `net` to the top of the stack",
+            ),
+        ]))
+        .subcommand_required(true)
+        .subcommand(
+            clap::Command::new("add")
+                .about("Add a label to patches")
+                .arg(label_arg())
+                .arg(
+                    Arg::new("patchranges")
+                        .help("Patches to label")
+                        .value_name("patch")
+                        .num_args(1..)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(PatchRange))
+                        .required(true),
+                )
+                .arg(argset::branch_arg()),
+        )
+        .subcommand(
+            clap::Command::new("remove")
+                .about("Remove a label from patches")
+                .long_about(
+                    "Remove a label from patches. If no patches are given, the label is \
+                     removed from all patches having it.",
+                )
+                .arg(label_arg())
+                .arg(
+                    Arg::new("patchranges")
+                        .help("Patches to unlabel")
+                        .value_name("patch")
+                        .num_args(1..)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(PatchRange)),
+                )
+                .arg(argset::branch_arg()),
+        )
+        .subcommand(
+            clap::Command::new("list")
+                .about("List labels")
+                .long_about(
+                    "List labels along with the patches having each label. If patches \
+                     are given, the labels of each of the given patches are listed \
+                     instead.",
+                )
+                .arg(
+                    Arg::new("patchranges")
+                        .help("Patches to list labels of")
+                        .value_name("patch")
+                        .num_args(1..)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(PatchRange)),
+                )
+                .arg(argset::branch_arg()),
+        )
+}
+
+fn label_arg() -> Arg {
+    Arg::new("label")
+        .help("Label name")
+        .required(true)
+        .value_parser(|s: &str| patchrange::check_label(s).map(|()| s.to_string()))
+}
+
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let (subcommand, sub_matches) = matches
+        .subcommand()
+        .expect("clap ensures a subcommand is provided");
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        sub_matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+
+    let patchnames: Option<Vec<PatchName>> = sub_matches
+        .get_many::<PatchRange>("patchranges")
+        .map(|range_specs| patchrange::resolve_names(&stack, range_specs, RangeConstraint::All))
+        .transpose()?;
+
+    match subcommand {
+        "add" => {
+            let label = sub_matches
+                .get_one::<String>("label")
+                .expect("required argument");
+            let patchnames = patchnames.expect("required argument");
+            stack
+                .setup_transaction()
+                .command_line(command_line)
+                .allow_conflicts(true)
+                .with_output(Output::new(matches))
+                .transact(|trans| {
+                    trans.add_label(label, &patchnames);
+                    Ok(())
+                })
+                .execute(&format!("label add {label}"))?;
+        }
+        "remove" => {
+            let label = sub_matches
+                .get_one::<String>("label")
+                .expect("required argument");
+            let labeled: Vec<PatchName> =
+                stack.patches_labeled(label).into_iter().cloned().collect();
+            if labeled.is_empty() {
+                return Err(anyhow!("no patches are labeled `{label}`"));
+            }
+            let patchnames = patchnames.unwrap_or(labeled);
+            stack
+                .setup_transaction()
+                .command_line(command_line)
+                .allow_conflicts(true)
+                .with_output(Output::new(matches))
+                .transact(|trans| {
+                    trans.remove_label(label, &patchnames);
+                    Ok(())
+                })
+                .execute(&format!("label remove {label}"))?;
+        }
+        "list" => {
+            let stdout = std::io::stdout();
+            let mut stdout = stdout.lock();
+            if let Some(patchnames) = patchnames {
+                for patchname in &patchnames {
+                    write!(stdout, "{patchname}:")?;
+                    for label in stack.labels().get(patchname).into_iter().flatten() {
+                        write!(stdout, " {label}")?;
+                    }
+                    writeln!(stdout)?;
+                }
+            } else {
+                let mut labels: Vec<&String> = stack.labels().values().flatten().collect();
+                labels.sort();
+                labels.dedup();
+                for label in labels {
+                    write!(stdout, "{label}:")?;
+                    for patchname in stack.patches_labeled(label) {
+                        write!(stdout, " {patchname}")?;
+                    }
+                    writeln!(stdout)?;
+                }
+            }
+        }
+        _ => panic!("valid subcommand is expected"),
+    }
+
+    Ok(())
+}
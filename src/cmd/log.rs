@@ -2,21 +2,25 @@
 
 //! `stg log` implementation.
 
+use std::{io::Write, path::PathBuf};
+
 use anyhow::{anyhow, Result};
-use clap::{Arg, ArgMatches};
+use clap::{Arg, ArgGroup, ArgMatches};
+use gix::prelude::ObjectIdExt;
 
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::RepositoryExtended,
     patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackAccess},
-    stupid::Stupid,
+    stack::{history, InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::{LogOptions, Stupid},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "log",
     category: super::CommandCategory::PatchInspection,
+    read_only: true,
     make,
     run,
 };
@@ -32,13 +36,36 @@ fn make() -> clap::Command {
              through historical stack states. The 'stg reset' command may be used to \
              reset the stack directly to a historic state.\n\
              \n\
+             The '--since' and '--until' options limit the output to changes made \
+             after or before the given date. Dates may be given in any of the formats \
+             accepted by git, including relative dates such as '2 days ago' or \
+             'yesterday'.\n\
+             \n\
+             With '--compare' or '--range-diff', the given patch is compared to its \
+             version in a historical stack state instead of showing the stack log. \
+             The old and new versions of the patch are shown using 'git range-diff', \
+             i.e. as the changes to the patch's diff and message. Renames of the \
+             patch since the historical state are followed.\n\
+             \n\
+             With '--export', the stack log entries are written to <file> in JSON \
+             format, from oldest to newest, along with the command line that \
+             produced each entry and the resulting patch commit and tree ids. \
+             Combined with '--number', only the newest entries are exported. The \
+             exported operations may be re-executed against another repository with \
+             'stg replay', e.g. to reproduce a problem.\n\
+             \n\
              The '--clear' option may be used to delete the stack's change history. \
              Undo and redo are unavailable on a stack without change history. Clearing \
              the stack state history cannot be undone.",
         )
         .override_usage(super::make_usage(
             "stg log",
-            &["[OPTIONS] [--] [patch]...", "--clear"],
+            &[
+                "[OPTIONS] [--] [patch]...",
+                "(--compare <state> | --range-diff <n>) [-O <option>] <patch>",
+                "--export <file> [-n <n>]",
+                "--clear",
+            ],
         ))
         .arg(
             Arg::new("patchranges-all")
@@ -64,6 +91,22 @@ fn make() -> clap::Command {
                 .value_name("n")
                 .value_parser(argset::parse_usize),
         )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .help("Show changes more recent than <date>")
+                .value_name("date")
+                .value_parser(argset::parse_time)
+                .value_hint(clap::ValueHint::Other),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .help("Show changes older than <date>")
+                .value_name("date")
+                .value_parser(argset::parse_time)
+                .value_hint(clap::ValueHint::Other),
+        )
         .arg(
             Arg::new("full")
                 .long("full")
@@ -71,13 +114,77 @@ fn make() -> clap::Command {
                 .help("Show using full commit log format")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("oneline")
+                .long("oneline")
+                .help("Show each stack change on a single line")
+                .long_help(
+                    "Show each stack change on a single line with the abbreviated \
+                     stack log commit id, the operation description, and the relative \
+                     time of the change.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["diff", "full"]),
+        )
         .arg(
             Arg::new("graphical")
                 .long("graphical")
                 .short('g')
                 .help("Run gitk instead of printing to stdout")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["diff", "number", "full"]),
+                .conflicts_with_all(["diff", "number", "since", "until", "full", "oneline"]),
+        )
+        .arg(
+            Arg::new("compare")
+                .long("compare")
+                .help("Compare the patch to its version in stack state <state>")
+                .long_help(
+                    "Compare the patch to its version in the stack state <state>. \
+                     The state is specified with a commit id from the stack log, \
+                     which may be viewed with 'stg log'.",
+                )
+                .value_name("state")
+                .value_hint(clap::ValueHint::Other),
+        )
+        .arg(
+            Arg::new("range-diff")
+                .long("range-diff")
+                .help("Compare the patch to its version <n> stack log entries ago")
+                .value_name("n")
+                .value_parser(argset::parse_usize),
+        )
+        .group(
+            ArgGroup::new("compare-mode")
+                .args(["compare", "range-diff"])
+                .requires("patchranges-all")
+                .conflicts_with_all([
+                    "diff",
+                    "number",
+                    "since",
+                    "until",
+                    "full",
+                    "oneline",
+                    "graphical",
+                ]),
+        )
+        .arg(argset::diff_opts_arg().requires("compare-mode"))
+        .arg(
+            Arg::new("export")
+                .long("export")
+                .help("Export the stack operations to <file> for 'stg replay'")
+                .value_name("file")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath)
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "diff",
+                    "since",
+                    "until",
+                    "full",
+                    "oneline",
+                    "graphical",
+                    "compare-mode",
+                ]),
         )
         .arg(
             Arg::new("clear")
@@ -85,11 +192,22 @@ fn make() -> clap::Command {
                 .help("Clear the stack history")
                 // .exclusive(true),
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["patchranges-all", "diff", "number", "full", "graphical"]),
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "diff",
+                    "number",
+                    "since",
+                    "until",
+                    "full",
+                    "oneline",
+                    "graphical",
+                    "compare-mode",
+                    "export",
+                ]),
         )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let mut stack = Stack::from_branch_locator(
         &repo,
@@ -99,6 +217,10 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     if matches.get_flag("clear") {
         stack.clear_state_log("clear log")
+    } else if let Some(path) = matches.get_one::<PathBuf>("export") {
+        super::replay::export(&stack, matches.get_one::<usize>("number").copied(), path)
+    } else if let Some(state_id) = compare_state_id(&stack, matches)? {
+        compare_patch(&stack, state_id, matches)
     } else {
         let pathspecs: Option<Vec<String>> =
             if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
@@ -126,15 +248,107 @@ fn run(matches: &ArgMatches) -> Result<()> {
         if matches.get_flag("graphical") {
             stupid.gitk(simplified_parent_id, pathspecs)
         } else {
-            let num_commits = matches.get_one::<usize>("number").copied();
+            let mut log_options = LogOptions::default();
+            log_options
+                .max_count(matches.get_one::<usize>("number").copied())
+                .since(matches.get_one::<gix::date::Time>("since").copied())
+                .until(matches.get_one::<gix::date::Time>("until").copied());
+            let pretty_format = if matches.get_flag("full") {
+                None
+            } else {
+                Some(pretty_format(matches.get_flag("oneline")))
+            };
             stupid.log(
                 simplified_parent_id,
                 pathspecs,
-                num_commits,
+                &log_options,
                 crate::color::use_color(matches),
-                matches.get_flag("full"),
+                pretty_format,
                 matches.get_flag("diff"),
             )
         }
     }
 }
+
+/// Get the stack state commit selected with `--compare` or `--range-diff`, if any.
+fn compare_state_id(stack: &Stack, matches: &ArgMatches) -> Result<Option<gix::ObjectId>> {
+    if let Some(committish) = argset::get_one_str(matches, "compare") {
+        let commit_id = stack
+            .repo
+            .rev_parse_single(committish)
+            .map_err(|_| anyhow!("invalid committish `{committish}`"))?
+            .object()?
+            .peel_tags_to_end()?
+            .try_into_commit()
+            .map_err(|_| anyhow!("target `{committish}` is not a commit"))?
+            .id;
+        Ok(Some(commit_id))
+    } else if let Some(n) = matches.get_one::<usize>("range-diff").copied() {
+        let entry = history::stack_log(stack)?
+            .nth(n)
+            .transpose()?
+            .ok_or_else(|| anyhow!("the stack log has fewer than {} entries", n + 1))?;
+        Ok(Some(entry.simplified_id().detach()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Show how a single patch changed since the given historical stack state.
+fn compare_patch(stack: &Stack, state_id: gix::ObjectId, matches: &ArgMatches) -> Result<()> {
+    let range_specs = matches
+        .get_many::<PatchRange>("patchranges-all")
+        .expect("compare mode requires patch argument");
+    let patchnames = patchrange::resolve_names(stack, range_specs, RangeConstraint::All)?;
+    let [patchname] = patchnames.as_slice() else {
+        return Err(anyhow!("exactly one patch must be given to compare"));
+    };
+
+    let repo = stack.repo;
+    let old = history::patch_at_state(stack, patchname, state_id)?.ok_or_else(|| {
+        anyhow!(
+            "patch `{patchname}` does not exist in stack state `{}`",
+            state_id.attach(repo).shorten_or_id()
+        )
+    })?;
+    let new_commit_id = stack.get_patch(patchname).commit.id;
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    if old.patchname == *patchname {
+        writeln!(
+            stdout,
+            "Since {}: {}",
+            old.entry.simplified_id().shorten_or_id(),
+            old.entry.summary()
+        )?;
+    } else {
+        writeln!(
+            stdout,
+            "Since {}: {} (as `{}`)",
+            old.entry.simplified_id().shorten_or_id(),
+            old.entry.summary(),
+            old.patchname
+        )?;
+    }
+    stdout.flush()?;
+
+    repo.stupid().range_diff_commits(
+        old.commit.id,
+        new_commit_id,
+        crate::color::use_color(matches),
+        argset::get_diff_opts(matches, &stack.config(), false, false),
+    )
+}
+
+/// Get the `git log --pretty` format for stack log entries.
+///
+/// Commit ids are abbreviated according to `core.abbrev`. In the default, multi-column
+/// format, the date column is padded to a fixed width so that the descriptions line up.
+pub(super) fn pretty_format(oneline: bool) -> &'static str {
+    if oneline {
+        "%C(auto)%h %s %C(auto,blue)(%ar)%C(auto)"
+    } else {
+        "%C(auto)%h   %C(auto,blue)%<(31)%aD%C(auto)   %s"
+    }
+}
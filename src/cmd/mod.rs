@@ -7,8 +7,16 @@
 
 use clap::builder::StyledStr;
 
+use crate::{
+    ext::{ContextError, RepositoryExtended, BARE_REPOSITORY_COMMANDS},
+    stupid::program::{set_index_locking, IndexLocking},
+};
+
+pub(crate) mod add;
+pub(crate) mod base;
 pub(crate) mod branch;
 pub(crate) mod clean;
+pub(crate) mod clone;
 pub(crate) mod commit;
 pub(crate) mod completion;
 pub(crate) mod delete;
@@ -19,12 +27,16 @@ pub(crate) mod export;
 pub(crate) mod files;
 pub(crate) mod float;
 pub(crate) mod fold;
+pub(crate) mod foreach;
 pub(crate) mod goto;
+pub(crate) mod grep;
 pub(crate) mod hide;
 pub(crate) mod id;
 pub(crate) mod import;
 pub(crate) mod init;
+pub(crate) mod label;
 pub(crate) mod log;
+pub(crate) mod mv;
 pub(crate) mod name;
 pub(crate) mod new;
 pub(crate) mod next;
@@ -36,10 +48,14 @@ pub(crate) mod pull;
 pub(crate) mod push;
 pub(crate) mod rebase;
 pub(crate) mod redo;
+pub(crate) mod refname;
 pub(crate) mod refresh;
 pub(crate) mod rename;
 pub(crate) mod repair;
+pub(crate) mod replay;
 pub(crate) mod reset;
+pub(crate) mod resolved;
+pub(crate) mod rm;
 pub(crate) mod series;
 pub(crate) mod show;
 pub(crate) mod sink;
@@ -72,11 +88,22 @@ pub(crate) struct StGitCommand {
     /// Category the command belongs in.
     pub category: CommandCategory,
 
+    /// Whether the command modifies neither the index nor the work tree.
+    ///
+    /// Read-only commands avoid taking any locks on the index, such that they may be
+    /// used while another process holds the index lock, e.g. during a `git merge` or
+    /// `git rebase`.
+    pub read_only: bool,
+
     /// Function pointer for making the [`clap::Command`] for the StGit subcommand.
     pub make: fn() -> clap::Command,
 
     /// Function pointer for running the StGit subcommand.
-    pub run: fn(&clap::ArgMatches) -> anyhow::Result<()>,
+    ///
+    /// Besides the subcommand's argument matches, the command line is passed such that
+    /// it may be recorded in the stack states produced by the command. The command line
+    /// begins with the subcommand name and excludes any top-level options.
+    pub run: fn(&clap::ArgMatches, &[String]) -> anyhow::Result<()>,
 }
 
 /// Builtin [`StGitCommand`]'s.
@@ -84,8 +111,11 @@ pub(crate) struct StGitCommand {
 /// This is used in [`crate::main`] for command line argument parsing and eventual
 /// dispatch of a subcommand.
 pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
+    add::STGIT_COMMAND,
+    base::STGIT_COMMAND,
     branch::STGIT_COMMAND,
     clean::STGIT_COMMAND,
+    clone::STGIT_COMMAND,
     commit::STGIT_COMMAND,
     completion::STGIT_COMMAND,
     delete::STGIT_COMMAND,
@@ -96,12 +126,16 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     files::STGIT_COMMAND,
     float::STGIT_COMMAND,
     fold::STGIT_COMMAND,
+    foreach::STGIT_COMMAND,
     goto::STGIT_COMMAND,
+    grep::STGIT_COMMAND,
     hide::STGIT_COMMAND,
     id::STGIT_COMMAND,
     import::STGIT_COMMAND,
     init::STGIT_COMMAND,
+    label::STGIT_COMMAND,
     log::STGIT_COMMAND,
+    mv::STGIT_COMMAND,
     name::STGIT_COMMAND,
     new::STGIT_COMMAND,
     next::STGIT_COMMAND,
@@ -113,10 +147,14 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     push::STGIT_COMMAND,
     rebase::STGIT_COMMAND,
     redo::STGIT_COMMAND,
+    refname::STGIT_COMMAND,
     refresh::STGIT_COMMAND,
     rename::STGIT_COMMAND,
     repair::STGIT_COMMAND,
+    replay::STGIT_COMMAND,
     reset::STGIT_COMMAND,
+    resolved::STGIT_COMMAND,
+    rm::STGIT_COMMAND,
     series::STGIT_COMMAND,
     show::STGIT_COMMAND,
     sink::STGIT_COMMAND,
@@ -130,6 +168,33 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     version::STGIT_COMMAND,
 ];
 
+/// Names of commands that do not operate on a git repository.
+const REPOSITORYLESS_COMMANDS: &[&str] = &["clone", "completion", "version"];
+
+/// Check that the repository context is suitable for running the given command.
+///
+/// Commands fail early, with a specific error, when run outside of a git repository or
+/// when a work tree is required but the repository is bare. Finer-grained context
+/// errors, e.g. for a detached HEAD, occur when the command determines its branch.
+///
+/// Git subprocesses of read-only commands do not take optional locks on the index,
+/// while those of other commands wait for any other process to release the index lock.
+pub(crate) fn check_repository_context(command: &StGitCommand) -> anyhow::Result<()> {
+    if REPOSITORYLESS_COMMANDS.contains(&command.name) {
+        return Ok(());
+    }
+    let repo = gix::Repository::open()?;
+    if repo.work_dir().is_none() && !BARE_REPOSITORY_COMMANDS.contains(&command.name) {
+        return Err(ContextError::BareRepository(command.name.to_string()).into());
+    }
+    set_index_locking(if command.read_only {
+        IndexLocking::NoOptionalLocks
+    } else {
+        IndexLocking::Wait(repo.index_lock_timeout())
+    });
+    Ok(())
+}
+
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum Error {
     #[error("{0}")]
@@ -159,3 +224,27 @@ pub(crate) fn make_usage(command_name: &str, usages: &[&str]) -> clap::builder::
     }
     s
 }
+
+/// Make the examples section appended to a command's long help.
+///
+/// Each example is a command line followed by a one line description of its effect.
+/// The section is also rendered into the command's man page by `stg completion man`,
+/// which relies on this layout.
+pub(crate) fn make_examples(examples: &[(&str, &str)]) -> StyledStr {
+    use std::fmt::Write as _;
+    let mut s = StyledStr::new();
+    let header = STYLES.get_header();
+    let literal = STYLES.get_literal();
+    write!(s, "{}Examples:{}", header.render(), header.render_reset()).unwrap();
+    for (i, (command_line, description)) in examples.iter().enumerate() {
+        let sep = if i == 0 { "" } else { "\n" };
+        write!(
+            s,
+            "{sep}\n  {}{command_line}{}\n          {description}",
+            literal.render(),
+            literal.render_reset()
+        )
+        .unwrap();
+    }
+    s
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg mv` implementation.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BString, ByteSlice};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    ext::RepositoryExtended,
+    index::{join_path, IndexEditor},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "mv",
+    category: super::CommandCategory::PatchManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Move or rename files in the index and work tree")
+        .long_about(
+            "Move or rename a tracked file or directory, in both the work tree and \
+             the index.\n\
+             \n\
+             When the destination is an existing directory, the sources are moved \
+             into that directory. Otherwise, exactly one source must be given, which \
+             is renamed to the destination.",
+        )
+        .override_usage(super::make_usage(
+            "stg mv",
+            &[
+                "[OPTIONS] <source> <destination>",
+                "[OPTIONS] <source>... <directory>",
+            ],
+        ))
+        .arg(
+            Arg::new("paths")
+                .help("Sources followed by the destination")
+                .value_name("path")
+                .num_args(2..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::AnyPath)
+                .required(true),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Overwrite an existing destination file")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let mut editor = IndexEditor::open(&repo)?;
+    let force = matches.get_flag("force");
+
+    let mut paths = matches
+        .get_many::<PathBuf>("paths")
+        .expect("paths are required")
+        .map(|path| editor.root_relative_path(path))
+        .collect::<Result<Vec<BString>>>()?;
+    let destination = paths.pop().expect("at least two paths are required");
+    let sources = paths;
+
+    let into_dir = editor.work_path(destination.as_bstr()).is_dir();
+    if !into_dir && sources.len() > 1 {
+        return Err(anyhow!(
+            "destination `{}` is not a directory",
+            destination.to_str_lossy()
+        ));
+    }
+
+    let mut moves: Vec<(BString, BString)> = Vec::new();
+    for src in sources {
+        if src.is_empty() {
+            return Err(anyhow!("cannot move the work tree root"));
+        }
+        let dst = if into_dir {
+            let name = src.rsplit_str(b"/").next().expect("path is not empty");
+            join_path(destination.as_bstr(), name.as_bstr())
+        } else {
+            destination.clone()
+        };
+
+        let tracked = editor.tracked_paths(src.as_bstr());
+        if tracked.is_empty() {
+            return Err(anyhow!(
+                "`{}` is not under version control",
+                src.to_str_lossy()
+            ));
+        }
+        if let Some(conflicted) = tracked
+            .iter()
+            .find(|path| editor.is_conflicted(path.as_bstr()))
+        {
+            return Err(anyhow!("`{}` is in conflict", conflicted.to_str_lossy()));
+        }
+        if dst == src || dst.starts_with(format!("{src}/").as_bytes()) {
+            return Err(anyhow!("cannot move `{}` into itself", src.to_str_lossy()));
+        }
+
+        let dst_work_path = editor.work_path(dst.as_bstr());
+        let src_is_dir = editor.work_path(src.as_bstr()).is_dir();
+        if let Ok(meta) = dst_work_path.symlink_metadata() {
+            if !force || meta.is_dir() || src_is_dir {
+                return Err(anyhow!(
+                    "destination `{}` already exists",
+                    dst.to_str_lossy()
+                ));
+            }
+        } else if !editor.tracked_paths(dst.as_bstr()).is_empty() && !force {
+            return Err(anyhow!("destination `{}` is tracked", dst.to_str_lossy()));
+        }
+        moves.push((src, dst));
+    }
+
+    for (src, dst) in &moves {
+        let src_work_path = editor.work_path(src.as_bstr());
+        let dst_work_path = editor.work_path(dst.as_bstr());
+        if let Some(parent) = dst_work_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(&src_work_path, &dst_work_path).with_context(|| {
+            format!(
+                "renaming `{}` to `{}`",
+                src.to_str_lossy(),
+                dst.to_str_lossy()
+            )
+        })?;
+        editor.rename(src.as_bstr(), dst.as_bstr())?;
+    }
+    editor.write()?;
+
+    for (src, dst) in &moves {
+        crate::print_info_message(
+            matches,
+            &format!("Moved `{}` to `{}`", src.to_str_lossy(), dst.to_str_lossy()),
+        );
+    }
+    Ok(())
+}
@@ -19,6 +19,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "name",
     category: super::CommandCategory::PatchInspection,
+    read_only: true,
     make,
     run,
 };
@@ -58,7 +59,7 @@ fn make() -> clap::Command {
         )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
 
     let stack = Stack::from_branch_locator(
@@ -82,7 +83,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(anyhow!("patch name not found for revision `{oid}`"));
     };
 
-    let mut stdout = crate::color::get_color_stdout(matches);
+    let mut stdout = crate::output::Output::new(matches).data();
     let mut color_spec = termcolor::ColorSpec::new();
     color_spec.set_bold(true);
     stdout.set_color(&color_spec)?;
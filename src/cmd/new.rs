@@ -2,23 +2,33 @@
 
 //! `stg new` implementation.
 
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
 use clap::{Arg, ArgGroup, ArgMatches};
 
-use super::refresh;
+use super::{refresh, squash};
 use crate::{
-    color::get_color_stdout,
-    ext::{RepositoryExtended, SignatureExtended},
-    patch::{patchedit, PatchName},
+    argset,
+    ext::{CommitExtended, RepositoryExtended, SignatureExtended},
+    hook,
+    output::Output,
+    patch::{patchedit, PatchLocator, PatchName, Subject},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
-    stupid::Stupid,
+    stupid::{ApplyOptions, Stupid},
 };
 
+/// Name of the file in the git directory where a failed `--from-diff` is reported.
+const REJECTS_FILE_NAME: &str = "stgit-new-rejects";
+
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "new",
     category: super::CommandCategory::PatchManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -47,13 +57,20 @@ fn make() -> clap::Command {
              An editor will be launched to edit the commit message to be used for the \
              patch, unless the '--message' flag already specified one. The \
              'patchdescr.tmpl' template file (if available) is used to pre-fill the \
-             editor.",
+             editor.\n\
+             \n\
+             With '--from-diff', the given diff is applied to the index and work tree \
+             and becomes the content of the new patch. A three-way merge is attempted \
+             where the diff does not apply directly. If the diff does not apply, no \
+             patch is created, the index and work tree are left unchanged, and the \
+             output of 'git apply' is saved to '.git/stgit-new-rejects'.",
         )
         .override_usage(super::make_usage(
             "stg new",
             &[
                 "[OPTIONS] [patchname] [-- <path>...]",
                 "[OPTIONS] [--name <patchname>] [-- <path>...]",
+                "[OPTIONS] --from-diff <file> [patchname]",
             ],
         ))
         .arg(
@@ -90,6 +107,32 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PatchName))
                 .conflicts_with("patchname"),
         )
+        .arg(
+            Arg::new("fixup")
+                .long("fixup")
+                .help("Create a fixup patch for <target>")
+                .long_help(
+                    "Create a patch whose changes are intended to be squashed into the \
+                     <target> patch with 'stg squash --fixups'. The new patch is named \
+                     \"fixup-<n>-<target>\" and its message records the target with a \
+                     \"Stg-Fixup: <target>\" trailer. Unless a message is provided, the \
+                     message is \"fixup! <subject>\", using the target patch's subject, \
+                     and no editor is launched.",
+                )
+                .value_name("target")
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all(["patchname", "name", "save-template"]),
+        )
+        .arg(
+            Arg::new("from-diff")
+                .long("from-diff")
+                .help("Use the diff in <file> as the new patch's content")
+                .value_name("file")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath)
+                .conflicts_with_all(["pathspecs", "refresh", "staged", "save-template"]),
+        )
         .next_help_heading("Refresh Options")
         .arg(
             Arg::new("refresh")
@@ -137,6 +180,26 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with("index"),
         )
+        .arg(
+            Arg::new("staged")
+                .long("staged")
+                .help("Refresh new patch with only the staged changes")
+                .long_help(
+                    "Refresh the new patch with the changes staged in the index, \
+                     as with '--refresh --index'. Unstaged changes in the work tree \
+                     are left as-is. It is an error for there to be no staged \
+                     changes unless '--allow-empty' is also given.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["refresh", "pathspecs", "save-template"]),
+        )
+        .arg(
+            Arg::new("allow-empty")
+                .long("allow-empty")
+                .help("Allow '--staged' with no staged changes")
+                .action(clap::ArgAction::SetTrue)
+                .requires("staged"),
+        )
         .arg(
             Arg::new("submodules")
                 .long("submodules")
@@ -153,10 +216,14 @@ fn make() -> clap::Command {
         )
         .group(ArgGroup::new("submodule-group").args(["submodules", "no-submodules"]));
     patchedit::add_args(app, true, true)
+        .arg(argset::date_arg())
+        .args(argset::sign_args())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
-    let repo = gix::Repository::open()?;
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let fixed_date = argset::get_fixed_date(matches);
+    let mut repo = gix::Repository::open()?;
+    argset::set_commit_signing(&mut repo, matches)?;
     let stack = Stack::current(&repo, InitializationPolicy::AutoInitialize)?;
     let stupid = repo.stupid();
 
@@ -165,7 +232,14 @@ fn run(matches: &ArgMatches) -> Result<()> {
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
 
-    let patchname = if let Some(patchname) = matches
+    let fixup_target = matches
+        .get_one::<PatchLocator>("fixup")
+        .map(|target_loc| target_loc.resolve_name(&stack))
+        .transpose()?;
+
+    let patchname = if let Some(target) = fixup_target.as_ref() {
+        Ok(Some(fixup_patchname(&stack, target)))
+    } else if let Some(patchname) = matches
         .get_one::<PatchName>("patchname")
         .or_else(|| matches.get_one::<PatchName>("name"))
         .cloned()
@@ -179,27 +253,73 @@ fn run(matches: &ArgMatches) -> Result<()> {
         Ok(None)
     }?;
 
-    let is_refreshing = matches.get_flag("refresh") || matches.contains_id("pathspecs");
+    let is_staged = matches.get_flag("staged");
+    let is_refreshing =
+        is_staged || matches.get_flag("refresh") || matches.contains_id("pathspecs");
 
-    let tree_id = if is_refreshing {
-        refresh::assemble_refresh_tree(&stack, matches, None)?
+    if is_staged
+        && !matches.get_flag("allow-empty")
+        && !stupid.diff_index_quiet(stack.get_branch_head().tree_id()?.detach())?
+    {
+        return Err(anyhow!(
+            "no changes staged in the index; use `--allow-empty` to create an empty patch"
+        ));
+    }
+
+    let diff_path = matches.get_one::<PathBuf>("from-diff");
+    let head_tree_id = stack.get_branch_head().tree_id()?.detach();
+    let tree_id = if let Some(diff_path) = diff_path {
+        statuses.check_index_and_worktree_clean()?;
+        apply_diff_file(&stack, matches, diff_path)?
+    } else if is_refreshing {
+        let use_index = is_staged || matches.get_flag("index");
+        refresh::assemble_refresh_tree(&stack, matches, use_index, None)?
     } else {
-        stack.get_branch_head().tree_id()?.detach()
+        // Like `git commit --allow-empty`, the hook runs even though the new patch is
+        // empty. Refreshing runs the hook as part of assembling the refresh tree.
+        if !matches.get_flag("no-verify") {
+            hook::run_pre_commit_hook(&repo, matches.get_flag("edit"))?;
+        }
+        head_tree_id
     };
 
     let parent_id = stack.get_branch_head().id;
 
-    let (patchname, commit_id) = match patchedit::EditBuilder::default()
+    let mut default_author = repo.get_author()?;
+    if let Some(date) = fixed_date {
+        default_author.time = date;
+    }
+    let mut edit_builder = patchedit::EditBuilder::default()
         .allow_autosign(true)
         .allow_diff_edit(false)
-        .allow_implicit_edit(true)
+        .allow_implicit_edit(fixup_target.is_none())
         .allow_template_save(!is_refreshing)
         .original_patchname(patchname.as_ref())
-        .default_author(repo.get_author()?.override_author(matches))
+        .default_author(default_author.override_author(matches))
         .override_tree_id(tree_id)
         .override_parent_id(parent_id)
-        .edit(&stack, &repo, matches)?
-    {
+        .fixed_date(fixed_date);
+
+    if let Some(target) = fixup_target.as_ref() {
+        let subject = Subject::new(
+            &stack.get_patch_commit(target).message_ex().decode()?,
+            target,
+        )
+        .text;
+        edit_builder = edit_builder
+            .default_message(format!("fixup! {subject}\n"))
+            .extra_trailer(squash::FIXUP_TRAILER, target.to_string());
+    }
+
+    let outcome = match edit_builder.edit(&stack, &repo, matches) {
+        Err(e) if diff_path.is_some() => {
+            stupid.read_tree_checkout_hard(head_tree_id)?;
+            return Err(e);
+        }
+        outcome => outcome?,
+    };
+
+    let (patchname, commit_id) = match outcome {
         patchedit::EditOutcome::TemplateSaved(_) => return Ok(()),
         patchedit::EditOutcome::Edited {
             new_patchname,
@@ -220,8 +340,56 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
-        .with_output_stream(get_color_stdout(matches))
+        .command_line(command_line)
+        .fixed_date(fixed_date)
+        .with_output(Output::new(matches))
         .transact(|trans| trans.new_applied(&patchname, commit_id))
         .execute(&format!("new: {patchname}"))?;
     Ok(())
 }
+
+/// Apply the diff in the file at `path` to the index and work tree.
+///
+/// The id of the resulting index tree is returned. If the diff does not apply, the
+/// index and work tree are reset to the branch head and the output of `git apply` is
+/// saved to [`REJECTS_FILE_NAME`] in the git directory.
+fn apply_diff_file(stack: &Stack, matches: &ArgMatches, path: &Path) -> Result<gix::ObjectId> {
+    let diff = std::fs::read(path).with_context(|| format!("reading `{}`", path.display()))?;
+    let stupid = stack.repo.stupid();
+    let trimmed_diff = diff.trim_end_with(|c| c.is_ascii_whitespace());
+    if trimmed_diff.is_empty() {
+        return Ok(stack.get_branch_head().tree_id()?.detach());
+    }
+
+    match stupid.apply_to_worktree_and_index(diff.as_bstr(), ApplyOptions::default().threeway(true))
+    {
+        Ok(report) => {
+            super::fold::print_apply_report(matches, &report);
+            stupid.write_tree()
+        }
+        Err(e) => {
+            stupid.read_tree_checkout_hard(stack.get_branch_head().tree_id()?.detach())?;
+            let rejects_path = stack.repo.git_dir().join(REJECTS_FILE_NAME);
+            std::fs::write(&rejects_path, format!("{e:#}\n"))?;
+            Err(anyhow!(
+                "`{}` does not apply; see `{}` for the rejected hunks",
+                path.display(),
+                rejects_path.display()
+            ))
+        }
+    }
+}
+
+/// Generate the name of a new fixup patch for the `target` patch.
+///
+/// The name is "fixup-<n>-<target>" with the smallest `n` that does not collide with
+/// an existing patch.
+fn fixup_patchname<'repo>(stack: &impl StackStateAccess<'repo>, target: &PatchName) -> PatchName {
+    (1..)
+        .map(|n| {
+            PatchName::from_str(&format!("fixup-{n}-{target}"))
+                .expect("fixup of a valid patch name is valid")
+        })
+        .find(|patchname| stack.collides(patchname).is_none())
+        .expect("unused fixup patch name is found")
+}
@@ -17,6 +17,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "next",
     category: super::CommandCategory::StackInspection,
+    read_only: true,
     make,
     run,
 };
@@ -34,7 +35,7 @@ fn make() -> clap::Command {
         .arg(argset::branch_arg())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
         &repo,
@@ -43,7 +44,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     )?;
 
     if let Some(patchname) = stack.unapplied().first() {
-        let mut stdout = crate::color::get_color_stdout(matches);
+        let mut stdout = crate::output::Output::new(matches).data();
         let mut color_spec = termcolor::ColorSpec::new();
         color_spec.set_bold(true);
         stdout.set_color(&color_spec)?;
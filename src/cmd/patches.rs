@@ -14,14 +14,16 @@ use clap::{Arg, ArgMatches, ValueHint};
 use crate::{
     argset,
     branchloc::BranchLocator,
-    ext::{CommitExtended, RepositoryExtended},
-    stack::{Stack, StackAccess, StackStateAccess},
+    ext::RepositoryExtended,
+    pathspec,
+    stack::{Stack, StackAccess, StackStateAccess, StackView},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "patches",
     category: super::CommandCategory::StackInspection,
+    read_only: true,
     make,
     run,
 };
@@ -53,7 +55,7 @@ fn make() -> clap::Command {
         .arg(argset::diff_opts_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
         &repo,
@@ -69,7 +71,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let stupid = repo.stupid();
 
     let pathsbuf;
-    let pathspecs: Vec<&Path> = if let Some(pathspecs) = matches.get_many::<PathBuf>("pathspecs") {
+    let paths: Vec<&Path> = if let Some(pathspecs) = matches.get_many::<PathBuf>("pathspecs") {
         pathspecs.map(PathBuf::as_path).collect()
     } else {
         let prefix = repo.prefix().context("determining Git prefix")?;
@@ -92,45 +94,42 @@ fn run(matches: &ArgMatches) -> Result<()> {
         paths
     };
 
-    if pathspecs.is_empty() {
+    if paths.is_empty() {
         return Err(anyhow!("no local changes and no paths specified"));
     }
+    let pathspecs = pathspec::normalize(&repo, paths)?;
 
-    let revs = stupid.rev_list(stack.base().id, stack.top().id, Some(&pathspecs))?;
+    let view = StackView::new(&stack);
+    let touching = view.applied_touching(&pathspecs)?;
 
     if diff_flag {
         // TODO: pager?
         let stdout = std::io::stdout();
         let mut stdout = stdout.lock();
-        let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, false);
-        for patchname in stack.applied() {
-            let patch_commit = stack.get_patch_commit(patchname);
-            let parent_commit = patch_commit.get_parent_commit()?;
-            if revs.contains(&patch_commit.id) {
-                write!(
-                    stdout,
-                    "--------------------------------------------------\n\
-                     {patchname}\n\
-                     --------------------------------------------------\n"
-                )?;
-                stdout.write_all(patch_commit.message_raw()?)?;
-                write!(stdout, "\n---\n")?;
-                let diff = stupid.diff_tree_patch(
-                    parent_commit.tree_id()?.detach(),
-                    patch_commit.tree_id()?.detach(),
-                    Some(&pathspecs),
-                    crate::color::use_color(matches),
-                    diff_opts.iter(),
-                )?;
-                stdout.write_all(&diff)?;
-            }
+        let diff_opts = argset::get_diff_opts(matches, &stack.config(), false, false);
+        for patch in touching {
+            write!(
+                stdout,
+                "--------------------------------------------------\n\
+                 {}\n\
+                 --------------------------------------------------\n",
+                patch.name()
+            )?;
+            stdout.write_all(patch.commit().message_raw()?)?;
+            write!(stdout, "\n---\n")?;
+            let (bottom_tree_id, top_tree_id) = patch.trees()?;
+            let diff = stupid.diff_tree_patch(
+                bottom_tree_id,
+                top_tree_id,
+                Some(&pathspecs),
+                crate::color::use_color(matches),
+                diff_opts.iter(),
+            )?;
+            stdout.write_all(&diff)?;
         }
     } else {
-        for patchname in stack.applied() {
-            let patch_commit = stack.get_patch_commit(patchname);
-            if revs.contains(&patch_commit.id) {
-                println!("{patchname}");
-            }
+        for patch in touching {
+            println!("{}", patch.name());
         }
     }
 
@@ -3,6 +3,7 @@
 //! `stg pick` implementation.
 
 use std::{
+    ffi::OsString,
     path::{Path, PathBuf},
     rc::Rc,
     str::FromStr,
@@ -15,31 +16,45 @@ use clap::Arg;
 use crate::{
     argset,
     branchloc::BranchLocator,
-    color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
+    output::Output,
     patch::{
-        revspec, PatchName, RangeConstraint, RangeRevisionSpec, SingleRevisionSpec, StGitRevision,
+        name::{self, NameTemplate},
+        patchrange, revspec, LocationGroup, PatchName, RangeConstraint, RangeRevisionSpec,
+        SingleRevisionSpec, StGitRevision,
     },
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
-    stupid::Stupid,
+    pathspec,
+    stack::{
+        message, InitializationPolicy, Stack, StackAccess, StackStateAccess, TransactionError,
+    },
+    stupid::{ApplyOptions, Stupid},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "pick",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
 
 fn make() -> clap::Command {
-    clap::Command::new(STGIT_COMMAND.name)
+    let app = clap::Command::new(STGIT_COMMAND.name)
         .about("Import a patch from another branch or a commit object")
         .long_about(
             "Import one or more patches from another branch or commit object into the \
              current series.\n\
              \n\
+             A range of patches may be picked from another branch with \
+             '<branch>:<patch1>..<patch2>'. Open-ended ranges, e.g. '<branch>:<patch>..' \
+             or '<branch>:..<patch>', extend to the first or last visible patch of the \
+             other branch's stack. The patches in a range are picked in the order they \
+             appear in the other stack. Hidden patches may only be included in a range \
+             with the '--hidden' option.\n\
+             \n\
              By default, the imported patch's name is reused, but may be overridden \
-             with the '--name' option. A commit object can be reverted with the \
+             with the '--name' option. If the name is already used in the current \
+             stack, a unique name is derived from it. A commit object can be reverted with the \
              '--revert' option.\n\
              \n\
              When using the '--expose' option, the format of the commit message is \
@@ -47,8 +62,29 @@ fn make() -> clap::Command {
              option is a format string as may be supplied to the '--pretty' option of \
              'git show'. The default is \"format:%B%n(imported from commit %H)\", \
              which appends the commit hash of the picked commit to the patch's commit \
-             message.",
+             message.\n\
+             \n\
+             Instead of a branch or commit object, a patch may be picked from a patch \
+             file with the '--patch-file' option or from a url with the '--url' \
+             option. The patch is parsed in the same way as by 'stg import', \
+             including patches in mail format, and the author, date, and message are \
+             taken from the patch's headers. With '--fold', the patch's diff is \
+             applied to the current patch like 'stg fold'.\n\
+             \n\
+             With '--committed', the picked commits become regular commits below the \
+             stack rather than patches, e.g. to insert an upstream fix beneath the \
+             stack's patches.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg pick other:fix",
+                "Import the patch `fix` from the stack on branch `other`",
+            ),
+            (
+                "stg pick other:p1..p3",
+                "Import the patches `p1` through `p3` from branch `other`",
+            ),
+        ]))
         .override_usage(super::make_usage(
             "stg pick",
             &[
@@ -56,13 +92,15 @@ fn make() -> clap::Command {
                 "[OPTIONS] [--name NAME] [--parent COMMITTISH] <source>",
                 "[OPTIONS] --fold [--file PATH]... <source>...",
                 "[OPTIONS] --update <source>...",
+                "[OPTIONS] --committed <source>...",
+                "[OPTIONS] [--fold] (--patch-file PATH | --url URL)",
             ],
         ))
         .arg(
             Arg::new("stgit-revision")
                 .help("Patch name or committish to import")
                 .value_name("source")
-                .required(true)
+                .required_unless_present_any(["patch-file", "url"])
                 .num_args(1..)
                 .value_parser(clap::value_parser!(RangeRevisionSpec)),
         )
@@ -97,6 +135,12 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with_all(["fold", "update"]),
         )
+        .arg(
+            Arg::new("hidden")
+                .long("hidden")
+                .help("Allow hidden patches in patch ranges")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("name")
                 .long("name")
@@ -116,6 +160,66 @@ fn make() -> clap::Command {
                 .conflicts_with_all(["fold", "update"]),
         )
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(
+            Arg::new("keep-committer")
+                .long("keep-committer")
+                .help("Keep the committer identity and date of the picked commits")
+                .long_help(
+                    "Keep the committer identity and date of the picked commits for the \
+                     new patch commits, also when the patches are pushed, instead of \
+                     using the current committer and time.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["committer-date-is-author-date", "revert", "fold", "update"]),
+        )
+        .arg(
+            Arg::new("copy-notes")
+                .long("copy-notes")
+                .help("Copy the notes of the picked commits to the new patches")
+                .long_help(
+                    "Copy the notes of the picked commits to the new patch commits. \
+                     Without <ref>, the notes of the default notes ref, as determined by \
+                     core.notesRef, are copied.\n\
+                     \n\
+                     Notes of the default notes ref follow patch commits as the patches \
+                     are refreshed or pushed. To have the notes of another ref follow \
+                     as well, include it in the notes.rewriteRef configuration, as for \
+                     `git commit --amend` and `git rebase`.",
+                )
+                .value_name("ref")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("")
+                .conflicts_with_all(["revert", "fold", "update"]),
+        )
+        .arg(
+            Arg::new("committed")
+                .long("committed")
+                .help("Insert the picked commits below the stack as regular commits")
+                .long_help(
+                    "Insert the picked commits below the stack as regular commits instead \
+                     of importing them as patches. The applied patches are popped, each \
+                     picked commit is cherry-picked onto the stack base, which then \
+                     advances to the new commit, and the patches are pushed back.\n\
+                     \n\
+                     A commit whose parent is the stack base is used as-is. Should \
+                     pushing back a patch result in conflicts, resolve them and run \
+                     `stg push --continue` to push the remaining patches or \
+                     `stg push --abort` to leave them unapplied.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "revert",
+                    "noapply",
+                    "name",
+                    "parent",
+                    "copy-notes",
+                    "fold",
+                    "update",
+                ]),
+        )
+        .arg(argset::marker_size_arg().conflicts_with_all(["noapply", "fold", "update"]))
+        .args(argset::sign_args())
         .arg(
             Arg::new("fold")
                 .long("fold")
@@ -139,10 +243,52 @@ fn make() -> clap::Command {
                 .value_name("path")
                 .requires("fold"),
         )
+        .arg(
+            Arg::new("patch-file")
+                .long("patch-file")
+                .help("Pick the patch from the patch file <path>")
+                .value_name("path")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath)
+                .conflicts_with_all([
+                    "stgit-revision",
+                    "ref-branch",
+                    "revert",
+                    "expose",
+                    "update",
+                    "file",
+                    "keep-committer",
+                    "copy-notes",
+                    "committed",
+                ]),
+        );
+    if cfg!(feature = "import-url") {
+        app.arg(
+            Arg::new("url")
+                .long("url")
+                .help("Pick the patch from <url>")
+                .value_name("url")
+                .conflicts_with_all([
+                    "stgit-revision",
+                    "ref-branch",
+                    "revert",
+                    "expose",
+                    "update",
+                    "file",
+                    "patch-file",
+                    "keep-committer",
+                    "copy-notes",
+                    "committed",
+                ]),
+        )
+    } else {
+        app
+    }
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
-    let repo = gix::Repository::open()?;
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
+    let mut repo = gix::Repository::open()?;
+    argset::set_commit_signing(&mut repo, matches)?;
     let stack = Stack::current(&repo, InitializationPolicy::AutoInitialize)?;
     let ref_stack = Stack::from_branch_locator(
         &repo,
@@ -161,16 +307,58 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         stack.check_head_top_mismatch()?;
     }
 
-    let picks = revspec::resolve(
-        &repo,
-        Some(&ref_stack),
-        matches
-            .get_many::<RangeRevisionSpec>("stgit-revision")
-            .expect("required argument"),
-        RangeConstraint::VisibleWithAppliedBoundary,
-    )?;
+    if let Some(path) = matches.get_one::<PathBuf>("patch-file") {
+        return pick_patch_file(
+            stack,
+            matches,
+            command_line,
+            path,
+            &path.display().to_string(),
+        );
+    } else if cfg!(feature = "import-url") && matches.contains_id("url") {
+        let url = matches.get_one::<String>("url").expect("url is present");
+        let (_download_dir, download_path) = super::import::download_url(url)?;
+        return pick_patch_file(stack, matches, command_line, &download_path, url);
+    }
 
-    if matches.get_flag("fold") || matches.get_flag("update") {
+    let allow_hidden = matches.get_flag("hidden");
+    let mut picks = Vec::new();
+    for spec in matches
+        .get_many::<RangeRevisionSpec>("stgit-revision")
+        .expect("required argument")
+    {
+        // Ranges from another branch are not bounded by that branch's applied patches
+        // since the patches are picked regardless of whether they are applied there.
+        let other_branch = matches!(spec, RangeRevisionSpec::BranchRange { .. })
+            || matches.contains_id("ref-branch");
+        let constraint = match (other_branch, allow_hidden) {
+            (true, true) => RangeConstraint::All,
+            (true, false) => RangeConstraint::Visible,
+            (false, true) => RangeConstraint::AllWithAppliedBoundary,
+            (false, false) => RangeConstraint::VisibleWithAppliedBoundary,
+        };
+        picks.extend(
+            revspec::resolve(&repo, Some(&ref_stack), [spec], constraint).map_err(|e| {
+                match e.downcast::<patchrange::Error>() {
+                    Ok(e) => e.map_each(|e| match e {
+                        patchrange::Error::Name(name::Error::PatchNotAllowed {
+                            patchname,
+                            loc_group: LocationGroup::Hidden,
+                        }) => anyhow!(
+                            "patch `{}` in `{spec}` is hidden; use `--hidden` to pick it",
+                            patchname.quoted()
+                        ),
+                        e => e.into(),
+                    }),
+                    Err(e) => e,
+                }
+            })?,
+        );
+    }
+
+    if matches.get_flag("committed") {
+        pick_committed(stack, matches, command_line, &picks)
+    } else if matches.get_flag("fold") || matches.get_flag("update") {
         // Fold into current patch
         fold_picks(&stack, matches, &picks)
     } else {
@@ -191,10 +379,135 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                     .map(|rev| rev.commit)
             })
             .transpose()?;
-        pick_picks(stack, matches, opt_parent, &picks)
+        let source_branch = ref_stack.get_branch_name().to_string();
+        pick_picks(
+            stack,
+            matches,
+            command_line,
+            opt_parent,
+            &picks,
+            &source_branch,
+        )
+    }
+}
+
+/// Cherry-pick commits onto the stack base and push the applied patches back onto it.
+fn pick_committed(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    picks: &[StGitRevision],
+) -> Result<()> {
+    let repo = stack.repo;
+    let stupid = repo.stupid();
+    let mut base = stack.base().clone();
+
+    for StGitRevision { patchname, commit } in picks {
+        let name = if let Some(patchname) = patchname {
+            format!("`{patchname}`")
+        } else {
+            format!("`{}`", commit.id)
+        };
+        if stupid.merge_bases(base.id, commit.id)?.contains(&commit.id) {
+            return Err(anyhow!("{name} is already in the stack base"));
+        }
+
+        let parent = commit.get_parent_commit()?;
+        let new_commit_id = if parent.id == base.id {
+            commit.id
+        } else {
+            let base_tree_id = base.tree_id()?.detach();
+            let parent_tree_id = parent.tree_id()?.detach();
+            let commit_tree_id = commit.tree_id()?.detach();
+            let maybe_tree_id = stupid.with_temp_index(|stupid_temp| {
+                stupid_temp.read_tree(base_tree_id)?;
+                Ok(
+                    if stupid_temp.apply_treediff_to_index(parent_tree_id, commit_tree_id, true)? {
+                        Some(stupid_temp.write_tree()?)
+                    } else {
+                        None
+                    },
+                )
+            })?;
+            let tree_id = if let Some(tree_id) = maybe_tree_id {
+                tree_id
+            } else if let Some(tree_id) =
+                stupid.merge_trees(parent_tree_id, base_tree_id, commit_tree_id)?
+            {
+                tree_id
+            } else {
+                return Err(anyhow!(
+                    "{name} does not apply cleanly to the stack base; nothing was changed"
+                ));
+            };
+            repo.commit_ex(
+                &commit.author_strict()?,
+                &pick_committer(&stack, matches, commit)?,
+                &crate::wrap::Message::String(pick_message(&stack, matches, commit)?),
+                tree_id,
+                [base.id],
+            )?
+        };
+        base = Rc::new(repo.find_commit(new_commit_id)?);
+    }
+
+    let applied = stack.applied().to_vec();
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .use_index_and_worktree(true)
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            trans.pop_patches(|pn| applied.contains(pn))?;
+            trans.set_base(base.clone());
+            Ok(())
+        })
+        .execute(&format!("pick --committed {} (pop)", base.id))?;
+
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    let state_id = repo
+        .find_reference(stack.get_stack_refname())?
+        .peel_to_commit()?
+        .id;
+    let result = stack
+        .setup_transaction()
+        .command_line(command_line)
+        .use_index_and_worktree(true)
+        .conflict_marker_size(matches.get_one::<usize>("marker-size").copied())
+        .with_output(Output::new(matches))
+        .transact(|trans| trans.push_patches(&applied, false))
+        .execute(&format!("pick --committed {} (reapply)", base.id));
+
+    crate::print_info_message(
+        matches,
+        &format!("Stack base is now `{}`", base.id.to_hex_with_len(7)),
+    );
+    if super::push::is_conflicts_halt(&result) {
+        // The push may be resumed like one halted by `stg push`.
+        super::push::record_pending_push(repo, state_id, None, &applied)?;
+        return Err(match result.err().unwrap().downcast::<TransactionError>() {
+            Ok(TransactionError::TransactionHalt {
+                msg,
+                conflicts: true,
+            }) => TransactionError::TransactionHalt {
+                msg: format!("{msg}\n{CONTINUE_HINT}"),
+                conflicts: true,
+            }
+            .into(),
+            Ok(e) => e.into(),
+            Err(e) => e,
+        });
     }
+    result.map(|_| ())
 }
 
+/// Instructions for resolving a `--committed` pick that stopped before pushing back
+/// all patches.
+const CONTINUE_HINT: &str = "\
+    resolve the conflicts and run `stg push --continue` to push back the remaining \
+    patches\n\
+    or run `stg push --abort` to leave them unapplied";
+
 fn fold_picks(stack: &Stack, matches: &clap::ArgMatches, picks: &[StGitRevision]) -> Result<()> {
     let stupid = stack.repo.stupid();
     for StGitRevision { patchname, commit } in picks {
@@ -205,20 +518,24 @@ fn fold_picks(stack: &Stack, matches: &clap::ArgMatches, picks: &[StGitRevision]
             (commit, &parent)
         };
 
-        let diff_files;
-
-        let pathspecs: Option<Vec<&Path>> = if matches.get_flag("fold") {
+        let pathspecs: Option<Vec<OsString>> = if matches.get_flag("fold") {
             matches
                 .get_many::<PathBuf>("file")
-                .map(|pathbufs| pathbufs.map(PathBuf::as_path).collect())
+                .map(|pathbufs| pathspec::normalize(stack.repo, pathbufs))
+                .transpose()?
         } else {
             assert!(matches.get_flag("update"));
             let branch_head = stack.get_branch_head();
-            diff_files = stupid.diff_tree_files(
+            let diff_files = stupid.diff_tree_files(
                 branch_head.get_parent_commit()?.tree_id()?.detach(),
                 branch_head.tree_id()?.detach(),
             )?;
-            Some(diff_files.iter().collect())
+            Some(
+                diff_files
+                    .iter()
+                    .map(|path| pathspec::top_pathspec(gix::path::into_bstr(path).as_ref(), true))
+                    .collect(),
+            )
         };
 
         let conflicts = !stupid
@@ -252,17 +569,17 @@ fn fold_picks(stack: &Stack, matches: &clap::ArgMatches, picks: &[StGitRevision]
 fn pick_picks(
     stack: Stack,
     matches: &clap::ArgMatches,
+    command_line: &[String],
     opt_parent: Option<Rc<gix::Commit>>,
     picks: &[StGitRevision],
+    source_branch: &str,
 ) -> Result<()> {
-    let stupid = stack.repo.stupid();
-    let config = stack.repo.config_snapshot();
-    let patchname_len_limit = PatchName::get_length_limit(&config);
+    let mut name_template = NameTemplate::new(&stack)?.with_branch(source_branch);
     let mut new_patches: Vec<(PatchName, gix::ObjectId)> = Vec::with_capacity(picks.len());
+    let mut disallow: Vec<PatchName> = stack.all_patches().cloned().collect();
 
     for StGitRevision { patchname, commit } in picks {
         let commit_ref = commit.decode()?;
-        let mut disallow: Vec<&PatchName> = stack.all_patches().collect();
 
         let patchname = if let Some(name) = matches.get_one::<PatchName>("name") {
             name.clone()
@@ -273,53 +590,13 @@ fn pick_picks(
                 patchname.clone()
             }
         } else {
-            PatchName::make(
-                &commit_ref.message.to_str_lossy(),
-                false,
-                patchname_len_limit,
-            )
+            name_template.make(&commit_ref.message.to_str_lossy(), false)?
         }
-        .uniquify(&[], &disallow);
+        .uniquify(&[], &disallow, name_template.len_limit());
 
-        let commit_id_string = commit.id.to_string();
-        let message = if matches.get_flag("revert") {
-            let message = commit_ref.message.to_str().ok();
-            let (subject, body) = if let Some(message) = message {
-                message.split_once('\n').unwrap_or((message, ""))
-            } else {
-                (commit_id_string.as_str(), "")
-            };
-            format!(
-                "Revert \"{subject}\"\n\
-                 \n\
-                 This reverts commit {commit_id_string}.\n\
-                 \n\
-                 {body}"
-            )
-        } else if matches.get_flag("expose") {
-            let expose_format = config.string_by("stgit", Some("pick".into()), "expose-format");
-            let expose_format = expose_format
-                .as_ref()
-                .map(|bs| bs.to_str().ok())
-                .unwrap_or(None)
-                .unwrap_or("format:%B%n(imported from commit %H)%n");
-            stupid
-                .show_pretty(commit.id, expose_format)?
-                .to_str_lossy()
-                .to_string()
-        } else {
-            commit_ref.message.to_str_lossy().to_string()
-        };
-        let message = &crate::wrap::Message::String(message);
+        let message = &crate::wrap::Message::String(pick_message(&stack, matches, commit)?);
         let author = commit.author_strict()?;
-        let default_committer = stack.repo.get_committer()?;
-        let committer = if matches.get_flag("committer-date-is-author-date") {
-            let mut committer = default_committer.to_owned();
-            committer.time = author.time;
-            committer
-        } else {
-            default_committer.to_owned()
-        };
+        let committer = pick_committer(&stack, matches, commit)?;
         let parent = if let Some(parent) = opt_parent.as_ref() {
             parent.clone()
         } else {
@@ -338,14 +615,173 @@ fn pick_picks(
             top.tree_id()?.detach(),
             [bottom.id],
         )?;
+        disallow.push(patchname.clone());
         new_patches.push((patchname, new_commit_id));
-        disallow.push(&new_patches[new_patches.len() - 1].0);
     }
 
+    let stack = push_new_patches(stack, matches, command_line, &new_patches)?;
+
+    if let Some(notes_ref) = argset::get_one_str(matches, "copy-notes") {
+        // Notes are copied once the patches are pushed since pushing may rewrite their
+        // commits. Failure to copy is okay; a picked commit may not have notes.
+        let notes_ref = (!notes_ref.is_empty()).then_some(notes_ref);
+        let stupid = stack.repo.stupid();
+        for ((patchname, _), StGitRevision { commit, .. }) in new_patches.iter().zip(picks) {
+            stupid
+                .notes_copy_ref(notes_ref, commit.id, stack.get_patch_commit_id(patchname))
+                .ok();
+        }
+    }
+    Ok(())
+}
+
+/// Make the message for the commit made from a picked commit.
+fn pick_message(stack: &Stack, matches: &clap::ArgMatches, commit: &gix::Commit) -> Result<String> {
+    let commit_ref = commit.decode()?;
+    let commit_id_string = commit.id.to_string();
+    Ok(if matches.get_flag("revert") {
+        let message = commit_ref.message.to_str().ok();
+        let (subject, body) = if let Some(message) = message {
+            message.split_once('\n').unwrap_or((message, ""))
+        } else {
+            (commit_id_string.as_str(), "")
+        };
+        format!(
+            "Revert \"{subject}\"\n\
+             \n\
+             This reverts commit {commit_id_string}.\n\
+             \n\
+             {body}"
+        )
+    } else if matches.get_flag("expose") {
+        let expose_format = stack.config().string("stgit.pick.expose-format");
+        let expose_format = expose_format
+            .as_ref()
+            .map(|bs| bs.to_str().ok())
+            .unwrap_or(None)
+            .unwrap_or("format:%B%n(imported from commit %H)%n");
+        stack
+            .repo
+            .stupid()
+            .show_pretty(commit.id, expose_format)?
+            .to_str_lossy()
+            .to_string()
+    } else {
+        commit_ref.message.to_str_lossy().to_string()
+    })
+}
+
+/// Determine the committer of the commit made from a picked commit.
+fn pick_committer(
+    stack: &Stack,
+    matches: &clap::ArgMatches,
+    commit: &gix::Commit,
+) -> Result<gix::actor::Signature> {
+    let default_committer = stack.repo.get_committer()?;
+    Ok(if matches.get_flag("keep-committer") {
+        commit.committer_strict()?
+    } else if matches.get_flag("committer-date-is-author-date") {
+        let mut committer = default_committer.to_owned();
+        committer.time = commit.author_strict()?.time;
+        committer
+    } else {
+        default_committer.to_owned()
+    })
+}
+
+fn pick_patch_file(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    path: &Path,
+    source: &str,
+) -> Result<()> {
+    let repo = stack.repo;
+    let stupid = repo.stupid();
+    let content =
+        super::import::read_patch_file(path).with_context(|| format!("reading `{source}`"))?;
+    let (headers, message, diff) = super::import::parse_patch(&stupid, content)
+        .with_context(|| format!("parsing `{source}`"))?;
+    let trimmed_diff = diff.trim_end_with(|c| c.is_ascii_whitespace());
+    let is_empty = trimmed_diff.is_empty() || trimmed_diff == b"---";
+
+    if matches.get_flag("fold") {
+        if stack.applied().is_empty() {
+            return Err(super::Error::NoAppliedPatches.into());
+        }
+        if !is_empty {
+            let report = stupid
+                .apply_to_worktree_and_index(diff.as_bstr(), &ApplyOptions::default())
+                .with_context(|| format!("folding `{source}`"))?;
+            super::fold::print_apply_report(matches, &report);
+        }
+        return Ok(());
+    }
+
+    let parent = if let Some(parent_spec) = matches.get_one::<SingleRevisionSpec>("parent") {
+        parent_spec.resolve(repo, Some(&stack))?.commit
+    } else {
+        stack.get_branch_head().clone()
+    };
+    let parent_tree_id = parent.tree_id()?.detach();
+    let tree_id = if is_empty {
+        parent_tree_id
+    } else {
+        stupid
+            .with_temp_index(|stupid_temp| {
+                stupid_temp.read_tree(parent_tree_id)?;
+                stupid_temp.apply_to_index(diff.as_bstr(), None)?;
+                stupid_temp.write_tree()
+            })
+            .with_context(|| format!("applying `{source}`"))?
+    };
+
+    let message = headers.full_message(message.as_bstr());
+    let mut name_template = NameTemplate::new(&stack)?;
+    let disallow: Vec<&PatchName> = stack.all_patches().collect();
+    let patchname = if let Some(name) = matches.get_one::<PatchName>("name") {
+        name.clone()
+    } else if let Some(name) = headers.patchname.as_deref() {
+        PatchName::make(name, false, name_template.len_limit())
+    } else {
+        name_template.make(&message, true)?
+    }
+    .uniquify(&[], &disallow, name_template.len_limit());
+
+    let author = headers.author(repo)?;
+    let default_committer = repo.get_committer()?;
+    let committer = if matches.get_flag("committer-date-is-author-date") {
+        let mut committer = default_committer.to_owned();
+        committer.time = author.time;
+        committer
+    } else {
+        default_committer.to_owned()
+    };
+    let new_commit_id = repo.commit_ex(
+        &author,
+        &committer,
+        &crate::wrap::Message::String(message),
+        tree_id,
+        [parent.id],
+    )?;
+
+    push_new_patches(stack, matches, command_line, &[(patchname, new_commit_id)])?;
+    Ok(())
+}
+
+fn push_new_patches<'repo>(
+    stack: Stack<'repo>,
+    matches: &clap::ArgMatches,
+    command_line: &[String],
+    new_patches: &[(PatchName, gix::ObjectId)],
+) -> Result<Stack<'repo>> {
     stack
         .setup_transaction()
-        .with_output_stream(get_color_stdout(matches))
+        .command_line(command_line)
+        .with_output(Output::new(matches))
+        .keep_committer(matches.get_flag("keep-committer"))
         .use_index_and_worktree(true)
+        .conflict_marker_size(matches.get_one::<usize>("marker-size").copied())
         .transact(|trans| {
             let mut to_push = Vec::new();
             for (i, (patchname, commit_id)) in new_patches.iter().enumerate() {
@@ -357,6 +793,11 @@ fn pick_picks(
             }
             Ok(())
         })
-        .execute("pick")?;
-    Ok(())
+        .execute(&message::with_patches(
+            "pick",
+            &new_patches
+                .iter()
+                .map(|(patchname, _)| patchname)
+                .collect::<Vec<_>>(),
+        ))
 }
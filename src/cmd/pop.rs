@@ -9,16 +9,20 @@ use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::RepositoryExtended,
-    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    output::Output,
+    patch::{
+        patchedit, patchrange, LocationConstraint, PatchLocator, PatchName, PatchRange,
+        RangeConstraint,
+    },
+    stack::{message, InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "pop",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -35,14 +39,31 @@ fn make() -> clap::Command {
              performed such that only the patches specified on the command line \
              are unapplied at the end of the operation. It is possible for some \
              of these intermediate push operations to fail due to conflicts if \
-             patches are popped out of last-pushed first-popped order.",
+             patches are popped out of last-pushed first-popped order.\n\
+             \n\
+             With --interactive, the applied patches are listed in an editor and \
+             the patches remaining in the list when the editor exits are popped.",
         )
+        .after_long_help(super::make_examples(&[
+            ("stg pop -n 3", "Pop the three topmost applied patches"),
+            (
+                "stg pop --to p2",
+                "Pop the patches above `p2`, leaving `p2` as the topmost patch",
+            ),
+            ("stg pop p2..", "Pop `p2` and all applied patches above it"),
+            (
+                "stg pop '%{wip}'",
+                "Pop only the applied patches labeled `wip`",
+            ),
+        ]))
         .override_usage(super::make_usage(
             "stg pop",
             &[
                 "[OPTIONS] [patch]...",
                 "[OPTIONS] --all",
                 "[OPTIONS] -n <number>",
+                "[OPTIONS] --to <patch>",
+                "[OPTIONS] --interactive",
             ],
         ))
         .arg(
@@ -52,7 +73,7 @@ fn make() -> clap::Command {
                 .num_args(1..)
                 .allow_negative_numbers(true)
                 .value_parser(clap::value_parser!(PatchRange))
-                .conflicts_with_all(["all", "number"]),
+                .conflicts_with_all(["all", "number", "to"]),
         )
         .arg(
             Arg::new("all")
@@ -60,7 +81,7 @@ fn make() -> clap::Command {
                 .short('a')
                 .help("Pop all applied patches")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with("number"),
+                .conflicts_with_all(["number", "to"]),
         )
         .arg(
             Arg::new("number")
@@ -71,12 +92,36 @@ fn make() -> clap::Command {
                     "Pop the specified <number> of patches.\n\
                      \n\
                      A negative number indicates to pop all but that number \
-                     of patches",
+                     of patches. Use '--to' to pop until a given patch is the \
+                     topmost applied patch.",
                 )
                 .num_args(1)
                 .allow_negative_numbers(true)
                 .value_name("number")
-                .value_parser(clap::value_parser!(isize)),
+                .value_parser(clap::value_parser!(isize))
+                .conflicts_with("to"),
+        )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .help("Pop the patches above <patch>")
+                .long_help(
+                    "Pop the patches above <patch>, such that <patch> becomes the \
+                     topmost applied patch. The patch itself remains applied; use \
+                     '--to <patch>~' to also pop it. Nothing is popped, with a \
+                     notice, if <patch> already is the topmost applied patch.",
+                )
+                .value_name("patch")
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator)),
+        )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .short('i')
+                .help("Choose the patches to pop in an editor")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["patchranges-applied", "all", "number", "to"]),
         )
         .arg(
             Arg::new("spill")
@@ -85,10 +130,17 @@ fn make() -> clap::Command {
                 .help("Keep patches' modifications in index and worktree after popping")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::dry_run_arg())
         .arg(argset::keep_arg())
+        .arg(argset::force_overwrite_untracked_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+const INTERACTIVE_INSTRUCTIONS: &str = "\
+# The patches listed above are popped. Remove or comment out a patch's line
+# to keep it applied. An empty list aborts the pop.
+";
+
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
 
@@ -102,44 +154,68 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(super::Error::NoAppliedPatches.into());
     }
 
-    let mut patches: indexmap::IndexSet<PatchName> = if matches.get_flag("all") {
-        stack.applied().iter().cloned().collect()
-    } else if let Some(number) = opt_number {
-        let num_applied = stack.applied().len();
-        let num_to_take: usize = {
-            if number >= 0 {
-                std::cmp::min(number as usize, num_applied)
-            } else if number.unsigned_abs() < num_applied {
-                num_applied - number.unsigned_abs()
-            } else {
-                // User asked to retain more patches than are applied, so nothing to do.
+    let mut patches: indexmap::IndexSet<PatchName> =
+        if matches.get_flag("all") || matches.get_flag("interactive") {
+            stack.applied().iter().cloned().collect()
+        } else if let Some(locator) = matches.get_one::<PatchLocator>("to") {
+            let patchname = locator
+                .resolve_name_allowed(&stack, LocationConstraint::Applied)?
+                .constrain(&stack, LocationConstraint::Applied)
+                .map_err(|e| match e {
+                    crate::patch::name::Error::PatchNotAllowed { patchname, .. } => {
+                        anyhow!("patch `{patchname}` is not applied")
+                    }
+                    _ => e.into(),
+                })?;
+            let pos = stack
+                .applied()
+                .iter()
+                .position(|pn| pn == &patchname)
+                .expect("patch is applied");
+            if pos + 1 == stack.applied().len() {
+                Output::new(matches).info(&format!(
+                    "patch `{patchname}` is already the topmost applied patch"
+                ));
                 return Ok(());
             }
+            stack.applied()[pos + 1..].iter().cloned().collect()
+        } else if let Some(number) = opt_number {
+            let num_applied = stack.applied().len();
+            let num_to_take: usize = {
+                if number >= 0 {
+                    std::cmp::min(number as usize, num_applied)
+                } else if number.unsigned_abs() < num_applied {
+                    num_applied - number.unsigned_abs()
+                } else {
+                    // User asked to retain more patches than are applied, so nothing to do.
+                    return Ok(());
+                }
+            };
+            stack
+                .applied()
+                .iter()
+                .rev()
+                .take(num_to_take)
+                .cloned()
+                .collect()
+        } else if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-applied") {
+            indexmap::IndexSet::from_iter(
+                patchrange::resolve_names(&stack, range_specs, RangeConstraint::Applied).map_err(
+                    |e| {
+                        e.map_each(|e| match e {
+                            patchrange::Error::Name(
+                                crate::patch::name::Error::PatchNotAllowed { patchname, .. },
+                            ) if stack.is_unapplied(&patchname) => {
+                                anyhow!("patch `{patchname}` is already unapplied")
+                            }
+                            _ => e.into(),
+                        })
+                    },
+                )?,
+            )
+        } else {
+            stack.applied().iter().rev().take(1).cloned().collect()
         };
-        stack
-            .applied()
-            .iter()
-            .rev()
-            .take(num_to_take)
-            .cloned()
-            .collect()
-    } else if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-applied") {
-        indexmap::IndexSet::from_iter(
-            patchrange::resolve_names(&stack, range_specs, RangeConstraint::Applied).map_err(
-                |e| match e {
-                    patchrange::Error::Name(crate::patch::name::Error::PatchNotAllowed {
-                        patchname,
-                        ..
-                    }) if stack.is_unapplied(&patchname) => {
-                        anyhow!("patch `{patchname}` is already unapplied")
-                    }
-                    _ => e.into(),
-                },
-            )?,
-        )
-    } else {
-        stack.applied().iter().rev().take(1).cloned().collect()
-    };
 
     assert!(!patches.is_empty());
 
@@ -150,12 +226,27 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let stupid = repo.stupid();
     let statuses = stupid.statuses(None)?;
 
+    stack.check_conflicted_patch()?;
+    stack.check_pending_push()?;
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
     if !keep_flag && !spill_flag {
         statuses.check_index_and_worktree_clean()?;
     }
 
+    if matches.get_flag("interactive") {
+        patches = indexmap::IndexSet::from_iter(patchedit::edit_patch_list(
+            &stack,
+            stack.applied(),
+            LocationConstraint::Applied,
+            INTERACTIVE_INSTRUCTIONS,
+            &stack.config(),
+        )?);
+        if patches.is_empty() {
+            return Err(anyhow!("no patches to pop"));
+        }
+    }
+
     let mut new_unapplied: Vec<PatchName> = vec![];
     let mut new_applied: Vec<PatchName> = vec![];
 
@@ -181,6 +272,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 
+    let log_msg = message::with_patches("pop", &new_unapplied);
     new_unapplied.reserve(stack.unapplied().len());
     stack
         .unapplied()
@@ -189,13 +281,17 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .dry_run(matches.get_flag("dry-run"))
         .use_index_and_worktree(!spill_flag)
-        .with_output_stream(get_color_stdout(matches))
+        .keep_local_changes(keep_flag)
+        .force_overwrite_untracked(matches.get_flag("force-overwrite-untracked"))
+        .with_output(Output::new(matches))
         .transact(|trans| {
             trans.reorder_patches(Some(&new_applied), Some(&new_unapplied), None)?;
             Ok(())
         })
-        .execute("pop")?;
+        .execute(&log_msg)?;
 
     Ok(())
 }
@@ -2,7 +2,7 @@
 
 //! `stg pull` implementation.
 
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, path::PathBuf, rc::Rc, str::FromStr};
 
 use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
@@ -10,16 +10,21 @@ use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::RepositoryExtended,
+    output::Output,
+    patch::PatchName,
     print_info_message,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stack::{
+        InitializationPolicy, Stack, StackAccess, StackConfig, StackState, StackStateAccess,
+        TransactionError,
+    },
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "pull",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -40,10 +45,60 @@ fn make() -> clap::Command {
              patches back onto the stack. Merge conflicts may occur during the final \
              push step. Those conflicts need to be resolved manually.\n\
              \n\
+             If pushing back the patches stops due to conflicts, the pull remains in \
+             progress. Once the conflicts are resolved and refreshed, 'stg pull \
+             --continue' pushes back the remaining patches without fetching again. \
+             Alternatively, 'stg pull --abort' restores the stack and its base to \
+             how they were before the pull.\n\
+             \n\
+             With --no-fetch, nothing is fetched from the remote repository. The \
+             stack is instead rebased onto the already-fetched upstream of the \
+             current branch.\n\
+             \n\
+             With --dry-run, the remote repository is fetched from, unless --no-fetch \
+             is given, and the outcome of pushing back the patches onto the fetched \
+             changes is predicted without changing the stack. With the \"pull\" \
+             pull-policy, the pulled changes are predicted as a rebase onto the \
+             fetched commit.\n\
+             \n\
              See git-fetch(1) for the format of remote repository argument.
              ",
         )
+        .override_usage(super::make_usage(
+            "stg pull",
+            &["[OPTIONS] [repository]", "--continue", "--abort"],
+        ))
         .arg(Arg::new("repository").help("Repository to pull from"))
+        .arg(
+            Arg::new("no-fetch")
+                .long("no-fetch")
+                .help("Rebase onto the already-fetched upstream without fetching")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("repository"),
+        )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .help("Continue pushing back patches after resolving conflicts")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["repository", "no-fetch", "nopush", "abort"]),
+        )
+        .arg(
+            Arg::new("abort")
+                .long("abort")
+                .help("Abort the pull in progress, restoring the stack")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "repository",
+                    "no-fetch",
+                    "nopush",
+                    "merged",
+                    "not-merged",
+                    "conflicts",
+                    "committer-date-is-author-date",
+                    "ignore-date",
+                ]),
+        )
         .arg(
             Arg::new("nopush")
                 .long("nopush")
@@ -52,15 +107,40 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with("merged"),
         )
-        .arg(argset::merged_arg().long_help(
+        .arg(argset::merged_mode_arg().long_help(
             "Check for patches that may have been merged upstream.\n\
              \n\
              When pushing-back patches, each patch is checked to see if its changes \
              already exist in the just-pulled upstream changes. If a patch's changes \
              have already been merged upstream, the patch will still exist in the \
-             stack, but become empty after the pull operation.",
+             stack, but become empty after the pull operation.\n\
+             \n\
+             With `--merged=subject`, patches whose diffs are not found upstream are \
+             additionally considered merged if their subject matches, after \
+             normalization, the subject of an upstream commit added since the old \
+             stack base. This helps detect patches applied upstream with small edits. \
+             The matching upstream commit is reported for each such patch.\n\
+             \n\
+             With `--merged=aggressive`, runs of consecutive patches whose combined \
+             diff has the same patch id, see git-patch-id(1), as one of these upstream \
+             commits are also considered merged, before subjects are matched. This \
+             detects patches that upstream squashed into a single commit. Runs are \
+             limited to the number of patches given by the \"stgit.mergedmaxrun\" \
+             configuration variable, which defaults to 5.\n\
+             \n\
+             With just `--merged`, the mode is determined by the \"stgit.mergeddetect\" \
+             configuration variable, which defaults to \"exact\".",
         ))
+        .arg(argset::not_merged_arg())
+        .arg(
+            argset::dry_run_arg()
+                .long_help(super::rebase::DRY_RUN_HELP)
+                .conflicts_with_all(["continue", "abort"]),
+        )
+        .arg(argset::autostash_arg("pull"))
         .arg(argset::push_conflicts_arg())
+        .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::ignore_date_arg())
 }
 
 enum PullPolicy {
@@ -93,26 +173,125 @@ impl Display for PullPolicy {
     }
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+/// Record of a pull whose patches have not all been pushed back.
+///
+/// The record is written to the `stgit-pull` file in the git directory once the
+/// patches are popped and is removed once they are all pushed back again or the pull is
+/// aborted.
+struct PullInProgress {
+    /// Name of the branch being pulled.
+    branch_name: String,
+
+    /// Stack state commit from before the pull, which an abort resets to.
+    state_id: gix::ObjectId,
+
+    /// Stack base from before the pull.
+    old_base_id: gix::ObjectId,
+
+    /// Patches that were applied before the pull and are to be pushed back.
+    applied: Vec<PatchName>,
+}
+
+impl PullInProgress {
+    fn path(repo: &gix::Repository) -> PathBuf {
+        repo.git_dir().join("stgit-pull")
+    }
+
+    /// Read the record of the pull in progress, if any.
+    fn read(repo: &gix::Repository) -> Result<Option<Self>> {
+        let path = Self::path(repo);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        let bad_record = || anyhow!("invalid pull record `{}`", path.display());
+        let mut branch_name = None;
+        let mut state_id = None;
+        let mut old_base_id = None;
+        let mut applied = Vec::new();
+        for line in contents.lines() {
+            let (key, value) = line.split_once(' ').ok_or_else(bad_record)?;
+            match key {
+                "branch" => branch_name = Some(value.to_string()),
+                "state" => state_id = Some(gix::ObjectId::from_hex(value.as_bytes())?),
+                "base" => old_base_id = Some(gix::ObjectId::from_hex(value.as_bytes())?),
+                "patch" => applied.push(PatchName::from_str(value)?),
+                _ => return Err(bad_record()),
+            }
+        }
+        Ok(Some(Self {
+            branch_name: branch_name.ok_or_else(bad_record)?,
+            state_id: state_id.ok_or_else(bad_record)?,
+            old_base_id: old_base_id.ok_or_else(bad_record)?,
+            applied,
+        }))
+    }
+
+    fn write(&self, repo: &gix::Repository) -> Result<()> {
+        let mut contents = format!(
+            "branch {}\nstate {}\nbase {}\n",
+            self.branch_name, self.state_id, self.old_base_id
+        );
+        for patchname in &self.applied {
+            contents.push_str(&format!("patch {patchname}\n"));
+        }
+        std::fs::write(Self::path(repo), contents)?;
+        Ok(())
+    }
+
+    fn remove(repo: &gix::Repository) -> Result<()> {
+        std::fs::remove_file(Self::path(repo))?;
+        Ok(())
+    }
+
+    /// Read the record of the pull in progress on the given branch.
+    fn read_for_branch(repo: &gix::Repository, branch_name: &str) -> Result<Self> {
+        match Self::read(repo)? {
+            Some(pull) if pull.branch_name == branch_name => Ok(pull),
+            Some(pull) => Err(anyhow!(
+                "the pull in progress is for branch `{}`, not `{branch_name}`",
+                pull.branch_name
+            )),
+            None => Err(anyhow!("no pull in progress")),
+        }
+    }
+}
+
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
+    if matches.get_flag("continue") {
+        return continue_pull(&repo, matches, command_line);
+    } else if matches.get_flag("abort") {
+        return abort_pull(&repo, matches, command_line);
+    }
+
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
     let stupid = repo.stupid();
     let branch_name = stack.get_branch_name().to_string();
-    let config = repo.config_snapshot();
+    let config = stack.config();
+
+    if let Some(pull) = PullInProgress::read(&repo)? {
+        // A record whose patches were all pushed back by other means, e.g. with `stg
+        // push`, is stale.
+        if pull.branch_name == branch_name && !pull.applied.iter().any(|pn| stack.is_unapplied(pn))
+        {
+            PullInProgress::remove(&repo)?;
+        } else {
+            return Err(anyhow!(
+                "a pull of branch `{}` is already in progress\n\
+                 use `stg pull --continue` or `stg pull --abort`",
+                pull.branch_name
+            ));
+        }
+    }
+
     let policy = PullPolicy::from_str(
         &config
-            .string_by(
-                "branch",
-                Some(format!("{branch_name}.stgit").as_str().into()),
-                "pull-policy",
-            )
-            .or_else(|| config.string("stgit.pull-policy"))
+            .string("stgit.pull-policy")
             .map(|bs| bs.to_str_lossy().to_string())
             .unwrap_or_else(|| "pull".to_string()),
     )?;
 
-    let allow_push_conflicts = argset::resolve_allow_push_conflicts(&config, matches);
-
     let parent_remote;
     let remote_name = match policy {
         PullPolicy::Rebase => {
@@ -125,6 +304,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
         PullPolicy::Pull | PullPolicy::FetchRebase => {
             parent_remote = config
+                .snapshot()
                 .string_by("branch", Some(branch_name.as_str().into()), "remote")
                 .and_then(|bs| bs.to_str().map(str::to_string).ok());
             let remote_name = matches
@@ -141,129 +321,362 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     };
 
-    if stack.is_protected(&config) {
+    if stack.is_protected() {
         return Err(anyhow!("this branch is protected; pulls are not permitted"));
     }
 
-    stupid.statuses(None)?.check_index_and_worktree_clean()?;
+    if !argset::resolve_autostash(&config, matches) {
+        stupid.statuses(None)?.check_index_and_worktree_clean()?;
+    }
     stack.check_head_top_mismatch()?;
 
-    let applied = stack.applied().to_vec();
+    // The already-fetched upstream is found before making any changes.
+    let no_fetch_target = if matches.get_flag("no-fetch") && !matches!(policy, PullPolicy::Rebase) {
+        let upstream_spec = format!("{branch_name}@{{upstream}}");
+        let upstream_name = stupid
+            .rev_parse_symbolic_full_name(&upstream_spec)
+            .ok()
+            .flatten()
+            .ok_or_else(|| {
+                anyhow!(
+                    "no upstream is configured for branch `{branch_name}`; \
+                     cannot pull without fetching"
+                )
+            })?;
+        let target_id = repo
+            .find_reference(upstream_name.as_str())
+            .with_context(|| format!("finding `{upstream_name}`"))?
+            .peel_to_commit()
+            .with_context(|| format!("peeling `{upstream_name}` to commit"))?
+            .id;
+        Some(target_id)
+    } else {
+        None
+    };
+
+    if matches.get_flag("dry-run") {
+        // Validate the merged options before fetching.
+        argset::resolve_merged_mode(&config, matches)?;
+        argset::resolve_not_merged(matches, &stack)?;
+        let target_id = if let Some(target_id) = no_fetch_target {
+            target_id
+        } else if let PullPolicy::Rebase = policy {
+            parent_branch_target(&repo, &config, &branch_name)?
+        } else {
+            fetch(
+                &repo,
+                &config,
+                matches,
+                &remote_name.expect("remote is known for pull policies that fetch"),
+            )?
+        };
+        return predict_pull(&repo, stack, matches, command_line, target_id);
+    }
+
+    let pull = PullInProgress {
+        branch_name: branch_name.clone(),
+        state_id: repo
+            .find_reference(stack.get_stack_refname())?
+            .peel_to_commit()?
+            .id,
+        old_base_id: stack.base().id,
+        applied: stack.applied().to_vec(),
+    };
+    // Validate the merged options before making any changes.
+    argset::resolve_merged_mode(&config, matches)?;
+    argset::resolve_not_merged(matches, &stack)?;
+    argset::autostash_changes(&repo, &config, &branch_name, matches)?;
 
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(true)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| {
-            trans.pop_patches(|pn| applied.contains(pn))?;
+            trans.pop_patches(|pn| pull.applied.contains(pn))?;
             Ok(())
         })
         .execute("pull (pop)")?;
 
+    let nopush = matches.get_flag("nopush");
+    if !nopush {
+        pull.write(&repo)?;
+    }
+
     let rebase_target = match policy {
+        PullPolicy::Pull | PullPolicy::FetchRebase if no_fetch_target.is_some() => no_fetch_target,
         PullPolicy::Pull => {
             let pull_cmd = config
-                .string_by(
-                    "branch",
-                    Some(format!("{branch_name}.stgit").as_str().into()),
-                    "pullcmd",
-                )
-                .or_else(|| config.string("stgit.pullcmd"))
+                .string("stgit.pullcmd")
                 .and_then(|bs| bs.to_str().map(str::to_string).ok())
                 .unwrap_or_else(|| "git pull".to_string());
             let remote_name = remote_name.unwrap();
             print_info_message(matches, &format!("Pulling from `{remote_name}`"));
             if !stupid.user_pull(&pull_cmd, &remote_name)? {
-                return Err(super::Error::CausedConflicts(
-                    "pull resulted in conflicts".to_string(),
-                )
+                return Err(super::Error::CausedConflicts(if nopush {
+                    "pull resulted in conflicts".to_string()
+                } else {
+                    format!("pull resulted in conflicts\n{CONTINUE_HINT}")
+                })
                 .into());
             }
             None
         }
-        PullPolicy::FetchRebase => {
-            let fetch_cmd = config
-                .string_by(
-                    "branch",
-                    Some(format!("{branch_name}.stgit").as_str().into()),
-                    "fetchcmd",
-                )
-                .or_else(|| config.string("stgit.fetchcmd"))
-                .and_then(|bs| bs.to_str().map(str::to_string).ok())
-                .unwrap_or_else(|| "git fetch".to_string());
-            let remote_name = remote_name.unwrap();
-            print_info_message(matches, &format!("Fetching from `{remote_name}`"));
-            stupid.user_fetch(&fetch_cmd, &remote_name)?;
-            let target_id = repo
-                .find_reference("FETCH_HEAD")
-                .context("finding `FETCH_HEAD`")?
-                .peel_to_commit()
-                .context("peeling `FETCH_HEAD` to commit")?
-                .id;
-            Some(target_id)
-        }
-        PullPolicy::Rebase => {
-            let parent_branch_name = config.string_by(
-                "branch",
-                Some(format!("{branch_name}.stgit").as_str().into()),
-                "parentbranch",
-            );
-            let parent_branch_name = parent_branch_name.as_ref().and_then(|bs| bs.to_str().ok());
-
-            let parent_object = if let Some(name) = parent_branch_name {
-                repo.rev_parse_single_ex(name)?.object()?
-            } else {
-                repo.rev_parse_single("heads/origin")
-                    .map_err(|_| anyhow!("cannot find a parent branch for `{branch_name}`"))?
-                    .object()?
-            };
-            let parent_commit = parent_object
-                .peel_tags_to_end()
-                .context("peel parent object to commit")?
-                .try_into_commit()?;
-            Some(parent_commit.id)
-        }
+        PullPolicy::FetchRebase => Some(fetch(
+            &repo,
+            &config,
+            matches,
+            &remote_name.expect("remote is known for fetch-rebase policy"),
+        )?),
+        PullPolicy::Rebase => Some(parent_branch_target(&repo, &config, &branch_name)?),
     };
 
     if let Some(rebase_target) = rebase_target {
         let rebase_cmd = config
-            .string_by(
-                "branch",
-                Some(format!("{branch_name}.stgit").as_str().into()),
-                "rebasecmd",
-            )
-            .or_else(|| config.string("stgit.rebasecmd"))
+            .string("stgit.rebasecmd")
             .and_then(|bs| bs.to_str().map(str::to_string).ok())
             .unwrap_or_else(|| "git reset --hard".to_string());
         print_info_message(matches, &format!("Rebasing to `{rebase_target}`"));
         stupid.user_rebase(&rebase_cmd, rebase_target)?;
     }
 
-    // The above pull and rebase action may have moved the stack's branch reference,
-    // so we initialize the stack afresh.
-    let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+    if nopush {
+        // The above pull and rebase action may have moved the stack's branch
+        // reference, so the stack is initialized afresh.
+        let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+        if !stack.is_head_top() {
+            stack.log_external_mods(Some("pull"))?;
+        }
+        argset::reapply_autostash(&repo, &branch_name, matches)?;
+    } else {
+        push_back(&repo, matches, command_line, &pull)?;
+    }
+
+    if config.boolean("stgit.keepoptimized").unwrap_or(false) {
+        stupid.repack()?;
+    }
+
+    Ok(())
+}
+
+/// Fetch from the remote repository, returning the fetched commit to rebase onto.
+fn fetch(
+    repo: &gix::Repository,
+    config: &StackConfig,
+    matches: &ArgMatches,
+    remote_name: &str,
+) -> Result<gix::ObjectId> {
+    let fetch_cmd = config
+        .string("stgit.fetchcmd")
+        .and_then(|bs| bs.to_str().map(str::to_string).ok())
+        .unwrap_or_else(|| "git fetch".to_string());
+    print_info_message(matches, &format!("Fetching from `{remote_name}`"));
+    repo.stupid().user_fetch(&fetch_cmd, remote_name)?;
+    let target_id = repo
+        .find_reference("FETCH_HEAD")
+        .context("finding `FETCH_HEAD`")?
+        .peel_to_commit()
+        .context("peeling `FETCH_HEAD` to commit")?
+        .id;
+    Ok(target_id)
+}
+
+/// Find the commit of the branch's parent branch to rebase onto.
+fn parent_branch_target(
+    repo: &gix::Repository,
+    config: &StackConfig,
+    branch_name: &str,
+) -> Result<gix::ObjectId> {
+    let parent_branch_name = config.snapshot().string_by(
+        "branch",
+        Some(format!("{branch_name}.stgit").as_str().into()),
+        "parentbranch",
+    );
+    let parent_branch_name = parent_branch_name.as_ref().and_then(|bs| bs.to_str().ok());
+
+    let parent_object = if let Some(name) = parent_branch_name {
+        repo.rev_parse_single_ex(name)?.object()?
+    } else {
+        repo.rev_parse_single("heads/origin")
+            .map_err(|_| anyhow!("cannot find a parent branch for `{branch_name}`"))?
+            .object()?
+    };
+    let parent_commit = parent_object
+        .peel_tags_to_end()
+        .context("peel parent object to commit")?
+        .try_into_commit()?;
+    Ok(parent_commit.id)
+}
+
+/// Predict the outcome of pushing back the applied patches onto the pulled commit.
+///
+/// With the "pull" pull-policy, the pulled changes are predicted as a rebase onto the
+/// fetched commit.
+fn predict_pull(
+    repo: &gix::Repository,
+    stack: Stack,
+    matches: &ArgMatches,
+    command_line: &[String],
+    target_id: gix::ObjectId,
+) -> Result<()> {
+    let config = stack.config();
+    let applied = stack.applied().to_vec();
+    let merged_mode = argset::resolve_merged_mode(&config, matches)?;
+    let not_merged = argset::resolve_not_merged(matches, &stack)?;
+    let merged_check = merged_mode
+        .map(|merged_mode| {
+            argset::make_merged_check(
+                repo,
+                &config,
+                merged_mode,
+                not_merged,
+                [stack.base().id],
+                target_id,
+            )
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let target = Rc::new(repo.find_commit(target_id)?);
+    let nopush = matches.get_flag("nopush");
+
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .predict_pushes(true)
+        .use_index_and_worktree(true)
+        .allow_push_conflicts(argset::resolve_allow_push_conflicts(&config, matches))
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .ignore_date(matches.get_flag("ignore-date"))
+        .merged_check(merged_check)
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            trans.pop_patches(|pn| applied.contains(pn))?;
+            trans.set_base(target.clone());
+            if !nopush {
+                trans.push_patches(&applied, merged_mode.is_some())?;
+            }
+            Ok(())
+        })
+        .execute("pull")?;
+    Ok(())
+}
+
+/// Instructions for resolving a pull that stopped before pushing back all patches.
+const CONTINUE_HINT: &str = "\
+    resolve the conflicts and run `stg pull --continue` to push back the remaining \
+    patches\n\
+    or run `stg pull --abort` to restore the stack to how it was before the pull";
+
+/// Push back the patches that were applied before the pull.
+///
+/// Patches from the pull record that are already applied, e.g. when continuing after
+/// resolving conflicts, are skipped. The pull record is removed and any autostashed
+/// changes are reapplied once all patches are pushed back.
+fn push_back(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    command_line: &[String],
+    pull: &PullInProgress,
+) -> Result<()> {
+    // The pull and rebase actions may have moved the stack's branch reference, so the
+    // stack is initialized afresh.
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
     let stack = if stack.is_head_top() {
         stack
     } else {
         // Record a new stack state with updated head since the pull moved the head.
         stack.log_external_mods(Some("pull"))?
     };
+    let config = stack.config();
 
-    if !matches.get_flag("nopush") {
-        stack.check_head_top_mismatch()?;
-        let check_merged = matches.get_flag("merged");
+    stack.check_head_top_mismatch()?;
+    let to_push: Vec<PatchName> = pull
+        .applied
+        .iter()
+        .filter(|pn| stack.is_unapplied(pn))
+        .cloned()
+        .collect();
+
+    if !to_push.is_empty() {
+        let merged_mode = argset::resolve_merged_mode(&config, matches)?;
+        let not_merged = argset::resolve_not_merged(matches, &stack)?;
+        let merged_check = merged_mode
+            .map(|merged_mode| {
+                argset::make_merged_check(
+                    repo,
+                    &config,
+                    merged_mode,
+                    not_merged,
+                    [pull.old_base_id],
+                    stack.base().id,
+                )
+            })
+            .transpose()?
+            .unwrap_or_default();
         stack
             .setup_transaction()
+            .command_line(command_line)
             .use_index_and_worktree(true)
-            .allow_push_conflicts(allow_push_conflicts)
-            .with_output_stream(get_color_stdout(matches))
-            .transact(|trans| trans.push_patches(&applied, check_merged))
-            .execute("pull (reapply)")?;
+            .allow_push_conflicts(argset::resolve_allow_push_conflicts(&config, matches))
+            .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+            .ignore_date(matches.get_flag("ignore-date"))
+            .merged_check(merged_check)
+            .with_output(Output::new(matches))
+            .transact(|trans| trans.push_patches(&to_push, merged_mode.is_some()))
+            .execute("pull (reapply)")
+            .map_err(|e| match e.downcast::<TransactionError>() {
+                Ok(TransactionError::TransactionHalt { msg, conflicts }) => {
+                    TransactionError::TransactionHalt {
+                        msg: format!("{msg}\n{CONTINUE_HINT}"),
+                        conflicts,
+                    }
+                    .into()
+                }
+                Ok(e) => e.into(),
+                Err(e) => e,
+            })?;
     }
 
-    if config.boolean("stgit.keepoptimized").unwrap_or(false) {
-        stupid.repack()?;
-    }
+    PullInProgress::remove(repo)?;
+    argset::reapply_autostash(repo, &pull.branch_name, matches)
+}
 
-    Ok(())
+/// Continue a pull after conflicts are resolved, without fetching again.
+fn continue_pull(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    command_line: &[String],
+) -> Result<()> {
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    let pull = PullInProgress::read_for_branch(repo, stack.get_branch_name())?;
+
+    repo.check_repository_state()?;
+    let statuses = repo.stupid().statuses(None)?;
+    statuses.check_conflicts()?;
+    statuses.check_index_and_worktree_clean()?;
+
+    push_back(repo, matches, command_line, &pull)
+}
+
+/// Abort a pull, restoring the stack and its base to how they were before the pull.
+fn abort_pull(repo: &gix::Repository, matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    let pull = PullInProgress::read_for_branch(repo, stack.get_branch_name())?;
+
+    repo.check_repository_state()?;
+    let state_commit = repo.find_commit(pull.state_id)?;
+    let state = StackState::from_commit(repo, &state_commit)?;
+
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .use_index_and_worktree(true)
+        .allow_bad_head(true)
+        .discard_changes(true)
+        .with_output(Output::new(matches))
+        .transact(|trans| trans.reset_to_state(state))
+        .execute("pull (abort)")?;
+
+    PullInProgress::remove(repo)?;
+    argset::reapply_autostash(repo, &pull.branch_name, matches)
 }
@@ -2,21 +2,30 @@
 
 //! `stg push` implementation.
 
+use std::{rc::Rc, time::Instant};
+
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
-    color::get_color_stdout,
-    ext::RepositoryExtended,
-    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    ext::{CommitExtended, RepositoryExtended},
+    output::Output,
+    patch::{
+        patchedit, patchrange, LocationConstraint, PatchLocator, PatchName, PatchRange,
+        RangeConstraint,
+    },
+    stack::{
+        conflicts, message, pending, InitializationPolicy, MergedCheck, Stack, StackAccess,
+        StackState, StackStateAccess, TransactionError,
+    },
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "push",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -29,19 +38,54 @@ fn make() -> clap::Command {
              \n\
              By default, the first unapplied patch is pushed.\n\
              \n\
+             With --all, patches may be excluded from the push by giving them as \
+             '!<patch>', e.g. 'stg push --all '!broken''.\n\
+             \n\
+             With --interactive, the unapplied patches are listed in an editor. \
+             The patches remaining in the list when the editor exits are pushed in \
+             the order they are listed.\n\
+             \n\
              Unapplied patches may be pushed in arbitrary order, but out of \
              order pushes may result in merge conflicts. If there are conflicts \
              while pushing a patch, the conflicts are written to the work tree \
              and the push command halts. Conflicts may then be resolved using \
              the normal Git methods, or alternatively the push may be undone \
-             using 'stg undo'.",
+             using 'stg undo'. A report of the conflicted patch and the kind of \
+             conflict for each conflicted path is printed when the push halts.\n\
+             \n\
+             Once the conflicts are resolved and the resolved paths are marked \
+             with 'stg resolved' or 'git add', 'stg push --continue' records the \
+             resolution in the conflicted patch and pushes the patches that \
+             remained to be pushed. Alternatively, 'stg push --abort' restores the \
+             stack, index, and work tree to their state from before the push. \
+             Other pushes, pops, and gotos are refused while a halted push is in \
+             progress. Recording the resolution with 'stg refresh' instead ends \
+             the halted push without pushing the remaining patches.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg push 'fix-*'",
+                "Push the unapplied patches whose names begin with `fix-`",
+            ),
+            (
+                "stg push --all '!broken'",
+                "Push all unapplied patches except for `broken`",
+            ),
+            (
+                "stg push --to p4",
+                "Push the unapplied patches up to and including `p4`",
+            ),
+        ]))
         .override_usage(super::make_usage(
             "stg push",
             &[
                 "[OPTIONS] [patch]...",
                 "[OPTIONS] -n <number>",
-                "[OPTIONS] --all",
+                "[OPTIONS] --to <patch>",
+                "[OPTIONS] --all [!<patch>]...",
+                "[OPTIONS] --interactive",
+                "--continue",
+                "--abort",
             ],
         ))
         .arg(
@@ -50,7 +94,7 @@ fn make() -> clap::Command {
                 .value_name("patch")
                 .num_args(1..)
                 .value_parser(clap::value_parser!(PatchRange))
-                .conflicts_with_all(["all", "number"]),
+                .conflicts_with_all(["number", "to"]),
         )
         .arg(
             Arg::new("all")
@@ -58,7 +102,7 @@ fn make() -> clap::Command {
                 .short('a')
                 .help("Push all unapplied patches")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with("number"),
+                .conflicts_with_all(["number", "to"]),
         )
         .arg(
             Arg::new("number")
@@ -74,13 +118,38 @@ fn make() -> clap::Command {
                 .num_args(1)
                 .allow_negative_numbers(true)
                 .value_name("n")
-                .value_parser(clap::value_parser!(isize)),
+                .value_parser(clap::value_parser!(isize))
+                .conflicts_with("to"),
+        )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .help("Push the unapplied patches up to and including <patch>")
+                .long_help(
+                    "Push the unapplied patches up to and including <patch>, such that \
+                     <patch> becomes the topmost applied patch, the same as 'stg goto' \
+                     does for an unapplied patch. Nothing is pushed, with a notice, if \
+                     <patch> already is the topmost applied patch.",
+                )
+                .value_name("patch")
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with("reverse"),
+        )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .short('i')
+                .help("Choose the patches to push, and their order, in an editor")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["patchranges-unapplied", "all", "number", "to"]),
         )
         .arg(
             Arg::new("reverse")
                 .long("reverse")
                 .help("Push the patches in reverse order")
-                .action(clap::ArgAction::SetTrue),
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("interactive"),
         )
         .arg(
             Arg::new("noapply")
@@ -110,20 +179,129 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("porcelain")
+                .long("porcelain")
+                .help("Report conflicts in a machine-readable format")
+                .long_help(
+                    "Report conflicts in a machine-readable format.\n\
+                     \n\
+                     Instead of the conflict report, a line of the form \
+                     'conflict <patch> <kind> <path>' is printed to stdout for \
+                     each conflicted path. The kind is one of 'content', \
+                     'add/add', 'delete/modify', 'rename', or 'mode'.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .help("Resume a push halted by conflicts")
+                .long_help(
+                    "Resume a push halted by conflicts.\n\
+                     \n\
+                     The resolved changes are recorded in the conflicted patch \
+                     and the patches remaining from the halted push are pushed. \
+                     All conflicts must be resolved first.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "patchranges-unapplied",
+                    "all",
+                    "number",
+                    "to",
+                    "interactive",
+                    "reverse",
+                    "noapply",
+                    "set-tree",
+                    "dry-run",
+                    "keep",
+                ]),
+        )
+        .arg(
+            Arg::new("abort")
+                .long("abort")
+                .help("Abandon a push halted by conflicts")
+                .long_help(
+                    "Abandon a push halted by conflicts.\n\
+                     \n\
+                     The stack, index, and work tree are restored to their state \
+                     from before the push. Any changes made while resolving the \
+                     conflicts are discarded.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "patchranges-unapplied",
+                    "all",
+                    "number",
+                    "to",
+                    "interactive",
+                    "reverse",
+                    "noapply",
+                    "set-tree",
+                    "dry-run",
+                    "keep",
+                    "continue",
+                ]),
+        )
+        .arg(argset::dry_run_arg())
         .arg(argset::keep_arg())
-        .arg(argset::merged_arg())
+        .arg(argset::force_overwrite_untracked_arg())
+        .arg(argset::merged_mode_arg().long_help(
+            "Check for patches that may have been merged upstream.\n\
+             \n\
+             Before pushing, each patch is checked to see if its changes already exist \
+             in the tree the patches are pushed onto. Patches whose changes are found \
+             will still exist in the stack, but become empty.\n\
+             \n\
+             With `--merged=subject`, patches whose diffs are not found are \
+             additionally considered merged if their subject matches, after \
+             normalization, the subject of an upstream commit, i.e. a commit in the \
+             stack base that is not in the patches' parents, as after `stg rebase \
+             --nopush`.\n\
+             \n\
+             With `--merged=aggressive`, runs of consecutive patches whose combined \
+             diff has the same patch id, see git-patch-id(1), as one of the upstream \
+             commits, are also considered merged. This detects patches that upstream \
+             squashed into a single commit. The matching upstream commit is reported \
+             for each such run. Runs are limited to the number of patches given by \
+             the \"stgit.mergedmaxrun\" configuration variable, which defaults to 5. \
+             The subjects of the remaining patches are then matched as with \
+             `--merged=subject`.\n\
+             \n\
+             With just `--merged`, the mode is determined by the \
+             \"stgit.mergeddetect\" configuration variable, which defaults to \
+             \"exact\".",
+        ))
+        .arg(argset::not_merged_arg())
         .arg(argset::committer_date_is_author_date_arg())
         .arg(argset::push_conflicts_arg())
+        .arg(argset::marker_size_arg())
+        .arg(argset::push_verbose_arg())
+        .arg(argset::restore_mtime_arg())
+        .args(argset::sign_args())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
-    let repo = gix::Repository::open()?;
+const INTERACTIVE_INSTRUCTIONS: &str = "\
+# The patches listed above are pushed in the order given. Remove or comment
+# out a patch's line to leave it unapplied. An empty list aborts the push.
+";
+
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let mut repo = gix::Repository::open()?;
+    argset::set_commit_signing(&mut repo, matches)?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
 
+    if matches.get_flag("continue") {
+        return continue_push(&repo, stack, matches, command_line);
+    } else if matches.get_flag("abort") {
+        return abort_push(&repo, stack, matches, command_line);
+    }
+
     let opt_number = matches.get_one::<isize>("number").copied();
-    let allow_push_conflicts =
-        argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches);
+    let allow_push_conflicts = argset::resolve_allow_push_conflicts(&stack.config(), matches);
+    let restore_mtime = argset::resolve_restore_mtime(&stack.config(), matches);
 
     if Some(0) == opt_number {
         return Ok(());
@@ -131,8 +309,20 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     let mut patches: Vec<PatchName> =
         if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-unapplied") {
-            patchrange::resolve_names(&stack, range_specs, RangeConstraint::Unapplied).map_err(
-                |e| match e {
+            if matches.get_flag("all")
+                && !range_specs
+                    .clone()
+                    .all(|range| matches!(range, PatchRange::Exclude(_)))
+            {
+                return Err(anyhow!(
+                    "only exclusions, e.g. `!<patch>`, may be used with `--all`"
+                ));
+            }
+            // With `--all`, the exclusions are excluded from all the unapplied patches.
+            let all = matches.get_flag("all").then(PatchRange::all);
+            let ranges = all.iter().chain(range_specs);
+            patchrange::resolve_names(&stack, ranges, RangeConstraint::Unapplied).map_err(|e| {
+                e.map_each(|e| match e {
                     patchrange::Error::Name(crate::patch::name::Error::PatchNotAllowed {
                         patchname,
                         ..
@@ -140,11 +330,35 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         anyhow!("patch `{patchname}` is already applied")
                     }
                     _ => e.into(),
-                },
-            )?
+                })
+            })?
+        } else if let Some(locator) = matches.get_one::<PatchLocator>("to") {
+            let patchname = locator.resolve_name_allowed(&stack, LocationConstraint::Unapplied)?;
+            if stack.applied().last() == Some(&patchname) {
+                Output::new(matches).info(&format!(
+                    "patch `{patchname}` is already the topmost applied patch"
+                ));
+                return Ok(());
+            }
+            let patchname = patchname
+                .constrain(&stack, LocationConstraint::Unapplied)
+                .map_err(|e| match e {
+                    crate::patch::name::Error::PatchNotAllowed { patchname, .. }
+                        if stack.is_applied(&patchname) =>
+                    {
+                        anyhow!("patch `{patchname}` is already applied")
+                    }
+                    _ => e.into(),
+                })?;
+            let pos = stack
+                .unapplied()
+                .iter()
+                .position(|pn| pn == &patchname)
+                .expect("patch is unapplied");
+            stack.unapplied()[..=pos].to_vec()
         } else if stack.unapplied().is_empty() {
             return Err(anyhow!("no unapplied patches"));
-        } else if matches.get_flag("all") {
+        } else if matches.get_flag("all") || matches.get_flag("interactive") {
             stack.unapplied().to_vec()
         } else if let Some(number) = opt_number {
             let num_unapplied = stack.unapplied().len();
@@ -172,27 +386,64 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let reverse_flag = matches.get_flag("reverse");
     let noapply_flag = matches.get_flag("noapply");
     let settree_flag = matches.get_flag("set-tree");
-    let merged_flag = matches.get_flag("merged");
     let keep_flag = matches.get_flag("keep");
 
     repo.check_repository_state()?;
+    let status_start = Instant::now();
     let statuses = stupid.statuses(None)?;
+    let status_check = status_start.elapsed();
+    stack.check_conflicted_patch()?;
+    stack.check_pending_push()?;
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
     if !keep_flag && !noapply_flag {
         statuses.check_index_and_worktree_clean()?;
     }
 
+    if matches.get_flag("interactive") {
+        patches = patchedit::edit_patch_list(
+            &stack,
+            &patches,
+            LocationConstraint::Unapplied,
+            INTERACTIVE_INSTRUCTIONS,
+            &stack.config(),
+        )?;
+        if patches.is_empty() {
+            return Err(anyhow!("no patches to push"));
+        }
+    }
+
     if reverse_flag {
         patches.reverse();
     }
 
-    stack
+    let merged_check = make_merged_check(&stack, matches, &patches)?;
+    let check_merged = merged_check.is_some();
+    let dry_run = matches.get_flag("dry-run");
+    let state_id = repo
+        .find_reference(stack.get_stack_refname())?
+        .peel_to_id_in_place()?
+        .detach();
+    let stash_id = if keep_flag && !dry_run && !statuses.is_empty() {
+        stupid.stash_create("stgit: changes kept by push")?
+    } else {
+        None
+    };
+
+    let result = stack
         .setup_transaction()
+        .command_line(command_line)
+        .dry_run(dry_run)
         .use_index_and_worktree(true)
+        .keep_local_changes(keep_flag)
+        .force_overwrite_untracked(matches.get_flag("force-overwrite-untracked"))
         .allow_push_conflicts(allow_push_conflicts)
+        .restore_mtime(restore_mtime)
+        .conflict_marker_size(matches.get_one::<usize>("marker-size").copied())
         .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
-        .with_output_stream(get_color_stdout(matches))
+        .status_check_time(status_check)
+        .merged_check(merged_check.unwrap_or_default())
+        .with_output(Output::new(matches))
         .transact(|trans| {
             if settree_flag {
                 for (i, patchname) in patches.iter().enumerate() {
@@ -211,10 +462,298 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 );
                 trans.reorder_patches(None, Some(&unapplied), None)
             } else {
-                trans.push_patches(&patches, merged_flag)
+                trans.push_patches(&patches, check_merged)
             }
         })
-        .execute("push")?;
+        .execute(&message::with_patches("push", &patches));
+
+    if is_conflicts_halt(&result) && !dry_run {
+        record_pending_push(&repo, state_id, stash_id, &patches)?;
+    }
+    report_result(&repo, result, matches)
+}
+
+/// Make the check for patches merged upstream selected with `--merged[=<mode>]`.
+///
+/// The upstream commits are those in the stack's base that are not in the parents of
+/// the patches to push, e.g. the commits the base moved by with `stg rebase --nopush`.
+/// `None` is returned when `--merged` is not given.
+fn make_merged_check(
+    stack: &Stack,
+    matches: &ArgMatches,
+    patches: &[PatchName],
+) -> Result<Option<MergedCheck>> {
+    let config = stack.config();
+    let Some(merged_mode) = argset::resolve_merged_mode(&config, matches)? else {
+        return Ok(None);
+    };
+    let mut parent_ids = Vec::new();
+    for patchname in patches {
+        let parent_id = stack.get_patch_commit(patchname).get_parent_commit()?.id;
+        if !parent_ids.contains(&parent_id) {
+            parent_ids.push(parent_id);
+        }
+    }
+    let not_merged = argset::resolve_not_merged(matches, stack)?;
+    argset::make_merged_check(
+        stack.repo,
+        &config,
+        merged_mode,
+        not_merged,
+        parent_ids,
+        stack.base().id,
+    )
+    .map(Some)
+}
+
+/// Resume the push halted by conflicts with `--continue`.
+fn continue_push(
+    repo: &gix::Repository,
+    stack: Stack,
+    matches: &ArgMatches,
+    command_line: &[String],
+) -> Result<()> {
+    let Some(pending) = stack.pending_push()? else {
+        return Err(anyhow!("no push in progress"));
+    };
+    let stupid = repo.stupid();
+    repo.check_repository_state()?;
+    if !stupid.ls_files_unmerged()?.is_empty() {
+        return Err(anyhow!(
+            "unresolved conflicts remain; mark resolved paths with `stg resolved` \
+             before running `stg push --continue`"
+        ));
+    }
+    stack.check_head_top_mismatch()?;
+    let branch_name = stack.get_branch_name().to_string();
+
+    // Only the previously conflicted paths are folded into the patch when the push
+    // kept unrelated local changes.
+    let statuses = stupid.statuses(None)?;
+    let conflicted_paths: Option<Vec<Vec<u8>>> = if pending.stash_id.is_some() {
+        Some(
+            conflicts::load(repo, &branch_name, stack.get_branch_head().id)?
+                .unwrap_or_default()
+                .iter()
+                .map(|entry| entry.path().to_vec())
+                .collect(),
+        )
+    } else {
+        None
+    };
+    let resolved_paths: Vec<&std::path::Path> = statuses
+        .iter()
+        .filter(|entry| {
+            conflicted_paths
+                .as_ref()
+                .map_or(true, |paths| paths.iter().any(|p| p == entry.path_bytes()))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    if !resolved_paths.is_empty() {
+        stupid.update_index(Some(&resolved_paths))?;
+    }
+    let tree_id = stupid.with_temp_index(|stupid_temp| {
+        stupid_temp.read_tree(stack.get_branch_head().tree_id()?.detach())?;
+        stupid_temp.update_index(Some(&resolved_paths))?;
+        stupid_temp.write_tree()
+    })?;
+
+    let patchname = pending.patchname;
+    let patch_commit = stack.get_patch_commit(&patchname).clone();
+    let stack = if tree_id != patch_commit.tree_id()?.detach() {
+        let parent_id = patch_commit.get_parent_commit()?.id;
+        let (extra_headers, _) = patch_commit.rewritable_extra_headers(&[parent_id])?;
+        let commit_id = repo.commit_ex_with_headers(
+            &patch_commit.author_strict()?,
+            repo.get_committer()?,
+            &patch_commit.message_ex(),
+            tree_id,
+            [parent_id],
+            extra_headers,
+        )?;
+        stack
+            .setup_transaction()
+            .command_line(command_line)
+            .with_output(Output::new(matches))
+            .transact(|trans| trans.update_patch(&patchname, commit_id))
+            .execute(&format!("push --continue {patchname} (record resolution)"))?
+    } else {
+        stack
+    };
+    conflicts::remove(repo, &branch_name)?;
+
+    let patches: Vec<PatchName> = pending
+        .remaining
+        .into_iter()
+        .filter(|pn| stack.is_unapplied(pn))
+        .collect();
+    if patches.is_empty() {
+        pending::remove(repo, &branch_name)?;
+        return argset::reapply_autostash(repo, &branch_name, matches);
+    }
+
+    let keep_flag = pending.stash_id.is_some();
+    if !keep_flag {
+        stupid.statuses(None)?.check_index_and_worktree_clean()?;
+    }
+    let allow_push_conflicts = argset::resolve_allow_push_conflicts(&stack.config(), matches);
+    let restore_mtime = argset::resolve_restore_mtime(&stack.config(), matches);
+    let merged_check = make_merged_check(&stack, matches, &patches)?;
+    let check_merged = merged_check.is_some();
+    let result = stack
+        .setup_transaction()
+        .command_line(command_line)
+        .use_index_and_worktree(true)
+        .keep_local_changes(keep_flag)
+        .force_overwrite_untracked(matches.get_flag("force-overwrite-untracked"))
+        .allow_push_conflicts(allow_push_conflicts)
+        .restore_mtime(restore_mtime)
+        .conflict_marker_size(matches.get_one::<usize>("marker-size").copied())
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .merged_check(merged_check.unwrap_or_default())
+        .with_output(Output::new(matches))
+        .transact(|trans| trans.push_patches(&patches, check_merged))
+        .execute(&message::with_patches("push --continue", &patches));
+
+    if is_conflicts_halt(&result) {
+        record_pending_push(repo, pending.state_id, pending.stash_id, &patches)?;
+    } else {
+        pending::remove(repo, &branch_name)?;
+        if result.is_ok() {
+            argset::reapply_autostash(repo, &branch_name, matches)?;
+        }
+    }
+    report_result(repo, result, matches)
+}
+
+/// Restore the stack as it was before the push halted by conflicts with `--abort`.
+fn abort_push(
+    repo: &gix::Repository,
+    stack: Stack,
+    matches: &ArgMatches,
+    command_line: &[String],
+) -> Result<()> {
+    let Some(pending) = stack.pending_push()? else {
+        return Err(anyhow!("no push in progress"));
+    };
+    let branch_name = stack.get_branch_name().to_string();
+    let state_commit = Rc::new(repo.find_commit(pending.state_id)?);
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .use_index_and_worktree(true)
+        .allow_bad_head(true)
+        .discard_changes(true)
+        .with_output(Output::new(matches))
+        .transact(|trans| trans.reset_to_state(StackState::from_commit(repo, &state_commit)?))
+        .execute(&format!("push --abort {}", pending.patchname))?;
+    pending::remove(repo, &branch_name)?;
+    conflicts::remove(repo, &branch_name)?;
+    if let Some(stash_id) = pending.stash_id {
+        repo.stupid().stash_apply_index(stash_id)?;
+    }
+    argset::reapply_autostash(repo, &branch_name, matches)
+}
+
+/// Determine whether a push result is a halt due to conflicts.
+pub(super) fn is_conflicts_halt<T>(result: &Result<T>) -> bool {
+    matches!(
+        result
+            .as_ref()
+            .err()
+            .and_then(|err| err.downcast_ref::<TransactionError>()),
+        Some(TransactionError::TransactionHalt {
+            conflicts: true,
+            ..
+        })
+    )
+}
+
+/// Record the push halted on conflicts so that it may be continued or aborted.
+///
+/// The conflicted patch is the topmost applied patch and the remaining patches are
+/// those following it that are still unapplied.
+pub(super) fn record_pending_push(
+    repo: &gix::Repository,
+    state_id: gix::ObjectId,
+    stash_id: Option<gix::ObjectId>,
+    patches: &[PatchName],
+) -> Result<()> {
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    let patchname = stack
+        .applied()
+        .last()
+        .ok_or(super::Error::NoAppliedPatches)?
+        .clone();
+    let remaining = patches
+        .iter()
+        .skip_while(|pn| *pn != &patchname)
+        .skip(1)
+        .filter(|pn| stack.is_unapplied(pn))
+        .cloned()
+        .collect();
+    pending::record(
+        repo,
+        stack.get_branch_name(),
+        &pending::PendingPush {
+            state_id,
+            head_id: stack.get_branch_head().id,
+            stash_id,
+            patchname,
+            remaining,
+        },
+    )
+}
+
+/// Report the outcome of a push, printing porcelain conflict lines if requested.
+fn report_result(
+    repo: &gix::Repository,
+    result: Result<Stack>,
+    matches: &ArgMatches,
+) -> Result<()> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(err) if matches.get_flag("porcelain") => Err(report_porcelain_conflicts(repo, err)),
+        Err(err) => Err(err),
+    }
+}
 
-    Ok(())
+/// Print porcelain conflict lines if `err` halted the push due to conflicts.
+///
+/// The returned error no longer indicates conflicts so that the default conflict
+/// report is not also printed.
+fn report_porcelain_conflicts(repo: &gix::Repository, err: anyhow::Error) -> anyhow::Error {
+    match err.downcast::<TransactionError>() {
+        Ok(TransactionError::TransactionHalt {
+            msg,
+            conflicts: true,
+        }) => {
+            let report = || -> Result<()> {
+                let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+                let patchname = stack
+                    .applied()
+                    .last()
+                    .ok_or(super::Error::NoAppliedPatches)?;
+                let unmerged = conflicts::parse_unmerged(&repo.stupid().ls_files_unmerged()?)?;
+                conflicts::write_porcelain(
+                    &mut std::io::stdout().lock(),
+                    patchname,
+                    &conflicts::classify(&unmerged),
+                )?;
+                Ok(())
+            };
+            if let Err(report_err) = report() {
+                report_err
+            } else {
+                TransactionError::TransactionHalt {
+                    msg,
+                    conflicts: false,
+                }
+                .into()
+            }
+        }
+        Ok(err) => err.into(),
+        Err(err) => err,
+    }
 }
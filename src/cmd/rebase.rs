@@ -10,17 +10,21 @@ use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::RepositoryExtended,
+    output::Output,
     patch::{patchedit, PatchName, SingleRevisionSpec},
     print_info_message,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stack::{
+        InitializationPolicy, MergedCheck, Stack, StackAccess, StackConfig, StackStateAccess,
+        TransactionError,
+    },
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "rebase",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -49,8 +53,34 @@ fn make() -> clap::Command {
         .arg(
             Arg::new("committish")
                 .help("New base commit for the stack")
+                .long_help(
+                    "New base commit for the stack. Defaults to the top of the branch \
+                     named by the \"branch.<name>.stgit.stackedon\" configuration \
+                     variable, if set, or else to the branch's upstream.",
+                )
                 .value_parser(clap::value_parser!(SingleRevisionSpec)),
         )
+        .arg(
+            Arg::new("chain")
+                .long("chain")
+                .help("Also rebase the branches stacked on this branch")
+                .long_help(
+                    "After rebasing the current branch, rebase each branch whose \
+                     \"branch.<name>.stgit.stackedon\" configuration names the current \
+                     branch onto the current branch's new top, and so on for the \
+                     branches stacked on those branches. The stacked branches' patches \
+                     are determined by their recorded stack bases, such that only their \
+                     own patches are pushed back onto the new top.\n\
+                     \n\
+                     The chain stops at the first branch whose patches conflict, which \
+                     then remains checked out, and instructions for resuming the chain \
+                     for it and for each branch not yet rebased are reported. Otherwise, \
+                     the original branch is checked out again once all stacked branches \
+                     are rebased.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["interactive", "nopush", "dry-run", "autostash"]),
+        )
         .arg(
             Arg::new("interactive")
                 .long("interactive")
@@ -66,52 +96,121 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with("merged"),
         )
-        .arg(argset::merged_arg().long_help(
+        .arg(
+            argset::dry_run_arg()
+                .long_help(DRY_RUN_HELP)
+                .conflicts_with("interactive"),
+        )
+        .arg(argset::merged_mode_arg().long_help(
             "Check for patches that may have been merged upstream.\n\
              \n\
              When pushing-back patches, each patch is checked to see if its changes \
              already exist in the new stack base. If a patch's changes are detected to \
              have been merged, the patch will still exist in the stack, but become \
-             empty after the rebase operation.",
+             empty after the rebase operation.\n\
+             \n\
+             With `--merged=subject`, patches whose diffs are not found upstream are \
+             additionally considered merged if their subject matches, after \
+             normalization, the subject of an upstream commit added since the old \
+             stack base. This helps detect patches applied upstream with small edits. \
+             The matching upstream commit is reported for each such patch.\n\
+             \n\
+             With `--merged=aggressive`, runs of consecutive patches whose combined \
+             diff has the same patch id, see git-patch-id(1), as one of these upstream \
+             commits are also considered merged, before subjects are matched. This \
+             detects patches that upstream squashed into a single commit. Runs are \
+             limited to the number of patches given by the \"stgit.mergedmaxrun\" \
+             configuration variable, which defaults to 5.\n\
+             \n\
+             With just `--merged`, the mode is determined by the \"stgit.mergeddetect\" \
+             configuration variable, which defaults to \"exact\".",
         ))
+        .arg(argset::not_merged_arg())
         .arg(argset::committer_date_is_author_date_arg())
-        .arg(
-            Arg::new("autostash")
-                .long("autostash")
-                .help("Stash changes before the rebase and reapply them after")
-                .long_help(
-                    "Automatically create a temporary stash before the operation \
-                     begins, and apply it after the operation completes. This allows a \
-                     rebase to be performed on a dirty work tree. Note however that \
-                     the final stash application may result in non-trivial conflicts.",
-                )
-                .action(clap::ArgAction::SetTrue),
-        )
+        .arg(argset::ignore_date_arg())
+        .arg(argset::autostash_arg("rebase"))
         .arg(argset::push_conflicts_arg())
+        .arg(argset::push_verbose_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+/// Long help for the `--dry-run` option of `stg rebase` and `stg pull`.
+pub(super) const DRY_RUN_HELP: &str =
+    "Predict the outcome of popping the patches, moving the stack base, and pushing \
+     the patches back, without changing anything. The patches are pushed with \
+     in-memory merges and a table is reported with the predicted outcome for each \
+     patch: clean, empty, merged, or conflicts along with the first conflicting \
+     path. Pushing continues past conflicting patches such that every patch is \
+     predicted.\n\
+     \n\
+     The exit status is 3 if any conflicts are predicted, allowing scripts to detect \
+     risky rebases. The prediction does not account for conflict resolutions \
+     recorded by git-rerere(1) or for custom merge drivers.";
+
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
-    let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
-    let config = repo.config_snapshot();
+    let committish = matches
+        .get_one::<SingleRevisionSpec>("committish")
+        .map(|target_rev_spec| {
+            let target_name = matches
+                .get_raw("committish")
+                .and_then(|mut values| values.next())
+                .expect("committish is present")
+                .to_string_lossy()
+                .to_string();
+            (target_rev_spec, target_name)
+        });
+    if matches.get_flag("chain") {
+        rebase_chain(&repo, matches, command_line, committish)
+    } else {
+        rebase_stack(&repo, matches, command_line, committish)
+    }
+}
+
+/// Rebase the stack of the current branch.
+///
+/// Without a `committish`, the stack is rebased onto the top of the branch it is
+/// stacked on or else onto the branch's upstream.
+fn rebase_stack(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    command_line: &[String],
+    committish: Option<(&SingleRevisionSpec, String)>,
+) -> Result<()> {
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    let config = stack.config();
     let stupid = repo.stupid();
     let branch_name = stack.get_branch_name().to_string();
     let allow_push_conflicts = argset::resolve_allow_push_conflicts(&config, matches);
     let committer_date_is_author_date = matches.get_flag("committer-date-is-author-date");
+    let ignore_date = matches.get_flag("ignore-date");
     let interactive = matches.get_flag("interactive");
 
-    let target_commit = if let Some(target_rev_spec) =
-        matches.get_one::<SingleRevisionSpec>("committish")
-    {
-        target_rev_spec.resolve(&repo, Some(&stack))?.commit
+    let (target_commit, target_name) = if let Some((target_rev_spec, target_name)) = committish {
+        (
+            target_rev_spec.resolve(repo, Some(&stack))?.commit,
+            target_name,
+        )
+    } else if let Some(stacked_on) = get_stacked_on(&config, &branch_name) {
+        let commit = repo
+            .find_reference(format!("refs/heads/{stacked_on}").as_str())
+            .map_err(|_| {
+                anyhow!("branch `{stacked_on}`, which `{branch_name}` is stacked on, not found")
+            })?
+            .peel_to_commit()?;
+        (std::rc::Rc::new(commit), stacked_on)
     } else if let Some(remote_ref) = repo
         .branch_remote_tracking_ref_name(stack.get_branch_refname(), gix::remote::Direction::Fetch)
         .transpose()?
     {
         let id = repo.rev_parse_single(remote_ref.as_bstr())?;
-        id.object()?.into_commit().into()
+        (
+            id.object()?.into_commit().into(),
+            remote_ref.shorten().to_string(),
+        )
     } else if interactive {
-        stack.base().clone()
+        let base = stack.base().clone();
+        let target_name = base.id().shorten_or_id().to_string();
+        (base, target_name)
     } else {
         print_info_message(
             matches,
@@ -132,132 +231,299 @@ fn run(matches: &ArgMatches) -> Result<()> {
             matches,
             &format!(
                 "Already based on {}",
-                formatted_target_id_and_ref(&repo, std::rc::Rc::clone(&target_commit))
+                formatted_target_id_and_ref(repo, std::rc::Rc::clone(&target_commit))
             ),
         );
         return Ok(());
     }
 
-    if stack.is_protected(&config) {
+    if stack.is_protected() {
         return Err(anyhow!("this branch is protected; rebase is not permitted"));
     }
 
     stack.check_head_top_mismatch()?;
-    let clean_result = stupid.statuses(None)?.check_index_and_worktree_clean();
 
-    let autostash = if matches.get_flag("autostash") {
-        true
-    } else {
-        config
-            .boolean_by(
-                "branch",
-                Some(format!("{branch_name}.stgit").as_str().into()),
-                "autostash",
-            )
-            .transpose()
-            .unwrap_or_else(|e| {
-                crate::print_warning_message(
-                    matches,
-                    &format!("Invalid config value `branch.{branch_name}.stgit.autostash`: {e}"),
-                );
-                Some(false)
-            })
-            .or_else(|| {
-                config
-                    .try_boolean("stgit.autostash")
-                    .transpose()
-                    .unwrap_or_else(|e| {
-                        crate::print_warning_message(
-                            matches,
-                            &format!("Invalid config value `stgit.autostash`: {e}"),
-                        );
-                        Some(false)
-                    })
+    let applied = stack.applied().to_vec();
+    let old_base_id = stack.base().id;
+    let merged_mode = argset::resolve_merged_mode(&config, matches)?;
+    let not_merged = argset::resolve_not_merged(matches, &stack)?;
+    let make_merged_check = |new_base_id| -> Result<Option<MergedCheck>> {
+        merged_mode
+            .map(|merged_mode| {
+                argset::make_merged_check(
+                    repo,
+                    &config,
+                    merged_mode,
+                    not_merged,
+                    [old_base_id],
+                    new_base_id,
+                )
             })
-            .unwrap_or(false)
+            .transpose()
     };
 
-    let using_stash = if autostash && clean_result.is_err() {
-        stupid.stash_push()?;
-        true
-    } else if let Err(e) = clean_result {
-        return Err(e);
-    } else {
-        false
-    };
+    if matches.get_flag("dry-run") {
+        // A dirty worktree would be stashed away with `--autostash`.
+        if !argset::resolve_autostash(&config, matches) {
+            stupid.statuses(None)?.check_index_and_worktree_clean()?;
+        }
+        let merged_check = make_merged_check(target_commit.id)?;
+        stack
+            .setup_transaction()
+            .command_line(command_line)
+            .predict_pushes(true)
+            .use_index_and_worktree(true)
+            .allow_push_conflicts(allow_push_conflicts)
+            .committer_date_is_author_date(committer_date_is_author_date)
+            .ignore_date(ignore_date)
+            .merged_check(merged_check.unwrap_or_default())
+            .with_output(Output::new(matches))
+            .transact(|trans| {
+                trans.pop_patches(|pn| applied.contains(pn))?;
+                trans.set_base(target_commit.clone());
+                if !matches.get_flag("nopush") {
+                    trans.push_patches(&applied, merged_mode.is_some())?;
+                }
+                Ok(())
+            })
+            .execute(&format!("rebase onto {target_name}"))?;
+        return Ok(());
+    }
 
-    let applied = stack.applied().to_vec();
+    argset::autostash_changes(repo, &config, &branch_name, matches)?;
 
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(true)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| {
             trans.pop_patches(|pn| applied.contains(pn))?;
             Ok(())
         })
-        .execute("rebase (pop)")?;
+        .execute(&format!("rebase onto {target_name} (pop)"))?;
 
     let rebase_cmd = config
-        .string_by(
-            "branch",
-            Some(format!("{branch_name}.stgit").as_str().into()),
-            "rebasecmd",
-        )
-        .or_else(|| config.string("stgit.rebasecmd"))
+        .string("stgit.rebasecmd")
         .and_then(|bs| bs.to_str().map(str::to_string).ok())
         .unwrap_or_else(|| "git reset --hard".to_string());
     print_info_message(
         matches,
         &format!(
             "Rebasing to {}",
-            formatted_target_id_and_ref(&repo, std::rc::Rc::clone(&target_commit))
+            formatted_target_id_and_ref(repo, std::rc::Rc::clone(&target_commit))
         ),
     );
     stupid.user_rebase(&rebase_cmd, target_commit.id)?;
 
-    let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
     let stack = if stack.is_head_top() {
         stack
     } else {
         // Record a new stack state with updated head since the head moved.
-        stack.log_external_mods(Some("rebase"))?
+        stack.log_external_mods(Some(&format!("rebase onto {target_name}")))?
     };
 
-    if matches.get_flag("interactive") {
+    let merged_check = make_merged_check(stack.base().id)?;
+
+    let result = if matches.get_flag("interactive") {
         interactive_pushback(
             stack,
-            &repo,
             &config,
             matches,
+            command_line,
             &applied,
-            allow_push_conflicts,
-            committer_date_is_author_date,
-        )?;
+            merged_check,
+            &target_name,
+        )
     } else if !matches.get_flag("nopush") {
         stack.check_head_top_mismatch()?;
-        let check_merged = matches.get_flag("merged");
-        stack
+        let state_id = repo
+            .find_reference(stack.get_stack_refname())?
+            .peel_to_commit()?
+            .id;
+        let result = stack
             .setup_transaction()
+            .command_line(command_line)
             .use_index_and_worktree(true)
             .allow_push_conflicts(allow_push_conflicts)
             .committer_date_is_author_date(committer_date_is_author_date)
-            .with_output_stream(get_color_stdout(matches))
-            .transact(|trans| trans.push_patches(&applied, check_merged))
-            .execute("rebase (reapply)")?;
-    }
-
-    if using_stash {
-        if stupid.stash_pop()? {
-            Ok(())
-        } else {
-            Err(super::Error::CausedConflicts("stash pop resulted in conflicts".to_string()).into())
+            .ignore_date(ignore_date)
+            .merged_check(merged_check.unwrap_or_default())
+            .with_output(Output::new(matches))
+            .transact(|trans| trans.push_patches(&applied, merged_mode.is_some()))
+            .execute(&format!("rebase onto {target_name} (reapply)"));
+        if super::push::is_conflicts_halt(&result) {
+            // The push may be resumed like one halted by `stg push`.
+            super::push::record_pending_push(repo, state_id, None, &applied)?;
         }
+        result
+            .map(|_| ())
+            .map_err(|e| match e.downcast::<TransactionError>() {
+                Ok(TransactionError::TransactionHalt {
+                    msg,
+                    conflicts: true,
+                }) => TransactionError::TransactionHalt {
+                    msg: format!("{msg}\n{CONTINUE_HINT}"),
+                    conflicts: true,
+                }
+                .into(),
+                Ok(e) => e.into(),
+                Err(e) => e,
+            })
     } else {
         Ok(())
+    };
+
+    if super::push::is_conflicts_halt(&result) {
+        // Local changes remain stashed until the push is finished.
+        return result;
+    }
+    argset::reapply_autostash(repo, &branch_name, matches)?;
+    result
+}
+
+/// Rebase the current branch and then, recursively, the branches stacked on it.
+///
+/// Each stacked branch is checked out and rebased onto the new top of the branch it is
+/// stacked on, as if by `stg rebase` without a committish.
+fn rebase_chain(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    command_line: &[String],
+    committish: Option<(&SingleRevisionSpec, String)>,
+) -> Result<()> {
+    let stupid = repo.stupid();
+    let start_branch = repo.get_current_branch()?;
+    let start_branchname = start_branch.get_branch_partial_name()?.to_string();
+    let root_hint = format!(
+        "then run `stg rebase --chain{}` to rebase the branches stacked on `{start_branchname}`",
+        committish
+            .as_ref()
+            .map(|(_, target_name)| format!(" {target_name}"))
+            .unwrap_or_default()
+    );
+
+    let mut pending: Vec<(String, String)> = stacked_branches(repo, &start_branchname)
+        .into_iter()
+        .rev()
+        .map(|branch_name| (branch_name, start_branchname.clone()))
+        .collect();
+    if pending.is_empty() {
+        return rebase_stack(repo, matches, command_line, committish);
+    }
+
+    // The stacked branches are checked out in turn.
+    stupid.statuses(None)?.check_index_and_worktree_clean()?;
+    add_chain_hint(
+        rebase_stack(repo, matches, command_line, committish),
+        &root_hint,
+    )?;
+
+    let mut visited = vec![start_branchname.clone()];
+    while let Some((branch_name, stacked_on)) = pending.pop() {
+        if visited.contains(&branch_name) {
+            continue;
+        }
+        visited.push(branch_name.clone());
+        print_info_message(
+            matches,
+            &format!("Rebasing stacked branch `{branch_name}` onto `{stacked_on}`"),
+        );
+        stupid.checkout(&branch_name)?;
+
+        let mut hint = format!(
+            "then run `stg rebase --chain` to rebase the branches stacked on `{branch_name}`"
+        );
+        let remaining: Vec<&String> = pending
+            .iter()
+            .rev()
+            .map(|(name, _)| name)
+            .filter(|name| !visited.contains(name))
+            .collect();
+        if !remaining.is_empty() {
+            hint.push_str(
+                "\nthe chain stopped before rebasing these branches; \
+                 run `stg rebase --chain` on each of them to resume:",
+            );
+            for name in remaining {
+                write!(hint, "\n    {name}")?;
+            }
+        }
+        add_chain_hint(rebase_stack(repo, matches, command_line, None), &hint)?;
+
+        pending.extend(
+            stacked_branches(repo, &branch_name)
+                .into_iter()
+                .rev()
+                .map(|child_name| (child_name, branch_name.clone())),
+        );
+    }
+
+    stupid.checkout(&start_branchname)
+}
+
+/// Get the branch that the stack of the given branch is stacked on.
+///
+/// This is the `branch.<name>.stgit.stackedon` configuration value, which is not
+/// looked up as `stgit.stackedon` since it only makes sense for a particular branch.
+fn get_stacked_on(config: &StackConfig, branch_name: &str) -> Option<String> {
+    config
+        .snapshot()
+        .string(format!("branch.{branch_name}.stgit.stackedon").as_str())
+        .and_then(|value| value.to_str().ok().map(str::to_string))
+        .filter(|value| !value.is_empty())
+}
+
+/// Get the names of the branches that are stacked on the given branch, sorted by name.
+fn stacked_branches(repo: &gix::Repository, branch_name: &str) -> Vec<String> {
+    let snapshot = repo.config_snapshot();
+    let mut branch_names = Vec::new();
+    if let Some(sections) = snapshot.plumbing().sections_by_name("branch") {
+        for section in sections {
+            let Some(name) = section
+                .header()
+                .subsection_name()
+                .and_then(|subsection| subsection.strip_suffix(b".stgit"))
+                .and_then(|name| name.to_str().ok())
+            else {
+                continue;
+            };
+            if section
+                .value("stackedon")
+                .is_some_and(|value| value.as_ref() == branch_name.as_bytes())
+            {
+                branch_names.push(name.to_string());
+            }
+        }
     }
+    branch_names.sort();
+    branch_names.dedup();
+    branch_names
 }
 
+/// Append instructions for resuming a chain of rebases to a conflicts halt.
+fn add_chain_hint(result: Result<()>, hint: &str) -> Result<()> {
+    result.map_err(|e| match e.downcast::<TransactionError>() {
+        Ok(TransactionError::TransactionHalt {
+            msg,
+            conflicts: true,
+        }) => TransactionError::TransactionHalt {
+            msg: format!("{msg}\n{hint}"),
+            conflicts: true,
+        }
+        .into(),
+        Ok(e) => e.into(),
+        Err(e) => e,
+    })
+}
+
+/// Instructions for resolving a rebase that stopped before pushing back all patches.
+const CONTINUE_HINT: &str = "\
+    resolve the conflicts and run `stg push --continue` to push back the remaining \
+    patches\n\
+    or run `stg push --abort` to leave them unapplied";
+
 fn formatted_target_id_and_ref(
     repo: &gix::Repository,
     target_commit: std::rc::Rc<gix::Commit>,
@@ -319,7 +585,7 @@ fn formatted_target_id_and_ref(
     id_and_ref
 }
 
-const INTERACTIVE_APPLY_LINE: &str = "# --- APPLY_LINE ---";
+const INTERACTIVE_APPLY_LINE: &str = "--- APPLY_LINE ---";
 const INTERACTIVE_HELP_LINES: &str = "\
 # Commands:
 #
@@ -354,30 +620,36 @@ enum Action {
 
 fn interactive_pushback(
     stack: Stack,
-    repo: &gix::Repository,
-    config: &gix::config::Snapshot,
+    config: &StackConfig,
     matches: &ArgMatches,
+    command_line: &[String],
     previously_applied: &[PatchName],
-    allow_push_conflicts: bool,
-    committer_date_is_author_date: bool,
+    merged_check: Option<MergedCheck>,
+    target_name: &str,
 ) -> Result<()> {
+    let repo = stack.repo;
     let mut stack = stack;
+    let allow_push_conflicts = argset::resolve_allow_push_conflicts(config, matches);
+    let committer_date_is_author_date = matches.get_flag("committer-date-is-author-date");
+    let ignore_date = matches.get_flag("ignore-date");
+    let check_merged = merged_check.is_some();
 
     if stack.all_patches().next().is_none() {
         return Ok(());
     }
 
     let filename = ".stgit-rebase-interactive.txt";
+    let comment = patchedit::comment_string(config, None)?;
     std::fs::write(
         filename,
-        make_instructions_template(&stack, previously_applied),
+        make_instructions_template(&stack, previously_applied, &comment),
     )?;
 
     let buf = patchedit::call_editor(filename, config)?;
     let buf = buf
         .to_str()
         .map_err(|_| anyhow!("`{filename}` is not valid UTF-8"))?;
-    let mut instructions = parse_instructions(buf)?;
+    let mut instructions = parse_instructions(buf, &comment)?;
 
     validate_instructions(&stack, &instructions)?;
 
@@ -408,7 +680,8 @@ fn interactive_pushback(
                     .collect();
                 stack = stack
                     .setup_transaction()
-                    .with_output_stream(get_color_stdout(matches))
+                    .command_line(command_line)
+                    .with_output(Output::new(matches))
                     .transact(|trans| {
                         let popped_extra = trans.delete_patches(|pn| to_delete.contains(&pn))?;
                         assert!(popped_extra.is_empty());
@@ -432,7 +705,8 @@ fn interactive_pushback(
                     .collect();
                 stack = stack
                     .setup_transaction()
-                    .with_output_stream(get_color_stdout(matches))
+                    .command_line(command_line)
+                    .with_output(Output::new(matches))
                     .transact(|trans| trans.hide_patches(&to_hide))
                     .execute("hide")?;
             }
@@ -457,8 +731,10 @@ fn interactive_pushback(
                         if new_patchname.is_some() || new_commit_id.is_some() {
                             stack = stack
                                 .setup_transaction()
+                                .command_line(command_line)
                                 .committer_date_is_author_date(committer_date_is_author_date)
-                                .with_output_stream(get_color_stdout(matches))
+                                .ignore_date(ignore_date)
+                                .with_output(Output::new(matches))
                                 .transact(|trans| {
                                     let patchname =
                                         if let Some(new_patchname) = new_patchname.as_ref() {
@@ -534,7 +810,8 @@ fn interactive_pushback(
 
                 stack = stack
                     .setup_transaction()
-                    .with_output_stream(get_color_stdout(matches))
+                    .command_line(command_line)
+                    .with_output(Output::new(matches))
                     .transact(|trans| {
                         let new_patchname = super::squash::squash(
                             trans,
@@ -565,28 +842,37 @@ fn interactive_pushback(
             }
         })
         .collect();
-    let check_merged = matches.get_flag("merged");
 
     stack.check_head_top_mismatch()?;
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(true)
         .allow_push_conflicts(allow_push_conflicts)
         .committer_date_is_author_date(committer_date_is_author_date)
-        .with_output_stream(get_color_stdout(matches))
+        .ignore_date(ignore_date)
+        .merged_check(merged_check.unwrap_or_default())
+        .with_output(Output::new(matches))
         .transact(|trans| trans.push_patches(&to_push, check_merged))
-        .execute("rebase (reapply)")?;
+        .execute(&format!("rebase onto {target_name} (reapply)"))?;
 
     Ok(())
 }
 
-fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -> String {
+/// Render the interactive rebase instructions, with comment lines starting with
+/// `comment`.
+fn make_instructions_template(
+    stack: &Stack,
+    previously_applied: &[PatchName],
+    comment: &str,
+) -> String {
     let name_width = stack.all_patches().map(PatchName::len).max().unwrap();
+    let apply_line = format!("{comment} {INTERACTIVE_APPLY_LINE}");
     let mut template = String::with_capacity(4096);
     let mut found_apply_boundary = false;
     for patchname in stack.all_patches() {
         if !found_apply_boundary && !previously_applied.contains(patchname) {
-            writeln!(template, "{INTERACTIVE_APPLY_LINE}").unwrap();
+            writeln!(template, "{apply_line}").unwrap();
             found_apply_boundary = true;
         }
         let commit = stack.get_patch_commit(patchname);
@@ -597,16 +883,20 @@ fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -
             .replace(['\r', '\n'], " ")
             .trim()
             .to_owned();
-        writeln!(template, "keep {patchname:name_width$} # {subject}").unwrap();
+        writeln!(template, "keep {patchname:name_width$} {comment} {subject}").unwrap();
     }
     if !found_apply_boundary {
-        writeln!(template, "{INTERACTIVE_APPLY_LINE}").unwrap();
+        writeln!(template, "{apply_line}").unwrap();
     }
-    template.push_str(INTERACTIVE_HELP_LINES);
+    template.push_str(&INTERACTIVE_HELP_LINES.replace('#', comment));
     template
 }
 
-fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
+/// Parse edited interactive rebase instructions.
+///
+/// Everything following `comment` on a line is a comment, except for the APPLY_LINE.
+fn parse_instructions(buf: &str, comment: &str) -> Result<Vec<Instruction>> {
+    let apply_line = format!("{comment} {INTERACTIVE_APPLY_LINE}");
     let mut instructions = Vec::new();
     let mut apply = true;
 
@@ -614,14 +904,14 @@ fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
         let line = line.trim();
 
         if line.contains(INTERACTIVE_APPLY_LINE) {
-            if line == INTERACTIVE_APPLY_LINE {
+            if line == apply_line {
                 apply = false;
             } else {
                 return Err(anyhow!("bad APPLY_LINE: `{line}`"));
             }
         }
 
-        let instruction_str = if let Some((instruction_str, _comment)) = line.split_once('#') {
+        let instruction_str = if let Some((instruction_str, _comment)) = line.split_once(comment) {
             instruction_str
         } else {
             line
@@ -5,17 +5,18 @@
 use anyhow::Result;
 use clap::Arg;
 
-use super::undo::find_undo_state;
+use super::undo::{find_undo_state, undo_redo_message};
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack},
+    output::Output,
+    stack::{InitializationPolicy, Stack, StackAccess},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "redo",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -53,25 +54,29 @@ fn make() -> clap::Command {
                 .help("Discard changes in the index and worktree")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::no_backup_arg())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
     let redo_steps = matches.get_one::<usize>("number").copied().unwrap_or(1);
 
+    argset::backup_discarded_changes(&repo, stack.get_branch_name(), matches)?;
+
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(true)
         .allow_bad_head(true)
         .discard_changes(matches.get_flag("hard"))
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| {
             let undo_steps = -(redo_steps as isize);
             let redo_state = find_undo_state(trans.stack(), undo_steps)?;
             trans.reset_to_state(redo_state)
         })
-        .execute(&format!("redo {redo_steps}"))?;
+        .execute(&undo_redo_message("redo", redo_steps as isize))?;
 
     Ok(())
 }
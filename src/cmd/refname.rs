@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg ref` implementation.
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::RepositoryExtended,
+    patch::{patchrange, PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, Stack, StackAccess},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "ref",
+    category: super::CommandCategory::StackInspection,
+    read_only: true,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Print the git references of patches and stacks")
+        .long_about(
+            "Print the fully qualified git reference names of patches, or of a \
+             branch's stack state and stack log.\n\
+             \n\
+             Each patch given by a patch locator or range is resolved and the name of \
+             the reference pointing to its commit is printed, one per line, in the \
+             order the patches are resolved. Hidden patches may be named as well.\n\
+             \n\
+             With '--stack', the reference of the branch's stack state is printed. \
+             With '--log', the reference whose history forms the stack log, as \
+             walked by 'stg log', is printed. In the current repository layout both \
+             are the same reference, but tools should not rely on that.\n\
+             \n\
+             This command is the stable interface for mapping patches and stacks to \
+             git references. Scripts and external tools should use it instead of \
+             constructing reference names themselves, such that they keep working \
+             should the layout of StGit's references change.",
+        )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg ref p1",
+                "Print the reference of patch `p1`, e.g. `refs/patches/main/p1`",
+            ),
+            (
+                "git log $(stg ref --log)",
+                "Inspect the stack log of the current branch with git",
+            ),
+            (
+                "stg ref -z .. | xargs -0 git show",
+                "Show the commits of all patches in the stack",
+            ),
+        ]))
+        .override_usage(super::make_usage(
+            "stg ref",
+            &["[OPTIONS] <patch>...", "[OPTIONS] [--stack] [--log]"],
+        ))
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("patchranges")
+                .help("Patches to print the references of")
+                .value_name("patch")
+                .num_args(1..)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchRange))
+                .required_unless_present_any(["stack", "log"])
+                .conflicts_with_all(["stack", "log"]),
+        )
+        .arg(
+            Arg::new("stack")
+                .long("stack")
+                .help("Print the reference of the stack state")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("log")
+                .long("log")
+                .help("Print the reference of the stack log")
+                .long_help(
+                    "Print the reference of the stack log. When given together with \
+                     '--stack', the stack state reference is printed first.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("null")
+                .short('z')
+                .help("Terminate references with NUL instead of newline")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    let mut refnames = Vec::new();
+    if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
+        let patchnames = patchrange::resolve_names(&stack, range_specs, RangeConstraint::All)?;
+        refnames.extend(
+            patchnames
+                .iter()
+                .map(|patchname| stack.patch_revspec(patchname.as_ref())),
+        );
+    }
+    if matches.get_flag("stack") {
+        refnames.push(stack.get_stack_refname().to_string());
+    }
+    if matches.get_flag("log") {
+        refnames.push(stack.get_stack_refname().to_string());
+    }
+
+    let terminator = if matches.get_flag("null") { '\0' } else { '\n' };
+    let mut stdout = std::io::stdout().lock();
+    for refname in refnames {
+        write!(stdout, "{refname}{terminator}")?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
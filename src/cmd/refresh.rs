@@ -8,15 +8,18 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
+use bstr::{BString, ByteSlice, ByteVec};
 use clap::{Arg, ArgGroup, ArgMatches, ValueHint};
+use gix::index::entry::Stage;
 use indexmap::IndexSet;
 
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended, SignatureExtended},
     hook::run_pre_commit_hook,
+    output::Output,
     patch::{patchedit, LocationConstraint, PatchLocator, PatchName},
+    pathspec,
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::{Status, StatusOptions, Statuses, Stupid, StupidContext},
     wrap::Message,
@@ -25,6 +28,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "refresh",
     category: super::CommandCategory::PatchManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -58,7 +62,27 @@ fn make() -> clap::Command {
              separate entry in the patch stack log; this means that one \
              undo step will undo the merge between the other patch and \
              the temp patch, and two undo steps will additionally get \
-             rid of the temp patch.",
+             rid of the temp patch.\n\
+             \n\
+             With --force-merge, the changes are first merged into the \
+             other patch without touching the worktree, even when the \
+             patches above it change the same files, and those patches \
+             are then pushed back onto the refreshed patch. Only if that \
+             merge fails is the temporary patch pushed onto the other \
+             patch, leaving any conflicts to be resolved in the usual \
+             way, regardless of the push conflicts policy.\n\
+             \n\
+             When editing the patch description with --edit, the diff of the \
+             refreshed patch, i.e. of the existing patch combined with the changes \
+             being refreshed, is shown below the scissors line with --diff or when \
+             stgit.edit.verbose is set. With --show-delta-only, only the changes \
+             being refreshed are shown instead.\n\
+             \n\
+             Lines added by the refresh are checked against the regular expressions \
+             of the multi-valued stgit.refresh.blockpatterns setting, e.g. to keep \
+             credentials out of patches. If any line matches, the refresh is refused \
+             and the matching lines are listed. Use --no-verify to skip this check \
+             along with the hooks.",
         )
         .arg(
             Arg::new("pathspecs")
@@ -101,6 +125,21 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(argset::push_conflicts_arg())
+        .arg(
+            Arg::new("force-merge")
+                .long("force-merge")
+                .help("Merge changes into <patch> even if later patches touch its files")
+                .long_help(
+                    "Three-way merge the changes into the patch given with --patch, \
+                     instead of refusing or leaving the changes in a temporary patch \
+                     when patches between it and the top change the same files. The \
+                     patches above the refreshed patch are pushed back and reported. \
+                     If the merge fails, the usual conflict workflow is used even if \
+                     push conflicts are disallowed.",
+                )
+                .requires("patch")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("patch")
                 .long("patch")
@@ -144,25 +183,42 @@ fn make() -> clap::Command {
         );
 
     patchedit::add_args(app, true, false)
+        .arg(
+            Arg::new("show-delta-only")
+                .long("show-delta-only")
+                .help("Show only the changes being refreshed when editing")
+                .long_help(
+                    "When editing the patch description, show the diff of only the \
+                     changes being refreshed instead of the refreshed patch's entire \
+                     diff. This implies --diff.",
+                )
+                .requires("edit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::date_arg())
+        .args(argset::sign_args())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     if matches.get_flag("spill") {
         return Err(anyhow!(
             "`stg refresh --spill` is obsolete; use `stg spill` instead"
         ));
     }
 
-    let repo = gix::Repository::open()?;
+    let mut repo = gix::Repository::open()?;
+    argset::set_commit_signing(&mut repo, matches)?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
-    let config = repo.config_snapshot();
-    let allow_push_conflicts = argset::resolve_allow_push_conflicts(&config, matches);
+    let config = stack.config();
+    let force_merge = matches.get_flag("force-merge");
+    let allow_push_conflicts =
+        force_merge || argset::resolve_allow_push_conflicts(&config, matches);
 
     stack.check_head_top_mismatch()?;
 
     let patchname = if let Some(patch_loc) = matches.get_one::<PatchLocator>("patch") {
         patch_loc
-            .resolve_name(&stack)?
+            .resolve_name_allowed(&stack, LocationConstraint::Visible)?
             .constrain(&stack, LocationConstraint::Visible)?
     } else if let Some(top_patchname) = stack.applied().last() {
         top_patchname.clone()
@@ -173,6 +229,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let tree_id = assemble_refresh_tree(
         &stack,
         matches,
+        matches.get_flag("index"),
         matches.get_flag("update").then_some(&patchname),
     )?;
 
@@ -180,9 +237,16 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let opt_annotate = matches.get_one::<String>("annotate");
 
     // Make temp patch
+    let fixed_date = argset::get_fixed_date(matches);
+    let mut author = repo.get_author()?;
+    let mut committer = repo.get_committer()?;
+    if let Some(date) = fixed_date {
+        author.time = date;
+        committer.time = date;
+    }
     let temp_commit_id = stack.repo.commit_ex(
-        &repo.get_author()?.override_author(matches),
-        repo.get_committer()?,
+        &author.override_author(matches),
+        committer,
         &Message::from(format!("Refresh of {patchname}")),
         tree_id,
         [stack.get_branch_head().id],
@@ -192,35 +256,59 @@ fn run(matches: &ArgMatches) -> Result<()> {
         let len_limit = None;
         let allow = vec![];
         let disallow: Vec<&PatchName> = stack.all_patches().collect();
-        PatchName::make("refresh-temp", true, len_limit).uniquify(&allow, &disallow)
+        PatchName::make("refresh-temp", true, len_limit).uniquify(&allow, &disallow, len_limit)
     };
 
     let stack = stack
         .setup_transaction()
-        .with_output_stream(get_color_stdout(matches))
+        .command_line(command_line)
+        .fixed_date(fixed_date)
+        .with_output(Output::new(matches))
         .transact(|trans| trans.new_applied(&temp_patchname, temp_commit_id))
         .execute(&format!(
             "refresh {temp_patchname} (create temporary patch)"
         ))?;
 
     let mut absorb_success = false;
+    let mut rewritten = Vec::new();
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .fixed_date(fixed_date)
         .use_index_and_worktree(true)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .allow_push_conflicts(allow_push_conflicts)
         .transact(|trans| {
             if let Some(pos) = trans.applied().iter().position(|pn| pn == &patchname) {
                 // Absorb temp patch into already applied patch
                 let to_pop = trans.applied()[pos + 1..].to_vec();
-                if to_pop.len() > 1 {
+                let merged_tree_id = if to_pop.len() > 1 {
                     let popped_extra = trans.pop_patches(|pn| to_pop.contains(pn))?;
                     assert!(
                         popped_extra.is_empty(),
                         "only requested patches should be popped"
                     );
-                    trans.push_patches(&[&temp_patchname], false)?;
-                }
+
+                    // Merging without the worktree avoids pushing the temp patch, which
+                    // may conflict with the changes to the same files in the popped
+                    // patches.
+                    let merged_tree_id = if force_merge {
+                        let temp_commit = trans.get_patch_commit(&temp_patchname);
+                        repo.stupid().merge_trees(
+                            temp_commit.get_parent_commit()?.tree_id()?.detach(),
+                            trans.get_patch_commit(&patchname).tree_id()?.detach(),
+                            temp_commit.tree_id()?.detach(),
+                        )?
+                    } else {
+                        None
+                    };
+                    if merged_tree_id.is_none() {
+                        trans.push_patches(&[&temp_patchname], false)?;
+                    }
+                    merged_tree_id
+                } else {
+                    None
+                };
 
                 let temp_commit = trans.get_patch_commit(&temp_patchname);
 
@@ -228,13 +316,20 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 let top_name = to_pop.pop();
                 assert_eq!(top_name.as_ref(), Some(&temp_patchname));
 
+                let delta_base_tree_id = if matches.get_flag("show-delta-only") {
+                    Some(trans.get_patch_commit(&patchname).tree_id()?.detach())
+                } else {
+                    None
+                };
                 let (new_patchname, new_commit_id) = match patchedit::EditBuilder::default()
                     .original_patchname(Some(&patchname))
                     .existing_patch_commit(trans.get_patch_commit(&patchname))
-                    .override_tree_id(temp_commit.tree_id()?.detach())
+                    .override_tree_id(merged_tree_id.unwrap_or(temp_commit.tree_id()?.detach()))
+                    .show_diff_from(delta_base_tree_id)
                     .allow_diff_edit(false)
                     .allow_implicit_edit(false)
                     .allow_template_save(false)
+                    .fixed_date(fixed_date)
                     .edit(trans, &repo, matches)?
                 {
                     patchedit::EditOutcome::Edited {
@@ -261,6 +356,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     log_msg.push_str(annotation);
                 }
 
+                if force_merge {
+                    rewritten.clone_from(&to_pop);
+                }
                 trans.push_patches(&to_pop, false)?;
                 absorb_success = true;
             } else {
@@ -279,7 +377,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 let ours = patch_commit.tree_id()?.detach();
                 let theirs = temp_commit.tree_id()?.detach();
 
-                if let Some(tree_id) = repo.stupid().with_temp_index(|stupid_temp| {
+                let maybe_tree_id = repo.stupid().with_temp_index(|stupid_temp| {
                     stupid_temp.read_tree(ours)?;
                     if stupid_temp.apply_treediff_to_index(base, theirs, true)? {
                         let tree_id = stupid_temp.write_tree()?;
@@ -287,13 +385,22 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     } else {
                         Ok(None)
                     }
-                })? {
+                })?;
+                let maybe_tree_id = if maybe_tree_id.is_none() && force_merge {
+                    repo.stupid().merge_trees(base, ours, theirs)?
+                } else {
+                    maybe_tree_id
+                };
+
+                if let Some(tree_id) = maybe_tree_id {
                     let (new_patchname, new_commit_id) = match patchedit::EditBuilder::default()
                         .original_patchname(Some(&patchname))
                         .existing_patch_commit(trans.get_patch_commit(&patchname))
                         .override_tree_id(tree_id)
+                        .show_diff_from(matches.get_flag("show-delta-only").then_some(ours))
                         .allow_diff_edit(false)
                         .allow_template_save(false)
+                        .fixed_date(fixed_date)
                         .edit(trans, &repo, matches)?
                     {
                         patchedit::EditOutcome::Edited {
@@ -326,18 +433,32 @@ fn run(matches: &ArgMatches) -> Result<()> {
         })
         .execute(&log_msg)?;
 
+    if !rewritten.is_empty() {
+        let rewritten = rewritten
+            .iter()
+            .map(|pn| format!("`{pn}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        crate::print_info_message(
+            matches,
+            &format!("rewrote {rewritten} on top of the refreshed patch"),
+        );
+    }
+
     if !absorb_success {
-        println!(
-            "The new changes did not apply cleanly to {}. \
-             They were saved in {}.",
-            &patchname, &temp_patchname,
+        crate::print_warning_message(
+            matches,
+            &format!(
+                "the new changes did not apply cleanly to `{patchname}`; \
+                 they were saved in `{temp_patchname}`",
+            ),
         );
     }
 
     Ok(())
 }
 
-fn determine_refresh_paths(
+pub(super) fn determine_refresh_paths(
     stupid: &StupidContext,
     statuses: &Statuses,
     patch_commit: Option<&Rc<gix::Commit>>,
@@ -398,7 +519,7 @@ fn determine_refresh_paths(
     Ok(refresh_paths)
 }
 
-fn write_tree(
+pub(super) fn write_tree(
     stack: &Stack,
     refresh_paths: &IndexSet<PathBuf>,
     is_path_limiting: bool,
@@ -408,8 +529,12 @@ fn write_tree(
     // may be formed into a coherent tree while leaving the default index as-is.
     let stupid = stack.repo.stupid();
     if is_path_limiting {
+        let untrusted_mode_info = untrusted_mode_info(stack.repo, refresh_paths)?;
         let tree_id_result = stupid.with_temp_index(|stupid_temp| {
             stupid_temp.read_tree(stack.get_branch_head().tree_id()?.detach())?;
+            if !untrusted_mode_info.is_empty() {
+                stupid_temp.update_index_info(&untrusted_mode_info)?;
+            }
             stupid_temp.update_index(Some(refresh_paths))?;
             stupid_temp.write_tree()
         });
@@ -423,9 +548,41 @@ fn write_tree(
     }
 }
 
+/// Get `--index-info` records for the default index's entries of `refresh_paths`.
+///
+/// When `core.fileMode` is false, `git update-index` takes a file's mode from its
+/// existing index entry instead of the work tree. The temporary index used for
+/// path-limited refreshes starts from the branch head's tree, so without these records
+/// a mode change staged in the default index, e.g. with `git update-index --chmod`,
+/// would be lost. Nothing is returned when the work tree's file modes are trusted.
+fn untrusted_mode_info(
+    repo: &gix::Repository,
+    refresh_paths: &IndexSet<PathBuf>,
+) -> Result<BString> {
+    let mut index_info = BString::default();
+    if repo
+        .config_snapshot()
+        .boolean("core.fileMode")
+        .unwrap_or(true)
+    {
+        return Ok(index_info);
+    }
+    let index = repo.index_or_empty()?;
+    for path in refresh_paths {
+        let path = gix::path::into_bstr(path.as_path());
+        if let Some(entry) = index.entry_by_path_and_stage(path.as_ref(), Stage::Unconflicted) {
+            index_info.push_str(format!("{:o} {}\t", entry.mode.bits(), entry.id));
+            index_info.push_str(path.as_ref());
+            index_info.push_byte(b'\0');
+        }
+    }
+    Ok(index_info)
+}
+
 pub(crate) fn assemble_refresh_tree(
     stack: &Stack,
     matches: &ArgMatches,
+    use_index: bool,
     limit_to_patchname: Option<&PatchName>,
 ) -> Result<gix::ObjectId> {
     let stupid = stack.repo.stupid();
@@ -433,7 +590,7 @@ pub(crate) fn assemble_refresh_tree(
     let is_path_limiting = limit_to_patchname.is_some() || opt_pathspecs.is_some();
     let statuses;
 
-    let refresh_paths = if matches.get_flag("index") {
+    let refresh_paths = if use_index {
         // When refreshing from the index, no path limiting may be used.
         assert!(!is_path_limiting);
         IndexSet::new()
@@ -443,8 +600,7 @@ pub(crate) fn assemble_refresh_tree(
         let nosubmodules_flag = matches.get_flag("no-submodules");
         let use_submodules = if !submodules_flag && !nosubmodules_flag {
             stack
-                .repo
-                .config_snapshot()
+                .config()
                 .boolean("stgit.refreshsubmodules")
                 .unwrap_or(false)
         } else {
@@ -453,7 +609,7 @@ pub(crate) fn assemble_refresh_tree(
         let mut status_opts = StatusOptions::default();
         status_opts.include_submodules(use_submodules);
         if let Some(pathspecs) = opt_pathspecs {
-            status_opts.pathspecs(pathspecs);
+            status_opts.pathspecs(pathspec::normalize(stack.repo, pathspecs)?);
         }
         statuses = stupid.statuses(Some(&status_opts))?;
 
@@ -477,5 +633,59 @@ pub(crate) fn assemble_refresh_tree(
         write_tree(stack, &refresh_paths, is_path_limiting)?
     };
 
+    if !matches.get_flag("no-verify") {
+        check_block_patterns(stack, tree_id)?;
+    }
+
     Ok(tree_id)
 }
+
+/// Refuse to refresh added lines matching any of the `stgit.refresh.blockpatterns`
+/// regular expressions, e.g. to keep credentials out of patches.
+///
+/// All matching lines are listed in the returned error. Nothing is checked when no
+/// patterns are configured.
+fn check_block_patterns(stack: &Stack, tree_id: gix::ObjectId) -> Result<()> {
+    let config = stack.repo.config_snapshot();
+    let Some(values) = config.plumbing().strings("stgit.refresh.blockpatterns") else {
+        return Ok(());
+    };
+    let patterns =
+        values
+            .iter()
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                let value = value.to_str_lossy();
+                regex_lite::Regex::new(&value).map(|regex| (value.to_string(), regex)).map_err(|e| {
+                anyhow!("invalid regular expression `{value}` in stgit.refresh.blockpatterns: {e}")
+            })
+            })
+            .collect::<Result<Vec<_>>>()?;
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let mut matches = Vec::new();
+    stack.repo.stupid().diff_tree_added_lines(
+        stack.get_branch_head().tree_id()?.detach(),
+        tree_id,
+        |path, line_number, line| {
+            let line = line.to_str_lossy();
+            for (pattern, regex) in &patterns {
+                if regex.is_match(&line) {
+                    matches.push(format!("{path}:{line_number}: matches `{pattern}`"));
+                }
+            }
+        },
+    )?;
+
+    if matches.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "refusing to refresh lines matching stgit.refresh.blockpatterns:\n  {}\n\
+             use `--no-verify` to refresh anyway",
+            matches.join("\n  ")
+        ))
+    }
+}
@@ -10,8 +10,8 @@ use clap::{Arg, ArgMatches};
 use crate::{
     argset,
     branchloc::BranchLocator,
-    color::get_color_stdout,
     ext::RepositoryExtended,
+    output::Output,
     patch::{PatchLocator, PatchName},
     stack::{InitializationPolicy, Stack, StackStateAccess},
 };
@@ -19,6 +19,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "rename",
     category: super::CommandCategory::PatchManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -28,13 +29,22 @@ fn make() -> clap::Command {
         .about("Rename a patch")
         .long_about(
             "Rename [old-patch] to <new-patch>. If [old-patch] is not given, the \
-             topmost patch will be renamed.",
+             topmost patch will be renamed.\n\
+             \n\
+             Stack metadata referring to the old patch name, such as labels and \
+             aliases, is updated to refer to the new name.\n\
+             \n\
+             With '--keep-alias', the old name is recorded as an alias of the renamed \
+             patch. Patch locators using the alias continue to resolve to the renamed \
+             patch, with a deprecation warning, until the alias is cleared with \
+             '--clear-alias' or a new patch with the old name is created.",
         )
         .override_usage(super::make_usage(
             "stg rename",
-            &["[OPTIONS] [old-patch] <new-patch>"],
+            &["[OPTIONS] [old-patch] <new-patch>", "--clear-alias <alias>"],
         ))
         .arg(argset::branch_arg())
+        .arg(argset::dry_run_arg())
         .arg(
             Arg::new("patches")
                 .help("Optional old patch and the new patch name")
@@ -43,9 +53,35 @@ fn make() -> clap::Command {
                 .num_args(1..=2)
                 .value_parser(clap::builder::NonEmptyStringValueParser::new()),
         )
+        .arg(
+            Arg::new("keep-alias")
+                .long("keep-alias")
+                .help("Keep the old patch name as an alias of the renamed patch")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("clear-alias")
+                .long("clear-alias")
+                .help("Remove a patch name alias")
+                .value_name("alias")
+                .value_parser(clap::value_parser!(PatchName))
+                .conflicts_with_all(["patches", "keep-alias"]),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Rename the patch with unresolved conflicts")
+                .long_help(
+                    "Rename the patch left with unresolved conflicts by a conflicting \
+                     push. By default, such a patch may not be renamed until the \
+                     conflicts are resolved or the push is undone.",
+                )
+                .conflicts_with("clear-alias")
+                .action(clap::ArgAction::SetTrue),
+        )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
         &repo,
@@ -53,6 +89,24 @@ fn run(matches: &ArgMatches) -> Result<()> {
         InitializationPolicy::AllowUninitialized,
     )?;
 
+    if let Some(alias) = matches.get_one::<PatchName>("clear-alias") {
+        if !stack.aliases().contains_key(alias) {
+            return Err(anyhow!("no patch alias named `{alias}`"));
+        }
+        stack
+            .setup_transaction()
+            .command_line(command_line)
+            .dry_run(matches.get_flag("dry-run"))
+            .allow_conflicts(true)
+            .with_output(Output::new(matches))
+            .transact(|trans| {
+                trans.remove_alias(alias);
+                Ok(())
+            })
+            .execute(&format!("rename --clear-alias {alias}"))?;
+        return Ok(());
+    }
+
     let patch_args: Vec<&String> = matches
         .get_many::<String>("patches")
         .expect("clap ensures one or two names are provided")
@@ -123,11 +177,24 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 
+    if !matches.get_flag("force") {
+        stack.check_conflicted_patch_filter(|pn| pn == &old_patchname)?;
+    }
+
+    let keep_alias = matches.get_flag("keep-alias");
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .dry_run(matches.get_flag("dry-run"))
         .allow_conflicts(true)
-        .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| trans.rename_patch(&old_patchname, &new_patchname))
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            trans.rename_patch(&old_patchname, &new_patchname)?;
+            if keep_alias {
+                trans.add_alias(&old_patchname, &new_patchname);
+            }
+            Ok(())
+        })
         .execute(&format!("rename {old_patchname} {new_patchname}"))?;
 
     Ok(())
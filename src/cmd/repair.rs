@@ -2,23 +2,30 @@
 
 //! `stg repair` implementation.
 
-use std::rc::Rc;
+use std::{collections::BTreeSet, rc::Rc};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
+use clap::Arg;
 use indexmap::{indexset, IndexSet};
 
 use crate::{
-    color::get_color_stdout,
+    argset,
     ext::{CommitExtended, RepositoryExtended},
-    patch::PatchName,
+    output::Output,
+    patch::{name::NameTemplate, PatchName},
     print_info_message, print_warning_message,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stack::{
+        has_case_insensitive_refs, InitializationPolicy, Stack, StackAccess, StackState,
+        StackStateAccess,
+    },
+    stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "repair",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -59,21 +66,94 @@ fn make() -> clap::Command {
              valid workflows where git commands are used followed by `stg repair`. For \
              example, new patches can be created by first making commits with a \
              graphical commit tool and then running `stg repair` to convert those \
-             commits into patches.",
+             commits into patches.\n\
+             \n\
+             - Patches whose commits are missing from the repository, e.g. because \
+             they were removed by git-gc(1), are removed from the stack.\n\
+             \n\
+             With `--prune-refs`, the stack metadata is left alone and, instead, patch \
+             references, i.e. `refs/patches/<branch>/<patch>`, that no stack's current \
+             state refers to are removed. Such references may be left behind when \
+             branches or stacks are removed outside of StGit. A reference is only \
+             removed if its commit is reachable from the log of some stack; otherwise \
+             the reference is kept, with a warning, since removing it could make the \
+             commit subject to garbage collection.",
+        )
+        .override_usage(super::make_usage(
+            "stg repair",
+            &[
+                "",
+                "--prune-refs [--all-branches] [--dry-run] [--expire <date>]",
+            ],
+        ))
+        .arg(
+            Arg::new("prune-refs")
+                .long("prune-refs")
+                .help("Remove patch refs not referred to by any stack")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("all-branches")
+                .long("all-branches")
+                .help("Prune the patch refs of all branches")
+                .long_help(
+                    "Prune the patch refs of all branches, including the refs of \
+                     branches that no longer exist, instead of just the refs of the \
+                     current branch.",
+                )
+                .requires("prune-refs")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("List the patch refs that would be pruned")
+                .requires("prune-refs")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("expire")
+                .long("expire")
+                .help("Only prune patch refs to commits older than <date>")
+                .long_help(format!(
+                    "Only prune patch refs whose commits have a committer date older \
+                     than <date>. The default is taken from the \
+                     \"stgit.repair.pruneexpire\" configuration variable and, when that \
+                     is not set, patch refs are pruned regardless of age.\n\
+                     \n\
+                     {}",
+                    argset::DATE_FORMATS_HELP,
+                ))
+                .requires("prune-refs")
+                .value_name("date")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
         )
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
+    if matches.get_flag("prune-refs") {
+        return prune_refs(&repo, matches);
+    }
+    crate::stack::allow_missing_patch_commits();
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
-    let config = repo.config_snapshot();
-    if stack.is_protected(&config) {
+    if stack.is_protected() {
         return Err(anyhow!(
             "this branch is protected; modification is not permitted."
         ));
     }
 
-    let patchname_len_limit = PatchName::get_length_limit(&config);
+    for (patchname, commit_id) in stack.missing_patches() {
+        print_warning_message(
+            matches,
+            &format!(
+                "removing patch `{patchname}`: its commit `{commit_id}` is missing from the \
+                 repository"
+            ),
+        );
+    }
+
+    let mut name_template = NameTemplate::new(&stack)?;
 
     // Find commits that are not patches as well as applied patches.
 
@@ -183,8 +263,9 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(false)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| {
             trans.repair_appliedness(applied, unapplied, hidden);
 
@@ -203,8 +284,11 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                     let message = commit.message_raw()?.to_str_lossy();
                     let allow = &[];
                     let disallow: Vec<_> = trans.all_patches().collect();
-                    let patchname = PatchName::make(&message, true, patchname_len_limit)
-                        .uniquify(allow, &disallow);
+                    let patchname = name_template.make(&message, true)?.uniquify(
+                        allow,
+                        &disallow,
+                        name_template.len_limit(),
+                    );
                     trans.new_applied(&patchname, commit.id)?;
                 }
             }
@@ -214,3 +298,136 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
 
     Ok(())
 }
+
+/// Remove patch refs that no stack's current state refers to.
+///
+/// Only refs whose commits are reachable from a stack state ref, i.e. from the log of
+/// some stack, are removed such that no commits become subject to garbage collection.
+fn prune_refs(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
+    let dry_run = matches.get_flag("dry-run");
+    let expire = if let Some(date) = matches.get_one::<String>("expire") {
+        Some(argset::parse_time(date)?)
+    } else if let Some(date) = repo.config_snapshot().string("stgit.repair.pruneexpire") {
+        Some(argset::parse_time(&date.to_str_lossy()).context("parsing stgit.repair.pruneexpire")?)
+    } else {
+        None
+    };
+    let ignore_case = has_case_insensitive_refs(repo);
+    let ref_key = |refname: &str| {
+        if ignore_case {
+            refname.to_ascii_lowercase()
+        } else {
+            refname.to_string()
+        }
+    };
+
+    let mut referenced: BTreeSet<String> = BTreeSet::new();
+    let mut state_ids: Vec<gix::ObjectId> = Vec::new();
+    for mut state_ref in repo
+        .references()?
+        .prefixed("refs/stacks/")?
+        .filter_map(Result::ok)
+    {
+        let Some(branch_name) = state_ref
+            .name()
+            .as_bstr()
+            .to_str()
+            .ok()
+            .and_then(|refname| refname.strip_prefix("refs/stacks/"))
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        let state_commit = state_ref.peel_to_commit()?;
+        let patchnames = StackState::patchnames_from_commit(repo, &state_commit)
+            .with_context(|| format!("reading stack state of branch `{branch_name}`"))?;
+        referenced.extend(
+            patchnames
+                .iter()
+                .map(|pn| ref_key(&format!("refs/patches/{branch_name}/{pn}"))),
+        );
+        state_ids.push(state_commit.id);
+    }
+
+    let all_branches = matches.get_flag("all-branches");
+    let prefix = if all_branches {
+        "refs/patches/".to_string()
+    } else {
+        let branch = repo.get_current_branch()?;
+        format!("refs/patches/{}/", branch.get_branch_name()?)
+    };
+
+    let mut candidates: Vec<(String, gix::ObjectId)> = Vec::new();
+    for reference in repo
+        .references()?
+        .prefixed(prefix.as_str())?
+        .filter_map(Result::ok)
+    {
+        let Ok(refname) = reference.name().as_bstr().to_str() else {
+            continue;
+        };
+        // Refs below a further subdirectory belong to another branch, e.g. the refs of
+        // branch `a/b` are below those of branch `a`.
+        let in_scope = refname
+            .strip_prefix(&prefix)
+            .is_some_and(|rest| !rest.is_empty() && (all_branches || !rest.contains('/')));
+        if !in_scope || referenced.contains(&ref_key(refname)) {
+            continue;
+        }
+        if let Some(id) = reference.target().try_id() {
+            candidates.push((refname.to_string(), id.to_owned()));
+        }
+    }
+
+    if let Some(expire) = expire {
+        let mut expired = Vec::with_capacity(candidates.len());
+        for (refname, id) in candidates {
+            let time = repo.find_commit(id)?.committer()?.time;
+            if time.seconds < expire.seconds {
+                expired.push((refname, id));
+            }
+        }
+        candidates = expired;
+    }
+
+    // Commits reachable from the patch refs, but not from any stack's log.
+    let unlogged = if candidates.is_empty() {
+        Vec::new()
+    } else {
+        repo.stupid()
+            .rev_list_excluding(candidates.iter().map(|(_, id)| *id), state_ids)?
+    };
+
+    let mut ref_edits = Vec::new();
+    for (refname, id) in candidates {
+        if unlogged.contains(&id) {
+            print_warning_message(
+                matches,
+                &format!(
+                    "keeping `{refname}`: its commit `{id}` is not reachable from any stack \
+                     log"
+                ),
+            );
+        } else if dry_run {
+            println!("{refname}");
+        } else {
+            print_info_message(matches, &format!("Pruning `{refname}`"));
+            ref_edits.push(gix::refs::transaction::RefEdit {
+                change: gix::refs::transaction::Change::Delete {
+                    expected: gix::refs::transaction::PreviousValue::ExistingMustMatch(
+                        gix::refs::Target::Object(id),
+                    ),
+                    log: gix::refs::transaction::RefLog::AndReference,
+                },
+                name: gix::refs::FullName::try_from(refname)?,
+                deref: false,
+            });
+        }
+    }
+
+    if !ref_edits.is_empty() {
+        repo.edit_references(ref_edits)?;
+    }
+
+    Ok(())
+}
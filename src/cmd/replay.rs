@@ -0,0 +1,424 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg replay` implementation.
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    ext::RepositoryExtended,
+    patch::PatchName,
+    stack::{history, InitializationPolicy, Stack, StackAccess, StackStateAccess},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "replay",
+    category: super::CommandCategory::StackManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+/// Version of the operation log format written by `stg log --export`.
+const FORMAT_VERSION: u32 = 1;
+
+/// Commands that may be replayed.
+///
+/// An operation log may come from someone else, e.g. attached to a bug report, so only
+/// commands that do nothing but manipulate the stack are replayed. Commands that run
+/// arbitrary programs, send mail, or download patches, e.g. `stg foreach`, `stg email`,
+/// or `stg import`, are refused.
+const REPLAYABLE_COMMANDS: &[&str] = &[
+    "base", "clean", "commit", "delete", "edit", "float", "goto", "hide", "label", "new", "pop",
+    "push", "rebase", "redo", "refresh", "rename", "repair", "reset", "sink", "spill", "squash",
+    "sync", "uncommit", "undo", "unhide",
+];
+
+/// Options of replayable commands that are refused nonetheless.
+///
+/// `--save-template` writes to an arbitrary file and `--diff-opt` passes arbitrary
+/// options, e.g. `--output`, to `git diff`.
+const UNREPLAYABLE_ARGS: &[&str] = &["save-template", "git-diff-opt"];
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Replay stack operations exported with 'stg log --export'")
+        .long_about(
+            "Re-execute the sequence of StGit commands recorded in <file> by \
+             'stg log --export' against the current stack.\n\
+             \n\
+             The first operation in <file> is the starting state; the current stack \
+             must match it before any commands are replayed. Each subsequent recorded \
+             command is then run in turn and the resulting stack state is compared to \
+             the recorded state. Replaying stops at the first divergence, reporting \
+             the expected and actual object ids.\n\
+             \n\
+             Stack states are compared by their patch names and by the tree ids of \
+             the patches and the stack head. Commit ids are not compared since they \
+             depend on the dates and identities of whoever ran the commands.\n\
+             \n\
+             Only command lines are recorded, not the changes made to the worktree \
+             between commands. A command such as 'stg refresh', which depends on the \
+             worktree, may thus diverge unless the worktree is prepared accordingly. \
+             Operations without a recorded command line, e.g. those recorded by \
+             older versions of StGit, cannot be replayed.\n\
+             \n\
+             Since an operation log may come from anywhere, only commands that solely \
+             manipulate the stack are replayed. Logs with commands that may run other \
+             programs, send mail, or write arbitrary files, e.g. 'stg foreach', 'stg \
+             email', or 'stg import', are refused before anything is replayed.",
+        )
+        .arg(
+            Arg::new("file")
+                .help("Operation log file, or '-' for stdin")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::FilePath),
+        )
+}
+
+/// Operation log, as exported by `stg log --export`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct OperationLog {
+    version: u32,
+    branch: String,
+    /// Operations from oldest to newest.
+    operations: Vec<Operation>,
+}
+
+/// A stack log entry.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Operation {
+    /// Description of the operation, i.e. the stack log entry's summary.
+    message: String,
+    /// Command line that produced this stack state, starting with the command name.
+    command: Option<Vec<String>>,
+    /// Id of the stack log commit.
+    state: String,
+    #[serde(flatten)]
+    snapshot: Snapshot,
+}
+
+/// The comparable parts of a stack state.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Snapshot {
+    head: ObjectIds,
+    applied: Vec<PatchName>,
+    unapplied: Vec<PatchName>,
+    hidden: Vec<PatchName>,
+    patches: BTreeMap<PatchName, ObjectIds>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ObjectIds {
+    commit: String,
+    tree: String,
+}
+
+impl ObjectIds {
+    fn new(commit: &gix::Commit) -> Result<Self> {
+        Ok(Self {
+            commit: commit.id.to_string(),
+            tree: commit.tree_id()?.to_string(),
+        })
+    }
+}
+
+impl Snapshot {
+    fn new<'repo>(state: &impl StackStateAccess<'repo>) -> Result<Self> {
+        let mut patches = BTreeMap::new();
+        for patchname in state.all_patches() {
+            patches.insert(
+                patchname.clone(),
+                ObjectIds::new(&state.get_patch(patchname).commit)?,
+            );
+        }
+        Ok(Self {
+            head: ObjectIds::new(state.head())?,
+            applied: state.applied().to_vec(),
+            unapplied: state.unapplied().to_vec(),
+            hidden: state.hidden().to_vec(),
+            patches,
+        })
+    }
+
+    /// Describe how the actual snapshot differs from this expected snapshot.
+    fn differences(&self, actual: &Snapshot) -> Vec<String> {
+        let mut differences = Vec::new();
+        for (kind, expected_names, actual_names) in [
+            ("applied", &self.applied, &actual.applied),
+            ("unapplied", &self.unapplied, &actual.unapplied),
+            ("hidden", &self.hidden, &actual.hidden),
+        ] {
+            if expected_names != actual_names {
+                differences.push(format!(
+                    "{kind} patches: expected [{}], actual [{}]",
+                    join_names(expected_names),
+                    join_names(actual_names),
+                ));
+            }
+        }
+        if self.head.tree != actual.head.tree {
+            differences.push(format!(
+                "head tree: expected {}, actual {}",
+                self.head.tree, actual.head.tree
+            ));
+        }
+        for (patchname, expected_ids) in &self.patches {
+            if let Some(actual_ids) = actual.patches.get(patchname) {
+                if expected_ids.tree != actual_ids.tree {
+                    differences.push(format!(
+                        "patch `{patchname}` tree: expected {}, actual {}",
+                        expected_ids.tree, actual_ids.tree
+                    ));
+                }
+            }
+        }
+        differences
+    }
+}
+
+fn join_names(names: &[PatchName]) -> String {
+    names
+        .iter()
+        .map(PatchName::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Format a recorded command line for display, quoting arguments as needed.
+fn display_command(command: &[String]) -> String {
+    let mut display = String::from("stg");
+    for arg in command {
+        display.push(' ');
+        if !arg.is_empty()
+            && arg
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%^~{}".contains(c))
+        {
+            display.push_str(arg);
+        } else {
+            display.push('\'');
+            display.push_str(&arg.replace('\'', "'\\''"));
+            display.push('\'');
+        }
+    }
+    display
+}
+
+/// Write the stack's operation log to `path`, or to stdout if `path` is `-`.
+///
+/// The newest `number` stack log entries are exported, or all entries if `None`.
+pub(super) fn export(stack: &Stack, number: Option<usize>, path: &Path) -> Result<()> {
+    let mut operations = Vec::new();
+    for entry in history::stack_log(stack)?.take(number.unwrap_or(usize::MAX)) {
+        let entry = entry?;
+        operations.push(Operation {
+            message: entry.summary(),
+            command: entry.state.command.clone(),
+            state: entry.simplified_id().to_string(),
+            snapshot: Snapshot::new(&entry.state)?,
+        });
+    }
+    operations.reverse();
+    let log = OperationLog {
+        version: FORMAT_VERSION,
+        branch: stack.get_branch_name().to_string(),
+        operations,
+    };
+
+    let mut data = serde_json::to_string_pretty(&log)?;
+    data.push('\n');
+    if path == Path::new("-") {
+        std::io::stdout().write_all(data.as_bytes())?;
+    } else {
+        std::fs::write(path, data).with_context(|| format!("writing `{}`", path.display()))?;
+    }
+    Ok(())
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let path = matches
+        .get_one::<PathBuf>("file")
+        .expect("file is required");
+    let data = if path == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        data
+    } else {
+        std::fs::read(path).with_context(|| format!("reading `{}`", path.display()))?
+    };
+
+    #[derive(serde::Deserialize)]
+    struct FormatVersion {
+        version: u32,
+    }
+
+    let FormatVersion { version } =
+        serde_json::from_slice(&data).context("reading operation log version")?;
+    if version != FORMAT_VERSION {
+        return Err(anyhow!("unsupported operation log version {version}"));
+    }
+    let log: OperationLog = serde_json::from_slice(&data).context("reading operation log")?;
+
+    let Some((start, operations)) = log.operations.split_first() else {
+        return Err(anyhow!("no operations in `{}`", path.display()));
+    };
+
+    // An operation with a command line is followed by any further stack states
+    // produced by the same command, the last of which is the command's resulting state.
+    let mut groups: Vec<(&[String], &Operation)> = Vec::new();
+    for operation in operations {
+        if let Some(command) = operation.command.as_ref() {
+            groups.push((command, operation));
+        } else if let Some((_, last)) = groups.last_mut() {
+            *last = operation;
+        } else {
+            return Err(anyhow!(
+                "cannot replay `{}`: no command line was recorded",
+                operation.message
+            ));
+        }
+    }
+
+    for (command, _) in &groups {
+        check_replayable(command)?;
+    }
+
+    let differences = start.snapshot.differences(&current_snapshot()?);
+    if !differences.is_empty() {
+        return Err(anyhow!(
+            "stack does not match the starting state `{}` ({})\n  {}",
+            start.message,
+            start.state,
+            differences.join("\n  ")
+        ));
+    }
+
+    let exe = std::env::current_exe().context("finding stg executable")?;
+    let total = groups.len();
+    for (i, (command, expected)) in groups.into_iter().enumerate() {
+        let display = display_command(command);
+        crate::print_info_message(
+            matches,
+            &format!("replaying {}/{total}: `{display}`", i + 1),
+        );
+        let status = std::process::Command::new(&exe)
+            .args(command)
+            .status()
+            .with_context(|| format!("running `{display}`"))?;
+        let differences = expected.snapshot.differences(&current_snapshot()?);
+        if !differences.is_empty() {
+            let status_note = if status.success() {
+                String::new()
+            } else if let Some(code) = status.code() {
+                format!(", which exited with status {code}")
+            } else {
+                String::from(", which was terminated by a signal")
+            };
+            return Err(anyhow!(
+                "stack state diverged after `{display}`{status_note}\n  {}",
+                differences.join("\n  ")
+            ));
+        }
+    }
+
+    crate::print_info_message(matches, &format!("replayed {total} operations"));
+    Ok(())
+}
+
+/// Ensure that a recorded command line is safe to replay.
+///
+/// The command must be one of [`REPLAYABLE_COMMANDS`] and the command line must parse
+/// without any of the [`UNREPLAYABLE_ARGS`].
+fn check_replayable(command: &[String]) -> Result<()> {
+    let refuse = |reason: &str| {
+        anyhow!(
+            "refusing to replay `{}`: {reason}",
+            display_command(command)
+        )
+    };
+    let stgit_command = command
+        .first()
+        .filter(|name| REPLAYABLE_COMMANDS.contains(&name.as_str()))
+        .and_then(|name| {
+            super::STGIT_COMMANDS
+                .iter()
+                .find(|stgit_command| stgit_command.name == name)
+        })
+        .ok_or_else(|| refuse("only commands that manipulate the stack may be replayed"))?;
+    let matches = (stgit_command.make)()
+        .try_get_matches_from(command)
+        .map_err(|_| refuse("invalid command line"))?;
+    if let Some(id) = matches
+        .ids()
+        .find(|id| UNREPLAYABLE_ARGS.contains(&id.as_str()))
+    {
+        let long = (stgit_command.make)()
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .and_then(clap::Arg::get_long)
+            .unwrap_or(id.as_str())
+            .to_string();
+        return Err(refuse(&format!(
+            "the `--{long}` option may not be replayed"
+        )));
+    }
+    Ok(())
+}
+
+/// Snapshot the current stack's state, reading it anew from the repository.
+fn current_snapshot() -> Result<Snapshot> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+    Snapshot::new(&stack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_command_quoting() {
+        let command = |args: &[&str]| {
+            display_command(&args.iter().map(ToString::to_string).collect::<Vec<_>>())
+        };
+        assert_eq!(command(&["push", "-a"]), "stg push -a");
+        assert_eq!(
+            command(&["new", "-m", "a msg", "p1"]),
+            "stg new -m 'a msg' p1"
+        );
+        assert_eq!(command(&["edit", "-m", "it's"]), "stg edit -m 'it'\\''s'");
+        assert_eq!(command(&["goto", ""]), "stg goto ''");
+    }
+
+    #[test]
+    fn replayable_commands() {
+        let check = |args: &[&str]| {
+            check_replayable(&args.iter().map(ToString::to_string).collect::<Vec<_>>())
+                .map_err(|e| e.to_string())
+        };
+        assert!(check(&["push", "-a"]).is_ok());
+        assert!(check(&["new", "-m", "a msg", "p1"]).is_ok());
+        assert_eq!(
+            check(&["foreach", "--exec", "true"]).unwrap_err(),
+            "refusing to replay `stg foreach --exec true`: only commands that manipulate \
+             the stack may be replayed"
+        );
+        assert!(check(&["series", "--status-cmd", "true"]).is_err());
+        assert!(check(&["-c", "core.editor=true", "edit"]).is_err());
+        assert_eq!(
+            check(&["edit", "--save-template", "/tmp/x", "p1"]).unwrap_err(),
+            "refusing to replay `stg edit --save-template /tmp/x p1`: the \
+             `--save-template` option may not be replayed"
+        );
+        assert!(check(&["refresh", "-O", "--output=/tmp/x"]).is_err());
+        assert!(check(&["push", "--no-such-option"]).is_err());
+    }
+}
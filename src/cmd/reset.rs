@@ -6,16 +6,18 @@ use anyhow::{anyhow, Result};
 use clap::Arg;
 
 use crate::{
-    color::get_color_stdout,
+    argset,
     ext::RepositoryExtended,
+    output::Output,
     patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackState},
+    stack::{InitializationPolicy, Stack, StackAccess, StackState},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "reset",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -55,9 +57,10 @@ fn make() -> clap::Command {
                 .help("Discard changes in the index and worktree")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::no_backup_arg())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     if let Some(committish) = crate::argset::get_one_str(matches, "committish") {
         let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
@@ -69,12 +72,14 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             .try_into_commit()
             .map_err(|_| anyhow!("target `{committish}` is not a commit"))?
             .id;
+        argset::backup_discarded_changes(&repo, stack.get_branch_name(), matches)?;
         stack
             .setup_transaction()
+            .command_line(command_line)
             .use_index_and_worktree(true)
             .discard_changes(matches.get_flag("hard"))
             .allow_bad_head(matches.get_many::<PatchRange>("patchranges-all").is_none())
-            .with_output_stream(get_color_stdout(matches))
+            .with_output(Output::new(matches))
             .transact(|trans| {
                 let commit = trans.repo().find_commit(commit_id)?;
                 let reset_state = StackState::from_commit(trans.repo(), &commit)?;
@@ -86,10 +91,12 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                     trans.reset_to_state(reset_state)
                 }
             })
-            .execute("reset")?;
+            .execute(&format!("reset {committish}"))?;
         Ok(())
     } else if matches.get_flag("hard") {
-        let head_tree_id = repo.head_commit()?.tree_id()?.detach();
+        let branch_name = repo.get_current_branch()?.get_branch_name()?.to_string();
+        argset::backup_discarded_changes(&repo, &branch_name, matches)?;
+        let head_tree_id = repo.head_commit_ex()?.tree_id()?.detach();
         repo.stupid().read_tree_checkout_hard(head_tree_id)
     } else {
         unreachable!();
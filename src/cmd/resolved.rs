@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg resolved` implementation.
+
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+use clap::{Arg, ArgGroup, ArgMatches};
+
+use crate::{
+    ext::RepositoryExtended,
+    pathspec,
+    stack::{
+        conflicts::{self, ConflictKind, UnmergedEntry},
+        InitializationPolicy, Stack, StackAccess,
+    },
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "resolved",
+    category: super::CommandCategory::PatchManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("List, mark, or unmark conflict resolutions")
+        .long_about(
+            "List, mark, or unmark conflict resolutions for conflicts resulting from \
+             pushing a patch.\n\
+             \n\
+             Without options, the conflicted paths are listed along with the kind of \
+             conflict and the merge stages (base, ours, theirs) present for each \
+             path. Paths whose conflicts have already been marked as resolved are \
+             listed as well.\n\
+             \n\
+             When paths are given, or with '--mark', the work tree content of the \
+             given paths is taken as their resolution, clearing the paths' unmerged \
+             index entries.\n\
+             \n\
+             Use '--unmark' to restore the conflicted index entries of paths that \
+             were previously marked as resolved. The conflict markers are also \
+             restored to the work tree, discarding any attempted resolution. The \
+             conflicted entries are recorded when the conflicting push occurs and \
+             remain available until the branch head changes, e.g. with 'stg refresh'.",
+        )
+        .override_usage(super::make_usage(
+            "stg resolved",
+            &["", "[--mark] <path>...", "--unmark <path>..."],
+        ))
+        .arg(
+            Arg::new("paths")
+                .help("Paths to mark or unmark")
+                .value_name("path")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("mark")
+                .long("mark")
+                .help("Mark paths as resolved")
+                .action(clap::ArgAction::SetTrue)
+                .requires("paths"),
+        )
+        .arg(
+            Arg::new("unmark")
+                .long("unmark")
+                .help("Restore the conflicts of resolved paths")
+                .action(clap::ArgAction::SetTrue)
+                .requires("paths"),
+        )
+        .group(ArgGroup::new("action").args(["mark", "unmark"]))
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
+    let stupid = repo.stupid();
+    let unmerged = conflicts::parse_unmerged(&stupid.ls_files_unmerged()?)?;
+    let recorded = conflicts::load(&repo, stack.get_branch_name(), stack.get_branch_head().id)?;
+
+    let Some(paths) = matches.get_many::<PathBuf>("paths") else {
+        return list(&unmerged, recorded.as_deref());
+    };
+
+    let prefix = repo
+        .prefix()
+        .context("determining Git prefix")?
+        .map(|prefix| gix::path::into_bstr(prefix).into_owned())
+        .unwrap_or_default();
+    let workdir = repo
+        .work_dir()
+        .ok_or_else(|| anyhow!("a work tree is required to resolve conflicts"))?;
+    let paths = paths
+        .map(|path| pathspec::root_relative_path(workdir, prefix.as_bstr(), path))
+        .collect::<Result<Vec<BString>>>()?;
+
+    if !matches.get_flag("unmark") {
+        for path in &paths {
+            if !unmerged.iter().any(|entry| entry.path() == path) {
+                return Err(anyhow!("`{path}` is not in conflict"));
+            }
+        }
+        stupid.update_index(Some(
+            paths
+                .iter()
+                .map(|path| path.to_os_str().expect("path is valid for the OS")),
+        ))
+    } else {
+        let recorded = recorded
+            .ok_or_else(|| anyhow!("no conflicts are recorded for the current branch head"))?;
+        let null_id = repo.object_hash().null();
+        let mut index_info = BString::default();
+        let mut merge_paths = Vec::new();
+
+        for path in &paths {
+            if unmerged.iter().any(|entry| entry.path() == path) {
+                return Err(anyhow!("`{path}` is still in conflict"));
+            }
+            let entries: Vec<&UnmergedEntry> = recorded
+                .iter()
+                .filter(|entry| entry.path() == path)
+                .collect();
+            if entries.is_empty() {
+                return Err(anyhow!("`{path}` has no recorded conflict"));
+            }
+            index_info.push_str(format!("0 {null_id}\t"));
+            index_info.push_str(path);
+            index_info.push_byte(b'\0');
+            for entry in &entries {
+                index_info.push_str(entry.index_info());
+                index_info.push_byte(b'\0');
+            }
+            // A conflicted file can only be recreated when both sides have content.
+            if [2, 3]
+                .iter()
+                .all(|stage| entries.iter().any(|entry| entry.stage() == *stage))
+            {
+                merge_paths.push(path.to_os_str().expect("path is valid for the OS"));
+            }
+        }
+
+        stupid.update_index_info(&index_info)?;
+        if !merge_paths.is_empty() {
+            stupid.checkout_merge(merge_paths)?;
+        }
+        Ok(())
+    }
+}
+
+fn list(unmerged: &[UnmergedEntry], recorded: Option<&[UnmergedEntry]>) -> Result<()> {
+    let mut paths: BTreeMap<&BStr, (bool, Vec<u8>, ConflictKind)> = BTreeMap::new();
+    let unmerged_kinds: BTreeMap<&BStr, ConflictKind> =
+        conflicts::classify(unmerged).into_iter().collect();
+    for entry in unmerged {
+        let (_, stages, _) =
+            paths
+                .entry(entry.path())
+                .or_insert((true, Vec::new(), unmerged_kinds[entry.path()]));
+        stages.push(entry.stage());
+    }
+    let recorded = recorded.unwrap_or_default();
+    let recorded_kinds: BTreeMap<&BStr, ConflictKind> =
+        conflicts::classify(recorded).into_iter().collect();
+    for entry in recorded {
+        let (is_unmerged, stages, _) =
+            paths
+                .entry(entry.path())
+                .or_insert((false, Vec::new(), recorded_kinds[entry.path()]));
+        if !*is_unmerged {
+            stages.push(entry.stage());
+        }
+    }
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for (path, (is_unmerged, stages, kind)) in paths {
+        let stages: Vec<&str> = stages
+            .iter()
+            .map(|stage| match stage {
+                1 => "base",
+                2 => "ours",
+                _ => "theirs",
+            })
+            .collect();
+        writeln!(
+            stdout,
+            "{} {path} ({kind}: {})",
+            if is_unmerged { 'U' } else { 'R' },
+            stages.join(", ")
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg rm` implementation.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use bstr::{BString, ByteSlice};
+use clap::{Arg, ArgMatches};
+
+use crate::{ext::RepositoryExtended, index::IndexEditor};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "rm",
+    category: super::CommandCategory::PatchManipulation,
+    read_only: false,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Remove files from the index and work tree")
+        .long_about(
+            "Remove the given files from the index and from the work tree. With \
+             '--cached', the files are only removed from the index and are left in \
+             the work tree.\n\
+             \n\
+             Files are not removed if their contents in the index differ from HEAD \
+             or, unless '--cached' is used, if they have local modifications in the \
+             work tree. Use '--force' to override these checks. Files with conflicts \
+             from pushing a patch may always be removed.",
+        )
+        .arg(
+            Arg::new("paths")
+                .help("Files to remove")
+                .value_name("path")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::AnyPath)
+                .required(true),
+        )
+        .arg(
+            Arg::new("cached")
+                .long("cached")
+                .help("Only remove files from the index")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Remove files even if they have changes")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("recursive")
+                .short('r')
+                .help("Allow recursive removal of directories")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let mut editor = IndexEditor::open(&repo)?;
+    let cached = matches.get_flag("cached");
+    let force = matches.get_flag("force");
+    let recursive = matches.get_flag("recursive");
+
+    let mut to_remove: Vec<BString> = Vec::new();
+    for path in matches
+        .get_many::<PathBuf>("paths")
+        .expect("paths are required")
+    {
+        let path = editor.root_relative_path(path)?;
+        let tracked = editor.tracked_paths(path.as_bstr());
+        if tracked.is_empty() {
+            return Err(anyhow!(
+                "pathspec `{}` did not match any files",
+                path.to_str_lossy()
+            ));
+        }
+        if !recursive && tracked.iter().any(|tracked_path| tracked_path != &path) {
+            return Err(anyhow!(
+                "not removing `{}` recursively without `-r`",
+                path.to_str_lossy()
+            ));
+        }
+        to_remove.extend(tracked);
+    }
+    to_remove.sort();
+    to_remove.dedup();
+
+    if !force {
+        let head_tree = repo
+            .head_commit()
+            .ok()
+            .and_then(|commit| commit.tree().ok());
+        for path in &to_remove {
+            if editor.is_conflicted(path.as_bstr()) {
+                continue;
+            }
+            // Files that have already vanished from the work tree can always be removed.
+            let Some(worktree_id) = editor.worktree_id(path.as_bstr())? else {
+                continue;
+            };
+            let index_id = editor.entry_id(path.as_bstr());
+            let head_id = if let Some(tree) = head_tree.as_ref() {
+                tree.lookup_entry_by_path(gix::path::from_bstr(path.as_bstr()))?
+                    .map(|entry| entry.object_id())
+            } else {
+                None
+            };
+            let staged = head_id != index_id;
+            let modified = Some(worktree_id) != index_id;
+            let problem = match (staged, modified) {
+                (true, true) => Some("has staged content different from both the file and HEAD"),
+                (true, false) if !cached => Some("has changes staged in the index"),
+                (false, true) if !cached => Some("has local modifications"),
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                return Err(anyhow!(
+                    "`{}` {problem}; use `--cached` to keep the file, or `--force` to force \
+                     removal",
+                    path.to_str_lossy()
+                ));
+            }
+        }
+    }
+
+    for path in &to_remove {
+        editor.remove(path.as_bstr());
+        if !cached {
+            remove_work_file(&editor, path)?;
+        }
+    }
+    editor.write()?;
+
+    for path in &to_remove {
+        crate::print_info_message(matches, &format!("Removed `{}`", path.to_str_lossy()));
+    }
+    Ok(())
+}
+
+/// Remove a file from the work tree along with any leading directories left empty.
+fn remove_work_file(editor: &IndexEditor, path: &BString) -> Result<()> {
+    let work_path = editor.work_path(path.as_bstr());
+    match std::fs::remove_file(&work_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+    let work_dir = editor.work_path(b"".as_bstr());
+    let mut dir = work_path.parent();
+    while let Some(parent) = dir {
+        if parent == work_dir || std::fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
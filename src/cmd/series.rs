@@ -2,26 +2,37 @@
 
 //! `stg series` implementation.
 
-use std::{io::Write, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
-use anyhow::{anyhow, Result};
-use bstr::ByteSlice;
+use anyhow::{anyhow, Context, Result};
+use bstr::{BString, ByteSlice};
 use clap::{Arg, ArgGroup, ArgMatches, ValueHint};
+use gix::prelude::ObjectIdExt;
 use termcolor::WriteColor;
 
 use crate::{
     argset,
     branchloc::BranchLocator,
-    ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    ext::{CommitExtended, RepositoryExtended, TimeExtended},
+    patch::{LocationGroup, PatchName, PatchRange, RangeConstraint, Subject},
+    patchfmt::{PatchFormat, PatchInfo},
+    stack::{
+        InitializationPolicy, PatchView, Stack, StackAccess, StackConfig, StackStateAccess,
+        StackView,
+    },
+    stupid::Stupid,
 };
 
-const UNPRINTABLE: &str = "???";
-
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "series",
     category: super::CommandCategory::StackInspection,
+    read_only: true,
     make,
     run,
 };
@@ -41,8 +52,35 @@ fn make() -> clap::Command {
              are displayed. The reversed order is more stack-like, with the base of \
              the stack appearing at the bottom of of the display.\n\
              \n\
-             Empty patches are prefixed with a '*' when the --empty option is used.",
+             Empty patches are prefixed with a '*' when the --empty option is used.\n\
+             \n\
+             With --touches-worktree, patches that change any of the files modified \
+             in the work tree or index are prefixed with a '~'. This helps to tell \
+             whether patches may be popped or pushed without disturbing local \
+             changes.\n\
+             \n\
+             With --dirty, or when the `stgit.series.showdirty` configuration \
+             variable is true, the topmost patch is prefixed with '>*' instead of '>' \
+             when the index or work tree have changes not yet refreshed into it.\n\
+             \n\
+             When the `stgit.series.window` configuration variable is set to a \
+             positive number <n>, only the <n> applied patches nearest the topmost \
+             patch and the first <n> unapplied patches are shown by default, with the \
+             number of omitted patches shown in \"... (<k> more)\" lines. The topmost \
+             patch is always shown. Patch ranges and the selection options, e.g. \
+             --all, or --no-window show all the selected patches.\n\
+             \n\
+             The layout of this output may change between releases. Tools parsing it \
+             should use --format or the frozen legacy layout selected with \
+             --compat-v1.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg series p2..",
+                "List `p2` and the applied patches above it",
+            ),
+            ("stg series '%{net}'", "List the patches labeled `net`"),
+        ]))
         .override_usage(super::make_usage(
             "stg series",
             &[
@@ -62,6 +100,8 @@ fn make() -> clap::Command {
                 .conflicts_with_all(["all", "applied", "unapplied", "hidden", "short"]),
         )
         .arg(argset::branch_arg())
+        .arg(argset::remote_stack_arg().conflicts_with("branch"))
+        .arg(argset::include_gaps_arg())
         .next_help_heading("Select Options")
         .arg(
             Arg::new("all")
@@ -82,6 +122,12 @@ fn make() -> clap::Command {
                 .value_parser(argset::parse_usize),
         )
         .group(ArgGroup::new("all-short-group").args(["all", "short"]))
+        .arg(
+            Arg::new("no-window")
+                .long("no-window")
+                .help("Show all patches regardless of `stgit.series.window`")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("applied")
                 .long("applied")
@@ -128,13 +174,107 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .overrides_with("author"),
         )
+        .arg(
+            Arg::new("date")
+                .long("date")
+                .help("Display author or committer date for each patch")
+                .long_help(
+                    "Display the author date or committer date for each patch. The \
+                     author date is displayed by default.",
+                )
+                .value_name("which")
+                .num_args(0..=1)
+                .default_missing_value("author")
+                .require_equals(true)
+                .value_parser(["author", "committer"]),
+        )
+        .arg(
+            Arg::new("no-date")
+                .long("no-date")
+                .help("Do not display patch dates")
+                .hide(true)
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("date"),
+        )
+        .arg(
+            Arg::new("date-format")
+                .long("date-format")
+                .help("Format of displayed dates")
+                .long_help(
+                    "Format of displayed dates. The \"iso\" format, the default, displays \
+                     dates like \"2005-04-07 15:13:13 -0700\". The \"short\" format only \
+                     displays the date as \"2005-04-07\". And the \"relative\" format \
+                     displays dates relative to the current time, e.g. \"2 hours ago\".",
+                )
+                .value_name("format")
+                .value_parser(["iso", "short", "relative"])
+                .requires("date"),
+        )
         .arg(
             Arg::new("count")
                 .long("count")
                 .short('c')
                 .help("Display the number of selected patches and exit")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["description", "author", "empty", "show-branch", "no-prefix"]),
+                .conflicts_with_all([
+                    "description",
+                    "author",
+                    "date",
+                    "empty",
+                    "touches-worktree",
+                    "show-branch",
+                    "no-prefix",
+                ]),
+        )
+        .arg(
+            Arg::new("save-order")
+                .long("save-order")
+                .help("Write the order of the applied and unapplied patches to <file>")
+                .long_help(
+                    "Write the names of the applied and unapplied patches to <file>, one \
+                     per line from bottom to top, and exit. After editing the file, the \
+                     stack may be reordered to match it using `stg float \
+                     --reorder-from`.",
+                )
+                .value_name("file")
+                .value_hint(ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "all",
+                    "applied",
+                    "unapplied",
+                    "hidden",
+                    "short",
+                    "missing",
+                    "count",
+                ]),
+        )
+        .arg(
+            Arg::new("save")
+                .long("save")
+                .help("Write the layout of all patches to <file>")
+                .long_help(
+                    "Write the layout of the stack to <file> and exit. The file lists \
+                     all patches from bottom to top, one per line, each marked as \
+                     applied ('+', or '>' for the topmost applied patch), unapplied \
+                     ('-'), or hidden ('!'). The layout may later be restored using \
+                     `stg float --restore`.",
+                )
+                .value_name("file")
+                .value_hint(ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "all",
+                    "applied",
+                    "unapplied",
+                    "hidden",
+                    "short",
+                    "missing",
+                    "count",
+                    "save-order",
+                ]),
         )
         .arg(
             Arg::new("commit-id")
@@ -147,7 +287,9 @@ fn make() -> clap::Command {
                      The optional length indicates how many prefix characters of the \
                      commit id to display. The default is \"full\", which displays the \
                      full commit id, but may alternatively be specified as a positive \
-                     integer greater than or equal to 4.",
+                     integer greater than or equal to 4, or as \"short\" to abbreviate \
+                     the commit id as git does, according to `core.abbrev` and long \
+                     enough to be unambiguous.",
                 )
                 .value_name("length")
                 .num_args(0..=1)
@@ -155,19 +297,37 @@ fn make() -> clap::Command {
                 .require_equals(true)
                 .value_parser(clap::value_parser!(CommitIdLength)),
         )
+        .arg(
+            Arg::new("short-id")
+                .long("short-id")
+                .help("Display the abbreviated commit id for each patch")
+                .long_help(
+                    "Display the abbreviated commit id for each patch. This is the same \
+                     as `--commit-id=short`.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("commit-id"),
+        )
         .arg(
             Arg::new("no-commit-id")
                 .long("no-commit-id")
                 .help("Do not display commit ids")
                 .hide(true)
                 .action(clap::ArgAction::SetTrue)
-                .overrides_with("commit-id"),
+                .overrides_with_all(["commit-id", "short-id"]),
         )
         .arg(
             Arg::new("description")
                 .long("description")
                 .short('d')
                 .help("Display short description for each patch")
+                .long_help(
+                    "Display a short description for each patch, i.e. the first line \
+                     of its message. Patches whose first message line is empty are \
+                     marked with \"[no subject]\" and described by the first non-empty \
+                     line of their message, truncated, or by their patch name when the \
+                     message is entirely empty.",
+                )
                 .action(clap::ArgAction::SetTrue)
                 .overrides_with("no-description"),
         )
@@ -199,6 +359,41 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .overrides_with("empty"),
         )
+        .arg(
+            Arg::new("touches-worktree")
+                .long("touches-worktree")
+                .help("Display whether patches touch locally modified files")
+                .long_help(
+                    "Before the '+', '>', '-', and '!' prefixes, print a column that \
+                     contains either '~' (for patches changing files that are \
+                     modified in the work tree or index) or a space (for other \
+                     patches). Untracked files are not considered. The column is \
+                     omitted, with a notice, while there are unresolved conflicts.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dirty")
+                .long("dirty")
+                .help("Mark the topmost patch when it has unrefreshed changes")
+                .long_help(
+                    "Mark the topmost patch when it has unrefreshed changes. The \
+                     topmost patch is prefixed with '>*' instead of '>' when the index \
+                     or work tree differ from the patch. Untracked files are not \
+                     considered. Enabled by default when `stgit.series.showdirty` is \
+                     true.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("no-dirty"),
+        )
+        .arg(
+            Arg::new("no-dirty")
+                .long("no-dirty")
+                .help("Do not mark the topmost patch with unrefreshed changes")
+                .hide(true)
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("dirty"),
+        )
         .arg(
             Arg::new("prefix")
                 .long("prefix")
@@ -289,11 +484,297 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .overrides_with("show-branch"),
         )
+        .arg(
+            Arg::new("quote")
+                .long("quote")
+                .help("Quote patch names with characters special to the shell")
+                .long_help(
+                    "Enclose patch names containing characters that are special to \
+                     the shell in single quotes, such that the names may be \
+                     copy-pasted into a command line. Enabled by default when \
+                     `stgit.series.quote` is true.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("no-quote"),
+        )
+        .arg(
+            Arg::new("no-quote")
+                .long("no-quote")
+                .help("Do not quote patch names")
+                .hide(true)
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("quote"),
+        )
+        .arg(
+            Arg::new("status-cmd")
+                .long("status-cmd")
+                .help("Display a status for each patch reported by <command>")
+                .long_help(
+                    "Display a status column with the status reported for each patch \
+                     by <command>, for example the result of continuous integration \
+                     runs. Defaults to the value of the `stgit.series.statuscmd` \
+                     configuration variable.\n\
+                     \n\
+                     The command is run once, through the shell, with a line \
+                     containing the patch name and commit id, separated by a tab, for \
+                     each listed patch on its standard input. Each line the command \
+                     writes to its standard output should contain a commit id \
+                     followed by whitespace and the status of that commit. Statuses \
+                     of \"pass\", \"fail\", and \"pending\" are colored accordingly; \
+                     any other status is displayed as-is.\n\
+                     \n\
+                     If the command fails or does not finish within the number of \
+                     seconds given by `stgit.series.statustimeout` (default 10), a \
+                     warning is printed and the patches are listed without statuses.",
+                )
+                .value_name("command")
+                .value_hint(ValueHint::CommandString)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("no-status-cmd")
+                .long("no-status-cmd")
+                .help("Do not display patch statuses")
+                .hide(true)
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("status-cmd"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output the selected patches as JSON or with a custom <format>")
+                .long_help(
+                    "Output the selected patches as JSON or with a custom <format>. The \
+                     \"json\" format is an array with an object for each patch and the \
+                     \"json-lines\" format has one such object per line.\n\
+                     \n\
+                     Each object has the patch's \"name\", its \"state\" (\"current\" \
+                     for the topmost patch, otherwise \"applied\", \"unapplied\", or \
+                     \"hidden\"), its \"commit_id\", its \"author\" with \"name\", \
+                     \"email\", and ISO 8601 \"date\", its \"description\", i.e. its \
+                     subject as shown with --description, and whether it is \
+                     \"empty\". Patches whose description stands in for an empty first \
+                     message line also have \"no_subject\" set to true.\n\
+                     \n\
+                     Any other <format> is a format string which is output for each \
+                     patch, followed by a newline. Placeholders of the form %(<name>) are \
+                     replaced with information about the patch and %% is a literal %. \
+                     The placeholders are:\n\
+                     \n\
+                     - %(name): the patch name\n\
+                     - %(state): the patch state sigil, i.e. '>', '+', '-', or '!'\n\
+                     - %(index): the absolute index of the patch in the stack\n\
+                     - %(commit) and %(commit:short): the full or abbreviated commit id\n\
+                     - %(author) and %(committer): the identity as \"Name <email>\"\n\
+                     - %(author:name) and %(author:email): the author name or email\n\
+                     - %(author:date): the author date in ISO 8601-like format\n\
+                     - %(author:date:<strftime>): the author date formatted with a \
+                     strftime-style format, e.g. %(author:date:%Y-%m-%d)\n\
+                     - %(committer:name), %(committer:email), %(committer:date), and \
+                     %(committer:date:<strftime>): likewise for the committer\n\
+                     - %(subject): the first line of the patch message or, when empty, \
+                     its fallback as for --description\n\
+                     - %(body): the patch message after the subject line\n\
+                     - %(empty): \"true\" if the patch makes no changes, else \
+                     \"false\"\n\
+                     \n\
+                     Placeholders, except for dates with a strftime-style format, may \
+                     have a :[-]<width>[.<max>] suffix which pads the replacement to \
+                     <width> characters, aligned right or, with '-', left, and truncates \
+                     it to <max> characters. E.g. %(name:-30) or %(subject:.50).\n\
+                     \n\
+                     Patches are selected exactly as for the text output, except that \
+                     `stgit.series.window` does not apply. The options that only \
+                     affect the text output may not be combined with --format.",
+                )
+                .value_name("format")
+                .value_parser(clap::value_parser!(SeriesFormat))
+                .conflicts_with_all([
+                    "count",
+                    "save-order",
+                    "save",
+                    "commit-id",
+                    "short-id",
+                    "description",
+                    "author",
+                    "date",
+                    "empty",
+                    "touches-worktree",
+                    "no-prefix",
+                    "indices",
+                    "offsets",
+                    "show-branch",
+                    "quote",
+                    "status-cmd",
+                ]),
+        )
+        .arg(
+            Arg::new("compat-v1")
+                .long("compat-v1")
+                .help("Display patches in the frozen layout of StGit 1.x")
+                .long_help(
+                    "Display the patches exactly as the Python implementation of StGit \
+                     1.x did, for tools that parse the output of `stg series`. Also \
+                     enabled by the `stgit.series.compat` configuration variable, \
+                     unless options not supported by the legacy layout are given.\n\
+                     \n\
+                     Each patch is printed on its own line, prefixed with '+ ', '> ', \
+                     '- ', or '! ' and, with --empty, preceded by a '0' for empty \
+                     patches or a space otherwise. Patch names are not quoted. With \
+                     --description or --author, the patch names, including any branch \
+                     prefix from --showbranch, are padded to the same width and followed \
+                     by ' # ' and the first line of the message or the author's name. \
+                     Only the description is shown when both options are given. The \
+                     output is never colored and `stgit.series.window` and \
+                     `stgit.series.showdirty` do not apply.\n\
+                     \n\
+                     This layout is frozen and will not change in future releases, \
+                     whereas the default output may gain new columns and markers.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(COMPAT_V1_UNSUPPORTED),
+        )
+        .arg(
+            Arg::new("no-compat-v1")
+                .long("no-compat-v1")
+                .help("Do not use the StGit 1.x layout")
+                .hide(true)
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("compat-v1"),
+        )
+}
+
+/// Options of the default text output not supported by the `--compat-v1` layout.
+const COMPAT_V1_UNSUPPORTED: [&str; 11] = [
+    "format",
+    "commit-id",
+    "short-id",
+    "date",
+    "touches-worktree",
+    "dirty",
+    "indices",
+    "offsets",
+    "reverse",
+    "quote",
+    "status-cmd",
+];
+
+/// Determine whether the patches are to be displayed in the `--compat-v1` layout.
+///
+/// The `stgit.series.compat` configuration variable only selects the legacy layout when
+/// none of the options it does not support are given.
+fn use_compat_v1(matches: &ArgMatches, stack: &Stack) -> bool {
+    matches.get_flag("compat-v1")
+        || (!matches.get_flag("no-compat-v1")
+            && stack
+                .config()
+                .boolean("stgit.series.compat")
+                .unwrap_or(false)
+            && !COMPAT_V1_UNSUPPORTED
+                .iter()
+                .any(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine)))
+}
+
+/// Write the patches in the frozen layout of the Python implementation of StGit 1.x.
+fn write_compat_v1<'v, 'repo: 'v>(
+    matches: &ArgMatches,
+    branch_name: &str,
+    patches: impl IntoIterator<Item = (PatchView<'v, 'repo>, char)>,
+) -> Result<()> {
+    let branch_prefix = if matches.get_flag("show-branch") {
+        format!("{branch_name}:")
+    } else {
+        String::new()
+    };
+    let patches: Vec<(PatchView, char, String)> = patches
+        .into_iter()
+        .map(|(patch, sigil)| (patch, sigil, format!("{branch_prefix}{}", patch.name())))
+        .collect();
+    let no_prefix_flag = matches.get_flag("no-prefix");
+    let empty_flag = matches.get_flag("empty");
+    let description_flag = matches.get_flag("description");
+    let author_flag = matches.get_flag("author");
+    let name_width = if description_flag || author_flag {
+        patches
+            .iter()
+            .map(|(_, _, name)| name.chars().count())
+            .max()
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for (patch, sigil, name) in patches {
+        let commit = patch.commit();
+        let mut line = String::new();
+        if !no_prefix_flag {
+            if empty_flag {
+                line.push(if commit.is_no_change()? { '0' } else { ' ' });
+            }
+            line.push(sigil);
+            line.push(' ');
+        }
+        line.push_str(&format!("{name:name_width$}"));
+        if description_flag {
+            let message = commit.message_raw_sloppy().to_str_lossy();
+            let description = message.trim().lines().next().unwrap_or("").trim_end();
+            line.push_str(&format!(" # {description}"));
+        } else if author_flag {
+            let author = commit.author_strict()?;
+            line.push_str(&format!(" # {}", author.name.to_str_lossy()));
+        }
+        writeln!(stdout, "{line}")?;
+    }
+    Ok(())
+}
+
+/// JSON representation of a patch listed by `stg series --format`.
+#[derive(serde::Serialize)]
+struct JsonSeriesPatch {
+    name: PatchName,
+    state: &'static str,
+    commit_id: String,
+    author: JsonSeriesAuthor,
+    description: String,
+    empty: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    no_subject: bool,
+}
+
+#[derive(serde::Serialize)]
+struct JsonSeriesAuthor {
+    name: String,
+    email: String,
+    /// Author date in strict ISO 8601 format.
+    date: String,
+}
+
+/// Output format given with `--format`.
+#[derive(Clone)]
+enum SeriesFormat {
+    Json,
+    JsonLines,
+    Custom(PatchFormat),
+}
+
+impl FromStr for SeriesFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(SeriesFormat::Json),
+            "json-lines" => Ok(SeriesFormat::JsonLines),
+            _ => Ok(SeriesFormat::Custom(PatchFormat::from_str(s)?)),
+        }
+    }
 }
 
 #[derive(Clone)]
 enum CommitIdLength {
     Full,
+    Short,
     Length(usize),
 }
 
@@ -303,6 +784,7 @@ impl FromStr for CommitIdLength {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "full" => Ok(CommitIdLength::Full),
+            "short" => Ok(CommitIdLength::Short),
             _ => usize::from_str(s)
                 .ok()
                 .and_then(|n| {
@@ -313,17 +795,23 @@ impl FromStr for CommitIdLength {
                     }
                 })
                 .ok_or_else(|| {
-                    anyhow!("length must be \"full\" or an integer greater than or equal to 4")
+                    anyhow!(
+                        "length must be \"full\", \"short\", or an integer greater than or \
+                         equal to 4"
+                    )
                 }),
         }
     }
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
-    let opt_branch = matches.get_one::<BranchLocator>("branch");
     let opt_missing = matches.get_one::<BranchLocator>("missing");
 
+    // Patches with commits missing from the repository can still be listed by name,
+    // so they are reported instead of failing.
+    crate::stack::allow_missing_patch_commits();
+
     let (stack, ref_stack) = if let Some(ref_branch) = opt_missing {
         (
             Stack::from_branch_locator(
@@ -331,64 +819,71 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 Some(ref_branch),
                 InitializationPolicy::AllowUninitialized,
             )?,
-            Some(Stack::from_branch_locator(
+            Some(argset::stack_from_args(
                 &repo,
-                opt_branch,
+                matches,
                 InitializationPolicy::RequireInitialized,
             )?),
         )
     } else {
         (
-            Stack::from_branch_locator(
-                &repo,
-                opt_branch,
-                InitializationPolicy::AllowUninitialized,
-            )?,
+            argset::stack_from_args(&repo, matches, InitializationPolicy::AllowUninitialized)?,
             None,
         )
     };
 
+    for (patchname, commit_id) in stack.missing_patches() {
+        crate::print_warning_message(
+            matches,
+            &format!(
+                "patch `{patchname}` is not listed: its commit `{commit_id}` is missing \
+                 from the repository; see `stg repair`"
+            ),
+        );
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("save-order") {
+        return save_order(&stack, path);
+    } else if let Some(path) = matches.get_one::<PathBuf>("save") {
+        return save_layout(&stack, path);
+    }
+
     let all_flag = matches.get_flag("all");
     let applied_flag = matches.get_flag("applied");
     let unapplied_flag = matches.get_flag("unapplied");
     let hidden_flag = matches.get_flag("hidden");
 
-    struct Entry {
-        patchname: PatchName,
-        commit_id: gix::ObjectId,
+    struct Entry<'v, 'repo> {
+        patch: PatchView<'v, 'repo>,
         sigil: char,
-        index: usize,
-        offset_from_top: isize,
     }
 
+    impl<'v, 'repo> Entry<'v, 'repo> {
+        fn new(patch: PatchView<'v, 'repo>) -> Self {
+            let sigil = match patch.location() {
+                _ if patch.is_top() => '>',
+                LocationGroup::Applied => '+',
+                LocationGroup::Unapplied => '-',
+                LocationGroup::Hidden => '!',
+            };
+            Self { patch, sigil }
+        }
+    }
+
+    let view = StackView::new(&stack);
+
     let mut patches: Vec<Entry> = vec![];
 
     if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
-        let top_patchname = stack.applied().last();
-        for patchname in patchrange::resolve_names_contiguous(
+        let positions: HashMap<&PatchName, PatchView> =
+            view.all().map(|patch| (patch.name(), patch)).collect();
+        for patchname in argset::resolve_names_contiguous(
             &stack,
             range_specs,
             RangeConstraint::AllWithAppliedBoundary,
+            matches,
         )? {
-            let commit_id = stack.get_patch_commit_id(&patchname);
-            let sigil = if Some(&patchname) == top_patchname {
-                '>'
-            } else if stack.is_applied(&patchname) {
-                '+'
-            } else if stack.is_unapplied(&patchname) {
-                '-'
-            } else {
-                '!'
-            };
-            let index = stack.index_of(&patchname);
-            let offset_from_top = stack.distance_from(&patchname, top_patchname);
-            patches.push(Entry {
-                patchname,
-                commit_id,
-                sigil,
-                index,
-                offset_from_top,
-            });
+            patches.push(Entry::new(positions[&patchname]));
         }
     } else {
         let show_applied = applied_flag || all_flag || !(unapplied_flag || hidden_flag);
@@ -396,58 +891,19 @@ fn run(matches: &ArgMatches) -> Result<()> {
         let show_hidden = hidden_flag || all_flag;
 
         if show_applied {
-            if let Some((last_patchname, rest)) = stack.applied().split_last() {
-                for patchname in rest {
-                    patches.push(Entry {
-                        patchname: patchname.clone(),
-                        commit_id: stack.get_patch_commit_id(patchname),
-                        sigil: '+',
-                        index: stack.index_of(patchname),
-                        offset_from_top: stack.distance_from(patchname, Some(last_patchname)),
-                    });
-                }
-                patches.push(Entry {
-                    patchname: last_patchname.clone(),
-                    commit_id: stack.get_patch_commit_id(last_patchname),
-                    sigil: '>',
-                    index: stack.index_of(last_patchname),
-                    offset_from_top: 0,
-                });
-            }
+            patches.extend(view.applied().map(Entry::new));
         }
-
         if show_unapplied {
-            for (i, patchname) in stack.unapplied().iter().enumerate() {
-                patches.push(Entry {
-                    patchname: patchname.clone(),
-                    commit_id: stack.get_patch_commit_id(patchname),
-                    sigil: '-',
-                    index: stack.index_of(patchname),
-                    offset_from_top: (i + 1) as isize,
-                });
-            }
+            patches.extend(view.unapplied().map(Entry::new));
         }
-
         if show_hidden {
-            let top_patchname = stack.applied().last();
-            for patchname in stack.hidden() {
-                patches.push(Entry {
-                    patchname: patchname.clone(),
-                    commit_id: stack.get_patch_commit_id(patchname),
-                    sigil: '!',
-                    index: stack.index_of(patchname),
-                    offset_from_top: stack.distance_from(patchname, top_patchname),
-                });
-            }
+            patches.extend(view.hidden().map(Entry::new));
         }
     }
 
     if let Some(ref_stack) = ref_stack {
-        patches.retain(|Entry { patchname, .. }| {
-            ref_stack
-                .all_patches()
-                .all(|ref_patchname| patchname != ref_patchname)
-        });
+        let ref_patchnames: HashSet<&PatchName> = ref_stack.all_patches().collect();
+        patches.retain(|Entry { patch, .. }| !ref_patchnames.contains(patch.name()));
     }
 
     if matches.contains_id("short") {
@@ -455,7 +911,8 @@ fn run(matches: &ArgMatches) -> Result<()> {
             .get_one::<usize>("short")
             .copied()
             .unwrap_or_else(|| {
-                repo.config_snapshot()
+                stack
+                    .config()
                     .integer("stgit.shortnr")
                     .map(|i| if i.is_negative() { 0 } else { i as usize })
                     .unwrap_or(5)
@@ -478,9 +935,119 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Ok(());
     }
 
-    let opt_commit_id = matches.get_one::<CommitIdLength>("commit-id");
+    if let Some(SeriesFormat::Custom(format)) = matches.get_one::<SeriesFormat>("format") {
+        if matches.get_flag("reverse") {
+            patches.reverse();
+        }
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        for entry in &patches {
+            let line = format.render(&PatchInfo {
+                name: entry.patch.name(),
+                sigil: entry.sigil,
+                index: entry.patch.index(),
+                commit: entry.patch.commit(),
+            })?;
+            writeln!(stdout, "{line}")?;
+        }
+        return Ok(());
+    }
+
+    if let Some(format) = matches.get_one::<SeriesFormat>("format") {
+        let mut records = patches
+            .iter()
+            .map(|entry| -> Result<JsonSeriesPatch> {
+                let commit = entry.patch.commit();
+                let commit_ref = commit.decode()?;
+                let author = commit_ref.author();
+                let subject = Subject::new(&commit.message_ex().decode()?, entry.patch.name());
+                Ok(JsonSeriesPatch {
+                    name: entry.patch.name().clone(),
+                    state: match entry.sigil {
+                        '>' => "current",
+                        '+' => "applied",
+                        '-' => "unapplied",
+                        _ => "hidden",
+                    },
+                    commit_id: commit.id.to_string(),
+                    author: JsonSeriesAuthor {
+                        name: author.name.to_str_lossy().to_string(),
+                        email: author.email.to_str_lossy().to_string(),
+                        date: author.time.format(gix::date::time::format::ISO8601_STRICT),
+                    },
+                    no_subject: subject.is_fallback(),
+                    description: subject.text,
+                    empty: commit.is_no_change()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if matches.get_flag("reverse") {
+            records.reverse();
+        }
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        if matches!(format, SeriesFormat::Json) {
+            serde_json::to_writer_pretty(&mut stdout, &records)?;
+            writeln!(stdout)?;
+        } else {
+            for record in &records {
+                serde_json::to_writer(&mut stdout, record)?;
+                writeln!(stdout)?;
+            }
+        }
+        return Ok(());
+    }
+
+    if use_compat_v1(matches, &stack) {
+        return write_compat_v1(
+            matches,
+            stack.get_branch_name(),
+            patches
+                .iter()
+                .map(|Entry { patch, sigil }| (*patch, *sigil)),
+        );
+    }
+
+    let is_default_selection = !(matches.contains_id("patchranges-all")
+        || all_flag
+        || applied_flag
+        || unapplied_flag
+        || hidden_flag
+        || matches.contains_id("short")
+        || opt_missing.is_some()
+        || matches.get_flag("no-window"));
+    let window = if is_default_selection {
+        stack
+            .config()
+            .integer("stgit.series.window")
+            .filter(|n| n.is_positive())
+            .map(|n| n as usize)
+    } else {
+        None
+    };
+
+    // With the default selection, the applied patches are followed by the unapplied
+    // patches. The window keeps those nearest the topmost patch, which is thus always
+    // shown.
+    let (mut elided_before, mut elided_after) = if let Some(window) = window {
+        let num_applied = stack.applied().len();
+        let elided_applied = num_applied.saturating_sub(window);
+        let elided_unapplied = (patches.len() - num_applied).saturating_sub(window);
+        patches.truncate(patches.len() - elided_unapplied);
+        patches.drain(..elided_applied);
+        (elided_applied, elided_unapplied)
+    } else {
+        (0, 0)
+    };
+
+    let opt_commit_id = if matches.get_flag("short-id") {
+        Some(CommitIdLength::Short)
+    } else {
+        matches.get_one::<CommitIdLength>("commit-id").cloned()
+    };
     let description_flag = matches.get_flag("description");
     let author_flag = matches.get_flag("author");
+    let date_kind = matches.get_one::<String>("date").map(String::as_str);
 
     let branch_prefix = format!("{}:", &stack.get_branch_name());
     let branch_prefix = if matches.get_flag("show-branch") {
@@ -489,73 +1056,160 @@ fn run(matches: &ArgMatches) -> Result<()> {
         ""
     };
 
-    let patchname_width = if opt_commit_id.is_some() || description_flag || author_flag {
+    let quote_names = matches.get_flag("quote")
+        || (!matches.get_flag("no-quote")
+            && stack
+                .config()
+                .boolean("stgit.series.quote")
+                .unwrap_or(false));
+
+    let patchname_width =
+        if opt_commit_id.is_some() || description_flag || author_flag || date_kind.is_some() {
+            patches
+                .iter()
+                .map(|Entry { patch, .. }| patch.name().quoted_if(quote_names).to_string().len())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+    let mut commit_ids: Vec<String> = if let Some(commit_length) = opt_commit_id.as_ref() {
         patches
             .iter()
-            .map(|Entry { patchname, .. }| patchname.len())
+            .map(|Entry { patch, .. }| patch.commit())
+            .map(|commit| match commit_length {
+                CommitIdLength::Full => commit.id.to_string(),
+                CommitIdLength::Short => commit.id.attach(&repo).shorten_or_id().to_string(),
+                CommitIdLength::Length(n) => commit
+                    .id
+                    .to_hex_with_len((*n).min(commit.id.kind().len_in_hex()))
+                    .to_string(),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let commit_id_width = commit_ids.iter().map(String::len).max().unwrap_or(0);
+
+    let mut columns = if author_flag || date_kind.is_some() {
+        let date_format = matches
+            .get_one::<String>("date-format")
+            .map_or("iso", String::as_str);
+        get_columns(
+            &repo,
+            patches.iter().map(|entry| entry.patch.commit().as_ref()),
+            author_flag,
+            date_kind,
+            date_format,
+        )?
+    } else {
+        Vec::new()
+    };
+    let author_width = if date_kind.is_some() || description_flag {
+        columns
+            .iter()
+            .filter_map(|column| column.author.as_ref())
+            .map(|author| author.chars().count())
+            .max()
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    let date_width = if description_flag {
+        columns
+            .iter()
+            .filter_map(|column| column.date.as_ref())
+            .map(|date| date.chars().count())
             .max()
             .unwrap_or(0)
     } else {
         0
     };
 
-    let author_width: usize = if author_flag && description_flag {
+    let statuses = get_statuses(
+        &repo,
+        &stack.config(),
+        matches,
         patches
             .iter()
-            .map(|Entry { commit_id, .. }| -> usize {
-                if let Ok(commit) = repo.find_commit(*commit_id) {
-                    commit
-                        .author()
-                        .ok()
-                        .and_then(|author| author.name.to_str().ok())
-                        .unwrap_or(UNPRINTABLE)
-                        .len()
-                } else {
-                    0
-                }
-            })
+            .map(|Entry { patch, .. }| (patch.name(), patch.commit().id)),
+    );
+    let status_width = statuses.as_ref().map_or(0, |statuses| {
+        statuses
+            .values()
+            .map(|status| status.chars().count())
             .max()
             .unwrap_or(0)
+            .max(1)
+    });
+
+    let touching = if matches.get_flag("touches-worktree") {
+        get_worktree_touches(
+            &repo,
+            &view,
+            matches,
+            patches.iter().map(|Entry { patch, .. }| *patch),
+        )?
     } else {
-        0
+        None
     };
 
     let no_prefix_flag = matches.get_flag("no-prefix");
+    let show_dirty = !no_prefix_flag
+        && (matches.get_flag("dirty")
+            || (!matches.get_flag("no-dirty")
+                && stack
+                    .config()
+                    .boolean("stgit.series.showdirty")
+                    .unwrap_or(false)));
+    // Local changes only belong to the current branch's stack.
+    let top_is_dirty = show_dirty
+        && patches.iter().any(|Entry { sigil, .. }| *sigil == '>')
+        && repo
+            .get_current_branch()
+            .is_ok_and(|branch| branch.get_branch_name().ok() == Some(stack.get_branch_name()))
+        && super::top::has_unrefreshed_changes(&repo)?;
     let empty_flag = matches.get_flag("empty");
     let indices_flag = matches.get_flag("indices");
     let offsets_flag = matches.get_flag("offsets");
 
-    let index_width = (indices_flag && !patches.is_empty())
-        .then(|| patches.last().unwrap().index.to_string().len())
-        .unwrap_or_default();
+    let index_width = if indices_flag && !patches.is_empty() {
+        patches.last().unwrap().patch.index().to_string().len()
+    } else {
+        0
+    };
 
-    let offset_width = (offsets_flag && !patches.is_empty())
-        .then(|| {
-            [patches.first().unwrap(), patches.last().unwrap()]
-                .iter()
-                .map(|entry| format!("{:+}", entry.offset_from_top).len())
-                .max()
-                .unwrap()
-        })
-        .unwrap_or_default();
+    let offset_width = if offsets_flag && !patches.is_empty() {
+        [patches.first().unwrap(), patches.last().unwrap()]
+            .iter()
+            .map(|entry| format!("{:+}", entry.patch.offset_from_top()).len())
+            .max()
+            .unwrap()
+    } else {
+        0
+    };
 
-    let mut stdout = crate::color::get_color_stdout(matches);
+    let mut stdout = crate::output::Output::new(matches).data();
     let mut color_spec = termcolor::ColorSpec::new();
 
     if matches.get_flag("reverse") {
         patches.reverse();
+        commit_ids.reverse();
+        columns.reverse();
+        std::mem::swap(&mut elided_before, &mut elided_after);
     }
+    let mut columns = columns.into_iter();
+    let mut commit_ids = commit_ids.into_iter();
 
-    for Entry {
-        patchname,
-        commit_id,
-        sigil,
-        index,
-        offset_from_top,
-    } in patches
-    {
-        let commit = repo.find_commit(commit_id)?;
-        let commit_ref = commit.decode()?;
+    write_elided(&mut stdout, elided_before)?;
+
+    for Entry { patch, sigil } in patches {
+        let patchname = patch.name();
+        let commit = patch.commit();
+        let index = patch.index();
+        let offset_from_top = patch.offset_from_top();
+        let column = columns.next().unwrap_or_default();
 
         if empty_flag {
             if commit.is_no_change()? {
@@ -567,6 +1221,16 @@ fn run(matches: &ArgMatches) -> Result<()> {
             }
         }
 
+        if let Some(touching) = touching.as_ref() {
+            if touching.contains(&commit.id) {
+                stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Yellow)))?;
+                write!(stdout, "~")?;
+                stdout.set_color(color_spec.set_fg(None))?;
+            } else {
+                write!(stdout, " ")?;
+            }
+        }
+
         let sigil_color = match sigil {
             '+' => Some(termcolor::Color::Green),
             '>' => Some(termcolor::Color::Blue),
@@ -577,7 +1241,13 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
         if !no_prefix_flag {
             stdout.set_color(color_spec.set_fg(sigil_color))?;
-            write!(stdout, "{sigil} ")?;
+            write!(stdout, "{sigil}")?;
+            if sigil == '>' && top_is_dirty {
+                stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Yellow)))?;
+                write!(stdout, "*")?;
+            } else {
+                write!(stdout, " ")?;
+            }
             stdout.set_color(color_spec.set_fg(None))?;
         }
 
@@ -593,6 +1263,19 @@ fn run(matches: &ArgMatches) -> Result<()> {
             stdout.set_color(color_spec.set_fg(None))?;
         }
 
+        if let Some(statuses) = statuses.as_ref() {
+            let status = statuses.get(&commit.id).map_or("-", String::as_str);
+            let status_color = match status {
+                "pass" => Some(termcolor::Color::Green),
+                "fail" => Some(termcolor::Color::Red),
+                "pending" => Some(termcolor::Color::Yellow),
+                _ => None,
+            };
+            stdout.set_color(color_spec.set_fg(status_color))?;
+            write!(stdout, "{status:status_width$} ")?;
+            stdout.set_color(color_spec.set_fg(None))?;
+        }
+
         match sigil {
             '+' => color_spec.set_intense(true),
             '>' => color_spec.set_bold(true),
@@ -602,48 +1285,350 @@ fn run(matches: &ArgMatches) -> Result<()> {
         };
         stdout.set_color(&color_spec)?;
 
-        if let Some(commit_length) = opt_commit_id.as_ref() {
-            let id_str = commit_id.to_string();
-            let id_prefix = match commit_length {
-                CommitIdLength::Full => id_str.as_str(),
-                CommitIdLength::Length(n) => {
-                    let n = (*n).min(id_str.len());
-                    &id_str[..n]
-                }
-            };
+        if let Some(id_str) = commit_ids.next() {
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Yellow)))?;
-            write!(stdout, "{id_prefix} ")?;
+            write!(stdout, "{id_str:commit_id_width$} ")?;
             stdout.set_color(color_spec.set_fg(None))?;
         }
 
-        write!(stdout, "{branch_prefix}{patchname:patchname_width$}")?;
+        write!(
+            stdout,
+            "{branch_prefix}{:patchname_width$}",
+            patchname.quoted_if(quote_names)
+        )?;
 
-        if author_flag {
+        if let Some(author) = column.author {
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Black)))?;
             write!(stdout, " # ")?;
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Blue)))?;
-            if let Ok(author) = commit.author_strict() {
-                write!(stdout, "{:author_width$}", &author.name.to_str().unwrap())?;
-            } else {
-                let name = commit_ref.author().name.to_str_lossy();
-                write!(stdout, "{name:author_width$}")?;
-            }
+            write!(stdout, "{author:author_width$}")?;
+        }
+        if let Some(date) = column.date {
+            stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Black)))?;
+            write!(stdout, " # ")?;
+            stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Cyan)))?;
+            write!(stdout, "{date:date_width$}")?;
         }
         if description_flag {
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Black)))?;
             write!(stdout, " #")?;
-            let summary = commit_ref.message_summary();
-            if !summary.is_empty() {
-                if let Ok(summary) = summary.to_str() {
-                    stdout.set_color(color_spec.set_fg(None))?;
-                    write!(stdout, " {summary}")?;
-                }
+            let subject = Subject::new(&commit.message_ex().decode()?, patchname);
+            if subject.is_fallback() {
+                stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Red)))?;
+                write!(stdout, " [no subject]")?;
             }
+            stdout.set_color(color_spec.set_fg(None))?;
+            write!(stdout, " {}", subject.text)?;
         }
         color_spec.clear();
         stdout.set_color(&color_spec)?;
         writeln!(stdout)?;
     }
 
+    write_elided(&mut stdout, elided_after)?;
+
+    Ok(())
+}
+
+/// Write a line standing in for patches omitted by `stgit.series.window`.
+fn write_elided(stdout: &mut impl WriteColor, count: usize) -> Result<()> {
+    if count > 0 {
+        let mut color_spec = termcolor::ColorSpec::new();
+        stdout.set_color(color_spec.set_dimmed(true).set_italic(true))?;
+        write!(stdout, "... ({count} more)")?;
+        stdout.reset()?;
+        writeln!(stdout)?;
+    }
     Ok(())
 }
+
+/// Get the status of each patch commit from the configured status command.
+///
+/// The command given by `--status-cmd`, or else `stgit.series.statuscmd`, is run once
+/// for all the patches. `None` is returned when no status command is configured or
+/// when the command fails or times out, in which case a warning is printed and the
+/// series is displayed without statuses.
+fn get_statuses<'a>(
+    repo: &gix::Repository,
+    config: &StackConfig,
+    matches: &ArgMatches,
+    patches: impl Iterator<Item = (&'a PatchName, gix::ObjectId)>,
+) -> Option<HashMap<gix::ObjectId, String>> {
+    let command = if matches.get_flag("no-status-cmd") {
+        return None;
+    } else if let Some(command) = matches.get_one::<String>("status-cmd") {
+        command.clone()
+    } else {
+        config
+            .string("stgit.series.statuscmd")?
+            .to_str_lossy()
+            .to_string()
+    };
+    let timeout = config
+        .integer("stgit.series.statustimeout")
+        .map_or(10, |secs| secs.max(0) as u64);
+
+    let mut input = Vec::new();
+    for (patchname, commit_id) in patches {
+        input.extend_from_slice(format!("{patchname}\t{commit_id}\n").as_bytes());
+    }
+
+    match run_status_command(repo, &command, input, Duration::from_secs(timeout)) {
+        Ok(output) => Some(parse_statuses(&output)),
+        Err(e) => {
+            crate::print_warning_message(
+                matches,
+                &format!("status unavailable: status command `{command}` {e:#}"),
+            );
+            None
+        }
+    }
+}
+
+/// Get the ids of the patch commits that change files modified in the work tree or
+/// index.
+///
+/// `None` is returned, with a notice, if there are unresolved conflicts.
+fn get_worktree_touches<'v, 'repo>(
+    repo: &gix::Repository,
+    view: &'v StackView<'v, 'repo>,
+    matches: &ArgMatches,
+    patches: impl Iterator<Item = PatchView<'v, 'repo>> + Clone,
+) -> Result<Option<HashSet<gix::ObjectId>>> {
+    if repo.work_dir().is_none() {
+        return Err(anyhow!("`--touches-worktree` requires a work tree"));
+    }
+    let stupid = repo.stupid();
+    let statuses = stupid.statuses(None)?;
+    if statuses.check_conflicts().is_err() {
+        crate::print_info_message(
+            matches,
+            "not marking patches touching the worktree while there are unresolved \
+             conflicts",
+        );
+        return Ok(None);
+    }
+
+    let modified: HashSet<BString> = statuses
+        .iter()
+        .map(|entry| gix::path::into_bstr(entry.path()).into_owned())
+        .collect();
+    let mut touching = HashSet::new();
+    if modified.is_empty() {
+        return Ok(Some(touching));
+    }
+    view.load_files(patches.clone())?;
+    for patch in patches {
+        if patch.files()?.iter().any(|path| modified.contains(path)) {
+            touching.insert(patch.commit().id);
+        }
+    }
+    Ok(Some(touching))
+}
+
+/// Run the status command, feeding it `input` and returning its standard output.
+///
+/// The command is killed if it does not complete within `timeout`. Its standard input
+/// is written and its standard output read from separate threads such that a
+/// misbehaving command can never block beyond the timeout.
+fn run_status_command(
+    repo: &gix::Repository,
+    command: &str,
+    input: Vec<u8>,
+    timeout: Duration,
+) -> Result<Vec<u8>> {
+    let mut command: std::process::Command = gix::command::prepare(command)
+        .with_shell()
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .into();
+    if let Some(work_dir) = repo.work_dir() {
+        command.current_dir(work_dir);
+    }
+    let mut child = command.spawn().context("could not be run")?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    std::thread::spawn(move || stdin.write_all(&input));
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let result = stdout.read_to_end(&mut output).map(|_| output);
+        sender.send(result)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let output = match receiver.recv_timeout(timeout) {
+        Ok(result) => result.context("output could not be read")?,
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("timed out after {} seconds", timeout.as_secs()));
+        }
+    };
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if status.success() {
+                return Ok(output);
+            } else {
+                return Err(anyhow!("failed with {status}"));
+            }
+        } else if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("timed out after {} seconds", timeout.as_secs()));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Parse `<commit id> <status>` lines output by a status command.
+///
+/// Lines that do not start with a valid commit id are ignored.
+fn parse_statuses(output: &[u8]) -> HashMap<gix::ObjectId, String> {
+    let mut statuses = HashMap::new();
+    for line in output.lines() {
+        let line = line.to_str_lossy();
+        let line = line.trim();
+        let Some((oid, status)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let status = status.trim();
+        if let (Ok(oid), false) = (gix::ObjectId::from_hex(oid.as_bytes()), status.is_empty()) {
+            statuses.insert(oid, status.to_string());
+        }
+    }
+    statuses
+}
+
+/// Optional author and date columns for a patch.
+#[derive(Default)]
+struct Columns {
+    author: Option<String>,
+    date: Option<String>,
+}
+
+/// Get author and date column content for each of the given patch commits.
+///
+/// Authors are mapped through the repository's mailmap, when enabled, with a single
+/// invocation of `git check-mailmap`.
+fn get_columns<'a, 'repo: 'a>(
+    repo: &gix::Repository,
+    commits: impl ExactSizeIterator<Item = &'a gix::Commit<'repo>>,
+    author_flag: bool,
+    date_kind: Option<&str>,
+    date_format: &str,
+) -> Result<Vec<Columns>> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let mut columns = Vec::with_capacity(commits.len());
+    let mut contacts = Vec::new();
+
+    for commit in commits {
+        let author = if author_flag {
+            let (name, email) = if let Ok(author) = commit.author_strict() {
+                (
+                    author.name.to_str_lossy().to_string(),
+                    author.email.to_str_lossy().to_string(),
+                )
+            } else {
+                let author = commit.decode()?.author();
+                (
+                    author.name.to_str_lossy().to_string(),
+                    author.email.to_str_lossy().to_string(),
+                )
+            };
+            contacts.push(BString::from(format!("{name} <{email}>")));
+            Some(name)
+        } else {
+            None
+        };
+        let date = date_kind
+            .map(|kind| -> Result<String> {
+                let commit_ref = commit.decode()?;
+                let time = if kind == "committer" {
+                    commit_ref.committer().time
+                } else {
+                    commit_ref.author().time
+                };
+                Ok(match date_format {
+                    "relative" => time.format_relative(now),
+                    "short" => time.format(gix::date::time::format::SHORT),
+                    _ => time.format(gix::date::time::format::ISO8601),
+                })
+            })
+            .transpose()?;
+        columns.push(Columns { author, date });
+    }
+
+    if author_flag && use_mailmap(repo) {
+        let stupid = repo.stupid();
+        for (column, contact) in columns.iter_mut().zip(stupid.check_mailmap(&contacts)?) {
+            if let Some((name, _)) = contact.rsplit_once_str(" <") {
+                column.author = Some(name.to_str_lossy().to_string());
+            }
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Determine whether authors should be mapped using a mailmap.
+///
+/// Like `git log`, the mailmap is used unless `log.mailmap` is false. To avoid needless
+/// work, the mailmap is only consulted if one is present in the work tree or configured
+/// with `mailmap.file` or `mailmap.blob`.
+fn use_mailmap(repo: &gix::Repository) -> bool {
+    let config = repo.config_snapshot();
+    config.boolean("log.mailmap").unwrap_or(true)
+        && (config.string("mailmap.file").is_some()
+            || config.string("mailmap.blob").is_some()
+            || repo
+                .work_dir()
+                .is_some_and(|work_dir| work_dir.join(".mailmap").is_file()))
+}
+
+/// Write the names of the applied and unapplied patches to an order file.
+fn save_order(stack: &Stack, path: &Path) -> Result<()> {
+    let mut contents = format!(
+        "# Patch order for branch `{}`, from bottom to top.\n\
+         # Reorder the stack to match with `stg float --reorder-from`.\n",
+        stack.get_branch_name()
+    );
+    for patchname in stack.applied().iter().chain(stack.unapplied()) {
+        contents.push_str(patchname.as_ref());
+        contents.push('\n');
+    }
+    std::fs::write(path, contents).with_context(|| format!("writing `{}`", path.to_string_lossy()))
+}
+
+/// Write the layout of all patches, including their applied, unapplied, or hidden
+/// state, to a layout file.
+fn save_layout(stack: &Stack, path: &Path) -> Result<()> {
+    let mut contents = format!(
+        "# Stack layout for branch `{}`, from bottom to top.\n\
+         # Patches are marked applied (+, > for the topmost), unapplied (-), or hidden (!).\n\
+         # Restore the layout with `stg float --restore`.\n",
+        stack.get_branch_name()
+    );
+    let top = stack.applied().last();
+    for patchname in stack.all_patches() {
+        let marker = if Some(patchname) == top {
+            '>'
+        } else if stack.is_applied(patchname) {
+            '+'
+        } else if stack.is_unapplied(patchname) {
+            '-'
+        } else {
+            '!'
+        };
+        contents.push(marker);
+        contents.push(' ');
+        contents.push_str(patchname.as_ref());
+        contents.push('\n');
+    }
+    std::fs::write(path, contents).with_context(|| format!("writing `{}`", path.to_string_lossy()))
+}
@@ -2,23 +2,24 @@
 
 //! `stg show` implementation.
 
-use std::path::PathBuf;
+use std::{ffi::OsString, io::Write, path::PathBuf};
 
-use anyhow::Result;
-use clap::{Arg, ArgMatches};
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgGroup, ArgMatches};
 
 use crate::{
     argset,
-    branchloc::BranchLocator,
     ext::RepositoryExtended,
-    patch::{RangeConstraint, RangeRevisionSpec},
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
-    stupid::Stupid,
+    patch::{PatchLocator, PatchName, RangeConstraint, RangeRevisionSpec, StGitBoundaryRevisions},
+    pathspec,
+    stack::{history, InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::{DiffFormat, Stupid},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "show",
     category: super::CommandCategory::PatchInspection,
+    read_only: true,
     make,
     run,
 };
@@ -30,14 +31,50 @@ fn make() -> clap::Command {
             "Show the commit log and diff corresponding to the given patches. \
              The topmost patch is shown by default, or HEAD if no patches are \
              applied.\n\
-             The output is similar to 'git show'.",
+             The output is similar to 'git show'.\n\
+             \n\
+             With '--stat' or '--numstat', a diffstat or line counts are shown under \
+             each patch's header instead of its diff. With '--combined-stat', a \
+             single cumulative diffstat is shown for the whole patch or range \
+             instead, computed from the parent of the range's first patch to its \
+             last patch. Paths given after '--' limit both diffs and diffstats, and \
+             may use pathspec magic such as ':(exclude)'.\n\
+             \n\
+             The number of context lines of the diffs is set with '--unified'. Unlike \
+             with other commands, there is no short '-U' form since that selects the \
+             unapplied patches.\n\
+             \n\
+             With '--history', the evolution of a single patch is shown instead. \
+             The stack log is walked to find each distinct commit the patch had \
+             over time, following any renames of the patch. The differences between \
+             consecutive versions of the patch are shown using 'git range-diff', or \
+             the versions may be listed with '--list'.\n\
+             \n\
+             With '--message-only', only the commit message of each patch is shown, \
+             and with '--raw', the raw commit object, i.e. its headers and message. \
+             No diffs are generated in either mode. The messages are separated by a \
+             line holding the '--separator' string, an empty line by default, or are \
+             each terminated by NUL with '-z'.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg show --stat p1..p3",
+                "Show the messages and diffstats of `p1` through `p3`",
+            ),
+            (
+                "stg show ':/fix typo'",
+                "Show the patch whose message contains `fix typo`",
+            ),
+        ]))
         .override_usage(super::make_usage(
             "stg show",
             &[
                 "[OPTIONS] [patch-or-rev]... [-- <path>...]",
                 "[OPTIONS] [--patch <patch-or-rev>]... [-- <path>...]",
                 "[OPTIONS] [-A] [-U] [-H] [-- <path>...]",
+                "[OPTIONS] --combined-stat [patch-or-rev] [-- <path>...]",
+                "[OPTIONS] --history <patch> [--list]",
+                "[OPTIONS] (--message-only | --raw) [-z | --separator <sep>] [patch-or-rev]...",
             ],
         ))
         .arg(
@@ -75,7 +112,29 @@ fn make() -> clap::Command {
                 .allow_hyphen_values(true)
                 .conflicts_with("patchranges-all"),
         )
+        .args(argset::stdin_patches_args("patchranges-all").map(|arg| {
+            if arg.get_id() == "stdin-nul" {
+                // `-z` applies to the output of `--message-only` and `--raw` too.
+                Arg::new("stdin-nul")
+                    .short('z')
+                    .help(
+                        "Patches read with --stdin and messages shown with \
+                             --message-only or --raw are NUL-terminated",
+                    )
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("nul-terminated-group")
+            } else {
+                arg.conflicts_with_all(["patchranges", "applied", "unapplied", "hidden"])
+            }
+        }))
+        .group(ArgGroup::new("message-mode-group").args(["message-only", "raw"]))
+        .group(
+            ArgGroup::new("nul-terminated-group")
+                .args(["stdin", "message-only", "raw"])
+                .multiple(true),
+        )
         .arg(argset::branch_arg())
+        .arg(argset::remote_stack_arg().conflicts_with("branch"))
         .arg(
             Arg::new("stat")
                 .long("stat")
@@ -83,7 +142,87 @@ fn make() -> clap::Command {
                 .help("Show a diffstat summary instead of the full diff")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::numstat_arg())
+        .arg(
+            Arg::new("combined-stat")
+                .long("combined-stat")
+                .help("Show a single diffstat for the whole patch range")
+                .long_help(
+                    "Show a single, cumulative diffstat for the given patch or range \
+                     instead of a diffstat per patch. The diffstat is taken between the \
+                     parent of the range's first patch and its last patch. With \
+                     '--numstat', cumulative line counts are shown instead.",
+                )
+                .conflicts_with_all(["stat", "applied", "unapplied", "hidden"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::unified_arg().short(None))
         .arg(argset::diff_opts_arg())
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .visible_alias("full-history")
+                .help("Show how a patch evolved over the stack log")
+                .value_name("patch")
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "patchranges",
+                    "pathspecs",
+                    "stat",
+                    "numstat",
+                    "combined-stat",
+                    "applied",
+                    "unapplied",
+                    "hidden",
+                ]),
+        )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .help("List the versions of the patch from --history")
+                .requires("history")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("message-only")
+                .long("message-only")
+                .help("Show only the commit message of each patch")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "pathspecs",
+                    "stat",
+                    "numstat",
+                    "combined-stat",
+                    "unified",
+                    "git-diff-opt",
+                    "history",
+                ]),
+        )
+        .arg(
+            Arg::new("raw")
+                .long("raw")
+                .help("Show the raw commit object of each patch")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "message-only",
+                    "pathspecs",
+                    "stat",
+                    "numstat",
+                    "combined-stat",
+                    "unified",
+                    "git-diff-opt",
+                    "history",
+                ]),
+        )
+        .arg(
+            Arg::new("separator")
+                .long("separator")
+                .help("Separate messages with a line holding <sep>")
+                .value_name("sep")
+                .conflicts_with("stdin-nul")
+                .requires("message-mode-group"),
+        )
         .next_help_heading("Selection Options")
         .arg(
             Arg::new("applied")
@@ -108,17 +247,40 @@ fn make() -> clap::Command {
         )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
-    let opt_branch = matches.get_one::<BranchLocator>("branch");
-    let stack =
-        Stack::from_branch_locator(&repo, opt_branch, InitializationPolicy::AllowUninitialized)?;
+    let stack = argset::stack_from_args(&repo, matches, InitializationPolicy::AllowUninitialized)?;
+
+    if let Some(patch_loc) = matches.get_one::<PatchLocator>("history") {
+        return show_history(&stack, &patch_loc.resolve_name(&stack)?, matches);
+    }
 
-    let stat_flag = matches.get_flag("stat");
     let applied_flag = matches.get_flag("applied");
     let unapplied_flag = matches.get_flag("unapplied");
     let hidden_flag = matches.get_flag("hidden");
 
+    let pathspecs = matches
+        .get_many::<PathBuf>("pathspecs")
+        .map(|pathspecs| pathspec::normalize(&repo, pathspecs))
+        .transpose()?;
+
+    let range_specs = if let Some(range_specs) =
+        argset::get_patch_ranges::<RangeRevisionSpec>(matches, "patchranges-all")?
+    {
+        if range_specs.is_empty() {
+            return Ok(());
+        }
+        Some(range_specs)
+    } else {
+        matches
+            .get_many::<RangeRevisionSpec>("patchranges")
+            .map(|specs| specs.cloned().collect::<Vec<_>>())
+    };
+
+    if matches.get_flag("combined-stat") {
+        return show_combined_stat(&stack, matches, range_specs.as_deref(), pathspecs);
+    }
+
     let mut oids: Vec<gix::ObjectId> = Vec::new();
 
     if applied_flag {
@@ -136,10 +298,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
             oids.push(stack.get_patch(patchname).commit.id);
         }
     }
-    if let Some(range_specs) = matches
-        .get_many::<RangeRevisionSpec>("patchranges-all")
-        .or_else(|| matches.get_many::<RangeRevisionSpec>("patchranges"))
-    {
+    if let Some(range_specs) = range_specs.as_ref() {
         for spec in range_specs {
             crate::patch::revspec::resolve(
                 &repo,
@@ -154,11 +313,172 @@ fn run(matches: &ArgMatches) -> Result<()> {
         oids.push(stack.get_branch_head().id);
     }
 
+    if matches.get_flag("message-only") || matches.get_flag("raw") {
+        return show_messages(&repo, &oids, matches);
+    }
+
+    // Merge commits are shown without diffs, so only diffs against a single parent
+    // need their blobs.
+    let mut tree_pairs = Vec::with_capacity(oids.len());
+    for oid in &oids {
+        let commit = repo.find_commit(*oid)?;
+        let mut parent_ids = commit.parent_ids();
+        if let (Some(parent_id), None) = (parent_ids.next(), parent_ids.next()) {
+            let parent_tree_id = parent_id.object()?.peel_to_commit()?.tree_id()?;
+            tree_pairs.push((parent_tree_id.detach(), commit.tree_id()?.detach()));
+        }
+    }
+    repo.prefetch_blobs(&tree_pairs, &[])?;
+
     repo.stupid().show(
         oids,
-        matches.get_many::<PathBuf>("pathspecs"),
-        stat_flag,
+        pathspecs,
+        argset::get_diff_format(matches),
         crate::color::use_color(matches),
-        argset::get_diff_opts(matches, &repo.config_snapshot(), false, false),
+        argset::get_diff_opts(matches, &stack.config(), false, false),
     )
 }
+
+/// Show the commit messages, or with `--raw` the raw commit objects, of the given
+/// commits without generating any diffs.
+fn show_messages(
+    repo: &gix::Repository,
+    oids: &[gix::ObjectId],
+    matches: &ArgMatches,
+) -> Result<()> {
+    let raw_flag = matches.get_flag("raw");
+    let nul_terminated = matches.get_flag("stdin-nul");
+    let separator = matches
+        .get_one::<String>("separator")
+        .map_or("", String::as_str);
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for (i, oid) in oids.iter().enumerate() {
+        let commit = repo.find_commit(*oid)?;
+        let content = if raw_flag {
+            commit.data.as_slice()
+        } else {
+            commit.message_raw()?.as_ref()
+        };
+        if nul_terminated {
+            stdout.write_all(content)?;
+            stdout.write_all(b"\0")?;
+        } else {
+            if i > 0 {
+                writeln!(stdout, "{separator}")?;
+            }
+            stdout.write_all(content)?;
+            if !content.ends_with(b"\n") {
+                stdout.write_all(b"\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Show a single diffstat spanning the boundaries of the given patch or range.
+fn show_combined_stat(
+    stack: &Stack,
+    matches: &ArgMatches,
+    range_specs: Option<&[RangeRevisionSpec]>,
+    pathspecs: Option<Vec<OsString>>,
+) -> Result<()> {
+    let repo = stack.repo;
+    let mut range_specs = range_specs.into_iter().flatten();
+    let range_spec = range_specs.next();
+    if range_specs.next().is_some() {
+        return Err(anyhow!(
+            "`--combined-stat` requires a single patch or range"
+        ));
+    }
+
+    let (begin_commit, include_begin, end_commit) = if let Some(range_spec) = range_spec {
+        match range_spec.resolve_revisions(repo, Some(stack), true)? {
+            StGitBoundaryRevisions::Single(rev) => (rev.commit.clone(), true, rev.commit),
+            StGitBoundaryRevisions::Bounds((rev0, rev1)) => {
+                // A range beginning at a commit other than a patch, e.g. the stack
+                // base when no patches are applied, only includes what follows it.
+                let include_begin = rev0.patchname.is_some();
+                (rev0.commit, include_begin, rev1.commit)
+            }
+        }
+    } else {
+        let head = stack.get_branch_head().clone();
+        (head.clone(), true, head)
+    };
+
+    let begin_tree_id = if include_begin {
+        if let Some(parent_id) = begin_commit.parent_ids().next() {
+            parent_id.object()?.peel_to_commit()?.tree_id()?.detach()
+        } else {
+            gix::ObjectId::empty_tree(repo.object_hash())
+        }
+    } else {
+        begin_commit.tree_id()?.detach()
+    };
+    let end_tree_id = end_commit.tree_id()?.detach();
+    repo.prefetch_blobs(&[(begin_tree_id, end_tree_id)], &[])?;
+
+    let format = if matches.get_flag("numstat") {
+        DiffFormat::NumStat
+    } else {
+        DiffFormat::Stat
+    };
+    repo.stupid().diff(
+        &format!("{begin_tree_id}..{end_tree_id}"),
+        pathspecs,
+        format,
+        crate::color::use_color(matches),
+        argset::get_diff_opts(matches, &stack.config(), false, false),
+    )
+}
+
+fn show_history(stack: &Stack, patchname: &PatchName, matches: &ArgMatches) -> Result<()> {
+    let versions = history::patch_versions(stack, patchname)?;
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    if matches.get_flag("list") {
+        let num_width = versions.len().to_string().len();
+        for (i, version) in versions.iter().enumerate() {
+            writeln!(
+                stdout,
+                "{:>num_width$} {} {} {}",
+                i + 1,
+                version.commit.id().shorten_or_id(),
+                version.patchname,
+                version.entry.summary(),
+            )?;
+        }
+        return Ok(());
+    }
+
+    if versions.len() < 2 {
+        return Err(anyhow!(
+            "patch `{patchname}` has only one version in the stack log"
+        ));
+    }
+
+    let repo = stack.repo;
+    let stupid = repo.stupid();
+    let use_color = crate::color::use_color(matches);
+    let diff_opts = argset::get_diff_opts(matches, &stack.config(), false, false);
+    for (i, pair) in versions.windows(2).enumerate() {
+        let [old, new] = pair else {
+            unreachable!("windows are pairs")
+        };
+        if i > 0 {
+            writeln!(stdout)?;
+        }
+        writeln!(
+            stdout,
+            "Version {} -> {}: {}",
+            i + 1,
+            i + 2,
+            new.entry.summary()
+        )?;
+        stdout.flush()?;
+        stupid.range_diff_commits(old.commit.id, new.commit.id, use_color, &diff_opts)?;
+    }
+    Ok(())
+}
@@ -7,16 +7,17 @@ use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::RepositoryExtended,
+    output::Output,
     patch::{patchrange, LocationConstraint, PatchLocator, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{message, InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "sink",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -30,19 +31,32 @@ fn make() -> clap::Command {
              If no patch is specified on the command line, the current (topmost) patch \
              is sunk. By default, patches are sunk to the bottom of the stack, but the \
              '--above' or '--below' (alias '--to') options may be used to place them \
-             above or below any applied patch.\n\
+             above or below any other patch.\n\
              \n\
              Internally, sinking involves popping all patches to the bottom (or to the \
              target patch if '--above' or '--below' is used), then pushing the patches \
              to sink, and then, unless '--nopush' is specified, pushing back any other \
              formerly applied patches.\n\
              \n\
+             The target patch may also be unapplied, in which case the unapplied \
+             patches up to the target are pushed along with the patches to sink.\n\
+             \n\
              Sinking may be useful, for example, to group stable patches at the bottom \
              of the stack where they less likely to be impacted by the push of another \
              patch, and from where they can be more easily committed or pushed to \
              another repository.\n\
              ",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg sink",
+                "Sink the topmost patch to the bottom of the stack",
+            ),
+            (
+                "stg sink --to p2 p5 p6",
+                "Move `p5` and `p6` just below `p2`",
+            ),
+        ]))
         .arg(
             Arg::new("patchranges")
                 .help("Patches to sink")
@@ -51,6 +65,7 @@ fn make() -> clap::Command {
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange)),
         )
+        .args(argset::stdin_patches_args("patchranges"))
         .arg(
             Arg::new("nopush")
                 .long("nopush")
@@ -94,114 +109,166 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PatchLocator))
                 .conflicts_with("target-below"),
         )
+        .arg(argset::dry_run_arg())
         .arg(argset::keep_arg())
+        .arg(argset::force_overwrite_untracked_arg())
         .arg(argset::committer_date_is_author_date_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
 
     let nopush_flag = matches.get_flag("nopush");
-    let keep_flag = matches.contains_id("keep");
+    let keep_flag = matches.get_flag("keep");
 
     repo.check_repository_state()?;
     let statuses = stupid.statuses(None)?;
+    stack.check_conflicted_patch()?;
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
     if !keep_flag {
         statuses.check_index_and_worktree_clean()?;
     }
 
-    let is_above = matches.contains_id("target-above");
+    let opt_target = resolve_target(&stack, matches, "sink")?;
+
+    let patches: Vec<PatchName> = if let Some(range_specs) =
+        argset::get_patch_ranges::<PatchRange>(matches, "patchranges")?
+    {
+        if range_specs.is_empty() {
+            return Ok(());
+        }
+        patchrange::resolve_names(&stack, range_specs.iter(), RangeConstraint::All)?
+    } else if let Some(patchname) = stack.applied().last() {
+        vec![patchname.clone()]
+    } else {
+        return Err(super::Error::NoAppliedPatches.into());
+    };
+
+    let log_msg = message::with_patches("sink", &patches);
+    let (applied, unapplied) = place_patches(
+        &stack,
+        &patches,
+        opt_target.as_ref(),
+        Placement::Apply {
+            push_back: !nopush_flag,
+        },
+        "sink",
+    )?;
 
-    let opt_target: Option<PatchName> = matches
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .dry_run(matches.get_flag("dry-run"))
+        .print_delta(true)
+        .use_index_and_worktree(true)
+        .keep_local_changes(keep_flag)
+        .force_overwrite_untracked(matches.get_flag("force-overwrite-untracked"))
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .with_output(Output::new(matches))
+        .transact(|trans| trans.reorder_patches(Some(&applied), Some(&unapplied), None))
+        .execute(&log_msg)?;
+
+    Ok(())
+}
+
+/// Target of a relative placement with `--above` or `--below`.
+pub(super) struct Target {
+    patchname: PatchName,
+    above: bool,
+}
+
+/// How moved patches are placed relative to the applied patches.
+pub(super) enum Placement {
+    /// The moved patches become applied. Unapplied patches below the insertion point
+    /// are pushed first. With `push_back`, formerly applied patches above the
+    /// insertion point are pushed back after the moved patches.
+    Apply { push_back: bool },
+
+    /// The moved patches become unapplied. Applied patches above the insertion point
+    /// are popped.
+    NoApply,
+}
+
+/// Resolve the `--above` or `--below` target patch, if any.
+///
+/// The target may be any visible patch, applied or unapplied.
+pub(super) fn resolve_target(
+    stack: &Stack,
+    matches: &ArgMatches,
+    verb: &str,
+) -> Result<Option<Target>> {
+    let above = matches.contains_id("target-above");
+    matches
         .get_one::<PatchLocator>("target-above")
         .or_else(|| matches.get_one::<PatchLocator>("target-below"))
-        .map(|loc| loc.resolve_name(&stack))
+        .map(|loc| loc.resolve_name_allowed(stack, LocationConstraint::Visible))
         .transpose()
         .map_err(|e| anyhow!("target: {e}"))?
-        .map(|name| name.constrain(&stack, LocationConstraint::Applied))
+        .map(|name| name.constrain(stack, LocationConstraint::Visible))
         .transpose()
         .map_err(|e| match e {
             crate::patch::name::Error::PatchNotAllowed { patchname, .. } => {
                 anyhow!(
-                    "cannot sink {} `{patchname}` since it is not applied",
-                    if is_above { "above" } else { "below" }
+                    "cannot {verb} {} `{patchname}` since it is hidden",
+                    if above { "above" } else { "below" }
                 )
             }
             _ => e.into(),
-        })?;
-
-    let patches: Vec<PatchName> =
-        if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
-            patchrange::resolve_names(&stack, range_specs, RangeConstraint::All)?
-        } else if let Some(patchname) = stack.applied().last() {
-            vec![patchname.clone()]
-        } else {
-            return Err(super::Error::NoAppliedPatches.into());
-        };
-
-    if let Some(target_patch) = &opt_target {
-        if patches.contains(target_patch) {
-            return Err(anyhow!(
-                "target patch `{target_patch}` may not also be a patch to sink",
-            ));
-        }
-    }
+        })
+        .map(|opt_name| opt_name.map(|patchname| Target { patchname, above }))
+}
 
-    let mut remaining_unapplied: Vec<PatchName> = stack
-        .unapplied()
-        .iter()
+/// Determine the new applied and unapplied patches after moving `patches`.
+///
+/// The moved patches keep their given order and are placed immediately above or below
+/// the `target` patch, or at the bottom of the stack without a target.
+pub(super) fn place_patches(
+    stack: &Stack,
+    patches: &[PatchName],
+    target: Option<&Target>,
+    placement: Placement,
+    verb: &str,
+) -> Result<(Vec<PatchName>, Vec<PatchName>)> {
+    let mut remaining: Vec<PatchName> = stack
+        .applied_and_unapplied()
         .filter(|pn| !patches.contains(pn))
         .cloned()
         .collect();
-
-    let mut remaining_applied: Vec<PatchName> = stack
+    let remaining_applied_len = stack
         .applied()
         .iter()
         .filter(|pn| !patches.contains(pn))
-        .cloned()
-        .collect();
+        .count();
 
-    let target_pos = if let Some(target_patch) = &opt_target {
-        remaining_applied
+    let insert_pos = if let Some(Target { patchname, above }) = target {
+        if patches.contains(patchname) {
+            return Err(anyhow!(
+                "target patch `{patchname}` may not also be a patch to {verb}"
+            ));
+        }
+        remaining
             .iter()
-            .position(|pn| pn == target_patch)
-            .expect("already validated that target is applied")
-            + if is_above { 1 } else { 0 }
+            .position(|pn| pn == patchname)
+            .expect("target is a visible patch that is not moved")
+            + usize::from(*above)
     } else {
         0
     };
 
-    let mut patches = patches;
-
-    let (applied, unapplied) = if nopush_flag {
-        let mut applied: Vec<PatchName> = Vec::with_capacity(target_pos + patches.len());
-        applied.extend(remaining_applied.drain(0..target_pos));
-        applied.append(&mut patches);
-        let mut unapplied: Vec<PatchName> =
-            Vec::with_capacity(remaining_applied.len() + remaining_unapplied.len());
-        unapplied.append(&mut remaining_applied);
-        unapplied.append(&mut remaining_unapplied);
-        (applied, unapplied)
-    } else {
-        let mut applied: Vec<PatchName> =
-            Vec::with_capacity(remaining_applied.len() + patches.len());
-        applied.extend(remaining_applied.drain(0..target_pos));
-        applied.append(&mut patches);
-        applied.append(&mut remaining_applied);
-        (applied, remaining_unapplied)
+    let applied_len = match placement {
+        Placement::Apply { push_back: true } => {
+            remaining_applied_len.max(insert_pos) + patches.len()
+        }
+        Placement::Apply { push_back: false } => insert_pos + patches.len(),
+        Placement::NoApply => remaining_applied_len.min(insert_pos),
     };
 
-    stack
-        .setup_transaction()
-        .use_index_and_worktree(true)
-        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
-        .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| trans.reorder_patches(Some(&applied), Some(&unapplied), None))
-        .execute("sink")?;
-
-    Ok(())
+    let tail = remaining.split_off(insert_pos);
+    remaining.extend(patches.iter().cloned());
+    remaining.extend(tail);
+    let unapplied = remaining.split_off(applied_len);
+    Ok((remaining, unapplied))
 }
@@ -9,8 +9,9 @@ use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
-    color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
+    output::Output,
+    pathspec,
     stack::{InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
@@ -18,6 +19,7 @@ use crate::{
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "spill",
     category: super::CommandCategory::PatchManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -62,7 +64,7 @@ fn make() -> clap::Command {
         )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
@@ -84,6 +86,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let parent_commit_ref = parent.decode()?;
 
     let tree_id = if let Some(pathspecs) = matches.get_many::<PathBuf>("pathspecs") {
+        let pathspecs = pathspec::normalize(&repo, pathspecs)?;
         stupid.with_temp_index(|stupid_temp| {
             stupid_temp.read_tree(patch_commit_ref.tree())?;
             stupid_temp.apply_pathlimited_treediff_to_index(
@@ -126,8 +129,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(false)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| trans.update_patch(&patchname, commit_id))
         .execute(&reflog_msg)?;
 
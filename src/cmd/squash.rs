@@ -2,23 +2,30 @@
 
 //! `stg squash` implementation.
 
-use std::fmt::Write;
+use std::{fmt::Write, str::FromStr};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
-    color::get_color_stdout,
+    argset,
     ext::{CommitExtended, RepositoryExtended, SignatureExtended},
-    patch::{patchedit, patchrange, PatchName, PatchRange, RangeConstraint},
+    output::Output,
+    patch::{patchedit, patchrange, PatchLocator, PatchName, PatchRange, RangeConstraint},
     print_info_message,
-    stack::{InitializationPolicy, Stack, StackStateAccess, StackTransaction},
+    stack::{
+        message, InitializationPolicy, Stack, StackAccess, StackStateAccess, StackTransaction,
+    },
     stupid::Stupid,
 };
 
+/// Trailer recording the target patch of a fixup patch created by `stg new --fixup`.
+pub(super) const FIXUP_TRAILER: &str = "Stg-Fixup";
+
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "squash",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -47,8 +54,38 @@ fn make() -> clap::Command {
             \n\
             Conflicts can occur whenever a patch is pushed; this is, in steps (2) and \
             (5). If conflicts occur, the squash command will halt such that the \
-            conflicts may be resolved manually.",
+            conflicts may be resolved manually.\n\
+            \n\
+            With '--into', the changes from the given patches are instead folded into \
+            an existing patch, which keeps its name, author, and message unless \
+            changed with the patch edit options (e.g. '--edit'). The folded patch \
+            keeps its place in the stack and the other given patches are deleted. \
+            The given patches must be adjacent to the target patch unless \
+            '--reorder' is used, in which case they are pushed on top of the target \
+            patch before being folded. Nothing is deleted if conflicts occur while \
+            folding.\n\
+            \n\
+            With '--fixups', each fixup patch created with 'stg new --fixup' is \
+            folded into the patch named by its \"Stg-Fixup\" trailer, as with \
+            '--into' and '--reorder'. The target patches keep their names and \
+            messages and no editor is launched. All visible patches are searched for \
+            fixup patches unless patches are given. Multiple fixups of the same \
+            target are folded in stack order.\n\
+            \n\
+            Hidden patches, including ones within a given patch range, are only \
+            squashed with '--unhide'. They are then unhidden as part of squashing, \
+            as if by 'stg unhide', and squashed like any other unapplied patch.",
         )
+        .after_long_help(super::make_examples(&[
+            (
+                "stg squash --name=combined p1..p3",
+                "Squash `p1` through `p3` into a new patch named `combined`",
+            ),
+            (
+                "stg squash --into=p1 --reorder 'fixup-*'",
+                "Fold the patches named `fixup-*` into `p1`, wherever they are",
+            ),
+        ]))
         .arg(
             Arg::new("patchranges")
                 .help("Patches to squash")
@@ -56,7 +93,7 @@ fn make() -> clap::Command {
                 .num_args(1..)
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange))
-                .required(true),
+                .required_unless_present("fixups"),
         )
         .arg(
             Arg::new("name")
@@ -66,12 +103,44 @@ fn make() -> clap::Command {
                 .value_name("name")
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchName)),
-        );
+        )
+        .arg(
+            Arg::new("into")
+                .long("into")
+                .help("Fold the patches into the existing <target> patch")
+                .value_name("target")
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all(["name", "save-template"]),
+        )
+        .arg(
+            Arg::new("reorder")
+                .long("reorder")
+                .help("Reorder the patches to be adjacent to the --into target")
+                .action(clap::ArgAction::SetTrue)
+                .requires("into"),
+        )
+        .arg(
+            Arg::new("fixups")
+                .long("fixups")
+                .help("Fold fixup patches into their target patches")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["name", "into", "edit", "message", "file", "save-template"]),
+        )
+        .arg(
+            Arg::new("unhide")
+                .long("unhide")
+                .help("Unhide and squash any given hidden patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::dry_run_arg().conflicts_with_all(["edit", "save-template"]))
+        .args(argset::sign_args());
     patchedit::add_args(command, true, true)
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
-    let repo = gix::Repository::open()?;
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
+    let mut repo = gix::Repository::open()?;
+    argset::set_commit_signing(&mut repo, matches)?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
 
@@ -80,13 +149,23 @@ fn run(matches: &ArgMatches) -> Result<()> {
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
 
-    let squash_patchnames: Vec<PatchName> = patchrange::resolve_names(
-        &stack,
-        matches
-            .get_many::<PatchRange>("patchranges")
-            .expect("clap ensures two or more patches"),
-        RangeConstraint::All,
-    )?;
+    if matches.get_flag("fixups") {
+        return run_fixups(matches, command_line, stack);
+    }
+
+    let range_specs = matches
+        .get_many::<PatchRange>("patchranges")
+        .expect("clap ensures two or more patches");
+    patchrange::check_message_duplicates(&stack, range_specs.clone())?;
+    let squash_patchnames: Vec<PatchName> =
+        patchrange::resolve_names(&stack, range_specs, RangeConstraint::All)?;
+
+    if let Some(target_loc) = matches.get_one::<PatchLocator>("into") {
+        let target = target_loc.resolve_name(&stack)?;
+        return run_fold_into(matches, command_line, stack, target, squash_patchnames);
+    }
+
+    let to_unhide = check_hidden(matches, &stack, &squash_patchnames)?;
 
     let patchname: Option<PatchName> = matches.get_one::<PatchName>("name").cloned();
 
@@ -103,6 +182,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     }
 
     if matches.contains_id("save-template") {
+        let (message, comment) = prepare_message(&stack, &squash_patchnames)?;
         let first_patch_commit = stack.get_patch_commit(&squash_patchnames[0]);
         if let patchedit::EditOutcome::TemplateSaved(template_path) =
             patchedit::EditBuilder::default()
@@ -111,7 +191,8 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 .allow_template_save(true)
                 .template_patchname(patchname.as_ref())
                 .default_author(repo.get_author()?.override_author(matches))
-                .default_message(prepare_message(&stack, &squash_patchnames)?)
+                .default_message(message)
+                .comment_string(comment)
                 .edit(&stack, &repo, matches)?
         {
             let template_path = template_path.to_string_lossy();
@@ -131,11 +212,17 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
         stack
             .setup_transaction()
+            .command_line(command_line)
+            .dry_run(matches.get_flag("dry-run"))
+            .print_delta(true)
             .allow_conflicts(true)
             .use_index_and_worktree(true)
             .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
-            .with_output_stream(get_color_stdout(matches))
+            .with_output(Output::new(matches))
             .transact(|trans| {
+                if !to_unhide.is_empty() {
+                    trans.unhide_patches(&to_unhide)?;
+                }
                 squash(
                     trans,
                     matches,
@@ -145,30 +232,237 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 )?;
                 Ok(())
             })
-            .execute("squash")?;
+            .execute(&message::with_patches("squash", &squash_patchnames))?;
         Ok(())
     }
 }
 
+fn run_fold_into(
+    matches: &ArgMatches,
+    command_line: &[String],
+    stack: Stack,
+    target: PatchName,
+    donor_patchnames: Vec<PatchName>,
+) -> Result<()> {
+    if stack.is_hidden(&target) && !matches.get_flag("unhide") {
+        return Err(anyhow!(
+            "cannot fold into hidden patch `{target}`; use `--unhide` to unhide it"
+        ));
+    }
+
+    let mut patchnames = donor_patchnames;
+    patchnames.retain(|pn| pn != &target);
+    patchnames.insert(0, target);
+    let to_unhide = check_hidden(matches, &stack, &patchnames)?;
+    if patchnames.len() < 2 {
+        return Err(anyhow!(
+            "need at least one patch to fold into `{}`",
+            patchnames[0]
+        ));
+    }
+
+    let reorder = matches.get_flag("reorder");
+    if !reorder {
+        let mut indices: Vec<usize> = patchnames.iter().map(|pn| stack.index_of(pn)).collect();
+        indices.sort_unstable();
+        if indices.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+            return Err(anyhow!(
+                "patches are not adjacent to `{}`; use --reorder to fold them anyway",
+                patchnames[0]
+            ));
+        }
+    }
+
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .dry_run(matches.get_flag("dry-run"))
+        .print_delta(true)
+        .allow_conflicts(true)
+        .use_index_and_worktree(true)
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            if !to_unhide.is_empty() {
+                trans.unhide_patches(&to_unhide)?;
+            }
+            fold_into(trans, matches, &patchnames, reorder)
+        })
+        .execute(&message::with_patches("squash", &patchnames))?;
+    Ok(())
+}
+
+/// Fold each fixup patch into the patch named by its [`FIXUP_TRAILER`].
+fn run_fixups(matches: &ArgMatches, command_line: &[String], stack: Stack) -> Result<()> {
+    let candidates: Vec<PatchName> =
+        if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
+            patchrange::resolve_names(&stack, range_specs, RangeConstraint::Visible)?
+        } else {
+            stack
+                .applied()
+                .iter()
+                .chain(stack.unapplied())
+                .cloned()
+                .collect()
+        };
+
+    let mut fixups: Vec<(PatchName, PatchName)> = Vec::new();
+    for patchname in stack.all_patches().filter(|pn| candidates.contains(pn)) {
+        if let Some(target) = fixup_target(stack.get_patch_commit(patchname))
+            .with_context(|| format!("patch `{}`", patchname.quoted()))?
+        {
+            fixups.push((patchname.clone(), target));
+        }
+    }
+
+    if fixups.is_empty() {
+        print_info_message(matches, "no fixup patches to squash");
+        return Ok(());
+    }
+
+    // A fixup of a fixup is folded into the final target.
+    let mut resolved: Vec<(PatchName, PatchName)> = Vec::with_capacity(fixups.len());
+    for (fixup, target) in &fixups {
+        let mut target = target;
+        let mut hops = 0;
+        while let Some((_, next_target)) = fixups.iter().find(|(pn, _)| pn == target) {
+            hops += 1;
+            if hops > fixups.len() {
+                return Err(anyhow!(
+                    "fixup patch `{}` is part of a cycle of fixups",
+                    fixup.quoted()
+                ));
+            }
+            target = next_target;
+        }
+        if !stack.has_patch(target) {
+            return Err(anyhow!(
+                "fixup patch `{}` targets `{}`, which does not exist",
+                fixup.quoted(),
+                target.quoted(),
+            ));
+        } else if stack.is_hidden(target) && !matches.get_flag("unhide") {
+            return Err(anyhow!(
+                "fixup patch `{}` targets hidden patch `{}`; use `--unhide` to unhide it",
+                fixup.quoted(),
+                target.quoted(),
+            ));
+        }
+        resolved.push((fixup.clone(), target.clone()));
+    }
+
+    let fixup_patchnames: Vec<PatchName> = resolved.iter().map(|(pn, _)| pn.clone()).collect();
+    let mut to_unhide: Vec<PatchName> = Vec::new();
+    for pn in stack.hidden() {
+        if resolved.iter().any(|(_, target)| target == pn) {
+            to_unhide.push(pn.clone());
+        }
+    }
+
+    stack
+        .setup_transaction()
+        .command_line(command_line)
+        .dry_run(matches.get_flag("dry-run"))
+        .print_delta(true)
+        .allow_conflicts(true)
+        .use_index_and_worktree(true)
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .with_output(Output::new(matches))
+        .transact(|trans| {
+            if !to_unhide.is_empty() {
+                trans.unhide_patches(&to_unhide)?;
+            }
+            for (fixup, target) in &resolved {
+                fold_into(trans, matches, &[target.clone(), fixup.clone()], true).with_context(
+                    || {
+                        format!(
+                            "squashing fixup `{}` into `{}`",
+                            fixup.quoted(),
+                            target.quoted()
+                        )
+                    },
+                )?;
+            }
+            Ok(())
+        })
+        .execute(&message::with_patches("squash", &fixup_patchnames))?;
+    Ok(())
+}
+
+/// Get the hidden patches among the patches to be squashed, in stack order.
+///
+/// Squashing hidden patches requires `--unhide`, in which case the returned patches
+/// are to be unhidden before squashing.
+fn check_hidden(
+    matches: &ArgMatches,
+    stack: &Stack,
+    patchnames: &[PatchName],
+) -> Result<Vec<PatchName>> {
+    let hidden: Vec<PatchName> = stack
+        .hidden()
+        .iter()
+        .filter(|pn| patchnames.contains(pn))
+        .cloned()
+        .collect();
+    if hidden.is_empty() || matches.get_flag("unhide") {
+        return Ok(hidden);
+    }
+    let names = hidden
+        .iter()
+        .map(|pn| format!("`{pn}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(if hidden.len() == 1 {
+        anyhow!("cannot squash hidden patch {names}; use `--unhide` to include it")
+    } else {
+        anyhow!("cannot squash hidden patches {names}; use `--unhide` to include them")
+    })
+}
+
+/// Get the target patch name from a fixup patch's [`FIXUP_TRAILER`], if any.
+///
+/// Only the trailers in the last paragraph of the message are considered.
+fn fixup_target(commit: &gix::Commit<'_>) -> Result<Option<PatchName>> {
+    let message = commit.message_ex();
+    let message = message.decode()?;
+    let last_paragraph = message.trim_end().rsplit("\n\n").next().unwrap_or_default();
+    let prefix = format!("{FIXUP_TRAILER}:");
+    last_paragraph
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix(prefix.as_str()))
+        .map(|value| PatchName::from_str(value.trim()))
+        .transpose()
+        .with_context(|| format!("invalid `{FIXUP_TRAILER}` trailer"))
+}
+
+/// Prepare the default message of the squashed patch from the squashed patches'
+/// messages.
+///
+/// Each message is introduced by a comment line. The comment string used is returned
+/// along with the message, since with `core.commentChar=auto` it depends on the
+/// messages.
 fn prepare_message<'repo>(
-    stack_state: &impl StackStateAccess<'repo>,
+    stack_state: &impl StackAccess<'repo>,
     patchnames: &[PatchName],
-) -> Result<String> {
-    let mut squash_message = String::new();
-    for (i, patchname) in patchnames.iter().enumerate() {
+) -> Result<(String, String)> {
+    let mut messages = Vec::with_capacity(patchnames.len());
+    for patchname in patchnames {
         let commit = stack_state.get_patch_commit(patchname);
-        let message = commit.message_ex();
-        let message = message.decode()?;
-        let message = message.trim_end();
+        messages.push(commit.message_ex().decode()?.trim_end().to_string());
+    }
+    let comment = patchedit::comment_string(&stack_state.config(), Some(&messages.join("\n")))?;
+    let mut squash_message = String::new();
+    for (i, (patchname, message)) in patchnames.iter().zip(messages).enumerate() {
         let patch_number = i + 1;
         write!(
             squash_message,
-            "# Commit message from patch #{patch_number}: {patchname}\n\
+            "{comment} Commit message from patch #{patch_number}: {patchname}\n\
              {message}\n\
              \n"
         )?;
     }
-    Ok(squash_message)
+    Ok((squash_message, comment))
 }
 
 pub(super) fn squash(
@@ -224,43 +518,25 @@ fn try_squash(
     let base_commit = trans.get_patch_commit(&patchnames[0]);
     let base_author = base_commit.author()?;
     let mut use_base_author = true;
-    let base_commit_ref = base_commit.decode()?;
-    if let Some(tree_id) = repo.stupid().with_temp_index(|stupid_temp| {
-        stupid_temp.read_tree(base_commit_ref.tree())?;
-        for commit in patchnames[1..].iter().map(|pn| trans.get_patch_commit(pn)) {
-            let commit_ref = commit.decode()?;
-            let author = commit.author()?;
-            if author != base_author {
-                use_base_author = false;
-            }
-            let parent = commit.get_parent_commit()?;
-            let parent_commit_ref = parent.decode()?;
-            if parent_commit_ref.tree() != commit_ref.tree()
-                && !stupid_temp.apply_treediff_to_index(
-                    parent_commit_ref.tree(),
-                    commit_ref.tree(),
-                    true,
-                )?
-            {
-                return Ok(None);
-            }
+    for commit in patchnames[1..].iter().map(|pn| trans.get_patch_commit(pn)) {
+        if commit.author()? != base_author {
+            use_base_author = false;
         }
-
-        let tree_id = stupid_temp.write_tree()?;
-        Ok(Some(tree_id))
-    })? {
+    }
+    if let Some(tree_id) = combine_trees(trans, patchnames)? {
+        let (message, comment) = prepare_message(trans, patchnames)?;
+        let parent_id = base_commit
+            .decode()?
+            .parents()
+            .next()
+            .expect("first patch has a parent");
         if let patchedit::EditOutcome::Edited {
             new_patchname,
             new_commit_id,
         } = patchedit::EditBuilder::default()
-            .override_parent_id(
-                base_commit_ref
-                    .parents()
-                    .next()
-                    .expect("first patch has a parent"),
-            )
+            .override_parent_id(parent_id)
             .override_tree_id(tree_id)
-            .allow_implicit_edit(true)
+            .allow_implicit_edit(!trans.is_dry_run())
             .allow_diff_edit(false)
             .allow_template_save(false)
             .template_patchname(patchname)
@@ -273,7 +549,8 @@ fn try_squash(
                 }
                 .override_author(matches),
             )
-            .default_message(prepare_message(trans, patchnames)?)
+            .default_message(message)
+            .comment_string(comment)
             .edit(trans, repo, matches)?
         {
             Ok(Some((
@@ -287,3 +564,131 @@ fn try_squash(
         Ok(None)
     }
 }
+
+/// Fold the changes from the other patches into the first patch.
+///
+/// The first patch's name, author, and message are kept unless changed with the patch
+/// edit options. The folded patch takes the place of the first patch in the stack
+/// and the other patches are deleted.
+fn fold_into(
+    trans: &mut StackTransaction,
+    matches: &ArgMatches,
+    patchnames: &[PatchName],
+    reorder: bool,
+) -> Result<()> {
+    let target = &patchnames[0];
+    let original_order: Vec<PatchName> = trans.all_patches().cloned().collect();
+    let position = |pn: &PatchName| original_order.iter().position(|opn| opn == pn);
+    let target_was_applied = trans.is_applied(target);
+
+    // Changes from patches below the target are already part of the target's tree, so
+    // they have to be pushed onto the target's parent to be recombined.
+    let has_donor_below = patchnames[1..]
+        .iter()
+        .any(|pn| position(pn) < position(target));
+
+    let simple_fold = if reorder || has_donor_below {
+        None
+    } else {
+        try_fold(trans, matches, patchnames)?
+    };
+
+    let (new_patchname, commit_id, to_push) = if let Some((new_patchname, commit_id)) = simple_fold
+    {
+        let to_push = trans.delete_patches(|pn| patchnames.contains(pn))?;
+        (new_patchname, commit_id, to_push)
+    } else {
+        let to_push = trans.pop_patches(|pn| patchnames.contains(pn))?;
+        trans.push_patches(patchnames, false)?;
+        if let Some((new_patchname, commit_id)) = try_fold(trans, matches, patchnames)? {
+            let popped_extra = trans.delete_patches(|pn| patchnames.contains(pn))?;
+            assert!(popped_extra.is_empty());
+            (new_patchname, commit_id, to_push)
+        } else {
+            return Err(super::Error::CausedConflicts(format!(
+                "conflicts while folding into `{target}`"
+            ))
+            .into());
+        }
+    };
+
+    let insert_pos = trans
+        .unapplied()
+        .iter()
+        .take_while(|pn| position(pn) < position(target))
+        .count();
+    trans.new_unapplied(&new_patchname, commit_id, insert_pos)?;
+
+    let mut to_push = to_push;
+    if target_was_applied {
+        to_push.insert(insert_pos, new_patchname);
+    }
+    trans.push_patches(&to_push, false)?;
+
+    Ok(())
+}
+
+fn try_fold(
+    trans: &StackTransaction,
+    matches: &ArgMatches,
+    patchnames: &[PatchName],
+) -> Result<Option<(PatchName, gix::ObjectId)>> {
+    let repo = trans.repo();
+    let target = &patchnames[0];
+    let target_commit = trans.get_patch_commit(target);
+    if let Some(tree_id) = combine_trees(trans, patchnames)? {
+        if let patchedit::EditOutcome::Edited {
+            new_patchname,
+            new_commit_id,
+        } = patchedit::EditBuilder::default()
+            .original_patchname(Some(target))
+            .existing_patch_commit(target_commit)
+            .override_tree_id(tree_id)
+            .allow_implicit_edit(false)
+            .allow_diff_edit(false)
+            .allow_template_save(false)
+            .extra_allowed_patchnames(patchnames)
+            .edit(trans, repo, matches)?
+        {
+            Ok(Some((
+                new_patchname.unwrap_or_else(|| target.clone()),
+                new_commit_id.unwrap_or(target_commit.id),
+            )))
+        } else {
+            panic!("expected edit to commit, not save template")
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Apply the changes from the other patches onto the first patch's tree.
+///
+/// Returns `None` if any of the changes do not apply cleanly.
+fn combine_trees(
+    trans: &StackTransaction,
+    patchnames: &[PatchName],
+) -> Result<Option<gix::ObjectId>> {
+    let base_commit = trans.get_patch_commit(&patchnames[0]);
+    let base_commit_ref = base_commit.decode()?;
+    trans.repo().stupid().with_temp_index(|stupid_temp| {
+        stupid_temp.read_tree(base_commit_ref.tree())?;
+        for commit in patchnames[1..].iter().map(|pn| trans.get_patch_commit(pn)) {
+            let commit_ref = commit.decode()?;
+            let parent = commit.get_parent_commit()?;
+            let parent_commit_ref = parent.decode()?;
+            if parent_commit_ref.tree() != commit_ref.tree()
+                && !stupid_temp.apply_treediff_to_index(
+                    parent_commit_ref.tree(),
+                    commit_ref.tree(),
+                    true,
+                )?
+            {
+                return Ok(None);
+            }
+        }
+
+        let tree_id = stupid_temp.write_tree()?;
+        Ok(Some(tree_id))
+    })
+}
@@ -15,16 +15,19 @@ use clap::{Arg, ArgGroup};
 use crate::{
     argset,
     branchloc::BranchLocator,
-    color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess, StackTransaction},
-    stupid::Stupid,
+    output::Output,
+    patch::{PatchName, PatchRange, RangeConstraint},
+    stack::{
+        message, InitializationPolicy, Stack, StackAccess, StackStateAccess, StackTransaction,
+    },
+    stupid::{ApplyOptions, MergeOptions, Stupid},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "sync",
     category: super::CommandCategory::PatchManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -63,6 +66,7 @@ fn make() -> clap::Command {
                 .args(["patchranges", "all"])
                 .required(false),
         )
+        .arg(argset::include_gaps_arg())
         .arg(
             Arg::new("ref-branch")
                 .long("ref-branch")
@@ -88,7 +92,7 @@ fn make() -> clap::Command {
         .arg(argset::committer_date_is_author_date_arg())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
@@ -99,10 +103,11 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     let patches: Vec<PatchName> = if matches.get_flag("all") {
         stack.applied().to_vec()
     } else if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
-        patchrange::resolve_names_contiguous(
+        argset::resolve_names_contiguous(
             &stack,
             range_specs,
             RangeConstraint::VisibleWithAppliedBoundary,
+            matches,
         )?
     } else if let Some(patchname) = stack.applied().last() {
         vec![patchname.clone()]
@@ -179,8 +184,9 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         if !to_pop.is_empty() {
             stack = stack
                 .setup_transaction()
+                .command_line(command_line)
                 .use_index_and_worktree(true)
-                .with_output_stream(get_color_stdout(matches))
+                .with_output(Output::new(matches))
                 .transact(|trans| {
                     let popped_extra = trans.pop_patches(|pn| to_pop.contains(pn))?;
                     assert!(popped_extra.is_empty());
@@ -197,8 +203,9 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(true)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| {
             for pn in pushed.iter().chain(popped.iter()) {
                 let parent_id = trans.top().id;
@@ -243,7 +250,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             }
             Ok(())
         })
-        .execute("sync")?;
+        .execute(&message::with_patches("sync", &patches))?;
 
     Ok(())
 }
@@ -265,10 +272,18 @@ fn branch_merge_patch(
         commit_ref.tree(),
     )?;
     stupid.update_index_refresh()?;
+    let ancestor_label = format!("ancestor {}", ref_parent.id.to_hex_with_len(7));
+    let their_label = format!("patch {patchname} from {}", ref_stack.get_branch_name());
+    let mut merge_options = MergeOptions::default();
+    merge_options
+        .ancestor_label(&ancestor_label)
+        .our_label("current top")
+        .their_label(&their_label);
     if !stupid.merge_recursive(
         ref_parent_ref.tree(),
         commit_ref.tree(),
         ref_commit_ref.tree(),
+        &merge_options,
     )? {
         return Err(super::Error::CausedConflicts(format!(
             "merge conflicts syncing `{patchname}`"
@@ -306,7 +321,7 @@ fn series_merge_patch(
     stupid.update_index_refresh()?;
     stupid.read_tree_checkout(trans_head_tree_id, parent_commit_ref.tree())?;
     stupid
-        .apply_to_worktree_and_index(diff.as_ref(), false, false, None, None, None)
+        .apply_to_worktree_and_index(diff.as_ref(), &ApplyOptions::default())
         .with_context(|| format!("applying {patchname} from series"))?;
     stupid.update_index_refresh()?;
 
@@ -327,7 +342,19 @@ fn series_merge_patch(
     let tree_id = stupid.write_tree()?;
 
     stupid.read_tree_checkout(tree_id, trans_head_tree_id)?;
-    if !stupid.merge_recursive(parent_commit_ref.tree(), trans_head_tree_id, tree_id)? {
+    let ancestor_label = format!("ancestor {}", parent.id.to_hex_with_len(7));
+    let their_label = format!("patch {patchname} from series");
+    let mut merge_options = MergeOptions::default();
+    merge_options
+        .ancestor_label(&ancestor_label)
+        .our_label("current top")
+        .their_label(&their_label);
+    if !stupid.merge_recursive(
+        parent_commit_ref.tree(),
+        trans_head_tree_id,
+        tree_id,
+        &merge_options,
+    )? {
         return Err(super::Error::CausedConflicts(format!(
             "merge conflicts syncing `{patchname}`"
         ))
@@ -12,11 +12,13 @@ use crate::{
     branchloc::BranchLocator,
     ext::RepositoryExtended,
     stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "top",
     category: super::CommandCategory::StackInspection,
+    read_only: true,
     make,
     run,
 };
@@ -31,9 +33,22 @@ fn make() -> clap::Command {
              message will be printed if no patches are applied.",
         )
         .arg(argset::branch_arg())
+        .arg(
+            clap::Arg::new("dirty-exit-code")
+                .long("dirty-exit-code")
+                .help("Exit with status 1 when the top patch has unrefreshed changes")
+                .long_help(
+                    "Exit with status 1 after printing the name of the top patch when \
+                     the index or work tree differ from the patch, the same as \
+                     'stg series --dirty' marks the patch. Untracked files are not \
+                     considered. This is meant for use in shell prompts.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("branch"),
+        )
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, _command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
         &repo,
@@ -42,7 +57,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     )?;
 
     if let Some(patchname) = stack.applied().last() {
-        let mut stdout = crate::color::get_color_stdout(matches);
+        let mut stdout = crate::output::Output::new(matches).data();
         let mut color_spec = termcolor::ColorSpec::new();
         color_spec.set_bold(true);
         stdout.set_color(&color_spec)?;
@@ -50,8 +65,24 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         color_spec.clear();
         stdout.set_color(&color_spec)?;
         writeln!(stdout)?;
+        if matches.get_flag("dirty-exit-code") && has_unrefreshed_changes(&repo)? {
+            stdout.flush()?;
+            crate::exit(1);
+        }
         Ok(())
     } else {
         Err(super::Error::NoAppliedPatches.into())
     }
 }
+
+/// Determine whether the index or work tree have changes not refreshed into the
+/// topmost patch.
+///
+/// A single status check is made, ignoring untracked files. Without a work tree, there
+/// are no unrefreshed changes.
+pub(super) fn has_unrefreshed_changes(repo: &gix::Repository) -> Result<bool> {
+    if repo.work_dir().is_none() {
+        return Ok(false);
+    }
+    Ok(!repo.stupid().statuses(None)?.is_empty())
+}
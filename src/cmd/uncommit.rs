@@ -9,16 +9,20 @@ use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
-    color::get_color_stdout,
-    ext::{CommitExtended, RepositoryExtended},
-    patch::PatchName,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    ext::{CommitExtended, RepositoryExtended, ShallowHistoryError},
+    output::Output,
+    patch::{
+        name::{NameCollision, NameTemplate},
+        PatchName, SingleRevisionSpec,
+    },
+    stack::{message, InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "uncommit",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -44,17 +48,31 @@ fn make() -> clap::Command {
              \n\
              The -t/--to option specifies that all commits up to and including the \
              given commit should be uncommitted. The -x/--exclusive option may be \
-             used to exclude the \"to\" commit.\n\
+             used to exclude the \"to\" commit; -i/--inclusive restores the default. \
+             The given commit must be an ancestor of the stack base and is found by \
+             following the first parent of each commit from the stack base. \
+             Uncommitting fails if a merge commit is encountered along the way.\n\
+             \n\
+             When a generated patch name collides with a hidden patch, the \
+             \"stgit.name.collision\" variable or the '--name-collision' option \
+             determine whether the name is suffixed, the uncommit fails, or the hidden \
+             patch is renamed aside to make way for the new patch.\n\
              \n\
              Only commits with exactly one parent can be uncommitted; in other words, \
-             merge commits may not be uncommitted.",
+             merge commits may not be uncommitted.\n\
+             \n\
+             Since the commits themselves become the patch commits, their notes and \
+             committer identity are retained as is. Notes of the default notes ref \
+             follow the patch commits when the patches are later refreshed or \
+             pushed; notes of other refs do so when they are included in the \
+             notes.rewriteRef configuration.",
         )
         .override_usage(super::make_usage(
             "stg uncommit",
             &[
                 "<patchname-1> [<patchname-2> ...]",
                 "-n number [<patchname-prefix>]",
-                "-t <committish> [-x]",
+                "-t <committish> [-x | -i]",
             ],
         ))
         .arg(
@@ -86,44 +104,76 @@ fn make() -> clap::Command {
                 .long("exclusive")
                 .short('x')
                 .help("Exclude the commit specified by the '--to' option")
-                .action(clap::ArgAction::SetTrue),
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("inclusive"),
+        )
+        .arg(
+            Arg::new("inclusive")
+                .long("inclusive")
+                .short('i')
+                .help("Include the commit specified by the '--to' option (default)")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("exclusive"),
         )
+        .arg(argset::name_collision_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AutoInitialize)?;
     stack.check_head_top_mismatch()?;
-    let config = repo.config_snapshot();
+    let config = stack.config();
 
     let opt_number = matches.get_one::<usize>("number").copied();
 
-    let patchname_len_limit = PatchName::get_length_limit(&config);
+    let mut name_template = NameTemplate::new(&stack)?;
+    let name_collision = argset::resolve_name_collision(&config, matches)?;
 
-    let (commits, patchnames) = if let Some(committish) = matches.get_one::<String>("to") {
-        let mut target_commit = repo
-            .rev_parse_single(committish.as_str())
-            .map_err(|_| anyhow!("invalid committish `{committish}`"))?
-            .object()?
+    let (commits, (patchnames, renames)) = if let Some(committish) = matches.get_one::<String>("to")
+    {
+        let target_commit = SingleRevisionSpec::GitLike(committish.clone())
+            .resolve_object(&repo, &stack)
+            .map_err(|e| {
+                if e.is::<ShallowHistoryError>() {
+                    e
+                } else {
+                    anyhow!("invalid committish `{committish}`")
+                }
+            })?
             .peel_tags_to_end()?
             .try_into_commit()
             .map_err(|_| anyhow!("target `{committish}` does not resolve to a commit"))?;
 
         let bases = repo
             .stupid()
-            .merge_bases(target_commit.id, stack.base().id)?;
+            .merge_bases(target_commit.id, stack.base().id)
+            .map_err(|e| {
+                if repo.is_shallow() {
+                    ShallowHistoryError(format!("uncommitting to `{committish}`")).into()
+                } else {
+                    e
+                }
+            })?;
 
-        let exclusive = if bases.contains(&target_commit.id) {
-            matches.get_flag("exclusive")
-        } else {
-            target_commit = repo.find_commit(bases[0])?;
-            true
-        };
+        if !bases.contains(&target_commit.id) {
+            return Err(anyhow!(
+                "`{committish}` ({}) is not an ancestor of the stack base ({})",
+                target_commit.id().shorten_or_id(),
+                stack.base().id().shorten_or_id(),
+            ));
+        }
+        let exclusive = matches.get_flag("exclusive");
 
         if exclusive {
-            println!("Uncommitting to {} (exclusive)", target_commit.id());
+            crate::print_info_message(
+                matches,
+                &format!("uncommitting to `{}` (exclusive)", target_commit.id()),
+            );
         } else {
-            println!("Uncommitting to {}", target_commit.id());
+            crate::print_info_message(
+                matches,
+                &format!("uncommitting to `{}`", target_commit.id()),
+            );
         }
 
         let mut commits: Vec<Rc<gix::Commit<'_>>> = Vec::new();
@@ -132,18 +182,20 @@ fn run(matches: &ArgMatches) -> Result<()> {
         loop {
             if next_commit.id == target_commit.id {
                 if !exclusive {
+                    check_not_merge(&next_commit, committish)?;
                     check_commit(next_commit.as_ref())?;
                     commits.push(next_commit);
                 }
                 break;
             } else {
+                check_not_merge(&next_commit, committish)?;
                 check_commit(&next_commit)?;
                 let parent = next_commit.get_parent_commit()?;
                 commits.push(std::mem::replace(&mut next_commit, Rc::new(parent)));
             }
         }
 
-        let patchnames = make_patchnames(&stack, &commits, patchname_len_limit);
+        let patchnames = make_patchnames(&stack, &commits, &mut name_template, name_collision)?;
         (commits, patchnames)
     } else {
         let mut commits = Vec::new();
@@ -168,9 +220,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     patchnames.push(PatchName::try_from(format!("{prefix}{i}"))?);
                 }
                 check_patchnames(&stack, &patchnames)?;
-                patchnames
+                (patchnames, Vec::new())
             } else {
-                make_patchnames(&stack, &commits, patchname_len_limit)
+                make_patchnames(&stack, &commits, &mut name_template, name_collision)?
             }
         } else if let Some(user_patchnames) = matches.get_many::<PatchName>("patchname") {
             let patchnames = user_patchnames.cloned().collect::<Vec<_>>();
@@ -180,11 +232,11 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 let parent = next_commit.get_parent_commit()?;
                 commits.push(std::mem::replace(&mut next_commit, Rc::new(parent)));
             }
-            patchnames
+            (patchnames, Vec::new())
         } else {
             check_commit(&next_commit)?;
             commits.push(next_commit);
-            make_patchnames(&stack, &commits, patchname_len_limit)
+            make_patchnames(&stack, &commits, &mut name_template, name_collision)?
         };
         (commits, patchnames)
     };
@@ -193,11 +245,16 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack
         .setup_transaction()
+        .command_line(command_line)
+        .print_delta(true)
         .use_index_and_worktree(false)
         .allow_conflicts(true)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .set_head(false)
         .transact(|trans| {
+            for (hidden, aside) in &renames {
+                trans.rename_patch(hidden, aside)?;
+            }
             trans.uncommit_patches(
                 patchnames
                     .iter()
@@ -205,41 +262,84 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     .rev(),
             )
         })
-        .execute("uncommit")?;
+        .execute(&message::with_patches(
+            "uncommit",
+            &patchnames.iter().rev().collect::<Vec<_>>(),
+        ))?;
 
     Ok(())
 }
 
 fn check_commit(commit: &gix::Commit) -> Result<()> {
-    if commit.parent_ids().count() == 1 {
-        Ok(())
-    } else {
-        Err(anyhow!(
+    let mut parent_ids = commit.parent_ids();
+    match (parent_ids.next(), parent_ids.next()) {
+        (Some(parent_id), None) => {
+            // The parent becomes the new stack base and thus must be present.
+            if commit.repo.is_shallow() && !commit.repo.has_object(parent_id) {
+                Err(ShallowHistoryError(format!("uncommitting `{}`", commit.id())).into())
+            } else {
+                Ok(())
+            }
+        }
+        _ => Err(anyhow!(
             "cannot uncommit `{}` which does not have exactly one parent",
             commit.id()
+        )),
+    }
+}
+
+/// Check that a commit encountered while uncommitting to `committish` is not a merge.
+fn check_not_merge(commit: &gix::Commit, committish: &str) -> Result<()> {
+    if commit.parent_ids().count() > 1 {
+        Err(anyhow!(
+            "cannot uncommit to `{committish}`: encountered merge commit {} \"{}\"",
+            commit.id().shorten_or_id(),
+            commit
+                .message_ex()
+                .decode()?
+                .lines()
+                .next()
+                .unwrap_or_default(),
         ))
+    } else {
+        Ok(())
     }
 }
 
+/// A hidden patch name paired with the name the hidden patch is renamed aside to.
+type HiddenRename = (PatchName, PatchName);
+
+/// Derive unique patch names for the commits.
+///
+/// Also returns the hidden patches whose names are taken per `name_collision`, paired
+/// with the names that they must be renamed to.
 fn make_patchnames(
     stack: &Stack,
     commits: &[Rc<gix::Commit<'_>>],
-    patchname_len_limit: Option<usize>,
-) -> Vec<PatchName> {
+    name_template: &mut NameTemplate,
+    name_collision: NameCollision,
+) -> Result<(Vec<PatchName>, Vec<HiddenRename>)> {
     let mut patchnames = Vec::with_capacity(commits.len());
-    let mut taken_names: Vec<_> = stack.all_patches().cloned().collect();
+    let mut renames = Vec::new();
+    let mut taken_names: Vec<PatchName> = Vec::new();
     for commit in commits.iter().rev() {
-        let patchname = PatchName::make(
-            &commit.message_ex().decode().unwrap_or_default(),
-            true,
-            patchname_len_limit,
-        )
-        .uniquify(&[], &taken_names);
+        let (patchname, replaced) = name_template
+            .make(&commit.message_ex().decode().unwrap_or_default(), true)?
+            .uniquify_in_stack(
+                stack,
+                &taken_names,
+                name_collision,
+                name_template.len_limit(),
+            )?;
         taken_names.push(patchname.clone());
+        if let Some((hidden, aside)) = replaced {
+            taken_names.push(aside.clone());
+            renames.push((hidden, aside));
+        }
         patchnames.push(patchname);
     }
     patchnames.reverse();
-    patchnames
+    Ok((patchnames, renames))
 }
 
 fn check_patchnames(stack: &Stack, patchnames: &[PatchName]) -> Result<()> {
@@ -2,21 +2,23 @@
 
 //! `stg undo` implementation.
 
-use std::rc::Rc;
+use std::{io::Write, rc::Rc};
 
 use anyhow::{anyhow, Result};
 use bstr::{BStr, ByteSlice};
 use clap::Arg;
 
 use crate::{
-    color::get_color_stdout,
+    argset,
     ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack, StackAccess, StackState},
+    output::Output,
+    stack::{history, InitializationPolicy, Stack, StackAccess, StackState},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "undo",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -26,7 +28,38 @@ fn make() -> clap::Command {
         .about("Undo the last command")
         .long_about(
             "Reset the patch stack to the state before the last operation. \
-             Consecutive undos will go back to yet older stack states.",
+             Consecutive undos will go back to yet older stack states.\n\
+             \n\
+             With '--to-date', the stack is reset to the newest stack state recorded \
+             at or before the given date, i.e. to the state the stack was in at \
+             that time. Dates may be given in any of the formats accepted by git, \
+             including relative dates such as '2 days ago' or 'yesterday'. Such an \
+             undo may itself be undone with 'stg undo'.\n\
+             \n\
+             Given an <operation>, the stack is reset to the state before the stack \
+             log entry whose description, as shown by 'stg log', begins with \
+             <operation>. For example, 'stg undo squash' undoes the squash and all \
+             operations since. The operation must be matched by a single entry; use \
+             '--last' to undo back to the most recent of several matching entries, \
+             e.g. 'stg undo --last squash'. Operations that were already undone are \
+             not matched. When no entry or more than one entry matches, recent \
+             candidates are listed.\n\
+             \n\
+             With '--dry-run', the operations that would be undone are listed without \
+             changing the stack.",
+        )
+        .arg(
+            Arg::new("operation")
+                .help("Undo back to before <operation>")
+                .value_name("operation")
+                .conflicts_with_all(["number", "to-date"]),
+        )
+        .arg(
+            Arg::new("last")
+                .long("last")
+                .help("Undo back to before the most recent matching operation")
+                .requires("operation")
+                .action(clap::ArgAction::SetTrue),
         )
         .arg(
             Arg::new("number")
@@ -46,31 +79,212 @@ fn make() -> clap::Command {
                         })
                 }),
         )
+        .arg(
+            Arg::new("to-date")
+                .long("to-date")
+                .help("Undo to the stack state as of <date>")
+                .value_name("date")
+                .value_parser(argset::parse_time)
+                .value_hint(clap::ValueHint::Other)
+                .conflicts_with("number"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("List the operations that would be undone")
+                .long_help(
+                    "List the operations that would be undone and report the effect \
+                     on the stack, without changing anything.",
+                )
+                .conflicts_with("to-date")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("hard")
                 .long("hard")
                 .help("Discard changes in the index and worktree")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(argset::no_backup_arg())
 }
 
-fn run(matches: &clap::ArgMatches) -> Result<()> {
+fn run(matches: &clap::ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
-    let undo_steps = matches.get_one::<isize>("number").copied().unwrap_or(1);
+    let branch_name = stack.get_branch_name().to_string();
+    let to_date = matches.get_one::<gix::date::Time>("to-date").copied();
+    let dry_run = matches.get_flag("dry-run");
+    let undo_steps = if let Some(selector) = matches.get_one::<String>("operation") {
+        find_operation(&stack, selector, matches.get_flag("last"))?
+    } else {
+        matches.get_one::<isize>("number").copied().unwrap_or(1)
+    };
+
+    if dry_run {
+        print_undone_operations(&stack, undo_steps, Output::new(matches))?;
+    } else {
+        argset::backup_discarded_changes(&repo, &branch_name, matches)?;
+    }
+
+    let message = if to_date.is_some() {
+        let date_str = matches
+            .get_raw("to-date")
+            .and_then(|mut values| values.next())
+            .expect("to-date is present")
+            .to_string_lossy()
+            .to_string();
+        format!("undo to {date_str}")
+    } else {
+        undo_redo_message("undo", undo_steps)
+    };
 
     stack
         .setup_transaction()
+        .command_line(command_line)
         .use_index_and_worktree(true)
         .allow_bad_head(true)
         .discard_changes(matches.get_flag("hard"))
-        .with_output_stream(get_color_stdout(matches))
+        .dry_run(dry_run)
+        .with_output(Output::new(matches))
         .transact(|trans| {
-            let undo_state = find_undo_state(trans.stack(), undo_steps)?;
+            let undo_state = if let Some(date) = to_date {
+                find_dated_state(trans.stack(), date)?
+            } else {
+                find_undo_state(trans.stack(), undo_steps)?
+            };
             trans.reset_to_state(undo_state)
         })
-        .execute(&format!("undo {undo_steps}"))?;
+        .execute(&message)?;
 
+    if !dry_run {
+        argset::reapply_autostash(&repo, &branch_name, matches)?;
+    }
+    Ok(())
+}
+
+/// Find the newest stack state recorded at or before the given date.
+///
+/// Stack log entries are ordered by their committer dates, newest first.
+fn find_dated_state<'repo>(
+    stack: &Stack<'repo>,
+    date: gix::date::Time,
+) -> Result<StackState<'repo>> {
+    let mut state_commit = Rc::new(
+        stack
+            .repo
+            .find_reference(stack.get_stack_refname())?
+            .peel_to_commit()?,
+    );
+    loop {
+        let state = StackState::from_commit(stack.repo, &state_commit)?;
+        if state_commit.committer()?.time.seconds <= date.seconds {
+            break Ok(state);
+        }
+        state_commit = state.prev.ok_or_else(|| {
+            anyhow!(
+                "no stack log entry as old as {}",
+                date.format(gix::date::time::format::ISO8601)
+            )
+        })?;
+    }
+}
+
+/// Walk the operations that consecutive undos would undo, newest first.
+///
+/// Each operation's stack log entry is paired with the number of undo steps that undo
+/// it. As with [`find_undo_state()`], the entries of undos and of the operations they
+/// undid are skipped.
+fn undoable_operations<'repo>(
+    stack: &Stack<'repo>,
+) -> Result<impl Iterator<Item = Result<(isize, history::StackLogEntry<'repo>)>>> {
+    let mut skip = 0;
+    let mut steps = 0;
+    Ok(history::stack_log(stack)?.filter_map(move |entry| {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e)),
+        };
+        if let Some(URState::Undo(n)) = parse_undo_redo_message(entry.commit.message_raw_sloppy()) {
+            skip += n;
+            None
+        } else if skip > 0 {
+            skip -= 1;
+            None
+        } else {
+            steps += 1;
+            Some(Ok((steps, entry)))
+        }
+    }))
+}
+
+/// Whether a stack log entry summary describes the selected operation.
+///
+/// The selector must match the beginning of the summary up to a word boundary, such
+/// that e.g. "squash" matches "squash p1 p2" and "new" matches "new: p3", but "new"
+/// does not match "newest".
+fn is_operation_match(summary: &str, selector: &str) -> bool {
+    summary.strip_prefix(selector).is_some_and(|rest| {
+        rest.is_empty() || rest.starts_with(|c: char| c.is_ascii_whitespace() || c == ':')
+    })
+}
+
+/// Maximum number of candidate operations listed when an operation cannot be found.
+const MAX_CANDIDATES: usize = 10;
+
+/// Find the number of undo steps needed to undo the selected operation.
+///
+/// Unless `last` is true, the operation must be matched by exactly one undoable
+/// stack log entry.
+fn find_operation(stack: &Stack, selector: &str, last: bool) -> Result<isize> {
+    let selector = selector.trim();
+    let mut recent = Vec::new();
+    let mut matching = Vec::new();
+    for operation in undoable_operations(stack)? {
+        let (steps, entry) = operation?;
+        let summary = entry.summary();
+        let candidate = format!("  {steps}: {summary}");
+        if is_operation_match(&summary, selector) {
+            if last {
+                return Ok(steps);
+            }
+            matching.push((steps, candidate));
+        } else if recent.len() < MAX_CANDIDATES {
+            recent.push(candidate);
+        }
+    }
+
+    match matching.as_slice() {
+        [] => Err(anyhow!(
+            "no operation matching `{selector}` found in the stack log\n\
+             recent operations are:\n\
+             {}",
+            recent.join("\n")
+        )),
+        [(steps, _)] => Ok(*steps),
+        [..] => {
+            let candidates: Vec<&str> = matching
+                .iter()
+                .take(MAX_CANDIDATES)
+                .map(|(_, candidate)| candidate.as_str())
+                .collect();
+            Err(anyhow!(
+                "multiple operations match `{selector}`:\n\
+                 {}\n\
+                 use `--last`, `--number`, or a more specific operation",
+                candidates.join("\n")
+            ))
+        }
+    }
+}
+
+/// Print the operations that undoing the given number of steps would undo.
+fn print_undone_operations(stack: &Stack, undo_steps: isize, output: Output) -> Result<()> {
+    let mut stream = output.commentary();
+    writeln!(stream, "{}:", undo_redo_message("Would undo", undo_steps))?;
+    for operation in undoable_operations(stack)?.take(undo_steps as usize) {
+        let (steps, entry) = operation?;
+        writeln!(stream, "  {steps}: {}", entry.summary())?;
+    }
     Ok(())
 }
 
@@ -112,6 +326,18 @@ pub(super) fn find_undo_state<'repo>(
     }
 }
 
+/// Make the stack log message for an undo or redo of the given number of operations.
+///
+/// The message is parsed by [`parse_undo_redo_message()`] when later undoing or
+/// redoing, so the leading `<undo|redo> <n>` must be maintained.
+pub(super) fn undo_redo_message(operation: &str, steps: isize) -> String {
+    if steps == 1 {
+        format!("{operation} {steps} operation")
+    } else {
+        format!("{operation} {steps} operations")
+    }
+}
+
 #[derive(Debug)]
 enum URState {
     Undo(isize),
@@ -120,7 +346,10 @@ enum URState {
 
 fn parse_undo_redo_message(msg: &BStr) -> Option<URState> {
     let fields: Vec<_> = msg.fields_with(|c| c.is_ascii_whitespace()).collect();
-    if fields.len() == 2 {
+    // Older versions of StGit recorded just `<undo|redo> <n>`.
+    if fields.len() == 2
+        || (fields.len() == 3 && (fields[2] == b"operation" || fields[2] == b"operations"))
+    {
         if let Some(n) = fields[1]
             .to_str()
             .ok()
@@ -8,15 +8,16 @@ use clap::{Arg, ArgMatches};
 use crate::{
     argset,
     branchloc::BranchLocator,
-    color::get_color_stdout,
     ext::RepositoryExtended,
+    output::Output,
     patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack},
+    stack::{message, InitializationPolicy, Stack},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "unhide",
     category: super::CommandCategory::StackManipulation,
+    read_only: false,
     make,
     run,
 };
@@ -29,18 +30,23 @@ fn make() -> clap::Command {
              \n\
              Hidden patches are no longer shown in the plain 'series' output.",
         )
+        .after_long_help(super::make_examples(&[(
+            "stg unhide '%{old}'",
+            "Unhide the hidden patches labeled `old`",
+        )]))
         .arg(
             Arg::new("patchranges-hidden")
                 .help("Patches to unhide")
                 .value_name("patch")
                 .num_args(1..)
                 .value_parser(clap::value_parser!(PatchRange))
-                .required(true),
+                .required_unless_present("stdin"),
         )
+        .args(argset::stdin_patches_args("patchranges-hidden"))
         .arg(argset::branch_arg())
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, command_line: &[String]) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
         &repo,
@@ -50,29 +56,34 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack.check_head_top_mismatch()?;
 
-    let range_specs = matches
-        .get_many::<PatchRange>("patchranges-hidden")
+    let range_specs = argset::get_patch_ranges::<PatchRange>(matches, "patchranges-hidden")?
         .expect("clap ensures at least one range is provided");
+    if range_specs.is_empty() {
+        return Ok(());
+    }
 
     let patches: Vec<PatchName> =
-        patchrange::resolve_names(&stack, range_specs, RangeConstraint::Hidden).map_err(
-            |e| match e {
-                patchrange::Error::Name(crate::patch::name::Error::PatchNotAllowed {
-                    patchname,
-                    ..
-                }) => {
-                    anyhow!("patch `{patchname}` is not hidden")
-                }
-                _ => e.into(),
+        patchrange::resolve_names(&stack, range_specs.iter(), RangeConstraint::Hidden).map_err(
+            |e| {
+                e.map_each(|e| match e {
+                    patchrange::Error::Name(crate::patch::name::Error::PatchNotAllowed {
+                        patchname,
+                        ..
+                    }) => {
+                        anyhow!("patch `{patchname}` is not hidden")
+                    }
+                    _ => e.into(),
+                })
             },
         )?;
 
     stack
         .setup_transaction()
+        .command_line(command_line)
         .allow_conflicts(true)
-        .with_output_stream(get_color_stdout(matches))
+        .with_output(Output::new(matches))
         .transact(|trans| trans.unhide_patches(&patches))
-        .execute("unhide")?;
+        .execute(&message::with_patches("unhide", &patches))?;
 
     Ok(())
 }
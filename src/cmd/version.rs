@@ -5,11 +5,12 @@
 use anyhow::Result;
 use clap::ArgMatches;
 
-use crate::stupid::StupidContext;
+use crate::stupid::{program::git_program, StupidContext};
 
 pub(crate) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "version",
     category: super::CommandCategory::Administration,
+    read_only: false,
     make,
     run,
 };
@@ -24,9 +25,17 @@ fn make() -> clap::Command {
                 .help("Show abbreviated version information")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            clap::Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Also show the git executable and options in use")
+                .action(clap::ArgAction::Count)
+                .conflicts_with("short"),
+        )
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
+fn run(matches: &ArgMatches, _command_line: &[String]) -> Result<()> {
     let pkg_version = env!("CARGO_PKG_VERSION");
     let hash_suffix = option_env!("STGIT_BUILD_GIT_HASH")
         .and_then(|rev_hash| Some(format!(" ({rev_hash})")))
@@ -44,6 +53,13 @@ fn run(matches: &ArgMatches) -> Result<()> {
              SPDX-License-Identifier: {license_id}",
         );
         println!("{}", StupidContext::default().version()?);
+        if matches.get_count("verbose") > 0 {
+            let program = git_program();
+            println!("git executable: {}", program.path().to_string_lossy());
+            for opt in program.config() {
+                println!("git option: -c {opt}");
+            }
+        }
     }
     Ok(())
 }
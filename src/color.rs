@@ -6,7 +6,6 @@ use std::ffi::OsString;
 
 use clap::{Arg, ArgMatches};
 use is_terminal::IsTerminal;
-use termcolor::StandardStream;
 
 pub(crate) fn get_color_arg() -> Arg {
     Arg::new("color")
@@ -54,24 +53,6 @@ pub(crate) fn termcolor_choice_to_clap(color_choice: termcolor::ColorChoice) ->
     }
 }
 
-/// Get [`termcolor::StandardStream`] for stdout based on `--color` option.
-pub(crate) fn get_color_stdout(matches: &ArgMatches) -> StandardStream {
-    let mut choice = get_color_choice(Some(matches));
-    if choice == termcolor::ColorChoice::Auto && !std::io::stdout().is_terminal() {
-        choice = termcolor::ColorChoice::Never;
-    }
-    StandardStream::stdout(choice)
-}
-
-/// Get [`termcolor::StandardStream`] for stderr based on `--color` option.
-pub(crate) fn get_color_stderr(matches: &ArgMatches) -> StandardStream {
-    let mut choice = get_color_choice(Some(matches));
-    if choice == termcolor::ColorChoice::Auto && !std::io::stderr().is_terminal() {
-        choice = termcolor::ColorChoice::Never;
-    }
-    StandardStream::stderr(choice)
-}
-
 /// Get [`termcolor::ColorChoice`] from argument matches.
 pub(crate) fn get_color_choice(maybe_matches: Option<&ArgMatches>) -> termcolor::ColorChoice {
     str_choice_to_termcolor(
@@ -83,10 +64,16 @@ pub(crate) fn get_color_choice(maybe_matches: Option<&ArgMatches>) -> termcolor:
 }
 
 /// Determine if color should be used based on `--color` and if terminal is a tty.
+///
+/// The result determines whether `git` is asked for colored output, which is always
+/// made of ANSI escape sequences. When coloring automatically, a console that does not
+/// render ANSI escape sequences thus gets no color.
 pub(crate) fn use_color(matches: &ArgMatches) -> bool {
     match crate::color::get_color_choice(Some(matches)) {
         termcolor::ColorChoice::Always | termcolor::ColorChoice::AlwaysAnsi => true,
-        termcolor::ColorChoice::Auto => std::io::stdout().is_terminal(),
+        termcolor::ColorChoice::Auto => {
+            std::io::stdout().is_terminal() && crate::console::supports_ansi()
+        }
         termcolor::ColorChoice::Never => false,
     }
 }
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Runtime setup of the terminal console.
+//!
+//! Windows consoles, like cmd.exe and older versions of PowerShell, do not interpret
+//! ANSI escape sequences unless virtual terminal processing is enabled, and decode
+//! output bytes with the console's code page instead of as UTF-8. Both affect the
+//! colored and non-ASCII output of `git` processes whose output StGit passes through,
+//! e.g. for `stg show`, as well as that of pagers. Output written by StGit itself is
+//! unaffected by the code page since the Rust standard library writes to the console
+//! with its wide character API.
+//!
+//! The console's capabilities are detected when StGit starts. The console's output
+//! code page, which outlives the StGit process, is restored by [`restore()`] when
+//! StGit exits. On other platforms, the terminal is assumed to handle both ANSI escape
+//! sequences and UTF-8.
+
+use std::sync::OnceLock;
+
+static ANSI: OnceLock<bool> = OnceLock::new();
+
+/// The console's output code page before it was changed by [`setup()`].
+#[cfg(windows)]
+static ORIGINAL_OUTPUT_CP: OnceLock<u32> = OnceLock::new();
+
+/// Prepare the console for StGit's output.
+///
+/// This should be called once, early, before any output is written.
+pub(crate) fn setup() {
+    ANSI.get_or_init(setup_console);
+}
+
+/// Test whether ANSI escape sequences written to the console are rendered.
+///
+/// When they are not, colored output from `git` must not be requested when coloring
+/// automatically. StGit's own colored output, written with [`termcolor`], uses the
+/// Windows console API in that case.
+pub(crate) fn supports_ansi() -> bool {
+    *ANSI.get_or_init(setup_console)
+}
+
+#[cfg(windows)]
+fn setup_console() -> bool {
+    use is_terminal::IsTerminal;
+
+    if !(std::io::stdout().is_terminal() || std::io::stderr().is_terminal()) {
+        return true;
+    }
+
+    // Child processes, including `git` and the pager, inherit the console and write
+    // UTF-8 encoded text. Failure leaves the console's code page as it was.
+    const CP_UTF8: u32 = 65001;
+    unsafe {
+        use windows_sys::Win32::System::Console::{GetConsoleOutputCP, SetConsoleOutputCP};
+        let original_cp = GetConsoleOutputCP();
+        if original_cp != 0 && original_cp != CP_UTF8 && SetConsoleOutputCP(CP_UTF8) != 0 {
+            let _ = ORIGINAL_OUTPUT_CP.set(original_cp);
+        }
+    }
+
+    anstyle_query::windows::enable_virtual_terminal_processing().is_ok()
+}
+
+#[cfg(not(windows))]
+fn setup_console() -> bool {
+    true
+}
+
+/// Restore the console's output code page as it was before [`setup()`].
+///
+/// This should be called before the process exits.
+#[cfg(windows)]
+pub(crate) fn restore() {
+    if let Some(original_cp) = ORIGINAL_OUTPUT_CP.get() {
+        unsafe {
+            windows_sys::Win32::System::Console::SetConsoleOutputCP(*original_cp);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn restore() {}
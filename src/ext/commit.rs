@@ -3,8 +3,12 @@
 use anyhow::{anyhow, Result};
 use bstr::{BString, ByteSlice};
 
+use super::ShallowHistoryError;
 use crate::wrap::Message;
 
+/// Extra commit headers, as name and value pairs.
+pub(crate) type ExtraHeaders = Vec<(BString, BString)>;
+
 /// Extension trait for [`gix::Commit`].
 pub(crate) trait CommitExtended<'a> {
     /// Get author signature, strictly.
@@ -21,57 +25,49 @@ pub(crate) trait CommitExtended<'a> {
     /// guaranteed to have valid UTF-8 name and email strings.
     fn author_strict(&self) -> Result<gix::actor::Signature>;
 
+    /// Get committer signature, strictly.
+    ///
+    /// Like [`CommitExtended::author_strict()`], the signature is decoded according to
+    /// the commit's encoding.
+    fn committer_strict(&self) -> Result<gix::actor::Signature>;
+
     /// Get commit message with extended capabilities.
-    fn message_ex(&self) -> Message;
+    fn message_ex(&self) -> Message<'_>;
 
     /// Determine whether the commit has the same tree as its parent.
+    ///
+    /// The tree ids are compared directly, so a commit that only changes a file's mode
+    /// or a submodule's gitlink is not considered to be without change.
     fn is_no_change(&self) -> Result<bool>;
 
     fn get_parent_commit(&self) -> Result<gix::Commit<'a>>;
+
+    /// Get the commit's extra headers that remain valid if the commit is rewritten
+    /// with the given parents.
+    ///
+    /// Returns the headers to carry over to the rewritten commit along with the names
+    /// of the headers that had to be dropped. Signature headers (`gpgsig` and
+    /// `gpgsig-sha256`) cover the entire commit object and are thus invalidated by any
+    /// rewrite. A `mergetag` header remains valid only as long as the tagged commit
+    /// remains a parent. All other headers are carried over as-is.
+    fn rewritable_extra_headers(
+        &self,
+        parent_ids: &[gix::ObjectId],
+    ) -> Result<(ExtraHeaders, Vec<BString>)>;
 }
 
 impl<'a> CommitExtended<'a> for gix::Commit<'a> {
     fn author_strict(&self) -> Result<gix::actor::Signature> {
         let commit_ref = self.decode()?;
-        let sig = commit_ref.author();
-        let encoding = if let Some(encoding_name) = commit_ref.encoding {
-            encoding_rs::Encoding::for_label(encoding_name).ok_or_else(|| {
-                anyhow!(
-                    "unhandled commit encoding `{}` in commit `{}`",
-                    encoding_name.to_str_lossy(),
-                    self.id,
-                )
-            })?
-        } else {
-            encoding_rs::UTF_8
-        };
-
-        if let Some(name) = encoding.decode_without_bom_handling_and_without_replacement(sig.name) {
-            if let Some(email) =
-                encoding.decode_without_bom_handling_and_without_replacement(sig.email)
-            {
-                Ok(gix::actor::Signature {
-                    name: BString::from(name.as_ref()),
-                    email: BString::from(email.as_ref()),
-                    time: sig.time,
-                })
-            } else {
-                Err(anyhow!(
-                    "could not decode author email as `{}` for commit `{}`",
-                    encoding.name(),
-                    self.id,
-                ))
-            }
-        } else {
-            Err(anyhow!(
-                "could not decode author name as `{}` for commit `{}`",
-                encoding.name(),
-                self.id,
-            ))
-        }
+        decode_signature(self.id, &commit_ref, commit_ref.author(), "author")
+    }
+
+    fn committer_strict(&self) -> Result<gix::actor::Signature> {
+        let commit_ref = self.decode()?;
+        decode_signature(self.id, &commit_ref, commit_ref.committer(), "committer")
     }
 
-    fn message_ex(&self) -> Message {
+    fn message_ex(&self) -> Message<'_> {
         let commit_ref = self.decode().expect("commit can be decoded");
         if let Ok(message) = commit_ref.message.to_str() {
             Message::Str(message)
@@ -101,11 +97,82 @@ impl<'a> CommitExtended<'a> for gix::Commit<'a> {
     }
 
     fn get_parent_commit(&self) -> Result<gix::Commit<'a>> {
-        Ok(self
+        let parent_id = self
             .parent_ids()
             .next()
-            .ok_or_else(|| anyhow!("commit `{}` does not have a parent", self.id))?
-            .object()?
-            .try_into_commit()?)
+            .ok_or_else(|| anyhow!("commit `{}` does not have a parent", self.id))?;
+        if self.repo.is_shallow() && !self.repo.has_object(parent_id) {
+            return Err(
+                ShallowHistoryError(format!("finding the parent of commit `{}`", self.id)).into(),
+            );
+        }
+        Ok(parent_id.object()?.try_into_commit()?)
+    }
+
+    fn rewritable_extra_headers(
+        &self,
+        parent_ids: &[gix::ObjectId],
+    ) -> Result<(ExtraHeaders, Vec<BString>)> {
+        let commit_ref = self.decode()?;
+        let mut kept = Vec::new();
+        let mut dropped = Vec::new();
+        for (name, value) in commit_ref.extra_headers {
+            let is_valid = match name.as_bytes() {
+                b"gpgsig" | b"gpgsig-sha256" => false,
+                b"mergetag" => value
+                    .lines()
+                    .next()
+                    .and_then(|line| line.strip_prefix(b"object "))
+                    .and_then(|hex| gix::ObjectId::from_hex(hex).ok())
+                    .is_some_and(|tagged_id| parent_ids.contains(&tagged_id)),
+                _ => true,
+            };
+            if is_valid {
+                kept.push((name.to_owned(), value.into_owned()));
+            } else {
+                dropped.push(name.to_owned());
+            }
+        }
+        Ok((kept, dropped))
+    }
+}
+
+/// Decode a commit's signature according to the commit's encoding.
+fn decode_signature(
+    commit_id: gix::ObjectId,
+    commit_ref: &gix::objs::CommitRef<'_>,
+    sig: gix::actor::SignatureRef<'_>,
+    role: &str,
+) -> Result<gix::actor::Signature> {
+    let encoding = if let Some(encoding_name) = commit_ref.encoding {
+        encoding_rs::Encoding::for_label(encoding_name).ok_or_else(|| {
+            anyhow!(
+                "unhandled commit encoding `{}` in commit `{commit_id}`",
+                encoding_name.to_str_lossy(),
+            )
+        })?
+    } else {
+        encoding_rs::UTF_8
+    };
+
+    if let Some(name) = encoding.decode_without_bom_handling_and_without_replacement(sig.name) {
+        if let Some(email) = encoding.decode_without_bom_handling_and_without_replacement(sig.email)
+        {
+            Ok(gix::actor::Signature {
+                name: BString::from(name.as_ref()),
+                email: BString::from(email.as_ref()),
+                time: sig.time,
+            })
+        } else {
+            Err(anyhow!(
+                "could not decode {role} email as `{}` for commit `{commit_id}`",
+                encoding.name(),
+            ))
+        }
+    } else {
+        Err(anyhow!(
+            "could not decode {role} name as `{}` for commit `{commit_id}`",
+            encoding.name(),
+        ))
     }
 }
@@ -9,7 +9,10 @@ mod time;
 
 pub(crate) use self::{
     commit::CommitExtended,
-    repository::{CommitOptions, RepositoryExtended},
+    repository::{
+        index_locked_error, CommitOptions, ContextError, RepositoryExtended, ShallowHistoryError,
+        BARE_REPOSITORY_COMMANDS,
+    },
     signature::SignatureExtended,
     time::TimeExtended,
 };
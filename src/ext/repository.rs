@@ -1,20 +1,113 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::{anyhow, Result};
-use bstr::BStr;
+use anyhow::{anyhow, Context, Result};
+use bstr::{BStr, BString, ByteSlice, ByteVec};
 
+use super::{commit::ExtraHeaders, TimeExtended};
 use crate::{
+    output::Output,
     stupid::Stupid,
     wrap::{Branch, Message, PartialRefName},
 };
 
+/// Errors for repository contexts in which StGit commands cannot operate.
+///
+/// Each of these classes of error is mapped to a distinct process exit code.
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum ContextError {
+    #[error(
+        "not a git repository (or any of the parent directories)\n\
+         change to a git repository or create one with `git init`"
+    )]
+    NotARepository,
+
+    #[error(
+        "`stg {0}` requires a work tree, but the repository is bare\n\
+         only read-only commands may be used in a bare repository: {commands}\n\
+         use `--branch` to select a branch other than HEAD's",
+        commands = BARE_REPOSITORY_COMMANDS
+            .iter()
+            .map(|name| format!("`stg {name}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )]
+    BareRepository(String),
+
+    #[error(
+        "HEAD is detached at `{0}`\n\
+         use `stg branch --create <name>` to create a branch at this commit"
+    )]
+    DetachedHead(String),
+
+    #[error("HEAD points to `{0}` which is not a local branch")]
+    NotLocalBranch(String),
+
+    #[error(
+        "branch `{0}` is unborn\n\
+         StGit requires the branch to have a first commit, e.g. made with `git commit`"
+    )]
+    UnbornBranch(String),
+
+    #[error("StGit stack not initialized for branch `{0}`")]
+    StackNotInitialized(String),
+}
+
+/// Error for an operation that needs history missing from a shallow repository.
+///
+/// The contained string describes the operation, e.g. "resolving `HEAD~3`".
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "{0} needs history that is missing from this shallow repository\n\
+     fetch more history with `git fetch --deepen=<depth>` or all of it with \
+     `git fetch --unshallow`"
+)]
+pub(crate) struct ShallowHistoryError(pub(crate) String);
+
+/// Names of commands that only read from the repository and thus may be used in a
+/// repository without a work tree, i.e. a bare repository.
+pub(crate) const BARE_REPOSITORY_COMMANDS: &[&str] = &[
+    "export", "files", "id", "log", "name", "next", "prev", "series", "show", "top",
+];
+
 /// Extends [`gix::Repository`] with additional methods.
 pub(crate) trait RepositoryExtended {
     /// Open git repository based on current directory and any environment overrides.
+    ///
+    /// The `GIT_AUTHOR_NAME`, `GIT_AUTHOR_EMAIL`, `GIT_COMMITTER_NAME`, and
+    /// `GIT_COMMITTER_EMAIL` environment variables take precedence over the `author.*`,
+    /// `committer.*`, and `user.*` configuration, as they do for git.
     fn open() -> Result<gix::Repository> {
-        Ok(gix::ThreadSafeRepository::discover_with_environment_overrides(".")?.into())
+        use gix::discover::Error as DiscoverError;
+        let overrides = identity_env_overrides();
+        let mut trust_map: gix::sec::trust::Mapping<gix::open::Options> = Default::default();
+        trust_map.full = trust_map.full.config_overrides(overrides.clone());
+        trust_map.reduced = trust_map.reduced.config_overrides(overrides);
+        match gix::ThreadSafeRepository::discover_with_environment_overrides_opts(
+            ".",
+            Default::default(),
+            trust_map,
+        ) {
+            Ok(repo) => Ok(repo.into()),
+            Err(DiscoverError::Discover(gix::discover::upwards::Error::NoGitRepository {
+                ..
+            }))
+            | Err(DiscoverError::Discover(
+                gix::discover::upwards::Error::NoGitRepositoryWithinCeiling { .. },
+            ))
+            | Err(DiscoverError::Discover(
+                gix::discover::upwards::Error::NoGitRepositoryWithinFs { .. },
+            ))
+            | Err(DiscoverError::Open(gix::open::Error::NotARepository { .. })) => {
+                Err(ContextError::NotARepository.into())
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     /// Determine whether the repository is in a clean state.
@@ -24,10 +117,22 @@ pub(crate) trait RepositoryExtended {
     /// [`gix::state::InProgress`].
     fn check_repository_state(&self) -> Result<()>;
 
+    /// Get how long to wait for another process to release its lock on the index.
+    ///
+    /// The timeout is `stgit.indexlocktimeout` milliseconds, defaulting to 1000.
+    fn index_lock_timeout(&self) -> Duration;
+
     /// Get the author signature or error if it is unavailable.
+    ///
+    /// The signature's time is fixed by the `STG_AUTHOR_DATE` environment variable or,
+    /// failing that, the `stgit.authordate` configuration value, if either is set.
     fn get_author(&self) -> Result<gix::actor::SignatureRef<'_>>;
 
     /// Get the committer signature or error if it is unavailable.
+    ///
+    /// The signature's time is fixed by the `STG_COMMITTER_DATE` environment variable
+    /// or, failing that, the `stgit.committerdate` configuration value, if either is
+    /// set.
     fn get_committer(&self) -> Result<gix::actor::SignatureRef<'_>>;
 
     /// Get [`Branch`], with StGit-specific error messaging.
@@ -40,6 +145,11 @@ pub(crate) trait RepositoryExtended {
     /// Returns an error if the head is detached or unborn.
     fn get_current_branch(&self) -> Result<Branch<'_>>;
 
+    /// Get the commit that HEAD points to, which may be detached.
+    ///
+    /// Returns an error if HEAD's branch is unborn.
+    fn head_commit_ex(&self) -> Result<gix::Commit<'_>>;
+
     /// Get repository-local config file which can be used to change local
     /// configuration.
     fn local_config_file(&self) -> Result<gix::config::File<'static>>;
@@ -62,6 +172,22 @@ pub(crate) trait RepositoryExtended {
         parent_ids: impl IntoIterator<Item = gix::ObjectId>,
     ) -> Result<gix::ObjectId>;
 
+    /// Create a new commit object in the repository, carrying over extra headers.
+    ///
+    /// This is the same as [`RepositoryExtended::commit_ex()`], but the provided extra
+    /// headers are also written to the commit object. This is used when rewriting
+    /// existing commits to preserve headers obtained with
+    /// [`crate::ext::CommitExtended::rewritable_extra_headers()`].
+    fn commit_ex_with_headers<'a>(
+        &self,
+        author: impl Into<gix::actor::SignatureRef<'a>>,
+        committer: impl Into<gix::actor::SignatureRef<'a>>,
+        message: &Message,
+        tree_id: gix::ObjectId,
+        parent_ids: impl IntoIterator<Item = gix::ObjectId>,
+        extra_headers: ExtraHeaders,
+    ) -> Result<gix::ObjectId>;
+
     /// Create a new commit object in the repository.
     ///
     /// The provided [`CommitOptions`] gives finer-grained control versus
@@ -78,6 +204,33 @@ pub(crate) trait RepositoryExtended {
 
     /// [`gix::Repository::rev_parse_single()`] with StGit-specific error mapping.
     fn rev_parse_single_ex(&self, spec: &str) -> Result<gix::Id<'_>>;
+
+    /// Get the name of the promisor remote if the repository is a partial clone.
+    fn promisor_remote(&self) -> Option<String>;
+
+    /// Find a worktree, other than the current one, with the given branch checked out.
+    ///
+    /// The main worktree, unless the repository is bare, and all linked worktrees are
+    /// considered. Worktrees on a detached HEAD do not have any branch checked out.
+    /// The path of the found worktree is returned.
+    fn find_other_worktree_with_branch(
+        &self,
+        branch_name: &gix::refs::FullNameRef,
+    ) -> Result<Option<PathBuf>>;
+
+    /// Fetch blobs missing from a partial clone that are needed to diff the given
+    /// pairs of trees.
+    ///
+    /// Without prefetching, each missing blob would be fetched lazily, one at a time.
+    /// Instead, all blobs at the paths that differ between each pair of trees are
+    /// fetched from the promisor remote with a single fetch. The blobs at those paths
+    /// in each of `other_trees`, e.g. a tree that the diffs will be applied to, are
+    /// also fetched. Nothing is done if the repository is not a partial clone.
+    fn prefetch_blobs(
+        &self,
+        tree_pairs: &[(gix::ObjectId, gix::ObjectId)],
+        other_trees: &[gix::ObjectId],
+    ) -> Result<()>;
 }
 
 /// Options for creating a git commit object.
@@ -87,6 +240,9 @@ pub(crate) struct CommitOptions<'a> {
 
     /// Determine whether the commit object should be signed with GPG.
     pub(crate) gpgsign: bool,
+
+    /// Extra headers, beyond the standard commit headers, to write to the commit.
+    pub(crate) extra_headers: ExtraHeaders,
 }
 
 impl RepositoryExtended for gix::Repository {
@@ -111,16 +267,32 @@ impl RepositoryExtended for gix::Repository {
         }
     }
 
+    fn index_lock_timeout(&self) -> Duration {
+        let timeout = self
+            .config_snapshot()
+            .integer("stgit.indexlocktimeout")
+            .map_or(1000, |millis| millis.max(0) as u64);
+        Duration::from_millis(timeout)
+    }
+
     fn get_author(&self) -> Result<gix::actor::SignatureRef<'_>> {
-        Ok(self.author().ok_or_else(|| {
+        let mut author = self.author().ok_or_else(|| {
             anyhow!("author identity unknown; please configure `user.name` and `user.email`.")
-        })??)
+        })??;
+        if let Some(time) = fixed_date(self, "STG_AUTHOR_DATE", "stgit.authordate")? {
+            author.time = time;
+        }
+        Ok(author)
     }
 
     fn get_committer(&self) -> Result<gix::actor::SignatureRef<'_>> {
-        Ok(self.committer().ok_or_else(|| {
+        let mut committer = self.committer().ok_or_else(|| {
             anyhow!("committer identity unknown; please configure `user.name` and `user.email`.")
-        })??)
+        })??;
+        if let Some(time) = fixed_date(self, "STG_COMMITTER_DATE", "stgit.committerdate")? {
+            committer.time = time;
+        }
+        Ok(committer)
     }
 
     fn get_branch(&self, branch_name: &PartialRefName) -> Result<Branch<'_>> {
@@ -132,7 +304,16 @@ impl RepositoryExtended for gix::Repository {
                 anyhow!("invalid branch name `{branch_name}`: {inner}")
             }
             gix::reference::find::existing::Error::NotFound { name } => {
-                anyhow!("branch `{}` not found", name.as_ref().as_bstr())
+                if self.head().is_ok_and(|head| {
+                    head.is_unborn()
+                        && head
+                            .referent_name()
+                            .is_some_and(|full_name| full_name.shorten() == name.as_ref().as_bstr())
+                }) {
+                    ContextError::UnbornBranch(name.as_ref().as_bstr().to_string()).into()
+                } else {
+                    anyhow!("branch `{}` not found", name.as_ref().as_bstr())
+                }
             }
         })?;
 
@@ -153,14 +334,24 @@ impl RepositoryExtended for gix::Repository {
                         .expect("inner reference is known to be valid");
                     Ok(Branch::wrap(reference))
                 } else {
-                    Err(anyhow!(
-                        "HEAD points to `{}` which is not a local branch",
-                        inner_reference.name
-                    ))
+                    Err(ContextError::NotLocalBranch(inner_reference.name.to_string()).into())
                 }
             }
-            Kind::Unborn(full_name) => Err(anyhow!("branch `{full_name}` is unborn")),
-            Kind::Detached { .. } => Err(anyhow!("not on branch, HEAD is detached")),
+            Kind::Unborn(full_name) => {
+                Err(ContextError::UnbornBranch(full_name.shorten().to_string()).into())
+            }
+            Kind::Detached { target, .. } => {
+                Err(ContextError::DetachedHead(target.to_hex_with_len(7).to_string()).into())
+            }
+        }
+    }
+
+    fn head_commit_ex(&self) -> Result<gix::Commit<'_>> {
+        let mut head = self.head()?;
+        if let gix::head::Kind::Unborn(full_name) = &head.kind {
+            Err(ContextError::UnbornBranch(full_name.shorten().to_string()).into())
+        } else {
+            Ok(head.peel_to_commit_in_place()?)
         }
     }
 
@@ -202,6 +393,18 @@ impl RepositoryExtended for gix::Repository {
         message: &Message,
         tree_id: gix::ObjectId,
         parent_ids: impl IntoIterator<Item = gix::ObjectId>,
+    ) -> Result<gix::ObjectId> {
+        self.commit_ex_with_headers(author, committer, message, tree_id, parent_ids, vec![])
+    }
+
+    fn commit_ex_with_headers<'a>(
+        &self,
+        author: impl Into<gix::actor::SignatureRef<'a>>,
+        committer: impl Into<gix::actor::SignatureRef<'a>>,
+        message: &Message,
+        tree_id: gix::ObjectId,
+        parent_ids: impl IntoIterator<Item = gix::ObjectId>,
+        extra_headers: ExtraHeaders,
     ) -> Result<gix::ObjectId> {
         let config = self.config_snapshot();
         let commit_encoding = config.string("i18n.commitencoding");
@@ -215,6 +418,7 @@ impl RepositoryExtended for gix::Repository {
             &CommitOptions {
                 commit_encoding,
                 gpgsign,
+                extra_headers,
             },
         )
     }
@@ -239,10 +443,22 @@ impl RepositoryExtended for gix::Repository {
             None => None,
         };
 
-        if options.gpgsign
-            || (commit_encoding.is_some() && commit_encoding != Some(encoding_rs::UTF_8))
-        {
+        let needs_encoding =
+            commit_encoding.is_some() && commit_encoding != Some(encoding_rs::UTF_8);
+
+        if options.gpgsign || (needs_encoding && options.extra_headers.is_empty()) {
             // Use git for any commit that needs to be signed
+            if !options.extra_headers.is_empty() {
+                let names: Vec<_> = options
+                    .extra_headers
+                    .iter()
+                    .map(|(name, _)| format!("`{name}`"))
+                    .collect();
+                Output::current().warning(&format!(
+                    "extra commit headers are not preserved in signed commits: {}",
+                    names.join(", ")
+                ));
+            }
             self.stupid().commit_tree(
                 author,
                 committer,
@@ -259,8 +475,12 @@ impl RepositoryExtended for gix::Repository {
                 author: author.to_owned(),
                 committer: committer.to_owned(),
                 encoding: commit_encoding.map(|enc| enc.name().into()),
-                message: message.raw_bytes().into(),
-                extra_headers: vec![],
+                message: if needs_encoding {
+                    message.encode_with(commit_encoding)?.as_ref().into()
+                } else {
+                    message.raw_bytes().into()
+                },
+                extra_headers: options.extra_headers.clone(),
             })?;
             Ok(commit_id.detach())
         }
@@ -317,6 +537,9 @@ impl RepositoryExtended for gix::Repository {
                                 SpecParseError::SingleNotFound => {
                                     break Error::RevisionNotFound(spec.to_string()).into()
                                 }
+                                SpecParseError::AncestorOutOfRange { .. } if self.is_shallow() => {
+                                    break ShallowHistoryError(format!("resolving `{spec}`")).into()
+                                }
                                 e => {
                                     break Error::InvalidRevision(spec.to_string(), e.to_string())
                                         .into()
@@ -330,4 +553,193 @@ impl RepositoryExtended for gix::Repository {
                 }
             })
     }
+
+    fn promisor_remote(&self) -> Option<String> {
+        let config = self.config_snapshot();
+        if let Some(remote) = config.string("extensions.partialClone") {
+            return Some(remote.to_string());
+        }
+        self.remote_names()
+            .into_iter()
+            .find(|name| {
+                config
+                    .boolean(format!("remote.{name}.promisor").as_str())
+                    .unwrap_or(false)
+            })
+            .map(|name| name.to_string())
+    }
+
+    fn find_other_worktree_with_branch(
+        &self,
+        branch_name: &gix::refs::FullNameRef,
+    ) -> Result<Option<PathBuf>> {
+        let has_branch_checked_out = |repo: &gix::Repository| {
+            repo.head_name()
+                .ok()
+                .flatten()
+                .is_some_and(|head_name| head_name.as_ref() == branch_name)
+        };
+        if !self.is_bare() && has_branch_checked_out(self) {
+            return Ok(None);
+        }
+
+        let canonical_git_dir =
+            |git_dir: &Path| std::fs::canonicalize(git_dir).unwrap_or_else(|_| git_dir.into());
+        let current_git_dir = canonical_git_dir(self.git_dir());
+
+        let main_repo = self.main_repo()?;
+        if !main_repo.is_bare()
+            && canonical_git_dir(main_repo.git_dir()) != current_git_dir
+            && has_branch_checked_out(&main_repo)
+        {
+            return Ok(main_repo.work_dir().map(Path::to_path_buf));
+        }
+
+        for proxy in self.worktrees()? {
+            if canonical_git_dir(proxy.git_dir()) == current_git_dir {
+                continue;
+            }
+            let git_dir = proxy.git_dir().to_path_buf();
+            let base = proxy.base().ok();
+            let worktree_repo = proxy.into_repo_with_possibly_inaccessible_worktree()?;
+            if has_branch_checked_out(&worktree_repo) {
+                return Ok(Some(base.unwrap_or(git_dir)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn prefetch_blobs(
+        &self,
+        tree_pairs: &[(gix::ObjectId, gix::ObjectId)],
+        other_trees: &[gix::ObjectId],
+    ) -> Result<()> {
+        let Some(remote) = self.promisor_remote() else {
+            return Ok(());
+        };
+        let tree_pairs: Vec<_> = tree_pairs
+            .iter()
+            .copied()
+            .filter(|(tree1, tree2)| tree1 != tree2)
+            .collect();
+        if tree_pairs.is_empty() {
+            return Ok(());
+        }
+
+        let stupid = self.stupid();
+        let raw_diff = stupid.diff_tree_raw(&tree_pairs)?;
+        let other_trees = other_trees
+            .iter()
+            .map(|tree_id| self.find_tree(*tree_id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut needed = indexmap::IndexSet::new();
+        for entry in raw_diff.iter() {
+            needed.extend(entry.old_blob);
+            needed.extend(entry.new_blob);
+            let path = gix::path::try_from_byte_slice(entry.path)?;
+            for tree in &other_trees {
+                if let Some(tree_entry) = tree.lookup_entry_by_path(path)? {
+                    if tree_entry.mode().is_blob_or_symlink() {
+                        needed.insert(tree_entry.object_id());
+                    }
+                }
+            }
+        }
+        needed.retain(|oid| !self.has_object(oid));
+        if needed.is_empty() {
+            return Ok(());
+        }
+        stupid.fetch_missing_objects(&remote, needed)
+    }
+}
+
+/// Get the fixed date for new signatures from the environment or configuration.
+/// Configuration overrides for identities given by the environment.
+///
+/// Git gives the `GIT_{AUTHOR,COMMITTER}_{NAME,EMAIL}` environment variables
+/// precedence over any configuration, whereas gitoxide only falls back to them when
+/// `author.*` or `committer.*` are not configured. Overriding the configuration with
+/// the environment's values ensures commits are created with the same identities as
+/// git would use.
+fn identity_env_overrides() -> Vec<BString> {
+    [
+        ("GIT_AUTHOR_NAME", "author.name"),
+        ("GIT_AUTHOR_EMAIL", "author.email"),
+        ("GIT_COMMITTER_NAME", "committer.name"),
+        ("GIT_COMMITTER_EMAIL", "committer.email"),
+    ]
+    .into_iter()
+    .filter_map(|(env_name, config_key)| {
+        let value = gix::path::os_string_into_bstring(std::env::var_os(env_name)?).ok()?;
+        let mut key_value = BString::from(format!("{config_key}="));
+        key_value.push_str(value);
+        Some(key_value)
+    })
+    .collect()
+}
+
+fn fixed_date(
+    repo: &gix::Repository,
+    env_name: &str,
+    config_key: &str,
+) -> Result<Option<gix::date::Time>> {
+    let (source, value) = if let Some(value) = std::env::var_os(env_name) {
+        let value = value
+            .into_string()
+            .map_err(|_| anyhow!("`{env_name}` is not valid UTF-8"))?;
+        (env_name, value)
+    } else if let Some(value) = repo.config_snapshot().string(config_key) {
+        let value = value
+            .to_str()
+            .map_err(|_| anyhow!("`{config_key}` is not valid UTF-8"))?
+            .to_string();
+        (config_key, value)
+    } else {
+        return Ok(None);
+    };
+    gix::date::Time::parse_time(&value)
+        .map(Some)
+        .with_context(|| format!("parsing `{source}`"))
+}
+
+/// Make the error for failing to lock the index because `lock_path` exists.
+///
+/// The error names the process holding the lock, when it can be found.
+pub(crate) fn index_locked_error(lock_path: &Path) -> anyhow::Error {
+    let holder = index_lock_holder(lock_path)
+        .map(|(pid, name)| format!(" by process {pid} (`{name}`)"))
+        .unwrap_or_default();
+    anyhow!(
+        "the index is locked{holder}: `{}` exists\n\
+         another git process seems to be running in this repository; \
+         if it crashed, remove the lock file to continue",
+        lock_path.display()
+    )
+}
+
+/// Find the process holding the index lock file open.
+///
+/// Processes are found by their open file descriptors in `/proc`, so the holder can
+/// only be determined on Linux and only while it keeps the lock file open.
+#[cfg(target_os = "linux")]
+fn index_lock_holder(lock_path: &Path) -> Option<(u32, String)> {
+    let lock_path = std::fs::canonicalize(lock_path).ok()?;
+    std::fs::read_dir("/proc").ok()?.find_map(|entry| {
+        let entry = entry.ok()?;
+        let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+        let holds_lock = std::fs::read_dir(entry.path().join("fd"))
+            .ok()?
+            .filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+            .any(|target| target == lock_path);
+        holds_lock.then(|| {
+            let name = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            (pid, name.trim_end().to_string())
+        })
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn index_lock_holder(_lock_path: &Path) -> Option<(u32, String)> {
+    None
 }
@@ -4,6 +4,12 @@
 
 use anyhow::{anyhow, Result};
 
+/// Description of the accepted date formats, for use in error messages.
+const ACCEPTED_FORMATS: &str = "expected ISO 8601 (e.g. `2022-01-06 09:32:07 -0500`), \
+     RFC 2822 (e.g. `Thu, 6 Jan 2022 09:32:07 -0500`), a Unix timestamp (e.g. \
+     `@1641479527 -0500`), or a relative date (e.g. `now`, `yesterday`, `2 weeks ago`, \
+     or `last friday 3pm`)";
+
 /// Extend [`gix::date::Time`] with additional methods.
 pub(crate) trait TimeExtended {
     /// Attempt to parse a time string of one of several well-known formats.
@@ -15,37 +21,239 @@ pub(crate) trait TimeExtended {
     /// | `iso8601`         | `2022-01-06 09:32:07 -0500`      |
     /// | `iso8601-strict`  | `2022-01-06T09:32:07-05:00`      |
     /// | `raw`             | `1641479527 -0500`               |
+    /// | unix timestamp    | `@1641479527 -0500`              |
     /// | `now`             | `now`                            |
+    /// | `yesterday`       | `yesterday`                      |
+    /// | relative          | `2 days ago`                     |
+    /// | approxidate       | `last friday 3pm`                |
     /// | `gitoxide default`| `Thu Jan 6 2022 09:32:07 -0500`  |
+    ///
+    /// The time zone offset of the given date is preserved. Dates without an offset,
+    /// including relative dates, are in the local time zone.
+    ///
+    /// This is the one place dates given by users, e.g. with `--authdate` or
+    /// `--date`, are parsed such that all commands accept the same formats.
     fn parse_time(time_str: &str) -> Result<gix::date::Time> {
         let time_str = time_str.trim();
         let now = std::time::SystemTime::now();
         let zoned_now = jiff::Zoned::try_from(now).unwrap_or_else(|_| jiff::Zoned::now());
 
-        if time_str == "now" {
-            Ok(gix::date::Time::new(
-                zoned_now.timestamp().as_second(),
-                zoned_now.offset().seconds(),
-            ))
+        if let Some(timestamp) = time_str.strip_prefix('@') {
+            let (seconds, offset) = timestamp
+                .split_once(char::is_whitespace)
+                .map_or((timestamp, "+0000"), |(seconds, offset)| {
+                    (seconds, offset.trim())
+                });
+            if !seconds.is_empty() && seconds.bytes().all(|b| b.is_ascii_digit()) {
+                if let Ok(time) = gix::date::parse(&format!("{seconds} {offset}"), Some(now)) {
+                    return Ok(time);
+                }
+            }
+        } else if let Some(zoned) = parse_approxidate(time_str, &zoned_now) {
+            return Ok(gix::date::Time::new(
+                zoned.timestamp().as_second(),
+                zoned.offset().seconds(),
+            ));
         } else if let Ok(time) = gix::date::parse(time_str, Some(now)) {
-            Ok(time)
+            return Ok(time);
         } else if let Ok(time) = gix::date::parse(
             &format!("{time_str} {}", zoned_now.strftime("%z")),
             Some(now),
         ) {
-            Ok(time)
+            return Ok(time);
         } else if let Ok(time) = gix::date::parse(
             &format!("{time_str}{}", zoned_now.strftime("%:z")),
             Some(now),
         ) {
-            Ok(time)
+            return Ok(time);
+        } else if let Ok(datetime) = jiff::civil::DateTime::strptime("%Y-%m-%d %H:%M", time_str) {
+            if let Ok(zoned) = datetime.to_zoned(zoned_now.time_zone().clone()) {
+                return Ok(gix::date::Time::new(
+                    zoned.timestamp().as_second(),
+                    zoned.offset().seconds(),
+                ));
+            }
+        }
+        Err(anyhow!("invalid date `{time_str}`: {ACCEPTED_FORMATS}"))
+    }
+
+    /// Format time relative to `now`, in seconds since the epoch, e.g. "3 days ago".
+    ///
+    /// The formatting and rounding match that of git's `--date=relative`.
+    fn format_relative(&self, now: i64) -> String;
+}
+
+impl TimeExtended for gix::date::Time {
+    fn format_relative(&self, now: i64) -> String {
+        fn plural(n: i64, unit: &str) -> String {
+            if n == 1 {
+                format!("{n} {unit}")
+            } else {
+                format!("{n} {unit}s")
+            }
+        }
+
+        if now < self.seconds {
+            return "in the future".to_string();
+        }
+        let seconds = now - self.seconds;
+        if seconds < 90 {
+            return format!("{} ago", plural(seconds, "second"));
+        }
+        let minutes = (seconds + 30) / 60;
+        if minutes < 90 {
+            return format!("{} ago", plural(minutes, "minute"));
+        }
+        let hours = (minutes + 30) / 60;
+        if hours < 36 {
+            return format!("{} ago", plural(hours, "hour"));
+        }
+        let days = (hours + 12) / 24;
+        if days < 14 {
+            format!("{} ago", plural(days, "day"))
+        } else if days < 70 {
+            format!("{} ago", plural((days + 3) / 7, "week"))
+        } else if days < 365 {
+            format!("{} ago", plural((days + 15) / 30, "month"))
+        } else if days < 1825 {
+            let total_months = (days * 12 * 2 + 365) / (365 * 2);
+            let (years, months) = (total_months / 12, total_months % 12);
+            if months == 0 {
+                format!("{} ago", plural(years, "year"))
+            } else {
+                format!("{}, {} ago", plural(years, "year"), plural(months, "month"))
+            }
         } else {
-            Err(anyhow!("invalid date `{time_str}`"))
+            format!("{} ago", plural((days + 183) / 365, "year"))
         }
     }
 }
 
-impl TimeExtended for gix::date::Time {}
+/// Parse a relative date expression, a subset of git's "approxidate".
+///
+/// Expressions are made of words, separated by whitespace or dots, that each move
+/// the date back from `now` or set the time of day: "now", "today", "yesterday",
+/// "<n> <unit>s [ago]" with units from seconds to years, "a" or "last" instead of a
+/// number of one, weekday names for the most recent such day before today, "noon",
+/// "midnight", "tea" (5pm), and times like "10:30", "10:30:05", or "3pm".
+///
+/// The current time of day is kept unless a time is given. `None` is returned if the
+/// string is not such an expression.
+fn parse_approxidate(s: &str, now: &jiff::Zoned) -> Option<jiff::Zoned> {
+    use jiff::{civil, Span};
+
+    let lowered = s.to_ascii_lowercase();
+    let mut tokens = lowered
+        .split(|c: char| c.is_ascii_whitespace() || c == '.' || c == ',')
+        .filter(|token| !token.is_empty())
+        .peekable();
+    tokens.peek()?;
+
+    let mut zoned = now.clone();
+    let mut time_of_day: Option<civil::Time> = None;
+    let mut number: Option<i64> = None;
+
+    while let Some(token) = tokens.next() {
+        let (digits, suffix) = token.split_at(
+            token
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(token.len()),
+        );
+        if !digits.is_empty() && (suffix == "am" || suffix == "pm") {
+            let hour: i8 = digits.parse().ok().filter(|hour| (1..=12).contains(hour))?;
+            let hour = hour % 12 + if suffix == "pm" { 12 } else { 0 };
+            time_of_day = Some(civil::Time::new(hour, 0, 0, 0).ok()?);
+            continue;
+        } else if !digits.is_empty() && suffix.is_empty() {
+            if number.is_some() {
+                return None;
+            }
+            let value: i64 = digits.parse().ok()?;
+            if let Some(&meridiem) = tokens.peek().filter(|t| **t == "am" || **t == "pm") {
+                tokens.next();
+                let hour = i8::try_from(value)
+                    .ok()
+                    .filter(|hour| (1..=12).contains(hour))?;
+                let hour = hour % 12 + if meridiem == "pm" { 12 } else { 0 };
+                time_of_day = Some(civil::Time::new(hour, 0, 0, 0).ok()?);
+            } else {
+                number = Some(value);
+            }
+            continue;
+        } else if token.contains(':') {
+            let mut fields = token.split(':').map(|field| field.parse::<i8>().ok());
+            let hour = fields.next()??;
+            let minute = fields.next()??;
+            let second = fields.next().map_or(Some(0), |second| second)?;
+            if fields.next().is_some() {
+                return None;
+            }
+            time_of_day = Some(civil::Time::new(hour, minute, second, 0).ok()?);
+            continue;
+        }
+
+        let unit_span = |n: i64| -> Option<Span> {
+            let unit = token.strip_suffix('s').unwrap_or(token);
+            match unit {
+                "second" | "sec" => Span::new().try_seconds(n).ok(),
+                "minute" | "min" => Span::new().try_minutes(n).ok(),
+                "hour" => Span::new().try_hours(n).ok(),
+                "day" => Span::new().try_days(n).ok(),
+                "week" => Span::new().try_weeks(n).ok(),
+                "month" => Span::new().try_months(n).ok(),
+                "year" => Span::new().try_years(n).ok(),
+                _ => None,
+            }
+        };
+        let weekday = [
+            "sunday",
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+        ]
+        .iter()
+        .position(|day| token.len() >= 3 && day.starts_with(token));
+
+        match token {
+            "now" | "today" | "ago" if number.is_none() => {}
+            "yesterday" if number.is_none() => {
+                zoned = zoned.checked_sub(Span::new().days(1)).ok()?
+            }
+            "noon" => time_of_day = Some(civil::time(12, 0, 0, 0)),
+            "midnight" => time_of_day = Some(civil::time(0, 0, 0, 0)),
+            "tea" => time_of_day = Some(civil::time(17, 0, 0, 0)),
+            "a" | "an" | "last" if number.is_none() => number = Some(1),
+            _ => {
+                if let Some(span) = unit_span(number.take().unwrap_or(1)) {
+                    zoned = zoned.checked_sub(span).ok()?;
+                } else if let Some(weekday) = weekday {
+                    let weeks = number.take().unwrap_or(1) - 1;
+                    let today = i64::from(zoned.weekday().to_sunday_zero_offset());
+                    let mut days_back = (today - weekday as i64).rem_euclid(7);
+                    if days_back == 0 {
+                        days_back = 7;
+                    }
+                    zoned = zoned
+                        .checked_sub(Span::new().try_days(days_back + 7 * weeks).ok()?)
+                        .ok()?;
+                } else {
+                    return None;
+                }
+            }
+        }
+    }
+    if number.is_some() {
+        return None;
+    }
+
+    if let Some(time) = time_of_day {
+        zoned = zoned.with().time(time).build().ok()?;
+    }
+    Some(zoned)
+}
 
 #[cfg(test)]
 mod tests {
@@ -96,6 +304,94 @@ mod tests {
         Time::parse_time("now").unwrap();
     }
 
+    #[test]
+    fn parse_time_relative() {
+        let now = Time::parse_time("now").unwrap().seconds;
+        let yesterday = Time::parse_time("yesterday").unwrap().seconds;
+        assert!((now - 24 * 60 * 60 - yesterday).abs() <= 1);
+        let two_days_ago = Time::parse_time("2 days ago").unwrap().seconds;
+        assert!((now - 2 * 24 * 60 * 60 - two_days_ago).abs() <= 1);
+    }
+
+    #[test]
+    fn parse_unix_timestamp() {
+        let time = Time::parse_time("@1641479527").unwrap();
+        assert_eq!(time, Time::parse_time("1641479527 +0000").unwrap());
+        let time = Time::parse_time("@1641479527 -0500").unwrap();
+        assert_eq!(time, Time::parse_time("2022-01-06 09:32:07 -0500").unwrap());
+        assert!(Time::parse_time("@").is_err());
+        assert!(Time::parse_time("@12ab").is_err());
+    }
+
+    #[test]
+    fn parse_iso8601_without_seconds() {
+        let time = Time::parse_time("2022-01-06 09:32").unwrap();
+        assert!(time
+            .format(gix::date::time::format::ISO8601)
+            .starts_with("2022-01-06 09:32:00"));
+    }
+
+    fn approxidate(s: &str) -> Option<String> {
+        // Thursday, 2022-01-06 09:32:07 -0500
+        let now = jiff::Timestamp::from_second(1641479527)
+            .unwrap()
+            .to_zoned(jiff::tz::TimeZone::fixed(jiff::tz::offset(-5)));
+        super::parse_approxidate(s, &now).map(|zoned| zoned.strftime("%F %T %z").to_string())
+    }
+
+    #[test]
+    fn parse_approxidates() {
+        for (s, expected) in [
+            ("now", "2022-01-06 09:32:07 -0500"),
+            ("today", "2022-01-06 09:32:07 -0500"),
+            ("yesterday", "2022-01-05 09:32:07 -0500"),
+            ("2 weeks ago", "2021-12-23 09:32:07 -0500"),
+            ("2.weeks.ago", "2021-12-23 09:32:07 -0500"),
+            ("a week ago", "2021-12-30 09:32:07 -0500"),
+            ("3 days ago 10:00", "2022-01-03 10:00:00 -0500"),
+            ("1 hour, 30 minutes ago", "2022-01-06 08:02:07 -0500"),
+            ("2 months ago", "2021-11-06 09:32:07 -0500"),
+            ("1 year ago", "2021-01-06 09:32:07 -0500"),
+            ("noon", "2022-01-06 12:00:00 -0500"),
+            ("yesterday midnight", "2022-01-05 00:00:00 -0500"),
+            ("tea", "2022-01-06 17:00:00 -0500"),
+            ("last friday", "2021-12-31 09:32:07 -0500"),
+            ("last friday 3pm", "2021-12-31 15:00:00 -0500"),
+            ("Thursday 9 am", "2021-12-30 09:00:00 -0500"),
+            ("12am", "2022-01-06 00:00:00 -0500"),
+            ("yesterday 10:30:05", "2022-01-05 10:30:05 -0500"),
+        ] {
+            assert_eq!(approxidate(s).as_deref(), Some(expected), "{s}");
+        }
+    }
+
+    #[test]
+    fn parse_bad_approxidates() {
+        for s in [
+            "",
+            "2",
+            "ago 2",
+            "2 3 days",
+            "13pm",
+            "25:00",
+            "10:00:00:00",
+            "fortnight",
+            "2022-01-06",
+            "Thu Jan 6 09:32:07 2022 -0500",
+        ] {
+            assert_eq!(approxidate(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn parse_time_error_lists_formats() {
+        let err = Time::parse_time("next tuesday").unwrap_err().to_string();
+        assert!(err.starts_with("invalid date `next tuesday`: expected ISO 8601"));
+        assert!(err.contains("RFC 2822"));
+        assert!(err.contains("`@1641479527 -0500`"));
+        assert!(err.contains("`2 weeks ago`"));
+    }
+
     #[test]
     fn test_parse_time_negative_offset() {
         let time = Time::parse_time("123456 -0230").unwrap();
@@ -116,4 +412,25 @@ mod tests {
             assert!(Time::parse_time(bad_str).is_err());
         }
     }
+
+    #[test]
+    fn relative_time_formatting() {
+        let time = Time::parse_time("1641479527 -0500").unwrap();
+        let now = time.seconds;
+        assert_eq!(time.format_relative(now), "0 seconds ago");
+        assert_eq!(time.format_relative(now - 1), "in the future");
+        assert_eq!(time.format_relative(now + 1), "1 second ago");
+        assert_eq!(time.format_relative(now + 89), "89 seconds ago");
+        assert_eq!(time.format_relative(now + 90), "2 minutes ago");
+        assert_eq!(time.format_relative(now + 3 * 3600), "3 hours ago");
+        assert_eq!(time.format_relative(now + 36 * 3600), "2 days ago");
+        assert_eq!(time.format_relative(now + 20 * 86400), "3 weeks ago");
+        assert_eq!(time.format_relative(now + 100 * 86400), "3 months ago");
+        assert_eq!(time.format_relative(now + 365 * 86400), "1 year ago");
+        assert_eq!(
+            time.format_relative(now + 500 * 86400),
+            "1 year, 4 months ago"
+        );
+        assert_eq!(time.format_relative(now + 2000 * 86400), "5 years ago");
+    }
 }
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Help topics shown by `stg help <topic>`.
+//!
+//! Each topic's text is kept in a markdown file next to this module. The same files
+//! are rendered for the terminal, converted to asciidoc man pages by `stg completion
+//! man`, and included as the documentation of the patch specification parsers in
+//! [`crate::patch::parse`], such that the user documentation and the parsers are
+//! described by a single source.
+//!
+//! The markdown is limited to paragraphs, `backticked` literals, and definition list
+//! entries, where a line starting with `- ` gives a term and the following lines,
+//! indented by two spaces, give its definition.
+
+use std::fmt::Write as _;
+
+use clap::builder::StyledStr;
+
+/// A help topic describing a concept shared by several StGit commands.
+pub(crate) struct HelpTopic {
+    /// Name of the topic, as given to `stg help`.
+    pub(crate) name: &'static str,
+
+    /// One line summary of the topic.
+    pub(crate) about: &'static str,
+
+    /// Topic text in the markdown subset described in the module documentation.
+    pub(crate) text: &'static str,
+}
+
+/// All help topics, in the order shown in the top-level help.
+pub(crate) const HELP_TOPICS: &[HelpTopic] = &[
+    HelpTopic {
+        name: "locators",
+        about: "Specifying a single patch",
+        text: include_str!("locators.md"),
+    },
+    HelpTopic {
+        name: "patterns",
+        about: "Specifying patch ranges, labels, globs, and exclusions",
+        text: include_str!("patterns.md"),
+    },
+];
+
+/// Find the help topic with the given name.
+pub(crate) fn find_topic(name: &str) -> Option<&'static HelpTopic> {
+    HELP_TOPICS.iter().find(|topic| topic.name == name)
+}
+
+/// Make the list of help topics appended to the top-level help.
+pub(crate) fn make_topics_list() -> StyledStr {
+    let mut s = StyledStr::new();
+    let header = crate::cmd::STYLES.get_header();
+    let literal = crate::cmd::STYLES.get_literal();
+    write!(
+        s,
+        "{}Help topics:{}",
+        header.render(),
+        header.render_reset()
+    )
+    .unwrap();
+    for topic in HELP_TOPICS {
+        write!(
+            s,
+            "\n  {}{:<10}{}{}",
+            literal.render(),
+            topic.name,
+            literal.render_reset(),
+            topic.about
+        )
+        .unwrap();
+    }
+    write!(
+        s,
+        "\n\nSee 'stg help <command>' or 'stg help <topic>' for more information."
+    )
+    .unwrap();
+    s
+}
+
+impl HelpTopic {
+    /// Make a [`clap::Command`] whose long help displays the topic.
+    ///
+    /// Going through clap lets the topic's output follow the same `--color` and
+    /// styling rules as the rest of the help output.
+    pub(crate) fn make_command(&self) -> clap::Command {
+        clap::Command::new(self.name)
+            .styles(crate::cmd::STYLES)
+            .about(self.about)
+            .after_long_help(self.render())
+            .help_template("{about}{after-help}")
+    }
+
+    /// Render the topic text for the terminal.
+    ///
+    /// Definition list terms are indented by two columns, their definitions by four,
+    /// and terms are styled as literals.
+    fn render(&self) -> StyledStr {
+        let mut s = StyledStr::new();
+        let literal = crate::cmd::STYLES.get_literal();
+        for line in self.text.trim_end().lines() {
+            if let Some(term) = line.strip_prefix("- ") {
+                writeln!(s, "  {}{term}{}", literal.render(), literal.render_reset())
+            } else if line.starts_with("  ") {
+                writeln!(s, "  {line}")
+            } else {
+                writeln!(s, "{line}")
+            }
+            .unwrap();
+        }
+        s
+    }
+
+    /// Make the topic's man page in asciidoc format.
+    ///
+    /// References to other topics in the form `stg help <topic>` become links to the
+    /// other topics' man pages.
+    pub(crate) fn asciidoc(&self) -> String {
+        let mut page = String::new();
+        let title = format!("stg{}(7)", self.name);
+        writeln!(page, "{title}\n{}\n", "=".repeat(title.len())).unwrap();
+        writeln!(
+            page,
+            "NAME\n----\nstg{} - {}\n\nDESCRIPTION\n-----------\n",
+            self.name, self.about
+        )
+        .unwrap();
+        for line in self.text.trim_end().lines() {
+            let mut line = line.to_string();
+            for topic in HELP_TOPICS {
+                line = line.replace(
+                    &format!("`stg help {}`", topic.name),
+                    &format!("linkman:stg{}[7]", topic.name),
+                );
+            }
+            if let Some(term) = line.strip_prefix("- ") {
+                writeln!(page, "{term}::")
+            } else {
+                writeln!(page, "{line}")
+            }
+            .unwrap();
+        }
+        page.push_str("\nStGit\n-----\nPart of the StGit suite - see linkman:stg[1]\n");
+        page
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HELP_TOPICS;
+
+    #[test]
+    fn topic_text_layout() {
+        for topic in HELP_TOPICS {
+            for line in topic.text.lines() {
+                // Definitions are indented by another two columns for the terminal.
+                let width = if line.starts_with("  ") { 78 } else { 80 };
+                assert!(line.len() <= width, "{}: line too long: {line}", topic.name);
+                assert!(
+                    line.is_empty()
+                        || line.starts_with("- ")
+                        || line.starts_with("  ")
+                        || !line.starts_with(' '),
+                    "{}: unexpected indentation: {line}",
+                    topic.name
+                );
+                assert_eq!(
+                    line.matches('`').count() % 2,
+                    0,
+                    "{}: unbalanced literal: {line}",
+                    topic.name
+                );
+            }
+        }
+    }
+}
@@ -11,7 +11,7 @@ use std::{
 use anyhow::{anyhow, Context, Result};
 use bstr::BString;
 
-use crate::wrap::Message;
+use crate::{stack::StackConfig, stupid::program::git_program, wrap::Message};
 
 /// Find path to hook script given a hook name.
 ///
@@ -72,6 +72,7 @@ pub(crate) fn run_pre_commit_hook(repo: &gix::Repository, use_editor: bool) -> R
         gix::command::prepare(hook_path).stdout(std::process::Stdio::inherit()),
     );
     hook_command.current_dir(work_dir);
+    git_program().setup_child_env(&mut hook_command);
     if !use_editor {
         hook_command.env("GIT_EDITOR", ":");
     }
@@ -123,6 +124,7 @@ pub(crate) fn run_commit_msg_hook<'repo>(
         gix::command::prepare(hook_path).stdout(std::process::Stdio::inherit()),
     );
     hook_command.current_dir(work_dir);
+    git_program().setup_child_env(&mut hook_command);
     hook_command.env("GIT_INDEX_FILE", &index_path);
     if !use_editor {
         hook_command.env("GIT_EDITOR", ":");
@@ -152,6 +154,56 @@ pub(crate) fn run_commit_msg_hook<'repo>(
     }
 }
 
+/// Run the git `post-rewrite` hook script.
+///
+/// The `command` argument is passed to the hook to indicate the kind of rewrite, i.e.
+/// `amend` or `rebase`. The old and new commit ids of each rewritten commit are written
+/// to the hook's stdin, one pair per line.
+///
+/// As with git, the hook's exit status is ignored since the rewrite is already
+/// complete by the time the hook runs.
+pub(crate) fn run_post_rewrite_hook(
+    repo: &gix::Repository,
+    command: &str,
+    rewrites: &[(gix::ObjectId, gix::ObjectId)],
+) -> Result<()> {
+    let hook_name = "post-rewrite";
+    if rewrites.is_empty() {
+        return Ok(());
+    }
+    let hook_path = if let Some(hook_path) = get_hook_path(repo, hook_name)? {
+        hook_path
+    } else {
+        return Ok(());
+    };
+
+    let mut hook_command = std::process::Command::from(
+        gix::command::prepare(hook_path)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::inherit()),
+    );
+    hook_command.current_dir(repo.work_dir().unwrap_or_else(|| repo.common_dir()));
+    git_program().setup_child_env(&mut hook_command);
+    hook_command.arg(command);
+
+    let mut child = hook_command
+        .spawn()
+        .with_context(|| format!("`{hook_name}` hook"))?;
+    let mut stdin = child.stdin.take().expect("hook stdin is piped");
+    for (old_id, new_id) in rewrites {
+        match writeln!(stdin, "{old_id} {new_id}") {
+            // The hook is not obliged to read its input.
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => break,
+            result => result.with_context(|| format!("`{hook_name}` hook"))?,
+        }
+    }
+    drop(stdin);
+    child
+        .wait()
+        .with_context(|| format!("`{hook_name}` hook"))?;
+    Ok(())
+}
+
 /// Temporary commit message file for commit-msg hook.
 ///
 /// The temporary file is created relative to the work dir using the StGit process id to
@@ -209,3 +261,52 @@ fn is_executable(meta: &std::fs::Metadata) -> bool {
 fn is_executable(_meta: &std::fs::Metadata) -> bool {
     true
 }
+
+/// Run the `stgit.notify.command` command after the stack state is updated.
+///
+/// The command is run with the shell and is not waited for, with its standard streams
+/// closed, such that it can neither slow down nor fail the StGit command. Failing to
+/// start the command is also ignored. The update is described to the command by these
+/// environment variables:
+///
+/// - `STGIT_NOTIFY_BRANCH`: the name of the stack's branch.
+/// - `STGIT_NOTIFY_COMMAND`: the StGit command, e.g. `push`.
+/// - `STGIT_NOTIFY_MESSAGE`: the stack log message.
+/// - `STGIT_NOTIFY_OLD_STATE`: the id of the previous stack state commit.
+/// - `STGIT_NOTIFY_NEW_STATE`: the id of the new stack state commit.
+pub(crate) fn run_notify_command(
+    repo: &gix::Repository,
+    branch_name: &str,
+    command_name: Option<&str>,
+    message: &str,
+    old_state_id: gix::ObjectId,
+    new_state_id: gix::ObjectId,
+) {
+    let config = StackConfig::new(repo, Some(branch_name));
+    let Some(notify_command) = config
+        .string("stgit.notify.command")
+        .filter(|command| !command.is_empty())
+    else {
+        return;
+    };
+    let Ok(notify_command) = gix::path::try_from_bstring(notify_command.into_owned()) else {
+        return;
+    };
+
+    let mut command = std::process::Command::from(
+        gix::command::prepare(notify_command)
+            .with_shell()
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null()),
+    );
+    command.current_dir(repo.work_dir().unwrap_or_else(|| repo.common_dir()));
+    git_program().setup_child_env(&mut command);
+    command
+        .env("STGIT_NOTIFY_BRANCH", branch_name)
+        .env("STGIT_NOTIFY_COMMAND", command_name.unwrap_or_default())
+        .env("STGIT_NOTIFY_MESSAGE", message)
+        .env("STGIT_NOTIFY_OLD_STATE", old_state_id.to_string())
+        .env("STGIT_NOTIFY_NEW_STATE", new_state_id.to_string());
+    let _ = command.spawn();
+}
@@ -0,0 +1,595 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Direct manipulation of the repository's index.
+//!
+//! The file tracking commands, `stg add`, `stg rm`, and `stg mv`, use [`IndexEditor`]
+//! to update the index in-process instead of delegating to `git`. Paths given on the
+//! command line are relative to the current directory and are converted to paths
+//! relative to the root of the work tree with [`crate::pathspec::root_relative_path()`].
+//!
+//! Content is hashed in-process, except for paths that are subject to content filters,
+//! i.e. end-of-line conversion, `filter`, `ident`, or `working-tree-encoding`
+//! attributes. Such paths are hashed with `git hash-object` so that the stored blobs
+//! are the same as those stored by `git add`.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+use gix::index::entry::{Flags, Mode, Stage, Stat};
+
+use crate::{
+    ext::{index_locked_error, RepositoryExtended},
+    stupid::Stupid,
+};
+
+type SparsePatterns = gix::glob::search::pattern::List<gix::ignore::search::Ignore>;
+
+/// Attributes that determine whether a path's content is filtered when it is added.
+///
+/// The order matters to [`IndexEditor::check_filters()`].
+const FILTER_ATTRIBUTES: [&str; 6] = [
+    "text",
+    "crlf",
+    "eol",
+    "filter",
+    "ident",
+    "working-tree-encoding",
+];
+
+/// Join a work tree root relative directory path with a child path.
+pub(crate) fn join_path(dir: &BStr, name: &BStr) -> BString {
+    if dir.is_empty() {
+        name.to_owned()
+    } else {
+        let mut path = dir.to_owned();
+        path.push_byte(b'/');
+        path.push_str(name);
+        path
+    }
+}
+
+/// Determine whether `path` is `dir` or is contained in `dir`.
+///
+/// An empty `dir` contains all paths.
+fn is_within(path: &BStr, dir: &BStr) -> bool {
+    dir.is_empty() || path == dir || (path.starts_with(dir) && path.get(dir.len()) == Some(&b'/'))
+}
+
+/// The state of a tracked path's content in the work tree relative to the index.
+pub(crate) enum WorktreeState {
+    /// The path does not exist in the work tree.
+    Missing,
+
+    /// The work tree content is the same as the index's stage 0 entry.
+    Unchanged,
+
+    /// The work tree content differs from the index or the path is in conflict.
+    Changed,
+}
+
+/// In-memory editor of the repository's index.
+///
+/// The index is locked for the lifetime of the editor. Changes are only written back
+/// to the index file by [`IndexEditor::write()`].
+pub(crate) struct IndexEditor<'repo> {
+    repo: &'repo gix::Repository,
+    lock: gix::lock::File,
+    work_dir: PathBuf,
+    prefix: BString,
+    index: gix::index::File,
+    excludes: gix::AttributeStack<'repo>,
+    sparse_patterns: Option<SparsePatterns>,
+    trust_file_mode: bool,
+    autocrlf: bool,
+    split_index: bool,
+    filtered: RefCell<HashMap<BString, bool>>,
+}
+
+impl<'repo> IndexEditor<'repo> {
+    /// Lock and read the repository's index, or start with an empty index if there is
+    /// none.
+    ///
+    /// Another process holding the index lock is waited for up to
+    /// `stgit.indexlocktimeout` milliseconds.
+    pub(crate) fn open(repo: &'repo gix::Repository) -> Result<Self> {
+        let work_dir = repo
+            .work_dir()
+            .ok_or_else(|| anyhow!("a work tree is required to update the index"))?
+            .to_owned();
+        let prefix = repo
+            .prefix()
+            .context("determining Git prefix")?
+            .map(|prefix| gix::path::into_bstr(prefix).into_owned())
+            .unwrap_or_default();
+
+        let lock = gix::lock::File::acquire_to_update_resource(
+            repo.index_path(),
+            repo.index_lock_timeout().into(),
+            None,
+        )
+        .map_err(|err| match err {
+            gix::lock::acquire::Error::PermanentlyLocked { resource_path, .. } => {
+                let mut lock_path = resource_path.into_os_string();
+                lock_path.push(".lock");
+                index_locked_error(Path::new(&lock_path))
+            }
+            err => anyhow::Error::new(err).context("locking the index"),
+        })?;
+
+        let index = if repo.index_path().exists() {
+            repo.open_index()?
+        } else {
+            gix::index::File::from_state(
+                gix::index::State::new(repo.object_hash()),
+                repo.index_path(),
+            )
+        };
+        if index.is_sparse() {
+            return Err(anyhow!("sparse indexes are not supported"));
+        }
+
+        let excludes = repo.excludes(
+            &index,
+            None,
+            gix::worktree::stack::state::ignore::Source::WorktreeThenIdMappingIfNotSkipped,
+        )?;
+
+        let config = repo.config_snapshot();
+        let sparse_patterns = if config.boolean("core.sparseCheckout").unwrap_or(false) {
+            let mut buf = Vec::new();
+            SparsePatterns::from_file(
+                repo.git_dir().join("info").join("sparse-checkout"),
+                None,
+                true,
+                &mut buf,
+            )?
+            .or_else(|| Some(SparsePatterns::default()))
+        } else {
+            None
+        };
+        let trust_file_mode = config.boolean("core.fileMode").unwrap_or(true);
+        let autocrlf = config.string("core.autocrlf").is_some_and(|value| {
+            value.eq_ignore_ascii_case(b"input")
+                || gix::config::Boolean::try_from(value.as_ref()).is_ok_and(|value| value.0)
+        });
+
+        // The split index is dissolved when the index is read, so its use is instead
+        // determined by configuration or the presence of shared index files.
+        let split_index = config.boolean("core.splitIndex").unwrap_or(false)
+            || std::fs::read_dir(repo.git_dir())?.any(|entry| {
+                entry.is_ok_and(|entry| {
+                    entry
+                        .file_name()
+                        .to_string_lossy()
+                        .starts_with("sharedindex.")
+                })
+            });
+
+        Ok(Self {
+            repo,
+            lock,
+            work_dir,
+            prefix,
+            index,
+            excludes,
+            sparse_patterns,
+            trust_file_mode,
+            autocrlf,
+            split_index,
+            filtered: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Convert a user-provided path to a path relative to the work tree root.
+    pub(crate) fn root_relative_path(&self, path: &Path) -> Result<BString> {
+        crate::pathspec::root_relative_path(&self.work_dir, self.prefix.as_bstr(), path)
+    }
+
+    /// Get the location of a work tree root relative path in the file system.
+    pub(crate) fn work_path(&self, path: &BStr) -> PathBuf {
+        self.work_dir.join(gix::path::from_bstr(path))
+    }
+
+    /// Determine whether the index has any entries for `path`.
+    pub(crate) fn is_tracked(&self, path: &BStr) -> bool {
+        self.index.entry_range(path).is_some()
+    }
+
+    /// Determine whether `path` has unmerged entries in the index.
+    pub(crate) fn is_conflicted(&self, path: &BStr) -> bool {
+        self.index.entry_range(path).is_some_and(|range| {
+            self.index.entries()[range]
+                .iter()
+                .any(|e| e.stage() != Stage::Unconflicted)
+        })
+    }
+
+    /// Find the distinct tracked paths that are `path` or are contained in `path`.
+    pub(crate) fn tracked_paths(&self, path: &BStr) -> Vec<BString> {
+        let mut paths: Vec<BString> = Vec::new();
+        for entry in self.index.entries() {
+            let entry_path = entry.path(&self.index);
+            if is_within(entry_path, path) && paths.last().map(|p| p.as_bstr()) != Some(entry_path)
+            {
+                paths.push(entry_path.to_owned());
+            }
+        }
+        paths
+    }
+
+    /// Determine whether a tracked path is hidden from the work tree by sparse checkout.
+    pub(crate) fn is_skip_worktree(&self, path: &BStr) -> bool {
+        self.index
+            .entry_by_path(path)
+            .is_some_and(|entry| entry.flags.contains(Flags::SKIP_WORKTREE))
+    }
+
+    /// Determine whether `path` is excluded by the repository's ignore rules.
+    pub(crate) fn is_ignored(&mut self, path: &BStr, is_dir: bool) -> Result<bool> {
+        let mode = if is_dir { Mode::DIR } else { Mode::FILE };
+        Ok(self.excludes.at_entry(path, Some(mode))?.is_excluded())
+    }
+
+    /// Determine whether `path` is outside of the sparse-checkout definition.
+    ///
+    /// Tracked paths are outside of the sparse-checkout when their index entries are
+    /// flagged to skip the work tree. Other paths are matched against the
+    /// sparse-checkout patterns, with a path's leading directories being consulted in
+    /// turn until a pattern matches.
+    pub(crate) fn is_outside_sparse_checkout(&self, path: &BStr) -> bool {
+        let Some(patterns) = self.sparse_patterns.as_ref() else {
+            return false;
+        };
+        if self.is_tracked(path) {
+            return self.is_skip_worktree(path);
+        }
+        let mut candidate = path;
+        let mut is_dir = false;
+        loop {
+            let basename_pos = candidate.rfind_byte(b'/').map(|pos| pos + 1);
+            if let Some(m) = gix::ignore::search::pattern_matching_relative_path(
+                patterns,
+                candidate,
+                basename_pos,
+                Some(is_dir),
+                gix::glob::pattern::Case::Sensitive,
+            ) {
+                return m.pattern.is_negative();
+            }
+            match candidate.rfind_byte(b'/') {
+                Some(pos) => {
+                    candidate = candidate[..pos].as_bstr();
+                    is_dir = true;
+                }
+                None => return true,
+            }
+        }
+    }
+
+    /// Collect the files in the work tree directory `dir` that are eligible to be added.
+    ///
+    /// Untracked files that are ignored are skipped unless `force` is true. Nested
+    /// repositories are not descended into.
+    pub(crate) fn collect_files(
+        &mut self,
+        dir: &BStr,
+        force: bool,
+        files: &mut Vec<BString>,
+    ) -> Result<()> {
+        let mut dir_entries = std::fs::read_dir(self.work_path(dir))?
+            .collect::<std::io::Result<Vec<std::fs::DirEntry>>>()?;
+        dir_entries.sort_by_key(std::fs::DirEntry::file_name);
+
+        for dir_entry in dir_entries {
+            let name = Vec::from_os_string(dir_entry.file_name())
+                .map_err(|name| anyhow!("`{}` is not a valid path", name.to_string_lossy()))?;
+            if name == b".git" {
+                continue;
+            }
+            let path = join_path(dir, name.as_bstr());
+            if dir_entry.file_type()?.is_dir() {
+                if dir_entry.path().join(".git").exists() {
+                    continue;
+                }
+                let has_tracked = !self.tracked_paths(path.as_bstr()).is_empty();
+                if force || has_tracked || !self.is_ignored(path.as_bstr(), true)? {
+                    self.collect_files(path.as_bstr(), force, files)?;
+                }
+            } else if force
+                || self.is_tracked(path.as_bstr())
+                || !self.is_ignored(path.as_bstr(), false)?
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Determine which of the given paths are subject to content filters.
+    ///
+    /// The attributes of all paths are looked up at once and remembered, sparing
+    /// [`IndexEditor::add()`] and friends from looking up the attributes one path at a
+    /// time. A path is filtered if any of [`FILTER_ATTRIBUTES`] is set or has a value,
+    /// or if `core.autocrlf` is enabled and the path is not marked as binary.
+    pub(crate) fn check_filters(&self, paths: &[&BStr]) -> Result<()> {
+        let paths: Vec<&BStr> = paths
+            .iter()
+            .copied()
+            .filter(|path| !self.filtered.borrow().contains_key(*path))
+            .collect();
+        let values = self.repo.stupid().check_attr(&FILTER_ATTRIBUTES, &paths)?;
+        let mut filtered = self.filtered.borrow_mut();
+        for (path, values) in paths.into_iter().zip(values) {
+            let is_specified = |value: &BString| value != "unspecified" && value != "unset";
+            let is_binary = values[0] == "unset" || values[1] == "unset";
+            let is_filtered = values.iter().any(is_specified) || (self.autocrlf && !is_binary);
+            filtered.insert(path.to_owned(), is_filtered);
+        }
+        Ok(())
+    }
+
+    /// Determine whether `path` is subject to content filters.
+    fn has_filters(&self, path: &BStr) -> Result<bool> {
+        if let Some(is_filtered) = self.filtered.borrow().get(path) {
+            return Ok(*is_filtered);
+        }
+        self.check_filters(&[path])?;
+        Ok(self.filtered.borrow()[path])
+    }
+
+    /// Compare the work tree content of a tracked path with its index entry.
+    pub(crate) fn worktree_state(&self, path: &BStr) -> Result<WorktreeState> {
+        let work_path = self.work_path(path);
+        let Ok(meta) = gix::index::fs::Metadata::from_path_no_follow(&work_path) else {
+            return Ok(WorktreeState::Missing);
+        };
+        if meta.is_dir() {
+            return Ok(WorktreeState::Missing);
+        }
+        let Some(entry) = self
+            .index
+            .entry_by_path_and_stage(path, Stage::Unconflicted)
+        else {
+            return Ok(WorktreeState::Changed);
+        };
+        if Stat::from_fs(&meta).is_ok_and(|stat| stat == entry.stat)
+            && !entry
+                .stat
+                .is_racy(self.index.timestamp(), Default::default())
+        {
+            return Ok(WorktreeState::Unchanged);
+        }
+        let (id, mode) = self.hash_work_path(path, &meta, false)?;
+        if id == entry.id && mode == entry.mode {
+            Ok(WorktreeState::Unchanged)
+        } else {
+            Ok(WorktreeState::Changed)
+        }
+    }
+
+    /// Get the object id of a path's stage 0 index entry.
+    pub(crate) fn entry_id(&self, path: &BStr) -> Option<gix::ObjectId> {
+        self.index
+            .entry_by_path_and_stage(path, Stage::Unconflicted)
+            .map(|entry| entry.id)
+    }
+
+    /// Compute the blob id of a path's work tree content, if it exists.
+    pub(crate) fn worktree_id(&self, path: &BStr) -> Result<Option<gix::ObjectId>> {
+        let work_path = self.work_path(path);
+        match gix::index::fs::Metadata::from_path_no_follow(&work_path) {
+            Ok(meta) if !meta.is_dir() => Ok(Some(self.hash_work_path(path, &meta, false)?.0)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Hash the content of a work tree path, optionally writing it to the object
+    /// database, and determine the index entry mode for the path.
+    ///
+    /// The content of files with content filters is converted by `git hash-object`.
+    fn hash_work_path(
+        &self,
+        path: &BStr,
+        meta: &gix::index::fs::Metadata,
+        write: bool,
+    ) -> Result<(gix::ObjectId, Mode)> {
+        let work_path = self.work_path(path);
+        let (data, mode) = if meta.is_symlink() {
+            let target = std::fs::read_link(&work_path)?;
+            let target = Vec::from_path_buf(target).map_err(|target| {
+                anyhow!("symbolic link target `{}` is not valid", target.display())
+            })?;
+            (target, Mode::SYMLINK)
+        } else {
+            let data = std::fs::read(&work_path).with_context(|| format!("reading `{path}`"))?;
+            (data, Mode::FILE)
+        };
+
+        let mode = if mode == Mode::SYMLINK {
+            mode
+        } else if self.trust_file_mode {
+            if meta.is_executable() {
+                Mode::FILE_EXECUTABLE
+            } else {
+                Mode::FILE
+            }
+        } else {
+            self.index
+                .entry_by_path_and_stage(path, Stage::Unconflicted)
+                .map(|entry| entry.mode)
+                .filter(|mode| *mode == Mode::FILE_EXECUTABLE)
+                .unwrap_or(Mode::FILE)
+        };
+
+        let id = if mode != Mode::SYMLINK && self.has_filters(path)? {
+            self.repo
+                .stupid()
+                .hash_object_at_path(&gix::path::from_bstr(path), &data, write)?
+        } else if write {
+            self.repo.write_blob(&data)?.detach()
+        } else {
+            gix::objs::compute_hash(self.repo.object_hash(), gix::objs::Kind::Blob, &data)
+        };
+        Ok((id, mode))
+    }
+
+    /// Update the index with the work tree content of `path`.
+    ///
+    /// Any unmerged entries for the path are replaced by the new stage 0 entry, thus
+    /// marking the path's conflict as resolved. Index entries that would conflict with
+    /// the new entry, i.e. a file where a leading directory of `path` is now or files
+    /// below `path` that has since become a file, are removed.
+    pub(crate) fn add(&mut self, path: &BStr) -> Result<()> {
+        let work_path = self.work_path(path);
+        let meta = gix::index::fs::Metadata::from_path_no_follow(&work_path)
+            .with_context(|| format!("reading `{path}`"))?;
+        let (id, mode) = self.hash_work_path(path, &meta, true)?;
+        let stat = Stat::from_fs(&meta)?;
+
+        self.index.remove_entries(|_, entry_path, _| {
+            is_within(entry_path, path) || is_within(path, entry_path)
+        });
+        self.index
+            .dangerously_push_entry(stat, id, Flags::empty(), mode, path);
+        self.index.sort_entries();
+        Ok(())
+    }
+
+    /// Remove all index entries for `path`, including unmerged entries.
+    pub(crate) fn remove(&mut self, path: &BStr) {
+        self.index
+            .remove_entries(|_, entry_path, _| entry_path == path);
+    }
+
+    /// Move the index entries at or below `src` to be at or below `dst`.
+    ///
+    /// The entries' stat information is refreshed from the work tree, so the move
+    /// should already have been performed in the work tree.
+    pub(crate) fn rename(&mut self, src: &BStr, dst: &BStr) -> Result<()> {
+        let mut moved = Vec::new();
+        for entry in self.index.entries() {
+            let entry_path = entry.path(&self.index);
+            if is_within(entry_path, src) {
+                let mut new_path = dst.to_owned();
+                new_path.push_str(&entry_path[src.len()..]);
+                moved.push((entry.clone(), new_path));
+            }
+        }
+        self.index.remove_entries(|_, entry_path, _| {
+            is_within(entry_path, src) || is_within(entry_path, dst)
+        });
+        for (entry, new_path) in moved {
+            let stat =
+                gix::index::fs::Metadata::from_path_no_follow(&self.work_path(new_path.as_bstr()))
+                    .ok()
+                    .and_then(|meta| Stat::from_fs(&meta).ok())
+                    .unwrap_or(entry.stat);
+            self.index.dangerously_push_entry(
+                stat,
+                entry.id,
+                entry.flags,
+                entry.mode,
+                new_path.as_bstr(),
+            );
+        }
+        self.index.sort_entries();
+        Ok(())
+    }
+
+    /// Write the updated index back to the index file and release the lock.
+    ///
+    /// The cached tree extension is not written since it may no longer match the
+    /// index entries. Other extensions cannot be written in-process, so when the index
+    /// has a split index, untracked cache, file system monitor, or resolve-undo
+    /// extension, the changed entries are instead applied with `git update-index`,
+    /// which preserves them.
+    pub(crate) fn write(self) -> Result<()> {
+        if self.split_index
+            || self.index.untracked().is_some()
+            || self.index.fs_monitor().is_some()
+            || self.index.resolve_undo().is_some()
+        {
+            return self.write_with_git();
+        }
+        let skip_hash = self
+            .repo
+            .config_snapshot()
+            .boolean("index.skipHash")
+            .unwrap_or(false);
+        let mut out = std::io::BufWriter::new(self.lock);
+        self.index.write_to(
+            &mut out,
+            gix::index::write::Options {
+                extensions: gix::index::write::Extensions::Given {
+                    tree_cache: false,
+                    end_of_index_entry: true,
+                },
+                skip_hash,
+            },
+        )?;
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .commit()
+            .map_err(|err| err.error)
+            .context("writing the index")?;
+        Ok(())
+    }
+    /// Apply the differences between the index file and the updated index with
+    /// `git update-index`.
+    ///
+    /// The lock is released first so that `git update-index` may take it. Entries added
+    /// this way have no stat information, so the index is refreshed afterwards.
+    fn write_with_git(self) -> Result<()> {
+        let Self {
+            repo, lock, index, ..
+        } = self;
+        let original = repo.open_index()?;
+        drop(lock);
+
+        let entries_by_path = |index: &gix::index::File| {
+            let mut by_path: BTreeMap<BString, Vec<(u32, gix::ObjectId, Mode)>> = BTreeMap::new();
+            for entry in index.entries() {
+                by_path
+                    .entry(entry.path(index).to_owned())
+                    .or_default()
+                    .push((entry.stage_raw(), entry.id, entry.mode));
+            }
+            by_path
+        };
+        let old_entries = entries_by_path(&original);
+        let new_entries = entries_by_path(&index);
+
+        let null_id = gix::ObjectId::null(repo.object_hash());
+        let mut index_info = Vec::new();
+        let paths: BTreeSet<&BString> = old_entries.keys().chain(new_entries.keys()).collect();
+        for path in paths {
+            let old = old_entries.get(path);
+            let new = new_entries.get(path);
+            if old == new {
+                continue;
+            }
+            if old.is_some() {
+                index_info.push_str(format!("0 {null_id}\t"));
+                index_info.push_str(path);
+                index_info.push_byte(0);
+            }
+            for (stage, id, mode) in new.into_iter().flatten() {
+                index_info.push_str(format!("{:o} {id} {stage}\t", mode.bits()));
+                index_info.push_str(path);
+                index_info.push_byte(0);
+            }
+        }
+
+        if !index_info.is_empty() {
+            let stupid = repo.stupid();
+            stupid.update_index_info(&index_info)?;
+            stupid.update_index_refresh()?;
+        }
+        Ok(())
+    }
+}
@@ -9,26 +9,32 @@
 
 mod alias;
 mod argset;
+mod autocorrect;
 mod branchloc;
 mod cmd;
 mod color;
+mod console;
 mod ext;
+mod help;
 mod hook;
+mod index;
+mod output;
 mod patch;
+mod patchfmt;
+mod pathspec;
 mod signal;
 mod stack;
 mod stupid;
 mod templates;
 mod wrap;
 
-use std::{ffi::OsString, fmt::Write as _, io::Write as _, path::PathBuf};
+use std::{ffi::OsString, fmt::Write as _, path::PathBuf};
 
 use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
 use clap::ArgMatches;
 use ext::RepositoryExtended;
 use stupid::StupidContext;
-use termcolor::WriteColor;
 
 use self::cmd::STGIT_COMMANDS;
 
@@ -41,6 +47,22 @@ const COMMAND_ERROR: i32 = 2;
 /// Process exit code for when a command halts due to merge conflicts.
 const CONFLICT_ERROR: i32 = 3;
 
+/// Process exit code for when a command is run outside of a git repository.
+const NOT_A_REPOSITORY_ERROR: i32 = 4;
+
+/// Process exit code for when a command requiring a work tree is run in a bare
+/// repository.
+const BARE_REPOSITORY_ERROR: i32 = 5;
+
+/// Process exit code for when HEAD is detached or does not point to a local branch.
+const DETACHED_HEAD_ERROR: i32 = 6;
+
+/// Process exit code for when the current branch is unborn.
+const UNBORN_BRANCH_ERROR: i32 = 7;
+
+/// Process exit code for when the branch's StGit stack is not initialized.
+const NO_STACK_ERROR: i32 = 8;
+
 /// Create base [`clap::Command`] instance.
 ///
 /// The base [`clap::Command`] returned by this function is intended to be supplemented
@@ -90,6 +112,38 @@ fn get_base_command(color_choice: Option<termcolor::ColorChoice>) -> clap::Comma
                 .value_name("path")
                 .value_hint(clap::ValueHint::AnyPath),
         )
+        .arg(
+            clap::Arg::new("git-path")
+                .long("git-path")
+                .help("Use <path> as the git executable")
+                .long_help(
+                    "Use '<path>' as the git executable for all git commands run by \
+                     StGit. The `STG_GIT` environment variable is used if this option \
+                     is not given. Otherwise, `git` is found via `PATH`.\n\
+                     \n\
+                     The executable must exist and be a supported version of git. \
+                     Hooks and user-configured commands run by StGit find this git \
+                     first in their `PATH`.",
+                )
+                .value_parser(clap::value_parser!(OsString))
+                .value_name("path")
+                .value_hint(clap::ValueHint::ExecutablePath),
+        )
+        .arg(
+            clap::Arg::new("git-opt")
+                .long("git-opt")
+                .help("Pass '-c <name>=<value>' to every git command")
+                .long_help(
+                    "Pass a configuration option to every git command run by StGit, as \
+                     with `git -c <name>=<value>`. This option may be repeated. Hooks \
+                     and user-configured commands run by StGit inherit these options \
+                     via `GIT_CONFIG_PARAMETERS`.",
+                )
+                .action(clap::ArgAction::Append)
+                .value_name("name=value")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+        .arg(output::get_quiet_arg().global(true).display_order(997))
         .arg(color::get_color_arg().global(true).display_order(998));
 
     // Ensure "stg" and not "stg.exe" shows up in usage on Windows.
@@ -138,6 +192,36 @@ pub(crate) fn get_full_command(
         .subcommand_value_name("command")
         .subcommands(STGIT_COMMANDS.iter().map(|command| (command.make)()))
         .subcommands(aliases.values().map(alias::Alias::make))
+        .after_help(help::make_topics_list())
+}
+
+/// Get the help topic requested with `stg help <topic>`, if any.
+///
+/// Help topics take precedence over aliases. Since no alias may be named "help", the
+/// topic names do not need to be distinct from the alias names.
+fn get_help_topic(sub_name: &str, user_args: &[OsString]) -> Option<&'static help::HelpTopic> {
+    if sub_name != "help" {
+        return None;
+    }
+    match user_args {
+        [topic] => topic.to_str().and_then(help::find_topic),
+        _ => None,
+    }
+}
+
+/// Print the help topic to stdout and exit.
+fn print_help_topic(topic: &help::HelpTopic, color_choice: Option<termcolor::ColorChoice>) -> ! {
+    let mut command = topic.make_command();
+    if let Some(color_choice) = color_choice {
+        command = command.color(color::termcolor_choice_to_clap(color_choice));
+    }
+    exit_with_result(command.print_long_help().map_err(Into::into), color_choice)
+}
+
+/// Terminate the process with the given exit code, first restoring the console.
+pub(crate) fn exit(code: i32) -> ! {
+    console::restore();
+    std::process::exit(code)
 }
 
 /// Main entry point for `stg` executable.
@@ -152,6 +236,8 @@ fn main() -> ! {
     // get the color choice.
     let color_choice = color::parse_color_choice(&argv);
 
+    console::setup();
+
     if let Err(e) = self::signal::setup() {
         exit_with_result(Err(e), color_choice)
     }
@@ -163,11 +249,15 @@ fn main() -> ! {
     // like a subcommand name (i.e. by using AppSettings::AllowExternalSubcommands).
     if let Ok(matches) = get_bootstrap_command(color_choice).try_get_matches_from(&argv) {
         // N.B. changing directories here, early, affects which aliases will ultimately
-        // be found.
-        if matches.get_flag("version") {
+        // be found. The git program is selected beforehand so that a relative
+        // --git-path is interpreted relative to the original working directory.
+        if let Err(e) = setup_git_program(&matches) {
+            exit_with_result(Err(e), color_choice)
+        } else if matches.get_flag("version") {
             execute_command(
                 &self::cmd::version::STGIT_COMMAND,
                 vec![argv[0].clone(), OsString::from("version")],
+                vec![String::from("version")],
                 color_choice,
             )
         } else if let Err(e) = change_directories(&matches) {
@@ -175,55 +265,10 @@ fn main() -> ! {
         } else if matches.get_flag("help-option") {
             full_app_help(argv, None, color_choice)
         } else if let Some((sub_name, sub_matches)) = matches.subcommand() {
-            // If the name matches any known subcommands, then only the Command for that
-            // particular command is constructed and the costs of searching for aliases
-            // and constructing all subcommands' Command instances are avoided.
-            if let Some(command) = STGIT_COMMANDS
-                .iter()
-                .find(|command| command.name == sub_name)
-            {
-                execute_command(command, argv, color_choice)
-            } else {
-                // If the subcommand name does not match a builtin subcommand, the
-                // aliases are located, which involves finding the Git repo and parsing
-                // the various levels of config files. If the subcommand name matches an
-                // alias, it is executed and the cost of constructing all subcommands'
-                // Command instances is still avoided.
-                match get_aliases() {
-                    Err(e) => exit_with_result(Err(e), color_choice),
-                    Ok((aliases, maybe_repo)) => {
-                        if let Some(alias) = aliases.get(sub_name) {
-                            let user_args: Vec<OsString> = sub_matches
-                                .get_many::<OsString>("")
-                                .map_or_else(Vec::new, |vals| vals.cloned().collect());
-
-                            match alias.kind {
-                                alias::AliasKind::Shell => execute_shell_alias(
-                                    alias,
-                                    user_args,
-                                    color_choice,
-                                    maybe_repo.as_ref(),
-                                ),
-                                alias::AliasKind::StGit => execute_stgit_alias(
-                                    alias,
-                                    &argv[0],
-                                    user_args,
-                                    color_choice,
-                                    &aliases,
-                                ),
-                            }
-                        } else {
-                            // If no command or alias matches can be determined from the
-                            // above process, then a complete clap::Command instance is
-                            // constructed with all subcommand Command instances for
-                            // each subcommand and alias. The command line is then
-                            // re-processed by this full-blown Command instance which is
-                            // expected to terminate with an appropriate help message.
-                            full_app_help(argv, Some(aliases), color_choice)
-                        }
-                    }
-                }
-            }
+            let user_args: Vec<OsString> = sub_matches
+                .get_many::<OsString>("")
+                .map_or_else(Vec::new, |vals| vals.cloned().collect());
+            execute_subcommand(argv, sub_name, user_args, true, color_choice)
         } else {
             full_app_help(argv, None, color_choice)
         }
@@ -234,6 +279,81 @@ fn main() -> ! {
     }
 }
 
+/// Execute the named subcommand, help topic, or alias.
+///
+/// If the name matches any known subcommands, then only the Command for that
+/// particular command is constructed and the costs of searching for aliases and
+/// constructing all subcommands' Command instances are avoided. A name that matches
+/// neither a subcommand nor an alias is autocorrected, when `autocorrect` is true, by
+/// rewriting `argv` with the resolved name.
+fn execute_subcommand(
+    mut argv: Vec<OsString>,
+    sub_name: &str,
+    user_args: Vec<OsString>,
+    autocorrect: bool,
+    color_choice: Option<termcolor::ColorChoice>,
+) -> ! {
+    if let Some(command) = STGIT_COMMANDS
+        .iter()
+        .find(|command| command.name == sub_name)
+    {
+        let command_line = command_line(
+            std::iter::once(std::ffi::OsStr::new(sub_name))
+                .chain(user_args.iter().map(OsString::as_os_str)),
+        );
+        execute_command(command, argv, command_line, color_choice)
+    } else if let Some(topic) = get_help_topic(sub_name, &user_args) {
+        print_help_topic(topic, color_choice)
+    } else {
+        // If the subcommand name does not match a builtin subcommand, the aliases are
+        // located, which involves finding the Git repo and parsing the various levels
+        // of config files. If the subcommand name matches an alias, it is executed and
+        // the cost of constructing all subcommands' Command instances is still
+        // avoided.
+        match get_aliases() {
+            Err(e) => exit_with_result(Err(e), color_choice),
+            Ok((aliases, maybe_repo)) => {
+                if let Some(alias) = aliases.get(sub_name) {
+                    match alias.kind {
+                        alias::AliasKind::Shell => {
+                            execute_shell_alias(alias, user_args, color_choice, maybe_repo.as_ref())
+                        }
+                        alias::AliasKind::StGit => {
+                            execute_stgit_alias(alias, &argv[0], user_args, color_choice, &aliases)
+                        }
+                    }
+                } else if !autocorrect {
+                    full_app_help(argv, Some(aliases), color_choice)
+                } else {
+                    let builtin_names = STGIT_COMMANDS.iter().map(|command| command.name);
+                    match autocorrect::resolve(
+                        sub_name,
+                        builtin_names,
+                        &aliases,
+                        maybe_repo.as_ref(),
+                    ) {
+                        Ok(Some(resolved_name)) => {
+                            // The subcommand name is followed by its arguments at the
+                            // end of argv.
+                            let sub_index = argv.len() - user_args.len() - 1;
+                            argv[sub_index] = OsString::from(&resolved_name);
+                            execute_subcommand(argv, &resolved_name, user_args, false, color_choice)
+                        }
+                        // If no command or alias matches can be determined from the
+                        // above process, then a complete clap::Command instance is
+                        // constructed with all subcommand Command instances for each
+                        // subcommand and alias. The command line is then re-processed
+                        // by this full-blown Command instance which is expected to
+                        // terminate with an appropriate help message.
+                        Ok(None) => full_app_help(argv, Some(aliases), color_choice),
+                        Err(e) => exit_with_result(Err(e), color_choice),
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Exit the program based on the provided [`Result`].
 ///
 /// Error results from conflicts trigger merge conflicts to be printed and an exit code
@@ -246,7 +366,7 @@ fn exit_with_result(result: Result<()>, color_choice: Option<termcolor::ColorCho
             // calling Command::try_get_matches_from().
             if let Some(clap_err) = e.downcast_ref::<clap::Error>() {
                 clap_err.print().expect("clap can print its error message");
-                std::process::exit(if clap_err.use_stderr() {
+                exit(if clap_err.use_stderr() {
                     GENERAL_ERROR
                 } else {
                     0
@@ -264,7 +384,20 @@ fn exit_with_result(result: Result<()>, color_choice: Option<termcolor::ColorCho
                         CONFLICT_ERROR
                     }
                     stack::TransactionError::CheckoutConflicts(_) => CONFLICT_ERROR,
+                    stack::TransactionError::Interrupted(_) => signal::SIGINT_CODE,
+                }
+            } else if let Some(e) = e.downcast_ref::<ext::ContextError>() {
+                match e {
+                    ext::ContextError::NotARepository => NOT_A_REPOSITORY_ERROR,
+                    ext::ContextError::BareRepository(_) => BARE_REPOSITORY_ERROR,
+                    ext::ContextError::DetachedHead(_) | ext::ContextError::NotLocalBranch(_) => {
+                        DETACHED_HEAD_ERROR
+                    }
+                    ext::ContextError::UnbornBranch(_) => UNBORN_BRANCH_ERROR,
+                    ext::ContextError::StackNotInitialized(_) => NO_STACK_ERROR,
                 }
+            } else if e.is::<autocorrect::UnresolvedCommandError>() {
+                GENERAL_ERROR
             } else if let Some(e) = e.downcast_ref::<cmd::Error>() {
                 match e {
                     cmd::Error::CausedConflicts(_) => CONFLICT_ERROR,
@@ -275,7 +408,7 @@ fn exit_with_result(result: Result<()>, color_choice: Option<termcolor::ColorCho
             }
         }
     };
-    std::process::exit(code)
+    exit(code)
 }
 
 /// Change the current directory based on any -C options from the top-level Command
@@ -292,6 +425,18 @@ fn change_directories(matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Select the git executable and options based on the top-level Command matches.
+fn setup_git_program(matches: &ArgMatches) -> Result<()> {
+    stupid::program::setup(
+        matches
+            .get_one::<OsString>("git-path")
+            .map(OsString::as_os_str),
+        matches
+            .get_many::<String>("git-opt")
+            .map_or_else(Vec::new, |opts| opts.cloned().collect()),
+    )
+}
+
 /// Display the help for the fully-instantiated top-level [`clap::Command`].
 ///
 /// Process `argv` using full top-level [`clap::Command`] instance with the expectation
@@ -379,7 +524,15 @@ fn full_app_help(
         .try_get_matches_from(argv)
         .expect_err("command line should not have viable matches");
     err.print().expect("failed to print clap error");
-    std::process::exit(if err.use_stderr() { GENERAL_ERROR } else { 0 })
+    exit(if err.use_stderr() { GENERAL_ERROR } else { 0 })
+}
+
+/// Get the command line to be recorded in stack states written by a subcommand.
+///
+/// The command line begins with the subcommand name. Top-level options, e.g. `-C`,
+/// are not recorded such that the command may be replayed elsewhere.
+fn command_line<'a>(args: impl Iterator<Item = &'a std::ffi::OsStr>) -> Vec<String> {
+    args.map(|arg| arg.to_string_lossy().to_string()).collect()
 }
 
 /// Execute regular StGit subcommand.
@@ -392,6 +545,7 @@ fn full_app_help(
 fn execute_command(
     command: &cmd::StGitCommand,
     argv: Vec<OsString>,
+    command_line: Vec<String>,
     color_choice: Option<termcolor::ColorChoice>,
 ) -> ! {
     match get_base_command(color_choice)
@@ -402,12 +556,17 @@ fn execute_command(
             let (_sub_name, sub_matches) = top_matches
                 .subcommand()
                 .expect("this subcommand is already known to be in argv");
-            exit_with_result((command.run)(sub_matches), color_choice)
+            output::Output::new(sub_matches).install();
+            exit_with_result(
+                cmd::check_repository_context(command)
+                    .and_then(|()| (command.run)(sub_matches, &command_line)),
+                color_choice,
+            )
         }
 
         Err(err) => {
             err.print().expect("clap can print its error message");
-            std::process::exit(if err.use_stderr() { GENERAL_ERROR } else { 0 })
+            exit(if err.use_stderr() { GENERAL_ERROR } else { 0 })
         }
     }
 }
@@ -447,6 +606,7 @@ fn execute_shell_alias(
         std::process::Command::new(&alias.command)
     };
     command.args(user_args);
+    stupid::program::git_program().setup_child_env(&mut command);
 
     if let Some(repo) = repo {
         if let Some(work_dir) = repo.work_dir() {
@@ -467,7 +627,7 @@ fn execute_shell_alias(
             alias.name, alias.command
         )
     }) {
-        Ok(status) => std::process::exit(status.code().unwrap_or(-1)),
+        Ok(status) => exit(status.code().unwrap_or(-1)),
         Err(e) => exit_with_result(Err(e), color_choice),
     }
 }
@@ -506,7 +666,8 @@ fn execute_stgit_alias(
                 .iter()
                 .find(|command| command.name == resolved_cmd_name)
             {
-                execute_command(command, argv, color_choice)
+                let command_line = command_line(argv[1..].iter().map(OsString::as_os_str));
+                execute_command(command, argv, command_line, color_choice)
             } else if aliases.contains_key(resolved_cmd_name) {
                 Err(anyhow!("recursive alias `{}`", alias.name))
             } else {
@@ -547,64 +708,18 @@ pub(crate) fn get_aliases() -> Result<(alias::Aliases, Option<gix::Repository>)>
     Ok((aliases, maybe_repo))
 }
 
-/// Print user-facing message to stderr.
-///
-/// Any parts of `msg` enclosed in backticks (``) are highlighted in yellow.
-fn print_message(
-    label: &str,
-    label_color: termcolor::Color,
-    stderr: &mut termcolor::StandardStream,
-    msg: &str,
-) {
-    let mut color = termcolor::ColorSpec::new();
-    stderr
-        .set_color(color.set_fg(Some(label_color)).set_bold(true))
-        .unwrap();
-    write!(stderr, "{label}: ").unwrap();
-    stderr
-        .set_color(color.set_fg(None).set_bold(false))
-        .unwrap();
-    let mut remainder: &str = msg;
-    loop {
-        let parts: Vec<&str> = remainder.splitn(3, '`').collect();
-        match parts.len() {
-            0 => {
-                writeln!(stderr).unwrap();
-                break;
-            }
-            1 => {
-                writeln!(stderr, "{}", parts[0]).unwrap();
-                break;
-            }
-            2 => {
-                writeln!(stderr, "{}`{}", parts[0], parts[1]).unwrap();
-                break;
-            }
-            3 => {
-                write!(stderr, "{}`", parts[0]).unwrap();
-                stderr
-                    .set_color(color.set_fg(Some(termcolor::Color::Yellow)))
-                    .unwrap();
-                write!(stderr, "{}", parts[1]).unwrap();
-                stderr.set_color(color.set_fg(None)).unwrap();
-                write!(stderr, "`").unwrap();
-                remainder = parts[2];
-            }
-            _ => panic!("unhandled split len"),
-        }
-    }
-}
-
 /// Print user-facing informational message to stderr.
+///
+/// The message is suppressed by `--quiet`.
 pub(crate) fn print_info_message(matches: &ArgMatches, msg: &str) {
-    let mut stderr = color::get_color_stderr(matches);
-    print_message("info", termcolor::Color::Blue, &mut stderr, msg);
+    output::Output::new(matches).info(msg);
 }
 
 /// Print user-facing warning message to stderr.
+///
+/// The message is suppressed by `-qq`.
 pub(crate) fn print_warning_message(matches: &ArgMatches, msg: &str) {
-    let mut stderr = color::get_color_stderr(matches);
-    print_message("warning", termcolor::Color::Yellow, &mut stderr, msg);
+    output::Output::new(matches).warning(msg);
 }
 
 /// Print user-facing error message to stderr.
@@ -619,12 +734,17 @@ fn print_error_message(color_choice: Option<termcolor::ColorChoice>, err: &anyho
     });
     let mut stderr = termcolor::StandardStream::stderr(color_choice);
     let err_string = format!("{err:#}");
-    print_message("error", termcolor::Color::Red, &mut stderr, &err_string);
+    output::print_message("error", termcolor::Color::Red, &mut stderr, &err_string);
 }
 
-/// Print file names with merge conflicts to stdout.
-// TODO: this should print to stderr instead.
+/// Print a report of the merge conflicts to stderr.
+///
+/// If the conflicts cannot be classified, the conflicted paths are listed using `git
+/// status` instead.
 fn print_merge_conflicts() {
+    if print_conflict_report().is_ok() {
+        return;
+    }
     let stupid = StupidContext::default();
     let cdup: OsString = stupid
         .rev_parse_cdup()
@@ -643,3 +763,37 @@ fn print_merge_conflicts() {
     };
     stupid.status_short(Some(pathspecs)).unwrap_or_default();
 }
+
+/// Print the conflicted patch along with the kind of conflict for each path.
+///
+/// The conflicted patch is the topmost applied patch, i.e. the patch whose push
+/// resulted in the conflicts.
+fn print_conflict_report() -> Result<()> {
+    use stack::{conflicts, InitializationPolicy, Stack, StackAccess, StackStateAccess};
+    use stupid::Stupid;
+
+    let repo = gix::Repository::open()?;
+    let unmerged = conflicts::parse_unmerged(&repo.stupid().ls_files_unmerged()?)?;
+    if unmerged.is_empty() {
+        return Ok(());
+    }
+    let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized).ok();
+    let patchname = stack
+        .as_ref()
+        .and_then(|stack| stack.applied().last().cloned());
+    let quote_names = stack
+        .as_ref()
+        .map_or_else(
+            || stack::StackConfig::current(&repo),
+            |stack| stack.config(),
+        )
+        .boolean("stgit.quotenames")
+        .unwrap_or(true);
+    conflicts::write_report(
+        &mut std::io::stderr().lock(),
+        patchname.as_ref(),
+        quote_names,
+        &conflicts::classify(&unmerged),
+    )?;
+    Ok(())
+}
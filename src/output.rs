@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Separation of data and commentary output and handling of `--quiet`.
+//!
+//! Data, such as the patch names printed by `stg series` or the commit id printed by
+//! `stg id`, is written to stdout. Everything else, e.g. the "Now at patch" summary of
+//! stack transactions, informational messages, and warnings, is commentary that is
+//! written to stderr. Commentary is subject to the command's verbosity, which is
+//! lowered by each `-q`/`--quiet` and raised by each `-v`/`--verbose` of commands
+//! having that option.
+
+use std::sync::OnceLock;
+
+use clap::{Arg, ArgMatches};
+use is_terminal::IsTerminal;
+use termcolor::{ColorChoice, StandardStream, WriteColor};
+
+/// How much commentary a command outputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Verbosity {
+    /// No commentary at all, not even warnings. Errors are still reported.
+    Silent,
+    /// Warnings, but no other commentary.
+    Quiet,
+    /// The default.
+    Normal,
+    /// Additional commentary, e.g. push timings.
+    Verbose,
+}
+
+impl Verbosity {
+    /// Determine the verbosity from the net count of `-v` and `-q` options.
+    fn from_level(level: i16) -> Self {
+        match level {
+            ..=-2 => Self::Silent,
+            -1 => Self::Quiet,
+            0 => Self::Normal,
+            _ => Self::Verbose,
+        }
+    }
+}
+
+/// Get the global `-q`/`--quiet` option.
+pub(crate) fn get_quiet_arg() -> Arg {
+    Arg::new("quiet")
+        .long("quiet")
+        .short('q')
+        .help("Suppress commentary; repeat to also suppress warnings")
+        .long_help(
+            "Suppress all commentary output, such as the summary of the resulting \
+             stack printed by commands that modify the stack and informational \
+             messages. Warnings are still printed unless this option is given twice. \
+             Errors are always printed.\n\
+             \n\
+             Commentary is always written to stderr; data output such as that of \
+             `stg series` or `stg id` is written to stdout and is not affected.",
+        )
+        .action(clap::ArgAction::Count)
+}
+
+/// Output context of the current command.
+///
+/// Provides the streams for data and commentary output according to the command's
+/// `--color` and verbosity options.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Output {
+    verbosity: Verbosity,
+    color_choice: ColorChoice,
+}
+
+/// Output context of the running command, as installed by [`Output::install()`].
+static CURRENT: OnceLock<Output> = OnceLock::new();
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            verbosity: Verbosity::Normal,
+            color_choice: ColorChoice::Auto,
+        }
+    }
+}
+
+impl Output {
+    /// Get the output context from a command's argument matches.
+    pub(crate) fn new(matches: &ArgMatches) -> Self {
+        let count = |id: &str| {
+            matches
+                .try_get_one::<u8>(id)
+                .ok()
+                .flatten()
+                .copied()
+                .map_or(0, i16::from)
+        };
+        Self {
+            verbosity: Verbosity::from_level(count("verbose") - count("quiet")),
+            color_choice: crate::color::get_color_choice(Some(matches)),
+        }
+    }
+
+    /// Make this the output context of the running command.
+    ///
+    /// This is used by [`Output::current()`] for output from code that has no access
+    /// to the command's argument matches.
+    pub(crate) fn install(self) {
+        let _ = CURRENT.set(self);
+    }
+
+    /// Get the output context of the running command.
+    pub(crate) fn current() -> Self {
+        CURRENT.get().copied().unwrap_or_default()
+    }
+
+    /// Whether commentary other than warnings is suppressed.
+    pub(crate) fn is_quiet(&self) -> bool {
+        self.verbosity < Verbosity::Normal
+    }
+
+    /// Whether additional commentary is requested.
+    pub(crate) fn is_verbose(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
+
+    /// Get the stream for data output, i.e. stdout.
+    pub(crate) fn data(&self) -> StandardStream {
+        StandardStream::stdout(self.stream_choice(std::io::stdout().is_terminal()))
+    }
+
+    /// Get the stream for commentary output.
+    ///
+    /// This is stderr, unless commentary is suppressed, in which case anything written
+    /// to the returned stream is discarded.
+    pub(crate) fn commentary(&self) -> Box<dyn WriteColor> {
+        if self.is_quiet() {
+            Box::new(termcolor::NoColor::new(std::io::sink()))
+        } else {
+            Box::new(self.stderr())
+        }
+    }
+
+    /// Print informational message to stderr, unless quiet.
+    pub(crate) fn info(&self, msg: &str) {
+        if !self.is_quiet() {
+            print_message("info", termcolor::Color::Blue, &mut self.stderr(), msg);
+        }
+    }
+
+    /// Print warning message to stderr, unless silent.
+    pub(crate) fn warning(&self, msg: &str) {
+        if self.verbosity > Verbosity::Silent {
+            print_message("warning", termcolor::Color::Yellow, &mut self.stderr(), msg);
+        }
+    }
+
+    fn stderr(&self) -> StandardStream {
+        StandardStream::stderr(self.stream_choice(std::io::stderr().is_terminal()))
+    }
+
+    fn stream_choice(&self, is_terminal: bool) -> ColorChoice {
+        if self.color_choice == ColorChoice::Auto && !is_terminal {
+            ColorChoice::Never
+        } else {
+            self.color_choice
+        }
+    }
+}
+
+/// Print user-facing message with the given label.
+///
+/// Any parts of `msg` enclosed in backticks (``) are highlighted in yellow.
+pub(crate) fn print_message(
+    label: &str,
+    label_color: termcolor::Color,
+    stream: &mut impl WriteColor,
+    msg: &str,
+) {
+    let mut color = termcolor::ColorSpec::new();
+    stream
+        .set_color(color.set_fg(Some(label_color)).set_bold(true))
+        .unwrap();
+    write!(stream, "{label}: ").unwrap();
+    stream
+        .set_color(color.set_fg(None).set_bold(false))
+        .unwrap();
+    let mut remainder: &str = msg;
+    loop {
+        let parts: Vec<&str> = remainder.splitn(3, '`').collect();
+        match parts.len() {
+            0 => {
+                writeln!(stream).unwrap();
+                break;
+            }
+            1 => {
+                writeln!(stream, "{}", parts[0]).unwrap();
+                break;
+            }
+            2 => {
+                writeln!(stream, "{}`{}", parts[0], parts[1]).unwrap();
+                break;
+            }
+            3 => {
+                write!(stream, "{}`", parts[0]).unwrap();
+                stream
+                    .set_color(color.set_fg(Some(termcolor::Color::Yellow)))
+                    .unwrap();
+                write!(stream, "{}", parts[1]).unwrap();
+                stream.set_color(color.set_fg(None)).unwrap();
+                write!(stream, "`").unwrap();
+                remainder = parts[2];
+            }
+            _ => panic!("unhandled split len"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verbosity(args: &[&str]) -> Verbosity {
+        let command = clap::Command::new("stg")
+            .arg(get_quiet_arg().global(true))
+            .arg(crate::color::get_color_arg().global(true))
+            .subcommand(
+                clap::Command::new("push").arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .action(clap::ArgAction::Count),
+                ),
+            )
+            .subcommand(clap::Command::new("id").arg(Arg::new("verbose").short('v')));
+        let matches = command.get_matches_from(args);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        Output::new(sub_matches).verbosity
+    }
+
+    #[test]
+    fn count_quiet_and_verbose() {
+        assert_eq!(verbosity(&["stg", "push"]), Verbosity::Normal);
+        assert_eq!(verbosity(&["stg", "-q", "push"]), Verbosity::Quiet);
+        assert_eq!(verbosity(&["stg", "push", "-q"]), Verbosity::Quiet);
+        assert_eq!(verbosity(&["stg", "push", "-qq"]), Verbosity::Silent);
+        assert_eq!(verbosity(&["stg", "push", "-qqq"]), Verbosity::Silent);
+        assert_eq!(verbosity(&["stg", "push", "-v"]), Verbosity::Verbose);
+        assert_eq!(verbosity(&["stg", "push", "-vv"]), Verbosity::Verbose);
+        assert_eq!(verbosity(&["stg", "push", "-v", "-q"]), Verbosity::Normal);
+        assert_eq!(verbosity(&["stg", "push", "-qq", "-v"]), Verbosity::Quiet);
+    }
+
+    #[test]
+    fn ignore_non_count_verbose() {
+        assert_eq!(verbosity(&["stg", "id", "-v", "3"]), Verbosity::Normal);
+        assert_eq!(verbosity(&["stg", "id", "-q", "-v", "3"]), Verbosity::Quiet);
+    }
+}
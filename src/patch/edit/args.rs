@@ -8,7 +8,7 @@ use clap::{
 };
 
 use super::parse::{parse_email, parse_name, parse_name_email2};
-use crate::{argset, ext::TimeExtended};
+use crate::argset;
 
 /// Add patch editing options to a StGit command.
 pub(crate) fn add_args(
@@ -97,7 +97,7 @@ pub(crate) fn add_args(
         .arg(
             Arg::new("no-verify")
                 .long("no-verify")
-                .help("Disable commit-msg hook")
+                .help("Disable pre-commit and commit-msg hooks")
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(
@@ -213,14 +213,15 @@ pub(crate) fn add_args(
             Arg::new("authdate")
                 .long("authdate")
                 .help("Set the author date")
-                .long_help(
+                .long_help(format!(
                     "Set the date the patch was authored.\n\
                      \n\
-                     Use \"now\" to use the current time and date.",
-                )
+                     {}",
+                    argset::DATE_FORMATS_HELP
+                ))
                 .value_name("date")
                 .num_args(1)
-                .value_parser(ValueParser::new(gix::date::Time::parse_time))
+                .value_parser(argset::parse_time)
                 .value_hint(ValueHint::Other),
         )
         .arg(argset::committer_date_is_author_date_arg());
@@ -59,11 +59,18 @@ pub(super) struct EditablePatchDescription {
     pub diff: Option<DiffBuffer>,
 }
 
-const CUT_LINE: &str = "# ------------------------ >8 ------------------------\n";
+/// Make the cut line separating the diff from the rest of the patch description.
+fn cut_line(comment: &str) -> String {
+    format!("{comment} ------------------------ >8 ------------------------\n")
+}
 
 impl EditablePatchDescription {
     /// Write user-editable patch description to the provided stream.
-    pub(super) fn write<S: Write>(&self, stream: &mut S) -> Result<()> {
+    ///
+    /// The instructions and the cut line are written as comments starting with
+    /// `comment`. The instructions are written with '#' as the comment character,
+    /// which is replaced by `comment`.
+    pub(super) fn write<S: Write>(&self, stream: &mut S, comment: &str) -> Result<()> {
         let patchname = if let Some(patchname) = &self.patchname {
             patchname.as_ref()
         } else {
@@ -84,16 +91,16 @@ impl EditablePatchDescription {
         let message = self.message.trim_end_matches('\n');
         write!(stream, "\n{message}\n")?;
         if let Some(instruction) = self.instruction {
-            write!(stream, "\n{instruction}")?;
+            write!(stream, "\n{}", instruction.replace('#', comment))?;
         } else {
             writeln!(stream)?;
         }
         if let Some(diff) = self.diff.as_ref() {
             if let Some(diff_instruction) = self.diff_instruction {
-                write!(stream, "{diff_instruction}")?;
+                write!(stream, "{}", diff_instruction.replace('#', comment))?;
             }
-            stream.write_all(CUT_LINE.as_bytes())?;
-            writeln!(stream, "# Do not modify or remove the line above.")?;
+            stream.write_all(cut_line(comment).as_bytes())?;
+            writeln!(stream, "{comment} Do not modify or remove the line above.")?;
             stream.write_all(diff.as_ref())?;
         }
         Ok(())
@@ -141,13 +148,11 @@ pub(super) struct EditedPatchDescription {
     pub diff: Option<DiffBuffer>,
 }
 
-impl TryFrom<&[u8]> for EditedPatchDescription {
-    type Error = anyhow::Error;
-
+impl EditedPatchDescription {
     /// Attempt to parse user-edited patch description.
     ///
-    /// Any lines starting with '#' are treated as comments and discarded, except for
-    /// the cut line which separates the headers and message from the diff content.
+    /// Any lines starting with `comment` are treated as comments and discarded, except
+    /// for the cut line which separates the headers and message from the diff content.
     ///
     /// The "Patch", "Author", and "Date" headers, if present, must be the first three
     /// lines of the message. This rigidity is done to allow the message, which follows
@@ -156,7 +161,8 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
     /// If all headers are absent and the trimmed message is empty, an error is
     /// returned. Blanking-out the headers and message is thus a mechanism for the user
     /// to abort the interactive edit.
-    fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
+    pub(super) fn parse(buf: &[u8], comment: &str) -> Result<Self> {
+        let cut_line = cut_line(comment);
         let mut raw_patchname: Option<Option<String>> = None;
         let mut raw_author: Option<Option<String>> = None;
         let mut raw_authdate: Option<Option<String>> = None;
@@ -172,10 +178,10 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
             .enumerate()
         {
             pos += line.len();
-            if line.starts_with(CUT_LINE.as_bytes()) {
+            if line.starts_with(cut_line.as_bytes()) {
                 consume_diff = true;
                 break;
-            } else if line.starts_with(b"#") {
+            } else if line.starts_with(comment.as_bytes()) {
                 continue;
             }
 
@@ -283,7 +289,7 @@ impl TryFrom<&[u8]> for EditedPatchDescription {
         let diff = if consume_diff {
             // Skip any comment lines after the cut line.
             for line in buf[pos..].split_inclusive(|&b| b == b'\n') {
-                if line.starts_with(b"#") {
+                if line.starts_with(comment.as_bytes()) {
                     pos += line.len();
                 } else {
                     break;
@@ -406,7 +412,7 @@ mod tests {
         };
 
         let mut buf: Vec<u8> = vec![];
-        editable.write(&mut buf).unwrap();
+        editable.write(&mut buf, "#").unwrap();
 
         assert_eq!(
             buf.to_str().unwrap(),
@@ -419,7 +425,7 @@ mod tests {
              # Instruction\n",
         );
 
-        let edited = EditedPatchDescription::try_from(buf.as_slice()).unwrap();
+        let edited = EditedPatchDescription::parse(buf.as_slice(), "#").unwrap();
 
         compare_patch_descs(&edited, &editable);
     }
@@ -440,7 +446,7 @@ mod tests {
         };
 
         let mut buf: Vec<u8> = vec![];
-        patch_desc.write(&mut buf).unwrap();
+        patch_desc.write(&mut buf, "#").unwrap();
 
         assert_eq!(
             buf.to_str().unwrap(),
@@ -453,7 +459,7 @@ mod tests {
              # Instruction\n",
         );
 
-        let edited_desc = EditedPatchDescription::try_from(buf.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(buf.as_slice(), "#").unwrap();
 
         compare_patch_descs(&edited_desc, &patch_desc);
     }
@@ -481,7 +487,7 @@ mod tests {
         };
 
         let mut buf: Vec<u8> = vec![];
-        patch_desc.write(&mut buf).unwrap();
+        patch_desc.write(&mut buf, "#").unwrap();
 
         assert_eq!(
             buf.to_str().unwrap(),
@@ -499,7 +505,7 @@ mod tests {
              # Instruction\n",
         );
 
-        let edited_desc = EditedPatchDescription::try_from(buf.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(buf.as_slice(), "#").unwrap();
 
         compare_patch_descs(&edited_desc, &patch_desc);
     }
@@ -532,7 +538,7 @@ mod tests {
         };
 
         let mut buf: Vec<u8> = vec![];
-        pd.write(&mut buf).unwrap();
+        pd.write(&mut buf, "#").unwrap();
 
         assert_eq!(
             buf.to_str().unwrap(),
@@ -559,8 +565,59 @@ mod tests {
              \\ No newline at end of file\n",
         );
 
-        let edited_desc = EditedPatchDescription::try_from(buf.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(buf.as_slice(), "#").unwrap();
+
+        compare_patch_descs(&edited_desc, &pd);
+    }
+
+    #[test]
+    fn with_custom_comment() {
+        let pd = EditablePatchDescription {
+            patchname: Some("patch".parse::<PatchName>().unwrap()),
+            author: Some(gix::actor::Signature {
+                name: BString::from("The Author"),
+                email: BString::from("author@example.com"),
+                time: gix::date::Time::new(987654321, 21600),
+            }),
+            message: "Subject\n\n#123 is fixed\n".to_string(),
+            instruction: Some("# Lines starting with '#' are ignored.\n"),
+            diff_instruction: Some("# Diff instruction\n"),
+            diff: Some(DiffBuffer(BString::from(
+                "diff --git a/foo.txt b/foo.txt\n\
+                 --- a/foo.txt\n\
+                 +++ b/foo.txt\n\
+                 @@ -1 +1 @@\n\
+                 -hello\n\
+                 +goodbye\n",
+            ))),
+        };
+
+        let mut buf: Vec<u8> = vec![];
+        pd.write(&mut buf, ";").unwrap();
+
+        assert_eq!(
+            buf.to_str().unwrap(),
+            "Patch:  patch\n\
+             Author: The Author <author@example.com>\n\
+             Date:   2001-04-19 10:25:21 +0600\n\
+             \n\
+             Subject\n\
+             \n\
+             #123 is fixed\n\
+             \n\
+             ; Lines starting with ';' are ignored.\n\
+             ; Diff instruction\n\
+             ; ------------------------ >8 ------------------------\n\
+             ; Do not modify or remove the line above.\n\
+             diff --git a/foo.txt b/foo.txt\n\
+             --- a/foo.txt\n\
+             +++ b/foo.txt\n\
+             @@ -1 +1 @@\n\
+             -hello\n\
+             +goodbye\n",
+        );
 
+        let edited_desc = EditedPatchDescription::parse(buf.as_slice(), ";").unwrap();
         compare_patch_descs(&edited_desc, &pd);
     }
 
@@ -586,7 +643,7 @@ mod tests {
         };
 
         let mut buf: Vec<u8> = vec![];
-        patch_desc.write(&mut buf).unwrap();
+        patch_desc.write(&mut buf, "#").unwrap();
 
         assert_eq!(
             buf.to_str().unwrap(),
@@ -619,7 +676,7 @@ mod tests {
         \n\
         # Instruction\n";
 
-        let edited_desc = EditedPatchDescription::try_from(edited.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(edited.as_slice(), "#").unwrap();
 
         compare_patch_descs(&edited_desc, &patch_desc);
     }
@@ -635,7 +692,7 @@ mod tests {
         \n\
         # Instruction\n";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
 
         let expected = EditedPatchDescription {
             patchname: None,
@@ -662,7 +719,7 @@ mod tests {
         \n\
         # Instruction\n";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
 
         let commented_time = gix::date::Time::new(987654321, 21600);
         assert!(edited_desc.author.is_some());
@@ -700,7 +757,7 @@ mod tests {
         \n\
         # Instruction\n";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
 
         let expected = EditedPatchDescription {
             patchname: Some(Some("patch".parse::<PatchName>().unwrap())),
@@ -728,7 +785,7 @@ mod tests {
         \n\
         # Instruction\n";
 
-        assert!(EditedPatchDescription::try_from(description.as_slice()).is_err());
+        assert!(EditedPatchDescription::parse(description.as_slice(), "#").is_err());
     }
 
     #[test]
@@ -741,7 +798,7 @@ mod tests {
         \n\
         # Instruction\n";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
         assert_eq!(edited_desc.message, "Subject\n");
     }
 
@@ -758,7 +815,7 @@ mod tests {
         \n\
         # Instruction\n";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
 
         assert_eq!(edited_desc.message, "Subject\n");
     }
@@ -773,7 +830,7 @@ mod tests {
         Subject\n\
         # Instruction\n";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
 
         assert_eq!(edited_desc.message, "Subject\n");
     }
@@ -787,7 +844,7 @@ mod tests {
         \n\
         Subject";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
 
         assert_eq!(edited_desc.message, "Subject\n");
     }
@@ -812,7 +869,7 @@ mod tests {
         # Instruction\n\
         \n";
 
-        let edited_desc = EditedPatchDescription::try_from(description.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(description.as_slice(), "#").unwrap();
 
         assert_eq!(
             edited_desc.message,
@@ -836,7 +893,7 @@ mod tests {
             b"# ------------------------ >8 ------------------------  \n",
             b"# ------------------------ >8 ------------------------\n  \n",
         ] {
-            let result = EditedPatchDescription::try_from(description);
+            let result = EditedPatchDescription::parse(description, "#");
             assert!(result.is_err());
         }
     }
@@ -849,7 +906,7 @@ mod tests {
         Body1.\n\
         Body2.\n";
 
-        let edited_desc = EditedPatchDescription::try_from(edited.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(edited.as_slice(), "#").unwrap();
 
         assert!(edited_desc.patchname.is_none());
         assert!(edited_desc.author.is_none());
@@ -869,7 +926,7 @@ mod tests {
         Body1.\n\
         Body2.\n";
 
-        let edited_desc = EditedPatchDescription::try_from(edited.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(edited.as_slice(), "#").unwrap();
 
         assert!(edited_desc.patchname.unwrap().is_none());
         assert!(edited_desc.author.unwrap().is_none());
@@ -887,7 +944,7 @@ mod tests {
         ---\n\
         Extra.\n";
 
-        let edited_desc = EditedPatchDescription::try_from(edited.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(edited.as_slice(), "#").unwrap();
 
         assert!(edited_desc.patchname.is_none());
         assert!(edited_desc.author.is_none());
@@ -917,7 +974,7 @@ mod tests {
         ---\n\
         Extra.\n";
 
-        let edited_desc = EditedPatchDescription::try_from(edited.as_slice()).unwrap();
+        let edited_desc = EditedPatchDescription::parse(edited.as_slice(), "#").unwrap();
 
         assert!(edited_desc.patchname.unwrap().is_none());
         assert!(edited_desc.author.unwrap().is_none());
@@ -10,9 +10,10 @@ use std::{
 };
 
 use anyhow::{anyhow, Context, Result};
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 
 use super::description::{EditablePatchDescription, EditedPatchDescription};
+use crate::stack::StackConfig;
 
 pub(crate) static EDIT_INSTRUCTION: &str = "\
     # Please enter the message for your patch. Lines starting with\n\
@@ -30,13 +31,54 @@ static EDIT_FILE_NAME: &str = ".stgit-edit.txt";
 /// Default file name for interactively editable patch description with diff.
 static EDIT_FILE_NAME_DIFF: &str = ".stgit-edit.patch";
 
+/// Comment characters tried, in order, when `core.commentChar` is "auto".
+///
+/// These are the same candidates used by git.
+const AUTO_COMMENT_CHARS: &str = "#;@!$%^&|:";
+
+/// Determine the string starting comment lines in the editable patch description.
+///
+/// Like git, this is '#' unless configured with `core.commentString` or
+/// `core.commentChar`. When configured as "auto", the first candidate character that
+/// does not start any line of `message` is used. Without a message, e.g. when reading
+/// a description from a file, "auto" means '#'.
+pub(crate) fn comment_string(config: &StackConfig, message: Option<&str>) -> Result<String> {
+    let Some(value) = config
+        .string("core.commentString")
+        .or_else(|| config.string("core.commentChar"))
+    else {
+        return Ok("#".to_string());
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| anyhow!("core.commentChar is not valid UTF-8"))?;
+    if value == "auto" {
+        let Some(message) = message else {
+            return Ok("#".to_string());
+        };
+        AUTO_COMMENT_CHARS
+            .chars()
+            .find(|&c| !message.lines().any(|line| line.starts_with(c)))
+            .map(String::from)
+            .ok_or_else(|| {
+                anyhow!("unable to select a comment character that is not used in the message")
+            })
+    } else if value.is_empty() || value.contains(['\n', '\r']) || value.trim().is_empty() {
+        Err(anyhow!("invalid core.commentChar `{value}`"))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
 /// Conduct interactive patch edit session.
 ///
 /// The patch description is written to a file, the user's editor of choice is invoked,
-/// and the modified description is read-back and parsed.
+/// and the modified description is read-back and parsed. Comment lines start with
+/// `comment`, see [`comment_string()`].
 pub(super) fn edit_interactive(
     patch_desc: &EditablePatchDescription,
-    config: &gix::config::Snapshot,
+    comment: &str,
+    config: &StackConfig,
 ) -> Result<EditedPatchDescription> {
     let filename = if patch_desc.diff.is_some() {
         EDIT_FILE_NAME_DIFF
@@ -47,11 +89,11 @@ pub(super) fn edit_interactive(
     {
         let file = File::create(filename)?;
         let mut stream = BufWriter::new(file);
-        patch_desc.write(&mut stream)?;
+        patch_desc.write(&mut stream, comment)?;
     }
 
     let buf = call_editor(filename, config)?;
-    let edited_desc = EditedPatchDescription::try_from(buf.as_slice())?;
+    let edited_desc = EditedPatchDescription::parse(buf.as_slice(), comment)?;
     Ok(edited_desc)
 }
 
@@ -69,10 +111,7 @@ fn is_terminal_dumb() -> bool {
 /// Run the user's editor to edit the file at `path`.
 ///
 /// Upon successfully reading back the file's content, the file is deleted.
-pub(crate) fn call_editor<P: AsRef<Path>>(
-    path: P,
-    config: &gix::config::Snapshot,
-) -> Result<BString> {
+pub(crate) fn call_editor<P: AsRef<Path>>(path: P, config: &StackConfig) -> Result<BString> {
     let editor = get_editor(config)?;
 
     if editor != *":" {
@@ -124,7 +163,7 @@ pub(crate) fn call_editor<P: AsRef<Path>>(
 }
 
 /// Determine user's editor of choice based on config and environment.
-fn get_editor(config: &gix::config::Snapshot) -> Result<OsString> {
+fn get_editor(config: &StackConfig) -> Result<OsString> {
     let editor = if let Some(editor) = std::env::var_os("GIT_EDITOR") {
         editor
     } else if let Some(editor) = config
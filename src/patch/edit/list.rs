@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Interactive editing of lists of patch names.
+
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+
+use super::interactive::{call_editor, comment_string};
+use crate::{
+    patch::{LocationConstraint, PatchName},
+    stack::{StackConfig, StackStateAccess},
+};
+
+/// Default file name for interactively editable patch lists.
+static EDIT_LIST_FILE_NAME: &str = ".stgit-patch-list.txt";
+
+/// Interactively edit a list of patches in the user's editor.
+///
+/// Each of `patchnames` is written on its own line, followed by the patch's subject as
+/// a comment, and then by the `instructions` comment lines. Once the editor exits, the
+/// patch names remaining on non-comment lines are returned in the order given.
+///
+/// The `instructions` are written with '#' as the comment character, which is replaced
+/// by the comment string configured with `core.commentChar`.
+///
+/// Each returned patch must exist in the stack, satisfy `constraint`, and be listed
+/// only once. An empty list is returned as-is; it is up to the caller to decide whether
+/// that aborts the operation.
+pub(crate) fn edit_patch_list<'repo>(
+    stack: &impl StackStateAccess<'repo>,
+    patchnames: &[PatchName],
+    constraint: LocationConstraint,
+    instructions: &str,
+    config: &StackConfig,
+) -> Result<Vec<PatchName>> {
+    let comment = comment_string(config, None)?;
+    std::fs::write(
+        EDIT_LIST_FILE_NAME,
+        make_list_template(stack, patchnames, instructions, &comment),
+    )?;
+    let buf = call_editor(EDIT_LIST_FILE_NAME, config)?;
+    let buf = buf
+        .to_str()
+        .map_err(|_| anyhow!("`{EDIT_LIST_FILE_NAME}` is not valid UTF-8"))?;
+
+    let mut edited: Vec<PatchName> = Vec::new();
+    for (line_num, patchname) in parse_patch_list(buf, &comment)? {
+        let context = || format!("{EDIT_LIST_FILE_NAME}:{line_num}");
+        if !stack.has_patch(&patchname) {
+            return Err(anyhow!("patch `{}` does not exist", patchname.quoted()))
+                .with_context(context);
+        } else if edited.contains(&patchname) {
+            return Err(anyhow!(
+                "patch `{}` is listed more than once",
+                patchname.quoted()
+            ))
+            .with_context(context);
+        }
+        edited.push(
+            patchname
+                .constrain(stack, constraint)
+                .with_context(context)?,
+        );
+    }
+    Ok(edited)
+}
+
+/// Render the editable list of patches.
+fn make_list_template<'repo>(
+    stack: &impl StackStateAccess<'repo>,
+    patchnames: &[PatchName],
+    instructions: &str,
+    comment: &str,
+) -> String {
+    let name_width = patchnames.iter().map(PatchName::len).max().unwrap_or(0);
+    let mut template = String::with_capacity(4096);
+    for patchname in patchnames {
+        let subject = stack
+            .get_patch_commit(patchname)
+            .message()
+            .map(|message_ref| message_ref.title.to_str_lossy())
+            .unwrap_or_default()
+            .replace(['\r', '\n'], " ")
+            .trim()
+            .to_owned();
+        writeln!(template, "{patchname:name_width$} {comment} {subject}").unwrap();
+    }
+    template.push_str(&instructions.replace('#', comment));
+    template
+}
+
+/// Parse the patch names from an edited list, along with their line numbers.
+///
+/// Everything following `comment` on a line is a comment. Blank lines are ignored.
+fn parse_patch_list(buf: &str, comment: &str) -> Result<Vec<(usize, PatchName)>> {
+    let mut patchnames = Vec::new();
+    for (i, line) in buf.lines().enumerate() {
+        let name = if let Some((name, _comment)) = line.split_once(comment) {
+            name
+        } else {
+            line
+        }
+        .trim();
+        if !name.is_empty() {
+            let patchname = PatchName::from_str(name)
+                .with_context(|| format!("{EDIT_LIST_FILE_NAME}:{}", i + 1))?;
+            patchnames.push((i + 1, patchname));
+        }
+    }
+    Ok(patchnames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_edited_list() {
+        let buf = "\
+            p2   # second patch\n\
+            \n\
+            # p1 # first patch\n\
+              p0 \n\
+            # Instructions\n";
+        let parsed: Vec<(usize, String)> = parse_patch_list(buf, "#")
+            .unwrap()
+            .into_iter()
+            .map(|(line_num, pn)| (line_num, pn.to_string()))
+            .collect();
+        assert_eq!(parsed, [(1, "p2".to_string()), (4, "p0".to_string())]);
+
+        let buf = "p#2 ; second patch\n; p1 ; first patch\n";
+        let parsed: Vec<String> = parse_patch_list(buf, ";")
+            .unwrap()
+            .into_iter()
+            .map(|(_, pn)| pn.to_string())
+            .collect();
+        assert_eq!(parsed, ["p#2"]);
+    }
+
+    #[test]
+    fn parse_bad_list() {
+        let err = parse_patch_list("p0\nnot a name\n", "#").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            ".stgit-patch-list.txt:2: invalid patch name `not a name`: \
+             patch name may not contain whitespace"
+        );
+    }
+}
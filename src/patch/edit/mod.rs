@@ -9,6 +9,7 @@
 mod args;
 mod description;
 mod interactive;
+mod list;
 mod parse;
 mod trailers;
 
@@ -23,15 +24,21 @@ use anyhow::{anyhow, Result};
 use bstr::{BString, ByteSlice};
 use clap::ArgMatches;
 
-pub(crate) use self::{args::add_args, interactive::call_editor, parse::parse_name_email};
+pub(crate) use self::{
+    args::add_args,
+    interactive::{call_editor, comment_string},
+    list::edit_patch_list,
+    parse::parse_name_email,
+};
 use self::{
     description::{DiffBuffer, EditablePatchDescription, EditedPatchDescription},
     interactive::edit_interactive,
 };
-use super::PatchName;
+use super::{name::NameTemplate, PatchName};
 use crate::{
+    argset,
     ext::{CommitExtended, RepositoryExtended, SignatureExtended},
-    stack::StackStateAccess,
+    stack::StackAccess,
     stupid::Stupid,
     wrap::Message,
 };
@@ -80,6 +87,10 @@ pub(crate) struct EditBuilder<'a, 'repo> {
     allow_diff_edit: bool,
     allow_implicit_edit: bool,
     allow_template_save: bool,
+    diff_base_tree_id: Option<gix::ObjectId>,
+    comment: Option<String>,
+    extra_trailers: Vec<(String, String)>,
+    fixed_date: Option<gix::date::Time>,
     overlay: Overlay,
 }
 
@@ -175,6 +186,14 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         self
     }
 
+    /// Use the given date, if any, as the committer date of the edited patch commit.
+    ///
+    /// The date is also the author date of a new patch without a default author.
+    pub(crate) fn fixed_date(mut self, date: Option<gix::date::Time>) -> Self {
+        self.fixed_date = date;
+        self
+    }
+
     /// Set the default message for the patch.
     ///
     /// This is useful for `stg import` where the imported patch may have a message from
@@ -184,6 +203,14 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         self
     }
 
+    /// Add a trailer to the patch message.
+    ///
+    /// Extra trailers follow any trailers added with the patch edit options.
+    pub(crate) fn extra_trailer(mut self, trailer: &str, value: String) -> Self {
+        self.extra_trailers.push((trailer.to_string(), value));
+        self
+    }
+
     /// Set a tree id to override the tree id from the existing patch commit.
     ///
     /// This is needed for commands that modify a patch's tree in addition to exposing
@@ -202,6 +229,28 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         self
     }
 
+    /// Show the diff from the given tree in an interactive edit.
+    ///
+    /// By default, the diff is only shown with `--diff` or `stgit.edit.verbose` and is
+    /// the patch's entire diff relative to its parent. When a tree is provided, the
+    /// diff from that tree to the patch's tree is always shown, e.g. such that only the
+    /// changes being added to an existing patch are shown.
+    pub(crate) fn show_diff_from(mut self, tree_id: Option<gix::ObjectId>) -> Self {
+        self.diff_base_tree_id = tree_id;
+        self
+    }
+
+    /// Set the string starting comment lines in an interactive edit.
+    ///
+    /// By default, the comment string is determined by [`comment_string()`] based on
+    /// the configuration and the message. This is needed when the default message
+    /// already contains comment lines, which would otherwise affect the automatic
+    /// choice of the comment character with `core.commentChar=auto`.
+    pub(crate) fn comment_string(mut self, comment: String) -> Self {
+        self.comment = Some(comment);
+        self
+    }
+
     /// Perform the patch edits.
     ///
     /// The provided `matches` must come from a [`clap::Command`] that was setup with
@@ -215,7 +264,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
     /// which case potentially new patch commit and patch name are returned.
     pub(crate) fn edit(
         self,
-        stack_state: &impl StackStateAccess<'repo>,
+        stack_state: &impl StackAccess<'repo>,
         repo: &'repo gix::Repository,
         matches: &ArgMatches,
     ) -> Result<EditOutcome> {
@@ -228,6 +277,10 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             allow_diff_edit,
             allow_implicit_edit,
             allow_template_save,
+            diff_base_tree_id,
+            comment,
+            extra_trailers,
+            fixed_date,
             overlay:
                 Overlay {
                     author: overlay_author,
@@ -238,8 +291,16 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         } = self;
 
         let stupid = repo.stupid();
-        let config = repo.config_snapshot();
-        let default_committer = repo.get_committer()?;
+        let config = stack_state.config();
+        let mut default_committer = repo.get_committer()?;
+        if let Some(date) = fixed_date {
+            default_committer.time = date;
+        }
+        let comment_for = |message: &str| {
+            comment
+                .clone()
+                .map_or_else(|| comment_string(&config, Some(message)), Ok)
+        };
 
         let EditedPatchDescription {
             patchname: file_patchname,
@@ -247,12 +308,13 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             message: file_message,
             diff: file_diff,
         } = if let Some(file_os) = matches.get_one::<PathBuf>("file") {
+            let comment = comment_string(&config, None)?;
             if file_os.to_str() == Some("-") {
                 let mut buf: Vec<u8> = Vec::with_capacity(8192);
                 std::io::stdin().read_to_end(&mut buf)?;
-                EditedPatchDescription::try_from(buf.as_slice())?
+                EditedPatchDescription::parse(buf.as_slice(), &comment)?
             } else {
-                EditedPatchDescription::try_from(std::fs::read(file_os)?.as_slice())?
+                EditedPatchDescription::parse(std::fs::read(file_os)?.as_slice(), &comment)?
             }
         } else {
             EditedPatchDescription::default() // i.e. all Nones
@@ -319,21 +381,27 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             Message::default()
         };
 
-        let patchname_len_limit = PatchName::get_length_limit(&config);
+        let mut name_template = NameTemplate::new(stack_state)?;
+        let patchname_len_limit = name_template.len_limit();
         let disallow_patchnames: Vec<&PatchName> = stack_state.all_patches().collect();
         let allowed_patchnames: Vec<&PatchName> = allowed_patchnames.iter().collect();
 
         let patchname = if let Some(template_patchname) = template_patchname.as_ref() {
             template_patchname.clone()
         } else if let Some(Some(patchname)) = file_patchname {
-            Some(patchname.uniquify(&allowed_patchnames, &disallow_patchnames))
+            Some(patchname.uniquify(
+                &allowed_patchnames,
+                &disallow_patchnames,
+                patchname_len_limit,
+            ))
         } else if let Some(original_patchname) = original_patchname.as_ref() {
             Some(original_patchname.clone())
         } else if !message.is_empty() && !need_interactive_edit {
-            Some(
-                PatchName::make(&message.decode()?, true, patchname_len_limit)
-                    .uniquify(&allowed_patchnames, &disallow_patchnames),
-            )
+            Some(name_template.make(&message.decode()?, true)?.uniquify(
+                &allowed_patchnames,
+                &disallow_patchnames,
+                patchname_len_limit,
+            ))
         } else {
             None
         };
@@ -349,7 +417,14 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             // N.B. add_trailers needs to operate on utf-8 data. The user providing
             // trailer-altering options (e.g. --review) will force the message to be
             // decoded. In such cases the returned message will wrap a utf-8 String.
-            trailers::add_trailers(repo, message, matches, default_committer, autosign)?
+            trailers::add_trailers(
+                repo,
+                message,
+                matches,
+                default_committer,
+                autosign,
+                &extra_trailers,
+            )?
         };
 
         let tree_id = overlay_tree_id.unwrap_or_else(|| {
@@ -372,17 +447,27 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         let (diff, computed_diff) = if file_diff.is_some() {
             (file_diff, None)
         } else if need_interactive_edit
-            && (matches.get_flag("diff") || config.boolean("stgit.edit.verbose").unwrap_or(false))
+            && (diff_base_tree_id.is_some()
+                || matches.get_flag("diff")
+                || config.boolean("stgit.edit.verbose").unwrap_or(false))
         {
-            let old_tree = repo.find_commit(parent_id)?.tree()?;
+            let old_tree = if let Some(diff_base_tree_id) = diff_base_tree_id {
+                repo.find_tree(diff_base_tree_id)?
+            } else {
+                repo.find_commit(parent_id)?.tree()?
+            };
             let new_tree = repo.find_tree(tree_id)?;
             let diff_buf = if patch_commit.is_some() || old_tree.id != new_tree.id {
+                let mut diff_opts = vec![String::from("--full-index")];
+                if let Some(context_lines) = argset::get_context_lines(matches, &config) {
+                    diff_opts.push(format!("--unified={context_lines}"));
+                }
                 stupid.diff_tree_patch(
                     old_tree.id,
                     new_tree.id,
                     <Option<Vec<OsString>>>::None,
                     false,
-                    ["--full-index"],
+                    diff_opts,
                 )?
             } else {
                 // This is a special case for `stg new` without the `--refresh` option.
@@ -421,6 +506,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
 
         if allow_template_save && matches.contains_id("save-template") {
             let message = message.decode()?.to_string();
+            let comment = comment_for(&message)?;
             let patch_description = EditablePatchDescription {
                 patchname,
                 author,
@@ -432,19 +518,21 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             let path = matches.get_one::<PathBuf>("save-template").unwrap().clone();
             if path.to_str() == Some("-") {
                 let mut stream = BufWriter::new(std::io::stdout());
-                patch_description.write(&mut stream)?;
+                patch_description.write(&mut stream, &comment)?;
             } else {
                 let mut stream = BufWriter::new(File::create(&path)?);
-                patch_description.write(&mut stream)?;
+                patch_description.write(&mut stream, &comment)?;
             };
             return Ok(EditOutcome::TemplateSaved(path));
         }
 
         let (patchname, author, message, diff) = if need_interactive_edit {
+            let message = message.decode()?.to_string();
+            let comment = comment_for(&message)?;
             let mut patch_description = EditablePatchDescription {
                 patchname,
                 author,
-                message: message.decode()?.to_string(),
+                message,
                 instruction,
                 diff_instruction,
                 diff,
@@ -455,7 +543,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                 author: edited_author,
                 message: edited_message,
                 diff: edited_diff,
-            } = edit_interactive(&patch_description, &config)?;
+            } = edit_interactive(&patch_description, &comment, &config)?;
 
             let patchname = match edited_patchname {
                 Some(Some(patchname)) => Some(patchname),
@@ -473,7 +561,11 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                 Some(None) => Some(if let Some(commit) = patch_commit {
                     commit.author_strict()?
                 } else {
-                    repo.get_author()?.to_owned()
+                    let mut author = repo.get_author()?.to_owned();
+                    if let Some(date) = fixed_date {
+                        author.time = date;
+                    }
+                    author
                 }),
                 None => patch_description.author.take(),
             };
@@ -493,26 +585,45 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
 
         let tree_id = if need_to_apply_diff {
             let diff = diff.unwrap().0;
+            // Not all commands using the editor have the `--whitespace` option.
+            let whitespace_mode = matches
+                .try_get_one::<String>("whitespace")
+                .ok()
+                .flatten()
+                .map(String::as_str);
             let tree_result = stupid.with_temp_index(|stupid_temp| {
                 stupid_temp.read_tree(parent_id)?;
-                stupid_temp.apply_to_index(diff.as_ref())?;
-                stupid_temp.write_tree()
+                let whitespace = stupid_temp.apply_to_index(diff.as_ref(), whitespace_mode)?;
+                Ok((stupid_temp.write_tree()?, whitespace))
             });
             match tree_result {
-                Ok(tree_id) => tree_id,
+                Ok((tree_id, whitespace)) => {
+                    if !whitespace.is_empty() {
+                        crate::print_warning_message(
+                            matches,
+                            &format!(
+                                "edited diff has whitespace errors:\n  {}",
+                                whitespace.join("\n  ")
+                            ),
+                        );
+                    }
+                    tree_id
+                }
                 Err(e) => {
                     let diff = Some(DiffBuffer(diff));
                     let failed_description_path = ".stgit-failed.patch";
                     let mut stream = BufWriter::new(File::create(failed_description_path)?);
+                    let message = message.decode()?.to_string();
+                    let comment = comment_for(&message)?;
                     let failed_patch_description = EditablePatchDescription {
                         patchname,
                         author,
-                        message: message.decode()?.to_string(),
+                        message,
                         instruction,
                         diff_instruction,
                         diff,
                     };
-                    failed_patch_description.write(&mut stream)?;
+                    failed_patch_description.write(&mut stream, &comment)?;
                     return Err(anyhow!(
                         "edited patch did not apply due to:\n\
                          {e:#};\n\
@@ -534,12 +645,23 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
         };
 
         let patchname = if let Some(patchname) = patchname {
-            patchname.uniquify(&allowed_patchnames, &disallow_patchnames)
+            patchname.uniquify(
+                &allowed_patchnames,
+                &disallow_patchnames,
+                patchname_len_limit,
+            )
         } else if let Some(Some(template_patchname)) = template_patchname {
-            template_patchname.uniquify(&allowed_patchnames, &disallow_patchnames)
+            template_patchname.uniquify(
+                &allowed_patchnames,
+                &disallow_patchnames,
+                patchname_len_limit,
+            )
         } else {
-            PatchName::make(&message.decode()?, true, patchname_len_limit)
-                .uniquify(&allowed_patchnames, &disallow_patchnames)
+            name_template.make(&message.decode()?, true)?.uniquify(
+                &allowed_patchnames,
+                &disallow_patchnames,
+                patchname_len_limit,
+            )
         };
 
         let committer = if matches.get_flag("committer-date-is-author-date") {
@@ -565,7 +687,35 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             }) {
             None
         } else {
-            Some(repo.commit_ex(&author, &committer, &message, tree_id, [parent_id])?)
+            let extra_headers = if let Some(patch_commit) = patch_commit {
+                let (extra_headers, dropped_headers) =
+                    patch_commit.rewritable_extra_headers(&[parent_id])?;
+                if !dropped_headers.is_empty() {
+                    let names: Vec<_> = dropped_headers
+                        .iter()
+                        .map(|name| format!("`{name}`"))
+                        .collect();
+                    crate::print_info_message(
+                        matches,
+                        &format!(
+                            "dropping invalidated commit header{} {} from `{patchname}`",
+                            if names.len() == 1 { "" } else { "s" },
+                            names.join(", "),
+                        ),
+                    );
+                }
+                extra_headers
+            } else {
+                vec![]
+            };
+            Some(repo.commit_ex_with_headers(
+                &author,
+                &committer,
+                &message,
+                tree_id,
+                [parent_id],
+                extra_headers,
+            )?)
         };
 
         let new_patchname = if original_patchname.as_ref() == Some(&patchname) {
@@ -11,13 +11,15 @@ use crate::{stupid::Stupid, wrap::Message};
 /// Add trailers to commit message based on user-provided command line options.
 ///
 /// The `matches` provided to this function must be from a [`clap::Command`] that was
-/// setup with [`super::add_args`].
+/// setup with [`super::add_args`]. The `extra_trailers` are added after any trailers
+/// from the command line options.
 pub(crate) fn add_trailers<'a, 'b>(
     repo: &gix::Repository,
     message: Message<'a>,
     matches: &ArgMatches,
     signature: impl Into<gix::actor::SignatureRef<'b>>,
     autosign: Option<&str>,
+    extra_trailers: &[(String, String)],
 ) -> Result<Message<'a>> {
     let signature = signature.into();
     let mut trailers: Vec<(usize, &str, &str)> = vec![];
@@ -42,6 +44,10 @@ pub(crate) fn add_trailers<'a, 'b>(
         }
     }
 
+    for (trailer, value) in extra_trailers {
+        trailers.push((usize::MAX, trailer, value));
+    }
+
     if trailers.is_empty() && autosign.is_none() {
         Ok(message)
     } else {
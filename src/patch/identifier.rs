@@ -14,6 +14,7 @@ impl std::fmt::Display for PatchId {
             PatchId::BelowTop(None) => '~'.fmt(f),
             PatchId::BelowLast(None) => '^'.fmt(f),
             PatchId::BelowLast(Some(n)) => format!("^{n}").fmt(f),
+            PatchId::MessagePattern(pattern) => format!(":/{pattern}").fmt(f),
         }
     }
 }
@@ -2,10 +2,19 @@
 
 //! Implementations for [`PatchLocator`].
 
-use std::{cmp::Ordering, fmt::Write, rc::Rc, str::FromStr};
+use std::{fmt::Write, rc::Rc, str::FromStr};
 
-use super::{PatchId, PatchLocator, PatchName, PatchOffsetAtom, PatchOffsets, StGitRevision};
-use crate::stack::{StackAccess, StackStateAccess};
+use bstr::ByteSlice;
+
+use super::{
+    lookup::PatchLookup, LocationConstraint, PatchId, PatchLocator, PatchName, PatchOffsetAtom,
+    PatchOffsets, StGitRevision,
+};
+use crate::{
+    ext::ShallowHistoryError,
+    output::Output,
+    stack::{StackAccess, StackStateAccess},
+};
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum Error {
@@ -13,15 +22,21 @@ pub(crate) enum Error {
     InvalidPatchLocator(String),
 
     #[error(
-        "patch `{patchname}` does not exist, \
-         but is similar to {similar_patchnames}"
+        "patch `{}` does not exist; did you mean {similar_patchnames}?",
+        .patchname.quoted()
     )]
     PatchSimilar {
         patchname: PatchName,
         similar_patchnames: String,
     },
 
-    #[error("patch `{patchname}` does not exist")]
+    #[error("ambiguous patch name `{}`; did you mean {candidates}?", .patchname.quoted())]
+    AmbiguousPatchName {
+        patchname: PatchName,
+        candidates: String,
+    },
+
+    #[error("patch `{}` does not exist", .patchname.quoted())]
     PatchNotKnown { patchname: PatchName },
 
     #[error("invalid patch index `{0}`")]
@@ -48,8 +63,17 @@ pub(crate) enum Error {
         patchnames: String,
     },
 
+    #[error("no patch message contains `{0}`")]
+    MessagePatternNotFound(String),
+
+    #[error("`:/{pattern}` matches patches with identical messages: {patchnames}")]
+    DuplicateMessages { pattern: String, patchnames: String },
+
     #[error("finding ancestor: {0}")]
     Ancestors(String),
+
+    #[error(transparent)]
+    ShallowHistory(#[from] ShallowHistoryError),
 }
 
 impl FromStr for PatchLocator {
@@ -91,6 +115,7 @@ struct DisambiguatedLocator<'a> {
 #[derive(Debug)]
 enum DisambiguatedId<'a> {
     Name(&'a PatchName),
+    AmbiguousName(&'a PatchName, Vec<&'a PatchName>),
     CommitId(gix::hash::Prefix),
     Top,
     Base,
@@ -98,12 +123,15 @@ enum DisambiguatedId<'a> {
     FromTop(isize),
     FromBase(isize),
     FromLast(isize),
+    MessagePattern(&'a str),
 }
 
 impl DisambiguatedId<'_> {
     fn string_for_error(&self) -> String {
         match self {
-            DisambiguatedId::Name(name) => format!("`{name}`"),
+            DisambiguatedId::Name(name) | DisambiguatedId::AmbiguousName(name, _) => {
+                format!("`{name}`")
+            }
             DisambiguatedId::CommitId(oid_prefix) => format!("commit id `{oid_prefix}`"),
             DisambiguatedId::Top => "topmost patch".to_string(),
             DisambiguatedId::Base => "stack base".to_string(),
@@ -117,6 +145,7 @@ impl DisambiguatedId<'_> {
                     format!("{offset:+} from last patch")
                 }
             }
+            DisambiguatedId::MessagePattern(pattern) => format!("`:/{pattern}`"),
         }
     }
 }
@@ -125,133 +154,104 @@ impl PatchLocator {
     /// Resolve a patch name in the provided stack from this locator.
     ///
     /// The locator's offsets must stay within the given stack.
+    ///
+    /// A name not matching any patch may be a unique prefix of a patch name. See
+    /// [`PatchLocator::resolve_name_allowed()`].
     pub(crate) fn resolve_name<'a, 'repo>(
         &'a self,
         stack: &'a impl StackStateAccess<'repo>,
     ) -> Result<PatchName, Error> {
-        let DisambiguatedLocator { id, offsets } = self.disambiguate(stack);
+        self.resolve_name_allowed(stack, LocationConstraint::All)
+    }
 
-        let patches: Vec<_> = stack.all_patches().collect();
+    /// Resolve a patch name, matching abbreviated names against the allowed patches.
+    ///
+    /// When the locator's name neither matches a patch nor can be interpreted as a
+    /// commit id, index, or offset, it is matched as a prefix of the names of the
+    /// patches allowed by `allow`. If none of the allowed patches match, all the
+    /// patches in the stack are tried. Either way, the prefix must match exactly one
+    /// patch. Abbreviated names are disabled by setting `stgit.abbrevpatchnames` to
+    /// false.
+    ///
+    /// The resolved patch name is not itself constrained; callers still need to use
+    /// [`PatchName::constrain()`] as needed.
+    pub(crate) fn resolve_name_allowed<'a, 'repo>(
+        &'a self,
+        stack: &'a impl StackStateAccess<'repo>,
+        allow: LocationConstraint,
+    ) -> Result<PatchName, Error> {
+        self.resolve_name_using(stack, &PatchLookup::new(stack), allow)
+    }
 
-        let mut index: isize = match id {
-            DisambiguatedId::Name(pn) => {
-                if stack.has_patch(pn) {
-                    Ok(stack.index_of(pn) as isize)
-                } else if let Some(similar_patchnames) = similar_patchnames(pn, stack) {
-                    Err(Error::PatchSimilar {
-                        patchname: pn.clone(),
-                        similar_patchnames,
-                    })
-                } else {
-                    Err(Error::PatchNotKnown {
-                        patchname: pn.clone(),
-                    })
-                }
-            }
-            DisambiguatedId::CommitId(oid_prefix) => {
-                let matching_names: Vec<_> = patches
-                    .iter()
-                    .filter_map(|&pn| {
-                        (oid_prefix.cmp_oid(&stack.get_patch_commit_id(pn)) == Ordering::Equal)
-                            .then_some(pn)
-                    })
-                    .collect();
-                match matching_names.len() {
-                    0 => panic!("disambiguation should prevent this"),
-                    1 => Ok(stack.index_of(matching_names[0]) as isize),
-                    _ => Err(Error::AmbiguousCommitId {
-                        oid_prefix,
-                        patchnames: patchnames_string(&matching_names).unwrap(),
-                    }),
-                }
-            }
-            DisambiguatedId::Top => Ok((stack.applied().len() as isize) - 1),
-            DisambiguatedId::Base => {
-                if offsets.is_empty() {
-                    Err(Error::BaseNeedsOffset)
-                } else {
-                    Ok(-1)
-                }
-            }
-            DisambiguatedId::Index(index) => {
-                if index < patches.len() {
-                    Ok(index as isize)
-                } else {
-                    Err(Error::InvalidPatchIndex(index))
-                }
-            }
-            DisambiguatedId::FromTop(offset) => {
-                let index = (stack.applied().len() as isize) - 1 + offset;
-                if index >= 0 && (index as usize) < patches.len() {
-                    Ok(index)
-                } else {
-                    Err(Error::InvalidOffsetFrom(id.string_for_error()))
-                }
-            }
-            DisambiguatedId::FromBase(offset) => {
-                if offset < 1 {
-                    Err(Error::BaseNeedsPositiveOffset)
-                } else {
-                    let index = -1 + offset;
-                    if (index as usize) < patches.len() {
-                        Ok(index)
-                    } else {
-                        Err(Error::InvalidOffsetFrom(id.string_for_error()))
-                    }
-                }
-            }
-            DisambiguatedId::FromLast(offset) => {
-                if patches.is_empty() {
-                    Err(Error::NoLastPatch)
-                } else if let Some(index) = (stack.applied_and_unapplied().count() - 1)
-                    .checked_add_signed(offset)
-                    .filter(|&index| index < patches.len())
-                {
-                    Ok(index as isize)
-                } else {
-                    Err(Error::InvalidOffsetFrom(id.string_for_error()))
-                }
-            }
-        }?;
+    /// Resolve a patch name the same as [`PatchLocator::resolve_name_allowed()`], but
+    /// using already built lookup tables for the stack.
+    ///
+    /// This is used when resolving many locators against the same stack.
+    pub(crate) fn resolve_name_using<'a, 'repo>(
+        &'a self,
+        stack: &'a impl StackStateAccess<'repo>,
+        lookup: &PatchLookup<'a>,
+        allow: LocationConstraint,
+    ) -> Result<PatchName, Error> {
+        resolve_disambiguated_name(self.disambiguate(stack, lookup, Some(allow)), stack, lookup)
+    }
 
-        for atom in offsets.atoms() {
-            let new_index = match atom {
-                PatchOffsetAtom::Plus(n) => index.checked_add_unsigned(n.unwrap_or(1)),
-                PatchOffsetAtom::Tilde(n) => index.checked_sub_unsigned(n.unwrap_or(1)),
-            };
+    /// Resolve a patch name, borrowing the anchor of another locator when this locator
+    /// consists only of offsets.
+    ///
+    /// This is used for the end bound of patch ranges such as `fix-io+1..+3`, where the
+    /// unanchored `+3` is located relative to the begin bound's anchor, i.e. the same
+    /// as `fix-io+3`. As with other locators, a patch actually named like an offset,
+    /// e.g. `+3`, takes precedence.
+    pub(crate) fn resolve_name_with_anchor<'a, 'repo>(
+        &'a self,
+        anchor: &'a PatchLocator,
+        stack: &'a impl StackStateAccess<'repo>,
+        lookup: &PatchLookup<'a>,
+        allow: LocationConstraint,
+    ) -> Result<PatchName, Error> {
+        use winnow::Parser;
 
-            if new_index.is_none()
-                || new_index < Some(0)
-                || new_index >= Some(patches.len() as isize)
+        let unanchored_offsets = match &self.id {
+            PatchId::Name(patchname)
+                if patchname.0.starts_with('+')
+                    && !stack.has_patch(patchname)
+                    && !stack.aliases().contains_key(patchname) =>
             {
-                return Err(Error::InvalidPatchOffset {
-                    id: id.string_for_error(),
-                    offsets,
-                });
+                super::parse::patch_offsets
+                    .parse(patchname.0.as_str())
+                    .ok()
+                    .map(|offsets| offsets.join(&self.offsets))
             }
+            _ => None,
+        };
 
-            index = new_index.unwrap();
+        if let Some(offsets) = unanchored_offsets {
+            let DisambiguatedLocator { id, .. } = anchor.disambiguate(stack, lookup, Some(allow));
+            resolve_disambiguated_name(DisambiguatedLocator { id, offsets }, stack, lookup)
+        } else {
+            self.resolve_name_using(stack, lookup, allow)
         }
-
-        Ok(patches[index as usize].clone())
     }
 
     /// Resolve patchname and commit object based on patch location and offsets.
     ///
     /// Unlike [`PatchLocator::resolve_name()`], the offsets are allowed to land on a
-    /// commit outside of the stack.
+    /// commit outside of the stack. Abbreviated patch names are not matched since a
+    /// revision that is not a patch may instead be a git revision, e.g. a branch name.
     pub(crate) fn resolve_revision<'a, 'repo>(
         &'a self,
         stack: &'a impl StackAccess<'repo>,
     ) -> Result<StGitRevision<'repo>, Error> {
-        let DisambiguatedLocator { id, offsets } = self.disambiguate(stack);
+        let lookup = PatchLookup::new(stack);
+        let DisambiguatedLocator { id, offsets } = self.disambiguate(stack, &lookup, None);
 
-        let patches: Vec<_> = stack.all_patches().collect();
+        let patches = lookup.patches();
 
         let mut index: isize = match id {
             DisambiguatedId::Name(pn) => {
-                if stack.has_patch(pn) {
-                    Ok(stack.index_of(pn) as isize)
+                if let Some(pos) = lookup.position(pn.as_ref()) {
+                    Ok(pos as isize)
                 } else if let Some(similar_patchnames) = similar_patchnames(pn, stack) {
                     Err(Error::PatchSimilar {
                         patchname: pn.clone(),
@@ -263,17 +263,14 @@ impl PatchLocator {
                     })
                 }
             }
+            DisambiguatedId::AmbiguousName(pn, ref candidates) => {
+                Err(ambiguous_patchname(pn, candidates))
+            }
             DisambiguatedId::CommitId(oid_prefix) => {
-                let matching_names: Vec<_> = patches
-                    .iter()
-                    .filter_map(|&pn| {
-                        (oid_prefix.cmp_oid(&stack.get_patch_commit_id(pn)) == Ordering::Equal)
-                            .then_some(pn)
-                    })
-                    .collect();
+                let matching_names = lookup.matching_commit_ids(&oid_prefix, stack);
                 match matching_names.len() {
                     0 => panic!("disambiguation should prevent this"),
-                    1 => Ok(stack.index_of(matching_names[0]) as isize),
+                    1 => Ok(position_of(&lookup, matching_names[0])),
                     _ => Err(Error::AmbiguousCommitId {
                         oid_prefix,
                         patchnames: patchnames_string(&matching_names).unwrap(),
@@ -281,6 +278,9 @@ impl PatchLocator {
                 }
             }
             DisambiguatedId::Top => Ok((stack.applied().len() as isize) - 1),
+            DisambiguatedId::MessagePattern(pattern) => {
+                first_matching_message(pattern, stack).map(|pn| position_of(&lookup, pn))
+            }
             DisambiguatedId::Base => Ok(-1),
             DisambiguatedId::Index(index) => {
                 if index < patches.len() {
@@ -369,6 +369,8 @@ impl PatchLocator {
                     patchname: None,
                     commit: Rc::new(commit),
                 })
+            } else if stack.base().repo.is_shallow() {
+                Err(ShallowHistoryError(format!("resolving `{self}`")).into())
             } else {
                 Err(Error::Ancestors(
                     "repository does not have enough commit depth".to_string(),
@@ -377,9 +379,46 @@ impl PatchLocator {
         }
     }
 
+    /// Check that a `:/<pattern>` locator does not match patches with identical messages.
+    ///
+    /// A message pattern normally resolves to the first matching patch. Destructive
+    /// commands use this check to refuse choosing between patches that cannot be told
+    /// apart by their messages. Other kinds of locators always pass this check.
+    pub(crate) fn check_message_duplicates<'repo>(
+        &self,
+        stack: &impl StackStateAccess<'repo>,
+    ) -> Result<(), Error> {
+        if let PatchId::MessagePattern(pattern) = &self.id {
+            let matching = matching_messages(pattern, stack);
+            if let Some((first, rest)) = matching.split_first() {
+                let message = stack.get_patch_commit(first).message_raw_sloppy();
+                let duplicates: Vec<&PatchName> =
+                    std::iter::once(*first)
+                        .chain(rest.iter().copied().filter(|pn| {
+                            stack.get_patch_commit(pn).message_raw_sloppy() == message
+                        }))
+                        .collect();
+                if duplicates.len() > 1 {
+                    return Err(Error::DuplicateMessages {
+                        pattern: pattern.clone(),
+                        patchnames: patchnames_string(&duplicates).unwrap(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Disambiguate the locator in the context of the stack.
+    ///
+    /// When `abbrev` is provided, a name that cannot otherwise be disambiguated is
+    /// matched as a prefix of patch names, preferring the patches allowed by the
+    /// constraint.
     fn disambiguate<'a, 'repo>(
         &'a self,
         stack: &'a impl StackStateAccess<'repo>,
+        lookup: &PatchLookup<'a>,
+        abbrev: Option<LocationConstraint>,
     ) -> DisambiguatedLocator<'a> {
         match &self.id {
             PatchId::Base => DisambiguatedLocator {
@@ -407,10 +446,26 @@ impl PatchLocator {
                 id: DisambiguatedId::FromLast(n.map_or(0, |n| -n)),
                 offsets: self.offsets.clone(),
             },
+            PatchId::MessagePattern(pattern) => DisambiguatedLocator {
+                id: DisambiguatedId::MessagePattern(pattern),
+                offsets: self.offsets.clone(),
+            },
             PatchId::Name(patchname) if stack.has_patch(patchname) => DisambiguatedLocator {
                 id: DisambiguatedId::Name(patchname),
                 offsets: self.offsets.clone(),
             },
+            PatchId::Name(patchname) if stack.aliases().contains_key(patchname) => {
+                let target = &stack.aliases()[patchname];
+                Output::current().warning(&format!(
+                    "`{}` is a deprecated alias for patch `{target}`; use `{target}` instead",
+                    patchname.quoted(),
+                    target = target.quoted(),
+                ));
+                DisambiguatedLocator {
+                    id: DisambiguatedId::Name(target),
+                    offsets: self.offsets.clone(),
+                }
+            }
             PatchId::Name(patchname) => {
                 use winnow::Parser;
                 let patch_offsets = |s| super::parse::patch_offsets.parse(s).ok();
@@ -423,15 +478,18 @@ impl PatchLocator {
 
                 // A patch from the stack is a prefix match for this patchname and the
                 // remaining suffix can be interpreted as "plussy" offsets, e.g.
-                // "++3+".
-                if let Some((prefix_pn, offsets)) = stack
-                    .all_patches()
-                    .filter_map(|pn| {
-                        name.strip_prefix(pn.0.as_str())
-                            .and_then(patch_offsets)
-                            .map(|suffix| (pn, suffix))
+                // "++3+". Such a suffix starts with '+' or '~', so only the prefixes
+                // ending before those characters need to be looked up, longest first.
+                if let Some((prefix_pn, offsets)) = name
+                    .char_indices()
+                    .rev()
+                    .filter(|(_, c)| matches!(c, '+' | '~'))
+                    .find_map(|(i, _)| {
+                        let (prefix, suffix) = name.split_at(i);
+                        lookup
+                            .get(prefix)
+                            .and_then(|pn| patch_offsets(suffix).map(|offsets| (pn, offsets)))
                     })
-                    .max_by_key(|(pn, _suffix)| pn.len())
                 {
                     DisambiguatedLocator {
                         id: DisambiguatedId::Name(prefix_pn),
@@ -458,9 +516,7 @@ impl PatchLocator {
                     }
                 } else if let Some((oid_prefix, offsets)) =
                     oid_prefix_offsets(name).filter(|(oid_prefix, _)| {
-                        stack.all_patches().any(|pn| {
-                            oid_prefix.cmp_oid(&stack.get_patch_commit_id(pn)) == Ordering::Equal
-                        })
+                        !lookup.matching_commit_ids(oid_prefix, stack).is_empty()
                     })
                 {
                     DisambiguatedLocator {
@@ -492,6 +548,13 @@ impl PatchLocator {
                     };
                     let offsets = offsets.join(&self.offsets);
                     DisambiguatedLocator { id, offsets }
+                } else if let Some(id) =
+                    abbrev.and_then(|allow| abbreviated_patchname(patchname, stack, lookup, allow))
+                {
+                    DisambiguatedLocator {
+                        id,
+                        offsets: self.offsets.clone(),
+                    }
                 } else {
                     DisambiguatedLocator {
                         id: DisambiguatedId::Name(patchname),
@@ -503,29 +566,238 @@ impl PatchLocator {
     }
 }
 
+/// Resolve the patch name of an already disambiguated locator.
+fn resolve_disambiguated_name<'repo>(
+    locator: DisambiguatedLocator<'_>,
+    stack: &impl StackStateAccess<'repo>,
+    lookup: &PatchLookup<'_>,
+) -> Result<PatchName, Error> {
+    let DisambiguatedLocator { id, offsets } = locator;
+
+    let patches = lookup.patches();
+    let mut index: isize = match id {
+        DisambiguatedId::Name(pn) => {
+            if let Some(pos) = lookup.position(pn.as_ref()) {
+                Ok(pos as isize)
+            } else if let Some(similar_patchnames) = similar_patchnames(pn, stack) {
+                Err(Error::PatchSimilar {
+                    patchname: pn.clone(),
+                    similar_patchnames,
+                })
+            } else {
+                Err(Error::PatchNotKnown {
+                    patchname: pn.clone(),
+                })
+            }
+        }
+        DisambiguatedId::AmbiguousName(pn, ref candidates) => {
+            Err(ambiguous_patchname(pn, candidates))
+        }
+        DisambiguatedId::CommitId(oid_prefix) => {
+            let matching_names = lookup.matching_commit_ids(&oid_prefix, stack);
+            match matching_names.len() {
+                0 => panic!("disambiguation should prevent this"),
+                1 => Ok(position_of(lookup, matching_names[0])),
+                _ => Err(Error::AmbiguousCommitId {
+                    oid_prefix,
+                    patchnames: patchnames_string(&matching_names).unwrap(),
+                }),
+            }
+        }
+        DisambiguatedId::Top => Ok((stack.applied().len() as isize) - 1),
+        DisambiguatedId::MessagePattern(pattern) => {
+            first_matching_message(pattern, stack).map(|pn| position_of(lookup, pn))
+        }
+        DisambiguatedId::Base => {
+            if offsets.is_empty() {
+                Err(Error::BaseNeedsOffset)
+            } else {
+                Ok(-1)
+            }
+        }
+        DisambiguatedId::Index(index) => {
+            if index < patches.len() {
+                Ok(index as isize)
+            } else {
+                Err(Error::InvalidPatchIndex(index))
+            }
+        }
+        DisambiguatedId::FromTop(offset) => {
+            let index = (stack.applied().len() as isize) - 1 + offset;
+            if index >= 0 && (index as usize) < patches.len() {
+                Ok(index)
+            } else {
+                Err(Error::InvalidOffsetFrom(id.string_for_error()))
+            }
+        }
+        DisambiguatedId::FromBase(offset) => {
+            if offset < 1 {
+                Err(Error::BaseNeedsPositiveOffset)
+            } else {
+                let index = -1 + offset;
+                if (index as usize) < patches.len() {
+                    Ok(index)
+                } else {
+                    Err(Error::InvalidOffsetFrom(id.string_for_error()))
+                }
+            }
+        }
+        DisambiguatedId::FromLast(offset) => {
+            if patches.is_empty() {
+                Err(Error::NoLastPatch)
+            } else if let Some(index) = (stack.applied_and_unapplied().count() - 1)
+                .checked_add_signed(offset)
+                .filter(|&index| index < patches.len())
+            {
+                Ok(index as isize)
+            } else {
+                Err(Error::InvalidOffsetFrom(id.string_for_error()))
+            }
+        }
+    }?;
+
+    for atom in offsets.atoms() {
+        let new_index = match atom {
+            PatchOffsetAtom::Plus(n) => index.checked_add_unsigned(n.unwrap_or(1)),
+            PatchOffsetAtom::Tilde(n) => index.checked_sub_unsigned(n.unwrap_or(1)),
+        };
+
+        if new_index.is_none() || new_index < Some(0) || new_index >= Some(patches.len() as isize) {
+            return Err(Error::InvalidPatchOffset {
+                id: id.string_for_error(),
+                offsets,
+            });
+        }
+
+        index = new_index.unwrap();
+    }
+
+    Ok(patches[index as usize].clone())
+}
+
+/// Get the stack position of a patch known to be in the stack.
+fn position_of(lookup: &PatchLookup<'_>, patchname: &PatchName) -> isize {
+    lookup
+        .position(patchname.as_ref())
+        .expect("patchname must exist") as isize
+}
+
+/// Find the patches whose commit messages contain `pattern`.
+///
+/// The patches are in search order: applied patches from the topmost down, followed by
+/// the unapplied and hidden patches.
+fn matching_messages<'a, 'repo>(
+    pattern: &str,
+    stack: &'a impl StackStateAccess<'repo>,
+) -> Vec<&'a PatchName> {
+    stack
+        .applied()
+        .iter()
+        .rev()
+        .chain(stack.unapplied())
+        .chain(stack.hidden())
+        .filter(|pn| {
+            stack
+                .get_patch_commit(pn)
+                .message_raw_sloppy()
+                .contains_str(pattern)
+        })
+        .collect()
+}
+
+fn first_matching_message<'a, 'repo>(
+    pattern: &str,
+    stack: &'a impl StackStateAccess<'repo>,
+) -> Result<&'a PatchName, Error> {
+    matching_messages(pattern, stack)
+        .first()
+        .copied()
+        .ok_or_else(|| Error::MessagePatternNotFound(pattern.to_string()))
+}
+
+/// Maximum number of patch names suggested in errors.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Find the patch abbreviated by `prefix`.
+///
+/// The patches allowed by `allow` are matched first, falling back to all patches in
+/// the stack. Returns `None` when no patch names start with `prefix` or when
+/// abbreviated names are disabled.
+fn abbreviated_patchname<'a, 'repo>(
+    prefix: &'a PatchName,
+    stack: &impl StackStateAccess<'repo>,
+    lookup: &PatchLookup<'a>,
+    allow: LocationConstraint,
+) -> Option<DisambiguatedId<'a>> {
+    if !stack.allow_abbreviated_names() {
+        return None;
+    }
+    let all_candidates = lookup.matching_names(prefix.as_ref());
+    let mut candidates: Vec<&PatchName> = all_candidates
+        .iter()
+        .copied()
+        .filter(|pn| lookup.allowed_position(allow, pn.as_ref()).is_some())
+        .collect();
+    if candidates.is_empty() {
+        candidates = all_candidates;
+    }
+    match candidates.len() {
+        0 => None,
+        1 => Some(DisambiguatedId::Name(candidates[0])),
+        _ => Some(DisambiguatedId::AmbiguousName(prefix, candidates)),
+    }
+}
+
+fn ambiguous_patchname(patchname: &PatchName, candidates: &[&PatchName]) -> Error {
+    let mut candidates = candidates.to_vec();
+    sort_by_distance(patchname, &mut candidates);
+    candidates.truncate(MAX_SUGGESTIONS);
+    Error::AmbiguousPatchName {
+        patchname: patchname.clone(),
+        candidates: join_patchnames(&candidates, "or").unwrap(),
+    }
+}
+
 fn similar_patchnames<'a>(
     patchname: &PatchName,
     stack: &impl StackStateAccess<'a>,
 ) -> Option<String> {
-    let similar: Vec<&PatchName> = stack
+    let mut similar: Vec<&PatchName> = stack
         .all_patches()
         .filter(|pn| strsim::jaro_winkler(pn.as_ref(), patchname.as_ref()) > 0.75)
         .collect();
-    patchnames_string(&similar)
+    sort_by_distance(patchname, &mut similar);
+    similar.truncate(MAX_SUGGESTIONS);
+    join_patchnames(&similar, "or")
+}
+
+/// Sort patch names by edit distance from `patchname`, closest first.
+///
+/// Equally distant patch names keep their stack order.
+fn sort_by_distance(patchname: &PatchName, patchnames: &mut [&PatchName]) {
+    patchnames.sort_by_cached_key(|pn| strsim::levenshtein(pn.as_ref(), patchname.as_ref()));
 }
 
 fn patchnames_string(patchnames: &[&PatchName]) -> Option<String> {
+    join_patchnames(patchnames, "and")
+}
+
+fn join_patchnames(patchnames: &[&PatchName], conjunction: &str) -> Option<String> {
     match patchnames.len() {
         0 => None,
-        1 => Some(format!("`{}`", patchnames[0])),
-        2 => Some(format!("`{}` and `{}`", patchnames[0], patchnames[1])),
+        1 => Some(format!("`{}`", patchnames[0].quoted())),
+        2 => Some(format!(
+            "`{}` {conjunction} `{}`",
+            patchnames[0].quoted(),
+            patchnames[1].quoted()
+        )),
         _ => {
             let mut s = String::new();
             for pn in patchnames.iter().take(patchnames.len() - 1) {
-                write!(&mut s, "`{pn}`, ").unwrap();
+                write!(&mut s, "`{}`, ", pn.quoted()).unwrap();
             }
             let last_pn = patchnames.last().unwrap();
-            write!(&mut s, "and `{last_pn}`").unwrap();
+            write!(&mut s, "{conjunction} `{}`", last_pn.quoted()).unwrap();
             Some(s)
         }
     }
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Lookup tables for resolving patch locators against a stack.
+
+use std::{cell::OnceCell, cmp::Ordering, collections::HashMap, ops::Range};
+
+use super::{LocationConstraint, LocationGroup, PatchName};
+use crate::stack::StackStateAccess;
+
+/// Lookup tables for the patches of a stack.
+///
+/// Resolving a patch locator may require finding a patch's position in the stack, the
+/// patches whose commit ids start with a given prefix, or the patches whose names
+/// start with a given prefix. Scanning the stack for each of these is linear in the
+/// number of patches, which adds up when resolving many locators against a large
+/// stack. A `PatchLookup` is built once and then shared by all the locators resolved
+/// by a command.
+///
+/// The commit id and name prefix tables are only built when first needed.
+pub(crate) struct PatchLookup<'a> {
+    /// All patches in stack order: applied, unapplied, and hidden.
+    patches: Vec<&'a PatchName>,
+    num_applied: usize,
+    num_unapplied: usize,
+    /// Position in `patches` of each patch, by name.
+    positions: HashMap<&'a str, usize>,
+    /// Patch commit ids and the positions of their patches, sorted by commit id.
+    commit_ids: OnceCell<Vec<(gix::ObjectId, usize)>>,
+    /// Positions of the patches, sorted by patch name.
+    sorted_names: OnceCell<Vec<usize>>,
+}
+
+impl<'a> PatchLookup<'a> {
+    /// Build the lookup tables for the given stack.
+    pub(crate) fn new<'repo>(stack: &'a impl StackStateAccess<'repo>) -> Self {
+        let patches: Vec<&'a PatchName> = stack.all_patches().collect();
+        let positions = patches
+            .iter()
+            .enumerate()
+            .map(|(pos, &pn)| (pn.as_ref(), pos))
+            .collect();
+        Self {
+            patches,
+            num_applied: stack.applied().len(),
+            num_unapplied: stack.unapplied().len(),
+            positions,
+            commit_ids: OnceCell::new(),
+            sorted_names: OnceCell::new(),
+        }
+    }
+
+    /// All patches in stack order.
+    pub(crate) fn patches(&self) -> &[&'a PatchName] {
+        &self.patches
+    }
+
+    /// Number of applied and unapplied patches.
+    pub(crate) fn num_visible(&self) -> usize {
+        self.num_applied + self.num_unapplied
+    }
+
+    /// Get the stack's patch with the given name.
+    pub(crate) fn get(&self, name: &str) -> Option<&'a PatchName> {
+        self.position(name).map(|pos| self.patches[pos])
+    }
+
+    /// Get the position in the stack of the patch with the given name.
+    ///
+    /// This is the same as [`StackStateAccess::index_of()`], but without the linear
+    /// search.
+    pub(crate) fn position(&self, name: &str) -> Option<usize> {
+        self.positions.get(name).copied()
+    }
+
+    /// Get the location group of the patch at the given position.
+    pub(crate) fn location_group(&self, pos: usize) -> LocationGroup {
+        if pos < self.num_applied {
+            LocationGroup::Applied
+        } else if pos < self.num_visible() {
+            LocationGroup::Unapplied
+        } else {
+            LocationGroup::Hidden
+        }
+    }
+
+    /// Get the patches allowed by the constraint, in stack order.
+    pub(crate) fn allowed(&self, constraint: LocationConstraint) -> &[&'a PatchName] {
+        &self.patches[self.allowed_range(constraint)]
+    }
+
+    /// Get the position of the named patch within the patches allowed by the
+    /// constraint.
+    pub(crate) fn allowed_position(
+        &self,
+        constraint: LocationConstraint,
+        name: &str,
+    ) -> Option<usize> {
+        let range = self.allowed_range(constraint);
+        self.position(name)
+            .filter(|pos| range.contains(pos))
+            .map(|pos| pos - range.start)
+    }
+
+    fn allowed_range(&self, constraint: LocationConstraint) -> Range<usize> {
+        match constraint {
+            LocationConstraint::All => 0..self.patches.len(),
+            LocationConstraint::Visible => 0..self.num_visible(),
+            LocationConstraint::Applied => 0..self.num_applied,
+            LocationConstraint::Unapplied => self.num_applied..self.num_visible(),
+            LocationConstraint::Hidden => self.num_visible()..self.patches.len(),
+        }
+    }
+
+    /// Constrain a patch name to the patches allowed by the constraint.
+    ///
+    /// This is the same as [`PatchName::constrain()`].
+    pub(crate) fn constrain(
+        &self,
+        patchname: PatchName,
+        constraint: LocationConstraint,
+    ) -> Result<PatchName, super::name::Error> {
+        if self
+            .allowed_position(constraint, patchname.as_ref())
+            .is_some()
+        {
+            Ok(patchname)
+        } else {
+            let pos = self
+                .position(patchname.as_ref())
+                .expect("patchname must exist");
+            Err(super::name::Error::PatchNotAllowed {
+                patchname,
+                loc_group: self.location_group(pos),
+            })
+        }
+    }
+
+    /// Get the patches whose commit ids start with the given prefix, in stack order.
+    pub(crate) fn matching_commit_ids<'repo>(
+        &self,
+        oid_prefix: &gix::hash::Prefix,
+        stack: &impl StackStateAccess<'repo>,
+    ) -> Vec<&'a PatchName> {
+        let commit_ids = self.commit_ids.get_or_init(|| {
+            let mut commit_ids: Vec<_> = self
+                .patches
+                .iter()
+                .enumerate()
+                .map(|(pos, pn)| (stack.get_patch_commit_id(pn), pos))
+                .collect();
+            commit_ids.sort_unstable();
+            commit_ids
+        });
+        let start =
+            commit_ids.partition_point(|(oid, _)| oid_prefix.cmp_oid(oid) == Ordering::Greater);
+        let mut positions: Vec<usize> = commit_ids[start..]
+            .iter()
+            .take_while(|(oid, _)| oid_prefix.cmp_oid(oid) == Ordering::Equal)
+            .map(|&(_, pos)| pos)
+            .collect();
+        positions.sort_unstable();
+        positions.into_iter().map(|pos| self.patches[pos]).collect()
+    }
+
+    /// Get the patches whose names start with the given prefix, in stack order.
+    pub(crate) fn matching_names(&self, prefix: &str) -> Vec<&'a PatchName> {
+        let sorted_names = self.sorted_names.get_or_init(|| {
+            let mut sorted_names: Vec<usize> = (0..self.patches.len()).collect();
+            sorted_names.sort_unstable_by_key(|&pos| self.patches[pos]);
+            sorted_names
+        });
+        let start = sorted_names.partition_point(|&pos| {
+            let name: &str = self.patches[pos].as_ref();
+            name < prefix
+        });
+        let mut positions: Vec<usize> = sorted_names[start..]
+            .iter()
+            .copied()
+            .take_while(|&pos| self.patches[pos].0.starts_with(prefix))
+            .collect();
+        positions.sort_unstable();
+        positions.into_iter().map(|pos| self.patches[pos]).collect()
+    }
+}
@@ -6,11 +6,13 @@ mod constraint;
 pub(crate) mod edit;
 mod identifier;
 pub(crate) mod locator;
+mod lookup;
 pub(crate) mod name;
 mod offset;
 pub(crate) mod parse;
 pub(crate) mod range;
 pub(crate) mod revspec;
+mod subject;
 
 #[cfg(test)]
 mod tests;
@@ -19,7 +21,10 @@ use std::rc::Rc;
 
 use serde::{Deserialize, Serialize};
 
-pub(crate) use self::{edit as patchedit, range as patchrange};
+pub(crate) use self::{
+    edit as patchedit, range as patchrange,
+    subject::{Subject, SubjectSource},
+};
 use crate::branchloc::BranchLocator;
 
 /// A range of patches in the stack.
@@ -28,18 +33,48 @@ use crate::branchloc::BranchLocator;
 /// `p0..p3`. The begin and end locators, typically a patch names, are both optional.
 /// The begin and end patch locators are inclusive to the range.
 ///
+/// An end locator consisting only of offsets, e.g. the `+3` in `p0+1..+3`, is relative
+/// to the begin locator's patch, such that the range is equivalent to `p0+1..p0+3`.
+///
 /// Ranges with open beginnings always start with the bottommost patch, i.e. the first
 /// patch after the stack's base commit.
 ///
 /// The last patch in an open-ended range depends on command-specific policy which is
 /// determined by the [`RangeConstraint`] used with [`patchrange::resolve_names()`] or
 /// [`patchrange::resolve_names_contiguous()`].
+///
+/// A range may also be specified as `%{<label>}`, which selects all patches having the
+/// given label, in stack order.
+///
+/// A range containing any of the glob characters `*`, `?`, or `[` is a pattern which
+/// selects all patches with matching names, in stack order. Since patch names may not
+/// contain these characters, a pattern is never ambiguous with a patch name.
+///
+/// Finally, `!<locator>` excludes the located patch from the patches selected by the
+/// other ranges. A patch actually named like an exclusion, e.g. `!patch`, still refers
+/// to that patch.
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum PatchRange {
     /// A range consisting of a single patch.
     Single(PatchLocator),
     /// A range bound by optional begin and end patches.
     Range(PatchRangeBounds),
+    /// The patches having the given label.
+    Label(String),
+    /// The patches with names matching the given glob pattern.
+    Glob(String),
+    /// A patch to exclude from the patches selected by other ranges.
+    Exclude(PatchLocator),
+}
+
+impl PatchRange {
+    /// Get the range of all patches, i.e. `..`.
+    pub(crate) fn all() -> Self {
+        Self::Range(PatchRangeBounds {
+            begin: None,
+            end: None,
+        })
+    }
 }
 
 /// Patch locations bounding a range of patches.
@@ -91,6 +126,12 @@ pub(crate) struct PatchLocator {
 /// contains a patch named `5` then this identifier would refer to the patch named `5`
 /// (regardless of its index). If the stack did not contain a patch named `5`, then the
 /// identifier would resolve to the sixth patch (index `5`) in the the stack.
+///
+/// Similar to git's `:/<text>` revision syntax, `:/<pattern>` identifies the first
+/// patch whose commit message contains the pattern text. Applied patches are searched
+/// from the topmost patch down, followed by the unapplied and then hidden patches. The
+/// pattern may not contain `~`, `+`, `^`, or `..` since these begin offsets, git
+/// revision suffixes, or the end of a range.
 #[derive(Clone, Debug, PartialEq)]
 enum PatchId {
     Name(PatchName),
@@ -98,6 +139,7 @@ enum PatchId {
     Top,
     BelowLast(Option<isize>),
     BelowTop(Option<usize>),
+    MessagePattern(String),
 }
 
 /// Offsets from one patch location to another in the stack.
@@ -2,21 +2,40 @@
 
 //! Patch name string support.
 
-use std::str::FromStr;
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context};
+use bstr::ByteSlice;
 
 use super::{LocationConstraint, LocationGroup, PatchName};
-use crate::stack::StackStateAccess;
+use crate::{
+    ext::RepositoryExtended,
+    stack::{StackAccess, StackConfig, StackStateAccess},
+};
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum Error {
     #[error("invalid patch name `{name}`: {reason}")]
     InvalidPatchName { name: String, reason: String },
 
-    #[error("{loc_group} patch `{patchname}` is not allowed")]
+    #[error("{loc_group} patch `{}` is not allowed", .patchname.quoted())]
     PatchNotAllowed {
         patchname: PatchName,
         loc_group: LocationGroup,
     },
+
+    #[error(
+        "patch name `{}` collides with hidden patch `{}`",
+        .patchname.quoted(),
+        .hidden.quoted()
+    )]
+    HiddenCollision {
+        patchname: PatchName,
+        hidden: PatchName,
+    },
 }
 
 impl Error {
@@ -28,6 +47,232 @@ impl Error {
     }
 }
 
+/// How a derived patch name colliding with a hidden patch is resolved.
+///
+/// Configured with `stgit.name.collision`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum NameCollision {
+    /// Suffix the name with a unique integer, as for any other collision.
+    #[default]
+    Suffix,
+    /// Refuse the name.
+    Error,
+    /// Rename the hidden patch aside and take its name.
+    ReplaceHidden,
+}
+
+impl FromStr for NameCollision {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "suffix" => Ok(Self::Suffix),
+            "error" => Ok(Self::Error),
+            "replace-hidden" => Ok(Self::ReplaceHidden),
+            _ => Err(anyhow::anyhow!(
+                "invalid name collision mode `{s}`; \
+                 expected `suffix`, `error`, or `replace-hidden`"
+            )),
+        }
+    }
+}
+
+/// Element of a patch name template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(String),
+    Slug,
+    Prefix,
+    Date,
+    Counter,
+    Branch,
+}
+
+/// Generator of patch names for patches created from commit messages.
+///
+/// Names are generated according to the `stgit.nametemplate` template, which defaults
+/// to `%(slug)`, and are limited in length by `stgit.namelength`. The template may
+/// contain these placeholders:
+///
+/// - `%(slug)`: the first line of the message made into a valid patch name.
+/// - `%(prefix)`: the value of `stgit.nameprefix`.
+/// - `%(date)`: the current date, as YYYY-MM-DD.
+/// - `%(counter)`: an integer counting up from one more than the number of patches in
+///   the stack, incremented for each generated name.
+/// - `%(branch)`: the name of the branch the patch comes from.
+///
+/// A literal '%' is written as `%%`.
+pub(crate) struct NameTemplate {
+    parts: Vec<TemplatePart>,
+    len_limit: Option<usize>,
+    prefix: String,
+    date: String,
+    counter: usize,
+    branch: String,
+}
+
+impl NameTemplate {
+    /// Get the name template configured for the stack.
+    ///
+    /// An invalid `stgit.nametemplate` is an error.
+    pub(crate) fn new<'repo>(stack: &impl StackAccess<'repo>) -> anyhow::Result<Self> {
+        let config = stack.config();
+        let parts = if let Some(template) = config.string("stgit.nametemplate") {
+            let template = template
+                .to_str()
+                .map_err(|_| anyhow!("`stgit.nametemplate` is not valid UTF-8"))?;
+            Self::parse(template)?
+        } else {
+            vec![TemplatePart::Slug]
+        };
+        let prefix = config
+            .string("stgit.nameprefix")
+            .map(|prefix| prefix.to_string())
+            .unwrap_or_default();
+        let date = stack
+            .head()
+            .repo
+            .get_committer()?
+            .time
+            .format(gix::date::time::format::SHORT);
+        Ok(Self {
+            parts,
+            len_limit: PatchName::get_length_limit(&config),
+            prefix,
+            date,
+            counter: stack.all_patches().count() + 1,
+            branch: stack.get_branch_name().to_string(),
+        })
+    }
+
+    fn parse(template: &str) -> anyhow::Result<Vec<TemplatePart>> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(pos) = rest.find('%') {
+            literal.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+            if let Some(after) = rest.strip_prefix("%%") {
+                literal.push('%');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("%(") {
+                let end = after.find(')').ok_or_else(|| {
+                    anyhow!("unterminated placeholder `{rest}` in `stgit.nametemplate`")
+                })?;
+                let part = match &after[..end] {
+                    "slug" => TemplatePart::Slug,
+                    "prefix" => TemplatePart::Prefix,
+                    "date" => TemplatePart::Date,
+                    "counter" => TemplatePart::Counter,
+                    "branch" => TemplatePart::Branch,
+                    name => {
+                        return Err(anyhow!(
+                            "invalid placeholder `%({name})` in `stgit.nametemplate`; \
+                             expected `%(slug)`, `%(prefix)`, `%(date)`, `%(counter)`, \
+                             or `%(branch)`"
+                        ))
+                    }
+                };
+                if !literal.is_empty() {
+                    parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(part);
+                rest = &after[end + 1..];
+            } else {
+                literal.push('%');
+                rest = &rest[1..];
+            }
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        Ok(parts)
+    }
+
+    /// Use the given branch name for `%(branch)` instead of the stack's branch.
+    #[must_use]
+    pub(crate) fn with_branch(mut self, branch: &str) -> Self {
+        self.branch = branch.to_string();
+        self
+    }
+
+    /// Get the length limit of generated patch names.
+    pub(crate) fn len_limit(&self) -> Option<usize> {
+        self.len_limit
+    }
+
+    /// Generate a patch name from a raw string, e.g. a commit message.
+    ///
+    /// The slug is made from the raw string as by [`PatchName::make()`], shortened such
+    /// that the whole name fits within the length limit, if possible.
+    pub(crate) fn make(&mut self, raw: &str, lower: bool) -> anyhow::Result<PatchName> {
+        if self.parts == [TemplatePart::Slug] {
+            return Ok(PatchName::make(raw, lower, self.len_limit));
+        }
+
+        let counter = self.counter.to_string();
+        self.counter += 1;
+        let branch = PatchName::make(&self.branch, false, None);
+        let mut fixed_len = 0;
+        let mut num_slugs = 0;
+        for part in &self.parts {
+            fixed_len += match part {
+                TemplatePart::Literal(s) => s.len(),
+                TemplatePart::Slug => {
+                    num_slugs += 1;
+                    0
+                }
+                TemplatePart::Prefix => self.prefix.len(),
+                TemplatePart::Date => self.date.len(),
+                TemplatePart::Counter => counter.len(),
+                TemplatePart::Branch => branch.len(),
+            };
+        }
+        let slug = if num_slugs > 0 {
+            let slug_limit = self
+                .len_limit
+                .filter(|&limit| limit > 0)
+                .map(|limit| (limit.saturating_sub(fixed_len) / num_slugs).max(1));
+            PatchName::make(raw, lower, slug_limit).0
+        } else {
+            String::new()
+        };
+
+        let mut name = String::with_capacity(fixed_len + num_slugs * slug.len());
+        for part in &self.parts {
+            name.push_str(match part {
+                TemplatePart::Literal(s) => s,
+                TemplatePart::Slug => &slug,
+                TemplatePart::Prefix => &self.prefix,
+                TemplatePart::Date => &self.date,
+                TemplatePart::Counter => &counter,
+                TemplatePart::Branch => branch.as_ref(),
+            });
+        }
+        PatchName::try_from(name).context("generating patch name with `stgit.nametemplate`")
+    }
+}
+
+/// Display adapter for patch names in human-facing output.
+///
+/// See [`PatchName::quoted()`] and [`PatchName::quoted_if()`].
+pub(crate) struct QuotedPatchName<'a> {
+    name: &'a PatchName,
+    enabled: bool,
+}
+
+impl std::fmt::Display for QuotedPatchName<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.name.as_ref();
+        if self.enabled && PatchName::needs_quoting(name) {
+            f.pad(&format!("'{}'", name.replace('\'', "'\\''")))
+        } else {
+            f.pad(name)
+        }
+    }
+}
+
 impl std::fmt::Debug for PatchName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("PatchName(\"{}\")", self.0))
@@ -35,6 +280,43 @@ impl std::fmt::Debug for PatchName {
 }
 
 impl PatchName {
+    /// Get a displayable form of the patch name suitable for human-facing output.
+    ///
+    /// Patch names containing characters that are special to the shell are enclosed
+    /// in single quotes, with any embedded single quotes escaped, such that the name
+    /// may be copy-pasted into a shell command line. This is akin to how git quotes
+    /// unusual paths. Names without such characters are displayed as-is.
+    ///
+    /// Messages, such as errors and warnings, always quote names this way. Output
+    /// intended for machine consumption should use the plain [`std::fmt::Display`]
+    /// implementation.
+    pub(crate) fn quoted(&self) -> QuotedPatchName<'_> {
+        self.quoted_if(true)
+    }
+
+    /// Get a displayable form of the patch name that is only quoted, as with
+    /// [`PatchName::quoted()`], when `enabled`.
+    ///
+    /// Used for output whose quoting is configurable, e.g. by `stgit.quotenames`.
+    pub(crate) fn quoted_if(&self, enabled: bool) -> QuotedPatchName<'_> {
+        QuotedPatchName {
+            name: self,
+            enabled,
+        }
+    }
+
+    /// Determine whether a patch name needs quoting to be safely used in a shell.
+    ///
+    /// Only ASCII characters are special to the shell, so non-ASCII characters never
+    /// require quoting.
+    fn needs_quoting(name: &str) -> bool {
+        !name.chars().all(|c| {
+            !c.is_ascii()
+                || c.is_ascii_alphanumeric()
+                || matches!(c, '-' | '_' | '.' | '/' | '+' | ',' | '=' | '@' | '%')
+        })
+    }
+
     /// Length of patch name string, in bytes.
     pub(crate) fn len(&self) -> usize {
         self.0.len()
@@ -150,7 +432,7 @@ impl PatchName {
     }
 
     /// Get the configured patch name length limit.
-    pub(crate) fn get_length_limit(config: &gix::config::Snapshot) -> Option<usize> {
+    pub(crate) fn get_length_limit(config: &StackConfig) -> Option<usize> {
         config
             .integer("stgit.namelength")
             .and_then(|n| usize::try_from(n).ok())
@@ -160,11 +442,13 @@ impl PatchName {
     /// Make patch name unique relative to provided list of disallowed names.
     ///
     /// If the patch name conflicts with a name in the `disallow` slice, it will be
-    /// suffixed with a unique integer. E.g. "patch-1".
+    /// suffixed with a unique integer. E.g. "patch-1". If `len_limit` is provided and
+    /// the patch name is within the limit, the name is shortened as needed for the
+    /// suffixed name to also be within the limit.
     ///
     /// If the patch name matches with the `allow` slice, the patch name will be used
     /// without modification.
-    pub(crate) fn uniquify<P>(self, allow: &[P], disallow: &[P]) -> Self
+    pub(crate) fn uniquify<P>(self, allow: &[P], disallow: &[P], len_limit: Option<usize>) -> Self
     where
         P: AsRef<PatchName>,
     {
@@ -175,18 +459,112 @@ impl PatchName {
             {
                 break candidate;
             } else {
-                let inner = &candidate.0;
-                let base = inner.trim_end_matches(|c: char| c.is_ascii_digit());
-                let num_digits = inner.len() - base.len();
-                candidate = if num_digits > 0 {
-                    let digits_str = &inner[inner.len() - num_digits..];
-                    let n = digits_str.parse::<usize>().unwrap() + 1;
-                    Self(format!("{base}{n}"))
-                } else {
-                    Self(format!("{base}-1"))
+                candidate = candidate.next_suffixed(len_limit);
+            }
+        }
+    }
+
+    /// Make patch name unique, resolving collisions with hidden patches per `mode`.
+    ///
+    /// Collisions with names in `disallow` are always resolved by suffixing, as with
+    /// [`PatchName::uniquify()`]. A collision with a name in `hidden` is resolved
+    /// according to `mode`. With [`NameCollision::ReplaceHidden`], the colliding
+    /// hidden patch name is returned alongside the unique name; the caller is then
+    /// responsible for moving that hidden patch out of the way.
+    pub(crate) fn uniquify_hidden<P>(
+        self,
+        disallow: &[P],
+        hidden: &[P],
+        mode: NameCollision,
+        len_limit: Option<usize>,
+    ) -> Result<(Self, Option<PatchName>), Error>
+    where
+        P: AsRef<PatchName>,
+    {
+        let mut candidate = self;
+        loop {
+            if disallow.iter().any(|pn| candidate.collides(pn.as_ref())) {
+                candidate = candidate.next_suffixed(len_limit);
+            } else if let Some(hidden) = hidden.iter().find(|pn| candidate.collides(pn.as_ref())) {
+                let hidden = hidden.as_ref().clone();
+                match mode {
+                    NameCollision::Suffix => candidate = candidate.next_suffixed(len_limit),
+                    NameCollision::Error => {
+                        break Err(Error::HiddenCollision {
+                            patchname: candidate,
+                            hidden,
+                        })
+                    }
+                    NameCollision::ReplaceHidden => break Ok((candidate, Some(hidden))),
                 }
+            } else {
+                break Ok((candidate, None));
+            }
+        }
+    }
+
+    /// Make patch name unique within a stack, resolving collisions with hidden patches
+    /// per `mode`.
+    ///
+    /// The names in `taken` are treated as if they were visible patches of the stack;
+    /// e.g. names already chosen for other new patches. If a hidden patch's name is
+    /// taken, the hidden patch's name is returned along with a unique `.old`-suffixed
+    /// name that the hidden patch should be renamed to.
+    pub(crate) fn uniquify_in_stack<'repo>(
+        self,
+        stack: &impl StackStateAccess<'repo>,
+        taken: &[PatchName],
+        mode: NameCollision,
+        len_limit: Option<usize>,
+    ) -> Result<(Self, Option<(PatchName, PatchName)>), Error> {
+        let disallow: Vec<&PatchName> = stack.applied_and_unapplied().chain(taken).collect();
+        let hidden: Vec<&PatchName> = stack.hidden().iter().collect();
+        let (patchname, replaced) = self.uniquify_hidden(&disallow, &hidden, mode, len_limit)?;
+        let replaced = replaced.map(|hidden| {
+            let disallow: Vec<&PatchName> = stack
+                .all_patches()
+                .chain(taken)
+                .chain(std::iter::once(&patchname))
+                .collect();
+            let aside = Self(format!("{hidden}.old")).uniquify(&[], &disallow, None);
+            (hidden, aside)
+        });
+        Ok((patchname, replaced))
+    }
+
+    /// Get the next candidate name when uniquifying, e.g. "patch-1" for "patch".
+    ///
+    /// If the name is within `len_limit` but the suffixed name would not be, the base
+    /// of the name is shortened to make room for the suffix.
+    fn next_suffixed(&self, len_limit: Option<usize>) -> Self {
+        let inner = &self.0;
+        let base = inner.trim_end_matches(|c: char| c.is_ascii_digit());
+        let num_digits = inner.len() - base.len();
+        let (base, suffix) = if num_digits > 0 {
+            let digits_str = &inner[inner.len() - num_digits..];
+            let n = digits_str.parse::<usize>().unwrap() + 1;
+            (base, n.to_string())
+        } else {
+            (inner.as_str(), "-1".to_string())
+        };
+        if let Some(limit) = len_limit
+            .filter(|&limit| limit > 0 && inner.len() <= limit && base.len() + suffix.len() > limit)
+        {
+            let (stem, separator) = base
+                .strip_suffix('-')
+                .map_or((base, ""), |stem| (stem, "-"));
+            let mut end = limit
+                .saturating_sub(separator.len() + suffix.len())
+                .min(stem.len());
+            while !stem.is_char_boundary(end) {
+                end -= 1;
+            }
+            let stem = stem[..end].trim_end_matches(['-', '.']);
+            if !stem.is_empty() {
+                return Self(format!("{stem}{separator}{suffix}"));
             }
         }
+        Self(format!("{base}{suffix}"))
     }
 
     /// Test if another patch name is the same as self, ignoring case.
@@ -198,6 +576,30 @@ impl PatchName {
         self.0.eq_ignore_ascii_case(&other.0)
     }
 
+    /// Find pairs of patch names that differ only by case.
+    ///
+    /// Each colliding name is paired with the first name it collides with, in the
+    /// order the names are provided.
+    pub(crate) fn case_collisions<'a>(
+        patchnames: impl IntoIterator<Item = &'a PatchName>,
+    ) -> Vec<(&'a PatchName, &'a PatchName)> {
+        let mut seen: BTreeMap<String, &PatchName> = BTreeMap::new();
+        let mut collisions = Vec::new();
+        for patchname in patchnames {
+            match seen.entry(patchname.0.to_ascii_lowercase()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(patchname);
+                }
+                Entry::Occupied(entry) => {
+                    if *entry.get() != patchname {
+                        collisions.push((*entry.get(), patchname));
+                    }
+                }
+            }
+        }
+        collisions
+    }
+
     pub(crate) fn constrain<'repo>(
         self,
         stack: &impl StackStateAccess<'repo>,
@@ -420,9 +822,161 @@ mod tests {
         ];
 
         for (raw, expected, len_limit) in cases.iter() {
-            let unique = PatchName::make(raw, true, *len_limit)
-                .uniquify(allow.as_slice(), disallow.as_slice());
+            let unique = PatchName::make(raw, true, *len_limit).uniquify(
+                allow.as_slice(),
+                disallow.as_slice(),
+                *len_limit,
+            );
             assert_eq!(&unique.0, expected);
         }
     }
+
+    #[test]
+    fn make_unique_patch_names_within_limit() {
+        let disallow: Vec<PatchName> = ["abcde-fghi", "abcde-fg-1", "abcde-fghijk"]
+            .iter()
+            .map(|s| PatchName(s.to_string()))
+            .collect();
+        let unique = |name: &str| PatchName(name.into()).uniquify(&[], &disallow, Some(10)).0;
+        assert_eq!(unique("abcde-fghi"), "abcde-fg-2");
+        assert_eq!(unique("abcde-fgh"), "abcde-fgh");
+        // Names already over the limit are suffixed as-is.
+        assert_eq!(unique("abcde-fghijk"), "abcde-fghijk-1");
+
+        let next = |name: &str| PatchName(name.into()).next_suffixed(Some(10)).0;
+        assert_eq!(next("abcde-fg-9"), "abcde-f-10");
+        assert_eq!(next("abcdefghij"), "abcdefgh-1");
+        assert_eq!(next("abcdefg.hi"), "abcdefg-1");
+        assert_eq!(next("ééééé"), "éééé-1");
+        assert_eq!(next("short"), "short-1");
+    }
+
+    #[test]
+    fn make_template_patch_names() {
+        let mut template = NameTemplate {
+            parts: NameTemplate::parse("%(prefix)%(slug)-%(counter)%%").unwrap(),
+            len_limit: Some(16),
+            prefix: String::from("wip-"),
+            date: String::from("2024-05-06"),
+            counter: 3,
+            branch: String::from("feature/x"),
+        };
+        assert_eq!(
+            template.make("Add the frobnicator", true).unwrap().0,
+            "wip-add-the-3%"
+        );
+        assert_eq!(template.make("!!!", true).unwrap().0, "wip-patch-4%");
+        assert_eq!(
+            template.make("Überlange Änderung", false).unwrap().0,
+            "wip-Überlange-5%"
+        );
+
+        template.parts = NameTemplate::parse("%(date)-%(branch)").unwrap();
+        template.len_limit = None;
+        assert_eq!(
+            template.make("ignored", true).unwrap().0,
+            "2024-05-06-feature-x"
+        );
+
+        template.parts = NameTemplate::parse("%(slug)").unwrap();
+        template.len_limit = Some(8);
+        assert_eq!(template.make("one two three", true).unwrap().0, "one-two");
+
+        template.parts = NameTemplate::parse("%(prefix)%(slug)").unwrap();
+        template.prefix = String::from(".bad");
+        assert!(template.make("name", true).is_err());
+    }
+
+    #[test]
+    fn parse_bad_name_templates() {
+        for (template, message) in [
+            (
+                "%(slug)-%(dat)",
+                "invalid placeholder `%(dat)` in `stgit.nametemplate`",
+            ),
+            ("%(slug", "unterminated placeholder `%(slug` in"),
+        ] {
+            let err = NameTemplate::parse(template).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{err}");
+        }
+        assert_eq!(
+            NameTemplate::parse("100%").unwrap(),
+            [TemplatePart::Literal(String::from("100%"))]
+        );
+    }
+
+    #[test]
+    fn make_unique_patch_names_with_hidden() {
+        let disallow = [PatchName("patch".into()), PatchName("patch-2".into())];
+        let hidden = [PatchName("patch-1".into()), PatchName("Patch-3".into())];
+        let make = |raw: &str, mode| {
+            PatchName(raw.into()).uniquify_hidden(
+                disallow.as_slice(),
+                hidden.as_slice(),
+                mode,
+                None,
+            )
+        };
+
+        let (name, replaced) = make("patch", NameCollision::Suffix).unwrap();
+        assert_eq!(name.0, "patch-4");
+        assert_eq!(replaced, None);
+
+        let (name, replaced) = make("patch", NameCollision::ReplaceHidden).unwrap();
+        assert_eq!(name.0, "patch-1");
+        assert_eq!(replaced, Some(PatchName("patch-1".into())));
+
+        let (name, replaced) = make("patch-2", NameCollision::ReplaceHidden).unwrap();
+        assert_eq!(name.0, "patch-3");
+        assert_eq!(replaced, Some(PatchName("Patch-3".into())));
+
+        match make("patch-2", NameCollision::Error) {
+            Err(Error::HiddenCollision { patchname, hidden }) => {
+                assert_eq!(patchname.0, "patch-3");
+                assert_eq!(hidden.0, "Patch-3");
+            }
+            _ => panic!("expected hidden collision"),
+        }
+
+        let (name, replaced) = make("other", NameCollision::Error).unwrap();
+        assert_eq!(name.0, "other");
+        assert_eq!(replaced, None);
+    }
+
+    #[test]
+    fn find_case_collisions() {
+        let names: Vec<PatchName> = ["Fix-IO", "other", "fix-io", "OTHER-2", "FIX-io", "other"]
+            .iter()
+            .map(|s| PatchName(s.to_string()))
+            .collect();
+        let collisions: Vec<(&str, &str)> = PatchName::case_collisions(&names)
+            .into_iter()
+            .map(|(pn0, pn1)| (pn0.as_ref(), pn1.as_ref()))
+            .collect();
+        assert_eq!(collisions, [("Fix-IO", "fix-io"), ("Fix-IO", "FIX-io")]);
+
+        let names = [PatchName("p0".into()), PatchName("p1".into())];
+        assert!(PatchName::case_collisions(&names).is_empty());
+    }
+
+    #[test]
+    fn quote_patch_names() {
+        for (name, quoted) in [
+            ("plain-name_1.2", "plain-name_1.2"),
+            ("café-1", "café-1"),
+            ("-😼-", "-😼-"),
+            ("a\u{a0}patch", "a\u{a0}patch"),
+            ("a#patch$", "'a#patch$'"),
+            ("(a-patch)", "'(a-patch)'"),
+            ("x;y|z", "'x;y|z'"),
+            ("'single'", "''\\''single'\\'''"),
+        ] {
+            let patchname = PatchName::from_str(name).unwrap();
+            assert_eq!(patchname.quoted().to_string(), quoted);
+            assert_eq!(patchname.to_string(), name);
+        }
+        let patchname = PatchName::from_str("x;y").unwrap();
+        assert_eq!(format!("[{:7}]", patchname.quoted()), "['x;y'  ]");
+        assert_eq!(patchname.quoted_if(false).to_string(), "x;y");
+    }
 }
@@ -1,10 +1,16 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
 //! Parsing support for [`PatchLocator`].
+//!
+//! The syntax parsed here is documented for users by `stg help locators`, whose text
+//! follows.
+//!
+#![doc = include_str!("../../help/locators.md")]
 
 use winnow::{
     ascii::hex_digit1,
-    combinator::{alt, opt, repeat},
+    combinator::{alt, not, opt, preceded, repeat, terminated},
+    token::none_of,
     ModalResult, Parser,
 };
 
@@ -21,6 +27,7 @@ pub(in super::super) fn patch_locator(input: &mut &str) -> ModalResult<PatchLoca
         patch_locator_from_last,
         patch_locator_top,
         patch_locator_base,
+        patch_locator_message,
     ))
     .parse_next(input)
 }
@@ -66,6 +73,31 @@ pub(in super::super) fn patch_locator_base(input: &mut &str) -> ModalResult<Patc
         .parse_next(input)
 }
 
+/// Parse a `:/<pattern>` locator for patches with messages containing the pattern.
+///
+/// The pattern ends at the first `~`, `+`, or `^`, or at a `..` range separator, such
+/// that offsets and git revision suffixes may follow the pattern.
+fn patch_locator_message(input: &mut &str) -> ModalResult<PatchLocator> {
+    (preceded(":/", message_pattern), patch_offsets)
+        .map(|(pattern, offsets)| PatchLocator {
+            id: PatchId::MessagePattern(pattern.to_string()),
+            offsets,
+        })
+        .parse_next(input)
+}
+
+fn message_pattern<'s>(input: &mut &'s str) -> ModalResult<&'s str> {
+    repeat::<_, _, (), _, _>(
+        1..,
+        alt((
+            none_of(['~', '+', '^', '.']).void(),
+            terminated('.', not('.')).void(),
+        )),
+    )
+    .take()
+    .parse_next(input)
+}
+
 pub(in super::super) fn patch_offsets(input: &mut &str) -> ModalResult<PatchOffsets> {
     repeat::<_, _, Vec<PatchOffsetAtom>, _, _>(0.., patch_offset_atom)
         .take()
@@ -1,17 +1,26 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
 //! Parsing support for [`PatchRange`] and [`PatchRangeBounds`].
+//!
+//! The syntax parsed here is documented for users by `stg help patterns`, whose text
+//! follows.
+//!
+#![doc = include_str!("../../help/patterns.md")]
 
 use winnow::{
-    combinator::{alt, opt, separated_pair},
+    combinator::{alt, delimited, eof, opt, preceded, separated_pair, terminated},
+    token::take_while,
     ModalResult, Parser,
 };
 
 use super::patch_locator;
-use crate::patch::{PatchRange, PatchRangeBounds};
+use crate::patch::{PatchLocator, PatchRange, PatchRangeBounds};
 
 pub(in super::super) fn patch_range(input: &mut &str) -> ModalResult<PatchRange> {
     alt((
+        patch_label.map(|label: &str| PatchRange::Label(label.to_string())),
+        patch_exclusion.map(PatchRange::Exclude),
+        patch_glob.map(|pattern: &str| PatchRange::Glob(pattern.to_string())),
         patch_range_bounds.map(PatchRange::Range),
         patch_locator.map(PatchRange::Single),
     ))
@@ -23,3 +32,37 @@ pub(super) fn patch_range_bounds(input: &mut &str) -> ModalResult<PatchRangeBoun
         .map(|(begin, end)| PatchRangeBounds { begin, end })
         .parse_next(input)
 }
+
+/// Parse a `%{<label>}` patch label reference.
+pub(in super::super) fn patch_label<'s>(input: &mut &'s str) -> ModalResult<&'s str> {
+    delimited("%{", take_while(1.., is_label_char), "}").parse_next(input)
+}
+
+/// Parse a `!<locator>` patch exclusion.
+pub(in super::super) fn patch_exclusion(input: &mut &str) -> ModalResult<PatchLocator> {
+    terminated(preceded('!', patch_locator), eof).parse_next(input)
+}
+
+/// Parse a glob pattern for matching patch names.
+///
+/// A pattern must contain at least one of `*`, `?`, or `[`, none of which are allowed
+/// in patch names. A leading `!` is reserved for exclusions.
+pub(in super::super) fn patch_glob<'s>(input: &mut &'s str) -> ModalResult<&'s str> {
+    take_while(1.., is_glob_char)
+        .verify(|pattern: &str| {
+            pattern.contains(['*', '?', '['])
+                && !pattern.contains("..")
+                && !pattern.starts_with('!')
+        })
+        .parse_next(input)
+}
+
+/// Determine whether a character is allowed in a patch name glob pattern.
+fn is_glob_char(c: char) -> bool {
+    !(c.is_whitespace() || c.is_control() || c == '/')
+}
+
+/// Determine whether a character is allowed in a patch label.
+pub(in super::super) fn is_label_char(c: char) -> bool {
+    !(c.is_whitespace() || c.is_control() || matches!(c, '{' | '}' | '%'))
+}
@@ -6,14 +6,16 @@
 
 use winnow::{
     ascii::{digit1, take_escaped},
-    combinator::{alt, delimited, opt, preceded, repeat, terminated},
+    combinator::{alt, delimited, opt, preceded, repeat, separated_pair, terminated},
     stream::Stream,
     token::{none_of, one_of},
     ModalResult, Parser,
 };
 
 use super::{
-    super::{GitRevisionSuffix, PatchLikeSpec, RangeRevisionSpec, SingleRevisionSpec},
+    super::{
+        GitRevisionSuffix, PatchLikeSpec, PatchRangeBounds, RangeRevisionSpec, SingleRevisionSpec,
+    },
     numbers::unsigned_int,
     patch_locator,
     range::patch_range_bounds,
@@ -22,7 +24,7 @@ use crate::{branchloc::BranchLocator, wrap::partial_ref_name};
 
 pub(in super::super) fn range_revision_spec(input: &mut &str) -> ModalResult<RangeRevisionSpec> {
     alt((
-        (branch_prefix, patch_range_bounds)
+        branch_range_bounds
             .map(|(branch_loc, bounds)| RangeRevisionSpec::BranchRange { branch_loc, bounds }),
         patch_range_bounds.map(RangeRevisionSpec::Range),
         single_revision_spec.map(RangeRevisionSpec::Single),
@@ -47,6 +49,26 @@ fn branch_prefix(input: &mut &str) -> ModalResult<BranchLocator> {
     terminated(branch_locator, ':').parse_next(input)
 }
 
+/// Parse a branch-qualified patch range, e.g. `branch:{base}..branch:@`.
+///
+/// The end of the range may repeat the branch prefix, but may not name another branch.
+fn branch_range_bounds(input: &mut &str) -> ModalResult<(BranchLocator, PatchRangeBounds)> {
+    let branch_loc = branch_prefix.parse_next(input)?;
+    let (begin, end) = separated_pair(
+        opt(patch_locator),
+        "..",
+        opt(alt((
+            preceded(
+                branch_prefix.verify(|end_loc: &BranchLocator| end_loc == &branch_loc),
+                patch_locator,
+            ),
+            patch_locator,
+        ))),
+    )
+    .parse_next(input)?;
+    Ok((branch_loc, PatchRangeBounds { begin, end }))
+}
+
 #[derive(Debug)]
 struct PrevCheckoutError(&'static str);
 
@@ -479,3 +479,62 @@ fn oid_prefix_parsing() {
         Err(ErrMode::Backtrack(_)),
     ));
 }
+
+#[test]
+fn message_pattern_locators() {
+    let pattern = |s: &str| PatchId::MessagePattern(s.to_string());
+
+    assert_eq!(
+        complete_locator(":/fix typo"),
+        PatchLocator {
+            id: pattern("fix typo"),
+            offsets: offsets(""),
+        }
+    );
+    assert_eq!(
+        complete_locator(":/fix typo~1"),
+        PatchLocator {
+            id: pattern("fix typo"),
+            offsets: offsets("~1"),
+        }
+    );
+    assert_eq!(
+        complete_locator(":/v1.2: a/b+~"),
+        PatchLocator {
+            id: pattern("v1.2: a/b"),
+            offsets: offsets("+~"),
+        }
+    );
+    assert_eq!(
+        good_locator(":/fix typo^{tree}"),
+        (
+            "^{tree}",
+            PatchLocator {
+                id: pattern("fix typo"),
+                offsets: offsets(""),
+            }
+        )
+    );
+    assert_eq!(
+        good_locator(":/fix..:/typo"),
+        (
+            "..:/typo",
+            PatchLocator {
+                id: pattern("fix"),
+                offsets: offsets(""),
+            }
+        )
+    );
+    assert!(matches!(
+        patch_locator.parse_peek(":/"),
+        Err(ErrMode::Backtrack(_))
+    ));
+    assert!(matches!(
+        patch_locator.parse_peek(":/~1"),
+        Err(ErrMode::Backtrack(_))
+    ));
+    assert!(matches!(
+        patch_locator.parse_peek(":fix"),
+        Err(ErrMode::Backtrack(_))
+    ));
+}
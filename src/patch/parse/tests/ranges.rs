@@ -98,4 +98,60 @@ fn range_parsing() {
             })
         )
     );
+    assert_eq!(
+        patch_range.parse_peek("%{net}").unwrap(),
+        ("", PatchRange::Label("net".to_string()))
+    );
+    assert!(!matches!(
+        patch_range.parse_peek("%{}"),
+        Ok((_, PatchRange::Label(_)))
+    ));
+    assert!(!matches!(
+        patch_range.parse_peek("%{a b}"),
+        Ok((_, PatchRange::Label(_)))
+    ));
+    assert_eq!(
+        patch_range.parse_peek("wip-*").unwrap(),
+        ("", PatchRange::Glob("wip-*".to_string()))
+    );
+    assert_eq!(
+        patch_range.parse_peek("p?").unwrap(),
+        ("", PatchRange::Glob("p?".to_string()))
+    );
+    assert_eq!(
+        patch_range.parse_peek("p[0-9]").unwrap(),
+        ("", PatchRange::Glob("p[0-9]".to_string()))
+    );
+    assert!(!matches!(
+        patch_range.parse_peek("wip-*..p3"),
+        Ok((_, PatchRange::Glob(_)))
+    ));
+    assert!(!matches!(
+        patch_range.parse_peek("wip/*"),
+        Ok(("", PatchRange::Glob(_)))
+    ));
+    assert_eq!(
+        patch_range.parse_peek("!p1").unwrap(),
+        (
+            "",
+            PatchRange::Exclude(PatchLocator {
+                id: PatchId::Name(name("p1")),
+                offsets: offsets(""),
+            })
+        )
+    );
+    assert_eq!(
+        patch_range.parse_peek("!~2").unwrap(),
+        (
+            "",
+            PatchRange::Exclude(PatchLocator {
+                id: PatchId::BelowTop(Some(2)),
+                offsets: offsets(""),
+            })
+        )
+    );
+    assert!(!matches!(
+        patch_range.parse_peek("!p1..p3"),
+        Ok((_, PatchRange::Exclude(_)))
+    ));
 }
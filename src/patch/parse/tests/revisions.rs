@@ -8,8 +8,9 @@ use super::{name, offsets};
 use crate::{
     branchloc::BranchLocator,
     patch::{
-        parse::{branch_locator, single_revision_spec, tilde_number},
-        GitRevisionSuffix, PatchId, PatchLikeSpec, PatchLocator, SingleRevisionSpec,
+        parse::{branch_locator, range_revision_spec, single_revision_spec, tilde_number},
+        GitRevisionSuffix, PatchId, PatchLikeSpec, PatchLocator, PatchRangeBounds,
+        RangeRevisionSpec, SingleRevisionSpec,
     },
     wrap::PartialRefName,
 };
@@ -192,6 +193,48 @@ fn single_specs() {
     );
 }
 
+#[test]
+fn branch_range_specs() {
+    let branch_loc = BranchLocator::Name(PartialRefName::from_str("other").unwrap());
+    let expected = RangeRevisionSpec::BranchRange {
+        branch_loc: branch_loc.clone(),
+        bounds: PatchRangeBounds {
+            begin: Some(PatchLocator {
+                id: PatchId::Base,
+                offsets: offsets(""),
+            }),
+            end: Some(PatchLocator {
+                id: PatchId::Top,
+                offsets: offsets("~1"),
+            }),
+        },
+    };
+    assert_eq!(
+        range_revision_spec
+            .parse("other:{base}..other:@~1")
+            .unwrap(),
+        expected
+    );
+    assert_eq!(
+        range_revision_spec.parse("other:{base}..@~1").unwrap(),
+        expected
+    );
+    assert_eq!(
+        range_revision_spec.parse("other:..other:^").unwrap(),
+        RangeRevisionSpec::BranchRange {
+            branch_loc,
+            bounds: PatchRangeBounds {
+                begin: None,
+                end: Some(PatchLocator {
+                    id: PatchId::BelowLast(None),
+                    offsets: offsets(""),
+                }),
+            },
+        }
+    );
+    assert!(range_revision_spec.parse("other:{base}..master:@").is_err());
+}
+
 #[test]
 fn branch_locators() {
     assert_eq!(
@@ -241,3 +284,31 @@ fn branch_locators() {
         )
     );
 }
+
+#[test]
+fn message_pattern_specs() {
+    assert_eq!(
+        single_revision_spec.parse(":/fix typo~1^{tree}").unwrap(),
+        SingleRevisionSpec::PatchLike(PatchLikeSpec {
+            patch_loc: PatchLocator {
+                id: PatchId::MessagePattern(String::from("fix typo")),
+                offsets: offsets("~1")
+            },
+            suffix: GitRevisionSuffix(String::from("^{tree}"))
+        })
+    );
+
+    assert_eq!(
+        single_revision_spec.parse("other::/fix typo").unwrap(),
+        SingleRevisionSpec::Branch {
+            branch_loc: BranchLocator::Name(PartialRefName::from_str("other").unwrap()),
+            patch_like: PatchLikeSpec {
+                patch_loc: PatchLocator {
+                    id: PatchId::MessagePattern(String::from("fix typo")),
+                    offsets: offsets("")
+                },
+                suffix: GitRevisionSuffix(String::from(""))
+            }
+        }
+    );
+}
@@ -2,10 +2,11 @@
 
 //! Implementations for [`PatchRange`] and [`PatchRangeBounds`].
 
-use std::str::FromStr;
+use std::{borrow::Cow, collections::HashSet, str::FromStr};
 
 use super::{
-    PatchName, PatchRange, PatchRangeBounds, RangeConstraint, StGitBoundaryRevisions, StGitRevision,
+    lookup::PatchLookup, LocationConstraint, PatchId, PatchLocator, PatchName, PatchRange,
+    PatchRangeBounds, RangeConstraint, StGitBoundaryRevisions, StGitRevision,
 };
 use crate::stack::{StackAccess, StackStateAccess};
 
@@ -21,17 +22,85 @@ pub(crate) enum Error {
     #[error("invalid patch range `{0}`")]
     InvalidPatchRange(String),
 
-    #[error("patch `{patchname}` is used more than once")]
+    #[error("invalid patch label `{0}`")]
+    InvalidLabel(String),
+
+    #[error("no patches are labeled `{0}`")]
+    UnknownLabel(String),
+
+    #[error("no patches match `{0}`")]
+    NoMatchingPatches(String),
+
+    #[error("excluded patch `{}` is not among the selected patches", .patchname.quoted())]
+    ExclusionNotSelected { patchname: PatchName },
+
+    #[error("excluding patch `{}` would split the selected patches", .patchname.quoted())]
+    ExclusionSplitsRange { patchname: PatchName },
+
+    #[error("patch `{}` is used more than once", .patchname.quoted())]
     Duplicate { patchname: PatchName },
 
+    #[error("exclusions, e.g. `!<patch>`, must be combined with patches to select")]
+    OnlyExclusions,
+
     #[error("`{range}` not contiguous with preceding range `{prev_range}`")]
     NotContiguous { range: String, prev_range: String },
 
-    #[error("end patch `{end_patchname}` is out of order with `{begin_patchname}`")]
+    #[error(
+        "including the patches in between would include hidden patch `{}`",
+        .patchname.quoted()
+    )]
+    HiddenInGap { patchname: PatchName },
+
+    #[error(
+        "end patch `{}` is out of order with `{}`",
+        .end_patchname.quoted(),
+        .begin_patchname.quoted()
+    )]
     BoundaryOrder {
         begin_patchname: PatchName,
         end_patchname: PatchName,
     },
+
+    #[error("{}", combine_messages(.0.iter().map(ToString::to_string)))]
+    Multiple(Vec<Error>),
+}
+
+impl Error {
+    /// Combine the errors from resolving several patch ranges into a single error.
+    ///
+    /// `None` is returned if there are no errors.
+    fn combine(mut errors: Vec<Error>) -> Option<Self> {
+        match errors.len() {
+            0 => None,
+            1 => errors.pop(),
+            _ => Some(Self::Multiple(errors)),
+        }
+    }
+
+    /// Convert each of the, possibly multiple, errors with `f`.
+    ///
+    /// This allows callers to rephrase specific errors while still reporting all of
+    /// them together.
+    pub(crate) fn map_each(self, mut f: impl FnMut(Error) -> anyhow::Error) -> anyhow::Error {
+        if let Self::Multiple(errors) = self {
+            anyhow::anyhow!(
+                "{}",
+                combine_messages(errors.into_iter().map(|err| format!("{:#}", f(err))))
+            )
+        } else {
+            f(self)
+        }
+    }
+}
+
+fn combine_messages(messages: impl ExactSizeIterator<Item = String>) -> String {
+    let mut combined = format!("{} errors resolving patches:", messages.len());
+    for message in messages {
+        combined.push_str("\n  ");
+        combined.push_str(&message);
+    }
+    combined
 }
 
 impl std::fmt::Display for PatchRange {
@@ -39,6 +108,9 @@ impl std::fmt::Display for PatchRange {
         match self {
             PatchRange::Single(patch_loc) => patch_loc.fmt(f),
             PatchRange::Range(bounds) => bounds.fmt(f),
+            PatchRange::Label(label) => write!(f, "%{{{label}}}"),
+            PatchRange::Glob(pattern) => pattern.fmt(f),
+            PatchRange::Exclude(patch_loc) => write!(f, "!{patch_loc}"),
         }
     }
 }
@@ -72,6 +144,11 @@ impl std::fmt::Display for PatchRangeBounds {
 }
 
 impl PatchRangeBounds {
+    /// Whether both the beginning and end of the range are given.
+    pub(crate) fn is_bounded(&self) -> bool {
+        self.begin.is_some() && self.end.is_some()
+    }
+
     /// Resolve patchname and commit object based on patch location and offsets.
     ///
     /// Unlike [`resolve_names()`], the offsets are allowed to land on commits outside
@@ -123,6 +200,238 @@ impl PatchRangeBounds {
 
         Ok(StGitBoundaryRevisions::Bounds((rev0, rev1)))
     }
+
+    /// Resolve the begin and end bounds into patch names from the allowed patches.
+    ///
+    /// An end bound consisting only of offsets, e.g. the `+3` in `fix-io+1..+3`, is
+    /// relative to the begin bound's anchor.
+    fn resolve_bound_names<'a, 'repo>(
+        &'a self,
+        stack: &'a impl StackStateAccess<'repo>,
+        lookup: &PatchLookup<'a>,
+        allow: RangeConstraint,
+    ) -> Result<(Option<PatchName>, Option<PatchName>), Error> {
+        let begin = self
+            .begin
+            .as_ref()
+            .map(|loc| loc.resolve_name_using(stack, lookup, allow.into()))
+            .transpose()?
+            .map(|pn| lookup.constrain(pn, allow.into()))
+            .transpose()?;
+
+        let end = self
+            .end
+            .as_ref()
+            .map(|loc| {
+                if let Some(anchor) = self.begin.as_ref() {
+                    loc.resolve_name_with_anchor(anchor, stack, lookup, allow.into())
+                } else {
+                    loc.resolve_name_using(stack, lookup, allow.into())
+                }
+            })
+            .transpose()?
+            .map(|pn| lookup.constrain(pn, allow.into()))
+            .transpose()?;
+
+        Ok((begin, end))
+    }
+}
+
+/// Check whether a string is valid for use as a patch label.
+///
+/// Labels may not be empty and may not contain whitespace, `%`, `{`, or `}`.
+pub(crate) fn check_label(label: &str) -> Result<(), Error> {
+    if !label.is_empty() && label.chars().all(super::parse::is_label_char) {
+        Ok(())
+    } else {
+        Err(Error::InvalidLabel(label.to_string()))
+    }
+}
+
+/// Check that no `:/<pattern>` locator in `ranges` matches patches with identical
+/// messages.
+///
+/// See [`PatchLocator::check_message_duplicates()`].
+pub(crate) fn check_message_duplicates<'a, 'repo>(
+    stack: &impl StackStateAccess<'repo>,
+    ranges: impl IntoIterator<Item = &'a PatchRange>,
+) -> Result<(), Error> {
+    for range in ranges {
+        match range {
+            PatchRange::Single(patch_loc) | PatchRange::Exclude(patch_loc) => {
+                patch_loc.check_message_duplicates(stack)?;
+            }
+            PatchRange::Range(PatchRangeBounds { begin, end }) => {
+                for patch_loc in begin.iter().chain(end.iter()) {
+                    patch_loc.check_message_duplicates(stack)?;
+                }
+            }
+            PatchRange::Label(_) | PatchRange::Glob(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Patches selected by patch ranges, in the order they were selected.
+struct Selection<'a> {
+    patches: Vec<&'a PatchName>,
+    selected: HashSet<&'a PatchName>,
+}
+
+impl<'a> Selection<'a> {
+    fn new() -> Self {
+        Self {
+            patches: Vec::new(),
+            selected: HashSet::new(),
+        }
+    }
+
+    /// Select a patch, which must not already be selected.
+    fn push(&mut self, patchname: &'a PatchName) -> Result<(), Error> {
+        if self.selected.insert(patchname) {
+            self.patches.push(patchname);
+            Ok(())
+        } else {
+            Err(Error::Duplicate {
+                patchname: patchname.clone(),
+            })
+        }
+    }
+
+    fn contains(&self, patchname: &PatchName) -> bool {
+        self.selected.contains(patchname)
+    }
+}
+
+/// Get the allowed patches having the given label, in stack order.
+fn labeled_patches<'a, 'repo>(
+    stack: &impl StackStateAccess<'repo>,
+    label: &str,
+    allowed_patches: &[&'a PatchName],
+) -> Result<Vec<&'a PatchName>, Error> {
+    let labels = stack.labels();
+    if labels.values().any(|labels| labels.contains(label)) {
+        Ok(allowed_patches
+            .iter()
+            .filter(|pn| {
+                labels
+                    .get(**pn)
+                    .is_some_and(|labels| labels.contains(label))
+            })
+            .copied()
+            .collect())
+    } else {
+        Err(Error::UnknownLabel(label.to_string()))
+    }
+}
+
+/// Get the allowed patches with names matching the glob pattern, in stack order.
+///
+/// It is an error for none of the allowed patches to match.
+fn matching_patches<'a>(
+    pattern: &str,
+    allowed_patches: &[&'a PatchName],
+) -> Result<Vec<&'a PatchName>, Error> {
+    use gix::glob::wildmatch;
+    let matching: Vec<&PatchName> = allowed_patches
+        .iter()
+        .filter(|pn| {
+            let name: &str = pn.as_ref();
+            wildmatch(pattern.into(), name.into(), wildmatch::Mode::empty())
+        })
+        .copied()
+        .collect();
+    if matching.is_empty() {
+        Err(Error::NoMatchingPatches(pattern.to_string()))
+    } else {
+        Ok(matching)
+    }
+}
+
+/// Separate the exclusions, i.e. `!<locator>`, from the other patch ranges.
+///
+/// An exclusion spelled like the name of an existing patch, e.g. a patch actually named
+/// `!patch`, refers to that patch instead. It is an error to only provide exclusions;
+/// commands that select all patches by default, e.g. `stg push --all`, provide the
+/// range of all patches, [`PatchRange::all()`], along with the exclusions.
+fn split_exclusions<'a, 'repo>(
+    stack: &impl StackStateAccess<'repo>,
+    ranges: impl IntoIterator<Item = &'a PatchRange>,
+) -> Result<(Vec<Cow<'a, PatchRange>>, Vec<&'a PatchLocator>), Error> {
+    let mut inclusions = Vec::new();
+    let mut exclusions = Vec::new();
+    for range in ranges {
+        if let PatchRange::Exclude(patch_loc) = range {
+            if let Some(literal_loc) = PatchLocator::from_str(&range.to_string())
+                .ok()
+                .filter(|loc| matches!(&loc.id, PatchId::Name(pn) if stack.has_patch(pn)))
+            {
+                inclusions.push(Cow::Owned(PatchRange::Single(literal_loc)));
+            } else {
+                exclusions.push(patch_loc);
+            }
+        } else {
+            inclusions.push(Cow::Borrowed(range));
+        }
+    }
+    if inclusions.is_empty() && !exclusions.is_empty() {
+        Err(Error::OnlyExclusions)
+    } else {
+        Ok((inclusions, exclusions))
+    }
+}
+
+/// Resolve the located patch into the stack's patch name, constrained to the allowed
+/// patches.
+fn resolve_single<'a, 'repo>(
+    patch_loc: &PatchLocator,
+    stack: &'a impl StackStateAccess<'repo>,
+    lookup: &PatchLookup<'a>,
+    allow: RangeConstraint,
+) -> Result<&'a PatchName, Error> {
+    let patchname = lookup.constrain(
+        patch_loc.resolve_name_using(stack, lookup, allow.into())?,
+        allow.into(),
+    )?;
+    Ok(lookup
+        .get(patchname.as_ref())
+        .expect("patchname already constrained to the allowed patches"))
+}
+
+/// Resolve the exclusions into names of patches from the stack.
+///
+/// When `selection` is provided, it is an error for an excluded patch to not be
+/// among the selected patches. Errors are added to `errors` such that all exclusions
+/// are resolved.
+fn resolve_exclusions<'a, 'repo>(
+    stack: &'a impl StackStateAccess<'repo>,
+    lookup: &PatchLookup<'a>,
+    exclusions: &[&'a PatchLocator],
+    selection: Option<&Selection<'a>>,
+    errors: &mut Vec<Error>,
+) -> HashSet<&'a PatchName> {
+    let mut excluded = HashSet::with_capacity(exclusions.len());
+    for patch_loc in exclusions {
+        let patchname = match patch_loc.resolve_name_using(stack, lookup, LocationConstraint::All) {
+            Ok(patchname) => lookup.get(patchname.as_ref()).expect("patch exists"),
+            Err(err) => {
+                errors.push(err.into());
+                continue;
+            }
+        };
+        if excluded.contains(patchname) {
+            errors.push(Error::Duplicate {
+                patchname: patchname.clone(),
+            });
+        } else if selection.is_some_and(|selection| !selection.contains(patchname)) {
+            errors.push(Error::ExclusionNotSelected {
+                patchname: patchname.clone(),
+            });
+        } else {
+            excluded.insert(patchname);
+        }
+    }
+    excluded
 }
 
 /// Resolve user-provided patch ranges into patch names.
@@ -132,6 +441,12 @@ impl PatchRangeBounds {
 ///
 /// It is an error for the same patch to be duplicated in any of the provided ranges.
 ///
+/// Exclusions, i.e. `!<locator>`, are removed from the patches selected by the other
+/// ranges. It is an error for an exclusion to name a patch that is not selected.
+///
+/// All ranges are resolved even when some of them fail, such that the errors for all
+/// the failing ranges are reported together with [`Error::Multiple`].
+///
 /// The ordering of patches as found in `ranges` does not have to match the ordering
 /// found in the stack. See [`resolve_names_contiguous()`] for a similar function which
 /// does impose this ordering constraint.
@@ -140,194 +455,321 @@ pub(crate) fn resolve_names<'a, 'repo>(
     ranges: impl IntoIterator<Item = &'a PatchRange>,
     allow: RangeConstraint,
 ) -> Result<Vec<PatchName>, Error> {
-    let allowed_patches: Vec<&PatchName> = stack.get_allowed(allow.into());
-    let mut patches: Vec<PatchName> = Vec::new();
+    let lookup = PatchLookup::new(stack);
+    let selection = select_names(stack, &lookup, ranges, allow)?;
+    Ok(selection.patches.into_iter().cloned().collect())
+}
 
-    for range in ranges {
-        match range {
-            PatchRange::Range(PatchRangeBounds { begin, end }) => {
-                let begin = begin
-                    .as_ref()
-                    .map(|loc| loc.resolve_name(stack))
-                    .transpose()?
-                    .map_or_else(
-                        || Ok(None),
-                        |pn| Some(pn.constrain(stack, allow.into())).transpose(),
-                    )?;
-
-                let end = end
-                    .as_ref()
-                    .map(|loc| loc.resolve_name(stack))
-                    .transpose()?
-                    .map_or_else(
-                        || Ok(None),
-                        |pn| Some(pn.constrain(stack, allow.into())).transpose(),
-                    )?;
-
-                let begin_pos = begin.map_or(0, |patchname| {
-                    allowed_patches
-                        .iter()
-                        .position(|&pn| pn == &patchname)
-                        .expect("begin patchname already constrained to the allowed patches")
-                });
+/// Select the patches for [`resolve_names()`] using the stack's lookup tables.
+fn select_names<'a, 'repo>(
+    stack: &'a impl StackStateAccess<'repo>,
+    lookup: &PatchLookup<'a>,
+    ranges: impl IntoIterator<Item = &'a PatchRange>,
+    allow: RangeConstraint,
+) -> Result<Selection<'a>, Error> {
+    let allowed_patches = lookup.allowed(allow.into());
+    let mut selection = Selection::new();
+    let mut errors = Vec::new();
+    let (inclusions, exclusions) = split_exclusions(stack, ranges)?;
+
+    for range in inclusions.iter().map(Cow::as_ref) {
+        if let Err(err) = select_range(stack, lookup, range, allow, allowed_patches, &mut selection)
+        {
+            errors.push(err);
+        }
+    }
 
-                let end_pos = if let Some(patchname) = end {
-                    allowed_patches
-                        .iter()
-                        .position(|&pn| pn == &patchname)
-                        .expect("end patchname already constrained to allowed patches")
-                } else if allow.use_applied_boundary()
-                    && !stack.applied().is_empty()
-                    && begin_pos < stack.applied().len()
-                {
-                    stack.applied().len() - 1
-                } else if !allowed_patches.is_empty() {
-                    allowed_patches.len() - 1
-                } else {
-                    continue;
-                };
+    let excluded = resolve_exclusions(stack, lookup, &exclusions, Some(&selection), &mut errors);
+    if let Some(err) = Error::combine(errors) {
+        return Err(err);
+    }
+    selection.patches.retain(|pn| !excluded.contains(pn));
+    selection.selected.retain(|pn| !excluded.contains(pn));
 
-                let selected_patches = if begin_pos <= end_pos {
-                    allowed_patches[begin_pos..=end_pos].to_vec()
-                } else {
-                    allowed_patches[end_pos..=begin_pos]
-                        .iter()
-                        .rev()
-                        .copied()
-                        .collect()
-                };
-
-                for pn in selected_patches {
-                    let patchname = pn.clone();
-                    if patches.contains(pn) {
-                        return Err(Error::Duplicate { patchname });
-                    }
-                    patches.push(patchname);
+    Ok(selection)
+}
+
+/// Add the patches of a single, non-exclusion, patch range to the selection.
+fn select_range<'a, 'repo>(
+    stack: &'a impl StackStateAccess<'repo>,
+    lookup: &PatchLookup<'a>,
+    range: &PatchRange,
+    allow: RangeConstraint,
+    allowed_patches: &[&'a PatchName],
+    selection: &mut Selection<'a>,
+) -> Result<(), Error> {
+    match range {
+        PatchRange::Range(bounds) => {
+            let (begin, end) = bounds.resolve_bound_names(stack, lookup, allow)?;
+
+            let begin_pos = begin.map_or(0, |patchname| {
+                lookup
+                    .allowed_position(allow.into(), patchname.as_ref())
+                    .expect("begin patchname already constrained to the allowed patches")
+            });
+
+            let end_pos = if let Some(patchname) = end {
+                lookup
+                    .allowed_position(allow.into(), patchname.as_ref())
+                    .expect("end patchname already constrained to allowed patches")
+            } else if allow.use_applied_boundary()
+                && !stack.applied().is_empty()
+                && begin_pos < stack.applied().len()
+            {
+                stack.applied().len() - 1
+            } else if !allowed_patches.is_empty() {
+                allowed_patches.len() - 1
+            } else {
+                return Ok(());
+            };
+
+            if begin_pos <= end_pos {
+                for &pn in &allowed_patches[begin_pos..=end_pos] {
+                    selection.push(pn)?;
+                }
+            } else {
+                for &pn in allowed_patches[end_pos..=begin_pos].iter().rev() {
+                    selection.push(pn)?;
                 }
             }
+        }
 
-            PatchRange::Single(patch_loc) => {
-                let patchname = patch_loc
-                    .resolve_name(stack)?
-                    .constrain(stack, allow.into())?;
-                if patches.contains(&patchname) {
-                    return Err(Error::Duplicate { patchname });
-                }
-                patches.push(patchname);
+        PatchRange::Single(patch_loc) => {
+            selection.push(resolve_single(patch_loc, stack, lookup, allow)?)?;
+        }
+
+        PatchRange::Label(label) => {
+            for pn in labeled_patches(stack, label, allowed_patches)? {
+                selection.push(pn)?;
             }
         }
-    }
 
-    Ok(patches)
+        PatchRange::Glob(pattern) => {
+            for pn in matching_patches(pattern, allowed_patches)? {
+                selection.push(pn)?;
+            }
+        }
+
+        PatchRange::Exclude(_) => unreachable!("exclusions are split from the ranges"),
+    }
+    Ok(())
 }
 
 /// Resolve user-provided patch ranges into contiguous patch names.
 ///
-/// It is an error if any of the ranges provided in `ranges` are discontiguous.
+/// It is an error if any of the ranges provided in `ranges` are discontiguous. Label
+/// references, i.e. `%{<label>}`, and glob patterns are the exception; their patches
+/// need not be contiguous with each other or with neighboring ranges.
+///
+/// Exclusions, i.e. `!<locator>`, may only trim patches from either end of the selected
+/// patches. It is an error for an exclusion to split the selected patches.
+///
+/// As with [`resolve_names()`], the errors for all failing ranges are reported
+/// together. Whether the ranges are contiguous is only reported once all of them
+/// resolve.
 pub(crate) fn resolve_names_contiguous<'a>(
     stack: &'a impl StackStateAccess<'a>,
     ranges: impl IntoIterator<Item = &'a PatchRange>,
     allow: RangeConstraint,
 ) -> Result<Vec<PatchName>, Error> {
-    let allowed_patches: Vec<&PatchName> = stack.get_allowed(allow.into());
-    let mut patches: Vec<PatchName> = Vec::new();
+    let lookup = PatchLookup::new(stack);
+    let allowed_patches = lookup.allowed(allow.into());
+    let mut selection = Selection::new();
+    let mut errors = Vec::new();
+    let mut discontiguity: Option<Error> = None;
     let mut next_pos: Option<usize> = None;
     let mut prev_range: Option<&PatchRange> = None;
-
-    for range in ranges {
-        match range {
-            PatchRange::Range(PatchRangeBounds { begin, end }) => {
-                let begin = begin
-                    .as_ref()
-                    .map(|loc| loc.resolve_name(stack))
-                    .transpose()?
-                    .map_or_else(
-                        || Ok(None),
-                        |pn| Some(pn.constrain(stack, allow.into())).transpose(),
-                    )?;
-
-                let end = end
-                    .as_ref()
-                    .map(|loc| loc.resolve_name(stack))
-                    .transpose()?
-                    .map_or_else(
-                        || Ok(None),
-                        |pn| Some(pn.constrain(stack, allow.into())).transpose(),
-                    )?;
-
-                let begin_pos = begin.map_or(0, |patchname| {
-                    allowed_patches
-                        .iter()
-                        .position(|&pn| pn == &patchname)
-                        .expect("begin patchname already constrained to the allowed patches")
-                });
-                if next_pos.is_some() && Some(begin_pos) != next_pos {
-                    return Err(Error::NotContiguous {
+    let (inclusions, exclusions) = split_exclusions(stack, ranges)?;
+
+    for range in inclusions.iter().map(Cow::as_ref) {
+        match select_contiguous_range(
+            stack,
+            &lookup,
+            range,
+            allow,
+            allowed_patches,
+            &mut selection,
+        ) {
+            Ok(RangeSpan::Positions(begin_pos, end_pos)) => {
+                if next_pos.is_some() && Some(begin_pos) != next_pos && discontiguity.is_none() {
+                    discontiguity = Some(Error::NotContiguous {
                         range: range.to_string(),
                         prev_range: prev_range.unwrap().to_string(),
                     });
                 }
+                next_pos = Some(end_pos + 1);
+            }
+            Ok(RangeSpan::Unordered) => next_pos = None,
+            Ok(RangeSpan::Empty) => continue,
+            Err(err) => {
+                errors.push(err);
+                next_pos = None;
+            }
+        }
 
-                let end_pos = if let Some(patchname) = end {
-                    let end_pos = allowed_patches
-                        .iter()
-                        .position(|&pn| pn == &patchname)
-                        .expect("end patchname already constrained to allowed patches");
-                    if end_pos < begin_pos {
-                        return Err(Error::BoundaryOrder {
-                            begin_patchname: allowed_patches[begin_pos].clone(),
-                            end_patchname: patchname,
-                        });
-                    }
-                    end_pos
-                } else if allow.use_applied_boundary()
-                    && !stack.applied().is_empty()
-                    && begin_pos < stack.applied().len()
-                {
-                    stack.applied().len() - 1
-                } else if !allowed_patches.is_empty() {
-                    allowed_patches.len() - 1
-                } else {
-                    continue;
-                };
+        prev_range = Some(range);
+    }
 
-                let selected_patches = allowed_patches[begin_pos..=end_pos].to_vec();
+    let excluded = resolve_exclusions(stack, &lookup, &exclusions, Some(&selection), &mut errors);
+    if let Some(err) = Error::combine(errors).or(discontiguity) {
+        return Err(err);
+    }
+    let mut patches = selection.patches;
+    let first_kept = patches.iter().position(|pn| !excluded.contains(pn));
+    let last_kept = patches.iter().rposition(|pn| !excluded.contains(pn));
+    if let (Some(first_kept), Some(last_kept)) = (first_kept, last_kept) {
+        if let Some(patchname) = patches[first_kept..=last_kept]
+            .iter()
+            .find(|pn| excluded.contains(*pn))
+        {
+            return Err(Error::ExclusionSplitsRange {
+                patchname: (*patchname).clone(),
+            });
+        }
+    }
+    patches.retain(|pn| !excluded.contains(pn));
 
-                for pn in selected_patches {
-                    let patchname = pn.clone();
-                    if patches.contains(pn) {
-                        return Err(Error::Duplicate { patchname });
-                    }
-                    patches.push(patchname.clone());
+    Ok(patches.into_iter().cloned().collect())
+}
+
+/// Positions of the patches selected by a single patch range.
+enum RangeSpan {
+    /// First and last positions of the range's patches among the allowed patches.
+    Positions(usize, usize),
+
+    /// Patches selected by label or glob pattern, which are exempt from the contiguity
+    /// requirement.
+    Unordered,
+
+    /// An open range with no patches.
+    Empty,
+}
+
+/// Add the patches of a single, non-exclusion, patch range to a contiguous selection.
+fn select_contiguous_range<'a>(
+    stack: &'a impl StackStateAccess<'a>,
+    lookup: &PatchLookup<'a>,
+    range: &PatchRange,
+    allow: RangeConstraint,
+    allowed_patches: &[&'a PatchName],
+    selection: &mut Selection<'a>,
+) -> Result<RangeSpan, Error> {
+    match range {
+        PatchRange::Range(bounds) => {
+            let (begin, end) = bounds.resolve_bound_names(stack, lookup, allow)?;
+
+            let begin_pos = begin.map_or(0, |patchname| {
+                lookup
+                    .allowed_position(allow.into(), patchname.as_ref())
+                    .expect("begin patchname already constrained to the allowed patches")
+            });
+
+            let end_pos = if let Some(patchname) = end {
+                let end_pos = lookup
+                    .allowed_position(allow.into(), patchname.as_ref())
+                    .expect("end patchname already constrained to allowed patches");
+                if end_pos < begin_pos {
+                    return Err(Error::BoundaryOrder {
+                        begin_patchname: allowed_patches[begin_pos].clone(),
+                        end_patchname: patchname,
+                    });
                 }
+                end_pos
+            } else if allow.use_applied_boundary()
+                && !stack.applied().is_empty()
+                && begin_pos < stack.applied().len()
+            {
+                stack.applied().len() - 1
+            } else if !allowed_patches.is_empty() {
+                allowed_patches.len() - 1
+            } else {
+                return Ok(RangeSpan::Empty);
+            };
 
-                next_pos = Some(end_pos + 1);
+            for &pn in &allowed_patches[begin_pos..=end_pos] {
+                selection.push(pn)?;
             }
-            PatchRange::Single(patch_loc) => {
-                let patchname = patch_loc
-                    .resolve_name(stack)?
-                    .constrain(stack, allow.into())?;
-                if patches.contains(&patchname) {
-                    return Err(Error::Duplicate { patchname });
-                } else {
-                    let pos = allowed_patches
-                        .iter()
-                        .position(|&pn| pn == &patchname)
-                        .expect("patchname already constrained to allowed patches");
-                    if next_pos.is_some() && Some(pos) != next_pos {
-                        return Err(Error::NotContiguous {
-                            range: range.to_string(),
-                            prev_range: prev_range.unwrap().to_string(),
-                        });
-                    }
-                    patches.push(patchname);
-                    next_pos = Some(pos + 1);
-                }
+            Ok(RangeSpan::Positions(begin_pos, end_pos))
+        }
+        PatchRange::Single(patch_loc) => {
+            let patchname = resolve_single(patch_loc, stack, lookup, allow)?;
+            selection.push(patchname)?;
+            let pos = lookup
+                .allowed_position(allow.into(), patchname.as_ref())
+                .expect("patchname already constrained to allowed patches");
+            Ok(RangeSpan::Positions(pos, pos))
+        }
+        PatchRange::Label(label) => {
+            // Labeled patches are selected by membership rather than position, so
+            // they are exempt from the contiguity requirement.
+            for pn in labeled_patches(stack, label, allowed_patches)? {
+                selection.push(pn)?;
             }
+            Ok(RangeSpan::Unordered)
         }
+        PatchRange::Glob(pattern) => {
+            // Likewise, patches matching a pattern are exempt from the contiguity
+            // requirement.
+            for pn in matching_patches(pattern, allowed_patches)? {
+                selection.push(pn)?;
+            }
+            Ok(RangeSpan::Unordered)
+        }
+        PatchRange::Exclude(_) => unreachable!("exclusions are split from the ranges"),
+    }
+}
 
-        prev_range = Some(range);
+/// Resolve user-provided patch ranges into the minimal contiguous range covering them.
+///
+/// Rather than failing when the resolved patches are discontiguous, as
+/// [`resolve_names_contiguous()`] does, any allowed patches in the gaps between them
+/// are included. The resulting patches are in stack order. The patches added to fill
+/// the gaps are also returned.
+///
+/// Hidden patches are never included implicitly; it is an error for a gap to contain
+/// a hidden patch that was not itself specified. Likewise, it is an error for a gap to
+/// contain an excluded patch.
+pub(crate) fn resolve_names_filling_gaps<'a>(
+    stack: &'a impl StackStateAccess<'a>,
+    ranges: impl IntoIterator<Item = &'a PatchRange>,
+    allow: RangeConstraint,
+) -> Result<(Vec<PatchName>, Vec<PatchName>), Error> {
+    let ranges: Vec<&PatchRange> = ranges.into_iter().collect();
+    let lookup = PatchLookup::new(stack);
+    let selection = select_names(stack, &lookup, ranges.iter().copied(), allow)?;
+    let (_, exclusions) = split_exclusions(stack, ranges.iter().copied())?;
+    let mut errors = Vec::new();
+    let excluded = resolve_exclusions(stack, &lookup, &exclusions, None, &mut errors);
+    if let Some(err) = Error::combine(errors) {
+        return Err(err);
+    }
+    let allowed_patches = lookup.allowed(allow.into());
+    let positions = selection.patches.iter().map(|patchname| {
+        lookup
+            .allowed_position(allow.into(), patchname.as_ref())
+            .expect("patchname already constrained to allowed patches")
+    });
+    let (Some(first_pos), Some(last_pos)) = (positions.clone().min(), positions.max()) else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+
+    let mut patches = Vec::with_capacity(last_pos + 1 - first_pos);
+    let mut gap_patches = Vec::new();
+    for &patchname in &allowed_patches[first_pos..=last_pos] {
+        if !selection.contains(patchname) {
+            if stack.is_hidden(patchname) {
+                return Err(Error::HiddenInGap {
+                    patchname: patchname.clone(),
+                });
+            } else if excluded.contains(patchname) {
+                return Err(Error::ExclusionSplitsRange {
+                    patchname: patchname.clone(),
+                });
+            }
+            gap_patches.push(patchname.clone());
+        }
+        patches.push(patchname.clone());
     }
 
-    Ok(patches)
+    Ok((patches, gap_patches))
 }
@@ -11,6 +11,8 @@
 //! - Names of patches in the current stack may be specified. E.g. a specification of
 //!   `patch` would refer to the patch `patch`'s commit. This is equivalent to
 //!   specifying `refs/stacks/<branch>/patch`.
+//! - Any of the above may be prefixed with `<branch>:` to resolve the patch or stack
+//!   position in another branch's stack, e.g. `other:{base}~2` or `other:@^2`.
 
 use std::{rc::Rc, str::FromStr};
 
@@ -21,7 +23,8 @@ use super::{
     SingleRevisionSpec, StGitBoundaryRevisions, StGitRevision,
 };
 use crate::{
-    ext::RepositoryExtended,
+    branchloc::BranchLocator,
+    ext::{RepositoryExtended, ShallowHistoryError},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
 };
 
@@ -123,6 +126,16 @@ impl std::fmt::Display for GitRevisionSuffix {
 }
 
 impl RangeRevisionSpec {
+    /// Whether the specification is a single revision or a range with both ends given.
+    pub(crate) fn is_bounded(&self) -> bool {
+        match self {
+            RangeRevisionSpec::BranchRange { bounds, .. } | RangeRevisionSpec::Range(bounds) => {
+                bounds.is_bounded()
+            }
+            RangeRevisionSpec::Single(_) => true,
+        }
+    }
+
     /// Resolve begin and end boundary revisions from a range revision specification.
     pub(crate) fn resolve_revisions<'repo>(
         &self,
@@ -132,11 +145,7 @@ impl RangeRevisionSpec {
     ) -> Result<StGitBoundaryRevisions<'repo>> {
         match self {
             RangeRevisionSpec::BranchRange { branch_loc, bounds } => {
-                let stack = Stack::from_branch_locator(
-                    repo,
-                    Some(branch_loc),
-                    InitializationPolicy::AllowUninitialized,
-                )?;
+                let stack = branch_stack(repo, branch_loc)?;
                 bounds
                     .resolve_revisions(&stack, use_applied_boundary)
                     .map_err(anyhow::Error::from)
@@ -161,6 +170,21 @@ impl RangeRevisionSpec {
     }
 }
 
+/// Get the stack of the branch named by a branch-qualified revision specification.
+///
+/// Patches, `{base}`, and other stack positions of a branch are meaningless without
+/// its stack, so the branch's stack must already be initialized.
+fn branch_stack<'repo>(
+    repo: &'repo gix::Repository,
+    branch_loc: &BranchLocator,
+) -> Result<Stack<'repo>> {
+    Stack::from_branch_locator(
+        repo,
+        Some(branch_loc),
+        InitializationPolicy::RequireInitialized,
+    )
+}
+
 /// Resolve many ranged revision specifications.
 pub(crate) fn resolve<'a, 'repo>(
     repo: &'repo gix::Repository,
@@ -172,11 +196,7 @@ pub(crate) fn resolve<'a, 'repo>(
     for spec in specs {
         match spec {
             RangeRevisionSpec::BranchRange { branch_loc, bounds } => {
-                let stack = Stack::from_branch_locator(
-                    repo,
-                    Some(branch_loc),
-                    InitializationPolicy::AllowUninitialized,
-                )?;
+                let stack = branch_stack(repo, branch_loc)?;
                 let range = PatchRange::from(bounds);
                 for patchname in patchrange::resolve_names(&stack, [&range], allow)? {
                     let commit = stack.get_patch_commit(&patchname).clone();
@@ -247,6 +267,18 @@ impl PatchLikeSpec {
     }
 }
 
+/// Choose between the errors from resolving a spec as patch-like or as git-like.
+///
+/// The patch-like error is usually the more helpful one, unless the git-like
+/// resolution failed because history is missing from a shallow repository.
+fn prefer_shallow(patch_err: anyhow::Error, git_err: anyhow::Error) -> anyhow::Error {
+    if git_err.is::<ShallowHistoryError>() {
+        git_err
+    } else {
+        patch_err
+    }
+}
+
 /// Resolve git-like revision specification.
 fn resolve_git_like<'repo>(
     repo: &'repo gix::Repository,
@@ -273,24 +305,20 @@ impl SingleRevisionSpec {
                 branch_loc,
                 patch_like,
             } => {
-                let stack = Stack::from_branch_locator(
-                    repo,
-                    Some(branch_loc),
-                    InitializationPolicy::AllowUninitialized,
-                )?;
+                let stack = branch_stack(repo, branch_loc)?;
                 patch_like.resolve(repo, &stack)
             }
             SingleRevisionSpec::PatchAndGitLike(patch_like, git_like) => {
                 if let Some(stack) = stack {
-                    patch_like
-                        .resolve(repo, stack)
-                        .or_else(|e| resolve_git_like(repo, git_like).map_err(|_| e))
+                    patch_like.resolve(repo, stack).or_else(|e| {
+                        resolve_git_like(repo, git_like).map_err(|git_e| prefer_shallow(e, git_e))
+                    })
                 } else if let Ok(stack) =
                     Stack::current(repo, InitializationPolicy::AllowUninitialized)
                 {
-                    patch_like
-                        .resolve(repo, &stack)
-                        .or_else(|e| resolve_git_like(repo, git_like).map_err(|_| e))
+                    patch_like.resolve(repo, &stack).or_else(|e| {
+                        resolve_git_like(repo, git_like).map_err(|git_e| prefer_shallow(e, git_e))
+                    })
                 } else {
                     resolve_git_like(repo, git_like)
                 }
@@ -322,18 +350,14 @@ impl SingleRevisionSpec {
                 branch_loc,
                 patch_like,
             } => {
-                let stack = Stack::from_branch_locator(
-                    repo,
-                    Some(branch_loc),
-                    InitializationPolicy::AllowUninitialized,
-                )?;
+                let stack = branch_stack(repo, branch_loc)?;
                 patch_like.resolve_object(repo, &stack)?
             }
             SingleRevisionSpec::PatchAndGitLike(patch_like, git_like) => {
                 patch_like.resolve_object(repo, stack).or_else(|e| {
                     repo.rev_parse_single_ex(git_like)
                         .and_then(|id| id.object().map_err(anyhow::Error::from))
-                        .map_err(|_| e)
+                        .map_err(|git_e| prefer_shallow(e, git_e))
                 })?
             }
             SingleRevisionSpec::GitLike(name_suffix) => {
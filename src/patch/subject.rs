@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Subject of a patch, with fallbacks for patches with an empty subject line.
+
+use super::PatchName;
+
+/// Maximum number of characters of a body line used as a patch's subject.
+const MAX_BODY_SUBJECT_CHARS: usize = 60;
+
+/// Where a patch's subject comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SubjectSource {
+    /// The first line of the patch's message.
+    FirstLine,
+
+    /// The first non-empty line of the message body, when the first line is empty.
+    Body,
+
+    /// The patch name, when the message is entirely empty.
+    PatchName,
+}
+
+/// Subject of a patch.
+///
+/// The subject is the first line of the patch's message. When that line is empty, the
+/// first non-empty line of the message is used instead, truncated to a reasonable
+/// length. A patch whose message is entirely empty uses its patch name as subject.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Subject {
+    pub(crate) text: String,
+    pub(crate) source: SubjectSource,
+}
+
+impl Subject {
+    /// Determine the subject of the patch with the given message.
+    pub(crate) fn new(message: &str, patchname: &PatchName) -> Self {
+        let mut lines = message.lines().map(str::trim);
+        let first_line = lines.next().unwrap_or_default();
+        if !first_line.is_empty() {
+            Self {
+                text: first_line.to_string(),
+                source: SubjectSource::FirstLine,
+            }
+        } else if let Some(line) = lines.find(|line| !line.is_empty()) {
+            let text = if line.chars().count() > MAX_BODY_SUBJECT_CHARS {
+                let truncated: String = line.chars().take(MAX_BODY_SUBJECT_CHARS - 3).collect();
+                format!("{}...", truncated.trim_end())
+            } else {
+                line.to_string()
+            };
+            Self {
+                text,
+                source: SubjectSource::Body,
+            }
+        } else {
+            Self {
+                text: patchname.to_string(),
+                source: SubjectSource::PatchName,
+            }
+        }
+    }
+
+    /// Determine whether the subject is not from the first line of the message.
+    pub(crate) fn is_fallback(&self) -> bool {
+        self.source != SubjectSource::FirstLine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn subject_fallbacks() {
+        let patchname = PatchName::from_str("p0").unwrap();
+
+        let subject = Subject::new("Fix the thing\n\nBecause.\n", &patchname);
+        assert_eq!(subject.text, "Fix the thing");
+        assert_eq!(subject.source, SubjectSource::FirstLine);
+
+        let subject = Subject::new("  \n\n  Body line  \nMore\n", &patchname);
+        assert_eq!(subject.text, "Body line");
+        assert_eq!(subject.source, SubjectSource::Body);
+
+        let long_line = "word ".repeat(20);
+        let subject = Subject::new(&format!("\n{long_line}\n"), &patchname);
+        assert!(subject.text.starts_with("word word "));
+        assert!(subject.text.ends_with("wo..."));
+        assert_eq!(subject.text.chars().count(), MAX_BODY_SUBJECT_CHARS);
+
+        let subject = Subject::new(" \n\n", &patchname);
+        assert_eq!(subject.text, "p0");
+        assert_eq!(subject.source, SubjectSource::PatchName);
+        assert!(subject.is_fallback());
+    }
+}
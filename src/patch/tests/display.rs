@@ -44,6 +44,7 @@ fn display_patch_id() {
     check_same("-000");
     check_same("-001");
     check_same("--3");
+    check_same(":/fix typo");
 
     let check_display =
         |s, expected| assert_eq!(PatchLocator::from_str(s).unwrap().id.to_string(), expected);
@@ -64,6 +65,7 @@ fn display_locators() {
     check_same("^-3~02");
     check_same("{base}++~");
     check_same("@~++");
+    check_same(":/fix typo~1");
 }
 
 #[test]
@@ -83,6 +85,7 @@ fn display_range() {
     check_same("~1");
     check_same("patch");
     check_same("patch++~++");
+    check_same("%{net}");
 }
 
 #[test]
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use std::{collections::BTreeMap, rc::Rc, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    str::FromStr,
+};
 
 use super::super::*;
 use crate::stack::{PatchState, StackStateAccess};
@@ -11,6 +15,9 @@ struct DummyStack {
     unapplied: Vec<PatchName>,
     hidden: Vec<PatchName>,
     commit_ids: BTreeMap<PatchName, gix::ObjectId>,
+    labels: BTreeMap<PatchName, BTreeSet<String>>,
+    aliases: BTreeMap<PatchName, PatchName>,
+    strict_names: bool,
 }
 
 impl<'repo> StackStateAccess<'repo> for DummyStack {
@@ -30,6 +37,14 @@ impl<'repo> StackStateAccess<'repo> for DummyStack {
         panic!()
     }
 
+    fn labels(&self) -> &BTreeMap<PatchName, BTreeSet<String>> {
+        &self.labels
+    }
+
+    fn aliases(&self) -> &BTreeMap<PatchName, PatchName> {
+        &self.aliases
+    }
+
     fn get_patch_commit_id(&self, patchname: &PatchName) -> gix::ObjectId {
         self.commit_ids[patchname]
     }
@@ -38,6 +53,10 @@ impl<'repo> StackStateAccess<'repo> for DummyStack {
         self.commit_ids.contains_key(patchname)
     }
 
+    fn allow_abbreviated_names(&self) -> bool {
+        !self.strict_names
+    }
+
     fn top(&self) -> &Rc<gix::Commit<'repo>> {
         panic!()
     }
@@ -189,3 +208,721 @@ fn should_resolve_ambiguity() {
     ));
     assert_eq!(name("patch"), resolve("beef3"));
 }
+
+#[test]
+fn should_resolve_labels() {
+    let mut stack = DummyStack::from_series(&[
+        ('+', "a", None),
+        ('>', "b", None),
+        ('-', "c", None),
+        ('-', "d", None),
+        ('!', "e", None),
+    ]);
+    for pn in ["a", "c", "e"] {
+        stack
+            .labels
+            .insert(name(pn), BTreeSet::from(["net".to_string()]));
+    }
+
+    let ranges = |specs: &[&str]| -> Vec<PatchRange> {
+        specs
+            .iter()
+            .map(|s| PatchRange::from_str(s).expect("valid patch range"))
+            .collect()
+    };
+
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&["%{net}"]), RangeConstraint::All).unwrap(),
+        vec![name("a"), name("c"), name("e")]
+    );
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&["%{net}"]), RangeConstraint::Visible).unwrap(),
+        vec![name("a"), name("c")]
+    );
+    assert_eq!(
+        patchrange::resolve_names_contiguous(
+            &stack,
+            &ranges(&["%{net}", "d"]),
+            RangeConstraint::Visible
+        )
+        .unwrap(),
+        vec![name("a"), name("c"), name("d")]
+    );
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["a", "%{net}"]), RangeConstraint::All),
+        Err(patchrange::Error::Duplicate { .. })
+    ));
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["%{usb}"]), RangeConstraint::All),
+        Err(patchrange::Error::UnknownLabel(_))
+    ));
+}
+
+#[test]
+fn should_resolve_globs() {
+    let stack = DummyStack::from_series(&[
+        ('+', "wip-a", None),
+        ('+', "fix-1", None),
+        ('>', "wip-b", None),
+        ('-', "fix-2", None),
+        ('-', "wip-c", None),
+        ('!', "wip-d", None),
+    ]);
+
+    let ranges = |specs: &[&str]| -> Vec<PatchRange> {
+        specs
+            .iter()
+            .map(|s| PatchRange::from_str(s).expect("valid patch range"))
+            .collect()
+    };
+
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&["wip-*"]), RangeConstraint::All).unwrap(),
+        vec![name("wip-a"), name("wip-b"), name("wip-c"), name("wip-d")]
+    );
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&["wip-*"]), RangeConstraint::Unapplied).unwrap(),
+        vec![name("wip-c")]
+    );
+    assert_eq!(
+        patchrange::resolve_names(
+            &stack,
+            &ranges(&["fix-?", "wip-[ab]"]),
+            RangeConstraint::All
+        )
+        .unwrap(),
+        vec![name("fix-1"), name("fix-2"), name("wip-a"), name("wip-b")]
+    );
+    assert_eq!(
+        patchrange::resolve_names_contiguous(
+            &stack,
+            &ranges(&["fix-*", "wip-c"]),
+            RangeConstraint::Visible
+        )
+        .unwrap(),
+        vec![name("fix-1"), name("fix-2"), name("wip-c")]
+    );
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["wip-a", "wip-*"]), RangeConstraint::All),
+        Err(patchrange::Error::Duplicate { .. })
+    ));
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["tmp-*"]), RangeConstraint::All),
+        Err(patchrange::Error::NoMatchingPatches(_))
+    ));
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["fix-*"]), RangeConstraint::Hidden),
+        Err(patchrange::Error::NoMatchingPatches(_))
+    ));
+}
+
+#[test]
+fn should_resolve_exclusions() {
+    let stack = DummyStack::from_series(&[
+        ('+', "a", None),
+        ('+', "b", None),
+        ('>', "c", None),
+        ('-', "d", None),
+        ('-', "e", None),
+        ('!', "f", None),
+        ('!', "!g", None),
+    ]);
+
+    let ranges = |specs: &[&str]| -> Vec<PatchRange> {
+        specs
+            .iter()
+            .map(|s| PatchRange::from_str(s).expect("valid patch range"))
+            .collect()
+    };
+    let names = |names: &[&str]| -> Vec<PatchName> { names.iter().map(|s| name(s)).collect() };
+
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&["a..e", "!c"]), RangeConstraint::All).unwrap(),
+        names(&["a", "b", "d", "e"])
+    );
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&["!d", ".."]), RangeConstraint::Visible)
+            .unwrap(),
+        names(&["a", "b", "c", "e"])
+    );
+    // Open-ended ranges stop at the topmost applied patch.
+    assert_eq!(
+        patchrange::resolve_names(
+            &stack,
+            &ranges(&["b..", "!c"]),
+            RangeConstraint::AllWithAppliedBoundary
+        )
+        .unwrap(),
+        names(&["b"])
+    );
+    assert!(matches!(
+        patchrange::resolve_names(
+            &stack,
+            &ranges(&["b..", "!d"]),
+            RangeConstraint::AllWithAppliedBoundary
+        ),
+        Err(patchrange::Error::ExclusionNotSelected { .. })
+    ));
+    // Exclusions alone select no patches to exclude from.
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["!e"]), RangeConstraint::Unapplied),
+        Err(patchrange::Error::OnlyExclusions)
+    ));
+    // Exclusions from all the allowed patches, as with `stg push --all`.
+    let all_but = |spec: &str| {
+        let mut ranges = ranges(&[spec]);
+        ranges.insert(0, PatchRange::all());
+        patchrange::resolve_names(&stack, &ranges, RangeConstraint::Unapplied)
+    };
+    assert_eq!(all_but("!e").unwrap(), names(&["d"]));
+    assert!(matches!(
+        all_but("!a"),
+        Err(patchrange::Error::ExclusionNotSelected { .. })
+    ));
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["a..c", "!b", "!b"]), RangeConstraint::All),
+        Err(patchrange::Error::Duplicate { .. })
+    ));
+    // An existing patch named like an exclusion is selected rather than excluded.
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&["f", "!g"]), RangeConstraint::Hidden).unwrap(),
+        names(&["f", "!g"])
+    );
+
+    assert_eq!(
+        patchrange::resolve_names_contiguous(
+            &stack,
+            &ranges(&["a..", "!a", "!c"]),
+            RangeConstraint::AllWithAppliedBoundary
+        )
+        .unwrap(),
+        names(&["b"])
+    );
+    assert_eq!(
+        patchrange::resolve_names_contiguous(
+            &stack,
+            &ranges(&["..e", "!e"]),
+            RangeConstraint::Visible
+        )
+        .unwrap(),
+        names(&["a", "b", "c", "d"])
+    );
+    assert!(matches!(
+        patchrange::resolve_names_contiguous(
+            &stack,
+            &ranges(&["a..e", "!c"]),
+            RangeConstraint::Visible
+        ),
+        Err(patchrange::Error::ExclusionSplitsRange { .. })
+    ));
+    assert!(matches!(
+        patchrange::resolve_names_filling_gaps(
+            &stack,
+            &ranges(&["a..d", "!b"]),
+            RangeConstraint::Visible
+        ),
+        Err(patchrange::Error::ExclusionSplitsRange { .. })
+    ));
+}
+
+#[test]
+fn should_report_all_errors() {
+    let stack = DummyStack::from_series(&[
+        ('+', "a", None),
+        ('+', "b", None),
+        ('>', "c", None),
+        ('-', "d", None),
+        ('!', "e", None),
+    ]);
+
+    let ranges = |specs: &[&str]| -> Vec<PatchRange> {
+        specs
+            .iter()
+            .map(|s| PatchRange::from_str(s).expect("valid patch range"))
+            .collect()
+    };
+
+    let Err(patchrange::Error::Multiple(errors)) = patchrange::resolve_names(
+        &stack,
+        &ranges(&["a", "x", "b", "e", "b", "!y"]),
+        RangeConstraint::Visible,
+    ) else {
+        panic!("expected multiple errors");
+    };
+    assert_eq!(errors.len(), 4);
+    assert!(matches!(
+        &errors[0],
+        patchrange::Error::Locator(super::super::locator::Error::PatchNotKnown { patchname })
+            if patchname == &name("x")
+    ));
+    assert!(matches!(
+        &errors[1],
+        patchrange::Error::Name(super::super::name::Error::PatchNotAllowed { patchname, .. })
+            if patchname == &name("e")
+    ));
+    assert!(matches!(
+        &errors[2],
+        patchrange::Error::Duplicate { patchname } if patchname == &name("b")
+    ));
+    assert!(matches!(&errors[3], patchrange::Error::Locator(_)));
+
+    // A single error is reported as is.
+    assert!(matches!(
+        patchrange::resolve_names(&stack, &ranges(&["a", "x"]), RangeConstraint::All),
+        Err(patchrange::Error::Locator(_))
+    ));
+
+    // Resolution errors take precedence over the ranges being discontiguous.
+    let Err(patchrange::Error::Multiple(errors)) = patchrange::resolve_names_contiguous(
+        &stack,
+        &ranges(&["a", "c", "x", "d..b"]),
+        RangeConstraint::Visible,
+    ) else {
+        panic!("expected multiple errors");
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+        &errors[1],
+        patchrange::Error::BoundaryOrder { .. }
+    ));
+    assert!(matches!(
+        patchrange::resolve_names_contiguous(&stack, &ranges(&["a", "c"]), RangeConstraint::All),
+        Err(patchrange::Error::NotContiguous { .. })
+    ));
+
+    let message = patchrange::resolve_names(&stack, &ranges(&["x", "y"]), RangeConstraint::All)
+        .unwrap_err()
+        .to_string();
+    assert_eq!(
+        message,
+        "2 errors resolving patches:\n  \
+         patch `x` does not exist\n  \
+         patch `y` does not exist"
+    );
+}
+
+#[test]
+fn should_resolve_aliases() {
+    let mut stack =
+        DummyStack::from_series(&[('+', "a", None), ('>', "b", None), ('-', "c", None)]);
+    stack.aliases.insert(name("old-b"), name("b"));
+    stack.aliases.insert(name("c"), name("a"));
+
+    let resolve = |s| {
+        PatchLocator::from_str(s)
+            .expect("valid patch locator")
+            .resolve_name(&stack)
+            .unwrap()
+    };
+
+    assert_eq!(name("b"), resolve("old-b"));
+    assert_eq!(name("a"), resolve("old-b~"));
+    // Existing patches take precedence over aliases.
+    assert_eq!(name("c"), resolve("c"));
+}
+
+#[test]
+fn should_resolve_abbreviated_names() {
+    use super::super::locator::Error;
+
+    let mut stack = DummyStack::from_series(&[
+        ('+', "parse-args", Some("aaaa1111")),
+        ('+', "fix-io", Some("bbbb2222")),
+        ('>', "refactor-parser-errors", Some("cccc3333")),
+        ('-', "fix-iommu", Some("dddd4444")),
+        ('-', "dead-code", Some("eeee5555")),
+        ('-', "2-step", Some("ffff6666")),
+        ('!', "wip", Some("abab7777")),
+    ]);
+
+    let locator = |s| PatchLocator::from_str(s).expect("valid patch locator");
+    let resolve = |s| locator(s).resolve_name(&stack).unwrap();
+
+    assert_eq!(name("parse-args"), resolve("parse"));
+    assert_eq!(name("refactor-parser-errors"), resolve("ref"));
+    assert_eq!(name("fix-io"), resolve("ref~"));
+    assert_eq!(name("wip"), resolve("w"));
+
+    // Commit ids, indexes, and offsets take precedence over abbreviations.
+    assert_eq!(name("fix-iommu"), resolve("dddd"));
+    assert_eq!(name("dead-code"), resolve("dead"));
+    assert_eq!(name("fix-io"), resolve("bbbb"));
+    assert_eq!(name("refactor-parser-errors"), resolve("2"));
+    assert_eq!(name("fix-io"), resolve("-1"));
+
+    // An ambiguous prefix must match a single allowed patch.
+    match locator("fix").resolve_name(&stack) {
+        Err(Error::AmbiguousPatchName { candidates, .. }) => {
+            assert_eq!(candidates, "`fix-io` or `fix-iommu`");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    assert_eq!(
+        name("fix-iommu"),
+        locator("fix")
+            .resolve_name_allowed(&stack, LocationConstraint::Unapplied)
+            .unwrap()
+    );
+    assert_eq!(
+        patchrange::resolve_names(
+            &stack,
+            &[PatchRange::from_str("fix").unwrap()],
+            RangeConstraint::Unapplied
+        )
+        .unwrap(),
+        vec![name("fix-iommu")]
+    );
+
+    // Abbreviations outside of the allowed patches are still found, to be rejected by
+    // the constraint.
+    assert_eq!(
+        name("wip"),
+        locator("wi")
+            .resolve_name_allowed(&stack, LocationConstraint::Visible)
+            .unwrap()
+    );
+
+    match locator("fix-iu").resolve_name(&stack) {
+        Err(Error::PatchSimilar {
+            similar_patchnames, ..
+        }) => {
+            assert_eq!(similar_patchnames, "`fix-io` or `fix-iommu`");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+
+    stack.strict_names = true;
+    assert!(matches!(
+        locator("parse").resolve_name(&stack),
+        Err(Error::PatchSimilar { .. })
+    ));
+    assert_eq!(
+        name("parse-args"),
+        locator("parse-args").resolve_name(&stack).unwrap()
+    );
+}
+
+#[test]
+fn should_fill_gaps() {
+    let stack = DummyStack::from_series(&[
+        ('+', "a", None),
+        ('+', "b", None),
+        ('>', "c", None),
+        ('-', "d", None),
+        ('-', "e", None),
+        ('!', "f", None),
+        ('!', "g", None),
+    ]);
+
+    let ranges = |specs: &[&str]| -> Vec<PatchRange> {
+        specs
+            .iter()
+            .map(|s| PatchRange::from_str(s).expect("valid patch range"))
+            .collect()
+    };
+
+    assert_eq!(
+        patchrange::resolve_names_filling_gaps(
+            &stack,
+            &ranges(&["d", "a", "b"]),
+            RangeConstraint::Visible
+        )
+        .unwrap(),
+        (
+            vec![name("a"), name("b"), name("c"), name("d")],
+            vec![name("c")]
+        )
+    );
+    assert_eq!(
+        patchrange::resolve_names_filling_gaps(
+            &stack,
+            &ranges(&["b", "e"]),
+            RangeConstraint::Visible
+        )
+        .unwrap()
+        .1,
+        vec![name("c"), name("d")]
+    );
+    assert!(matches!(
+        patchrange::resolve_names_filling_gaps(
+            &stack,
+            &ranges(&["e", "g"]),
+            RangeConstraint::All
+        ),
+        Err(patchrange::Error::HiddenInGap { patchname }) if patchname == name("f")
+    ));
+    assert_eq!(
+        patchrange::resolve_names_filling_gaps(
+            &stack,
+            &ranges(&["e", "f", "g"]),
+            RangeConstraint::All
+        )
+        .unwrap()
+        .1,
+        Vec::<PatchName>::new()
+    );
+}
+
+#[test]
+fn should_resolve_ranges_relative_to_anchor() {
+    let stack = DummyStack::from_series(&[
+        ('+', "a", None),
+        ('+', "fix-io", None),
+        ('>', "b", None),
+        ('-', "c", None),
+        ('-', "d", None),
+        ('!', "e", None),
+    ]);
+
+    let resolve = |spec: &str, allow: RangeConstraint| {
+        patchrange::resolve_names(
+            &stack,
+            &[PatchRange::from_str(spec).expect("valid patch range")],
+            allow,
+        )
+    };
+
+    for allow in [
+        RangeConstraint::All,
+        RangeConstraint::AllWithAppliedBoundary,
+        RangeConstraint::Visible,
+        RangeConstraint::VisibleWithAppliedBoundary,
+    ] {
+        assert_eq!(
+            resolve("fix-io+1..+3", allow).unwrap(),
+            vec![name("b"), name("c"), name("d")]
+        );
+        assert_eq!(
+            resolve("fix-io+1..fix-io+3", allow).unwrap(),
+            vec![name("b"), name("c"), name("d")]
+        );
+        assert_eq!(
+            resolve("a..+1", allow).unwrap(),
+            vec![name("a"), name("fix-io")]
+        );
+    }
+
+    assert_eq!(
+        resolve("fix-io..+4", RangeConstraint::All).unwrap(),
+        vec![name("fix-io"), name("b"), name("c"), name("d"), name("e")]
+    );
+    assert!(matches!(
+        resolve("fix-io..+4", RangeConstraint::Visible),
+        Err(patchrange::Error::Name(
+            super::super::name::Error::PatchNotAllowed { .. }
+        ))
+    ));
+    assert_eq!(
+        resolve("a..+2", RangeConstraint::Applied).unwrap(),
+        vec![name("a"), name("fix-io"), name("b")]
+    );
+    assert!(matches!(
+        resolve("fix-io+1..+3", RangeConstraint::Applied),
+        Err(patchrange::Error::Name(
+            super::super::name::Error::PatchNotAllowed { .. }
+        ))
+    ));
+    assert_eq!(
+        resolve("fix-io+2..+3", RangeConstraint::Unapplied).unwrap(),
+        vec![name("c"), name("d")]
+    );
+    assert!(matches!(
+        resolve("fix-io+1..+3", RangeConstraint::Unapplied),
+        Err(patchrange::Error::Name(
+            super::super::name::Error::PatchNotAllowed { .. }
+        ))
+    ));
+    assert_eq!(
+        resolve("d+1..+1", RangeConstraint::Hidden).unwrap(),
+        vec![name("e")]
+    );
+    assert!(matches!(
+        resolve("fix-io+1..+5", RangeConstraint::All),
+        Err(patchrange::Error::Locator(
+            super::super::locator::Error::InvalidPatchOffset { .. }
+        ))
+    ));
+    assert_eq!(
+        patchrange::resolve_names_contiguous(
+            &stack,
+            &[
+                PatchRange::from_str("a").unwrap(),
+                PatchRange::from_str("fix-io..+2").unwrap(),
+            ],
+            RangeConstraint::Visible
+        )
+        .unwrap(),
+        vec![name("a"), name("fix-io"), name("b"), name("c")]
+    );
+
+    // A patch named like an offset is not relative to the begin bound.
+    let stack = DummyStack::from_series(&[
+        ('+', "a", None),
+        ('>', "+1", None),
+        ('-', "b", None),
+        ('-', "c", None),
+    ]);
+    assert_eq!(
+        patchrange::resolve_names(
+            &stack,
+            &[PatchRange::from_str("b..+1").unwrap()],
+            RangeConstraint::All
+        )
+        .unwrap(),
+        vec![name("b"), name("+1")]
+    );
+}
+
+#[test]
+fn should_keep_precedence_of_names_commit_ids_and_indices() {
+    use super::super::locator::Error;
+
+    let stack = DummyStack::from_series(&[
+        ('+', "1", Some("aaaa0001")),
+        ('+', "cafe", Some("bbbb0002")),
+        ('+', "p", Some("cafe0003")),
+        ('>', "q", Some("2222ffff")),
+        ('-', "2222", Some("dddd0004")),
+        ('-', "r", Some("0003eeee")),
+        ('!', "s", Some("0003ffff")),
+    ]);
+
+    let locator = |s| PatchLocator::from_str(s).expect("valid patch locator");
+    let resolve = |s| locator(s).resolve_name(&stack).unwrap();
+
+    // A patch name beats an index.
+    assert_eq!(name("1"), resolve("1"));
+    assert_eq!(name("cafe"), resolve("0001"));
+
+    // A patch name beats a commit id.
+    assert_eq!(name("cafe"), resolve("cafe"));
+    assert_eq!(name("2222"), resolve("2222"));
+
+    // A commit id beats an index.
+    assert_eq!(name("p"), resolve("cafe0"));
+    assert_eq!(name("q"), resolve("2222f"));
+    assert!(matches!(
+        locator("0003").resolve_name(&stack),
+        Err(Error::AmbiguousCommitId { .. })
+    ));
+
+    // An index applies only when no name or commit id matches.
+    assert_eq!(name("p"), resolve("2"));
+    assert_eq!(name("s"), resolve("6"));
+    assert_eq!(name("p"), resolve("0002"));
+
+    // A name with offsets beats a commit id or index with offsets.
+    assert_eq!(name("cafe"), resolve("1+"));
+    assert_eq!(name("p"), resolve("cafe+"));
+    assert_eq!(name("r"), resolve("2222+"));
+    assert_eq!(name("q"), resolve("cafe0+"));
+
+    // Abbreviated names apply last.
+    assert_eq!(name("cafe"), resolve("caf"));
+    assert!(matches!(
+        locator("222").resolve_name(&stack),
+        Err(Error::InvalidPatchIndex(222))
+    ));
+}
+
+#[test]
+fn should_resolve_large_stacks() {
+    const NUM_APPLIED: usize = 2000;
+    const NUM_UNAPPLIED: usize = 1000;
+    const NUM_HIDDEN: usize = 100;
+
+    let names: Vec<String> = (0..NUM_APPLIED + NUM_UNAPPLIED + NUM_HIDDEN)
+        .map(|i| format!("patch-{i}"))
+        .collect();
+    let oids: Vec<String> = (0..names.len()).map(|i| format!("{i:08x}ffff")).collect();
+    let series: Vec<(char, &str, Option<&str>)> = names
+        .iter()
+        .zip(&oids)
+        .enumerate()
+        .map(|(i, (name, oid))| {
+            let sigil = if i + 1 == NUM_APPLIED {
+                '>'
+            } else if i < NUM_APPLIED {
+                '+'
+            } else if i < NUM_APPLIED + NUM_UNAPPLIED {
+                '-'
+            } else {
+                '!'
+            };
+            (sigil, name.as_str(), Some(oid.as_str()))
+        })
+        .collect();
+    let stack = DummyStack::from_series(&series);
+    let all_names: Vec<PatchName> = names.iter().map(|s| name(s)).collect();
+
+    let ranges = |specs: &[String]| -> Vec<PatchRange> {
+        specs
+            .iter()
+            .map(|s| PatchRange::from_str(s).expect("valid patch range"))
+            .collect()
+    };
+
+    // Every patch by name, in stack order and in reverse.
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&names), RangeConstraint::All).unwrap(),
+        all_names
+    );
+    let reversed: Vec<String> = names.iter().rev().cloned().collect();
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&reversed), RangeConstraint::All).unwrap(),
+        all_names.iter().rev().cloned().collect::<Vec<_>>()
+    );
+
+    // Every visible patch by commit id, contiguously.
+    let visible = NUM_APPLIED + NUM_UNAPPLIED;
+    assert_eq!(
+        patchrange::resolve_names_contiguous(
+            &stack,
+            &ranges(&oids[..visible]),
+            RangeConstraint::Visible
+        )
+        .unwrap(),
+        all_names[..visible]
+    );
+
+    // Every patch by index and by offset from a named anchor.
+    let indices: Vec<String> = (0..names.len()).map(|i| i.to_string()).collect();
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&indices), RangeConstraint::All).unwrap(),
+        all_names
+    );
+    let offsets: Vec<String> = (0..names.len()).map(|i| format!("patch-0+{i}")).collect();
+    assert_eq!(
+        patchrange::resolve_names(&stack, &ranges(&offsets), RangeConstraint::All).unwrap(),
+        all_names
+    );
+
+    // Ranges, exclusions, and gaps.
+    assert_eq!(
+        patchrange::resolve_names(
+            &stack,
+            &ranges(&["..".to_string(), "!patch-1999".to_string()]),
+            RangeConstraint::AllWithAppliedBoundary
+        )
+        .unwrap(),
+        all_names[..NUM_APPLIED - 1]
+    );
+    assert_eq!(
+        patchrange::resolve_names_filling_gaps(
+            &stack,
+            &ranges(&["patch-10".to_string(), "patch-2990".to_string()]),
+            RangeConstraint::Visible
+        )
+        .unwrap()
+        .0,
+        all_names[10..=2990]
+    );
+    assert!(matches!(
+        patchrange::resolve_names(
+            &stack,
+            &ranges(&["patch-5..patch-50".to_string(), "patch-49".to_string()]),
+            RangeConstraint::All
+        ),
+        Err(patchrange::Error::Duplicate { patchname }) if patchname == name("patch-49")
+    ));
+}
@@ -0,0 +1,610 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Format patches according to user-provided format strings.
+//!
+//! A format string is literal text interspersed with `%(<placeholder>)` items, similar
+//! to `git for-each-ref --format`. A literal `%` may be written as `%%`.
+//!
+//! | Placeholder                  | Expansion                                          |
+//! |------------------------------|----------------------------------------------------|
+//! | `name`                       | Patch name                                         |
+//! | `state`                      | State sigil: `>`, `+`, `-`, or `!`                 |
+//! | `index`                      | Absolute index of the patch in the stack           |
+//! | `commit`                     | Full commit id                                     |
+//! | `commit:short`               | Abbreviated commit id                              |
+//! | `author`, `committer`        | Identity as `Name <email>`                         |
+//! | `author:name`, ...           | Identity name                                      |
+//! | `author:email`, ...          | Identity email                                     |
+//! | `author:date`, ...           | Date in ISO 8601-like format                       |
+//! | `author:date:<strftime>`,... | Date formatted with the strftime-style format      |
+//! | `subject`                    | Patch subject, see [`Subject`]                     |
+//! | `body`                       | Message after the subject line                     |
+//! | `empty`                      | `true` if the patch makes no changes, else `false` |
+//!
+//! Any placeholder other than a date with a strftime-style format may be suffixed with
+//! a `:[-]<width>[.<max>]` modifier. The expansion is padded to `<width>` characters,
+//! aligned to the right or, with `-`, to the left, and truncated to `<max>` characters.
+//! E.g. `%(name:-30)` or `%(subject:.50)`.
+//!
+//! Commits that need not be patches, e.g. for `stg id --format`, are instead formatted
+//! with a [`CommitFormat`] using a subset of the `git log --format` placeholders:
+//!
+//! | Placeholder    | Expansion                                              |
+//! |----------------|--------------------------------------------------------|
+//! | `%H`, `%h`     | Full or abbreviated commit id                          |
+//! | `%T`, `%t`     | Full or abbreviated tree id                            |
+//! | `%P`, `%p`     | Full or abbreviated parent ids, separated by spaces    |
+//! | `%an`, `%cn`   | Author or committer name                               |
+//! | `%ae`, `%ce`   | Author or committer email                              |
+//! | `%ad`, `%cd`   | Author or committer date in git's default format       |
+//! | `%ai`, `%ci`   | Author or committer date in ISO 8601-like format       |
+//! | `%at`, `%ct`   | Author or committer date as a UNIX timestamp           |
+//! | `%s`           | First line of the message                              |
+//! | `%b`           | Message after the subject line                         |
+//! | `%n`, `%%`     | A newline or a literal `%`                             |
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use crate::{
+    ext::CommitExtended,
+    patch::{PatchName, Subject},
+};
+
+/// A parsed patch format string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PatchFormat(Vec<Item>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Item {
+    Literal(String),
+    Placeholder(Field, Modifier),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Field {
+    Name,
+    State,
+    Index,
+    Commit,
+    CommitShort,
+    Tree,
+    TreeShort,
+    Parents,
+    ParentsShort,
+    Identity(Role, IdentityPart),
+    Subject,
+    Body,
+    Empty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    Author,
+    Committer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum IdentityPart {
+    Full,
+    Name,
+    Email,
+    /// Date with optional strftime-style format.
+    Date(Option<String>),
+}
+
+/// Padding and truncation applied to a placeholder's expansion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Modifier {
+    width: usize,
+    left_align: bool,
+    max: Option<usize>,
+}
+
+/// A parsed `git log`-style commit format string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CommitFormat(Vec<Item>);
+
+/// The patch information made available to a [`PatchFormat`].
+pub(crate) struct PatchInfo<'a, 'repo> {
+    pub(crate) name: &'a PatchName,
+    pub(crate) sigil: char,
+    pub(crate) index: usize,
+    pub(crate) commit: &'a gix::Commit<'repo>,
+}
+
+impl FromStr for PatchFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut items = Vec::new();
+        let mut literal = String::new();
+        let mut rest = s;
+        while let Some(pos) = rest.find('%') {
+            literal.push_str(&rest[..pos]);
+            rest = &rest[pos + 1..];
+            if let Some(after) = rest.strip_prefix('%') {
+                literal.push('%');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('(') {
+                let end = after
+                    .find(')')
+                    .ok_or_else(|| anyhow!("unterminated placeholder `%({after}`"))?;
+                let placeholder = &after[..end];
+                if !literal.is_empty() {
+                    items.push(Item::Literal(std::mem::take(&mut literal)));
+                }
+                let (field, modifier) = parse_placeholder(placeholder)
+                    .ok_or_else(|| anyhow!("unknown placeholder `%({placeholder})`"))?;
+                items.push(Item::Placeholder(field, modifier));
+                rest = &after[end + 1..];
+            } else {
+                literal.push('%');
+            }
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            items.push(Item::Literal(literal));
+        }
+        Ok(Self(items))
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Option<(Field, Modifier)> {
+    for (prefix, role) in [
+        ("author:date:", Role::Author),
+        ("committer:date:", Role::Committer),
+    ] {
+        if let Some(date_format) = placeholder.strip_prefix(prefix) {
+            if let Some(modifier) = parse_modifier(date_format) {
+                let field = Field::Identity(role, IdentityPart::Date(None));
+                return Some((field, modifier));
+            }
+            check_date_format(date_format)?;
+            let field = Field::Identity(role, IdentityPart::Date(Some(date_format.to_string())));
+            return Some((field, Modifier::default()));
+        }
+    }
+
+    let (name, modifier) = match placeholder.rsplit_once(':') {
+        Some((name, spec))
+            if spec.starts_with(['-', '.']) || spec.starts_with(char::is_numeric) =>
+        {
+            (name, parse_modifier(spec)?)
+        }
+        _ => (placeholder, Modifier::default()),
+    };
+
+    let field = match name {
+        "name" => Field::Name,
+        "state" => Field::State,
+        "index" => Field::Index,
+        "commit" => Field::Commit,
+        "commit:short" => Field::CommitShort,
+        "subject" => Field::Subject,
+        "body" => Field::Body,
+        "empty" => Field::Empty,
+        _ => {
+            let (role, part) = name.split_once(':').unwrap_or((name, ""));
+            let role = match role {
+                "author" => Role::Author,
+                "committer" => Role::Committer,
+                _ => return None,
+            };
+            let part = match part {
+                "" => IdentityPart::Full,
+                "name" => IdentityPart::Name,
+                "email" => IdentityPart::Email,
+                "date" => IdentityPart::Date(None),
+                _ => return None,
+            };
+            Field::Identity(role, part)
+        }
+    };
+    Some((field, modifier))
+}
+
+impl FromStr for CommitFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        /// strftime-style equivalent of git's default date format.
+        const GIT_DEFAULT_DATE: &str = "%a %b %-d %H:%M:%S %Y %z";
+
+        let unknown = |rest: &str, len: usize| {
+            let placeholder: String = rest.chars().take(len).collect();
+            anyhow!("unknown placeholder `%{placeholder}`")
+        };
+        let mut items = Vec::new();
+        let mut literal = String::new();
+        let mut rest = s;
+        while let Some(pos) = rest.find('%') {
+            literal.push_str(&rest[..pos]);
+            rest = &rest[pos + 1..];
+            let mut chars = rest.chars();
+            let field = match chars.next() {
+                Some('%') => {
+                    literal.push('%');
+                    None
+                }
+                Some('n') => {
+                    literal.push('\n');
+                    None
+                }
+                Some('H') => Some(Field::Commit),
+                Some('h') => Some(Field::CommitShort),
+                Some('T') => Some(Field::Tree),
+                Some('t') => Some(Field::TreeShort),
+                Some('P') => Some(Field::Parents),
+                Some('p') => Some(Field::ParentsShort),
+                Some('s') => Some(Field::Subject),
+                Some('b') => Some(Field::Body),
+                Some(c @ ('a' | 'c')) => {
+                    let role = if c == 'a' {
+                        Role::Author
+                    } else {
+                        Role::Committer
+                    };
+                    let part = match chars.next() {
+                        Some('n') => Some(IdentityPart::Name),
+                        Some('e') => Some(IdentityPart::Email),
+                        Some('d') => Some(IdentityPart::Date(Some(GIT_DEFAULT_DATE.into()))),
+                        Some('i') => Some(IdentityPart::Date(None)),
+                        Some('t') => Some(IdentityPart::Date(Some("%s".into()))),
+                        _ => None,
+                    };
+                    Some(Field::Identity(role, part.ok_or_else(|| unknown(rest, 2))?))
+                }
+                _ => return Err(unknown(rest, 1)),
+            };
+            rest = chars.as_str();
+            if let Some(field) = field {
+                if !literal.is_empty() {
+                    items.push(Item::Literal(std::mem::take(&mut literal)));
+                }
+                items.push(Item::Placeholder(field, Modifier::default()));
+            }
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            items.push(Item::Literal(literal));
+        }
+        Ok(Self(items))
+    }
+}
+
+/// Parse a `[-]<width>[.<max>]` modifier.
+fn parse_modifier(spec: &str) -> Option<Modifier> {
+    let (left_align, spec) = match spec.strip_prefix('-') {
+        Some(spec) => (true, spec),
+        None => (false, spec),
+    };
+    let (width, max) = match spec.split_once('.') {
+        Some((width, max)) => (width, Some(max)),
+        None => (spec, None),
+    };
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !(is_number(width) || width.is_empty() && max.is_some()) {
+        return None;
+    }
+    let width = if width.is_empty() {
+        0
+    } else {
+        width.parse().ok()?
+    };
+    let max = match max {
+        Some(max) if is_number(max) => Some(max.parse().ok()?),
+        Some(_) => return None,
+        None => None,
+    };
+    Some(Modifier {
+        width,
+        left_align,
+        max,
+    })
+}
+
+/// Check that a strftime-style date format is usable.
+fn check_date_format(date_format: &str) -> Option<()> {
+    format_date(gix::date::Time::new(0, 0), Some(date_format))
+        .ok()
+        .map(|_| ())
+}
+
+fn format_date(time: gix::date::Time, date_format: Option<&str>) -> Result<String> {
+    let Some(date_format) = date_format else {
+        return Ok(time.format(gix::date::time::format::ISO8601));
+    };
+    let offset = jiff::tz::Offset::from_seconds(time.offset)?;
+    let zoned = jiff::Timestamp::from_second(time.seconds)?.to_zoned(offset.to_time_zone());
+    Ok(jiff::fmt::strtime::format(date_format, &zoned)?)
+}
+
+impl Modifier {
+    fn apply(&self, value: String) -> String {
+        let value = match self.max {
+            Some(max) if value.chars().count() > max => value.chars().take(max).collect(),
+            _ => value,
+        };
+        let width = self.width;
+        if self.left_align {
+            format!("{value:<width$}")
+        } else {
+            format!("{value:>width$}")
+        }
+    }
+}
+
+impl PatchFormat {
+    /// Expand the format string for the given patch.
+    pub(crate) fn render(&self, info: &PatchInfo) -> Result<String> {
+        let mut output = String::new();
+        for item in &self.0 {
+            match item {
+                Item::Literal(literal) => output.push_str(literal),
+                Item::Placeholder(field, modifier) => {
+                    output.push_str(&modifier.apply(expand(field, info)?));
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl CommitFormat {
+    /// Expand the format string for the given commit.
+    pub(crate) fn render(&self, commit: &gix::Commit) -> Result<String> {
+        let mut output = String::new();
+        for item in &self.0 {
+            match item {
+                Item::Literal(literal) => output.push_str(literal),
+                Item::Placeholder(field, modifier) => {
+                    output.push_str(&modifier.apply(expand_commit(field, commit)?));
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn expand(field: &Field, info: &PatchInfo) -> Result<String> {
+    Ok(match field {
+        Field::Name => info.name.to_string(),
+        Field::State => info.sigil.to_string(),
+        Field::Index => info.index.to_string(),
+        Field::Subject => Subject::new(&info.commit.message_ex().decode()?, info.name).text,
+        _ => expand_commit(field, info.commit)?,
+    })
+}
+
+/// Expand a placeholder that only depends on the commit.
+fn expand_commit(field: &Field, commit: &gix::Commit) -> Result<String> {
+    Ok(match field {
+        Field::Name | Field::State | Field::Index => {
+            unreachable!("patch placeholders are expanded with the patch info")
+        }
+        Field::Commit => commit.id.to_string(),
+        Field::CommitShort => commit.id().shorten_or_id().to_string(),
+        Field::Tree => commit.tree_id()?.to_string(),
+        Field::TreeShort => commit.tree_id()?.shorten_or_id().to_string(),
+        Field::Parents | Field::ParentsShort => {
+            let parent_ids: Vec<String> = commit
+                .parent_ids()
+                .map(|id| {
+                    if matches!(field, Field::Parents) {
+                        id.to_string()
+                    } else {
+                        id.shorten_or_id().to_string()
+                    }
+                })
+                .collect();
+            parent_ids.join(" ")
+        }
+        Field::Identity(role, part) => {
+            let commit_ref = commit.decode()?;
+            let signature = match role {
+                Role::Author => commit_ref.author(),
+                Role::Committer => commit_ref.committer(),
+            };
+            match part {
+                IdentityPart::Full => format!(
+                    "{} <{}>",
+                    signature.name.to_str_lossy(),
+                    signature.email.to_str_lossy()
+                ),
+                IdentityPart::Name => signature.name.to_str_lossy().to_string(),
+                IdentityPart::Email => signature.email.to_str_lossy().to_string(),
+                IdentityPart::Date(date_format) => {
+                    format_date(signature.time, date_format.as_deref())?
+                }
+            }
+        }
+        Field::Subject | Field::Body => {
+            let message = commit.message_ex();
+            let message = message.decode()?;
+            let (subject, body) = message.split_once('\n').unwrap_or((&message, ""));
+            if matches!(field, Field::Subject) {
+                subject.trim_end().to_string()
+            } else {
+                body.trim().to_string()
+            }
+        }
+        Field::Empty => commit.is_no_change()?.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholder(field: Field) -> Item {
+        Item::Placeholder(field, Modifier::default())
+    }
+
+    #[test]
+    fn parse_literals_and_placeholders() {
+        let format: PatchFormat = "%(name) 100%% %(commit:short)%x".parse().unwrap();
+        assert_eq!(
+            format.0,
+            vec![
+                placeholder(Field::Name),
+                Item::Literal(" 100% ".into()),
+                placeholder(Field::CommitShort),
+                Item::Literal("%x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_identities() {
+        let format: PatchFormat =
+            "%(author)%(committer:email)%(author:date)%(committer:date:%Y-%m-%d %H:%M)"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            format.0,
+            vec![
+                placeholder(Field::Identity(Role::Author, IdentityPart::Full)),
+                placeholder(Field::Identity(Role::Committer, IdentityPart::Email)),
+                placeholder(Field::Identity(Role::Author, IdentityPart::Date(None))),
+                placeholder(Field::Identity(
+                    Role::Committer,
+                    IdentityPart::Date(Some("%Y-%m-%d %H:%M".into()))
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_modifiers() {
+        let format: PatchFormat = "%(name:-30)%(index:4)%(subject:.10)%(author:date:-25.25)"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            format.0,
+            vec![
+                Item::Placeholder(
+                    Field::Name,
+                    Modifier {
+                        width: 30,
+                        left_align: true,
+                        max: None
+                    }
+                ),
+                Item::Placeholder(
+                    Field::Index,
+                    Modifier {
+                        width: 4,
+                        left_align: false,
+                        max: None
+                    }
+                ),
+                Item::Placeholder(
+                    Field::Subject,
+                    Modifier {
+                        width: 0,
+                        left_align: false,
+                        max: Some(10)
+                    }
+                ),
+                Item::Placeholder(
+                    Field::Identity(Role::Author, IdentityPart::Date(None)),
+                    Modifier {
+                        width: 25,
+                        left_align: true,
+                        max: Some(25)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (format, message) in [
+            ("%(nme)", "unknown placeholder `%(nme)`"),
+            (
+                "x %(author:phone) y",
+                "unknown placeholder `%(author:phone)`",
+            ),
+            ("%(name:-)", "unknown placeholder `%(name:-)`"),
+            ("%(name:3.x)", "unknown placeholder `%(name:3.x)`"),
+            ("%(commit:long)", "unknown placeholder `%(commit:long)`"),
+            (
+                "%(author:date:%Y-%)",
+                "unknown placeholder `%(author:date:%Y-%)`",
+            ),
+            ("%(name", "unterminated placeholder `%(name`"),
+        ] {
+            let err = PatchFormat::from_str(format).unwrap_err();
+            assert_eq!(err.to_string(), message, "format {format:?}");
+        }
+    }
+
+    #[test]
+    fn parse_commit_format() {
+        let format: CommitFormat = "%h %an <%ae>%n%s 100%% %t %p".parse().unwrap();
+        assert_eq!(
+            format.0,
+            vec![
+                placeholder(Field::CommitShort),
+                Item::Literal(" ".into()),
+                placeholder(Field::Identity(Role::Author, IdentityPart::Name)),
+                Item::Literal(" <".into()),
+                placeholder(Field::Identity(Role::Author, IdentityPart::Email)),
+                Item::Literal(">\n".into()),
+                placeholder(Field::Subject),
+                Item::Literal(" 100% ".into()),
+                placeholder(Field::TreeShort),
+                Item::Literal(" ".into()),
+                placeholder(Field::ParentsShort),
+            ]
+        );
+
+        for (format, message) in [
+            ("%x", "unknown placeholder `%x`"),
+            ("%aq", "unknown placeholder `%aq`"),
+            ("%(name)", "unknown placeholder `%(`"),
+            ("abc %", "unknown placeholder `%`"),
+        ] {
+            let err = CommitFormat::from_str(format).unwrap_err();
+            assert_eq!(err.to_string(), message, "format {format:?}");
+        }
+    }
+
+    #[test]
+    fn apply_modifiers() {
+        let modifier = |spec| parse_modifier(spec).unwrap();
+        assert_eq!(modifier("6").apply("abc".into()), "   abc");
+        assert_eq!(modifier("-6").apply("abc".into()), "abc   ");
+        assert_eq!(modifier("2").apply("abcd".into()), "abcd");
+        assert_eq!(modifier(".2").apply("abcd".into()), "ab");
+        assert_eq!(modifier("-4.2").apply("äöüß".into()), "äö  ");
+    }
+
+    #[test]
+    fn date_formats() {
+        let time = gix::date::Time::new(1112911993, -7 * 60 * 60);
+        assert_eq!(
+            format_date(time, None).unwrap(),
+            "2005-04-07 15:13:13 -0700"
+        );
+        assert_eq!(
+            format_date(time, Some("%Y/%m/%d %H:%M %z")).unwrap(),
+            "2005/04/07 15:13 -0700"
+        );
+        let CommitFormat(items) = "%ad|%at".parse().unwrap();
+        let dates: Vec<String> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Placeholder(Field::Identity(_, IdentityPart::Date(date_format)), _) => {
+                    Some(format_date(time, date_format.as_deref()).unwrap())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(dates, ["Thu Apr 7 15:13:13 2005 -0700", "1112911993"]);
+    }
+}
@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Normalization of user-provided pathspecs.
+//!
+//! As with `git`, paths given on the command line are relative to the current
+//! directory. Paths prefixed with `:/` are instead relative to the root of the work
+//! tree, e.g. `:/Makefile` refers to the top-level makefile no matter which
+//! subdirectory StGit is run from.
+//!
+//! Some `git` commands run by StGit are run from the root of the work tree, so
+//! pathspecs are normalized to be anchored at the root before being handed to `git`.
+//! This way, pathspecs are interpreted the same way by every command.
+
+use std::{
+    ffi::OsString,
+    path::{Component, Path},
+};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+
+/// Convert a user-provided path to a path relative to the work tree root.
+///
+/// Relative paths are relative to the current directory, as indicated by `prefix`,
+/// unless they begin with `:/`.
+pub(crate) fn root_relative_path(workdir: &Path, prefix: &BStr, path: &Path) -> Result<BString> {
+    let (base, path) = if let Some(top_path) = strip_top_magic(path) {
+        (BString::default(), top_path)
+    } else if path.is_absolute() {
+        let workdir = gix::path::realpath(workdir)?;
+        let relative = path
+            .strip_prefix(&workdir)
+            .map_err(|_| anyhow!("`{}` is outside the work tree", path.display()))?;
+        (BString::default(), relative)
+    } else {
+        (prefix.to_owned(), path)
+    };
+
+    let mut components: Vec<BString> = base
+        .split_str(b"/")
+        .filter(|component| !component.is_empty())
+        .map(BString::from)
+        .collect();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(
+                Vec::from_os_string(name.to_owned())
+                    .map_err(|_| anyhow!("`{}` is not a valid path", path.display()))?
+                    .into(),
+            ),
+            Component::ParentDir => {
+                if components.pop().is_none() {
+                    return Err(anyhow!("`{}` is outside the work tree", path.display()));
+                }
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    Ok(bstr::join("/", components).into())
+}
+
+/// Normalize user-provided pathspecs into pathspecs anchored at the work tree root.
+///
+/// Pathspecs using magic other than `:/`, e.g. `:(exclude)*.md`, are passed through
+/// as-is. Without a work tree, all pathspecs are passed through as-is.
+pub(crate) fn normalize<I, P>(repo: &gix::Repository, pathspecs: I) -> Result<Vec<OsString>>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let Some(workdir) = repo.work_dir() else {
+        return Ok(pathspecs
+            .into_iter()
+            .map(|pathspec| pathspec.as_ref().as_os_str().to_owned())
+            .collect());
+    };
+    let prefix = repo
+        .prefix()
+        .context("determining Git prefix")?
+        .map(|prefix| gix::path::into_bstr(prefix).into_owned())
+        .unwrap_or_default();
+
+    pathspecs
+        .into_iter()
+        .map(|pathspec| {
+            let pathspec = pathspec.as_ref();
+            let bytes = gix::path::into_bstr(pathspec);
+            if bytes.starts_with(b":") && !bytes.starts_with(b":/") {
+                Ok(pathspec.as_os_str().to_owned())
+            } else {
+                let path = root_relative_path(workdir, prefix.as_bstr(), pathspec)?;
+                Ok(top_pathspec(path.as_bstr(), false))
+            }
+        })
+        .collect()
+}
+
+/// Make a pathspec for a work tree root relative path.
+///
+/// The resulting pathspec matches the same files regardless of the directory `git` is
+/// run from. When `literal` is true, wildcards in the path are not expanded.
+pub(crate) fn top_pathspec(path: &BStr, literal: bool) -> OsString {
+    let mut pathspec = BString::from(if literal { ":(top,literal)" } else { ":(top)" });
+    pathspec.push_str(path);
+    gix::path::from_bstring(pathspec).into_os_string()
+}
+
+/// Get the remainder of a path beginning with the `:/` pathspec magic.
+fn strip_top_magic(path: &Path) -> Option<&Path> {
+    path.to_str()
+        .and_then(|s| s.strip_prefix(":/"))
+        .map(Path::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_relative_paths() {
+        let workdir = Path::new("/work");
+        let prefix = BStr::new("src/sub");
+        let relative = |path: &str| root_relative_path(workdir, prefix, Path::new(path)).unwrap();
+
+        assert_eq!(relative("a.c"), "src/sub/a.c");
+        assert_eq!(relative("../b.c"), "src/b.c");
+        assert_eq!(relative("."), "src/sub");
+        assert_eq!(relative(":/a.c"), "a.c");
+        assert_eq!(relative(":/lib/../c.c"), "c.c");
+        assert_eq!(relative(":/"), "");
+        assert!(root_relative_path(workdir, prefix, Path::new("../../../x")).is_err());
+        assert!(root_relative_path(workdir, prefix, Path::new(":/../x")).is_err());
+    }
+
+    #[test]
+    fn top_pathspecs() {
+        assert_eq!(top_pathspec(BStr::new("src/a.c"), false), ":(top)src/a.c");
+        assert_eq!(top_pathspec(BStr::new("*.c"), true), ":(top,literal)*.c");
+    }
+}
@@ -1,18 +1,18 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use anyhow::{anyhow, Context, Result};
 
 static SIGNALED: AtomicBool = AtomicBool::new(false);
-static CRITICAL: AtomicBool = AtomicBool::new(false);
-const SIGINT_CODE: i32 = 130;
+static HOLD_OFF_DEPTH: AtomicUsize = AtomicUsize::new(0);
+pub(crate) const SIGINT_CODE: i32 = 130;
 
-/// Setup signal/event handler for ctrl-c.
+/// Setup signal/event handler for ctrl-c and termination signals.
 pub(super) fn setup() -> Result<()> {
     ctrlc::set_handler(|| {
-        if SIGNALED.load(Ordering::SeqCst) || !CRITICAL.load(Ordering::SeqCst) {
-            std::process::exit(SIGINT_CODE);
+        if SIGNALED.load(Ordering::SeqCst) || HOLD_OFF_DEPTH.load(Ordering::SeqCst) == 0 {
+            crate::exit(SIGINT_CODE);
         } else {
             SIGNALED.store(true, Ordering::SeqCst);
         }
@@ -20,6 +20,31 @@ pub(super) fn setup() -> Result<()> {
     Ok(())
 }
 
+/// Guard holding off termination by a single signal/event while it is alive.
+///
+/// While any `HoldOff` guard is alive, a single ctrl-c signal/event does not terminate
+/// the process, but is instead recorded such that the holder may stop at the next
+/// consistent point, as determined by polling [`interrupted()`]. A second ctrl-c
+/// terminates the process immediately.
+pub(crate) struct HoldOff(());
+
+impl Drop for HoldOff {
+    fn drop(&mut self) {
+        HOLD_OFF_DEPTH.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Hold off termination by signals/events until the returned guard is dropped.
+pub(crate) fn hold_off() -> HoldOff {
+    HOLD_OFF_DEPTH.fetch_add(1, Ordering::SeqCst);
+    HoldOff(())
+}
+
+/// Determine whether a signal/event was received while termination was held off.
+pub(crate) fn interrupted() -> bool {
+    SIGNALED.load(Ordering::SeqCst)
+}
+
 /// Execute critical section where a single ctrl-c signal/event is held-off.
 ///
 /// If a single ctrl-c signal/event is received during the critical section, the
@@ -27,16 +52,17 @@ pub(super) fn setup() -> Result<()> {
 /// ctrl-c is received, the process will be terminated immediately.
 ///
 /// Returns a result of the inner function when not interrupted, or an error if
-/// interrupted.
+/// interrupted. When the critical section is nested within an outer [`HoldOff`], the
+/// result is returned as-is, leaving the interruption for the outer holder to handle.
 pub(crate) fn critical<F, T>(f: F) -> Result<T>
 where
     F: FnOnce() -> Result<T>,
 {
-    CRITICAL.store(true, Ordering::SeqCst);
+    let guard = hold_off();
     let result = f();
-    CRITICAL.store(false, Ordering::SeqCst);
+    drop(guard);
 
-    if SIGNALED.load(Ordering::SeqCst) {
+    if HOLD_OFF_DEPTH.load(Ordering::SeqCst) == 0 && SIGNALED.load(Ordering::SeqCst) {
         // Resetting SIGNALED allows another critical section to be used if the program
         // is allowed to continue after this critical section.
         SIGNALED.store(false, Ordering::SeqCst);
@@ -3,13 +3,22 @@
 //! The [`StackStateAccess`] trait allows uniform access to stack information for
 //! stack-like objects.
 
-use std::rc::Rc;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
+
+use anyhow::Result;
 
 use super::{
     iter::{AllPatches, BothPatches},
     state::PatchState,
+    StackConfig,
+};
+use crate::{
+    ext::{CommitExtended, RepositoryExtended},
+    patch::{LocationGroup, PatchName},
 };
-use crate::patch::{LocationConstraint, LocationGroup, PatchName};
 
 /// Trait for accessing information about a stack, including its parent branch.
 ///
@@ -38,6 +47,13 @@ pub(crate) trait StackAccess<'repo>: StackStateAccess<'repo> {
 
     /// Get stack's base commit.
     fn base(&self) -> &Rc<gix::Commit<'repo>>;
+
+    /// Get the configuration as seen by this stack.
+    ///
+    /// See [`StackConfig`] for how branch-specific values take precedence.
+    fn config(&self) -> StackConfig<'repo> {
+        StackConfig::new(self.head().repo, Some(self.get_branch_name()))
+    }
 }
 
 /// Trait for accessing stack state.
@@ -56,6 +72,17 @@ pub(crate) trait StackStateAccess<'repo> {
     /// Get patch state for given patch name.
     fn get_patch(&self, patchname: &PatchName) -> &PatchState<'repo>;
 
+    /// Get mapping of patch names to their labels.
+    ///
+    /// Patches without labels are not present in the map.
+    fn labels(&self) -> &BTreeMap<PatchName, BTreeSet<String>>;
+
+    /// Get mapping of patch name aliases to the patches they refer to.
+    ///
+    /// Aliases are former names of renamed patches that continue to resolve to the
+    /// renamed patches.
+    fn aliases(&self) -> &BTreeMap<PatchName, PatchName>;
+
     /// Test whether given patch name exists in the stack.
     ///
     /// N.B. use [`StackStateAccess::collides()`] to test for potential patch name
@@ -70,6 +97,12 @@ pub(crate) trait StackStateAccess<'repo> {
         self.all_patches().find(|pn| patchname.collides(pn))
     }
 
+    /// Test whether patch names may be abbreviated to a unique prefix when locating
+    /// patches.
+    fn allow_abbreviated_names(&self) -> bool {
+        true
+    }
+
     /// Get stack's top commit, or base if no applied patches.
     fn top(&self) -> &Rc<gix::Commit<'repo>>;
 
@@ -89,6 +122,19 @@ pub(crate) trait StackStateAccess<'repo> {
         self.get_patch_commit(patchname).id
     }
 
+    /// Prefetch the blobs needed to diff the given patches in a partial clone.
+    ///
+    /// See [`RepositoryExtended::prefetch_blobs()`].
+    fn prefetch_patch_blobs(&self, patchnames: &[PatchName]) -> Result<()> {
+        let mut tree_pairs = Vec::with_capacity(patchnames.len());
+        for patchname in patchnames {
+            let commit = self.get_patch_commit(patchname);
+            let parent = commit.get_parent_commit()?;
+            tree_pairs.push((parent.tree_id()?.detach(), commit.tree_id()?.detach()));
+        }
+        self.head().repo.prefetch_blobs(&tree_pairs, &[])
+    }
+
     /// Test whether given patch name is applied.
     fn is_applied(&self, patchname: &PatchName) -> bool {
         self.applied().contains(patchname)
@@ -114,6 +160,17 @@ pub(crate) trait StackStateAccess<'repo> {
         BothPatches::new(self.applied(), self.unapplied())
     }
 
+    /// Get names of patches having the given label, in stack order.
+    fn patches_labeled(&self, label: &str) -> Vec<&PatchName> {
+        self.all_patches()
+            .filter(|pn| {
+                self.labels()
+                    .get(*pn)
+                    .is_some_and(|labels| labels.contains(label))
+            })
+            .collect()
+    }
+
     /// Return absolute index of patch in stack.
     fn index_of(&self, patchname: &PatchName) -> usize {
         self.all_patches()
@@ -121,47 +178,6 @@ pub(crate) trait StackStateAccess<'repo> {
             .expect("patchname must exist")
     }
 
-    /// Signed distance from a patch to an optional reference patch in the stack.
-    ///
-    /// When the reference patchname is `None`, the distance is taken from the stack
-    /// base.
-    fn distance_from(&self, patchname: &PatchName, ref_patchname: Option<&PatchName>) -> isize {
-        if Some(patchname) == ref_patchname {
-            return 0;
-        }
-
-        let mut iter = self.all_patches().enumerate();
-        let (index0, is_patchname_first) = iter
-            .find_map(|(i, pn)| {
-                if pn == patchname {
-                    Some((i, true))
-                } else if Some(pn) == ref_patchname {
-                    Some((i, false))
-                } else {
-                    None
-                }
-            })
-            .expect("patchname must be in stack");
-
-        if is_patchname_first {
-            if let Some(ref_patchname) = ref_patchname {
-                let index1 = iter
-                    .find_map(|(i, pn)| (pn == ref_patchname).then_some(i))
-                    .expect("ref_patchname must be in stack");
-                let distance: isize = (index1 - index0).try_into().expect("distance fits isize");
-                -distance
-            } else {
-                // No reference patch, so measuring from stack base.
-                (index0 + 1).try_into().expect("index fits isize")
-            }
-        } else {
-            let index1 = iter
-                .find_map(|(i, pn)| (pn == patchname).then_some(i))
-                .expect("patchname must be in stack");
-            (index1 - index0).try_into().expect("distance fits isize")
-        }
-    }
-
     fn location_group(&self, patchname: &PatchName) -> LocationGroup {
         if self.applied().contains(patchname) {
             LocationGroup::Applied
@@ -173,14 +189,4 @@ pub(crate) trait StackStateAccess<'repo> {
             panic!("BUG: location_group() must be called with known patch name")
         }
     }
-
-    fn get_allowed(&self, constraint: LocationConstraint) -> Vec<&PatchName> {
-        match constraint {
-            LocationConstraint::All => self.all_patches().collect(),
-            LocationConstraint::Visible => self.applied_and_unapplied().collect(),
-            LocationConstraint::Applied => self.applied().iter().collect(),
-            LocationConstraint::Unapplied => self.unapplied().iter().collect(),
-            LocationConstraint::Hidden => self.hidden().iter().collect(),
-        }
-    }
 }
@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Local changes stashed away for the duration of a rebase or pull.
+//!
+//! With `--autostash`, the index and work tree changes are saved as a stash commit
+//! referenced by `refs/stgit/autostash/<branch>` before the stack's patches are popped.
+//! The reference outlives the command, such that when pushing the patches back halts
+//! on conflicts, the changes are reapplied once the push is finished with `stg push
+//! --continue` or `stg pull --continue`, or abandoned with `--abort` or `stg undo`.
+//! Until then, the changes may also be recovered with `git stash apply
+//! refs/stgit/autostash/<branch>`.
+
+use anyhow::{anyhow, Result};
+
+use crate::stupid::Stupid;
+
+/// Get name of the reference holding the autostashed changes for the given branch.
+pub(crate) fn autostash_refname(branch_name: &str) -> String {
+    format!("refs/stgit/autostash/{branch_name}")
+}
+
+/// Message of the autostash commit and of its stash reflog entry.
+fn stash_message(branch_name: &str) -> String {
+    format!("stgit: autostash on {branch_name}")
+}
+
+/// Outcome of reapplying the autostashed changes.
+pub(crate) enum Reapplied {
+    /// There were no autostashed changes for the branch.
+    Nothing,
+    /// The changes were applied cleanly.
+    Clean,
+    /// Applying the changes resulted in conflicts. The changes were added to the stash
+    /// reflog, such that they remain available as `stash@{0}`.
+    Conflicts,
+}
+
+/// Stash the index and work tree changes and clean the work tree.
+///
+/// Returns whether any changes were stashed. Untracked files are only stashed with
+/// `include_untracked`. It is an error if changes from an earlier operation remain
+/// stashed for the branch.
+pub(crate) fn save(
+    repo: &gix::Repository,
+    branch_name: &str,
+    include_untracked: bool,
+) -> Result<bool> {
+    let refname = autostash_refname(branch_name);
+    if repo.try_find_reference(&refname)?.is_some() {
+        return Err(anyhow!(
+            "local changes stashed by an earlier rebase or pull are still pending in \
+             `{refname}`\n\
+             finish that operation, or apply them with `git stash apply {refname}` and \
+             delete the reference with `git update-ref -d {refname}`"
+        ));
+    }
+
+    let stupid = repo.stupid();
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    if !include_untracked && statuses.check_index_and_worktree_clean().is_ok() {
+        return Ok(false);
+    }
+
+    let stash_top = || -> Result<Option<gix::ObjectId>> {
+        Ok(repo
+            .try_find_reference("refs/stash")?
+            .map(|mut reference| reference.peel_to_id_in_place())
+            .transpose()?
+            .map(gix::Id::detach))
+    };
+    let previous_top = stash_top()?;
+    stupid.stash_push_with_message(&stash_message(branch_name), include_untracked)?;
+    let stash_id = match stash_top()? {
+        Some(stash_id) if Some(stash_id) != previous_top => stash_id,
+        _ => return Ok(false),
+    };
+    repo.reference(
+        refname.as_str(),
+        stash_id,
+        gix::refs::transaction::PreviousValue::MustNotExist,
+        "stgit: autostash",
+    )?;
+    stupid.stash_drop()?;
+    Ok(true)
+}
+
+/// Reapply the autostashed changes for the branch, if any, and remove its reference.
+pub(crate) fn reapply(repo: &gix::Repository, branch_name: &str) -> Result<Reapplied> {
+    let Some(mut reference) = repo.try_find_reference(&autostash_refname(branch_name))? else {
+        return Ok(Reapplied::Nothing);
+    };
+    let stash_id = reference.peel_to_id_in_place()?.detach();
+    let stupid = repo.stupid();
+    let reapplied = if stupid.stash_apply(stash_id)? {
+        Reapplied::Clean
+    } else {
+        stupid.stash_store(stash_id, &stash_message(branch_name))?;
+        Reapplied::Conflicts
+    };
+    reference.delete()?;
+    Ok(reapplied)
+}
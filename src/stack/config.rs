@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Branch-scoped access to StGit configuration.
+
+use std::{borrow::Cow, path::Path};
+
+use bstr::{BStr, BString};
+
+/// Configuration as seen by the stack of a particular branch.
+///
+/// Values for `stgit.<key>` are first looked up as `branch.<name>.stgit.<key>` for the
+/// stack's branch, falling back to `stgit.<key>`. This allows, for example,
+/// `branch.feature-x.stgit.autostash` to override `stgit.autostash` for the
+/// `feature-x` branch only. Keys outside of the `stgit` section are looked up as-is.
+pub(crate) struct StackConfig<'repo> {
+    snapshot: gix::config::Snapshot<'repo>,
+    branch_name: Option<String>,
+}
+
+impl<'repo> StackConfig<'repo> {
+    /// Get configuration for the stack on the given branch.
+    ///
+    /// Without a branch name, e.g. when HEAD is detached, only `stgit.<key>` values
+    /// apply.
+    pub(crate) fn new(repo: &'repo gix::Repository, branch_name: Option<&str>) -> Self {
+        Self {
+            snapshot: repo.config_snapshot(),
+            branch_name: branch_name.map(ToString::to_string),
+        }
+    }
+
+    /// Get configuration for the stack on the current branch.
+    pub(crate) fn current(repo: &'repo gix::Repository) -> Self {
+        let branch_name = repo
+            .head_name()
+            .ok()
+            .flatten()
+            .map(|name| name.shorten().to_string());
+        Self::new(repo, branch_name.as_deref())
+    }
+
+    /// Get the underlying configuration snapshot, which is not branch-scoped.
+    pub(crate) fn snapshot(&self) -> &gix::config::Snapshot<'repo> {
+        &self.snapshot
+    }
+
+    /// Get the branch-specific variant, `branch.<name>.stgit.<key>`, of a `stgit.<key>`.
+    fn branch_key(&self, key: &str) -> Option<String> {
+        let branch_name = self.branch_name.as_ref()?;
+        key.strip_prefix("stgit.")
+            .map(|key| format!("branch.{branch_name}.stgit.{key}"))
+    }
+
+    /// Look up a value, first for the branch and then in general.
+    fn lookup<T>(&self, key: &str, get: impl Fn(&str) -> Option<T>) -> Option<T> {
+        self.branch_key(key)
+            .and_then(|branch_key| get(branch_key.as_str()))
+            .or_else(|| get(key))
+    }
+
+    /// Get a boolean value, ignoring values that are not valid booleans.
+    pub(crate) fn boolean(&self, key: &str) -> Option<bool> {
+        self.lookup(key, |key| self.snapshot.boolean(key))
+    }
+
+    /// Get a boolean value, reporting a value that is not a valid boolean.
+    pub(crate) fn try_boolean(&self, key: &str) -> Option<Result<bool, gix::config::value::Error>> {
+        self.lookup(key, |key| self.snapshot.try_boolean(key))
+    }
+
+    /// Get an integer value, ignoring values that are not valid integers.
+    pub(crate) fn integer(&self, key: &str) -> Option<i64> {
+        self.lookup(key, |key| self.snapshot.integer(key))
+    }
+
+    /// Get a string value.
+    pub(crate) fn string(&self, key: &str) -> Option<Cow<'repo, BStr>> {
+        self.lookup(key, |key| self.snapshot.string(key))
+    }
+
+    /// Get all values of a multi-valued variable.
+    pub(crate) fn strings(&self, key: &str) -> Option<Vec<BString>> {
+        self.lookup(key, |key| {
+            self.snapshot
+                .plumbing()
+                .strings(key)
+                .map(|values| values.into_iter().map(Cow::into_owned).collect())
+        })
+    }
+
+    /// Get a path value, subject to the usual trust checks.
+    pub(crate) fn trusted_path(
+        &self,
+        key: &str,
+    ) -> Option<Result<Cow<'repo, Path>, gix::config::path::interpolate::Error>> {
+        self.lookup(key, |key| self.snapshot.trusted_path(key))
+    }
+}
@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Record of the unmerged index entries left behind by a conflicting push.
+//!
+//! When a push results in merge conflicts, the conflicted stages of the index are
+//! saved to a blob referenced by `refs/stgit/conflicts/<branch>`. This allows
+//! `stg resolved --unmark` to restore the conflict for a path after it has been
+//! marked as resolved. The record is only valid while the branch head remains the
+//! commit that was current when the conflicts occurred.
+//!
+//! The unmerged stages of each path are also used to classify the kind of conflict,
+//! which is reported when a push stops on conflicts.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BStr, BString, ByteSlice};
+
+use crate::{patch::PatchName, stupid::Stupid};
+
+/// An unmerged index entry, as output by `git ls-files --unmerged`.
+pub(crate) struct UnmergedEntry {
+    /// Raw "<mode> <oid> <stage>\t<path>" record.
+    record: BString,
+    stage: u8,
+    path_offset: usize,
+}
+
+impl UnmergedEntry {
+    /// Merge stage of the entry: 1 for base, 2 for ours, and 3 for theirs.
+    pub(crate) fn stage(&self) -> u8 {
+        self.stage
+    }
+
+    /// File mode of the entry, e.g. "100644".
+    pub(crate) fn mode(&self) -> &BStr {
+        self.fields().0
+    }
+
+    /// Object id of the entry's blob, as a hex string.
+    pub(crate) fn oid(&self) -> &BStr {
+        self.fields().1
+    }
+
+    fn fields(&self) -> (&BStr, &BStr) {
+        let mut fields = self.record[..self.path_offset - 1].splitn_str(3, b" ");
+        let mode = fields.next().unwrap_or_default().as_bstr();
+        let oid = fields.next().unwrap_or_default().as_bstr();
+        (mode, oid)
+    }
+
+    /// Path of the entry, relative to the work tree root.
+    pub(crate) fn path(&self) -> &BStr {
+        self.record[self.path_offset..].as_bstr()
+    }
+
+    /// Get the entry as a record suitable for `git update-index -z --index-info`.
+    pub(crate) fn index_info(&self) -> &[u8] {
+        &self.record
+    }
+}
+
+/// Parse the NUL-terminated output of `git ls-files --unmerged -z`.
+pub(crate) fn parse_unmerged(data: &[u8]) -> Result<Vec<UnmergedEntry>> {
+    let mut entries = Vec::new();
+    for record in data.split_str(b"\0").filter(|record| !record.is_empty()) {
+        let tab_pos = record
+            .find_byte(b'\t')
+            .ok_or_else(|| anyhow!("malformed unmerged entry `{}`", record.as_bstr()))?;
+        let stage = match record[..tab_pos].rsplit_str(b" ").next() {
+            Some(b"1") => 1,
+            Some(b"2") => 2,
+            Some(b"3") => 3,
+            _ => return Err(anyhow!("malformed unmerged entry `{}`", record.as_bstr())),
+        };
+        entries.push(UnmergedEntry {
+            record: record.into(),
+            stage,
+            path_offset: tab_pos + 1,
+        });
+    }
+    Ok(entries)
+}
+
+/// Kind of conflict of an unmerged path, as determined by its merge stages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConflictKind {
+    /// Both sides changed the content of the path.
+    Content,
+    /// Both sides added the path with different content.
+    AddAdd,
+    /// One side deleted the path while the other side modified it.
+    DeleteModify,
+    /// The path was renamed on one or both sides.
+    Rename,
+    /// Both sides changed the path's mode, but not its content.
+    Mode,
+}
+
+impl ConflictKind {
+    /// Short tag for the kind of conflict, as used in conflict reports.
+    pub(crate) fn tag(self) -> &'static str {
+        match self {
+            ConflictKind::Content => "content",
+            ConflictKind::AddAdd => "add/add",
+            ConflictKind::DeleteModify => "delete/modify",
+            ConflictKind::Rename => "rename",
+            ConflictKind::Mode => "mode",
+        }
+    }
+}
+
+impl std::fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+/// Classify the conflict of each unmerged path.
+///
+/// The paths are returned in the order they first appear in `entries`, which is the
+/// index order for entries from `git ls-files --unmerged`.
+///
+/// The kind is determined from which stages are present for the path. A path missing
+/// its base stage was added on both sides. A path with a base stage but missing one
+/// of the other stages was deleted on that side. When a path exists on only one side,
+/// or only in the base, the merge has attributed content to or from another path,
+/// i.e. the path was renamed.
+pub(crate) fn classify(entries: &[UnmergedEntry]) -> Vec<(&BStr, ConflictKind)> {
+    let mut paths: Vec<(&BStr, [Option<&UnmergedEntry>; 3])> = Vec::new();
+    for entry in entries {
+        let stages =
+            if let Some((_, stages)) = paths.iter_mut().find(|(path, _)| *path == entry.path()) {
+                stages
+            } else {
+                paths.push((entry.path(), [None; 3]));
+                &mut paths.last_mut().unwrap().1
+            };
+        stages[usize::from(entry.stage() - 1)] = Some(entry);
+    }
+
+    paths
+        .into_iter()
+        .map(|(path, stages)| {
+            let kind = match stages {
+                [Some(_), Some(ours), Some(theirs)] => {
+                    if ours.oid() == theirs.oid() && ours.mode() != theirs.mode() {
+                        ConflictKind::Mode
+                    } else {
+                        ConflictKind::Content
+                    }
+                }
+                [None, Some(_), Some(_)] => ConflictKind::AddAdd,
+                [Some(_), Some(_), None] | [Some(_), None, Some(_)] => ConflictKind::DeleteModify,
+                _ => ConflictKind::Rename,
+            };
+            (path, kind)
+        })
+        .collect()
+}
+
+/// Write a report of the conflicts left by pushing `patchname`.
+///
+/// The report names the conflicted patch, quoted according to `quote_names`, and lists
+/// each conflicted path with its kind of conflict, followed by the commands for
+/// proceeding from the conflicted state.
+pub(crate) fn write_report(
+    out: &mut impl Write,
+    patchname: Option<&PatchName>,
+    quote_names: bool,
+    conflicts: &[(&BStr, ConflictKind)],
+) -> std::io::Result<()> {
+    let count = conflicts.len();
+    let files = if count == 1 { "file" } else { "files" };
+    if let Some(patchname) = patchname {
+        writeln!(
+            out,
+            "Conflicts in patch `{}` ({count} {files}):",
+            patchname.quoted_if(quote_names)
+        )?;
+    } else {
+        writeln!(out, "Conflicts ({count} {files}):")?;
+    }
+    let width = conflicts
+        .iter()
+        .map(|(_, kind)| kind.tag().len())
+        .max()
+        .unwrap_or(0);
+    for (path, kind) in conflicts {
+        writeln!(out, "  {:width$}  {path}", kind.tag())?;
+    }
+    writeln!(out, "Next steps:")?;
+    writeln!(
+        out,
+        "  stg resolved <path>...  mark paths as resolved once fixed"
+    )?;
+    writeln!(
+        out,
+        "  stg refresh             record the resolution in the patch"
+    )?;
+    writeln!(out, "  stg undo --hard         undo the operation instead")?;
+    Ok(())
+}
+
+/// Write `conflict <patch> <kind> <path>` lines for each conflicted path.
+pub(crate) fn write_porcelain(
+    out: &mut impl Write,
+    patchname: &PatchName,
+    conflicts: &[(&BStr, ConflictKind)],
+) -> std::io::Result<()> {
+    for (path, kind) in conflicts {
+        writeln!(out, "conflict {patchname} {kind} {path}")?;
+    }
+    Ok(())
+}
+
+/// Get name of the reference holding the conflicts record for the given branch.
+pub(super) fn conflicts_refname(branch_name: &str) -> String {
+    format!("refs/stgit/conflicts/{branch_name}")
+}
+
+/// Record the index's current unmerged entries for the branch.
+///
+/// The `head_id` is the commit that the branch head points to with these conflicts.
+pub(crate) fn record(
+    repo: &gix::Repository,
+    branch_name: &str,
+    head_id: gix::ObjectId,
+) -> Result<()> {
+    let unmerged = repo.stupid().ls_files_unmerged()?;
+    let mut content = BString::from(format!("{head_id}\n"));
+    content.extend_from_slice(&unmerged);
+    let blob_id = repo.write_blob(&content)?.detach();
+    repo.reference(
+        conflicts_refname(branch_name),
+        blob_id,
+        gix::refs::transaction::PreviousValue::Any,
+        "stgit: record conflicts",
+    )?;
+    Ok(())
+}
+
+/// Load the unmerged entries recorded for the branch.
+///
+/// `None` is returned if no conflicts were recorded with `head_id` as the branch head.
+pub(crate) fn load(
+    repo: &gix::Repository,
+    branch_name: &str,
+    head_id: gix::ObjectId,
+) -> Result<Option<Vec<UnmergedEntry>>> {
+    let Some(mut reference) = repo.try_find_reference(&conflicts_refname(branch_name))? else {
+        return Ok(None);
+    };
+    let blob = reference
+        .peel_to_id_in_place()?
+        .object()?
+        .try_into_blob()
+        .context("reading conflicts record")?;
+    let Some((recorded_head, unmerged)) = blob.data.split_once_str(b"\n") else {
+        return Err(anyhow!("malformed conflicts record"));
+    };
+    if recorded_head != head_id.to_string().as_bytes() {
+        return Ok(None);
+    }
+    parse_unmerged(unmerged).map(Some)
+}
+
+/// Remove the conflicts record for the branch, if any.
+pub(crate) fn remove(repo: &gix::Repository, branch_name: &str) -> Result<()> {
+    if let Some(reference) = repo.try_find_reference(&conflicts_refname(branch_name))? {
+        reference.delete()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, parse_unmerged, ConflictKind};
+
+    #[test]
+    fn parse_unmerged_entries() {
+        let data = b"\
+            100644 5716ca5987cbf97d6bb54920bea6adde242d87e6 1\tfoo.txt\0\
+            100644 257cc5642cb1a054f08cc83f2d943e56fd3ebe99 2\tfoo.txt\0\
+            100755 76018072e09c5d31c8c6e3113b8aa0fe625195ca 3\tdir/with space.sh\0";
+        let entries = parse_unmerged(data).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].stage(), 1);
+        assert_eq!(entries[0].path(), "foo.txt");
+        assert_eq!(entries[1].stage(), 2);
+        assert_eq!(entries[2].stage(), 3);
+        assert_eq!(entries[2].path(), "dir/with space.sh");
+        assert_eq!(
+            entries[2].index_info(),
+            b"100755 76018072e09c5d31c8c6e3113b8aa0fe625195ca 3\tdir/with space.sh"
+        );
+    }
+
+    #[test]
+    fn parse_malformed_unmerged_entry() {
+        assert!(parse_unmerged(b"100644 5716ca5987cbf97d6bb54920bea6adde242d87e6 1").is_err());
+        assert!(parse_unmerged(b"100644 5716ca5987cbf97d6bb54920bea6adde242d87e6 0\tf").is_err());
+    }
+
+    #[test]
+    fn classify_conflicts() {
+        let data = b"\
+            100644 5716ca5987cbf97d6bb54920bea6adde242d87e6 1\tcontent.txt\0\
+            100644 257cc5642cb1a054f08cc83f2d943e56fd3ebe99 2\tcontent.txt\0\
+            100644 76018072e09c5d31c8c6e3113b8aa0fe625195ca 3\tcontent.txt\0\
+            100644 5716ca5987cbf97d6bb54920bea6adde242d87e6 2\tadded.txt\0\
+            100644 257cc5642cb1a054f08cc83f2d943e56fd3ebe99 3\tadded.txt\0\
+            100644 5716ca5987cbf97d6bb54920bea6adde242d87e6 1\tdeleted.txt\0\
+            100644 257cc5642cb1a054f08cc83f2d943e56fd3ebe99 3\tdeleted.txt\0\
+            100644 5716ca5987cbf97d6bb54920bea6adde242d87e6 1\tmode.sh\0\
+            100755 257cc5642cb1a054f08cc83f2d943e56fd3ebe99 2\tmode.sh\0\
+            100644 257cc5642cb1a054f08cc83f2d943e56fd3ebe99 3\tmode.sh\0\
+            100644 76018072e09c5d31c8c6e3113b8aa0fe625195ca 3\trenamed.txt\0";
+        let entries = parse_unmerged(data).unwrap();
+        assert_eq!(entries[8].mode(), "100755");
+        assert_eq!(entries[8].oid(), "257cc5642cb1a054f08cc83f2d943e56fd3ebe99");
+        let classified: Vec<(String, ConflictKind)> = classify(&entries)
+            .into_iter()
+            .map(|(path, kind)| (path.to_string(), kind))
+            .collect();
+        assert_eq!(
+            classified,
+            [
+                ("content.txt".to_string(), ConflictKind::Content),
+                ("added.txt".to_string(), ConflictKind::AddAdd),
+                ("deleted.txt".to_string(), ConflictKind::DeleteModify),
+                ("mode.sh".to_string(), ConflictKind::Mode),
+                ("renamed.txt".to_string(), ConflictKind::Rename),
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Backup of the local changes discarded by a hard undo, redo, or reset.
+//!
+//! Before the index and work tree are reset, their state is saved as a stash commit
+//! referenced by `refs/stgit/discarded/<branch>`. The discarded changes may then be
+//! recovered with `git stash apply refs/stgit/discarded/<branch>`. Only the most
+//! recently discarded changes are kept.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{
+    ext::RepositoryExtended,
+    stupid::{StatusEntryKind, Stupid},
+};
+
+/// Get name of the reference holding the discarded changes for the given branch.
+pub(super) fn discarded_refname(branch_name: &str) -> String {
+    format!("refs/stgit/discarded/{branch_name}")
+}
+
+/// Save the index and work tree changes that are about to be discarded.
+///
+/// The name of the reference to the saved changes is returned, or `None` if there
+/// were no local changes to save.
+///
+/// The index cannot be stashed while it has unmerged entries, in which case the
+/// work tree content of the changed files is saved as both the index and work tree
+/// state.
+pub(crate) fn record(repo: &gix::Repository, branch_name: &str) -> Result<Option<String>> {
+    let stupid = repo.stupid();
+    let message = format!("stgit: changes discarded on {branch_name}");
+    let statuses = stupid.statuses(None)?;
+    if statuses.is_empty() {
+        return Ok(None);
+    }
+
+    let stash_id = if statuses
+        .iter()
+        .any(|entry| matches!(entry.kind(), StatusEntryKind::Unmerged))
+    {
+        let head_tree_id = repo.head_commit_ex()?.tree_id()?.detach();
+        let paths: Vec<&Path> = statuses.iter().map(|entry| entry.path()).collect();
+        stupid.with_temp_index(|stupid_temp| {
+            stupid_temp.read_tree(head_tree_id)?;
+            stupid_temp.update_index(Some(paths))?;
+            stupid_temp.stash_create(&message)
+        })?
+    } else {
+        stupid.stash_create(&message)?
+    };
+
+    let Some(stash_id) = stash_id else {
+        return Ok(None);
+    };
+    let refname = discarded_refname(branch_name);
+    repo.reference(
+        refname.as_str(),
+        stash_id,
+        gix::refs::transaction::PreviousValue::Any,
+        "stgit: save discarded changes",
+    )?;
+    Ok(Some(refname))
+}
+
+/// Remove the discarded changes reference for the branch, if any.
+pub(crate) fn remove(repo: &gix::Repository, branch_name: &str) -> Result<()> {
+    if let Some(reference) = repo.try_find_reference(&discarded_refname(branch_name))? {
+        reference.delete()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Reading historical stack states from the stack log.
+//!
+//! Each stack state commit records the commit of the previous stack state, forming a
+//! log of the stack's states from newest to oldest. The facilities in this module walk
+//! this log to recover information about how the stack, or a single patch, has evolved
+//! over time.
+
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use super::{Stack, StackAccess, StackState, StackStateAccess};
+use crate::patch::PatchName;
+
+/// A stack state recorded in the stack log.
+pub(crate) struct StackLogEntry<'repo> {
+    /// The stack state commit.
+    pub(crate) commit: Rc<gix::Commit<'repo>>,
+
+    /// The stack state recorded by the commit.
+    pub(crate) state: StackState<'repo>,
+}
+
+impl StackLogEntry<'_> {
+    /// Get the first line of the stack state commit's message.
+    ///
+    /// This is the description of the operation that produced this stack state.
+    pub(crate) fn summary(&self) -> String {
+        self.commit
+            .message_raw_sloppy()
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_str_lossy()
+            .to_string()
+    }
+
+    /// Get the id of the simplified stack state commit.
+    ///
+    /// Each stack state commit's first parent is a simplified commit with the same
+    /// stack state tree. The simplified commits form the history shown by `stg log`.
+    pub(crate) fn simplified_id(&self) -> gix::Id<'_> {
+        self.commit
+            .parent_ids()
+            .next()
+            .unwrap_or_else(|| self.commit.id())
+    }
+}
+
+/// Iterator over [`StackLogEntry`] instances, from newest to oldest.
+pub(crate) struct StackLogIter<'repo> {
+    repo: &'repo gix::Repository,
+    next_commit: Option<Rc<gix::Commit<'repo>>>,
+}
+
+impl<'repo> Iterator for StackLogIter<'repo> {
+    type Item = Result<StackLogEntry<'repo>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let commit = self.next_commit.take()?;
+        match StackState::from_commit(self.repo, &commit) {
+            Ok(state) => {
+                self.next_commit.clone_from(&state.prev);
+                Some(Ok(StackLogEntry { commit, state }))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Walk the stack log, starting with the stack's current state.
+pub(crate) fn stack_log<'repo>(stack: &Stack<'repo>) -> Result<StackLogIter<'repo>> {
+    let repo = stack.repo;
+    let commit = repo
+        .find_reference(stack.get_stack_refname())?
+        .peel_to_commit()?;
+    Ok(StackLogIter {
+        repo,
+        next_commit: Some(Rc::new(commit)),
+    })
+}
+
+/// A distinct version of a patch found in the stack log.
+pub(crate) struct PatchVersion<'repo> {
+    /// Name of the patch at the time of this version.
+    pub(crate) patchname: PatchName,
+
+    /// The patch commit of this version.
+    pub(crate) commit: Rc<gix::Commit<'repo>>,
+
+    /// The oldest stack log entry in which this version of the patch appears, i.e. the
+    /// entry for the operation that produced this version.
+    pub(crate) entry: StackLogEntry<'repo>,
+}
+
+/// Find the distinct versions of a patch in the stack log, from oldest to newest.
+///
+/// The stack log is walked from newest to oldest, collecting the patch commit the
+/// patch had in each stack state. Consecutive states with the same patch commit are
+/// collapsed into a single version. Renames are followed, so the history of a patch
+/// may cross a rename. The walk stops at the stack state where the patch was created
+/// or when the beginning of the stack log is reached.
+pub(crate) fn patch_versions<'repo>(
+    stack: &Stack<'repo>,
+    patchname: &PatchName,
+) -> Result<Vec<PatchVersion<'repo>>> {
+    let mut versions: Vec<PatchVersion<'repo>> = Vec::new();
+    let mut tracked_name = patchname.clone();
+
+    for entry in stack_log(stack)? {
+        let entry = entry?;
+
+        let found_name = if entry.state.has_patch(&tracked_name) {
+            Some(tracked_name.clone())
+        } else if let Some(newer) = versions.last() {
+            find_renamed_patch(&entry, newer)
+        } else {
+            None
+        };
+
+        let Some(found_name) = found_name else {
+            break;
+        };
+
+        let commit = entry.state.get_patch(&found_name).commit.clone();
+        if let Some(newer) = versions.last_mut() {
+            if newer.commit.id == commit.id {
+                newer.patchname = found_name.clone();
+                newer.entry = entry;
+                tracked_name = found_name;
+                continue;
+            }
+        }
+        versions.push(PatchVersion {
+            patchname: found_name.clone(),
+            commit,
+            entry,
+        });
+        tracked_name = found_name;
+    }
+
+    versions.reverse();
+    Ok(versions)
+}
+
+/// Find the version of a patch recorded in a historical stack state.
+///
+/// The stack log is walked from the stack's current state back to the stack state
+/// commit `state_id`, following any renames of the patch along the way. Either a stack
+/// state commit or its simplified commit, as shown by `stg log`, may be given. The
+/// entry of the returned version is that of `state_id`. `None` is returned if the patch did not
+/// yet exist in that stack state, and an error is returned if `state_id` is not in the
+/// stack log.
+pub(crate) fn patch_at_state<'repo>(
+    stack: &Stack<'repo>,
+    patchname: &PatchName,
+    state_id: gix::ObjectId,
+) -> Result<Option<PatchVersion<'repo>>> {
+    let mut newer: Option<PatchVersion<'repo>> = None;
+    let mut tracking = true;
+
+    for entry in stack_log(stack)? {
+        let entry = entry?;
+        let is_target = entry.commit.id == state_id || entry.simplified_id() == state_id;
+
+        if tracking {
+            let found_name = if let Some(newer) = newer.as_ref() {
+                if entry.state.has_patch(&newer.patchname) {
+                    Some(newer.patchname.clone())
+                } else {
+                    find_renamed_patch(&entry, newer)
+                }
+            } else if entry.state.has_patch(patchname) {
+                Some(patchname.clone())
+            } else {
+                None
+            };
+
+            if let Some(found_name) = found_name {
+                let version = PatchVersion {
+                    commit: entry.state.get_patch(&found_name).commit.clone(),
+                    patchname: found_name,
+                    entry,
+                };
+                if is_target {
+                    return Ok(Some(version));
+                }
+                newer = Some(version);
+                continue;
+            }
+            tracking = false;
+        }
+
+        if is_target {
+            return Ok(None);
+        }
+    }
+
+    Err(anyhow!(
+        "`{state_id}` is not in the stack log of branch `{}`",
+        stack.get_branch_name()
+    ))
+}
+
+/// Find how far back in the stack log each of the given patch names first appears.
+///
+/// For each patch name, the number of stack states between the current state and the
+/// oldest state having a patch of that name is returned, so a patch created earlier has
+/// a larger value. Only names are matched; a renamed patch is taken to be created when
+/// it got its current name.
+pub(crate) fn first_appearances(stack: &Stack, patchnames: &[PatchName]) -> Result<Vec<usize>> {
+    let mut depths = vec![0; patchnames.len()];
+    for (depth, entry) in stack_log(stack)?.enumerate() {
+        let entry = entry?;
+        for (patchname, patch_depth) in patchnames.iter().zip(depths.iter_mut()) {
+            if entry.state.has_patch(patchname) {
+                *patch_depth = depth;
+            }
+        }
+    }
+    Ok(depths)
+}
+
+/// Find the name a patch had in an older stack state, before it was renamed.
+///
+/// The log message of the newer version's stack state is consulted first since
+/// `stg rename` records both the old and new names. Failing that, a patch in the older
+/// state with the same commit as the newer version is assumed to be the renamed patch.
+fn find_renamed_patch(older: &StackLogEntry, newer: &PatchVersion) -> Option<PatchName> {
+    let summary = newer.entry.summary();
+    let mut words = summary.split_whitespace();
+    if let (Some("rename"), Some(old_name), Some(new_name), None) =
+        (words.next(), words.next(), words.next(), words.next())
+    {
+        if new_name == AsRef::<str>::as_ref(&newer.patchname) {
+            if let Ok(old_name) = old_name.parse::<PatchName>() {
+                if older.state.has_patch(&old_name) {
+                    return Some(old_name);
+                }
+            }
+        }
+    }
+
+    older
+        .state
+        .all_patches()
+        .find(|pn| older.state.get_patch(pn).commit.id == newer.commit.id)
+        .cloned()
+}
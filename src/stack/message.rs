@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Descriptions of stack operations.
+//!
+//! Each stack-modifying operation is described by a log message that becomes the
+//! message of the new stack state commit, i.e. the entry shown by `stg log`. The
+//! reflog messages of the branch and stack state references updated by the operation
+//! are derived from that same log message so that the two never diverge.
+
+use crate::patch::PatchName;
+
+/// Maximum number of patch names spelled out in a log message.
+const MAX_NAMED_PATCHES: usize = 3;
+
+/// Describe an operation applied to a list of patches.
+///
+/// For example, `push fix-io fix-net`. Long lists of patches are elided after the
+/// first few names, e.g. `delete p1 p2 p3 (+4 more)`.
+pub(crate) fn with_patches<P: AsRef<PatchName>>(operation: &str, patchnames: &[P]) -> String {
+    let mut msg = operation.to_string();
+    for patchname in patchnames.iter().take(MAX_NAMED_PATCHES) {
+        msg.push_str(&format!(" {}", patchname.as_ref()));
+    }
+    if patchnames.len() > MAX_NAMED_PATCHES {
+        msg.push_str(&format!(
+            " (+{} more)",
+            patchnames.len() - MAX_NAMED_PATCHES
+        ));
+    }
+    msg
+}
+
+/// Derive the reflog message for references updated by an operation from the
+/// operation's log message.
+///
+/// Only the first line of the log message is used.
+pub(super) fn reflog(log_msg: &str) -> String {
+    format!("stg {}", log_msg.lines().next().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<PatchName> {
+        names
+            .iter()
+            .map(|name| PatchName::from_str(name).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn with_patches_names_few_patches() {
+        assert_eq!(with_patches::<PatchName>("push", &[]), "push");
+        assert_eq!(with_patches("push", &names(&["fix-io"])), "push fix-io");
+        assert_eq!(with_patches("pop", &names(&["a", "b", "c"])), "pop a b c");
+    }
+
+    #[test]
+    fn with_patches_elides_many_patches() {
+        assert_eq!(
+            with_patches("delete", &names(&["a", "b", "c", "d", "e"])),
+            "delete a b c (+2 more)"
+        );
+    }
+
+    #[test]
+    fn reflog_uses_first_line() {
+        assert_eq!(reflog("refresh p1\n\nannotation"), "stg refresh p1");
+        assert_eq!(reflog("push fix-io"), "stg push fix-io");
+    }
+}
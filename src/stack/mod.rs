@@ -2,15 +2,30 @@
 
 //! The StGit stack data structure.
 mod access;
+pub(crate) mod autostash;
+mod config;
+pub(crate) mod conflicts;
+pub(crate) mod discarded;
+pub(crate) mod history;
 mod iter;
+pub(crate) mod message;
+pub(crate) mod pending;
 mod serde;
 #[allow(clippy::module_inception)]
 mod stack;
 mod state;
 mod transaction;
 mod upgrade;
+mod view;
 
 pub(crate) use access::{StackAccess, StackStateAccess};
-pub(crate) use stack::{state_refname_from_branch_name, InitializationPolicy, Stack};
+pub(crate) use config::StackConfig;
+pub(crate) use stack::{
+    allow_missing_patch_commits, has_case_insensitive_refs, metadata_references,
+    state_refname_from_branch_name, InitializationPolicy, Stack,
+};
 pub(crate) use state::{PatchState, StackState};
-pub(crate) use transaction::{Error as TransactionError, StackTransaction};
+pub(crate) use transaction::{
+    normalize_subject, Error as TransactionError, MergedCheck, StackTransaction,
+};
+pub(crate) use view::{commit_trees, PatchView, StackView};
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Record of a push that halted on merge conflicts.
+//!
+//! When `stg push` stops because a patch conflicts, the stack state from before the
+//! push, the conflicted patch, and the patches that remain to be pushed are saved to
+//! a blob referenced by `refs/stgit/push/<branch>`. This allows `stg push --continue`
+//! to resume the push once the conflicts are resolved and `stg push --abort` to
+//! restore the stack as it was before the push. The record is only valid while the
+//! branch head remains the conflicted patch's commit from when the push halted; once
+//! the resolution is recorded otherwise, e.g. with `stg refresh`, or the push is
+//! undone, the push is no longer considered to be in progress.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+
+use crate::patch::PatchName;
+
+/// A push halted by conflicts, to be continued or aborted.
+pub(crate) struct PendingPush {
+    /// Stack state commit from before the push started.
+    pub(crate) state_id: gix::ObjectId,
+    /// Commit of the conflicted patch that the branch head pointed to at the halt.
+    pub(crate) head_id: gix::ObjectId,
+    /// Stash commit of the local changes kept by the push with `--keep`, if any.
+    pub(crate) stash_id: Option<gix::ObjectId>,
+    /// The patch whose push resulted in conflicts.
+    pub(crate) patchname: PatchName,
+    /// Patches remaining to be pushed after the conflicted patch, in push order.
+    pub(crate) remaining: Vec<PatchName>,
+}
+
+impl PendingPush {
+    fn serialize(&self) -> String {
+        let mut content = format!("state {}\nhead {}\n", self.state_id, self.head_id);
+        if let Some(stash_id) = self.stash_id {
+            content.push_str(&format!("stash {stash_id}\n"));
+        }
+        content.push_str(&format!("conflicted {}\n", self.patchname));
+        for patchname in &self.remaining {
+            content.push_str(&format!("remaining {patchname}\n"));
+        }
+        content
+    }
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let malformed = || anyhow!("malformed push record");
+        let parse_oid = |value: &str| gix::ObjectId::from_hex(value.as_bytes());
+        let mut state_id = None;
+        let mut head_id = None;
+        let mut stash_id = None;
+        let mut patchname = None;
+        let mut remaining = Vec::new();
+        for line in data.to_str().map_err(|_| malformed())?.lines() {
+            let (key, value) = line.split_once(' ').ok_or_else(malformed)?;
+            match key {
+                "state" => state_id = Some(parse_oid(value)?),
+                "head" => head_id = Some(parse_oid(value)?),
+                "stash" => stash_id = Some(parse_oid(value)?),
+                "conflicted" => patchname = Some(PatchName::from_str(value)?),
+                "remaining" => remaining.push(PatchName::from_str(value)?),
+                _ => return Err(malformed()),
+            }
+        }
+        Ok(Self {
+            state_id: state_id.ok_or_else(malformed)?,
+            head_id: head_id.ok_or_else(malformed)?,
+            stash_id,
+            patchname: patchname.ok_or_else(malformed)?,
+            remaining,
+        })
+    }
+}
+
+/// Get name of the reference holding the pending push record for the given branch.
+pub(super) fn pending_refname(branch_name: &str) -> String {
+    format!("refs/stgit/push/{branch_name}")
+}
+
+/// Record the pending push for the branch, replacing any previous record.
+pub(crate) fn record(
+    repo: &gix::Repository,
+    branch_name: &str,
+    pending: &PendingPush,
+) -> Result<()> {
+    let blob_id = repo.write_blob(pending.serialize())?.detach();
+    repo.reference(
+        pending_refname(branch_name),
+        blob_id,
+        gix::refs::transaction::PreviousValue::Any,
+        "stgit: record pending push",
+    )?;
+    Ok(())
+}
+
+/// Load the pending push recorded for the branch, if any.
+///
+/// The record is returned as-is; see [`super::Stack::pending_push()`] for a record
+/// checked against the current stack.
+pub(crate) fn load(repo: &gix::Repository, branch_name: &str) -> Result<Option<PendingPush>> {
+    let Some(mut reference) = repo.try_find_reference(&pending_refname(branch_name))? else {
+        return Ok(None);
+    };
+    let blob = reference
+        .peel_to_id_in_place()?
+        .object()?
+        .try_into_blob()
+        .context("reading push record")?;
+    PendingPush::parse(&blob.data).map(Some)
+}
+
+/// Remove the pending push record for the branch, if any.
+pub(crate) fn remove(repo: &gix::Repository, branch_name: &str) -> Result<()> {
+    if let Some(reference) = repo.try_find_reference(&pending_refname(branch_name))? {
+        reference.delete()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::PendingPush;
+    use crate::patch::PatchName;
+
+    #[test]
+    fn round_trip_pending_push() {
+        let pending = PendingPush {
+            state_id: gix::ObjectId::from_hex(b"5716ca5987cbf97d6bb54920bea6adde242d87e6").unwrap(),
+            head_id: gix::ObjectId::from_hex(b"257cc5642cb1a054f08cc83f2d943e56fd3ebe99").unwrap(),
+            stash_id: None,
+            patchname: PatchName::from_str("p2").unwrap(),
+            remaining: vec![
+                PatchName::from_str("p3").unwrap(),
+                PatchName::from_str("p4").unwrap(),
+            ],
+        };
+        let parsed = PendingPush::parse(pending.serialize().as_bytes()).unwrap();
+        assert_eq!(parsed.state_id, pending.state_id);
+        assert_eq!(parsed.head_id, pending.head_id);
+        assert_eq!(parsed.stash_id, None);
+        assert_eq!(parsed.patchname, pending.patchname);
+        assert_eq!(parsed.remaining, pending.remaining);
+    }
+
+    #[test]
+    fn parse_malformed_pending_push() {
+        assert!(PendingPush::parse(b"conflicted p1\n").is_err());
+        assert!(PendingPush::parse(b"state 5716ca5987cbf97d6bb54920bea6adde242d87e6\n").is_err());
+        assert!(PendingPush::parse(b"bogus\n").is_err());
+    }
+}
@@ -1,12 +1,35 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
 //! Serialize and deserialize stack state to/from JSON representation.
+//!
+//! The `version` field of the serialized stack state identifies its format:
+//!
+//! - Version 6 adds the optional `labels` and `aliases` fields.
+//! - Version 5 is the original JSON format, introduced in StGit `v1.2`.
+//!
+//! The optional `command` field, recording the command line of the StGit command
+//! that produced the stack state, was added without a version change. It is purely
+//! informational and older versions of StGit simply ignore it.
+//!
+//! All versions from [`MIN_FORMAT_VERSION`] through [`FORMAT_VERSION`] can be read.
+//! Stack state is written with the oldest format version able to represent it: a
+//! stack without labels or aliases is written as version 5 so that it remains
+//! readable by older versions of StGit, and is only upgraded to version 6 once labels
+//! or aliases are added. Fields added by future format versions are ignored when
+//! reading, but stack states with a format version newer than [`FORMAT_VERSION`] are
+//! rejected since they may not be interpreted correctly.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Context, Result};
 
-use std::collections::BTreeMap;
+use crate::patch::PatchName;
 
-use anyhow::{Context, Result};
+/// Stack state format version written by this version of StGit.
+pub(crate) const FORMAT_VERSION: i64 = 6;
 
-use crate::patch::PatchName;
+/// Oldest stack state format version that may be read.
+pub(crate) const MIN_FORMAT_VERSION: i64 = 5;
 
 /// Raw state deserialization representation.
 ///
@@ -18,6 +41,9 @@ pub(crate) struct RawStackState {
     pub unapplied: Vec<PatchName>,
     pub hidden: Vec<PatchName>,
     pub patches: BTreeMap<PatchName, RawPatchState>,
+    pub labels: BTreeMap<PatchName, BTreeSet<String>>,
+    pub aliases: BTreeMap<PatchName, PatchName>,
+    pub command: Option<Vec<String>>,
 }
 
 /// Raw patch state representation.
@@ -28,8 +54,26 @@ pub(crate) struct RawPatchState {
 
 impl RawStackState {
     /// Deserialize stack state blob into [`RawStackState`] instance.
+    ///
+    /// The format version is checked before the rest of the stack state such that
+    /// stack states from newer versions of StGit result in a meaningful error instead
+    /// of an arbitrary deserialization error.
     pub(crate) fn from_stack_json(data: &[u8]) -> Result<Self> {
-        serde_json::from_slice(data).context("derserializing stack state")
+        #[derive(serde::Deserialize)]
+        struct FormatVersion {
+            pub version: i64,
+        }
+
+        let FormatVersion { version } =
+            serde_json::from_slice(data).context("deserializing stack state version")?;
+        if version > FORMAT_VERSION {
+            return Err(anyhow!(
+                "stack written by a newer StGit (format {version}); please upgrade"
+            ));
+        } else if version < MIN_FORMAT_VERSION {
+            return Err(anyhow!("unsupported stack state format version {version}"));
+        }
+        serde_json::from_slice(data).context("deserializing stack state")
     }
 }
 
@@ -50,6 +94,13 @@ impl<'de> serde::Deserialize<'de> for RawStackState {
             pub unapplied: Vec<PatchName>,
             pub hidden: Vec<PatchName>,
             pub patches: BTreeMap<PatchName, DeserPatchState>,
+            // Labels and aliases are absent from version 5 stack states.
+            #[serde(default)]
+            pub labels: BTreeMap<PatchName, BTreeSet<String>>,
+            #[serde(default)]
+            pub aliases: BTreeMap<PatchName, PatchName>,
+            #[serde(default)]
+            pub command: Option<Vec<String>>,
         }
 
         #[derive(serde::Deserialize)]
@@ -59,10 +110,10 @@ impl<'de> serde::Deserialize<'de> for RawStackState {
 
         let ds = DeserState::deserialize(deserializer)?;
 
-        if ds.version != 5 {
+        if !(MIN_FORMAT_VERSION..=FORMAT_VERSION).contains(&ds.version) {
             return Err(D::Error::invalid_value(
                 ::serde::de::Unexpected::Signed(ds.version),
-                &"5",
+                &"a supported format version",
             ));
         }
 
@@ -100,6 +151,9 @@ impl<'de> serde::Deserialize<'de> for RawStackState {
             unapplied: ds.unapplied,
             hidden: ds.hidden,
             patches,
+            labels: ds.labels,
+            aliases: ds.aliases,
+            command: ds.command,
         })
     }
 }
@@ -118,6 +172,12 @@ impl serde::Serialize for super::state::StackState<'_> {
             pub unapplied: &'a Vec<PatchName>,
             pub hidden: &'a Vec<PatchName>,
             pub patches: BTreeMap<&'a PatchName, SerializablePatchState>,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            pub labels: &'a BTreeMap<PatchName, BTreeSet<String>>,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            pub aliases: &'a BTreeMap<PatchName, PatchName>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub command: &'a Option<Vec<String>>,
         }
 
         #[derive(serde::Serialize)]
@@ -137,14 +197,23 @@ impl serde::Serialize for super::state::StackState<'_> {
             );
         }
 
+        let version = if self.labels.is_empty() && self.aliases.is_empty() {
+            MIN_FORMAT_VERSION
+        } else {
+            FORMAT_VERSION
+        };
+
         let ss = SerializableState {
-            version: 5,
+            version,
             prev,
             head,
             applied: &self.applied,
             unapplied: &self.unapplied,
             hidden: &self.hidden,
             patches,
+            labels: &self.labels,
+            aliases: &self.aliases,
+            command: &self.command,
         };
 
         ss.serialize(serializer)
@@ -2,19 +2,27 @@
 
 //! High-level StGit stack representation.
 
-use std::{collections::BTreeMap, rc::Rc, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    rc::Rc,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
 
 use super::{
     state::StackState, transaction::TransactionBuilder, upgrade::stack_upgrade, PatchState,
-    StackAccess, StackStateAccess,
+    StackAccess, StackConfig, StackStateAccess,
 };
 use crate::{
     branchloc::BranchLocator,
-    ext::RepositoryExtended,
+    ext::{ContextError, RepositoryExtended},
+    output::Output,
     patch::PatchName,
+    stupid::Stupid,
     wrap::{Branch, PartialRefName},
 };
 
@@ -31,6 +39,24 @@ pub(crate) struct Stack<'repo> {
     base: Rc<gix::Commit<'repo>>,
     state: StackState<'repo>,
     is_initialized: bool,
+    case_sensitive_names: bool,
+    abbreviated_names: bool,
+    missing_patches: Vec<(PatchName, gix::ObjectId)>,
+    recorded_command_line: bool,
+}
+
+/// Whether stacks may be read with patch commits missing from the repository.
+static ALLOW_MISSING_PATCH_COMMITS: AtomicBool = AtomicBool::new(false);
+
+/// Allow stacks read by this process to have patch commits missing from the repository.
+///
+/// By default, reading a stack whose state references patch commits that are missing
+/// from the repository fails with a [`super::state::MissingPatchCommits`] error.
+/// Commands that can do their work without those commits, e.g. `stg series` and `stg
+/// repair`, call this such that the patches with missing commits are instead left out
+/// of the stack and reported by [`Stack::missing_patches()`].
+pub(crate) fn allow_missing_patch_commits() {
+    ALLOW_MISSING_PATCH_COMMITS.store(true, Ordering::Relaxed);
 }
 
 /// Policy for stack initialization when opening/discovering a stack for a branch.
@@ -58,8 +84,10 @@ pub(crate) enum InitializationPolicy {
 impl<'repo> Stack<'repo> {
     /// Remove StGit stack state from the repository.
     ///
-    /// This removes the reference to the stack state, i.e. `refs/stacks/<name>`, and
-    /// references to the stacks patches found in `refs/patches/<name>/`. StGit specific
+    /// This removes the reference to the stack state, i.e. `refs/stacks/<name>`,
+    /// references to the stacks patches found in `refs/patches/<name>/`, any record
+    /// of conflicts in `refs/stgit/conflicts/<name>`, and any backup of discarded
+    /// changes in `refs/stgit/discarded/<name>`. StGit specific
     /// configuration associated with the stack is also removed from the config.
     ///
     /// N.B. stack and patch commits that become unreferenced are subject to git's
@@ -87,6 +115,9 @@ impl<'repo> Stack<'repo> {
             patch_reference.delete()?;
         }
         state_ref.delete()?;
+        super::conflicts::remove(repo, &branch_name)?;
+        super::discarded::remove(repo, &branch_name)?;
+        super::pending::remove(repo, &branch_name)?;
 
         // It is ok if the StGit-specific config section does not exist.
         let mut local_config_file = repo.local_config_file()?;
@@ -151,32 +182,26 @@ impl<'repo> Stack<'repo> {
 
         let maybe_state_ref = repo.find_reference(&stack_refname).ok();
 
-        let state_and_base_from_ref =
-            |state_ref: gix::Reference<'repo>| -> Result<(StackState<'repo>, Rc<gix::Commit<'repo>>)> {
-                let stack_tree = state_ref.id().object()?.try_into_commit()?.tree()?;
-                let state = StackState::from_tree(repo, stack_tree)?;
-                let base = if let Some(first_patchname) = state.applied.first() {
-                    Rc::new(
-                        repo.find_object(
-                            state.patches[first_patchname]
-                                .commit
-                                .parent_ids()
-                                .next()
-                                .unwrap(),
-                        )?
-                        .try_into_commit()?,
-                    )
-                } else {
-                    branch_head.clone()
-                };
-                Ok((state, base))
-            };
+        let allow_missing = ALLOW_MISSING_PATCH_COMMITS.load(Ordering::Relaxed);
+        let mut missing_patches = Vec::new();
+        let mut state_and_base_from_ref = |state_ref: gix::Reference<'repo>| {
+            state_and_base_from_ref(
+                repo,
+                state_ref,
+                &branch_head,
+                allow_missing.then_some(&mut missing_patches),
+            )
+        };
 
         let initialize_state_and_base =
             || -> Result<(StackState<'repo>, Rc<gix::Commit<'repo>>)> {
+                hint_default_branch_protection(
+                    &StackConfig::new(repo, Some(&branch_name)),
+                    &branch_name,
+                );
                 let state = StackState::new(branch_head.clone());
                 let base = branch_head.clone();
-                state.commit(repo, Some(&stack_refname), "initialize")?;
+                state.commit(repo, &branch_name, Some(&stack_refname), "initialize", None)?;
                 Ok((state, base))
             };
 
@@ -203,9 +228,8 @@ impl<'repo> Stack<'repo> {
                 initialize_state_and_base()?
             }
             InitializationPolicy::RequireInitialized => {
-                let state_ref = maybe_state_ref.ok_or_else(|| {
-                    anyhow!("StGit stack not initialized for branch `{branch_name}`")
-                })?;
+                let state_ref = maybe_state_ref
+                    .ok_or_else(|| ContextError::StackNotInitialized(branch_name.clone()))?;
                 is_initialized = true;
                 state_and_base_from_ref(state_ref)?
             }
@@ -222,7 +246,13 @@ impl<'repo> Stack<'repo> {
             }
         };
 
-        ensure_patch_refs(repo, &branch_name, &state)?;
+        let config = StackConfig::new(repo, Some(&branch_name));
+        let case_sensitive_names = config
+            .boolean("stgit.stack.casesensitivenames")
+            .unwrap_or(false);
+        let abbreviated_names = config.boolean("stgit.abbrevpatchnames").unwrap_or(true);
+
+        ensure_patch_refs(repo, &branch_name, &state, &missing_patches)?;
         Ok(Self {
             repo,
             branch_name,
@@ -232,19 +262,80 @@ impl<'repo> Stack<'repo> {
             base,
             state,
             is_initialized,
+            case_sensitive_names,
+            abbreviated_names,
+            missing_patches,
+            recorded_command_line: false,
         })
     }
 
-    /// Check whether the stack is marked as protected in the config.
-    pub(crate) fn is_protected(&self, config: &gix::config::Snapshot) -> bool {
-        config
-            .boolean_by(
-                "branch",
-                Some(format!("{}.stgit", self.branch_name).as_str().into()),
-                "protect",
-            )
-            .unwrap_or(Ok(false))
-            .unwrap_or(false)
+    /// Get a read-only stack from a fetched remote branch.
+    ///
+    /// The remote branch, e.g. `origin/feature`, must have been fetched to
+    /// `refs/remotes/<remote>/<branch>` and its stack state to
+    /// `refs/remote-stacks/<remote>/<branch>`, e.g. by fetching with the
+    /// `+refs/stacks/*:refs/remote-stacks/<remote>/*` refspec. The patches are
+    /// reconstructed from the stack state alone, so no local refs are needed or created.
+    ///
+    /// As with uninitialized stacks, transactions are prohibited on the returned stack.
+    pub(crate) fn from_remote_branch(
+        repo: &'repo gix::Repository,
+        remote_branch_name: &PartialRefName,
+    ) -> Result<Self> {
+        let branch_name = remote_branch_name.as_ref().to_string();
+        let reference = repo
+            .try_find_reference(format!("refs/remotes/{branch_name}").as_str())?
+            .ok_or_else(|| anyhow!("no such remote branch `{branch_name}`"))?;
+        let branch = Branch::wrap(reference);
+        let branch_head = Rc::new(branch.get_commit()?);
+        let stack_refname = remote_state_refname(&branch_name);
+        let state_ref = repo
+            .try_find_reference(stack_refname.as_str())?
+            .ok_or_else(|| {
+                anyhow!(
+                    "remote branch `{branch_name}` has no stack metadata fetched\n\
+                     fetch `refs/stacks/*` from the remote into `refs/remote-stacks/<remote>/*`"
+                )
+            })?;
+        let (state, base) = state_and_base_from_ref(repo, state_ref, &branch_head, None)?;
+        let config = StackConfig::new(repo, Some(&branch_name));
+        let case_sensitive_names = config
+            .boolean("stgit.stack.casesensitivenames")
+            .unwrap_or(false);
+        let abbreviated_names = config.boolean("stgit.abbrevpatchnames").unwrap_or(true);
+
+        Ok(Self {
+            repo,
+            branch_name,
+            branch,
+            branch_head,
+            stack_refname,
+            base,
+            state,
+            is_initialized: false,
+            case_sensitive_names,
+            abbreviated_names,
+            missing_patches: Vec::new(),
+            recorded_command_line: false,
+        })
+    }
+
+    /// Check whether the stack is protected.
+    ///
+    /// A `branch.<name>.stgit.protect` setting takes precedence. Otherwise, when
+    /// `stgit.protect-default-branches` is enabled, the stack is protected if its
+    /// branch is one of the default branches, see [`is_default_branch()`].
+    pub(crate) fn is_protected(&self) -> bool {
+        let config = self.config();
+        explicit_protection(config.snapshot(), &self.branch_name)
+            .unwrap_or_else(|| self.is_protected_by_default(&config))
+    }
+
+    /// Check whether the stack is protected without a `branch.<name>.stgit.protect`
+    /// setting.
+    fn is_protected_by_default(&self, config: &StackConfig) -> bool {
+        protect_default_branches(config) == Some(true)
+            && is_default_branch(config, &self.branch_name)
     }
 
     /// Set the stack's protected state in the config.
@@ -262,6 +353,14 @@ impl<'repo> Stack<'repo> {
                 "protect",
                 "true",
             )?;
+        } else if self.is_protected_by_default(&self.config()) {
+            // Removing the setting would leave the branch protected by default.
+            local_config_file.set_raw_value_by(
+                section,
+                Some(subsection.into()),
+                "protect",
+                "false",
+            )?;
         } else {
             if let Ok(mut value) =
                 local_config_file.raw_value_mut_by(section, Some(subsection.into()), "protect")
@@ -281,6 +380,102 @@ impl<'repo> Stack<'repo> {
         Ok(())
     }
 
+    /// Get the name of the branch linked to the stack, if any.
+    ///
+    /// A linked branch is force-updated to the stack's branch head whenever the stack
+    /// state is updated. The link is configured with `stgit.linkedbranch`, which, like
+    /// other StGit variables, may be set for the branch with
+    /// `branch.<name>.stgit.linkedbranch`. An empty value means there is no link, as
+    /// does a value naming the stack's own branch, e.g. a general `stgit.linkedbranch`
+    /// read by the stack of the linked branch itself.
+    pub(crate) fn linked_branch(&self) -> Option<String> {
+        self.config()
+            .string("stgit.linkedbranch")
+            .and_then(|bs| bs.to_str().ok().map(str::to_string))
+            .filter(|name| !name.is_empty() && name != &self.branch_name)
+    }
+
+    /// Set or clear the stack's linked branch in the config.
+    ///
+    /// The link is set with `branch.<name>.stgit.linkedbranch`. When clearing the link
+    /// while a general `stgit.linkedbranch` is set, the branch-specific value is set
+    /// empty to override the general value.
+    pub(crate) fn set_linked_branch(&self, linked_branch: Option<&PartialRefName>) -> Result<()> {
+        let section = "branch";
+        let subsection = format!("{}.stgit", self.branch_name);
+        let subsection = subsection.as_str();
+
+        let mut local_config_file = self.repo.local_config_file()?;
+
+        if let Some(linked_branch) = linked_branch {
+            local_config_file.set_raw_value_by(
+                section,
+                Some(subsection.into()),
+                "linkedbranch",
+                linked_branch.as_ref(),
+            )?;
+        } else if self
+            .config()
+            .snapshot()
+            .string("stgit.linkedbranch")
+            .is_some_and(|name| !name.is_empty())
+        {
+            local_config_file.set_raw_value_by(
+                section,
+                Some(subsection.into()),
+                "linkedbranch",
+                "",
+            )?;
+        } else {
+            if let Ok(mut value) =
+                local_config_file.raw_value_mut_by(section, Some(subsection.into()), "linkedbranch")
+            {
+                value.delete();
+            }
+            if let Ok(section) = local_config_file
+                .section_by_key(format!("{section}.{subsection}").as_bytes().as_bstr())
+            {
+                if section.num_values() == 0 {
+                    local_config_file.remove_section_by_id(section.id());
+                }
+            }
+        }
+
+        self.repo.write_local_config(local_config_file)?;
+        Ok(())
+    }
+
+    /// Make a reference edit force-updating the stack's linked branch, if any, to
+    /// `head_id`.
+    pub(super) fn linked_branch_edit(
+        &self,
+        head_id: gix::ObjectId,
+        log: gix::refs::transaction::LogChange,
+    ) -> Result<Option<gix::refs::transaction::RefEdit>> {
+        let Some(linked_branch) = self.linked_branch() else {
+            return Ok(None);
+        };
+        let name = gix::refs::FullName::try_from(format!("refs/heads/{linked_branch}"))
+            .with_context(|| format!("invalid linked branch name `{linked_branch}`"))?;
+        Ok(Some(gix::refs::transaction::RefEdit {
+            change: gix::refs::transaction::Change::Update {
+                log,
+                expected: gix::refs::transaction::PreviousValue::Any,
+                new: gix::refs::Target::Object(head_id),
+            },
+            name,
+            deref: false,
+        }))
+    }
+
+    /// Get the patches left out of the stack because their commits are missing.
+    ///
+    /// The patch names are paired with the ids of their missing commits. This is
+    /// always empty unless [`allow_missing_patch_commits()`] was called.
+    pub(crate) fn missing_patches(&self) -> &[(PatchName, gix::ObjectId)] {
+        &self.missing_patches
+    }
+
     /// Check whether the stack's recorded head matches the branch's head.
     pub(crate) fn is_head_top(&self) -> bool {
         self.state.head.id() == self.branch_head.id()
@@ -299,6 +494,84 @@ impl<'repo> Stack<'repo> {
         }
     }
 
+    /// Get the patch owning the unresolved conflicts left behind by a conflicting push.
+    ///
+    /// A conflicting push leaves the conflicted patch as the topmost applied patch,
+    /// with the conflicts recorded against the branch head. The patch is only
+    /// considered conflicted while the index still has unmerged entries.
+    pub(crate) fn conflicted_patch(&self) -> Result<Option<&PatchName>> {
+        let Some(top_patchname) = self.state.applied.last() else {
+            return Ok(None);
+        };
+        if !self.is_head_top()
+            || super::conflicts::load(self.repo, &self.branch_name, self.branch_head.id)?.is_none()
+            || self.repo.stupid().ls_files_unmerged()?.is_empty()
+        {
+            return Ok(None);
+        }
+        Ok(Some(top_patchname))
+    }
+
+    /// Return an error if the patch owning unresolved conflicts is affected.
+    ///
+    /// The predicate determines whether the conflicted patch is affected by the
+    /// operation about to be performed.
+    pub(crate) fn check_conflicted_patch_filter<F>(&self, predicate: F) -> Result<()>
+    where
+        F: Fn(&PatchName) -> bool,
+    {
+        match self.conflicted_patch()? {
+            Some(patchname) if predicate(patchname) => {
+                if self.pending_push()?.is_some() {
+                    Err(anyhow!(
+                        "patch `{patchname}` has unresolved conflicts; resolve them and run \
+                         `stg refresh` or `stg push --continue`, or run `stg undo --hard` \
+                         or `stg push --abort` to abandon the push"
+                    ))
+                } else {
+                    Err(anyhow!(
+                        "patch `{patchname}` has unresolved conflicts; resolve them and run \
+                         `stg refresh`, or run `stg undo --hard` to abandon the push"
+                    ))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Return an error if a conflicting push left unresolved conflicts.
+    pub(crate) fn check_conflicted_patch(&self) -> Result<()> {
+        self.check_conflicted_patch_filter(|_| true)
+    }
+
+    /// Get the push that halted on conflicts and is yet to be continued or aborted.
+    ///
+    /// A recorded push is only considered pending while its conflicted patch remains
+    /// the topmost applied patch and the branch head is still the commit from when the
+    /// push halted.
+    pub(crate) fn pending_push(&self) -> Result<Option<super::pending::PendingPush>> {
+        let Some(top_patchname) = self.state.applied.last() else {
+            return Ok(None);
+        };
+        Ok(
+            super::pending::load(self.repo, &self.branch_name)?.filter(|pending| {
+                &pending.patchname == top_patchname && pending.head_id == self.branch_head.id
+            }),
+        )
+    }
+
+    /// Return an error if a push that halted on conflicts is still pending.
+    pub(crate) fn check_pending_push(&self) -> Result<()> {
+        match self.pending_push()? {
+            Some(pending) => Err(anyhow!(
+                "a push halted on conflicts in patch `{}` is in progress; run \
+                 `stg push --continue` to resume it or `stg push --abort` to abandon it",
+                pending.patchname
+            )),
+            None => Ok(()),
+        }
+    }
+
     /// Re-commit stack state with updated branch head.
     pub(crate) fn log_external_mods(self, message: Option<&str>) -> Result<Self> {
         assert!(
@@ -311,26 +584,26 @@ impl<'repo> Stack<'repo> {
             .find_reference(&self.stack_refname)?
             .peel_to_commit()?;
         let prev_state_commit_id = prev_state_commit.id;
-        let state = self
-            .state
-            .advance_head(self.branch_head.clone(), Rc::new(prev_state_commit));
-
         let message = message.unwrap_or(
             "external modifications\n\
              \n\
              Modifications by tools other than StGit (e.g. git).\n",
         );
-        let reflog_msg = "external modifications";
+        let log = gix::refs::transaction::LogChange {
+            mode: gix::refs::transaction::RefLog::AndReference,
+            force_create_reflog: false,
+            message: super::message::reflog(message).into(),
+        };
+        let linked_branch_edit = self.linked_branch_edit(self.branch_head.id, log.clone())?;
+        let state = self
+            .state
+            .advance_head(self.branch_head.clone(), Rc::new(prev_state_commit));
 
-        let state_commit_id = state.commit(self.repo, None, message)?;
+        let state_commit_id = state.commit(self.repo, &self.branch_name, None, message, None)?;
 
-        self.repo.edit_reference(gix::refs::transaction::RefEdit {
+        let mut ref_edits = vec![gix::refs::transaction::RefEdit {
             change: gix::refs::transaction::Change::Update {
-                log: gix::refs::transaction::LogChange {
-                    mode: gix::refs::transaction::RefLog::AndReference,
-                    force_create_reflog: false,
-                    message: reflog_msg.into(),
-                },
+                log,
                 expected: gix::refs::transaction::PreviousValue::ExistingMustMatch(
                     gix::refs::Target::Object(prev_state_commit_id),
                 ),
@@ -338,7 +611,9 @@ impl<'repo> Stack<'repo> {
             },
             name: gix::refs::FullName::try_from(self.stack_refname.as_str())?,
             deref: false,
-        })?;
+        }];
+        ref_edits.extend(linked_branch_edit);
+        self.repo.edit_references(ref_edits)?;
 
         Ok(Self { state, ..self })
     }
@@ -352,11 +627,25 @@ impl<'repo> Stack<'repo> {
         TransactionBuilder::new(self)
     }
 
+    /// Ensure the stack's branch is not checked out in another worktree.
+    ///
+    /// See [`Branch::check_not_checked_out_elsewhere()`].
+    pub(crate) fn check_not_checked_out_elsewhere(&self) -> Result<()> {
+        self.branch.check_not_checked_out_elsewhere()
+    }
+
     /// Clear the stack state history.
     pub(crate) fn clear_state_log(&mut self, reflog_msg: &str) -> Result<()> {
+        self.check_not_checked_out_elsewhere()?;
         self.state.prev = None;
-        self.state
-            .commit(self.repo, Some(&self.stack_refname), reflog_msg)?;
+        self.state.command = None;
+        self.state.commit(
+            self.repo,
+            &self.branch_name,
+            Some(&self.stack_refname),
+            reflog_msg,
+            None,
+        )?;
         Ok(())
     }
 
@@ -371,6 +660,23 @@ impl<'repo> Stack<'repo> {
         &mut self.state
     }
 
+    /// Get the command line to record in the next stack state.
+    ///
+    /// A command's command line is only recorded in the first stack state produced by
+    /// its transactions; subsequent states, e.g. the intermediate states of `stg
+    /// refresh`, do not record it.
+    pub(super) fn take_command_line(
+        &mut self,
+        command_line: Option<&[String]>,
+    ) -> Option<Vec<String>> {
+        if self.recorded_command_line {
+            None
+        } else {
+            self.recorded_command_line = command_line.is_some();
+            command_line.map(<[String]>::to_vec)
+        }
+    }
+
     /// Get reference name for a patch.
     pub(super) fn patch_refname(&self, patchname: &PatchName) -> String {
         self.patch_revspec(patchname.as_ref())
@@ -423,10 +729,36 @@ impl<'repo> StackStateAccess<'repo> for Stack<'repo> {
         self.state.get_patch(patchname)
     }
 
+    fn labels(&self) -> &BTreeMap<PatchName, BTreeSet<String>> {
+        self.state.labels()
+    }
+
+    fn aliases(&self) -> &BTreeMap<PatchName, PatchName> {
+        self.state.aliases()
+    }
+
     fn has_patch(&self, patchname: &PatchName) -> bool {
         self.state.has_patch(patchname)
     }
 
+    /// Test whether given patch name collides with an existing patch name.
+    ///
+    /// Unless `stgit.stack.casesensitivenames` is set, patch names that differ only by
+    /// case are considered to collide, regardless of the filesystem's case
+    /// sensitivity.
+    fn collides(&self, patchname: &PatchName) -> Option<&PatchName> {
+        if self.case_sensitive_names {
+            self.all_patches().find(|pn| *pn == patchname)
+        } else {
+            self.all_patches().find(|pn| patchname.collides(pn))
+        }
+    }
+
+    /// Unless `stgit.abbrevpatchnames` is set to false, patch names may be abbreviated.
+    fn allow_abbreviated_names(&self) -> bool {
+        self.abbreviated_names
+    }
+
     fn top(&self) -> &Rc<gix::Commit<'repo>> {
         self.state.top()
     }
@@ -441,6 +773,180 @@ pub(crate) fn state_refname_from_branch_name(branch_name: &str) -> String {
     format!("refs/stacks/{branch_name}")
 }
 
+/// Get the references holding StGit metadata for the given branch name.
+///
+/// These are the stack state reference, the patch references, and any conflicts record,
+/// pending push record, or backup of discarded changes. The references are found
+/// regardless of whether the branch itself exists.
+pub(crate) fn metadata_references(
+    repo: &gix::Repository,
+    branch_name: &str,
+) -> Result<Vec<gix::refs::Reference>> {
+    let mut references = Vec::new();
+    for refname in [
+        state_refname_from_branch_name(branch_name),
+        super::conflicts::conflicts_refname(branch_name),
+        super::discarded::discarded_refname(branch_name),
+        super::pending::pending_refname(branch_name),
+    ] {
+        references.extend(
+            repo.try_find_reference(refname.as_str())?
+                .map(gix::Reference::detach),
+        );
+    }
+    let patch_ref_prefix = get_patch_refname(branch_name, "");
+    let platform = repo.references()?;
+    references.extend(
+        platform
+            .prefixed(patch_ref_prefix.as_str())?
+            .filter_map(Result::ok)
+            .map(gix::Reference::detach),
+    );
+    Ok(references)
+}
+
+/// Read the stack state from a stack state reference and determine the stack base.
+///
+/// If `missing` is provided, patches whose commits are missing from the repository
+/// are left out of the state and recorded in `missing` instead of failing.
+fn state_and_base_from_ref<'repo>(
+    repo: &'repo gix::Repository,
+    state_ref: gix::Reference<'repo>,
+    branch_head: &Rc<gix::Commit<'repo>>,
+    missing: Option<&mut Vec<(PatchName, gix::ObjectId)>>,
+) -> Result<(StackState<'repo>, Rc<gix::Commit<'repo>>)> {
+    let stack_tree = state_ref.id().object()?.try_into_commit()?.tree()?;
+    let state = if let Some(missing) = missing {
+        let (state, missing_patches) = StackState::from_tree_allow_missing(repo, stack_tree)?;
+        *missing = missing_patches;
+        state
+    } else {
+        StackState::from_tree(repo, stack_tree)?
+    };
+    let base = if let Some(first_patchname) = state.applied.first() {
+        Rc::new(
+            repo.find_object(
+                state.patches[first_patchname]
+                    .commit
+                    .parent_ids()
+                    .next()
+                    .unwrap(),
+            )?
+            .try_into_commit()?,
+        )
+    } else {
+        branch_head.clone()
+    };
+    Ok((state, base))
+}
+
+/// Get reference name for the fetched StGit stack state of a remote branch.
+fn remote_state_refname(remote_branch_name: &str) -> String {
+    format!("refs/remote-stacks/{remote_branch_name}")
+}
+
+/// Get the branch's `branch.<name>.stgit.protect` setting, if any.
+fn explicit_protection(config: &gix::config::Snapshot, branch_name: &str) -> Option<bool> {
+    config
+        .boolean_by(
+            "branch",
+            Some(format!("{branch_name}.stgit").as_str().into()),
+            "protect",
+        )
+        .and_then(Result::ok)
+}
+
+/// Get the `stgit.protect-default-branches` setting, if any.
+fn protect_default_branches(config: &StackConfig) -> Option<bool> {
+    config.boolean("stgit.protect-default-branches")
+}
+
+/// Determine whether the branch is one of the repository's default branches.
+///
+/// The default branches are given by the glob patterns of the multi-valued
+/// `stgit.protected-branch-patterns` setting, where each value may hold several
+/// whitespace-separated patterns. Without the setting, the default branches are
+/// `main`, `master`, and the branch named by `init.defaultBranch`, if set.
+fn is_default_branch(config: &StackConfig, branch_name: &str) -> bool {
+    use gix::glob::wildmatch;
+    let patterns: Vec<String> =
+        if let Some(values) = config.strings("stgit.protected-branch-patterns") {
+            values
+                .iter()
+                .flat_map(|value| {
+                    value
+                        .to_str_lossy()
+                        .split_whitespace()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        } else {
+            config
+                .string("init.defaultbranch")
+                .map(|name| name.to_str_lossy().into_owned())
+                .into_iter()
+                .chain(["main".to_string(), "master".to_string()])
+                .collect()
+        };
+    patterns.iter().any(|pattern| {
+        wildmatch(
+            pattern.as_str().into(),
+            branch_name.into(),
+            wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+        )
+    })
+}
+
+/// Explain how to protect default branches when initializing a stack on one.
+///
+/// The hint is shown whenever a stack is initialized on a default branch while
+/// `stgit.protect-default-branches` is not set, so at most once per branch. Setting it
+/// to either value silences the hint.
+fn hint_default_branch_protection(config: &StackConfig, branch_name: &str) {
+    if protect_default_branches(config).is_none() && is_default_branch(config, branch_name) {
+        let _ = writeln!(
+            Output::current().commentary(),
+            "hint: Initializing a stack on the default branch `{branch_name}`.\n\
+             hint: To protect default branches from `stg pull`, `stg rebase`, and other\n\
+             hint: modifications unless unprotected with `stg branch --unprotect`, run:\n\
+             hint:\n\
+             hint: \tgit config stgit.protect-default-branches true\n\
+             hint:\n\
+             hint: Set it to `false` to disable this message."
+        );
+    }
+}
+
+/// Determine whether patch refs are stored on a case-insensitive filesystem.
+///
+/// Git records the filesystem's case sensitivity in `core.ignorecase` when the
+/// repository is created.
+pub(crate) fn has_case_insensitive_refs(repo: &gix::Repository) -> bool {
+    repo.config_snapshot()
+        .boolean("core.ignorecase")
+        .unwrap_or(false)
+}
+
+/// Get lowercased names of patches whose refs would be shared with another patch.
+///
+/// On a case-insensitive filesystem, `refs/patches/<branch>/Fix-IO` and
+/// `refs/patches/<branch>/fix-io` are the same file. The refs for such patches cannot
+/// be maintained independently, so they are left alone.
+pub(super) fn shared_patch_refs<'a>(
+    repo: &gix::Repository,
+    patchnames: impl IntoIterator<Item = &'a PatchName>,
+) -> BTreeSet<String> {
+    if has_case_insensitive_refs(repo) {
+        PatchName::case_collisions(patchnames)
+            .into_iter()
+            .map(|(patchname, _)| patchname.to_string().to_ascii_lowercase())
+            .collect()
+    } else {
+        BTreeSet::new()
+    }
+}
+
 /// Get reference name for a patch in the given branch.
 fn get_patch_refname(branch_name: &str, patch_spec: &str) -> String {
     format!("refs/patches/{branch_name}/{patch_spec}")
@@ -453,10 +959,27 @@ fn get_patch_refname(branch_name: &str, patch_spec: &str) -> String {
 ///
 /// This is done when instantiating a [`Stack`] to guard against external modifications
 /// to the stack's patch refs.
-fn ensure_patch_refs(repo: &gix::Repository, branch_name: &str, state: &StackState) -> Result<()> {
+fn ensure_patch_refs(
+    repo: &gix::Repository,
+    branch_name: &str,
+    state: &StackState,
+    missing_patches: &[(PatchName, gix::ObjectId)],
+) -> Result<()> {
     let patch_ref_prefix = get_patch_refname(branch_name, "");
     let mut state_patches: BTreeMap<&PatchName, &PatchState> = state.patches.iter().collect();
 
+    let shared_refs = shared_patch_refs(repo, state.all_patches());
+    if !shared_refs.is_empty() {
+        for (patchname0, patchname1) in PatchName::case_collisions(state.all_patches()) {
+            Output::current().warning(&format!(
+                "patches `{patchname0}` and `{patchname1}` differ only by case and \
+                 cannot have distinct patch refs on this filesystem"
+            ));
+            state_patches.remove(patchname0);
+            state_patches.remove(patchname1);
+        }
+    }
+
     for mut existing_ref in repo
         .references()?
         .all()?
@@ -472,8 +995,15 @@ fn ensure_patch_refs(repo: &gix::Repository, branch_name: &str, state: &StackSta
             let patchname_str = existing_refname
                 .strip_prefix(&patch_ref_prefix)
                 .expect("did starts_with above");
-            if let Ok(existing_patchname) = PatchName::from_str(patchname_str) {
-                if let Some(patchdesc) = state_patches.remove(&existing_patchname) {
+            if shared_refs.contains(&patchname_str.to_ascii_lowercase()) {
+                // Ref is shared by patches differing only by case. Leave it be.
+            } else if let Ok(existing_patchname) = PatchName::from_str(patchname_str) {
+                if missing_patches
+                    .iter()
+                    .any(|(patchname, _)| patchname == &existing_patchname)
+                {
+                    // The patch's commit is missing. Keep its ref until it is removed.
+                } else if let Some(patchdesc) = state_patches.remove(&existing_patchname) {
                     if let Some(existing_id) = existing_ref.target().try_id() {
                         if existing_id == patchdesc.commit.id {
                             // Patch ref is good. Do nothing.
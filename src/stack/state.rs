@@ -5,12 +5,16 @@
 //! This stack state representation is serialized to/from the `stack.json` blob
 //! in the stack state tree.
 
-use std::{collections::BTreeMap, rc::Rc, str};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+    str,
+};
 
 use anyhow::{anyhow, Result};
 use bstr::{BString, ByteVec};
 
-use super::{access::StackStateAccess, iter::AllPatches, serde::RawStackState};
+use super::{access::StackStateAccess, iter::AllPatches, serde::RawStackState, StackConfig};
 use crate::{
     ext::{CommitExtended, CommitOptions, RepositoryExtended},
     patch::PatchName,
@@ -45,6 +49,51 @@ pub(crate) struct StackState<'repo> {
 
     /// Mapping of patch names to their state.
     pub(super) patches: BTreeMap<PatchName, PatchState<'repo>>,
+
+    /// Mapping of patch names to their labels.
+    pub(super) labels: BTreeMap<PatchName, BTreeSet<String>>,
+
+    /// Mapping of former patch names to the patches they were renamed to.
+    pub(super) aliases: BTreeMap<PatchName, PatchName>,
+
+    /// Command line of the StGit command that produced this stack state.
+    ///
+    /// Only recorded for the first state produced by a command's stack transactions;
+    /// subsequent states produced by the same command, e.g. the intermediate states
+    /// of `stg refresh`, do not record the command line. The command line excludes
+    /// the executable and any top-level options, e.g. `["push", "-a"]`.
+    pub(crate) command: Option<Vec<String>>,
+}
+
+/// Error for patch commits of a stack state that are missing from the repository.
+///
+/// Patch commits may go missing if, for example, they are removed by garbage
+/// collection in a clone whose stack metadata references were not preserved.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "{}\n\
+     {} may have been removed by `git gc`; fetch {} from another repository, e.g. with \
+     `git fetch <remote> <commit>`, or remove the {} from the stack with `stg repair`",
+    describe_missing(.0),
+    if .0.len() == 1 { "the commit" } else { "the commits" },
+    if .0.len() == 1 { "it" } else { "them" },
+    if .0.len() == 1 { "patch" } else { "patches" },
+)]
+pub(crate) struct MissingPatchCommits(pub(crate) Vec<(PatchName, gix::ObjectId)>);
+
+fn describe_missing(missing: &[(PatchName, gix::ObjectId)]) -> String {
+    if let [(patchname, commit_id)] = missing {
+        format!("commit `{commit_id}` of patch `{patchname}` is missing from the repository")
+    } else {
+        let mut description = format!(
+            "the commits of {} patches are missing from the repository:",
+            missing.len()
+        );
+        for (patchname, commit_id) in missing {
+            description.push_str(&format!("\n  `{patchname}`: `{commit_id}`"));
+        }
+        description
+    }
 }
 
 /// State associated with a patch.
@@ -72,6 +121,14 @@ impl<'repo> StackStateAccess<'repo> for StackState<'repo> {
         &self.patches[patchname]
     }
 
+    fn labels(&self) -> &BTreeMap<PatchName, BTreeSet<String>> {
+        &self.labels
+    }
+
+    fn aliases(&self) -> &BTreeMap<PatchName, PatchName> {
+        &self.aliases
+    }
+
     fn has_patch(&self, patchname: &PatchName) -> bool {
         self.patches.contains_key(patchname)
     }
@@ -103,6 +160,9 @@ impl<'repo> StackState<'repo> {
             unapplied: vec![],
             hidden: vec![],
             patches: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            command: None,
         }
     }
 
@@ -114,14 +174,47 @@ impl<'repo> StackState<'repo> {
         Self::from_tree(repo, commit.tree()?)
     }
 
+    /// Read the names of all patches in the stack state of the given state commit.
+    ///
+    /// Unlike [`StackState::from_commit()`], patches whose commits are missing from the
+    /// repository are included.
+    pub(crate) fn patchnames_from_commit(
+        repo: &'repo gix::Repository,
+        commit: &gix::Commit<'repo>,
+    ) -> Result<Vec<PatchName>> {
+        let (state, missing) = Self::from_tree_allow_missing(repo, commit.tree()?)?;
+        let mut patchnames: Vec<PatchName> = state.all_patches().cloned().collect();
+        patchnames.extend(missing.into_iter().map(|(patchname, _)| patchname));
+        Ok(patchnames)
+    }
+
     /// Read and parse stack state from given stack state tree.
+    ///
+    /// A [`MissingPatchCommits`] error is returned if any patch commits are missing
+    /// from the repository.
     pub(super) fn from_tree(repo: &'repo gix::Repository, tree: gix::Tree<'repo>) -> Result<Self> {
+        let (state, missing) = Self::from_tree_allow_missing(repo, tree)?;
+        if missing.is_empty() {
+            Ok(state)
+        } else {
+            Err(MissingPatchCommits(missing).into())
+        }
+    }
+
+    /// Read and parse stack state from given stack state tree, tolerating missing
+    /// patch commits.
+    ///
+    /// See [`StackState::from_raw_state_allow_missing()`].
+    pub(super) fn from_tree_allow_missing(
+        repo: &'repo gix::Repository,
+        tree: gix::Tree<'repo>,
+    ) -> Result<(Self, Vec<(PatchName, gix::ObjectId)>)> {
         let mut tree = tree;
         let stack_json = tree.peel_to_entry_by_path("stack.json")?;
         if let Some(stack_json) = stack_json {
             let stack_json_data = stack_json.object()?.try_into_blob()?.take_data();
             let raw_state = RawStackState::from_stack_json(&stack_json_data)?;
-            Self::from_raw_state(repo, raw_state)
+            Self::from_raw_state_allow_missing(repo, raw_state)
         } else {
             Err(anyhow!("stack metadata not found"))
         }
@@ -131,33 +224,90 @@ impl<'repo> StackState<'repo> {
     ///
     /// Commit objects are looked-up from commit ids in the raw state. This may
     /// fail if the raw state references commit ids not present in the
-    /// repository.
+    /// repository. Missing patch commits are reported with a
+    /// [`MissingPatchCommits`] error.
     pub(super) fn from_raw_state(
         repo: &'repo gix::Repository,
         raw_state: RawStackState,
     ) -> Result<Self> {
+        let (state, missing) = Self::from_raw_state_allow_missing(repo, raw_state)?;
+        if missing.is_empty() {
+            Ok(state)
+        } else {
+            Err(MissingPatchCommits(missing).into())
+        }
+    }
+
+    /// Convert [`RawStackState`] to [`StackState`], leaving out patches whose commits
+    /// are missing from the repository.
+    ///
+    /// The names and commit ids of the patches left out are returned along with the
+    /// state. Missing head and previous state commits are still errors.
+    pub(super) fn from_raw_state_allow_missing(
+        repo: &'repo gix::Repository,
+        raw_state: RawStackState,
+    ) -> Result<(Self, Vec<(PatchName, gix::ObjectId)>)> {
         let mut patches = BTreeMap::new();
+        let mut missing = Vec::new();
         for (patchname, raw_state) in raw_state.patches {
-            let commit = repo.find_object(raw_state.oid)?.try_into_commit()?;
-            patches.insert(
-                patchname,
-                PatchState {
-                    commit: Rc::new(commit),
-                },
-            );
+            if let Some(object) = repo.try_find_object(raw_state.oid)? {
+                patches.insert(
+                    patchname,
+                    PatchState {
+                        commit: Rc::new(object.try_into_commit()?),
+                    },
+                );
+            } else {
+                missing.push((patchname, raw_state.oid));
+            }
         }
-        Ok(Self {
+        let is_present = |patchname: &PatchName| patches.contains_key(patchname);
+        let mut applied = raw_state.applied;
+        let mut unapplied = raw_state.unapplied;
+        let mut hidden = raw_state.hidden;
+        let mut labels = raw_state.labels;
+        let mut aliases = raw_state.aliases;
+        if !missing.is_empty() {
+            applied.retain(is_present);
+            unapplied.retain(is_present);
+            hidden.retain(is_present);
+            labels.retain(|patchname, _| is_present(patchname));
+            aliases.retain(|_, patchname| is_present(patchname));
+        }
+        let state = Self {
             prev: if let Some(prev_id) = raw_state.prev {
-                Some(Rc::new(repo.find_object(prev_id)?.try_into_commit()?))
+                Some(Rc::new(
+                    repo.try_find_object(prev_id)?
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "previous stack state commit `{prev_id}` is missing from the \
+                                 repository"
+                            )
+                        })?
+                        .try_into_commit()?,
+                ))
             } else {
                 None
             },
-            head: Rc::new(repo.find_object(raw_state.head)?.try_into_commit()?),
-            applied: raw_state.applied,
-            unapplied: raw_state.unapplied,
-            hidden: raw_state.hidden,
+            head: Rc::new(
+                repo.try_find_object(raw_state.head)?
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "stack head commit `{}` is missing from the repository",
+                            raw_state.head
+                        )
+                    })?
+                    .try_into_commit()?,
+            ),
+            applied,
+            unapplied,
+            hidden,
             patches,
-        })
+            labels,
+            aliases,
+            command: raw_state.command,
+        };
+        Ok((state, missing))
     }
 
     /// Iterator over all patches.
@@ -183,6 +333,7 @@ impl<'repo> StackState<'repo> {
         Self {
             prev: Some(prev_state),
             head: new_head,
+            command: None,
             ..self
         }
     }
@@ -195,22 +346,34 @@ impl<'repo> StackState<'repo> {
     /// commit objects have parent commits with tree content of the associated
     /// branch in addition to a "regular" parent commit from the stack state
     /// branch.
+    ///
+    /// The stack state commit is signed per the `stgit.gpgsign` configuration of the
+    /// stack's branch, `branch_name`. Its author and committer dates are `fixed_date`,
+    /// if given.
     pub(crate) fn commit(
         &self,
         repo: &'repo gix::Repository,
+        branch_name: &str,
         update_ref: Option<&str>,
         message: &str,
+        fixed_date: Option<gix::date::Time>,
     ) -> Result<gix::ObjectId> {
         let (state_tree_id, prev_state) = if let Some(prev_commit) = self.prev.as_ref() {
-            let prev_state = Self::from_tree(repo, prev_commit.tree()?)?;
+            // The previous state is only consulted for what is unchanged, so any of its
+            // patch commits that have gone missing are of no concern.
+            let (prev_state, _) = Self::from_tree_allow_missing(repo, prev_commit.tree()?)?;
             let state_tree_id = self.make_tree(repo, Some((&prev_state, prev_commit.tree()?)))?;
             (state_tree_id, Some(prev_state))
         } else {
             (self.make_tree(repo, None)?, None)
         };
-        let config = repo.config_snapshot();
-        let committer = repo.get_committer()?;
-        let author = repo.get_author()?;
+        let config = StackConfig::new(repo, Some(branch_name));
+        let mut committer = repo.get_committer()?;
+        let mut author = repo.get_author()?;
+        if let Some(date) = fixed_date {
+            committer.time = date;
+            author.time = date;
+        }
 
         let simplified_parents: Vec<gix::ObjectId> = match &self.prev {
             Some(prev_commit) => {
@@ -223,11 +386,13 @@ impl<'repo> StackState<'repo> {
             None => vec![],
         };
 
+        let reflog_msg = super::message::reflog(message);
         let message = Message::from(message);
 
         let commit_opts = CommitOptions {
             commit_encoding: None,
             gpgsign: config.boolean("stgit.gpgsign").unwrap_or(false),
+            extra_headers: vec![],
         };
 
         let simplified_parent_id = repo.commit_with_options(
@@ -289,7 +454,7 @@ impl<'repo> StackState<'repo> {
                 refname,
                 commit_oid,
                 gix::refs::transaction::PreviousValue::Any,
-                message.raw_bytes(),
+                reflog_msg,
             )?;
         }
 
@@ -1,20 +1,25 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, time::Duration};
 
 use anyhow::Result;
 
 use super::{
-    options::{ConflictMode, TransactionOptions},
+    mtime::MtimeSnapshot,
+    options::{ConflictMode, MergedCheck, TransactionOptions},
+    stats::{self, TransactionStats},
     ui::TransactionUserInterface,
     ExecuteContext, StackTransaction,
 };
-use crate::stack::{Stack, StackAccess, StackStateAccess};
+use crate::{
+    output::Output,
+    stack::{Stack, StackAccess, StackStateAccess},
+};
 
 /// Builder used to setup a stack transaction.
 pub(crate) struct TransactionBuilder<'repo> {
     stack: Stack<'repo>,
-    output: Option<termcolor::StandardStream>,
+    output: Option<Output>,
     options: TransactionOptions,
 }
 
@@ -83,6 +88,17 @@ impl<'repo> TransactionBuilder<'repo> {
         self
     }
 
+    /// Keep local changes to the index and work tree across the transaction.
+    ///
+    /// The transaction only proceeds if none of the files changed by its checkouts
+    /// have local changes; otherwise it fails listing the overlapping files. Callers
+    /// keeping local changes are expected to skip their own clean worktree check.
+    #[must_use]
+    pub(crate) fn keep_local_changes(mut self, keep: bool) -> Self {
+        self.options.keep_local_changes = keep;
+        self
+    }
+
     /// Allow the stack transaction operations modify index and/or work tree state. This
     /// primarily affects operations that cause patches to be pushed. When use of the
     /// index and worktree is disallowed (the default), all pushes must apply cleanly
@@ -93,13 +109,99 @@ impl<'repo> TransactionBuilder<'repo> {
         self
     }
 
-    /// Set the output stream for the transaction. This method must be called.
+    /// Allow checkouts to overwrite untracked work tree files that are not ignored.
+    /// Ignored untracked files are always overwritten. By default, the transaction
+    /// will fail if a checkout would overwrite an untracked file that is not ignored.
+    #[must_use]
+    pub(crate) fn force_overwrite_untracked(mut self, force: bool) -> Self {
+        self.options.force_overwrite_untracked = force;
+        self
+    }
+
+    /// Restore the modification times of work tree files whose content is the same
+    /// after the transaction's checkouts as before. This is best-effort; files whose
+    /// modification times cannot be read or set are skipped.
+    #[must_use]
+    pub(crate) fn restore_mtime(mut self, restore: bool) -> Self {
+        self.options.restore_mtime = restore;
+        self
+    }
+
+    /// Print a summary of how the transaction changed the stack: the resulting top
+    /// patch, the changes to the number of applied and unapplied patches, and the
+    /// patches created and removed.
+    ///
+    /// The summary is only printed when opted into with `stgit.stacksummary`.
+    #[must_use]
+    pub(crate) fn print_delta(mut self, yes: bool) -> Self {
+        self.options.print_delta = yes
+            && self
+                .stack
+                .config()
+                .boolean("stgit.stacksummary")
+                .unwrap_or(false);
+        self
+    }
+
+    /// Perform the transaction's operations without applying their outcome.
+    ///
+    /// In a dry run, patches are pushed using in-memory merges only and, instead of
+    /// updating any references, the index, or the worktree, the outcome the
+    /// transaction would have is reported.
+    #[must_use]
+    pub(crate) fn dry_run(mut self, yes: bool) -> Self {
+        self.options.dry_run = yes;
+        self
+    }
+
+    /// Predict the outcome of every push in a dry run.
+    ///
+    /// Implies a dry run. Rather than stopping at the first patch that would conflict,
+    /// the conflicting patch is pushed with conflict markers left in its tree such
+    /// that the patches above it are also predicted. A table of the predicted outcomes
+    /// is reported in place of the pushed patches and execution fails with a halt
+    /// error if any conflicts are predicted.
+    #[must_use]
+    pub(crate) fn predict_pushes(mut self, yes: bool) -> Self {
+        self.options.predict_pushes = yes;
+        self.options.dry_run |= yes;
+        self
+    }
+
+    /// Set the command line of the command running the transaction.
+    ///
+    /// The command line is recorded in the first stack state the stack's transactions
+    /// produce, such that the command may be replayed with `stg replay`. It begins with
+    /// the subcommand name, e.g. `["push", "-a"]`.
+    #[must_use]
+    pub(crate) fn command_line(mut self, command_line: &[String]) -> Self {
+        self.options.command_line = Some(command_line.to_vec());
+        self
+    }
+
+    /// Set the output context for the transaction. This method must be called.
+    ///
+    /// The transaction's output is commentary, and is thus suppressed when quiet. When
+    /// verbose, per-patch push timings and a summary of the transaction's pushes are
+    /// also reported.
     #[must_use]
-    pub(crate) fn with_output_stream(mut self, output: termcolor::StandardStream) -> Self {
+    pub(crate) fn with_output(mut self, output: Output) -> Self {
+        self.options.verbose = output.is_verbose();
         self.output = Some(output);
         self
     }
 
+    /// Record the time taken by the command's worktree status check.
+    ///
+    /// The status check is performed by the command before the transaction is set up.
+    /// Its duration is reported along with the transaction's timings when verbose or
+    /// with `STG_PERF=1`.
+    #[must_use]
+    pub(crate) fn status_check_time(mut self, elapsed: Duration) -> Self {
+        self.options.status_check = Some(elapsed);
+        self
+    }
+
     /// Determines whether the branch and stack metadata refs should be updated when the
     /// transaction executes successfully. This is the default. Disabling this is only
     /// useful in very special circumstances (e.g. for `stg uncommit`).
@@ -117,6 +219,48 @@ impl<'repo> TransactionBuilder<'repo> {
         self
     }
 
+    /// Use the given date, if any, as the committer date of every commit created.
+    ///
+    /// The date is also the author date of the stack state commit.
+    #[must_use]
+    pub(crate) fn fixed_date(mut self, date: Option<gix::date::Time>) -> Self {
+        self.options.fixed_date = date;
+        self
+    }
+
+    /// Determines whether the patches' existing committer identity and date are kept,
+    /// instead of using the current committer, when pushing patches.
+    #[must_use]
+    pub(crate) fn keep_committer(mut self, yes: bool) -> Self {
+        self.options.keep_committer = yes;
+        self
+    }
+
+    /// Determines whether the author date will be reset to the current time when
+    /// pushing patches.
+    #[must_use]
+    pub(crate) fn ignore_date(mut self, yes: bool) -> Self {
+        self.options.ignore_date = yes;
+        self
+    }
+
+    /// Refine how patches merged upstream are detected when patches are pushed with
+    /// `check_merged`.
+    #[must_use]
+    pub(crate) fn merged_check(mut self, merged_check: MergedCheck) -> Self {
+        self.options.merged_check = merged_check;
+        self
+    }
+
+    /// Set the length of the conflict markers left by merges when pushing patches.
+    ///
+    /// Git's default marker size is used if no size is given.
+    #[must_use]
+    pub(crate) fn conflict_marker_size(mut self, size: Option<usize>) -> Self {
+        self.options.conflict_marker_size = size;
+        self
+    }
+
     /// Perform stack transaction operations.
     ///
     /// The closure provided to this method may call various methods on the provided
@@ -131,7 +275,7 @@ impl<'repo> TransactionBuilder<'repo> {
     #[must_use]
     pub(crate) fn transact<F>(self, f: F) -> ExecuteContext<'repo>
     where
-        F: FnOnce(&mut StackTransaction) -> Result<()>,
+        F: FnOnce(&mut StackTransaction<'repo>) -> Result<()>,
     {
         let Self {
             stack,
@@ -139,8 +283,17 @@ impl<'repo> TransactionBuilder<'repo> {
             options,
         } = self;
 
-        let ui =
-            TransactionUserInterface::new(output.expect("with_output_stream() must be called"));
+        let mtimes =
+            (options.restore_mtime && options.use_index_and_worktree && !options.discard_changes)
+                .then(MtimeSnapshot::default);
+        let dump_json = stats::perf_env_enabled();
+        let stats = (options.verbose || dump_json)
+            .then(|| TransactionStats::new(options.status_check, dump_json));
+        let predictions = options.predict_pushes.then(Vec::new);
+        let ui = TransactionUserInterface::new(
+            output.expect("with_output() must be called").commentary(),
+            stack.config().boolean("stgit.quotenames").unwrap_or(true),
+        );
 
         let current_tree_id = stack
             .get_branch_head()
@@ -150,6 +303,8 @@ impl<'repo> TransactionBuilder<'repo> {
         let applied = stack.applied().to_vec();
         let unapplied = stack.unapplied().to_vec();
         let hidden = stack.hidden().to_vec();
+        let labels = stack.labels().clone();
+        let aliases = stack.aliases().clone();
 
         let mut transaction = StackTransaction {
             stack,
@@ -159,13 +314,32 @@ impl<'repo> TransactionBuilder<'repo> {
             unapplied,
             hidden,
             updated_patches: BTreeMap::new(),
+            labels,
+            aliases,
             updated_head: None,
             updated_base: None,
             current_tree_id,
             error: None,
+            stats,
+            predictions,
+            mtimes,
+            hold_off: crate::signal::hold_off(),
         };
 
-        transaction.error = f(&mut transaction).err();
+        // A branch checked out in another worktree must not be modified; check before
+        // any changes are made, or reported, by the transaction operations.
+        let checked = if transaction.options.dry_run {
+            Ok(())
+        } else {
+            transaction.stack.check_not_checked_out_elsewhere()
+        };
+        let reported = checked.and_then(|()| match transaction.options.status_check {
+            Some(status_check) if transaction.options.verbose => {
+                transaction.ui.print_status_check(status_check)
+            }
+            _ => Ok(()),
+        });
+        transaction.error = reported.and_then(|()| f(&mut transaction)).err();
 
         ExecuteContext(transaction)
     }
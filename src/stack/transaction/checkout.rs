@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Check out trees such that a failed checkout does not leave a partially updated
+//! work tree behind.
+//!
+//! `git read-tree -m -u` first verifies that the checkout can be performed and then
+//! writes the changed files one by one. Should writing fail midway, e.g. due to a full
+//! disk, a permission error, or a failing smudge filter, the index is left unchanged,
+//! but the files written up to that point are not rolled back. Such files would
+//! otherwise show up as local changes once the stack transaction rolls back the stack
+//! state.
+//!
+//! No bookkeeping is needed when the checkout succeeds. After a failure, the files
+//! changed between the two trees are inspected: files whose content is that of the new
+//! tree, and files of the old tree that were removed, were written by the checkout and
+//! are restored from the unchanged index. Files that were added are removed again.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use crate::stupid::Stupid;
+
+/// Check out `new_tree_id` from `old_tree_id`, restoring the work tree on failure.
+///
+/// If the checkout fails, the original error is returned once the work tree is
+/// restored. Any paths that could not be restored are listed in the error.
+pub(super) fn read_tree_checkout(
+    repo: &gix::Repository,
+    old_tree_id: gix::ObjectId,
+    new_tree_id: gix::ObjectId,
+) -> Result<()> {
+    let stupid = repo.stupid();
+    let Err(checkout_err) = stupid.read_tree_checkout(old_tree_id, new_tree_id) else {
+        return Ok(());
+    };
+    let Some(work_dir) = repo.work_dir() else {
+        return Err(checkout_err);
+    };
+
+    let restore_result = find_written(repo, work_dir, old_tree_id, new_tree_id)
+        .and_then(|written| restore(repo, work_dir, &written));
+    match restore_result {
+        Ok(unrestored) if unrestored.is_empty() => Err(checkout_err),
+        Ok(unrestored) => {
+            let mut msg =
+                "the checkout failed midway and these paths could not be restored:".to_string();
+            for path in unrestored {
+                msg.push_str(&format!("\n  {}", path.display()));
+            }
+            Err(anyhow!("{msg}\n{checkout_err:#}"))
+        }
+        Err(restore_err) => Err(anyhow!(
+            "the checkout failed midway and the work tree could not be restored: \
+             {restore_err:#}\n\
+             {checkout_err:#}"
+        )),
+    }
+}
+
+/// A path written by a failed checkout.
+struct Written {
+    path: PathBuf,
+
+    /// Blob id of the path in the old tree, if the path was a blob in that tree.
+    old_blob: Option<gix::ObjectId>,
+}
+
+/// Find the paths of the work tree written by a failed checkout.
+fn find_written(
+    repo: &gix::Repository,
+    work_dir: &Path,
+    old_tree_id: gix::ObjectId,
+    new_tree_id: gix::ObjectId,
+) -> Result<Vec<Written>> {
+    let raw_diff = repo.stupid().diff_tree_raw(&[(old_tree_id, new_tree_id)])?;
+    let mut written = Vec::new();
+    let mut candidates = Vec::new();
+    for entry in raw_diff.iter() {
+        let Ok(path) = entry.path.to_path() else {
+            continue;
+        };
+        let exists = work_dir.join(path).symlink_metadata().is_ok();
+        match (entry.old_blob, entry.new_blob) {
+            // Files of the old tree are unlinked before the new content is written.
+            (Some(old_blob), _) if !exists => written.push(Written {
+                path: path.to_owned(),
+                old_blob: Some(old_blob),
+            }),
+            (old_blob, Some(new_blob)) if exists => {
+                candidates.push((path.to_owned(), old_blob, new_blob));
+            }
+            _ => {}
+        }
+    }
+
+    let blob_ids = hash_paths(repo, work_dir, candidates.iter().map(|(path, ..)| path))?;
+    for ((path, old_blob, new_blob), blob_id) in candidates.into_iter().zip(blob_ids) {
+        if blob_id == Some(new_blob) {
+            written.push(Written { path, old_blob });
+        }
+    }
+    Ok(written)
+}
+
+/// Restore written paths to their content in the index, which is that of the old tree.
+///
+/// The paths that are not restored afterwards are returned.
+fn restore(repo: &gix::Repository, work_dir: &Path, written: &[Written]) -> Result<Vec<PathBuf>> {
+    let mut unrestored = Vec::new();
+    for Written { path, .. } in written.iter().filter(|w| w.old_blob.is_none()) {
+        let full_path = work_dir.join(path);
+        if std::fs::remove_file(&full_path).is_err() {
+            unrestored.push(path.clone());
+            continue;
+        }
+        // Remove any leading directories created by the checkout.
+        for dir in full_path.ancestors().skip(1) {
+            if dir == work_dir || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+
+    let mut to_checkout: Vec<&Written> = written.iter().filter(|w| w.old_blob.is_some()).collect();
+    if !to_checkout.is_empty() {
+        // `git checkout-index` stops at the first path it fails to write. The paths
+        // not restored by checking out all at once are thus retried one by one.
+        let stupid = repo.stupid();
+        if stupid
+            .checkout_index_force(to_checkout.iter().map(|w| w.path.as_path()))
+            .is_err()
+        {
+            let blob_ids = hash_paths(repo, work_dir, to_checkout.iter().map(|w| &w.path))?;
+            to_checkout = to_checkout
+                .into_iter()
+                .zip(blob_ids)
+                .filter_map(|(w, blob_id)| (blob_id != w.old_blob).then_some(w))
+                .collect();
+            for w in &to_checkout {
+                let _ = stupid.checkout_index_force([w.path.as_path()]);
+            }
+        }
+        let blob_ids = hash_paths(repo, work_dir, to_checkout.iter().map(|w| &w.path))?;
+        for (w, blob_id) in to_checkout.into_iter().zip(blob_ids) {
+            if blob_id != w.old_blob {
+                unrestored.push(w.path.clone());
+            }
+        }
+    }
+    unrestored.sort();
+    Ok(unrestored)
+}
+
+/// Compute blob ids of work tree paths, or `None` for paths that do not exist.
+///
+/// Symbolic links and paths that cannot be passed to `git hash-object` are hashed
+/// without applying any filters.
+fn hash_paths<'a>(
+    repo: &gix::Repository,
+    work_dir: &Path,
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+) -> Result<Vec<Option<gix::ObjectId>>> {
+    let mut blob_ids = Vec::new();
+    let mut filtered = Vec::new();
+    for path in paths {
+        let full_path = work_dir.join(path);
+        let blob_id = match full_path.symlink_metadata() {
+            Err(_) => None,
+            Ok(meta) if meta.is_symlink() => {
+                let target = std::fs::read_link(&full_path)?;
+                Some(hash_blob(repo, gix::path::into_bstr(target).as_ref()))
+            }
+            Ok(meta) if !meta.is_file() => None,
+            Ok(_) if gix::path::into_bstr(path.as_path()).contains(&b'\n') => {
+                Some(hash_blob(repo, &std::fs::read(&full_path)?))
+            }
+            Ok(_) => {
+                filtered.push((blob_ids.len(), path.as_path()));
+                None
+            }
+        };
+        blob_ids.push(blob_id);
+    }
+    if !filtered.is_empty() {
+        let filtered_ids = repo
+            .stupid()
+            .hash_object_paths(filtered.iter().map(|(_, path)| *path))?;
+        for ((i, _), blob_id) in filtered.into_iter().zip(filtered_ids) {
+            blob_ids[i] = Some(blob_id);
+        }
+    }
+    Ok(blob_ids)
+}
+
+fn hash_blob(repo: &gix::Repository, data: &[u8]) -> gix::ObjectId {
+    gix::objs::compute_hash(repo.object_hash(), gix::objs::Kind::Blob, data)
+}
@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Summary of how a stack transaction changed the shape of the stack.
+
+use crate::patch::PatchName;
+
+/// Changes made by a stack transaction to the stack's patches.
+pub(super) struct StackDelta {
+    /// Topmost applied patch after the transaction, if any.
+    pub(super) top: Option<PatchName>,
+
+    /// Number of applied patches before and after the transaction.
+    pub(super) applied: (usize, usize),
+
+    /// Number of unapplied patches before and after the transaction.
+    pub(super) unapplied: (usize, usize),
+
+    /// Patches that did not exist before the transaction.
+    pub(super) created: Vec<PatchName>,
+
+    /// Patches that no longer exist after the transaction.
+    pub(super) removed: Vec<PatchName>,
+}
@@ -17,7 +17,7 @@
 //! ```no_run
 //! let new_stack = stack
 //!     .setup_transaction()
-//!     .with_output_stream(...)
+//!     .with_output(...)
 //!     ...  // Transaction option method calls
 //!     .transact(|trans| {
 //!         // Call StackTransaction methods
@@ -27,25 +27,40 @@
 //! ```
 
 mod builder;
+mod checkout;
+mod delta;
+mod mtime;
 mod options;
+mod predict;
+mod stats;
 mod ui;
 
-use std::{collections::BTreeMap, rc::Rc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use indexmap::IndexSet;
 
-pub(crate) use self::builder::TransactionBuilder;
+pub(crate) use self::{builder::TransactionBuilder, options::MergedCheck};
 use self::{
+    delta::StackDelta,
+    mtime::MtimeSnapshot,
     options::{ConflictMode, TransactionOptions},
+    predict::PushPrediction,
+    stats::{timed, PushStats, TransactionStats},
     ui::TransactionUserInterface,
 };
-use super::{state::StackState, StackAccess};
+use super::{conflicts, stack::shared_patch_refs, state::StackState, StackAccess};
 use crate::{
     ext::{CommitExtended, RepositoryExtended},
     patch::PatchName,
     stack::{PatchState, Stack, StackStateAccess},
-    stupid::{Stupid, StupidContext},
+    stupid::{MergeOptions, Stupid, StupidContext},
     wrap::Branch,
 };
 
@@ -56,6 +71,9 @@ pub(crate) enum Error {
 
     #[error("{msg}")]
     TransactionHalt { msg: String, conflicts: bool },
+
+    #[error("{0}")]
+    Interrupted(String),
 }
 
 /// Stack transaction state.
@@ -68,11 +86,17 @@ pub(crate) struct StackTransaction<'repo> {
     unapplied: Vec<PatchName>,
     hidden: Vec<PatchName>,
     updated_patches: BTreeMap<PatchName, Option<PatchState<'repo>>>,
+    labels: BTreeMap<PatchName, BTreeSet<String>>,
+    aliases: BTreeMap<PatchName, PatchName>,
     updated_head: Option<Rc<gix::Commit<'repo>>>,
     updated_base: Option<Rc<gix::Commit<'repo>>>,
 
     current_tree_id: gix::ObjectId,
     error: Option<anyhow::Error>,
+    stats: Option<TransactionStats>,
+    predictions: Option<Vec<PushPrediction>>,
+    mtimes: Option<MtimeSnapshot>,
+    hold_off: crate::signal::HoldOff,
 }
 
 /// Status of a pushed patch.
@@ -140,8 +164,14 @@ impl<'repo> ExecuteContext<'repo> {
             unapplied,
             hidden,
             updated_patches,
+            labels,
+            aliases,
             current_tree_id,
             error,
+            mut stats,
+            predictions,
+            mut mtimes,
+            hold_off: _hold_off,
             ..
         } = transaction;
 
@@ -153,12 +183,60 @@ impl<'repo> ExecuteContext<'repo> {
         let has_conflicts = if let Some(err) = &error {
             match err.downcast_ref::<Error>() {
                 Some(Error::TransactionHalt { conflicts, .. }) => *conflicts,
+                Some(Error::Interrupted(_)) => false,
                 _ => return Err(error.unwrap()),
             }
         } else {
             false
         };
 
+        let delta = (options.print_delta || options.dry_run).then(|| StackDelta {
+            top: trans_top_patchname.clone(),
+            applied: (stack.applied().len(), applied.len()),
+            unapplied: (stack.unapplied().len(), unapplied.len()),
+            created: updated_patches
+                .iter()
+                .filter(|(pn, patch)| patch.is_some() && !stack.has_patch(pn))
+                .map(|(pn, _)| pn.clone())
+                .collect(),
+            removed: updated_patches
+                .iter()
+                .filter(|(_, patch)| patch.is_none())
+                .map(|(pn, _)| pn.clone())
+                .collect(),
+        });
+
+        if options.dry_run {
+            if let Some(predictions) = predictions.as_ref() {
+                ui.print_predictions(predictions)?;
+            } else if !ui.printed_top() {
+                if let Some(top_patchname) = trans_top_patchname.as_ref() {
+                    ui.print_top(top_patchname)?;
+                }
+            }
+            if let Some(err) = error.as_ref() {
+                if has_conflicts {
+                    ui.print_dry_run_conflicts(trans_top_patchname.as_ref())?;
+                } else {
+                    ui.print_dry_run_halt(err)?;
+                }
+            }
+            if let Some(delta) = delta.as_ref() {
+                ui.print_delta(delta, true)?;
+            }
+            ui.print_dry_run()?;
+            let conflict_count = predictions.as_deref().map_or(0, predict::count_conflicts);
+            if conflict_count > 0 {
+                let plural = if conflict_count == 1 { "" } else { "es" };
+                return Err(Error::TransactionHalt {
+                    msg: format!("{conflict_count} patch{plural} predicted to conflict"),
+                    conflicts: false,
+                }
+                .into());
+            }
+            return Ok(stack);
+        }
+
         // Log external modifications
         let mut stack = if stack.is_head_top() {
             stack
@@ -169,10 +247,11 @@ impl<'repo> ExecuteContext<'repo> {
         // Roll back by checking out the stack top tree prior to any changes from this
         // stack transaction. The stack state reference must only be updated after all
         // possible rollback points are passed successfully.
-        let rollback = |old_tree_id, err| -> anyhow::Error {
+        let rollback = |old_tree_id, err: anyhow::Error| -> anyhow::Error {
             if let Err(checkout_err) = checkout(
                 repo,
                 &options,
+                &ui,
                 stack_top_patchname.as_ref(),
                 trans_top_patchname.as_ref(),
                 old_tree_id,
@@ -183,6 +262,11 @@ impl<'repo> ExecuteContext<'repo> {
             if let Err(print_err) = ui.print_rolled_back(stack_top_patchname.as_ref()) {
                 return print_err;
             }
+            let err = if crate::signal::interrupted() {
+                err.context("interrupted by user")
+            } else {
+                err
+            };
             anyhow!(
                 "{err:#};\n\
                  command aborted (all changes rolled back)"
@@ -193,30 +277,88 @@ impl<'repo> ExecuteContext<'repo> {
             if !options.allow_bad_head {
                 stack.check_head_top_mismatch()?;
             }
+            if let Some(mtimes) = mtimes.as_mut() {
+                mtimes
+                    .record(repo, current_tree_id, trans_head_tree_id)
+                    .map_err(|e| rollback(current_tree_id, e))?;
+            }
+            if options.keep_local_changes && !options.discard_changes {
+                check_local_changes(
+                    repo,
+                    current_tree_id,
+                    &[(current_tree_id, trans_head_tree_id)],
+                )
+                .map_err(|e| {
+                    rollback(
+                        current_tree_id,
+                        Error::CheckoutConflicts(format!("{e:#}")).into(),
+                    )
+                })?;
+            }
+            let checkout_start = stats.as_ref().map(|_| Instant::now());
             checkout(
                 repo,
                 &options,
+                &ui,
                 stack_top_patchname.as_ref(),
                 trans_top_patchname.as_ref(),
                 current_tree_id,
                 trans_head_tree_id,
             )
             .map_err(|e| rollback(current_tree_id, e))?;
+            if let (Some(stats), Some(start)) = (stats.as_mut(), checkout_start) {
+                stats.add_checkout(start.elapsed());
+            }
         }
 
-        crate::signal::critical(|| {
+        // Patches whose commits were replaced are reported to the `post-rewrite` hook
+        // once the updated stack state is committed. Rewrites that only replaced commits
+        // in-place, e.g. by refreshing, are amends, whereas reparenting is a rebase.
+        let rewrites: Vec<_> = applied
+            .iter()
+            .chain(unapplied.iter())
+            .chain(hidden.iter())
+            .filter_map(|patchname| {
+                let new_commit = &updated_patches.get(patchname)?.as_ref()?.commit;
+                stack
+                    .has_patch(patchname)
+                    .then(|| stack.get_patch_commit(patchname))
+                    .filter(|old_commit| old_commit.id != new_commit.id)
+                    .map(|old_commit| (old_commit.clone(), new_commit.clone()))
+            })
+            .collect();
+        let rewrite_kind = if rewrites.iter().all(|(old_commit, new_commit)| {
+            old_commit
+                .parent_ids()
+                .map(gix::Id::detach)
+                .eq(new_commit.parent_ids().map(gix::Id::detach))
+        }) {
+            "amend"
+        } else {
+            "rebase"
+        };
+        let rewrites: Vec<_> = rewrites
+            .iter()
+            .map(|(old_commit, new_commit)| (old_commit.id, new_commit.id))
+            .collect();
+
+        let conflict_msg;
+        let state_reflog_msg = if has_conflicts {
+            conflict_msg = format!("{reflog_msg} (CONFLICT)");
+            &conflict_msg
+        } else {
+            reflog_msg
+        };
+
+        let (prev_state_id, state_id) = crate::signal::critical(|| {
             // Commit updated stack state
-            let conflict_msg;
-            let state_reflog_msg = if has_conflicts {
-                conflict_msg = format!("{reflog_msg} (CONFLICT)");
-                &conflict_msg
-            } else {
-                reflog_msg
-            };
             let branch_ref_name = stack.get_branch_refname().to_owned();
+            let branch_name = stack.get_branch_name().to_string();
             let prev_state_commit = repo
                 .find_reference(stack.get_stack_refname())?
                 .peel_to_commit()?;
+            let prev_state_commit_id = prev_state_commit.id;
+            let command_line = stack.take_command_line(options.command_line.as_deref());
             let state = stack.state_mut();
             for (patchname, maybe_patch) in &updated_patches {
                 if let Some(patch) = maybe_patch {
@@ -230,7 +372,23 @@ impl<'repo> ExecuteContext<'repo> {
             state.applied = applied;
             state.unapplied = unapplied;
             state.hidden = hidden;
-            let state_commit_id = state.commit(repo, None, state_reflog_msg)?;
+            state.labels = labels;
+            state
+                .labels
+                .retain(|patchname, _| state.patches.contains_key(patchname));
+            state.command = command_line;
+            state.aliases = aliases;
+            state.aliases.retain(|alias, patchname| {
+                !state.patches.contains_key(alias) && state.patches.contains_key(patchname)
+            });
+            let state_commit_id = state.commit(
+                repo,
+                &branch_name,
+                None,
+                state_reflog_msg,
+                options.fixed_date,
+            )?;
+            let shared_refs = shared_patch_refs(repo, state.all_patches());
 
             // Update various refs as a single transaction. This reference transaction is
             // not quite atomic--it is possible for some, but not all references to be
@@ -239,9 +397,12 @@ impl<'repo> ExecuteContext<'repo> {
             let log = gix::refs::transaction::LogChange {
                 mode: gix::refs::transaction::RefLog::AndReference,
                 force_create_reflog: false,
-                message: state_reflog_msg.into(),
+                message: super::message::reflog(state_reflog_msg).into(),
             };
             for (patchname, maybe_patch) in &updated_patches {
+                if shared_refs.contains(&patchname.to_string().to_ascii_lowercase()) {
+                    continue;
+                }
                 let change = if let Some(patch) = maybe_patch {
                     gix::refs::transaction::Change::Update {
                         log: log.clone(),
@@ -280,6 +441,7 @@ impl<'repo> ExecuteContext<'repo> {
             });
 
             if options.set_head {
+                ref_edits.extend(stack.linked_branch_edit(trans_head.id, log.clone())?);
                 ref_edits.push(gix::refs::transaction::RefEdit {
                     change: gix::refs::transaction::Change::Update {
                         log,
@@ -293,6 +455,10 @@ impl<'repo> ExecuteContext<'repo> {
 
             repo.edit_references(ref_edits)?;
 
+            if has_conflicts && options.use_index_and_worktree {
+                conflicts::record(repo, stack.get_branch_name(), trans_head.id)?;
+            }
+
             if options.set_head {
                 stack.update_head(
                     Branch::wrap(repo.find_reference(&branch_ref_name)?),
@@ -300,19 +466,79 @@ impl<'repo> ExecuteContext<'repo> {
                 );
             }
 
-            Ok(())
+            Ok((prev_state_commit_id, state_commit_id))
         })
         .map_err(|e| rollback(trans_head_tree_id, e))?;
 
+        if let Some(mtimes) = mtimes.as_ref().filter(|_| !has_conflicts) {
+            mtimes.restore(repo, trans_head_tree_id)?;
+        }
+
+        // The stack state is already committed, so failing to run the hook must not
+        // fail the transaction.
+        if let Err(e) = crate::hook::run_post_rewrite_hook(repo, rewrite_kind, &rewrites) {
+            crate::output::Output::current().warning(&format!("{e:#}"));
+        }
+        crate::hook::run_notify_command(
+            repo,
+            stack.get_branch_name(),
+            options
+                .command_line
+                .as_ref()
+                .and_then(|command_line| command_line.first())
+                .map(String::as_str),
+            state_reflog_msg,
+            prev_state_id,
+            state_id,
+        );
+
+        if error.is_none() && !ui.printed_top() {
+            if let Some(top_patchname) = trans_top_patchname.as_ref() {
+                ui.print_top(top_patchname)?;
+            }
+        }
+
+        if let Some(delta) = delta.as_ref() {
+            if error.is_none() {
+                ui.print_delta(delta, false)?;
+            }
+        }
+
+        if let Some(stats) = stats.as_ref() {
+            if options.verbose {
+                ui.print_summary(stats)?;
+            }
+            if stats.dump_json() {
+                eprintln!("{}", stats.to_json());
+            }
+        }
+
+        // The interrupted transaction has either been committed up to its last
+        // consistent point or rolled back, so report where that leaves the stack.
+        let interrupted = error
+            .as_ref()
+            .and_then(|err| err.downcast_ref::<Error>())
+            .is_some_and(|err| matches!(err, Error::Interrupted(_)))
+            || (error.is_none() && crate::signal::interrupted());
+        let error = if interrupted {
+            let position = if let Some(top_patchname) = trans_top_patchname.as_ref() {
+                format!(
+                    "the stack is now at `{top_patchname}` with {} applied and {} \
+                     unapplied patches",
+                    stack.applied().len(),
+                    stack.unapplied().len(),
+                )
+            } else {
+                "no patches are applied".to_string()
+            };
+            Some(Error::Interrupted(format!("interrupted by user; {position}")).into())
+        } else {
+            error
+        };
+
         if let Some(err) = error {
             Err(err)
         } else {
-            if !ui.printed_top() {
-                if let Some(top_patchname) = trans_top_patchname.as_ref() {
-                    ui.print_top(top_patchname)?;
-                }
-            }
-
             Ok(stack)
         }
     }
@@ -321,6 +547,7 @@ impl<'repo> ExecuteContext<'repo> {
 fn checkout(
     repo: &gix::Repository,
     options: &TransactionOptions,
+    ui: &TransactionUserInterface,
     stack_top: Option<&PatchName>,
     trans_top: Option<&PatchName>,
     current_tree_id: gix::ObjectId,
@@ -342,25 +569,261 @@ fn checkout(
         stupid.read_tree_checkout_hard(tree_id)?;
     } else {
         stupid.update_index_refresh()?;
-        stupid
-            .read_tree_checkout(current_tree_id, tree_id)
+        move_overwritten_untracked(repo, options, current_tree_id, tree_id)
+            .and_then(|moved| {
+                moved.checkout(ui, || {
+                    checkout::read_tree_checkout(repo, current_tree_id, tree_id)
+                })
+            })
             .map_err(|e| Error::CheckoutConflicts(format!("{e:#}")))?;
     }
 
     Ok(())
 }
 
+/// Ensure that no file changed by the given tree transitions has local changes.
+///
+/// Local changes are the differences of the index and work tree from the checked-out
+/// `local_tree_id`. Such changes may only be kept when they are disjoint from the
+/// files changed by each `(from, to)` tree transition.
+fn check_local_changes(
+    repo: &gix::Repository,
+    local_tree_id: gix::ObjectId,
+    transitions: &[(gix::ObjectId, gix::ObjectId)],
+) -> Result<()> {
+    let stupid = repo.stupid();
+    stupid.update_index_refresh()?;
+    let local_names = stupid.diff_index_names(local_tree_id, None)?;
+    let local_paths: BTreeSet<&Path> = local_names
+        .split_str(b"\0")
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| name.to_path().ok())
+        .collect();
+    if local_paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut overlapping: BTreeSet<std::path::PathBuf> = BTreeSet::new();
+    for (from, to) in transitions.iter().filter(|(from, to)| from != to) {
+        let changed = stupid.diff_tree_files(*from, *to)?;
+        overlapping.extend(
+            changed
+                .iter()
+                .filter(|path| local_paths.contains(path))
+                .map(Path::to_path_buf),
+        );
+    }
+
+    if overlapping.is_empty() {
+        Ok(())
+    } else {
+        let mut msg = "local changes to the following files would be overwritten:".to_string();
+        for path in &overlapping {
+            msg.push_str("\n  ");
+            msg.push_str(&path.to_string_lossy());
+        }
+        msg.push_str("\nrefresh, stash, or revert these changes first");
+        Err(anyhow!(msg))
+    }
+}
+
+/// Untracked work tree files moved aside so that a checkout may overwrite them.
+///
+/// The files are kept in a temporary directory within `$GIT_DIR` until the checkout
+/// overwriting them is known to succeed. Should the checkout fail, the files are moved
+/// back into the work tree.
+struct MovedUntracked {
+    work_dir: std::path::PathBuf,
+    backup_dir: Option<tempfile::TempDir>,
+    paths: Vec<(std::path::PathBuf, bool)>,
+}
+
+impl MovedUntracked {
+    /// Run `checkout`, which may overwrite the moved files.
+    ///
+    /// On success, the overwritten files are reported and their backups are deleted. On
+    /// failure, the moved files are restored before the error is returned.
+    fn checkout<T>(
+        self,
+        ui: &TransactionUserInterface,
+        checkout: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        match checkout() {
+            Ok(value) => {
+                for (path, is_ignored) in &self.paths {
+                    ui.print_overwritten_untracked(path, *is_ignored)?;
+                }
+                Ok(value)
+            }
+            Err(e) => match self.restore() {
+                Ok(()) => Err(e),
+                Err(restore_err) => Err(anyhow!("{e:#}\n{restore_err:#}")),
+            },
+        }
+    }
+
+    /// Move the backed up files back to their original work tree locations.
+    ///
+    /// The backup directory is kept if any file could not be restored.
+    fn restore(self) -> Result<()> {
+        let Some(backup_dir) = self.backup_dir else {
+            return Ok(());
+        };
+        let mut unrestored = Vec::new();
+        for (i, (path, _)) in self.paths.iter().enumerate() {
+            let dest = self.work_dir.join(path);
+            let restored = dest
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| {
+                    if dest.symlink_metadata().is_ok() {
+                        std::fs::remove_file(&dest)?;
+                    }
+                    std::fs::rename(backup_dir.path().join(i.to_string()), &dest)
+                });
+            if restored.is_err() {
+                unrestored.push((i, path));
+            }
+        }
+        if unrestored.is_empty() {
+            Ok(())
+        } else {
+            let backup_path = backup_dir.into_path();
+            let mut msg = "failed to restore untracked files:".to_string();
+            for (i, path) in unrestored {
+                msg.push_str(&format!(
+                    "\n  {} (saved as {})",
+                    path.display(),
+                    backup_path.join(i.to_string()).display()
+                ));
+            }
+            Err(anyhow!(msg))
+        }
+    }
+}
+
+/// Move aside untracked work tree files that would be overwritten by checking out `tree_id`.
+///
+/// Untracked files that are ignored, as determined by the `.gitignore` files in the
+/// work tree, `$GIT_DIR/info/exclude`, and `core.excludesFile`, are always moved.
+/// Other untracked files are only moved when overwriting untracked files is forced;
+/// otherwise an error is returned and no files are moved.
+///
+/// The moved files are only deleted once [`MovedUntracked::checkout()`] succeeds.
+fn move_overwritten_untracked(
+    repo: &gix::Repository,
+    options: &TransactionOptions,
+    current_tree_id: gix::ObjectId,
+    tree_id: gix::ObjectId,
+) -> Result<MovedUntracked> {
+    let Some(work_dir) = repo.work_dir() else {
+        return Ok(MovedUntracked {
+            work_dir: std::path::PathBuf::new(),
+            backup_dir: None,
+            paths: Vec::new(),
+        });
+    };
+    let mut moved = MovedUntracked {
+        work_dir: work_dir.to_path_buf(),
+        backup_dir: None,
+        paths: Vec::new(),
+    };
+    let added_files = repo
+        .stupid()
+        .diff_tree_added_files(current_tree_id, tree_id)?;
+    let candidates: Vec<&Path> = added_files
+        .iter()
+        .filter(|path| {
+            work_dir
+                .join(path)
+                .symlink_metadata()
+                .is_ok_and(|meta| !meta.is_dir())
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Ok(moved);
+    }
+
+    let index = repo.index_or_empty()?;
+    let mut excludes = repo.excludes(
+        &index,
+        None,
+        gix::worktree::stack::state::ignore::Source::WorktreeThenIdMappingIfNotSkipped,
+    )?;
+    let mut overwrites = Vec::new();
+    let mut blocking = Vec::new();
+    for path in candidates {
+        let path_bytes = gix::path::into_bstr(path);
+        if index.entry_by_path(path_bytes.as_ref()).is_some() {
+            continue;
+        }
+        let is_ignored = excludes
+            .at_entry(path_bytes.as_ref(), Some(gix::index::entry::Mode::FILE))?
+            .is_excluded();
+        if !is_ignored {
+            blocking.push(path);
+        }
+        overwrites.push((path, is_ignored));
+    }
+
+    if !blocking.is_empty() && !options.force_overwrite_untracked {
+        let mut msg = "untracked working tree files would be overwritten:".to_string();
+        for path in blocking {
+            msg.push_str(&format!("\n  {}", path.display()));
+        }
+        msg.push_str("\nmove or remove them, or use `--force-overwrite-untracked`");
+        return Err(anyhow!(msg));
+    }
+    if overwrites.is_empty() {
+        return Ok(moved);
+    }
+
+    let backup_dir = tempfile::Builder::new()
+        .prefix("stgit-untracked-")
+        .tempdir_in(repo.path())?;
+    for (i, (path, is_ignored)) in overwrites.into_iter().enumerate() {
+        if let Err(e) = std::fs::rename(work_dir.join(path), backup_dir.path().join(i.to_string()))
+        {
+            moved.backup_dir = Some(backup_dir);
+            let e = anyhow!("moving aside untracked file `{}`: {e}", path.display());
+            return Err(match moved.restore() {
+                Ok(()) => e,
+                Err(restore_err) => anyhow!("{e:#}\n{restore_err:#}"),
+            });
+        }
+        moved.paths.push((path.to_path_buf(), is_ignored));
+    }
+    moved.backup_dir = Some(backup_dir);
+
+    Ok(moved)
+}
+
 impl<'repo> StackTransaction<'repo> {
     /// Get an immutable reference to the original stack.
     pub(crate) fn stack(&self) -> &Stack<'repo> {
         &self.stack
     }
 
+    /// Determine whether the transaction is a dry run.
+    pub(crate) fn is_dry_run(&self) -> bool {
+        self.options.dry_run
+    }
+
     /// Get a reference to the repo.
     pub(crate) fn repo(&self) -> &'repo gix::Repository {
         self.stack.repo
     }
 
+    /// Move the stack base to the given commit.
+    ///
+    /// All patches must have been popped beforehand; any patches pushed afterwards
+    /// are rebased onto the new base.
+    pub(crate) fn set_base(&mut self, commit: Rc<gix::Commit<'repo>>) {
+        assert!(self.applied.is_empty());
+        self.updated_base = Some(commit);
+        self.updated_head = None;
+    }
+
     /// Reset stack to a previous stack state.
     pub(crate) fn reset_to_state(&mut self, state: StackState<'repo>) -> Result<()> {
         for pn in self.all_patches().cloned().collect::<Vec<_>>() {
@@ -373,6 +836,9 @@ impl<'repo> StackTransaction<'repo> {
             unapplied,
             hidden,
             patches,
+            labels,
+            aliases,
+            command: _command,
         } = state;
         self.updated_base = Some(if let Some(pn) = applied.first() {
             Rc::new(patches[pn].commit.get_parent_commit()?)
@@ -386,6 +852,8 @@ impl<'repo> StackTransaction<'repo> {
         self.applied = applied;
         self.unapplied = unapplied;
         self.hidden = hidden;
+        self.labels = labels;
+        self.aliases = aliases;
         Ok(())
     }
 
@@ -465,20 +933,15 @@ impl<'repo> StackTransaction<'repo> {
     /// Update a patch with a different commit object.
     ///
     /// Any notes associated with the patch's previous commit are copied to the new
-    /// commit.
+    /// commit, see [`Self::copy_notes()`].
     pub(crate) fn update_patch(
         &mut self,
         patchname: &PatchName,
         commit_id: gix::ObjectId,
     ) -> Result<()> {
         let commit = self.stack.repo.find_commit(commit_id)?;
-        let old_commit = self.get_patch_commit(patchname);
-        // Failure to copy is okay. The old commit may not have a note to copy.
-        self.stack
-            .repo
-            .stupid()
-            .notes_copy(old_commit.id, commit_id)
-            .ok();
+        let old_commit_id = self.get_patch_commit(patchname).id;
+        self.copy_notes("amend", old_commit_id, commit_id);
         self.updated_patches.insert(
             patchname.clone(),
             Some(PatchState {
@@ -524,7 +987,7 @@ impl<'repo> StackTransaction<'repo> {
                 commit: Rc::new(commit),
             }),
         );
-        self.ui.print_popped(&[patchname.clone()])?;
+        self.ui.print_popped(std::slice::from_ref(patchname))?;
         Ok(())
     }
 
@@ -555,29 +1018,26 @@ impl<'repo> StackTransaction<'repo> {
         let push_status = if patch_commit.parent_ids().next().unwrap() == self.top().id() {
             PushStatus::Unmodified
         } else {
-            let author = patch_commit.author_strict()?;
-            let default_committer = repo.get_committer()?;
-            let committer = if self.options.committer_date_is_author_date {
-                let mut committer = default_committer.to_owned();
-                committer.time = author.time;
-                committer
-            } else {
-                default_committer.to_owned()
-            };
+            let (author, committer) =
+                self.rewrite_signatures(patch_commit, self.default_committer()?)?;
             let message = patch_commit.message_ex();
             let parent_ids = [self.top().id];
-            let new_commit_id = repo.commit_ex(
+            let (extra_headers, dropped_headers) =
+                patch_commit.rewritable_extra_headers(&parent_ids)?;
+            if !dropped_headers.is_empty() {
+                self.ui.print_dropped_headers(patchname, &dropped_headers)?;
+            }
+            let new_commit_id = repo.commit_ex_with_headers(
                 &author,
                 &committer,
                 &message,
                 patch_commit.tree_id()?.detach(),
                 parent_ids,
+                extra_headers,
             )?;
 
             let commit = repo.find_commit(new_commit_id)?;
-            repo.stupid()
-                .notes_copy(patch_commit.id, new_commit_id)
-                .ok();
+            self.copy_notes("rebase", patch_commit.id, new_commit_id);
             self.updated_patches.insert(
                 patchname.clone(),
                 Some(PatchState {
@@ -841,7 +1301,59 @@ impl<'repo> StackTransaction<'repo> {
                 .insert(new_patchname.clone(), Some(patch_state));
         }
 
-        self.ui.print_rename(old_patchname, new_patchname)
+        let mut updated_refs = Vec::new();
+        if let Some(labels) = self.labels.remove(old_patchname) {
+            for label in &labels {
+                updated_refs.push(format!("label `{label}`"));
+            }
+            self.labels.insert(new_patchname.clone(), labels);
+        }
+        for (alias, target) in &mut self.aliases {
+            if target == old_patchname {
+                updated_refs.push(format!("alias `{alias}`"));
+                *target = new_patchname.clone();
+            }
+        }
+
+        self.ui.print_rename(old_patchname, new_patchname)?;
+        self.ui.print_updated_refs(&updated_refs)
+    }
+
+    /// Record `alias` as an alternative name for an existing patch.
+    ///
+    /// Locators using the alias resolve to the patch, with a deprecation warning, until
+    /// the alias is removed or a patch named `alias` is created.
+    pub(crate) fn add_alias(&mut self, alias: &PatchName, patchname: &PatchName) {
+        self.aliases.insert(alias.clone(), patchname.clone());
+    }
+
+    /// Remove a patch name alias.
+    pub(crate) fn remove_alias(&mut self, alias: &PatchName) {
+        self.aliases.remove(alias);
+    }
+
+    /// Add a label to each of the given patches.
+    pub(crate) fn add_label(&mut self, label: &str, patchnames: &[PatchName]) {
+        for patchname in patchnames {
+            self.labels
+                .entry(patchname.clone())
+                .or_default()
+                .insert(label.to_string());
+        }
+    }
+
+    /// Remove a label from each of the given patches.
+    ///
+    /// Patches not having the label are ignored.
+    pub(crate) fn remove_label(&mut self, label: &str, patchnames: &[PatchName]) {
+        for patchname in patchnames {
+            if let Some(labels) = self.labels.get_mut(patchname) {
+                labels.remove(label);
+                if labels.is_empty() {
+                    self.labels.remove(patchname);
+                }
+            }
+        }
     }
 
     /// Delete one or more patches from the stack.
@@ -911,6 +1423,8 @@ impl<'repo> StackTransaction<'repo> {
             self.ui.print_deleted(&deleted_group)?;
         }
 
+        self.labels.retain(|patchname, _| !should_delete(patchname));
+
         Ok(incidental)
     }
 
@@ -969,6 +1483,8 @@ impl<'repo> StackTransaction<'repo> {
     where
         P: AsRef<PatchName>,
     {
+        self.prefetch_push_blobs(patchnames)?;
+
         let stupid = self.stack.repo.stupid();
         stupid.with_temp_index(|stupid_temp| {
             let mut temp_index_tree_id: Option<gix::ObjectId> = None;
@@ -985,6 +1501,9 @@ impl<'repo> StackTransaction<'repo> {
                 let already_merged = merged
                     .as_ref()
                     .is_some_and(|merged| merged.contains(&patchname));
+                if crate::signal::interrupted() {
+                    return Err(Error::Interrupted("interrupted by user".to_string()).into());
+                }
                 self.push_patch(
                     patchname,
                     already_merged,
@@ -998,6 +1517,36 @@ impl<'repo> StackTransaction<'repo> {
         })
     }
 
+    /// Prefetch the blobs needed to push the given patches in a partial clone.
+    ///
+    /// Patches that will be fast-forwarded need no blobs. The remaining patches need
+    /// the blobs of their own changes and of the same paths in the current top.
+    fn prefetch_push_blobs<P>(&self, patchnames: &[P]) -> Result<()>
+    where
+        P: AsRef<PatchName>,
+    {
+        let repo = self.stack.repo;
+        if repo.promisor_remote().is_none() {
+            return Ok(());
+        }
+        let mut expected_parent_id = Some(self.top().id);
+        let mut tree_pairs = Vec::new();
+        for patchname in patchnames {
+            let patch_commit = self.get_patch_commit(patchname.as_ref());
+            let old_parent = patch_commit.get_parent_commit()?;
+            if expected_parent_id == Some(old_parent.id) {
+                expected_parent_id = Some(patch_commit.id);
+            } else {
+                expected_parent_id = None;
+                tree_pairs.push((
+                    old_parent.tree_id()?.detach(),
+                    patch_commit.tree_id()?.detach(),
+                ));
+            }
+        }
+        repo.prefetch_blobs(&tree_pairs, &[self.top().tree_id()?.detach()])
+    }
+
     fn push_patch(
         &mut self,
         patchname: &PatchName,
@@ -1007,9 +1556,9 @@ impl<'repo> StackTransaction<'repo> {
         temp_index_tree_id: &mut Option<gix::ObjectId>,
     ) -> Result<()> {
         let repo = self.stack.repo;
-        let config = repo.config_snapshot();
+        let config = self.stack.config();
         let stupid = repo.stupid();
-        let default_committer = repo.get_committer()?;
+        let default_committer = self.default_committer()?;
         let patch_commit = self.get_patch_commit(patchname).clone();
         let old_parent = patch_commit.get_parent_commit()?;
         let new_parent = self.top().clone();
@@ -1018,6 +1567,11 @@ impl<'repo> StackTransaction<'repo> {
         let new_parent_ref = new_parent.decode()?;
 
         let mut push_status = PushStatus::Unmodified;
+        let mut conflict_path = None;
+        let timing = self.stats.is_some();
+        let push_start = timing.then(Instant::now);
+        let mut merge_time = Duration::ZERO;
+        let mut checkout_time = Duration::ZERO;
 
         let new_tree_id = if already_merged {
             push_status = PushStatus::AlreadyMerged;
@@ -1036,19 +1590,53 @@ impl<'repo> StackTransaction<'repo> {
             };
             let base = old_parent_ref.tree();
 
-            if temp_index_tree_id != &Some(ours) {
-                stupid_temp.read_tree(ours)?;
-                *temp_index_tree_id = Some(ours);
-            }
+            let maybe_tree_id = timed(timing, &mut merge_time, || -> Result<_> {
+                if temp_index_tree_id != &Some(ours) {
+                    stupid_temp.read_tree(ours)?;
+                    *temp_index_tree_id = Some(ours);
+                }
 
-            let maybe_tree_id = if stupid_temp.apply_treediff_to_index(base, theirs, true)? {
-                stupid_temp.write_tree().ok()
+                Ok(
+                    if stupid_temp.apply_treediff_to_index(base, theirs, true)? {
+                        stupid_temp.write_tree().ok()
+                    } else {
+                        None
+                    },
+                )
+            })?;
+
+            // When the patch does not apply as-is, e.g. because the patch's files were
+            // renamed or had their mode changed by a lower patch, and the work tree is
+            // not available for a merge, fall back to a rename-aware three-way merge of
+            // the trees alone.
+            let maybe_merged_tree_id = if maybe_tree_id.is_none()
+                && (!self.options.use_index_and_worktree || self.options.dry_run)
+            {
+                timed(timing, &mut merge_time, || {
+                    stupid.merge_trees(base, ours, theirs)
+                })?
             } else {
                 None
             };
 
             if let Some(tree_id) = maybe_tree_id {
                 tree_id
+            } else if let Some(tree_id) = maybe_merged_tree_id {
+                push_status = PushStatus::Modified;
+                tree_id
+            } else if self.options.predict_pushes {
+                // Conflicts are predicted whether or not push conflicts are allowed.
+                // The merged tree keeps its conflict markers such that the patches
+                // above this one may also be predicted.
+                push_status = PushStatus::Conflict;
+                if let Some((tree_id, conflicts)) = timed(timing, &mut merge_time, || {
+                    stupid.merge_trees_with_conflicts(base, ours, theirs)
+                })? {
+                    conflict_path = conflicts.into_iter().next();
+                    tree_id
+                } else {
+                    ours
+                }
             } else if !self.options.use_index_and_worktree {
                 return Err(Error::TransactionHalt {
                     msg: format!("{patchname} does not apply cleanly"),
@@ -1068,21 +1656,76 @@ impl<'repo> StackTransaction<'repo> {
                     conflicts: false,
                 }
                 .into());
+            } else if self.options.dry_run {
+                // A dry run may not touch the worktree, so the conflicts that would
+                // result from the worktree merge are only predicted.
+                push_status = PushStatus::Conflict;
+                ours
             } else {
-                if stupid
-                    .read_tree_checkout(self.current_tree_id, ours)
-                    .is_err()
-                {
-                    return Err(Error::TransactionHalt {
-                        msg: "index/worktree dirty".to_string(),
+                if self.options.keep_local_changes {
+                    check_local_changes(
+                        repo,
+                        self.current_tree_id,
+                        &[(self.current_tree_id, ours), (base, theirs)],
+                    )
+                    .map_err(|e| Error::TransactionHalt {
+                        msg: format!("{e:#}"),
                         conflicts: false,
-                    }
-                    .into());
+                    })?;
+                }
+                if let Some(mtimes) = self.mtimes.as_mut() {
+                    mtimes.record(repo, self.current_tree_id, ours)?;
                 }
+                move_overwritten_untracked(repo, &self.options, self.current_tree_id, ours)
+                    .and_then(|moved| {
+                        moved.checkout(&self.ui, || {
+                            timed(timing, &mut checkout_time, || {
+                                checkout::read_tree_checkout(repo, self.current_tree_id, ours)
+                            })
+                        })
+                    })
+                    .map_err(|e| Error::TransactionHalt {
+                        msg: format!("{e:#}"),
+                        conflicts: false,
+                    })?;
                 self.current_tree_id = ours;
 
+                if let Some(mtimes) = self.mtimes.as_mut() {
+                    mtimes.record(repo, ours, theirs)?;
+                }
+                let moved =
+                    move_overwritten_untracked(repo, &self.options, base, theirs).map_err(|e| {
+                        Error::TransactionHalt {
+                            msg: format!("{e:#}"),
+                            conflicts: false,
+                        }
+                    })?;
+
+                let ancestor_label = format!("ancestor {}", old_parent.id.to_hex_with_len(7));
+                let patch_label = format!("patch {patchname}");
+                let (our_label, their_label) = if ours == patch_commit_ref.tree() {
+                    (patch_label.as_str(), "current top")
+                } else {
+                    ("current top", patch_label.as_str())
+                };
+                let mut merge_options = MergeOptions::default();
+                merge_options
+                    .ancestor_label(&ancestor_label)
+                    .our_label(our_label)
+                    .their_label(their_label)
+                    .marker_size(self.options.conflict_marker_size);
                 let use_mergetool = config.boolean("stgit.autoimerge").unwrap_or(false);
-                match stupid.merge_recursive_or_mergetool(base, ours, theirs, use_mergetool) {
+                match moved.checkout(&self.ui, || {
+                    timed(timing, &mut merge_time, || {
+                        stupid.merge_recursive_or_mergetool(
+                            base,
+                            ours,
+                            theirs,
+                            &merge_options,
+                            use_mergetool,
+                        )
+                    })
+                }) {
                     Ok(true) => {
                         // Success, no conflicts
                         let tree_id = stupid.write_tree().map_err(|_| Error::TransactionHalt {
@@ -1109,23 +1752,25 @@ impl<'repo> StackTransaction<'repo> {
         };
 
         if new_tree_id != patch_commit_ref.tree() || new_parent.id != old_parent.id {
-            let author = patch_commit.author_strict()?;
-            let committer = if self.options.committer_date_is_author_date {
-                let mut committer = default_committer.to_owned();
-                committer.time = author.time;
-                committer
-            } else {
-                default_committer.to_owned()
-            };
-            let commit_id = repo.commit_ex(
+            let (author, committer) =
+                self.rewrite_signatures(&patch_commit, default_committer.clone())?;
+            let (extra_headers, dropped_headers) =
+                patch_commit.rewritable_extra_headers(&[new_parent.id])?;
+            if !dropped_headers.is_empty() {
+                self.ui.print_dropped_headers(patchname, &dropped_headers)?;
+            }
+            let commit_id = repo.commit_ex_with_headers(
                 &author,
                 &committer,
                 &patch_commit.message_ex(),
                 new_tree_id,
                 [new_parent.id],
+                extra_headers,
             )?;
             let commit = Rc::new(repo.find_commit(commit_id)?);
-            stupid.notes_copy(patch_commit.id, commit_id).ok();
+            if !self.options.dry_run {
+                self.copy_notes("rebase", patch_commit.id, commit_id);
+            }
             if push_status == PushStatus::Conflict {
                 // In the case of a conflict, update() will be called after the
                 // execute() performs the checkout. Setting the transaction head
@@ -1153,9 +1798,31 @@ impl<'repo> StackTransaction<'repo> {
         }
         self.applied.push(patchname.clone());
 
-        self.ui.print_pushed(patchname, push_status, is_last)?;
+        if let Some(predictions) = self.predictions.as_mut() {
+            predictions.push(PushPrediction {
+                patchname: patchname.clone(),
+                status: push_status,
+                conflict_path,
+            });
+        } else {
+            self.ui.print_pushed(patchname, push_status, is_last)?;
+        }
+
+        if let (Some(stats), Some(push_start)) = (self.stats.as_mut(), push_start) {
+            let push_stats = PushStats {
+                patchname: patchname.clone(),
+                status: push_status,
+                merge: merge_time,
+                checkout: checkout_time,
+                total: push_start.elapsed(),
+            };
+            if self.options.verbose {
+                self.ui.print_push_stats(&push_stats)?;
+            }
+            stats.record_push(push_stats);
+        }
 
-        if push_status == PushStatus::Conflict {
+        if push_status == PushStatus::Conflict && !self.options.predict_pushes {
             Err(Error::TransactionHalt {
                 msg: "merge conflicts; \
                       resolve conflicts manually then refresh or \
@@ -1169,6 +1836,62 @@ impl<'repo> StackTransaction<'repo> {
         }
     }
 
+    /// Get the committer of the commits created by the transaction.
+    fn default_committer(&self) -> Result<gix::actor::Signature> {
+        let mut committer = self.stack.repo.get_committer()?.to_owned();
+        if let Some(date) = self.options.fixed_date {
+            committer.time = date;
+        }
+        Ok(committer)
+    }
+
+    /// Determine the author and committer signatures for a rewritten patch commit.
+    ///
+    /// With `ignore_date`, the author date is reset to the current committer date.
+    /// With `committer_date_is_author_date`, the committer date is taken from the
+    /// (possibly reset) author date.
+    fn rewrite_signatures(
+        &self,
+        patch_commit: &gix::Commit,
+        default_committer: gix::actor::Signature,
+    ) -> Result<(gix::actor::Signature, gix::actor::Signature)> {
+        let mut author = patch_commit.author_strict()?;
+        if self.options.ignore_date {
+            author.time = default_committer.time;
+        }
+        let mut committer = if self.options.keep_committer {
+            patch_commit.committer_strict()?
+        } else {
+            default_committer
+        };
+        if self.options.committer_date_is_author_date {
+            committer.time = author.time;
+        }
+        Ok((author, committer))
+    }
+
+    /// Copy the notes of a patch's previous commit to its rewritten commit.
+    ///
+    /// Notes of the default notes ref are always copied. When notes refs are
+    /// configured to follow rewritten commits with `notes.rewriteRef`, their notes are
+    /// also copied as they would be by `git <command>`, i.e. `git commit --amend` or
+    /// `git rebase`. Failure to copy is okay; the old commit may not have notes to
+    /// copy.
+    fn copy_notes(&self, command: &str, old_commit_id: gix::ObjectId, commit_id: gix::ObjectId) {
+        let repo = self.stack.repo;
+        let stupid = repo.stupid();
+        if std::env::var_os("GIT_NOTES_REWRITE_REF").is_some()
+            || repo.config_snapshot().string("notes.rewriteRef").is_some()
+        {
+            stupid
+                .notes_copy_for_rewrite(command, old_commit_id, commit_id)
+                .ok();
+        }
+        // When the default notes ref is among the rewrite refs, its notes were
+        // already copied and this copy fails rather than duplicating them.
+        stupid.notes_copy(old_commit_id, commit_id).ok();
+    }
+
     /// Find patches that have already been merged into the stack base's tree.
     ///
     /// The diffs for each provided patchname are applied to the stack's base tree (in
@@ -1191,12 +1914,14 @@ impl<'repo> StackTransaction<'repo> {
             *temp_index_tree_id = Some(head_tree_id);
         }
 
+        let exclude = &self.options.merged_check.exclude;
+
         for patchname in patchnames.iter().rev() {
             let patchname = patchname.as_ref();
             let patch_commit = self.get_patch_commit(patchname);
 
-            if patch_commit.is_no_change()? {
-                continue; // No change
+            if exclude.contains(patchname) || patch_commit.is_no_change()? {
+                continue; // Excluded or no change
             }
 
             let parent_commit = patch_commit.get_parent_commit()?;
@@ -1211,10 +1936,133 @@ impl<'repo> StackTransaction<'repo> {
             }
         }
 
+        if let (Some(upstream_ids), Some(max_run)) = (
+            self.options.merged_check.upstream.as_ref(),
+            self.options.merged_check.max_run,
+        ) {
+            self.check_merged_runs(patchnames, upstream_ids, max_run, &mut merged)?;
+        }
+
+        if let Some(upstream_ids) = self.options.merged_check.upstream.as_ref() {
+            let mut upstream_subjects: BTreeMap<String, gix::ObjectId> = BTreeMap::new();
+            for commit_id in upstream_ids {
+                let commit = self.stack.repo.find_commit(*commit_id)?;
+                let subject = normalize_subject(&commit.decode()?.message_summary().to_str_lossy());
+                if !subject.is_empty() {
+                    upstream_subjects.entry(subject).or_insert(*commit_id);
+                }
+            }
+
+            for patchname in patchnames.iter() {
+                let patchname = patchname.as_ref();
+                if merged.contains(&patchname) || exclude.contains(patchname) {
+                    continue;
+                }
+                let patch_commit = self.get_patch_commit(patchname);
+                if patch_commit.is_no_change()? {
+                    continue;
+                }
+                let subject =
+                    normalize_subject(&patch_commit.decode()?.message_summary().to_str_lossy());
+                if let Some(upstream_id) = upstream_subjects.get(&subject) {
+                    self.ui.print_merged_by_subject(patchname, *upstream_id)?;
+                    merged.push(patchname);
+                }
+            }
+        }
+
         self.ui.print_merged(&merged)?;
 
         Ok(merged)
     }
+
+    /// Find runs of consecutive patches whose combined diff was merged upstream.
+    ///
+    /// Runs of up to `max_run` of the patches to be pushed, not yet found to be merged,
+    /// are compared by patch id with the upstream commits. Only runs whose patch
+    /// commits form a chain are considered. For each patch, the longest matching run
+    /// starting with it is taken, and each upstream commit matches at most one run.
+    /// Single patches whose diffs no longer reverse-apply to the base tree, e.g. due
+    /// to later upstream changes, are thus also found.
+    fn check_merged_runs<'a, P>(
+        &self,
+        patchnames: &'a [P],
+        upstream_ids: &[gix::ObjectId],
+        max_run: usize,
+        merged: &mut Vec<&'a PatchName>,
+    ) -> Result<()>
+    where
+        P: AsRef<PatchName>,
+    {
+        let stupid = self.stack.repo.stupid();
+        let mut upstream_patch_ids: BTreeMap<gix::ObjectId, gix::ObjectId> = BTreeMap::new();
+        for (commit_id, patch_id) in stupid.patch_ids(upstream_ids.iter().copied())? {
+            upstream_patch_ids.entry(patch_id).or_insert(commit_id);
+        }
+        if upstream_patch_ids.is_empty() {
+            return Ok(());
+        }
+
+        let exclude = &self.options.merged_check.exclude;
+        let mut chain: Vec<(&PatchName, gix::ObjectId, gix::ObjectId)> = Vec::new();
+        for patchname in patchnames {
+            let patchname = patchname.as_ref();
+            let patch_commit = self.get_patch_commit(patchname);
+            chain.push((
+                patchname,
+                patch_commit.get_parent_commit()?.id,
+                patch_commit.id,
+            ));
+        }
+        let is_candidate =
+            |patchname: &PatchName| !merged.contains(&patchname) && !exclude.contains(patchname);
+
+        // Runs, as start and end indices into `chain`, with the diffs to compare.
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        let mut pairs: Vec<(gix::ObjectId, gix::ObjectId)> = Vec::new();
+        for start in 0..chain.len() {
+            let (patchname, parent_id, _) = chain[start];
+            if !is_candidate(patchname) {
+                continue;
+            }
+            for end in start..chain.len().min(start + max_run) {
+                let (patchname, end_parent_id, end_id) = chain[end];
+                if end > start && (!is_candidate(patchname) || end_parent_id != chain[end - 1].2) {
+                    break;
+                }
+                runs.push((start, end));
+                pairs.push((parent_id, end_id));
+            }
+        }
+
+        let patch_ids = stupid.diff_patch_ids(&pairs)?;
+        let mut used_upstream: BTreeSet<gix::ObjectId> = BTreeSet::new();
+        let mut next_start = 0;
+        let mut i = 0;
+        while i < runs.len() {
+            let start = runs[i].0;
+            let mut matched: Option<(usize, gix::ObjectId)> = None;
+            while i < runs.len() && runs[i].0 == start {
+                if start >= next_start {
+                    if let Some(upstream_id) = patch_ids[i]
+                        .and_then(|patch_id| upstream_patch_ids.get(&patch_id))
+                        .filter(|upstream_id| !used_upstream.contains(*upstream_id))
+                    {
+                        matched = Some((runs[i].1, *upstream_id));
+                    }
+                }
+                i += 1;
+            }
+            if let Some((end, upstream_id)) = matched {
+                used_upstream.insert(upstream_id);
+                let run: Vec<&PatchName> = chain[start..=end].iter().map(|(pn, ..)| *pn).collect();
+                self.ui.print_merged_run(&run, upstream_id)?;
+                merged.extend(run);
+                next_start = end + 1;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'repo> StackAccess<'repo> for StackTransaction<'repo> {
@@ -1266,6 +2114,14 @@ impl<'repo> StackStateAccess<'repo> for StackTransaction<'repo> {
         }
     }
 
+    fn labels(&self) -> &BTreeMap<PatchName, BTreeSet<String>> {
+        &self.labels
+    }
+
+    fn aliases(&self) -> &BTreeMap<PatchName, PatchName> {
+        &self.aliases
+    }
+
     fn has_patch(&self, patchname: &PatchName) -> bool {
         if let Some(maybe_patch) = self.updated_patches.get(patchname) {
             maybe_patch.is_some()
@@ -1274,6 +2130,10 @@ impl<'repo> StackStateAccess<'repo> for StackTransaction<'repo> {
         }
     }
 
+    fn allow_abbreviated_names(&self) -> bool {
+        self.stack.allow_abbreviated_names()
+    }
+
     fn top(&self) -> &Rc<gix::Commit<'repo>> {
         if let Some(patchname) = self.applied.last() {
             self.get_patch_commit(patchname)
@@ -1290,3 +2150,25 @@ impl<'repo> StackStateAccess<'repo> for StackTransaction<'repo> {
         }
     }
 }
+
+/// Normalize a commit subject for comparison with other subjects.
+///
+/// Leading bracketed tags such as `[PATCH v2 1/3]` are removed, whitespace is
+/// collapsed, trailing periods are dropped, and the subject is lowercased. This allows
+/// matching subjects that were lightly edited when patches were applied upstream.
+pub(crate) fn normalize_subject(subject: &str) -> String {
+    let mut subject = subject.trim();
+    while let Some(rest) = subject.strip_prefix('[') {
+        if let Some((_, rest)) = rest.split_once(']') {
+            subject = rest.trim_start();
+        } else {
+            break;
+        }
+    }
+    subject
+        .trim_end_matches('.')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
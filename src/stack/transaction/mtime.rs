@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Preservation of work tree file modification times across checkouts.
+//!
+//! Checking out a sequence of trees may rewrite a file several times even when the
+//! file's content in the final tree is the same as before the transaction started.
+//! Build systems relying on modification times would then needlessly rebuild. To
+//! avoid that, the modification time of each file is recorded before it is first
+//! touched by a checkout, and restored afterwards if the file's blob is unchanged.
+//!
+//! This is best-effort: files whose modification time cannot be read or set, e.g. on
+//! filesystems lacking the capability, are silently skipped.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+use filetime::FileTime;
+
+use crate::stupid::Stupid;
+
+/// Modification times of work tree files, as captured prior to their first checkout.
+#[derive(Default)]
+pub(super) struct MtimeSnapshot {
+    entries: BTreeMap<PathBuf, (gix::ObjectId, FileTime)>,
+}
+
+impl MtimeSnapshot {
+    /// Record the modification times of files that differ between two trees.
+    ///
+    /// The files are expected to be checked out from `from_tree_id`. Files that were
+    /// already recorded by a previous call keep their original record.
+    pub(super) fn record(
+        &mut self,
+        repo: &gix::Repository,
+        from_tree_id: gix::ObjectId,
+        to_tree_id: gix::ObjectId,
+    ) -> Result<()> {
+        if from_tree_id == to_tree_id {
+            return Ok(());
+        }
+        let Some(work_dir) = repo.work_dir() else {
+            return Ok(());
+        };
+        let from_tree = repo.find_tree(from_tree_id)?;
+        for path in repo
+            .stupid()
+            .diff_tree_files(from_tree_id, to_tree_id)?
+            .iter()
+        {
+            if self.entries.contains_key(path) {
+                continue;
+            }
+            let Some(entry) = from_tree.lookup_entry_by_path(path)? else {
+                continue;
+            };
+            if !entry.mode().is_blob() {
+                continue;
+            }
+            let Ok(meta) = std::fs::symlink_metadata(work_dir.join(path)) else {
+                continue;
+            };
+            if meta.is_file() {
+                self.entries.insert(
+                    path.to_owned(),
+                    (
+                        entry.object_id(),
+                        FileTime::from_last_modification_time(&meta),
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore recorded modification times of files whose blob in `tree_id` is the same
+    /// as when their modification time was recorded.
+    ///
+    /// The index is refreshed afterwards so that its cached file stats stay in sync
+    /// with the work tree.
+    pub(super) fn restore(&self, repo: &gix::Repository, tree_id: gix::ObjectId) -> Result<()> {
+        let Some(work_dir) = repo.work_dir() else {
+            return Ok(());
+        };
+        let tree = repo.find_tree(tree_id)?;
+        let mut restored = false;
+        for (path, (blob_id, mtime)) in &self.entries {
+            let unchanged = tree
+                .lookup_entry_by_path(path)?
+                .is_some_and(|entry| entry.object_id() == *blob_id);
+            if unchanged && filetime::set_file_mtime(work_dir.join(path), *mtime).is_ok() {
+                restored = true;
+            }
+        }
+        if restored {
+            repo.stupid().update_index_refresh()?;
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,32 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
+use std::time::Duration;
+
+use crate::patch::PatchName;
+
 /// Options for fine-tuning stack transaction behaviors.
 pub(super) struct TransactionOptions {
     pub(super) conflict_mode: ConflictMode,
     pub(super) allow_push_conflicts: Option<bool>,
     pub(super) discard_changes: bool,
+    pub(super) keep_local_changes: bool,
     pub(super) use_index_and_worktree: bool,
+    pub(super) force_overwrite_untracked: bool,
+    pub(super) restore_mtime: bool,
     pub(super) set_head: bool,
     pub(super) allow_bad_head: bool,
     pub(super) committer_date_is_author_date: bool,
+    pub(super) ignore_date: bool,
+    pub(super) keep_committer: bool,
+    pub(super) fixed_date: Option<gix::date::Time>,
+    pub(super) merged_check: MergedCheck,
+    pub(super) conflict_marker_size: Option<usize>,
+    pub(super) verbose: bool,
+    pub(super) status_check: Option<Duration>,
+    pub(super) print_delta: bool,
+    pub(super) dry_run: bool,
+    pub(super) predict_pushes: bool,
+    pub(super) command_line: Option<Vec<String>>,
 }
 
 impl Default for TransactionOptions {
@@ -17,20 +35,36 @@ impl Default for TransactionOptions {
             conflict_mode: ConflictMode::Disallow,
             allow_push_conflicts: None,
             discard_changes: false,
+            keep_local_changes: false,
             use_index_and_worktree: false,
+            force_overwrite_untracked: false,
+            restore_mtime: false,
             set_head: true,
             allow_bad_head: false,
             committer_date_is_author_date: false,
+            ignore_date: false,
+            keep_committer: false,
+            fixed_date: None,
+            merged_check: MergedCheck::default(),
+            conflict_marker_size: None,
+            verbose: false,
+            status_check: None,
+            print_delta: false,
+            dry_run: false,
+            predict_pushes: false,
+            command_line: None,
         }
     }
 }
 
 /// Policies for whether a transaction may execute when conflicts emerge from the
 /// transactions operations.
+#[derive(Default)]
 pub(crate) enum ConflictMode {
     /// Transaction execution will fail if there are conflicts recorded in the index.
     ///
     /// This is the default.
+    #[default]
     Disallow,
 
     /// Transaction execution will succeed even if there are outstanding conflicts.
@@ -41,8 +75,28 @@ pub(crate) enum ConflictMode {
     AllowIfSameTop,
 }
 
-impl Default for ConflictMode {
-    fn default() -> Self {
-        Self::Disallow
-    }
+/// Refinements to how patches merged upstream are detected when pushing patches.
+///
+/// By default, a patch is only considered merged if its diff can be reverse-applied to
+/// the stack's base tree.
+#[derive(Default)]
+pub(crate) struct MergedCheck {
+    /// Patches that are never considered to be merged upstream.
+    pub(crate) exclude: Vec<PatchName>,
+
+    /// Upstream commits to match patches against by subject.
+    ///
+    /// When provided, a patch that is not found to be exactly merged is still
+    /// considered merged if its normalized subject matches the subject of one of these
+    /// commits. This allows detecting patches that were applied upstream with small
+    /// modifications.
+    pub(crate) upstream: Option<Vec<gix::ObjectId>>,
+
+    /// Maximum number of consecutive patches to match against upstream commits.
+    ///
+    /// When provided along with `upstream`, runs of up to this many consecutive patches
+    /// are merged if their combined diff has the same patch id as one of the upstream
+    /// commits, as happens when upstream squashed the patches into a single commit.
+    /// This check runs before matching by subject.
+    pub(crate) max_run: Option<usize>,
 }
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Predicted outcomes of pushing patches in a dry run.
+
+use bstr::BString;
+
+use super::PushStatus;
+use crate::patch::PatchName;
+
+/// Predicted outcome of pushing a single patch.
+pub(super) struct PushPrediction {
+    pub(super) patchname: PatchName,
+    pub(super) status: PushStatus,
+
+    /// The first conflicted path, when the push is predicted to conflict and the
+    /// conflicted paths could be determined.
+    pub(super) conflict_path: Option<BString>,
+}
+
+impl PushPrediction {
+    /// Short description of the predicted outcome.
+    pub(super) fn outcome(&self) -> &'static str {
+        match self.status {
+            PushStatus::New | PushStatus::Modified | PushStatus::Unmodified => "clean",
+            PushStatus::AlreadyMerged => "merged",
+            PushStatus::Empty => "empty",
+            PushStatus::Conflict => "conflicts",
+        }
+    }
+}
+
+/// Count the predicted pushes that conflict.
+pub(super) fn count_conflicts(predictions: &[PushPrediction]) -> usize {
+    predictions
+        .iter()
+        .filter(|prediction| prediction.status == PushStatus::Conflict)
+        .count()
+}
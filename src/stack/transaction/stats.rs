@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Timing statistics for stack transactions.
+//!
+//! Statistics are only collected when requested with `--verbose` or by setting the
+//! `STG_PERF=1` environment variable. With `STG_PERF=1`, the statistics are written
+//! to stderr as JSON when the transaction finishes.
+
+use std::time::{Duration, Instant};
+
+use super::PushStatus;
+use crate::patch::PatchName;
+
+/// Timings for pushing a single patch.
+pub(super) struct PushStats {
+    pub(super) patchname: PatchName,
+    pub(super) status: PushStatus,
+    pub(super) merge: Duration,
+    pub(super) checkout: Duration,
+    pub(super) total: Duration,
+}
+
+/// Statistics collected over the course of a stack transaction.
+pub(super) struct TransactionStats {
+    start: Instant,
+    pushes: Vec<PushStats>,
+    checkout: Duration,
+    status_check: Option<Duration>,
+    dump_json: bool,
+}
+
+/// Determine whether `STG_PERF=1` is set in the environment.
+pub(super) fn perf_env_enabled() -> bool {
+    std::env::var_os("STG_PERF").is_some_and(|value| value == "1")
+}
+
+impl TransactionStats {
+    pub(super) fn new(status_check: Option<Duration>, dump_json: bool) -> Self {
+        Self {
+            start: Instant::now(),
+            pushes: Vec::new(),
+            checkout: Duration::ZERO,
+            status_check,
+            dump_json,
+        }
+    }
+
+    pub(super) fn record_push(&mut self, push_stats: PushStats) {
+        self.pushes.push(push_stats);
+    }
+
+    pub(super) fn add_checkout(&mut self, duration: Duration) {
+        self.checkout += duration;
+    }
+
+    pub(super) fn pushes(&self) -> &[PushStats] {
+        &self.pushes
+    }
+
+    pub(super) fn checkout(&self) -> Duration {
+        self.checkout
+    }
+
+    pub(super) fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub(super) fn count(&self, status: PushStatus) -> usize {
+        self.pushes.iter().filter(|ps| ps.status == status).count()
+    }
+
+    pub(super) fn dump_json(&self) -> bool {
+        self.dump_json
+    }
+
+    pub(super) fn to_json(&self) -> serde_json::Value {
+        let patches: Vec<serde_json::Value> = self
+            .pushes
+            .iter()
+            .map(|ps| {
+                serde_json::json!({
+                    "patch": ps.patchname.to_string(),
+                    "status": status_name(ps.status),
+                    "merge_secs": ps.merge.as_secs_f64(),
+                    "checkout_secs": ps.checkout.as_secs_f64(),
+                    "total_secs": ps.total.as_secs_f64(),
+                })
+            })
+            .collect();
+        let mut json = serde_json::json!({
+            "patches": patches,
+            "pushed": self.pushes.len(),
+            "conflicts": self.count(PushStatus::Conflict),
+            "empty": self.count(PushStatus::Empty),
+            "checkout_secs": self.checkout.as_secs_f64(),
+            "elapsed_secs": self.elapsed().as_secs_f64(),
+        });
+        if let Some(status_check) = self.status_check {
+            json["status_check_secs"] = status_check.as_secs_f64().into();
+        }
+        json
+    }
+}
+
+/// Run `f`, adding the time it takes to `elapsed` when timing is enabled.
+pub(super) fn timed<T>(enabled: bool, elapsed: &mut Duration, f: impl FnOnce() -> T) -> T {
+    if enabled {
+        let start = Instant::now();
+        let result = f();
+        *elapsed += start.elapsed();
+        result
+    } else {
+        f()
+    }
+}
+
+fn status_name(status: PushStatus) -> &'static str {
+    match status {
+        PushStatus::New => "new",
+        PushStatus::AlreadyMerged => "merged",
+        PushStatus::Conflict => "conflict",
+        PushStatus::Empty => "empty",
+        PushStatus::Modified => "modified",
+        PushStatus::Unmodified => "unmodified",
+    }
+}
@@ -1,23 +1,33 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use std::{cell::RefCell, io::Write};
+use std::{cell::RefCell, io::Write, path::Path, time::Duration};
 
 use anyhow::Result;
+use bstr::BString;
 use termcolor::WriteColor;
 
-use super::PushStatus;
+use super::{
+    delta::StackDelta,
+    predict::{self, PushPrediction},
+    stats::{PushStats, TransactionStats},
+    PushStatus,
+};
 use crate::patch::PatchName;
 
 /// User output for stack transactions.
+///
+/// Patch names are quoted according to `quote_names`, i.e. `stgit.quotenames`.
 pub(super) struct TransactionUserInterface {
-    output: RefCell<termcolor::StandardStream>,
+    output: RefCell<Box<dyn WriteColor>>,
+    quote_names: bool,
     printed_top: bool,
 }
 
 impl TransactionUserInterface {
-    pub(super) fn new(output: termcolor::StandardStream) -> TransactionUserInterface {
+    pub(super) fn new(output: Box<dyn WriteColor>, quote_names: bool) -> TransactionUserInterface {
         TransactionUserInterface {
             output: RefCell::new(output),
+            quote_names,
             printed_top: false,
         }
     }
@@ -38,6 +48,65 @@ impl TransactionUserInterface {
         Ok(())
     }
 
+    pub(super) fn print_merged_by_subject(
+        &self,
+        patchname: &PatchName,
+        upstream_id: gix::ObjectId,
+    ) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        write!(output, "Patch ")?;
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_bold(true))?;
+        write!(output, "{}", patchname.quoted_if(self.quote_names))?;
+        output.reset()?;
+        write!(output, " matches subject of upstream commit ")?;
+        output.set_color(
+            color_spec
+                .set_bold(false)
+                .set_fg(Some(termcolor::Color::Yellow)),
+        )?;
+        write!(output, "{upstream_id}")?;
+        output.reset()?;
+        writeln!(output)?;
+        Ok(())
+    }
+
+    pub(super) fn print_merged_run(
+        &self,
+        patchnames: &[&PatchName],
+        upstream_id: gix::ObjectId,
+    ) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let mut color_spec = termcolor::ColorSpec::new();
+        let (first, last) = (patchnames[0], patchnames[patchnames.len() - 1]);
+        if patchnames.len() == 1 {
+            write!(output, "Patch ")?;
+            output.set_color(color_spec.set_bold(true))?;
+            write!(output, "{}", first.quoted_if(self.quote_names))?;
+            output.reset()?;
+            write!(output, " matches the patch id of upstream commit ")?;
+        } else {
+            write!(output, "Patches ")?;
+            output.set_color(color_spec.set_bold(true))?;
+            write!(output, "{}", first.quoted_if(self.quote_names))?;
+            output.reset()?;
+            write!(output, "..")?;
+            output.set_color(&color_spec)?;
+            write!(output, "{}", last.quoted_if(self.quote_names))?;
+            output.reset()?;
+            write!(output, " match squashed upstream commit ")?;
+        }
+        output.set_color(
+            color_spec
+                .set_bold(false)
+                .set_fg(Some(termcolor::Color::Yellow)),
+        )?;
+        write!(output, "{upstream_id}")?;
+        output.reset()?;
+        writeln!(output)?;
+        Ok(())
+    }
+
     pub(super) fn print_rename(
         &self,
         old_patchname: &PatchName,
@@ -46,12 +115,20 @@ impl TransactionUserInterface {
         let mut output = self.output.borrow_mut();
         let mut color_spec = termcolor::ColorSpec::new();
         output.set_color(color_spec.set_dimmed(true))?;
-        write!(output, "{old_patchname}")?;
+        write!(output, "{}", old_patchname.quoted_if(self.quote_names))?;
         color_spec.clear();
         output.set_color(color_spec.set_fg(Some(termcolor::Color::Blue)))?;
         write!(output, " => ")?;
         output.reset()?;
-        writeln!(output, "{new_patchname}")?;
+        writeln!(output, "{}", new_patchname.quoted_if(self.quote_names))?;
+        Ok(())
+    }
+
+    pub(super) fn print_updated_refs(&self, updated_refs: &[String]) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        for updated_ref in updated_refs {
+            writeln!(output, "  updated {updated_ref}")?;
+        }
         Ok(())
     }
 
@@ -62,13 +139,13 @@ impl TransactionUserInterface {
         write!(output, "$ ")?;
         color_spec.set_fg(None);
         output.set_color(color_spec.set_intense(true))?;
-        write!(output, "{}", committed[0])?;
+        write!(output, "{}", committed[0].quoted_if(self.quote_names))?;
         if committed.len() > 1 {
             output.set_color(color_spec.set_intense(false))?;
             write!(output, "..")?;
             output.set_color(color_spec.set_intense(true))?;
             let last = &committed[committed.len() - 1];
-            write!(output, "{last}")?;
+            write!(output, "{}", last.quoted_if(self.quote_names))?;
         }
         output.reset()?;
         writeln!(output)?;
@@ -84,10 +161,15 @@ impl TransactionUserInterface {
             output.set_color(color_spec.set_fg(None))?;
             let first = uncommitted.first().unwrap();
             if uncommitted.len() == 1 {
-                writeln!(output, "{first}")?;
+                writeln!(output, "{}", first.quoted_if(self.quote_names))?;
             } else {
                 let last = uncommitted.last().unwrap();
-                writeln!(output, "{first}..{last}")?;
+                writeln!(
+                    output,
+                    "{}..{}",
+                    first.quoted_if(self.quote_names),
+                    last.quoted_if(self.quote_names)
+                )?;
             }
         }
         Ok(())
@@ -101,13 +183,13 @@ impl TransactionUserInterface {
             write!(output, "# ")?;
             color_spec.set_fg(None);
             output.set_color(color_spec.set_dimmed(true))?;
-            write!(output, "{}", deleted[0])?;
+            write!(output, "{}", deleted[0].quoted_if(self.quote_names))?;
             if deleted.len() > 1 {
                 output.set_color(color_spec.set_dimmed(false))?;
                 write!(output, "..")?;
                 output.set_color(color_spec.set_dimmed(true))?;
                 let last = &deleted[deleted.len() - 1];
-                write!(output, "{last}")?;
+                write!(output, "{}", last.quoted_if(self.quote_names))?;
             }
             output.reset()?;
             writeln!(output)?;
@@ -123,7 +205,7 @@ impl TransactionUserInterface {
             write!(output, "! ")?;
             color_spec.set_fg(None);
             output.set_color(color_spec.set_dimmed(true).set_italic(true))?;
-            writeln!(output, "{patchname}")?;
+            writeln!(output, "{}", patchname.quoted_if(self.quote_names))?;
             color_spec.clear();
             output.reset()?;
         }
@@ -138,7 +220,7 @@ impl TransactionUserInterface {
             write!(output, "- ")?;
             color_spec.set_fg(None);
             output.set_color(color_spec.set_dimmed(true))?;
-            writeln!(output, "{patchname}")?;
+            writeln!(output, "{}", patchname.quoted_if(self.quote_names))?;
             color_spec.clear();
             output.reset()?;
         }
@@ -153,13 +235,13 @@ impl TransactionUserInterface {
             write!(output, "- ")?;
             color_spec.set_fg(None);
             output.set_color(color_spec.set_dimmed(true))?;
-            write!(output, "{}", popped[0])?;
+            write!(output, "{}", popped[0].quoted_if(self.quote_names))?;
             if popped.len() > 1 {
                 output.set_color(color_spec.set_dimmed(false))?;
                 write!(output, "..")?;
                 output.set_color(color_spec.set_dimmed(true))?;
                 let last = &popped[popped.len() - 1];
-                write!(output, "{last}")?;
+                write!(output, "{}", last.quoted_if(self.quote_names))?;
             }
             output.reset()?;
             writeln!(output)?;
@@ -188,7 +270,7 @@ impl TransactionUserInterface {
         write!(output, "{sigil} ")?;
         color_spec.clear();
         output.set_color(color_spec.set_bold(is_last).set_intense(!is_last))?;
-        write!(output, "{patchname}")?;
+        write!(output, "{}", patchname.quoted_if(self.quote_names))?;
         output.reset()?;
 
         let status_str = match status {
@@ -214,6 +296,77 @@ impl TransactionUserInterface {
         Ok(())
     }
 
+    pub(super) fn print_push_stats(&self, push_stats: &PushStats) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_dimmed(true))?;
+        write!(
+            output,
+            "  merge {:.3}s, checkout {:.3}s, total {:.3}s",
+            push_stats.merge.as_secs_f64(),
+            push_stats.checkout.as_secs_f64(),
+            push_stats.total.as_secs_f64(),
+        )?;
+        output.reset()?;
+        writeln!(output)?;
+        Ok(())
+    }
+
+    pub(super) fn print_status_check(&self, elapsed: Duration) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_dimmed(true))?;
+        write!(output, "  status check {:.3}s", elapsed.as_secs_f64())?;
+        output.reset()?;
+        writeln!(output)?;
+        Ok(())
+    }
+
+    pub(super) fn print_summary(&self, stats: &TransactionStats) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let pushed = stats.pushes().len();
+        let conflicts = stats.count(PushStatus::Conflict);
+        let empty = stats.count(PushStatus::Empty);
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        writeln!(
+            output,
+            "Pushed {pushed} patch{} ({conflicts} conflict{}, {empty} empty) in {:.3}s, \
+             checkout {:.3}s",
+            if pushed == 1 { "" } else { "es" },
+            plural(conflicts),
+            stats.elapsed().as_secs_f64(),
+            stats.checkout().as_secs_f64(),
+        )?;
+        Ok(())
+    }
+
+    pub(super) fn print_overwritten_untracked(&self, path: &Path, is_ignored: bool) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let kind = if is_ignored { "ignored" } else { "untracked" };
+        writeln!(output, "Overwriting {kind} file `{}`", path.display())?;
+        Ok(())
+    }
+
+    pub(super) fn print_dropped_headers(
+        &self,
+        patchname: &PatchName,
+        header_names: &[BString],
+    ) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let names: Vec<_> = header_names
+            .iter()
+            .map(|name| format!("`{name}`"))
+            .collect();
+        let plural = if names.len() == 1 { "" } else { "s" };
+        writeln!(
+            output,
+            "Dropping invalidated commit header{plural} {} from `{}`",
+            names.join(", "),
+            patchname.quoted_if(self.quote_names),
+        )?;
+        Ok(())
+    }
+
     pub(super) fn print_top(&self, patchname: &PatchName) -> Result<()> {
         let mut output = self.output.borrow_mut();
         let mut color_spec = termcolor::ColorSpec::new();
@@ -221,8 +374,131 @@ impl TransactionUserInterface {
         write!(output, "> ")?;
         color_spec.clear();
         output.set_color(color_spec.set_bold(true).set_intense(false))?;
-        writeln!(output, "{patchname}")?;
+        writeln!(output, "{}", patchname.quoted_if(self.quote_names))?;
+        output.reset()?;
+        Ok(())
+    }
+
+    pub(super) fn print_delta(&self, delta: &StackDelta, dry_run: bool) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let (now_at, created, removed) = if dry_run {
+            ("Would be at", "Would create", "would remove")
+        } else {
+            ("Now at", "Created", "removed")
+        };
+        write!(output, "{now_at} ")?;
+        output.set_color(termcolor::ColorSpec::new().set_bold(true))?;
+        if let Some(top) = delta.top.as_ref() {
+            write!(output, "{}", top.quoted_if(self.quote_names))?;
+        } else {
+            write!(output, "{{base}}")?;
+        }
+        output.reset()?;
+        write!(output, " with ")?;
+        write_count(&mut *output, "applied", delta.applied)?;
+        write!(output, " and ")?;
+        write_count(&mut *output, "unapplied", delta.unapplied)?;
+        writeln!(output)?;
+
+        if !delta.created.is_empty() {
+            write!(output, "{created} ")?;
+            write_patchnames(&mut *output, &delta.created, self.quote_names)?;
+            if !delta.removed.is_empty() {
+                write!(output, "; {removed} ")?;
+                write_patchnames(&mut *output, &delta.removed, self.quote_names)?;
+            }
+            writeln!(output)?;
+        } else if !delta.removed.is_empty() {
+            let mut removed = removed.to_string();
+            removed[..1].make_ascii_uppercase();
+            write!(output, "{removed} ")?;
+            write_patchnames(&mut *output, &delta.removed, self.quote_names)?;
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn print_dry_run_conflicts(&self, patchname: Option<&PatchName>) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        write!(output, "Would stop with merge conflicts in ")?;
+        output.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Red)))?;
+        if let Some(patchname) = patchname {
+            write!(output, "{}", patchname.quoted_if(self.quote_names))?;
+        } else {
+            write!(output, "{{base}}")?;
+        }
+        output.reset()?;
+        writeln!(output)?;
+        Ok(())
+    }
+
+    pub(super) fn print_dry_run_halt(&self, err: &anyhow::Error) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        writeln!(output, "Would stop: {err:#}")?;
+        Ok(())
+    }
+
+    pub(super) fn print_predictions(&self, predictions: &[PushPrediction]) -> Result<()> {
+        if predictions.is_empty() {
+            return Ok(());
+        }
+        let mut output = self.output.borrow_mut();
+        let names: Vec<String> = predictions
+            .iter()
+            .map(|prediction| prediction.patchname.quoted_if(self.quote_names).to_string())
+            .collect();
+        let width = names.iter().map(String::len).max().unwrap_or(0).max(5);
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_bold(true))?;
+        write!(output, "{:width$}  Prediction", "Patch")?;
+        output.reset()?;
+        writeln!(output)?;
+        for (prediction, name) in predictions.iter().zip(names.iter()) {
+            write!(output, "{name:width$}  ")?;
+            color_spec.clear();
+            output.set_color(color_spec.set_fg(Some(match prediction.status {
+                PushStatus::Conflict => termcolor::Color::Red,
+                PushStatus::Empty | PushStatus::AlreadyMerged => termcolor::Color::Yellow,
+                _ => termcolor::Color::Green,
+            })))?;
+            write!(output, "{}", prediction.outcome())?;
+            output.reset()?;
+            if let Some(path) = prediction.conflict_path.as_ref() {
+                write!(output, " ({path})")?;
+            }
+            writeln!(output)?;
+        }
+
+        let conflict_count = predict::count_conflicts(predictions);
+        let last_conflicts = predictions
+            .last()
+            .is_some_and(|prediction| prediction.status == PushStatus::Conflict);
+        color_spec.clear();
+        output.set_color(color_spec.set_dimmed(true))?;
+        write!(
+            output,
+            "Predicted with in-memory merges, without git-rerere(1) resolutions or custom \
+             merge drivers"
+        )?;
+        if conflict_count > 1 || (conflict_count == 1 && !last_conflicts) {
+            write!(
+                output,
+                "\nPatches above a conflict are predicted with its conflict markers left in \
+                 place"
+            )?;
+        }
         output.reset()?;
+        writeln!(output)?;
+        Ok(())
+    }
+
+    pub(super) fn print_dry_run(&self) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_dimmed(true))?;
+        write!(output, "Dry run: no changes were made")?;
+        output.reset()?;
+        writeln!(output)?;
         Ok(())
     }
 
@@ -234,7 +510,7 @@ impl TransactionUserInterface {
         color_spec.clear();
         output.set_color(color_spec.set_bold(true).set_intense(false))?;
         if let Some(patchname) = patchname {
-            write!(output, "{patchname}")?;
+            write!(output, "{}", patchname.quoted_if(self.quote_names))?;
         } else {
             write!(output, "{{base}}")?;
         }
@@ -261,8 +537,45 @@ impl TransactionUserInterface {
                 .set_intense(is_applied && !is_top)
                 .set_dimmed(!is_applied),
         )?;
-        writeln!(output, "{patchname}")?;
+        writeln!(output, "{}", patchname.quoted_if(self.quote_names))?;
         output.reset()?;
         Ok(())
     }
 }
+
+/// Write a patch count followed by its change, if any, e.g. "3 applied (+1)".
+fn write_count(
+    output: &mut impl WriteColor,
+    label: &str,
+    (before, after): (usize, usize),
+) -> Result<()> {
+    write!(output, "{after} {label}")?;
+    if after != before {
+        let (color, sign, change) = if after > before {
+            (termcolor::Color::Green, '+', after - before)
+        } else {
+            (termcolor::Color::Red, '-', before - after)
+        };
+        output.set_color(termcolor::ColorSpec::new().set_fg(Some(color)))?;
+        write!(output, " ({sign}{change})")?;
+        output.reset()?;
+    }
+    Ok(())
+}
+
+/// Write a comma-separated list of patch names.
+fn write_patchnames(
+    output: &mut impl WriteColor,
+    patchnames: &[PatchName],
+    quote_names: bool,
+) -> Result<()> {
+    for (i, patchname) in patchnames.iter().enumerate() {
+        if i > 0 {
+            write!(output, ", ")?;
+        }
+        output.set_color(termcolor::ColorSpec::new().set_intense(true))?;
+        write!(output, "{}", patchname.quoted_if(quote_names))?;
+        output.reset()?;
+    }
+    Ok(())
+}
@@ -2,9 +2,12 @@
 
 //! Methods for upgrading old stack state representations to the current version.
 //!
-//! The current stack state format is version 5, introduced in StGit `v1.2`.
+//! The current stack state format is version 6, which shares the storage layout of
+//! version 5. Version 5 stack states are read as-is and are only written as version 6
+//! once patch labels or aliases are added; see [`super::serde`].
 //!
 //! This module is capable of upgrading stack state version 4 to version 5.
+//! - Stack state version 6 adds patch labels and aliases to the version 5 format.
 //! - Stack state version 5 was introduced in StGit `v1.2`.
 //! - Stack state version 4 was introduced in StGit `v1.0`.
 //! - Stack state version 3 was introduced in StGit `v0.20`.
@@ -21,12 +24,13 @@ use anyhow::{anyhow, Context, Result};
 use bstr::{ByteSlice, ByteVec};
 
 use super::serde::{RawPatchState, RawStackState};
-use crate::{ext::RepositoryExtended, patch::PatchName, stack::state::StackState};
+use crate::{ext::RepositoryExtended, output::Output, patch::PatchName, stack::state::StackState};
 
 /// Upgrade stack state metadata to most recent version.
 pub(crate) fn stack_upgrade(repo: &gix::Repository, branch_name: &str) -> Result<()> {
     let version = get_format_version(repo, branch_name)?;
     match version {
+        // Versions 5 and later share a layout and are upgraded when next written.
         5 => Ok(()),
         4 => stack_upgrade_from_4(repo, branch_name),
         3 => stack_upgrade_from_3(repo, branch_name),
@@ -181,10 +185,14 @@ fn stack_upgrade_from_4(repo: &gix::Repository, branch_name: &str) -> Result<()>
                 unapplied,
                 hidden,
                 patches,
+                labels: BTreeMap::new(),
+                aliases: BTreeMap::new(),
+                command: None,
             };
 
             let state = StackState::from_raw_state(repo, raw_stack_state)?;
-            let new_state_commit_id = state.commit(repo, None, "stack upgrade to version 5")?;
+            let new_state_commit_id =
+                state.commit(repo, branch_name, None, "stack upgrade to version 5", None)?;
             let refname = state_refname_from_branch_name_v5(branch_name);
             repo.reference(
                 refname.as_str(),
@@ -197,7 +205,9 @@ fn stack_upgrade_from_4(repo: &gix::Repository, branch_name: &str) -> Result<()>
             stack_ref_v4
                 .delete()
                 .with_context(|| format!("deleting old `{refname_v4}` ref"))?;
-            eprintln!("Upgraded {branch_name} to stack format version 5");
+            Output::current().info(&format!(
+                "upgraded `{branch_name}` to stack format version 5"
+            ));
         };
     }
 
@@ -265,10 +275,14 @@ fn stack_upgrade_from_3(repo: &gix::Repository, branch_name: &str) -> Result<()>
         unapplied,
         hidden,
         patches,
+        labels: BTreeMap::new(),
+        aliases: BTreeMap::new(),
+        command: None,
     };
 
     let state = StackState::from_raw_state(repo, raw_stack_state)?;
-    let new_state_commit_id = state.commit(repo, None, "stack upgrade to version 5")?;
+    let new_state_commit_id =
+        state.commit(repo, branch_name, None, "stack upgrade to version 5", None)?;
     let refname = state_refname_from_branch_name_v5(branch_name);
     repo.reference(
         refname.as_str(),
@@ -293,7 +307,9 @@ fn stack_upgrade_from_3(repo: &gix::Repository, branch_name: &str) -> Result<()>
     // .git/patches will be removed after the last stack is converted
     remove_dir(repo.git_dir().join("patches")).ok();
 
-    eprintln!("Upgraded {branch_name} to stack format version 5");
+    Output::current().info(&format!(
+        "upgraded `{branch_name}` to stack format version 5"
+    ));
 
     Ok(())
 }
@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Read-only view of a stack's patches for inspection commands.
+//!
+//! [`StackView`] iterates patches along with their commit, position, and location in
+//! the stack. The set of files changed by each patch is computed lazily and memoized
+//! for the lifetime of the view, such that a command inspecting the same patches
+//! several times only diffs each patch once.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use bstr::BString;
+
+use super::StackStateAccess;
+use crate::{
+    ext::CommitExtended,
+    patch::{LocationGroup, PatchName},
+    stupid::Stupid,
+};
+
+/// Set of paths changed by a patch.
+pub(crate) type FileSet = BTreeSet<BString>;
+
+/// Read-only view of a stack with memoized per-patch file sets.
+pub(crate) struct StackView<'s, 'repo> {
+    stack: &'s dyn StackStateAccess<'repo>,
+    files: RefCell<HashMap<gix::ObjectId, Rc<FileSet>>>,
+}
+
+/// A patch as seen through a [`StackView`].
+#[derive(Clone, Copy)]
+pub(crate) struct PatchView<'v, 'repo> {
+    view: &'v StackView<'v, 'repo>,
+    name: &'v PatchName,
+    index: usize,
+}
+
+impl<'s, 'repo> StackView<'s, 'repo> {
+    pub(crate) fn new(stack: &'s dyn StackStateAccess<'repo>) -> Self {
+        Self {
+            stack,
+            files: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Iterator over the applied patches, from bottom to top.
+    pub(crate) fn applied(&self) -> impl Iterator<Item = PatchView<'_, 'repo>> + '_ {
+        self.all().take(self.stack.applied().len())
+    }
+
+    /// Iterator over the unapplied patches.
+    pub(crate) fn unapplied(&self) -> impl Iterator<Item = PatchView<'_, 'repo>> + '_ {
+        self.all()
+            .skip(self.stack.applied().len())
+            .take(self.stack.unapplied().len())
+    }
+
+    /// Iterator over the hidden patches.
+    pub(crate) fn hidden(&self) -> impl Iterator<Item = PatchView<'_, 'repo>> + '_ {
+        self.all()
+            .skip(self.stack.applied().len() + self.stack.unapplied().len())
+    }
+
+    /// Iterator over all patches: applied, unapplied, and hidden.
+    pub(crate) fn all(&self) -> impl Iterator<Item = PatchView<'_, 'repo>> + '_ {
+        self.stack
+            .all_patches()
+            .enumerate()
+            .map(move |(index, name)| PatchView {
+                view: self,
+                name,
+                index,
+            })
+    }
+
+    /// Compute the file sets of the given patches that are not yet known.
+    ///
+    /// The patches are diffed with a single `git diff-tree --stdin` instead of one
+    /// `git diff-tree` per patch, which matters for large stacks. Calling this is
+    /// optional: [`PatchView::files()`] computes any missing file set on demand.
+    pub(crate) fn load_files<'v>(
+        &'v self,
+        patches: impl IntoIterator<Item = PatchView<'v, 'repo>>,
+    ) -> Result<()> {
+        let mut tree_pairs = Vec::new();
+        let mut pending: HashMap<_, Vec<gix::ObjectId>> = HashMap::new();
+        {
+            let files = self.files.borrow();
+            for patch in patches {
+                let commit = patch.commit();
+                if !files.contains_key(&commit.id) {
+                    let trees = patch.trees()?;
+                    let commit_ids = pending.entry(trees).or_default();
+                    if commit_ids.is_empty() {
+                        tree_pairs.push(trees);
+                    }
+                    commit_ids.push(commit.id);
+                }
+            }
+        }
+        if tree_pairs.is_empty() {
+            return Ok(());
+        }
+
+        let mut sets: HashMap<(gix::ObjectId, gix::ObjectId), FileSet> = HashMap::new();
+        let raw_diff = self.repo().stupid().diff_tree_raw(&tree_pairs)?;
+        for entry in raw_diff.iter() {
+            if let Some(trees) = entry.trees {
+                sets.entry(trees)
+                    .or_default()
+                    .insert(BString::from(entry.path));
+            }
+        }
+        let mut files = self.files.borrow_mut();
+        for (trees, commit_ids) in pending {
+            let set = Rc::new(sets.remove(&trees).unwrap_or_default());
+            for commit_id in commit_ids {
+                files.insert(commit_id, set.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the applied patches that change any of the given pathspecs.
+    ///
+    /// Pathspecs have their full Git meaning here. A single `git rev-list` finds the
+    /// matching patches.
+    pub(crate) fn applied_touching<SpecIter, SpecArg>(
+        &self,
+        pathspecs: SpecIter,
+    ) -> Result<Vec<PatchView<'_, 'repo>>>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<std::ffi::OsStr>,
+    {
+        let Some(first) = self.applied().next() else {
+            return Ok(Vec::new());
+        };
+        let base_id = first.commit().get_parent_commit()?.id;
+        let revs = self
+            .repo()
+            .stupid()
+            .rev_list(base_id, self.stack.top().id, Some(pathspecs))?;
+        Ok(self
+            .applied()
+            .filter(|patch| revs.contains(&patch.commit().id))
+            .collect())
+    }
+
+    fn repo(&self) -> &'repo gix::Repository {
+        self.stack.head().repo
+    }
+}
+
+impl<'v, 'repo> PatchView<'v, 'repo> {
+    /// Name of the patch.
+    pub(crate) fn name(&self) -> &'v PatchName {
+        self.name
+    }
+
+    /// Absolute index of the patch in the stack.
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Offset of the patch from the topmost applied patch.
+    ///
+    /// The topmost patch has offset 0, patches below it have negative offsets, and
+    /// unapplied and hidden patches have positive offsets.
+    pub(crate) fn offset_from_top(&self) -> isize {
+        self.index as isize + 1 - self.view.stack.applied().len() as isize
+    }
+
+    /// Whether the patch is applied, unapplied, or hidden.
+    pub(crate) fn location(&self) -> LocationGroup {
+        let num_applied = self.view.stack.applied().len();
+        if self.index < num_applied {
+            LocationGroup::Applied
+        } else if self.index < num_applied + self.view.stack.unapplied().len() {
+            LocationGroup::Unapplied
+        } else {
+            LocationGroup::Hidden
+        }
+    }
+
+    /// Test whether this is the topmost applied patch.
+    pub(crate) fn is_top(&self) -> bool {
+        self.index + 1 == self.view.stack.applied().len()
+    }
+
+    /// The patch's commit.
+    pub(crate) fn commit(&self) -> &'v Rc<gix::Commit<'repo>> {
+        self.view.stack.get_patch_commit(self.name)
+    }
+
+    /// Ids of the patch's bottom and top trees.
+    ///
+    /// See [`commit_trees()`].
+    pub(crate) fn trees(&self) -> Result<(gix::ObjectId, gix::ObjectId)> {
+        commit_trees(self.commit())
+    }
+
+    /// Get the set of files changed by the patch.
+    pub(crate) fn files(&self) -> Result<Rc<FileSet>> {
+        if let Some(files) = self.view.files.borrow().get(&self.commit().id) {
+            return Ok(files.clone());
+        }
+        self.view.load_files([*self])?;
+        Ok(self.view.files.borrow()[&self.commit().id].clone())
+    }
+}
+
+/// Get the ids of the bottom and top trees of a patch-like commit.
+///
+/// The bottom tree is the tree of the commit's parent, i.e. the tree the commit
+/// applies to, and the top tree is the commit's own tree.
+pub(crate) fn commit_trees(commit: &gix::Commit<'_>) -> Result<(gix::ObjectId, gix::ObjectId)> {
+    Ok((
+        commit.get_parent_commit()?.tree_id()?.detach(),
+        commit.tree_id()?.detach(),
+    ))
+}
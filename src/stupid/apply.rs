@@ -0,0 +1,705 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Options for and output from `git apply`.
+
+use std::{collections::HashMap, path::Path};
+
+use bstr::ByteSlice;
+
+/// Options affecting how a patch is applied with `git apply`.
+#[derive(Default)]
+pub(crate) struct ApplyOptions<'a> {
+    pub(super) reject: bool,
+    pub(super) threeway: bool,
+    pub(super) strip_level: Option<usize>,
+    pub(super) directory: Option<&'a Path>,
+    pub(super) context_lines: Option<usize>,
+    pub(super) fuzz: Option<usize>,
+    pub(super) ignore_whitespace: bool,
+    pub(super) whitespace: Option<&'a str>,
+    pub(super) skip_applied: bool,
+}
+
+impl<'a> ApplyOptions<'a> {
+    /// Leave rejected hunks in `.rej` files instead of failing.
+    pub(crate) fn reject(&mut self, reject: bool) -> &mut Self {
+        self.reject = reject;
+        self
+    }
+
+    /// Attempt a three-way merge if the patch does not apply cleanly.
+    pub(crate) fn threeway(&mut self, threeway: bool) -> &mut Self {
+        self.threeway = threeway;
+        self
+    }
+
+    /// Remove leading components from diff paths.
+    pub(crate) fn strip_level(&mut self, strip_level: Option<usize>) -> &mut Self {
+        self.strip_level = strip_level;
+        self
+    }
+
+    /// Prepend a root directory to all diff paths.
+    pub(crate) fn directory(&mut self, directory: Option<&'a Path>) -> &mut Self {
+        self.directory = directory;
+        self
+    }
+
+    /// Ensure at least this many lines of surrounding context match for each hunk.
+    pub(crate) fn context_lines(&mut self, context_lines: Option<usize>) -> &mut Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    /// Allow up to this many lines of each hunk's context to not match.
+    ///
+    /// Since `git apply` has no notion of fuzz, this is translated to a minimum amount of
+    /// matching context based on the widest context found in the patch's hunks.
+    pub(crate) fn fuzz(&mut self, fuzz: Option<usize>) -> &mut Self {
+        self.fuzz = fuzz;
+        self
+    }
+
+    /// Ignore changes in whitespace in context lines.
+    pub(crate) fn ignore_whitespace(&mut self, ignore: bool) -> &mut Self {
+        self.ignore_whitespace = ignore;
+        self
+    }
+
+    /// Handle whitespace errors in added lines according to a `git apply --whitespace`
+    /// mode.
+    ///
+    /// When no mode is given, `git apply` follows the `apply.whitespace` configuration.
+    pub(crate) fn whitespace(&mut self, mode: Option<&'a str>) -> &mut Self {
+        self.whitespace = mode;
+        self
+    }
+
+    /// Skip hunks that are already applied instead of failing to apply them.
+    ///
+    /// A hunk is already applied when it applies in reverse but not forward, as with
+    /// the "previously applied patch" detection of GNU patch.
+    pub(crate) fn skip_applied(&mut self, skip: bool) -> &mut Self {
+        self.skip_applied = skip;
+        self
+    }
+
+    /// Determine the `-C` argument, if any, for applying the given diff.
+    pub(super) fn effective_context_lines(&self, diff: &[u8]) -> Option<usize> {
+        if let Some(fuzz) = self.fuzz {
+            Some(max_hunk_context(diff).saturating_sub(fuzz))
+        } else {
+            self.context_lines
+        }
+    }
+}
+
+/// Outcome of successfully applying a patch with `git apply`.
+pub(crate) struct ApplyReport {
+    /// Descriptions of hunks that applied at an offset or with reduced context.
+    pub(crate) adjusted_hunks: Vec<String>,
+
+    /// Output of `git apply` when some hunks were rejected.
+    pub(crate) rejects: Option<String>,
+
+    /// Descriptions of whitespace errors found, or fixed, in the added lines.
+    pub(crate) whitespace: Vec<String>,
+
+    /// Count of the already applied hunks skipped in each file.
+    pub(crate) skipped_hunks: Vec<String>,
+}
+
+/// A file's part of a diff, with its header and hunks.
+pub(super) struct DiffFile<'a> {
+    /// Path of the file, with leading components removed per the strip level.
+    pub(super) path: String,
+
+    /// Header lines preceding the first hunk, e.g. the "---" and "+++" lines.
+    pub(super) header: &'a [u8],
+
+    /// Hunks, each starting with its "@@" line.
+    pub(super) hunks: Vec<&'a [u8]>,
+}
+
+/// Split a diff into the text preceding the first file and the diff of each file.
+///
+/// The lines following a hunk that are not part of it, such as an email signature, are
+/// kept with the hunk. Files without hunks, such as binary files, have only a header.
+pub(super) fn split_diff_files(diff: &[u8], strip_level: usize) -> (&[u8], Vec<DiffFile<'_>>) {
+    struct Ranges {
+        start: usize,
+        header_end: Option<usize>,
+        hunks: Vec<(usize, usize)>,
+        old_path: Option<String>,
+        new_path: Option<String>,
+    }
+
+    let strip = |path: &[u8]| -> String {
+        let path = path.to_str_lossy();
+        let path = path.trim_end().split('\t').next().unwrap_or_default();
+        if path == "/dev/null" {
+            return path.to_string();
+        }
+        path.splitn(strip_level + 1, '/')
+            .last()
+            .unwrap_or(path)
+            .to_string()
+    };
+
+    let mut preamble_end = diff.len();
+    let mut files: Vec<Ranges> = Vec::new();
+    let mut old_remaining = 0isize;
+    let mut new_remaining = 0isize;
+    let mut offset = 0;
+
+    for line in diff.lines_with_terminator() {
+        let start = offset;
+        offset += line.len();
+        let current = files.last_mut();
+
+        if old_remaining > 0 || new_remaining > 0 {
+            match line.first() {
+                Some(b'+') => new_remaining -= 1,
+                Some(b'-') => old_remaining -= 1,
+                Some(b'\\') => {}
+                _ => {
+                    old_remaining -= 1;
+                    new_remaining -= 1;
+                }
+            }
+            if let Some(hunk) = current.and_then(|file| file.hunks.last_mut()) {
+                hunk.1 = offset;
+            }
+            continue;
+        }
+
+        let in_hunks = current.as_ref().is_some_and(|file| !file.hunks.is_empty());
+        if line.starts_with(b"diff ")
+            || (line.starts_with(b"--- ") && (current.is_none() || in_hunks))
+        {
+            if files.is_empty() {
+                preamble_end = start;
+            }
+            files.push(Ranges {
+                start,
+                header_end: None,
+                hunks: Vec::new(),
+                old_path: None,
+                new_path: None,
+            });
+            if let Some(path) = line.strip_prefix(b"--- ") {
+                files.last_mut().unwrap().old_path = Some(strip(path));
+            }
+        } else if let Some(file) = current {
+            if let Some(ranges) = line.strip_prefix(b"@@ -") {
+                let ranges = ranges.to_str_lossy();
+                let mut parts = ranges.split(' ');
+                let count = |range: &str| -> isize {
+                    range
+                        .split_once(',')
+                        .map_or(Some(1), |(_, count)| count.parse().ok())
+                        .unwrap_or(0)
+                };
+                old_remaining = count(parts.next().unwrap_or_default());
+                new_remaining = count(parts.next().unwrap_or_default());
+                file.header_end.get_or_insert(start);
+                file.hunks.push((start, offset));
+            } else if let Some(hunk) = file.hunks.last_mut() {
+                hunk.1 = offset;
+            } else if let Some(path) = line.strip_prefix(b"--- ") {
+                file.old_path = Some(strip(path));
+            } else if let Some(path) = line.strip_prefix(b"+++ ") {
+                file.new_path = Some(strip(path));
+            }
+        }
+    }
+
+    let ends: Vec<usize> = files
+        .iter()
+        .skip(1)
+        .map(|file| file.start)
+        .chain(std::iter::once(diff.len()))
+        .collect();
+    let files = files
+        .into_iter()
+        .zip(ends)
+        .map(|(file, end)| {
+            let path = file
+                .new_path
+                .filter(|path| path != "/dev/null")
+                .or(file.old_path)
+                .unwrap_or_default();
+            DiffFile {
+                path,
+                header: &diff[file.start..file.header_end.unwrap_or(end)],
+                hunks: file
+                    .hunks
+                    .iter()
+                    .map(|&(start, end)| &diff[start..end])
+                    .collect(),
+            }
+        })
+        .collect();
+    (&diff[..preamble_end], files)
+}
+
+/// Find the most lines of leading or trailing context of any hunk in a diff.
+fn max_hunk_context(diff: &[u8]) -> usize {
+    let mut max_context = 0;
+    let mut in_hunk = false;
+    let mut leading: Option<usize> = None;
+    let mut trailing = 0;
+
+    for line in diff.lines() {
+        if line.starts_with(b"@@") {
+            max_context = max_context.max(leading.unwrap_or(0)).max(trailing);
+            in_hunk = true;
+            leading = None;
+            trailing = 0;
+        } else if !in_hunk {
+            continue;
+        } else if line.starts_with(b" ") || line.is_empty() {
+            trailing += 1;
+        } else if line.starts_with(b"+") || line.starts_with(b"-") {
+            leading.get_or_insert(trailing);
+            trailing = 0;
+        } else if line.starts_with(b"\\") {
+            continue;
+        } else {
+            max_context = max_context.max(leading.unwrap_or(0)).max(trailing);
+            in_hunk = false;
+            leading = None;
+            trailing = 0;
+        }
+    }
+    max_context.max(leading.unwrap_or(0)).max(trailing)
+}
+
+/// Test whether any hunk of a diff has no context lines at all.
+///
+/// Such hunks, as generated with `--unified=0`, are refused by `git apply` unless
+/// `--unidiff-zero` is given, since their position in the file cannot be verified.
+pub(super) fn has_zero_context_hunk(diff: &[u8]) -> bool {
+    let mut in_hunk = false;
+    let mut has_context = true;
+
+    for line in diff.lines() {
+        if line.starts_with(b"@@") {
+            if !has_context {
+                return true;
+            }
+            in_hunk = true;
+            has_context = false;
+        } else if !in_hunk {
+            continue;
+        } else if line.starts_with(b" ") || line.is_empty() {
+            has_context = true;
+        } else if line.starts_with(b"+") || line.starts_with(b"-") || line.starts_with(b"\\") {
+            continue;
+        } else {
+            if !has_context {
+                return true;
+            }
+            in_hunk = false;
+            has_context = true;
+        }
+    }
+    !has_context
+}
+
+/// Filter verbose `git apply` output down to the lines describing hunk application.
+///
+/// The per-file progress lines are dropped and the file name is prefixed to each hunk
+/// report so that the output resembles that of GNU patch.
+pub(super) fn hunk_reports(stderr: &[u8]) -> Vec<String> {
+    let mut reports = Vec::new();
+    let mut current_file = None;
+    for line in stderr.lines() {
+        let line = line.to_str_lossy();
+        if let Some(path) = line
+            .strip_prefix("Checking patch ")
+            .and_then(|rest| rest.strip_suffix("..."))
+        {
+            current_file = Some(path.to_string());
+        } else if line.starts_with("Hunk #") || line.starts_with("Context reduced to ") {
+            if let Some(file) = current_file.as_ref() {
+                reports.push(format!("{file}: {line}"));
+            } else {
+                reports.push(line.to_string());
+            }
+        }
+    }
+    reports
+}
+
+/// Describe the whitespace errors reported by `git apply` in terms of the patched files.
+///
+/// `git apply` reports each whitespace error by its line number in the diff. These are
+/// translated to the path and line number of the patched file. When the errors were
+/// fixed, the number of fixed lines in each file is reported instead. The summary
+/// lines output by `git apply`, such as the count of squelched errors, are retained.
+///
+/// Since `git apply` stops reporting individual errors after the first few, the
+/// per-file counts of fixed lines are reported as lower bounds when errors were
+/// squelched. The total number of fixed lines is given by the retained summary.
+pub(super) fn whitespace_reports(diff: &[u8], stderr: &[u8], strip_level: usize) -> Vec<String> {
+    let (paths, locations) = added_line_locations(diff, strip_level);
+    let mut errors: Vec<(Option<usize>, String)> = Vec::new();
+    let mut summary = Vec::new();
+    let mut fixed = false;
+    let mut squelched = false;
+
+    let mut lines = stderr.lines();
+    while let Some(line) = lines.next() {
+        let line = line.to_str_lossy();
+        if let Some((diff_line, problem)) = line
+            .strip_prefix("<stdin>:")
+            .and_then(|rest| rest.split_once(": "))
+            .and_then(|(num, problem)| num.parse::<usize>().ok().map(|num| (num, problem)))
+        {
+            // The offending line is echoed on the following line.
+            lines.next();
+            let problem = problem.strip_suffix('.').unwrap_or(problem);
+            if let Some(&(path_index, line_num)) = locations.get(&diff_line) {
+                errors.push((
+                    Some(path_index),
+                    format!("{}:{line_num}: {problem}", paths[path_index]),
+                ));
+            } else {
+                errors.push((None, format!("line {diff_line} of diff: {problem}")));
+            }
+        } else if let Some(warning) = line.strip_prefix("warning: ") {
+            if warning.contains("whitespace error") {
+                fixed |= warning.contains("after fixing");
+                squelched |= warning.starts_with("squelched");
+                summary.push(warning.strip_suffix('.').unwrap_or(warning).to_string());
+            }
+        }
+    }
+
+    let mut reports = if fixed {
+        let mut counts: Vec<usize> = vec![0; paths.len()];
+        for (path_index, _) in &errors {
+            if let Some(path_index) = path_index {
+                counts[*path_index] += 1;
+            }
+        }
+        paths
+            .iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|(path, count)| {
+                let plural = if count == 1 { "" } else { "s" };
+                let at_least = if squelched { "at least " } else { "" };
+                format!("{path}: fixed whitespace errors in {at_least}{count} line{plural}")
+            })
+            .collect()
+    } else {
+        errors
+            .into_iter()
+            .map(|(_, report)| report)
+            .collect::<Vec<_>>()
+    };
+    reports.extend(summary);
+    reports
+}
+
+/// Map the line numbers of a diff's added lines to their locations in the new files.
+///
+/// The returned map values are an index into the returned paths and the line number
+/// in that file.
+fn added_line_locations(
+    diff: &[u8],
+    strip_level: usize,
+) -> (Vec<String>, HashMap<usize, (usize, usize)>) {
+    let mut paths: Vec<String> = Vec::new();
+    let mut locations = HashMap::new();
+    let mut old_remaining = 0;
+    let mut new_remaining = 0;
+    let mut new_line = 0;
+
+    for (i, line) in diff.lines().enumerate() {
+        let diff_line = i + 1;
+        if old_remaining > 0 || new_remaining > 0 {
+            match line.first() {
+                Some(b'+') => {
+                    if !paths.is_empty() {
+                        locations.insert(diff_line, (paths.len() - 1, new_line));
+                    }
+                    new_line += 1;
+                    new_remaining -= 1;
+                }
+                Some(b'-') => old_remaining -= 1,
+                Some(b'\\') => {}
+                _ => {
+                    new_line += 1;
+                    old_remaining -= 1;
+                    new_remaining -= 1;
+                }
+            }
+            old_remaining = old_remaining.max(0);
+            new_remaining = new_remaining.max(0);
+        } else if let Some(path) = line.strip_prefix(b"+++ ") {
+            let path = path.to_str_lossy();
+            let path = path.split('\t').next().unwrap_or_default();
+            let path = path.splitn(strip_level + 1, '/').last().unwrap_or(path);
+            paths.push(path.to_string());
+        } else if let Some(ranges) = line.strip_prefix(b"@@ -") {
+            let ranges = ranges.to_str_lossy();
+            let mut parts = ranges.split(' ');
+            let count = |range: &str| -> (isize, isize) {
+                let mut fields = range.splitn(2, ',');
+                let start = fields.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                let count = fields.next().and_then(|s| s.parse().ok()).unwrap_or(1);
+                (start, count)
+            };
+            let (_, old_count) = count(parts.next().unwrap_or_default());
+            let (new_start, new_count) =
+                count(parts.next().unwrap_or_default().trim_start_matches('+'));
+            old_remaining = old_count;
+            new_remaining = new_count;
+            new_line = new_start.max(0) as usize;
+        }
+    }
+    (paths, locations)
+}
+
+/// Remove verbose progress lines from failed `git apply` output.
+pub(super) fn strip_progress(stderr: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(stderr.len());
+    for line in stderr.lines_with_terminator() {
+        if !(line.starts_with(b"Checking patch ")
+            || (line.starts_with(b"Applied patch ") && line.trim_end().ends_with(b"cleanly.")))
+        {
+            stripped.extend_from_slice(line);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        has_zero_context_hunk, hunk_reports, max_hunk_context, split_diff_files, strip_progress,
+        whitespace_reports,
+    };
+
+    #[test]
+    fn hunk_context() {
+        let diff = b"\
+diff --git a/foo.txt b/foo.txt
+--- a/foo.txt
++++ b/foo.txt
+@@ -1,4 +1,4 @@
+ a
+-b
++B
+ c
+ d
+@@ -10,5 +10,5 @@ heading
+ j
+ k
+ l
+-m
++M
+ n
+diff --git a/bar.txt b/bar.txt
+--- a/bar.txt
++++ b/bar.txt
+@@ -1 +1 @@
+-x
++y
+";
+        assert_eq!(max_hunk_context(diff), 3);
+        assert_eq!(max_hunk_context(b"@@ -1 +1 @@\n-x\n+y\n"), 0);
+    }
+
+    #[test]
+    fn diff_files() {
+        let diff = b"\
+Preamble
+diff --git a/foo.txt b/foo.txt
+--- a/foo.txt
++++ b/foo.txt
+@@ -1,3 +1,3 @@
+ a
+--- b
++B
+ c
+@@ -10 +10 @@
+-j
+\\ No newline at end of file
++J
+diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++new
+-- 
+2.39.5
+diff --git a/image.png b/image.png
+Binary files a/image.png and b/image.png differ
+";
+        let (preamble, files) = split_diff_files(diff, 1);
+        assert_eq!(preamble, b"Preamble\n");
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "foo.txt");
+        assert_eq!(
+            files[0].header,
+            b"diff --git a/foo.txt b/foo.txt\n--- a/foo.txt\n+++ b/foo.txt\n"
+        );
+        assert_eq!(files[0].hunks.len(), 2);
+        assert_eq!(files[0].hunks[0], b"@@ -1,3 +1,3 @@\n a\n--- b\n+B\n c\n");
+        assert_eq!(
+            files[0].hunks[1],
+            b"@@ -10 +10 @@\n-j\n\\ No newline at end of file\n+J\n"
+        );
+        assert_eq!(files[1].path, "new.txt");
+        assert_eq!(files[1].hunks, [&b"@@ -0,0 +1 @@\n+new\n-- \n2.39.5\n"[..]]);
+        assert_eq!(files[2].path, "");
+        assert!(files[2].hunks.is_empty());
+
+        let (preamble, files) = split_diff_files(b"--- a.c\n+++ b.c\n@@ -1 +1 @@\n-a\n+b\n", 0);
+        assert!(preamble.is_empty());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "b.c");
+    }
+
+    #[test]
+    fn zero_context_hunks() {
+        let with_context = b"\
+diff --git a/foo.txt b/foo.txt
+--- a/foo.txt
++++ b/foo.txt
+@@ -1,2 +1,2 @@
+ a
+-b
++B
+";
+        assert!(!has_zero_context_hunk(with_context));
+
+        let zero_context = b"\
+diff --git a/foo.txt b/foo.txt
+--- a/foo.txt
++++ b/foo.txt
+@@ -1,2 +1,2 @@
+ a
+-b
++B
+diff --git a/bar.txt b/bar.txt
+--- a/bar.txt
++++ b/bar.txt
+@@ -5 +5 @@
+-x
+\\ No newline at end of file
++y
+diff --git a/baz.txt b/baz.txt
+";
+        assert!(has_zero_context_hunk(zero_context));
+        assert!(has_zero_context_hunk(b"@@ -3 +3 @@\n-x\n+y\n"));
+        assert!(!has_zero_context_hunk(b""));
+    }
+
+    #[test]
+    fn verbose_apply_output() {
+        let stderr = b"\
+Checking patch foo.txt...
+Hunk #1 succeeded at 3 (offset 2 lines).
+Context reduced to (2/2) to apply fragment at 12
+Checking patch bar.txt...
+Applied patch foo.txt cleanly.
+Applied patch bar.txt cleanly.
+";
+        assert_eq!(
+            hunk_reports(stderr),
+            vec![
+                "foo.txt: Hunk #1 succeeded at 3 (offset 2 lines).".to_string(),
+                "foo.txt: Context reduced to (2/2) to apply fragment at 12".to_string(),
+            ]
+        );
+        assert_eq!(
+            strip_progress(stderr),
+            b"Hunk #1 succeeded at 3 (offset 2 lines).\n\
+              Context reduced to (2/2) to apply fragment at 12\n"
+        );
+    }
+
+    #[test]
+    fn whitespace_errors() {
+        let diff = b"\
+diff --git a/foo.txt b/foo.txt
+--- a/foo.txt
++++ b/foo.txt
+@@ -1,2 +1,3 @@
+ a
+-b
++b 
++c
+@@ -10,2 +10,2 @@
+ j
+-k
++ \tk
+diff --git a/dir/bar.txt b/dir/bar.txt
+--- a/dir/bar.txt
++++ b/dir/bar.txt
+@@ -0,0 +1 @@
++x\t
+";
+        let warned = b"\
+<stdin>:7: trailing whitespace.
+b 
+<stdin>:12: space before tab in indent.
+ \tk
+<stdin>:17: trailing whitespace.
+x\t
+warning: 3 lines add whitespace errors.
+";
+        assert_eq!(
+            whitespace_reports(diff, warned, 1),
+            vec![
+                "foo.txt:2: trailing whitespace",
+                "foo.txt:11: space before tab in indent",
+                "dir/bar.txt:1: trailing whitespace",
+                "3 lines add whitespace errors",
+            ]
+        );
+
+        let fixed = b"\
+<stdin>:7: trailing whitespace.
+b 
+<stdin>:12: space before tab in indent.
+ \tk
+<stdin>:17: trailing whitespace.
+x\t
+warning: 3 lines applied after fixing whitespace errors.
+";
+        assert_eq!(
+            whitespace_reports(diff, fixed, 1),
+            vec![
+                "foo.txt: fixed whitespace errors in 2 lines",
+                "dir/bar.txt: fixed whitespace errors in 1 line",
+                "3 lines applied after fixing whitespace errors",
+            ]
+        );
+
+        let squelched = b"\
+<stdin>:7: trailing whitespace.
+b 
+<stdin>:12: space before tab in indent.
+ \tk
+warning: squelched 1 whitespace error
+warning: 3 lines applied after fixing whitespace errors.
+";
+        assert_eq!(
+            whitespace_reports(diff, squelched, 1),
+            vec![
+                "foo.txt: fixed whitespace errors in at least 2 lines",
+                "squelched 1 whitespace error",
+                "3 lines applied after fixing whitespace errors",
+            ]
+        );
+
+        assert!(whitespace_reports(diff, b"Checking patch foo.txt...\n", 1).is_empty());
+    }
+}
@@ -3,13 +3,19 @@
 //! Traits to extend [`std::process::Command`] for running `git`.
 
 use std::{
-    io::Write,
+    ffi::OsStr,
+    io::{Read, Write},
+    path::{Path, PathBuf},
     process::{Child, Command, ExitStatus, Output, Stdio},
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
 
+use super::program::{index_locking, IndexLocking};
+use crate::ext::index_locked_error;
+
 const GIT_EXEC_FAIL: &str = "could not execute `git`";
 
 pub(super) trait StupidCommand {
@@ -20,7 +26,8 @@ pub(super) trait StupidCommand {
 
     /// Run git command, wait for completion, and collect output streams.
     ///
-    /// By default, stdout and stderr are piped and stdin is null.
+    /// By default, stdout and stderr are piped and stdin is null. The command is run
+    /// again while it fails to lock the index; see [`retry_while_index_locked()`].
     fn output_git(&mut self) -> Result<Output>;
 
     /// Write input to child process and gather its output.
@@ -29,8 +36,15 @@ pub(super) trait StupidCommand {
     /// that can occur if the child process's input buffer is filled without
     /// concurrently reading from the child's stdout and stderr.
     ///
-    /// By default, stdout is inherited. Stdin and stderr are piped.
+    /// By default, stdout is inherited. Stdin and stderr are piped. The command is run
+    /// again while it fails to lock the index; see [`retry_while_index_locked()`].
     fn in_and_out(&mut self, input: &[u8]) -> Result<Output>;
+
+    /// Run git command, copying its stdout to this process's stdout as it is produced.
+    ///
+    /// The copied stdout is also collected in the returned output. Stderr is piped
+    /// and read from a separate thread.
+    fn tee_output_git(&mut self) -> Result<Output>;
 }
 
 impl StupidCommand for Command {
@@ -39,24 +53,141 @@ impl StupidCommand for Command {
     }
 
     fn output_git(&mut self) -> Result<Output> {
-        self.output().context(GIT_EXEC_FAIL)
+        let lock_path = index_lock_path(self);
+        retry_while_index_locked(lock_path, || self.output().context(GIT_EXEC_FAIL))
     }
 
     fn in_and_out(&mut self, input: &[u8]) -> Result<Output> {
-        let mut child = self.stdin(Stdio::piped()).spawn_git()?;
-        let mut stdin = child.stdin.take().unwrap();
-        let output_result: Result<Output> = std::thread::scope(|scope| {
-            let handle = scope.spawn(move || -> Result<()> { Ok(stdin.write_all(input)?) });
-            let output_result = child.wait_with_output();
-            handle
+        let lock_path = index_lock_path(self);
+        retry_while_index_locked(lock_path, || {
+            let mut child = self.stdin(Stdio::piped()).spawn_git()?;
+            let mut stdin = child.stdin.take().unwrap();
+            std::thread::scope(|scope| {
+                let handle = scope.spawn(move || -> Result<()> { Ok(stdin.write_all(input)?) });
+                let output = child.wait_with_output()?;
+                let write_result = handle
+                    .join()
+                    .map_err(|_| anyhow!("panic while writing to stdin"))?;
+                // A failing command, e.g. one unable to lock the index, may exit without
+                // reading all of its input.
+                if output.status.success() {
+                    write_result?;
+                }
+                Ok(output)
+            })
+        })
+    }
+
+    fn tee_output_git(&mut self) -> Result<Output> {
+        let mut child = self.stdout(Stdio::piped()).spawn_git()?;
+        let mut child_stdout = child.stdout.take().unwrap();
+        let mut child_stderr = child.stderr.take().unwrap();
+        std::thread::scope(|scope| {
+            let handle = scope.spawn(move || -> Result<Vec<u8>> {
+                let mut stderr = Vec::new();
+                child_stderr.read_to_end(&mut stderr)?;
+                Ok(stderr)
+            });
+            let mut stdout = Vec::new();
+            let mut buf = [0u8; 4096];
+            let mut out = std::io::stdout();
+            loop {
+                let n = child_stdout.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                out.write_all(&buf[..n])?;
+                out.flush()?;
+                stdout.extend_from_slice(&buf[..n]);
+            }
+            let status = child.wait()?;
+            let stderr = handle
                 .join()
-                .map_err(|_| anyhow!("panic while writing to stdin"))??;
-            Ok(output_result?)
-        });
-        output_result
+                .map_err(|_| anyhow!("panic while reading from stderr"))??;
+            Ok(Output {
+                status,
+                stdout,
+                stderr,
+            })
+        })
     }
 }
 
+/// Run a git command, running it again while it fails because the index is locked.
+///
+/// Git gives up immediately when another process holds the index lock. With
+/// [`IndexLocking::Wait`], the command is retried until the lock is released or the
+/// timeout elapses. A command still unable to lock the index fails with an error naming
+/// the lock holder.
+///
+/// Since git's error messages may be translated, a failure is attributed to the index
+/// lock when the `lock_path` file exists and git's error output names it.
+fn retry_while_index_locked(
+    lock_path: Option<PathBuf>,
+    mut run: impl FnMut() -> Result<Output>,
+) -> Result<Output> {
+    let timeout = match index_locking() {
+        Some(IndexLocking::Wait(timeout)) => timeout,
+        _ => Duration::ZERO,
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        let output = run()?;
+        if output.status.success() {
+            return Ok(output);
+        }
+        let Some(lock_path) = lock_path
+            .as_deref()
+            .filter(|lock_path| is_index_locked(lock_path, &output.stderr))
+        else {
+            return Ok(output);
+        };
+        if Instant::now() >= deadline {
+            return Err(index_locked_error(lock_path));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Get the path of the lock file of the index used by the git command.
+///
+/// The index is that of the command's `GIT_INDEX_FILE` or `GIT_DIR` environment
+/// variables, falling back to those of this process.
+fn index_lock_path(command: &Command) -> Option<PathBuf> {
+    let env_var = |name: &str| {
+        command
+            .get_envs()
+            .find(|(key, _)| *key == name)
+            .map_or_else(
+                || std::env::var_os(name),
+                |(_, value)| value.map(OsStr::to_os_string),
+            )
+    };
+    let index_path = if let Some(index_path) = env_var("GIT_INDEX_FILE") {
+        PathBuf::from(index_path)
+    } else {
+        PathBuf::from(env_var("GIT_DIR")?).join("index")
+    };
+    let index_path = match command.get_current_dir() {
+        Some(current_dir) if index_path.is_relative() => current_dir.join(index_path),
+        _ => index_path,
+    };
+    let mut lock_name = index_path.file_name()?.to_os_string();
+    lock_name.push(".lock");
+    Some(index_path.with_file_name(lock_name))
+}
+
+/// Determine whether a git command failed because the index is locked.
+///
+/// The index is locked when the lock file exists. Git's error output, in whatever
+/// language, names the lock file when the command failed due to the lock.
+fn is_index_locked(lock_path: &Path, stderr: &[u8]) -> bool {
+    lock_path.exists()
+        && lock_path.file_name().is_some_and(|lock_name| {
+            stderr.contains_str(gix::path::into_bstr(Path::new(lock_name)).as_ref())
+        })
+}
+
 pub(super) trait StupidOutput {
     /// Ensure that Child or Output is successful, returning Output.
     fn require_success(self, command: &str) -> Result<Output>;
@@ -103,6 +234,15 @@ pub(super) fn git_command_error(command: &str, stderr: &[u8]) -> anyhow::Error {
     anyhow!(err_str.to_string()).context(format!("`git {command}`"))
 }
 
+/// Write the stdout of a git command that is only commentary, such as that of `git
+/// stash`, to the StGit command's commentary stream.
+pub(super) fn write_commentary(stdout: &[u8]) -> Result<()> {
+    let mut commentary = crate::output::Output::current().commentary();
+    commentary.write_all(stdout)?;
+    commentary.flush()?;
+    Ok(())
+}
+
 pub(super) trait StupidExitStatus {
     /// Test whether command exit was due to a particular signal.
     fn is_signal(&self, signum: i32) -> bool;
@@ -120,3 +260,41 @@ impl StupidExitStatus for ExitStatus {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_index_lock_path() {
+        let mut command = Command::new("git");
+        command.env("GIT_DIR", "/repo/.git");
+        assert_eq!(
+            index_lock_path(&command),
+            Some(PathBuf::from("/repo/.git/index.lock"))
+        );
+        command.env("GIT_INDEX_FILE", "tmp-index");
+        command.current_dir("/repo");
+        assert_eq!(
+            index_lock_path(&command),
+            Some(PathBuf::from("/repo/tmp-index.lock"))
+        );
+    }
+
+    #[test]
+    fn detect_locked_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_path = dir.path().join("index.lock");
+        let stderr = format!(
+            "fatal: Impossible de cr\u{e9}er '{}' : Le fichier existe.",
+            lock_path.display()
+        );
+        assert!(!is_index_locked(&lock_path, stderr.as_bytes()));
+        std::fs::write(&lock_path, b"").unwrap();
+        assert!(is_index_locked(&lock_path, stderr.as_bytes()));
+        assert!(!is_index_locked(
+            &lock_path,
+            b"error: patch failed: a.txt:1"
+        ));
+    }
+}
@@ -2,7 +2,8 @@
 
 //! Context for executing Git commands via the `git` executable.
 //!
-//! It is assumed/required that `git` is in `PATH`.
+//! The `git` executable is found in `PATH` unless another is selected; see
+//! [`super::program`].
 
 use std::{
     cell::RefCell,
@@ -16,9 +17,13 @@ use anyhow::{anyhow, Context, Result};
 use bstr::{BStr, BString, ByteSlice, ByteVec};
 
 use super::{
-    command::{git_command_error, StupidCommand, StupidExitStatus, StupidOutput},
-    diff::DiffFiles,
+    apply::{self, ApplyOptions, ApplyReport},
+    command::{git_command_error, write_commentary, StupidCommand, StupidExitStatus, StupidOutput},
+    diff::{AddedLines, DiffFileStatuses, DiffFiles, DiffFormat, RawDiff},
+    log::LogOptions,
+    merge::MergeOptions,
     oid::parse_oid,
+    program::git_program,
     status::{StatusOptions, Statuses},
     tempindex::TempIndex,
     version::StupidVersion,
@@ -58,13 +63,13 @@ impl StupidContext<'_, '_> {
 
 impl StupidContext<'_, '_> {
     fn git(&self) -> Command {
-        let mut command = Command::new("git");
+        let mut command = git_program().command();
         self.setup_git_env(&mut command);
         command
     }
 
     fn git_in_work_root(&self) -> Result<Command> {
-        let mut command = Command::new("git");
+        let mut command = git_program().command();
         let work_dir = self
             .work_dir
             .expect("work_dir is required for this command");
@@ -112,63 +117,162 @@ impl StupidContext<'_, '_> {
 
 impl StupidContext<'_, '_> {
     /// Apply a patch (diff) to the specified index using `git apply --cached`.
-    pub(crate) fn apply_to_index(&self, diff: &BStr) -> Result<()> {
-        self.git_in_work_root()?
-            .args(["apply", "--cached"]) // TODO: use --recount?
+    ///
+    /// Whitespace errors in the added lines are handled according to the given
+    /// `git apply --whitespace` mode or, without a mode, the `apply.whitespace`
+    /// configuration. Returns descriptions of any whitespace errors found.
+    pub(crate) fn apply_to_index(
+        &self,
+        diff: &BStr,
+        whitespace: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let mut command = self.git_in_work_root()?;
+        command.args(["apply", "--cached"]); // TODO: use --recount?
+        if apply::has_zero_context_hunk(diff) {
+            command.arg("--unidiff-zero");
+        }
+        if let Some(mode) = whitespace {
+            command.arg(format!("--whitespace={mode}"));
+        }
+        let output = command
             .stdout(Stdio::null())
             .in_and_out(diff)?
             .require_success("apply")?;
-        Ok(())
+        Ok(apply::whitespace_reports(diff, &output.stderr, 1))
     }
 
     /// Apply a patch (diff) to both the index and the working tree.
     ///
-    /// Returns `None` if the patch applies cleanly or output of the `git apply`
-    /// command if the patch does not apply and caller indicated that rejects
-    /// are allowed.
+    /// On success, the returned report describes any hunks that did not apply exactly
+    /// and, if the caller indicated that rejects are allowed, the output of the
+    /// `git apply` command when some hunks were rejected.
     pub(crate) fn apply_to_worktree_and_index(
         &self,
         diff: &BStr,
-        reject: bool,
-        threeway: bool,
-        strip_level: Option<usize>,
-        directory: Option<&Path>,
-        context_lines: Option<usize>,
-    ) -> Result<Option<String>> {
+        options: &ApplyOptions,
+    ) -> Result<ApplyReport> {
+        let (diff, skipped_hunks) = if options.skip_applied {
+            self.skip_applied_hunks(diff, options)?
+        } else {
+            (diff.to_owned(), Vec::new())
+        };
+        if options.skip_applied && diff.is_empty() {
+            return Ok(ApplyReport {
+                adjusted_hunks: Vec::new(),
+                rejects: None,
+                whitespace: Vec::new(),
+                skipped_hunks,
+            });
+        }
+        let diff = diff.as_slice();
+
         let mut command = self.git_in_work_root()?;
-        command.args(["apply", "--index"]);
-        if reject {
+        command.args(["apply", "--index", "--verbose"]);
+        if options.reject {
             command.arg("--reject");
         }
-        if threeway {
+        if options.threeway {
             command.arg("--3way");
         }
-        if let Some(strip_level) = strip_level {
-            command.arg(format!("-p{strip_level}"));
-        }
-        if let Some(directory) = directory {
-            command.arg("--directory");
-            command.arg(directory);
-        }
-        if let Some(context_lines) = context_lines {
-            command.arg(format!("-C{context_lines}"));
+        if let Some(mode) = options.whitespace {
+            command.arg(format!("--whitespace={mode}"));
         }
+        add_apply_location_args(&mut command, diff, options);
         let apply_output = command.stdout(Stdio::null()).in_and_out(diff)?;
+        let adjusted_hunks = apply::hunk_reports(&apply_output.stderr);
+        let whitespace =
+            apply::whitespace_reports(diff, &apply_output.stderr, options.strip_level.unwrap_or(1));
         if apply_output.status.success() {
-            Ok(None)
+            Ok(ApplyReport {
+                adjusted_hunks,
+                rejects: None,
+                whitespace,
+                skipped_hunks,
+            })
         } else {
-            let err = git_command_error("apply --index", &apply_output.stderr);
-            if reject && apply_output.status.code() == Some(1) {
-                Ok(Some(format!("{err:#}")))
+            let err = git_command_error(
+                "apply --index",
+                &apply::strip_progress(&apply_output.stderr),
+            );
+            if options.reject && apply_output.status.code() == Some(1) {
+                Ok(ApplyReport {
+                    adjusted_hunks,
+                    rejects: Some(format!("{err:#}")),
+                    whitespace,
+                    skipped_hunks,
+                })
             } else {
                 Err(err)
             }
         }
     }
 
+    /// Remove the already applied hunks from a diff.
+    ///
+    /// Each hunk is checked against the working tree on its own. A hunk is already
+    /// applied when it applies in reverse, but not forward. The remaining diff, which is
+    /// empty if no file is left to patch, is returned along with the count of skipped
+    /// hunks for each file with skipped hunks.
+    fn skip_applied_hunks(
+        &self,
+        diff: &BStr,
+        options: &ApplyOptions,
+    ) -> Result<(BString, Vec<String>)> {
+        let (preamble, files) = apply::split_diff_files(diff, options.strip_level.unwrap_or(1));
+        let mut remaining = BString::from(preamble);
+        let mut skipped_hunks = Vec::new();
+        let mut has_files = false;
+        for file in files {
+            let mut kept = Vec::new();
+            for hunk in &file.hunks {
+                let mut hunk_diff = file.header.to_vec();
+                hunk_diff.extend_from_slice(hunk);
+                if self.apply_check(&hunk_diff, options, true)?
+                    && !self.apply_check(&hunk_diff, options, false)?
+                {
+                    continue;
+                }
+                kept.push(*hunk);
+            }
+            let num_skipped = file.hunks.len() - kept.len();
+            if num_skipped > 0 {
+                skipped_hunks.push(format!(
+                    "{}: {num_skipped} of {} hunk{}",
+                    file.path,
+                    file.hunks.len(),
+                    if file.hunks.len() == 1 { "" } else { "s" }
+                ));
+            }
+            if file.hunks.is_empty() || !kept.is_empty() {
+                remaining.push_str(file.header);
+                kept.iter().for_each(|hunk| remaining.push_str(hunk));
+                has_files = true;
+            }
+        }
+        if !has_files {
+            remaining.clear();
+        }
+        Ok((remaining, skipped_hunks))
+    }
+
+    /// Check whether a diff applies to the working tree, forward or in reverse.
+    fn apply_check(&self, diff: &[u8], options: &ApplyOptions, reverse: bool) -> Result<bool> {
+        let mut command = self.git_in_work_root()?;
+        command.args(["apply", "--check"]);
+        if reverse {
+            command.arg("--reverse");
+        }
+        add_apply_location_args(&mut command, diff, options);
+        let output = command
+            .stdout(Stdio::null())
+            .in_and_out(diff)?
+            .require_code_less_than("apply", 128)?;
+        Ok(output.status.success())
+    }
+
     /// Apply diff between two trees to specified index.
     ///
-    /// Pipes `git diff-tree | git apply --cached`.
+    /// The output of `git diff-tree` is applied with `git apply --cached`.
     ///
     /// Returns `true` if the patch application is successful, `false` otherwise.
     pub(crate) fn apply_treediff_to_index(
@@ -180,7 +284,7 @@ impl StupidContext<'_, '_> {
         if tree1 == tree2 {
             return Ok(true);
         }
-        let mut diff_tree_child = self
+        let diff = self
             .git()
             .args(["diff-tree", "--full-index", "--binary", "--patch"])
             .arg(tree1.to_string())
@@ -188,20 +292,21 @@ impl StupidContext<'_, '_> {
             .arg("--")
             .stdin(Stdio::null())
             .stdout(Stdio::piped())
-            .spawn_git()?;
+            .output_git()?
+            .require_success("diff-tree")?
+            .stdout;
 
+        // The diff is collected up front, rather than piped to `git apply`, so that
+        // `git apply` may be run again should it fail to lock the index.
         let mut apply_cmd = self.git_in_work_root()?;
         apply_cmd.args(["apply", "--cached"]);
         if want_3way && self.at_least_version(&StupidVersion::new(2, 32, 0))? {
             apply_cmd.arg("--3way");
         }
         let apply_output = apply_cmd
-            .stdin(diff_tree_child.stdout.take().unwrap())
             .stdout(Stdio::null())
-            .output_git()?
+            .in_and_out(&diff)?
             .require_code_less_than("apply", 128)?;
-
-        diff_tree_child.require_success("diff-tree")?;
         Ok(apply_output.status.success())
     }
 
@@ -338,28 +443,6 @@ impl StupidContext<'_, '_> {
         Ok(())
     }
 
-    /// Move branch
-    ///
-    /// Moves branch ref, moves reflog, updates HEAD, and renames `branch.<name>` config
-    /// sections.
-    pub(crate) fn branch_move(
-        &self,
-        old_branchname: Option<&str>,
-        new_branchname: &str,
-    ) -> Result<()> {
-        let mut command = self.git();
-        command.args(["branch", "--move"]);
-        if let Some(old_branchname) = old_branchname {
-            command.arg(old_branchname);
-        }
-        command.arg(new_branchname);
-        command
-            .stdout(Stdio::null())
-            .output_git()?
-            .require_success("branch --move")?;
-        Ok(())
-    }
-
     /// Checkout a branch.
     pub(crate) fn checkout(&self, branch_name: &str) -> Result<()> {
         self.git()
@@ -372,6 +455,66 @@ impl StupidContext<'_, '_> {
         Ok(())
     }
 
+    /// Look up gitattributes(5) attribute values of paths with `git check-attr`.
+    ///
+    /// Paths must be relative to the work tree root. For each path, the values of
+    /// `attrs` are returned in the same order as `attrs`. Values are as reported by
+    /// `git check-attr`, i.e. "set", "unset", "unspecified", or the attribute's value.
+    pub(crate) fn check_attr(&self, attrs: &[&str], paths: &[&BStr]) -> Result<Vec<Vec<BString>>> {
+        if paths.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut input = Vec::new();
+        for path in paths {
+            input.extend_from_slice(path);
+            input.push(0);
+        }
+        let output = self
+            .git_in_work_root()?
+            .args(["check-attr", "-z", "--stdin"])
+            .args(attrs)
+            .stdout(Stdio::piped())
+            .in_and_out(&input)?
+            .require_success("check-attr")?;
+        let mut fields = output.stdout.split_str("\0");
+        let mut values = Vec::with_capacity(paths.len());
+        for _ in paths {
+            let mut path_values = Vec::with_capacity(attrs.len());
+            for _ in attrs {
+                let (Some(_path), Some(_attr), Some(value)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(anyhow!("unexpected output from `git check-attr`"));
+                };
+                path_values.push(BString::from(value));
+            }
+            values.push(path_values);
+        }
+        Ok(values)
+    }
+
+    /// Map contacts through the repository's mailmap with `git check-mailmap`.
+    ///
+    /// Each contact is of the form "Name <user@host>". The mapped contacts are returned
+    /// in the same order as the input contacts.
+    pub(crate) fn check_mailmap(&self, contacts: &[BString]) -> Result<Vec<BString>> {
+        if contacts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let output = self
+            .git()
+            .arg("check-mailmap")
+            .args(contacts.iter().map(|contact| contact.to_os_str_lossy()))
+            .output_git()?
+            .require_success("check-mailmap")?;
+        let mapped: Vec<BString> = output.stdout.lines().map(BString::from).collect();
+        if mapped.len() == contacts.len() {
+            Ok(mapped)
+        } else {
+            Err(anyhow!("unexpected output from `git check-mailmap`"))
+        }
+    }
+
     /// Create a commit for the specified tree id using `git commit-tree`.
     ///
     /// The newly created commit id is returned.
@@ -439,7 +582,7 @@ impl StupidContext<'_, '_> {
         &self,
         revspec: &str,
         pathspecs: Option<SpecIter>,
-        stat: bool,
+        format: DiffFormat,
         use_color: bool,
         diff_opts: OptIter,
     ) -> Result<()>
@@ -451,8 +594,14 @@ impl StupidContext<'_, '_> {
     {
         let mut command = self.git();
         command.arg("diff");
-        if stat {
-            command.args(["--stat", "--summary"]);
+        match format {
+            DiffFormat::Patch => {}
+            DiffFormat::Stat => {
+                command.args(["--stat", "--summary"]);
+            }
+            DiffFormat::NumStat => {
+                command.arg("--numstat");
+            }
         }
 
         command.arg(if use_color {
@@ -554,6 +703,61 @@ impl StupidContext<'_, '_> {
             .map(|output| DiffFiles::new(output.stdout))
     }
 
+    /// Get names of files that are added to `tree2` relative to `tree1`.
+    pub(crate) fn diff_tree_added_files(
+        &self,
+        tree1: gix::ObjectId,
+        tree2: gix::ObjectId,
+    ) -> Result<DiffFiles> {
+        self.git()
+            .args([
+                "diff-tree",
+                "-r",
+                "--name-only",
+                "--no-renames",
+                "--diff-filter=A",
+                "-z",
+            ])
+            .args([tree1.to_string(), tree2.to_string()])
+            .output_git()?
+            .require_success("diff-tree")
+            .map(|output| DiffFiles::new(output.stdout))
+    }
+
+    /// Get status and names of files that differ between `tree1` and `tree2`.
+    pub(crate) fn diff_tree_name_status(
+        &self,
+        tree1: gix::ObjectId,
+        tree2: gix::ObjectId,
+    ) -> Result<DiffFileStatuses> {
+        self.git()
+            .args(["diff-tree", "-r", "--name-status", "-z"])
+            .args([tree1.to_string(), tree2.to_string()])
+            .output_git()?
+            .require_success("diff-tree")
+            .map(|output| DiffFileStatuses::new(output.stdout))
+    }
+
+    /// Get raw diffs, with blob ids, for several pairs of trees using a single
+    /// `git diff-tree --stdin`.
+    ///
+    /// Renames are not detected such that no blobs need to be read.
+    pub(crate) fn diff_tree_raw(
+        &self,
+        tree_pairs: &[(gix::ObjectId, gix::ObjectId)],
+    ) -> Result<RawDiff> {
+        let mut input = String::new();
+        for (tree1, tree2) in tree_pairs {
+            input.push_str(&format!("{tree1} {tree2}\n"));
+        }
+        self.git()
+            .args(["diff-tree", "--stdin", "-r", "--raw", "--no-renames", "-z"])
+            .stdout(Stdio::piped())
+            .in_and_out(input.as_bytes())?
+            .require_success("diff-tree --stdin")
+            .map(|output| RawDiff::new(output.stdout))
+    }
+
     /// Interactive diff-tree (for 'stg files').
     pub(crate) fn diff_tree_files_status(
         &self,
@@ -614,6 +818,60 @@ impl StupidContext<'_, '_> {
         Ok(BString::from(output.stdout))
     }
 
+    /// Call `f` for each line added between two trees.
+    ///
+    /// The output of `git diff-tree -p` is streamed and parsed line by line. `f` is given
+    /// the path of the file, the line number in `tree2`, and the line's content. Lines of
+    /// binary files are not considered.
+    pub(crate) fn diff_tree_added_lines<F>(
+        &self,
+        tree1: gix::ObjectId,
+        tree2: gix::ObjectId,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&BStr, usize, &BStr),
+    {
+        use std::io::BufRead;
+
+        let mut child = self
+            .git()
+            .args([
+                "-c",
+                "core.quotePath=false",
+                "diff-tree",
+                "-r",
+                "-p",
+                "-U0",
+                "--no-color",
+                "--no-ext-diff",
+                "--no-textconv",
+                "--src-prefix=a/",
+                "--dst-prefix=b/",
+            ])
+            .args([tree1.to_string(), tree2.to_string()])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn_git()?;
+
+        let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+        let mut parser = AddedLines::default();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if stdout.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            if let Some((path, line_number, added)) = parser.parse(content) {
+                f(path, line_number, added);
+            }
+        }
+        drop(stdout);
+        child.wait_with_output()?.require_success("diff-tree")?;
+        Ok(())
+    }
+
     /// Get unmerged path list using `git diff --name-only --diff-filter=U`.
     ///
     /// The returned unmerged paths are relative to the work tree root regardless of the
@@ -634,8 +892,22 @@ impl StupidContext<'_, '_> {
         Ok(paths)
     }
 
+    /// Get unmerged index entries using `git ls-files --unmerged`.
+    ///
+    /// The raw, NUL-terminated records are returned. Paths are relative to the work tree
+    /// root regardless of the current working dir.
+    pub(crate) fn ls_files_unmerged(&self) -> Result<BString> {
+        let output = self
+            .git_in_work_root()?
+            .args(["ls-files", "--unmerged", "-z"])
+            .output_git()?
+            .require_success("ls-files --unmerged")?;
+        Ok(output.stdout.into())
+    }
+
     /// Run `git format-patch` with arbitrary arguments.
-    pub(crate) fn format_patch<OptIter, OptArg>(&self, args: OptIter) -> Result<()>
+    /// Run `git format-patch`, returning its output after copying it to stdout.
+    pub(crate) fn format_patch<OptIter, OptArg>(&self, args: OptIter) -> Result<Vec<u8>>
     where
         OptIter: IntoIterator<Item = OptArg>,
         OptArg: AsRef<OsStr>,
@@ -643,12 +915,11 @@ impl StupidContext<'_, '_> {
         let mut command = self.git();
         command.arg("format-patch");
         command.args(args);
-        command
+        let output = command
             .stdin(Stdio::inherit())
-            .stdout(Stdio::inherit())
-            .output_git()?
+            .tee_output_git()?
             .require_success("format-patch")?;
-        Ok(())
+        Ok(output.stdout)
     }
 
     /// Show log in `gitk`
@@ -663,6 +934,7 @@ impl StupidContext<'_, '_> {
     {
         let mut command = Command::new("gitk");
         self.setup_git_env(&mut command);
+        git_program().setup_child_env(&mut command);
         command.arg(commit_id.to_string());
         if let Some(pathspecs) = pathspecs {
             command.arg("--");
@@ -682,6 +954,58 @@ impl StupidContext<'_, '_> {
         }
     }
 
+    /// Find the lines matching a pattern using `git grep --no-index`.
+    ///
+    /// Each newline-terminated line of `lines` is matched independently and the
+    /// zero-based indexes of the matching lines are returned. The pattern is a basic
+    /// regular expression, as with `git grep`, unless `fixed_strings` is set.
+    pub(crate) fn grep_lines(
+        &self,
+        lines: &[u8],
+        pattern: &str,
+        ignore_case: bool,
+        fixed_strings: bool,
+    ) -> Result<Vec<usize>> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("lines"), lines)?;
+        let mut command = git_program().command();
+        command.current_dir(dir.path()).args([
+            "grep",
+            "--no-index",
+            "--no-color",
+            "--text",
+            "-h",
+            "-n",
+        ]);
+        if ignore_case {
+            command.arg("--ignore-case");
+        }
+        if fixed_strings {
+            command.arg("--fixed-strings");
+        }
+        let output = command
+            .arg("-e")
+            .arg(pattern)
+            .args(["--", "lines"])
+            .stdin(Stdio::null())
+            .output_git()?
+            .require_code_less_than("grep", 2)?;
+        output
+            .stdout
+            .lines()
+            .map(|line| {
+                line.split_str(":")
+                    .next()
+                    .and_then(|n| n.to_str().ok())
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .and_then(|n| n.checked_sub(1))
+                    .ok_or_else(|| {
+                        anyhow!("unexpected `git grep` output line `{}`", line.as_bstr())
+                    })
+            })
+            .collect()
+    }
+
     /// Add trailers to commit message with `git interpret-trailers`.
     pub(crate) fn interpret_trailers<'a>(
         &self,
@@ -706,20 +1030,30 @@ impl StupidContext<'_, '_> {
         &self,
         commit_id: gix::ObjectId,
         pathspecs: Option<SpecIter>,
-        num_commits: Option<usize>,
+        opts: &LogOptions,
         use_color: bool,
-        full_index: bool,
+        pretty_format: Option<&str>,
         show_diff: bool,
     ) -> Result<()>
     where
         SpecIter: IntoIterator<Item = SpecArg>,
         SpecArg: AsRef<OsStr>,
     {
-        let mut command = self.git_in_work_root()?;
+        let mut command = if self.work_dir.is_some() {
+            self.git_in_work_root()?
+        } else {
+            self.git()
+        };
         command.arg("log");
-        if let Some(n) = num_commits {
+        if let Some(n) = opts.max_count {
             command.arg(format!("-{n}"));
         }
+        if let Some(since) = opts.since {
+            command.arg(format!("--max-age={}", since.seconds));
+        }
+        if let Some(until) = opts.until {
+            command.arg(format!("--min-age={}", until.seconds));
+        }
         command.arg(if use_color {
             "--color=always"
         } else {
@@ -727,8 +1061,8 @@ impl StupidContext<'_, '_> {
         });
         if show_diff {
             command.arg("-p");
-        } else if !full_index {
-            command.arg("--pretty=tformat:%C(auto)%h   %C(auto,blue)%aD   %C(auto)%s");
+        } else if let Some(pretty_format) = pretty_format {
+            command.arg(format!("--pretty=tformat:{pretty_format}"));
         }
         command.arg(commit_id.to_string());
         if let Some(pathspecs) = pathspecs {
@@ -750,9 +1084,11 @@ impl StupidContext<'_, '_> {
         &self,
         input: Option<std::fs::File>,
         copy_message_id: bool,
+        reencode: bool,
     ) -> Result<(BString, BString, BString)> {
         let mut command = self.git();
-        command.args(["mailinfo", "--scissors", "--encoding=UTF-8"]);
+        command.args(["mailinfo", "--scissors"]);
+        command.arg(if reencode { "--encoding=UTF-8" } else { "-n" });
         if copy_message_id {
             command.arg("--message-id");
         }
@@ -831,36 +1167,187 @@ impl StupidContext<'_, '_> {
         Ok(oids)
     }
 
+    /// Perform rename-aware three-way merge of trees with `git merge-tree`.
+    ///
+    /// The merge is performed without touching the index or work tree. The merged tree
+    /// id is returned if the merge is clean. `None` is returned if there are conflicts
+    /// or if the git version is too old to support `git merge-tree --write-tree`.
+    pub(crate) fn merge_trees(
+        &self,
+        base_tree_id: gix::ObjectId,
+        our_tree_id: gix::ObjectId,
+        their_tree_id: gix::ObjectId,
+    ) -> Result<Option<gix::ObjectId>> {
+        Ok(self
+            .merge_trees_with_conflicts(base_tree_id, our_tree_id, their_tree_id)?
+            .and_then(|(tree_id, conflicts)| conflicts.is_empty().then_some(tree_id)))
+    }
+
+    /// Perform rename-aware three-way merge of trees, reporting any conflicted paths.
+    ///
+    /// Like [`StupidContext::merge_trees()`], but the merged tree id is also returned
+    /// when there are conflicts, along with the conflicted paths. The conflicted files
+    /// in the merged tree contain conflict markers. `None` is returned if the git
+    /// version is too old to support `git merge-tree --write-tree`.
+    pub(crate) fn merge_trees_with_conflicts(
+        &self,
+        base_tree_id: gix::ObjectId,
+        our_tree_id: gix::ObjectId,
+        their_tree_id: gix::ObjectId,
+    ) -> Result<Option<(gix::ObjectId, Vec<BString>)>> {
+        if !self.at_least_version(&StupidVersion::new(2, 38, 0))? {
+            return Ok(None);
+        }
+
+        // `git merge-tree` only merges commits, so temporary commits are made for the
+        // trees with the base tree's commit as their common ancestor.
+        let signature = gix::actor::Signature {
+            name: "stgit".into(),
+            email: "stgit".into(),
+            time: gix::date::Time::now_utc(),
+        };
+        let base_id = self.commit_tree(&signature, &signature, b"base", base_tree_id, [], false)?;
+        let ours_id = self.commit_tree(
+            &signature,
+            &signature,
+            b"current",
+            our_tree_id,
+            [base_id],
+            false,
+        )?;
+        let theirs_id = self.commit_tree(
+            &signature,
+            &signature,
+            b"patched",
+            their_tree_id,
+            [base_id],
+            false,
+        )?;
+
+        let output = self
+            .git()
+            .args([
+                "merge-tree",
+                "--write-tree",
+                "--no-messages",
+                "--name-only",
+                "-z",
+            ])
+            .arg(ours_id.to_string())
+            .arg(theirs_id.to_string())
+            .output_git()?;
+
+        if output.status.success() || output.status.code() == Some(1) {
+            let mut fields = output.stdout.split_str(b"\0");
+            let tree_id = fields
+                .next()
+                .filter(|field| !field.is_empty())
+                .ok_or_else(|| anyhow!("no tree id from `git merge-tree`"))?;
+            let tree_id = parse_oid(tree_id)?;
+            let mut conflicts: Vec<BString> = Vec::new();
+            for path in fields.take_while(|field| !field.is_empty()) {
+                if conflicts.last().map(|last| last.as_bstr()) != Some(path.as_bstr()) {
+                    conflicts.push(path.into());
+                }
+            }
+            Ok(Some((tree_id, conflicts)))
+        } else {
+            Err(git_command_error("merge-tree", &output.stderr))
+        }
+    }
+
     /// Perform three-way merge with `git merge-recursive`.
     ///
-    /// Returns `true` if the merge was successful, `false` otherwise.
+    /// Conflict markers left in the work tree are labeled and sized according to the
+    /// merge options. Returns `true` if the merge was successful, `false` otherwise.
     pub(crate) fn merge_recursive(
         &self,
         base_tree_id: gix::ObjectId,
         our_tree_id: gix::ObjectId,
         their_tree_id: gix::ObjectId,
+        options: &MergeOptions,
     ) -> Result<bool> {
-        let output = self
-            .git()
+        let mut command = self.git();
+        let attributes_file = if let Some(marker_size) = options.marker_size {
+            Some(self.marker_size_attributes(marker_size)?)
+        } else {
+            None
+        };
+        if let Some(attributes_file) = attributes_file.as_ref() {
+            let mut config_arg = OsString::from("core.attributesFile=");
+            config_arg.push(attributes_file.path());
+            command.arg("-c").arg(config_arg);
+        }
+        let output = command
             .arg("merge-recursive")
             .arg(base_tree_id.to_string())
             .arg("--")
             .arg(our_tree_id.to_string())
             .arg(their_tree_id.to_string())
-            .env(format!("GITHEAD_{base_tree_id}"), "ancestor")
-            .env(format!("GITHEAD_{our_tree_id}"), "current")
-            .env(format!("GITHEAD_{their_tree_id}"), "patched")
+            .env(format!("GITHEAD_{base_tree_id}"), options.ancestor_label)
+            .env(format!("GITHEAD_{our_tree_id}"), options.our_label)
+            .env(format!("GITHEAD_{their_tree_id}"), options.their_label)
             .output_git()?;
 
         if output.status.success() {
             Ok(true)
         } else if output.status.code() == Some(1) {
+            self.relabel_conflicted_ancestors(options)?;
             Ok(false)
         } else {
             Err(git_command_error("merge-recursive", &output.stderr))
         }
     }
 
+    /// Create a temporary attributes file giving all paths the conflict marker size.
+    ///
+    /// The attributes file takes the place of the user's global attributes file, so
+    /// the global attributes are copied into it. Attributes from the repository, e.g. a
+    /// `conflict-marker-size` for particular paths, still take precedence.
+    fn marker_size_attributes(&self, marker_size: usize) -> Result<tempfile::NamedTempFile> {
+        let output = self
+            .git()
+            .args(["config", "--path", "--get", "core.attributesFile"])
+            .output_git()?;
+        let global_path = if output.status.success() {
+            Some(output.stdout.trim_end().to_path()?.to_path_buf())
+        } else {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(std::path::PathBuf::from)
+                .or_else(|| gix::path::env::home_dir().map(|home| home.join(".config")))
+                .map(|dir| dir.join("git").join("attributes"))
+        };
+        let mut attributes_file = tempfile::NamedTempFile::new()?;
+        if let Some(content) = global_path.and_then(|path| std::fs::read(path).ok()) {
+            attributes_file.write_all(&content)?;
+            if !content.ends_with(b"\n") {
+                attributes_file.write_all(b"\n")?;
+            }
+        }
+        writeln!(attributes_file, "* conflict-marker-size={marker_size}")?;
+        attributes_file.flush()?;
+        Ok(attributes_file)
+    }
+
+    /// Apply the ancestor label to the merge base markers of conflicted files.
+    fn relabel_conflicted_ancestors(&self, options: &MergeOptions) -> Result<()> {
+        let Some(work_dir) = self.work_dir else {
+            return Ok(());
+        };
+        for path in self.diff_unmerged_names()? {
+            let path = work_dir.join(path);
+            let Ok(content) = std::fs::read(&path) else {
+                continue;
+            };
+            if let Some(relabeled) = options.relabel_ancestor(&content) {
+                std::fs::write(&path, relabeled)
+                    .with_context(|| format!("relabeling conflicts in `{}`", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
     /// Perform three-way merge, with optional auto-resolution of conflicts with
     /// `git merge-tool`.
     pub(crate) fn merge_recursive_or_mergetool(
@@ -868,9 +1355,10 @@ impl StupidContext<'_, '_> {
         base_tree_id: gix::ObjectId,
         our_tree_id: gix::ObjectId,
         their_tree_id: gix::ObjectId,
+        options: &MergeOptions,
         use_mergetool: bool,
     ) -> Result<bool> {
-        if self.merge_recursive(base_tree_id, our_tree_id, their_tree_id)? {
+        if self.merge_recursive(base_tree_id, our_tree_id, their_tree_id, options)? {
             Ok(true)
         } else if use_mergetool {
             self.mergetool()
@@ -893,8 +1381,24 @@ impl StupidContext<'_, '_> {
 
     /// Copy notes from one object to another using `git notes copy`.
     pub(crate) fn notes_copy(&self, from_oid: gix::ObjectId, to_oid: gix::ObjectId) -> Result<()> {
-        self.git()
-            .args(["notes", "copy"])
+        self.notes_copy_ref(None, from_oid, to_oid)
+    }
+
+    /// Copy notes of the given notes ref, or of the default notes ref, from one object
+    /// to another using `git notes copy`.
+    pub(crate) fn notes_copy_ref(
+        &self,
+        notes_ref: Option<&str>,
+        from_oid: gix::ObjectId,
+        to_oid: gix::ObjectId,
+    ) -> Result<()> {
+        let mut command = self.git();
+        command.arg("notes");
+        if let Some(notes_ref) = notes_ref {
+            command.arg(format!("--ref={notes_ref}"));
+        }
+        command
+            .arg("copy")
             .arg(from_oid.to_string())
             .arg(to_oid.to_string())
             .stdout(Stdio::null())
@@ -903,6 +1407,26 @@ impl StupidContext<'_, '_> {
         Ok(())
     }
 
+    /// Copy notes from a rewritten commit to its replacement as `git <command>` would.
+    ///
+    /// Uses `git notes copy --for-rewrite=<command>`, which copies the notes of the
+    /// refs configured with `notes.rewriteRef`, subject to `notes.rewrite.<command>`
+    /// and `notes.rewriteMode`.
+    pub(crate) fn notes_copy_for_rewrite(
+        &self,
+        command: &str,
+        from_oid: gix::ObjectId,
+        to_oid: gix::ObjectId,
+    ) -> Result<()> {
+        self.git()
+            .args(["notes", "copy"])
+            .arg(format!("--for-rewrite={command}"))
+            .stdout(Stdio::null())
+            .in_and_out(format!("{from_oid} {to_oid}\n").as_bytes())?
+            .require_success("notes copy")?;
+        Ok(())
+    }
+
     /// Read content of a tree into specified index using `git read-tree`.
     pub(crate) fn read_tree(&self, tree_id: gix::ObjectId) -> Result<()> {
         self.git_in_work_root()?
@@ -946,6 +1470,97 @@ impl StupidContext<'_, '_> {
         Ok(())
     }
 
+    /// Recreate conflicted merge for paths in the work tree using `git checkout --merge`.
+    ///
+    /// Paths must be relative to the work tree root.
+    pub(crate) fn checkout_merge<SpecIter, SpecArg>(&self, paths: SpecIter) -> Result<()>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
+        self.git_in_work_root()?
+            .args(["checkout", "--merge", "--"])
+            .args(paths)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("checkout --merge")?;
+        Ok(())
+    }
+
+    /// Force checkout of index entries to the work tree using `git checkout-index`.
+    ///
+    /// Paths must be relative to the work tree root.
+    pub(crate) fn checkout_index_force<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<()> {
+        let mut input = Vec::new();
+        for path in paths {
+            input.extend_from_slice(gix::path::into_bstr(path).as_ref());
+            input.push(0);
+        }
+        self.git_in_work_root()?
+            .args(["checkout-index", "--force", "--quiet", "--stdin", "-z"])
+            .stdout(Stdio::null())
+            .in_and_out(&input)?
+            .require_success("checkout-index")?;
+        Ok(())
+    }
+
+    /// Compute the blob id of content as if it were at a work tree path using
+    /// `git hash-object`, optionally writing the blob to the object database.
+    ///
+    /// The content is filtered as configured by the path's attributes, just as when it
+    /// is added to the index. The path must be relative to the work tree root.
+    pub(crate) fn hash_object_at_path(
+        &self,
+        path: &Path,
+        data: &[u8],
+        write: bool,
+    ) -> Result<gix::ObjectId> {
+        let mut command = self.git_in_work_root()?;
+        command.args(["hash-object", "--stdin"]);
+        if write {
+            command.arg("-w");
+        }
+        let output = command
+            .arg("--path")
+            .arg(path)
+            .stdout(Stdio::piped())
+            .in_and_out(data)?
+            .require_success("hash-object")?;
+        parse_oid(&output.stdout)
+    }
+
+    /// Compute the blob ids of the given work tree files using `git hash-object`.
+    ///
+    /// The files are filtered as configured by their attributes, just as when they are
+    /// added to the index. Paths must be relative to the work tree root and must not
+    /// contain newlines.
+    pub(crate) fn hash_object_paths<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<Vec<gix::ObjectId>> {
+        let mut input = Vec::new();
+        for path in paths {
+            input.extend_from_slice(gix::path::into_bstr(path).as_ref());
+            input.push(b'\n');
+        }
+        let output = self
+            .git_in_work_root()?
+            .args(["hash-object", "--stdin-paths"])
+            .stdout(Stdio::piped())
+            .in_and_out(&input)?
+            .require_success("hash-object")?;
+        output
+            .stdout
+            .lines()
+            .map(|line| {
+                gix::ObjectId::from_hex(line).map_err(|_| anyhow!("invalid hash-object output"))
+            })
+            .collect()
+    }
+
     /// Pack unpacked objects
     pub(crate) fn repack(&self) -> Result<()> {
         self.git()
@@ -987,6 +1602,183 @@ impl StupidContext<'_, '_> {
         Ok(oids)
     }
 
+    /// Get revisions reachable from any of `tips`, but not from `base`.
+    ///
+    /// Only revisions modifying the given pathspecs are included in the output.
+    pub(crate) fn rev_list_tips<SpecIter, SpecArg>(
+        &self,
+        base: gix::ObjectId,
+        tips: impl IntoIterator<Item = gix::ObjectId>,
+        pathspecs: SpecIter,
+    ) -> Result<Vec<gix::ObjectId>>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
+        let mut command = self.git();
+        command.arg("rev-list").arg(format!("^{base}"));
+        command.args(tips.into_iter().map(|tip| tip.to_string()));
+        command.arg("--").args(pathspecs);
+
+        let output = command.output_git()?.require_success("rev-list")?;
+        let mut oids: Vec<gix::ObjectId> = Vec::new();
+        for line in output
+            .stdout
+            .split_str("\n")
+            .filter(|line| !line.is_empty())
+        {
+            oids.push(parse_oid(line)?);
+        }
+        Ok(oids)
+    }
+
+    /// Get revisions reachable from any of `tips`, but not from any of `excluded`.
+    ///
+    /// The revisions are given to `git rev-list --stdin`, such that there may be any
+    /// number of them.
+    pub(crate) fn rev_list_excluding(
+        &self,
+        tips: impl IntoIterator<Item = gix::ObjectId>,
+        excluded: impl IntoIterator<Item = gix::ObjectId>,
+    ) -> Result<Vec<gix::ObjectId>> {
+        let mut input = Vec::new();
+        for tip in tips {
+            writeln!(input, "{tip}")?;
+        }
+        for id in excluded {
+            writeln!(input, "^{id}")?;
+        }
+        let output = self
+            .git()
+            .args(["rev-list", "--stdin"])
+            .stdout(Stdio::piped())
+            .in_and_out(&input)?
+            .require_success("rev-list --stdin")?;
+        let mut oids: Vec<gix::ObjectId> = Vec::new();
+        for line in output.stdout.lines().filter(|line| !line.is_empty()) {
+            oids.push(parse_oid(line)?);
+        }
+        Ok(oids)
+    }
+
+    /// Get the stable patch ids of commits using `git patch-id --stable`.
+    ///
+    /// Pairs of commit id and patch id are returned. Commits without a diff, such as
+    /// merge commits and commits that do not change their parent's tree, are omitted.
+    pub(crate) fn patch_ids(
+        &self,
+        commit_ids: impl IntoIterator<Item = gix::ObjectId>,
+    ) -> Result<Vec<(gix::ObjectId, gix::ObjectId)>> {
+        let mut log_child = self
+            .git()
+            .args([
+                "log",
+                "--stdin",
+                "--no-walk=unsorted",
+                "--patch",
+                "--no-color",
+                "--no-ext-diff",
+                "--format=%H",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn_git()?;
+        let patch_id_child = self
+            .git()
+            .args(["patch-id", "--stable"])
+            .stdin(log_child.stdout.take().unwrap())
+            .stdout(Stdio::piped())
+            .spawn_git()?;
+
+        // `git log --stdin` reads all of its input before producing any output.
+        let mut log_stdin = log_child.stdin.take().unwrap();
+        for commit_id in commit_ids {
+            writeln!(log_stdin, "{commit_id}")?;
+        }
+        drop(log_stdin);
+
+        let output = patch_id_child.require_success("patch-id")?;
+        log_child.require_success("log")?;
+
+        let mut patch_ids = Vec::new();
+        for line in output.stdout.lines() {
+            let Some((patch_id, commit_id)) = line.split_once_str(b" ") else {
+                return Err(anyhow!("unexpected patch-id output `{}`", line.as_bstr()));
+            };
+            patch_ids.push((parse_oid(commit_id)?, parse_oid(patch_id)?));
+        }
+        Ok(patch_ids)
+    }
+
+    /// Get the stable patch ids of the diffs between pairs of commits.
+    ///
+    /// For each `(old, new)` pair of commit ids, the patch id of the diff from `old` to
+    /// `new` is returned, or `None` if the two commits' trees do not differ.
+    pub(crate) fn diff_patch_ids(
+        &self,
+        pairs: &[(gix::ObjectId, gix::ObjectId)],
+    ) -> Result<Vec<Option<gix::ObjectId>>> {
+        let Some(hex_len) = pairs.first().map(|(old, _)| old.kind().len_in_hex()) else {
+            return Ok(Vec::new());
+        };
+        let mut input = Vec::new();
+        for (old, new) in pairs {
+            writeln!(input, "{new} {old}")?;
+        }
+        // With `--always`, each diff is preceded by a line with the new commit's id,
+        // even when the diff is empty.
+        let output = self
+            .git()
+            .args([
+                "diff-tree",
+                "--stdin",
+                "--always",
+                "--patch",
+                "--no-color",
+                "--no-ext-diff",
+            ])
+            .stdout(Stdio::piped())
+            .in_and_out(&input)?
+            .require_success("diff-tree --stdin")?;
+
+        // Each diff is labeled with its index into `pairs` such that the diffs can be
+        // told apart in the output of `git patch-id`, which omits empty diffs.
+        let mut diffs = Vec::new();
+        let mut next = 0;
+        for line in output.stdout.lines_with_terminator() {
+            if next < pairs.len() && line.trim_end() == pairs[next].1.to_string().as_bytes() {
+                writeln!(diffs, "commit {:0hex_len$x}", next)?;
+                next += 1;
+            } else {
+                diffs.extend_from_slice(line);
+            }
+        }
+        if next != pairs.len() {
+            return Err(anyhow!("unexpected diff-tree output"));
+        }
+
+        let output = self
+            .git()
+            .args(["patch-id", "--stable"])
+            .stdout(Stdio::piped())
+            .in_and_out(&diffs)?
+            .require_success("patch-id")?;
+        let mut patch_ids = vec![None; pairs.len()];
+        for line in output.stdout.lines() {
+            let Some((patch_id, label)) = line.split_once_str(b" ") else {
+                return Err(anyhow!("unexpected patch-id output `{}`", line.as_bstr()));
+            };
+            let index = label
+                .to_str()
+                .ok()
+                .and_then(|label| usize::from_str_radix(label, 16).ok())
+                .filter(|index| *index < pairs.len())
+                .ok_or_else(|| anyhow!("unexpected patch-id output `{}`", line.as_bstr()))?;
+            patch_ids[index] = Some(parse_oid(patch_id)?);
+        }
+        Ok(patch_ids)
+    }
+
     /// Get `cdup` for current directory from `git rev-parse --show-cdup`.
     pub(crate) fn rev_parse_cdup(&self) -> Result<OsString> {
         let output = self
@@ -1023,7 +1815,11 @@ impl StupidContext<'_, '_> {
         }
     }
 
-    pub(crate) fn send_email<OptIter, OptArg>(&self, args: OptIter) -> Result<()>
+    /// Run `git send-email`, returning its output after copying it to stdout.
+    ///
+    /// The output is also returned when `git send-email` fails, along with the
+    /// failure, such that the emails sent before the failure may be determined.
+    pub(crate) fn send_email<OptIter, OptArg>(&self, args: OptIter) -> Result<(Vec<u8>, Result<()>)>
     where
         OptIter: IntoIterator<Item = OptArg>,
         OptArg: AsRef<OsStr>,
@@ -1031,12 +1827,29 @@ impl StupidContext<'_, '_> {
         let mut command = self.git();
         command.arg("send-email");
         command.args(args);
-        command
+        let output = command.stdin(Stdio::inherit()).tee_output_git()?;
+        let status = if output.status.success() {
+            Ok(())
+        } else {
+            Err(git_command_error("send-email", &output.stderr))
+        };
+        Ok((output.stdout, status))
+    }
+
+    /// Run `git send-email`, returning its output without copying it to stdout.
+    pub(crate) fn send_email_captured<OptIter, OptArg>(&self, args: OptIter) -> Result<Vec<u8>>
+    where
+        OptIter: IntoIterator<Item = OptArg>,
+        OptArg: AsRef<OsStr>,
+    {
+        let mut command = self.git();
+        command.arg("send-email");
+        command.args(args);
+        let output = command
             .stdin(Stdio::inherit())
-            .stdout(Stdio::inherit())
             .output_git()?
             .require_success("send-email")?;
-        Ok(())
+        Ok(output.stdout)
     }
 
     pub(crate) fn send_email_dump_aliases(&self) -> Result<()> {
@@ -1054,7 +1867,7 @@ impl StupidContext<'_, '_> {
         &self,
         oids: impl IntoIterator<Item = gix::ObjectId>,
         pathspecs: Option<SpecIter>,
-        stat: bool,
+        format: DiffFormat,
         use_color: bool,
         diff_opts: OptIter,
     ) -> Result<()>
@@ -1066,11 +1879,11 @@ impl StupidContext<'_, '_> {
     {
         let mut command = self.git();
         command.arg("show");
-        if stat {
-            command.args(["--stat", "--summary"]);
-        } else {
-            command.arg("--patch");
-        }
+        match format {
+            DiffFormat::Patch => command.arg("--patch"),
+            DiffFormat::Stat => command.args(["--stat", "--summary"]),
+            DiffFormat::NumStat => command.arg("--numstat"),
+        };
 
         command.arg(if use_color {
             "--color=always"
@@ -1093,6 +1906,36 @@ impl StupidContext<'_, '_> {
         Ok(())
     }
 
+    /// Show the differences between two versions of a single commit using `git
+    /// range-diff`.
+    pub(crate) fn range_diff_commits<OptIter, OptArg>(
+        &self,
+        old_commit_id: gix::ObjectId,
+        new_commit_id: gix::ObjectId,
+        use_color: bool,
+        diff_opts: OptIter,
+    ) -> Result<()>
+    where
+        OptIter: IntoIterator<Item = OptArg>,
+        OptArg: AsRef<OsStr>,
+    {
+        let mut command = self.git();
+        // A creation factor of 100 ensures that the two commits are always paired.
+        command.args(["range-diff", "--creation-factor=100"]);
+        command.arg(if use_color {
+            "--color=always"
+        } else {
+            "--color=never"
+        });
+        command.args(diff_opts);
+        command.args([format!("{old_commit_id}^!"), format!("{new_commit_id}^!")]);
+        command
+            .stdout(Stdio::inherit())
+            .output_git()?
+            .require_success("range-diff")?;
+        Ok(())
+    }
+
     /// Show object with custom pretty format.
     pub(crate) fn show_pretty(&self, oid: gix::ObjectId, pretty_format: &str) -> Result<Vec<u8>> {
         let output = self
@@ -1110,11 +1953,8 @@ impl StupidContext<'_, '_> {
     /// Returns `Ok(true)` if stash application is successful, `Ok(false)` if stash
     /// application results in conflicts, or Err otherwise.
     pub(crate) fn stash_pop(&self) -> Result<bool> {
-        let output = self
-            .git()
-            .args(["stash", "pop"])
-            .stdout(Stdio::inherit())
-            .output_git()?;
+        let output = self.git().args(["stash", "pop"]).output_git()?;
+        write_commentary(&output.stdout)?;
 
         if output.status.success() {
             Ok(true)
@@ -1127,15 +1967,105 @@ impl StupidContext<'_, '_> {
 
     /// Stash changes from working tree and index.
     pub(crate) fn stash_push(&self) -> Result<()> {
-        self.git()
+        let output = self
+            .git()
             .args(["stash", "push"])
-            .stdout(Stdio::inherit())
             .output_git()?
             .require_success("stash push")?;
+        write_commentary(&output.stdout)
+    }
+
+    /// Stash changes from the index and work tree with the given stash message.
+    ///
+    /// Untracked files are also stashed and removed from the work tree when
+    /// `include_untracked` is set.
+    pub(crate) fn stash_push_with_message(
+        &self,
+        message: &str,
+        include_untracked: bool,
+    ) -> Result<()> {
+        let mut command = self.git_in_work_root()?;
+        command.args(["stash", "push", "--quiet"]);
+        if include_untracked {
+            command.arg("--include-untracked");
+        }
+        command
+            .args(["--message", message])
+            .output_git()?
+            .require_success("stash push")?;
+        Ok(())
+    }
+
+    /// Drop the most recent entry of the stash reflog.
+    pub(crate) fn stash_drop(&self) -> Result<()> {
+        self.git()
+            .args(["stash", "drop", "--quiet"])
+            .output_git()?
+            .require_success("stash drop")?;
+        Ok(())
+    }
+
+    /// Add a stash commit to the stash reflog with `git stash store`.
+    pub(crate) fn stash_store(&self, stash_id: gix::ObjectId, message: &str) -> Result<()> {
+        self.git()
+            .args(["stash", "store", "--quiet", "--message", message])
+            .arg(stash_id.to_string())
+            .output_git()?
+            .require_success("stash store")?;
+        Ok(())
+    }
+
+    /// Apply the changes of a stash commit to the work tree with `git stash apply`.
+    ///
+    /// Returns `Ok(true)` if the changes applied cleanly, `Ok(false)` if applying them
+    /// resulted in conflicts, or Err otherwise.
+    pub(crate) fn stash_apply(&self, stash_id: gix::ObjectId) -> Result<bool> {
+        let output = self
+            .git_in_work_root()?
+            .args(["stash", "apply", "--quiet"])
+            .arg(stash_id.to_string())
+            .output_git()?;
+        if output.status.success() {
+            Ok(true)
+        } else if output.status.code() == Some(1) && !self.ls_files_unmerged()?.is_empty() {
+            Ok(false)
+        } else {
+            Err(git_command_error("stash apply", &output.stderr))
+        }
+    }
+
+    /// Create a stash commit from the index and work tree with `git stash create`.
+    ///
+    /// The stash commit is not added to the stash reflog. `None` is returned if there
+    /// are no local changes to stash.
+    pub(crate) fn stash_create(&self, message: &str) -> Result<Option<gix::ObjectId>> {
+        let output = self
+            .git_in_work_root()?
+            .args(["stash", "create", message])
+            .output_git()?
+            .require_success("stash create")?;
+        if output.stdout.trim().is_empty() {
+            Ok(None)
+        } else {
+            parse_oid(&output.stdout).map(Some)
+        }
+    }
+
+    /// Restore the index and work tree changes of a stash commit with `git stash apply`.
+    pub(crate) fn stash_apply_index(&self, stash_id: gix::ObjectId) -> Result<()> {
+        self.git_in_work_root()?
+            .args(["stash", "apply", "--index", "--quiet"])
+            .arg(stash_id.to_string())
+            .output_git()?
+            .require_success("stash apply")?;
         Ok(())
     }
 
     /// Get index and worktree change statuses relative to HEAD.
+    ///
+    /// The statuses are determined by `git status`, which makes use of any configured
+    /// `core.fsmonitor` hook or daemon and of the untracked cache, such that checking
+    /// the worktree of a large repository is no slower than `git status` itself.
     pub(crate) fn statuses(&self, options: Option<&StatusOptions>) -> Result<Statuses> {
         let default_options;
         let options = if let Some(options) = options {
@@ -1185,7 +2115,7 @@ impl StupidContext<'_, '_> {
         Ok(Statuses::from_data(status_data))
     }
 
-    /// Show short status using `git status`.
+    /// Show short status using `git status`, as commentary on stderr.
     pub(crate) fn status_short<SpecIter, SpecArg>(&self, pathspecs: Option<SpecIter>) -> Result<()>
     where
         SpecIter: IntoIterator<Item = SpecArg>,
@@ -1196,11 +2126,8 @@ impl StupidContext<'_, '_> {
         if let Some(pathspecs) = pathspecs {
             command.args(pathspecs);
         }
-        command
-            .stdout(Stdio::inherit())
-            .output_git()?
-            .require_success("status -s")?;
-        Ok(())
+        let output = command.output_git()?.require_success("status -s")?;
+        write_commentary(&output.stdout)
     }
 
     /// Update index with changes from work tree.
@@ -1211,8 +2138,16 @@ impl StupidContext<'_, '_> {
         SpecIter: IntoIterator<Item = SpecArg> + Send,
         SpecArg: AsRef<OsStr> + Send,
     {
-        let mut child = self
-            .git_in_work_root()?
+        let mut input = Vec::new();
+        if let Some(pathspecs) = pathspecs {
+            for spec in pathspecs {
+                if let Some(spec_bytes) = <[u8]>::from_os_str(spec.as_ref()) {
+                    input.extend_from_slice(spec_bytes);
+                    input.push(0);
+                }
+            }
+        }
+        self.git_in_work_root()?
             .args([
                 "update-index",
                 "--remove",
@@ -1221,42 +2156,95 @@ impl StupidContext<'_, '_> {
                 "-z",
                 "--stdin",
             ])
-            .stdin(Stdio::piped())
             .stdout(Stdio::null())
-            .spawn_git()?;
+            .in_and_out(&input)?
+            .require_success("update-index")?;
+        Ok(())
+    }
 
-        {
-            let mut stdin = child.stdin.take().unwrap();
-
-            if let Some(pathspecs) = pathspecs {
-                let write_result: Result<()> = std::thread::scope(|scope| {
-                    let handle = scope.spawn(|| {
-                        for spec in pathspecs {
-                            if let Some(spec_bytes) = <[u8]>::from_os_str(spec.as_ref()) {
-                                stdin.write_all(spec_bytes)?;
-                                stdin.write_all(&[0])?;
-                            }
-                        }
-                        Ok(())
-                    });
-                    handle
-                        .join()
-                        .map_err(|_| anyhow!("failed writing pathspecs to stdin"))?
-                });
-                write_result?;
-            }
-        }
-        child.wait_with_output()?.require_success("update-index")?;
+    /// Update index entries with `git update-index --index-info`.
+    ///
+    /// The `index_info` records must be NUL-terminated and have paths relative to the
+    /// repository root.
+    pub(crate) fn update_index_info(&self, index_info: &[u8]) -> Result<()> {
+        self.git_in_work_root()?
+            .args(["update-index", "-z", "--index-info"])
+            .stdout(Stdio::null())
+            .in_and_out(index_info)?
+            .require_success("update-index --index-info")?;
         Ok(())
     }
 
     /// Update default index from working tree with `git update-index`.
+    ///
+    /// Files that need updating, i.e. that have local changes, are not an error. This
+    /// is indicated by exit code 1 rather than with `-q`, which would also silence the
+    /// error when the index cannot be locked.
     pub(crate) fn update_index_refresh(&self) -> Result<()> {
         self.git()
-            .args(["update-index", "-q", "--unmerged", "--refresh"])
+            .args(["update-index", "--unmerged", "--refresh"])
             .stdout(Stdio::null())
             .output_git()?
-            .require_success("update-index")?;
+            .require_code_less_than("update-index", 128)?;
+        Ok(())
+    }
+
+    /// Clone `repository` into a new `directory` using `git clone`.
+    ///
+    /// Only the given branch is checked out and, with a depth, the clone is shallow.
+    pub(crate) fn clone_repository(
+        &self,
+        repository: &str,
+        directory: &Path,
+        branch: Option<&str>,
+        depth: Option<u32>,
+    ) -> Result<()> {
+        let mut command = self.git();
+        command.arg("clone");
+        if let Some(branch) = branch {
+            command.args(["--branch", branch]);
+        }
+        if let Some(depth) = depth {
+            command.arg(format!("--depth={depth}"));
+        }
+        command
+            .arg("--")
+            .arg(repository)
+            .arg(directory)
+            .stdin(Stdio::null())
+            .output_git()?
+            .require_success("clone")?;
+        Ok(())
+    }
+
+    /// Fetch objects missing from a partial clone from its promisor `remote`.
+    ///
+    /// This is the same fetch git performs when it lazily fetches a missing object,
+    /// but for all of the given objects at once.
+    pub(crate) fn fetch_missing_objects(
+        &self,
+        remote: &str,
+        oids: impl IntoIterator<Item = gix::ObjectId>,
+    ) -> Result<()> {
+        let mut input = String::new();
+        for oid in oids {
+            input.push_str(&format!("{oid}\n"));
+        }
+        self.git()
+            .args([
+                "-c",
+                "fetch.negotiationAlgorithm=noop",
+                "fetch",
+                remote,
+                "--no-tags",
+                "--no-write-fetch-head",
+                "--recurse-submodules=no",
+                "--filter=blob:none",
+                "--stdin",
+            ])
+            .stdout(Stdio::null())
+            .in_and_out(input.as_bytes())?
+            .require_success("fetch")?;
         Ok(())
     }
 
@@ -1266,6 +2254,7 @@ impl StupidContext<'_, '_> {
         if let Some(command_name) = args.next() {
             let mut command = Command::new(command_name);
             self.setup_git_env(&mut command);
+            git_program().setup_child_env(&mut command);
             let status = command
                 .args(args)
                 .arg(remote_name)
@@ -1296,6 +2285,7 @@ impl StupidContext<'_, '_> {
         if let Some(command_name) = args.next() {
             let mut command = Command::new(command_name);
             self.setup_git_env(&mut command);
+            git_program().setup_child_env(&mut command);
             let status = command
                 .args(args)
                 .arg(remote_name)
@@ -1325,6 +2315,7 @@ impl StupidContext<'_, '_> {
         if let Some(command_name) = args.next() {
             let mut command = Command::new(command_name);
             self.setup_git_env(&mut command);
+            git_program().setup_child_env(&mut command);
             let status = command
                 .args(args)
                 .arg(target.to_string())
@@ -1371,3 +2362,23 @@ impl StupidContext<'_, '_> {
         parse_oid(&output.stdout)
     }
 }
+
+/// Add the `git apply` arguments locating where in the files the diff applies.
+fn add_apply_location_args(command: &mut Command, diff: &[u8], options: &ApplyOptions) {
+    if options.ignore_whitespace {
+        command.arg("--ignore-whitespace");
+    }
+    if let Some(strip_level) = options.strip_level {
+        command.arg(format!("-p{strip_level}"));
+    }
+    if let Some(directory) = options.directory {
+        command.arg("--directory");
+        command.arg(directory);
+    }
+    if let Some(context_lines) = options.effective_context_lines(diff) {
+        command.arg(format!("-C{context_lines}"));
+    }
+    if apply::has_zero_context_hunk(diff) {
+        command.arg("--unidiff-zero");
+    }
+}
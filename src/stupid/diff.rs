@@ -4,7 +4,18 @@
 
 use std::path::Path;
 
-use bstr::ByteSlice;
+use bstr::{BStr, BString, ByteSlice};
+
+/// Output format of diffs shown to the user, e.g. by `stg show` and `stg diff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DiffFormat {
+    /// The full diff.
+    Patch,
+    /// A diffstat summary, as with `git diff --stat --summary`.
+    Stat,
+    /// Machine-readable added and deleted line counts, as with `git diff --numstat`.
+    NumStat,
+}
 
 /// Diff output containing only names of differing files.
 ///
@@ -52,6 +63,288 @@ impl<'a> Iterator for DiffFilesIter<'a> {
     }
 }
 
+/// Diff output containing the status and names of differing files.
+///
+/// E.g. from `git diff-tree --name-status -z`
+pub(crate) struct DiffFileStatuses {
+    data: Vec<u8>,
+}
+
+impl DiffFileStatuses {
+    pub(super) fn new(data: Vec<u8>) -> Self {
+        DiffFileStatuses { data }
+    }
+
+    pub(crate) fn iter(&self) -> DiffFileStatusesIter<'_> {
+        DiffFileStatusesIter {
+            index: 0,
+            data: &self.data,
+        }
+    }
+}
+
+/// Status of a single differing file from [`DiffFileStatuses`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct DiffFileStatus<'a> {
+    /// Status letter, possibly followed by a similarity score (e.g. `M` or `R100`).
+    pub(crate) status: &'a str,
+
+    /// Path of the file before it was renamed or copied.
+    pub(crate) src_path: Option<&'a Path>,
+
+    /// Path of the file.
+    pub(crate) path: &'a Path,
+}
+
+/// Iterate file statuses from [`DiffFileStatuses`].
+pub(crate) struct DiffFileStatusesIter<'a> {
+    index: usize,
+    data: &'a [u8],
+}
+
+impl<'a> DiffFileStatusesIter<'a> {
+    fn next_field(&mut self) -> &'a [u8] {
+        let remaining = &self.data[self.index..];
+        let null_offset = remaining
+            .find_byte(0)
+            .expect("all fields are nul terminated");
+        self.index += null_offset + 1;
+        &remaining[..null_offset]
+    }
+
+    fn next_path(&mut self) -> &'a Path {
+        self.next_field()
+            .to_path()
+            .expect("paths on Windows must be utf8")
+    }
+}
+
+impl<'a> Iterator for DiffFileStatusesIter<'a> {
+    type Item = DiffFileStatus<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.data.len() {
+            let status = self.next_field().to_str().expect("status is ascii");
+            let src_path = if status.starts_with(['R', 'C']) {
+                Some(self.next_path())
+            } else {
+                None
+            };
+            let path = self.next_path();
+            Some(DiffFileStatus {
+                status,
+                src_path,
+                path,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Raw diff output for one or more pairs of trees.
+///
+/// E.g. from `git diff-tree --stdin -r --raw -z`, where each pair of trees is preceded
+/// by a newline-terminated header line naming the trees.
+pub(crate) struct RawDiff {
+    data: Vec<u8>,
+}
+
+impl RawDiff {
+    pub(super) fn new(data: Vec<u8>) -> Self {
+        RawDiff { data }
+    }
+
+    pub(crate) fn iter(&self) -> RawDiffIter<'_> {
+        RawDiffIter {
+            index: 0,
+            data: &self.data,
+            trees: None,
+        }
+    }
+}
+
+/// A single differing file from [`RawDiff`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct RawDiffEntry<'a> {
+    /// The pair of trees compared, as named by the header preceding the entry.
+    pub(crate) trees: Option<(gix::ObjectId, gix::ObjectId)>,
+
+    /// Blob id of the file in the first tree, if it is a blob in that tree.
+    pub(crate) old_blob: Option<gix::ObjectId>,
+
+    /// Blob id of the file in the second tree, if it is a blob in that tree.
+    pub(crate) new_blob: Option<gix::ObjectId>,
+
+    /// Path of the file.
+    pub(crate) path: &'a [u8],
+}
+
+/// Iterate differing files from [`RawDiff`].
+pub(crate) struct RawDiffIter<'a> {
+    index: usize,
+    data: &'a [u8],
+    trees: Option<(gix::ObjectId, gix::ObjectId)>,
+}
+
+impl<'a> Iterator for RawDiffIter<'a> {
+    type Item = RawDiffEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let remaining = &self.data[self.index..];
+            if remaining.is_empty() {
+                return None;
+            } else if remaining[0] != b':' {
+                // Skip the header line naming the pair of trees.
+                let newline_offset = remaining.find_byte(b'\n').unwrap_or(remaining.len() - 1);
+                self.trees =
+                    remaining[..newline_offset]
+                        .split_once_str(" ")
+                        .and_then(|(tree1, tree2)| {
+                            Some((
+                                gix::ObjectId::from_hex(tree1).ok()?,
+                                gix::ObjectId::from_hex(tree2).ok()?,
+                            ))
+                        });
+                self.index += newline_offset + 1;
+                continue;
+            }
+            let meta_len = remaining
+                .find_byte(0)
+                .expect("raw diff metadata is nul terminated");
+            let path_len = remaining[meta_len + 1..]
+                .find_byte(0)
+                .expect("raw diff path is nul terminated");
+            let path = &remaining[meta_len + 1..meta_len + 1 + path_len];
+            self.index += meta_len + 1 + path_len + 1;
+
+            // E.g. ":100644 100644 <old-id> <new-id> M"
+            let fields: Vec<&[u8]> = remaining[1..meta_len].split_str(" ").collect();
+            let blob = |mode: &[u8], id: &[u8]| {
+                if mode.starts_with(b"10") || mode.starts_with(b"12") {
+                    gix::ObjectId::from_hex(id).ok()
+                } else {
+                    None
+                }
+            };
+            return Some(RawDiffEntry {
+                trees: self.trees,
+                old_blob: blob(fields[0], fields[2]),
+                new_blob: blob(fields[1], fields[3]),
+                path,
+            });
+        }
+    }
+}
+
+/// Parser of the lines added by a diff, as output by `git diff-tree -p`.
+///
+/// The diff is fed to the parser one line at a time, allowing it to be streamed from
+/// `git` rather than held in memory. Binary files have no lines in the diff and are
+/// thus never reported.
+#[derive(Default)]
+pub(crate) struct AddedLines {
+    path: Option<BString>,
+    old_remaining: usize,
+    new_remaining: usize,
+    line_number: usize,
+}
+
+impl AddedLines {
+    /// Parse a line of diff output, without its line terminator.
+    ///
+    /// For an added line, the path of the file, the line's number in the new version
+    /// of the file, and the line's content are returned.
+    pub(crate) fn parse<'a>(&mut self, line: &'a [u8]) -> Option<(&BStr, usize, &'a BStr)> {
+        if self.old_remaining > 0 || self.new_remaining > 0 {
+            match line.first() {
+                Some(b'+') => {
+                    self.new_remaining = self.new_remaining.saturating_sub(1);
+                    self.line_number += 1;
+                    let path = self.path.as_ref()?;
+                    return Some((path.as_bstr(), self.line_number - 1, line[1..].as_bstr()));
+                }
+                Some(b'-') => self.old_remaining = self.old_remaining.saturating_sub(1),
+                Some(b' ') => {
+                    self.old_remaining = self.old_remaining.saturating_sub(1);
+                    self.new_remaining = self.new_remaining.saturating_sub(1);
+                    self.line_number += 1;
+                }
+                _ => {}
+            }
+        } else if line.starts_with(b"diff ") {
+            self.path = None;
+        } else if let Some(path) = line.strip_prefix(b"+++ ") {
+            // Paths with spaces are followed by a tab.
+            let path = path.strip_suffix(b"\t").unwrap_or(path);
+            self.path = unquote_path(path).and_then(|path| {
+                path.strip_prefix(b"b/")
+                    .map(|path| BString::from(path.to_vec()))
+            });
+        } else if let Some(header) = line.strip_prefix(b"@@ -") {
+            let mut ranges = header.split_str(" ");
+            let old_range = ranges.next()?;
+            let new_range = ranges.next()?.strip_prefix(b"+")?;
+            let parse_range = |range: &[u8]| -> Option<(usize, usize)> {
+                let mut parts = range.splitn_str(2, ",");
+                let start = parts.next()?.to_str().ok()?.parse().ok()?;
+                let count = parts
+                    .next()
+                    .map_or(Some(1), |count| count.to_str().ok()?.parse().ok())?;
+                Some((start, count))
+            };
+            let (_, old_count) = parse_range(old_range)?;
+            let (new_start, new_count) = parse_range(new_range)?;
+            self.old_remaining = old_count;
+            self.new_remaining = new_count;
+            self.line_number = new_start;
+        }
+        None
+    }
+}
+
+/// Undo the C-style quoting `git` applies to paths with unusual characters.
+///
+/// Paths that are not quoted are returned as-is. `None` is returned for a malformed
+/// quoted path.
+fn unquote_path(path: &[u8]) -> Option<BString> {
+    let Some(quoted) = path
+        .strip_prefix(b"\"")
+        .and_then(|path| path.strip_suffix(b"\""))
+    else {
+        return Some(BString::from(path.to_vec()));
+    };
+    let mut unquoted = Vec::with_capacity(quoted.len());
+    let mut bytes = quoted.iter().copied();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            unquoted.push(b);
+            continue;
+        }
+        let escaped = match bytes.next()? {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'v' => 0x0b,
+            b'f' => 0x0c,
+            b'r' => b'\r',
+            digit @ b'0'..=b'3' => {
+                let mut value = digit - b'0';
+                for _ in 0..2 {
+                    let digit = bytes.next().filter(|d| (b'0'..=b'7').contains(d))?;
+                    value = value * 8 + (digit - b'0');
+                }
+                value
+            }
+            other => other,
+        };
+        unquoted.push(escaped);
+    }
+    Some(BString::from(unquoted))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,4 +358,147 @@ mod tests {
         assert_eq!(it.next(), Some(Path::new("jkl")));
         assert!(it.next().is_none());
     }
+
+    #[test]
+    fn raw_diff_iteration() {
+        let old = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+        let new = "587be6b4c3f93f93c489c0111bba5596147a26cb";
+        let null = "0000000000000000000000000000000000000000";
+        let data = format!(
+            "{old} {new}\n:100644 100644 {old} {new} M\0a b\0:000000 120000 {null} {new} A\0c\0\
+             {new} {old}\n:160000 000000 {old} {null} D\0sub\0"
+        );
+        let raw_diff = RawDiff::new(data.into_bytes());
+        let mut it = raw_diff.iter();
+        let id = |hex: &str| gix::ObjectId::from_hex(hex.as_bytes()).unwrap();
+        assert_eq!(
+            it.next(),
+            Some(RawDiffEntry {
+                trees: Some((id(old), id(new))),
+                old_blob: Some(id(old)),
+                new_blob: Some(id(new)),
+                path: b"a b"
+            })
+        );
+        assert_eq!(
+            it.next(),
+            Some(RawDiffEntry {
+                trees: Some((id(old), id(new))),
+                old_blob: None,
+                new_blob: Some(id(new)),
+                path: b"c"
+            })
+        );
+        assert_eq!(
+            it.next(),
+            Some(RawDiffEntry {
+                trees: Some((id(new), id(old))),
+                old_blob: None,
+                new_blob: None,
+                path: b"sub"
+            })
+        );
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn diff_status_iteration() {
+        let statuses =
+            DiffFileStatuses::new(b"M\0abc\0A\0def\nghi\0R100\0old\0new\0D\0jkl\0".to_vec());
+        let mut it = statuses.iter();
+        assert_eq!(
+            it.next(),
+            Some(DiffFileStatus {
+                status: "M",
+                src_path: None,
+                path: Path::new("abc")
+            })
+        );
+        assert_eq!(
+            it.next(),
+            Some(DiffFileStatus {
+                status: "A",
+                src_path: None,
+                path: Path::new("def\nghi")
+            })
+        );
+        assert_eq!(
+            it.next(),
+            Some(DiffFileStatus {
+                status: "R100",
+                src_path: Some(Path::new("old")),
+                path: Path::new("new")
+            })
+        );
+        assert_eq!(
+            it.next(),
+            Some(DiffFileStatus {
+                status: "D",
+                src_path: None,
+                path: Path::new("jkl")
+            })
+        );
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn added_lines_parsing() {
+        let diff = b"diff --git a/a b/a\n\
+                     index 1234567..89abcde 100644\n\
+                     --- a/a\n\
+                     +++ b/a\n\
+                     @@ -1 +1,2 @@\n\
+                     -old\n\
+                     +new\n\
+                     +++ not a header\n\
+                     @@ -9,0 +11 @@ context\n\
+                     +eleven\n\
+                     diff --git a/with space b/with space\n\
+                     --- a/with space\t\n\
+                     +++ b/with space\t\n\
+                     @@ -1 +1 @@\n\
+                     -x\n\
+                     +y\n\
+                     diff --git a/bin b/bin\n\
+                     Binary files a/bin and b/bin differ\n\
+                     diff --git \"a/tab\\there\" \"b/tab\\there\"\n\
+                     new file mode 100644\n\
+                     --- /dev/null\n\
+                     +++ \"b/tab\\there\"\n\
+                     @@ -0,0 +1 @@\n\
+                     +\\303\\251\n\
+                     diff --git a/gone b/gone\n\
+                     deleted file mode 100644\n\
+                     --- a/gone\n\
+                     +++ /dev/null\n\
+                     @@ -1 +0,0 @@\n\
+                     -gone\n";
+        let mut parser = AddedLines::default();
+        let added: Vec<(String, usize, String)> = diff
+            .lines()
+            .filter_map(|line| {
+                parser.parse(line).map(|(path, line_number, content)| {
+                    (path.to_string(), line_number, content.to_string())
+                })
+            })
+            .collect();
+        assert_eq!(
+            added,
+            [
+                ("a".to_string(), 1, "new".to_string()),
+                ("a".to_string(), 2, "++ not a header".to_string()),
+                ("a".to_string(), 11, "eleven".to_string()),
+                ("with space".to_string(), 1, "y".to_string()),
+                ("tab\there".to_string(), 1, "\\303\\251".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unquote_paths() {
+        assert_eq!(unquote_path(b"plain").unwrap(), "plain");
+        assert_eq!(unquote_path(b"\"a\\tb\"").unwrap(), "a\tb");
+        assert_eq!(unquote_path(b"\"\\303\\251\\\"\"").unwrap(), "\u{e9}\"");
+        assert!(unquote_path(b"\"bad\\3\"").is_none());
+    }
 }
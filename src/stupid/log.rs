@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Options for `git log`.
+
+/// Options limiting which commits are shown by `git log`.
+#[derive(Default)]
+pub(crate) struct LogOptions {
+    pub(super) max_count: Option<usize>,
+    pub(super) since: Option<gix::date::Time>,
+    pub(super) until: Option<gix::date::Time>,
+}
+
+impl LogOptions {
+    /// Limit the number of commits shown.
+    pub(crate) fn max_count(&mut self, max_count: Option<usize>) -> &mut Self {
+        self.max_count = max_count;
+        self
+    }
+
+    /// Only show commits with committer dates at or after the given time.
+    pub(crate) fn since(&mut self, since: Option<gix::date::Time>) -> &mut Self {
+        self.since = since;
+        self
+    }
+
+    /// Only show commits with committer dates at or before the given time.
+    pub(crate) fn until(&mut self, until: Option<gix::date::Time>) -> &mut Self {
+        self.until = until;
+        self
+    }
+}
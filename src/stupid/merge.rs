@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Options for three-way merges performed with `git merge-recursive`.
+
+use bstr::{BString, ByteSlice, ByteVec};
+
+/// Label `git merge-recursive` gives the merge base in `diff3` style conflict markers.
+const CONSTRUCTED_BASE_LABEL: &[u8] = b"constructed merge base";
+
+/// Options affecting the conflict markers left by a three-way merge.
+///
+/// The style of the conflict markers follows the `merge.conflictStyle` configuration.
+pub(crate) struct MergeOptions<'a> {
+    pub(super) ancestor_label: &'a str,
+    pub(super) our_label: &'a str,
+    pub(super) their_label: &'a str,
+    pub(super) marker_size: Option<usize>,
+}
+
+impl Default for MergeOptions<'_> {
+    fn default() -> Self {
+        Self {
+            ancestor_label: "ancestor",
+            our_label: "current",
+            their_label: "patched",
+            marker_size: None,
+        }
+    }
+}
+
+impl<'a> MergeOptions<'a> {
+    /// Label for the merge base in `diff3` and `zdiff3` style conflict markers.
+    pub(crate) fn ancestor_label(&mut self, label: &'a str) -> &mut Self {
+        self.ancestor_label = label;
+        self
+    }
+
+    /// Label for our side of the merge in conflict markers.
+    pub(crate) fn our_label(&mut self, label: &'a str) -> &mut Self {
+        self.our_label = label;
+        self
+    }
+
+    /// Label for their side of the merge in conflict markers.
+    pub(crate) fn their_label(&mut self, label: &'a str) -> &mut Self {
+        self.their_label = label;
+        self
+    }
+
+    /// Length of conflict markers, for files without a `conflict-marker-size`
+    /// attribute.
+    ///
+    /// Git's default marker size of 7 is used when no size is given.
+    pub(crate) fn marker_size(&mut self, size: Option<usize>) -> &mut Self {
+        self.marker_size = size;
+        self
+    }
+
+    /// Relabel the merge base in the conflict markers of a conflicted file's content.
+    ///
+    /// `git merge-recursive` does not allow the merge base to be labeled, so the base
+    /// markers it writes are rewritten with the ancestor label instead. Returns `None`
+    /// if the content has no base markers.
+    pub(super) fn relabel_ancestor(&self, content: &[u8]) -> Option<BString> {
+        let mut relabeled = BString::default();
+        let mut found = false;
+        for line in content.lines_with_terminator() {
+            let marker_len = line.iter().take_while(|&&b| b == b'|').count();
+            let rest = &line[marker_len..];
+            if marker_len >= 7
+                && rest.starts_with(b" ")
+                && rest[1..].trim_end_with(|c| c == '\n' || c == '\r') == CONSTRUCTED_BASE_LABEL
+            {
+                found = true;
+                relabeled.push_str(&line[..=marker_len]);
+                relabeled.push_str(self.ancestor_label);
+                relabeled.push_str(&rest[1 + CONSTRUCTED_BASE_LABEL.len()..]);
+            } else {
+                relabeled.push_str(line);
+            }
+        }
+        found.then_some(relabeled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MergeOptions;
+
+    #[test]
+    fn relabel_ancestor_markers() {
+        let mut options = MergeOptions::default();
+        options.ancestor_label("ancestor 1234567");
+        let content = b"<<<<<<< current top\n\
+                        a\n\
+                        ||||||| constructed merge base\n\
+                        b\n\
+                        ||||||||||| constructed merge base\r\n\
+                        ===\n\
+                        || constructed merge base\n\
+                        >>>>>>> patch p1\n";
+        assert_eq!(
+            options.relabel_ancestor(content).unwrap(),
+            "<<<<<<< current top\n\
+             a\n\
+             ||||||| ancestor 1234567\n\
+             b\n\
+             ||||||||||| ancestor 1234567\r\n\
+             ===\n\
+             || constructed merge base\n\
+             >>>>>>> patch p1\n"
+        );
+        assert!(options.relabel_ancestor(b"||||||| base\n").is_none());
+    }
+}
@@ -6,10 +6,14 @@
 //! StGit. This module originally existed to overcome limitations of `libgit2`, but
 //! remains until `gitoxide` can replace its behaviors.
 
+mod apply;
 mod command;
 mod context;
 mod diff;
+mod log;
+mod merge;
 mod oid;
+pub(crate) mod program;
 mod status;
 mod tempindex;
 mod version;
@@ -17,8 +21,12 @@ mod version;
 use std::cell::RefCell;
 
 pub(crate) use self::{
+    apply::{ApplyOptions, ApplyReport},
     context::StupidContext,
-    status::{Status, StatusOptions, Statuses},
+    diff::DiffFormat,
+    log::LogOptions,
+    merge::MergeOptions,
+    status::{Status, StatusEntryKind, StatusOptions, Statuses},
 };
 
 pub(crate) trait Stupid<'repo, 'index> {
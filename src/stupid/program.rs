@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Selection of the git executable and the options passed to each git invocation.
+//!
+//! By default, `git` is found via `PATH` and run without any extra options. The
+//! executable may instead be chosen with the `--git-path` option or the `STG_GIT`
+//! environment variable, and `--git-opt` config options may be passed to every git
+//! subprocess.
+//!
+//! How git subprocesses contend for the index lock depends on the StGit command being
+//! run; see [`IndexLocking`].
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::{command::StupidOutput, version::StupidVersion};
+
+/// Minimum version of git required by StGit.
+const MIN_GIT_VERSION: (u16, u16, u16) = (2, 2, 0);
+
+static GIT_PROGRAM: OnceLock<GitProgram> = OnceLock::new();
+
+static INDEX_LOCKING: OnceLock<IndexLocking> = OnceLock::new();
+
+/// How git subprocesses contend for the index lock.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum IndexLocking {
+    /// Keep git from taking optional locks, e.g. to refresh the index as a side effect
+    /// of `git status`.
+    NoOptionalLocks,
+
+    /// Retry git commands that fail because another process holds the index lock until
+    /// the lock is released or the duration elapses.
+    Wait(Duration),
+}
+
+/// The git executable and config options used for git subprocesses.
+#[derive(Debug)]
+pub(crate) struct GitProgram {
+    path: PathBuf,
+    explicit: bool,
+    config: Vec<String>,
+}
+
+impl Default for GitProgram {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("git"),
+            explicit: false,
+            config: Vec::new(),
+        }
+    }
+}
+
+impl GitProgram {
+    /// Path to the git executable.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Config options, each of the form `<name>[=<value>]`, passed to git with `-c`.
+    pub(crate) fn config(&self) -> &[String] {
+        &self.config
+    }
+
+    /// Create a [`Command`] for running git with the config options applied.
+    pub(crate) fn command(&self) -> Command {
+        let mut command = Command::new(&self.path);
+        for opt in &self.config {
+            command.arg("-c").arg(opt);
+        }
+        if let Some(IndexLocking::NoOptionalLocks) = index_locking() {
+            command.env("GIT_OPTIONAL_LOCKS", "0");
+        }
+        command
+    }
+
+    /// Setup the environment of a non-git child process, such as a hook or a
+    /// user-provided command, such that the git commands it runs are affected by the
+    /// selected executable and config options in the same way as StGit's own.
+    pub(crate) fn setup_child_env(&self, command: &mut Command) {
+        if self.explicit {
+            if let Some(dir) = self.path.parent().filter(|dir| dir.is_absolute()) {
+                let mut paths = vec![dir.to_path_buf()];
+                if let Some(path_var) = std::env::var_os("PATH") {
+                    paths.extend(std::env::split_paths(&path_var));
+                }
+                if let Ok(path_var) = std::env::join_paths(paths) {
+                    command.env("PATH", path_var);
+                }
+            }
+        }
+        if !self.config.is_empty() {
+            let mut parameters = std::env::var_os("GIT_CONFIG_PARAMETERS").unwrap_or_default();
+            for opt in &self.config {
+                if !parameters.is_empty() {
+                    parameters.push(" ");
+                }
+                parameters.push(sq_quote(opt));
+            }
+            command.env("GIT_CONFIG_PARAMETERS", parameters);
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let output = Command::new(&self.path)
+            .arg("version")
+            .output()
+            .with_context(|| {
+                format!(
+                    "git executable `{}` could not be run",
+                    self.path.to_string_lossy()
+                )
+            })?
+            .require_success("version")?;
+        let version_line = String::from_utf8_lossy(&output.stdout);
+        let version = version_line
+            .trim_end()
+            .parse::<StupidVersion>()
+            .with_context(|| format!("`{}`", self.path.to_string_lossy()))?;
+        let (major, minor, micro) = MIN_GIT_VERSION;
+        if version < StupidVersion::new(major, minor, micro) {
+            Err(anyhow!(
+                "git executable `{}` is too old: `{}`; \
+                 StGit requires git {major}.{minor}.{micro} or newer",
+                self.path.to_string_lossy(),
+                version_line.trim_end(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Get the git executable and options selected for this process.
+pub(crate) fn git_program() -> &'static GitProgram {
+    GIT_PROGRAM.get_or_init(GitProgram::default)
+}
+
+/// Select the git executable and config options for all git subprocesses.
+///
+/// The executable is taken from `path` if provided, otherwise from the `STG_GIT`
+/// environment variable, otherwise `git` is found via `PATH`. An explicitly chosen
+/// executable is checked to exist and to meet the minimum git version. Relative paths
+/// are resolved against the current directory, so this must be called before any `-C`
+/// directory changes.
+pub(crate) fn setup(path: Option<&OsStr>, config: Vec<String>) -> Result<()> {
+    let env_path = std::env::var_os("STG_GIT").filter(|path| !path.is_empty());
+    let program = if let Some(path) = path.map(OsString::from).or(env_path) {
+        let path = PathBuf::from(path);
+        let path = if path.components().count() > 1 && path.is_relative() {
+            std::env::current_dir()?.join(path)
+        } else {
+            path
+        };
+        let program = GitProgram {
+            path,
+            explicit: true,
+            config,
+        };
+        program.validate()?;
+        program
+    } else {
+        GitProgram {
+            config,
+            ..Default::default()
+        }
+    };
+    GIT_PROGRAM
+        .set(program)
+        .expect("git program should only be setup once");
+    Ok(())
+}
+
+/// Get how git subprocesses contend for the index lock, if selected.
+pub(crate) fn index_locking() -> Option<IndexLocking> {
+    INDEX_LOCKING.get().copied()
+}
+
+/// Select how git subprocesses contend for the index lock.
+pub(crate) fn set_index_locking(locking: IndexLocking) {
+    INDEX_LOCKING
+        .set(locking)
+        .expect("index locking should only be setup once");
+}
+
+/// Quote a string for use in `GIT_CONFIG_PARAMETERS`.
+fn sq_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_config_parameters() {
+        assert_eq!(sq_quote("protocol.version=2"), "'protocol.version=2'");
+        assert_eq!(sq_quote("a.b=it's"), r"'a.b=it'\''s'");
+    }
+}
@@ -71,6 +71,35 @@ pub(crate) fn specialize_template(
     template: &str,
     replacements: &HashMap<&str, Cow<'_, BStr>>,
 ) -> Vec<u8> {
+    expand_template(
+        template,
+        |name| {
+            replacements
+                .get(name)
+                .map(|replacement| Cow::Borrowed(replacement.as_ref()))
+        },
+        false,
+    )
+    .expect("expansion of unknown placeholders is not an error")
+}
+
+/// Specialize a template, looking up each placeholder's replacement.
+///
+/// The template syntax is the same as for [`specialize_template()`], but unlike with
+/// that function, a placeholder for which `lookup` has no replacement is an error
+/// naming the unknown placeholder.
+pub(crate) fn try_specialize_template<'a>(
+    template: &str,
+    lookup: impl FnMut(&str) -> Option<Cow<'a, BStr>>,
+) -> Result<Vec<u8>> {
+    expand_template(template, lookup, true)
+}
+
+fn expand_template<'a>(
+    template: &str,
+    mut lookup: impl FnMut(&str) -> Option<Cow<'a, BStr>>,
+    strict: bool,
+) -> Result<Vec<u8>> {
     enum State {
         Start,
         Percent,
@@ -108,8 +137,10 @@ pub(crate) fn specialize_template(
             }
             State::ClosedParen => {
                 if c == 's' {
-                    if let Some(replacement) = replacements.get(name.as_str()) {
+                    if let Some(replacement) = lookup(name.as_str()) {
                         special.extend(replacement.iter());
+                    } else if strict {
+                        return Err(anyhow!("unknown template placeholder `%({name})s`"));
                     } else {
                         special.push_str("%(");
                         special.push_str(name.as_str());
@@ -134,7 +165,7 @@ pub(crate) fn specialize_template(
         }
     }
 
-    special.into()
+    Ok(special.into())
 }
 
 /// Default patch export template.
@@ -147,3 +178,14 @@ From: %(authname)s <%(authemail)s>
 ---
 %(diffstat)s
 ";
+
+/// Default template for patches exported to a single mbox file with `--combined`.
+pub(crate) const PATCHEXPORT_MBOX_TMPL: &str = "\
+From: %(authname)s <%(authemail)s>
+Date: %(authdate)s
+Subject: [PATCH] %(shortdescr)s
+
+%(longdescr)s
+---
+%(diffstat)s
+";
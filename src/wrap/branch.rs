@@ -5,7 +5,7 @@ use std::str::FromStr;
 use anyhow::{anyhow, Result};
 use bstr::ByteSlice;
 
-use crate::wrap::PartialRefName;
+use crate::{ext::RepositoryExtended, wrap::PartialRefName};
 
 #[derive(Clone, Debug)]
 pub(crate) struct Branch<'repo> {
@@ -44,6 +44,26 @@ impl<'repo> Branch<'repo> {
         Ok(self.inner.id().object()?.try_into_commit()?)
     }
 
+    /// Ensure the branch is not checked out in a worktree other than the current one.
+    ///
+    /// Updating a branch that is checked out in another worktree would leave that
+    /// worktree's index and work tree out of sync with its HEAD.
+    pub(crate) fn check_not_checked_out_elsewhere(&self) -> Result<()> {
+        if let Some(path) = self
+            .inner
+            .repo
+            .find_other_worktree_with_branch(self.get_reference_name())?
+        {
+            Err(anyhow!(
+                "branch `{}` is checked out in another worktree at `{}`",
+                self.get_branch_name()?,
+                path.display()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     pub(crate) fn delete(self) -> Result<()> {
         self.inner.delete()?;
         Ok(())
@@ -11,6 +11,10 @@
 GNUPGHOME="$PWD/gpghome"
 export GNUPGHOME
 
+GPGSSH_HOME="$PWD/gpgssh"
+GPGSSH_KEY="$GPGSSH_HOME/ed25519_ssh_signing_key"
+GPGSSH_ALLOWED_SIGNERS="$GPGSSH_HOME/allowed_signers"
+
 test_lazy_prereq GPG '
 	gpg_version=$(gpg --version 2>&1)
 	test $? != 127 || exit 1
@@ -53,6 +57,17 @@ test_lazy_prereq GPG '
 	esac
 '
 
+test_lazy_prereq GPGSSH '
+	ssh_version=$(ssh-keygen -Y find-principals -n "git" 2>&1)
+	test $? != 127 || exit 1
+	echo $ssh_version | grep -q "find-principals:missing signature file" ||
+		exit 1
+	mkdir -p "$GPGSSH_HOME" &&
+	chmod 0700 "$GPGSSH_HOME" &&
+	ssh-keygen -t ed25519 -N "" -C "stgit ed25519 key" -f "$GPGSSH_KEY" >/dev/null &&
+	echo "\"principal with number 1\" $(cat "$GPGSSH_KEY.pub")" >"$GPGSSH_ALLOWED_SIGNERS"
+'
+
 sanitize_pgp() {
 	perl -ne '
 		/^-----END PGP/ and $in_pgp = 0;
@@ -19,13 +19,163 @@ test_expect_success 'Init repo' '
     done
 '
 
-test_expect_success 'Too many arguments' '
-    general_error stg id patch-1 patch-2 2>err &&
-    grep -e "unexpected argument .patch-2." err
+test_expect_success 'Multiple arguments' '
+    stg id patch-1 patch-2 >out &&
+    printf "%s\n" "$(git rev-parse HEAD~)" "$(git rev-parse HEAD)" >expected &&
+    test_cmp expected out
 '
 
 test_expect_success 'Provide patch argument' '
     test "$(echo $(stg id))" = "$(echo $(stg id $(stg top)))"
 '
 
+test_expect_success 'Setup more patches' '
+    printf "%s\n" out expected err >>.git/info/exclude &&
+    for i in 3 4; do
+        echo "line $i" >>foo.txt &&
+        stg new -m "patch-$i" &&
+        stg refresh || return 1
+    done &&
+    stg pop &&
+    stg new -m "patch-5" &&
+    stg pop &&
+    stg hide patch-5 &&
+    test "$(echo $(stg series --all))" = "+ patch-1 + patch-2 > patch-3 - patch-4 ! patch-5"
+'
+
+test_expect_success 'Ids of a patch range' '
+    stg id patch-2..patch-4 >out &&
+    printf "%s\n" $(stg id patch-2) $(stg id patch-3) $(stg id patch-4) >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Open-ended ranges exclude hidden patches' '
+    stg id patch-3.. >out &&
+    printf "%s\n" $(stg id patch-3) $(stg id patch-4) >expected &&
+    test_cmp expected out &&
+    stg id ..patch-2 >out &&
+    printf "%s\n" $(stg id patch-1) $(stg id patch-2) >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Ids with patch names' '
+    stg id --patch-names patch-1..patch-2 HEAD {base} >out &&
+    cat >expected <<-EOF &&
+	patch-1 $(stg id patch-1)
+	patch-2 $(stg id patch-2)
+	patch-3 $(stg id patch-3)
+	- $(stg id {base})
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Format commits like git log' '
+    format="%H|%h|%T|%t|%P|%p|%an|%ae|%ad|%ai|%at|%cn|%ce|%cd|%ci|%ct|%s|%b|100%%" &&
+    stg id --format "$format" patch-2 patch-1 patch-2 >out &&
+    for rev in patch-2 patch-1 patch-2; do
+        git log -1 --format="$format" "$(stg id $rev)" || return 1
+    done >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Format ranges, branch prefixes, and git suffixes' '
+    stg branch --clone formatted &&
+    stg branch master &&
+    stg id --format "%s %p" formatted:patch-2..patch-3 formatted:{base}^0 >out &&
+    cat >expected <<-EOF &&
+	patch-2 $(git rev-parse --short $(stg id patch-1))
+	patch-3 $(git rev-parse --short $(stg id patch-2))
+	initial $(git log -1 --format=%p $(stg id {base}))
+	EOF
+    test_cmp expected out &&
+    stg id --format "%n%h" {base} >out &&
+    printf "\n%s\n" "$(git rev-parse --short $(stg id {base}))" >expected &&
+    test_cmp expected out &&
+    stg branch --delete --force formatted
+'
+
+test_expect_success 'Invalid formats' '
+    general_error stg id --format "%h %x" 2>err &&
+    grep -e "unknown placeholder .%x." err &&
+    general_error stg id --format "%h" --patch-names 2>err &&
+    grep -e "cannot be used with" err &&
+    command_error stg id --format "%h" "HEAD^{tree}" 2>err &&
+    grep -e "error: .HEAD^{tree}. is not a commit" err
+'
+
+test_expect_success 'Ids of a range from another branch' '
+    stg branch --clone other &&
+    stg id --patch-names ..patch-2 >expected &&
+    stg branch master &&
+    stg id --patch-names other:..patch-2 >out &&
+    test_cmp expected out &&
+    stg branch --delete --force other
+'
+
+test_expect_success 'Stack positions of another branch' '
+    stg branch --clone other &&
+    stg pop -n 2 &&
+    stg branch master &&
+    test "$(stg id other:{base})" = "$(stg id {base})" &&
+    test "$(stg id other:@)" = "$(stg id patch-1)" &&
+    test "$(stg id other:@+1)" = "$(stg id patch-2)" &&
+    test "$(stg id other:^)" = "$(stg id patch-4)" &&
+    test "$(stg id other:^1)" = "$(stg id patch-3)" &&
+    test "$(stg id other:2)" = "$(stg id patch-3)" &&
+    test "$(stg id other:{base}+2)" = "$(stg id patch-2)" &&
+    test "$(stg id other:patch-3^)" = "$(stg id patch-2)" &&
+    stg id --patch-names other:{base}+1..other:+3 >out &&
+    stg id --patch-names patch-1..patch-3 >expected &&
+    test_cmp expected out &&
+    stg branch --delete --force other
+'
+
+test_expect_success 'Base of another branch with offsets and git suffix' '
+    git checkout -b side &&
+    echo side >side.txt &&
+    git add side.txt &&
+    git commit -m side &&
+    git checkout -b merged HEAD~ &&
+    echo merged >merged.txt &&
+    git add merged.txt &&
+    git commit -m merged &&
+    git merge --no-edit side &&
+    test_commit_bulk --message="after %s" 4 &&
+    stg init &&
+    stg uncommit &&
+    stg branch master &&
+    test "$(stg id merged:{base})" = "$(git rev-parse merged~)" &&
+    test "$(stg id merged:{base}~3^2)" = "$(git rev-parse side)" &&
+    test "$(stg id merged:{base}~3^2~)" = "$(git rev-parse side~)" &&
+    test "$(stg id merged:@~4^1)" = "$(git rev-parse merged~4^1)" &&
+    stg branch --delete --force merged &&
+    git branch -D side
+'
+
+test_expect_success 'Branch-qualified revisions of a branch without a stack' '
+    git branch nostack &&
+    no_stack_error stg id nostack:{base} 2>err &&
+    grep -e "StGit stack not initialized for branch .nostack." err &&
+    no_stack_error stg id nostack:@ 2>err &&
+    grep -e "StGit stack not initialized for branch .nostack." err &&
+    no_stack_error stg id nostack:..@ 2>err &&
+    grep -e "StGit stack not initialized for branch .nostack." err &&
+    git branch -D nostack
+'
+
+test_expect_success 'Ranges may not end on another branch' '
+    stg branch --clone other &&
+    stg branch master &&
+    general_error stg id other:patch-1..master:patch-2 &&
+    stg branch --delete --force other
+'
+
+test_expect_success 'Range resolving to no patches' '
+    stg branch --create empty &&
+    command_error stg id .. 2>err &&
+    grep -e "no patches match \`..\`" err &&
+    stg branch master &&
+    stg branch --delete --force empty
+'
+
 test_done
@@ -26,8 +26,9 @@ test_expect_success 'Test help on regular command' '
 
 test_expect_success 'Test help on ambiguous command' '
     general_error stg pu 2>err &&
-    grep -e "unrecognized subcommand .pu." err &&
-    grep -e "some similar subcommands exist: .pull., .push." err &&
+    grep -e "ambiguous command .pu." err &&
+    grep -e "^    pull$" err &&
+    grep -e "^    push$" err &&
     general_error stg help pu 2>err &&
     grep -e "unrecognized subcommand .pu." err
 '
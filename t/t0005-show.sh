@@ -144,4 +144,113 @@ test_expect_success 'Multiple path limits' '
     grep -e "ccc\.txt" out
 '
 
+test_expect_success 'Setup patch history' '
+    stg new -m hist &&
+    echo one >hist.txt &&
+    stg add hist.txt &&
+    stg refresh &&
+    echo two >>hist.txt &&
+    stg refresh &&
+    stg pop &&
+    stg push &&
+    stg rename hist hist-renamed &&
+    echo three >>hist.txt &&
+    stg refresh
+'
+
+test_expect_success 'Invalid history options' '
+    general_error stg show --history hist-renamed --stat &&
+    general_error stg show --history hist-renamed many-paths &&
+    general_error stg show --list
+'
+
+test_expect_success 'List patch history across rename' '
+    stg show --history hist-renamed --list >out &&
+    test_line_count = 4 out &&
+    head -n 1 out | grep -E "^1 [0-9a-f]+ hist new: hist$" &&
+    sed -n 2p out | grep -E "^2 [0-9a-f]+ hist refresh hist$" &&
+    sed -n 3p out | grep -E "^3 [0-9a-f]+ hist refresh hist$" &&
+    tail -n 1 out | grep -E "^4 [0-9a-f]+ hist-renamed refresh hist-renamed$" &&
+    test "$(tail -n 1 out | cut -d" " -f2)" = "$(git rev-parse --short $(stg id hist-renamed))"
+'
+
+test_expect_success 'Show interdiffs of patch history' '
+    stg show --full-history hist-renamed --color=never >out &&
+    test $(grep -c "^Version" out) = 3 &&
+    grep "^Version 3 -> 4: refresh hist-renamed" out &&
+    grep -e "++three" out
+'
+
+test_expect_success 'History of patch with one version' '
+    stg new -m single &&
+    stg show --history single --list >out &&
+    test_line_count = 1 out &&
+    command_error stg show --history single 2>err &&
+    grep "patch .single. has only one version in the stack log" err
+'
+
+test_expect_success 'Show messages only' '
+    stg show --message-only patch-aaa..patch-ccc >out &&
+    cat >expected <<-\EOF &&
+	patch-aaa
+
+	patch-bbb
+
+	patch-ccc
+	EOF
+    test_cmp expected out &&
+    ! grep -e "^diff" out
+'
+
+test_expect_success 'Show messages with separator' '
+    stg show --message-only --separator=--- patch-aaa patch-bbb >out &&
+    printf "patch-aaa\n---\npatch-bbb\n" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Show NUL-terminated messages' '
+    stg show --message-only -z patch-aaa patch-bbb >out &&
+    printf "patch-aaa\n\0patch-bbb\n\0" >expected &&
+    test_cmp expected out &&
+    printf "patch-bbb\0patch-aaa\0" | stg show --stdin -z --message-only >out &&
+    printf "patch-bbb\n\0patch-aaa\n\0" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Show raw commit objects' '
+    stg show --raw patch-aaa patch-bbb >out &&
+    {
+        git cat-file commit $(stg id patch-aaa) &&
+        echo &&
+        git cat-file commit $(stg id patch-bbb)
+    } >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Show messages of hidden patches and other branch' '
+    stg branch --clone other-branch &&
+    stg hide patch-bbb &&
+    stg show --message-only -H >out &&
+    echo patch-bbb >expected &&
+    test_cmp expected out &&
+    stg show --message-only patch-bbb patch-aaa..patch-ccc >out &&
+    printf "patch-bbb\n\npatch-aaa\n\npatch-ccc\n" >expected &&
+    test_cmp expected out &&
+    stg branch master &&
+    stg show --message-only --branch=other-branch patch-bbb >out &&
+    echo patch-bbb >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Invalid message-only options' '
+    general_error stg show --message-only --stat 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg show --raw --message-only 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg show --separator=x 2>err &&
+    grep -e "required arguments were not provided" err &&
+    general_error stg show -z 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
 test_done
@@ -22,6 +22,12 @@ test_expect_success 'Invalid bare and stat' '
     general_error stg files --bare --stat
 '
 
+test_expect_success 'Invalid json and null' '
+    general_error stg files --json -z &&
+    general_error stg files --json --bare &&
+    general_error stg files -z --stat
+'
+
 test_expect_success 'Too many arguments' '
     general_error stg files patch-a-b patch-b-c
 '
@@ -88,4 +94,47 @@ test_expect_success 'Moved file bare' '
     test_cmp a-d-bare.log expected-a-d-bare.log
 '
 
+test_expect_success 'NUL-terminated output' '
+    stg files -z patch-b-c | tr "\\000" "|" >b-c-null.log &&
+    printf "M|b.txt|A|c.txt|" >expected-b-c-null.log &&
+    test_cmp expected-b-c-null.log b-c-null.log &&
+    stg files -z --bare patch-b-c | tr "\\000" "|" >b-c-null-bare.log &&
+    printf "b.txt|c.txt|" >expected-b-c-null-bare.log &&
+    test_cmp expected-b-c-null-bare.log b-c-null-bare.log
+'
+
+test_expect_success 'JSON output' '
+    stg files --json patch-b-c >b-c.json &&
+    cat >expected-b-c.json <<-\EOF &&
+	[
+	  {
+	    "path": "b.txt",
+	    "status": "M"
+	  },
+	  {
+	    "path": "c.txt",
+	    "status": "A"
+	  }
+	]
+	EOF
+    test_cmp expected-b-c.json b-c.json &&
+    stg files --json empty-patch >empty.json &&
+    echo "[]" >expected-empty.json &&
+    test_cmp expected-empty.json empty.json
+'
+
+test_expect_success 'Path with space and newline' '
+    stg new -m patch-odd &&
+    echo odd >"odd
+name.txt" &&
+    stg add "odd
+name.txt" &&
+    stg refresh &&
+    stg files -z | tr "\\000\\n" "|^" >odd-null.log &&
+    printf "A|odd^name.txt|" >expected-odd-null.log &&
+    test_cmp expected-odd-null.log odd-null.log &&
+    stg files --json >odd.json &&
+    grep -F "\"path\": \"odd\\nname.txt\"" odd.json
+'
+
 test_done
@@ -80,6 +80,50 @@ test_expect_success 'Test author' '
     test_cmp expected.txt series.txt
 '
 
+test_expect_success 'Test author and date' '
+    stg series --author --date --description >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ p0 # A Ú Thor # 2005-04-01 13:14:15 +0200 # message 0
+	+ p1 # B Author # 2005-04-01 13:14:15 +0200 # message 1
+	> p2 # A Ú Thor # 2005-04-01 13:14:15 +0200 # message 2
+	- p3 # A Ú Thor # 2005-04-01 13:14:15 +0200 # message 3
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test date formats' '
+    stg series --date=committer --date-format=short >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ p0 # 2005-04-01
+	+ p1 # 2005-04-01
+	> p2 # 2005-04-01
+	- p3 # 2005-04-01
+	EOF
+    test_cmp expected.txt series.txt &&
+    stg series --date --date-format=relative >series.txt &&
+    test_line_count = 4 series.txt &&
+    grep -e "^+ p0 # [0-9]* years ago$" series.txt &&
+    general_error stg series --date-format=short 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_expect_success 'Test author with mailmap' '
+    test_when_finished "rm .mailmap" &&
+    echo "Bee <author@example.com> B Author <author@example.com>" >.mailmap &&
+    stg series --author --date=author --date-format=short >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ p0 # A Ú Thor # 2005-04-01
+	+ p1 # Bee      # 2005-04-01
+	> p2 # A Ú Thor # 2005-04-01
+	- p3 # A Ú Thor # 2005-04-01
+	EOF
+    test_cmp expected.txt series.txt &&
+    test_config log.mailmap false &&
+    stg series --author p1 >series.txt &&
+    echo "+ p1 # B Author" >expected.txt &&
+    test_cmp expected.txt series.txt
+'
+
 test_expect_success 'Test description' '
     stg series --description >series.txt 2>error.txt &&
     test_line_count = 4 series.txt &&
@@ -160,6 +204,39 @@ test_expect_success 'Test effects' '
     test_line_count = 3 series.txt
 '
 
+test_expect_success 'Test short commit ids' '
+    stg series --short-id >series.txt &&
+    for p in p0 p1; do
+        echo "+ $(git rev-parse --short $(stg id $p)) $p" || return 1
+    done >expected.txt &&
+    echo "> $(git rev-parse --short $(stg id p2)) p2" >>expected.txt &&
+    echo "- $(git rev-parse --short $(stg id p3)) p3" >>expected.txt &&
+    test_cmp expected.txt series.txt &&
+    stg series --commit-id=short >series.txt &&
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test short commit ids with core.abbrev' '
+    test_config core.abbrev 12 &&
+    stg series --short-id --noprefix --description p1 >series.txt &&
+    echo "$(git rev-parse --short=12 $(stg id p1)) p1 # message 1" >expected.txt &&
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test commit id options override each other' '
+    stg series --commit-id=8 --short-id --noprefix p1 >series.txt &&
+    echo "$(git rev-parse --short $(stg id p1)) p1" >expected.txt &&
+    test_cmp expected.txt series.txt &&
+    stg series --short-id --commit-id=8 --noprefix p1 >series.txt &&
+    echo "$(git rev-parse --short=8 $(stg id p1)) p1" >expected.txt &&
+    test_cmp expected.txt series.txt &&
+    stg series --short-id --no-commit-id --noprefix p1 >series.txt &&
+    echo "p1" >expected.txt &&
+    test_cmp expected.txt series.txt &&
+    general_error stg series --commit-id=3 2>err &&
+    grep -e "length must be \"full\", \"short\", or an integer" err
+'
+
 test_expect_success 'Test missing' '
     stg branch --clone -- other &&
     test "$(stg branch)" = "other" &&
@@ -5,7 +5,7 @@ test_description='Test the log command'
 . ./test-lib.sh
 
 test_expect_success 'Attempt log on uninitialized branch' '
-    command_error stg log 2>err >/dev/null &&
+    no_stack_error stg log 2>err >/dev/null &&
     grep -e "StGit stack not initialized for branch \`master\`" err
 '
 
@@ -136,6 +136,26 @@ test_expect_success 'Log with number' '
     head -n 3 log.txt | tail -n 1 | grep -e "refresh"
 '
 
+test_expect_success 'Log oneline' '
+    stg log --oneline -n2 p2 p3 >log.txt &&
+    test_line_count = 2 log.txt &&
+    head -n 1 log.txt | grep -E "^[0-9a-f]{7,} edit: p3 \(.* ago\)$" &&
+    head -n 2 log.txt | tail -n 1 | grep -E "^[0-9a-f]{7,} goto p3 \(.* ago\)$"
+'
+
+test_expect_success 'Log abbreviates per core.abbrev' '
+    test_config core.abbrev 12 &&
+    stg log -n1 >log.txt &&
+    grep -E "^[0-9a-f]{12}   " log.txt &&
+    stg log --oneline -n1 >log.txt &&
+    grep -E "^[0-9a-f]{12} " log.txt
+'
+
+test_expect_success 'Log oneline conflicts with full' '
+    general_error stg log --oneline --full 2>err &&
+    grep -e "cannot be used with" err
+'
+
 test_expect_success 'Clear the log' '
     stg log --clear &&
     test "$(echo $(stg series --noprefix))" = "p0 p1 p2 p3" &&
@@ -144,4 +164,114 @@ test_expect_success 'Clear the log' '
     head -n 1 log.txt | grep -e "clear log"
 '
 
+test_expect_success 'Make dated stack changes' '
+    GIT_COMMITTER_DATE="2022-01-01 10:00:00 +0000" stg pop &&
+    GIT_COMMITTER_DATE="2022-01-02 10:00:00 +0000" stg pop &&
+    GIT_COMMITTER_DATE="2022-01-03 10:00:00 +0000" stg push
+'
+
+test_expect_success 'Log since date' '
+    stg log --oneline --since "2022-01-02 00:00:00 +0000" >log.txt &&
+    test_line_count = 2 log.txt &&
+    head -n 1 log.txt | grep -e "push p2" &&
+    tail -n 1 log.txt | grep -e "pop p2"
+'
+
+test_expect_success 'Log until date' '
+    stg log --oneline --until "2022-01-02 10:00:00 +0000" >log.txt &&
+    test_line_count = 3 log.txt &&
+    head -n 1 log.txt | grep -e "pop p2" &&
+    tail -n 1 log.txt | grep -e "clear log"
+'
+
+test_expect_success 'Log between dates' '
+    stg log --oneline --since "2022-01-01 12:00:00 +0000" --until "2022-01-02 12:00:00 +0000" >log.txt &&
+    test_line_count = 1 log.txt &&
+    grep -e "pop p2" log.txt
+'
+
+test_expect_success 'Log with relative date' '
+    stg log --oneline --since "2 days ago" >log.txt &&
+    test_must_be_empty log.txt &&
+    stg log --oneline --until yesterday >log.txt &&
+    test_line_count = 4 log.txt
+'
+
+test_expect_success 'Log with invalid date' '
+    general_error stg log --since "not a date" 2>err &&
+    grep -e "invalid date \`not a date\`" err
+'
+
+test_expect_success 'Setup patch rework' '
+    before_state=$(stg log --oneline -n1 | cut -d" " -f1) &&
+    stg new -m "cmp" cmp &&
+    echo one >cmp.txt &&
+    stg add cmp.txt &&
+    stg refresh &&
+    old_state=$(stg log --oneline -n1 | cut -d" " -f1) &&
+    echo two >>cmp.txt &&
+    stg refresh &&
+    stg edit -m "cmp reworded" cmp &&
+    stg rename cmp cmp-renamed
+'
+
+test_expect_success 'Compare patch to historical stack state' '
+    stg log --compare $old_state --color=never cmp-renamed >out &&
+    head -n 1 out | grep -e "^Since $old_state: refresh cmp (as \`cmp\`)$" &&
+    grep -e "^1: .* ! 1: .* cmp$" out &&
+    grep -e "-    cmp$" out &&
+    grep -e "+    cmp reworded$" out &&
+    grep -e "++two$" out
+'
+
+test_expect_success 'Compare patch to stack state n entries ago' '
+    stg log --range-diff 2 --color=never cmp-renamed >out &&
+    state=$(stg log --oneline -n3 | tail -n 1 | cut -d" " -f1) &&
+    head -n 1 out | grep -e "^Since $state: " &&
+    grep -e "cmp reworded$" out &&
+    ! grep -e "++two$" out
+'
+
+test_expect_success 'Compare patch with diff options' '
+    stg log --compare $old_state -O --stat --color=never cmp-renamed >out &&
+    grep -e "1 file changed" out &&
+    ! grep -e "++two$" out
+'
+
+test_expect_success 'Compare patch after undo' '
+    stg undo &&
+    stg log --range-diff 1 --color=never cmp >out &&
+    head -n 1 out | grep -e "(as \`cmp-renamed\`)$" &&
+    grep -e "^1: .* = 1: .* cmp reworded$" out &&
+    stg redo
+'
+
+test_expect_success 'Compare patch not in historical stack state' '
+    command_error stg log --compare $before_state cmp-renamed 2>err &&
+    grep -e "patch \`cmp-renamed\` does not exist in stack state \`$before_state\`" err
+'
+
+test_expect_success 'Compare to commit not in the stack log' '
+    command_error stg log --compare HEAD cmp-renamed 2>err &&
+    grep -e "is not in the stack log of branch \`master\`" err
+'
+
+test_expect_success 'Compare to more stack log entries than exist' '
+    command_error stg log --range-diff 1000 cmp-renamed 2>err &&
+    grep -e "the stack log has fewer than 1001 entries" err
+'
+
+test_expect_success 'Compare requires exactly one patch' '
+    general_error stg log --range-diff 1 2>err &&
+    grep -e "required arguments were not provided" err &&
+    command_error stg log --range-diff 1 p0 cmp-renamed 2>err &&
+    grep -e "exactly one patch must be given to compare" err
+'
+
+test_expect_success 'Invalid compare options' '
+    general_error stg log --range-diff 1 --oneline cmp-renamed &&
+    general_error stg log --range-diff 1 --compare $old_state cmp-renamed &&
+    general_error stg log -O --stat cmp-renamed
+'
+
 test_done
@@ -5,8 +5,8 @@ test_description='Test aliases'
 . ./test-lib.sh
 
 test_expect_success 'Test help on builtin alias command' '
-    stg help add >out &&
-    grep -e "Alias for shell command \`git -C \"\$GIT_PREFIX\" add\`" out
+    stg help status >out &&
+    grep -e "Alias for shell command \`git status -s\`" out
 '
 
 test_expect_success 'Test ambiguous alias' '
@@ -15,7 +15,9 @@ test_expect_success 'Test ambiguous alias' '
     stg init &&
     stg show &&
     general_error stg sho 2>err &&
-    grep -e "tip: some similar subcommands exist: .show-stat., .show." err
+    grep -e "ambiguous command .sho." err &&
+    grep -e "^    show$" err &&
+    grep -e "^    show-stat$" err
 '
 
 test_expect_success 'Setup top-level and nested aliases' '
@@ -95,4 +95,133 @@ test_expect_success 'Disjoint ranges' '
     grep -e "error: \`p5\.\.\` not contiguous with preceding range \`p1\.\.p2\`" err
 '
 
+test_expect_success 'Disjoint ranges hint at including gaps' '
+    command_error stg series p1 p4 2>err &&
+    grep -e "use \`--include-gaps\` to also include p2, p3" err
+'
+
+test_expect_success 'Include gaps between disjoint ranges' '
+    stg series --include-gaps p4 p1 >series.txt 2>err &&
+    grep -e "including patches in between: p2, p3" err &&
+    cat >expected.txt <<-\EOF &&
+	+ p1
+	+ p2
+	> p3
+	- p4
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Include gaps never includes hidden patches' '
+    stg hide p5 &&
+    command_error stg series --include-gaps p4 p6 2>err &&
+    grep -e "would include hidden patch \`p5\`" err &&
+    stg unhide p5
+'
+
+test_expect_success 'Range end relative to begin anchor' '
+    stg series p1+1..+2 >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ p2
+	> p3
+	EOF
+    test_cmp expected.txt series.txt &&
+    stg series p2+1..p2+3 >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	> p3
+	- p4
+	- p5
+	EOF
+    test_cmp expected.txt series.txt &&
+    stg series p2+1..+3 >series2.txt &&
+    test_cmp series.txt series2.txt
+'
+
+test_expect_success 'Anchored range end is not bounded by applied patches' '
+    stg series p4..+2 >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	- p4
+	- p5
+	! p6
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Anchored range end constrained by command' '
+    command_error stg push p4..+2 2>err &&
+    grep -e "hidden patch \`p6\` is not allowed" err &&
+    command_error stg pop p2..+2 2>err &&
+    grep -e "patch \`p4\` is already unapplied" err &&
+    test "$(stg top)" = "p3"
+'
+
+test_expect_success 'Range with exclusions' '
+    stg series p1..p5 "!p1" "!~" >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	> p3
+	- p4
+	- p5
+	EOF
+    test_cmp expected.txt series.txt &&
+    stg series p1..p5 "!p1" "!p5" >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ p2
+	> p3
+	- p4
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Exclusion splitting a contiguous range' '
+    command_error stg series p1..p5 "!p3" 2>err &&
+    grep -e "error: excluding patch .p3. would split the selected patches" err
+'
+
+test_expect_success 'Exclusion of patch not in the range' '
+    command_error stg series p1..p3 "!p4" 2>err &&
+    grep -e "error: excluded patch .p4. is not among the selected patches" err
+'
+
+test_expect_success 'All patch range errors are reported' '
+    command_error stg series p1 px p2 py..p3 p1 2>err &&
+    cat >expected.txt <<-\EOF &&
+	error: 3 errors resolving patches:
+	  patch `px` does not exist
+	  patch `py` does not exist
+	  patch `p1` is used more than once
+	EOF
+    test_cmp expected.txt err
+'
+
+test_expect_success 'Callers rephrase each of the errors' '
+    command_error stg pop p1 p4 px 2>err &&
+    grep -e "^error: 2 errors resolving patches:$" err &&
+    grep -e "^  patch .p4. is already unapplied$" err &&
+    grep -e "^  patch .px. does not exist" err &&
+    command_error stg unhide p1 p6 p2 2>err &&
+    grep -e "^error: 2 errors resolving patches:$" err &&
+    grep -e "^  patch .p1. is not hidden$" err &&
+    grep -e "^  patch .p2. is not hidden$" err
+'
+
+test_expect_success 'Contiguity is checked once all patches resolve' '
+    command_error stg series p1 p3 px 2>err &&
+    grep -e "^error: patch .px. does not exist" err &&
+    ! grep -e "not contiguous" err
+'
+
+test_expect_success 'Push all with exclusions' '
+    command_error stg push --all p4 2>err &&
+    grep -e "only exclusions, e.g. .!<patch>., may be used with .--all." err &&
+    stg push --all "!p4" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p5" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4"
+'
+
+test_expect_success 'Float with exclusion' '
+    stg float p1..p5 "!p3" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p3 p1 p2 p5" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4"
+'
+
 test_done
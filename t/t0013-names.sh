@@ -193,4 +193,72 @@ test_expect_success 'Local committer.email overrides local user.email' '
     test_cmp expected names
 '
 
+test_expect_success 'Environment overrides author and committer config' '
+    test_config author.name "Config Author" &&
+    test_config author.email "config-author@example.com" &&
+    test_config committer.name "Config Committer" &&
+    test_config committer.email "config-committer@example.com" &&
+    stg new -m patch &&
+    show_names > names &&
+    cat >expected <<-\EOF &&
+	Author: A Ú Thor author@example.com
+	Committer: C Ó Mitter committer@example.com
+	EOF
+    test_cmp expected names
+'
+
+test_expect_success 'Setup stack with identities from environment' '
+    stg delete $(stg series --noprefix --all) &&
+    test_config author.name "Config Author" &&
+    test_config author.email "config-author@example.com" &&
+    test_config committer.name "Config Committer" &&
+    test_config committer.email "config-committer@example.com" &&
+    (
+        GIT_AUTHOR_NAME="Env Author" &&
+        GIT_AUTHOR_EMAIL="env-author@example.com" &&
+        GIT_AUTHOR_DATE="1234567890 +0100" &&
+        GIT_COMMITTER_NAME="Env Committer" &&
+        GIT_COMMITTER_EMAIL="env-committer@example.com" &&
+        GIT_COMMITTER_DATE="1234567899 +0100" &&
+        export GIT_AUTHOR_NAME GIT_AUTHOR_EMAIL GIT_AUTHOR_DATE &&
+        export GIT_COMMITTER_NAME GIT_COMMITTER_EMAIL GIT_COMMITTER_DATE &&
+        git rev-parse refs/stacks/master >old-state &&
+        stg new -m p1 &&
+        echo p1 >p1.txt &&
+        stg add p1.txt &&
+        stg refresh &&
+        stg new -m p2 &&
+        echo p2 >p2.txt &&
+        stg add p2.txt &&
+        stg refresh &&
+        stg pop -a &&
+        stg push p2 &&
+        stg push p1
+    )
+'
+
+test_expect_success 'Patch commits use identities from environment' '
+    git log --pretty=tformat:"%an %ae %ad%n%cn %ce %cd" --date=raw \
+        $(stg id {base})..$(stg id p1) >idents &&
+    test_line_count = 4 idents &&
+    sort -u idents >actual &&
+    cat >expected <<-\EOF &&
+	Env Author env-author@example.com 1234567890 +0100
+	Env Committer env-committer@example.com 1234567899 +0100
+	EOF
+    test_cmp expected actual
+'
+
+test_expect_success 'Stack state commits use identities from environment' '
+    git log --first-parent --pretty=tformat:"%an %ae %ad%n%cn %ce %cd" --date=raw \
+        $(cat old-state)..refs/stacks/master >idents &&
+    test $(wc -l <idents) -ge 14 &&
+    sort -u idents >actual &&
+    cat >expected <<-\EOF &&
+	Env Author env-author@example.com 1234567890 +0100
+	Env Committer env-committer@example.com 1234567899 +0100
+	EOF
+    test_cmp expected actual
+'
+
 test_done
@@ -0,0 +1,106 @@
+#!/bin/sh
+
+test_description='Test errors for unsuitable repository contexts'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup repositories' '
+    mkdir -p nongit/sub &&
+    git init unborn &&
+    stg init &&
+    stg new -m p0 &&
+    stg new -m p1 &&
+    git clone --mirror . bare.git &&
+    git branch nostack
+'
+
+test_expect_success 'Not a git repository' '
+    (
+        cd nongit/sub &&
+        GIT_CEILING_DIRECTORIES="$(cd .. && pwd)" &&
+        export GIT_CEILING_DIRECTORIES &&
+        not_a_repository_error stg series 2>err &&
+        grep "not a git repository" err &&
+        grep "git init" err
+    ) &&
+    GIT_DIR="$(pwd)/nongit" not_a_repository_error stg series 2>err &&
+    grep "not a git repository" err
+'
+
+test_expect_success 'Commands not needing a repository' '
+    (
+        cd nongit/sub &&
+        GIT_CEILING_DIRECTORIES="$(cd .. && pwd)" &&
+        export GIT_CEILING_DIRECTORIES &&
+        stg version
+    )
+'
+
+test_expect_success 'Unborn branch' '
+    (
+        cd unborn &&
+        unborn_branch_error stg series 2>err &&
+        grep "branch .master. is unborn" err &&
+        grep "first commit" err &&
+        unborn_branch_error stg files 2>err &&
+        grep "branch .master. is unborn" err &&
+        unborn_branch_error stg series --branch master 2>err &&
+        grep "branch .master. is unborn" err
+    )
+'
+
+test_expect_success 'Detached HEAD' '
+    test_when_finished "git checkout master" &&
+    git checkout --detach &&
+    short_id=$(git rev-parse --short=7 HEAD) &&
+    detached_head_error stg series 2>err &&
+    grep "HEAD is detached at .$short_id." err &&
+    grep "stg branch --create" err &&
+    detached_head_error stg new -m p2 2>err &&
+    stg series --branch master >out &&
+    test_line_count = 2 out
+'
+
+test_expect_success 'Branch without stack' '
+    test_when_finished "git checkout master" &&
+    git checkout nostack &&
+    no_stack_error stg log 2>err &&
+    grep "StGit stack not initialized for branch .nostack." err
+'
+
+test_expect_success 'Bare repository rejects work tree commands' '
+    (
+        cd bare.git &&
+        bare_repository_error stg new -m p2 2>err &&
+        grep "stg new. requires a work tree, but the repository is bare" err &&
+        grep "stg series" err &&
+        grep -e "--branch" err &&
+        bare_repository_error stg pop 2>err
+    )
+'
+
+test_expect_success 'Bare repository read-only commands' '
+    (
+        cd bare.git &&
+        cat >expected <<-\EOF &&
+	+ p0
+	> p1
+	EOF
+        stg series >out &&
+        test_cmp expected out &&
+        stg series --branch master >out &&
+        test_cmp expected out &&
+        test "$(stg top)" = "p1" &&
+        test "$(stg prev)" = "p0" &&
+        test "$(stg id p0)" = "$(git rev-parse refs/heads/master~)" &&
+        stg show p0 >out &&
+        grep "p0" out &&
+        stg files p1 &&
+        stg log -n 1 >out &&
+        grep "new: p1" out &&
+        stg export -s p0 >out &&
+        grep "p0" out
+    )
+'
+
+test_done
@@ -0,0 +1,280 @@
+#!/bin/sh
+
+test_description='Test stg add, stg rm, and stg mv'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup repository' '
+    cat >>.git/info/exclude <<-\EOF &&
+	/*expected
+	/*out
+	/err
+	EOF
+    echo "*.o" >.gitignore &&
+    echo base >base.txt &&
+    git add .gitignore base.txt &&
+    git commit -m base &&
+    stg init
+'
+
+test_expect_success 'Add files and directories' '
+    mkdir -p dir/sub &&
+    echo a >a.txt &&
+    echo b >dir/b.txt &&
+    echo c >dir/sub/c.txt &&
+    echo obj >dir/sub/c.o &&
+    stg add a.txt dir &&
+    git diff --cached --name-status >out &&
+    cat >expected <<-\EOF &&
+	A	a.txt
+	A	dir/b.txt
+	A	dir/sub/c.txt
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Added content matches work tree' '
+    test "$(git rev-parse :a.txt)" = "$(git hash-object a.txt)" &&
+    git diff --quiet
+'
+
+test_expect_success 'Add ignored file' '
+    command_error stg add dir/sub/c.o 2>err &&
+    grep -e "the following paths are ignored:" err &&
+    grep -e "use \`--force\` to add them" err &&
+    test -z "$(git ls-files dir/sub/c.o)" &&
+    stg add --force dir/sub/c.o &&
+    test -n "$(git ls-files dir/sub/c.o)"
+'
+
+test_expect_success 'Add nonexistent path' '
+    command_error stg add nonexistent 2>err &&
+    grep -e "pathspec \`nonexistent\` did not match any files" err
+'
+
+test_expect_success 'Add from subdirectory' '
+    echo new >new.txt &&
+    (
+        cd dir/sub &&
+        stg add ../../new.txt
+    ) &&
+    test -n "$(git ls-files new.txt)"
+'
+
+test_expect_success 'Add updated and removed tracked files' '
+    stg new -r -m p0 &&
+    echo more >>a.txt &&
+    rm dir/b.txt &&
+    echo untracked >untracked.txt &&
+    stg add -u &&
+    git diff --cached --name-status >out &&
+    cat >expected <<-\EOF &&
+	M	a.txt
+	D	dir/b.txt
+	EOF
+    test_cmp expected out &&
+    test -z "$(git ls-files untracked.txt)" &&
+    rm untracked.txt &&
+    stg refresh
+'
+
+test_expect_success 'Remove file with local modifications' '
+    echo change >>a.txt &&
+    command_error stg rm a.txt 2>err &&
+    grep -e "\`a.txt\` has local modifications" err &&
+    test_path_is_file a.txt &&
+    test -n "$(git ls-files a.txt)"
+'
+
+test_expect_success 'Remove file from the index only' '
+    stg rm --cached a.txt &&
+    test_path_is_file a.txt &&
+    test -z "$(git ls-files a.txt)" &&
+    stg add a.txt &&
+    git checkout a.txt
+'
+
+test_expect_success 'Remove file with staged changes' '
+    echo staged >>a.txt &&
+    stg add a.txt &&
+    command_error stg rm a.txt 2>err &&
+    grep -e "\`a.txt\` has changes staged in the index" err &&
+    stg rm --force a.txt &&
+    test_path_is_missing a.txt &&
+    git reset -q HEAD a.txt &&
+    git checkout a.txt
+'
+
+test_expect_success 'Remove directory' '
+    command_error stg rm dir 2>err &&
+    grep -e "not removing \`dir\` recursively without \`-r\`" err &&
+    stg rm -r dir &&
+    test_path_is_missing dir &&
+    git diff --cached --name-status >out &&
+    cat >expected <<-\EOF &&
+	D	dir/sub/c.o
+	D	dir/sub/c.txt
+	EOF
+    test_cmp expected out &&
+    git reset -q HEAD dir &&
+    git checkout dir
+'
+
+test_expect_success 'Move file' '
+    stg mv a.txt renamed.txt &&
+    test_path_is_missing a.txt &&
+    test_path_is_file renamed.txt &&
+    git diff --cached -M --name-status >out &&
+    cat >expected <<-\EOF &&
+	R100	a.txt	renamed.txt
+	EOF
+    test_cmp expected out &&
+    git diff --quiet
+'
+
+test_expect_success 'Move files into directory' '
+    command_error stg mv renamed.txt new.txt nonexistent 2>err &&
+    grep -e "destination \`nonexistent\` is not a directory" err &&
+    command_error stg mv renamed.txt new.txt 2>err &&
+    grep -e "destination \`new.txt\` already exists" err &&
+    stg mv renamed.txt new.txt dir &&
+    test_path_is_file dir/renamed.txt &&
+    test_path_is_file dir/new.txt &&
+    git diff --quiet &&
+    stg refresh
+'
+
+test_expect_success 'Move directory' '
+    stg mv dir moved &&
+    test_path_is_missing dir &&
+    git ls-files moved >out &&
+    cat >expected <<-\EOF &&
+	moved/new.txt
+	moved/renamed.txt
+	moved/sub/c.o
+	moved/sub/c.txt
+	EOF
+    test_cmp expected out &&
+    git diff --quiet &&
+    stg refresh
+'
+
+test_expect_success 'Move untracked file' '
+    echo untracked >untracked.txt &&
+    command_error stg mv untracked.txt other.txt 2>err &&
+    grep -e "\`untracked.txt\` is not under version control" err &&
+    rm untracked.txt
+'
+
+test_expect_success 'Setup conflicting patches' '
+    stg new -m p1 &&
+    echo p1 >base.txt &&
+    stg refresh &&
+    stg pop &&
+    stg new -m p2 &&
+    echo p2 >base.txt &&
+    stg refresh &&
+    conflict stg push p1 &&
+    test -n "$(git ls-files -u base.txt)"
+'
+
+test_expect_success 'Move conflicted file' '
+    command_error stg mv base.txt other.txt 2>err &&
+    grep -e "\`base.txt\` is in conflict" err
+'
+
+test_expect_success 'Add resolved file clears conflict' '
+    echo resolved >base.txt &&
+    stg add base.txt &&
+    test -z "$(git ls-files -u)" &&
+    test "$(git rev-parse :base.txt)" = "$(git hash-object base.txt)" &&
+    stg refresh &&
+    test "$(stg top)" = "p1" &&
+    test "$(git show HEAD:base.txt)" = "resolved"
+'
+
+test_expect_success 'Remove conflicted file' '
+    stg new -m p3 &&
+    echo p3 >base.txt &&
+    stg refresh &&
+    stg pop &&
+    stg new -m p4 &&
+    echo p4 >base.txt &&
+    stg refresh &&
+    conflict stg push p3 &&
+    stg rm base.txt &&
+    test -z "$(git ls-files -u)" &&
+    test_path_is_missing base.txt &&
+    stg refresh &&
+    test -z "$(git ls-files base.txt)"
+'
+
+test_expect_success 'Add converts line endings' '
+    test_config core.autocrlf true &&
+    printf "one\r\ntwo\r\n" >crlf.txt &&
+    stg add crlf.txt &&
+    test "$(git rev-parse :crlf.txt)" = "$(printf "one\ntwo\n" | git hash-object --stdin)" &&
+    git diff --quiet crlf.txt &&
+    stg refresh &&
+    stg rm crlf.txt &&
+    stg refresh
+'
+
+test_expect_success 'Add applies clean filter' '
+    test_config filter.upper.clean "tr a-z A-Z" &&
+    echo "*.up filter=upper" >.gitattributes &&
+    echo lower >file.up &&
+    stg add .gitattributes file.up &&
+    test "$(git cat-file blob :file.up)" = "LOWER" &&
+    stg add --update file.up &&
+    test "$(git cat-file blob :file.up)" = "LOWER" &&
+    stg refresh &&
+    stg rm file.up .gitattributes &&
+    stg refresh
+'
+
+test_expect_success 'Add with locked index' '
+    echo locked >locked.txt &&
+    touch .git/index.lock &&
+    test_when_finished "rm -f .git/index.lock" &&
+    test_must_fail stg add locked.txt 2>err &&
+    grep -e "index" err &&
+    rm .git/index.lock &&
+    stg add locked.txt &&
+    test_path_is_missing .git/index.lock &&
+    test -n "$(git ls-files locked.txt)"
+'
+
+test_expect_success 'Report added, removed, and moved files' '
+    echo report >report.txt &&
+    stg add report.txt 2>err &&
+    grep "info: Added \`report.txt\`" err &&
+    stg mv report.txt reported.txt 2>err &&
+    grep "info: Moved \`report.txt\` to \`reported.txt\`" err &&
+    stg rm --cached reported.txt 2>err &&
+    grep "info: Removed \`reported.txt\`" err &&
+    stg add --quiet reported.txt 2>err &&
+    test_must_be_empty err &&
+    stg rm -q --force reported.txt
+'
+
+test_expect_success 'Index extensions are preserved' '
+    test_when_finished "git update-index --no-split-index --no-untracked-cache" &&
+    test_config core.splitIndex true &&
+    git update-index --split-index --untracked-cache &&
+    git status >/dev/null &&
+    grep -a -q UNTR .git/index &&
+    echo ext >ext.txt &&
+    stg add ext.txt &&
+    test "$(git rev-parse :ext.txt)" = "$(git hash-object ext.txt)" &&
+    grep -a -q UNTR .git/index &&
+    stg mv ext.txt ext-moved.txt &&
+    test "$(git ls-files ext*)" = "ext-moved.txt" &&
+    grep -a -q UNTR .git/index &&
+    stg rm --force ext-moved.txt &&
+    test -z "$(git ls-files ext*)" &&
+    grep -a -q UNTR .git/index &&
+    git diff --quiet
+'
+
+test_done
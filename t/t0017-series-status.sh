@@ -0,0 +1,62 @@
+#!/bin/sh
+
+test_description='Test stg series patch status column'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    stg new -m p0 &&
+    stg new -m p1 &&
+    stg new -m p2 &&
+    stg pop &&
+    cat >status.sh <<-\EOF &&
+	while read name oid; do
+	    case $name in
+	    p0) echo "$oid pass";;
+	    p1) echo "$oid fail";;
+	    esac
+	done
+	EOF
+    chmod +x status.sh
+'
+
+test_expect_success 'Display statuses from command' '
+    stg series --status-cmd "sh status.sh" >out &&
+    cat >expected <<-\EOF &&
+	+ pass p0
+	> fail p1
+	- -    p2
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Command input has name and commit id' '
+    stg series --status-cmd "cat >input" &&
+    printf "p0\t%s\np1\t%s\np2\t%s\n" \
+        "$(stg id p0)" "$(stg id p1)" "$(stg id p2)" >expected &&
+    test_cmp expected input
+'
+
+test_expect_success 'Status command from config' '
+    test_config stgit.series.statuscmd "sh status.sh" &&
+    stg series -A >out &&
+    grep -e "^> fail p1$" out &&
+    stg series --no-status-cmd >out &&
+    grep -e "^> p1$" out
+'
+
+test_expect_success 'Failing status command' '
+    stg series --status-cmd "exit 3" >out 2>err &&
+    grep -e "status unavailable" err &&
+    grep -e "^> p1$" out
+'
+
+test_expect_success 'Status command timeout' '
+    test_config stgit.series.statustimeout 1 &&
+    stg series --status-cmd "sleep 5" >out 2>err &&
+    grep -e "status unavailable.*timed out after 1 seconds" err &&
+    grep -e "^> p1$" out
+'
+
+test_done
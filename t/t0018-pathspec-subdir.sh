@@ -0,0 +1,157 @@
+#!/bin/sh
+
+test_description='Test pathspecs given from a subdirectory
+
+Paths are relative to the current directory unless prefixed with ":/", in
+which case they are relative to the root of the work tree.'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    mkdir src &&
+    echo a >src/a.txt &&
+    echo b >b.txt &&
+    echo a >a.txt &&
+    printf "out\nerr\n" >>.git/info/exclude &&
+    git add src/a.txt b.txt a.txt &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p0 &&
+    echo a0 >src/a.txt &&
+    echo b0 >b.txt &&
+    stg refresh &&
+    stg new -m p1 &&
+    echo b1 >b.txt &&
+    stg refresh
+'
+
+test_expect_success 'Refresh from subdirectory' '
+    echo a1 >src/a.txt &&
+    echo a1 >a.txt &&
+    (cd src && stg refresh a.txt) &&
+    test "$(stg status)" = " M a.txt" &&
+    (cd src && stg refresh :/a.txt) &&
+    test "$(stg status)" = "" &&
+    test "$(echo $(stg files p1))" = "M a.txt M b.txt M src/a.txt"
+'
+
+test_expect_success 'New patch with paths from subdirectory' '
+    echo a2 >src/a.txt &&
+    echo b2 >b.txt &&
+    (cd src && stg new -m p2 -- :/b.txt) &&
+    test "$(stg status)" = " M src/a.txt" &&
+    test "$(stg files p2)" = "M b.txt" &&
+    git checkout src/a.txt
+'
+
+test_expect_success 'Diff from subdirectory' '
+    echo a3 >src/a.txt &&
+    echo a3 >a.txt &&
+    (cd src && stg diff --stat a.txt) >out &&
+    grep -e "src/a.txt" out &&
+    ! grep -e " a.txt" out &&
+    (cd src && stg diff --stat :/a.txt) >out &&
+    grep -e " a.txt" out &&
+    ! grep -e "src/a.txt" out &&
+    git checkout src/a.txt a.txt
+'
+
+test_expect_success 'Show from subdirectory' '
+    (cd src && stg show p1 -- a.txt) >out &&
+    grep -e "^+++ b/src/a.txt" out &&
+    ! grep -e "^+++ b/a.txt" out &&
+    (cd src && stg show p1 -- :/a.txt) >out &&
+    grep -e "^+++ b/a.txt" out &&
+    ! grep -e "^+++ b/src/a.txt" out
+'
+
+test_expect_success 'Patches from subdirectory' '
+    (cd src && stg patches a.txt) >out &&
+    test "$(echo $(cat out))" = "p0 p1" &&
+    (cd src && stg patches :/a.txt) >out &&
+    test "$(echo $(cat out))" = "p1" &&
+    (cd src && stg patches ../b.txt) >out &&
+    test "$(echo $(cat out))" = "p0 p1 p2"
+'
+
+test_expect_success 'Goto path from subdirectory' '
+    (cd src && stg goto --path :/a.txt) &&
+    test "$(stg top)" = "p1" &&
+    (cd src && stg goto --path a.txt --first) &&
+    test "$(stg top)" = "p0" &&
+    stg goto p2
+'
+
+test_expect_success 'Spill from subdirectory' '
+    stg goto p1 &&
+    (cd src && stg spill :/a.txt) &&
+    test "$(stg status)" = "M  a.txt" &&
+    stg refresh --index &&
+    (cd src && stg spill a.txt) &&
+    test "$(stg status)" = "M  src/a.txt" &&
+    stg refresh --index &&
+    test "$(echo $(stg files))" = "M a.txt M b.txt M src/a.txt" &&
+    stg goto p2
+'
+
+test_expect_success 'Setup patches to pick' '
+    stg new -m p3 &&
+    printf "1\n2\n3\n4\n5\n" >c.txt &&
+    printf "1\n2\n3\n4\n5\n" >src/c.txt &&
+    stg add c.txt src/c.txt &&
+    stg refresh &&
+    stg new -m p4 &&
+    printf "1\n2\n3\n4\nfive\n" >c.txt &&
+    printf "1\n2\n3\n4\nfive\n" >src/c.txt &&
+    echo b4 >b.txt &&
+    stg refresh &&
+    stg pop &&
+    stg new -m p5 &&
+    printf "one\n2\n3\n4\n5\n" >src/c.txt &&
+    stg refresh
+'
+
+test_expect_success 'Pick --fold with files from subdirectory' '
+    (cd src && stg pick --fold p4 -f ../c.txt) &&
+    test "$(stg status)" = "M  c.txt" &&
+    git reset --hard &&
+    (cd src && stg pick --fold p4 -f c.txt) &&
+    test "$(stg status)" = "M  src/c.txt" &&
+    git reset --hard &&
+    (cd src && stg pick --fold p4 -f :/b.txt) &&
+    test "$(stg status)" = "M  b.txt" &&
+    git reset --hard
+'
+
+test_expect_success 'Pick --update from subdirectory' '
+    (cd src && stg pick --update p4) &&
+    test "$(stg status)" = "M  src/c.txt" &&
+    test_cmp src/c.txt - <<-\EOF &&
+	one
+	2
+	3
+	4
+	five
+	EOF
+    git reset --hard
+'
+
+test_expect_success 'Add, mv, and rm from subdirectory' '
+    echo c >d.txt &&
+    (cd src && stg add :/d.txt) &&
+    test "$(stg status)" = "A  d.txt" &&
+    (cd src && stg mv :/d.txt d.txt) &&
+    test "$(stg status)" = "A  src/d.txt" &&
+    (cd src && stg rm --cached :/src/d.txt) &&
+    test "$(stg status)" = "?? src/d.txt" &&
+    rm src/d.txt
+'
+
+test_expect_success 'Pathspecs outside the work tree are rejected' '
+    (cd src && command_error stg add :/../x.txt 2>../err) &&
+    grep -e "is outside the work tree" err &&
+    (cd src && command_error stg diff ../../x.txt 2>../err) &&
+    grep -e "is outside the work tree" err
+'
+
+test_done
@@ -0,0 +1,93 @@
+#!/bin/sh
+
+test_description='Test the stg series review window'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    for i in 1 2 3 4 5 6 7 8 9; do
+        stg new -m "p$i" || return 1
+    done &&
+    stg pop -n 3
+'
+
+test_expect_success 'Default series is limited to the window' '
+    test_config stgit.series.window 2 &&
+    cat >expected <<-\EOF &&
+	... (4 more)
+	+ p5
+	> p6
+	- p7
+	- p8
+	... (1 more)
+	EOF
+    stg series >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Reversed series elides in reverse' '
+    test_config stgit.series.window 2 &&
+    cat >expected <<-\EOF &&
+	... (1 more)
+	- p8
+	- p7
+	> p6
+	+ p5
+	... (4 more)
+	EOF
+    stg series --reverse >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Window keeps the topmost patch' '
+    test_config stgit.series.window 1 &&
+    cat >expected <<-\EOF &&
+	... (5 more)
+	> p6
+	- p7
+	... (2 more)
+	EOF
+    stg series >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'No ellipsis when the whole stack fits' '
+    test_config stgit.series.window 10 &&
+    stg series >series &&
+    test_line_count = 9 series &&
+    ! stg series | grep -e "more)"
+'
+
+test_expect_success 'Explicit selections show everything' '
+    test_config stgit.series.window 2 &&
+    stg series --all >series &&
+    test_line_count = 9 series &&
+    stg series --applied >series &&
+    test_line_count = 6 series &&
+    stg series --unapplied >series &&
+    test_line_count = 3 series &&
+    stg series --no-window >series &&
+    test_line_count = 9 series &&
+    test "$(echo $(stg series --noprefix p1..p3))" = "p1 p2 p3" &&
+    ! stg series --all | grep -e "more)"
+'
+
+test_expect_success 'Count is not limited by the window' '
+    test_config stgit.series.window 2 &&
+    test "$(stg series --count)" = "9"
+'
+
+test_expect_success 'Window with no applied patches' '
+    test_config stgit.series.window 2 &&
+    stg pop -a &&
+    cat >expected <<-\EOF &&
+	- p1
+	- p2
+	... (7 more)
+	EOF
+    stg series >out &&
+    test_cmp expected out
+'
+
+test_done
@@ -0,0 +1,62 @@
+#!/bin/sh
+
+test_description='Test listing patches for shell completions'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize stacks on two branches' '
+    stg init &&
+    stg new -m "p0" &&
+    stg branch --create other &&
+    stg new -m "o0" &&
+    stg new -m "o1" &&
+    stg new -m "o2" &&
+    stg pop o1 o2 &&
+    stg hide o2 &&
+    stg branch master
+'
+
+test_expect_success 'List patches of the current branch' '
+    echo p0 >expected &&
+    stg completion list patches >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'List patches of another branch' '
+    git for-each-ref >refs-before &&
+    cat >expected <<-\EOF &&
+	o0
+	o1
+	o2
+	EOF
+    stg completion list patches --branch other >out &&
+    test_cmp expected out &&
+    test "$(git symbolic-ref --short HEAD)" = "master" &&
+    git for-each-ref >refs-after &&
+    test_cmp refs-before refs-after
+'
+
+test_expect_success 'List patches of another branch with states' '
+    printf "o0\tapplied\no1\tunapplied\no2\thidden\n" >expected &&
+    stg completion list patches --branch=other --style=fish >out &&
+    test_cmp expected out &&
+    printf "o0:applied\no1:unapplied\no2:hidden\n" >expected &&
+    stg completion list patches -b other --style=zsh >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'List patches of an unknown branch' '
+    command_error stg completion list patches --branch nonexistent 2>err &&
+    grep -e "nonexistent" err
+'
+
+test_expect_success 'Generated scripts complete other branches revisions' '
+    stg completion bash >stg.bash &&
+    grep -e "_revision_range \"\$(_all_patches)\"" stg.bash &&
+    grep -e "completion list patches --branch" stg.bash &&
+    stg completion fish >stg.fish &&
+    grep -e "(__fish_stg_revisions)" stg.fish &&
+    grep -e "completion list patches --style=fish --branch" stg.fish
+'
+
+test_done
@@ -0,0 +1,89 @@
+#!/bin/sh
+
+test_description='Test reflog messages of stack operations'
+
+. ./test-lib.sh
+
+branch_reflog () {
+    git reflog -1 --format=%gs refs/heads/master
+}
+
+stack_reflog () {
+    git reflog -1 --format=%gs refs/stacks/master
+}
+
+log_summary () {
+    stg log -n1 | sed -e "s/.*   //"
+}
+
+test_expect_success 'Initialize the StGit repository' '
+    git config core.logAllRefUpdates always &&
+    test_commit base &&
+    git tag v1 &&
+    stg init &&
+    test "$(stack_reflog)" = "stg initialize" &&
+    for p in p1 p2 p3 p4 p5; do
+        stg new -m $p &&
+        echo $p >$p.txt &&
+        stg add $p.txt &&
+        stg refresh || return 1
+    done &&
+    test "$(branch_reflog)" = "stg refresh p5"
+'
+
+test_expect_success 'Pop and push patches' '
+    stg pop -n 2 &&
+    test "$(branch_reflog)" = "stg pop p4 p5" &&
+    test "$(stack_reflog)" = "stg pop p4 p5" &&
+    test "$(log_summary)" = "pop p4 p5" &&
+    stg push p4 &&
+    test "$(branch_reflog)" = "stg push p4" &&
+    test "$(stack_reflog)" = "stg push p4" &&
+    stg goto p1 &&
+    test "$(branch_reflog)" = "stg goto p1" &&
+    stg push -a &&
+    test "$(branch_reflog)" = "stg push p2 p3 p4 (+1 more)"
+'
+
+test_expect_success 'Undo and redo operations' '
+    stg undo -n 2 &&
+    test "$(branch_reflog)" = "stg undo 2 operations" &&
+    test "$(stack_reflog)" = "stg undo 2 operations" &&
+    test "$(log_summary)" = "undo 2 operations" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    stg redo &&
+    test "$(branch_reflog)" = "stg redo 1 operation" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4 p5"
+'
+
+test_expect_success 'Delete and hide patches' '
+    stg delete p4 &&
+    test "$(branch_reflog)" = "stg delete p4" &&
+    stg hide --pop --keep-above p3 &&
+    test "$(stack_reflog)" = "stg hide p3"
+'
+
+test_expect_success 'Rebase onto a tag' '
+    git checkout -q --detach v1 &&
+    test_commit upstream &&
+    git tag v2 &&
+    git checkout -q master &&
+    stg rebase v2 &&
+    test "$(branch_reflog)" = "stg rebase onto v2 (reapply)" &&
+    git reflog --format=%gs refs/stacks/master >out &&
+    head -n 3 out >actual &&
+    cat >expected <<-\EOF &&
+	stg rebase onto v2 (reapply)
+	stg rebase onto v2
+	stg rebase onto v2 (pop)
+	EOF
+    test_cmp expected actual
+'
+
+test_expect_success 'Only first line of annotated messages is used' '
+    echo more >>p2.txt &&
+    stg refresh -p p2 --annotate "some annotation" &&
+    test "$(branch_reflog)" = "stg refresh p2"
+'
+
+test_done
@@ -0,0 +1,93 @@
+#!/bin/sh
+
+test_description='Test StGit in a shallow clone'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup shallow clone' '
+    test_commit_bulk --message="c%s" 5 &&
+    git clone --depth 2 "file://$(pwd)" shallow &&
+    git -C shallow rev-parse --is-shallow-repository >out &&
+    test "$(cat out)" = "true"
+'
+
+test_expect_success 'Stack operations work in a shallow clone' '
+    (
+        cd shallow &&
+        stg init &&
+        stg new -m p0 p0 &&
+        echo p0 >p0.txt &&
+        stg add p0.txt &&
+        stg refresh &&
+        stg new -m p1 p1 &&
+        echo p1 >p1.txt &&
+        stg add p1.txt &&
+        stg refresh &&
+        stg pop -a &&
+        stg push -a &&
+        stg series --noprefix >series.txt &&
+        printf "p0\np1\n" >expected.txt &&
+        test_cmp expected.txt series.txt
+    )
+'
+
+test_expect_success 'Uncommit within the shallow history' '
+    (
+        cd shallow &&
+        stg uncommit &&
+        test "$(echo $(stg series --applied --noprefix))" = "c5 p0 p1"
+    )
+'
+
+test_expect_success 'Uncommit beyond the shallow boundary' '
+    (
+        cd shallow &&
+        command_error stg uncommit 2>err &&
+        grep -e "error: uncommitting .$(git rev-parse HEAD~3). needs history that is missing from this shallow repository" err &&
+        grep -e "git fetch --deepen=<depth>" err &&
+        grep -e "git fetch --unshallow" err &&
+        command_error stg uncommit --to HEAD~5 2>err &&
+        grep -e "error: resolving .HEAD~5. needs history" err &&
+        test "$(echo $(stg series --applied --noprefix))" = "c5 p0 p1"
+    )
+'
+
+test_expect_success 'Resolve revisions beyond the shallow boundary' '
+    (
+        cd shallow &&
+        command_error stg id {base}~2 2>err &&
+        grep -e "error: resolving .{base}~2. needs history" err &&
+        command_error stg rebase HEAD~5 2>err &&
+        grep -e "error: resolving .HEAD~5. needs history" err &&
+        command_error stg id nosuch 2>err &&
+        grep -e "error: patch .nosuch. does not exist" err
+    )
+'
+
+test_expect_success 'Rebase with merged detection in a shallow clone' '
+    stg init &&
+    stg new -m p0 p0 &&
+    echo p0 >p0.txt &&
+    stg add p0.txt &&
+    stg refresh &&
+    stg commit -a &&
+    (
+        cd shallow &&
+        git fetch origin &&
+        stg rebase --merged origin/master 2>out &&
+        grep -e "^Found 2 patches merged upstream$" out &&
+        stg clean &&
+        test "$(echo $(stg series --applied --noprefix))" = "p1"
+    )
+'
+
+test_expect_success 'Uncommit after deepening the history' '
+    (
+        cd shallow &&
+        git fetch --unshallow origin &&
+        stg uncommit -n 2 &&
+        test "$(echo $(stg series --applied --noprefix))" = "c5 p0 p1"
+    )
+'
+
+test_done
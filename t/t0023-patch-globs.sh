@@ -0,0 +1,67 @@
+#!/bin/sh
+
+test_description='Test glob patterns as patch arguments'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    stg init &&
+    for p in wip-a fix-1 wip-b tmp-1 fix-2 tmp-2; do
+        stg new -m $p $p || return 1
+    done &&
+    stg pop tmp-2 fix-2
+'
+
+test_expect_success 'Series of matching patches in stack order' '
+    stg series "*-[12]" >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ fix-1
+	> tmp-1
+	- fix-2
+	- tmp-2
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Pattern matching no patches' '
+    command_error stg series "nope-*" 2>err &&
+    grep -e "error: no patches match .nope-\*." err &&
+    command_error stg delete "nope-*" 2>err &&
+    grep -e "error: no patches match .nope-\*." err
+'
+
+test_expect_success 'Hide and unhide matching patches' '
+    stg hide --pop "wip-*" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "wip-a wip-b" &&
+    test "$(echo $(stg series --applied --noprefix))" = "" &&
+    stg unhide "wip-?" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "fix-1 tmp-1 fix-2 tmp-2 wip-a wip-b"
+'
+
+test_expect_success 'Push only matches unapplied patches' '
+    stg push fix-1 &&
+    stg push "fix-*" &&
+    test "$(echo $(stg series --applied --noprefix))" = "fix-1 fix-2" &&
+    command_error stg push "fix-*" 2>err &&
+    grep -e "error: no patches match .fix-\*." err
+'
+
+test_expect_success 'Sink and float matching patches' '
+    stg sink "tmp-[0-9]" &&
+    test "$(echo $(stg series --applied --noprefix))" = "tmp-1 tmp-2 fix-1 fix-2" &&
+    stg float "wip*" &&
+    test "$(echo $(stg series --applied --noprefix))" = "tmp-1 tmp-2 fix-1 fix-2 wip-a wip-b"
+'
+
+test_expect_success 'Pattern and patch name duplicating each other' '
+    command_error stg delete tmp-1 "tmp-*" 2>err &&
+    grep -e "error: patch .tmp-1. is used more than once" err
+'
+
+test_expect_success 'Delete matching patches' '
+    stg delete "tmp-*" &&
+    test "$(echo $(stg series --noprefix))" = "fix-1 fix-2 wip-a wip-b"
+'
+
+test_done
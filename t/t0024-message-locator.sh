@@ -0,0 +1,60 @@
+#!/bin/sh
+
+test_description='Test locating patches by commit message text'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    stg init &&
+    for i in 1 2 3; do
+        stg new -m "fix typo $i
+
+body text $i" p$i &&
+        echo $i >f$i &&
+        stg add f$i &&
+        stg refresh || return 1
+    done &&
+    stg new -m "unrelated" p4 &&
+    stg new -m "other change" p5 &&
+    stg pop p5
+'
+
+test_expect_success 'Locate topmost patch matching subject or body' '
+    test "$(stg id ":/fix typo")" = "$(stg id p3)" &&
+    test "$(stg id ":/body text 1")" = "$(stg id p1)" &&
+    test "$(stg id ":/other")" = "$(stg id p5)" &&
+    command_error stg id ":/no such text" 2>err &&
+    grep -e "error: no patch message contains .no such text." err
+'
+
+test_expect_success 'Message pattern with offsets and revision suffixes' '
+    test "$(stg id ":/fix typo~1")" = "$(stg id p2)" &&
+    test "$(stg id ":/body text 1+")" = "$(stg id p2)" &&
+    test "$(stg id ":/fix typo 2^{tree}")" = "$(stg id "p2^{tree}")" &&
+    test "$(stg id ":/fix typo 2^")" = "$(stg id p1)"
+'
+
+test_expect_success 'Message pattern in ranges' '
+    test "$(echo $(stg series --noprefix ":/typo 1..:/typo 2"))" = "p1 p2"
+'
+
+test_expect_success 'Message pattern on another branch' '
+    git branch other &&
+    git checkout other &&
+    stg init &&
+    test "$(stg id "master::/typo 2")" = "$(stg id master:p2)" &&
+    git checkout master
+'
+
+test_expect_success 'Delete refuses patches with identical messages' '
+    stg new -m "fix typo 3
+
+body text 3" dup &&
+    command_error stg delete ":/typo 3" 2>err &&
+    grep -e "error: .:/typo 3. matches patches with identical messages: .dup. and .p3." err &&
+    test "$(stg id ":/typo 3")" = "$(stg id dup)" &&
+    stg delete ":/unrelated" &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p3 dup p5"
+'
+
+test_done
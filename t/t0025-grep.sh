@@ -0,0 +1,97 @@
+#!/bin/sh
+
+test_description='Test searching the lines added by patches'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    echo "base needle" >base.txt &&
+    git add base.txt &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p0 p0 &&
+    echo "first Needle" >a.txt &&
+    echo "no match" >b.txt &&
+    stg add a.txt b.txt &&
+    stg refresh &&
+    stg new -m p1 p1 &&
+    echo "needle.in a haystack" >>b.txt &&
+    echo "needle again" >>base.txt &&
+    stg refresh &&
+    stg new -m p2 p2 &&
+    echo "needle unapplied" >c.txt &&
+    stg add c.txt &&
+    stg refresh &&
+    stg pop
+'
+
+test_expect_success 'Search applied patches' '
+    stg grep needle >out &&
+    cat >expected <<-\EOF &&
+	p1:b.txt:needle.in a haystack
+	p1:base.txt:needle again
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Search ignoring case' '
+    stg grep -i needle >out &&
+    cat >expected <<-\EOF &&
+	p0:a.txt:first Needle
+	p1:b.txt:needle.in a haystack
+	p1:base.txt:needle again
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Search with fixed strings' '
+    stg grep "e.i" >out &&
+    test_line_count = 1 out &&
+    stg grep -F "e.i" >out &&
+    test_line_count = 1 out &&
+    stg grep -F "e a" >out &&
+    test_line_count = 1 out &&
+    stg grep "e a" >out &&
+    test_line_count = 1 out &&
+    stg grep -F "e.a" >out &&
+    test_line_count = 0 out
+'
+
+test_expect_success 'Search named patches' '
+    stg grep needle p2 >out &&
+    echo "p2:c.txt:needle unapplied" >expected &&
+    test_cmp expected out &&
+    stg grep -i needle p0 p2 >out &&
+    test_line_count = 2 out
+'
+
+test_expect_success 'Show only patch names' '
+    stg grep --name-only -i needle p0..p2 >out &&
+    cat >expected <<-\EOF &&
+	p0
+	p1
+	p2
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Limit search to paths' '
+    stg grep needle -- base.txt >out &&
+    echo "p1:base.txt:needle again" >expected &&
+    test_cmp expected out &&
+    stg grep -i needle p0..p2 -- a.txt c.txt >out &&
+    cat >expected <<-\EOF &&
+	p0:a.txt:first Needle
+	p2:c.txt:needle unapplied
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Search with no matches or bad pattern' '
+    stg grep nothing-matches >out &&
+    test_must_be_empty out &&
+    command_error stg grep "\\(" 2>err &&
+    grep -e "git grep" err
+'
+
+test_done
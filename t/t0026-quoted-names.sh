@@ -0,0 +1,73 @@
+#!/bin/sh
+
+test_description='Test quoting of patch names in output'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    stg init &&
+    stg new -m plain plain &&
+    stg new -m semi "x;y" &&
+    stg new -m paren "(z)"
+'
+
+test_expect_success 'Series does not quote names by default' '
+    stg series >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ plain
+	+ x;y
+	> (z)
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Series quotes special names when asked' '
+    stg series --quote >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+ plain
+	+ Qx;yQ
+	> Q(z)Q
+	EOF
+    sed "s/Q/$SQ/g" expected.txt >expected.txt.tmp && mv expected.txt.tmp expected.txt &&
+    test_cmp expected.txt series.txt &&
+    test_config stgit.series.quote true &&
+    stg series >series.txt &&
+    test_cmp expected.txt series.txt &&
+    stg series --no-quote --noprefix >series.txt &&
+    test "$(sed -n 2p series.txt)" = "x;y"
+'
+
+test_expect_success 'Quoted name may be pasted into a command' '
+    eval "stg pop $(stg series --quote --noprefix | sed -n 2p)" &&
+    test "$(stg series --unapplied --noprefix)" = "x;y"
+'
+
+test_expect_success 'Push output and errors quote names' '
+    stg push "x;y" 2>out &&
+    grep -e "$SQ""x;y$SQ" out &&
+    command_error stg delete "x;z" 2>err &&
+    grep -e "error: patch .$SQ""x;z$SQ. does not exist" err
+'
+
+test_expect_success 'Top is not quoted' '
+    test "$(stg top)" = "x;y"
+'
+
+test_expect_success 'Disable quoting' '
+    test_config stgit.quotenames false &&
+    stg pop "x;y" 2>out &&
+    grep -e "- x;y" out &&
+    ! grep -e "$SQ" out &&
+    stg push "x;y" &&
+    command_error stg delete "x;z" 2>err &&
+    grep -e "error: patch .$SQ""x;z$SQ. does not exist" err
+'
+
+test_expect_success 'Branch-specific quoting setting applies to its stack' '
+    test_config branch.master.stgit.quotenames false &&
+    stg pop "x;y" 2>out &&
+    ! grep -e "$SQ" out &&
+    stg push "x;y"
+'
+
+test_done
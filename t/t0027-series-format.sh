@@ -0,0 +1,121 @@
+#!/bin/sh
+
+test_description='Test machine-readable stg series output'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    stg init &&
+    stg new -m "first patch" p0 &&
+    echo p0 >p0.txt &&
+    stg add p0.txt &&
+    stg refresh &&
+    stg new -m "second patch" p1 &&
+    stg new -m "third patch" p2 &&
+    stg new -m "hidden patch" p3 &&
+    stg pop p2 p3 &&
+    stg hide p3
+'
+
+test_expect_success 'JSON lines for default selection' '
+    stg series --format json-lines >out &&
+    test_line_count = 3 out &&
+    grep -e "^{\"name\":\"p0\",\"state\":\"applied\",\"commit_id\":\"$(stg id p0)\"," out &&
+    grep -e "\"name\":\"p1\",\"state\":\"current\"" out &&
+    grep -e "\"name\":\"p2\",\"state\":\"unapplied\"" out &&
+    grep -e "\"author\":{\"name\":\"$GIT_AUTHOR_NAME\",\"email\":\"$GIT_AUTHOR_EMAIL\",\"date\":\"" out &&
+    grep -e "\"description\":\"first patch\",\"empty\":false}$" out &&
+    grep -e "\"description\":\"second patch\",\"empty\":true}$" out
+'
+
+test_expect_success 'JSON array follows patch selection' '
+    stg series --format json --all >out &&
+    test "$(grep -c "\"state\":" out)" = "4" &&
+    grep -e "^    \"state\": \"hidden\",$" out &&
+    stg series --format json --unapplied >out &&
+    test "$(echo $(grep "^    \"name\":" out))" = "\"name\": \"p2\"," &&
+    stg series --format json p0..p1 >out &&
+    test "$(echo $(grep "^    \"name\":" out))" = "\"name\": \"p0\", \"name\": \"p1\","
+'
+
+test_expect_success 'JSON lines in reverse order' '
+    stg series --format json-lines --reverse >out &&
+    head -n 1 out | grep -e "\"name\":\"p2\""
+'
+
+test_expect_success 'Window does not apply to JSON output' '
+    test_config stgit.series.window 1 &&
+    stg series --format json-lines --applied >out &&
+    test_line_count = 2 out
+'
+
+test_expect_success 'Text options conflict with format' '
+    general_error stg series --format json --no-prefix 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg series --format json --description 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Custom format with placeholders' '
+    stg series --format "%(state) %(name) %(commit:short) %(author:email) %(subject)" >out &&
+    cat >expected <<-EOF &&
+	+ p0 $(git rev-parse --short $(stg id p0)) $GIT_AUTHOR_EMAIL first patch
+	> p1 $(git rev-parse --short $(stg id p1)) $GIT_AUTHOR_EMAIL second patch
+	- p2 $(git rev-parse --short $(stg id p2)) $GIT_AUTHOR_EMAIL third patch
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Custom format identities, index, and empty flag' '
+    stg series --all --format "%(index):%(commit):%(empty):%(committer):%(author:name)" >out &&
+    cat >expected <<-EOF &&
+	0:$(stg id p0):false:$GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>:$GIT_AUTHOR_NAME
+	1:$(stg id p1):true:$GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>:$GIT_AUTHOR_NAME
+	2:$(stg id p2):true:$GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>:$GIT_AUTHOR_NAME
+	3:$(stg id p3):true:$GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>:$GIT_AUTHOR_NAME
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Custom format dates' '
+    stg series --format "%(author:date)|%(committer:date:%Y/%m/%d %H:%M)" p0 >out &&
+    git log -1 --date="format:%Y/%m/%d %H:%M" --format="%ai|%cd" $(stg id p0) >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Custom format padding and truncation' '
+    stg series --applied --format "[%(name:-4)][%(name:4)][%(subject:.5)][%(subject:-8.3)]" >out &&
+    cat >expected <<-EOF &&
+	[p0  ][  p0][first][fir     ]
+	[p1  ][  p1][secon][sec     ]
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Custom format body and literal percent' '
+    stg edit -m "second patch
+
+body line one
+body line two" p1 &&
+    stg series --format "100%% %(body)" p1 >out &&
+    printf "100%% body line one\nbody line two\n" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Custom format follows selection options' '
+    stg series --reverse --format "%(name)" >out &&
+    test "$(echo $(cat out))" = "p2 p1 p0" &&
+    stg series --hidden --format "%(state)%(name)" >out &&
+    test "$(cat out)" = "!p3" &&
+    stg series --format "%(name)" p0..p1 >out &&
+    test "$(echo $(cat out))" = "p0 p1"
+'
+
+test_expect_success 'Unknown placeholder is an error' '
+    general_error stg series --format "%(name) %(nope)" 2>err &&
+    grep -e "unknown placeholder \`%(nope)\`" err &&
+    general_error stg series --format "%(name" 2>err &&
+    grep -e "unterminated placeholder" err
+'
+
+test_done
@@ -0,0 +1,79 @@
+#!/bin/sh
+
+test_description='Test selecting the git executable and options'
+
+. ./test-lib.sh
+
+real_git="$(command -v git)"
+
+test_expect_success 'Setup git wrapper' '
+    mkdir bin &&
+    write_script bin/git <<-EOF &&
+	echo "\$*" >>"$(pwd)/git.log"
+	exec "$real_git" "\$@"
+	EOF
+    write_script old-git <<-\EOF &&
+	echo "git version 1.9.5"
+	EOF
+    stg init
+'
+
+test_expect_success 'Version reports default git' '
+    stg version -v >out &&
+    grep -e "^git executable: git$" out &&
+    ! grep -e "^git option:" out
+'
+
+test_expect_success 'Use git from --git-path' '
+    rm -f git.log &&
+    stg --git-path bin/git new -m p0 &&
+    test_path_is_file git.log &&
+    stg --git-path "$(pwd)/bin/git" version -v >out &&
+    grep -e "^git executable: $(pwd)/bin/git$" out
+'
+
+test_expect_success 'Use git from STG_GIT' '
+    rm -f git.log &&
+    STG_GIT="$(pwd)/bin/git" stg new -m p1 &&
+    test_path_is_file git.log
+'
+
+test_expect_success 'Relative --git-path is relative to original directory' '
+    mkdir -p sub &&
+    rm -f git.log &&
+    stg --git-path bin/git -C sub new -m p2 &&
+    test_path_is_file git.log
+'
+
+test_expect_success 'Pass --git-opt to git' '
+    rm -f git.log &&
+    echo content >file &&
+    stg add file &&
+    stg --git-path bin/git --git-opt protocol.version=2 --git-opt foo.bar=baz refresh &&
+    grep -e "^-c protocol.version=2 -c foo.bar=baz " git.log &&
+    stg --git-opt foo.bar=baz version -v >out &&
+    grep -e "^git option: -c foo.bar=baz$" out
+'
+
+test_expect_success 'Hooks see git options' '
+    test_hook pre-commit <<-\EOF &&
+	git config foo.bar >"$(git rev-parse --show-toplevel)/hook.out"
+	EOF
+    echo more >>file &&
+    stg --git-opt foo.bar=baz refresh &&
+    test "$(cat hook.out)" = "baz"
+'
+
+test_expect_success 'Missing git executable' '
+    command_error stg --git-path does-not-exist/git series 2>err &&
+    grep -e "error: git executable .*does-not-exist/git. could not be run" err &&
+    command_error env STG_GIT=does-not-exist/git stg series 2>err &&
+    grep -e "error: git executable .*does-not-exist/git. could not be run" err
+'
+
+test_expect_success 'Too old git executable' '
+    command_error stg --git-path ./old-git series 2>err &&
+    grep -e "error: git executable .*old-git. is too old: .git version 1.9.5." err
+'
+
+test_done
@@ -0,0 +1,77 @@
+#!/bin/sh
+
+test_description='Test abbreviated patch names'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    stg init &&
+    for pn in parse-args fix-io refactor-parser-errors fix-iommu; do
+        stg new -m "$pn" "$pn" &&
+        echo "$pn" >"$pn.txt" &&
+        stg add "$pn.txt" &&
+        stg refresh || return 1
+    done &&
+    stg pop &&
+    printf "%s\n" err >>.git/info/exclude &&
+    test "$(echo $(stg series))" = "+ parse-args + fix-io > refactor-parser-errors - fix-iommu"
+'
+
+test_expect_success 'Goto abbreviated patch name' '
+    stg goto par &&
+    test "$(stg top)" = "parse-args" &&
+    stg goto refac &&
+    test "$(stg top)" = "refactor-parser-errors"
+'
+
+test_expect_success 'Abbreviated patch names in ranges' '
+    test "$(echo $(stg series --noprefix par..refac))" = "parse-args fix-io refactor-parser-errors"
+'
+
+test_expect_success 'Ambiguous abbreviation' '
+    command_error stg delete fix 2>err &&
+    grep -e "ambiguous patch name \`fix\`; did you mean \`fix-io\` or \`fix-iommu\`?" err &&
+    test "$(echo $(stg series --noprefix))" = "parse-args fix-io refactor-parser-errors fix-iommu"
+'
+
+test_expect_success 'Abbreviation prefers patches allowed by the command' '
+    stg push fix &&
+    test "$(stg top)" = "fix-iommu" &&
+    stg pop
+'
+
+test_expect_success 'Abbreviation outside of allowed patches' '
+    command_error stg push par 2>err &&
+    grep -e "patch \`parse-args\` is already applied" err
+'
+
+test_expect_success 'Suggest closest patch names' '
+    command_error stg goto fix-iu 2>err &&
+    grep -e "patch \`fix-iu\` does not exist; did you mean \`fix-io\` or \`fix-iommu\`?" err
+'
+
+test_expect_success 'Commit ids take precedence over abbreviations' '
+    fix_io_id=$(stg id fix-io) &&
+    prefix=$(echo "$fix_io_id" | cut -c1-8) &&
+    stg new -m "$prefix" "${prefix}-patch" &&
+    stg goto "$prefix" &&
+    test "$(stg top)" = "fix-io" &&
+    stg goto "${prefix}-p" &&
+    test "$(stg top)" = "${prefix}-patch" &&
+    stg delete "${prefix}-patch"
+'
+
+test_expect_success 'Abbreviations are not revisions' '
+    command_error stg id par 2>err &&
+    grep -e "patch \`par\` does not exist; did you mean \`parse-args\`?" err
+'
+
+test_expect_success 'Disable abbreviated patch names' '
+    test_config stgit.abbrevpatchnames false &&
+    command_error stg goto par 2>err &&
+    grep -e "patch \`par\` does not exist" err &&
+    stg goto parse-args &&
+    test "$(stg top)" = "parse-args"
+'
+
+test_done
@@ -0,0 +1,83 @@
+#!/bin/sh
+
+test_description='Test read-only commands with hidden patches'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack with hidden patches' '
+    for p in p1 p2 p3 p4; do
+        stg new -m "$p" "$p" &&
+        echo "$p" >"$p.txt" &&
+        stg add "$p.txt" &&
+        stg refresh || return 1
+    done &&
+    stg pop p2 p3 p4 &&
+    stg hide p2 p3 &&
+    test "$(echo $(stg series --all))" = "> p1 - p4 ! p2 ! p3" &&
+    printf "%s\n" out err expected "export-*" "email-*" >>.git/info/exclude
+'
+
+test_expect_success 'Show hidden patch' '
+    stg show p2 >out &&
+    grep -e "^    p2$" out &&
+    grep -e "^+p2$" out
+'
+
+test_expect_success 'Files of hidden patch' '
+    echo "A p2.txt" >expected &&
+    stg files p2 >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Id of hidden patch and hidden range' '
+    test "$(stg id p2)" = "$(git rev-parse refs/patches/master/p2)" &&
+    stg id p2..p3 >out &&
+    printf "%s\n" "$(stg id p2)" "$(stg id p3)" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Open-ended id range stops at visible patches' '
+    stg id p1.. >out &&
+    printf "%s\n" "$(stg id p1)" "$(stg id p4)" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Diff of hidden patch' '
+    stg diff -r "{base}..p2" >out &&
+    grep -e "^+p2$" out &&
+    ! grep -e "p3" out
+'
+
+test_expect_success 'Export hidden patch' '
+    stg export -d export-dir p2 &&
+    test_path_is_file export-dir/p2 &&
+    grep -e "^p2$" export-dir/series &&
+    grep -e "^+p2$" export-dir/p2
+'
+
+test_expect_success 'Export to stdout marks hidden patches' '
+    stg export --stdout p1 p2 >out &&
+    grep -e "^p1$" out &&
+    grep -e "^! p2$" out
+'
+
+test_expect_success 'Export without patches still exports applied patches' '
+    stg export -d export-applied &&
+    test "$(grep -v "^#" export-applied/series)" = "p1"
+'
+
+test_expect_success 'Email format hidden patch' '
+    stg email format -o email-dir p2 >out &&
+    test_path_is_file email-dir/0001-p2.patch &&
+    grep -e "^Subject: \[PATCH\] p2$" email-dir/0001-p2.patch
+'
+
+test_expect_success 'Mutating commands keep their constraints' '
+    command_error stg push p2 2>err &&
+    grep -e "hidden patch \`p2\` is not allowed" err &&
+    command_error stg goto p2 2>err &&
+    grep -e "hidden patch \`p2\` is not allowed" err &&
+    test "$(echo $(stg series --all))" = "> p1 - p4 ! p2 ! p3"
+'
+
+test_done
@@ -0,0 +1,98 @@
+#!/bin/sh
+
+test_description='Test StGit in a blobless partial clone'
+
+. ./test-lib.sh
+
+# Make a fresh blobless clone of the test repository with an initialized stack.
+partial_clone () {
+    rm -rf clone &&
+    git clone -q --filter=blob:none "file://$TRASH_DIRECTORY" clone &&
+    (
+        cd clone &&
+        stg init &&
+        stg uncommit -n 3
+    )
+}
+
+# Count the fetches of missing objects made by a command.
+count_fetches () {
+    rm -f "$TRASH_DIRECTORY/trace" &&
+    GIT_TRACE="$TRASH_DIRECTORY/trace" "$@" &&
+    if test -f "$TRASH_DIRECTORY/trace"
+    then
+        grep -c -e "built-in: git fetch" "$TRASH_DIRECTORY/trace" >"$TRASH_DIRECTORY/fetches" || :
+    else
+        echo 0 >"$TRASH_DIRECTORY/fetches"
+    fi
+}
+
+test_expect_success 'Setup promisor remote and partial clone' '
+    git config uploadpack.allowFilter true &&
+    git config uploadpack.allowAnySHA1InWant true &&
+    printf "%s\n" clone fetches missing patches trace >>.git/info/exclude &&
+    for i in 1 2 3; do
+        echo "file $i" >"file$i.txt" || return 1
+    done &&
+    git add file1.txt file2.txt file3.txt &&
+    git commit -q -m base &&
+    for i in 1 2 3; do
+        echo "change $i" >>"file$i.txt" &&
+        git commit -q -a -m "commit $i" || return 1
+    done &&
+    partial_clone &&
+    test "$(git -C clone config remote.origin.promisor)" = "true" &&
+    git -C clone rev-list --objects --missing=print --all >missing &&
+    grep -e "^?" missing
+'
+
+test_expect_success 'Metadata commands need no blobs' '
+    (
+        cd clone &&
+        count_fetches stg series &&
+        test "$(cat ../fetches)" = "0" &&
+        count_fetches stg files commit-2 &&
+        test "$(cat ../fetches)" = "0" &&
+        count_fetches stg id commit-1 &&
+        test "$(cat ../fetches)" = "0" &&
+        count_fetches stg log &&
+        test "$(cat ../fetches)" = "0"
+    )
+'
+
+test_expect_success 'Export fetches missing blobs in one batch' '
+    (
+        cd clone &&
+        count_fetches stg export -d ../patches &&
+        test "$(cat ../fetches)" = "1" &&
+        grep -e "^+change 1" ../patches/commit-1 &&
+        grep -e "^+change 3" ../patches/commit-3 &&
+        count_fetches stg export -d ../patches &&
+        test "$(cat ../fetches)" = "0"
+    )
+'
+
+test_expect_success 'Show fetches missing blobs in one batch' '
+    partial_clone &&
+    (
+        cd clone &&
+        count_fetches stg show commit-1 commit-2 &&
+        test "$(cat ../fetches)" = "1" &&
+        count_fetches stg show commit-1 commit-2 &&
+        test "$(cat ../fetches)" = "0"
+    )
+'
+
+test_expect_success 'Reordering pushes fetch missing blobs in one batch' '
+    partial_clone &&
+    (
+        cd clone &&
+        count_fetches stg sink --dry-run commit-3 &&
+        test "$(cat ../fetches)" = "1" &&
+        count_fetches stg sink commit-3 &&
+        test "$(cat ../fetches)" = "0" &&
+        test "$(echo $(stg series --applied --noprefix))" = "commit-3 commit-1 commit-2"
+    )
+'
+
+test_done
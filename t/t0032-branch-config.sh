@@ -0,0 +1,87 @@
+#!/bin/sh
+
+test_description='Test branch-specific StGit configuration'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stacks on two branches' '
+    test_commit_bulk 2 &&
+    stg branch --create other &&
+    stg new -m q1 &&
+    stg new -m q2 &&
+    stg new -m q3 &&
+    stg branch master &&
+    stg init &&
+    stg new -m p1 &&
+    stg new -m p2 &&
+    stg new -m p3 &&
+    printf "%s\n" out err >>.git/info/exclude
+'
+
+test_expect_success 'Branch value takes precedence over general value' '
+    test_config stgit.shortnr 1 &&
+    test_config branch.master.stgit.shortnr 2 &&
+    stg series --short --noprefix >out &&
+    test "$(echo $(cat out))" = "p1 p2 p3"
+'
+
+test_expect_success 'General value applies to other branches' '
+    test_config stgit.shortnr 1 &&
+    test_config branch.master.stgit.shortnr 2 &&
+    stg series -b other --short --noprefix >out &&
+    test "$(echo $(cat out))" = "q2 q3"
+'
+
+test_expect_success 'Value of the --branch target applies' '
+    test_config stgit.shortnr 1 &&
+    test_config branch.other.stgit.shortnr 2 &&
+    stg series --short --noprefix >out &&
+    test "$(echo $(cat out))" = "p2 p3" &&
+    stg series -b other --short --noprefix >out &&
+    test "$(echo $(cat out))" = "q1 q2 q3"
+'
+
+test_expect_success 'Conflicting boolean settings per branch' '
+    test_config stgit.hide.autopop false &&
+    test_config branch.master.stgit.hide.autopop true &&
+    test_config branch.other.stgit.hide.autopop false &&
+    command_error stg hide -b other q3 2>err &&
+    grep -e "patch \`q3\` is applied; use \`--pop\` to pop and hide it" err &&
+    stg hide p3 &&
+    test "$(echo $(stg series --noprefix --hidden))" = "p3" &&
+    stg unhide p3 &&
+    stg push p3
+'
+
+test_expect_success 'Multi-level keys are branch-specific' '
+    test_config stgit.delete.backup false &&
+    test_config branch.other.stgit.delete.backup true &&
+    stg delete -b other q3 2>err &&
+    grep -e "deleted patches backed up" err &&
+    ls .git/stgit-backups >backups &&
+    test_line_count = 1 backups &&
+    stg delete p3 2>err &&
+    ! grep -e "deleted patches backed up" err &&
+    ls .git/stgit-backups >backups &&
+    test_line_count = 1 backups
+'
+
+test_expect_success 'Branch names with dots' '
+    stg branch --create v1.2 &&
+    stg new -m r1 &&
+    stg new -m r2 &&
+    test_config stgit.shortnr 2 &&
+    test_config branch.v1.2.stgit.shortnr 0 &&
+    stg series --short --noprefix >out &&
+    test "$(echo $(cat out))" = "r2" &&
+    stg series -b master --short --noprefix >out &&
+    test "$(echo $(cat out))" = "p1 p2"
+'
+
+test_expect_success 'Invalid branch value is reported' '
+    test_config branch.v1.2.stgit.autostash bogus &&
+    stg rebase --nopush "$(stg id {base})~1" 2>err &&
+    grep -e "Invalid config value \`stgit.autostash\`" err
+'
+
+test_done
@@ -0,0 +1,46 @@
+#!/bin/sh
+
+test_description='Test patch ranges and series on a large stack'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup a large stack' '
+    test_commit_bulk --message="patch %s" 1500 &&
+    stg init &&
+    stg uncommit -n 1500 &&
+    stg pop -n 500 &&
+    stg hide patch-1500 &&
+    printf "%s\n" expected out >>.git/info/exclude
+'
+
+test_expect_success 'Series with descriptions' '
+    awk "BEGIN { for (i = 1; i < 1500; i++) print \"patch-\" i \" # patch \" i }" >expected &&
+    stg series --description --noprefix | sed -e "s/  *#/ #/" >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Series of a large range' '
+    stg series --noprefix patch-10..patch-1200 >out &&
+    test_line_count = 1191 out &&
+    test "$(head -n 1 out)" = "patch-10" &&
+    test "$(tail -n 1 out)" = "patch-1200"
+'
+
+test_expect_success 'Series of many patch locators' '
+    test_seq 1 1499 | sed -e "s/^/patch-/" >expected &&
+    stg series --noprefix $(cat expected) >out &&
+    test_cmp expected out &&
+    stg series --noprefix $(test_seq 0 998) >out &&
+    head -n 999 expected | test_cmp - out &&
+    stg series --noprefix $(stg series --noprefix --commit-id=10 | sed -e "s/ .*//") >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Push and pop all patches' '
+    stg push --all &&
+    test "$(stg top)" = "patch-1499" &&
+    stg pop --all &&
+    test "$(stg series --applied --count)" = "0"
+'
+
+test_done
@@ -0,0 +1,124 @@
+#!/bin/sh
+
+test_description='Test separation of data and commentary output and --quiet'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    test_commit_bulk 2 &&
+    stg init &&
+    for i in 1 2 3; do
+        stg new -m "patch $i" p$i &&
+        echo "$i" >file$i.txt &&
+        stg add file$i.txt &&
+        stg refresh || return 1
+    done &&
+    printf "%s\n" out err >>.git/info/exclude
+'
+
+test_expect_success 'Id output is pipeline safe' '
+    stg id p2 >out 2>err &&
+    test_must_be_empty err &&
+    test "$(cat out)" = "$(git rev-parse HEAD~)" &&
+    test "$(stg id p2 | git cat-file --batch-check="%(objecttype)")" = "commit"
+'
+
+test_expect_success 'Series output is pipeline safe' '
+    stg series --format json-lines >out 2>err &&
+    test_must_be_empty err &&
+    test_line_count = 3 out &&
+    test "$(grep -c -e "^{.*}$" out)" = "3"
+'
+
+test_expect_success 'Files output is pipeline safe' '
+    stg files -z p2 >out 2>err &&
+    test_must_be_empty err &&
+    printf "A\000file2.txt\000" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Export output is pipeline safe' '
+    stg export --stdout p3 >out 2>err &&
+    test_must_be_empty err &&
+    head -n 1 out | grep -e "^patch 3$" &&
+    git apply --check -R out
+'
+
+test_expect_success 'Stack modifications report to stderr' '
+    test_config stgit.stacksummary true &&
+    stg pop >out 2>err &&
+    test_must_be_empty out &&
+    grep -e "^> p2$" err &&
+    stg push >out 2>err &&
+    test_must_be_empty out &&
+    grep -e "^> p3$" err &&
+    stg float p1 >out 2>err &&
+    test_must_be_empty out &&
+    grep -e "^Now at p1 with 3 applied and 0 unapplied$" err
+'
+
+test_expect_success 'Quiet suppresses commentary' '
+    stg -q pop >out 2>err &&
+    test_must_be_empty out &&
+    test_must_be_empty err &&
+    stg push --quiet >out 2>err &&
+    test_must_be_empty out &&
+    test_must_be_empty err &&
+    stg sink -q p1 2>err &&
+    test_must_be_empty err &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p3"
+'
+
+test_expect_success 'Quiet suppresses informational messages' '
+    stg hide --merged p1 2>err &&
+    grep -e "^info: no patches to hide$" err &&
+    stg hide -q --merged p1 2>err &&
+    test_must_be_empty err
+'
+
+test_expect_success 'Quiet does not affect data output' '
+    stg -q series --noprefix >out 2>err &&
+    test_must_be_empty err &&
+    test "$(echo $(cat out))" = "p1 p2 p3"
+'
+
+test_expect_success 'Setup deprecated alias' '
+    stg rename --keep-alias p3 q3 &&
+    stg id p3 2>err &&
+    grep -e "^warning: .p3. is a deprecated alias for patch .q3." err
+'
+
+test_expect_success 'Quiet still reports warnings' '
+    stg -q id p3 >out 2>err &&
+    test "$(cat out)" = "$(stg id q3)" &&
+    grep -e "deprecated alias" err
+'
+
+test_expect_success 'Quiet twice suppresses warnings' '
+    stg -qq id p3 >out 2>err &&
+    test "$(cat out)" = "$(stg id q3)" &&
+    test_must_be_empty err &&
+    stg id -qq p3 2>err &&
+    test_must_be_empty err
+'
+
+test_expect_success 'Errors are always reported' '
+    command_error stg -qq id p4 2>err &&
+    grep -e "^error: " err
+'
+
+test_expect_success 'Verbose and quiet counts offset' '
+    stg pop &&
+    stg push -v >out 2>err &&
+    test_must_be_empty out &&
+    grep -e "^Pushed 1 patch" err &&
+    stg pop &&
+    stg push -v -q 2>err &&
+    grep -e "^> q3$" err &&
+    ! grep -e "^Pushed" err &&
+    stg pop &&
+    stg push -vv -q 2>err &&
+    grep -e "^Pushed 1 patch" err
+'
+
+test_done
@@ -0,0 +1,94 @@
+#!/bin/sh
+
+test_description='Test stg series marking of patches touching the worktree'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    printf "%s\n" out err expected >>.git/info/exclude &&
+    test_commit_bulk --message="p%s" 4 &&
+    stg init &&
+    stg uncommit -n 4 &&
+    stg new -m empty &&
+    stg pop
+'
+
+test_expect_success 'No marks with clean worktree' '
+    stg series --touches-worktree >out &&
+    cat >expected <<-\EOF &&
+	 + p1
+	 + p2
+	 + p3
+	 > p4
+	 - empty
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Mark patches touching worktree and index changes' '
+    echo "change" >>2.t &&
+    echo "change" >>4.t &&
+    git add 4.t &&
+    echo "untracked" >1.t.new &&
+    stg series --touches-worktree >out &&
+    cat >expected <<-\EOF &&
+	 + p1
+	~+ p2
+	 + p3
+	~> p4
+	 - empty
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Marks compose with ranges and other columns' '
+    stg series --touches-worktree --empty -I p2..p3 >out &&
+    cat >expected <<-\EOF &&
+	 ~+ 1 p2
+	  + 2 p3
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Marks of unapplied patches' '
+    git reset -q 4.t &&
+    git checkout 2.t 4.t &&
+    stg new -m p5 &&
+    echo "p5" >>1.t &&
+    stg refresh &&
+    stg pop &&
+    echo "change" >>1.t &&
+    stg series --touches-worktree -U >out 2>err &&
+    test_must_be_empty err &&
+    cat >expected <<-\EOF &&
+	~- p5
+	 - empty
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Marks are colored' '
+    stg series --touches-worktree --color=always p1 | test_decode_color >out &&
+    grep -e "<YELLOW>~<RESET>.*<GREEN>+ <RESET>.*p1" out
+'
+
+test_expect_success 'Touches worktree conflicts with format and count' '
+    general_error stg series --touches-worktree --format=json &&
+    general_error stg series --touches-worktree --count
+'
+
+test_expect_success 'Marks are skipped with unresolved conflicts' '
+    git checkout 1.t &&
+    stg pop p3 &&
+    stg new -m conflict-p3 &&
+    echo "conflict" >3.t &&
+    stg add 3.t &&
+    stg refresh &&
+    conflict stg push p3 &&
+    stg series --touches-worktree >out 2>err &&
+    grep -e "^info: not marking patches touching the worktree" err &&
+    grep -e "^+ p1$" out &&
+    grep -e "^> p3$" out
+'
+
+test_done
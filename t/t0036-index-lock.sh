@@ -0,0 +1,102 @@
+#!/bin/sh
+
+test_description='Test commands while the index is locked'
+
+. ./test-lib.sh
+
+mtime () {
+    perl -e 'print((stat $ARGV[0])[9])' "$1"
+}
+
+release_index_lock_later () {
+    (sleep 1 && rm -f .git/index.lock) &
+}
+
+test_expect_success 'Setup stack and a merge in progress' '
+    printf "%s\n" out err expected "*.patch" >>.git/info/exclude &&
+    echo base >file.txt &&
+    git add file.txt &&
+    git commit -m base &&
+    git branch other &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >file.txt &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo p2 >other.txt &&
+    stg add other.txt &&
+    stg refresh &&
+    stg pop &&
+    git checkout other &&
+    echo other >file.txt &&
+    git commit -a -m other &&
+    git checkout master &&
+    test_must_fail git merge other &&
+    test_path_is_file .git/MERGE_HEAD
+'
+
+test_expect_success 'Read-only commands work with a locked index' '
+    test_when_finished "rm -f .git/index.lock" &&
+    touch .git/index.lock &&
+    stg series >out &&
+    cat >expected <<-\EOF &&
+	> p1
+	- p2
+	EOF
+    test_cmp expected out &&
+    test "$(stg series --touches-worktree --noprefix p1 2>/dev/null)" = "p1" &&
+    stg id p2 &&
+    stg top &&
+    stg next &&
+    stg name &&
+    stg show p2 &&
+    stg files p2 &&
+    stg diff -r p1 &&
+    stg patches file.txt &&
+    stg grep p1 &&
+    stg log &&
+    stg export --stdout p1 &&
+    test_must_be_empty .git/index.lock
+'
+
+test_expect_success 'State-changing commands fail with a held index lock' '
+    test_when_finished "rm -f .git/index.lock" &&
+    git merge --abort &&
+    touch .git/index.lock &&
+    test_config stgit.indexlocktimeout 100 &&
+    command_error stg push 2>err &&
+    grep -e "the index is locked: .*index.lock. exists" err &&
+    grep -e "another git process seems to be running" err &&
+    test "$(echo $(stg series))" = "> p1 - p2"
+'
+
+test_expect_success 'Read-only commands do not refresh the index' '
+    touch -t 200001010000 .git/index &&
+    touch -t 200001010000 file.txt &&
+    before=$(mtime .git/index) &&
+    stg series --touches-worktree >/dev/null &&
+    test "$(mtime .git/index)" = "$before"
+'
+
+test_expect_success 'State-changing commands wait for the index lock' '
+    test_when_finished "rm -f .git/index.lock" &&
+    touch .git/index.lock &&
+    test_config stgit.indexlocktimeout 5000 &&
+    release_index_lock_later &&
+    stg push &&
+    wait &&
+    test "$(echo $(stg series))" = "+ p1 > p2"
+'
+
+test_expect_success 'Index editing commands wait for the index lock' '
+    test_when_finished "rm -f .git/index.lock" &&
+    echo new >new.txt &&
+    touch .git/index.lock &&
+    test_config stgit.indexlocktimeout 5000 &&
+    release_index_lock_later &&
+    stg add new.txt &&
+    wait &&
+    test -n "$(git ls-files new.txt)"
+'
+
+test_done
@@ -0,0 +1,92 @@
+#!/bin/sh
+
+test_description='Test marking the topmost patch with unrefreshed changes'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    printf "%s\n" out err expected >>.git/info/exclude &&
+    test_commit_bulk --message="p%s" 3 &&
+    stg init &&
+    stg uncommit -n 3 &&
+    stg pop
+'
+
+test_expect_success 'No mark with clean worktree' '
+    stg series --dirty >out &&
+    cat >expected <<-\EOF &&
+	+ p1
+	> p2
+	- p3
+	EOF
+    test_cmp expected out &&
+    stg top --dirty-exit-code >out &&
+    test "$(cat out)" = "p2"
+'
+
+test_expect_success 'Untracked files are not considered' '
+    echo "untracked" >untracked.t &&
+    stg series --dirty >out &&
+    test_cmp expected out &&
+    stg top --dirty-exit-code &&
+    rm untracked.t
+'
+
+test_expect_success 'Mark topmost patch with worktree changes' '
+    echo "change" >>1.t &&
+    stg series --dirty >out &&
+    cat >expected <<-\EOF &&
+	+ p1
+	>*p2
+	- p3
+	EOF
+    test_cmp expected out &&
+    test_expect_code 1 stg top --dirty-exit-code >out &&
+    test "$(cat out)" = "p2"
+'
+
+test_expect_success 'Mark topmost patch with index changes' '
+    git add 1.t &&
+    stg series --dirty >out &&
+    test_cmp expected out &&
+    test_expect_code 1 stg top --dirty-exit-code &&
+    stg top
+'
+
+test_expect_success 'Not marked by default' '
+    stg series >out &&
+    cat >expected <<-\EOF &&
+	+ p1
+	> p2
+	- p3
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Mark enabled by config' '
+    test_config stgit.series.showdirty true &&
+    stg series >out &&
+    grep -e "^>\*p2" out &&
+    stg series --no-dirty >out &&
+    grep -e "^> p2" out &&
+    stg series --no-prefix >out &&
+    grep -e "^p2" out
+'
+
+test_expect_success 'Refresh clears mark' '
+    stg refresh &&
+    stg series --dirty >out &&
+    grep -e "^> p2" out &&
+    stg top --dirty-exit-code
+'
+
+test_expect_success 'No mark for other branches' '
+    stg branch --clone other &&
+    stg branch master &&
+    echo "change" >>1.t &&
+    stg series --dirty -b other >out &&
+    grep -e "^> p2" out &&
+    general_error stg top --dirty-exit-code -b other
+'
+
+test_done
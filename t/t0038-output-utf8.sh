@@ -0,0 +1,54 @@
+#!/bin/sh
+
+test_description='Test output of non-ASCII patch descriptions and diffs'
+
+. ./test-lib.sh
+. "$TEST_DIRECTORY"/lib-terminal.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    printf "%s\n" out expected >>.git/info/exclude &&
+    stg init &&
+    echo "内容" >file.txt &&
+    stg add file.txt &&
+    stg new -m "修正：バグを直す" p1 &&
+    stg refresh &&
+    echo "更新" >>file.txt &&
+    stg new -m "更新文件" p2 &&
+    stg refresh
+'
+
+test_expect_success 'Series description' '
+    stg series -d >out &&
+    cat >expected <<-\EOF &&
+	+ p1 # 修正：バグを直す
+	> p2 # 更新文件
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Colored series description' '
+    stg series -d --color=always | test_decode_color >out &&
+    grep -e "p2.*#.* 更新文件" out &&
+    grep -e "<RESET>" out
+'
+
+test_expect_success 'Show patch' '
+    stg show p1 >out &&
+    grep -e "^    修正：バグを直す$" out &&
+    grep -e "^+内容$" out
+'
+
+test_expect_success 'Colored show patch' '
+    stg show --color=always p2 | test_decode_color >out &&
+    grep -e "<GREEN>+<RESET><GREEN>更新<RESET>" out &&
+    grep -e "更新文件" out
+'
+
+test_expect_success TTY 'Show patch in a terminal' '
+    test_terminal stg show p2 | test_decode_color >out &&
+    grep -e "<GREEN>+<RESET><GREEN>更新<RESET>" out &&
+    test_terminal stg series -d | test_decode_color >out &&
+    grep -e "#.* 更新文件" out
+'
+
+test_done
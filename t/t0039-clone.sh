@@ -0,0 +1,92 @@
+#!/bin/sh
+
+test_description='Test stg clone'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup repository to clone' '
+    test_commit_bulk --message="c%s" 3 &&
+    git branch other HEAD~ &&
+    git init --bare empty.git
+'
+
+test_expect_success 'Clone and initialize stack' '
+    stg clone . cloned 2>err &&
+    grep -e "initialized StGit stack on branch \`master\`" err &&
+    git -C cloned rev-parse --verify -q refs/stacks/master &&
+    (
+        cd cloned &&
+        stg series >out &&
+        test_must_be_empty out &&
+        stg new -m p0 p0
+    )
+'
+
+test_expect_success 'Clone into directory derived from repository' '
+    mkdir sub &&
+    (
+        cd sub &&
+        stg clone ../cloned/.git &&
+        git -C cloned rev-parse --verify -q refs/stacks/master
+    )
+'
+
+test_expect_success 'Clone with branch' '
+    stg clone --branch other . cloned-other &&
+    test "$(git -C cloned-other symbolic-ref --short HEAD)" = "other" &&
+    git -C cloned-other rev-parse --verify -q refs/stacks/other &&
+    test_must_fail git -C cloned-other rev-parse --verify -q refs/stacks/master
+'
+
+test_expect_success 'Existing directory is rejected' '
+    mkdir existing &&
+    command_error stg clone . existing 2>err &&
+    grep -e "destination path \`existing\` already exists" err &&
+    test_dir_is_empty existing &&
+    command_error stg clone . cloned 2>err &&
+    grep -e "destination path \`cloned\` already exists" err
+'
+
+test_expect_success 'Clone errors are reported' '
+    command_error stg clone does-not-exist new 2>err &&
+    grep -e "error: \`git clone\`: fatal: .*does-not-exist" err &&
+    test_path_is_missing new
+'
+
+test_expect_success 'Clone of empty repository' '
+    test_expect_code 7 stg clone empty.git empty-clone 2>err &&
+    grep -e "could not initialize a StGit stack: branch \`master\` is unborn" err &&
+    test_path_is_dir empty-clone/.git
+'
+
+test_expect_success 'Shallow clone' '
+    stg clone --depth 1 "file://$(pwd)" shallow 2>err &&
+    grep -e "warning: the clone is shallow" err &&
+    test "$(git -C shallow rev-parse --is-shallow-repository)" = "true" &&
+    git -C shallow rev-parse --verify -q refs/stacks/master
+'
+
+test_expect_success 'Ignored depth is reported' '
+    stg clone --depth 1 . not-shallow 2>err &&
+    grep -e "warning: \`--depth\` was ignored" err &&
+    test "$(git -C not-shallow rev-parse --is-shallow-repository)" = "false"
+'
+
+test_expect_success 'Invalid depth' '
+    general_error stg clone --depth 0 . zero 2>err &&
+    test_path_is_missing zero
+'
+
+test_expect_success 'Clone outside of a repository' '
+    mkdir -p outside/sub &&
+    (
+        cd outside/sub &&
+        GIT_CEILING_DIRECTORIES="$(cd .. && pwd)" &&
+        export GIT_CEILING_DIRECTORIES &&
+        not_a_repository_error stg series &&
+        stg clone ../../cloned &&
+        git -C cloned rev-parse --verify -q refs/stacks/master
+    )
+'
+
+test_done
@@ -0,0 +1,82 @@
+#!/bin/sh
+
+test_description='Test reading patches from stdin with --stdin'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    printf "%s\n" err out expected patches >>.git/info/exclude &&
+    test_commit_bulk --message="p%s" 5 &&
+    stg uncommit -n 5
+'
+
+test_expect_success 'Hide and unhide patches read from stdin' '
+    printf "p4\n\np5\n" | stg hide --pop --stdin &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p4 p5" &&
+    stg series --hidden --noprefix | stg unhide --stdin &&
+    test "$(echo $(stg series --all --noprefix))" = "p1 p2 p3 p4 p5" &&
+    stg push p4 p5
+'
+
+test_expect_success 'Float patches in input order' '
+    printf "p3\np1\n" | stg float --stdin &&
+    test "$(echo $(stg series --noprefix))" = "p2 p4 p5 p3 p1" &&
+    printf "p5\0p4\0" | stg sink --stdin -z &&
+    test "$(echo $(stg series --noprefix))" = "p5 p4 p2 p3 p1"
+'
+
+test_expect_success 'Ranges and locators are accepted' '
+    printf "p4..p2\n" | stg show --stdin --stat >out &&
+    grep -e "p4" out &&
+    grep -e "p2" out &&
+    ! grep -e "p5" out &&
+    printf "@\n" | stg export --stdin --stdout >out &&
+    test "$(head -n 1 out)" = "p1"
+'
+
+test_expect_success 'Format emails for patches read from stdin' '
+    stg series --noprefix | sed -n "2,3p" | stg email format --stdin -o patches &&
+    ls patches >out &&
+    cat >expected <<-\EOF &&
+	0001-p4.patch
+	0002-p2.patch
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Delete patches read from stdin' '
+    printf "p1\n" | stg delete --stdin &&
+    test "$(echo $(stg series --noprefix))" = "p5 p4 p2 p3"
+'
+
+test_expect_success 'Empty selection is an error unless allowed' '
+    for cmd in "hide" "unhide" "delete" "float" "sink" "show" "export" "email format"; do
+        command_error stg $cmd --stdin </dev/null 2>err &&
+        grep -e "no patches read from stdin; use .--allow-empty-selection." err &&
+        printf "\n\n" | stg $cmd --stdin --allow-empty-selection >out &&
+        test_must_be_empty out ||
+        return 1
+    done &&
+    test "$(echo $(stg series --all --noprefix))" = "p5 p4 p2 p3"
+'
+
+test_expect_success 'Invalid patches read from stdin' '
+    printf "p5\nnot-a-patch\n" | command_error stg hide --stdin 2>err &&
+    grep -e "patch .not-a-patch. does not exist" err &&
+    printf "p5\n" | command_error stg unhide --stdin 2>err &&
+    grep -e "patch .p5. is not hidden" err &&
+    printf "p1..p2..p3\n" | command_error stg delete --stdin 2>err &&
+    grep -e "invalid patch range .p1..p2..p3. read from stdin" err &&
+    test "$(echo $(stg series --all --noprefix))" = "p5 p4 p2 p3"
+'
+
+test_expect_success 'The --stdin option conflicts with patch arguments' '
+    echo p5 | general_error stg hide --stdin p4 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg hide -z 2>err &&
+    grep -e "required arguments were not provided" err &&
+    general_error stg unhide --allow-empty-selection 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_done
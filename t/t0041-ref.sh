@@ -0,0 +1,85 @@
+#!/bin/sh
+
+test_description='Test stg ref'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    stg new -m p0 &&
+    stg new -m p1 &&
+    stg new -m p2 &&
+    stg pop &&
+    stg new -m h0 &&
+    stg hide --pop h0 &&
+    git branch other &&
+    stg branch --create new-branch
+'
+
+test_expect_success 'Print patch references' '
+    stg branch master &&
+    stg ref p1 >out &&
+    echo refs/patches/master/p1 >expected &&
+    test_cmp expected out &&
+    test "$(git rev-parse $(stg ref p1))" = "$(stg id p1)"
+'
+
+test_expect_success 'Print references of locators and ranges' '
+    stg ref @ h0 p2 >out &&
+    cat >expected <<-\EOF &&
+	refs/patches/master/p1
+	refs/patches/master/h0
+	refs/patches/master/p2
+	EOF
+    test_cmp expected out &&
+    stg ref p0..p1 >out &&
+    cat >expected <<-\EOF &&
+	refs/patches/master/p0
+	refs/patches/master/p1
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Print NUL-terminated references' '
+    stg ref -z p0 p2 >out &&
+    printf "refs/patches/master/p0\0refs/patches/master/p2\0" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Print stack and log references' '
+    test "$(stg ref --stack)" = "refs/stacks/master" &&
+    test "$(stg ref --log)" = "refs/stacks/master" &&
+    stg ref --stack --log >out &&
+    printf "refs/stacks/master\nrefs/stacks/master\n" >expected &&
+    test_cmp expected out &&
+    git log --format=%s $(stg ref --log) >out &&
+    grep -e "^hide h0$" out
+'
+
+test_expect_success 'References of another branch' '
+    stg new -m q0 &&
+    stg branch new-branch &&
+    test "$(stg ref -b master p0)" = "refs/patches/master/p0" &&
+    test "$(stg ref --branch=master --stack)" = "refs/stacks/master" &&
+    test "$(stg ref --stack)" = "refs/stacks/new-branch" &&
+    stg branch master
+'
+
+test_expect_success 'Unknown patch' '
+    command_error stg ref p0 bogus 2>err &&
+    grep -e "patch \`bogus\` does not exist" err
+'
+
+test_expect_success 'Uninitialized branch' '
+    no_stack_error stg ref -b other --stack 2>err &&
+    grep -e "StGit stack not initialized for branch .other." err
+'
+
+test_expect_success 'Patches conflict with --stack' '
+    general_error stg ref --stack p0 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg ref 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_done
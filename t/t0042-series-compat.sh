@@ -0,0 +1,105 @@
+#!/bin/sh
+
+test_description='Test the frozen StGit 1.x layout of stg series'
+
+. ./test-lib.sh
+
+compat_golden="$TEST_DIRECTORY/t0042"
+
+test_expect_success 'Initialize the StGit repository' '
+    printf "%s\n" out >>.git/info/exclude &&
+    test_commit base &&
+    stg init &&
+    echo one >one.t &&
+    stg add one.t &&
+    stg new -m "First patch" p1 &&
+    stg refresh &&
+    echo two >two.t &&
+    stg add two.t &&
+    stg new -m "Patch with a rather long name
+
+Spanning several lines." a-patch-with-a-rather-long-name &&
+    stg refresh &&
+    stg new -m "  Empty patch with surrounding blanks  " empty-top &&
+    stg new -m "Unapplied patch" u1 &&
+    echo three >three.t &&
+    stg add three.t &&
+    stg refresh &&
+    stg new -m "Empty unapplied patch" u-empty &&
+    stg new -m "Hidden patch" h1 &&
+    echo four >four.t &&
+    stg add four.t &&
+    stg refresh &&
+    stg new -m "Empty hidden patch with a long name" hidden-empty-patch-name &&
+    stg pop -n 4 &&
+    stg hide h1 hidden-empty-patch-name
+'
+
+test_expect_success 'Default selection' '
+    stg series --compat-v1 >out &&
+    test_cmp "$compat_golden/default.out" out
+'
+
+test_expect_success 'All patches' '
+    stg series --compat-v1 -a >out &&
+    test_cmp "$compat_golden/all.out" out
+'
+
+test_expect_success 'Empty patches' '
+    stg series --compat-v1 -a -e >out &&
+    test_cmp "$compat_golden/empty.out" out
+'
+
+test_expect_success 'Descriptions' '
+    stg series --compat-v1 -a -d >out &&
+    test_cmp "$compat_golden/description.out" out
+'
+
+test_expect_success 'Descriptions take precedence over authors' '
+    stg series --compat-v1 -a -d --author >out &&
+    test_cmp "$compat_golden/description.out" out
+'
+
+test_expect_success 'Authors with branch names' '
+    stg series --compat-v1 -a --author --showbranch >out &&
+    test_cmp "$compat_golden/author-branch.out" out
+'
+
+test_expect_success 'No prefix' '
+    stg series --compat-v1 -a -e -d --no-prefix >out &&
+    test_cmp "$compat_golden/noprefix.out" out
+'
+
+test_expect_success 'Selected patches' '
+    stg series --compat-v1 -d empty-top..u-empty >out &&
+    test_cmp "$compat_golden/selected.out" out
+'
+
+test_expect_success 'Window and dirty markers do not apply' '
+    test_config stgit.series.window 1 &&
+    test_config stgit.series.showdirty true &&
+    echo dirty >>one.t &&
+    stg series --compat-v1 >out &&
+    git checkout one.t &&
+    test_cmp "$compat_golden/default.out" out
+'
+
+test_expect_success 'Layout from stgit.series.compat' '
+    test_config stgit.series.compat true &&
+    stg series -a -e >out &&
+    test_cmp "$compat_golden/empty.out" out &&
+    stg series -a -e --no-compat-v1 >out &&
+    grep -e "^\*> empty-top$" out &&
+    stg series -a -e --indices >out &&
+    grep -e "^ + 0 p1$" out &&
+    grep -e "^\*> 2 empty-top$" out
+'
+
+test_expect_success 'Unsupported options' '
+    general_error stg series --compat-v1 --indices 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg series --compat-v1 --format json 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
@@ -0,0 +1,74 @@
+#!/bin/sh
+
+test_description='Test abbreviated and misspelled command names'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    stg new -m p1 &&
+    stg new -m p2
+'
+
+test_expect_success 'Unambiguous prefix runs the command' '
+    stg ser >out &&
+    stg series >expected &&
+    test_cmp expected out &&
+    stg unh --help >out &&
+    grep -e "stg unhide" out
+'
+
+test_expect_success 'Ambiguous prefix lists the candidates' '
+    general_error stg p 2>err &&
+    grep -e "ambiguous command .p.; could be any of:" err &&
+    grep -e "^    pop$" err &&
+    grep -e "^    push$" err &&
+    grep -e "^    patches$" err &&
+    test "$(stg top)" = "p2"
+'
+
+test_expect_success 'Aliases are prefix candidates' '
+    test_config stgit.alias.serialize "!echo SERIALIZED" &&
+    general_error stg seri 2>err &&
+    grep -e "^    serialize$" err &&
+    grep -e "^    series$" err &&
+    stg seria >out &&
+    grep -e "SERIALIZED" out
+'
+
+test_expect_success 'Misspelled command is suggested' '
+    general_error stg seires 2>err &&
+    grep -e "error: .seires. is not a stg command" err &&
+    grep -e "the most similar command is .series." err
+'
+
+test_expect_success 'Misspelled command is run with autocorrect' '
+    test_config stgit.autocorrect immediate &&
+    stg seires >out 2>err &&
+    grep -e "continuing assuming that you meant .series." err &&
+    stg series >expected &&
+    test_cmp expected out &&
+    test_config stgit.autocorrect 1 &&
+    stg seires >out 2>err &&
+    grep -e "continuing in 0.1 seconds assuming that you meant .series." err &&
+    test_cmp expected out
+'
+
+test_expect_success 'Autocorrect never disables prefixes' '
+    test_config stgit.autocorrect never &&
+    general_error stg ser 2>err &&
+    grep -e "unrecognized subcommand .ser." err
+'
+
+test_expect_success 'Invalid autocorrect value' '
+    test_config stgit.autocorrect sometimes &&
+    command_error stg ser 2>err &&
+    grep -e "invalid stgit.autocorrect value .sometimes." err
+'
+
+test_expect_success 'Unrelated names are unrecognized' '
+    general_error stg xyzzy 2>err &&
+    grep -e "unrecognized subcommand .xyzzy." err
+'
+
+test_done
@@ -0,0 +1,72 @@
+#!/bin/sh
+
+test_description='Test the subject of patches with an empty subject line'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit p1 &&
+    echo p2 >p2.t &&
+    git add p2.t &&
+    printf "\n\nThe body of p2\n" >msg &&
+    git commit --allow-empty-message --cleanup=verbatim -F msg &&
+    echo p3 >p3.t &&
+    git add p3.t &&
+    git commit --allow-empty-message -m "" &&
+    stg init &&
+    stg uncommit -n 3 &&
+    stg rename the-body-of-p2 p2 &&
+    stg rename patch p3
+'
+
+test_expect_success 'Series description falls back to body or patch name' '
+    stg series --description >out &&
+    cat >expected <<-\EOF &&
+	+ p1 # p1
+	+ p2 # [no subject] The body of p2
+	> p3 # [no subject] p3
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Series format uses the fallback subject' '
+    stg series --format "%(name) %(subject)" >out &&
+    cat >expected <<-\EOF &&
+	p1 p1
+	p2 The body of p2
+	p3 p3
+	EOF
+    test_cmp expected out &&
+    stg series --format json-lines p3 >out &&
+    grep -e "\"description\":\"p3\",\"empty\":false,\"no_subject\":true}$" out &&
+    stg series --format json-lines p1 >out &&
+    grep -e "\"description\":\"p1\",\"empty\":false}$" out
+'
+
+test_expect_success 'Fixup subject uses the fallback subject' '
+    stg new --fixup p2 --no-verify &&
+    test "$(git log -1 --format=%s)" = "fixup! The body of p2" &&
+    stg delete --top
+'
+
+test_expect_success 'Email format refuses an empty subject' '
+    command_error stg email format -o patches --all 2>err &&
+    grep -e "patch .p3. has an empty subject" err &&
+    test_path_is_missing patches &&
+    stg email format -o patches p1..p2 &&
+    test_path_exists patches/0002-The-body-of-p2.patch &&
+    rm -r patches
+'
+
+test_expect_success 'Email format with --force formats an empty subject' '
+    stg email format --force -o patches --all &&
+    grep -e "^Subject: \[PATCH 3/3\] *$" patches/0003-*.patch &&
+    rm -r patches
+'
+
+test_expect_success 'Email send refuses an empty subject' '
+    command_error stg email send --to=a@example.com --dry-run p3 2>err &&
+    grep -e "patch .p3. has an empty subject" err
+'
+
+test_done
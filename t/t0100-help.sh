@@ -12,4 +12,47 @@ test_expect_success 'Run "stg --help"' '
     stg --help
 '
 
+test_expect_success 'Top-level help lists help topics' '
+    stg --help >out &&
+    grep -e "^Help topics:" out &&
+    grep -e "^  locators  " out &&
+    grep -e "^  patterns  " out
+'
+
+test_expect_success 'Show help topics' '
+    stg help locators >out &&
+    head -n 1 out >first &&
+    test "$(cat first)" = "Specifying a single patch" &&
+    grep -e "^  \`{base}+\[<n>\]\`" out &&
+    stg help patterns >out &&
+    grep -e "^  \`%{<label>}\`" out &&
+    grep -e "stg help locators" out
+'
+
+test_expect_success 'Unknown help topic' '
+    general_error stg help no-such-topic 2>err &&
+    grep -e "unrecognized subcommand .no-such-topic." err
+'
+
+test_expect_success 'Command help shows examples' '
+    stg delete --help >out &&
+    sed -n "/^Examples:/,\$p" out >examples &&
+    grep -e "^  stg delete p0\.\.p4$" examples &&
+    stg help float >out &&
+    grep -e "^  stg float .%{net}.$" out &&
+    stg delete -h >out &&
+    ! grep -e "^Examples:" out
+'
+
+test_expect_success 'Generate man pages with examples and help topics' '
+    stg completion man --output=man &&
+    sed -n "/^EXAMPLES/,/^StGit/p" man/stg-delete.txt >examples &&
+    grep -e "^.stg delete p0\.\.p4.::$" examples &&
+    head -n 1 man/stglocators.txt >first &&
+    test "$(cat first)" = "stglocators(7)" &&
+    grep -e "^.@.::$" man/stglocators.txt &&
+    grep -e "linkman:stglocators\[7\]" man/stgpatterns.txt &&
+    ! grep -e "^EXAMPLES" man/stg-new.txt
+'
+
 test_done
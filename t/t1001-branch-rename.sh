@@ -117,4 +117,78 @@ test_expect_success 'Config section is renamed' '
     ! git config --get-regexp "branch\\.xxx\\."
 '
 
+test_expect_success 'Patch refs and reflog are renamed' '
+    _assert_current_branch_name "yyy" &&
+    git rev-parse refs/patches/yyy/p1 >expected-ids.txt &&
+    git reflog show yyy >expected-reflog.txt &&
+    stg branch --rename zzz &&
+    _assert_branch_missing yyy &&
+    _assert_branch_exists zzz &&
+    git rev-parse refs/patches/zzz/p1 >ids.txt &&
+    test_cmp expected-ids.txt ids.txt &&
+    test_must_fail git rev-parse --verify -q refs/patches/yyy/p1 &&
+    git reflog show zzz >reflog.txt &&
+    test $(wc -l <reflog.txt) -gt $(wc -l <expected-reflog.txt)
+'
+
+test_expect_success 'Rename refuses existing stack metadata' '
+    echo "*reflog.txt" >>.git/info/exclude &&
+    git update-ref refs/patches/www/stale HEAD &&
+    command_error stg branch --rename www 2>err &&
+    grep -e "\`refs/patches/www/stale\` already exists for branch \`www\`; use \`--force\` to overwrite it" err &&
+    _assert_current_branch_name "zzz" &&
+    _assert_branch_exists zzz &&
+    git rev-parse --verify -q refs/patches/www/stale &&
+    test_must_fail git rev-parse --verify -q refs/heads/www
+'
+
+test_expect_success 'Rename refuses existing stgit config section' '
+    git update-ref -d refs/patches/www/stale &&
+    git config branch.www.stgit.parentbranch gone &&
+    command_error stg branch --rename www 2>err &&
+    grep -e "config section \`branch.www.stgit\` already exists" err &&
+    _assert_branch_exists zzz &&
+    git config --unset branch.www.stgit.parentbranch
+'
+
+test_expect_success 'Failed rename leaves everything unchanged' '
+    mkdir -p .git/refs/patches/www &&
+    touch .git/refs/patches/www/p1.lock &&
+    command_error stg branch --rename www 2>err &&
+    grep -e "renaming branch \`zzz\` to \`www\`" err &&
+    rm .git/refs/patches/www/p1.lock &&
+    _assert_current_branch_name "zzz" &&
+    _assert_branch_exists zzz &&
+    test_must_fail git rev-parse --verify -q refs/heads/www &&
+    test_must_fail git rev-parse --verify -q refs/stacks/www &&
+    git rev-parse refs/patches/zzz/p1 >ids.txt &&
+    test_cmp expected-ids.txt ids.txt &&
+    test "$(echo $(stg series --noprefix --all))" = "p1 p0 h0"
+'
+
+test_expect_success 'Force rename over abandoned stack metadata' '
+    git update-ref refs/patches/www/stale HEAD &&
+    git update-ref refs/stacks/www HEAD &&
+    git config branch.www.stgit.stale true &&
+    stg branch --rename --force www &&
+    _assert_current_branch_name "www" &&
+    _assert_branch_exists www &&
+    _assert_branch_missing zzz &&
+    test_must_fail git rev-parse --verify -q refs/patches/www/stale &&
+    test_must_fail git config branch.www.stgit.stale &&
+    test "$(echo $(stg series --noprefix --all))" = "p1 p0 h0" &&
+    git rev-parse refs/patches/www/p1 >ids.txt &&
+    test_cmp expected-ids.txt ids.txt
+'
+
+test_expect_success 'Rename updates parent and linked branch config' '
+    stg branch --create child &&
+    test "$(git config branch.child.stgit.parentbranch)" = "www" &&
+    stg branch --link mirror www &&
+    stg branch --rename www vvv &&
+    test "$(git config branch.child.stgit.parentbranch)" = "vvv" &&
+    stg branch --rename mirror mirror2 &&
+    test "$(git config branch.vvv.stgit.linkedbranch)" = "mirror2"
+'
+
 test_done
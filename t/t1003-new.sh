@@ -49,6 +49,26 @@ test_expect_success 'Attempt to create patch with duplicate name' '
     grep -e "patch \`foo\` already exists" err
 '
 
+test_expect_success 'Attempt to create patch with name differing only by case' '
+    command_error stg new FOO -m "case foo" 2>err &&
+    grep -e "patch \`foo\` already exists" err
+'
+
+test_expect_success 'Create patch differing only by case with case-sensitive names' '
+    test_config stgit.stack.casesensitivenames true &&
+    stg new FOO -m "case foo" &&
+    test "$(echo $(stg series --noprefix --all))" = "foo yo FOO"
+'
+
+test_expect_success 'Load stack with case collisions on case-insensitive filesystem' '
+    test_config core.ignorecase true &&
+    stg series --noprefix --all >series 2>err &&
+    grep -e "patches \`foo\` and \`FOO\` differ only by case" err &&
+    test "$(echo $(cat series))" = "foo yo FOO" &&
+    test_config stgit.stack.casesensitivenames true &&
+    stg delete FOO
+'
+
 test_expect_success 'Attempt new with conflicts' '
     stg new -m p0 &&
     echo "something" >file.txt &&
@@ -140,4 +160,102 @@ test_expect_success 'Patch with escaped leading hyphen' '
     [ "$(echo $(stg top))" = "-patch-" ]
 '
 
+test_expect_success 'New with staged changes' '
+    echo staged >staged.txt &&
+    git add staged.txt &&
+    echo unstaged >>file.txt &&
+    stg new --staged -m "staged patch" staged-patch &&
+    test "$(stg top)" = "staged-patch" &&
+    stg files staged-patch >out &&
+    echo "A staged.txt" >expected &&
+    test_cmp expected out &&
+    git diff --cached --quiet &&
+    git diff --name-only >out &&
+    echo "file.txt" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'New with staged changes and empty index' '
+    command_error stg new --staged -m "empty staged" 2>err &&
+    grep -e "no changes staged in the index" err &&
+    test "$(stg top)" = "staged-patch" &&
+    stg new --staged --allow-empty -m "empty staged" empty-staged &&
+    test "$(stg top)" = "empty-staged" &&
+    test -z "$(stg files empty-staged)" &&
+    git diff --name-only >out &&
+    echo "file.txt" >expected &&
+    test_cmp expected out &&
+    git checkout file.txt
+'
+
+test_expect_success 'New from diff' '
+    test_seq 1 20 >seq.txt &&
+    stg add seq.txt &&
+    stg new -m "add seq" --refresh seq-patch &&
+    cat >fix.diff <<-\EOF &&
+	--- a/seq.txt
+	+++ b/seq.txt
+	@@ -2,7 +2,7 @@
+	 2
+	 3
+	 4
+	-5
+	+five
+	 6
+	 7
+	 8
+	EOF
+    stg new --from-diff fix.diff -m "fix five" fix-five &&
+    test "$(stg top)" = "fix-five" &&
+    stg files fix-five >out &&
+    echo "M seq.txt" >expected &&
+    test_cmp expected out &&
+    git diff --quiet &&
+    git diff --cached --quiet &&
+    test "$(sed -n 5p seq.txt)" = "five"
+'
+
+test_expect_success 'New from diff with three-way merge' '
+    git diff-tree -p --full-index $(stg id fix-five) >fix3.diff &&
+    stg pop &&
+    sed "s/^8\$/eight/" seq.txt >seq.txt.tmp && mv seq.txt.tmp seq.txt &&
+    stg refresh &&
+    test_must_fail git apply --check fix3.diff &&
+    stg new --from-diff fix3.diff -m "fix five again" fix-five-again &&
+    test "$(stg top)" = "fix-five-again" &&
+    test "$(sed -n 5p seq.txt)" = "five" &&
+    test "$(sed -n 8p seq.txt)" = "eight" &&
+    git diff --quiet &&
+    stg delete fix-five-again
+
+'
+
+test_expect_success 'New from diff that does not apply' '
+    rm -f .git/stgit-new-rejects &&
+    stg series >series-before &&
+    cat >bad.diff <<-\EOF &&
+	--- a/seq.txt
+	+++ b/seq.txt
+	@@ -1,3 +1,3 @@
+	 1
+	-7
+	+seven
+	 3
+	EOF
+    command_error stg new --from-diff bad.diff -m "bad" bad-patch 2>err &&
+    grep -e "\`bad.diff\` does not apply" err &&
+    grep -e "patch does not apply" .git/stgit-new-rejects &&
+    stg series >series-after &&
+    test_cmp series-before series-after &&
+    git diff --quiet &&
+    git diff --cached --quiet
+'
+
+test_expect_success 'New from diff with dirty work tree' '
+    echo dirty >>seq.txt &&
+    command_error stg new --from-diff fix.diff -m "dirty" 2>err &&
+    grep -e "worktree not clean" err &&
+    git checkout seq.txt
+'
+
 test_done
@@ -25,8 +25,14 @@ test_expect_success 'Create a branch (and switch to it)' '
 '
 
 test_expect_success 'Attempt to delete branch with patches' '
+    stg branch master &&
+    stg new -m only-on-master &&
+    stg branch foo &&
     command_error stg branch --delete master 2>err &&
-    grep -e "delete not permitted: the series still contains patches" err
+    grep -e "delete not permitted: the series still contains patches" err &&
+    grep -e "branch .master. has 3 patches (3 applied, 0 unapplied, 0 hidden), the newest changed " err &&
+    grep -e "; 1 patch not reachable from other refs$" err &&
+    git rev-parse --verify -q refs/heads/master
 '
 
 test_expect_success 'Delete subcommand ordering' '
@@ -39,7 +45,11 @@ test_expect_success 'Delete subcommand ordering' '
 '
 
 test_expect_success 'Force delete branch with patches' '
-    stg branch --delete --force master
+    state=$(git rev-parse refs/stacks/master) &&
+    head=$(git rev-parse refs/heads/master) &&
+    stg branch --delete --force master 2>err &&
+    grep -e "deleting stack state $state of branch .master." err &&
+    grep -e "git branch master $head && git update-ref refs/stacks/master $state" err
 '
 
 test_expect_success 'Make sure the branch ref was deleted' '
@@ -93,6 +103,29 @@ test_expect_success 'Attempt delete current branch with dirty worktree' '
     test_cmp expected out
 '
 
+test_expect_success 'Delete branch with patches reachable from another branch' '
+    stg branch -C qux &&
+    stg branch foo &&
+    stg branch --delete qux 2>err &&
+    grep -e "branch .qux. has 2 patches (2 applied, 0 unapplied, 0 hidden)" err &&
+    grep -e "; all are reachable from other refs$" err &&
+    test_must_fail git rev-parse --verify -q refs/heads/qux
+'
+
+test_expect_success 'Restore a force deleted stack' '
+    stg branch -c restore-me &&
+    stg new -m p-unique &&
+    stg new -m p-hidden &&
+    stg hide --pop p-hidden &&
+    stg branch foo &&
+    stg branch --delete --force restore-me 2>err &&
+    grep -e "has 2 patches (1 applied, 0 unapplied, 1 hidden)" err &&
+    restore=$(sed -n "s/.*restore it with \`\(.*\)\`$/\1/p" err) &&
+    test -n "$restore" &&
+    eval "$restore" &&
+    test "$(stg series -b restore-me --all --noprefix | tr "\n" " ")" = "p-unique p-hidden "
+'
+
 test_expect_success 'Invalid num args to delete' '
     general_error stg branch --delete foo extra 2>err &&
     grep -e "unexpected argument .extra." err
@@ -145,4 +145,63 @@ test_expect_success GPG 'Using invalid user.signingkey causes failure' '
     test "$(stg top)" = "p1"
 '
 
+test_expect_success GPG '--no-gpg-sign overrides commit.gpgsign' '
+    test_config user.signingkey ${GIT_COMMITTER_EMAIL} &&
+    test_config commit.gpgsign true &&
+    stg new --no-gpg-sign -m p3 &&
+    test_must_fail git verify-commit HEAD &&
+    echo "signed" >c.txt &&
+    stg add c.txt &&
+    stg refresh --gpg-sign &&
+    git verify-commit HEAD &&
+    stg delete p3
+'
+
+test_expect_success GPG '--gpg-sign overrides commit.gpgsign' '
+    test_config user.signingkey ${GIT_COMMITTER_EMAIL} &&
+    test_config commit.gpgsign false &&
+    stg new --gpg-sign -m p3 &&
+    git verify-commit HEAD &&
+    stg edit --no-gpg-sign -m "p3 unsigned" &&
+    test_must_fail git verify-commit HEAD &&
+    stg delete p3
+'
+
+test_expect_success 'Explicit --gpg-sign without a signing key is an error' '
+    test_unconfig user.signingkey &&
+    test_config gpg.format ssh &&
+    top=$(stg top 2>/dev/null || :) &&
+    command_error stg new --gpg-sign -m p3 2>err &&
+    grep -e "\`--gpg-sign\` requires a signing key" err &&
+    test "$(stg top 2>/dev/null || :)" = "$top"
+'
+
+test_expect_success 'Configured signing without a signing key fails when signing' '
+    test_unconfig user.signingkey &&
+    test_config gpg.format ssh &&
+    test_config commit.gpgsign true &&
+    top=$(stg top 2>/dev/null || :) &&
+    command_error stg new -m p3 2>err &&
+    ! grep -e "\`--gpg-sign\` requires a signing key" err &&
+    test "$(stg top 2>/dev/null || :)" = "$top" &&
+    stg new --no-gpg-sign -m p3 &&
+    test_must_fail git verify-commit HEAD &&
+    stg delete p3
+'
+
+test_expect_success GPGSSH 'SSH signed patches' '
+    test_config gpg.format ssh &&
+    test_config user.signingkey "$GPGSSH_KEY" &&
+    test_config gpg.ssh.allowedSignersFile "$GPGSSH_ALLOWED_SIGNERS" &&
+    test_config commit.gpgsign false &&
+    stg new --gpg-sign -m p3 &&
+    git verify-commit HEAD &&
+    test_config commit.gpgsign true &&
+    echo "ssh" >d.txt &&
+    stg add d.txt &&
+    stg refresh &&
+    git verify-commit HEAD &&
+    stg delete p3
+'
+
 test_done
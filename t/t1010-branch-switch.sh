@@ -57,4 +57,38 @@ test_expect_success 'Series using @{-N} syntax' '
     test_cmp expected out
 '
 
+test_expect_success 'Attempt switch with dirty worktree' '
+    test "$(stg branch)" = "master" &&
+    echo change >>1.t &&
+    command_error stg branch foo 2>err &&
+    grep -e "worktree not clean; use \`--autostash\`" err &&
+    test "$(stg branch)" = "master"
+'
+
+test_expect_success 'Switch with --autostash' '
+    echo staged >2.t &&
+    git add 2.t &&
+    stg branch --autostash foo &&
+    test "$(stg branch)" = "foo" &&
+    test "$(tail -n1 1.t)" = "change" &&
+    test "$(cat 2.t)" = "staged" &&
+    test -z "$(git stash list)" &&
+    stg branch --autostash - &&
+    test "$(stg branch)" = "master" &&
+    test "$(tail -n1 1.t)" = "change" &&
+    git reset -q --hard
+'
+
+test_expect_success 'Switch with --autostash resulting in conflicts' '
+    echo change >>3.t &&
+    conflict stg branch --autostash foo 2>err &&
+    grep -e "remain in the stash" err &&
+    test "$(stg branch)" = "foo" &&
+    test -n "$(git stash list)"
+'
+
+test_expect_success 'Options --autostash and --merge conflict' '
+    general_error stg branch --autostash --merge master
+'
+
 test_done
@@ -0,0 +1,105 @@
+#!/bin/sh
+
+test_description='Test linked branches'
+
+. ./test-lib.sh
+
+linked_is_head () {
+    test "$(git rev-parse refs/heads/review/foo)" = "$(git rev-parse HEAD)"
+}
+
+test_expect_success 'Initialize branch' '
+    stg branch --create foo &&
+    stg new -m p0 &&
+    stg new -m p1
+'
+
+test_expect_success 'Attempt link to self' '
+    command_error stg branch --link foo 2>err &&
+    grep "a branch cannot be linked to itself" err
+'
+
+test_expect_success 'Attempt unlink without link' '
+    command_error stg branch --unlink 2>err &&
+    grep "branch .foo. has no linked branch" err
+'
+
+test_expect_success 'Link branch' '
+    stg branch --link review/foo &&
+    test "$(git config branch.foo.stgit.linkedbranch)" = "review/foo" &&
+    linked_is_head
+'
+
+test_expect_success 'Pop and push update linked branch' '
+    stg pop &&
+    linked_is_head &&
+    stg pop -a &&
+    linked_is_head &&
+    stg push &&
+    linked_is_head &&
+    stg goto p1 &&
+    linked_is_head
+'
+
+test_expect_success 'New and refresh update linked branch' '
+    stg new -m p2 &&
+    linked_is_head &&
+    echo hello >file &&
+    stg add file &&
+    stg refresh &&
+    linked_is_head
+'
+
+test_expect_success 'External commits update linked branch' '
+    echo world >>file &&
+    git commit -a -m external &&
+    stg repair &&
+    linked_is_head &&
+    stg pop &&
+    linked_is_head
+'
+
+test_expect_success 'Unlink branch' '
+    head=$(git rev-parse HEAD) &&
+    stg branch --unlink &&
+    test_must_fail git config branch.foo.stgit.linkedbranch &&
+    stg pop &&
+    test "$(git rev-parse refs/heads/review/foo)" = "$head"
+'
+
+test_expect_success 'Delete linked branch warns' '
+    stg branch --link review/foo &&
+    stg branch master &&
+    stg branch --delete --force foo 2>err &&
+    grep "branch .foo. is linked to .review/foo., which will no longer be updated" err
+'
+
+test_expect_success 'General stgit.linkedbranch links the stack' '
+    stg branch --create bar &&
+    stg new -m q0 &&
+    test_config stgit.linkedbranch review/bar &&
+    stg new -m q1 &&
+    test "$(git rev-parse refs/heads/review/bar)" = "$(git rev-parse HEAD)"
+'
+
+test_expect_success 'Unlink overrides general stgit.linkedbranch' '
+    test_config stgit.linkedbranch review/bar &&
+    head=$(git rev-parse HEAD) &&
+    stg branch --unlink &&
+    test "$(git config branch.bar.stgit.linkedbranch)" = "" &&
+    stg pop &&
+    test "$(git rev-parse refs/heads/review/bar)" = "$head" &&
+    command_error stg branch --unlink 2>err &&
+    grep "branch .bar. has no linked branch" err
+'
+
+test_expect_success 'General stgit.linkedbranch naming the stack branch is ignored' '
+    test_config stgit.linkedbranch bar &&
+    stg push &&
+    test "$(stg top)" = "q1" &&
+    test "$(git rev-parse refs/heads/bar)" = "$(git rev-parse HEAD)" &&
+    command_error stg branch --unlink 2>err &&
+    grep "branch .bar. has no linked branch" err
+'
+
+test_done
@@ -0,0 +1,86 @@
+#!/bin/sh
+
+test_description='Test generation of patch names from commit messages'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init
+'
+
+test_expect_success 'Unicode subjects are kept' '
+    stg new -m "Ünïcode sübject: ça va" &&
+    test "$(stg top)" = "ünïcode-sübject-ça-va" &&
+    stg delete --top
+'
+
+test_expect_success 'Subject slugging to empty falls back to patch' '
+    stg new -m "!!! ... ???" &&
+    test "$(stg top)" = "patch" &&
+    stg new -m="###" &&
+    test "$(stg top)" = "patch-1" &&
+    stg delete patch patch-1
+'
+
+test_expect_success 'Uniquified names stay within the length limit' '
+    test_config stgit.namelength 10 &&
+    stg new -m "abcde fghi" &&
+    stg new -m "abcde fghi" &&
+    stg new -m "abcde fghi" &&
+    test "$(echo $(stg series --noprefix))" = "abcde-fghi abcde-fg-1 abcde-fg-2" &&
+    stg new -m "abcdefghijkl" &&
+    stg new -m "abcdefghijkl" &&
+    test "$(echo $(stg series --noprefix))" = \
+         "abcde-fghi abcde-fg-1 abcde-fg-2 abcdefghijkl abcdefghijkl-1" &&
+    stg delete $(stg series --noprefix)
+'
+
+test_expect_success 'Prefix and counter placeholders' '
+    test_config stgit.nametemplate "%(prefix)%(counter)-%(slug)" &&
+    test_config stgit.nameprefix "wip-" &&
+    test_config stgit.namelength 16 &&
+    stg new -m "first change to things" &&
+    stg new -m "second" &&
+    stg new explicit -m "explicit name" &&
+    test "$(echo $(stg series --noprefix))" = "wip-1-first wip-2-second explicit" &&
+    stg delete $(stg series --noprefix)
+'
+
+test_expect_success 'Date and branch placeholders for uncommitted patches' '
+    test_commit_bulk --message="commit %s" 2 &&
+    test_config stgit.nametemplate "%(branch)-%(date)-%(slug)" &&
+    test_config stgit.namelength 0 &&
+    stg uncommit -n 2 &&
+    date=$(git log -1 --format=%cs) &&
+    test "$(echo $(stg series --noprefix))" = \
+         "master-$date-commit-1 master-$date-commit-2" &&
+    stg commit -a
+'
+
+test_expect_success 'Pick names a commit after the source branch' '
+    git branch other &&
+    git checkout other &&
+    test_commit_bulk --message="other commit" 1 &&
+    git checkout master &&
+    test_config stgit.nametemplate "from-%(branch)-%(slug)" &&
+    stg pick $(git rev-parse other) &&
+    test "$(stg top)" = "from-master-other-commit" &&
+    stg delete --top
+'
+
+test_expect_success 'Invalid templates fail naming the placeholder' '
+    test_config stgit.nametemplate "%(slug)-%(bogus)" &&
+    command_error stg new -m "anything" 2>err &&
+    grep -e "invalid placeholder .%(bogus). in .stgit.nametemplate." err &&
+    test_config stgit.nametemplate "%(slug" &&
+    command_error stg new -m "anything" 2>err &&
+    grep -e "unterminated placeholder .%(slug. in .stgit.nametemplate." err &&
+    test_config stgit.nametemplate "%(prefix)%(slug)" &&
+    test_config stgit.nameprefix "bad~" &&
+    command_error stg new -m "anything" 2>err &&
+    grep -e "generating patch name with .stgit.nametemplate." err &&
+    grep -e "patch name may not contain .~." err &&
+    test -z "$(stg series)"
+'
+
+test_done
@@ -0,0 +1,107 @@
+#!/bin/sh
+
+test_description='Test protection of default branches'
+
+. ./test-lib.sh
+
+test_expect_success 'Hint about default branch protection on init' '
+    stg init 2>err &&
+    grep -e "hint: .*stgit.protect-default-branches true" err &&
+    stg branch --cleanup
+'
+
+test_expect_success 'No hint when quiet' '
+    stg --quiet init 2>err &&
+    test_must_be_empty err &&
+    stg branch --cleanup
+'
+
+test_expect_success 'No hint when configured' '
+    test_config stgit.protect-default-branches false &&
+    stg init 2>err &&
+    test_must_be_empty err &&
+    stg branch --cleanup
+'
+
+test_expect_success 'No hint for other branches' '
+    stg branch --create foo 2>err &&
+    ! grep -e "hint:" err &&
+    stg branch master
+'
+
+test_expect_success 'Default branches are not protected by default' '
+    stg init 2>/dev/null &&
+    stg branch --list >out &&
+    grep -E "> s[[:space:]]+master" out
+'
+
+test_expect_success 'Protect default branches' '
+    git config stgit.protect-default-branches true &&
+    stg branch --list >out &&
+    grep -E "> sp[[:space:]]+master" out &&
+    grep -E "  s[[:space:]]+foo" out &&
+    command_error stg branch --cleanup 2>err &&
+    grep "clean up not permitted: this branch is protected" err &&
+    command_error stg repair 2>err &&
+    grep "this branch is protected; modification is not permitted" err
+'
+
+test_expect_success 'Branch named by init.defaultBranch is protected' '
+    test_config init.defaultBranch foo &&
+    stg branch --list >out &&
+    grep -E "  sp[[:space:]]+foo" out
+'
+
+test_expect_success 'Custom protected branch patterns' '
+    test_config stgit.protected-branch-patterns "fo* release/*" &&
+    git config --add stgit.protected-branch-patterns "stable" &&
+    stg branch --create release/1.0 2>err &&
+    ! grep -e "hint:" err &&
+    stg branch --create release/v1/fix &&
+    stg branch --list >out &&
+    grep -E "  sp[[:space:]]+foo" out &&
+    grep -E "  sp[[:space:]]+release/1.0[[:space:]]" out &&
+    grep -E "> s[[:space:]]+release/v1/fix" out &&
+    grep -E "  s[[:space:]]+master" out &&
+    stg branch master
+'
+
+test_expect_success 'Unprotect default branch' '
+    stg branch --unprotect &&
+    test "$(git config branch.master.stgit.protect)" = "false" &&
+    stg branch --list >out &&
+    grep -E "> s[[:space:]]+master" out &&
+    stg repair
+'
+
+test_expect_success 'Protect and unprotect default branch again' '
+    stg branch --protect &&
+    stg branch --list >out &&
+    grep -E "> sp[[:space:]]+master" out &&
+    stg branch --unprotect &&
+    test "$(git config branch.master.stgit.protect)" = "false"
+'
+
+test_expect_success 'Unprotect other branch removes setting' '
+    stg branch --protect foo &&
+    stg branch --unprotect foo &&
+    test_must_fail git config branch.foo.stgit.protect
+'
+
+test_expect_success 'Disabling default branch protection' '
+    git config --unset branch.master.stgit.protect &&
+    git config stgit.protect-default-branches false &&
+    stg branch --list >out &&
+    grep -E "> s[[:space:]]+master" out &&
+    stg branch --cleanup
+'
+
+test_expect_success 'Branch-specific default branch protection' '
+    test_config branch.foo.stgit.protect-default-branches true &&
+    test_config branch.foo.stgit.protected-branch-patterns foo &&
+    stg branch --list >out &&
+    grep -E "  sp[[:space:]]+foo" out &&
+    grep -E "  s[[:space:]]+release/1.0[[:space:]]" out
+'
+
+test_done
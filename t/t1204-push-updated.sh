@@ -29,7 +29,7 @@ test_expect_success 'Setup patches' '
 
 test_expect_success 'Push unmodified patches' '
     stg pop -a &&
-    stg push -a >out &&
+    stg push -a 2>out &&
     cat >expected <<-\EOF &&
 	+ p0
 	> p1
@@ -57,7 +57,7 @@ test_expect_success 'Push indicates p1 was not modified' '
     cat >expected <<-\EOF &&
 	> p1
 	EOF
-    stg push p1 >out &&
+    stg push p1 2>out &&
     test_cmp expected out
 '
 
@@ -14,8 +14,8 @@ test_expect_success 'Use pop --spill' '
     stg add b.txt &&
     stg new -rm add-b &&
     conflict stg push 2>err &&
-    grep "untracked working tree files would be overwritten by merge" err &&
-    grep "a.txt" err &&
+    grep "untracked working tree files would be overwritten" err &&
+    grep "^  a.txt" err &&
     stg delete add-b &&
     rm -f a.txt b.txt
 '
@@ -23,7 +23,77 @@ test_expect_success 'Use pop --spill' '
 test_expect_success 'Use pop and manually create untracked file' '
     echo aaa >a.txt &&
     command_error stg push 2>err &&
-    grep "Untracked working tree file .a\.txt. would be overwritten by merge" err
+    grep "untracked working tree files would be overwritten" err &&
+    grep "^  a.txt" err &&
+    test "$(cat a.txt)" = "aaa" &&
+    test "$(echo $(stg series --applied --noprefix))" = ""
+'
+
+test_expect_success 'Force overwrite of untracked file' '
+    stg push --force-overwrite-untracked 2>out &&
+    grep "Overwriting untracked file \`a.txt\`" out &&
+    test "$(cat a.txt)" = "aaa" &&
+    test "$(echo $(stg series --applied --noprefix))" = "patch" &&
+    stg pop
+'
+
+test_expect_success 'Overwrite file ignored by core.excludesFile' '
+    echo "*.txt" >global-ignore &&
+    test_config core.excludesFile "$(pwd)/global-ignore" &&
+    echo aaa >a.txt &&
+    stg push 2>out &&
+    grep "Overwriting ignored file \`a.txt\`" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "patch" &&
+    stg pop
+'
+
+test_expect_success 'Overwrite file ignored by nested .gitignore' '
+    mkdir -p dir/sub &&
+    echo "generated" >dir/.gitignore &&
+    stg add dir/.gitignore &&
+    git commit -m "add ignores" &&
+    echo generated >dir/sub/generated &&
+    git add -f dir/sub/generated &&
+    stg new -rm gen &&
+    stg pop &&
+    mkdir dir/sub &&
+    echo untracked >dir/sub/generated &&
+    stg push gen 2>out &&
+    grep "Overwriting ignored file \`dir/sub/generated\`" out &&
+    test "$(cat dir/sub/generated)" = "generated" &&
+    stg pop
+'
+
+test_expect_success 'Non-ignored untracked file aborts goto' '
+    echo aaa >a.txt &&
+    command_error stg goto patch 2>err &&
+    grep "^  a.txt" err &&
+    stg goto --force-overwrite-untracked patch 2>out &&
+    grep "Overwriting untracked file \`a.txt\`" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "gen patch"
+'
+
+test_expect_success 'Ignored file is restored when the checkout fails' '
+    stg pop &&
+    echo "a.txt" >global-ignore &&
+    test_config core.excludesFile "$(pwd)/global-ignore" &&
+    echo ignored >a.txt &&
+    write_script .git/smudge <<-\EOF &&
+	exit 1
+	EOF
+    test_config filter.fail.smudge ".git/smudge" &&
+    test_config filter.fail.clean cat &&
+    test_config filter.fail.required true &&
+    echo "b.txt filter=fail" >.git/info/attributes &&
+    command_error stg push 2>err &&
+    rm .git/info/attributes &&
+    ! grep "Overwriting ignored file" err &&
+    test "$(cat a.txt)" = "ignored" &&
+    test "$(echo $(stg series --applied --noprefix))" = "gen" &&
+    stg push 2>out &&
+    grep "Overwriting ignored file \`a.txt\`" out &&
+    test "$(cat a.txt)" = "aaa" &&
+    test_path_is_missing .git/stgit-untracked-*
 '
 
 test_done
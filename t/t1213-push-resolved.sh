@@ -0,0 +1,121 @@
+#!/bin/sh
+
+test_description='Test stg resolved
+
+Exercise listing, marking, and unmarking conflict resolutions after a
+conflicting push.'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    echo base >a.txt &&
+    echo base >b.txt &&
+    mkdir dir &&
+    echo base >dir/c.txt &&
+    git add a.txt b.txt dir/c.txt &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >a.txt &&
+    echo p1 >b.txt &&
+    echo p1 >dir/c.txt &&
+    stg refresh &&
+    stg pop &&
+    stg new -m p2 &&
+    echo p2 >a.txt &&
+    echo p2 >b.txt &&
+    echo p2 >dir/c.txt &&
+    stg refresh
+'
+
+test_expect_success 'List with no conflicts' '
+    stg resolved >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Unmark with no recorded conflicts' '
+    command_error stg resolved --unmark a.txt 2>err &&
+    grep -e "no conflicts are recorded for the current branch head" err
+'
+
+test_expect_success 'Push with conflicts' '
+    conflict stg push p1 &&
+    cat >expected <<-\EOF &&
+	U a.txt (content: base, ours, theirs)
+	U b.txt (content: base, ours, theirs)
+	U dir/c.txt (content: base, ours, theirs)
+	EOF
+    stg resolved >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Mark path that is not in conflict' '
+    echo extra >extra.txt &&
+    test_when_finished "rm extra.txt" &&
+    command_error stg resolved --mark extra.txt 2>err &&
+    grep -e "\`extra.txt\` is not in conflict" err
+'
+
+test_expect_success 'Mark paths as resolved' '
+    echo resolved >a.txt &&
+    echo resolved >b.txt &&
+    stg resolved --mark a.txt &&
+    stg resolved b.txt &&
+    cat >expected <<-\EOF &&
+	R a.txt (content: base, ours, theirs)
+	R b.txt (content: base, ours, theirs)
+	U dir/c.txt (content: base, ours, theirs)
+	EOF
+    stg resolved >out &&
+    test_cmp expected out &&
+    test "$(git show :a.txt)" = "resolved"
+'
+
+test_expect_success 'Mark path from subdirectory' '
+    (cd dir &&
+     echo resolved >c.txt &&
+     stg resolved --mark ../dir/c.txt
+    ) &&
+    test -z "$(git ls-files -u)"
+'
+
+test_expect_success 'Unmark path restores conflict' '
+    stg resolved --unmark a.txt &&
+    git ls-files -u a.txt >unmerged &&
+    test_line_count = 3 unmerged &&
+    grep -e "^<<<<<<<" a.txt &&
+    cat >expected <<-\EOF &&
+	U a.txt (content: base, ours, theirs)
+	R b.txt (content: base, ours, theirs)
+	R dir/c.txt (content: base, ours, theirs)
+	EOF
+    stg resolved >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Unmark path that is still in conflict' '
+    command_error stg resolved --unmark a.txt 2>err &&
+    grep -e "\`a.txt\` is still in conflict" err
+'
+
+test_expect_success 'Unmark path that was not in conflict' '
+    command_error stg resolved --unmark p1.txt 2>err &&
+    grep -e "\`p1.txt\` has no recorded conflict" err
+'
+
+test_expect_success 'Unmark requires paths' '
+    general_error stg resolved --unmark 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_expect_success 'Refresh invalidates recorded conflicts' '
+    echo resolved >a.txt &&
+    stg resolved a.txt &&
+    stg refresh &&
+    stg resolved >out &&
+    test_must_be_empty out &&
+    command_error stg resolved --unmark a.txt 2>err &&
+    grep -e "no conflicts are recorded for the current branch head" err
+'
+
+test_done
@@ -0,0 +1,60 @@
+#!/bin/sh
+
+test_description='Test push timing reports with --verbose and STG_PERF'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg init &&
+    stg uncommit -n 3 &&
+    stg pop -a
+'
+
+test_expect_success 'Push with --verbose' '
+    stg push --verbose -a 2>out &&
+    test_line_count = 8 out &&
+    head -n 1 out | grep -E "^  status check [0-9]+\.[0-9]{3}s$" &&
+    grep -E "^  merge [0-9]+\.[0-9]{3}s, checkout [0-9]+\.[0-9]{3}s, total [0-9]+\.[0-9]{3}s$" out &&
+    grep -E "^Pushed 3 patches \(0 conflicts, 0 empty\) in [0-9]+\.[0-9]{3}s, checkout [0-9]+\.[0-9]{3}s$" out
+'
+
+test_expect_success 'Goto with -v' '
+    stg goto -v p1 2>out &&
+    grep -E "^  status check [0-9]+\.[0-9]{3}s$" out &&
+    grep -E "^Pushed 0 patches" out &&
+    stg goto -v p3 2>out &&
+    grep -E "^Pushed 2 patches" out
+'
+
+test_expect_success 'No timings without --verbose' '
+    stg pop -a &&
+    stg push -a 2>out &&
+    ! grep -e "merge" out &&
+    ! grep -e "status check" out &&
+    ! grep -e "^Pushed" out
+'
+
+test_expect_success 'Report conflicts and empty patches' '
+    stg pop -a &&
+    echo conflicting >1.t &&
+    echo 3 >3.t &&
+    stg add 1.t 3.t &&
+    git commit -m "upstream changes" &&
+    conflict stg push -v -a 2>out &&
+    grep -E "^Pushed 1 patch \(1 conflict, 0 empty\)" out &&
+    stg undo --hard
+'
+
+test_expect_success 'STG_PERF dumps JSON to stderr' '
+    STG_PERF=1 stg push p2 >out 2>err &&
+    ! grep -e "^Pushed" out &&
+    ! grep -e "^Pushed" err &&
+    test "$(grep -c -e "^{" err)" = "1" &&
+    grep -e "\"patch\":\"p2\"" err &&
+    grep -e "\"pushed\":1" err &&
+    grep -e "\"elapsed_secs\":" err &&
+    grep -e "\"status_check_secs\":" err
+'
+
+test_done
@@ -0,0 +1,74 @@
+#!/bin/sh
+
+test_description='Test preservation of extra commit headers'
+
+. ./test-lib.sh
+
+# Rewrite the HEAD commit with an extra header added after the committer header.
+add_header () {
+    git cat-file commit HEAD |
+    sed "/^committer /a\\
+$1" >commit.txt &&
+    new=$(git hash-object -t commit -w --stdin <commit.txt) &&
+    git update-ref HEAD "$new"
+}
+
+has_header () {
+    git cat-file commit "$(stg id "$1")" | sed "/^$/q" | grep -q "^$2 "
+}
+
+test_expect_success 'Initialize patches with extra headers' '
+    stg init &&
+    base=$(git rev-parse HEAD) &&
+    test_commit p1 &&
+    add_header "x-review-id 1234" &&
+    add_header "mergetag object $base" &&
+    test_commit p2 &&
+    add_header "gpgsig -----BEGIN PGP SIGNATURE-----" &&
+    stg uncommit -n 2 &&
+    has_header p1 x-review-id &&
+    has_header p1 mergetag &&
+    has_header p2 gpgsig
+'
+
+test_expect_success 'Mergetag survives rewrite with same parent' '
+    stg pop -a &&
+    stg edit -m "p1 edited" p1 &&
+    has_header p1 mergetag &&
+    has_header p1 x-review-id
+'
+
+test_expect_success 'Push with new parent drops signature with notice' '
+    stg push p2 >out 2>&1 &&
+    grep "Dropping invalidated commit header .gpgsig. from .p2." out &&
+    ! has_header p2 gpgsig
+'
+
+test_expect_success 'Unknown header survives pop and push round trip' '
+    stg push p1 >out 2>&1 &&
+    grep "Dropping invalidated commit header .mergetag. from .p1." out &&
+    ! has_header p1 mergetag &&
+    has_header p1 x-review-id &&
+    stg pop -a &&
+    stg push -a >out 2>&1 &&
+    has_header p1 x-review-id &&
+    ! grep "Dropping" out
+'
+
+test_expect_success 'Unknown header survives push --set-tree' '
+    stg pop -a &&
+    stg push p2 &&
+    stg push --set-tree p1 &&
+    has_header p1 x-review-id
+'
+
+test_expect_success 'Unknown header survives edit and refresh' '
+    stg goto p1 &&
+    stg edit -m "new message" &&
+    has_header p1 x-review-id &&
+    echo change >>p1.t &&
+    stg refresh &&
+    has_header p1 x-review-id
+'
+
+test_done
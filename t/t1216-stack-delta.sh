@@ -0,0 +1,68 @@
+#!/bin/sh
+
+test_description='Test the stack change summary printed by stack-shaping commands'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    git config stgit.stacksummary true &&
+    for p in p0 p1 p2 p3 p4; do
+        stg new -m $p &&
+        echo $p >$p.txt &&
+        stg add $p.txt &&
+        stg refresh || return 1
+    done &&
+    stg pop
+'
+
+test_expect_success 'Squash summary' '
+    stg squash -m p01 -n p01 p0 p1 2>out &&
+    grep -e "^Now at p3 with 3 applied (-1) and 1 unapplied$" out &&
+    grep -e "^Created p01; removed p0, p1$" out
+'
+
+test_expect_success 'Delete summary' '
+    stg delete p2 2>out &&
+    grep -e "^Now at p3 with 2 applied (-1) and 1 unapplied$" out &&
+    grep -e "^Removed p2$" out
+'
+
+test_expect_success 'Float summary' '
+    stg float p4 2>out &&
+    grep -e "^Now at p4 with 3 applied (+1) and 0 unapplied (-1)$" out &&
+    test_line_count = 2 out
+'
+
+test_expect_success 'Sink summary' '
+    stg sink p4 2>out &&
+    grep -e "^Now at p3 with 3 applied and 0 unapplied$" out
+'
+
+test_expect_success 'Commit and uncommit summaries' '
+    stg commit 2>out &&
+    grep -e "^Now at p3 with 2 applied (-1) and 0 unapplied$" out &&
+    grep -e "^Removed p4$" out &&
+    stg uncommit 2>out &&
+    grep -e "^Now at p3 with 3 applied (+1) and 0 unapplied$" out &&
+    grep -e "^Created p4$" out
+'
+
+test_expect_success 'No summary by default' '
+    test_when_finished "git config stgit.stacksummary true" &&
+    git config --unset stgit.stacksummary &&
+    stg float p01 2>out &&
+    ! grep -e "^Now at" out &&
+    stg sink p01 2>out &&
+    ! grep -e "^Now at" out
+'
+
+test_expect_success 'Summary suppressed with --quiet' '
+    stg delete -q p3 2>out &&
+    test_must_be_empty out &&
+    stg float --quiet p01 2>out &&
+    test_must_be_empty out &&
+    test "$(stg top)" = "p01"
+'
+
+test_done
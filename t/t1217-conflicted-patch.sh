@@ -0,0 +1,83 @@
+#!/bin/sh
+
+test_description='Test guarding the patch with unresolved conflicts'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup conflicting push' '
+    echo base >file.txt &&
+    git add file.txt &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p0 &&
+    echo p0 >other.txt &&
+    stg add other.txt &&
+    stg refresh &&
+    stg new -m p1 &&
+    echo p1 >file.txt &&
+    stg refresh &&
+    stg pop &&
+    stg new -m p2 &&
+    echo p2 >file.txt &&
+    stg refresh &&
+    stg new -m p3 &&
+    stg pop &&
+    conflict stg push p1 &&
+    test "$(stg top)" = "p1"
+'
+
+for cmd in "pop" "push p3" "goto p0" "float p0" "sink p1" "delete p1" "hide p1" "rename p1 renamed"
+do
+    test_expect_success "Refuse $cmd with unresolved conflicts" "
+        command_error stg $cmd 2>err &&
+        grep -e 'patch \`p1\` has unresolved conflicts' err &&
+        grep -e 'run \`stg undo --hard\`' err &&
+        test \"\$(stg top)\" = \"p1\" &&
+        test -n \"\$(git ls-files -u file.txt)\"
+    "
+done
+
+test_expect_success 'Allow operations not affecting the conflicted patch' '
+    stg hide p3 &&
+    stg rename p0 first &&
+    stg series --all >out &&
+    grep -e "^+ first$" out &&
+    grep -e "^! p3$" out
+'
+
+test_expect_success 'Allow hide and rename of the conflicted patch with --force' '
+    command_error stg hide --force p1 2>err &&
+    ! grep -e "unresolved conflicts" err &&
+    stg rename --force p1 renamed &&
+    test "$(stg top)" = "renamed" &&
+    test -n "$(git ls-files -u file.txt)" &&
+    stg rename --force renamed p1 &&
+    test "$(stg top)" = "p1"
+'
+
+test_expect_success 'Allow pop after resolving and refreshing' '
+    echo resolved >file.txt &&
+    stg add file.txt &&
+    stg refresh &&
+    stg pop &&
+    test "$(stg top)" = "p2"
+'
+
+test_expect_success 'Allow pop after undo --hard' '
+    stg pop &&
+    stg new -m p4 &&
+    echo p4 >file.txt &&
+    stg refresh &&
+    stg pop &&
+    stg push p2 &&
+    conflict stg push p4 &&
+    command_error stg pop 2>err &&
+    grep -e "patch \`p4\` has unresolved conflicts" err &&
+    stg undo --hard &&
+    test -z "$(git ls-files -u)" &&
+    test "$(stg top)" = "p2" &&
+    stg pop &&
+    test "$(stg top)" = "first"
+'
+
+test_done
@@ -0,0 +1,104 @@
+#!/bin/sh
+
+test_description='Test fixed author and committer dates for reproducible stacks'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup upstream repository' '
+    test_commit base &&
+    git clone . first &&
+    git clone . second
+'
+
+run_sequence () {
+    (
+        cd "$1" &&
+        sane_unset GIT_AUTHOR_DATE GIT_COMMITTER_DATE &&
+        {
+            stg init &&
+            stg new -m p1 &&
+            echo p1 >p1.txt &&
+            stg add p1.txt &&
+            stg refresh &&
+            stg new -m p2 &&
+            echo p2 >p2.txt &&
+            stg add p2.txt &&
+            stg refresh &&
+            stg pop -a &&
+            stg push p2 &&
+            sleep 1 &&
+            stg push p1 &&
+            stg edit -m "p2 edited" p2
+        } >/dev/null &&
+        stg id p1 &&
+        stg id p2 &&
+        git rev-parse refs/stacks/master
+    )
+}
+
+test_expect_success 'Fixed dates from environment yield identical commits' '
+    STG_AUTHOR_DATE="2020-01-01 00:00:00 +0000" &&
+    STG_COMMITTER_DATE="2020-01-02 00:00:00 +0000" &&
+    export STG_AUTHOR_DATE STG_COMMITTER_DATE &&
+    run_sequence first >first.ids &&
+    sleep 1 &&
+    run_sequence second >second.ids &&
+    sane_unset STG_AUTHOR_DATE STG_COMMITTER_DATE &&
+    test_line_count = 3 first.ids &&
+    test_cmp first.ids second.ids &&
+    git -C first log -1 --format="%ai %ci" $(head -n 1 first.ids) >dates &&
+    echo "2020-01-01 00:00:00 +0000 2020-01-02 00:00:00 +0000" >expected &&
+    test_cmp expected dates
+'
+
+test_expect_success 'Fixed dates from config' '
+    (
+        cd first &&
+        sane_unset GIT_AUTHOR_DATE GIT_COMMITTER_DATE &&
+        test_config stgit.authordate "2021-03-04 05:06:07 +0100" &&
+        test_config stgit.committerdate "2021-03-04 08:09:10 +0100" &&
+        stg new -m p3 &&
+        git log -1 --format="%ai %ci" >dates &&
+        echo "2021-03-04 05:06:07 +0100 2021-03-04 08:09:10 +0100" >expected &&
+        test_cmp expected dates
+    )
+'
+
+test_expect_success 'Environment takes precedence over config' '
+    (
+        cd first &&
+        sane_unset GIT_AUTHOR_DATE GIT_COMMITTER_DATE &&
+        test_config stgit.committerdate "2021-03-04 08:09:10 +0100" &&
+        STG_COMMITTER_DATE="2022-01-01 00:00:00 +0000" stg refresh -m p3-env &&
+        git log -1 --format="%ci" >dates &&
+        echo "2022-01-01 00:00:00 +0000" >expected &&
+        test_cmp expected dates
+    )
+'
+
+test_expect_success 'Date option takes precedence over environment' '
+    (
+        cd first &&
+        sane_unset GIT_AUTHOR_DATE GIT_COMMITTER_DATE &&
+        STG_AUTHOR_DATE="2022-01-01 00:00:00 +0000" &&
+        STG_COMMITTER_DATE="2022-01-01 00:00:00 +0000" &&
+        export STG_AUTHOR_DATE STG_COMMITTER_DATE &&
+        stg new --date "2023-05-06 07:08:09 +0000" -m p4 &&
+        git log -1 --format="%ai %ci" >dates &&
+        echo "2023-05-06 07:08:09 +0000 2023-05-06 07:08:09 +0000" >expected &&
+        test_cmp expected dates &&
+        stg edit --date "2024-01-01 00:00:00 +0000" -m p4-edited &&
+        git log -1 --format="%ai %ci" >dates &&
+        echo "2023-05-06 07:08:09 +0000 2024-01-01 00:00:00 +0000" >expected &&
+        test_cmp expected dates
+    )
+'
+
+test_expect_success 'Invalid fixed dates' '
+    general_error stg new --date bogus -m bad 2>err &&
+    grep -e "invalid date .bogus." err &&
+    STG_COMMITTER_DATE=bogus command_error stg new -m bad 2>err &&
+    grep -e "parsing .STG_COMMITTER_DATE." err
+'
+
+test_done
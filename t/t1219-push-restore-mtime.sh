@@ -0,0 +1,80 @@
+#!/bin/sh
+
+test_description='Test restoring modification times of unchanged files'
+
+. ./test-lib.sh
+
+mtime () {
+    perl -e 'print((stat $ARGV[0])[9])' "$1"
+}
+
+# Pushing p3 onto p1 needs a merge in the work tree because p1 renames the file
+# p3 modifies. The work tree is then checked out with p1's version of a.txt
+# before p2 reverts a.txt to its original content.
+test_expect_success 'Initialize the StGit repository' '
+    printf "line %s\n" 1 2 3 4 5 6 7 8 >old.txt &&
+    echo x >a.txt &&
+    git add old.txt a.txt &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p3 &&
+    printf "line %s\n" 1 2 3 4 5 6 7 eight >old.txt &&
+    stg refresh &&
+    stg pop &&
+    stg new -m p1 &&
+    echo y >a.txt &&
+    git mv old.txt new.txt &&
+    git add a.txt &&
+    stg refresh --index &&
+    stg new -m p2 &&
+    echo x >a.txt &&
+    stg refresh &&
+    stg pop -a
+'
+
+test_expect_success 'Modification times change without restoring' '
+    touch -t 200001010000 a.txt &&
+    git update-index --refresh &&
+    before=$(mtime a.txt) &&
+    stg push p1 p3 p2 &&
+    test "$(stg top)" = "p2" &&
+    grep -e "line eight" new.txt &&
+    test "$(cat a.txt)" = "x" &&
+    test "$(mtime a.txt)" != "$before" &&
+    stg pop -a
+'
+
+test_expect_success 'Restore modification times with --restore-mtime' '
+    touch -t 200001010000 a.txt &&
+    git update-index --refresh &&
+    before=$(mtime a.txt) &&
+    stg push --restore-mtime p1 p3 p2 &&
+    test "$(stg top)" = "p2" &&
+    grep -e "line eight" new.txt &&
+    test "$(mtime a.txt)" = "$before" &&
+    git diff-files --quiet &&
+    stg pop -a
+'
+
+test_expect_success 'Restore modification times with config' '
+    touch -t 200001010000 a.txt &&
+    git update-index --refresh &&
+    before=$(mtime a.txt) &&
+    test_config stgit.checkout.restore-mtime true &&
+    stg goto p2 &&
+    test "$(stg series --applied --noprefix | tr "\n" " ")" = "p1 p3 p2 " &&
+    test "$(mtime a.txt)" = "$before" &&
+    git diff-files --quiet &&
+    stg pop -a
+'
+
+test_expect_success 'Changed files get new modification times' '
+    touch -t 200001010000 a.txt &&
+    git update-index --refresh &&
+    before=$(mtime a.txt) &&
+    stg push --restore-mtime p1 p3 &&
+    test "$(cat a.txt)" = "y" &&
+    test "$(mtime a.txt)" != "$before"
+'
+
+test_done
@@ -0,0 +1,110 @@
+#!/bin/sh
+
+test_description='Test pushing patches over renames and mode changes'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    printf "1\n2\n3\n4\n5\n6\n7\n8\n9\n" >a.txt &&
+    printf "x\n1\n2\ny\n3\n4\nz\n" >script.sh &&
+    echo expected >>.git/info/exclude &&
+    git add a.txt script.sh &&
+    git commit -m base &&
+    stg init
+'
+
+test_expect_success 'Push patch changing a file renamed by a lower patch' '
+    stg new -m change-a &&
+    sed "s/^8$/eight/" a.txt >a.txt.tmp && mv a.txt.tmp a.txt &&
+    stg refresh &&
+    stg pop &&
+    stg new -m rename-a &&
+    stg mv a.txt b.txt &&
+    sed "s/^2$/two/" b.txt >b.txt.tmp && mv b.txt.tmp b.txt &&
+    stg add b.txt &&
+    stg refresh --index &&
+    stg push change-a &&
+    test "$(echo $(stg status))" = "" &&
+    test_path_is_missing a.txt &&
+    test "$(echo $(stg files change-a))" = "M b.txt" &&
+    cat >expected <<-\EOF &&
+	1
+	two
+	3
+	4
+	5
+	6
+	7
+	eight
+	9
+	EOF
+    test_cmp expected b.txt
+'
+
+test_expect_success 'Push patch renaming a file modified by a lower patch' '
+    stg delete change-a rename-a &&
+    stg new -m rename-a &&
+    stg mv a.txt c.txt &&
+    stg refresh --index &&
+    stg pop &&
+    stg new -m change-a &&
+    sed "s/^5$/five/" a.txt >a.txt.tmp && mv a.txt.tmp a.txt &&
+    stg refresh &&
+    stg push rename-a &&
+    test "$(echo $(stg status))" = "" &&
+    test_path_is_missing a.txt &&
+    grep -e "^five$" c.txt &&
+    test "$(echo $(stg files rename-a))" = "D a.txt A c.txt"
+'
+
+test_expect_success 'Push patch flipping executable bit on a file modified below' '
+    stg new -m make-executable &&
+    test_chmod +x script.sh &&
+    stg refresh --index &&
+    stg pop &&
+    stg new -m change-script &&
+    sed "s/^x$/X/" script.sh >script.sh.tmp && mv script.sh.tmp script.sh &&
+    stg refresh &&
+    stg push make-executable &&
+    test "$(echo $(stg status))" = "" &&
+    test "$(git ls-files -s script.sh | cut -d" " -f1)" = "100755" &&
+    test "$(echo $(cat script.sh))" = "X 1 2 y 3 4 z" &&
+    test "$(git diff-tree --format= --summary $(stg id make-executable^) $(stg id make-executable))" = \
+         " mode change 100644 => 100755 script.sh"
+'
+
+test_expect_success 'Push mode and content changes over content changes' '
+    stg new -m mode-and-content &&
+    chmod -x script.sh &&
+    sed "s/^z$/Z/" script.sh >script.sh.tmp && mv script.sh.tmp script.sh &&
+    stg add script.sh &&
+    stg refresh --index &&
+    stg pop &&
+    stg new -m more-content &&
+    sed "s/^y$/Y/" script.sh >script.sh.tmp && mv script.sh.tmp script.sh &&
+    stg refresh &&
+    stg push mode-and-content &&
+    test "$(echo $(stg status))" = "" &&
+    test "$(git ls-files -s script.sh | cut -d" " -f1)" = "100644" &&
+    test "$(echo $(cat script.sh))" = "X 1 2 Y 3 4 Z"
+'
+
+test_expect_success 'Push over rename without using the work tree' '
+    stg branch --create other &&
+    printf "%s\n" 1 2 3 4 5 6 7 8 >d.txt &&
+    stg add d.txt &&
+    stg new -rm add-d &&
+    stg mv d.txt e.txt &&
+    stg new -m rename-d &&
+    stg refresh --index &&
+    sed "s/8/eight/" e.txt >e.txt.tmp && mv e.txt.tmp e.txt &&
+    stg new -rm change-e &&
+    stg branch master &&
+    stg delete --branch other rename-d &&
+    test "$(echo $(stg series --branch other --noprefix))" = "add-d change-e" &&
+    git show other:d.txt >out &&
+    grep -q eight out &&
+    test_must_fail git cat-file -e other:e.txt
+'
+
+test_done
@@ -0,0 +1,93 @@
+#!/bin/sh
+
+test_description='Test interrupting stg push'
+
+. ./test-lib.sh
+
+# The faux git sends the given signals to its parent stg process the second time the
+# patches are applied, i.e. while the second patch is being pushed. It pauses after
+# each signal so that stg handles them before the push proceeds.
+test_expect_success 'Initialize the StGit repository' '
+    echo base >base.txt &&
+    git add base.txt &&
+    git commit -m base &&
+    stg init &&
+    for i in 1 2 3 4 5; do
+        echo "$i" >"f$i.txt" &&
+        stg add "f$i.txt" &&
+        stg new -rm "p$i" || return 1
+    done &&
+    stg pop -a &&
+    echo zero >f0.txt &&
+    stg add f0.txt &&
+    stg new -rm p0 &&
+    mkdir bin &&
+    real_git="$(command -v git)" &&
+    write_script bin/git <<-EOF &&
+	if test "\$1" = apply
+	then
+	    echo >>"$PWD/apply-count"
+	    if test "\$(wc -l <"$PWD/apply-count")" -eq 2
+	    then
+	        for sig in \$(cat "$PWD/signals")
+	        do
+	            kill -\$sig \$PPID
+	            sleep 1
+	        done
+	    fi
+	fi
+	exec "$real_git" "\$@"
+	EOF
+    echo bin >>.git/info/exclude &&
+    echo apply-count >>.git/info/exclude &&
+    echo signals >>.git/info/exclude &&
+    echo err >>.git/info/exclude
+'
+
+interrupted_push () {
+    echo "$*" >signals &&
+    rm -f apply-count &&
+    (
+        PATH="$PWD/bin:$PATH" &&
+        export PATH &&
+        test_expect_code 130 stg push -a 2>err
+    )
+}
+
+test_expect_success 'Interrupted push stops at a consistent point' '
+    interrupted_push INT &&
+    grep -e "interrupted by user; the stack is now at \`p2\` with 3 applied and 3 unapplied patches" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2" &&
+    test "$(git rev-parse HEAD)" = "$(stg id p2)" &&
+    test "$(echo $(stg status))" = "" &&
+    test_path_is_file f2.txt &&
+    test_path_is_missing f3.txt &&
+    stg push -a &&
+    test "$(stg top)" = "p5"
+'
+
+test_expect_success 'Terminated push stops at a consistent point' '
+    stg goto p0 &&
+    echo one >>f0.txt &&
+    stg refresh &&
+    interrupted_push TERM &&
+    grep -e "interrupted by user" err &&
+    test "$(stg top)" = "p2" &&
+    test "$(git rev-parse HEAD)" = "$(stg id p2)" &&
+    test "$(echo $(stg status))" = "" &&
+    stg push -a
+'
+
+test_expect_success 'Second interrupt terminates immediately' '
+    stg goto p0 &&
+    echo two >>f0.txt &&
+    stg refresh &&
+    interrupted_push INT INT &&
+    test "$(stg top)" = "p0" &&
+    test "$(git rev-parse HEAD)" = "$(stg id p0)" &&
+    test "$(echo $(stg status))" = "" &&
+    stg push -a &&
+    test "$(stg top)" = "p5"
+'
+
+test_done
@@ -0,0 +1,188 @@
+#!/bin/sh
+
+test_description='Test dry runs of stack-modifying commands'
+
+. ./test-lib.sh
+
+# Record the state of the references, reflogs, index, and worktree. Object
+# files are excluded since a dry run may write unreferenced objects.
+snapshot () {
+    {
+        git for-each-ref --format="%(refname) %(objectname)" &&
+        git symbolic-ref HEAD &&
+        (cd .git && find . -path ./objects -prune -o -name "snapshot-*" -prune \
+            -o -type f -print | sort | xargs cat) &&
+        GIT_OPTIONAL_LOCKS=0 git status --porcelain &&
+        find . -path ./.git -prune -o -type f -print | sort | xargs cat
+    } >.git/snapshot-$1
+}
+
+# Run an stg command with --dry-run and check that the repository is unchanged.
+dry_run () {
+    cmd=$1 &&
+    shift &&
+    rm -f out &&
+    snapshot before &&
+    stg "$cmd" --dry-run "$@" 2>.git/snapshot-out &&
+    snapshot after &&
+    test_cmp .git/snapshot-before .git/snapshot-after &&
+    mv .git/snapshot-out out &&
+    grep -e "^Dry run: no changes were made$" out
+}
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit base &&
+    stg init &&
+    stg new -m p1 &&
+    echo one >a.txt &&
+    stg add a.txt &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo two >b.txt &&
+    stg add b.txt &&
+    stg refresh &&
+    stg new -m p3 &&
+    echo three >a.txt &&
+    stg refresh &&
+    stg new -m p4 &&
+    stg new -m p5 &&
+    echo five >c.txt &&
+    stg add c.txt &&
+    stg refresh &&
+    stg pop
+'
+
+test_expect_success 'Dry run of pop' '
+    dry_run pop -n 2 &&
+    grep -e "^Would be at p2 with 2 applied (-2) and 3 unapplied (+2)$" out &&
+    test "$(stg top)" = "p4"
+'
+
+test_expect_success 'Dry run of push' '
+    dry_run push &&
+    grep -e "^> p5$" out &&
+    grep -e "^Would be at p5 with 5 applied (+1) and 0 unapplied (-1)$" out &&
+    test "$(stg top)" = "p4"
+'
+
+test_expect_success 'Dry run of goto' '
+    dry_run goto p1 &&
+    grep -e "^Would be at p1 with 1 applied (-3)" out &&
+    test "$(stg top)" = "p4"
+'
+
+test_expect_success 'Dry run of float predicts conflicts' '
+    dry_run float p1 &&
+    grep -e "^+ p3 (conflict)$" out &&
+    grep -e "^Would stop with merge conflicts in p3$" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Dry run of sink' '
+    dry_run sink p2 &&
+    grep -e "^> p4 (empty)$" out &&
+    grep -e "^Would be at p4 with 4 applied and 1 unapplied$" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Dry run of squash' '
+    dry_run squash -n p12 -m p12 p1 p2 &&
+    grep -e "^Would create p12; would remove p1, p2$" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    test_must_fail stg id p12
+'
+
+test_expect_success 'Squash dry run cannot edit the message' '
+    general_error stg squash --dry-run --edit p1 p2 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Dry run of delete' '
+    dry_run delete --backup p1 &&
+    grep -e "^+ p3 (conflict)$" out &&
+    grep -e "^Would stop with merge conflicts in p3$" out &&
+    grep -e "^Would remove p1$" out &&
+    test_path_is_missing .git/stgit-backups &&
+    stg id p1
+'
+
+test_expect_success 'Dry run of rename' '
+    dry_run rename p2 second &&
+    grep -e "^Would create second; would remove p2$" out &&
+    stg id p2 &&
+    test_must_fail stg id second
+'
+
+test_expect_success 'Dry run of clean' '
+    dry_run clean &&
+    grep -e "^Would remove p4$" out &&
+    stg id p4
+'
+
+test_expect_success 'Setup upstream' '
+    git checkout -q --detach base &&
+    echo two >b.txt &&
+    git add b.txt &&
+    test_tick &&
+    git commit -q -m upstream &&
+    git tag upstream &&
+    git checkout -q master
+'
+
+test_expect_success 'Dry run of rebase predicts empty patches' '
+    dry_run rebase upstream &&
+    grep -e "^p1  *clean$" out &&
+    grep -e "^p2  *empty$" out &&
+    grep -e "^p4  *empty$" out &&
+    ! grep -e "conflict" out &&
+    grep -e "^Would be at p4 with 4 applied and 1 unapplied$" out &&
+    test "$(stg id {base})" = "$(git rev-parse base)"
+'
+
+test_expect_success 'Dry run of rebase without pushing' '
+    dry_run rebase --nopush upstream &&
+    grep -e "^Would be at {base} with 0 applied (-4) and 5 unapplied (+4)$" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Dry run of rebase requires a clean worktree' '
+    echo dirty >>a.txt &&
+    command_error stg rebase --dry-run upstream 2>err &&
+    grep -e "worktree not clean" err &&
+    dry_run rebase --autostash upstream &&
+    grep -e "^Would be at p4" out &&
+    git checkout a.txt
+'
+
+test_expect_success 'Dry run of rebase predicts every conflict' '
+    git checkout -q --detach base &&
+    echo other >a.txt &&
+    git add a.txt &&
+    test_tick &&
+    git commit -q -m upstream2 &&
+    git tag upstream2 &&
+    git checkout -q master &&
+    snapshot before &&
+    conflict stg rebase --dry-run upstream2 2>.git/snapshot-out &&
+    snapshot after &&
+    test_cmp .git/snapshot-before .git/snapshot-after &&
+    mv .git/snapshot-out out &&
+    grep -e "^p1  *conflicts (a.txt)$" out &&
+    grep -e "^p2  *clean$" out &&
+    grep -e "^p3  *conflicts (a.txt)$" out &&
+    grep -e "^Patches above a conflict are predicted" out &&
+    grep -e "^Dry run: no changes were made$" out &&
+    grep -e "2 patches predicted to conflict" out
+'
+
+test_expect_success 'Dry run of rebase predicts conflicts regardless of push conflicts' '
+    conflict stg rebase --dry-run --conflicts=disallow upstream2 2>out &&
+    grep -e "^p1  *conflicts (a.txt)$" out
+'
+
+test_expect_success 'Dry run of rebase conflicts with interactive' '
+    general_error stg rebase --dry-run --interactive upstream 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
@@ -0,0 +1,107 @@
+#!/bin/sh
+
+test_description='Test push and pop --interactive'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    stg init &&
+    for p in p0 p1 p2 p3; do
+        stg new -m "subject $p" $p &&
+        echo $p >$p.txt &&
+        stg add $p.txt &&
+        stg refresh || return 1
+    done &&
+    stg pop -a
+'
+
+test_expect_success 'Template lists unapplied patches with subjects' '
+    write_script fake-editor <<-\EOF &&
+	cp "$1" template.txt &&
+	printf "p1\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg push --interactive &&
+    grep -e "^p0 # subject p0$" template.txt &&
+    grep -e "^p3 # subject p3$" template.txt &&
+    grep -e "^# .*pushed in the order given" template.txt &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1"
+'
+
+test_expect_success 'Push patches in the order given' '
+    write_script fake-editor <<-\EOF &&
+	printf "p3 # comment\n# p0\n\np2\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg push -i &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p0"
+'
+
+test_expect_success 'Empty list aborts push' '
+    write_script fake-editor <<-\EOF &&
+	printf "# p0\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg push --interactive 2>err &&
+    grep -e "error: no patches to push" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p2"
+'
+
+test_expect_success 'Unknown and applied patches are errors' '
+    write_script fake-editor <<-\EOF &&
+	printf "p0\nnosuch\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg push --interactive 2>err &&
+    grep -e "error: .stgit-patch-list.txt:2: patch .nosuch. does not exist" err &&
+    write_script fake-editor <<-\EOF &&
+	printf "p0\np1\n" >"$1"
+	EOF
+    command_error stg push --interactive 2>err &&
+    grep -e "error: .stgit-patch-list.txt:2: applied patch .p1. is not allowed" err &&
+    write_script fake-editor <<-\EOF &&
+	printf "p0\np0\n" >"$1"
+	EOF
+    command_error stg push --interactive 2>err &&
+    grep -e "error: .stgit-patch-list.txt:2: patch .p0. is listed more than once" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p2"
+'
+
+test_expect_success 'Interactive conflicts with patch arguments' '
+    general_error stg push --interactive p0 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg pop --interactive --all 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Pop the patches left in the list' '
+    write_script fake-editor <<-\EOF &&
+	cp "$1" template.txt &&
+	printf "p3\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg pop --interactive &&
+    grep -e "^p1 # subject p1$" template.txt &&
+    grep -e "^p2 # subject p2$" template.txt &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p3 p0"
+'
+
+test_expect_success 'Empty list aborts pop' '
+    write_script fake-editor <<-\EOF &&
+	printf "" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg pop --interactive 2>err &&
+    grep -e "error: no patches to pop" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_done
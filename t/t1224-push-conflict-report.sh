@@ -0,0 +1,87 @@
+#!/bin/sh
+
+test_description='Test the conflict report of a conflicting push'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    echo base >a.txt &&
+    echo base >b.txt &&
+    test_seq 1 20 >r.txt &&
+    git add a.txt b.txt r.txt &&
+    git commit -m base &&
+    printf "%s\n" out err expected report >>.git/info/exclude &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >a.txt &&
+    git rm -q b.txt &&
+    echo p1 >n.txt &&
+    git mv r.txt s.txt &&
+    git add -A &&
+    stg refresh --index &&
+    stg pop &&
+    stg new -m p2 &&
+    echo p2 >a.txt &&
+    echo p2 >b.txt &&
+    echo p2 >n.txt &&
+    git rm -q r.txt &&
+    git add -A &&
+    stg refresh --index &&
+    stg pop
+'
+
+test_expect_success 'Report conflicts of push' '
+    stg push p1 &&
+    conflict stg push p2 >out 2>err &&
+    test_must_be_empty out &&
+    grep -e "merge conflicts" err &&
+    sed -n "/^Conflicts/,\$p" err >report &&
+    cat >expected <<-\EOF &&
+	Conflicts in patch `p2` (4 files):
+	  content        a.txt
+	  delete/modify  b.txt
+	  add/add        n.txt
+	  rename         s.txt
+	Next steps:
+	  stg resolved <path>...  mark paths as resolved once fixed
+	  stg refresh             record the resolution in the patch
+	  stg undo --hard         undo the operation instead
+	EOF
+    test_cmp expected report
+'
+
+test_expect_success 'List conflict kinds with resolved' '
+    stg resolved >out &&
+    cat >expected <<-\EOF &&
+	U a.txt (content: base, ours, theirs)
+	U b.txt (delete/modify: base, theirs)
+	U n.txt (add/add: ours, theirs)
+	U s.txt (rename: ours)
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Report conflicts of push in porcelain format' '
+    stg undo --hard &&
+    conflict stg push --porcelain p2 >out 2>err &&
+    grep -e "merge conflicts" err &&
+    grep -e "^conflict " out >report &&
+    cat >expected <<-\EOF &&
+	conflict p2 content a.txt
+	conflict p2 delete/modify b.txt
+	conflict p2 add/add n.txt
+	conflict p2 rename s.txt
+	EOF
+    test_cmp expected report &&
+    ! grep -e "^Conflicts" out &&
+    ! grep -e "Next steps" out
+'
+
+test_expect_success 'Report conflicts of other commands' '
+    stg undo --hard &&
+    conflict stg goto p2 2>out &&
+    grep -e "^Conflicts in patch \`p2\` (4 files):" out &&
+    grep -e "^  rename         s.txt" out
+'
+
+test_done
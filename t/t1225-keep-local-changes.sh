@@ -0,0 +1,76 @@
+#!/bin/sh
+
+test_description='Test --keep semantics shared by push, pop, and goto
+
+Local changes may be kept if and only if none of the files changed by the
+tree transition have local changes.'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches' '
+    for f in a b c other; do
+        echo "$f" >"$f.txt" || return 1
+    done &&
+    git add a.txt b.txt c.txt other.txt &&
+    git commit -m "base" &&
+    stg init &&
+    for f in a b c; do
+        stg new -m "p$f" "p$f" &&
+        echo "p$f" >>"$f.txt" &&
+        stg refresh || return 1
+    done &&
+    echo err >>.git/info/exclude
+'
+
+# Each case is: command, stack position (top patch) before the command, stack
+# position after successful completion, and a file touched by the transition.
+while read cmd before after touched; do
+    test_expect_success "$cmd --keep: overlapping unstaged changes abort" '
+        stg goto $before &&
+        echo local >>$touched &&
+        command_error stg $cmd --keep 2>err &&
+        grep -e "local changes to the following files would be overwritten" err &&
+        grep -e "^  $touched\$" err &&
+        test "$(stg top)" = "$before" &&
+        test "$(tail -n 1 $touched)" = "local" &&
+        git checkout -- $touched
+    '
+
+    test_expect_success "$cmd --keep: overlapping staged changes abort" '
+        stg goto $before &&
+        echo local >>$touched &&
+        git add $touched &&
+        command_error stg $cmd --keep 2>err &&
+        grep -e "^  $touched\$" err &&
+        test "$(stg top)" = "$before" &&
+        test "$(git diff --cached --name-only)" = "$touched" &&
+        git reset --hard
+    '
+
+    test_expect_success "$cmd --keep: non-overlapping changes are kept" '
+        stg goto $before &&
+        echo local >>other.txt &&
+        echo staged >>a.txt &&
+        git add a.txt &&
+        stg $cmd --keep &&
+        test "$(stg top)" = "$after" &&
+        test "$(tail -n 1 other.txt)" = "local" &&
+        test "$(git diff --cached --name-only)" = "a.txt" &&
+        git reset --hard
+    '
+
+    test_expect_success "$cmd without --keep requires a clean worktree" '
+        stg goto $before &&
+        echo local >>other.txt &&
+        command_error stg $cmd 2>err &&
+        grep -e "worktree not clean" err &&
+        test "$(stg top)" = "$before" &&
+        git reset --hard
+    '
+done <<EOT
+push pb pc c.txt
+pop pc pb c.txt
+goto\ pb pc pb c.txt
+EOT
+
+test_done
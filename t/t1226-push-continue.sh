@@ -0,0 +1,180 @@
+#!/bin/sh
+
+test_description='Test continuing and aborting a push halted by conflicts'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches conflicting with the base' '
+    printf "%s\n" out err expected >>.git/info/exclude &&
+    echo base >file.txt &&
+    echo base >other.txt &&
+    git add file.txt other.txt &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >file.txt &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo p2 >other.txt &&
+    stg refresh &&
+    stg new -m p3 &&
+    echo p3 >new.txt &&
+    stg add new.txt &&
+    stg refresh &&
+    stg pop -a &&
+    stg new -m base2 &&
+    echo base2 >file.txt &&
+    stg refresh
+'
+
+test_expect_success 'No push in progress' '
+    command_error stg push --continue 2>err &&
+    grep -e "no push in progress" err &&
+    command_error stg push --abort 2>err &&
+    grep -e "no push in progress" err
+'
+
+test_expect_success 'Halted push is recorded' '
+    conflict stg push -a &&
+    test "$(stg top)" = "p1" &&
+    git show refs/stgit/push/master >out &&
+    grep -e "^conflicted p1$" out &&
+    grep -e "^remaining p2$" out &&
+    grep -e "^remaining p3$" out
+'
+
+test_expect_success 'Refuse push, pop, and goto while push is in progress' '
+    for cmd in "push" "pop" "goto base2"
+    do
+        command_error stg $cmd 2>err &&
+        grep -e "run .stg refresh. or .stg push --continue., or run .stg undo --hard. or .stg push --abort." err ||
+        return 1
+    done &&
+    echo resolved >file.txt &&
+    stg add file.txt &&
+    for cmd in "push" "pop" "goto base2"
+    do
+        command_error stg $cmd 2>err &&
+        grep -e "push halted on conflicts in patch .p1. is in progress" err &&
+        grep -e "stg push --continue" err &&
+        grep -e "stg push --abort" err ||
+        return 1
+    done
+'
+
+test_expect_success 'Continue refuses unresolved conflicts' '
+    stg resolved --unmark file.txt &&
+    test -n "$(git ls-files -u file.txt)" &&
+    command_error stg push --continue 2>err &&
+    grep -e "unresolved conflicts remain" err &&
+    test "$(stg top)" = "p1"
+'
+
+test_expect_success 'Continue folds resolution and pushes remaining patches' '
+    echo resolved >file.txt &&
+    stg resolved file.txt &&
+    stg push --continue &&
+    test "$(echo $(stg series --applied --noprefix))" = "base2 p1 p2 p3" &&
+    test "$(git show $(stg id p1):file.txt)" = "resolved" &&
+    test "$(git log -1 --format=%s $(stg id p1))" = "p1" &&
+    test -z "$(git status --porcelain -uno)" &&
+    test -z "$(git ls-files -u)" &&
+    test_must_fail git rev-parse -q --verify refs/stgit/push/master
+'
+
+test_expect_success 'Continue after repeated conflicts' '
+    stg pop -a &&
+    stg new -m base3 &&
+    echo base3 >other.txt &&
+    stg refresh &&
+    conflict stg push p2 p1 p3 &&
+    test "$(stg top)" = "p2" &&
+    echo resolved-p2 >other.txt &&
+    stg resolved other.txt &&
+    conflict stg push --continue &&
+    test "$(stg top)" = "p1" &&
+    git show refs/stgit/push/master >out &&
+    grep -e "^conflicted p1$" out &&
+    grep -e "^remaining p3$" out &&
+    ! grep -e "^remaining p2$" out &&
+    echo resolved-p1 >file.txt &&
+    stg resolved file.txt &&
+    stg push --continue &&
+    test "$(echo $(stg series --applied --noprefix))" = "base3 p2 p1 p3" &&
+    test "$(git show $(stg id p2):other.txt)" = "resolved-p2"
+'
+
+test_expect_success 'Abort restores stack and worktree' '
+    stg pop -a &&
+    stg delete base3 &&
+    stg push base2 &&
+    stg series >expected &&
+    git rev-parse HEAD >expected-head &&
+    conflict stg push -a &&
+    echo garbage >file.txt &&
+    stg push --abort &&
+    stg series >out &&
+    test_cmp expected out &&
+    test "$(git rev-parse HEAD)" = "$(cat expected-head)" &&
+    test "$(cat file.txt)" = "base2" &&
+    test -z "$(git status --porcelain -uno)" &&
+    test -z "$(git ls-files -u)" &&
+    test_must_fail git rev-parse -q --verify refs/stgit/push/master &&
+    test_must_fail git rev-parse -q --verify refs/stgit/conflicts/master
+'
+
+test_expect_success 'Abort restores local changes kept by the push' '
+    echo local >>other.txt &&
+    conflict stg push --keep p1 p3 &&
+    git show refs/stgit/push/master >out &&
+    grep -e "^stash " out &&
+    stg push --abort &&
+    test "$(stg top)" = "base2" &&
+    test "$(git status --porcelain -uno)" = " M other.txt" &&
+    test "$(tail -n 1 other.txt)" = "local" &&
+    test "$(cat file.txt)" = "base2" &&
+    git checkout other.txt
+'
+
+test_expect_success 'Refresh ends the halted push' '
+    conflict stg push p1 p3 &&
+    echo resolved >file.txt &&
+    stg add file.txt &&
+    stg refresh &&
+    command_error stg push --continue 2>err &&
+    grep -e "no push in progress" err &&
+    stg push p3 &&
+    test "$(echo $(stg series --applied --noprefix))" = "base2 p1 p3"
+'
+
+test_expect_success 'Undo ends the halted push' '
+    stg pop p1 p3 &&
+    stg new -m p5 &&
+    echo p5 >file.txt &&
+    stg refresh &&
+    conflict stg push p1 &&
+    stg undo --hard &&
+    test "$(stg top)" = "p5" &&
+    command_error stg push --abort 2>err &&
+    grep -e "no push in progress" err
+'
+
+test_expect_success 'Continue only folds conflicted paths with kept local changes' '
+    echo local >>other.txt &&
+    conflict stg push --keep p1 &&
+    echo resolved >file.txt &&
+    stg resolved file.txt &&
+    stg push --continue &&
+    test "$(stg top)" = "p1" &&
+    test "$(git show $(stg id p1):file.txt)" = "resolved" &&
+    test "$(git diff --name-only $(stg id p1)^ $(stg id p1))" = "file.txt" &&
+    test "$(git status --porcelain -uno)" = " M other.txt"
+'
+
+test_expect_success 'Continue and abort conflict with patch selection' '
+    general_error stg push --continue p1 &&
+    general_error stg push --abort --all &&
+    general_error stg push --continue --abort
+'
+
+test_done
@@ -0,0 +1,119 @@
+#!/bin/sh
+
+test_description='Test labels and size of conflict markers from push merges'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches conflicting with the base' '
+    printf "%s\n" out err expected fold.diff >>.git/info/exclude &&
+    echo base >file.txt &&
+    git add file.txt &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >file.txt &&
+    stg refresh &&
+    git diff HEAD~ HEAD >fold.diff &&
+    stg pop &&
+    stg new -m base2 &&
+    echo base2 >file.txt &&
+    stg refresh
+'
+
+test_expect_success 'Conflict markers name the top and the patch' '
+    conflict stg push p1 &&
+    cat >expected <<-\EOF &&
+	<<<<<<< current top
+	base2
+	=======
+	p1
+	>>>>>>> patch p1
+	EOF
+    test_cmp expected file.txt &&
+    stg undo --hard
+'
+
+test_expect_success 'Diff3 conflict style names the ancestor commit' '
+    test_config merge.conflictStyle diff3 &&
+    conflict stg push p1 &&
+    cat >expected <<-EOF &&
+	<<<<<<< current top
+	base2
+	||||||| ancestor $(git rev-parse --short=7 $(stg id base2)^)
+	base
+	=======
+	p1
+	>>>>>>> patch p1
+	EOF
+    test_cmp expected file.txt &&
+    stg undo --hard
+'
+
+test_expect_success 'Marker size option' '
+    test_config merge.conflictStyle diff3 &&
+    conflict stg push --marker-size=10 p1 &&
+    cat >expected <<-EOF &&
+	<<<<<<<<<< current top
+	base2
+	|||||||||| ancestor $(git rev-parse --short=7 $(stg id base2)^)
+	base
+	==========
+	p1
+	>>>>>>>>>> patch p1
+	EOF
+    test_cmp expected file.txt &&
+    stg undo --hard
+'
+
+test_expect_success 'Marker size attribute takes precedence' '
+    test_when_finished "rm -f .git/info/attributes" &&
+    echo "file.txt conflict-marker-size=12" >.git/info/attributes &&
+    conflict stg push --marker-size=9 p1 &&
+    grep -e "^<<<<<<<<<<<< current top$" file.txt &&
+    stg undo --hard
+'
+
+test_expect_success 'Invalid marker size' '
+    general_error stg push --marker-size=3 p1 2>err &&
+    grep -e "marker size must be at least 7" err &&
+    general_error stg push --marker-size=x p1 2>err &&
+    grep -e "is not a positive integer" err
+'
+
+test_expect_success 'Pick conflict markers name the picked patch' '
+    stg pop &&
+    stg new -m base3 &&
+    echo base3 >file.txt &&
+    stg refresh &&
+    conflict stg pick --name=picked --marker-size=8 base2 &&
+    cat >expected <<-\EOF &&
+	<<<<<<<< current top
+	base3
+	========
+	base2
+	>>>>>>>> patch picked
+	EOF
+    test_cmp expected file.txt &&
+    stg undo --hard &&
+    stg delete base3 &&
+    stg push base2
+'
+
+test_expect_success 'Fold conflict markers name the folded diff' '
+    test_config merge.conflictStyle diff3 &&
+    conflict stg fold --threeway --marker-size=8 fold.diff &&
+    cat >expected <<-EOF &&
+	<<<<<<<< current top
+	base2
+	|||||||| ancestor $(git rev-parse --short=7 $(stg id base2)^)
+	base
+	========
+	p1
+	>>>>>>>> folded diff
+	EOF
+    test_cmp expected file.txt &&
+    git reset --hard &&
+    general_error stg fold --marker-size=8 fold.diff
+'
+
+test_done
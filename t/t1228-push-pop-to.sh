@@ -0,0 +1,75 @@
+#!/bin/sh
+
+test_description='Test stg pop --to and stg push --to'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    for i in 0 1 2 3 4 5; do
+        stg new p$i -m p$i || return 1
+    done
+'
+
+test_expect_success 'Pop to patch' '
+    stg pop --to p3 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p3" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4 p5"
+'
+
+test_expect_success 'Pop to topmost patch is a no-op' '
+    stg pop --to p3 2>err &&
+    grep -e "patch \`p3\` is already the topmost applied patch" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p3"
+'
+
+test_expect_success 'Pop to relative locator' '
+    stg pop --to p3~2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1"
+'
+
+test_expect_success 'Pop to unapplied patch' '
+    command_error stg pop --to p4 2>err &&
+    grep -e "patch \`p4\` is not applied" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1"
+'
+
+test_expect_success 'Pop to conflicts with other selections' '
+    general_error stg pop --to p0 -n 1 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg pop --to p0 --all 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg pop --to p0 p1 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Push to patch' '
+    stg push --to p3 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p3" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4 p5"
+'
+
+test_expect_success 'Push to topmost patch is a no-op' '
+    stg push --to p3 2>err &&
+    grep -e "patch \`p3\` is already the topmost applied patch" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p3"
+'
+
+test_expect_success 'Push to applied patch' '
+    command_error stg push --to p1 2>err &&
+    grep -e "patch \`p1\` is already applied" err
+'
+
+test_expect_success 'Push to conflicts with other selections' '
+    general_error stg push --to p5 -n 1 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg push --to p5 --reverse 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Push to last patch' '
+    stg push --to p5 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p3 p4 p5"
+'
+
+test_done
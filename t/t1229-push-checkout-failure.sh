@@ -0,0 +1,72 @@
+#!/bin/sh
+
+test_description='Test restoring the work tree after a failed checkout'
+
+. ./test-lib.sh
+
+# A smudge filter that fails for z is used to make the checkout fail midway. The
+# checkout removes d and then writes a, b, dir/f, and e before failing on z.
+test_expect_success 'Initialize the StGit repository' '
+    printf "%s\n" err expected status >>.git/info/exclude &&
+    for f in a b d z; do echo "$f 1" >$f || return 1; done &&
+    git add a b d z &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p1 &&
+    for f in a b z; do echo "$f 2" >$f || return 1; done &&
+    mkdir dir &&
+    echo new >dir/f &&
+    echo new >e &&
+    git add a b z dir/f e &&
+    git rm -q d &&
+    stg refresh --index &&
+    stg pop &&
+    write_script .git/smudge <<-\EOF &&
+	if test "$1" = z && test -f .git/fail-z; then
+	    test -f .git/fail-z-always || rm .git/fail-z
+	    exit 1
+	fi
+	cat
+	EOF
+    git config filter.fail.smudge ".git/smudge %f" &&
+    git config filter.fail.clean cat &&
+    git config filter.fail.required true &&
+    echo "* filter=fail" >.git/info/attributes
+'
+
+test_expect_success 'Work tree is restored after a failed push' '
+    touch .git/fail-z &&
+    command_error stg push 2>err &&
+    grep -e "smudge filter fail failed" err &&
+    grep -e "command aborted (all changes rolled back)" err &&
+    test_path_is_missing .git/fail-z &&
+    git status --porcelain >status &&
+    test_must_be_empty status &&
+    for f in a b d z; do echo "$f 1" >expected && test_cmp expected $f || return 1; done &&
+    test_path_is_missing e &&
+    test_path_is_missing dir &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1"
+'
+
+test_expect_success 'Push succeeds once the checkout succeeds' '
+    stg push &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1" &&
+    git status --porcelain >status &&
+    test_must_be_empty status &&
+    stg pop
+'
+
+test_expect_success 'Paths that cannot be restored are reported' '
+    touch .git/fail-z .git/fail-z-always &&
+    command_error stg push 2>err &&
+    grep -e "the checkout failed midway and these paths could not be restored:" err &&
+    grep -e "^  z$" err &&
+    git status --porcelain >status &&
+    echo " D z" >expected &&
+    test_cmp expected status &&
+    for f in a b d; do echo "$f 1" >expected && test_cmp expected $f || return 1; done &&
+    test_path_is_missing e &&
+    test_path_is_missing dir
+'
+
+test_done
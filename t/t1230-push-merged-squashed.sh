@@ -0,0 +1,104 @@
+#!/bin/sh
+
+test_description='Test detecting patches squashed upstream with --merged=aggressive'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches and upstream squashing them' '
+    test_commit base &&
+    stg init &&
+    echo a1 >a &&
+    stg add a &&
+    stg new -m p1 &&
+    stg refresh &&
+    echo a2 >>a &&
+    stg new -m p2 &&
+    stg refresh &&
+    echo a3 >>a &&
+    stg new -m p3 &&
+    stg refresh &&
+    git checkout -b upstream base &&
+    git show $(stg id master:p3):a >a &&
+    git add a &&
+    git commit -m "Squashed a1 through a3" &&
+    sed "s/^a/A/" a >a.tmp &&
+    mv a.tmp a &&
+    git commit -a -m "Capitalize a" &&
+    git checkout master &&
+    stg branch --clone rebase-test &&
+    stg branch master &&
+    stg rebase --nopush upstream
+'
+
+test_expect_success 'Exact detection does not find the squashed patches' '
+    conflict stg push --all --merged &&
+    stg undo --hard &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p2 p3"
+'
+
+test_expect_success 'Runs longer than stgit.mergedmaxrun are not found' '
+    test_config stgit.mergedmaxrun 2 &&
+    conflict stg push --all --merged=aggressive &&
+    stg undo --hard &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p2 p3"
+'
+
+test_expect_success 'Excluded patches break up runs' '
+    conflict stg push --all --merged=aggressive --not-merged p2 &&
+    stg undo --hard &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p2 p3"
+'
+
+test_expect_success 'Push patches squashed upstream' '
+    stg push --all --merged=aggressive 2>err &&
+    grep -e "Patches p1..p3 match squashed upstream commit $(git rev-parse upstream~)" err &&
+    grep -e "Found 3 patches merged upstream" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3" &&
+    test "$(stg series --empty | grep -c "^\*")" = "3" &&
+    test_cmp_rev upstream^{tree} HEAD^{tree}
+'
+
+test_expect_success 'Aggressive detection from stgit.mergeddetect' '
+    stg undo --hard &&
+    test_config stgit.mergeddetect aggressive &&
+    stg push --all --merged 2>err &&
+    grep -e "Patches p1..p3 match squashed upstream commit" err &&
+    test_cmp_rev upstream^{tree} HEAD^{tree}
+'
+
+test_expect_success 'Rebase onto upstream with squashed patches' '
+    stg branch rebase-test &&
+    stg rebase --merged=aggressive upstream 2>err &&
+    grep -e "Patches p1..p3 match squashed upstream commit $(git rev-parse upstream~)" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3" &&
+    test_cmp_rev upstream^{tree} HEAD^{tree}
+'
+
+test_expect_success 'Single patches are matched by patch id' '
+    stg branch master &&
+    stg pop --all &&
+    stg delete p1 p2 p3 &&
+    echo b1 >b &&
+    stg add b &&
+    stg new -m q1 &&
+    stg refresh &&
+    stg pop &&
+    git checkout -b upstream2 &&
+    echo b1 >b &&
+    git add b &&
+    git commit -m "Add b upstream" &&
+    echo B1 >b &&
+    git commit -a -m "Capitalize b" &&
+    git checkout master &&
+    stg rebase --nopush upstream2 &&
+    stg push --merged=aggressive 2>err &&
+    grep -e "Patch q1 matches the patch id of upstream commit $(git rev-parse upstream2~)" err &&
+    test_cmp_rev upstream2^{tree} HEAD^{tree}
+'
+
+test_expect_success 'Invalid merged detection mode' '
+    general_error stg push --merged=bogus 2>err &&
+    grep -e "\"exact\", \"subject\", or \"aggressive\"" err
+'
+
+test_done
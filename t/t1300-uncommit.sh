@@ -131,8 +131,38 @@ test_expect_success 'Uncommit to something that does not resolve to a commit' '
 '
 
 test_expect_success 'Uncommit to an annotated tag' '
-    git tag -a -m "Test tag" testtag HEAD^ &&
-    stg uncommit --to testtag
+    git tag -a -m "Test tag" testtag HEAD~3 &&
+    stg uncommit --to testtag &&
+    [ "$(stg id {base})" = "$(git rev-parse testtag^{commit}^)" ]
+'
+
+test_expect_success 'Uncommit with --inclusive overriding --exclusive' '
+    stg commit --all --allow-empty &&
+    stg uncommit --to HEAD~2 --exclusive --inclusive &&
+    [ "$(stg id {base})" = "$(git rev-parse HEAD~3)" ] &&
+    stg commit --all --allow-empty &&
+    stg uncommit --to HEAD~2 --inclusive -x &&
+    [ "$(stg id {base})" = "$(git rev-parse HEAD~2)" ] &&
+    stg commit --all --allow-empty
+'
+
+test_expect_success 'Uncommit to a commit that is not an ancestor' '
+    git checkout -q -b side HEAD~2 &&
+    test_commit --no-tag "Side commit" &&
+    git checkout -q master &&
+    command_error stg uncommit --to side 2>err &&
+    grep -e "error: \`side\` ($(git rev-parse --short side)) is not an ancestor of the stack base ($(git rev-parse --short HEAD))" err &&
+    [ "$(echo $(stg series))" = "" ]
+'
+
+test_expect_success 'Uncommit through a merge commit' '
+    git merge -q --no-ff -m "Merge side" side &&
+    test_commit --no-tag "After merge" &&
+    command_error stg uncommit --to HEAD~3 2>err &&
+    grep -e "error: cannot uncommit to \`HEAD~3\`: encountered merge commit $(git rev-parse --short HEAD^) \"Merge side\"" err &&
+    [ "$(echo $(stg series))" = "" ] &&
+    stg uncommit --to HEAD^ --exclusive &&
+    [ "$(echo $(stg series --applied --noprefix))" = "after-merge" ]
 '
 
 test_done
@@ -50,7 +50,7 @@ test_expect_success !WINDOWS 'Check underlying commit is ISO8859-1' '
 '
 
 test_expect_success 'Encoding after edit' '
-    stg edit --sign 2>"$HOME"/warnings.txt &&
+    stg -q edit --sign 2>"$HOME"/warnings.txt &&
     test_must_be_empty "$HOME"/warnings.txt &&
     git cat-file -p HEAD | grep -e "ÄËÑÏÖ" &&
     git cat-file -p HEAD | grep -e "Ábçdèfg" &&
@@ -0,0 +1,140 @@
+#!/bin/sh
+
+test_description='Test resolving generated patch names colliding with hidden patches'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack with hidden patches' '
+    test_commit base &&
+    stg init &&
+    stg new -m "foo" foo &&
+    stg new -m "bar again" bar-1 &&
+    stg pop -a &&
+    stg hide foo bar-1 &&
+    test_commit --no-tag foo file-foo &&
+    test "$(echo $(stg series --hidden --noprefix))" = "foo bar-1" &&
+    printf "%s\n" out err ps >>.git/info/exclude
+'
+
+test_expect_success 'Suffix by default' '
+    stg uncommit &&
+    test "$(echo $(stg series --noprefix))" = "foo-1" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "foo bar-1" &&
+    stg undo
+'
+
+test_expect_success 'Error on collision with hidden patch' '
+    test_config stgit.name.collision error &&
+    command_error stg uncommit 2>err &&
+    grep -e "patch name \`foo\` collides with hidden patch \`foo\`" err &&
+    test "$(echo $(stg series --noprefix))" = "" &&
+    test "$(git log -1 --format=%s)" = "foo"
+'
+
+test_expect_success 'Replace hidden patch' '
+    stg uncommit --name-collision=replace-hidden &&
+    test "$(echo $(stg series --noprefix))" = "foo" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "foo.old bar-1" &&
+    test "$(stg show -O --format=%s -O --no-patch foo.old)" = "foo" &&
+    test "$(stg id foo)" = "$(git rev-parse HEAD)"
+'
+
+test_expect_success 'Replacing hidden patch is undoable' '
+    stg undo &&
+    test "$(echo $(stg series --noprefix))" = "" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "foo bar-1"
+'
+
+test_expect_success 'Option overrides config' '
+    test_config stgit.name.collision replace-hidden &&
+    stg uncommit --name-collision=suffix &&
+    test "$(echo $(stg series --noprefix))" = "foo-1" &&
+    stg undo
+'
+
+test_expect_success 'Invalid config value' '
+    test_config stgit.name.collision bogus &&
+    command_error stg uncommit 2>err &&
+    grep -e "invalid name collision mode \`bogus\`" err
+'
+
+test_expect_success 'Collisions with visible patches are always suffixed' '
+    git commit --allow-empty -m "baz" &&
+    stg new -m "baz" baz &&
+    stg uncommit --name-collision=error &&
+    test "$(echo $(stg series --noprefix))" = "baz-1 baz" &&
+    stg undo &&
+    stg delete baz &&
+    git reset --hard HEAD^
+'
+
+test_expect_success 'Error names hidden patch reached by suffixing' '
+    git commit --allow-empty -m "bar" &&
+    stg new -m "bar" bar &&
+    command_error stg uncommit --name-collision=error 2>err &&
+    grep -e "patch name \`bar-1\` collides with hidden patch \`bar-1\`" err &&
+    test "$(echo $(stg series --noprefix))" = "bar"
+'
+
+test_expect_success 'Replace hidden patch reached by suffixing' '
+    stg uncommit --name-collision=replace-hidden &&
+    test "$(echo $(stg series --noprefix))" = "bar-1 bar" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "foo bar-1.old" &&
+    test "$(stg show -O --format=%s -O --no-patch bar-1.old)" = "bar again" &&
+    stg undo &&
+    stg delete bar &&
+    git reset --hard HEAD^
+'
+
+test_expect_success 'Replace hidden patches when uncommitting several commits' '
+    stg new -m "foo old" foo.old &&
+    stg pop &&
+    stg hide foo.old &&
+    git commit --allow-empty -m "foo" &&
+    git commit --allow-empty -m "foo" &&
+    stg uncommit -n 3 --name-collision=replace-hidden &&
+    test "$(echo $(stg series --noprefix))" = "foo foo-1 foo-2" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "foo.old foo.old-1 bar-1" &&
+    test "$(stg show -O --format=%s -O --no-patch foo.old-1)" = "foo" &&
+    test "$(stg show -O --format=%s -O --no-patch foo.old)" = "foo old" &&
+    stg undo
+'
+
+test_expect_success 'Error when uncommitting several commits' '
+    command_error stg uncommit -n 3 --name-collision=error 2>err &&
+    grep -e "patch name \`foo\` collides with hidden patch \`foo\`" err &&
+    test "$(echo $(stg series --noprefix))" = ""
+'
+
+test_expect_success 'Setup patch files to import' '
+    git format-patch -3 -o ps &&
+    git reset --hard HEAD~3 &&
+    stg repair
+'
+
+test_expect_success 'Import suffixes by default' '
+    stg import --stripname ps/0001-foo.patch &&
+    test "$(echo $(stg series --noprefix))" = "foo-1" &&
+    stg undo --hard
+'
+
+test_expect_success 'Import errors on collision with hidden patch' '
+    command_error stg import --stripname --name-collision=error ps/0001-foo.patch 2>err &&
+    grep -e "patch name \`foo\` collides with hidden patch \`foo\`" err &&
+    test "$(echo $(stg series --noprefix))" = ""
+'
+
+test_expect_success 'Import replaces hidden patch' '
+    test_config stgit.name.collision replace-hidden &&
+    stg import --stripname ps/0001-foo.patch &&
+    test "$(echo $(stg series --noprefix))" = "foo" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "foo.old foo.old-1 bar-1" &&
+    stg undo --hard
+'
+
+test_expect_success 'Import name collision conflicts with replace' '
+    general_error stg import --replace --name-collision=error ps/0001-foo.patch 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
@@ -0,0 +1,56 @@
+#!/bin/sh
+
+test_description='Test stacks with patch commits missing from the repository'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack with a missing patch commit' '
+    test_commit base &&
+    stg init &&
+    stg new -m p1 &&
+    stg new -m p2 &&
+    echo p2 >p2.txt &&
+    stg add p2.txt &&
+    stg refresh &&
+    stg new -m p3 &&
+    echo p3 >p3.txt &&
+    stg add p3.txt &&
+    stg refresh &&
+    stg pop p3 &&
+    p3_id=$(stg id p3) &&
+    echo $p3_id >p3-id &&
+    rm .git/objects/$(echo $p3_id | cut -c1-2)/$(echo $p3_id | cut -c3-) &&
+    test_must_fail git cat-file -e $p3_id
+'
+
+test_expect_success 'Series lists the remaining patches with a warning' '
+    stg series 2>err >series &&
+    cat >expected <<-\EOF &&
+	+ p1
+	> p2
+	EOF
+    test_cmp expected series &&
+    grep -e "warning: patch \`p3\` is not listed: its commit \`$(cat p3-id)\` is missing" err
+'
+
+test_expect_success 'Show fails with a clean error' '
+    command_error stg show p1 2>err &&
+    grep -e "error: commit \`$(cat p3-id)\` of patch \`p3\` is missing from the repository" err &&
+    grep -e "stg repair" err
+'
+
+test_expect_success 'Push fails with a clean error' '
+    command_error stg push 2>err &&
+    grep -e "error: commit \`$(cat p3-id)\` of patch \`p3\` is missing from the repository" err &&
+    test_cmp_rev refs/patches/master/p2 HEAD
+'
+
+test_expect_success 'Repair removes the patch with the missing commit' '
+    stg repair 2>err &&
+    grep -e "warning: removing patch \`p3\`" err &&
+    test "$(echo $(stg series --noprefix --all))" = "p1 p2" &&
+    stg show p1 &&
+    test_must_fail git rev-parse --verify -q refs/patches/master/p3
+'
+
+test_done
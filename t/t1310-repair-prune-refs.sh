@@ -0,0 +1,114 @@
+#!/bin/sh
+
+test_description='Test pruning stale patch refs with stg repair --prune-refs'
+
+. ./test-lib.sh
+
+# Check that the patch refs are exactly those of the patches of each stack.
+check_patch_refs () {
+    for branch in $(git for-each-ref --format="%(refname:lstrip=2)" refs/stacks); do
+        for patch in $(stg series --branch "$branch" --all --noprefix); do
+            echo "refs/patches/$branch/$patch"
+        done
+    done | sort >expected-refs &&
+    git for-each-ref --format="%(refname)" refs/patches | sort >refs &&
+    test_cmp expected-refs refs
+}
+
+test_expect_success 'Initialize stacks on two branches' '
+    test_commit base &&
+    stg init &&
+    for p in p1 p2 p3 p4 p5; do
+        stg new -m $p && echo $p >$p.txt && stg add $p.txt && stg refresh || return 1
+    done &&
+    stg branch --create side &&
+    stg new -m s1 &&
+    stg new -m s2 &&
+    stg branch master
+'
+
+test_expect_success 'Deleting and renaming patches leaves no stale refs' '
+    stg delete p5 &&
+    stg rename p4 p4-renamed &&
+    stg squash -n p23 -m p23 p2 p3 &&
+    stg commit p1 &&
+    stg pop p4-renamed &&
+    stg hide p4-renamed &&
+    check_patch_refs &&
+    stg repair --prune-refs --all-branches --dry-run >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Renaming and deleting branches leaves no stale refs' '
+    stg branch --clone clone &&
+    stg branch --rename clone clone2 &&
+    stg branch master &&
+    stg branch --delete --force clone2 &&
+    check_patch_refs &&
+    test -z "$(git for-each-ref refs/patches/clone refs/patches/clone2)"
+'
+
+test_expect_success 'Setup stale patch refs' '
+    stg new -m tmp &&
+    tmp_id=$(stg id tmp) &&
+    stg delete tmp &&
+    git update-ref refs/patches/gone/tmp $tmp_id &&
+    git update-ref refs/patches/gone/p23 $(stg id p23) &&
+    git update-ref refs/patches/master/x/y $(stg id p23) &&
+    unlogged_id=$(git commit-tree -p HEAD -m unlogged HEAD^{tree}) &&
+    git update-ref refs/patches/gone/unlogged $unlogged_id
+'
+
+test_expect_success 'Dry run lists the stale refs' '
+    stg repair --prune-refs --all-branches --dry-run >out 2>err &&
+    cat >expected <<-\EOF &&
+	refs/patches/gone/p23
+	refs/patches/gone/tmp
+	refs/patches/master/x/y
+	EOF
+    test_cmp expected out &&
+    grep -e "keeping .refs/patches/gone/unlogged.: its commit .* is not reachable" err &&
+    git rev-parse --verify refs/patches/gone/tmp
+'
+
+test_expect_success 'Pruning the current branch leaves other branches alone' '
+    stg repair --prune-refs --dry-run >out &&
+    test_must_be_empty out &&
+    git checkout -q -b gone &&
+    stg repair --prune-refs --dry-run >out &&
+    cat >expected <<-\EOF &&
+	refs/patches/gone/p23
+	refs/patches/gone/tmp
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Refs newer than the expiry date are kept' '
+    stg repair --prune-refs --expire="@1000000000 +0000" &&
+    git rev-parse --verify refs/patches/gone/tmp &&
+    test_config stgit.repair.pruneexpire "@1000000000 +0000" &&
+    stg repair --prune-refs &&
+    git rev-parse --verify refs/patches/gone/tmp &&
+    stg repair --prune-refs --expire=now &&
+    test_must_fail git rev-parse --verify refs/patches/gone/tmp
+'
+
+test_expect_success 'Prune stale refs of all branches' '
+    git checkout -q master &&
+    stg repair --prune-refs --all-branches 2>err &&
+    grep -e "Pruning .refs/patches/master/x/y." err &&
+    grep -e "keeping .refs/patches/gone/unlogged." err &&
+    test "$(git for-each-ref --format="%(refname)" refs/patches/gone)" = \
+        "refs/patches/gone/unlogged" &&
+    git update-ref -d refs/patches/gone/unlogged &&
+    check_patch_refs
+'
+
+test_expect_success 'Prune options require --prune-refs' '
+    general_error stg repair --all-branches 2>err &&
+    grep -e "--prune-refs" err &&
+    general_error stg repair --dry-run &&
+    general_error stg repair --expire=now
+'
+
+test_done
@@ -97,7 +97,73 @@ test_expect_success 'Attempt float with no series file and no arguments' '
 test_expect_success 'Series with bogus patch name' '
     printf "p1\np2\np3\nBOGUS\np4\np5\np6\np7\n" |
     command_error stg float --series=- 2>&1 |
-    grep -e "error: <stdin>: patch \`BOGUS\` does not exist"
+    grep -e "error: <stdin>:4: patch \`BOGUS\` does not exist"
+'
+
+test_expect_success 'Float with ranges, comments, and blank lines in series' '
+    cat >range-series.txt <<-\EOF &&
+	# Bottom patches first
+
+	p5..p7  # the rest
+	!p6
+	p1..p3
+	p6
+	EOF
+    stg float -S range-series.txt &&
+    test "$(echo $(stg series --applied --noprefix))" = "p4 p5 p7 p1 p2 p3 p6"
+'
+
+test_expect_success 'All series errors are reported before floating' '
+    cat >bad-series.txt <<-\EOF &&
+	p1..p3
+	BOGUS
+	p2
+	a..b..c
+	!p4
+	EOF
+    command_error stg float -S bad-series.txt 2>err &&
+    cat >expected <<-\EOF &&
+	error: 4 errors in series `bad-series.txt`:
+	  bad-series.txt:2: patch `BOGUS` does not exist
+	  bad-series.txt:3: patch `p2` is already listed on line 1
+	  bad-series.txt:4: invalid patch range `a..b..c`
+	  bad-series.txt:5: excluded patch `p4` is not listed on a preceding line
+	EOF
+    test_cmp expected err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p4 p5 p7 p1 p2 p3 p6"
+'
+
+test_expect_success 'Sort applied patches by name' '
+    stg float --sort=name 2>err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4 p5 p6 p7" &&
+    grep -e "order before: p4 p5 p7 p1 p2 p3 p6" err &&
+    grep -e "order after: p1 p2 p3 p4 p5 p6 p7" err
+'
+
+test_expect_success 'Sort by creation keeps order of patches created together' '
+    stg float --sort=created p6 p2 p4 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p5 p7 p6 p2 p4"
+'
+
+test_expect_success 'Sort unapplied patches with --noapply' '
+    stg new -m zz-new &&
+    stg new -m aa-new &&
+    stg float --noapply p5 aa-new p2 zz-new &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p7 p6 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 aa-new p2 zz-new" &&
+    stg float --sort=created --noapply 2>err &&
+    grep -e "order after: p5 p2 zz-new aa-new" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p7 p6 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 p2 zz-new aa-new" &&
+    stg float --sort=name --noapply &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "aa-new p2 p5 zz-new"
+'
+
+test_expect_success 'Sort is a single undoable operation' '
+    stg float --sort=name &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p4 p6 p7" &&
+    stg undo &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p7 p6 p4"
 '
 
 test_done
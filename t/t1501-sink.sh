@@ -43,9 +43,11 @@ test_expect_success 'sink and reorder specified without applied patches' '
     test "$(echo $(stg series --applied --noprefix))" = "p2 p1"
 '
 
-test_expect_success 'attempt sink below unapplied' '
-    command_error stg sink --to=p4 2>&1 |
-    grep -e "cannot sink below \`p4\` since it is not applied"
+test_expect_success 'sink below unapplied' '
+    stg sink --to=p4 p22 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p2 p1 p3 p22" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4" &&
+    stg undo
 '
 
 test_expect_success 'sink patches to the bottom of the stack' '
@@ -26,11 +26,11 @@ test_expect_success 'Float a patch, causing a conflict with the next patch' '
     test "$(stg status)" = "UU foo.txt" &&
     cat >expected.txt <<-\EOF &&
 	first line
-	<<<<<<< current
+	<<<<<<< current top
 	=======
 	foo
 	foo2
-	>>>>>>> patched
+	>>>>>>> patch p2
 	EOF
     test_cmp foo.txt expected.txt
 '
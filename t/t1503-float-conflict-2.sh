@@ -30,11 +30,11 @@ test_expect_success 'Float a patch, causing a conflict two patches down' '
     test "$(stg status)" = "UU foo.txt" &&
     cat >expected.txt <<-\EOF &&
 	first line
-	<<<<<<< current
+	<<<<<<< current top
 	=======
 	foo
 	foo2
-	>>>>>>> patched
+	>>>>>>> patch p3
 	EOF
     test_cmp foo.txt expected.txt
 '
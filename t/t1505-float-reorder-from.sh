@@ -0,0 +1,78 @@
+#!/bin/sh
+
+test_description='Test saving and restoring the patch order'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 6 &&
+    stg uncommit -n 6 &&
+    stg goto p4 &&
+    stg hide p6
+'
+
+test_expect_success 'Save the patch order' '
+    stg series --save-order order.txt &&
+    grep -v -e "^#" order.txt >names &&
+    cat >expected <<-\EOF &&
+	p1
+	p2
+	p3
+	p4
+	p5
+	EOF
+    test_cmp expected names
+'
+
+test_expect_success 'Reorder from file keeping the number of applied patches' '
+    cat >order.txt <<-\EOF &&
+	# comments and blank lines are ignored
+
+	p5
+	p3  # trailing comment
+	p1
+	EOF
+    stg float --reorder-from order.txt &&
+    test "$(echo $(stg series --applied --noprefix))" = "p5 p3 p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p6"
+'
+
+test_expect_success 'Round trip through saved order' '
+    stg float --reorder-from order.txt >out &&
+    stg series --save-order saved.txt &&
+    stg float --reorder-from saved.txt &&
+    test "$(echo $(stg series --noprefix))" = "p5 p3 p1 p2 p4"
+'
+
+test_expect_success 'Reorder from stdin' '
+    printf "p1\np2\np3\np4\np5\n" | stg float --reorder-from - &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5"
+'
+
+test_expect_success 'Reject unknown patch name' '
+    printf "p1\nBOGUS\n" >order.txt &&
+    command_error stg float --reorder-from order.txt 2>err &&
+    grep -e "error: order.txt:2: patch \`BOGUS\` does not exist" err
+'
+
+test_expect_success 'Reject hidden patch name' '
+    printf "p6\n" >order.txt &&
+    command_error stg float --reorder-from order.txt 2>err &&
+    grep -e "error: order.txt:1: patch \`p6\` is hidden" err
+'
+
+test_expect_success 'Reject repeated patch name' '
+    printf "p2\np1\np2\n" >order.txt &&
+    command_error stg float --reorder-from order.txt 2>err &&
+    grep -e "error: order.txt:3: patch \`p2\` is listed more than once" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Reject reorder with patch arguments' '
+    general_error stg float --reorder-from order.txt p1 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
@@ -0,0 +1,119 @@
+#!/bin/sh
+
+test_description='Test float and sink relative to a target patch'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 6 &&
+    stg uncommit -n 6 &&
+    echo err >>.git/info/exclude &&
+    stg goto p4 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 p6"
+'
+
+test_expect_success 'Float patch above applied target' '
+    stg float --above p1 p3 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p2 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 p6"
+'
+
+test_expect_success 'Float patch range below applied target keeping order' '
+    stg float --below p1 p3..p2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p3 p2 p1 p4" &&
+    stg undo &&
+    stg undo
+'
+
+test_expect_success 'Target may be any patch locator' '
+    stg float --above @~3 p4 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p4 p2 p3" &&
+    stg undo
+'
+
+test_expect_success 'Float applied patch above unapplied target' '
+    stg float --above p5 p2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p4 p5 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p6" &&
+    stg undo
+'
+
+test_expect_success 'Float unapplied patch below applied target' '
+    stg float --below p2 p6 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p6 p2 p3 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5" &&
+    stg undo
+'
+
+test_expect_success 'Float --noapply above unapplied target' '
+    stg float --noapply --above p6 p2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p3 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 p6 p2" &&
+    stg undo
+'
+
+test_expect_success 'Float --noapply below applied target pops patches' '
+    stg float --noapply --below p3 p6 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p6 p3 p4 p5" &&
+    stg undo
+'
+
+test_expect_success 'Sink patch above unapplied target' '
+    stg sink --above p5 p1 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p2 p3 p4 p5 p1" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p6" &&
+    stg undo
+'
+
+test_expect_success 'Sink --nopush below unapplied target' '
+    stg sink --nopush --below p6 p1 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p2 p3 p4 p5 p1" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p6" &&
+    stg undo
+'
+
+test_expect_success 'Relative placement is a single undo step' '
+    stg sink --below p6 p2 p1 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p3 p4 p5 p2 p1" &&
+    stg undo &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5 p6"
+'
+
+test_expect_success 'Target may not be moved' '
+    command_error stg float --above p2 p1 p2 2>err &&
+    grep -e "target patch \`p2\` may not also be a patch to float" err &&
+    command_error stg sink --below p5 p5 2>err &&
+    grep -e "target patch \`p5\` may not also be a patch to sink" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Target may not be hidden' '
+    stg hide p6 &&
+    command_error stg float --above p6 p1 2>err &&
+    grep -e "cannot float above \`p6\` since it is hidden" err &&
+    stg unhide p6
+'
+
+test_expect_success 'Setup conflicting patches' '
+    stg goto p4 &&
+    stg new -m c1 c1 &&
+    echo c1 >conflict.txt &&
+    stg add conflict.txt &&
+    stg refresh &&
+    stg new -m c2 c2 &&
+    echo c2 >conflict.txt &&
+    stg refresh &&
+    stg pop c1 c2
+'
+
+test_expect_success 'Float with conflicting push' '
+    conflict stg float --below c1 c2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4 c2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "c1 p5 p6" &&
+    test "$(echo $(stg status))" = "DU conflict.txt"
+'
+
+test_done
@@ -0,0 +1,126 @@
+#!/bin/sh
+
+test_description='Test saving and restoring the stack layout'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 6 &&
+    stg uncommit -n 6 &&
+    stg goto p3 &&
+    stg hide p6
+'
+
+test_expect_success 'Save the stack layout' '
+    stg series --save layout.txt >out &&
+    test_must_be_empty out &&
+    grep -e "^# Stack layout for branch .master." layout.txt &&
+    grep -v -e "^#" layout.txt >entries &&
+    cat >expected <<-\EOF &&
+	+ p1
+	+ p2
+	> p3
+	- p4
+	- p5
+	! p6
+	EOF
+    test_cmp expected entries
+'
+
+test_expect_success 'Rearrange the stack' '
+    stg float p5 &&
+    stg pop -a &&
+    stg hide p1 &&
+    stg unhide p6 &&
+    stg push p6 p4 &&
+    test "$(echo $(stg series --all))" = "+ p6 > p4 - p2 - p3 - p5 ! p1"
+'
+
+test_expect_success 'Restore the saved layout' '
+    stg float --restore layout.txt &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4 p5" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p6" &&
+    test "$(stg top)" = "p3"
+'
+
+test_expect_success 'Restore an edited layout' '
+    cat >layout.txt <<-\EOF &&
+	# comments and blank lines are ignored
+
+	+ p4
+	> p2  # trailing comment
+	- p1
+	! p3
+	- p6
+	! p5
+	EOF
+    stg float --restore layout.txt &&
+    test "$(echo $(stg series --applied --noprefix))" = "p4 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p6" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p3 p5"
+'
+
+test_expect_success 'Round trip through saved layout' '
+    stg series --save saved.txt &&
+    stg float --restore saved.txt 2>err &&
+    ! grep -e "warning" err &&
+    test "$(echo $(stg series --all))" = "+ p4 > p2 - p1 - p6 ! p3 ! p5"
+'
+
+test_expect_success 'Restore layout from stdin' '
+    printf "+ p1\n+ p2\n> p3\n- p4\n- p5\n! p6\n" | stg float --restore - &&
+    test "$(echo $(stg series --all))" = "+ p1 + p2 > p3 - p4 - p5 ! p6"
+'
+
+test_expect_success 'Report patches missing from the layout' '
+    printf "+ p3\n- p1\n" >layout.txt &&
+    stg float --restore layout.txt 2>err &&
+    grep -e "warning: patches missing from the layout are kept at the end: p2, p4, p5, p6" err &&
+    test "$(echo $(stg series --all))" = "+ p3 > p2 - p1 - p4 - p5 ! p6"
+'
+
+test_expect_success 'Reject unknown patch name before changing anything' '
+    printf "+ p1\n- BOGUS\n! p2\n" >layout.txt &&
+    command_error stg float --restore layout.txt 2>err &&
+    grep -e "error: layout.txt:2: patch .BOGUS. does not exist" err &&
+    test "$(echo $(stg series --all))" = "+ p3 > p2 - p1 - p4 - p5 ! p6"
+'
+
+test_expect_success 'Reject repeated patch name' '
+    printf "+ p1\n- p2\n! p1\n" >layout.txt &&
+    command_error stg float --restore layout.txt 2>err &&
+    grep -e "error: layout.txt:3: patch .p1. is listed more than once" err
+'
+
+test_expect_success 'Reject invalid state marker' '
+    printf "+ p1\np2\n" >layout.txt &&
+    command_error stg float --restore layout.txt 2>err &&
+    grep -e "error: layout.txt:2: invalid patch state marker .p." err &&
+    test "$(echo $(stg series --all))" = "+ p3 > p2 - p1 - p4 - p5 ! p6"
+'
+
+test_expect_success 'Dry run restore' '
+    stg series --save layout.txt &&
+    stg float --restore layout.txt --dry-run >out 2>&1 &&
+    grep -e "Dry run" out &&
+    printf "+ p1\n" | stg float --restore - --dry-run &&
+    test "$(echo $(stg series --all))" = "+ p3 > p2 - p1 - p4 - p5 ! p6"
+'
+
+test_expect_success 'Refuse restore with dirty worktree' '
+    echo dirty >>2.t &&
+    printf "> p1\n" >layout.txt &&
+    command_error stg float --restore layout.txt 2>err &&
+    grep -e "worktree not clean" err &&
+    git checkout 2.t
+'
+
+test_expect_success 'Reject restore with patch arguments' '
+    general_error stg float --restore layout.txt p1 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg series --save layout.txt --save-order order.txt 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
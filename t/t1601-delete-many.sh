@@ -70,6 +70,16 @@ test_expect_success 'Delete --all' '
     stg undo --hard
 '
 
+test_expect_success 'Delete with only exclusions is refused' '
+    [ "$(echo $(stg series --all --noprefix))" = "p0 p1 p2 p5 p8 p9" ] &&
+    command_error stg delete "!p1" 2>err &&
+    grep -e "exclusions, e.g. .!<patch>., must be combined with patches to select" err &&
+    [ "$(echo $(stg series --all --noprefix))" = "p0 p1 p2 p5 p8 p9" ] &&
+    stg delete p5..p9 "!p8" &&
+    [ "$(echo $(stg series --all --noprefix))" = "p0 p1 p2 p8" ] &&
+    stg undo --hard
+'
+
 test_expect_success 'Delete leading to conflict when re-pushing' '
     echo "stuff" >foo.txt &&
     stg new -m p-stuff &&
@@ -0,0 +1,77 @@
+#!/bin/sh
+
+test_description='Test backing up patches deleted with "stg delete"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    for i in 0 1 2 3; do
+        stg new p$i -m "patch $i" &&
+        echo "line $i" >>foo.txt &&
+        stg add foo.txt &&
+        stg refresh || return 1
+    done &&
+    printf "\\000\\001\\002binary" >bin.dat &&
+    cp bin.dat bin.orig &&
+    stg add bin.dat &&
+    stg new -rm "binary patch" binpatch
+'
+
+test_expect_success 'Delete without backup by default' '
+    stg delete p3 2>err &&
+    ! grep -e "backed up" err &&
+    test_path_is_missing .git/stgit-backups
+'
+
+test_expect_success 'Backup to given directory' '
+    stg delete --backup=saved binpatch 2>err &&
+    grep -e "deleted patches backed up to \`saved\`" err &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2" &&
+    test_path_is_file saved/binpatch &&
+    grep -e "^GIT binary patch" saved/binpatch &&
+    cat >expected <<-EOF &&
+	# This series applies on Git commit $(stg id p2)
+	binpatch
+	EOF
+    test_cmp expected saved/series
+'
+
+test_expect_success 'Restore backed up binary patch' '
+    test_path_is_missing bin.dat &&
+    stg import --series saved/series &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2 binpatch" &&
+    test_cmp_bin bin.orig bin.dat
+'
+
+test_expect_success 'Backup to default directory' '
+    stg delete --backup p1 p2 binpatch 2>err &&
+    grep -e "deleted patches backed up to \`.*stgit-backups/" err &&
+    ls .git/stgit-backups >backups &&
+    test_line_count = 1 backups &&
+    backup_dir=.git/stgit-backups/$(ls .git/stgit-backups) &&
+    test_path_is_file "$backup_dir/p1" &&
+    test_path_is_file "$backup_dir/p2" &&
+    test "$(echo $(grep -v "^#" "$backup_dir/series"))" = "p1 p2 binpatch" &&
+    test "$(head -n 1 "$backup_dir/p1")" = "patch 1"
+'
+
+test_expect_success 'Backup enabled by config' '
+    stg import --series "$(ls -d .git/stgit-backups/*)/series" &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2 binpatch" &&
+    test_config stgit.delete.backup true &&
+    stg delete binpatch 2>err &&
+    grep -e "deleted patches backed up to" err &&
+    ls .git/stgit-backups >backups &&
+    test_line_count = 2 backups
+'
+
+test_expect_success 'Backup config overridden by --no-backup' '
+    test_config stgit.delete.backup true &&
+    stg delete --no-backup p2 2>err &&
+    ! grep -e "backed up" err &&
+    ls .git/stgit-backups >backups &&
+    test_line_count = 2 backups
+'
+
+test_done
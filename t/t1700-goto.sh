@@ -57,7 +57,7 @@ test_expect_success 'Goto by partial sha1' '
 
 test_expect_success 'Refuse to go to a hidden patch' '
     stg new h0 -m "hidden patch" &&
-    stg hide h0 &&
+    stg hide --pop h0 &&
     command_error stg goto h0 2>err &&
     grep -e "hidden patch \`h0\` is not allowed" err &&
     test "$(echo $(stg series --hidden --noprefix))" = "h0" &&
@@ -75,7 +75,40 @@ test_expect_success 'Goto with ambiguous patch substring' '
     command_error stg goto q1 2>err &&
     grep "error: patch \`q1\` does not exist" err &&
     command_error stg goto p 2>err &&
-    grep "patch \`p\` does not exist, but is similar to \`p1\`, \`p2\`" err
+    grep "ambiguous patch name \`p\`; did you mean \`p1\`, \`p2\`" err
+'
+
+test_expect_success 'Goto patch modifying path' '
+    stg goto --path file2 &&
+    test "$(echo $(stg top))" = "p2" &&
+    stg goto --path file4 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Goto path with no modifying patch' '
+    command_error stg goto --path no-such-file 2>err &&
+    grep -e "no patches modify the given paths" err
+'
+
+test_expect_success 'Goto path with multiple modifying patches' '
+    command_error stg goto --path file1 --path file5 2>err &&
+    grep -e "multiple patches modify the given paths" err &&
+    grep -e "^  p1$" err &&
+    grep -e "^  p5$" err &&
+    test "$(echo $(stg top))" = "p4" &&
+    stg goto --path file1 --path file5 --last &&
+    test "$(echo $(stg top))" = "p5" &&
+    stg goto --path file1 --path file5 --first &&
+    test "$(echo $(stg top))" = "p1"
+'
+
+test_expect_success 'Goto path options conflict with patch' '
+    general_error stg goto --path file1 p2 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg goto --first p2 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg goto --last 2>err &&
+    grep -e "required arguments were not provided" err
 '
 
 test_done
@@ -376,6 +376,64 @@ test_expect_success 'Import patch that does not apply cleanly with --reject' '
     stg delete --top
 '
 
+test_expect_success 'Setup fuzzy patch' '
+    stg new -m numbers &&
+    test_write_lines 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 >numbers.txt &&
+    stg add numbers.txt &&
+    stg refresh &&
+    sed -e "s/^10$/ten/" numbers.txt >numbers.new &&
+    mv numbers.new numbers.txt &&
+    git diff >fuzzy.diff &&
+    test_write_lines 0 00 1 2 3 4 5 6 7 eight 9 10 11 12 13 14 15 16 17 18 19 20 >numbers.txt &&
+    stg refresh
+'
+
+test_expect_success 'Import patch failing without fuzz reports hunk' '
+    command_error stg import -n fuzzy fuzzy.diff 2>err &&
+    grep -e "while searching for:" err &&
+    grep -e "patch failed: numbers.txt:7" err &&
+    ! grep -e "Checking patch" err &&
+    test "$(echo $(stg top))" = "numbers"
+'
+
+test_expect_success 'Import patch with fuzz' '
+    stg import -n fuzzy --fuzz 2 fuzzy.diff 2>err &&
+    grep -e "info: patch .fuzzy. applied with adjustments:" err &&
+    grep -e "numbers.txt: Context reduced to (1/1) to apply fragment at 11" err &&
+    grep -e "^ten$" numbers.txt &&
+    stg delete --top
+'
+
+test_expect_success 'Import patch with offset' '
+    test_write_lines 0 00 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 >numbers.txt &&
+    stg refresh &&
+    stg import -n offset fuzzy.diff 2>err &&
+    grep -e "numbers.txt: Hunk #1 succeeded at 9 (offset 2 lines)." err &&
+    stg delete --top
+'
+
+test_expect_success 'Import patch ignoring whitespace' '
+    stg delete --top &&
+    stg new -m spaces &&
+    test_write_lines "a b" "c d" "e f" >spaces.txt &&
+    stg add spaces.txt &&
+    stg refresh &&
+    sed -e "s/^c d$/changed/" spaces.txt >spaces.new &&
+    mv spaces.new spaces.txt &&
+    git diff >spaces.diff &&
+    test_write_lines "a   b" "c d" "e	f" >spaces.txt &&
+    stg refresh &&
+    command_error stg import -n ws spaces.diff &&
+    stg import -n ws --ignore-whitespace spaces.diff &&
+    grep -e "^changed$" spaces.txt &&
+    stg delete ws spaces
+'
+
+test_expect_success 'Fuzz conflicts with context lines' '
+    general_error stg import --fuzz 1 -C1 fuzzy.diff 2>err &&
+    grep -e "cannot be used with" err
+'
+
 test_expect_success STG_IMPORT_URL 'Attempt url' '
     general_error stg import --url 2>err &&
     grep -e "required arguments were not provided" err
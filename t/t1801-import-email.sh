@@ -214,4 +214,75 @@ test_expect_success 'Import from git format-patch output' '
     )
 '
 
+test_expect_success 'Setup patch to file with CRLF line endings' '
+    (
+        cd upstream &&
+        printf "one\r\ntwo\r\n" >crlf.txt &&
+        git add crlf.txt &&
+        git commit -m "Add crlf" &&
+        printf "one\r\ntwo\r\nthree\r\n" >crlf.txt &&
+        git commit -a -m "crlf change" &&
+        git format-patch --stdout HEAD~1 >../crlf.mbox &&
+        git reset --hard HEAD~1 &&
+        stg repair
+    )
+'
+
+test_expect_success 'Import patch to CRLF file without --keep-cr' '
+    (
+        cd upstream &&
+        command_error stg import --mbox ../crlf.mbox 2>err &&
+        grep -e "crlf.txt: patch does not apply" err &&
+        test "$(stg top)" = "add-crlf"
+    )
+'
+
+test_expect_success 'Import patch to CRLF file with --keep-cr' '
+    (
+        cd upstream &&
+        stg import --keep-cr --mbox ../crlf.mbox &&
+        printf "one\r\ntwo\r\nthree\r\n" >expected &&
+        test_cmp expected crlf.txt &&
+        stg delete --top
+    )
+'
+
+test_expect_success 'Import patch to CRLF file with config and --no-keep-cr' '
+    (
+        cd upstream &&
+        test_config am.keepcr true &&
+        stg import --mbox ../crlf.mbox &&
+        printf "one\r\ntwo\r\nthree\r\n" >expected &&
+        test_cmp expected crlf.txt &&
+        stg delete --top &&
+        command_error stg import --no-keep-cr --mbox ../crlf.mbox &&
+        test "$(stg top)" = "add-crlf"
+    )
+'
+
+test_expect_success 'Import latin-1 email without charset declaration' '
+    (
+        cd upstream &&
+        printf "From: J\351r\364me <jerome@example.com>\n" >latin1.eml &&
+        printf "Subject: [PATCH] Caf\351 change\n\n" >>latin1.eml &&
+        printf "Na\357ve description.\n---\n" >>latin1.eml &&
+        printf "diff --git a/latin1.bin b/latin1.bin\n" >>latin1.eml &&
+        printf "new file mode 100644\n--- /dev/null\n+++ b/latin1.bin\n" >>latin1.eml &&
+        printf "@@ -0,0 +1 @@\n+byte \351\n" >>latin1.eml &&
+        stg import --charset latin1 --mail latin1.eml &&
+        git cat-file commit $(stg id) >commit &&
+        grep -e "^author Jérôme <jerome@example.com>" commit &&
+        grep -e "^Café change$" commit &&
+        grep -e "^Naïve description.$" commit &&
+        printf "byte \351\n" >expected &&
+        test_cmp expected latin1.bin &&
+        stg delete --top
+    )
+'
+
+test_expect_success 'Reject unknown charset' '
+    general_error stg import --charset bogus --mail upstream/latin1.eml 2>err &&
+    grep -e "unknown encoding .bogus." err
+'
+
 test_done
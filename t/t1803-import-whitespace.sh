@@ -0,0 +1,102 @@
+#!/bin/sh
+
+test_description='Test whitespace error handling when applying diffs'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup diff with whitespace errors' '
+    cat >>.git/info/exclude <<-\EOF &&
+	/*.diff
+	/err
+	/out
+	/expected
+	EOF
+    echo a >f &&
+    git add f &&
+    git commit -m base &&
+    stg init &&
+    printf "a\nb  \n \tc\n" >f &&
+    git diff >ws.diff &&
+    git checkout f
+'
+
+test_expect_success 'Import warns about whitespace errors by default' '
+    stg import -n p1 ws.diff 2>err &&
+    grep -e "patch \`p1\` has whitespace errors:" err &&
+    grep -e "f:2: trailing whitespace" err &&
+    grep -e "f:3: space before tab in indent" err &&
+    test "$(git show HEAD:f | sed -n 2p)" = "b  " &&
+    stg delete p1
+'
+
+test_expect_success 'Import refuses whitespace errors with apply.whitespace=error' '
+    test_config apply.whitespace error &&
+    command_error stg import -n p2 ws.diff 2>err &&
+    grep -e "trailing whitespace" err &&
+    test "$(echo $(stg series --noprefix))" = "" &&
+    stg import --whitespace=nowarn -n p2 ws.diff 2>err &&
+    ! grep -e "whitespace" err &&
+    stg delete p2
+'
+
+test_expect_success 'Import fixes whitespace errors with apply.whitespace=fix' '
+    test_config apply.whitespace fix &&
+    stg import -n p3 ws.diff 2>err &&
+    grep -e "f: fixed whitespace errors in 2 lines" err &&
+    printf "a\nb\n\tc\n" >expected &&
+    git show HEAD:f >out &&
+    test_cmp expected out &&
+    stg delete p3
+'
+
+test_expect_success 'Option --whitespace overrides apply.whitespace' '
+    test_config apply.whitespace fix &&
+    command_error stg import --whitespace=error -n p4 ws.diff &&
+    test "$(echo $(stg series --noprefix))" = ""
+'
+
+test_expect_success 'Fold fixes whitespace errors' '
+    stg new -m p5 &&
+    stg fold --whitespace=fix ws.diff 2>err &&
+    grep -e "f: fixed whitespace errors in 2 lines" err &&
+    printf "a\nb\n\tc\n" >expected &&
+    test_cmp expected f
+'
+
+test_expect_success 'Edited diff follows the --whitespace option' '
+    git reset --hard &&
+    stg delete p5 &&
+    printf "a\nb\nc\n" >f &&
+    stg new -m p6 &&
+    stg refresh &&
+    write_script diffedit <<-\EOF &&
+	sed -e "s/^+b$/+b  /" -e "s/^+c$/+ $(printf "\t")c/" "$1" >"$1".tmp &&
+	mv "$1".tmp "$1"
+	EOF
+    test_when_finished "rm -f diffedit .stgit-failed.patch" &&
+    EDITOR=./diffedit command_error stg edit --diff --whitespace=error 2>err &&
+    grep -e "trailing whitespace" err &&
+    printf "a\nb\nc\n" >expected &&
+    git show HEAD:f >out &&
+    test_cmp expected out &&
+    EDITOR=./diffedit stg edit --diff --whitespace=fix 2>err &&
+    grep -e "f: fixed whitespace errors in 2 lines" err &&
+    printf "a\nb\n\tc\n" >expected &&
+    git show HEAD:f >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Counts of fixed lines are lower bounds when errors are squelched' '
+    stg new -m p7 &&
+    printf "%s \n" 1 2 3 4 5 6 7 >g &&
+    git add g &&
+    git diff --cached >many.diff &&
+    git reset -q g &&
+    rm g &&
+    stg fold --whitespace=fix many.diff 2>err &&
+    grep -e "g: fixed whitespace errors in at least 5 lines" err &&
+    grep -e "squelched 2 whitespace errors" err &&
+    grep -e "7 lines applied after fixing whitespace errors" err
+'
+
+test_done
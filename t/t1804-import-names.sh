@@ -0,0 +1,100 @@
+#!/bin/sh
+
+test_description='Test naming patches of series imports'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup a series with poorly named patch files' '
+    test_commit base &&
+    test_commit one &&
+    test_commit two &&
+    test_commit three &&
+    git format-patch -k -o ps base &&
+    git reset --hard base &&
+    (
+        cd ps &&
+        mv 0001-one.patch 0001-.patch &&
+        mv 0002-two.patch 0002-.patch &&
+        mv 0003-three.patch 0003-.patch &&
+        printf "0001-.patch\n0002-.patch\n0003-.patch\n" >series
+    ) &&
+    stg init
+'
+
+test_expect_success 'Names from file require a series import' '
+    echo name >names &&
+    general_error stg import --names-from names ps/0001-.patch 2>err &&
+    grep -e "--series" err
+'
+
+test_expect_success 'Import series with a list of names' '
+    cat >names <<-\EOF &&
+	# names for the series
+	first
+
+	second
+	third
+	EOF
+    stg import --names-from names --series ps/series &&
+    test "$(echo $(stg series --noprefix))" = "first second third" &&
+    test "$(stg show -O --format=%s -O --no-patch second)" = "two" &&
+    stg delete ..
+'
+
+test_expect_success 'Mismatched number of names aborts the import' '
+    printf "first\nsecond\n" >names &&
+    command_error stg import --names-from names --series ps/series 2>err &&
+    grep -e "has 2 names, but the series has 3 patches" err &&
+    test "$(stg series -c)" = "0"
+'
+
+test_expect_success 'Import series with a mapping of names' '
+    cat >names <<-\EOF &&
+	0002-.patch = middle
+	EOF
+    stg import --names-from names --series ps/series &&
+    test "$(echo $(stg series --noprefix))" = "0001-patch middle 0003-patch" &&
+    stg delete ..
+'
+
+test_expect_success 'Mapping unknown patch file aborts the import' '
+    echo "0004-.patch=fourth" >names &&
+    command_error stg import --names-from names --series ps/series 2>err &&
+    grep -e "patch file .0004-.patch. is not in the series" err &&
+    test "$(stg series -c)" = "0"
+'
+
+test_expect_success 'Mixing names and mappings aborts the import' '
+    printf "first\n0002-.patch=second\nthird\n" >names &&
+    command_error stg import --names-from names --series ps/series 2>err &&
+    grep -e "mixes names and mappings" err &&
+    test "$(stg series -c)" = "0"
+'
+
+test_expect_success 'Invalid name aborts the import' '
+    printf "first\nbad..name\nthird\n" >names &&
+    command_error stg import --names-from names --series ps/series 2>err &&
+    grep -e "invalid name in names file" err &&
+    test "$(stg series -c)" = "0"
+'
+
+test_expect_success 'Duplicate names abort the import' '
+    printf "first\nsecond\nfirst\n" >names &&
+    command_error stg import --names-from names --series ps/series 2>err &&
+    grep -e "patch name .first. is used more than once" err &&
+    test "$(stg series -c)" = "0"
+'
+
+test_expect_success 'Names colliding with existing patches abort the import' '
+    stg new -m existing second &&
+    printf "first\nsecond\nthird\n" >names &&
+    command_error stg import --names-from names --series ps/series 2>err &&
+    grep -e "patch .second. already exists" err &&
+    test "$(echo $(stg series --noprefix))" = "second" &&
+    stg pop &&
+    stg import --replace --names-from names --series ps/series &&
+    test "$(echo $(stg series --noprefix))" = "first second third" &&
+    test "$(stg show -O --format=%s -O --no-patch second)" = "two"
+'
+
+test_done
@@ -0,0 +1,108 @@
+#!/bin/sh
+
+test_description='Test import from urls and public-inbox threads'
+
+. ./test-lib.sh
+
+if ! test_have_prereq STG_IMPORT_URL; then
+    skip_all='skipping import url tests, import-url feature not available'
+    test_done
+fi
+
+test_expect_success 'Initialize the StGit repository' '
+    cp "$TEST_DIRECTORY"/t1800/foo.txt . &&
+    stg add foo.txt &&
+    git commit -a -m "initial version" &&
+    printf "%s\n" err split inbox "*.gz" "*.eml" >>.git/info/exclude
+'
+
+test_expect_success !MINGW 'Detect mbox from url content' '
+    stg import -u "file://$TEST_DIRECTORY"/t1801/email-mbox &&
+    test "$(echo $(stg series --noprefix))" = "change-1 change-2 change-3-colon" &&
+    stg delete ..
+'
+
+test_expect_success !MINGW 'Decompress gzipped mbox from url' '
+    gzip -c "$TEST_DIRECTORY"/t1801/email-mbox >series.mbox.gz &&
+    stg import -u "file://$(pwd)/series.mbox.gz" &&
+    test "$(echo $(stg series --noprefix))" = "change-1 change-2 change-3-colon" &&
+    stg delete ..
+'
+
+test_expect_success !MINGW 'Plain patch from url still imported by file name' '
+    stg import -u "file://$TEST_DIRECTORY"/t1800/git-diff &&
+    test "$(echo $(stg series --noprefix))" = "git-diff" &&
+    stg delete ..
+'
+
+test_expect_success !MINGW 'Missing url is reported' '
+    command_error stg import -u "file://$(pwd)/missing.patch" 2>err &&
+    grep -e "downloading .file://.*/missing.patch." err
+'
+
+test_expect_success 'Setup public-inbox thread' '
+    mkdir split &&
+    git mailsplit -osplit "$TEST_DIRECTORY"/t1801/email-mbox &&
+    cat >cover.eml <<-\EOF &&
+	From: Inge Ström <inge@power.com>
+	Subject: [PATCH 0/3] Some changes
+	Date: Sat, 11 Nov 2006 12:45:27 +0100
+	Message-ID: <cover@localhost>
+
+	A cover letter.
+	EOF
+    cat >reply.eml <<-\EOF &&
+	From: Reviewer <reviewer@example.com>
+	Subject: Re: [PATCH 1/3] Change 1
+	Date: Sat, 11 Nov 2006 13:45:27 +0100
+	Message-ID: <reply@localhost>
+	In-Reply-To: <20061111114527.31778.12942.stgit@localhost>
+
+	Looks good.
+	EOF
+    mkdir -p inbox/all/cover@localhost &&
+    for f in cover.eml split/0003 reply.eml split/0001 split/0002; do
+        echo "From nobody Sat Nov 11 12:45:27 2006" &&
+        cat $f &&
+        echo || return 1
+    done | gzip >inbox/all/cover@localhost/t.mbox.gz
+'
+
+test_expect_success !MINGW 'Import series from thread in order' '
+    test_config stgit.import.inbox "file://$(pwd)/inbox/all/" &&
+    stg import --thread "<cover@localhost>" &&
+    test "$(echo $(stg series --noprefix))" = "change-1 change-2 change-3-colon" &&
+    test "$(git log -1 --format=%an $(stg id change-1))" = "Inge Ström" &&
+    stg delete ..
+'
+
+test_expect_success !MINGW 'Import options apply to thread import' '
+    test_config stgit.import.inbox "file://$(pwd)/inbox/all" &&
+    stg import --thread cover@localhost --message-id --sign &&
+    git cat-file commit $(stg id change-2) >commit &&
+    grep -e "^Message-I[Dd]: <20061111114527.31778.92851.stgit@localhost>" commit &&
+    grep -e "^Signed-off-by: $GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>" commit &&
+    stg delete ..
+'
+
+test_expect_success !MINGW 'Missing thread is reported with its url' '
+    test_config stgit.import.inbox "file://$(pwd)/inbox/all" &&
+    command_error stg import --thread missing@localhost 2>err &&
+    grep -e "inbox/all/missing@localhost/t.mbox.gz" err
+'
+
+test_expect_success !MINGW 'Thread without patches' '
+    mkdir inbox/all/reply@localhost &&
+    { echo "From nobody Sat Nov 11 12:45:27 2006" && cat reply.eml; } |
+    gzip >inbox/all/reply@localhost/t.mbox.gz &&
+    test_config stgit.import.inbox "file://$(pwd)/inbox/all" &&
+    command_error stg import --thread reply@localhost 2>err &&
+    grep -e "no patches found in thread .reply@localhost." err
+'
+
+test_expect_success 'Thread conflicts with source' '
+    general_error stg import --thread cover@localhost foo.patch 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
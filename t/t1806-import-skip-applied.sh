@@ -0,0 +1,97 @@
+#!/bin/sh
+
+test_description='Test skipping already applied hunks with import and fold'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup partially applied diff' '
+    printf "%s\n" err out expected "*.diff" >>.git/info/exclude &&
+    test_seq 1 20 >a.txt &&
+    test_seq 1 5 >b.txt &&
+    git add a.txt b.txt &&
+    git commit -m "add files" &&
+    sed -e "s/^2$/two/" -e "s/^18$/eighteen/" a.txt >a.new &&
+    mv a.new a.txt &&
+    sed -e "s/^3$/three/" b.txt >b.new &&
+    mv b.new b.txt &&
+    git diff >full.diff &&
+    git checkout a.txt b.txt &&
+    stg new -m partial &&
+    sed -e "s/^2$/two/" a.txt >a.new &&
+    mv a.new a.txt &&
+    sed -e "s/^3$/three/" b.txt >b.new &&
+    mv b.new b.txt &&
+    stg refresh
+'
+
+test_expect_success 'Import of partially applied diff fails' '
+    command_error stg import --name backport full.diff 2>err &&
+    grep -e "patch does not apply" err &&
+    test "$(stg top)" = "partial"
+'
+
+test_expect_success 'Import skips already applied hunks' '
+    stg import --skip-applied --name backport full.diff 2>err &&
+    test_when_finished "stg delete backport" &&
+    cat >expected <<-\EOF &&
+	info: patch `backport` skipped already applied hunks:
+	  a.txt: 1 of 2 hunks
+	  b.txt: 1 of 1 hunk
+	EOF
+    grep -e "skipped already applied" -A2 err >out &&
+    test_cmp expected out &&
+    stg files backport >out &&
+    echo "M a.txt" >expected &&
+    test_cmp expected out &&
+    test "$(sed -n -e 18p a.txt)" = "eighteen" &&
+    test "$(sed -n -e 2p a.txt)" = "two"
+'
+
+test_expect_success 'Skip applied conflicts with three-way merging' '
+    general_error stg import --skip-applied --3way full.diff 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg fold --skip-applied --threeway full.diff 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Fold skips already applied hunks' '
+    stg fold --skip-applied full.diff 2>err &&
+    test_when_finished "stg reset --hard" &&
+    grep -e "a.txt: 1 of 2 hunks" err &&
+    grep -e "b.txt: 1 of 1 hunk" err &&
+    test "$(sed -n -e 18p a.txt)" = "eighteen" &&
+    stg status --porcelain >out &&
+    echo "M  a.txt" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Fold of fully applied diff changes nothing' '
+    git diff HEAD^ HEAD >partial.diff &&
+    test_when_finished "rm partial.diff" &&
+    command_error stg fold partial.diff &&
+    stg fold --skip-applied partial.diff 2>err &&
+    grep -e "a.txt: 1 of 1 hunk" err &&
+    grep -e "b.txt: 1 of 1 hunk" err &&
+    stg status --porcelain >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Hunks that do not apply either way still fail' '
+    cat >conflict.diff <<-\EOF &&
+	diff --git a/b.txt b/b.txt
+	--- a/b.txt
+	+++ b/b.txt
+	@@ -3,3 +3,3 @@
+	 x
+	 4
+	-5
+	+five
+	EOF
+    command_error stg fold --skip-applied conflict.diff 2>err &&
+    grep -e "patch does not apply" err &&
+    ! grep -e "skipped already applied" err &&
+    stg status --porcelain >out &&
+    test_must_be_empty out
+'
+
+test_done
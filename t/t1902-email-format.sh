@@ -80,4 +80,145 @@ test_expect_success 'With cover letter' '
     rmdir out
 '
 
+test_expect_success 'Print Message-IDs of threaded patches' '
+    stg email format -o out --thread p1..p2 >output &&
+    test_line_count = 4 output &&
+    grep -e "^out/0001-p1.patch$" output &&
+    msgid=$(sed -n -e "s/^Message-ID: //Ip" out/0002-p2.patch) &&
+    grep -e "^p2: $msgid$" output &&
+    rm -r out
+'
+
+test_expect_success 'Print links of threaded patches using template' '
+    test_config stgit.email.linktemplate "https://lore.kernel.org/r/{msgid}" &&
+    stg email format -o out --thread --cover-letter p1..p2 >output &&
+    msgid=$(sed -n -e "s/^Message-ID: <\(.*\)>$/\1/Ip" out/0001-p1.patch) &&
+    grep -e "^p1: https://lore.kernel.org/r/$msgid$" output &&
+    msgid=$(sed -n -e "s/^Message-ID: <\(.*\)>$/\1/Ip" out/0000-cover-letter.patch) &&
+    grep -e "^https://lore.kernel.org/r/$msgid$" output &&
+    rm -r out
+'
+
+test_expect_success 'No Message-IDs without threading' '
+    stg email format -o out p1..p2 >output &&
+    test_line_count = 2 output &&
+    rm -r out
+'
+
+test_expect_success 'Setup second version of series' '
+    git tag v1 $(stg id p4) &&
+    git rev-parse refs/stacks/master >v1-state &&
+    stg edit -m "p2 changed" p2 &&
+    stg rename p3 q3
+'
+
+test_expect_success 'Range-diff against stack state implies cover letter' '
+    stg email format -o out --range-diff "$(cat v1-state)" p1..p4 &&
+    test_path_exists out/0000-cover-letter.patch &&
+    grep -e "^Range-diff:$" out/0000-cover-letter.patch &&
+    grep -e "^[0-9]:  $(git rev-parse --short v1~2) ! [0-9]:  $(git rev-parse --short $(stg id p2)) p2$" \
+        out/0000-cover-letter.patch &&
+    grep -e "^[0-9]:  $(git rev-parse --short v1~1) = [0-9]:  $(git rev-parse --short $(stg id q3)) p3$" \
+        out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Range-diff against stack state of single patch' '
+    stg email format -o out --range-diff "$(cat v1-state)" q3 &&
+    test_path_is_missing out/0000-cover-letter.patch &&
+    grep -e "^Range-diff:$" out/0001-p3.patch &&
+    grep -e "^1:  $(git rev-parse --short v1~1) = 1:  $(git rev-parse --short $(stg id q3)) p3$" \
+        out/0001-p3.patch &&
+    rm -r out
+'
+
+test_expect_success 'Range-diff against previous tip and range' '
+    stg email format -o out --cover-letter --range-diff v1 --all &&
+    grep -e "^[0-9]:  $(git rev-parse --short v1~2) ! [0-9]:  $(git rev-parse --short $(stg id p2)) p2$" \
+        out/0000-cover-letter.patch &&
+    rm -r out &&
+    stg email format -o out --range-diff "$(stg id p1)^..v1" --creation-factor=80 --all &&
+    grep -e "^[0-9]:  $(git rev-parse --short v1~2) ! [0-9]:  $(git rev-parse --short $(stg id p2)) p2$" \
+        out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Invalid range-diff spec fails before formatting' '
+    command_error stg email format -o out --range-diff bogus --all 2>err &&
+    grep -e "invalid range-diff committish .bogus." err &&
+    command_error stg email format -o out --range-diff bogus..v1 --all 2>err &&
+    grep -e "invalid range-diff range .bogus\.\.v1." err &&
+    test_path_is_missing out
+'
+
+test_expect_success 'Range-diff against stack state without the patches' '
+    state=$(git rev-parse refs/stacks/master) &&
+    echo p8 >p8.txt &&
+    stg add p8.txt &&
+    stg new -m p8 --refresh p8 &&
+    command_error stg email format -o out --range-diff "$state" p8 2>err &&
+    grep -e "none of the patches exist in stack state" err &&
+    stg delete p8 &&
+    stg push p5 &&
+    command_error stg email format -o out --range-diff "$(cat v1-state)" p5 2>err &&
+    grep -e "patch .p5. is not applied in stack state" err &&
+    stg pop p5 &&
+    test_path_is_missing out
+'
+
+test_expect_success 'Range-diff of series requires cover letter' '
+    command_error stg email format -o out --no-cover-letter --range-diff v1 --all 2>err &&
+    grep -e "requires a cover letter" err &&
+    test_path_is_missing out &&
+    general_error stg email format -o out --creation-factor=80 --all 2>err &&
+    grep -e "the following required arguments were not provided" err &&
+    test_path_is_missing out
+'
+
+test_expect_success 'Setup patch with commentary and CRLF line endings' '
+    printf "one\r\ntwo\r\n" >crlf.txt &&
+    stg add crlf.txt &&
+    stg new -m "$(printf "crlf patch\n\nCommentary for the patch.")" --refresh crlf-patch &&
+    stg id crlf-patch >crlf-patch-id
+'
+
+test_expect_success 'Format patch as attachment' '
+    stg email format -o out --attach crlf-patch &&
+    f=out/0001-crlf-patch.patch &&
+    grep -e "^Subject: \[PATCH\] crlf patch" $f &&
+    grep -e "^Content-Type: multipart/mixed" $f &&
+    grep -e "^Commentary for the patch\.$" $f &&
+    grep -e "^Content-Type: text/x-patch; name=\"0001-crlf-patch.patch\"" $f &&
+    grep -e "^Content-Disposition: attachment; filename=\"0001-crlf-patch.patch\"" $f &&
+    rm -r out
+'
+
+test_expect_success 'Format patch as inline attachment' '
+    stg email format -o out --attach=inline crlf-patch &&
+    grep -e "^Content-Disposition: inline; filename=\"0001-crlf-patch.patch\"" \
+        out/0001-crlf-patch.patch &&
+    rm -r out &&
+    stg email format -o out --inline crlf-patch &&
+    grep -e "^Content-Disposition: inline; filename=\"0001-crlf-patch.patch\"" \
+        out/0001-crlf-patch.patch &&
+    rm -r out &&
+    general_error stg email format -o out --attach --inline crlf-patch 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg email format -o out --attach=bogus crlf-patch 2>err &&
+    grep -e "invalid value .bogus. for .--attach" err &&
+    test_path_is_missing out
+'
+
+test_expect_success 'Attached patch applies with git am' '
+    stg email format -o out --attach crlf-patch &&
+    git checkout -b am-test $(cat crlf-patch-id)^ &&
+    test_when_finished "git checkout master" &&
+    git am --keep-cr out/0001-crlf-patch.patch &&
+    test_cmp_rev $(cat crlf-patch-id)^{tree} HEAD^{tree} &&
+    git log -1 --format=%B $(cat crlf-patch-id) >expected &&
+    git log -1 --format=%B >actual &&
+    test_cmp expected actual &&
+    rm -r out
+'
+
 test_done
@@ -42,6 +42,12 @@ test_expect_success GITSENDEMAIL 'Send single patch' '
     test_cmp expected subjects
 '
 
+test_expect_success GITSENDEMAIL 'Send with SMTP batching options' '
+    stg email send --dry-run --to someone@example.com --batch-size 2 --relogin-delay 0 --all >out &&
+    grep "Subject: " out >subjects &&
+    test_line_count = 4 subjects
+'
+
 test_expect_success 'Setup another branch' '
     stg branch --create other &&
     test_commit_bulk --filename=other%s.txt --message="other%s" 5 &&
@@ -61,4 +67,163 @@ test_expect_success GITSENDEMAIL 'Send patches from other branch' '
     test_cmp expected subjects
 '
 
+# The stand-in logs its arguments to send-email.log and fails to send the email with
+# the subject in $FAIL_SUBJECT.
+test_expect_success !GITSENDEMAIL 'Setup stand-in git send-email' '
+    mkdir bin &&
+    write_script bin/git-send-email <<-\EOF &&
+	echo "$*" >>"$HOME/send-email.log"
+	n=0
+	for arg in "$@"; do
+	    case "$arg" in
+	    -*) ;;
+	    *) n=$((n + 1)) &&
+	       rev=$(sed -n "1s/^From \([0-9a-f]*\) .*/\1/p" "$arg") &&
+	       subject=$(sed -n "s/^Subject: //p" "$arg") &&
+	       msgid=$(sed -n "s/^Message-ID: //p" "$arg") &&
+	       if test "$subject" = "$FAIL_SUBJECT"; then
+	           echo "fatal: failed to send $subject" >&2
+	           exit 1
+	       fi &&
+	       echo "Subject: $subject" &&
+	       echo "Message-ID: ${msgid:-<$n.$rev@example.com>}" &&
+	       echo;;
+	    esac
+	done
+	EOF
+    test_set_prereq FAKESENDEMAIL
+'
+
+test_expect_success FAKESENDEMAIL 'Print Message-IDs of sent patches' '
+    PATH="$(pwd)/bin:$PATH" stg email send --to someone@example.com p1..p2 >out &&
+    grep -e "^p1: <1\.$(stg id p1)@example.com>$" out &&
+    grep -e "^p2: <2\.$(stg id p2)@example.com>$" out
+'
+
+test_expect_success FAKESENDEMAIL 'Add Link trailers requires template' '
+    PATH="$(pwd)/bin:$PATH" command_error stg email send --add-link-trailer \
+        --to someone@example.com p1..p2 2>err &&
+    grep -e "requires .stgit.email.linktemplate." err
+'
+
+test_expect_success FAKESENDEMAIL 'Add Link trailers to sent patches' '
+    test_config stgit.email.linktemplate "https://lore.kernel.org/r/{msgid}" &&
+    p1=$(stg id p1) &&
+    p2=$(stg id p2) &&
+    tree=$(git rev-parse HEAD^{tree}) &&
+    PATH="$(pwd)/bin:$PATH" stg email send --add-link-trailer \
+        --to someone@example.com p1..p2 >out &&
+    grep -e "^p1: https://lore.kernel.org/r/1\.$p1@example.com$" out &&
+    test "$(stg top)" = "p4" &&
+    test "$(git rev-parse HEAD^{tree})" = "$tree" &&
+    git log -1 --format=%B $(stg id p1) >msg &&
+    grep -e "^Link: https://lore.kernel.org/r/1\.$p1@example.com$" msg &&
+    git log -1 --format=%B $(stg id p2) >msg &&
+    grep -e "^Link: https://lore.kernel.org/r/2\.$p2@example.com$" msg &&
+    git log -1 --format=%B $(stg id p3) >msg &&
+    ! grep -e "^Link:" msg
+'
+
+test_expect_success 'Batching options take numbers' '
+    general_error stg email send --dry-run --to someone@example.com \
+        --batch-size two --all 2>err &&
+    grep -e "invalid value .two. for .--batch-size <n>." err &&
+    general_error stg email send --dry-run --to someone@example.com \
+        --batch-size 0 --all 2>err &&
+    grep -e "invalid value .0. for .--batch-size <n>." err &&
+    general_error stg email send --dry-run --to someone@example.com \
+        --relogin-delay soon --all 2>err &&
+    grep -e "invalid value .soon. for .--relogin-delay <secs>." err
+'
+
+test_expect_success FAKESENDEMAIL 'Batching options are passed to git send-email' '
+    rm -f send-email.log &&
+    PATH="$(pwd)/bin:$PATH" stg email send --to someone@example.com \
+        --batch-size 2 --relogin-delay 0 p1..p3 >out &&
+    test_line_count = 1 send-email.log &&
+    grep -e "--batch-size=2 --relogin-delay=0 " send-email.log &&
+    grep -e "^p3: <3\.$(stg id p3)@example.com>$" out
+'
+
+test_expect_success FAKESENDEMAIL 'Report the emails sent before a failure' '
+    p1=$(stg id p1) &&
+    p2=$(stg id p2) &&
+    FAIL_SUBJECT="[PATCH 3/4] p3" PATH="$(pwd)/bin:$PATH" \
+        command_error stg email send --to someone@example.com --all 2>err &&
+    grep -e "failed to send \[PATCH 3/4\] p3" err &&
+    grep -e "^sent 2 of 4 emails:$" err &&
+    grep -e "^  p1 <1\.$p1@example.com>$" err &&
+    grep -e "^  p2 <2\.$p2@example.com>$" err &&
+    grep -e "--resume-from=p3 --in-reply-to=<1\.$p1@example.com>" err
+'
+
+test_expect_success FAKESENDEMAIL 'Resume sending a series' '
+    rm -f send-email.log &&
+    PATH="$(pwd)/bin:$PATH" stg email send --to someone@example.com --all \
+        --resume-from=p3 --in-reply-to="<1.$(stg id p1)@example.com>" >out &&
+    grep "^Subject: " out >subjects &&
+    cat >expected <<-\EOF &&
+	Subject: [PATCH 3/4] p3
+	Subject: [PATCH 4/4] p4
+	EOF
+    test_cmp expected subjects &&
+    grep -e "^p3: " out &&
+    grep -e "^p4: " out &&
+    ! grep -e "^p1: " out &&
+    grep -e "--in-reply-to=<1\.$(stg id p1)@example.com>" send-email.log
+'
+
+test_expect_success FAKESENDEMAIL 'Resume point must be sent' '
+    PATH="$(pwd)/bin:$PATH" command_error stg email send --to someone@example.com \
+        --resume-from=p7 p1..p2 2>err &&
+    grep -e "\`p7\` is not a patch or email file to be sent" err
+'
+
+test_expect_success FAKESENDEMAIL 'Resume sending email files' '
+    stg email format -o mails --cover-letter --all &&
+    rm -f send-email.log &&
+    PATH="$(pwd)/bin:$PATH" stg email send --to someone@example.com \
+        --resume-from=mails/0003-p3.patch mails >out &&
+    grep "^Subject: " out >subjects &&
+    cat >expected <<-\EOF &&
+	Subject: [PATCH 3/4] p3
+	Subject: [PATCH 4/4] p4
+	EOF
+    test_cmp expected subjects
+'
+
+test_expect_success FAKESENDEMAIL 'Pause between separately sent emails' '
+    rm -f send-email.log &&
+    PATH="$(pwd)/bin:$PATH" stg email send --to someone@example.com \
+        --pause 1 p1..p3 >out &&
+    test_line_count = 3 send-email.log &&
+    grep "^Message-ID: " out >ids &&
+    test_line_count = 3 ids &&
+    first=$(sed -n "1s/^Message-ID: //p" ids) &&
+    sed -n 1p send-email.log >first-args &&
+    ! grep -e "--in-reply-to" first-args &&
+    sed -n "2,3p" send-email.log >later-args &&
+    test "$(grep -c -e "--in-reply-to=$first " later-args)" = 2 &&
+    grep -e "^p2: <" out &&
+    grep -e "^p3: <" out
+'
+
+test_expect_success FAKESENDEMAIL 'Deep threading of separately sent emails' '
+    rm -f send-email.log &&
+    PATH="$(pwd)/bin:$PATH" stg email send --to someone@example.com \
+        --pause 0 --thread=deep p1..p3 >out &&
+    grep "^Message-ID: " out >ids &&
+    second=$(sed -n "2s/^Message-ID: //p" ids) &&
+    sed -n 3p send-email.log >last-args &&
+    grep -e "--in-reply-to=$second " last-args
+'
+
+test_expect_success FAKESENDEMAIL 'Report the separately sent emails before a failure' '
+    FAIL_SUBJECT="[PATCH 2/3] p2" PATH="$(pwd)/bin:$PATH" \
+        command_error stg email send --to someone@example.com --pause 0 p1..p3 2>err &&
+    grep -e "^sent 1 of 3 emails:$" err &&
+    grep -e "^  p1 <.*-stgit@example.com>$" err &&
+    grep -e "--resume-from=p2 --in-reply-to=<" err
+'
+
 test_done
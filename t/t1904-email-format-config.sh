@@ -0,0 +1,92 @@
+#!/bin/sh
+
+test_description="Test 'stg email format' with format.* configuration"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2
+'
+
+test_expect_success 'Subject prefix from format.subjectPrefix' '
+    test_config format.subjectPrefix "RFC PATCH" &&
+    stg email format -o out --all &&
+    grep -e "^Subject: \[RFC PATCH 1/2\] p1$" out/0001-p1.patch &&
+    grep -e "^Subject: \[RFC PATCH 2/2\] p2$" out/0002-p2.patch &&
+    rm -r out
+'
+
+test_expect_success 'Subject prefix option overrides format.subjectPrefix' '
+    test_config format.subjectPrefix "RFC PATCH" &&
+    stg email format -o out --subject-prefix "PATCH v2" p1 &&
+    grep -e "^Subject: \[PATCH v2\] p1$" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Signed-off-by trailer from format.signOff' '
+    test_config format.signOff true &&
+    stg email format -o out p1 &&
+    grep -e "^Signed-off-by: $GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>$" \
+        out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Signed-off-by trailer uses committer identity' '
+    test_config format.signOff true &&
+    GIT_COMMITTER_NAME="Sig Ner" GIT_COMMITTER_EMAIL="signer@example.com" \
+        stg email format -o out p1 &&
+    grep -e "^Signed-off-by: Sig Ner <signer@example.com>$" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'No Signed-off-by trailer with --no-signoff' '
+    test_config format.signOff true &&
+    stg email format -o out --no-signoff p1 &&
+    ! grep -e "^Signed-off-by:" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Recipients from format.to and format.cc' '
+    test_config format.to "to@example.com" &&
+    test_config format.cc "cc@example.com" &&
+    stg email format -o out p1 &&
+    grep -e "^To: to@example.com$" out/0001-p1.patch &&
+    grep -e "^Cc: cc@example.com$" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Recipient options override format.to and format.cc' '
+    test_config format.to "to@example.com" &&
+    test_config format.cc "cc@example.com" &&
+    stg email format -o out --no-to --to other@example.com --no-cc p1 &&
+    grep -e "^To: other@example.com$" out/0001-p1.patch &&
+    ! grep -e "to@example.com" out/0001-p1.patch &&
+    ! grep -e "^Cc:" out/0001-p1.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter from format.coverLetter' '
+    test_config format.coverLetter true &&
+    stg email format -o out p1 &&
+    test_path_exists out/0000-cover-letter.patch &&
+    rm -r out &&
+    test_config format.coverLetter auto &&
+    stg email format -o out p1 &&
+    test_path_is_missing out/0000-cover-letter.patch &&
+    rm -r out &&
+    stg email format -o out --all &&
+    test_path_exists out/0000-cover-letter.patch &&
+    grep -e "^Subject: \[PATCH 0/2\]" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'No cover letter with --no-cover-letter' '
+    test_config format.coverLetter true &&
+    stg email format -o out --no-cover-letter --all &&
+    test_path_is_missing out/0000-cover-letter.patch &&
+    test_path_exists out/0001-p1.patch &&
+    rm -r out
+'
+
+test_done
@@ -0,0 +1,113 @@
+#!/bin/sh
+
+test_description="Test email address aliases of 'stg email'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack and aliases' '
+    printf "%s\n" out err expected headers mails "*.aliases" >>.git/info/exclude &&
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2 &&
+    cat >mutt.aliases <<-\EOF
+	# mutt aliases
+	alias ann Ann Author <ann@example.com>
+	alias -group devs bob Bob Builder <bob@example.com> # the builder
+	alias devs ann, bob, "Lee, Carl" <carl@example.com>
+	EOF
+'
+
+test_expect_success 'List resolved aliases' '
+    test_config sendemail.aliasesFile "$(pwd)/mutt.aliases" &&
+    test_config sendemail.aliasFileType mutt &&
+    stg email aliases >out &&
+    cat >expected <<-\EOF &&
+	ann: Ann Author <ann@example.com>
+	bob: Bob Builder <bob@example.com>
+	devs: Ann Author <ann@example.com>, Bob Builder <bob@example.com>, "Lee, Carl" <carl@example.com>
+	EOF
+    test_cmp expected out &&
+    stg email aliases bob >out &&
+    test "$(cat out)" = "bob: Bob Builder <bob@example.com>"
+'
+
+test_expect_success 'Format expands aliases in to and cc headers' '
+    test_config sendemail.aliasesFile "$(pwd)/mutt.aliases" &&
+    test_config sendemail.aliasFileType mutt &&
+    stg email format -o mails --to=ann --cc=devs --cc=dee@example.com p1 &&
+    sed -n -e "/^To:/,/^$/p" mails/0001-p1.patch >headers &&
+    cat >expected <<-\EOF &&
+	To: Ann Author <ann@example.com>
+	Cc: Ann Author <ann@example.com>,
+	    Bob Builder <bob@example.com>,
+	    "Lee, Carl" <carl@example.com>,
+	    dee@example.com
+
+	EOF
+    test_cmp expected headers &&
+    rm -r mails
+'
+
+test_expect_success 'Unknown alias is an error listing close matches' '
+    test_config sendemail.aliasesFile "$(pwd)/mutt.aliases" &&
+    test_config sendemail.aliasFileType mutt &&
+    command_error stg email format -o mails --to=ann --cc=dev p1 2>err &&
+    grep -e "unknown email alias .dev.; did you mean .devs.?" err &&
+    test_path_is_missing mails &&
+    command_error stg email aliases nobody 2>err &&
+    grep -e "unknown email alias .nobody.$" err
+'
+
+test_expect_success GITSENDEMAIL 'Unknown alias is an error before sending' '
+    test_config sendemail.aliasesFile "$(pwd)/mutt.aliases" &&
+    test_config sendemail.aliasFileType mutt &&
+    command_error stg email send --dry-run --to=ann --bcc=bobby p1 >out 2>err &&
+    grep -e "unknown email alias .bobby.; did you mean .bob.?" err &&
+    test_must_be_empty out
+'
+
+test_expect_success GITSENDEMAIL 'Send expands aliases' '
+    test_config sendemail.aliasesFile "$(pwd)/mutt.aliases" &&
+    test_config sendemail.aliasFileType mutt &&
+    stg email send --dry-run --to=devs p1 >out &&
+    grep -e "^To: Ann Author <ann@example.com>" out &&
+    grep -e "Lee, Carl" out
+'
+
+test_expect_success 'Mailrc and sendmail aliases files' '
+    cat >mailrc.aliases <<-\EOF &&
+	alias dee "Dee Dev <dee@example.com>"
+	alias pair dee eve@example.com
+	EOF
+    cat >sendmail.aliases <<-\EOF &&
+	# sendmail aliases
+	eve: eve@example.com
+	team: eve,
+	  "Fay F" <fay@example.com>,
+	  |/usr/bin/archive
+	EOF
+    test_config sendemail.aliasesFile "$(pwd)/mailrc.aliases" &&
+    test_config sendemail.aliasFileType mailrc &&
+    stg email aliases pair >out &&
+    test "$(cat out)" = "pair: Dee Dev <dee@example.com>, eve@example.com" &&
+    test_config sendemail.aliasesFile "$(pwd)/sendmail.aliases" &&
+    test_config sendemail.aliasFileType sendmail &&
+    stg email aliases team >out &&
+    test "$(cat out)" = "team: eve@example.com, \"Fay F\" <fay@example.com>"
+'
+
+test_expect_success 'Alias loops and misconfiguration are errors' '
+    printf "alias a b\nalias b a\n" >loop.aliases &&
+    test_config sendemail.aliasesFile "$(pwd)/loop.aliases" &&
+    test_config sendemail.aliasFileType mailrc &&
+    command_error stg email aliases 2>err &&
+    grep -e "email alias .a. expands to itself" err &&
+    test_config sendemail.aliasFileType pine &&
+    command_error stg email aliases 2>err &&
+    grep -e "unsupported .sendemail.aliasFileType. .pine." err &&
+    test_config sendemail.aliasesFile "$(pwd)/missing.aliases" &&
+    test_config sendemail.aliasFileType mutt &&
+    command_error stg email format --to=ann p1 2>err &&
+    grep -e "reading aliases file .*missing.aliases." err
+'
+
+test_done
@@ -0,0 +1,126 @@
+#!/bin/sh
+
+test_description="Test reviewing emails with 'stg email send --confirm'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    printf "%s\n" out err send-args subjects expected expected-subjects bin patches edit-subject drop-subject >>.git/info/exclude &&
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3
+'
+
+test_expect_success !GITSENDEMAIL 'Setup stand-in git send-email' '
+    mkdir bin &&
+    write_script bin/git-send-email <<-\EOF &&
+	printf "%s\n" "$@" >send-args
+	n=0
+	for arg in "$@"; do
+	    case "$arg" in
+	    -*) ;;
+	    *) if test -f "$arg"; then
+	           n=$((n + 1)) &&
+	           grep -m 1 -e "^Subject: " "$arg" &&
+	           echo "Message-ID: <$n@example.com>" &&
+	           echo
+	       else
+	           git log --reverse --format="Subject: %s%n" "$arg"
+	       fi;;
+	    esac
+	done
+	EOF
+    test_set_prereq FAKESENDEMAIL
+'
+
+send () {
+    PATH="$(pwd)/bin:$PATH" stg email send --to someone@example.com "$@"
+}
+
+test_expect_success FAKESENDEMAIL 'Accept and skip emails' '
+    printf "y\ns\ny\n" | send --confirm=always p1..p3 >out 2>err &&
+    grep -e "^--- Email 1/3: 0001-p1.patch (p1)$" err &&
+    grep -e "^Subject: \[PATCH 2/3\] p2$" err &&
+    grep -e "skipped 1 of 3 emails; the sent emails keep their original numbering" err &&
+    grep -e "^--confirm=never$" send-args &&
+    grep -e "^Subject: " out >subjects &&
+    cat >expected <<-\EOF &&
+	Subject: [PATCH 1/3] p1
+	Subject: [PATCH 3/3] p3
+	EOF
+    test_cmp expected subjects &&
+    grep -e "^p1: <1@example.com>$" out &&
+    grep -e "^p3: <2@example.com>$" out
+'
+
+test_expect_success FAKESENDEMAIL 'Invalid answers are asked again' '
+    printf "x\nyes\n" | send --confirm=always p1 >out 2>err &&
+    grep -o -e "Send this email?" err >prompts &&
+    test_line_count = 2 prompts &&
+    grep -e "^Subject: \[PATCH\] p1$" out
+'
+
+test_expect_success FAKESENDEMAIL 'Abort sends nothing' '
+    rm -f send-args &&
+    printf "y\na\n" | command_error send --confirm=always p1..p3 2>err &&
+    grep -e "aborted; no emails were sent" err &&
+    test_path_is_missing send-args &&
+    command_error send --confirm=always p1..p3 </dev/null 2>err &&
+    grep -e "aborted; no emails were sent" err &&
+    test_path_is_missing send-args
+'
+
+test_expect_success FAKESENDEMAIL 'Skip all emails' '
+    printf "s\ns\n" | send --confirm=always p1..p2 2>err &&
+    grep -e "all emails skipped; no emails were sent" err &&
+    test_path_is_missing send-args
+'
+
+test_expect_success FAKESENDEMAIL 'Edit email before sending' '
+    write_script edit-subject <<-\EOF &&
+	sed "s/^Subject: .*/Subject: [PATCH] edited/" "$1" >"$1".tmp && mv "$1".tmp "$1"
+	EOF
+    printf "e\ny\n" | GIT_EDITOR=./edit-subject send --confirm=always p2 >out 2>err &&
+    grep -e "^Subject: \[PATCH\] edited$" out &&
+    test "$(git log -1 --format=%s $(stg id p2))" = "p2"
+'
+
+test_expect_success FAKESENDEMAIL 'Edited headers are validated' '
+    write_script drop-subject <<-\EOF &&
+	sed "/^Subject: /d" "$1" >"$1".tmp && mv "$1".tmp "$1"
+	EOF
+    rm -f send-args &&
+    printf "e\ny\ns\n" | GIT_EDITOR=./drop-subject send --confirm=always p2 2>err &&
+    grep -e "edited email is invalid: missing .Subject. header$" err &&
+    grep -e "edit it again or skip it" err &&
+    test_path_is_missing send-args
+'
+
+test_expect_success FAKESENDEMAIL 'Review email files without modifying them' '
+    stg email format -o patches p1..p2 &&
+    cp patches/0001-p1.patch expected &&
+    printf "e\ny\ny\n" | GIT_EDITOR=./edit-subject send --confirm=always patches >out 2>err &&
+    grep -e "^--- Email 1/2: 0001-p1.patch$" err &&
+    grep -e "^Subject: " out >subjects &&
+    cat >expected-subjects <<-\EOF &&
+	Subject: [PATCH] edited
+	Subject: [PATCH 2/2] p2
+	EOF
+    test_cmp expected-subjects subjects &&
+    test_cmp expected patches/0001-p1.patch
+'
+
+test_expect_success FAKESENDEMAIL 'Auto confirmation outside a terminal' '
+    send --confirm=auto p1..p2 >out 2>err &&
+    ! grep -e "Send this email?" err &&
+    grep -e "^--confirm=never$" send-args &&
+    grep -e "/0001-p1\.patch$" send-args &&
+    grep -e "/0002-p2\.patch$" send-args
+'
+
+test_expect_success FAKESENDEMAIL 'Other confirmation modes are passed to git' '
+    send --confirm=cc patches/0001-p1.patch >out 2>err &&
+    grep -e "^--confirm=cc$" send-args &&
+    ! grep -e "Send this email?" err
+'
+
+test_done
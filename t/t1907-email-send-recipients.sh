@@ -0,0 +1,172 @@
+#!/bin/sh
+
+test_description="Test recipients and threading of 'stg email send'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    printf "%s\n" out err send-args expected bin fp patches >>.git/info/exclude &&
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3
+'
+
+test_expect_success !GITSENDEMAIL 'Setup stand-in git send-email' '
+    mkdir bin &&
+    write_script bin/git-send-email <<-\EOF &&
+	printf "%s\n" "$@" >send-args
+	to= cc= bcc= chain= thread=yes
+	for arg in "$@"; do
+	    case "$arg" in
+	    --to=*) to="$to ${arg#--to=}";;
+	    --cc=*) cc="$cc ${arg#--cc=}";;
+	    --bcc=*) bcc="$bcc ${arg#--bcc=}";;
+	    --chain-reply-to) chain=yes;;
+	    --no-chain-reply-to) chain=;;
+	    --no-thread) thread=;;
+	    esac
+	done
+	n=0 first= prev=
+	for arg in "$@"; do
+	    case "$arg" in
+	    -*) continue;;
+	    esac
+	    if test -d "$arg"; then
+	        files=$(ls "$arg"/*)
+	    elif test -f "$arg"; then
+	        files=$arg
+	    else
+	        rm -rf fp && files=$(git format-patch -o fp "$arg")
+	    fi
+	    for file in $files; do
+	        n=$((n + 1))
+	        fto=$(sed -n -e "/^$/q" -e "s/^To: //p" "$file")
+	        fcc=$(sed -n -e "/^$/q" -e "s/^Cc: //p" "$file")
+	        echo "Dry-OK. Log says:"
+	        echo "Sendmail: /usr/sbin/sendmail -i" $to $fto $cc $fcc $bcc
+	        echo "From: A U Thor <author@example.com>"
+	        test -z "$(echo $to $fto)" || echo "To: $(echo $to $fto | sed -e "s/ /, /g")"
+	        test -z "$(echo $cc $fcc)" || echo "Cc: $(echo $cc $fcc | sed -e "s/ /, /g")"
+	        grep -m 1 -e "^Subject: " "$file"
+	        echo "Message-ID: <$n@example.com>"
+	        if test -n "$thread" && test -n "$first"; then
+	            if test -n "$chain"; then
+	                echo "In-Reply-To: $prev"
+	            else
+	                echo "In-Reply-To: $first"
+	            fi
+	        fi
+	        echo
+	        echo "Result: OK"
+	        test -n "$first" || first="<$n@example.com>"
+	        prev="<$n@example.com>"
+	    done
+	done
+	EOF
+    test_set_prereq FAKESENDEMAIL
+'
+
+send () {
+    PATH="$(pwd)/bin:$PATH" stg email send --to maint@example.com "$@"
+}
+
+test_expect_success 'Show recipients requires dry run' '
+    general_error stg email send --show-recipients --to maint@example.com p1 2>err &&
+    grep -e "the following required arguments were not provided" err
+'
+
+test_expect_success 'Thread style conflicts with no thread' '
+    general_error stg email send --dry-run --thread=deep --no-thread \
+        --to maint@example.com p1 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg email send --dry-run --thread=deep --no-thread-deep \
+        --to maint@example.com p1 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success FAKESENDEMAIL 'Show recipients of each email' '
+    send --dry-run --show-recipients --cc reviewer@example.com \
+        --bcc archive@example.com p1..p2 >out &&
+    cat >expected <<-\EOF &&
+	Email  To                 Cc                    Bcc                  Reply to
+	p1     maint@example.com  reviewer@example.com  archive@example.com  -
+	p2     maint@example.com  reviewer@example.com  archive@example.com  p1
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success FAKESENDEMAIL 'Cover letter only recipients' '
+    send --dry-run --show-recipients --cover-letter \
+        --to-cover list@example.com --cc-cover other-list@example.com p1..p2 >out &&
+    cat >expected <<-\EOF &&
+	Email         To                 Cc                      Bcc  Reply to
+	cover letter  maint@example.com  other-list@example.com  -    -
+	              list@example.com
+	p1            maint@example.com  -                       -    cover letter
+	p2            maint@example.com  -                       -    cover letter
+	EOF
+    test_cmp expected out &&
+    grep -e "^--to=maint@example.com$" send-args &&
+    ! grep -e "list@example.com" send-args
+'
+
+test_expect_success FAKESENDEMAIL 'Cover letter recipients require a cover letter' '
+    command_error send --dry-run --to-cover list@example.com p1..p2 2>err &&
+    grep -e "require a cover letter" err
+'
+
+test_expect_success FAKESENDEMAIL 'Last of --cover-letter and --no-cover-letter wins' '
+    command_error send --dry-run --cover-letter --no-cover-letter \
+        --to-cover list@example.com p1..p2 2>err &&
+    grep -e "require a cover letter" err &&
+    send --dry-run --show-recipients --no-cover-letter --cover-letter \
+        --to-cover list@example.com p1..p2 >out &&
+    grep -e "^cover letter " out
+'
+
+test_expect_success FAKESENDEMAIL 'Cover letter recipients in formatted emails' '
+    stg email format -o patches --cover-letter p1..p2 &&
+    cat patches/0000-cover-letter.patch >expected &&
+    send --dry-run --show-recipients --to-cover list@example.com patches >out &&
+    grep -e "^cover letter  *maint@example.com  *-  *-  *-$" out &&
+    grep -e "^  *list@example.com$" out &&
+    grep -e "^\[PATCH 2/2\] p2  *maint@example.com  *-  *-  *cover letter$" out &&
+    test_cmp expected patches/0000-cover-letter.patch &&
+    rm -r patches
+'
+
+test_expect_success FAKESENDEMAIL 'Deep threading' '
+    send --dry-run --show-recipients --thread=deep p1..p3 >out &&
+    grep -e "^--thread$" send-args &&
+    grep -e "^--chain-reply-to$" send-args &&
+    cat >expected <<-\EOF &&
+	Email  To                 Cc  Bcc  Reply to
+	p1     maint@example.com  -   -    -
+	p2     maint@example.com  -   -    p1
+	p3     maint@example.com  -   -    p2
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success FAKESENDEMAIL 'Shallow threading' '
+    send --dry-run --thread=shallow p1..p3 >out &&
+    grep -e "^--thread$" send-args &&
+    grep -e "^--no-chain-reply-to$" send-args &&
+    send --dry-run --thread p1..p3 >out &&
+    grep -e "^--thread$" send-args &&
+    ! grep -e "chain-reply-to" send-args
+'
+
+test_expect_success FAKESENDEMAIL 'No deep threading' '
+    send --dry-run --no-thread-deep p1..p3 >out &&
+    grep -e "^--no-chain-reply-to$" send-args &&
+    ! grep -e "^--thread$" send-args
+'
+
+test_expect_success GITSENDEMAIL 'Show recipients with git send-email' '
+    stg email send --dry-run --show-recipients --to maint@example.com \
+        --cover-letter --to-cover list@example.com --thread=deep p1..p2 >out &&
+    grep -e "^cover letter " out &&
+    grep -e "^p2 .* p1$" out
+'
+
+test_done
@@ -0,0 +1,134 @@
+#!/bin/sh
+
+test_description='Test continuing and aborting stg pull'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup upstream repo, clone it, and add patches to the clone' '
+    test_create_repo upstream &&
+    (
+        cd upstream &&
+        echo base >file &&
+        git add file &&
+        git commit -m base &&
+        stg init
+    ) &&
+    git clone upstream clone &&
+    (
+        cd clone &&
+        git config branch.master.stgit.pull-policy fetch-rebase &&
+        stg init &&
+        stg new -m c1 c1 &&
+        echo clone >file &&
+        stg refresh &&
+        stg new -m c2 c2 &&
+        echo c2 >file2 &&
+        stg add file2 &&
+        stg refresh
+    )
+'
+
+test_expect_success 'Pull stops on conflicts with instructions' '
+    (
+        cd upstream &&
+        echo upstream >file &&
+        git commit -a -m u1
+    ) &&
+    (
+        cd clone &&
+        conflict stg pull 2>err &&
+        grep -e "stg pull --continue" err &&
+        grep -e "stg pull --abort" err &&
+        test_path_is_file .git/stgit-pull &&
+        test "$(echo $(stg series --applied --noprefix))" = "c1" &&
+        test "$(echo $(stg series --unapplied --noprefix))" = "c2"
+    )
+'
+
+test_expect_success 'Pull refuses to start while a pull is in progress' '
+    (
+        cd clone &&
+        command_error stg pull 2>err &&
+        grep -e "a pull of branch .master. is already in progress" err
+    )
+'
+
+test_expect_success 'Continue refuses unresolved conflicts' '
+    (
+        cd clone &&
+        command_error stg pull --continue 2>err &&
+        grep -e "resolve outstanding conflicts first" err
+    )
+'
+
+test_expect_success 'Continue pushes back remaining patches without fetching' '
+    test_config -C clone stgit.fetchcmd false &&
+    (
+        cd clone &&
+        echo resolved >file &&
+        stg add file &&
+        stg refresh &&
+        stg pull --continue &&
+        test_path_is_missing .git/stgit-pull &&
+        test "$(echo $(stg series --applied --noprefix))" = "c1 c2" &&
+        test "$(stg id {base})" = "$(git -C ../upstream rev-parse HEAD)" &&
+        test "$(cat file)" = "resolved"
+    )
+'
+
+test_expect_success 'No pull in progress' '
+    (
+        cd clone &&
+        command_error stg pull --continue 2>err &&
+        grep -e "no pull in progress" err &&
+        command_error stg pull --abort 2>err &&
+        grep -e "no pull in progress" err
+    )
+'
+
+test_expect_success 'Abort restores the stack and base' '
+    (
+        cd upstream &&
+        echo upstream2 >file &&
+        git commit -a -m u2
+    ) &&
+    (
+        cd clone &&
+        old_base=$(stg id {base}) &&
+        old_c1=$(stg id c1) &&
+        old_c2=$(stg id c2) &&
+        conflict stg pull &&
+        stg pull --abort &&
+        test_path_is_missing .git/stgit-pull &&
+        test "$(echo $(stg series --applied --noprefix))" = "c1 c2" &&
+        test "$(stg id {base})" = "$old_base" &&
+        test "$(stg id c1)" = "$old_c1" &&
+        test "$(stg id c2)" = "$old_c2" &&
+        test "$(cat file)" = "resolved" &&
+        git diff-index --quiet HEAD
+    )
+'
+
+test_expect_success 'Pull onto the already-fetched upstream' '
+    git -C clone fetch origin &&
+    test_config -C clone stgit.fetchcmd false &&
+    (
+        cd clone &&
+        conflict stg pull --no-fetch &&
+        test "$(stg id {base})" = "$(git -C ../upstream rev-parse HEAD)" &&
+        stg pull --abort
+    )
+'
+
+test_expect_success 'Pull without fetching needs an upstream' '
+    (
+        cd clone &&
+        git config --unset branch.master.merge &&
+        command_error stg pull --no-fetch 2>err &&
+        grep -e "no upstream is configured for branch .master." err &&
+        test_path_is_missing .git/stgit-pull &&
+        test "$(echo $(stg series --applied --noprefix))" = "c1 c2"
+    )
+'
+
+test_done
@@ -0,0 +1,140 @@
+#!/bin/sh
+
+test_description='Test stg pull --autostash'
+
+. ./test-lib.sh
+
+test_expect_success 'Fork stack off parent branch' '
+    printf "%s\n" out err rebase-check rebase-ls upstream >>.git/info/exclude &&
+    write_script rebase-check <<-\EOF &&
+	ls >rebase-ls &&
+	git reset --hard "$1"
+	EOF
+    test_commit_bulk --filename="file%s" --contents="base" 2 &&
+    git branch -m master parent &&
+    stg init &&
+    stg branch --create stack &&
+    git config branch.stack.stgit.pull-policy rebase &&
+    stg new -m p1 &&
+    echo p1 >>file1 &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo p2 >file3 &&
+    stg add file3 &&
+    stg refresh &&
+    git worktree add upstream parent &&
+    (
+        cd upstream &&
+        echo upstream >file4 &&
+        git add file4 &&
+        git commit -m upstream
+    )
+'
+
+test_expect_success 'Dirty work tree prevents pull' '
+    echo dirty >>file3 &&
+    command_error stg pull 2>err &&
+    grep -e "worktree not clean" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Pull with --autostash' '
+    stg pull --autostash &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test_path_is_file file4 &&
+    git diff --name-only >out &&
+    test "$(cat out)" = "file3" &&
+    test "$(tail -n 1 file3)" = "dirty" &&
+    test -z "$(git stash list)" &&
+    test_must_fail git rev-parse --verify -q refs/stgit/autostash/stack
+'
+
+test_expect_success 'Autostash with untracked files from config' '
+    test_config stgit.autostash true &&
+    test_config stgit.autostash.includeUntracked true &&
+    test_config stgit.rebasecmd ./rebase-check &&
+    echo untracked >untracked.txt &&
+    (
+        cd upstream &&
+        echo upstream2 >>file4 &&
+        git commit -am upstream2
+    ) &&
+    stg pull &&
+    ! grep -e "untracked.txt" rebase-ls &&
+    test "$(cat untracked.txt)" = "untracked" &&
+    test "$(tail -n 1 file3)" = "dirty" &&
+    test -z "$(git stash list)"
+'
+
+test_expect_success 'Autostash with merged patches' '
+    rm untracked.txt &&
+    (
+        cd upstream &&
+        echo p1 >>file1 &&
+        git commit -am "p1 upstream"
+    ) &&
+    stg pull --autostash --merged >out 2>&1 &&
+    grep -e "p1 (merged)" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test "$(git rev-parse "$(stg id p1)^{tree}")" = "$(git rev-parse "$(stg id p1)~^{tree}")" &&
+    test "$(tail -n 1 file3)" = "dirty" &&
+    test -z "$(git stash list)"
+'
+
+test_expect_success 'Reapplying conflicting changes keeps them stashed' '
+    stg delete p1 &&
+    git checkout file3 &&
+    echo local >file4 &&
+    (
+        cd upstream &&
+        echo upstream3 >file4 &&
+        git commit -am upstream3
+    ) &&
+    conflict stg pull --autostash 2>err &&
+    grep -e "reapplying autostash resulted in conflicts" err &&
+    grep -e "your changes are safe in .stash@{0}." err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p2" &&
+    git stash list >out &&
+    grep -e "stgit: autostash on stack" out &&
+    test_must_fail git rev-parse --verify -q refs/stgit/autostash/stack &&
+    git checkout HEAD file4 &&
+    git stash show -p >out &&
+    grep -e "^+local" out &&
+    git stash drop
+'
+
+test_expect_success 'Conflicting push back keeps changes stashed until continued' '
+    echo local >file1 &&
+    (
+        cd upstream &&
+        echo upstream >file3 &&
+        git add file3 &&
+        git commit -m "add file3"
+    ) &&
+    conflict stg pull --autostash 2>err &&
+    grep -e "stg pull --continue" err &&
+    git rev-parse --verify -q refs/stgit/autostash/stack &&
+    ! grep -e local file1 &&
+    echo resolved >file3 &&
+    stg add file3 &&
+    stg refresh &&
+    stg pull --continue &&
+    test "$(cat file1)" = "local" &&
+    test_must_fail git rev-parse --verify -q refs/stgit/autostash/stack
+'
+
+test_expect_success 'Aborted pull reapplies autostashed changes' '
+    (
+        cd upstream &&
+        echo upstream2 >file3 &&
+        git commit -am "change file3"
+    ) &&
+    conflict stg pull --autostash &&
+    ! grep -e local file1 &&
+    stg pull --abort &&
+    test "$(cat file1)" = "local" &&
+    test "$(cat file3)" = "resolved" &&
+    test_must_fail git rev-parse --verify -q refs/stgit/autostash/stack
+'
+
+test_done
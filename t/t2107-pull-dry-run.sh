@@ -0,0 +1,84 @@
+#!/bin/sh
+
+test_description='Test predicting the outcome of stg pull with --dry-run'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup upstream repo, clone it, and add patches to the clone' '
+    test_create_repo upstream &&
+    (
+        cd upstream &&
+        echo base >file &&
+        git add file &&
+        git commit -m base &&
+        stg init
+    ) &&
+    git clone upstream clone &&
+    (
+        cd clone &&
+        git config branch.master.stgit.pull-policy fetch-rebase &&
+        stg init &&
+        stg new -m c1 c1 &&
+        echo clone >file &&
+        stg refresh &&
+        stg new -m c2 c2 &&
+        echo c2 >file2 &&
+        stg add file2 &&
+        stg refresh
+    )
+'
+
+test_expect_success 'Dry run fetches and predicts clean pushes' '
+    (
+        cd upstream &&
+        echo other >other &&
+        git add other &&
+        git commit -m u1
+    ) &&
+    (
+        cd clone &&
+        old_base=$(stg id {base}) &&
+        stg pull --dry-run 2>err &&
+        grep -e "^c1  *clean$" err &&
+        grep -e "^c2  *clean$" err &&
+        grep -e "^Dry run: no changes were made$" err &&
+        test "$(git rev-parse FETCH_HEAD)" = "$(git -C ../upstream rev-parse HEAD)" &&
+        test "$(stg id {base})" = "$old_base" &&
+        test_path_is_missing .git/stgit-pull &&
+        test "$(echo $(stg series --applied --noprefix))" = "c1 c2"
+    )
+'
+
+test_expect_success 'Dry run without fetching predicts conflicts' '
+    (
+        cd upstream &&
+        echo upstream >file &&
+        git commit -a -m u2
+    ) &&
+    git -C clone fetch origin &&
+    test_config -C clone stgit.fetchcmd false &&
+    (
+        cd clone &&
+        old_c1=$(stg id c1) &&
+        conflict stg pull --dry-run --no-fetch 2>err &&
+        grep -e "^c1  *conflicts (file)$" err &&
+        grep -e "^c2  *clean$" err &&
+        grep -e "1 patch predicted to conflict" err &&
+        test "$(stg id c1)" = "$old_c1" &&
+        test "$(cat file)" = "clone" &&
+        git diff-index --quiet HEAD &&
+        test_path_is_missing .git/stgit-pull
+    )
+'
+
+test_expect_success 'Dry run conflicts with continue and abort' '
+    (
+        cd clone &&
+        general_error stg pull --dry-run --continue 2>err &&
+        grep -e "cannot be used with" err &&
+        general_error stg pull --dry-run --abort 2>err &&
+        grep -e "cannot be used with" err
+    )
+'
+
+test_done
@@ -0,0 +1,100 @@
+#!/bin/sh
+
+test_description='Inspect fetched remote stacks with --remote'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup upstream with patches' '
+    test_create_repo upstream &&
+    (
+        cd upstream &&
+        test_commit_bulk --message="patch %s" --filename=foo.txt --contents="line %s" 3 &&
+        stg uncommit -n 3 &&
+        stg pop &&
+        git branch nostack HEAD
+    )
+'
+
+test_expect_success 'Fetch remote branches and stack state' '
+    test_create_repo cloned &&
+    (
+        cd cloned &&
+        git remote add upstream ../upstream &&
+        git fetch upstream &&
+        git fetch upstream "+refs/stacks/*:refs/remote-stacks/upstream/*" &&
+        git checkout -b local upstream/nostack
+    )
+'
+
+test_expect_success 'Series of remote stack' '
+    (
+        cd cloned &&
+        stg series --remote upstream/master >out &&
+        cat >expected <<-\EOF &&
+	+ patch-1
+	> patch-2
+	- patch-3
+	EOF
+        test_cmp expected out &&
+        test "$(echo $(stg series --remote upstream/master --unapplied --noprefix))" = "patch-3" &&
+        test "$(git for-each-ref refs/stacks refs/patches)" = ""
+    )
+'
+
+test_expect_success 'Show and files of remote stack patches' '
+    (
+        cd cloned &&
+        stg show --remote upstream/master patch-3 >out &&
+        grep -e "patch 3" out &&
+        grep -e "^+line 3" out &&
+        test "$(stg files --remote upstream/master patch-1)" = "A foo.txt" &&
+        test "$(stg files --remote upstream/master)" = "M foo.txt"
+    )
+'
+
+test_expect_success 'Export remote stack' '
+    (
+        cd cloned &&
+        stg export --remote upstream/master --dir export &&
+        test_path_is_file export/series &&
+        test "$(echo $(grep -v "^#" export/series))" = "patch-1 patch-2" &&
+        test_path_is_file export/patch-2
+    )
+'
+
+test_expect_success 'Format email from remote stack' '
+    (
+        cd cloned &&
+        stg email format --remote upstream/master -o mail patch-1..patch-2 &&
+        test_path_is_file mail/0001-patch-1.patch &&
+        test_path_is_file mail/0002-patch-2.patch
+    )
+'
+
+test_expect_success 'Remote branch without stack metadata' '
+    (
+        cd cloned &&
+        command_error stg series --remote upstream/nostack 2>err &&
+        grep -e "remote branch \`upstream/nostack\` has no stack metadata fetched" err
+    )
+'
+
+test_expect_success 'No such remote branch' '
+    (
+        cd cloned &&
+        command_error stg series --remote upstream/bogus 2>err &&
+        grep -e "no such remote branch \`upstream/bogus\`" err &&
+        command_error stg files --remote upstream/bogus 2>err &&
+        grep -e "no such remote branch \`upstream/bogus\`" err
+    )
+'
+
+test_expect_success 'Remote conflicts with branch' '
+    (
+        cd cloned &&
+        general_error stg series --remote upstream/master --branch local 2>err &&
+        grep -e "cannot be used with" err
+    )
+'
+
+test_done
@@ -61,4 +61,57 @@ test_expect_success 'Rebase without argument' '
     grep bar file2
 '
 
+test_expect_success 'Setup patch applied upstream with modifications' '
+    stg new q1 -m "Add baz file" &&
+    echo baz >file3 &&
+    stg add file3 &&
+    stg refresh &&
+    git checkout master &&
+    echo "baz, edited" >file3 &&
+    git add file3 &&
+    git commit -m "[PATCH] add  baz file." &&
+    git checkout stack
+'
+
+test_expect_success 'Rebase with subject merged detection' '
+    stg rebase --merged=subject master 2>out &&
+    grep -e "Patch q1 matches subject of upstream commit $(git rev-parse master)" out &&
+    grep -e "q1 (merged)" out &&
+    test -z "$(stg files q1)" &&
+    test "$(cat file3)" = "baz, edited"
+'
+
+test_expect_success 'Return to base prior to upstream commit' '
+    stg undo --hard &&
+    stg rebase --nopush master~1 &&
+    stg push --all &&
+    test "$(cat file3)" = "baz"
+'
+
+test_expect_success 'Rebase with exact merged detection' '
+    conflict stg rebase --merged=exact master &&
+    stg undo --hard &&
+    stg rebase --nopush master~1 &&
+    stg push --all
+'
+
+test_expect_success 'Exclude patch from subject merged detection' '
+    conflict stg rebase --merged=subject --not-merged q1 master &&
+    stg undo --hard &&
+    stg rebase --nopush master~1 &&
+    stg push --all
+'
+
+test_expect_success 'Subject merged detection from config' '
+    test_config stgit.mergeddetect subject &&
+    stg rebase --merged master 2>out &&
+    grep -e "q1 (merged)" out &&
+    test -z "$(stg files q1)"
+'
+
+test_expect_success 'Invalid merged detection mode' '
+    general_error stg rebase --merged=fuzzy master 2>err &&
+    grep -e "invalid value .fuzzy. for .--merged" err
+'
+
 test_done
@@ -50,7 +50,48 @@ test_expect_success 'rebase --autostash throws helpful error message in conflict
     stg rebase master &&
     echo baz >file2 &&
     conflict stg rebase master~1 --interactive --autostash 2>err &&
-    grep -e "stash pop resulted in conflicts" err
+    grep -e "reapplying autostash resulted in conflicts" err &&
+    git stash list >out &&
+    grep -e "stgit: autostash on stack" out
+'
+
+test_expect_success 'Conflicting push back keeps changes stashed until continued' '
+    git reset --hard &&
+    git stash drop &&
+    stg push p &&
+    stg branch master &&
+    echo upstream >file1 &&
+    git commit -am upstream &&
+    stg branch stack &&
+    echo local >file3 &&
+    git add file3 &&
+    conflict stg rebase --autostash master 2>err &&
+    grep -e "stg push --continue" err &&
+    git rev-parse --verify -q refs/stgit/autostash/stack &&
+    test_path_is_missing file3 &&
+    echo resolved >file1 &&
+    stg add file1 &&
+    stg push --continue &&
+    test "$(cat file3)" = "local" &&
+    test "$(cat file1)" = "resolved" &&
+    test_must_fail git rev-parse --verify -q refs/stgit/autostash/stack
+'
+
+test_expect_success 'Undo of conflicting push back reapplies stashed changes' '
+    conflict stg rebase --autostash master~1 &&
+    test_path_is_missing file3 &&
+    stg undo --hard &&
+    test "$(cat file3)" = "local" &&
+    test_must_fail git rev-parse --verify -q refs/stgit/autostash/stack &&
+    test -z "$(git stash list)"
+'
+
+test_expect_success 'Pending autostash prevents another autostash' '
+    test_when_finished "git update-ref -d refs/stgit/autostash/stack" &&
+    git update-ref refs/stgit/autostash/stack HEAD &&
+    command_error stg rebase --autostash master 2>err &&
+    grep -e "still pending in .refs/stgit/autostash/stack." err &&
+    test "$(cat file3)" = "local"
 '
 
 test_done
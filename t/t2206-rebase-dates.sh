@@ -0,0 +1,109 @@
+#!/bin/sh
+
+test_description='Test commit dates of patches rewritten by rebase and pull'
+
+. ./test-lib.sh
+
+author_time () {
+    git log -1 --pretty=format:%at "$(stg id "$1")"
+}
+
+committer_time () {
+    git log -1 --pretty=format:%ct "$(stg id "$1")"
+}
+
+test_expect_success 'Setup a branch with a stack' '
+    test_commit base &&
+    test_tick &&
+    stg branch --create stack &&
+    test_tick &&
+    stg new -m p1 &&
+    echo one >p1.txt &&
+    stg add p1.txt &&
+    stg refresh &&
+    test_tick &&
+    stg new -m p2 &&
+    echo two >p2.txt &&
+    stg add p2.txt &&
+    stg refresh &&
+    author_time p1 >p1-atime &&
+    author_time p2 >p2-atime &&
+    git checkout master &&
+    test_tick &&
+    test_commit upstream1 &&
+    git checkout stack
+'
+
+test_expect_success 'Rebase onto the same base keeps commit ids' '
+    stg id p1 >p1-before &&
+    stg id p2 >p2-before &&
+    test_tick &&
+    stg rebase --committer-date-is-author-date master~1 &&
+    test "$(stg id p1)" = "$(cat p1-before)" &&
+    test "$(stg id p2)" = "$(cat p2-before)" &&
+    test_tick &&
+    stg rebase --ignore-date master~1 &&
+    test "$(stg id p1)" = "$(cat p1-before)" &&
+    test "$(stg id p2)" = "$(cat p2-before)"
+'
+
+test_expect_success 'Rebase with --committer-date-is-author-date' '
+    test_tick &&
+    stg rebase --committer-date-is-author-date master &&
+    test "$(stg id {base})" = "$(git rev-parse master)" &&
+    test "$(author_time p1)" -eq "$(cat p1-atime)" &&
+    test "$(author_time p2)" -eq "$(cat p2-atime)" &&
+    test "$(committer_time p1)" -eq "$(cat p1-atime)" &&
+    test "$(committer_time p2)" -eq "$(cat p2-atime)"
+'
+
+test_expect_success 'Rebase with --ignore-date' '
+    test_tick &&
+    stg rebase --ignore-date master~1 &&
+    test "$(author_time p1)" -eq "$test_tick" &&
+    test "$(author_time p2)" -eq "$test_tick" &&
+    test "$(committer_time p1)" -eq "$test_tick" &&
+    test "$(committer_time p2)" -eq "$test_tick"
+'
+
+test_expect_success 'Rebase without date options' '
+    author_time p1 >p1-atime &&
+    test_tick &&
+    stg rebase master &&
+    test "$(author_time p1)" -eq "$(cat p1-atime)" &&
+    test "$(committer_time p1)" -eq "$test_tick"
+'
+
+test_expect_success 'Pull with --committer-date-is-author-date' '
+    test_config branch.stack.remote . &&
+    test_config branch.stack.merge refs/heads/master &&
+    test_config branch.stack.stgit.pull-policy rebase &&
+    git checkout master &&
+    test_tick &&
+    test_commit upstream2 &&
+    git checkout stack &&
+    author_time p1 >p1-atime &&
+    author_time p2 >p2-atime &&
+    test_tick &&
+    stg pull --committer-date-is-author-date &&
+    test "$(stg id {base})" = "$(git rev-parse master)" &&
+    test "$(committer_time p1)" -eq "$(cat p1-atime)" &&
+    test "$(committer_time p2)" -eq "$(cat p2-atime)"
+'
+
+test_expect_success 'Pull with --ignore-date' '
+    test_config branch.stack.remote . &&
+    test_config branch.stack.merge refs/heads/master &&
+    test_config branch.stack.stgit.pull-policy rebase &&
+    git checkout master &&
+    test_tick &&
+    test_commit upstream3 &&
+    git checkout stack &&
+    test_tick &&
+    stg pull --ignore-date &&
+    test "$(stg id {base})" = "$(git rev-parse master)" &&
+    test "$(author_time p1)" -eq "$test_tick" &&
+    test "$(author_time p2)" -eq "$test_tick"
+'
+
+test_done
@@ -0,0 +1,83 @@
+#!/bin/sh
+
+test_description='Test stg base'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack with rewritten base' '
+    test_commit_bulk --message="base %s" 2 &&
+    stg init &&
+    for i in 1 2; do
+        stg new -m "patch $i" "p$i" &&
+        echo "p$i" >"p$i.txt" &&
+        stg add "p$i.txt" &&
+        stg refresh || return 1
+    done &&
+    rewritten=$(git commit-tree -p HEAD~3 -m "rewritten base 2" "$(stg id {base})^{tree}") &&
+    git tag rewritten "$rewritten" &&
+    git tag old-base "$(stg id {base})" &&
+    echo "changed" >>1.t &&
+    git add 1.t &&
+    different=$(git commit-tree -p HEAD~3 -m "different base" "$(git write-tree)") &&
+    git reset --hard &&
+    git tag different "$different" &&
+    printf "%s\n" out err >>.git/info/exclude
+'
+
+test_expect_success 'Print stack base' '
+    stg base >out &&
+    test "$(cat out)" = "$(git rev-parse old-base) base 2"
+'
+
+test_expect_success 'Set base with identical tree' '
+    top_tree=$(git rev-parse HEAD^{tree}) &&
+    stg base --set rewritten &&
+    test "$(stg id {base})" = "$(git rev-parse rewritten)" &&
+    test "$(git rev-parse $(stg id p1)^)" = "$(git rev-parse rewritten)" &&
+    test "$(git rev-parse HEAD^{tree})" = "$top_tree" &&
+    test "$(echo $(stg series))" = "+ p1 > p2" &&
+    stg base >out &&
+    test "$(cat out)" = "$(git rev-parse rewritten) rewritten base 2"
+'
+
+test_expect_success 'Setting base is one undoable step' '
+    stg undo &&
+    test "$(stg id {base})" = "$(git rev-parse old-base)" &&
+    test "$(echo $(stg series))" = "+ p1 > p2"
+'
+
+test_expect_success 'Refuse base with different tree when patches are applied' '
+    command_error stg base --set different 2>err &&
+    grep -e "tree of \`different\` differs from the tree of the stack base" err &&
+    grep -e "use \`stg rebase\`" err &&
+    test "$(stg id {base})" = "$(git rev-parse old-base)"
+'
+
+test_expect_success 'Force base with different tree' '
+    top_tree=$(git rev-parse HEAD^{tree}) &&
+    stg base --set different --force &&
+    test "$(stg id {base})" = "$(git rev-parse different)" &&
+    test "$(git rev-parse HEAD^{tree})" = "$top_tree" &&
+    test "$(echo $(stg series))" = "+ p1 > p2" &&
+    stg undo
+'
+
+test_expect_success 'Set base with different tree without applied patches' '
+    stg pop -a &&
+    stg base --set different &&
+    test "$(git rev-parse HEAD)" = "$(git rev-parse different)" &&
+    grep -e "changed" 1.t &&
+    test "$(echo $(stg series))" = "- p1 - p2"
+'
+
+test_expect_success 'Setting base to current base is a no-op' '
+    stg base --set different &&
+    test "$(git rev-parse HEAD)" = "$(git rev-parse different)"
+'
+
+test_expect_success 'Force requires set' '
+    general_error stg base --force 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_done
@@ -0,0 +1,132 @@
+#!/bin/sh
+
+test_description='Test rebasing stacked branches with "stg rebase --chain"'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stacked branches' '
+    echo base >base.txt &&
+    git add base.txt &&
+    git commit -m base &&
+
+    stg branch --create A &&
+    stg new a1 -m a1 &&
+    echo a1 >a.txt &&
+    stg add a.txt &&
+    stg refresh &&
+
+    stg branch --create B A &&
+    git config branch.B.stgit.stackedon A &&
+    stg new b1 -m b1 &&
+    echo b1 >b.txt &&
+    stg add b.txt &&
+    stg refresh &&
+
+    stg branch --create C B &&
+    git config branch.C.stgit.stackedon B &&
+    stg new c1 -m c1 &&
+    echo c1 >c.txt &&
+    stg add c.txt &&
+    stg refresh &&
+
+    stg branch --create D A &&
+    git config branch.D.stgit.stackedon A &&
+    stg new d1 -m d1 &&
+    echo d1 >d.txt &&
+    stg add d.txt &&
+    stg refresh &&
+
+    stg branch A
+'
+
+test_expect_success 'Rebase chain after reworking the base branch' '
+    stg new a2 -m a2 &&
+    echo a2 >>a.txt &&
+    stg refresh &&
+    stg rebase --chain master 2>err &&
+    grep -e "Rebasing stacked branch .B. onto .A." err &&
+    grep -e "Rebasing stacked branch .C. onto .B." err &&
+    grep -e "Rebasing stacked branch .D. onto .A." err &&
+    test "$(git symbolic-ref --short HEAD)" = "A" &&
+    test "$(stg id B:{base})" = "$(stg id A:a2)" &&
+    test "$(stg id C:{base})" = "$(stg id B:b1)" &&
+    test "$(stg id D:{base})" = "$(stg id A:a2)" &&
+    test "$(stg series -b B --noprefix)" = "b1" &&
+    test "$(stg series -b C --noprefix)" = "c1" &&
+    test "$(stg series -b D --noprefix)" = "d1"
+'
+
+test_expect_success 'Rebase chain onto a new upstream commit' '
+    stg branch master &&
+    echo more >>base.txt &&
+    git commit -a -m more &&
+    stg branch A &&
+    stg rebase --chain master &&
+    test "$(stg id A:{base})" = "$(git rev-parse master)" &&
+    test "$(stg id B:{base})" = "$(stg id A:a2)" &&
+    test "$(stg id C:{base})" = "$(stg id B:b1)" &&
+    test "$(stg id D:{base})" = "$(stg id A:a2)" &&
+    test "$(git show C:base.txt | tail -n 1)" = "more"
+'
+
+test_expect_success 'Rebase without committish uses the stacked-on branch' '
+    stg new a3 -m a3 &&
+    echo a3 >>a.txt &&
+    stg refresh &&
+    stg branch B &&
+    stg rebase &&
+    test "$(stg id B:{base})" = "$(stg id A:a3)" &&
+    test "$(stg id C:{base})" != "$(stg id B:b1)" &&
+    stg rebase --chain &&
+    test "$(git symbolic-ref --short HEAD)" = "B" &&
+    test "$(stg id C:{base})" = "$(stg id B:b1)" &&
+    stg branch A
+'
+
+test_expect_success 'Chain stops at the first conflict with resume instructions' '
+    stg branch B &&
+    stg new b2 -m b2 &&
+    echo b2 >>a.txt &&
+    stg refresh &&
+    stg branch A &&
+    stg new a4 -m a4 &&
+    echo a4 >>a.txt &&
+    stg refresh &&
+    conflict stg rebase --chain master 2>err &&
+    test "$(git symbolic-ref --short HEAD)" = "B" &&
+    grep -e "run .stg rebase --chain. to rebase the branches stacked on .B." err &&
+    grep -e "run .stg rebase --chain. on each of them to resume:" err &&
+    grep -e "^    D$" err &&
+    test "$(stg id C:{base})" != "$(stg id B:b1)"
+'
+
+test_expect_success 'Resume the chain for each branch' '
+    printf "a1\na2\na3\na4\nb2\n" >a.txt &&
+    stg add --update &&
+    stg refresh &&
+    stg rebase --chain &&
+    test "$(git symbolic-ref --short HEAD)" = "B" &&
+    test "$(stg id C:{base})" = "$(stg id B:b2)" &&
+    stg branch D &&
+    stg rebase --chain &&
+    test "$(stg id D:{base})" = "$(stg id A:a4)" &&
+    stg branch A
+'
+
+test_expect_success 'Chain requires a clean worktree' '
+    echo dirty >>base.txt &&
+    command_error stg rebase --chain master 2>err &&
+    git checkout base.txt
+'
+
+test_expect_success 'Chain may not be combined with --nopush' '
+    general_error stg rebase --chain --nopush master
+'
+
+test_expect_success 'Renaming a branch updates the stacked branches' '
+    stg branch --rename A A2 &&
+    test "$(git config branch.B.stgit.stackedon)" = "A2" &&
+    test "$(git config branch.D.stgit.stackedon)" = "A2"
+'
+
+test_done
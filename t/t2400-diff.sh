@@ -185,4 +185,18 @@ test_expect_success 'Binary diff range with --binary' '
     test_cmp num-binary.diff num-binary2.diff
 '
 
+test_expect_success 'Diff range of another branch' '
+    stg branch --clone other &&
+    stg branch master &&
+    stg diff -r {base}..@ >base-top.diff &&
+    stg diff -r other:{base}..other:@ >other-base-top.diff &&
+    test_cmp base-top.diff other-base-top.diff &&
+    stg diff -r other:{base}..@ >other-base-top.diff &&
+    test_cmp base-top.diff other-base-top.diff &&
+    stg diff -r other:{base}+1..other:@~1 --stat >other-range-stat.diff &&
+    stg diff -r {base}+1..~1 --stat >range-stat.diff &&
+    test_cmp range-stat.diff other-range-stat.diff &&
+    stg branch --delete --force other
+'
+
 test_done
@@ -0,0 +1,107 @@
+#!/bin/sh
+
+test_description='Test stg foreach'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    stg init &&
+    for i in 0 1 2 3; do
+        echo "line $i" >f$i.txt &&
+        stg add f$i.txt &&
+        stg new -rm "p$i" || return 1
+    done &&
+    stg pop p3
+'
+
+test_expect_success 'Exec is required' '
+    general_error stg foreach 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_expect_success 'Run command on all applied patches' '
+    stg foreach --exec "for f in f*.txt; do sed \"s/line/LINE/\" \$f >\$f.tmp && mv \$f.tmp \$f; done" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2" &&
+    test "$(echo $(stg top))" = "p2" &&
+    for i in 0 1 2; do
+        test "$(git show $(stg id p$i):f$i.txt)" = "LINE $i" || return 1
+    done &&
+    test "$(git show $(stg id p3):f3.txt)" = "line 3" &&
+    test "$(git log -1 --format=%B $(stg id p1))" = "p1"
+'
+
+test_expect_success 'Unchanged patches are left untouched' '
+    p0_id=$(stg id p0) &&
+    p1_id=$(stg id p1) &&
+    stg foreach -x "test ! -f f1.txt || echo more >>f1.txt" p0 p1 &&
+    test "$(stg id p0)" = "$p0_id" &&
+    test "$(stg id p1)" != "$p1_id" &&
+    test "$(git show $(stg id p1):f1.txt | tail -n 1)" = "more" &&
+    test "$(echo $(stg top))" = "p2"
+'
+
+test_expect_success 'Visit unapplied patch and restore top' '
+    stg foreach -x "echo more >>f3.txt" p3 &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2" &&
+    test "$(git show $(stg id p3):f3.txt | tail -n 1)" = "more"
+'
+
+test_expect_success 'Deleted files are refreshed' '
+    stg foreach -x "git rm -q f2.txt" p2 &&
+    test "$(echo $(stg files p2))" = ""
+'
+
+test_expect_success 'Update only refreshes files of each patch' '
+    command_error stg foreach -u -x "echo extra >>f0.txt && echo more >>f1.txt" p1 2>err &&
+    grep -e "error: changes remain after refreshing patch .p1." err &&
+    grep -e "stg foreach --continue" err &&
+    test "$(echo $(stg top))" = "p1" &&
+    test "$(git show $(stg id p1):f1.txt | tail -n 1)" = "more" &&
+    git checkout f0.txt &&
+    stg foreach --continue &&
+    test "$(echo $(stg top))" = "p2"
+'
+
+test_expect_success 'Failing command stops at patch' '
+    command_error stg foreach -x "case \$(stg top) in p0) echo fix >>f0.txt;; p1) false;; esac" 2>err &&
+    grep -e "error: .*exited with code 1 on patch .p1." err &&
+    test "$(echo $(stg top))" = "p1" &&
+    test "$(git show $(stg id p0):f0.txt | tail -n 1)" = "fix" &&
+    command_error stg foreach -x true 2>err &&
+    grep -e "a foreach is already in progress" err
+'
+
+test_expect_success 'Continue after failure' '
+    echo "manual fix" >>f1.txt &&
+    stg foreach --continue &&
+    test "$(echo $(stg top))" = "p2" &&
+    test "$(git show $(stg id p1):f1.txt | tail -n 1)" = "manual fix" &&
+    test "$(stg files p2)" = "" &&
+    command_error stg foreach --continue 2>err &&
+    grep -e "no foreach in progress" err
+'
+
+test_expect_success 'Quit a stopped foreach' '
+    command_error stg foreach -x false 2>err &&
+    test "$(echo $(stg top))" = "p0" &&
+    stg foreach --quit &&
+    test "$(echo $(stg top))" = "p0" &&
+    command_error stg foreach --quit 2>err &&
+    grep -e "no foreach in progress" err &&
+    stg goto p2
+'
+
+test_expect_success 'Stop at conflicting push and continue' '
+    stg foreach -x "test ! -f f1.txt || echo conflicting >>f3.txt && git add f3.txt" p1 &&
+    test "$(echo $(stg files p1))" = "A f1.txt A f3.txt" &&
+    conflict stg foreach -x true p3 2>err &&
+    grep -e "stg foreach --continue" err &&
+    test "$(echo $(stg top))" = "p3" &&
+    echo resolved >f3.txt &&
+    stg add f3.txt &&
+    stg refresh &&
+    stg foreach --continue &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2"
+'
+
+test_done
@@ -0,0 +1,112 @@
+#!/bin/sh
+
+test_description='Test diffstats of stg show and stg diff'
+
+. ./test-lib.sh
+
+test_expect_success 'Create patches' '
+    printf "%s\n" out err expected >>.git/info/exclude &&
+    stg init &&
+    mkdir -p src/patch doc &&
+    stg new -m p1 p1 &&
+    echo "a" >src/patch/a.rs &&
+    echo "a" >doc/a.md &&
+    stg add src doc &&
+    stg refresh &&
+    stg new -m p2 p2 &&
+    printf "b\nb\n" >>src/patch/a.rs &&
+    echo "b" >doc/b.md &&
+    stg add src doc &&
+    stg refresh &&
+    stg new -m p3 p3 &&
+    printf "\000\001\002" >src/patch/c.bin &&
+    echo "c" >src/main.rs &&
+    stg add src &&
+    stg refresh &&
+    stg new -m p4 p4 &&
+    echo "d" >>doc/a.md &&
+    stg refresh
+'
+
+test_expect_success 'Numstat for each patch of range' '
+    stg show --numstat p2..p3 >out &&
+    test $(grep -c -e "^commit " out) = "2" &&
+    grep -E "^2	0	src/patch/a\.rs$" out &&
+    grep -E "^1	0	doc/b\.md$" out &&
+    grep -E "^-	-	src/patch/c\.bin$" out &&
+    grep -E "^1	0	src/main\.rs$" out
+'
+
+test_expect_success 'Stat and numstat conflict' '
+    general_error stg show --stat --numstat p1 &&
+    general_error stg diff --stat --numstat
+'
+
+test_expect_success 'Stat for range limited by path' '
+    stg show --stat p1..p4 -- src/patch/ >out &&
+    test $(grep -c -e "^commit " out) = "3" &&
+    test $(grep -c -e " src/patch/a.rs |" out) = "2" &&
+    grep -e " src/patch/c.bin | Bin 0 -> 3 bytes" out &&
+    ! grep -e "doc/" out &&
+    ! grep -e "main.rs" out
+'
+
+test_expect_success 'Combined stat of range' '
+    stg show --combined-stat p2..p4 >out &&
+    cat >expected <<-\EOF &&
+	 doc/a.md        |   1 +
+	 doc/b.md        |   1 +
+	 src/main.rs     |   1 +
+	 src/patch/a.rs  |   2 ++
+	 src/patch/c.bin | Bin 0 -> 3 bytes
+	 5 files changed, 5 insertions(+)
+	 create mode 100644 doc/b.md
+	 create mode 100644 src/main.rs
+	 create mode 100644 src/patch/c.bin
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Combined numstat of range with pathspec magic' '
+    stg show --combined-stat --numstat p1..p3 -- ":(exclude)doc" >out &&
+    cat >expected <<-\EOF &&
+	1	0	src/main.rs
+	3	0	src/patch/a.rs
+	-	-	src/patch/c.bin
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Combined stat of single patch and default' '
+    stg show --combined-stat p4 >out &&
+    grep -e "^ doc/a.md | 1 +$" out &&
+    test_line_count = 2 out &&
+    stg show --combined-stat >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Combined stat of range with unapplied patches' '
+    stg pop p4 &&
+    stg show --combined-stat p3..p4 >out &&
+    grep -e "doc/a.md" out &&
+    grep -e "src/main.rs" out &&
+    ! grep -e "doc/b.md" out &&
+    stg push p4
+'
+
+test_expect_success 'Combined stat requires a single range' '
+    command_error stg show --combined-stat p1 p3 2>err &&
+    grep -e "--combined-stat. requires a single patch or range" err &&
+    general_error stg show --combined-stat -A &&
+    general_error stg show --combined-stat --stat p1
+'
+
+test_expect_success 'Diff numstat of range limited by path' '
+    stg diff --numstat -r p1..p3 -- src/ >out &&
+    grep -E "^-	-	src/patch/c\.bin$" out &&
+    grep -E "^2	0	src/patch/a\.rs$" out &&
+    grep -E "^1	0	src/main\.rs$" out &&
+    test_line_count = 3 out
+'
+
+test_done
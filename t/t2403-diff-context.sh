@@ -0,0 +1,105 @@
+#!/bin/sh
+
+test_description='Test the number of diff context lines'
+
+. ./test-lib.sh
+
+context_lines () { grep -c -e "^ [0-9]*$" "$1" ; }
+
+test_expect_success 'Create patch' '
+    printf "%s\n" out err saved mail >>.git/info/exclude &&
+    test_seq 1 20 >file &&
+    stg add file &&
+    git commit -m "Add file" &&
+    stg init &&
+    stg new -m p1 &&
+    sed "s/^10$/ten/" file >file.tmp &&
+    mv file.tmp file &&
+    stg refresh
+'
+
+test_expect_success 'Show with --unified' '
+    stg show >out &&
+    test "$(context_lines out)" = "6" &&
+    stg show --unified 1 >out &&
+    test "$(context_lines out)" = "2" &&
+    stg show --unified=0 >out &&
+    test "$(context_lines out)" = "0" &&
+    grep -e "^@@ -10 +10 @@" out
+'
+
+test_expect_success 'Show -U still selects unapplied patches' '
+    stg new -m p2 &&
+    stg pop &&
+    stg show -U >out &&
+    grep -e "^    p2$" out &&
+    ! grep -e "^    p1$" out &&
+    stg delete p2
+'
+
+test_expect_success 'Diff with -U' '
+    stg diff -r {base}..p1 -U 1 >out &&
+    test "$(context_lines out)" = "2" &&
+    stg diff -r {base}..p1 -U5 >out &&
+    test "$(context_lines out)" = "10"
+'
+
+test_expect_success 'Invalid number of context lines' '
+    general_error stg diff -U x 2>err &&
+    grep -e "is not a positive integer" err
+'
+
+test_expect_success 'Context lines from diff.context' '
+    test_config diff.context 1 &&
+    stg show >out &&
+    test "$(context_lines out)" = "2" &&
+    stg diff -r {base}..p1 >out &&
+    test "$(context_lines out)" = "2" &&
+    stg show --unified 2 >out &&
+    test "$(context_lines out)" = "4"
+'
+
+test_expect_success '-U takes precedence over -O' '
+    stg show -O -U2 --unified 0 >out &&
+    test "$(context_lines out)" = "0"
+'
+
+test_expect_success 'Export with -U0 and import' '
+    stg export -U 0 --stdout p1 >out &&
+    test "$(context_lines out)" = "0" &&
+    grep -e "^@@ -10 +10 @@" out &&
+    stg delete --top &&
+    test "$(stg series --noprefix)" = "" &&
+    stg import -n p1 out &&
+    test "$(stg series --noprefix)" = "p1" &&
+    grep -e "^ten$" file
+'
+
+test_expect_success 'Export with context from diff.context' '
+    test_config diff.context 0 &&
+    stg export --stdout p1 >out &&
+    test "$(context_lines out)" = "0"
+'
+
+test_expect_success 'Email format with -U' '
+    stg email format -U 1 -o mail p1 &&
+    test "$(context_lines mail/0001-p1.patch)" = "2" &&
+    rm -r mail
+'
+
+test_expect_success 'Edit diff without context' '
+    test_config diff.context 0 &&
+    write_script diffedit <<-\EOF &&
+	cp "$1" saved &&
+	sed "s/^+ten$/+TEN/" "$1" >"$1.tmp" &&
+	mv "$1.tmp" "$1"
+	EOF
+    EDITOR=./diffedit stg edit --diff p1 &&
+    grep -e "^@@ -10 +10 @@" saved &&
+    test "$(context_lines saved)" = "0" &&
+    grep -e "^TEN$" file &&
+    stg show p1 >out &&
+    grep -e "^+TEN$" out
+'
+
+test_done
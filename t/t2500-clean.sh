@@ -47,7 +47,7 @@ test_expect_success 'Selectively clean applied and unapplied patches' '
 
 test_expect_success 'Ensure hidden patches are not cleaned' '
     stg new e5 -m e5 &&
-    stg hide e5 &&
+    stg hide --pop e5 &&
     stg clean &&
     [ "$(echo $(stg series --applied --noprefix))" = "p0" ] &&
     [ "$(echo $(stg series --hidden --noprefix))" = "e5" ] &&
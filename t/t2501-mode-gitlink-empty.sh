@@ -0,0 +1,75 @@
+#!/bin/sh
+
+test_description='Test empty patch detection with mode-only and gitlink-only changes'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup upstream branch' '
+    echo foo >foo.txt &&
+    git add foo.txt &&
+    git commit -m base &&
+    git branch base &&
+    chmod +x foo.txt &&
+    git add foo.txt &&
+    git update-index --add --cacheinfo 160000,$(git rev-parse base),sub &&
+    git commit -m upstream &&
+    git branch upstream &&
+    git reset --hard base
+'
+
+test_expect_success 'Create mode-only and gitlink-only patches' '
+    stg init &&
+    stg new -m empty empty &&
+    stg new -m mode mode &&
+    chmod +x foo.txt &&
+    git add foo.txt &&
+    stg refresh --index &&
+    stg new -m gitlink gitlink &&
+    git update-index --add --cacheinfo 160000,$(git rev-parse base),sub &&
+    stg refresh --index &&
+    test "$(git diff --name-only HEAD~2 HEAD~1)" = "foo.txt" &&
+    test "$(git diff --name-only HEAD~1 HEAD)" = "sub"
+'
+
+test_expect_success 'Mode-only and gitlink-only patches are not shown as empty' '
+    stg series --empty >out &&
+    cat >expected <<-\EOF &&
+	*+ empty
+	 + mode
+	 > gitlink
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Clean keeps mode-only and gitlink-only patches' '
+    stg clean &&
+    test "$(echo $(stg series --applied --noprefix))" = "mode gitlink" &&
+    stg pop -a &&
+    stg clean &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "mode gitlink" &&
+    stg push -a
+'
+
+test_expect_success 'Commit mode-only and gitlink-only patches without --allow-empty' '
+    stg commit --all &&
+    test "$(echo $(stg series --noprefix))" = "" &&
+    test "$(git diff --name-only HEAD~2 HEAD~1)" = "foo.txt" &&
+    test "$(git diff --name-only HEAD~1 HEAD)" = "sub" &&
+    stg uncommit -n 2 &&
+    test "$(echo $(stg series --applied --noprefix))" = "mode gitlink"
+'
+
+test_expect_success 'Mode-only and gitlink-only patches merged upstream' '
+    stg rebase --merged upstream 2>out &&
+    grep -e "^Found 2 patches merged upstream$" out &&
+    stg series --empty >out &&
+    cat >expected <<-\EOF &&
+	*+ mode
+	*> gitlink
+	EOF
+    test_cmp expected out &&
+    stg clean &&
+    test "$(echo $(stg series --noprefix))" = ""
+'
+
+test_done
@@ -130,6 +130,37 @@ test_expect_success 'Empty commit message aborts the squash' '
     test "$(echo $(stg series))" = "+ p0 > q1"
 '
 
+test_expect_success 'Squashing hidden patches requires --unhide' '
+    echo "h1" >>baz.txt &&
+    stg new -rm "h1" &&
+    echo "h2" >>baz.txt &&
+    stg new -rm "h2" &&
+    stg pop h1 h2 &&
+    stg hide h1 h2 &&
+    command_error stg squash -m hq q1 h1 2>err &&
+    grep -e "cannot squash hidden patch .h1.; use .--unhide. to include it" err &&
+    command_error stg squash -m hq q1..h2 2>err &&
+    grep -e "cannot squash hidden patches .h1., .h2.; use .--unhide. to include them" err &&
+    command_error stg squash --into q1 h2 2>err &&
+    grep -e "cannot squash hidden patch .h2.; use .--unhide. to include it" err &&
+    test "$(echo $(stg series --all))" = "+ p0 > q1 ! h1 ! h2"
+'
+
+test_expect_success 'Squash hidden patches with --unhide' '
+    stg squash --unhide -n hq -m hq q1..h2 &&
+    test_when_finished "stg undo" &&
+    test "$(echo $(stg series --all))" = "+ p0 > hq" &&
+    test "$(tail -n 2 baz.txt)" = "$(printf "h1\nh2")"
+'
+
+test_expect_success 'Fold hidden patches into a hidden patch with --unhide' '
+    command_error stg squash --into h1 h2 2>err &&
+    grep -e "cannot fold into hidden patch .h1.; use .--unhide. to unhide it" err &&
+    stg squash --unhide --into h1 h2 &&
+    test_when_finished "stg undo" &&
+    test "$(echo $(stg series --all))" = "+ p0 > q1 - h1"
+'
+
 test_expect_success 'Squash with top != head' '
     write_script fake-editor <<-\EOF &&
 	#!/bin/sh
@@ -0,0 +1,94 @@
+#!/bin/sh
+
+test_description='Run "stg squash --into"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    stg init &&
+    for i in 0 1 2 3 4 5; do
+        echo "file $i" >f$i.txt &&
+        stg add f$i.txt &&
+        stg new -rm "p$i" || return 1
+    done
+'
+
+test_expect_success 'Fold requires adjacent patches' '
+    command_error stg squash --into p1 p3 2>err &&
+    grep -e "patches are not adjacent to \`p1\`; use --reorder" err &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2 p3 p4 p5"
+'
+
+test_expect_success 'Fold requires another patch' '
+    command_error stg squash --into p1 p1 2>err &&
+    grep -e "need at least one patch to fold into \`p1\`" err
+'
+
+test_expect_success 'Fold conflicts with --name' '
+    general_error stg squash --into p1 --name q1 p2 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Fold adjacent patch into existing patch' '
+    stg squash --into p1 p2 &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p3 p4 p5" &&
+    test "$(echo $(stg files p1))" = "A f1.txt A f2.txt" &&
+    test "$(git log -1 --format=%B $(stg id p1))" = "p1"
+'
+
+test_expect_success 'Fold non-adjacent patch with --reorder' '
+    stg squash --into p1 --reorder p4 &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p3 p5" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p3 p5" &&
+    test "$(echo $(stg files p1))" = "A f1.txt A f2.txt A f4.txt" &&
+    test "$(echo $(stg files p3))" = "A f3.txt"
+'
+
+test_expect_success 'Fold patch below the target' '
+    stg squash --into p5 --reorder p0 &&
+    test "$(echo $(stg series --noprefix))" = "p1 p3 p5" &&
+    test "$(echo $(stg files p5))" = "A f0.txt A f5.txt" &&
+    test "$(git log -1 --format=%B $(stg id p5))" = "p5" &&
+    test_path_is_file f0.txt
+'
+
+test_expect_success 'Fold into patch with edited message' '
+    write_script fake-editor <<-\EOF &&
+	sed "s/^p3$/p3 edited/" "$1" >"$1".tmp && mv "$1".tmp "$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg squash --into p3 -e p5 &&
+    test "$(echo $(stg series --noprefix))" = "p1 p3" &&
+    test "$(git log -1 --format=%B $(stg id p3))" = "p3 edited"
+'
+
+test_expect_success 'Fold into unapplied patch' '
+    for i in 6 7; do
+        echo "file $i" >f$i.txt &&
+        stg add f$i.txt &&
+        stg new -rm "p$i" || return 1
+    done &&
+    stg pop -n 2 &&
+    stg squash --into p6 p7 &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p6" &&
+    test "$(echo $(stg files p6))" = "A f6.txt A f7.txt"
+'
+
+test_expect_success 'Conflicts while folding do not delete patches' '
+    stg push p6 &&
+    echo 0 >g.txt &&
+    stg add g.txt &&
+    stg new -rm c0 &&
+    echo 1 >g.txt &&
+    stg new -rm c1 &&
+    echo 2 >g.txt &&
+    stg new -rm c2 &&
+    conflict stg squash --into c2 --reorder c0 2>err &&
+    grep -e "merge conflicts" err &&
+    test "$(echo $(stg series --noprefix))" = "p1 p3 p6 c2 c1 c0" &&
+    stg undo --hard &&
+    test "$(echo $(stg series --noprefix))" = "p1 p3 p6 c0 c1 c2"
+'
+
+test_done
@@ -0,0 +1,118 @@
+#!/bin/sh
+
+test_description='Test "stg new --fixup" and "stg squash --fixups"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    stg init &&
+    for i in 0 1 2; do
+        echo "file $i" >f$i.txt &&
+        stg add f$i.txt &&
+        stg new -rm "p$i" || return 1
+    done
+'
+
+test_expect_success 'Create fixup patches' '
+    echo "fix 1" >>f1.txt &&
+    stg new --fixup p1 -r &&
+    echo "fix 0" >>f0.txt &&
+    stg new --fixup p0 -r -m "adjust p0" &&
+    echo "more fix 1" >>f1.txt &&
+    stg new --fixup p1 -r &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2 fixup-1-p1 fixup-1-p0 fixup-2-p1" &&
+    test "$(git log -1 --format=%s $(stg id fixup-1-p1))" = "fixup! p1" &&
+    test "$(git log -1 --format=%s $(stg id fixup-1-p0))" = "adjust p0" &&
+    test "$(git log -1 --format="%(trailers:key=Stg-Fixup,valueonly)" $(stg id fixup-2-p1))" = "p1"
+'
+
+test_expect_success 'Fixup conflicts with patch name' '
+    general_error stg new --fixup p1 q1 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Fixup of unknown patch' '
+    command_error stg new --fixup nope 2>err &&
+    grep -e "patch \`nope\` does not exist" err
+'
+
+test_expect_success 'Squash fixups into their targets' '
+    stg squash --fixups &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2" &&
+    test "$(git log -1 --format=%B $(stg id p0))" = "p0" &&
+    test "$(git log -1 --format=%B $(stg id p1))" = "p1" &&
+    test "$(echo $(stg files p1))" = "A f1.txt" &&
+    git show $(stg id p1):f1.txt >f1-content &&
+    printf "file 1\nfix 1\nmore fix 1\n" >expected &&
+    test_cmp expected f1-content &&
+    git show $(stg id p0):f0.txt >f0-content &&
+    printf "file 0\nfix 0\n" >expected &&
+    test_cmp expected f0-content
+'
+
+test_expect_success 'No fixups to squash' '
+    stg squash --fixups 2>err &&
+    grep -e "no fixup patches to squash" err
+'
+
+test_expect_success 'Squash fixups within given range' '
+    echo "fix 2" >>f2.txt &&
+    stg new --fixup p2 -r &&
+    echo "again fix 0" >>f0.txt &&
+    stg new --fixup p0 -r &&
+    stg squash --fixups fixup-1-p2 &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2 fixup-1-p0" &&
+    stg squash --fixups &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2"
+'
+
+test_expect_success 'Fixup of a fixup is squashed into final target' '
+    echo "fix fix" >>f1.txt &&
+    stg new --fixup p1 -r &&
+    echo "fix fix fix" >>f1.txt &&
+    stg new --fixup fixup-1-p1 -r &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2 fixup-1-p1 fixup-1-fixup-1-p1" &&
+    stg squash --fixups &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p2" &&
+    test "$(git show $(stg id p1):f1.txt | tail -n 1)" = "fix fix fix"
+'
+
+test_expect_success 'Missing fixup target' '
+    echo "fix 2" >>f2.txt &&
+    stg new --fixup p2 -r &&
+    stg rename p2 q2 &&
+    command_error stg squash --fixups 2>err &&
+    grep -e "error: fixup patch \`fixup-1-p2\` targets \`p2\`, which does not exist" err &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 q2 fixup-1-p2" &&
+    stg delete fixup-1-p2 &&
+    git checkout f2.txt
+'
+
+test_expect_success 'Fixup of hidden patch' '
+    stg pop q2 &&
+    stg hide q2 &&
+    echo "fix q2" >>f1.txt &&
+    stg new --fixup q2 -r &&
+    command_error stg squash --fixups 2>err &&
+    grep -e "error: fixup patch \`fixup-1-q2\` targets hidden patch \`q2\`; use \`--unhide\` to unhide it" err &&
+    stg squash --fixups --unhide &&
+    test "$(echo $(stg series --all --noprefix))" = "p0 p1 q2" &&
+    test "$(git show $(stg id q2):f1.txt | tail -n 1)" = "fix q2" &&
+    stg push q2
+'
+
+test_expect_success 'Conflicting fixup' '
+    stg pop &&
+    echo "conflicting change" >f2.txt &&
+    stg add f2.txt &&
+    stg new -rm "p3" &&
+    echo "fix q2" >>f2.txt &&
+    stg new --fixup q2 -r &&
+    conflict stg squash --fixups 2>err &&
+    grep -e "error: squashing fixup \`fixup-1-q2\` into \`q2\`: merge conflicts" err &&
+    stg undo --hard &&
+    test "$(echo $(stg series --noprefix))" = "p0 p1 p3 fixup-1-q2 q2"
+'
+
+test_done
@@ -10,6 +10,9 @@ test_expect_success 'Initialize StGit stack' '
 	expected*.txt
 	files*.txt
 	status*.txt
+	*.diff
+	err
+	out
 	EOF
     for i in 1 2 3; do
         echo x >$i.txt &&
@@ -81,4 +84,52 @@ test_expect_success 'Refresh change to unapplied patch' '
     test_cmp expected.txt 2.txt
 '
 
+test_expect_success 'Setup patches changing the same file' '
+    stg delete $(stg series --noprefix --all) &&
+    git reset --hard &&
+    printf "%s\n" 1 2 3 4 5 6 7 8 9 >lines.txt &&
+    stg add lines.txt &&
+    stg new base -m "Add lines" &&
+    stg refresh &&
+    sed -e "s/^2$/two/" lines.txt >lines.txt.tmp && mv lines.txt.tmp lines.txt &&
+    stg new q1 -m "Change two" &&
+    stg refresh &&
+    sed -e "s/^8$/eight/" lines.txt >lines.txt.tmp && mv lines.txt.tmp lines.txt &&
+    stg new q2 -m "Change eight" &&
+    stg refresh &&
+    echo q3 >q3.txt &&
+    stg add q3.txt &&
+    stg new q3 -m "Add q3" &&
+    stg refresh
+'
+
+test_expect_success 'Force merge into patch below patches changing the same file' '
+    sed -e "s/^5$/five/" lines.txt >lines.txt.tmp && mv lines.txt.tmp lines.txt &&
+    stg refresh -p q1 --force-merge --conflicts=disallow >out 2>&1 &&
+    grep -e "rewrote .q2., .q3. on top of the refreshed patch" out &&
+    test "$(echo $(stg series --noprefix))" = "base q1 q2 q3" &&
+    test -z "$(stg status)" &&
+    stg show q1 >q1.diff &&
+    grep -e "^+five$" q1.diff &&
+    stg show q2 >q2.diff &&
+    ! grep -e "^+five" q2.diff &&
+    printf "%s\n" 1 two 3 4 five 6 7 eight 9 >expected.txt &&
+    test_cmp expected.txt lines.txt
+'
+
+test_expect_success 'Force merge conflict uses conflict workflow' '
+    sed -e "s/^eight$/EIGHT/" lines.txt >lines.txt.tmp && mv lines.txt.tmp lines.txt &&
+    conflict stg refresh -p q1 --force-merge --conflicts=disallow &&
+    test "$(echo $(stg series --noprefix --applied))" = "base q1 refresh-temp" &&
+    test "$(stg status lines.txt)" = "UU lines.txt" &&
+    stg undo --hard &&
+    stg undo --hard &&
+    test "$(echo $(stg series --noprefix))" = "base q1 q2 q3"
+'
+
+test_expect_success 'Force merge requires a patch' '
+    general_error stg refresh --force-merge 2>err &&
+    grep -e "--patch" err
+'
+
 test_done
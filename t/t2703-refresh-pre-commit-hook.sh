@@ -214,4 +214,35 @@ test_expect_success 'refresh with path limiting, failing hook that modifies file
     assert_pre_commit_hook_did_run
 '
 
+write_script "$HOOK" <<-EOF
+	touch "$HOOK_OUTPUT_FILE"
+	exit 0
+EOF
+
+test_expect_success 'new with succeeding hook' '
+    stg new -m new-patch-1 &&
+    assert_pre_commit_hook_did_run
+'
+
+# now a hook that fails
+write_script "$HOOK" <<-EOF
+	touch "$HOOK_OUTPUT_FILE"
+	exit 1
+EOF
+
+test_expect_success 'new with failing hook' '
+    stack_state=$(git rev-parse refs/stacks/master) &&
+    command_error stg new -m new-patch-2 2>err &&
+    grep -e "\`pre-commit\` hook returned 1" err &&
+    test "$(stg top)" = "new-patch-1" &&
+    test "$(git rev-parse refs/stacks/master)" = "$stack_state" &&
+    assert_pre_commit_hook_did_run
+'
+
+test_expect_success 'new --no-verify with failing hook' '
+    stg new --no-verify -m new-patch-2 &&
+    test "$(stg top)" = "new-patch-2" &&
+    assert_pre_commit_hook_did_not_run
+'
+
 test_done
@@ -56,4 +56,79 @@ test_expect_success 'Refresh with patch rename and message update' '
     test_cmp expected actual
 '
 
+test_expect_success 'Setup patch for diff tests' '
+    write_script keepedit <<-\EOF &&
+	cp "$1" edited.txt
+	EOF
+    echo existing >a.txt &&
+    stg add a.txt &&
+    stg new -m "diff patch" diff-patch &&
+    stg refresh
+'
+
+test_expect_success 'Refresh with --diff shows the combined diff' '
+    echo incoming >b.txt &&
+    stg add b.txt &&
+    EDITOR=./keepedit stg refresh -e -d &&
+    grep -e "^# ------------------------ >8 ------------------------$" edited.txt &&
+    grep -e "^+existing$" edited.txt &&
+    grep -e "^+incoming$" edited.txt &&
+    echo "diff patch" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Refresh shows the diff with stgit.edit.verbose' '
+    test_config stgit.edit.verbose true &&
+    echo more >>b.txt &&
+    EDITOR=./keepedit stg refresh -e &&
+    grep -e "^+existing$" edited.txt &&
+    grep -e "^+more$" edited.txt
+'
+
+test_expect_success 'Refresh with --show-delta-only shows only the incoming changes' '
+    echo delta >>a.txt &&
+    EDITOR=./keepedit stg refresh -e --show-delta-only &&
+    grep -e "^ existing$" edited.txt &&
+    grep -e "^+delta$" edited.txt &&
+    ! grep -e "b.txt" edited.txt &&
+    stg show diff-patch >show.txt &&
+    grep -e "^+delta$" show.txt &&
+    grep -e "^+incoming$" show.txt
+'
+
+test_expect_success '--show-delta-only requires --edit' '
+    general_error stg refresh --show-delta-only 2>err &&
+    grep -e "--edit" err
+'
+
+test_expect_success 'Refresh with a custom core.commentChar' '
+    test_config core.commentChar ";" &&
+    write_script commentedit <<-\EOF &&
+	cp "$1" edited.txt &&
+	sed "s/^diff patch$/diff patch\n\n#42 is fixed/" "$1" >"$1.tmp" &&
+	mv "$1.tmp" "$1"
+	EOF
+    echo commented >>a.txt &&
+    EDITOR=./commentedit stg refresh -e -d &&
+    grep -e "^; Please enter the message" edited.txt &&
+    grep -e "^; .;. will be ignored" edited.txt &&
+    grep -e "^; ------------------------ >8 ------------------------$" edited.txt &&
+    printf "diff patch\n\n#42 is fixed\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual &&
+    stg show diff-patch >show.txt &&
+    grep -e "^+commented$" show.txt
+'
+
+test_expect_success 'Refresh with core.commentChar set to auto' '
+    test_config core.commentChar auto &&
+    echo auto >>a.txt &&
+    EDITOR=./keepedit stg refresh -e -d &&
+    grep -e "^; ------------------------ >8 ------------------------$" edited.txt &&
+    printf "diff patch\n\n#42 is fixed\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
 test_done
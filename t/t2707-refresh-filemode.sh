@@ -0,0 +1,68 @@
+#!/bin/sh
+
+test_description='Mode-only patches with core.fileMode=false'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    # Ignore our own temp files.
+    cat >>.git/info/exclude <<-\EOF &&
+	series.txt
+	show.txt
+	status.txt
+	tree.txt
+	EOF
+    git config core.fileMode false &&
+    echo a >f.txt &&
+    echo b >g.txt &&
+    stg add f.txt g.txt &&
+    git commit -m "Add files" &&
+    stg init
+'
+
+test_expect_success 'Refresh mode-only change from index' '
+    stg new -m mode-only &&
+    git update-index --chmod=+x f.txt &&
+    stg refresh &&
+    stg series --empty >series.txt &&
+    grep -e "^ > mode-only$" series.txt &&
+    git ls-tree HEAD f.txt >tree.txt &&
+    grep -e "^100755 " tree.txt
+'
+
+test_expect_success 'Mode-only patch survives pop and push' '
+    stg pop &&
+    stg push &&
+    chmod -x f.txt &&
+    stg status >status.txt &&
+    test_must_be_empty status.txt &&
+    stg show mode-only >show.txt &&
+    grep -e "^new mode 100755$" show.txt
+'
+
+test_expect_success 'Refresh content keeps mode without work tree exec bit' '
+    chmod -x f.txt &&
+    echo aa >>f.txt &&
+    stg refresh &&
+    git ls-tree HEAD f.txt >tree.txt &&
+    grep -e "^100755 " tree.txt &&
+    stg show mode-only >show.txt &&
+    grep -e "^new mode 100755$" show.txt
+'
+
+test_expect_success 'Path-limited refresh keeps mode from index' '
+    stg new -m mode-limited &&
+    git update-index --chmod=+x g.txt &&
+    chmod -x g.txt &&
+    stg refresh g.txt &&
+    git ls-tree HEAD g.txt >tree.txt &&
+    grep -e "^100755 " tree.txt &&
+    echo bb >>g.txt &&
+    stg refresh g.txt &&
+    git ls-tree HEAD g.txt >tree.txt &&
+    grep -e "^100755 " tree.txt &&
+    stg status >status.txt &&
+    test_must_be_empty status.txt
+'
+
+test_done
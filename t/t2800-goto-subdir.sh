@@ -43,7 +43,7 @@ test_expect_success 'Goto in subdirectory (conflicting push)' '
     cat foo/bar >actual.txt &&
     cat >expected1a.txt <<-\EOF &&
 	foo1
-	<<<<<<< current
+	<<<<<<< current top
 	=======
 	foo2
 	foo3
@@ -52,11 +52,11 @@ test_expect_success 'Goto in subdirectory (conflicting push)' '
     # ... and this result after commit 606475f3.
     cat >expected1b.txt <<-\EOF &&
 	foo1
-	<<<<<<< current
+	<<<<<<< current top
 	=======
 	foo2
 	foo3
-	>>>>>>> patched
+	>>>>>>> patch p3
 	EOF
     (
       test_cmp expected1a.txt actual.txt ||
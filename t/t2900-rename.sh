@@ -81,4 +81,47 @@ test_expect_success 'Rename hidden' '
     test "$(echo $(stg series --all))" = "> foo ! pub"
 '
 
+test_expect_success 'Rename reports updated labels' '
+    stg label add topic foo &&
+    stg rename foo foo2 2>out &&
+    grep -e "^  updated label \`topic\`$" out &&
+    test "$(stg label list)" = "topic: foo2"
+'
+
+test_expect_success 'Rename with alias' '
+    stg rename --keep-alias foo2 foo3 &&
+    stg show foo2 >out 2>err &&
+    grep -e "foo2. is a deprecated alias for patch .foo3." err &&
+    test "$(echo $(stg series --all --noprefix))" = "foo3 pub" &&
+    git show refs/stacks/master:stack.json >stack.json &&
+    grep -e "\"aliases\"" stack.json
+'
+
+test_expect_success 'Alias follows further renames' '
+    stg rename foo3 foo4 2>out &&
+    grep -e "^  updated alias \`foo2\`$" out &&
+    stg id foo2 >id 2>err &&
+    test "$(cat id)" = "$(stg id foo4)"
+'
+
+test_expect_success 'Alias is dropped when name is reused' '
+    stg new -m reused foo2 &&
+    stg id foo2 >id 2>err &&
+    test_must_be_empty err &&
+    stg delete foo2 &&
+    command_error stg id foo2 2>err &&
+    ! grep -e "deprecated alias" err
+'
+
+test_expect_success 'Clear alias' '
+    stg rename --keep-alias foo4 foo5 &&
+    stg id foo4 &&
+    stg rename --clear-alias foo4 &&
+    command_error stg id foo4 &&
+    command_error stg rename --clear-alias foo4 2>err &&
+    grep -e "no patch alias named \`foo4\`" err &&
+    git show refs/stacks/master:stack.json >stack.json &&
+    ! grep -e "\"aliases\"" stack.json
+'
+
 test_done
@@ -76,4 +76,44 @@ test_expect_success 'Undo past end of history' '
     test_cmp expected.txt a
 '
 
+test_expect_success 'Make dated stack changes' '
+    GIT_COMMITTER_DATE="2022-01-01 10:00:00 +0000" stg pop -a &&
+    GIT_COMMITTER_DATE="2022-01-02 10:00:00 +0000" stg push &&
+    GIT_COMMITTER_DATE="2022-01-03 10:00:00 +0000" stg push -a &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3" &&
+    GIT_COMMITTER_DATE="2022-01-04 10:00:00 +0000"
+'
+
+test_expect_success 'Undo to date' '
+    stg undo --to-date "2022-01-02 12:00:00 +0000" &&
+    test "$(echo $(stg series))" = "> p1 - p2 - p3" &&
+    test "$(stg log -n1 --oneline | cut -d" " -f2-4)" = "undo to 2022-01-02"
+'
+
+test_expect_success 'Undo to date between entries' '
+    stg undo --to-date "2022-01-01 23:59:59 +0000" &&
+    test "$(echo $(stg series))" = "- p1 - p2 - p3"
+'
+
+test_expect_success 'Undo to date can be undone' '
+    stg undo &&
+    test "$(echo $(stg series))" = "> p1 - p2 - p3"
+'
+
+test_expect_success 'Undo to date with relative date' '
+    stg undo --to-date "2 days ago" &&
+    test "$(echo $(stg series))" = "> p1 - p2 - p3"
+'
+
+test_expect_success 'Undo to date older than all entries' '
+    command_error stg undo --to-date "2000-01-01 00:00:00 +0000" 2>err &&
+    grep -e "no stack log entry as old as 2000-01-01" err &&
+    test "$(echo $(stg series))" = "> p1 - p2 - p3"
+'
+
+test_expect_success 'Undo to date conflicts with number' '
+    general_error stg undo --to-date yesterday -n 2 2>err &&
+    grep -e "cannot be used with" err
+'
+
 test_done
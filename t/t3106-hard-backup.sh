@@ -0,0 +1,106 @@
+#!/bin/sh
+
+test_description='Test saving changes discarded by --hard'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    cat >>.git/info/exclude <<-\EOF &&
+	/err
+	/out
+	/*.discarded
+	/*.staged
+	EOF
+    echo base >a &&
+    printf "binary\000content\n" >b &&
+    git add a b &&
+    git commit -m base &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >>a &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo p2 >>a &&
+    stg refresh
+'
+
+test_expect_success 'Undo --hard saves discarded changes' '
+    echo staged >>a &&
+    git add a &&
+    echo unstaged >>a &&
+    printf "changed\000binary\377\n" >b &&
+    cp a a.discarded &&
+    cp b b.discarded &&
+    git show :a >a.staged &&
+    stg undo --hard 2>err &&
+    grep -e "saved discarded changes to \`refs/stgit/discarded/master\`" err &&
+    grep -e "git stash apply refs/stgit/discarded/master" err &&
+    git diff --quiet HEAD
+'
+
+test_expect_success 'Discarded content is recoverable byte for byte' '
+    git show refs/stgit/discarded/master:a >out &&
+    test_cmp a.discarded out &&
+    git show refs/stgit/discarded/master:b >out &&
+    test_cmp b.discarded out &&
+    git show refs/stgit/discarded/master^2:a >out &&
+    test_cmp a.staged out
+'
+
+test_expect_success 'Recover discarded changes with git stash apply' '
+    stg redo --hard &&
+    git stash apply --index refs/stgit/discarded/master &&
+    test_cmp a.discarded a &&
+    test_cmp b.discarded b &&
+    git show :a >out &&
+    test_cmp a.staged out &&
+    git reset -q --hard
+'
+
+test_expect_success 'Nothing saved without local changes' '
+    git update-ref -d refs/stgit/discarded/master &&
+    stg undo --hard 2>err &&
+    ! grep -e "saved discarded changes" err &&
+    test_must_fail git rev-parse -q --verify refs/stgit/discarded/master
+'
+
+test_expect_success 'Skip backup with --no-backup' '
+    echo nobackup >>a &&
+    stg redo --hard --no-backup 2>err &&
+    ! grep -e "saved discarded changes" err &&
+    test_must_fail git rev-parse -q --verify refs/stgit/discarded/master &&
+    git diff --quiet HEAD
+'
+
+test_expect_success 'Reset --hard saves discarded changes' '
+    echo reset >>a &&
+    cp a a.discarded &&
+    stg reset --hard 2>err &&
+    grep -e "saved discarded changes" err &&
+    git show refs/stgit/discarded/master:a >out &&
+    test_cmp a.discarded out &&
+    git diff --quiet HEAD
+'
+
+test_expect_success 'Undo --hard saves conflicted changes' '
+    stg pop &&
+    stg new -m p3 &&
+    echo p3 >>a &&
+    stg refresh &&
+    stg pop &&
+    stg push p2 &&
+    conflict stg push p3 &&
+    cp a a.discarded &&
+    stg undo --hard 2>err &&
+    grep -e "saved discarded changes" err &&
+    test -z "$(git ls-files -u)" &&
+    git show refs/stgit/discarded/master:a >out &&
+    test_cmp a.discarded out
+'
+
+test_expect_success 'Option --no-backup requires --hard' '
+    general_error stg undo --no-backup 2>err &&
+    grep -e "--hard" err
+'
+
+test_done
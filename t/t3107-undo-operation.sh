@@ -0,0 +1,99 @@
+#!/bin/sh
+
+test_description='Test "stg undo" of a selected operation'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack with four patches' '
+    printf "%s\n" out err >>.git/info/exclude &&
+    test_commit_bulk --message="p%s" 4 &&
+    stg init &&
+    stg uncommit -n 4 &&
+    stg squash -n p12 -m p12 p1 p2 &&
+    stg squash -n p34 -m p34 p3 p4 &&
+    stg pop &&
+    test "$(echo $(stg series))" = "> p12 - p34"
+'
+
+test_expect_success 'Undo operation not in stack log' '
+    command_error stg undo sink 2>err &&
+    grep -e "no operation matching .sink. found in the stack log" err &&
+    grep -e "^  1: pop p34$" err &&
+    grep -e "^  2: squash p3 p4$" err &&
+    test "$(echo $(stg series))" = "> p12 - p34"
+'
+
+test_expect_success 'Undo ambiguous operation' '
+    command_error stg undo squash 2>err &&
+    grep -e "multiple operations match .squash." err &&
+    grep -e "^  2: squash p3 p4$" err &&
+    grep -e "^  3: squash p1 p2$" err &&
+    test "$(echo $(stg series))" = "> p12 - p34"
+'
+
+test_expect_success 'Operation must match at word boundary' '
+    command_error stg undo squ 2>err &&
+    grep -e "no operation matching .squ." err &&
+    command_error stg undo "squash p3 p" 2>err &&
+    grep -e "no operation matching .squash p3 p." err
+'
+
+test_expect_success 'Dry run lists undone operations' '
+    stg undo --dry-run --last squash >out 2>err &&
+    test_must_be_empty out &&
+    cat >expected <<-\EOF &&
+	Would undo 2 operations:
+	  1: pop p34
+	  2: squash p3 p4
+	EOF
+    head -n 3 err >actual &&
+    test_cmp expected actual &&
+    grep -e "^Dry run: no changes were made$" err &&
+    test "$(echo $(stg series))" = "> p12 - p34"
+'
+
+test_expect_success 'Undo most recent matching operation' '
+    stg undo --last squash &&
+    test "$(echo $(stg series))" = "+ p12 + p3 > p4" &&
+    test "$(stg log -n1 --oneline | cut -d" " -f2-4)" = "undo 2 operations"
+'
+
+test_expect_success 'Undone operations are not matched' '
+    command_error stg undo "squash p3" 2>err &&
+    grep -e "no operation matching .squash p3." err &&
+    stg undo --dry-run squash 2>err &&
+    grep -e "^  1: squash p1 p2$" err
+'
+
+test_expect_success 'Undo of selected operation can be redone' '
+    stg redo &&
+    test "$(echo $(stg series))" = "> p12 - p34"
+'
+
+test_expect_success 'Undo unique operation' '
+    stg undo "squash p1 p2" &&
+    test "$(echo $(stg series))" = "+ p1 + p2 + p3 > p4"
+'
+
+test_expect_success 'Last requires operation' '
+    general_error stg undo --last 2>err &&
+    grep -e "the following required arguments were not provided" err
+'
+
+test_expect_success 'Operation conflicts with number' '
+    general_error stg undo -n 2 squash 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Undo selected operation with --hard' '
+    stg pop &&
+    stg new -m p5 p5 &&
+    echo dirty >4.t &&
+    command_error stg undo --last pop 2>err &&
+    test "$(echo $(stg series))" = "+ p1 + p2 + p3 > p5 - p4" &&
+    stg undo --hard --last pop &&
+    test "$(echo $(stg series))" = "+ p1 + p2 + p3 > p4" &&
+    test "$(cat 4.t)" = "content 4"
+'
+
+test_done
@@ -0,0 +1,120 @@
+#!/bin/sh
+
+test_description='Test stg log --export and stg replay'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack operations' '
+    test_commit_bulk --message="base %s" 2 &&
+    git clone -q . other &&
+    stg init &&
+    stg new -m "patch one" p1 &&
+    stg new -m "patch two" p2 &&
+    stg new -m "patch three" p3 &&
+    stg pop &&
+    stg rename p3 q3 &&
+    stg -C . hide q3 &&
+    printf "%s\n" ops.json out err other >>.git/info/exclude
+'
+
+test_expect_success 'Command lines are recorded in the stack state' '
+    git show refs/stacks/master:stack.json >out &&
+    grep -e "^  \"command\": \[$" out &&
+    grep -e "^    \"hide\",$" out &&
+    grep -e "^    \"q3\"$" out &&
+    git show refs/stacks/master~3:stack.json >out &&
+    grep -e "^    \"pop\"$" out
+'
+
+test_expect_success 'Export the stack operations' '
+    stg log --export ops.json &&
+    test "$(grep -c "\"message\":" ops.json)" = "7" &&
+    grep -e "\"message\": \"initialize\"" ops.json &&
+    grep -e "\"message\": \"rename p3 q3\"" ops.json &&
+    grep -e "\"state\": \"$(git rev-parse refs/stacks/master~1)\"" ops.json &&
+    grep -e "\"commit\": \"$(stg id p1)\"" ops.json &&
+    stg log --export - -n 2 >out &&
+    test "$(grep -c "\"message\":" out)" = "2"
+'
+
+test_expect_success 'Replay the operations in another repository' '
+    (
+        cd other &&
+        stg init &&
+        stg replay ../ops.json 2>out &&
+        grep -e "^info: replaying 1/6: \`stg new -m '"'"'patch one'"'"' p1\`$" out &&
+        grep -e "^info: replayed 6 operations$" out &&
+        test "$(echo $(stg series --all))" = "+ p1 > p2 ! q3"
+    )
+'
+
+test_expect_success 'Refresh records its command line once' '
+    stg new -m "patch four" p4 &&
+    echo "four" >four.txt &&
+    stg add four.txt &&
+    stg refresh &&
+    stg log --export ops.json -n 4 &&
+    test "$(grep -c "^        \"refresh\"$" ops.json)" = "1" &&
+    grep -e "\"message\": \"refresh refresh-temp" ops.json
+'
+
+test_expect_success 'Replay stops at the first divergence' '
+    (
+        cd other &&
+        command_error stg replay ../ops.json 2>err &&
+        grep -e "replaying 2/2: \`stg refresh\`" err &&
+        grep -e "stack state diverged after \`stg refresh\`" err &&
+        grep -e "patch \`p4\` tree: expected $(cd .. && git rev-parse $(stg id p4)^{tree}), actual $(git rev-parse $(stg id p4)^{tree})" err
+    )
+'
+
+test_expect_success 'Replay requires matching starting state' '
+    (
+        cd other &&
+        stg goto p1 &&
+        command_error stg replay ../ops.json 2>err &&
+        grep -e "stack does not match the starting state" err &&
+        grep -e "applied patches: expected \[p1 p2\], actual \[p1\]" err
+    )
+'
+
+test_expect_success 'Operations without command lines cannot be replayed' '
+    stg log --export ops.json -n 1 &&
+    test_commit --no-tag external &&
+    stg repair &&
+    stg log --export out -n 3 &&
+    grep -e "\"message\": \"external modifications\"" out &&
+    (
+        cd other &&
+        command_error stg replay ../out 2>err &&
+        grep -e "cannot replay \`external modifications\`: no command line was recorded" err
+    )
+'
+
+test_expect_success 'Commands that are not stack manipulations are refused' '
+    stg new -m "patch five" p5 &&
+    stg log --export ops.json -n 2 &&
+    sed -e "s/^        \"new\",$/        \"foreach\", \"--exec\", \"touch pwned\",/" ops.json >out &&
+    (
+        cd other &&
+        command_error stg replay ../out 2>err &&
+        grep -e "refusing to replay \`stg foreach --exec '"'"'touch pwned'"'"' -m '"'"'patch five'"'"' p5\`" err &&
+        test_path_is_missing pwned &&
+        test_path_is_missing ../pwned
+    ) &&
+    sed -e "s/^        \"-m\",$/        \"--save-template\",/" ops.json >out &&
+    (
+        cd other &&
+        command_error stg replay ../out 2>err &&
+        grep -e "the \`--save-template\` option may not be replayed" err &&
+        test_path_is_missing "patch five"
+    )
+'
+
+test_expect_success 'Unsupported operation log version' '
+    sed -e "s/^  \"version\": 1,$/  \"version\": 2,/" ops.json >out &&
+    command_error stg replay out 2>err &&
+    grep -e "unsupported operation log version 2" err
+'
+
+test_done
@@ -296,6 +296,19 @@ test_expect_success 'Rename patch and edit its diff' '
     stg rename p2-new p2
 '
 
+test_expect_success 'Rename patch keeping its old name as an alias' '
+    write_script diffedit <<-\EOF &&
+	sed "s/^Patch:  *p2/Patch: p2-new/" "$1" >"$1".tmp && mv "$1".tmp "$1"
+	EOF
+    test_when_finished "rm -f diffedit" &&
+    EDITOR=./diffedit stg edit --keep-alias &&
+    test "$(stg top)" = "p2-new" &&
+    test "$(stg id p2 2>err)" = "$(stg id p2-new)" &&
+    grep -e "deprecated alias" err &&
+    stg rename --clear-alias p2 &&
+    stg rename p2-new p2
+'
+
 test_expect_success 'Set author' '
     stg edit p2 --author "Jane Austin <jaustin@example.com>" &&
     test "$(auth HEAD)" = "Jane Austin, jaustin@example.com"
@@ -327,10 +340,24 @@ test_expect_success 'Set author date (ISO 8601 format)' '
 '
 
 test_expect_success 'Fail to set invalid author date' '
-    general_error stg edit p2 --authdate "28 Jan 1813" &&
+    general_error stg edit p2 --authdate "28 Jan 1813" 2>err &&
+    grep -e "invalid date .28 Jan 1813.: expected ISO 8601" err &&
+    grep -e "a relative date" err &&
     test "$(adate HEAD)" = "2013-01-28 22:30:00 -0300"
 '
 
+test_expect_success 'Set author date (Unix timestamp)' '
+    stg edit p2 --authdate "@1641479527 -0500" &&
+    test "$(adate HEAD)" = "2022-01-06 09:32:07 -0500"
+'
+
+test_expect_success 'Set author date (relative)' '
+    stg edit p2 --authdate "2.weeks.ago" &&
+    test "$(git log -1 --format=%at HEAD)" -lt "$(($(date +%s) - 13 * 86400))" &&
+    stg edit p2 --authdate "yesterday noon" &&
+    test "$(git log -1 --format=%ad --date=format:%T HEAD)" = "12:00:00"
+'
+
 test_expect_success 'Set author date and author' '
     stg edit p2 --authdate "2000-01-28 22:30:00 -0300" --author "Elizabeth Bennet <ebennet@example.com>" &&
     test "$(adate HEAD)" = "2000-01-28 22:30:00 -0300" &&
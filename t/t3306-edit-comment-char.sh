@@ -0,0 +1,135 @@
+#!/bin/sh
+
+test_description='Test core.commentChar in editor buffers'
+
+. ./test-lib.sh
+
+msg () { git cat-file -p "$1" | sed '1,/^$/d' ; }
+
+test_expect_success 'Initialize StGit stack' '
+    stg new -m p0 p0 &&
+    write_script keep-editor <<-\EOF
+	cp "$1" saved
+	EOF
+'
+
+test_expect_success 'New patch with ";" comment char' '
+    test_config core.commentChar ";" &&
+    write_script editor <<-\EOF &&
+	cp "$1" saved &&
+	sed "5s/^.*$/#1 is fixed/" "$1" >"$1.tmp" &&
+	mv "$1.tmp" "$1"
+	EOF
+    GIT_EDITOR=./editor stg new p1 &&
+    grep -e "^; Please enter the message" saved &&
+    grep -e "^; .;. will be ignored" saved &&
+    ! grep -e "^#" saved &&
+    echo "#1 is fixed" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Edit patch with ";" comment char' '
+    test_config core.commentChar ";" &&
+    write_script editor <<-\EOF &&
+	cp "$1" saved &&
+	sed "5a\\
+\\
+#2 is fixed too\\
+; a comment" "$1" >"$1.tmp" &&
+	mv "$1.tmp" "$1"
+	EOF
+    GIT_EDITOR=./editor stg edit &&
+    grep -e "^#1 is fixed$" saved &&
+    printf "#1 is fixed\n\n#2 is fixed too\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Edit patch with diff and ";" comment char' '
+    test_config core.commentChar ";" &&
+    echo content >file &&
+    stg add file &&
+    stg refresh &&
+    GIT_EDITOR=./keep-editor stg edit --diff &&
+    grep -e "^; ------------------------ >8 ------------------------$" saved &&
+    grep -e "^+content$" saved &&
+    printf "#1 is fixed\n\n#2 is fixed too\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Auto comment char avoids "#" used in the message' '
+    test_config core.commentChar auto &&
+    GIT_EDITOR=./keep-editor stg edit &&
+    grep -e "^; Please enter the message" saved &&
+    printf "#1 is fixed\n\n#2 is fixed too\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Auto comment char is "#" if unused by the message' '
+    test_config core.commentChar auto &&
+    GIT_EDITOR=./keep-editor stg edit p0 &&
+    grep -e "^# Please enter the message" saved &&
+    echo p0 >expected &&
+    msg $(stg id p0) >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Comment string from core.commentString' '
+    test_config core.commentString "//" &&
+    GIT_EDITOR=./keep-editor stg edit &&
+    grep -e "^// Please enter the message" saved &&
+    printf "#1 is fixed\n\n#2 is fixed too\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Squash with ";" comment char' '
+    test_config core.commentChar ";" &&
+    stg new -m "#a" a &&
+    stg new -m "#b" b &&
+    GIT_EDITOR=./keep-editor stg squash -n ab a b &&
+    grep -e "^; Commit message from patch #1: a$" saved &&
+    grep -e "^; Commit message from patch #2: b$" saved &&
+    printf "#a\n\n#b\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Squash with auto comment char' '
+    test_config core.commentChar auto &&
+    stg new -m "#c" c &&
+    GIT_EDITOR=./keep-editor stg squash -n abc ab c &&
+    grep -e "^; Commit message from patch #1: ab$" saved &&
+    printf "#a\n\n#b\n\n#c\n" >expected &&
+    msg HEAD >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Interactive push with ";" comment char' '
+    test_config core.commentChar ";" &&
+    stg pop -a &&
+    GIT_EDITOR=./keep-editor stg push --interactive &&
+    grep -e "^p1 *; #1 is fixed$" saved &&
+    grep -e "^; The patches listed above are pushed" saved &&
+    test "$(stg series --applied -c)" = "3"
+'
+
+test_expect_success 'Interactive rebase with ";" comment char' '
+    test_config core.commentChar ";" &&
+    GIT_EDITOR=./keep-editor stg rebase --interactive &&
+    grep -e "^; --- APPLY_LINE ---$" saved &&
+    grep -e "^keep p1 *; #1 is fixed$" saved &&
+    grep -e "^;   k, keep <patch>" saved &&
+    test "$(stg series --applied -c)" = "3"
+'
+
+test_expect_success 'Invalid comment char' '
+    test_config core.commentChar "" &&
+    command_error stg edit 2>err &&
+    grep -e "invalid core.commentChar" err
+'
+
+test_done
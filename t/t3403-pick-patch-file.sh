@@ -0,0 +1,85 @@
+#!/bin/sh
+
+test_description='Test picking patches from patch files and urls'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patch files' '
+    mkdir patches &&
+    echo patches >>.git/info/exclude &&
+    echo base >base.txt &&
+    git add base.txt &&
+    git commit -m base &&
+    git checkout -b other &&
+    echo mail >mail.txt &&
+    git add mail.txt &&
+    test_tick &&
+    git commit --author="Mail Author <mail@example.com>" \
+        -m "mail subject" -m "mail body" &&
+    git format-patch -1 --stdout >patches/mail.patch &&
+    echo plain >plain.txt &&
+    git add plain.txt &&
+    git commit -m "plain" &&
+    cat >patches/plain.patch <<-EOF &&
+	From: Plain Author <plain@example.com>
+	Subject: plain subject
+
+	plain body
+	---
+	$(git diff HEAD~1 HEAD)
+	EOF
+    git checkout master &&
+    stg init
+'
+
+test_expect_success 'Pick from a patch file in mail format' '
+    stg pick --patch-file patches/mail.patch &&
+    test "$(echo $(stg series --applied --noprefix))" = "mail-subject" &&
+    test "$(git show -s --format="%an <%ae>" HEAD)" = "Mail Author <mail@example.com>" &&
+    test "$(git show -s --format="%s%n%n%b" HEAD)" = "$(printf "mail subject\n\nmail body")" &&
+    test "$(cat mail.txt)" = "mail"
+'
+
+test_expect_success 'Pick from a patch file with --name and --noapply' '
+    stg pick --noapply --name plain --patch-file patches/plain.patch &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "plain" &&
+    test_path_is_missing plain.txt &&
+    stg push plain &&
+    test "$(git show -s --format="%an <%ae>" HEAD)" = "Plain Author <plain@example.com>" &&
+    test "$(git show -s --format=%s HEAD)" = "plain subject" &&
+    test "$(cat plain.txt)" = "plain"
+'
+
+test_expect_success 'Pick a patch file with --fold' '
+    stg delete plain &&
+    stg pick --fold --patch-file patches/plain.patch &&
+    test "$(echo $(stg status))" = "A plain.txt" &&
+    stg refresh &&
+    test "$(echo $(stg series --noprefix))" = "mail-subject" &&
+    test "$(echo $(stg files))" = "A mail.txt A plain.txt"
+'
+
+test_expect_success 'Pick a patch file that does not apply' '
+    command_error stg pick --patch-file patches/plain.patch 2>err &&
+    grep -e "error: applying .patches/plain\.patch." err &&
+    command_error stg pick --fold --patch-file patches/plain.patch 2>err &&
+    grep -e "error: folding .patches/plain\.patch." err &&
+    test "$(echo $(stg series --noprefix))" = "mail-subject" &&
+    test -z "$(git status --porcelain --untracked-files=no)"
+'
+
+test_expect_success 'Pick from a url' '
+    stg delete mail-subject &&
+    stg pick --url "file://$(pwd)/patches/mail.patch" &&
+    test "$(echo $(stg series --applied --noprefix))" = "mail-subject" &&
+    test "$(git show -s --format="%an <%ae>" HEAD)" = "Mail Author <mail@example.com>"
+'
+
+test_expect_success 'Patch file conflicts with other sources' '
+    general_error stg pick --patch-file patches/mail.patch master 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg pick --revert --patch-file patches/mail.patch 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_done
@@ -0,0 +1,73 @@
+#!/bin/sh
+
+test_description='Test picking ranges of patches from another branch'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    stg init &&
+    stg branch --create other &&
+    for i in 1 2 3 4 5; do
+        stg new -m "p$i" &&
+        echo "$i" >"f$i" &&
+        stg add "f$i" &&
+        stg refresh || return 1
+    done &&
+    stg pop -n 2 &&
+    stg hide p5 &&
+    test "$(echo $(stg series --all))" = "+ p1 + p2 > p3 - p4 ! p5" &&
+    stg branch master
+'
+
+test_expect_success 'Pick closed range from other branch' '
+    stg pick other:p2..p4 &&
+    test "$(echo $(stg series --noprefix))" = "p2 p3 p4" &&
+    test_path_is_file f4 &&
+    stg delete p2..p4
+'
+
+test_expect_success 'Pick open-ended ranges from other branch' '
+    stg pick other:..p2 &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2" &&
+    stg pick other:p3.. &&
+    test "$(echo $(stg series --noprefix))" = "p1 p2 p3 p4" &&
+    stg delete p1..p4
+'
+
+test_expect_success 'Pick range with ref-branch' '
+    stg pick -B other p3.. &&
+    test "$(echo $(stg series --noprefix))" = "p3 p4" &&
+    stg delete p3..p4
+'
+
+test_expect_success 'Pick range with colliding names' '
+    stg new -m p2 p2 &&
+    stg pick other:p1..p3 &&
+    test "$(echo $(stg series --noprefix))" = "p2 p1 p3 p4" &&
+    stg files p3 >files &&
+    test "$(cat files)" = "A f2" &&
+    stg files p4 >files &&
+    test "$(cat files)" = "A f3" &&
+    stg delete p1..p4
+'
+
+test_expect_success 'Pick range with --noapply' '
+    stg pick --noapply other:p2..p4 &&
+    test "$(echo $(stg series --all))" = "> p2 - p3 - p4 - p5" &&
+    test_path_is_missing f3 &&
+    stg delete p3..p5
+'
+
+test_expect_success 'Pick range including hidden patch' '
+    command_error stg pick other:p4..p5 2>err &&
+    grep -e "patch \`p5\` in \`other:p4..p5\` is hidden; use \`--hidden\` to pick it" err &&
+    test "$(echo $(stg series --noprefix))" = "p2"
+'
+
+test_expect_success 'Pick range including hidden patch with --hidden' '
+    stg pick --hidden other:p4..p5 &&
+    test "$(echo $(stg series --noprefix))" = "p2 p4 p5" &&
+    test_path_is_file f5
+'
+
+test_done
@@ -0,0 +1,114 @@
+#!/bin/sh
+
+test_description='Test notes and committer info of picked and uncommitted patches'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize source branch' '
+    test_commit base &&
+    git branch other &&
+    git checkout other &&
+    test_commit_bulk --start=1 --message="c%s" 2 &&
+    git notes add -m "default note" HEAD~ &&
+    git notes --ref=review add -m "review note" HEAD~ &&
+    git notes add -m "second note" HEAD &&
+    GIT_COMMITTER_NAME="Other Committer" \
+    GIT_COMMITTER_EMAIL="other@example.com" \
+    GIT_COMMITTER_DATE="2001-02-03 04:05:06 +0000" \
+    git commit --amend --no-edit &&
+    git notes copy HEAD@{1} HEAD &&
+    git checkout master &&
+    stg init
+'
+
+test_expect_success 'Pick does not copy notes by default' '
+    stg pick other~ &&
+    test_must_fail git notes show $(stg id c1) &&
+    stg delete c1
+'
+
+test_expect_success 'Pick with --copy-notes copies default notes' '
+    stg pick --copy-notes other~ other &&
+    test "$(git notes show $(stg id c1))" = "default note" &&
+    test "$(git notes show $(stg id c2))" = "second note" &&
+    test_must_fail git notes --ref=review show $(stg id c1) &&
+    stg delete c1 c2
+'
+
+test_expect_success 'Pick with --copy-notes=<ref>' '
+    stg pick --copy-notes=refs/notes/review other~ &&
+    test "$(git notes --ref=review show $(stg id c1))" = "review note" &&
+    stg delete c1
+'
+
+test_expect_success 'Pick unapplied patch with --copy-notes' '
+    stg pick --noapply --copy-notes other &&
+    test "$(git notes show $(stg id c2))" = "second note" &&
+    stg delete c2
+'
+
+test_expect_success 'Notes of other refs follow refresh with notes.rewriteRef' '
+    test_config notes.rewriteRef refs/notes/review &&
+    stg pick --copy-notes=review other~ &&
+    old=$(stg id c1) &&
+    echo follow >>1.t &&
+    stg refresh &&
+    test "$(stg id c1)" != "$old" &&
+    test "$(git notes --ref=review show $(stg id c1))" = "review note" &&
+    stg delete c1
+'
+
+test_expect_success 'Notes of other refs are not followed by default' '
+    stg pick --copy-notes=review other~ &&
+    echo no-follow >>1.t &&
+    stg refresh &&
+    test_must_fail git notes --ref=review show $(stg id c1) &&
+    stg delete c1
+'
+
+test_expect_success 'Default notes are not duplicated when also a rewrite ref' '
+    test_config notes.rewriteRef refs/notes/commits &&
+    stg pick --copy-notes other~ &&
+    echo no-dup >>1.t &&
+    stg refresh &&
+    test "$(git notes show $(stg id c1))" = "default note" &&
+    stg delete c1
+'
+
+test_expect_success 'Pick with --keep-committer' '
+    stg pick other &&
+    test "$(git log -1 --format=%ce $(stg id c2))" = "committer@example.com" &&
+    stg delete c2 &&
+    stg pick --keep-committer other &&
+    test "$(git log -1 --format="%cn %ce %cd" $(stg id c2))" = \
+         "$(git log -1 --format="%cn %ce %cd" other)" &&
+    stg delete c2
+'
+
+test_expect_success 'Conflicting options' '
+    general_error stg pick --keep-committer --committer-date-is-author-date other 2>err &&
+    grep -e "cannot be used with" err &&
+    general_error stg pick --copy-notes --fold other 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Uncommitted patches keep notes and committer' '
+    git reset --hard other &&
+    stg uncommit -n 2 &&
+    test "$(git notes show $(stg id c1))" = "default note" &&
+    test "$(git notes --ref=review show $(stg id c1))" = "review note" &&
+    test "$(git log -1 --format=%cn $(stg id c2))" = "Other Committer"
+'
+
+test_expect_success 'Notes of uncommitted patches follow refresh' '
+    test_config notes.rewriteRef refs/notes/review &&
+    stg goto c1 &&
+    echo change >>1.t &&
+    stg refresh &&
+    test "$(git notes show $(stg id c1))" = "default note" &&
+    test "$(git notes --ref=review show $(stg id c1))" = "review note" &&
+    stg push &&
+    test "$(git notes show $(stg id c2))" = "second note"
+'
+
+test_done
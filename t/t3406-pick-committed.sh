@@ -0,0 +1,102 @@
+#!/bin/sh
+
+test_description='Test stg pick --committed'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize branches' '
+    test_commit base &&
+    git branch upstream &&
+    git checkout upstream &&
+    test_commit up1 &&
+    test_commit up2 &&
+    test_commit conflict a.txt upstream-a &&
+    git checkout -b other base &&
+    test_commit other1 &&
+    git checkout master &&
+    stg init &&
+    stg new -m p1 &&
+    echo p1 >p1.txt &&
+    stg add p1.txt &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo p2 >p2.txt &&
+    stg add p2.txt &&
+    stg refresh
+'
+
+test_expect_success 'Pick commit below the stack' '
+    stg pick --committed upstream~2 2>err &&
+    grep -e "Stack base is now" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2" &&
+    test_cmp_rev upstream~2 $(stg id {base}) &&
+    test "$(git log -1 --format=%s $(stg id {base}))" = "up1" &&
+    test_path_is_file up1.t &&
+    test_path_is_file p1.txt &&
+    test_path_is_file p2.txt
+'
+
+test_expect_success 'Commit not on the base is cherry-picked' '
+    stg pick --committed --expose other &&
+    test "$(git log -1 --format=%s $(stg id {base}))" = "other1" &&
+    git log -1 --format=%B $(stg id {base}) >msg &&
+    grep -e "(imported from commit $(git rev-parse other))" msg &&
+    test_cmp_rev upstream~2 $(stg id {base})~ &&
+    test_path_is_file up1.t &&
+    test_path_is_file other1.t &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Pick commit already in the base' '
+    command_error stg pick --committed base 2>err &&
+    grep -e "\`$(git rev-parse base)\` is already in the stack base" err
+'
+
+test_expect_success 'Pick commit that does not apply to the base' '
+    git checkout -b side base &&
+    test_commit side up1.t side-up1 &&
+    git checkout master &&
+    base=$(stg id {base}) &&
+    command_error stg pick --committed side 2>err &&
+    grep -e "does not apply cleanly to the stack base" err &&
+    test_cmp_rev $base $(stg id {base}) &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2"
+'
+
+test_expect_success 'Pick with conflicting patch and continue' '
+    stg new -m p3 &&
+    echo patch-a >a.txt &&
+    stg add a.txt &&
+    stg refresh &&
+    stg new -m p4 &&
+    echo p4 >p4.txt &&
+    stg add p4.txt &&
+    stg refresh &&
+    conflict stg pick --committed upstream 2>err &&
+    grep -e "Stack base is now" err &&
+    grep -e "stg push --continue" err &&
+    test "$(git log -1 --format=%s $(stg id {base}))" = "conflict" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p4" &&
+    echo resolved >a.txt &&
+    stg add a.txt &&
+    stg push --continue &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3 p4" &&
+    test "$(cat a.txt)" = "resolved"
+'
+
+test_expect_success 'Pick with conflicting patch and abort' '
+    git checkout -b more upstream &&
+    test_commit more a.txt more-a &&
+    git checkout master &&
+    conflict stg pick --committed more &&
+    test "$(git log -1 --format=%s $(stg id {base}))" = "more" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p2 p3" &&
+    stg push --abort &&
+    test -z "$(stg series --applied --noprefix)" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p2 p3 p4" &&
+    test "$(git log -1 --format=%s $(stg id {base}))" = "more" &&
+    test "$(cat a.txt)" = "more-a"
+'
+
+test_done
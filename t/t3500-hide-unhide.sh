@@ -22,7 +22,7 @@ test_expect_success 'Add some patches' '
     stg new -m p0 p0 &&
     stg new -m p1 p1 &&
     stg new -m p2 p2 &&
-    stg hide p2
+    stg hide --pop p2
 '
 
 test_expect_success 'Hide already hidden patch' '
@@ -38,4 +38,79 @@ test_expect_success 'Unhide hidden patch' '
     stg unhide p2
 '
 
+test_expect_success 'Setup patches with changes' '
+    stg push p2 &&
+    for p in p3 p4 p5; do
+        stg new -m $p $p &&
+        echo $p >$p.txt &&
+        stg add $p.txt &&
+        stg refresh || return 1
+    done
+'
+
+test_expect_success 'Refuse to hide applied patch' '
+    command_error stg hide p3 2>err &&
+    grep -e "patch .p3. is applied; use .--pop. to pop and hide it" err &&
+    command_error stg hide p3 p4 2>err &&
+    grep -e "patches .p3., .p4. are applied; use .--pop. to pop and hide them" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p3 p4 p5" &&
+    test "$(echo $(stg series --hidden --noprefix))" = ""
+'
+
+test_expect_success 'Pop and hide applied patch' '
+    test_config stgit.stacksummary true &&
+    stg hide --pop p4 2>out &&
+    grep -e "^- p4..p5$" out &&
+    grep -e "^! p4$" out &&
+    grep -e "^Now at p3 with 4 applied (-2) and 1 unapplied (+1)$" out &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p5" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p4" &&
+    test_path_is_missing p4.txt &&
+    test_path_is_missing p5.txt &&
+    git diff-index --quiet HEAD &&
+    stg unhide p4 &&
+    stg push p4 p5
+'
+
+test_expect_success 'Pop and hide with stgit.hide.autopop' '
+    test_config stgit.hide.autopop true &&
+    test_config stgit.stacksummary true &&
+    stg hide p5 2>out &&
+    cat >expected <<-\EOF &&
+	- p5
+	> p4
+	! p5
+	Now at p4 with 5 applied (-1) and 0 unapplied
+	EOF
+    test_cmp expected out &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p5" &&
+    stg unhide p5 &&
+    stg push p5
+'
+
+test_expect_success 'Pop and hide keeping the patches above applied' '
+    test_config stgit.stacksummary true &&
+    stg hide --pop --keep-above p3 2>out &&
+    grep -e "^! p3$" out &&
+    grep -e "^> p5$" out &&
+    grep -e "^Now at p5 with 5 applied (-1) and 0 unapplied$" out &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p4 p5" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p3" &&
+    test_path_is_missing p3.txt &&
+    git diff-index --quiet HEAD &&
+    stg unhide p3
+'
+
+test_expect_success 'Conflicts pushing back the patches above abort the hide' '
+    stg new -m p6 p6 &&
+    echo p6 >p4.txt &&
+    stg refresh &&
+    command_error stg hide --pop --keep-above p4 2>err &&
+    grep -e "nothing was hidden" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 p4 p5 p6" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "" &&
+    test "$(cat p4.txt)" = "p6" &&
+    git diff-index --quiet HEAD
+'
+
 test_done
@@ -0,0 +1,101 @@
+#!/bin/sh
+
+test_description='Test hiding empty and merged patches'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="base %s" 1 &&
+    git branch upstream &&
+    stg init &&
+    for i in 1 2 3 4; do
+        stg new -m "patch $i" "p$i" &&
+        echo "p$i" >"p$i.txt" &&
+        stg add "p$i.txt" &&
+        stg refresh || return 1
+    done &&
+    stg new -m "empty one" e1 &&
+    stg pop e1 &&
+    stg new -m "empty two" e2 &&
+    test "$(echo $(stg series))" = "+ p1 + p2 + p3 + p4 > e2 - e1"
+'
+
+test_expect_success 'Hide requires patches or a selection option' '
+    general_error stg hide 2>err &&
+    grep -e "required arguments were not provided" err
+'
+
+test_expect_success 'Hide empty patches requires popping applied patches' '
+    command_error stg hide --empty 2>err &&
+    grep -e "patch \`e2\` is applied; use \`--pop\` to pop and hide it" err &&
+    test "$(echo $(stg series --hidden))" = ""
+'
+
+test_expect_success 'Hide empty patches in range' '
+    stg hide --empty p1..p4 >out 2>err &&
+    grep -e "no patches to hide" err &&
+    stg hide --empty e1 2>out &&
+    grep -e "^! e1$" out &&
+    test "$(echo $(stg series --all))" = "+ p1 + p2 + p3 + p4 > e2 ! e1" &&
+    stg unhide e1
+'
+
+test_expect_success 'Hide all empty patches' '
+    stg hide --empty --pop 2>out &&
+    grep -e "^! e2$" out &&
+    grep -e "^! e1$" out &&
+    test "$(echo $(stg series --all))" = "+ p1 + p2 + p3 > p4 ! e2 ! e1" &&
+    stg unhide e1 e2
+'
+
+test_expect_success 'Setup upstream with merged patches' '
+    p2_id=$(stg id p2) &&
+    git checkout upstream &&
+    git cherry-pick "$p2_id" &&
+    echo "p3 modified" >p3.txt &&
+    git add p3.txt &&
+    git commit -m "Patch 3." &&
+    git checkout master &&
+    stg pop -a &&
+    stg rebase --nopush upstream &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p2 p3 p4 e1 e2"
+'
+
+test_expect_success 'Hide merged patches by patch id' '
+    stg hide --merged 2>out &&
+    grep -e "^! p2$" out &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p3 p4 e1 e2" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p2"
+'
+
+test_expect_success 'Hide merged patches by subject' '
+    stg hide --merged=subject p1..p3 2>out &&
+    grep -e "^! p3$" out &&
+    ! grep -e "^! p4$" out &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p4 e1 e2" &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p3 p2"
+'
+
+test_expect_success 'Hide merged and empty patches together' '
+    stg unhide p2 &&
+    stg hide --merged --empty 2>out &&
+    grep -e "^! p2$" out &&
+    grep -e "^! e2$" out &&
+    grep -e "^! e1$" out &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p4" &&
+    stg undo &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p1 p4 e1 e2 p2"
+'
+
+test_expect_success 'Nothing merged to hide' '
+    stg hide --merged p1 p4 2>err &&
+    grep -e "no patches to hide" err &&
+    test "$(echo $(stg series --hidden --noprefix))" = "p3"
+'
+
+test_expect_success 'Invalid merged method' '
+    general_error stg hide --merged=bogus 2>err &&
+    grep -e "invalid value .bogus." err
+'
+
+test_done
@@ -106,6 +106,14 @@ test_expect_success 'Attempt to fold conflicting patch with -C0' '
     git checkout foo.txt
 '
 
+test_expect_success 'Fold conflicting patch with fuzz' '
+    stg fold --fuzz 3 fold1.diff 2>err &&
+    grep -e "info: diff applied with adjustments:" err &&
+    test "$(tail -n 1 foo.txt)" = "and fold1" &&
+    git reset -- foo.txt &&
+    git checkout foo.txt
+'
+
 test_expect_success 'Fold with base' '
     stg fold --base p1 threeway.diff &&
     test "preface hello from p2" = "$(echo $(cat foo.txt))" &&
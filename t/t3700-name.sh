@@ -5,7 +5,7 @@ test_description="Test 'stg name'"
 . ./test-lib.sh
 
 test_expect_success 'Test on uninitialized repo' '
-    command_error stg name 2>err &&
+    no_stack_error stg name 2>err &&
     grep "stack not initialized" err
 '
 
@@ -35,7 +35,7 @@ test_expect_success 'Get patch names' '
 '
 
 test_expect_success 'Fail to get patch names' '
-  command_error stg name -b nostack --showbranch 2>err &&
+  no_stack_error stg name -b nostack --showbranch 2>err &&
   grep "stack not initialized" err &&
   command_error stg name 0~2  2>err &&
   grep "patch name not found" err
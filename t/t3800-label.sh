@@ -0,0 +1,139 @@
+#!/bin/sh
+
+test_description='Test stg label and label patch ranges'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 6 &&
+    stg init &&
+    stg uncommit -n 6 &&
+    stg goto p4
+'
+
+test_expect_success 'List with no labels' '
+    stg label list >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Add labels' '
+    stg label add net p1 p3 p5 &&
+    stg label add usb p2..p3 &&
+    cat >expected <<-\EOF &&
+	net: p1 p3 p5
+	usb: p2 p3
+	EOF
+    stg label list >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'List labels of patches' '
+    cat >expected <<-\EOF &&
+	p3: net usb
+	p4:
+	EOF
+    stg label list p3 p4 >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Invalid label names' '
+    general_error stg label add "a b" p1 2>err &&
+    grep -e "invalid patch label" err &&
+    general_error stg label add "a}" p1 2>err &&
+    grep -e "invalid patch label" err
+'
+
+test_expect_success 'Series with label range' '
+    cat >expected <<-\EOF &&
+	+ p1
+	+ p3
+	- p5
+	EOF
+    stg series "%{net}" >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Unknown label' '
+    command_error stg series "%{nope}" 2>err &&
+    grep -e "no patches are labeled \`nope\`" err
+'
+
+test_expect_success 'Labels survive push and pop' '
+    stg pop -a &&
+    stg push -a &&
+    stg goto p4 &&
+    cat >expected <<-\EOF &&
+	net: p1 p3 p5
+	usb: p2 p3
+	EOF
+    stg label list >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Float label range' '
+    stg float "%{net}" &&
+    test "$(echo $(stg series --applied --noprefix))" = "p2 p4 p1 p3 p5"
+'
+
+test_expect_success 'Labels survive rename' '
+    stg rename p3 p3-renamed &&
+    cat >expected <<-\EOF &&
+	net: p1 p3-renamed p5
+	usb: p2 p3-renamed
+	EOF
+    stg label list >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Labels survive refresh' '
+    echo change >>p5.t &&
+    stg refresh &&
+    stg label list p5 >out &&
+    echo "p5: net" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Remove label from patch' '
+    stg label remove usb p2 &&
+    cat >expected <<-\EOF &&
+	net: p1 p3-renamed p5
+	usb: p3-renamed
+	EOF
+    stg label list >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Remove label from all patches' '
+    stg label remove usb &&
+    echo "net: p1 p3-renamed p5" >expected &&
+    stg label list >out &&
+    test_cmp expected out &&
+    command_error stg label remove usb 2>err &&
+    grep -e "no patches are labeled \`usb\`" err
+'
+
+test_expect_success 'Undo restores labels' '
+    stg undo &&
+    stg label list >out &&
+    grep -e "^usb: p3-renamed$" out &&
+    stg redo &&
+    stg label list >out &&
+    ! grep -e "^usb:" out
+'
+
+test_expect_success 'Delete drops labels' '
+    stg delete p5 &&
+    echo "net: p1 p3-renamed" >expected &&
+    stg label list >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Stack metadata omits labels when unused' '
+    git show refs/stacks/master:stack.json >stack.json &&
+    grep -e "\"labels\"" stack.json &&
+    stg label remove net &&
+    git show refs/stacks/master:stack.json >stack.json &&
+    ! grep -e "\"labels\"" stack.json
+'
+
+test_done
@@ -0,0 +1,84 @@
+#!/bin/sh
+
+test_description='Test reading and writing stack state format versions'
+
+. ./test-lib.sh
+
+# Replace the stack.json of the current stack state with the output of a sed
+# script applied to it.
+rewrite_stack_json () {
+    git show refs/stacks/master:stack.json | sed -e "$1" >stack.json &&
+    blob=$(git hash-object -w stack.json) &&
+    tree=$(git ls-tree refs/stacks/master |
+           sed -e "s/[0-9a-f]*\tstack.json$/$blob\tstack.json/" |
+           git mktree) &&
+    commit=$(git commit-tree -p refs/stacks/master -m "rewrite stack.json" $tree) &&
+    git update-ref refs/stacks/master $commit
+}
+
+stack_version () {
+    git show refs/stacks/master:stack.json | sed -n -e "s/^  \"version\": \([0-9]*\),$/\1/p"
+}
+
+test_expect_success 'Initialize the StGit repository' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg init &&
+    stg uncommit -n 3 &&
+    stg pop
+'
+
+test_expect_success 'Stack state without labels or aliases is written as version 5' '
+    test "$(stack_version)" = "5"
+'
+
+test_expect_success 'Version 5 stack state is kept on next write' '
+    stg push &&
+    test "$(stack_version)" = "5" &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Undo keeps version 5' '
+    stg undo &&
+    test "$(echo $(stg series))" = "+ p1 > p2 - p3" &&
+    test "$(stack_version)" = "5"
+'
+
+test_expect_success 'Labels upgrade the stack state to version 6' '
+    stg label add topic p1 &&
+    test "$(stack_version)" = "6" &&
+    test "$(stg label list)" = "topic: p1"
+'
+
+test_expect_success 'Version 6 stack state with aliases is readable' '
+    stg rename --keep-alias p3 p3-new &&
+    test "$(stack_version)" = "6" &&
+    stg id p3 2>err &&
+    grep -e "deprecated alias" err
+'
+
+test_expect_success 'Unknown fields are ignored' '
+    rewrite_stack_json "s/^  \"hidden\": \[\],$/  \"hidden\": [],\n  \"future-field\": {\"a\": 1},/" &&
+    grep -e "future-field" stack.json &&
+    test "$(echo $(stg series))" = "+ p1 > p2 - p3-new"
+'
+
+test_expect_success 'Newer stack state version is rejected' '
+    rewrite_stack_json "s/^  \"version\": 6,$/  \"version\": 7,/" &&
+    command_error stg series 2>err &&
+    grep -e "stack written by a newer StGit (format 7); please upgrade" err
+'
+
+test_expect_success 'Newer version is rejected before other fields are parsed' '
+    rewrite_stack_json "s/^  \"head\": \(.*\),$/  \"head\": {\"oid\": \1},/" &&
+    grep -e "\"head\": {\"oid\": " stack.json &&
+    command_error stg series 2>err &&
+    grep -e "stack written by a newer StGit (format 7); please upgrade" err
+'
+
+test_expect_success 'Older unsupported version is rejected' '
+    rewrite_stack_json "s/^  \"version\": 7,$/  \"version\": 4,/" &&
+    command_error stg series 2>err &&
+    grep -e "unsupported stack state format version 4" err
+'
+
+test_done
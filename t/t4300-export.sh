@@ -87,4 +87,121 @@ test_expect_success 'Export series with empty patch' '
     stg import -S export6/series
 '
 
+test_expect_success 'Export to JSON' '
+    stg export --format=json -d export7 &&
+    test_path_is_file export7/stack.json &&
+    test_path_is_missing export7/series &&
+    grep -e "\"version\": 1" export7/stack.json &&
+    grep -e "\"base\": \"$(git rev-parse $(stg id patch-1)~1)\"" export7/stack.json &&
+    grep -e "\"name\": \"patch-6\"" export7/stack.json
+'
+
+test_expect_success 'Export to JSON on stdout' '
+    stg export --format=json --stdout patch-1 >export8.json &&
+    grep -e "\"name\": \"patch-1\"" export8.json &&
+    ! grep -e "\"name\": \"patch-2\"" export8.json
+'
+
+test_expect_success 'Reimport JSON export' '
+    git log --format="%an %ae %ad %B" $(stg id patch-1)~1..$(stg id) >expected &&
+    stg delete $(stg series --noprefix) &&
+    stg import --format=json export7/stack.json &&
+    test "$(echo $(stg series --noprefix))" = \
+      "patch-1 patch-2 patch-3 patch-4 patch-5 patch-6" &&
+    git log --format="%an %ae %ad %B" $(stg id patch-1)~1..$(stg id) >actual &&
+    test_cmp expected actual
+'
+
+test_expect_success 'Reimport JSON export from stdin' '
+    stg delete $(stg series --noprefix) &&
+    stg import --format=json <export8.json &&
+    test "$(echo $(stg series --noprefix))" = "patch-1"
+'
+
+test_expect_success 'Import JSON with patch that does not apply' '
+    stg delete $(stg series --noprefix) &&
+    sed -e "s/ line 1\\\\n+line 2/ line X\\\\n+line 2/" \
+      export7/stack.json >bad.json &&
+    command_error stg import --format=json bad.json 2>err &&
+    grep -e "error: importing patch 2 of 6 (.patch-2.)" err &&
+    test "$(echo $(stg series --noprefix))" = "patch-1"
+'
+
+test_expect_success 'Import JSON with unsupported version' '
+    sed -e "s/\"version\": 1/\"version\": 99/" export7/stack.json >export9.json &&
+    command_error stg import --format=json export9.json 2>err &&
+    grep -e "format version 99 is newer than the supported version 1" err
+'
+
+test_expect_success 'Import JSON cannot be combined with series' '
+    command_error stg import --format=json --series export7/stack.json 2>err &&
+    grep -e "cannot be combined" err
+'
+
+test_expect_success 'Export combined mbox' '
+    stg delete $(stg series --noprefix) &&
+    stg import --format=json export7/stack.json &&
+    stg export --combined combined.mbox patch-1..patch-3 &&
+    test "$(grep -c -e "^From [0-9a-f]* Mon Sep 17 00:00:00 2001$" combined.mbox)" = "3" &&
+    grep -e "^Subject: \[PATCH\] patch-2$" combined.mbox &&
+    base=$(git rev-parse $(stg id patch-1)~1) &&
+    top_tree=$(git rev-parse $(stg id patch-3)^{tree}) &&
+    git log --format="%an %ae %ad %s" $base..$(stg id patch-3) >expected &&
+    git checkout -b am-target $base &&
+    test_when_finished "git checkout master && git branch -D am-target" &&
+    git am combined.mbox &&
+    git log --format="%an %ae %ad %s" $base..HEAD >actual &&
+    test_cmp expected actual &&
+    test "$(git rev-parse HEAD^{tree})" = "$top_tree"
+'
+
+test_expect_success 'Combined export conflicts with directory options' '
+    general_error stg export --combined combined2.mbox -d export10 2>err &&
+    grep -e "cannot be used with" err &&
+    command_error stg export --combined combined2.mbox --format=json 2>err &&
+    grep -e "do not apply to the JSON format" err &&
+    test_path_is_missing combined2.mbox
+'
+
+test_expect_success 'Setup patches touching several directories' '
+    mkdir a b &&
+    stg new -m dir-a &&
+    echo a >a/file &&
+    stg add a/file &&
+    stg refresh &&
+    stg new -m dir-b &&
+    echo b >b/file &&
+    stg add b/file &&
+    stg refresh &&
+    stg new -m dir-ab &&
+    echo more >>a/file &&
+    echo more >>b/file &&
+    stg refresh
+'
+
+test_expect_success 'Export split by directory' '
+    stg export --split-by-dir -n -d split dir-a..dir-ab &&
+    test "$(echo $(ls split))" = "a b" &&
+    cat >expected <<-EOF &&
+	# This series applies on Git commit $(git rev-parse $(stg id patch-1)~1)
+	01-dir-a
+	02-dir-ab
+	EOF
+    test_cmp expected split/a/series &&
+    test_path_is_file split/a/01-dir-a &&
+    test_path_is_file split/a/02-dir-ab &&
+    test "$(echo $(ls split/b))" = "01-dir-b 02-dir-ab series" &&
+    grep -e "^+++ b/b/file" split/b/02-dir-ab
+'
+
+test_expect_success 'Strict split by directory' '
+    stg export --split-by-dir --strict -d split2 dir-a dir-b &&
+    test "$(echo $(ls split2))" = "a b" &&
+    command_error stg export --split-by-dir --strict -d split3 dir-a..dir-ab 2>err &&
+    grep -e "patch .dir-ab. touches several top-level paths: .a., .b." err &&
+    test_path_is_missing split3 &&
+    general_error stg export --strict -d split3 2>err &&
+    grep -e "--split-by-dir" err
+'
+
 test_done
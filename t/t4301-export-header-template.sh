@@ -0,0 +1,103 @@
+#!/bin/sh
+
+test_description="Test 'stg export' with header templates"
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize repo with patches' '
+    printf "%s\n" "*.tmpl" err >>.git/info/exclude &&
+    echo "foo" >foo.txt &&
+    git add foo.txt &&
+    git commit -m "initial" &&
+    echo "line 1" >>foo.txt &&
+    stg new -m "patch-1" &&
+    stg refresh &&
+    echo "line 2" >>foo.txt &&
+    stg new -m "$(printf "patch-2\n\nSecond patch.\n\nTicket: ABC-1\nAcked-by: A <a@example.com>\nticket: ABC-2")" &&
+    stg refresh
+'
+
+test_expect_success 'Default export is unchanged without header template' '
+    stg export -d default &&
+    echo "X-Patch: %(patchname)s" >header.tmpl &&
+    stg export -d unchanged &&
+    test_cmp default/patch-1 unchanged/patch-1 &&
+    test_cmp default/patch-2 unchanged/patch-2
+'
+
+test_expect_success 'Header template is inserted before separator' '
+    cat >header.tmpl <<-\EOF &&
+	X-Patch: %(patchname)s
+	X-Subject: %(subject)s
+	X-Author: %(author)s
+	X-Ticket: %(trailer:ticket)s
+	EOF
+    stg export -d export1 --header-template header.tmpl &&
+    cat >expected <<-\EOF &&
+	patch-2
+
+	From: A Ú Thor <author@example.com>
+
+	Second patch.
+
+	Ticket: ABC-1
+	Acked-by: A <a@example.com>
+	ticket: ABC-2
+	X-Patch: patch-2
+	X-Subject: patch-2
+	X-Author: A Ú Thor <author@example.com>
+	X-Ticket: ABC-1, ABC-2
+	---
+	EOF
+    head -n 14 export1/patch-2 >actual &&
+    test_cmp expected actual &&
+    grep -e "^X-Ticket: $" export1/patch-1 &&
+    tail -n +15 export1/patch-2 >actual &&
+    tail -n +11 default/patch-2 >expected-rest &&
+    test_cmp expected-rest actual
+'
+
+test_expect_success 'Header template from config' '
+    printf "Date: %%(date)s\n%%(diffstat)s" >header.tmpl &&
+    test_config stgit.export.headertemplate header.tmpl &&
+    stg export --stdout patch-1 >out &&
+    grep -e "^Date: $(git log -1 --format=%aD $(stg id patch-1))$" out &&
+    grep -c -e "^ foo.txt | *1 +$" out >count &&
+    test "$(cat count)" = "2"
+'
+
+test_expect_success 'Header template option overrides config' '
+    echo "X-Config: yes" >config.tmpl &&
+    echo "X-Option: yes" >option.tmpl &&
+    test_config stgit.export.headertemplate config.tmpl &&
+    stg export --stdout --header-template option.tmpl patch-1 >out &&
+    grep -e "^X-Option: yes$" out &&
+    ! grep -e "^X-Config" out
+'
+
+test_expect_success 'Header template without separator in patch template' '
+    echo "%(shortdescr)s" >patch.tmpl &&
+    printf "X-Patch: %%(patchname)s" >header.tmpl &&
+    stg export --stdout -t patch.tmpl --header-template header.tmpl patch-1 >out &&
+    head -n 3 out >actual &&
+    cat >expected <<-\EOF &&
+	patch-1
+	X-Patch: patch-1
+	diff --git a/foo.txt b/foo.txt
+	EOF
+    test_cmp expected actual
+'
+
+test_expect_success 'Unknown placeholder in header template' '
+    echo "X-Bad: %(bogus)s" >header.tmpl &&
+    command_error stg export -d export2 --header-template header.tmpl 2>err &&
+    grep -e "unknown template placeholder .%(bogus)s." err &&
+    test_path_is_missing export2/series
+'
+
+test_expect_success 'Missing header template file' '
+    command_error stg export -d export3 --header-template missing.tmpl 2>err &&
+    grep -e "reading header template .missing.tmpl." err
+'
+
+test_done
@@ -158,7 +158,7 @@ test_expect_success 'Add patch with files outside cone' '
     echo "stuff" >d/outside.txt &&
     mkdir -p d/0 &&
     echo "addition" >>d/0/delta.txt &&
-    general_error stg add d &&
+    command_error stg add d &&
     echo "more" >>a/alpha.txt &&
     stg add --sparse d a &&
     stg new -r -m patch3 &&
@@ -0,0 +1,170 @@
+#!/bin/sh
+
+test_description='Test StGit with branches checked out in other worktrees'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup repository with linked worktree' '
+    git init main &&
+    (
+        cd main &&
+        printf "%s\n" out err >>.git/info/exclude &&
+        test_commit_bulk 3 &&
+        git worktree add ../linked
+    ) &&
+    (
+        cd linked &&
+        for i in 1 2 3; do
+            echo "update $i" >>$i.t &&
+            stg new -rm "p$i" "p$i" || return 1
+        done &&
+        stg pop
+    )
+'
+
+test_expect_success 'Read branch checked out in other worktree' '
+    (
+        cd main &&
+        stg series -b linked >out &&
+        test "$(echo $(cat out))" = "+ p1 > p2 - p3" &&
+        test "$(stg id linked:p1)" = "$(git rev-parse refs/patches/linked/p1)" &&
+        stg show -b linked p2 >out &&
+        grep -e "update 2" out
+    )
+'
+
+test_expect_success 'Refuse modifying stack checked out in other worktree' '
+    (
+        cd main &&
+        command_error stg delete -b linked p3 2>err &&
+        grep -e "branch .linked. is checked out in another worktree at .*/linked.$" err &&
+        command_error stg hide -b linked p3 2>err &&
+        grep -e "branch .linked. is checked out in another worktree" err &&
+        command_error stg rename -b linked p3 q3 >out 2>err &&
+        test_must_be_empty out &&
+        grep -e "branch .linked. is checked out in another worktree" err &&
+        command_error stg label add -b linked fix p1 2>err &&
+        grep -e "branch .linked. is checked out in another worktree" err &&
+        command_error stg log --clear -b linked 2>err &&
+        grep -e "branch .linked. is checked out in another worktree" err
+    ) &&
+    (
+        cd linked &&
+        stg series >out &&
+        test "$(echo $(cat out))" = "+ p1 > p2 - p3" &&
+        test -z "$(git status --porcelain -uno)"
+    )
+'
+
+test_expect_success 'Refuse branch operations on branch checked out in other worktree' '
+    (
+        cd main &&
+        command_error stg branch linked 2>err &&
+        grep -e "branch .linked. is checked out in another worktree" err &&
+        command_error stg branch --delete --force linked 2>err &&
+        grep -e "branch .linked. is checked out in another worktree" err &&
+        command_error stg branch --cleanup --force linked 2>err &&
+        grep -e "branch .linked. is checked out in another worktree" err &&
+        command_error stg branch --rename linked renamed 2>err &&
+        grep -e "branch .linked. is checked out in another worktree" err &&
+        git show-ref --verify refs/heads/linked &&
+        git show-ref --verify refs/stacks/linked
+    )
+'
+
+test_expect_success 'Refuse modifying branch of main worktree from linked worktree' '
+    (
+        cd linked &&
+        command_error stg init -b master 2>err &&
+        grep -e "branch .master. is checked out in another worktree at .*/main.$" err &&
+        test_must_fail git show-ref --verify refs/stacks/master
+    )
+'
+
+test_expect_success 'Current worktree may modify its own branch' '
+    (
+        cd linked &&
+        stg pop &&
+        stg push &&
+        stg branch --rename linked linked2 &&
+        stg branch --rename linked2 linked &&
+        test "$(git symbolic-ref HEAD)" = "refs/heads/linked"
+    )
+'
+
+test_expect_success 'Allow modifying branch of worktree on detached HEAD' '
+    (
+        cd linked &&
+        git switch --detach
+    ) &&
+    (
+        cd main &&
+        stg delete -b linked p3 &&
+        test "$(echo $(stg series -b linked))" = "+ p1 > p2"
+    ) &&
+    (
+        cd linked &&
+        git switch linked &&
+        test "$(echo $(stg series))" = "+ p1 > p2"
+    )
+'
+
+test_expect_success 'Setup bare repository with linked worktrees' '
+    git clone --bare main bare.git &&
+    git -C bare.git worktree add ../wt1 -b one master &&
+    git -C bare.git worktree add ../wt2 -b two master &&
+    (
+        cd wt2 &&
+        echo "two" >>1.t &&
+        stg new -rm "t1" t1
+    )
+'
+
+test_expect_success 'Read branch of other worktree of bare repository' '
+    (
+        cd wt1 &&
+        test "$(echo $(stg series -b two))" = "> t1" &&
+        test "$(stg id two:t1)" = "$(git rev-parse refs/patches/two/t1)"
+    ) &&
+    (
+        cd bare.git &&
+        test "$(echo $(stg series -b two))" = "> t1"
+    )
+'
+
+test_expect_success 'Refuse modifying branch of other worktree of bare repository' '
+    (
+        cd wt1 &&
+        command_error stg delete -b two t1 2>err &&
+        grep -e "branch .two. is checked out in another worktree at .*/wt2.$" err &&
+        command_error stg branch --delete --force two 2>err &&
+        grep -e "branch .two. is checked out in another worktree at .*/wt2.$" err
+    ) &&
+    (
+        cd wt2 &&
+        test "$(echo $(stg series))" = "> t1"
+    )
+'
+
+test_expect_success 'HEAD of bare repository is not a checked out branch' '
+    test "$(git -C bare.git symbolic-ref HEAD)" = "refs/heads/master" &&
+    (
+        cd wt1 &&
+        stg init -b master &&
+        git show-ref --verify refs/stacks/master
+    )
+'
+
+test_expect_success 'Allow modifying branch of bare repository worktree on detached HEAD' '
+    (
+        cd wt2 &&
+        git switch --detach
+    ) &&
+    (
+        cd wt1 &&
+        stg delete -b two t1 &&
+        test -z "$(stg series -b two)"
+    )
+'
+
+test_done
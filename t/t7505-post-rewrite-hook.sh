@@ -0,0 +1,98 @@
+#!/bin/sh
+
+test_description='Test the post-rewrite hook'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup patches and hook' '
+    for i in 1 2; do
+        stg new -m "p$i" "p$i" &&
+        echo "p$i" >"p$i.txt" &&
+        stg add "p$i.txt" &&
+        stg refresh || return 1
+    done &&
+    printf "%s\n" rewritten expected my-hooks >>.git/info/exclude &&
+    write_script "$(git rev-parse --git-path hooks/post-rewrite)" <<-EOF
+	echo "\$1" >>"$TRASH_DIRECTORY/rewritten"
+	cat >>"$TRASH_DIRECTORY/rewritten"
+	EOF
+'
+
+test_expect_success 'Refresh reports an amend' '
+    p2_old=$(stg id p2) &&
+    echo "more" >>p2.txt &&
+    stg refresh &&
+    cat >expected <<-EOF &&
+	amend
+	$p2_old $(stg id p2)
+	EOF
+    test_cmp expected rewritten &&
+    rm rewritten
+'
+
+test_expect_success 'Refreshing a lower patch reports a rebase' '
+    p1_old=$(stg id p1) &&
+    p2_old=$(stg id p2) &&
+    echo "more" >>p1.txt &&
+    stg refresh -p p1 &&
+    cat >expected <<-EOF &&
+	rebase
+	$p1_old $(stg id p1)
+	$p2_old $(stg id p2)
+	EOF
+    test_cmp expected rewritten &&
+    rm rewritten
+'
+
+test_expect_success 'Reordering push reports a rebase' '
+    p2_old=$(stg id p2) &&
+    stg pop -a &&
+    stg push p2 &&
+    cat >expected <<-EOF &&
+	rebase
+	$p2_old $(stg id p2)
+	EOF
+    test_cmp expected rewritten &&
+    rm rewritten
+'
+
+test_expect_success 'No report without rewrites' '
+    stg push &&
+    rm rewritten &&
+    stg new -m p3 p3 &&
+    stg pop &&
+    stg push &&
+    test_path_is_missing rewritten
+'
+
+test_expect_success 'Hook exit status is ignored' '
+    write_script "$(git rev-parse --git-path hooks/post-rewrite)" <<-EOF &&
+	touch "$TRASH_DIRECTORY/rewritten"
+	exit 1
+	EOF
+    stg edit -m "p3 edited" p3 &&
+    test_path_is_file rewritten &&
+    test "$(git log -1 --format=%s)" = "p3 edited" &&
+    rm rewritten
+'
+
+test_expect_success 'Hook is found in core.hooksPath' '
+    mkdir my-hooks &&
+    mv "$(git rev-parse --git-path hooks/post-rewrite)" my-hooks/ &&
+    test_config core.hooksPath my-hooks &&
+    stg edit -m "p3 edited again" p3 &&
+    test_path_is_file rewritten
+'
+
+test_expect_success 'Failure to run the hook is a warning' '
+    test_when_finished "rm -f $(git rev-parse --git-path hooks/post-rewrite)" &&
+    write_script "$(git rev-parse --git-path hooks/post-rewrite)" "/nonexistent/sh" <<-\EOF &&
+	exit 0
+	EOF
+    stg edit -m "p3 edited once more" p3 2>err &&
+    grep -e "warning: .post-rewrite. hook" err &&
+    test "$(git log -1 --format=%s)" = "p3 edited once more" &&
+    test "$(stg top)" = "p3"
+'
+
+test_done
@@ -0,0 +1,99 @@
+#!/bin/sh
+
+test_description='Test the stgit.notify.command notification command'
+
+. ./test-lib.sh
+
+wait_for_file () {
+    i=0 &&
+    while ! test -s "$1" && test $i -lt 100
+    do
+        sleep 0.1 &&
+        i=$((i + 1))
+    done &&
+    test -s "$1"
+}
+
+test_expect_success 'Setup notify command' '
+    printf "%s\n" notified notified.* expected out err slow-done >>.git/info/exclude &&
+    write_script notify <<-EOF &&
+	env | grep -e "^STGIT_NOTIFY_" | sort >"$TRASH_DIRECTORY/notified.tmp"
+	mv "$TRASH_DIRECTORY/notified.tmp" "$TRASH_DIRECTORY/notified"
+	EOF
+    echo notify >>.git/info/exclude &&
+    stg init
+'
+
+test_expect_success 'Notify command is not run when unset' '
+    stg new -m p0 &&
+    test_path_is_missing notified
+'
+
+test_expect_success 'Notify command describes the operation' '
+    test_config stgit.notify.command "\"$TRASH_DIRECTORY/notify\"" &&
+    old_state=$(git rev-parse refs/stacks/master) &&
+    stg new -m p1 &&
+    wait_for_file notified &&
+    cat >expected <<-EOF &&
+	STGIT_NOTIFY_BRANCH=master
+	STGIT_NOTIFY_COMMAND=new
+	STGIT_NOTIFY_MESSAGE=new: p1
+	STGIT_NOTIFY_NEW_STATE=$(git rev-parse refs/stacks/master)
+	STGIT_NOTIFY_OLD_STATE=$old_state
+	EOF
+    test_cmp expected notified &&
+    rm notified
+'
+
+test_expect_success 'Notify command is run for each transaction' '
+    test_config stgit.notify.command "\"$TRASH_DIRECTORY/notify\"" &&
+    stg pop &&
+    wait_for_file notified &&
+    grep -e "^STGIT_NOTIFY_COMMAND=pop$" notified &&
+    grep -e "^STGIT_NOTIFY_NEW_STATE=$(git rev-parse refs/stacks/master)$" notified &&
+    rm notified &&
+    stg push &&
+    wait_for_file notified &&
+    grep -e "^STGIT_NOTIFY_COMMAND=push$" notified &&
+    rm notified
+'
+
+test_expect_success 'Branch-specific notify command' '
+    test_config stgit.notify.command "echo general >\"$TRASH_DIRECTORY/general\"" &&
+    test_config branch.master.stgit.notify.command "\"$TRASH_DIRECTORY/notify\"" &&
+    stg pop &&
+    wait_for_file notified &&
+    grep -e "^STGIT_NOTIFY_COMMAND=pop$" notified &&
+    test_path_is_missing general &&
+    rm notified &&
+    stg push &&
+    wait_for_file notified &&
+    rm notified
+'
+
+test_expect_success 'Notify command is not run for dry runs' '
+    test_config stgit.notify.command "\"$TRASH_DIRECTORY/notify\"" &&
+    stg pop --dry-run &&
+    sleep 1 &&
+    test_path_is_missing notified
+'
+
+test_expect_success 'Notify command does not block' '
+    test_config stgit.notify.command "sleep 3 && echo done >slow-done" &&
+    stg pop >out &&
+    test_path_is_missing slow-done &&
+    stg push >out &&
+    test_path_is_missing slow-done &&
+    wait_for_file slow-done
+'
+
+test_expect_success 'Failing notify command does not fail the operation' '
+    test_config stgit.notify.command "echo oops >&2; exit 1" &&
+    stg pop 2>err &&
+    ! grep -e "oops" err &&
+    test_config stgit.notify.command "$TRASH_DIRECTORY/no-such-command" &&
+    stg push 2>err &&
+    test "$(stg top)" = "p1"
+'
+
+test_done
@@ -1797,6 +1797,11 @@ test_cmp_config_output () {
 general_error () { test_expect_code 1 "$@" ; }
 command_error () { test_expect_code 2 "$@" ; }
 conflict () { test_expect_code 3 "$@" ; }
+not_a_repository_error () { test_expect_code 4 "$@" ; }
+bare_repository_error () { test_expect_code 5 "$@" ; }
+detached_head_error () { test_expect_code 6 "$@" ; }
+unborn_branch_error () { test_expect_code 7 "$@" ; }
+no_stack_error () { test_expect_code 8 "$@" ; }
 
 # Fake implementation of the "test-tool" helper tool from Git's test infrastructure.
 # Only the bare minimum of subcommands are implemented.